@@ -0,0 +1,69 @@
+//! Any Provider - StandardAgent with a provider picked at runtime
+//!
+//! The agent only sees `Arc<dyn LlmProvider>`, so the same config runs on
+//! Anthropic, Gemini or a scripted mock. Features the model doesn't support
+//! (extended thinking, prompt caching) are turned off at spawn.
+//!
+//! Run with:
+//!   cargo run --example any_provider                    # MockLlmProvider, no network
+//!   cargo run --example any_provider -- --gemini        # GEMINI_API_KEY, GEMINI_MODEL
+//!   cargo run --example any_provider -- --anthropic     # ANTHROPIC_API_KEY, ANTHROPIC_MODEL
+
+use anyhow::Result;
+use std::env;
+use std::sync::Arc;
+
+use shadow_agent_sdk::{
+    agent::{AgentConfig, StandardAgent},
+    cli::{run_print_mode, PrintModeOptions},
+    llm::{AnthropicProvider, GeminiProvider, LlmProvider, MockLlmProvider},
+    runtime::AgentRuntime,
+    session::{AgentSession, SessionStorage},
+};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(env::var("RUST_LOG").unwrap_or_else(|_| "shadow_agent_sdk=warn".to_string()))
+        .init();
+
+    let args: Vec<String> = env::args().collect();
+    let llm: Arc<dyn LlmProvider> = if args.iter().any(|a| a == "--gemini") {
+        Arc::new(GeminiProvider::from_env()?)
+    } else if args.iter().any(|a| a == "--anthropic") {
+        Arc::new(AnthropicProvider::from_env()?)
+    } else {
+        Arc::new(MockLlmProvider::new().with_text("Hello from the mock provider!"))
+    };
+
+    let caps = llm.capabilities();
+    println!(
+        "Provider: {} ({}), thinking: {}, caching: {}",
+        llm.provider_name(),
+        llm.model(),
+        caps.supports_thinking,
+        caps.supports_caching
+    );
+
+    let config = AgentConfig::new("You are a helpful assistant. Be brief.")
+        .with_thinking(1024)
+        .with_auto_name(false);
+    let agent = StandardAgent::new(config, llm);
+
+    let dir = tempfile::tempdir()?;
+    let session = AgentSession::new_with_storage(
+        "any-provider",
+        "any-provider",
+        "Any Provider",
+        "StandardAgent with a runtime-selected provider",
+        SessionStorage::with_dir(dir.path()),
+    )?;
+
+    let runtime = AgentRuntime::new();
+    let handle = runtime.spawn(session, move |internals| agent.run(internals)).await;
+
+    run_print_mode(&handle, "Say hello", PrintModeOptions::new()).await?;
+    println!();
+    runtime.shutdown_all().await;
+    Ok(())
+}
@@ -96,11 +96,15 @@ async fn main() -> Result<()> {
                                     in_thinking = true;
                                 }
                             }
-                            ContentBlockStart::ToolUse { name, .. } => {
+                            ContentBlockStart::ToolUse { name, .. }
+                            | ContentBlockStart::ServerToolUse { name, .. } => {
                                 _current_block_type = Some("tool_use".to_string());
                                 print!("\n{} ", format!("[Tool: {}]", name).yellow());
                                 io::stdout().flush()?;
                             }
+                            ContentBlockStart::WebSearchToolResult { .. } => {
+                                _current_block_type = Some("web_search_tool_result".to_string());
+                            }
                         }
                     }
                     StreamEvent::ContentBlockDelta(delta_event) => {
@@ -122,6 +126,10 @@ async fn main() -> Result<()> {
                             ContentDelta::SignatureDelta { .. } => {
                                 // Signature is internal, don't display
                             }
+                            ContentDelta::CitationsDelta { citation } => {
+                                print!("{}", format!(" [{}]", citation.title().unwrap_or("source")).cyan());
+                                io::stdout().flush()?;
+                            }
                         }
                     }
                     StreamEvent::ContentBlockStop(_) => {
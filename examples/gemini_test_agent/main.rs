@@ -9,33 +9,19 @@
 //!   cargo run --example gemini_test_agent -- --stream      # Test with streaming
 //!   cargo run --example gemini_test_agent -- --interactive  # Interactive mode
 
-mod tools;
-
 use anyhow::Result;
 use std::env;
 use std::sync::Arc;
 
 use shadow_agent_sdk::{
-    agent::{AgentConfig, StandardAgent},
     cli::ConsoleRenderer,
     hooks::{HookContext, HookEvent, HookRegistry, HookResult},
     llm::{AuthConfig, GeminiProvider, LlmProvider, SwappableLlmProvider},
+    presets,
     runtime::AgentRuntime,
     session::{AgentSession, SessionStorage},
 };
 
-const SYSTEM_PROMPT: &str = r#"You are a helpful coding assistant with access to tools.
-
-You have the following tools available:
-- Read: Read file contents
-- Write: Write or create files
-- Bash: Execute shell commands
-- Grep: Search file contents
-- Glob: Find files by pattern
-
-When the user asks you to do something, use the appropriate tools.
-Be concise in your responses."#;
-
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
@@ -82,10 +68,6 @@ async fn main() -> Result<()> {
     let runtime = AgentRuntime::new();
     runtime.global_permissions();
 
-    // --- Create tool registry ---
-    let tools = Arc::new(tools::create_registry()?);
-    println!("[Setup] Tools registered: {:?}", tools.tool_names());
-
     // --- Create hooks ---
     let mut hooks = HookRegistry::new();
 
@@ -95,6 +77,14 @@ async fn main() -> Result<()> {
             HookResult::allow()
         });
 
+    // --- Build the coding agent preset ---
+    // The preset's Bash safety hook is added to the auto-approve hook, and
+    // its deny still wins
+    let mut preset = presets::coding_agent(llm, env::current_dir()?)
+        .with_hooks(hooks)
+        .build()?;
+    println!("[Setup] Tools registered: {:?}", preset.tools.tool_names());
+
     // --- Create session ---
     let session_id = format!(
         "gemini-test-{}",
@@ -110,22 +100,20 @@ async fn main() -> Result<()> {
     )?;
 
     // --- Configure agent ---
-    let mut config = AgentConfig::new(SYSTEM_PROMPT)
-        .with_hooks(hooks)
+    preset.config = preset
+        .config
         .with_streaming(use_streaming)
         .with_prompt_caching(false) // Gemini doesn't use Anthropic-style caching
         .with_auto_name(false); // Skip naming for non-interactive
 
-    if use_tools {
-        config = config.with_tools(tools);
+    if !use_tools {
+        preset.config.tools = None;
     }
 
-    let agent = StandardAgent::new(config, llm);
+    let (agent, options) = preset.into_agent();
 
     // --- Spawn agent ---
-    let handle = runtime
-        .spawn(session, move |internals| agent.run(internals))
-        .await;
+    let handle = runtime.spawn_with(session, agent, options).await;
 
     if interactive {
         // Interactive mode - use console renderer
@@ -156,48 +144,40 @@ async fn main() -> Result<()> {
         let mut output_rx = handle.subscribe();
         let mut full_response = String::new();
 
-        loop {
-            match output_rx.recv().await {
-                Ok(chunk) => {
-                    use shadow_agent_sdk::core::OutputChunk;
-                    match chunk {
-                        OutputChunk::TextDelta(text) => {
-                            print!("{}", text);
-                            full_response.push_str(&text);
-                        }
-                        OutputChunk::TextComplete(_) => {
-                            // Already printed via deltas
-                        }
-                        OutputChunk::ThinkingDelta(text) => {
-                            print!("[thinking] {}", text);
-                        }
-                        OutputChunk::ToolStart { name, input, .. } => {
-                            println!("\n[Tool: {}] Input: {}", name, input);
-                        }
-                        OutputChunk::ToolEnd { id: _, result } => {
-                            let result_text = match &result.content {
-                                shadow_agent_sdk::tools::ToolResultData::Text(t) => t.clone(),
-                                _ => "(non-text result)".to_string(),
-                            };
-                            let truncated = if result_text.len() > 200 {
-                                format!("{}...", &result_text[..200])
-                            } else {
-                                result_text
-                            };
-                            println!("[Tool result] {}", truncated);
-                        }
-                        OutputChunk::Error(err) => {
-                            eprintln!("\n[Error] {}", err);
-                        }
-                        OutputChunk::Done => {
-                            println!("\n---");
-                            println!("[Test] Complete!");
-                            break;
-                        }
-                        _ => {}
-                    }
+        while let Ok(chunk) = output_rx.recv().await {
+            use shadow_agent_sdk::core::OutputChunk;
+            match chunk {
+                OutputChunk::TextDelta(text) => {
+                    print!("{}", text);
+                    full_response.push_str(&text);
+                }
+                OutputChunk::TextComplete(_) => {
+                    // Already printed via deltas
+                }
+                OutputChunk::ThinkingDelta(text) => {
+                    print!("[thinking] {}", text);
+                }
+                OutputChunk::ToolStart { name, input, .. } => {
+                    println!("\n[Tool: {}] Input: {}", name, input);
+                }
+                OutputChunk::ToolEnd { id: _, result } => {
+                    let result_text = result.summary();
+                    let truncated = if result_text.len() > 200 {
+                        format!("{}...", &result_text[..200])
+                    } else {
+                        result_text
+                    };
+                    println!("[Tool result] {}", truncated);
+                }
+                OutputChunk::Error(err) => {
+                    eprintln!("\n[Error] {}", err);
+                }
+                OutputChunk::Done => {
+                    println!("\n---");
+                    println!("[Test] Complete!");
+                    break;
                 }
-                Err(_) => break,
+                _ => {}
             }
         }
     }
@@ -26,7 +26,7 @@ use shadow_agent_sdk::{
     hooks::{HookContext, HookEvent, HookRegistry, HookResult},
     llm::{AnthropicProvider, AuthConfig},
     mcp::{MCPServerManager, MCPToolProvider},
-    runtime::AgentRuntime,
+    runtime::{AgentRuntime, SpawnOptions},
     session::{AgentSession, SessionStorage},
     tools::ToolRegistry,
 };
@@ -236,13 +236,8 @@ async fn main() -> Result<()> {
 
     // --- Step 10: Spawn the agent ---
     println!("[Setup] Spawning agent...");
-    let todo_for_context = todo_manager.clone();
-    let handle = runtime
-        .spawn(session, move |mut internals| {
-            internals.context.insert_resource_arc(todo_for_context);
-            agent.run(internals)
-        })
-        .await;
+    let options = SpawnOptions::new().with_resource_arc(todo_manager.clone());
+    let handle = runtime.spawn_with(session, agent, options).await;
     println!("[Setup] Agent spawned!");
 
     // --- Step 11: Create and run the console renderer ---
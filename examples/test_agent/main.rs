@@ -1,53 +1,46 @@
 //! Test Agent Example - Using StandardAgent
 //!
 //! Demonstrates the standardized agent framework:
-//! - AgentConfig for configuration
+//! - The coding agent preset (tools, safety hook, permission rules)
 //! - StandardAgent for the agent loop
 //! - Context injections for dynamic message modification
 //! - TodoListManager for task tracking
 //! - Streaming responses (optional)
 //! - Prompt caching for cost savings (enabled by default)
 //!
-//! Read operations are pre-allowed, others will prompt the user.
+//! Read operations are pre-allowed, others will prompt the user. File tools
+//! are confined to the current directory.
 //!
 //! Run with:
 //!   cargo run --example test_agent                     # New session (with caching)
 //!   cargo run --example test_agent -- --resume         # Resume existing session
+//!   cargo run --example test_agent -- --pick           # Choose a session to resume
 //!   cargo run --example test_agent -- --stream         # New session with streaming
 //!   cargo run --example test_agent -- --stream --resume # Resume with streaming
 //!   cargo run --example test_agent -- --think          # Enable extended thinking
 //!   cargo run --example test_agent -- --stream --think # Streaming with thinking
 //!   cargo run --example test_agent -- --no-cache       # Disable prompt caching
+//!   cargo run --example test_agent -- --config examples/test_agent/agent.toml
+//!
+//! Non-interactive print mode (setup logs go to stderr):
+//!   cargo run --example test_agent -- -p "List the files here"
+//!   cargo run --example test_agent -- -p "..." --output-format json
+//!   cargo run --example test_agent -- -p "..." --output-format stream-json
+//!   cargo run --example test_agent -- -p "..." --allow-tools Bash,Write --max-turns 5 --timeout 120
 
-mod tools;
-
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use std::env;
 use std::sync::Arc;
+use std::time::Duration;
 
 use shadow_agent_sdk::{
-    agent::{AgentConfig, StandardAgent},
-    cli::ConsoleRenderer,
-    helpers::{inject_system_reminder, TodoListManager},
-    hooks::{HookContext, HookEvent, HookRegistry, HookResult},
-    llm::{AnthropicProvider, AuthConfig},
+    cli::{pick_session, run_print_mode, ConsoleRenderer, OutputFormat, PrintModeOptions},
+    llm::{AnthropicProvider, AuthConfig, LlmProvider},
+    presets,
     runtime::AgentRuntime,
     session::{AgentSession, SessionStorage},
 };
 
-/// System prompt for the test agent
-const SYSTEM_PROMPT: &str = r#"You are a helpful coding assistant with access to tools.
-
-You have the following tools available:
-- Read: Read file contents
-- Write: Write or create files
-- Bash: Execute shell commands
-- TodoWrite: Track tasks you need to perform
-
-When the user asks you to do something, use the appropriate tools.
-Use TodoWrite to track multi-step tasks and show progress.
-Be concise in your responses."#;
-
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
@@ -58,6 +51,8 @@ async fn main() -> Result<()> {
     // Parse command line arguments
     let args: Vec<String> = env::args().collect();
     let resume = args.iter().any(|a| a == "--resume" || a == "-r");
+    let print_prompt = flag_value(&args, &["-p", "--print"]);
+    let print_options = parse_print_options(&args)?;
 
     // Generate session ID with timestamp
     let session_id = format!(
@@ -65,19 +60,21 @@ async fn main() -> Result<()> {
         chrono::Local::now().format("%Y%m%d-%H%M%S")
     );
 
-    println!("=== Test Agent (StandardAgent) ===");
-    println!("This agent uses the standardized agent framework.");
-    println!("Read operations are pre-allowed. Others will require permission.");
-    println!("Use --stream/-s flag to enable streaming responses.");
-    println!("Use --think/-t flag to enable extended thinking.");
-    println!("Prompt caching is enabled by default (use --no-cache to disable).\n");
+    if print_prompt.is_none() {
+        println!("=== Test Agent (StandardAgent) ===");
+        println!("This agent uses the standardized agent framework.");
+        println!("Read operations are pre-allowed. Others will require permission.");
+        println!("Use --stream/-s flag to enable streaming responses.");
+        println!("Use --think/-t flag to enable extended thinking.");
+        println!("Prompt caching is enabled by default (use --no-cache to disable).\n");
+    }
 
     // --- Step 1: Create LLM provider with dynamic auth ---
-    println!("[Setup] Creating LLM provider with dynamic auth...");
+    eprintln!("[Setup] Creating LLM provider with dynamic auth...");
 
     // Using dynamic auth provider - callback is called before each API request
     // This demonstrates the pattern for JWT tokens that expire frequently
-    let llm = Arc::new(
+    let llm: Arc<dyn LlmProvider> = Arc::new(
         AnthropicProvider::with_auth_provider(|| async {
             // Read API key from ANTHROPIC_KEY (not ANTHROPIC_API_KEY)
             // In production, this would fetch a fresh JWT from your auth service
@@ -96,56 +93,48 @@ async fn main() -> Result<()> {
         )
         .with_max_tokens(32000),
     );
-    println!("[Setup] Model: {} (using dynamic auth)", llm.model());
+    eprintln!("[Setup] Model: {} (using dynamic auth)", llm.model());
 
     // --- Step 2: Create runtime with global Read permission ---
     let runtime = AgentRuntime::new();
     runtime.global_permissions();
-    println!("[Setup] Runtime created (Read tool globally allowed)");
-
-    // --- Step 3: Create tool registry ---
-    let tools = Arc::new(tools::create_registry()?);
-    println!("[Setup] Tools registered: {:?}", tools.tool_names());
-
-    // --- Step 4: Create TodoListManager (shared between agent and console) ---
-    let todo_manager = Arc::new(TodoListManager::new());
-    println!("[Setup] TodoListManager created");
-
-    // --- Step 5: Create hooks ---
-    let mut hooks = HookRegistry::new();
-
-    // Block dangerous Bash commands
-    hooks
-        .add_with_pattern(HookEvent::PreToolUse, "Bash", |ctx: &mut HookContext| {
-            println!("PreToolUse hook called with context: {:?}", ctx.tool_input.as_ref().map(|v| v.to_string()));
-            let cmd = ctx
-                .tool_input
-                .as_ref()
-                .and_then(|v| v.get("command"))
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
-
-            // Block dangerous patterns
-            if cmd.contains("rm ") {
-                HookResult::deny("Dangerous command blocked by safety hook")
-            } else {
-                HookResult::none() // Continue with normal permission flow
-            }
-        })
-        .expect("Invalid regex pattern");
-
-    // Auto-approve read-only tools (skip permission prompts)
-    hooks
-        .add_with_pattern(HookEvent::PreToolUse, "^(Read|Glob|Grep)$", |_ctx: &mut HookContext| {
-            HookResult::allow()
-        })
-        .expect("Invalid regex pattern");
-
-    println!("[Setup] Hooks configured: dangerous command blocker, read-only auto-approve");
+    eprintln!("[Setup] Runtime created (Read tool globally allowed)");
+
+    // --- Step 3: Build the coding agent preset ---
+    // Read/Write/Edit/Glob/Grep/LS confined to the working directory, Bash with
+    // a hook blocking `rm`, TodoWrite with a reminder injection, and rules
+    // allowing the read-only tools
+    let workdir = env::current_dir()?;
+    let mut preset = presets::coding_agent(llm, &workdir).build()?;
+    eprintln!("[Setup] Tools registered: {:?}", preset.tools.tool_names());
+
+    // --- Step 3b: Load the declarative agent definition (--config) ---
+    // The file sets the prompt, model, tool allow-list and permission rules;
+    // its deny rules are added to the preset's hooks.
+    let from_file = match flag_value(&args, &["--config"]) {
+        Some(path) => {
+            preset = preset.with_agent_file(&path)?;
+            eprintln!("[Setup] Loaded agent config from {} (model: {})", path, preset.llm.model());
+            true
+        }
+        None => false,
+    };
 
-    // --- Step 6: Create or load session ---
+    // --- Step 4: Create or load session ---
     let storage = SessionStorage::with_dir("./sessions");
-    let session = if resume {
+    let picked = if args.iter().any(|a| a == "--pick") {
+        pick_session(&storage)
+    } else {
+        None
+    };
+    let session = if let Some(picked) = picked {
+        let session = AgentSession::load_with_storage(&picked, storage)?;
+        eprintln!("[Setup] Resumed session: {} ({} messages in history)",
+            session.session_id(),
+            session.history().len()
+        );
+        session
+    } else if resume {
         // Resume existing session
         if !AgentSession::exists_with_storage(&session_id, &storage) {
             bail!(
@@ -154,7 +143,7 @@ async fn main() -> Result<()> {
             );
         }
         let session = AgentSession::load_with_storage(&session_id, storage)?;
-        println!("[Setup] Resumed session: {} ({} messages in history)",
+        eprintln!("[Setup] Resumed session: {} ({} messages in history)",
             session.session_id(),
             session.history().len()
         );
@@ -168,72 +157,63 @@ async fn main() -> Result<()> {
             "A test agent demonstrating the StandardAgent framework",
             storage,
         )?;
-        println!("[Setup] New session: {}", session.session_id());
+        eprintln!("[Setup] New session: {}", session.session_id());
         session
     };
 
-    // --- Step 7: Configure the agent ---
-    // Clone todo_manager for the injection closure
-    let todo_for_injection = todo_manager.clone();
-
+    // --- Step 5: Configure the agent ---
     // Check if streaming is requested via command line
     let streaming = args.iter().any(|a| a == "--stream" || a == "-s");
     // Check if extended thinking is requested via command line
     let thinking = args.iter().any(|a| a == "--think" || a == "-t");
     // Check if caching should be disabled (enabled by default)
     let no_cache = args.iter().any(|a| a == "--no-cache");
-    let caching = !no_cache;
 
-    let mut config = AgentConfig::new(SYSTEM_PROMPT)
-        .with_tools(tools)
-        .with_hooks(hooks) // Add hooks for safety and auto-approval
-        .with_debug(true) // Enable debug logging
-        .with_streaming(streaming) // Enable streaming if --stream flag is passed
-        .with_prompt_caching(caching); // Enable/disable prompt caching
+    let mut config = preset.config.with_debug(true); // Enable debug logging
+    if !from_file {
+        config = config
+            .with_streaming(streaming) // Enable streaming if --stream flag is passed
+            .with_prompt_caching(!no_cache); // Enable/disable prompt caching
+    }
+    let streaming = config.streaming_enabled;
+    let caching = config.enable_prompt_caching;
 
     // Enable extended thinking if --think flag is passed
     if thinking {
         config = config.with_thinking(16000); // 16k token budget for thinking
     }
+    preset.config = config;
 
-    let config = config.with_injection_fn("todo_status", move |_internals, mut messages| {
-            // Only inject reminder if todo list is empty
-            if todo_for_injection.is_empty() {
-                inject_system_reminder(
-                    &mut messages,
-                    "The TodoWrite tool hasn't been used yet. If you're working on tasks that would benefit from tracking progress, consider using the TodoWrite tool to track progress. Only use it if it's relevant to the current work.",
-                );
-            }
-            messages
-        });
-
-    println!(
+    eprintln!(
         "[Setup] AgentConfig created with debug logging, hooks{}{}{} and todo reminder injection",
         if streaming { ", streaming enabled" } else { "" },
         if thinking { ", extended thinking enabled" } else { "" },
         if caching { ", prompt caching enabled" } else { ", prompt caching disabled" }
     );
 
-    // --- Step 8: Create StandardAgent ---
-    let agent = StandardAgent::new(config, llm);
-
-    // --- Step 9: Spawn the agent ---
-    println!("[Setup] Spawning agent...");
-    let todo_for_context = todo_manager.clone();
-    let handle = runtime
-        .spawn(session, move |mut internals| {
-            // Insert TodoListManager into context for TodoWriteTool
-            // Use insert_resource_arc since todo_for_context is already Arc-wrapped
-            internals.context.insert_resource_arc(todo_for_context);
-            agent.run(internals)
-        })
-        .await;
-    println!("[Setup] Agent spawned!");
+    // --- Step 6: Create StandardAgent ---
+    // The todo list is shared between the agent and the console
+    let todo_manager = preset.todo_manager.clone();
+    let (agent, options) = preset.into_agent();
+
+    // --- Step 7: Spawn the agent ---
+    eprintln!("[Setup] Spawning agent...");
+    // The options carry the preset's permission rules and the todo list
+    // that TodoWriteTool finds in the agent's context
+    let handle = runtime.spawn_with(session, agent, options).await;
+    eprintln!("[Setup] Agent spawned!");
+
+    // --- Print mode: run one prompt and exit ---
+    if let Some(prompt) = print_prompt {
+        let result = run_print_mode(&handle, &prompt, print_options).await?;
+        runtime.shutdown_all().await;
+        std::process::exit(result.exit_code());
+    }
 
-    // --- Step 10: Create and run the console renderer ---
-    println!("[Setup] Starting console renderer...");
+    // --- Step 8: Create and run the console renderer ---
+    eprintln!("[Setup] Starting console renderer...");
     println!();
-    println!("Type your requests below. Read/Glob/Grep are auto-approved by hooks.");
+    println!("Type your requests below. Read/Glob/Grep/LS are auto-approved.");
     if caching {
         println!("💰 Prompt caching enabled: 90% cost savings on repeated content!");
         println!("   (Tools, system prompt, and conversation history are automatically cached)");
@@ -257,3 +237,34 @@ async fn main() -> Result<()> {
     println!("[Cleanup] Done.");
     Ok(())
 }
+
+/// Get the value following any of the given flags
+fn flag_value(args: &[String], names: &[&str]) -> Option<String> {
+    args.iter()
+        .position(|a| names.contains(&a.as_str()))
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+}
+
+/// Parse print mode options from the command line
+fn parse_print_options(args: &[String]) -> Result<PrintModeOptions> {
+    let mut options = PrintModeOptions::new();
+
+    if let Some(format) = flag_value(args, &["--output-format"]) {
+        let format: OutputFormat = format.parse().map_err(|e: String| anyhow!(e))?;
+        options = options.with_output_format(format);
+    }
+    if let Some(tools) = flag_value(args, &["--allow-tools"]) {
+        options = options.with_allowed_tools(
+            tools.split(',').map(str::trim).filter(|t| !t.is_empty()),
+        );
+    }
+    if let Some(max_turns) = flag_value(args, &["--max-turns"]) {
+        options = options.with_max_turns(max_turns.parse()?);
+    }
+    if let Some(timeout) = flag_value(args, &["--timeout"]) {
+        options = options.with_timeout(Duration::from_secs(timeout.parse()?));
+    }
+
+    Ok(options)
+}
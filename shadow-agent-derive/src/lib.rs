@@ -0,0 +1,301 @@
+//! Derive macros for shadow-agent-sdk
+//!
+//! `#[derive(ToolInput)]` generates a tool's input schema from the struct it
+//! deserializes, so the two can't drift apart. Use it through the re-export
+//! in `shadow_agent_sdk::tools`:
+//!
+//! ```ignore
+//! use serde::Deserialize;
+//! use shadow_agent_sdk::tools::ToolInput;
+//!
+//! #[derive(Deserialize, ToolInput)]
+//! struct SearchInput {
+//!     /// What to search for
+//!     query: String,
+//!     /// Maximum number of results
+//!     limit: Option<u32>,
+//! }
+//! ```
+//!
+//! Doc comments become descriptions, and a field is required unless it's
+//! an `Option` or has `#[serde(default)]`. Serde's `rename`, `rename_all`,
+//! `default` and `skip` are honored. Enums must have only unit variants and
+//! become string enums.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Error, Fields, Result};
+
+/// Derive `shadow_agent_sdk::tools::ToolInput`
+#[proc_macro_derive(ToolInput, attributes(serde))]
+pub fn derive_tool_input(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn expand(mut input: DeriveInput) -> Result<TokenStream2> {
+    let container = SerdeAttrs::parse(&input.attrs)?;
+    let body = match &input.data {
+        Data::Struct(data) => struct_schema(&data.fields, &container)?,
+        Data::Enum(data) => {
+            let mut names = Vec::new();
+            for variant in &data.variants {
+                if !matches!(variant.fields, Fields::Unit) {
+                    return Err(Error::new(variant.span(), "ToolInput enums can only have unit variants"));
+                }
+                let attrs = SerdeAttrs::parse(&variant.attrs)?;
+                if attrs.skip {
+                    continue;
+                }
+                names.push(attrs.name(&variant.ident.to_string(), NameKind::Variant, container.rename_all.as_deref())?);
+            }
+            quote! { __private::serde_json::json!({ "type": "string", "enum": [#(#names),*] }) }
+        }
+        Data::Union(_) => return Err(Error::new(input.span(), "ToolInput can't be derived for unions")),
+    };
+
+    let type_params: Vec<_> = input.generics.type_params().map(|p| p.ident.clone()).collect();
+    let where_clause = input.generics.make_where_clause();
+    for param in type_params {
+        where_clause.predicates.push(syn::parse_quote!(#param: ::shadow_agent_sdk::tools::ToolInput));
+    }
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::shadow_agent_sdk::tools::ToolInput for #name #type_generics #where_clause {
+            fn json_schema() -> ::shadow_agent_sdk::tools::__private::serde_json::Value {
+                use ::shadow_agent_sdk::tools::__private;
+                #body
+            }
+        }
+    })
+}
+
+/// An object schema with a property per (unskipped) named field
+fn struct_schema(fields: &Fields, container: &SerdeAttrs) -> Result<TokenStream2> {
+    let Fields::Named(fields) = fields else {
+        return Err(Error::new(fields.span(), "ToolInput needs a struct with named fields"));
+    };
+    let mut properties = Vec::new();
+    for field in &fields.named {
+        let attrs = SerdeAttrs::parse(&field.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+        if attrs.flatten {
+            return Err(Error::new(field.span(), "ToolInput doesn't support #[serde(flatten)]"));
+        }
+        let ident = field.ident.as_ref().expect("named field");
+        let name = attrs.name(ident.to_string().trim_start_matches("r#"), NameKind::Field, container.rename_all.as_deref())?;
+        let ty = &field.ty;
+        let describe = doc_comment(&field.attrs).map(|doc| {
+            quote! {
+                if let Some(object) = schema.as_object_mut() {
+                    object.insert("description".to_string(), __private::serde_json::Value::from(#doc));
+                }
+            }
+        });
+        let require = (!attrs.default && !container.default).then(|| {
+            quote! {
+                if !<#ty as ::shadow_agent_sdk::tools::ToolInput>::optional() {
+                    required.push(__private::serde_json::Value::from(#name));
+                }
+            }
+        });
+        properties.push(quote! {
+            let mut schema = <#ty as ::shadow_agent_sdk::tools::ToolInput>::json_schema();
+            #describe
+            properties.insert(#name.to_string(), schema);
+            #require
+        });
+    }
+    Ok(quote! {
+        let mut properties = __private::serde_json::Map::new();
+        let mut required: Vec<__private::serde_json::Value> = Vec::new();
+        #(#properties)*
+        let mut schema = __private::serde_json::Map::new();
+        schema.insert("type".to_string(), "object".into());
+        schema.insert("properties".to_string(), properties.into());
+        if !required.is_empty() {
+            schema.insert("required".to_string(), required.into());
+        }
+        schema.into()
+    })
+}
+
+/// The doc comment on an item, as one line per paragraph
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let mut paragraphs: Vec<String> = vec![String::new()];
+    for attr in attrs.iter().filter(|a| a.path().is_ident("doc")) {
+        let syn::Meta::NameValue(meta) = &attr.meta else { continue };
+        let syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(line), .. }) = &meta.value else { continue };
+        let line = line.value();
+        let line = line.trim();
+        let current = paragraphs.last_mut().expect("one paragraph");
+        if line.is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(String::new());
+            }
+        } else {
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(line);
+        }
+    }
+    paragraphs.retain(|p| !p.is_empty());
+    (!paragraphs.is_empty()).then(|| paragraphs.join("\n\n"))
+}
+
+/// The `#[serde(...)]` options that change the schema
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<String>,
+    default: bool,
+    skip: bool,
+    flatten: bool,
+}
+
+impl SerdeAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut parsed = SerdeAttrs::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                let key = meta.path.get_ident().map(|i| i.to_string()).unwrap_or_default();
+                match key.as_str() {
+                    "rename" | "rename_all" => {
+                        let value = if meta.input.peek(syn::Token![=]) {
+                            Some(meta.value()?.parse::<syn::LitStr>()?.value())
+                        } else {
+                            // rename(deserialize = "...") is the name that matters here
+                            let mut deserialize = None;
+                            meta.parse_nested_meta(|inner| {
+                                let value = inner.value()?.parse::<syn::LitStr>()?.value();
+                                if inner.path.is_ident("deserialize") {
+                                    deserialize = Some(value);
+                                }
+                                Ok(())
+                            })?;
+                            deserialize
+                        };
+                        if key == "rename" {
+                            parsed.rename = value;
+                        } else {
+                            parsed.rename_all = value;
+                        }
+                    }
+                    "default" => {
+                        parsed.default = true;
+                        skip_value(&meta)?;
+                    }
+                    "skip" | "skip_deserializing" => parsed.skip = true,
+                    "flatten" => parsed.flatten = true,
+                    _ => skip_value(&meta)?,
+                }
+                Ok(())
+            })?;
+        }
+        Ok(parsed)
+    }
+
+    /// The name serde reads this field or variant under
+    fn name(&self, ident: &str, kind: NameKind, rename_all: Option<&str>) -> Result<String> {
+        if let Some(rename) = &self.rename {
+            return Ok(rename.clone());
+        }
+        match rename_all {
+            Some(rule) => apply_rename_rule(ident, rule, kind),
+            None => Ok(ident.to_string()),
+        }
+    }
+}
+
+/// Consume an option's `= value` or `(...)`, if it has one
+fn skip_value(meta: &syn::meta::ParseNestedMeta) -> Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        let content;
+        syn::parenthesized!(content in meta.input);
+        content.parse::<TokenStream2>()?;
+    }
+    Ok(())
+}
+
+/// What a `rename_all` rule is applied to; serde renames each differently
+#[derive(Clone, Copy, PartialEq)]
+enum NameKind {
+    /// A snake_case field name
+    Field,
+    /// A PascalCase variant name
+    Variant,
+}
+
+/// Serde's `rename_all` rules, as in serde_derive's `RenameRule`
+fn apply_rename_rule(ident: &str, rule: &str, kind: NameKind) -> Result<String> {
+    const RULES: &[&str] = &[
+        "lowercase",
+        "UPPERCASE",
+        "PascalCase",
+        "camelCase",
+        "snake_case",
+        "SCREAMING_SNAKE_CASE",
+        "kebab-case",
+        "SCREAMING-KEBAB-CASE",
+    ];
+    if !RULES.contains(&rule) {
+        return Err(Error::new(proc_macro2::Span::call_site(), format!("unknown rename rule `{}`", rule)));
+    }
+    let lower_first = |name: String| {
+        let mut chars = name.chars();
+        chars.next().map(|c| c.to_ascii_lowercase().to_string() + chars.as_str()).unwrap_or_default()
+    };
+    Ok(match kind {
+        NameKind::Field => match rule {
+            "lowercase" | "snake_case" => ident.to_string(),
+            "UPPERCASE" | "SCREAMING_SNAKE_CASE" => ident.to_ascii_uppercase(),
+            "kebab-case" => ident.replace('_', "-"),
+            "SCREAMING-KEBAB-CASE" => ident.to_ascii_uppercase().replace('_', "-"),
+            _ => {
+                let mut pascal = String::new();
+                let mut capitalize = true;
+                for c in ident.chars() {
+                    if c == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        pascal.push(c.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        pascal.push(c);
+                    }
+                }
+                if rule == "camelCase" {
+                    lower_first(pascal)
+                } else {
+                    pascal
+                }
+            }
+        },
+        NameKind::Variant => {
+            let mut snake = String::new();
+            for (i, c) in ident.char_indices() {
+                if i > 0 && c.is_uppercase() {
+                    snake.push('_');
+                }
+                snake.push(c.to_ascii_lowercase());
+            }
+            match rule {
+                "PascalCase" => ident.to_string(),
+                "lowercase" => ident.to_ascii_lowercase(),
+                "UPPERCASE" => ident.to_ascii_uppercase(),
+                "camelCase" => lower_first(ident.to_string()),
+                "snake_case" => snake,
+                "SCREAMING_SNAKE_CASE" => snake.to_ascii_uppercase(),
+                "kebab-case" => snake.replace('_', "-"),
+                _ => snake.to_ascii_uppercase().replace('_', "-"),
+            }
+        }
+    })
+}
@@ -0,0 +1,286 @@
+//! Batch Evaluation
+//!
+//! Runs many single-shot prompts through the Message Batches API using an
+//! `AgentConfig`'s system prompt, sampling and cache settings, and writes
+//! each exchange into a fresh session, so bulk runs (e.g. nightly evals) can
+//! be inspected with the same tooling as interactive sessions.
+//!
+//! Tools are not executed: each session gets the prompt and the model's
+//! first reply, nothing more.
+
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::time::Duration;
+
+use super::AgentConfig;
+use crate::helpers::schema_instructions;
+use crate::llm::{
+    AnthropicBatchClient, BatchId, CacheControl, Message, MessageRequest, MessageResponse,
+    SystemBlock, SystemPrompt,
+};
+use crate::session::{AgentSession, SessionStorage};
+
+/// Session custom metadata key holding the batch id
+pub const BATCH_ID_METADATA_KEY: &str = "batch_id";
+
+/// Session custom metadata key holding the error of a failed request
+pub const BATCH_ERROR_METADATA_KEY: &str = "batch_error";
+
+/// Result of one prompt in a batch evaluation
+#[derive(Debug)]
+pub struct BatchOutcome {
+    /// Session the exchange was written to
+    pub session_id: String,
+    /// The model's reply, or why the request failed
+    pub result: Result<MessageResponse>,
+}
+
+/// Runs (session id, prompt) pairs through the Message Batches API
+///
+/// # Example
+///
+/// ```ignore
+/// let config = AgentConfig::new("You are a grader").with_prompt_caching(true);
+/// let eval = BatchEval::new(AnthropicProvider::from_env()?.batch_client(), config);
+///
+/// let outcomes = eval.run(vec![
+///     ("eval-001".to_string(), "What is 2 + 2?".to_string()),
+///     ("eval-002".to_string(), "Name a prime above 100".to_string()),
+/// ]).await?;
+/// ```
+pub struct BatchEval {
+    client: AnthropicBatchClient,
+    config: AgentConfig,
+    storage: SessionStorage,
+    poll_interval: Duration,
+}
+
+impl BatchEval {
+    /// Create a batch evaluation using the client's model and max tokens
+    pub fn new(client: AnthropicBatchClient, config: AgentConfig) -> Self {
+        Self {
+            client,
+            config,
+            storage: SessionStorage::new(),
+            poll_interval: Duration::from_secs(30),
+        }
+    }
+
+    /// Write sessions to custom storage
+    pub fn with_storage(mut self, storage: SessionStorage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// How long to wait between status polls (default: 30 seconds)
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Submit the prompts, wait for the batch to end and write the sessions
+    ///
+    /// Returns one outcome per prompt, in input order. Individual request
+    /// failures are reported in the outcomes; the call only fails if the
+    /// batch itself could not be submitted or collected.
+    pub async fn run(&self, prompts: Vec<(String, String)>) -> Result<Vec<BatchOutcome>> {
+        let id = self.submit(&prompts).await?;
+        self.collect(&id, prompts).await
+    }
+
+    /// Submit the prompts as a batch without waiting for it
+    ///
+    /// Fails before submitting if a session id is repeated or already exists.
+    /// Pass the returned id and the same prompts to `collect` later.
+    pub async fn submit(&self, prompts: &[(String, String)]) -> Result<BatchId> {
+        let mut seen = HashSet::new();
+        for (session_id, _) in prompts {
+            if !seen.insert(session_id.as_str()) {
+                anyhow::bail!("Session id {} appears more than once in the batch", session_id);
+            }
+            if AgentSession::exists_with_storage(session_id, &self.storage) {
+                anyhow::bail!("Session {} already exists", session_id);
+            }
+        }
+
+        let requests = prompts.iter().map(|(_, prompt)| self.build_request(prompt)).collect();
+        self.client.submit(requests).await
+    }
+
+    /// Wait for a submitted batch to end and write its results into sessions
+    pub async fn collect(&self, id: &BatchId, prompts: Vec<(String, String)>) -> Result<Vec<BatchOutcome>> {
+        self.client.wait(id, self.poll_interval).await?;
+        let results = self.client.results(id).await?;
+        if results.len() != prompts.len() {
+            anyhow::bail!(
+                "Batch {} has {} results for {} prompts",
+                id,
+                results.len(),
+                prompts.len()
+            );
+        }
+
+        let mut outcomes = Vec::with_capacity(prompts.len());
+        for ((session_id, prompt), result) in prompts.into_iter().zip(results) {
+            self.write_session(id, &session_id, prompt, &result)
+                .with_context(|| format!("Failed to write session {}", session_id))?;
+            outcomes.push(BatchOutcome { session_id, result });
+        }
+        tracing::info!("[BatchEval] Wrote {} sessions from batch {}", outcomes.len(), id);
+        Ok(outcomes)
+    }
+
+    /// Build the single-shot request for a prompt
+    ///
+    /// Uses the config's system prompt (plus output schema instructions),
+    /// thinking and sampling settings. With prompt caching enabled, the
+    /// system prompt is marked cacheable so requests in the batch share it.
+    pub fn build_request(&self, prompt: &str) -> MessageRequest {
+        let mut text = self.config.system_prompt.clone();
+        if let Some(ref schema) = self.config.output_schema {
+            text = format!("{}\n\n{}", text, schema_instructions(schema));
+        }
+        let system = if self.config.enable_prompt_caching {
+            SystemPrompt::Blocks(vec![SystemBlock::new(text).with_cache_control(CacheControl::ephemeral())])
+        } else {
+            SystemPrompt::Text(text)
+        };
+
+        let generation = &self.config.generation;
+        MessageRequest {
+            model: self.client.model().to_string(),
+            max_tokens: generation.max_tokens.unwrap_or_else(|| self.client.max_tokens()),
+            messages: vec![Message::user(prompt)],
+            system: Some(system),
+            tools: None,
+            tool_choice: None,
+            thinking: self.config.thinking_for(1),
+            // Thinking requires temperature 1
+            temperature: if self.config.thinking.is_some() {
+                Some(1.0)
+            } else {
+                generation.temperature
+            },
+            top_p: generation.top_p,
+            stop_sequences: (!generation.stop_sequences.is_empty())
+                .then(|| generation.stop_sequences.clone()),
+            stream: None,
+        }
+    }
+
+    fn write_session(
+        &self,
+        id: &BatchId,
+        session_id: &str,
+        prompt: String,
+        result: &Result<MessageResponse>,
+    ) -> Result<()> {
+        let mut session = AgentSession::new_with_storage(
+            session_id,
+            "batch",
+            session_id,
+            format!("Batch {}", id),
+            self.storage.clone(),
+        )?;
+        session.set_model(self.client.model());
+        session.set_provider("anthropic");
+        session.set_custom(BATCH_ID_METADATA_KEY, id.to_string());
+        if let Err(e) = result {
+            session.set_custom(BATCH_ERROR_METADATA_KEY, e.to_string());
+        }
+
+        session.add_message(Message::user(prompt))?;
+        if let Ok(response) = result {
+            session.add_message(Message::assistant_with_blocks(response.content.clone()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::batch::tests::{errored_line, mock_batch_api, succeeded_line};
+
+    fn prompts() -> Vec<(String, String)> {
+        vec![
+            ("eval-0".to_string(), "first prompt".to_string()),
+            ("eval-1".to_string(), "second prompt".to_string()),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_run_writes_fresh_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = SessionStorage::with_dir(dir.path());
+        let results = [succeeded_line("req-0", "answer"), errored_line("req-1", "overloaded")].join("\n");
+        let api = mock_batch_api(1, 2, results).await;
+
+        let config = AgentConfig::new("You are a grader")
+            .with_prompt_caching(true)
+            .with_temperature(0.2);
+        let eval = BatchEval::new(api.client().with_max_tokens(64), config)
+            .with_storage(storage.clone())
+            .with_poll_interval(Duration::from_millis(1));
+
+        let outcomes = eval.run(prompts()).await.unwrap();
+        assert_eq!(outcomes[0].session_id, "eval-0");
+        assert_eq!(outcomes[0].result.as_ref().unwrap().text(), "answer");
+        assert!(outcomes[1].result.is_err());
+
+        let params = api.submitted.lock().unwrap()[0]["requests"][1]["params"].clone();
+        assert_eq!(params["model"], "test-model");
+        assert_eq!(params["max_tokens"], 64);
+        assert_eq!(params["system"][0]["text"], "You are a grader");
+        assert_eq!(params["system"][0]["cache_control"]["type"], "ephemeral");
+        assert_eq!(params["messages"][0]["content"], "second prompt");
+        assert!(params.get("tools").is_none());
+
+        let ok = AgentSession::load_with_storage("eval-0", storage.clone()).unwrap();
+        assert_eq!(ok.history().len(), 2);
+        assert_eq!(ok.history()[0].text(), Some("first prompt"));
+        assert_eq!(ok.get_custom(BATCH_ID_METADATA_KEY).unwrap(), "msgbatch_1");
+        assert!(ok.get_custom(BATCH_ERROR_METADATA_KEY).is_none());
+
+        let failed = AgentSession::load_with_storage("eval-1", storage).unwrap();
+        assert_eq!(failed.history().len(), 1);
+        let error = failed.get_custom(BATCH_ERROR_METADATA_KEY).unwrap().as_str().unwrap();
+        assert!(error.contains("overloaded"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_existing_and_duplicate_sessions_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = SessionStorage::with_dir(dir.path());
+        let api = mock_batch_api(0, 2, String::new()).await;
+        let eval = BatchEval::new(api.client(), AgentConfig::new("prompt")).with_storage(storage.clone());
+
+        let mut duplicated = prompts();
+        duplicated[1].0 = "eval-0".to_string();
+        assert!(eval.submit(&duplicated).await.is_err());
+
+        AgentSession::new_with_storage("eval-1", "batch", "", "", storage).unwrap();
+        assert!(eval.submit(&prompts()).await.is_err());
+        assert!(api.submitted.lock().unwrap().is_empty(), "nothing should be submitted");
+    }
+
+    #[test]
+    fn test_build_request_without_caching() {
+        let config = AgentConfig::new("Be brief")
+            .with_prompt_caching(false)
+            .with_output_schema(serde_json::json!({"type": "object"}))
+            .with_thinking(1024);
+        let eval = BatchEval::new(AnthropicBatchClient::new("key").with_model("m"), config);
+
+        let request = eval.build_request("hello");
+        match request.system {
+            Some(SystemPrompt::Text(ref text)) => {
+                assert!(text.starts_with("Be brief\n\n# Output format"), "{text}");
+            }
+            ref other => panic!("expected a text system prompt, got {other:?}"),
+        }
+        assert_eq!(request.temperature, Some(1.0));
+        assert!(request.thinking.is_some());
+        assert!(request.tools.is_none());
+    }
+}
@@ -3,11 +3,84 @@
 //! Configuration options for the StandardAgent.
 
 use std::sync::Arc;
+use std::time::Duration;
 
-use crate::helpers::InjectionChain;
+use crate::helpers::{
+    AttachmentTags, Debugger, DebuggerConfig, FileEnforcement, InjectionChain, InputPreprocessor,
+    LoopDetectionConfig, MemoryConfig, NamerConfig, OutputOverflow, PromptTemplate, Redactor,
+    TemplateError, TemplateVars,
+};
 use crate::hooks::HookRegistry;
-use crate::llm::{LlmProvider, ThinkingConfig};
-use crate::tools::ToolRegistry;
+use crate::llm::{GenerationParams, LlmProvider, ResponseFormat, ThinkingConfig, ToolChoice, WebSearchTool};
+use crate::permissions::{PermissionTimeout, TimeoutDecision};
+use crate::tools::{InputValidation, ToolRegistry};
+
+use super::context_window::{ContextOverflow, DEFAULT_MIN_OUTPUT_TOKENS};
+use super::subagents::SubAgentRegistry;
+use super::task_tool::ForwardMode;
+
+/// Default follow-up message used by auto-continue
+const DEFAULT_CONTINUE_PROMPT: &str =
+    "Continue exactly where you left off. Do not repeat what you already wrote.";
+
+/// Default text recorded in the history when the user interrupts a response
+pub const DEFAULT_INTERRUPT_MARKER: &str =
+    "<vibe-working-agent-system>User interrupted this message</vibe-working-agent-system>";
+
+/// Thinking budget per phase of a turn
+///
+/// The first LLM call of a turn usually needs the deepest thinking; the
+/// calls that follow tool results mostly don't. Iterations are counted from
+/// 1, like `AgentContext::current_iteration`.
+///
+/// ```ignore
+/// let config = AgentConfig::new("You are a coding assistant")
+///     .with_thinking_policy(ThinkingPolicy::new(16000).with_tool_followup_budget(2048).disable_after(10));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThinkingPolicy {
+    /// Budget for the first call of a turn
+    pub first_call_budget: u32,
+    /// Budget for the calls after it (None = no thinking)
+    pub tool_followup_budget: Option<u32>,
+    /// Stop thinking after this many iterations of a turn (None = never)
+    pub disable_after_iterations: Option<usize>,
+}
+
+impl ThinkingPolicy {
+    /// Think with `first_call_budget` on the first call only
+    pub fn new(first_call_budget: u32) -> Self {
+        Self {
+            first_call_budget,
+            tool_followup_budget: None,
+            disable_after_iterations: None,
+        }
+    }
+
+    /// Think with `budget` on the calls after tool results
+    pub fn with_tool_followup_budget(mut self, budget: u32) -> Self {
+        self.tool_followup_budget = Some(budget);
+        self
+    }
+
+    /// Stop thinking after `iterations` iterations of a turn
+    pub fn disable_after(mut self, iterations: usize) -> Self {
+        self.disable_after_iterations = Some(iterations);
+        self
+    }
+
+    /// Budget for an iteration of a turn (None = no thinking)
+    pub fn budget_for(&self, iteration: usize) -> Option<u32> {
+        if self.disable_after_iterations.is_some_and(|limit| iteration > limit) {
+            return None;
+        }
+        if iteration <= 1 {
+            Some(self.first_call_budget)
+        } else {
+            self.tool_followup_budget
+        }
+    }
+}
 
 /// Configuration for a StandardAgent
 ///
@@ -35,12 +108,91 @@ pub struct AgentConfig {
     /// Maximum number of tool iterations per turn (prevents infinite loops)
     pub max_tool_iterations: usize,
 
+    /// How many times a turn may continue after hitting max tokens (0 = never)
+    pub max_continuations: usize,
+
+    /// User message sent to ask the model to continue a truncated response
+    pub continue_prompt: String,
+
+    /// How many times a turn may resume after the stream drops mid-response
+    pub max_stream_resumes: usize,
+
+    /// Thresholds for detecting a repeated failing tool call (None = disabled)
+    pub loop_detection: Option<LoopDetectionConfig>,
+
+    /// JSON Schema the final answer of each turn must match (optional)
+    pub output_schema: Option<serde_json::Value>,
+
+    /// How many times the model is asked to fix a final answer that fails validation
+    pub max_output_retries: usize,
+
+    /// Longest a turn may run before it stops after the current call (None = no limit)
+    pub turn_timeout: Option<Duration>,
+
+    /// Tool choice for the first LLM call of each turn (None = the provider's default)
+    /// Later calls in the turn use `ToolChoice::Auto`.
+    pub tool_choice: Option<ToolChoice>,
+
+    /// How long a permission request waits for an answer (None = forever)
+    pub permission_timeout: Option<PermissionTimeout>,
+
+    /// Whether Edit/Write refuse files the agent hasn't read (see `FileTracker`)
+    pub file_enforcement: FileEnforcement,
+
     /// Whether to auto-save session after each turn
     pub auto_save_session: bool,
 
     /// Whether to enable debug logging (API calls, tool calls)
     pub debug_enabled: bool,
 
+    /// Segment, size and output options for the debugger
+    pub debug_config: DebuggerConfig,
+
+    /// Debugger to log to instead of one created in the session directory
+    pub debugger: Option<Arc<Debugger>>,
+
+    /// Redactor applied to tool output before it is stored in the session
+    /// (None = store tool output verbatim)
+    pub tool_result_redactor: Option<Redactor>,
+
+    /// When to save long tool output to a file instead of the tool result
+    /// (None = always send the whole output)
+    pub output_overflow: Option<OutputOverflow>,
+
+    /// Which tools have their input checked against their schema before
+    /// they run (None = no checks)
+    pub input_validation: Option<InputValidation>,
+
+    /// What to do when a request won't fit the model's context window
+    pub context_overflow: ContextOverflow,
+
+    /// Context window to check requests against (None = the model's
+    /// `ModelCapabilities::max_context_tokens`; no check if that is unknown too)
+    pub context_window: Option<u32>,
+
+    /// Max tokens a reply is never cut below when the prompt is large
+    /// (see `AgentConfig::with_min_output_tokens`)
+    pub min_output_tokens: u32,
+
+    /// Anthropic's server-side web search tool (None = not offered)
+    pub web_search: Option<WebSearchTool>,
+
+    /// Subagent types advertised in the system prompt (spawned via `TaskTool`)
+    pub subagents: Option<Arc<SubAgentRegistry>>,
+
+    /// Turns user input into message content (None = the text as-is)
+    /// Defaults to `AttachmentTags`, which reads `<vibe-work-attachment>` tags.
+    pub input_preprocessor: Option<Arc<dyn InputPreprocessor>>,
+
+    /// Text recorded in the history where the user interrupted a response
+    pub interrupt_marker: String,
+
+    /// How much subagent output `TaskTool` forwards to this agent's stream
+    pub forward_subagent_output: ForwardMode,
+
+    /// Project memory files sent after the system prompt (None = not loaded)
+    pub project_memory: Option<MemoryConfig>,
+
     /// Whether to enable streaming responses from the LLM
     pub streaming_enabled: bool,
 
@@ -48,6 +200,17 @@ pub struct AgentConfig {
     /// When enabled, Claude will show its step-by-step reasoning process.
     pub thinking: Option<ThinkingConfig>,
 
+    /// Per-iteration thinking budgets (None = `thinking` on every call)
+    pub thinking_policy: Option<ThinkingPolicy>,
+
+    /// Send the interleaved-thinking beta with thinking requests
+    /// Turn off for models that reject it.
+    pub interleaved_thinking: bool,
+
+    /// Sampling parameters (temperature, top_p, stop sequences)
+    /// Unset values are left to the provider's defaults.
+    pub generation: GenerationParams,
+
     /// Hooks for intercepting agent behavior
     /// Use hooks to block dangerous operations, modify tool inputs, auto-approve tools, etc.
     pub hooks: Option<Arc<HookRegistry>>,
@@ -61,6 +224,7 @@ pub struct AgentConfig {
     /// - The last tool definition (caches all tools)
     /// - The system prompt (caches static instructions)
     /// - The last message block before each LLM call (caches conversation history)
+    ///
     /// This significantly reduces costs and latency for multi-turn conversations.
     pub enable_prompt_caching: bool,
 
@@ -69,17 +233,14 @@ pub struct AgentConfig {
     /// lightweight/fast model). If not set, the main agent LLM is used.
     pub naming_llm: Option<Arc<dyn LlmProvider>>,
 
-    /// Whether to enable hook short-circuiting.
-    ///
-    /// When enabled (false by default), if a hook returns `Deny`, subsequent hooks
-    /// will not run. This provides a performance optimization but means:
-    /// - Later security hooks might not run
-    /// - Logging/monitoring hooks might not fire
-    ///
-    /// **Default: false** (safer - all hooks run, Deny wins at the end)
+    /// Naming model, prompt and limits
+    pub namer: NamerConfig,
+
+    /// Ignored: hooks always stop at the first Deny
     ///
-    /// Set to true only if you need the performance optimization and understand
-    /// that security/monitoring hooks may be bypassed.
+    /// Kept so code that set it still compiles; order security hooks with
+    /// `HookMatcher::with_priority` instead.
+    #[deprecated(note = "hooks always stop at the first Deny; order them with `HookMatcher::with_priority`")]
     pub hook_short_circuit: bool,
 
     /// **DANGEROUS:** Skip all permission checks.
@@ -101,6 +262,12 @@ pub struct AgentConfig {
     ///
     /// This can be changed at runtime via `AgentHandle::set_dangerous_skip_permissions()`.
     pub dangerous_skip_permissions: bool,
+
+    /// Whether tools that aren't read-only are described instead of run
+    ///
+    /// None (the default) leaves the session's setting alone, so a resumed
+    /// session or an earlier `AgentHandle::set_dry_run()` keeps its mode.
+    pub dry_run: Option<bool>,
 }
 
 impl AgentConfig {
@@ -111,19 +278,78 @@ impl AgentConfig {
             tools: None,
             injections: InjectionChain::new(),
             max_tool_iterations: 100,
+            max_continuations: 0,
+            continue_prompt: DEFAULT_CONTINUE_PROMPT.to_string(),
+            max_stream_resumes: 2,
+            loop_detection: Some(LoopDetectionConfig::default()),
+            output_schema: None,
+            max_output_retries: 2,
+            turn_timeout: None,
+            tool_choice: None,
+            permission_timeout: Some(PermissionTimeout::default()),
+            file_enforcement: FileEnforcement::Off,
             auto_save_session: true,
             debug_enabled: false,
+            debug_config: DebuggerConfig::default(),
+            debugger: None,
+            tool_result_redactor: None,
+            output_overflow: Some(OutputOverflow::default()),
+            input_validation: Some(InputValidation::new()),
+            context_overflow: ContextOverflow::default(),
+            context_window: None,
+            min_output_tokens: DEFAULT_MIN_OUTPUT_TOKENS,
+            web_search: None,
+            subagents: None,
+            forward_subagent_output: ForwardMode::None,
+            input_preprocessor: Some(Arc::new(AttachmentTags::default())),
+            interrupt_marker: DEFAULT_INTERRUPT_MARKER.to_string(),
+            project_memory: None,
             streaming_enabled: false,
             thinking: None,
+            thinking_policy: None,
+            interleaved_thinking: true,
+            generation: GenerationParams::default(),
             hooks: None,
             auto_name_conversation: true,
             enable_prompt_caching: true,
             naming_llm: None,
-            hook_short_circuit: false, // Safe default: all hooks run
+            namer: NamerConfig::default(),
+            #[allow(deprecated)]
+            hook_short_circuit: false,
             dangerous_skip_permissions: false, // Safe default: permissions enforced
+            dry_run: None,
         }
     }
 
+    /// Load the agent options from a TOML or JSON file
+    ///
+    /// Applies the system prompt, streaming, thinking, prompt caching and
+    /// iteration limit. Tools, model, permissions and MCP servers need the
+    /// provider and registry, so use `load_agent` for those.
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+        let file = super::loader::AgentFile::load(path)?;
+        Ok(file.apply(Self::new("")))
+    }
+
+    /// Set the system prompt by rendering a template
+    ///
+    /// The template is rendered once, here; use `TemplateInjection` for
+    /// text that should be re-rendered each turn.
+    ///
+    /// ```ignore
+    /// let template = PromptTemplate::from_file("prompts/coder.md")?;
+    /// let vars = TemplateVars::from([("name".to_string(), "Coder".to_string())]);
+    /// let config = AgentConfig::new("").with_system_prompt_template(&template, &vars)?;
+    /// ```
+    pub fn with_system_prompt_template(
+        mut self,
+        template: &PromptTemplate,
+        vars: &TemplateVars,
+    ) -> Result<Self, TemplateError> {
+        self.system_prompt = template.render(vars)?;
+        Ok(self)
+    }
+
     /// Set the tool registry
     pub fn with_tools(mut self, tools: Arc<ToolRegistry>) -> Self {
         self.tools = Some(tools);
@@ -137,6 +363,9 @@ impl AgentConfig {
     }
 
     /// Add a single injection to the chain
+    ///
+    /// Its `priority()` and `frequency()` decide where and how often it runs
+    /// (see `FnInjection::with_priority` / `with_frequency`).
     pub fn with_injection<I: crate::helpers::ContextInjection + 'static>(
         mut self,
         injection: I,
@@ -146,6 +375,9 @@ impl AgentConfig {
     }
 
     /// Add a function-based injection
+    ///
+    /// Runs before every LLM call with priority 0. The name is what
+    /// `InjectionChain::set_enabled` takes.
     pub fn with_injection_fn<F>(mut self, name: impl Into<String>, func: F) -> Self
     where
         F: Fn(&crate::runtime::AgentInternals, Vec<crate::llm::Message>) -> Vec<crate::llm::Message>
@@ -163,6 +395,136 @@ impl AgentConfig {
         self
     }
 
+    /// Continue automatically when a response is cut off at max tokens
+    ///
+    /// The partial response stays in the history and the model is asked to
+    /// continue (see [`with_continue_prompt`](Self::with_continue_prompt)), up
+    /// to `max_continuations` times per turn. Output is stitched together, so
+    /// the response produces a single `TextComplete`. A response cut off in
+    /// the middle of a tool call is discarded and retried instead.
+    pub fn with_auto_continue(mut self, max_continuations: usize) -> Self {
+        self.max_continuations = max_continuations;
+        self
+    }
+
+    /// Set the message sent to ask the model to continue a truncated response
+    pub fn with_continue_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.continue_prompt = prompt.into();
+        self
+    }
+
+    /// Set how many times a turn may resume after the stream drops (default: 2)
+    ///
+    /// When a streamed response fails partway through, the text and complete
+    /// tool calls received so far are kept and the model is asked to continue
+    /// with the continue prompt, like a max tokens cut. Partial thinking and
+    /// partial tool input are discarded. With 0, or once the resumes are used
+    /// up, the partial response is kept and the turn ends. A stream that
+    /// fails before any content arrives still fails the turn.
+    pub fn with_stream_resumes(mut self, max_resumes: usize) -> Self {
+        self.max_stream_resumes = max_resumes;
+        self
+    }
+
+    /// Enable or disable loop detection
+    ///
+    /// When enabled (default), the agent watches for the same failing tool
+    /// call (same tool, same input) repeating within a turn. After
+    /// `warn_after` repeats the model gets a system reminder to change
+    /// approach; after `abort_after` more the turn ends with a status
+    /// message. Both fire the `LoopDetected` hook.
+    pub fn with_loop_detection(mut self, enabled: bool) -> Self {
+        self.loop_detection = enabled.then(|| self.loop_detection.unwrap_or_default());
+        self
+    }
+
+    /// Set the loop detection thresholds (enables loop detection)
+    ///
+    /// `warn_after` repeats of a failing call trigger a reminder, and
+    /// `abort_after` further repeats end the turn.
+    pub fn with_loop_thresholds(mut self, warn_after: usize, abort_after: usize) -> Self {
+        self.loop_detection = Some(LoopDetectionConfig {
+            warn_after,
+            abort_after,
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Require the final answer of each turn to be JSON matching a schema
+    ///
+    /// The schema is described in the system prompt. When the turn ends, the
+    /// last assistant text is parsed and validated; if it doesn't match, the
+    /// model is told what is wrong and asked again (up to
+    /// [`with_output_retries`](Self::with_output_retries) times). A valid
+    /// result is sent as `OutputChunk::StructuredResult` and stored in the
+    /// session's custom metadata under `"structured_result"`.
+    pub fn with_output_schema(mut self, schema: serde_json::Value) -> Self {
+        self.output_schema = Some(schema);
+        self
+    }
+
+    /// Set how many times the model may retry an invalid structured answer
+    pub fn with_output_retries(mut self, retries: usize) -> Self {
+        self.max_output_retries = retries;
+        self
+    }
+
+    /// Limit how long a turn may run
+    ///
+    /// The deadline is checked between calls: the LLM call or tool running
+    /// when it passes finishes, tool calls not started yet get a "timed out"
+    /// result, and the turn ends with a note in the history and a `Status`
+    /// chunk. Combine with an interrupt to also cancel the running call.
+    /// `AgentHandle::send_input_with_options` overrides this for a single
+    /// turn.
+    pub fn with_turn_timeout(mut self, timeout: Duration) -> Self {
+        self.turn_timeout = Some(timeout);
+        self
+    }
+
+    /// Constrain how the model uses tools on the first LLM call of each turn
+    ///
+    /// Use `ToolChoice::tool("TodoWrite")` to always start a turn with a
+    /// plan, for example. Only the first call is constrained so the model
+    /// can still finish the turn with text; the rest of the turn uses
+    /// `ToolChoice::Auto`. `AgentHandle::send_input_with_options` overrides
+    /// this for a single turn.
+    pub fn with_tool_choice(mut self, choice: ToolChoice) -> Self {
+        self.tool_choice = Some(choice);
+        self
+    }
+
+    /// Set how long a permission request may go unanswered
+    ///
+    /// When the timeout runs out, `decision` applies: the call is denied
+    /// ("permission request timed out"), allowed, or denied and the turn
+    /// ended. A `Status` chunk reports it, and denials fire the
+    /// `PostToolUseFailure` hook. A response that arrives after the timeout
+    /// is dropped. Defaults to denying after 5 minutes.
+    pub fn with_permission_timeout(mut self, after: Duration, decision: TimeoutDecision) -> Self {
+        self.permission_timeout = Some(PermissionTimeout::new(after, decision));
+        self
+    }
+
+    /// Wait for permission responses forever
+    pub fn without_permission_timeout(mut self) -> Self {
+        self.permission_timeout = None;
+        self
+    }
+
+    /// Make Edit and Write check that the file was read first
+    ///
+    /// The agent gets a `FileTracker` resource that ReadTool updates. With
+    /// `Strict`, writing a file that wasn't read this session (or that
+    /// changed on disk since) fails; with `Warn` the write goes ahead with a
+    /// warning in the result. Hooks can change the level for single paths
+    /// through the tracker. Defaults to `Off`.
+    pub fn with_file_enforcement(mut self, enforcement: FileEnforcement) -> Self {
+        self.file_enforcement = enforcement;
+        self
+    }
+
     /// Set whether to auto-save session after each turn
     pub fn with_auto_save(mut self, auto_save: bool) -> Self {
         self.auto_save_session = auto_save;
@@ -178,6 +540,171 @@ impl AgentConfig {
         self
     }
 
+    /// Enable debug logging with segment, size and output limits
+    pub fn with_debug_config(mut self, config: DebuggerConfig) -> Self {
+        self.debug_enabled = true;
+        self.debug_config = config;
+        self
+    }
+
+    /// Log to a debugger the caller created (enables debug logging)
+    ///
+    /// Keep a clone to `subscribe()` to its events or read its live sink
+    /// address. Agents given the same debugger share its files and sink, which
+    /// is bound once when the debugger is created.
+    pub fn with_debugger(mut self, debugger: Arc<Debugger>) -> Self {
+        self.debug_enabled = true;
+        self.debugger = Some(debugger);
+        self
+    }
+
+    /// Enable or disable redaction of tool output stored in the session
+    ///
+    /// Uses the built-in `Redactor` patterns. Redaction runs on the final tool
+    /// output, after any truncation done by the tool itself.
+    pub fn with_redact_tool_results(mut self, enabled: bool) -> Self {
+        self.tool_result_redactor = enabled.then(Redactor::new);
+        self
+    }
+
+    /// Redact tool output stored in the session with a custom redactor
+    pub fn with_tool_result_redactor(mut self, redactor: Redactor) -> Self {
+        self.tool_result_redactor = Some(redactor);
+        self
+    }
+
+    /// Enable or disable saving long tool output to a file
+    ///
+    /// When enabled (default), a tool result over 50 KB is saved in the
+    /// session's `outputs/` directory, and the model gets its first and last
+    /// lines with the path of the file, which it can Read without a
+    /// permission prompt.
+    pub fn with_output_overflow(mut self, enabled: bool) -> Self {
+        self.output_overflow = enabled.then(|| self.output_overflow.unwrap_or_default());
+        self
+    }
+
+    /// Set the threshold and preview size for long tool output (enables it)
+    pub fn with_output_overflow_config(mut self, overflow: OutputOverflow) -> Self {
+        self.output_overflow = Some(overflow);
+        self
+    }
+
+    /// Enable or disable checking tool input against the tool's schema
+    ///
+    /// When enabled (default), a call whose input is missing required
+    /// fields or has fields of the wrong type isn't run; the model gets an
+    /// error listing the problems and the expected schema instead.
+    pub fn with_input_validation(mut self, enabled: bool) -> Self {
+        self.input_validation = enabled.then(|| self.input_validation.unwrap_or_default());
+        self
+    }
+
+    /// Run these tools without checking their input (enables validation)
+    ///
+    /// For tools whose schemas don't describe what they accept, like some
+    /// MCP tools.
+    pub fn with_unvalidated_tools<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let validation = self.input_validation.take().unwrap_or_default();
+        self.input_validation = Some(names.into_iter().fold(validation, |v, name| v.skip_tool(name)));
+        self
+    }
+
+    /// Set what happens when a request won't fit the context window
+    ///
+    /// Before each LLM call the request is estimated against the window
+    /// less the reply's max tokens (or the thinking budget, if larger). By
+    /// default the turn fails with `FrameworkError::ContextOverflow` before
+    /// anything is sent; `DropOldest` and `Compact` shrink the request by
+    /// whole turns, oldest first, keeping the current one.
+    pub fn with_context_overflow(mut self, strategy: ContextOverflow) -> Self {
+        self.context_overflow = strategy;
+        self
+    }
+
+    /// Check requests against a window of `tokens` instead of the model's
+    ///
+    /// For models missing from the capabilities table, or to keep requests
+    /// smaller than the model allows.
+    pub fn with_context_window(mut self, tokens: u32) -> Self {
+        self.context_window = Some(tokens);
+        self
+    }
+
+    /// Set the least max tokens a reply is given near the context limit
+    ///
+    /// Each call asks for the configured max tokens, or for what the context
+    /// window has left after the estimated prompt, if that is less, but
+    /// never for fewer than `tokens`. Requests are only shrunk or failed by
+    /// the [`context overflow`](Self::with_context_overflow) check when even
+    /// this much doesn't fit.
+    pub fn with_min_output_tokens(mut self, tokens: u32) -> Self {
+        self.min_output_tokens = tokens;
+        self
+    }
+
+    /// Advertise subagent types in the system prompt
+    ///
+    /// The list is refreshed before each LLM call, so edits to a
+    /// directory-backed registry show up without restarting. Register a
+    /// `TaskTool` with the same registry so the model can spawn them.
+    pub fn with_subagents(mut self, subagents: Arc<SubAgentRegistry>) -> Self {
+        self.subagents = Some(subagents);
+        self
+    }
+
+    /// Forward subagent output to this agent's stream
+    ///
+    /// Applies to the subagents `TaskTool` spawns, unless the tool was
+    /// given its own mode. Forwarded chunks arrive as
+    /// `OutputChunk::SubAgentOutput` with the subagent's session ID.
+    pub fn with_forward_subagent_output(mut self, mode: ForwardMode) -> Self {
+        self.forward_subagent_output = mode;
+        self
+    }
+
+    /// Read attachment tags named `tag` (`<tag>path</tag>`) instead of
+    /// `<vibe-work-attachment>`
+    pub fn with_attachment_tag(mut self, tag: impl Into<String>) -> Self {
+        self.input_preprocessor = Some(Arc::new(AttachmentTags::new(tag)));
+        self
+    }
+
+    /// Enable or disable attachment tag parsing
+    ///
+    /// When disabled, user input is sent exactly as typed. Attachments sent
+    /// with `AgentHandle::send_input_with_attachments` are still loaded.
+    pub fn with_attachment_parsing(mut self, enabled: bool) -> Self {
+        self.input_preprocessor = enabled.then(|| Arc::new(AttachmentTags::default()) as Arc<dyn InputPreprocessor>);
+        self
+    }
+
+    /// Replace attachment tag parsing with a custom preprocessor
+    pub fn with_input_preprocessor<P: InputPreprocessor + 'static>(mut self, preprocessor: P) -> Self {
+        self.input_preprocessor = Some(Arc::new(preprocessor));
+        self
+    }
+
+    /// Set the text recorded in the history where the user interrupted a response
+    pub fn with_interrupt_marker(mut self, marker: impl Into<String>) -> Self {
+        self.interrupt_marker = marker.into();
+        self
+    }
+
+    /// Load project instructions from memory files (`AGENTS.md`, `CLAUDE.md`)
+    ///
+    /// The files are sent as a system block after the system prompt, cached
+    /// with it when prompt caching is on, and re-read at the start of a turn
+    /// when one of them was created, changed or deleted.
+    pub fn with_project_memory(mut self, memory: MemoryConfig) -> Self {
+        self.project_memory = Some(memory);
+        self
+    }
+
     /// Enable or disable streaming responses
     ///
     /// When enabled, the agent will stream LLM responses in real-time,
@@ -208,6 +735,77 @@ impl AgentConfig {
         self
     }
 
+    /// Vary the thinking budget across the iterations of a turn
+    ///
+    /// Enables thinking with the policy's first-call budget; later calls
+    /// use less, or none, as the policy says.
+    pub fn with_thinking_policy(mut self, policy: ThinkingPolicy) -> Self {
+        self.thinking = Some(ThinkingConfig::enabled(policy.first_call_budget));
+        self.thinking_policy = Some(policy);
+        self
+    }
+
+    /// Whether to send the interleaved-thinking beta (default true)
+    ///
+    /// Some models reject it; thinking still works without it, just not
+    /// between tool calls.
+    pub fn with_interleaved_thinking(mut self, enabled: bool) -> Self {
+        self.interleaved_thinking = enabled;
+        self
+    }
+
+    /// Thinking configuration for an iteration of a turn (counted from 1)
+    pub fn thinking_for(&self, iteration: usize) -> Option<ThinkingConfig> {
+        let mut thinking = self.thinking.clone()?;
+        if let Some(policy) = &self.thinking_policy {
+            thinking.budget_tokens = policy.budget_for(iteration)?;
+        }
+        thinking.interleaved &= self.interleaved_thinking;
+        Some(thinking)
+    }
+
+    /// Set the sampling temperature
+    ///
+    /// Ignored by Anthropic while thinking is enabled (the API requires 1).
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.generation.temperature = Some(temperature);
+        self
+    }
+
+    /// Set the max tokens of each response, overriding the provider's
+    ///
+    /// Cut per call when the prompt leaves less room in the context window
+    /// (see [`with_min_output_tokens`](Self::with_min_output_tokens)).
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.generation.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Set the nucleus sampling threshold
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.generation.top_p = Some(top_p);
+        self
+    }
+
+    /// Set sequences that stop generation when the model produces them
+    ///
+    /// The response's stop reason is `StopSequence` when one is hit.
+    pub fn with_stop_sequences(mut self, stop_sequences: Vec<String>) -> Self {
+        self.generation.stop_sequences = stop_sequences;
+        self
+    }
+
+    /// Constrain responses to JSON at the API level
+    ///
+    /// Applies to every LLM call of the agent. `ResponseFormat::Json` without
+    /// a schema uses the [`output schema`](Self::with_output_schema) when
+    /// there is one, so the final answer's validation only retries when the
+    /// JSON really doesn't match.
+    pub fn with_response_format(mut self, format: ResponseFormat) -> Self {
+        self.generation.response_format = Some(format);
+        self
+    }
+
     /// Set the hook registry for intercepting agent behavior
     ///
     /// Hooks allow you to:
@@ -251,6 +849,19 @@ impl AgentConfig {
         self
     }
 
+    /// Has no effect: hooks always stop at the first Deny
+    ///
+    /// Hooks run in priority order (see `HookMatcher::with_priority`), so a
+    /// hook that must see every call, like an audit log, goes first instead.
+    #[deprecated(note = "hooks always stop at the first Deny; order them with `HookMatcher::with_priority`")]
+    pub fn with_hook_short_circuit(mut self, enabled: bool) -> Self {
+        #[allow(deprecated)]
+        {
+            self.hook_short_circuit = enabled;
+        }
+        self
+    }
+
     /// Set a separate LLM provider for conversation naming
     ///
     /// This allows using a lightweight/fast model for naming (e.g., Haiku or Flash)
@@ -262,6 +873,21 @@ impl AgentConfig {
         self
     }
 
+    /// Configure automatic conversation naming and enable it
+    ///
+    /// With a model set, naming uses a `create_variant` of the naming LLM (or
+    /// the main LLM), so it works the same with any provider.
+    ///
+    /// ```ignore
+    /// let config = AgentConfig::new("...")
+    ///     .with_conversation_namer(NamerConfig::new().with_model("gemini-2.0-flash").with_max_length(40));
+    /// ```
+    pub fn with_conversation_namer(mut self, namer: NamerConfig) -> Self {
+        self.namer = namer;
+        self.auto_name_conversation = true;
+        self
+    }
+
     /// Enable or disable prompt caching
     ///
     /// When enabled (default), the agent automatically adds cache_control breakpoints to:
@@ -278,38 +904,6 @@ impl AgentConfig {
         self
     }
 
-    /// Enable or disable hook short-circuiting
-    ///
-    /// **Default: false** (safer - all hooks run, security can't be bypassed)
-    ///
-    /// When false (default):
-    /// - ALL hooks run to completion
-    /// - If ANY hook returns `Deny`, final result is `Deny`
-    /// - Security hooks can't be bypassed by earlier `Allow` hooks
-    /// - Logging/monitoring hooks always fire
-    ///
-    /// When true:
-    /// - First hook to return `Deny` stops execution
-    /// - Later hooks don't run (performance optimization)
-    /// - Security hooks might not run if bypassed by earlier hooks
-    ///
-    /// # Example
-    ///
-    /// ```ignore
-    /// // Safe mode (default): all hooks run
-    /// let config = AgentConfig::new("...")
-    ///     .with_hooks(hooks);
-    ///
-    /// // Performance mode: short-circuit on first Deny
-    /// let config = AgentConfig::new("...")
-    ///     .with_hooks(hooks)
-    ///     .with_hook_short_circuit(true);
-    /// ```
-    pub fn with_hook_short_circuit(mut self, enabled: bool) -> Self {
-        self.hook_short_circuit = enabled;
-        self
-    }
-
     /// **DANGEROUS:** Skip all permission checks
     ///
     /// **Default: false** (safe - permissions are enforced)
@@ -338,12 +932,45 @@ impl AgentConfig {
         self
     }
 
+    /// Describe tool calls instead of running them
+    ///
+    /// Calls of tools that aren't read-only (see `ToolInfo::read_only`) are
+    /// not executed: the model gets `Tool::describe_effect` back, prefixed
+    /// with `[DRY RUN]`, without a permission prompt. Read-only tools and
+    /// those that opt in with `Tool::runs_in_dry_run` (TodoWrite,
+    /// AskUserQuestion) run as usual so the model can still look around and
+    /// plan. Can be changed at runtime via `AgentHandle::set_dry_run()`.
+    pub fn with_dry_run(mut self, enabled: bool) -> Self {
+        self.dry_run = Some(enabled);
+        self
+    }
+
+    /// Offer Anthropic's server-side web search tool
+    ///
+    /// Searches run on Anthropic's side, so there is no local tool, permission
+    /// check or hook. The search call and its results are stored in the
+    /// history and sent as `OutputChunk::WebSearch`. Other providers ignore it.
+    ///
+    /// ```ignore
+    /// let config = AgentConfig::new("...")
+    ///     .with_web_search(WebSearchTool::new().with_max_uses(5).with_allowed_domains(["docs.rs"]));
+    /// ```
+    pub fn with_web_search(mut self, options: WebSearchTool) -> Self {
+        self.web_search = Some(options);
+        self
+    }
+
     /// Get tool definitions (empty vec if no tools)
     pub fn tool_definitions(&self) -> Vec<crate::llm::ToolDefinition> {
-        self.tools
+        let mut definitions = self
+            .tools
             .as_ref()
             .map(|t| t.get_definitions())
-            .unwrap_or_default()
+            .unwrap_or_default();
+        if let Some(ref web_search) = self.web_search {
+            definitions.push(crate::llm::ToolDefinition::WebSearch(web_search.clone()));
+        }
+        definitions
     }
 }
 
@@ -359,16 +986,38 @@ impl std::fmt::Debug for AgentConfig {
             .field("system_prompt", &format!("{}...", &self.system_prompt.chars().take(50).collect::<String>()))
             .field("tools", &self.tools.as_ref().map(|t| t.tool_names()))
             .field("max_tool_iterations", &self.max_tool_iterations)
+            .field("max_continuations", &self.max_continuations)
+            .field("loop_detection", &self.loop_detection)
+            .field("output_schema", &self.output_schema.is_some())
+            .field("turn_timeout", &self.turn_timeout)
+            .field("tool_choice", &self.tool_choice)
+            .field("permission_timeout", &self.permission_timeout)
             .field("auto_save_session", &self.auto_save_session)
             .field("debug_enabled", &self.debug_enabled)
+            .field("debug_config", &self.debug_config)
+            .field("debugger", &self.debugger.as_ref().map(|d| d.dir().to_path_buf()))
+            .field("subagents", &self.subagents.as_ref().map(|s| s.names()))
+            .field("project_memory", &self.project_memory)
+            .field("input_preprocessor", &self.input_preprocessor.is_some())
+            .field("interrupt_marker", &self.interrupt_marker)
+            .field("tool_result_redactor", &self.tool_result_redactor.as_ref().map(|r| r.pattern_names()))
+            .field("input_validation", &self.input_validation)
+            .field("context_overflow", &self.context_overflow)
+            .field("context_window", &self.context_window)
+            .field("min_output_tokens", &self.min_output_tokens)
+            .field("web_search", &self.web_search)
             .field("streaming_enabled", &self.streaming_enabled)
             .field("thinking", &self.thinking)
+            .field("thinking_policy", &self.thinking_policy)
+            .field("interleaved_thinking", &self.interleaved_thinking)
+            .field("generation", &self.generation)
             .field("hooks", &self.hooks.as_ref().map(|h| format!("{:?}", h)))
             .field("auto_name_conversation", &self.auto_name_conversation)
             .field("enable_prompt_caching", &self.enable_prompt_caching)
             .field("naming_llm", &self.naming_llm.as_ref().map(|l| l.model()))
-            .field("hook_short_circuit", &self.hook_short_circuit)
+            .field("namer", &self.namer)
             .field("dangerous_skip_permissions", &self.dangerous_skip_permissions)
+            .field("dry_run", &self.dry_run)
             .finish()
     }
 }
@@ -384,6 +1033,30 @@ mod tests {
         assert!(config.auto_save_session);
         assert!(config.auto_name_conversation);
         assert_eq!(config.max_tool_iterations, 100);
+        assert!(config.input_preprocessor.is_some());
+        assert_eq!(config.interrupt_marker, DEFAULT_INTERRUPT_MARKER);
+        assert_eq!(config.context_overflow, ContextOverflow::Fail);
+        assert_eq!(config.context_window, None);
+        assert_eq!(config.min_output_tokens, DEFAULT_MIN_OUTPUT_TOKENS);
+    }
+
+    #[test]
+    fn test_system_prompt_template() {
+        let template = PromptTemplate::new("You are {{name}}.{{#if tools}} Use tools.{{/if}}").with_strict(true);
+        let vars = TemplateVars::from([("name".to_string(), "Coder".to_string())]);
+        let config = AgentConfig::new("").with_system_prompt_template(&template, &vars).unwrap();
+        assert_eq!(config.system_prompt, "You are Coder.");
+        assert!(AgentConfig::new("").with_system_prompt_template(&template, &TemplateVars::new()).is_err());
+    }
+
+    #[test]
+    fn test_agent_config_input_options() {
+        let config = AgentConfig::new("Test")
+            .with_attachment_parsing(false)
+            .with_interrupt_marker("[interrupted]");
+        assert!(config.input_preprocessor.is_none());
+        assert_eq!(config.interrupt_marker, "[interrupted]");
+        assert!(config.with_attachment_tag("file").input_preprocessor.is_some());
     }
 
     #[test]
@@ -391,4 +1064,35 @@ mod tests {
         let config = AgentConfig::new("Test").with_debug(true);
         assert!(config.debug_enabled);
     }
+
+    #[test]
+    fn test_agent_config_generation_params() {
+        let config = AgentConfig::default();
+        assert!(config.generation.is_empty());
+
+        let config = AgentConfig::new("Test")
+            .with_temperature(0.2)
+            .with_top_p(0.9)
+            .with_stop_sequences(vec!["STOP".to_string()]);
+        assert_eq!(config.generation.temperature, Some(0.2));
+        assert_eq!(config.generation.top_p, Some(0.9));
+        assert_eq!(config.generation.stop_sequences, vec!["STOP".to_string()]);
+    }
+
+    #[test]
+    fn test_thinking_policy_budgets() {
+        let budgets = |config: &AgentConfig| (1..=4).map(|i| config.thinking_for(i).map(|t| t.budget_tokens)).collect::<Vec<_>>();
+
+        let config = AgentConfig::new("Test").with_thinking(4096);
+        assert_eq!(budgets(&config), vec![Some(4096); 4]);
+
+        let policy = ThinkingPolicy::new(8000).with_tool_followup_budget(1024).disable_after(3);
+        let config = AgentConfig::new("Test").with_thinking_policy(policy);
+        assert_eq!(budgets(&config), vec![Some(8000), Some(1024), Some(1024), None]);
+        assert!(config.thinking_for(1).unwrap().interleaved);
+
+        let config = config.with_interleaved_thinking(false);
+        assert!(!config.thinking_for(1).unwrap().interleaved);
+        assert!(AgentConfig::new("Test").thinking_for(1).is_none());
+    }
 }
@@ -0,0 +1,179 @@
+//! Context window checks
+//!
+//! Before each LLM call, `StandardAgent` estimates the size of the request
+//! (history, system prompt and tools, see `llm::tokens`) and compares it
+//! with the model's context window less the tokens reserved for the reply.
+//! A request that doesn't fit is handled by the `ContextOverflow` strategy
+//! instead of being sent and rejected by the provider.
+//!
+//! Only whole turns are left out, oldest first, and never the turn being
+//! run. When a request has to shrink, it shrinks to `TARGET_PERCENT` of the
+//! budget so the next few calls fit without shrinking again.
+//!
+//! The check reserves only the least the reply may be given
+//! (`AgentConfig::min_output_tokens`). The reply's max tokens are then cut
+//! per call to what the window leaves (see `output_budget`), so a large
+//! prompt with a short reply isn't rejected for the full max tokens.
+
+use crate::llm::{estimate_message_tokens, Message};
+
+use super::standard_loop::is_user_prompt;
+
+/// Custom metadata key of the summary that replaces compacted turns
+/// (a `SessionSummary`)
+pub const CONTEXT_SUMMARY_METADATA_KEY: &str = "context_summary";
+
+/// Percentage of the input budget a shrunk request is cut down to
+const TARGET_PERCENT: u64 = 75;
+
+/// Default for `AgentConfig::min_output_tokens`
+pub const DEFAULT_MIN_OUTPUT_TOKENS: u32 = 1024;
+
+/// Percentage of the window kept free for error in the prompt estimate
+const OUTPUT_MARGIN_PERCENT: u64 = 5;
+
+/// Max tokens of a compaction summary
+pub(super) const SUMMARY_MAX_TOKENS: u32 = 1024;
+
+/// Text before the summary in the message that replaces compacted turns
+pub(super) const SUMMARY_PREFIX: &str = "[Summary of the earlier conversation]";
+
+/// Reply to the summary message, so the history keeps alternating roles
+pub(super) const SUMMARY_ACK: &str = "Understood. I'll continue from that summary.";
+
+/// What `StandardAgent` does when a request won't fit the context window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContextOverflow {
+    /// End the turn with `FrameworkError::ContextOverflow` (the default)
+    #[default]
+    Fail,
+    /// Leave the oldest turns out of the request; the session keeps them
+    DropOldest,
+    /// Replace the oldest turns with a summary written by the model
+    ///
+    /// The summary is stored under `CONTEXT_SUMMARY_METADATA_KEY` and
+    /// reused (and extended) by later calls. The session keeps every message.
+    Compact,
+}
+
+/// First history message sent when `DropOldest` leaves turns out
+/// (an agent context resource)
+#[derive(Debug, Clone, Copy)]
+pub(super) struct DroppedMessages(pub usize);
+
+/// Tokens left for the input of a call
+///
+/// `reply_tokens` is the least the reply is given, thinking included; a
+/// thinking budget larger than that is reserved instead.
+pub(super) fn input_budget(window: u32, reply_tokens: Option<u32>, thinking_budget: Option<u32>) -> u64 {
+    let reserved = reply_tokens.unwrap_or(0).max(thinking_budget.unwrap_or(0));
+    u64::from(window).saturating_sub(u64::from(reserved))
+}
+
+/// Max tokens for the reply of a call with a `prompt`-token request
+///
+/// `max_tokens`, cut to what the window leaves after the prompt and a
+/// margin of `OUTPUT_MARGIN_PERCENT`, but not below `floor` (or
+/// `max_tokens`, if smaller). With thinking, the reply keeps more than the
+/// thinking budget, as the API requires.
+pub(super) fn output_budget(window: u32, prompt: u64, max_tokens: u32, floor: u32, thinking_budget: Option<u32>) -> u32 {
+    let margin = u64::from(window) * OUTPUT_MARGIN_PERCENT / 100;
+    let room = u64::from(window).saturating_sub(prompt + margin);
+    let floor = floor.max(thinking_budget.map_or(0, |budget| budget.saturating_add(1))).min(max_tokens);
+    u32::try_from(room).unwrap_or(u32::MAX).min(max_tokens).max(floor)
+}
+
+/// Tokens of the history from `start` on
+pub(super) fn tokens_from(history: &[Message], start: usize) -> u64 {
+    history[start..].iter().map(estimate_message_tokens).sum()
+}
+
+/// Whether the history can be sent starting at `start`
+///
+/// A request starts at a prompt, so no tool result loses its call.
+pub(super) fn is_cut_point(history: &[Message], start: usize) -> bool {
+    start == 0 || history.get(start).is_some_and(is_user_prompt)
+}
+
+/// Where to start a request that is over budget
+///
+/// `fixed` is the size of everything but the history, `prefix` the size of
+/// what replaces the left-out turns. Picks the first turn after `after`
+/// from which the request fits the target, else the current turn if it fits
+/// `budget` at all. None if not even the current turn fits.
+pub(super) fn choose_cut(history: &[Message], after: usize, fixed: u64, prefix: u64, budget: u64) -> Option<usize> {
+    let target = budget * TARGET_PERCENT / 100;
+    let starts: Vec<usize> = (after + 1..history.len())
+        .filter(|&i| is_user_prompt(&history[i]))
+        .collect();
+    let size = |start: usize| fixed + prefix + tokens_from(history, start);
+
+    starts
+        .iter()
+        .copied()
+        .find(|&start| size(start) <= target)
+        .or_else(|| starts.last().copied().filter(|&start| size(start) <= budget))
+}
+
+/// The messages that stand in for compacted turns
+pub(super) fn summary_messages(summary: &str) -> [Message; 2] {
+    [
+        Message::user(format!("{}\n{}", SUMMARY_PREFIX, summary)),
+        Message::assistant(SUMMARY_ACK),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::ContentBlock;
+
+    fn turn(text: &str) -> Vec<Message> {
+        vec![Message::user(text), Message::assistant(text)]
+    }
+
+    #[test]
+    fn test_input_budget_reserves_reply() {
+        assert_eq!(input_budget(1000, Some(200), None), 800);
+        assert_eq!(input_budget(1000, Some(200), Some(500)), 500);
+        assert_eq!(input_budget(100, Some(200), None), 0);
+        assert_eq!(input_budget(1000, None, None), 1000);
+    }
+
+    #[test]
+    fn test_output_budget_fits_the_window() {
+        // 10000 - 6000 - 500 (5% margin) = 3500
+        assert_eq!(output_budget(10_000, 6000, 8000, 1024, None), 3500);
+        assert_eq!(output_budget(10_000, 1000, 2000, 1024, None), 2000);
+        // Never below the floor, nor the floor above max_tokens
+        assert_eq!(output_budget(10_000, 9000, 8000, 1024, None), 1024);
+        assert_eq!(output_budget(10_000, 9000, 500, 1024, None), 500);
+        // The reply keeps more than the thinking budget
+        assert_eq!(output_budget(10_000, 6000, 8000, 1024, Some(4000)), 4001);
+    }
+
+    #[test]
+    fn test_choose_cut_keeps_whole_turns() {
+        // Each turn is 2 * (25 + 4) = 58 tokens
+        let mut history: Vec<Message> = ["a", "b", "c"].iter().flat_map(|t| turn(&t.repeat(100))).collect();
+        history.push(Message::assistant_with_blocks(vec![ContentBlock::tool_use(
+            "toolu_1",
+            "Read",
+            serde_json::json!({}),
+        )]));
+        history.push(Message::user_with_blocks(vec![ContentBlock::tool_result("toolu_1", "ok", false)]));
+
+        // A tool result is never a cut point
+        assert!(is_cut_point(&history, 2));
+        assert!(!is_cut_point(&history, 7));
+
+        let total = tokens_from(&history, 0);
+        assert_eq!(choose_cut(&history, 0, 0, 0, total), Some(2));
+        // Only the current turn fits the budget, not the target
+        let current = tokens_from(&history, 4);
+        assert_eq!(choose_cut(&history, 0, 0, 0, current), Some(4));
+        assert_eq!(choose_cut(&history, 0, 0, 0, current - 1), None);
+        // Cuts at or before `after` aren't considered
+        assert_eq!(choose_cut(&history, 2, 0, 0, total), Some(4));
+    }
+}
@@ -2,14 +2,33 @@
 //!
 //! Handles permission-aware tool execution with optional debug logging and hooks.
 
+use std::time::{Duration, Instant};
+
 use serde_json::Value;
+use tracing::Instrument;
 
 use crate::core::InputMessage;
-use crate::helpers::Debugger;
+use crate::helpers::{Debugger, FileTracker, OutputOverflow, Redactor};
 use crate::hooks::{HookContext, HookRegistry, PermissionDecision};
-use crate::permissions::{CheckResult, PermissionRule, PermissionScope};
-use crate::runtime::AgentInternals;
-use crate::tools::{ToolRegistry, ToolResult};
+use crate::permissions::{CheckResult, PermissionRule, PermissionScope, TimeoutDecision};
+use crate::runtime::{AgentInternals, PermissionWait};
+use crate::{metrics, telemetry};
+use crate::tools::{InputValidation, ToolConcurrency, ToolRegistry, ToolResult, ToolResultData};
+
+/// Error returned for a tool call whose permission request timed out
+pub(crate) const PERMISSION_TIMED_OUT: &str = "Permission denied: permission request timed out";
+
+/// Error returned when a permission timeout ends the turn
+///
+/// The agent loop stops after recording the tool results when it sees this.
+pub(crate) const PERMISSION_TIMEOUT_ABORT: &str =
+    "Permission denied: permission request timed out, turn ended";
+
+/// Marks the results of calls skipped by dry-run mode
+pub const DRY_RUN_PREFIX: &str = "[DRY RUN]";
+
+/// How long an interrupted tool gets to clean up before it is dropped
+const INTERRUPT_GRACE: Duration = Duration::from_secs(1);
 
 /// Handles tool execution with permission checking and hooks
 pub struct ToolExecutor;
@@ -18,12 +37,21 @@ impl ToolExecutor {
     /// Execute a tool with permission checking and hooks
     ///
     /// This handles the full flow:
-    /// 1. Run PreToolUse hooks (can block, allow, or modify input)
+    /// 0. In dry-run mode, describe calls of tools that aren't read-only
+    ///    instead of running them (no hooks, no permission prompt)
+    /// 1. Run PreToolUse hooks (can block, allow, or modify input), then
+    ///    reject input that doesn't match the tool's schema (with an
+    ///    `InputValidation` resource in the context)
     /// 2. Check if permission exists (unless hook already decided)
     /// 3. If not, ask user (via output channel)
-    /// 4. Wait for response
-    /// 5. Execute if allowed, return error if denied
+    /// 4. Wait for response (up to the permission timeout, if one is set)
+    /// 5. Execute if allowed (once a concurrency slot is free, within the
+    ///    tool's timeout, until the user interrupts), return error if denied
     /// 6. Run PostToolUse or PostToolUseFailure hooks
+    /// 7. Save output over the `OutputOverflow` threshold to a file, and
+    ///    return a preview pointing at it
+    ///
+    /// Runs inside a `tool_execution` span (see `telemetry::tool_span`).
     pub async fn execute_with_permission(
         internals: &mut AgentInternals,
         tools: &ToolRegistry,
@@ -31,9 +59,40 @@ impl ToolExecutor {
         tool_name: &str,
         tool_id: &str,
         input: &Value,
-        hook_short_circuit: bool,
     ) -> ToolResult {
+        let span = telemetry::tool_span(internals.session_id(), tool_name, tool_id);
+        let started = Instant::now();
+        let result = Self::check_and_execute(
+            internals,
+            tools,
+            hooks,
+            tool_name,
+            tool_id,
+            input,
+        )
+        .instrument(span.clone())
+        .await;
+        telemetry::record_tool_result(&span, result.is_error);
+        telemetry::record_duration(&span, started.elapsed());
+        metrics::record_tool_call(internals.agent_type(), tool_name, started.elapsed(), result.is_error);
+        result
+    }
+
+    /// Permission flow behind `execute_with_permission`
+    async fn check_and_execute(
+        internals: &mut AgentInternals,
+        tools: &ToolRegistry,
+        hooks: Option<&HookRegistry>,
+        tool_name: &str,
+        tool_id: &str,
+        input: &Value,
+    ) -> ToolResult {
+        if let Some(result) = Self::dry_run(internals, tools, tool_name, input).await {
+            return Self::invalid_input(internals, tools, tool_name, input).unwrap_or(result);
+        }
+
         let mut current_input = input.clone();
+        let mut hook_allowed = false;
 
         // === Run PreToolUse hooks ===
         if let Some(hooks) = hooks {
@@ -42,8 +101,7 @@ impl ToolExecutor {
                 tool_name,
                 &current_input,
                 tool_id,
-                hook_short_circuit,
-            );
+            ).await;
             let result = hooks.run(&mut ctx);
 
             // Hook may have modified tool_input
@@ -60,26 +118,31 @@ impl ToolExecutor {
                     tracing::info!("[Executor] Hook denied {}: {}", tool_name, reason);
                     return ToolResult::error(format!("Hook denied: {}", reason));
                 }
-                Some(PermissionDecision::Allow) => {
-                    // Skip permission check, execute directly
-                    tracing::info!("[Executor] Hook allowed {} (skipping permission check)", tool_name);
-                    return Self::execute_with_hooks(
-                        internals,
-                        tools,
-                        Some(hooks),
-                        tool_name,
-                        tool_id,
-                        &current_input,
-                        hook_short_circuit,
-                    )
-                    .await;
-                }
+                Some(PermissionDecision::Allow) => hook_allowed = true,
                 Some(PermissionDecision::Ask) | None => {
                     // Fall through to normal permission check
                 }
             }
         }
 
+        // Checked after the hooks, so the input that runs is the input checked
+        if let Some(result) = Self::invalid_input(internals, tools, tool_name, &current_input) {
+            return result;
+        }
+        if hook_allowed {
+            // Skip permission check, execute directly
+            tracing::info!("[Executor] Hook allowed {} (skipping permission check)", tool_name);
+            return Self::execute_with_hooks(
+                internals,
+                tools,
+                hooks,
+                tool_name,
+                tool_id,
+                &current_input,
+            )
+            .await;
+        }
+
         // Check if dangerous_skip_permissions is enabled (from session metadata)
         let should_skip_permissions = {
             let session = internals.session.read().await;
@@ -101,7 +164,6 @@ impl ToolExecutor {
                 tool_name,
                 tool_id,
                 &current_input,
-                hook_short_circuit,
             )
             .await;
         }
@@ -126,7 +188,6 @@ impl ToolExecutor {
                     tool_name,
                     tool_id,
                     &current_input,
-                    hook_short_circuit,
                 )
                 .await
             }
@@ -136,6 +197,20 @@ impl ToolExecutor {
                 ToolResult::error(format!("Permission denied for tool: {}", tool_name))
             }
 
+            // Output saved by the executor itself can always be read
+            CheckResult::AskUser if Self::reads_saved_output(internals, tool_name, &current_input) => {
+                tracing::info!("[Executor] Reading saved tool output, skipping permission check");
+                Self::execute_with_hooks(
+                    internals,
+                    tools,
+                    hooks,
+                    tool_name,
+                    tool_id,
+                    &current_input,
+                )
+                .await
+            }
+
             CheckResult::AskUser => {
                 tracing::info!("[Executor] Asking user for permission: {}", tool_name);
                 Self::ask_and_execute(
@@ -147,14 +222,60 @@ impl ToolExecutor {
                     &current_input,
                     &action_desc,
                     tool_info.and_then(|i| i.details),
-                    hook_short_circuit,
                 )
                 .await
             }
         }
     }
 
+    /// The error result for a call whose input doesn't match the tool's schema
+    fn invalid_input(
+        internals: &AgentInternals,
+        tools: &ToolRegistry,
+        tool_name: &str,
+        input: &Value,
+    ) -> Option<ToolResult> {
+        let validation = internals.context.get_resource::<InputValidation>()?;
+        if !validation.applies_to(tool_name) {
+            return None;
+        }
+        let tool = tools.get(tool_name)?;
+        let error = validation.validate(&tool.definition(), input).err()?;
+        tracing::info!("[Executor] Invalid input for {}, not running it", tool_name);
+        Some(ToolResult::error(error))
+    }
+
+    /// The dry-run result for a call, if dry-run mode is on (from session
+    /// metadata) and the tool isn't read-only or exempt
+    async fn dry_run(
+        internals: &AgentInternals,
+        tools: &ToolRegistry,
+        tool_name: &str,
+        input: &Value,
+    ) -> Option<ToolResult> {
+        let enabled = {
+            let session = internals.session.read().await;
+            session.get_custom("dry_run").and_then(|v| v.as_bool()).unwrap_or(false)
+        };
+        if !enabled {
+            return None;
+        }
+        let tool = tools.get(tool_name)?;
+        if tool.get_info(input).read_only || tool.runs_in_dry_run() {
+            return None;
+        }
+
+        tracing::info!("[Executor] Dry run: not executing {}", tool_name);
+        Some(ToolResult::success(format!(
+            "{} {} was not executed.\n{}",
+            DRY_RUN_PREFIX,
+            tool_name,
+            tool.describe_effect(input)
+        )))
+    }
+
     /// Ask user for permission and execute if granted
+    #[allow(clippy::too_many_arguments)]
     async fn ask_and_execute(
         internals: &mut AgentInternals,
         tools: &ToolRegistry,
@@ -164,30 +285,19 @@ impl ToolExecutor {
         input: &Value,
         action_desc: &str,
         details: Option<String>,
-        hook_short_circuit: bool,
     ) -> ToolResult {
         let input_str = input.to_string();
 
         // Send permission request
-        internals.send_permission_request(tool_name, action_desc, &input_str, details);
-        internals.set_waiting_for_permission().await;
+        let request_id = internals.send_permission_request(tool_name, action_desc, &input_str, details);
+        let previous = internals.state().await;
+        internals.set_waiting_for_permission(tool_name).await;
 
         // Wait for response
-        match internals.receive().await {
-            Some(InputMessage::PermissionResponse {
-                tool_name: resp_tool,
-                allowed,
-                remember,
-            }) => {
-                if resp_tool != tool_name {
-                    tracing::warn!(
-                        "[Executor] Permission response mismatch: expected {}, got {}",
-                        tool_name,
-                        resp_tool
-                    );
-                    return ToolResult::error("Permission response mismatch");
-                }
-
+        let response = internals.receive_permission_response(&request_id).await;
+        internals.set_state(previous).await;
+        match response {
+            PermissionWait::Answered { allowed, remember } => {
                 if remember && allowed {
                     tracing::info!("[Executor] Adding 'Always Allow' rule for {}", tool_name);
                     internals.add_permission_rule(
@@ -205,7 +315,6 @@ impl ToolExecutor {
                         tool_name,
                         tool_id,
                         input,
-                        hook_short_circuit,
                     )
                     .await
                 } else {
@@ -214,28 +323,86 @@ impl ToolExecutor {
                 }
             }
 
-            Some(InputMessage::Interrupt) => {
+            PermissionWait::TimedOut(timeout) => {
+                Self::apply_timeout(
+                    internals,
+                    tools,
+                    hooks,
+                    tool_name,
+                    tool_id,
+                    input,
+                    timeout.decision,
+                )
+                .await
+            }
+
+            PermissionWait::Received(Some(InputMessage::Interrupt)) => {
                 tracing::info!("[Executor] Interrupted while waiting for permission");
                 ToolResult::error("Interrupted")
             }
 
-            Some(InputMessage::Shutdown) => {
+            PermissionWait::Received(Some(InputMessage::Shutdown)) => {
                 tracing::info!("[Executor] Shutdown while waiting for permission");
                 ToolResult::error("Shutdown")
             }
 
-            None => {
+            PermissionWait::Received(None) => {
                 tracing::info!("[Executor] Channel closed while waiting for permission");
                 ToolResult::error("Channel closed")
             }
 
-            _ => {
+            PermissionWait::Received(Some(_)) => {
                 tracing::warn!("[Executor] Unexpected message while waiting for permission");
                 ToolResult::error("Unexpected message during permission request")
             }
         }
     }
 
+    /// Apply the default decision for a permission request nobody answered
+    async fn apply_timeout(
+        internals: &mut AgentInternals,
+        tools: &ToolRegistry,
+        hooks: Option<&HookRegistry>,
+        tool_name: &str,
+        tool_id: &str,
+        input: &Value,
+        decision: TimeoutDecision,
+    ) -> ToolResult {
+        let outcome = match decision {
+            TimeoutDecision::Allow => "allowing",
+            TimeoutDecision::Deny => "denying",
+            TimeoutDecision::AbortTurn => "ending turn",
+        };
+        tracing::info!("[Executor] Permission request for {} timed out, {}", tool_name, outcome);
+        internals.send_status(format!(
+            "Permission request for {} timed out, {}",
+            tool_name, outcome
+        ));
+
+        let error_msg = match decision {
+            TimeoutDecision::Allow => {
+                return Self::execute_with_hooks(internals, tools, hooks, tool_name, tool_id, input)
+                    .await;
+            }
+            TimeoutDecision::Deny => PERMISSION_TIMED_OUT,
+            TimeoutDecision::AbortTurn => PERMISSION_TIMEOUT_ABORT,
+        };
+
+        // Record the denial with PostToolUseFailure hooks
+        if let Some(hooks) = hooks {
+            let mut ctx = HookContext::post_tool_use_failure(
+                internals,
+                tool_name,
+                input,
+                tool_id,
+                error_msg,
+            ).await;
+            let _hook_result = hooks.run(&mut ctx);
+        }
+
+        ToolResult::error(error_msg)
+    }
+
     /// Execute a tool with post-execution hooks
     async fn execute_with_hooks(
         internals: &mut AgentInternals,
@@ -244,7 +411,6 @@ impl ToolExecutor {
         tool_name: &str,
         tool_id: &str,
         input: &Value,
-        hook_short_circuit: bool,
     ) -> ToolResult {
         // Set the current tool_use_id on context so tools can access it
         internals.context.current_tool_use_id = Some(tool_id.to_string());
@@ -260,10 +426,13 @@ impl ToolExecutor {
         }
 
         // Send tool start notification
-        internals.send_tool_start(tool_name, tool_name, input.clone());
+        internals.send_tool_start(tool_id, tool_name, input.clone());
 
-        // Execute
-        let result = match tools.execute(tool_name, input, internals).await {
+        // Execute (tools can read the call ID from the context)
+        internals.context.current_tool_use_id = Some(tool_id.to_string());
+        let outcome = Self::execute_interruptible(internals, tools, tool_name, input).await;
+        internals.context.clear_tool_use_id();
+        let result = match outcome {
             Ok(result) => {
                 // Run PostToolUse hooks
                 if let Some(hooks) = hooks {
@@ -273,8 +442,7 @@ impl ToolExecutor {
                         input,
                         tool_id,
                         &result,
-                        hook_short_circuit,
-                    );
+                    ).await;
                     let _hook_result = hooks.run(&mut ctx);
                     // PostToolUse hooks are for logging/observation, we don't act on the result
                 }
@@ -291,14 +459,14 @@ impl ToolExecutor {
                         input,
                         tool_id,
                         &error_msg,
-                        hook_short_circuit,
-                    );
+                    ).await;
                     let _hook_result = hooks.run(&mut ctx);
                 }
 
                 ToolResult::error(error_msg)
             }
         };
+        let result = Self::save_overflow(internals, tool_name, tool_id, input, result).await;
 
         // Log tool result if debugger is enabled
         if let Some(debugger) = internals.context.get_resource::<Debugger>() {
@@ -308,7 +476,7 @@ impl ToolExecutor {
         }
 
         // Send tool end notification
-        internals.send_tool_end(tool_name, result.clone());
+        internals.send_tool_end(tool_id, result.clone());
 
         // Clear the current tool_use_id
         internals.context.current_tool_use_id = None;
@@ -316,6 +484,155 @@ impl ToolExecutor {
         result
     }
 
+    /// Move output over the overflow threshold to the session's outputs directory
+    ///
+    /// The file is granted on the `FileTracker` so Read can open it. If it
+    /// can't be written, the whole output is returned as it is.
+    async fn save_overflow(
+        internals: &AgentInternals,
+        tool_name: &str,
+        tool_id: &str,
+        input: &Value,
+        result: ToolResult,
+    ) -> ToolResult {
+        let Some(overflow) = internals.context.get_resource::<OutputOverflow>() else {
+            return result;
+        };
+        let [ToolResultData::Text(ref text)] = result.content[..] else {
+            return result;
+        };
+        // Reading a saved file back in pages is the point, don't save it again
+        if !overflow.exceeds(text) || Self::reads_saved_output(internals, tool_name, input) {
+            return result;
+        }
+
+        let text = match internals.context.get_resource::<Redactor>() {
+            Some(redactor) => redactor.redact(text).into_owned(),
+            None => text.clone(),
+        };
+        let saved = {
+            let session = internals.session.read().await;
+            session.storage().save_tool_output(session.session_id(), tool_id, &text)
+        };
+        let path = match saved {
+            Ok(path) => std::fs::canonicalize(&path).unwrap_or(path),
+            Err(e) => {
+                tracing::warn!("[Executor] Failed to save {} output: {}", tool_name, e);
+                return result;
+            }
+        };
+        if let Some(tracker) = internals.context.get_resource::<FileTracker>() {
+            tracker.grant_read(&path);
+        }
+        tracing::info!(
+            "[Executor] Saved {} output ({} bytes) to {:?}",
+            tool_name,
+            text.len(),
+            path
+        );
+
+        ToolResult::new(vec![ToolResultData::Text(overflow.preview(&text, &path))], result.is_error)
+    }
+
+    /// Whether a call is a Read of output saved by `save_overflow`
+    fn reads_saved_output(internals: &AgentInternals, tool_name: &str, input: &Value) -> bool {
+        if tool_name != "Read" {
+            return false;
+        }
+        match (
+            input.get("file_path").and_then(|p| p.as_str()),
+            internals.context.get_resource::<FileTracker>(),
+        ) {
+            (Some(path), Some(tracker)) => tracker.is_granted(path),
+            _ => false,
+        }
+    }
+
+    /// Run a tool until it finishes or the user interrupts it
+    ///
+    /// An interrupt or shutdown cancels `context.cancellation` (see
+    /// `InterruptSignal` and `AgentInternals::turn_token`) and gives the tool `INTERRUPT_GRACE` to stop on its own, so it can
+    /// kill child processes or cancel remote requests, before dropping it.
+    /// The call's result is then `ToolResult::interrupted`.
+    async fn execute_interruptible(
+        internals: &mut AgentInternals,
+        tools: &ToolRegistry,
+        tool_name: &str,
+        input: &Value,
+    ) -> anyhow::Result<ToolResult> {
+        let signal = internals.interrupt_signal().clone();
+        let token = signal.arm_child(internals.turn_token());
+        internals.context.cancellation = token.clone();
+
+        let outcome = {
+            let run = Self::execute_limited(internals, tools, tool_name, input);
+            tokio::pin!(run);
+            tokio::select! {
+                outcome = &mut run => match outcome {
+                    // Stopped by the interrupt itself (AskUserQuestion reads it)
+                    Err(_) if token.is_cancelled() => Ok(ToolResult::interrupted()),
+                    outcome => outcome,
+                },
+                _ = token.cancelled() => {
+                    tracing::info!("[Executor] Interrupted {}", tool_name);
+                    if tokio::time::timeout(INTERRUPT_GRACE, &mut run).await.is_err() {
+                        tracing::warn!("[Executor] {} didn't stop within {:?}, dropping it", tool_name, INTERRUPT_GRACE);
+                    }
+                    Ok(ToolResult::interrupted())
+                }
+            }
+        };
+
+        signal.disarm();
+        internals.context.cancellation = Default::default();
+        outcome
+    }
+
+    /// Run a tool once a concurrency slot is free, within its timeout
+    async fn execute_limited(
+        internals: &mut AgentInternals,
+        tools: &ToolRegistry,
+        tool_name: &str,
+        input: &Value,
+    ) -> anyhow::Result<ToolResult> {
+        let (max_concurrency, timeout) = tools
+            .get(tool_name)
+            .map(|tool| (tool.max_concurrency(), tool.timeout()))
+            .unwrap_or_default();
+        let deadline = timeout.map(|timeout| tokio::time::Instant::now() + timeout);
+
+        let _permit = match internals.context.get_resource::<ToolConcurrency>() {
+            Some(slots) => match slots.try_acquire(tool_name, max_concurrency) {
+                Some(permit) => Some(permit),
+                None => {
+                    tracing::info!("[Executor] Waiting for a {} slot", tool_name);
+                    internals.send_status(format!("Waiting for a {} slot", tool_name));
+                    let wait = slots.acquire(tool_name, max_concurrency);
+                    match deadline {
+                        Some(deadline) => match tokio::time::timeout_at(deadline, wait).await {
+                            Ok(permit) => Some(permit),
+                            Err(_) => anyhow::bail!(
+                                "timed out after {:?} waiting for a {} slot",
+                                timeout.unwrap_or_default(),
+                                tool_name
+                            ),
+                        },
+                        None => Some(wait.await),
+                    }
+                }
+            },
+            None => None,
+        };
+
+        let run = tools.execute(tool_name, input, internals);
+        match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, run).await.unwrap_or_else(|_| {
+                Err(anyhow::anyhow!("timed out after {:?}", timeout.unwrap_or_default()))
+            }),
+            None => run.await,
+        }
+    }
+
     /// Execute a tool without hooks (for backwards compatibility)
     pub async fn execute(
         internals: &mut AgentInternals,
@@ -324,6 +641,374 @@ impl ToolExecutor {
         tool_id: &str,
         input: &Value,
     ) -> ToolResult {
-        Self::execute_with_hooks(internals, tools, None, tool_name, tool_id, input, false).await
+        Self::execute_with_hooks(internals, tools, None, tool_name, tool_id, input).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+
+    use crate::core::OutputChunk;
+    use crate::llm::{define_tool, ToolDefinition};
+    use crate::runtime::{AgentRuntime, OutputReceiver};
+    use crate::tools::{Tool, ToolInfo};
+
+    /// A tool that takes a while and records how many calls overlap
+    struct SlowTool {
+        active: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+        duration: Duration,
+        timeout: Option<Duration>,
+    }
+
+    #[async_trait]
+    impl Tool for SlowTool {
+        fn name(&self) -> &str {
+            "Slow"
+        }
+
+        fn description(&self) -> &str {
+            "Takes a while"
+        }
+
+        fn definition(&self) -> ToolDefinition {
+            define_tool("Slow", "Takes a while", serde_json::json!({}), vec![])
+        }
+
+        fn get_info(&self, _input: &Value) -> ToolInfo {
+            ToolInfo {
+                name: "Slow".to_string(),
+                action_description: "Wait".to_string(),
+                details: None,
+                read_only: true,
+            }
+        }
+
+        async fn execute(&self, _input: &Value, _internals: &mut AgentInternals) -> anyhow::Result<ToolResult> {
+            let now = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(self.duration).await;
+            self.active.fetch_sub(1, Ordering::SeqCst);
+            Ok(ToolResult::success("done"))
+        }
+
+        fn max_concurrency(&self) -> Option<usize> {
+            Some(1)
+        }
+
+        fn timeout(&self) -> Option<Duration> {
+            self.timeout
+        }
+    }
+
+    /// A tool that prints `lines` numbered lines
+    struct LogTool;
+
+    #[async_trait]
+    impl Tool for LogTool {
+        fn name(&self) -> &str {
+            "Log"
+        }
+
+        fn description(&self) -> &str {
+            "Prints a log"
+        }
+
+        fn definition(&self) -> ToolDefinition {
+            define_tool(
+                "Log",
+                "Prints a log",
+                serde_json::json!({"lines": {"type": "integer"}}),
+                vec!["lines".to_string()],
+            )
+        }
+
+        fn get_info(&self, _input: &Value) -> ToolInfo {
+            ToolInfo {
+                name: "Log".to_string(),
+                action_description: "Print".to_string(),
+                details: None,
+                read_only: true,
+            }
+        }
+
+        async fn execute(&self, input: &Value, _internals: &mut AgentInternals) -> anyhow::Result<ToolResult> {
+            let lines = input["lines"].as_u64().unwrap_or(0);
+            Ok(ToolResult::success(
+                (1..=lines).map(|i| format!("log line {}\n", i)).collect::<String>(),
+            ))
+        }
+    }
+
+    fn text(result: &ToolResult) -> &str {
+        match &result.content[..] {
+            [ToolResultData::Text(text)] => text,
+            other => panic!("expected text, got {:?}", other),
+        }
+    }
+
+    fn slow_tools(duration: Duration, timeout: Option<Duration>) -> (ToolRegistry, Arc<AtomicUsize>) {
+        let peak = Arc::new(AtomicUsize::new(0));
+        let mut tools = ToolRegistry::new();
+        tools.register(SlowTool {
+            active: Arc::new(AtomicUsize::new(0)),
+            peak: peak.clone(),
+            duration,
+            timeout,
+        });
+        (tools, peak)
+    }
+
+    /// Internals of an agent of `runtime`, as spawned agents get them
+    fn internals(runtime: &AgentRuntime, dir: &std::path::Path, id: &str) -> (AgentInternals, OutputReceiver) {
+        let mut internals = AgentInternals::for_test_session(dir, id, "test");
+        internals.context.shared_resources = runtime.shared_resources().clone();
+        let rx = internals.subscribe_output();
+        (internals, rx)
+    }
+
+    fn waited(rx: &mut OutputReceiver) -> bool {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .any(|chunk| matches!(chunk, OutputChunk::Status(ref s) if s == "Waiting for a Slow slot"))
+    }
+
+    #[tokio::test]
+    async fn test_tool_concurrency_limit_serializes_calls() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let (tools, peak) = slow_tools(Duration::from_millis(50), None);
+
+        let mut agents: Vec<_> = (0..3).map(|i| internals(&runtime, dir.path(), &format!("agent-{}", i))).collect();
+        let calls = agents.iter_mut().enumerate().map(|(i, (internals, _))| {
+            let tools = &tools;
+            async move {
+                ToolExecutor::execute(internals, tools, "Slow", &format!("call-{}", i), &serde_json::json!({})).await
+            }
+        });
+        let started = std::time::Instant::now();
+        let results = futures::future::join_all(calls).await;
+
+        assert!(results.iter().all(|r| !r.is_error));
+        assert_eq!(peak.load(Ordering::SeqCst), 1);
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        let waiting = agents.iter_mut().map(|(_, rx)| waited(rx)).filter(|w| *w).count();
+        assert_eq!(waiting, 2);
+    }
+
+    #[tokio::test]
+    async fn test_waiting_for_a_slot_counts_against_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let (tools, _) = slow_tools(Duration::from_millis(300), Some(Duration::from_millis(500)));
+        let (mut first, _) = internals(&runtime, dir.path(), "first");
+        let (mut second, mut rx) = internals(&runtime, dir.path(), "second");
+
+        let input = serde_json::json!({});
+        let (a, b) = tokio::join!(
+            ToolExecutor::execute(&mut first, &tools, "Slow", "a", &input),
+            async {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                ToolExecutor::execute(&mut second, &tools, "Slow", "b", &input).await
+            }
+        );
+        assert!(!a.is_error);
+        // Waited ~290ms for the slot, then ran out of time during its 300ms run
+        assert!(b.is_error);
+        assert!(format!("{:?}", b.content).contains("timed out after 500ms"), "{:?}", b.content);
+        assert!(waited(&mut rx));
+    }
+
+    #[tokio::test]
+    async fn test_global_tool_cap() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new().with_max_concurrent_tools(1);
+        let slots = runtime.shared_resources().get::<ToolConcurrency>().unwrap();
+        let held = slots.try_acquire("Read", None).unwrap();
+
+        let (tools, _) = slow_tools(Duration::from_millis(1), Some(Duration::from_millis(50)));
+        let (mut internals, _) = internals(&runtime, dir.path(), "capped");
+        let result = ToolExecutor::execute(&mut internals, &tools, "Slow", "a", &serde_json::json!({})).await;
+        assert!(format!("{:?}", result.content).contains("waiting for a Slow slot"));
+
+        drop(held);
+        let result = ToolExecutor::execute(&mut internals, &tools, "Slow", "b", &serde_json::json!({})).await;
+        assert!(!result.is_error);
+    }
+
+    #[tokio::test]
+    async fn test_output_under_threshold_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let (mut internals, _) = internals(&runtime, dir.path(), "short");
+        internals.context.insert_resource(OutputOverflow::new(1000));
+        let mut tools = ToolRegistry::new();
+        tools.register(LogTool);
+
+        let result = ToolExecutor::execute(&mut internals, &tools, "Log", "a", &serde_json::json!({"lines": 10})).await;
+        assert!(text(&result).ends_with("log line 10\n"));
+        assert!(!dir.path().join("short").join("outputs").exists());
+    }
+
+    #[tokio::test]
+    async fn test_long_output_is_saved_and_readable() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let (mut internals, _) = internals(&runtime, dir.path(), "long");
+        internals.context.insert_resource(OutputOverflow::new(1000).with_lines(2, 2));
+        internals.context.insert_resource(FileTracker::default());
+        // Read is confined elsewhere and has no permission rule
+        let elsewhere = tempfile::tempdir().unwrap();
+        internals
+            .context
+            .insert_resource(crate::tools::common::PathPolicy::new().allow_root(elsewhere.path()));
+        let mut tools = ToolRegistry::new();
+        tools.register(LogTool);
+        tools.register(crate::tools::common::ReadTool::new().unwrap());
+
+        let result = ToolExecutor::execute(&mut internals, &tools, "Log", "toolu_42", &serde_json::json!({"lines": 4213})).await;
+        let saved = std::fs::canonicalize(dir.path().join("long/outputs/toolu_42.txt")).unwrap();
+        assert_eq!(
+            text(&result),
+            format!(
+                "log line 1\nlog line 2\n... (4209 lines omitted) ...\nlog line 4212\nlog line 4213\n\n\
+                 [full output saved to {} (4213 lines) — use Read with offset/limit to inspect]",
+                saved.display()
+            )
+        );
+        assert_eq!(std::fs::read_to_string(&saved).unwrap().lines().count(), 4213);
+
+        let input = serde_json::json!({"file_path": saved, "offset": 3000, "limit": 2});
+        let read = tokio::time::timeout(
+            Duration::from_secs(5),
+            ToolExecutor::execute_with_permission(&mut internals, &tools, None, "Read", "b", &input),
+        )
+        .await
+        .expect("Read of saved output asked for permission");
+        assert!(!read.is_error, "{:?}", read.content);
+        assert!(text(&read).contains("log line 3000"), "{}", text(&read));
+        assert!(!text(&read).contains("saved to"));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_skips_tools_that_are_not_read_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let (mut internals, _rx) = internals(&runtime, dir.path(), "dry");
+        {
+            let mut session = internals.session.write().await;
+            session.set_custom("dry_run", true);
+            session.set_custom("dangerous_skip_permissions", true);
+        }
+        let mut tools = ToolRegistry::new();
+        tools.register(LogTool);
+        tools.register(crate::tools::common::WriteTool::with_base_dir(work.path().to_string_lossy()));
+
+        let input = serde_json::json!({"file_path": "notes.md", "content": "one\ntwo\n"});
+        let result = ToolExecutor::execute_with_permission(&mut internals, &tools, None, "Write", "a", &input).await;
+        assert!(!result.is_error);
+        assert_eq!(
+            text(&result),
+            format!(
+                "[DRY RUN] Write was not executed.\nCreate {} with 2 lines (8 bytes)",
+                work.path().join("notes.md").display()
+            )
+        );
+        assert!(!work.path().join("notes.md").exists());
+
+        // Read-only tools still run
+        let log = serde_json::json!({"lines": 2});
+        let result = ToolExecutor::execute_with_permission(&mut internals, &tools, None, "Log", "b", &log).await;
+        assert_eq!(text(&result), "log line 1\nlog line 2\n");
+
+        // So do tools that only touch the agent's own state
+        internals.context.insert_resource(crate::helpers::TodoListManager::new());
+        tools.register(crate::tools::common::TodoWriteTool::new());
+        let todos = serde_json::json!({"todos": [{"content": "Plan", "status": "pending", "activeForm": "Planning"}]});
+        let result = ToolExecutor::execute_with_permission(&mut internals, &tools, None, "TodoWrite", "t", &todos).await;
+        assert!(!text(&result).starts_with(DRY_RUN_PREFIX), "{}", text(&result));
+        let manager = internals.context.get_resource::<crate::helpers::TodoListManager>().unwrap();
+        assert_eq!(manager.len(), 1);
+
+        // Turned off at runtime, the write goes through
+        internals.session.write().await.set_custom("dry_run", false);
+        let result = ToolExecutor::execute_with_permission(&mut internals, &tools, None, "Write", "c", &input).await;
+        assert!(!text(&result).starts_with(DRY_RUN_PREFIX), "{}", text(&result));
+        assert!(work.path().join("notes.md").exists());
+    }
+
+    async fn run_log(internals: &mut AgentInternals, tools: &ToolRegistry, input: Value) -> ToolResult {
+        ToolExecutor::execute_with_permission(internals, tools, None, "Log", "a", &input).await
+    }
+
+    #[tokio::test]
+    async fn test_input_is_validated_before_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let (mut internals, _rx) = internals(&runtime, dir.path(), "validate");
+        internals.session.write().await.set_custom("dangerous_skip_permissions", true);
+        internals.context.insert_resource(InputValidation::new());
+        let mut tools = ToolRegistry::new();
+        tools.register(LogTool);
+
+        let missing = run_log(&mut internals, &tools, serde_json::json!({})).await;
+        assert!(missing.is_error);
+        assert!(text(&missing).starts_with("Invalid input for Log:\n- \"lines\" is a required property"), "{}", text(&missing));
+        let mistyped = run_log(&mut internals, &tools, serde_json::json!({"lines": "2"})).await;
+        assert!(text(&mistyped).contains("- /lines: \"2\" is not of type \"integer\""), "{}", text(&mistyped));
+        assert!(text(&mistyped).contains("The tool was not run"));
+
+        // Fields the schema doesn't mention are allowed
+        let extra = run_log(&mut internals, &tools, serde_json::json!({"lines": 1, "color": "red"})).await;
+        assert_eq!(text(&extra), "log line 1\n");
+
+        // A skipped tool runs with whatever it was given
+        internals.context.insert_resource(InputValidation::new().skip_tool("Log"));
+        let result = run_log(&mut internals, &tools, serde_json::json!({"lines": "2"})).await;
+        assert!(!result.is_error);
+        assert_eq!(text(&result), "");
+    }
+
+    #[tokio::test]
+    async fn test_input_is_validated_after_hooks_rewrite_it() {
+        use crate::hooks::{HookEvent, HookResult};
+
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let (mut internals, _rx) = internals(&runtime, dir.path(), "validate-hooks");
+        internals.context.insert_resource(InputValidation::new());
+        let mut tools = ToolRegistry::new();
+        tools.register(LogTool);
+        let mut hooks = HookRegistry::new();
+        hooks.add(HookEvent::PreToolUse, |ctx: &mut HookContext| {
+            let mut input = ctx.tool_input.clone().unwrap();
+            // Repairs a string count, and breaks a zero one
+            input["lines"] = match input["lines"].as_str() {
+                Some(lines) => serde_json::json!(lines.parse::<u64>().unwrap()),
+                None if input["lines"] == 0 => serde_json::json!("none"),
+                None => input["lines"].clone(),
+            };
+            ctx.tool_input = Some(input);
+            HookResult::allow()
+        });
+
+        let input = serde_json::json!({"lines": "2"});
+        let repaired = ToolExecutor::execute_with_permission(&mut internals, &tools, Some(&hooks), "Log", "a", &input).await;
+        assert_eq!(text(&repaired), "log line 1\nlog line 2\n");
+        let input = serde_json::json!({"lines": 0});
+        let broken = ToolExecutor::execute_with_permission(&mut internals, &tools, Some(&hooks), "Log", "b", &input).await;
+        assert!(text(&broken).contains("- /lines: \"none\" is not of type \"integer\""), "{}", text(&broken));
+    }
+
+    #[test]
+    fn test_describe_effect_defaults_to_the_input() {
+        let input = serde_json::json!({"lines": 2});
+        assert_eq!(LogTool.describe_effect(&input), "{\n  \"lines\": 2\n}");
     }
 }
@@ -0,0 +1,535 @@
+//! Declarative agent definitions
+//!
+//! Loads an agent from a TOML or JSON file instead of a chain of builder calls.
+//! The file format is picked from the extension (`.json` is JSON, anything else
+//! is TOML). Unknown fields are rejected with their path, e.g.
+//! `agent.toml: llm.modle: unknown field`.
+//!
+//! ```toml
+//! system_prompt = { file = "prompts/main.md" }   # or an inline string
+//! streaming = true
+//! prompt_caching = true
+//! thinking_budget = 16000
+//! max_tool_iterations = 50
+//! tools = ["Read", "Glob", "Bash"]
+//!
+//! [llm]
+//! provider = "anthropic"
+//! model = "claude-sonnet-4-5-20250929"
+//! max_tokens = 32000
+//!
+//! [permissions]
+//! allow = ["Read", "Bash(git status)"]
+//! deny = ["Bash(rm )"]
+//!
+//! [[mcp.servers]]
+//! id = "search"
+//! uri = "http://localhost:8005/mcp"
+//! ```
+//!
+//! Relative prompt files are resolved against the directory of the config file.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let loaded = load_agent("agent.toml", llm, registry)?;
+//! let config = loaded.config.with_hooks(loaded.hooks);
+//! let agent = StandardAgent::new(config, loaded.llm);
+//! let handle = runtime
+//!     .spawn_with_local_rules(session, loaded.permission_rules, |i| agent.run(i))
+//!     .await;
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::hooks::{HookContext, HookEvent, HookRegistry, HookResult};
+use crate::llm::LlmProvider;
+use crate::mcp::MCPConfig;
+use crate::permissions::PermissionRule;
+use crate::tools::ToolRegistry;
+
+use super::config::AgentConfig;
+
+/// Max tokens used when the file sets a model but no `max_tokens`
+const DEFAULT_MAX_TOKENS: u32 = 32000;
+
+/// Contents of an agent definition file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgentFile {
+    /// System prompt, inline or from a file
+    pub system_prompt: PromptSource,
+
+    /// Provider, model and max tokens
+    #[serde(default)]
+    pub llm: LlmSection,
+
+    /// Whether to stream responses
+    pub streaming: Option<bool>,
+
+    /// Extended thinking budget in tokens
+    pub thinking_budget: Option<u32>,
+
+    /// Whether to enable prompt caching
+    pub prompt_caching: Option<bool>,
+
+    /// Maximum number of tool iterations per turn
+    pub max_tool_iterations: Option<usize>,
+
+    /// Whether to auto-name the conversation after the first turn
+    pub auto_name: Option<bool>,
+
+    /// Tools to expose, resolved against the registry (None = all tools)
+    pub tools: Option<Vec<String>>,
+
+    /// Allow and deny rules
+    #[serde(default)]
+    pub permissions: PermissionsSection,
+
+    /// MCP servers to connect to
+    pub mcp: Option<MCPConfig>,
+}
+
+/// Where the system prompt comes from
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum PromptSource {
+    /// The prompt text itself
+    Inline(String),
+    /// A file containing the prompt
+    File {
+        /// Path to the prompt file
+        file: PathBuf,
+    },
+}
+
+/// The `[llm]` section
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LlmSection {
+    /// Expected provider name (must match `LlmProvider::provider_name`)
+    pub provider: Option<String>,
+    /// Model to use instead of the provider's default
+    pub model: Option<String>,
+    /// Max tokens per response
+    pub max_tokens: Option<u32>,
+}
+
+/// The `[permissions]` section
+///
+/// Rules are written as `Tool` or `Tool(prefix)`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PermissionsSection {
+    /// Tools (or command prefixes) that run without asking
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Tools (or command prefixes) that are always blocked
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+/// Everything built from an agent definition file
+pub struct LoadedAgent {
+    /// Agent configuration with prompt, tools and options applied
+    pub config: AgentConfig,
+    /// Deny hooks from `[permissions].deny`; add your own, then `config.with_hooks(hooks)`
+    pub hooks: HookRegistry,
+    /// The LLM provider, switched to the configured model and max tokens
+    pub llm: Arc<dyn LlmProvider>,
+    /// Local permission rules from `[permissions].allow`, for `spawn_with_local_rules`
+    pub permission_rules: Vec<PermissionRule>,
+    /// MCP servers from `[mcp]`
+    pub mcp: MCPConfig,
+}
+
+impl AgentFile {
+    /// Parse an agent definition file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read agent config {}", path.display()))?;
+
+        let is_json = path.extension().map(|e| e == "json").unwrap_or(false);
+        let mut file: AgentFile = if is_json {
+            parse(path, &mut serde_json::Deserializer::from_str(&text))?
+        } else {
+            parse(path, toml::Deserializer::new(&text))?
+        };
+
+        if let PromptSource::File { file: prompt_path } = &file.system_prompt {
+            let prompt_path = match path.parent() {
+                Some(dir) if prompt_path.is_relative() => dir.join(prompt_path),
+                _ => prompt_path.clone(),
+            };
+            let prompt = std::fs::read_to_string(&prompt_path).with_context(|| {
+                format!("Failed to read system prompt {}", prompt_path.display())
+            })?;
+            file.system_prompt = PromptSource::Inline(prompt);
+        }
+
+        Ok(file)
+    }
+
+    /// The system prompt text (prompt files are read by `load`)
+    pub fn system_prompt(&self) -> &str {
+        match &self.system_prompt {
+            PromptSource::Inline(prompt) => prompt,
+            PromptSource::File { .. } => "",
+        }
+    }
+
+    /// Apply the agent options (prompt, streaming, thinking, caching, iterations)
+    pub fn apply(&self, mut config: AgentConfig) -> AgentConfig {
+        config.system_prompt = self.system_prompt().to_string();
+        if let Some(streaming) = self.streaming {
+            config = config.with_streaming(streaming);
+        }
+        if let Some(budget) = self.thinking_budget {
+            config = config.with_thinking(budget);
+        }
+        if let Some(caching) = self.prompt_caching {
+            config = config.with_prompt_caching(caching);
+        }
+        if let Some(max) = self.max_tool_iterations {
+            config = config.with_max_tool_iterations(max);
+        }
+        if let Some(auto_name) = self.auto_name {
+            config = config.with_auto_name(auto_name);
+        }
+        config
+    }
+
+    /// Switch the provider to the configured model and max tokens
+    pub fn apply_llm(&self, llm: Arc<dyn LlmProvider>) -> Result<Arc<dyn LlmProvider>> {
+        if let Some(provider) = &self.llm.provider {
+            if provider != llm.provider_name() {
+                bail!(
+                    "llm.provider is '{}' but the provider passed in is '{}'",
+                    provider,
+                    llm.provider_name()
+                );
+            }
+        }
+
+        if self.llm.model.is_none() && self.llm.max_tokens.is_none() {
+            return Ok(llm);
+        }
+        let model = self.llm.model.clone().unwrap_or_else(|| llm.model());
+        let max_tokens = self.llm.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS);
+        Ok(llm.create_variant(&model, max_tokens))
+    }
+
+    /// Build the tool registry from the allow-list
+    pub fn resolve_tools(&self, registry: Arc<ToolRegistry>) -> Result<Arc<ToolRegistry>> {
+        match &self.tools {
+            Some(names) => registry.subset(names).map(Arc::new).context("tools"),
+            None => Ok(registry),
+        }
+    }
+
+    /// Local permission rules from `[permissions].allow`
+    pub fn permission_rules(&self) -> Result<Vec<PermissionRule>> {
+        self.permissions
+            .allow
+            .iter()
+            .map(|rule| {
+                let (tool, prefix) = parse_rule(rule)
+                    .with_context(|| format!("permissions.allow: invalid rule '{}'", rule))?;
+                Ok(match prefix {
+                    Some(prefix) => PermissionRule::allow_prefix(tool, prefix),
+                    None => PermissionRule::allow_tool(tool),
+                })
+            })
+            .collect()
+    }
+
+    /// Deny rules from `[permissions].deny`
+    pub fn deny_rules(&self) -> Result<Vec<PermissionRule>> {
+        self.permissions
+            .deny
+            .iter()
+            .map(|rule| {
+                let (tool, prefix) = parse_rule(rule)
+                    .with_context(|| format!("permissions.deny: invalid rule '{}'", rule))?;
+                Ok(match prefix {
+                    Some(prefix) => PermissionRule::deny_prefix(tool, prefix),
+                    None => PermissionRule::deny_tool(tool),
+                })
+            })
+            .collect()
+    }
+
+    /// Deny hooks from `[permissions].deny`
+    ///
+    /// Each hook matches its rule from `deny_rules`, so a Bash prefix is
+    /// checked against every command of the command line (`cd x && rm -rf /`
+    /// is blocked by `Bash(rm )`). As hooks, the rules also hold when
+    /// permission checks are skipped.
+    pub fn deny_hooks(&self) -> Result<HookRegistry> {
+        let mut hooks = HookRegistry::new();
+        for (rule, deny) in self.permissions.deny.iter().zip(self.deny_rules()?) {
+            let pattern = format!("^{}$", regex::escape(&deny.tool_name));
+            let reason = format!("Blocked by agent config rule '{}'", rule);
+
+            hooks.add_with_pattern(HookEvent::PreToolUse, &pattern, move |ctx: &mut HookContext| {
+                let input = ctx.tool_input.as_ref().map(Value::to_string).unwrap_or_default();
+                if deny.matches(&deny.tool_name, &input) {
+                    HookResult::deny(reason.clone())
+                } else {
+                    HookResult::none()
+                }
+            })?;
+        }
+        Ok(hooks)
+    }
+}
+
+/// Load an agent definition and build everything it describes
+///
+/// `llm` is switched to the configured model, `registry` is narrowed to the
+/// `tools` allow-list, and deny rules become `PreToolUse` hooks.
+pub fn load_agent(
+    path: impl AsRef<Path>,
+    llm: Arc<dyn LlmProvider>,
+    registry: Arc<ToolRegistry>,
+) -> Result<LoadedAgent> {
+    let path = path.as_ref();
+    let file = AgentFile::load(path)?;
+    let context = || format!("Invalid agent config {}", path.display());
+
+    let tools = file.resolve_tools(registry).with_context(context)?;
+    let config = file.apply(AgentConfig::new("")).with_tools(tools);
+
+    Ok(LoadedAgent {
+        config,
+        hooks: file.deny_hooks().with_context(context)?,
+        llm: file.apply_llm(llm).with_context(context)?,
+        permission_rules: file.permission_rules().with_context(context)?,
+        mcp: file.mcp.clone().unwrap_or_default(),
+    })
+}
+
+/// Deserialize, reporting the path of the offending field
+fn parse<'de, T, D>(path: &Path, deserializer: D) -> Result<T>
+where
+    T: DeserializeOwned,
+    D: serde::Deserializer<'de>,
+{
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        anyhow::anyhow!("{}: {}: {}", path.display(), e.path(), e.inner())
+    })
+}
+
+/// Split `Tool(prefix)` into its parts
+fn parse_rule(rule: &str) -> Result<(String, Option<String>)> {
+    let rule = rule.trim();
+    let Some(open) = rule.find('(') else {
+        if rule.is_empty() {
+            bail!("empty rule");
+        }
+        return Ok((rule.to_string(), None));
+    };
+    let Some(prefix) = rule[open + 1..].strip_suffix(')') else {
+        bail!("missing ')'");
+    };
+    let tool = rule[..open].trim();
+    if tool.is_empty() {
+        bail!("missing tool name");
+    }
+    Ok((tool.to_string(), Some(prefix.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::MockLlmProvider;
+    use crate::permissions::RuleType;
+    use crate::tools::{BashTool, GlobTool, ReadTool};
+    use serde_json::json;
+
+    fn registry() -> Arc<ToolRegistry> {
+        let mut registry = ToolRegistry::new();
+        registry.register(ReadTool::new().unwrap());
+        registry.register(GlobTool::new().unwrap());
+        registry.register(BashTool::new().unwrap());
+        Arc::new(registry)
+    }
+
+    fn write(dir: &Path, name: &str, contents: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_full_config_is_applied() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("prompts")).unwrap();
+        write(dir.path(), "prompts/main.md", "You are a file assistant.");
+        let path = write(
+            dir.path(),
+            "agent.toml",
+            r#"
+system_prompt = { file = "prompts/main.md" }
+streaming = true
+prompt_caching = false
+thinking_budget = 8000
+max_tool_iterations = 7
+auto_name = false
+tools = ["Read", "Bash"]
+
+[llm]
+provider = "mock"
+model = "mock-large"
+max_tokens = 1234
+
+[permissions]
+allow = ["Read", "Bash(git status)"]
+deny = ["Bash(rm )"]
+
+[mcp]
+global_timeout_ms = 5000
+
+[[mcp.servers]]
+id = "search"
+uri = "http://localhost:8005/mcp"
+reconnect_attempts = 1
+"#,
+        );
+
+        let mock = MockLlmProvider::new();
+        let loaded = load_agent(&path, Arc::new(mock.clone()), registry()).unwrap();
+
+        let config = &loaded.config;
+        assert_eq!(config.system_prompt, "You are a file assistant.");
+        assert!(config.streaming_enabled);
+        assert!(!config.enable_prompt_caching);
+        assert_eq!(config.thinking.as_ref().map(|t| t.budget_tokens), Some(8000));
+        assert_eq!(config.max_tool_iterations, 7);
+        assert!(!config.auto_name_conversation);
+
+        let mut tools = config.tools.as_ref().unwrap().tool_names();
+        tools.sort();
+        assert_eq!(tools, vec!["Bash", "Read"]);
+
+        assert_eq!(loaded.llm.model(), "mock-large");
+        loaded.llm.send_message("hi", &[], None, None).await.ok();
+        assert_eq!(mock.requests()[0].max_tokens, 1234);
+
+        assert_eq!(loaded.permission_rules.len(), 2);
+        assert_eq!(loaded.permission_rules[0].rule_type, RuleType::AllowTool);
+        assert_eq!(loaded.permission_rules[1].tool_name, "Bash");
+        assert_eq!(loaded.permission_rules[1].prefix.as_deref(), Some("git status"));
+
+        assert_eq!(loaded.mcp.global_timeout_ms, Some(5000));
+        assert_eq!(loaded.mcp.servers[0].id, "search");
+        assert_eq!(loaded.mcp.servers[0].reconnect_attempts, 1);
+
+        assert_eq!(loaded.hooks.hook_count(HookEvent::PreToolUse), 1);
+    }
+
+    #[test]
+    fn test_json_and_inline_prompt() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(
+            dir.path(),
+            "agent.json",
+            r#"{"system_prompt": "Be brief.", "max_tool_iterations": 3}"#,
+        );
+
+        let config = AgentConfig::from_file(&path).unwrap();
+        assert_eq!(config.system_prompt, "Be brief.");
+        assert_eq!(config.max_tool_iterations, 3);
+        assert!(config.tools.is_none());
+    }
+
+    #[test]
+    fn test_unknown_field_reports_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(
+            dir.path(),
+            "agent.toml",
+            "system_prompt = \"x\"\n[llm]\nmodle = \"typo\"\n",
+        );
+
+        let err = AgentFile::load(&path).unwrap_err().to_string();
+        assert!(err.contains("agent.toml: llm.modle:"), "{}", err);
+        assert!(err.contains("unknown field `modle`"), "{}", err);
+    }
+
+    #[test]
+    fn test_unknown_tool_and_provider_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(
+            dir.path(),
+            "agent.toml",
+            "system_prompt = \"x\"\ntools = [\"Read\", \"Nope\"]\n",
+        );
+        let err = load_agent(&path, Arc::new(MockLlmProvider::new()), registry()).err().unwrap();
+        assert!(format!("{:#}", err).contains("Nope"));
+
+        let path = write(
+            dir.path(),
+            "other.toml",
+            "system_prompt = \"x\"\n[llm]\nprovider = \"gemini\"\n",
+        );
+        let err = load_agent(&path, Arc::new(MockLlmProvider::new()), registry()).err().unwrap();
+        assert!(format!("{:#}", err).contains("gemini"));
+    }
+
+    #[test]
+    fn test_example_config_parses() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/test_agent/agent.toml");
+        let file = AgentFile::load(path).unwrap();
+        assert!(file.system_prompt().contains("coding assistant"));
+        assert!(file.permission_rules().is_ok());
+        assert!(file.deny_hooks().is_ok());
+    }
+
+    #[test]
+    fn test_deny_rules() {
+        let file: AgentFile =
+            toml::from_str("system_prompt = \"x\"\n[permissions]\ndeny = [\"Bash(rm )\", \"WebFetch\"]").unwrap();
+        let rules = file.deny_rules().unwrap();
+        assert_eq!(rules[0], PermissionRule::deny_prefix("Bash", "rm "));
+        assert_eq!(rules[1], PermissionRule::deny_tool("WebFetch"));
+
+        let bash = |command: &str| rules[0].matches("Bash", &json!({"command": command}).to_string());
+        assert!(bash("  rm -rf /"));
+        assert!(!bash("ls"));
+        assert!(!bash("rmdir x"));
+        assert!(rules[1].matches("WebFetch", &json!({"url": "x"}).to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_deny_hook_checks_every_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(dir.path(), "agent.toml", "system_prompt = \"x\"\n[permissions]\ndeny = [\"Bash(rm )\"]\n");
+        let hooks = AgentFile::load(&path).unwrap().deny_hooks().unwrap();
+        let mut internals = crate::runtime::AgentInternals::for_test(dir.path());
+
+        for (command, denied) in [("cd x && rm -rf /", true), ("ls; rm a", true), ("X=1 rm -rf /", true), ("sudo /bin/rm a", true), ("echo rm", false), ("cd x && ls", false)] {
+            let input = json!({"command": command});
+            let mut ctx = HookContext::pre_tool_use(&mut internals, "Bash", &input, "call_1").await;
+            assert_eq!(hooks.run(&mut ctx).decision.is_some(), denied, "{}", command);
+        }
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(parse_rule("Read").unwrap(), ("Read".to_string(), None));
+        assert_eq!(
+            parse_rule("Bash(git log)").unwrap(),
+            ("Bash".to_string(), Some("git log".to_string()))
+        );
+        assert!(parse_rule("Bash(git").is_err());
+        assert!(parse_rule("(x)").is_err());
+    }
+}
@@ -26,11 +26,25 @@
 //! - `AgentConfig` - Configuration for the agent (system prompt, tools, injections)
 //! - `StandardAgent` - The agent implementation
 //! - `ToolExecutor` - Handles permission-aware tool execution
+//! - `load_agent` - Builds an agent from a declarative `agent.toml` / JSON file
+//! - `SubAgentRegistry` / `TaskTool` - Subagent types from markdown files, spawned by the model
+//! - `ContextOverflow` - What happens when a request won't fit the context window
+//! - `BatchEval` - Single-shot prompts through the Message Batches API, written to sessions
 
+mod batch;
 mod config;
+mod context_window;
 mod executor;
+mod loader;
 mod standard_loop;
+mod subagents;
+mod task_tool;
 
-pub use config::AgentConfig;
-pub use executor::ToolExecutor;
+pub use batch::{BatchEval, BatchOutcome, BATCH_ERROR_METADATA_KEY, BATCH_ID_METADATA_KEY};
+pub use config::{AgentConfig, ThinkingPolicy, DEFAULT_INTERRUPT_MARKER};
+pub use context_window::{ContextOverflow, CONTEXT_SUMMARY_METADATA_KEY, DEFAULT_MIN_OUTPUT_TOKENS};
+pub use executor::{ToolExecutor, DRY_RUN_PREFIX};
+pub use loader::{load_agent, AgentFile, LlmSection, LoadedAgent, PermissionsSection, PromptSource};
 pub use standard_loop::StandardAgent;
+pub use subagents::{load_subagent_definitions, SubAgentDefinition, SubAgentRegistry};
+pub use task_tool::{ForwardMode, TaskTool};
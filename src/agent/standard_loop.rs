@@ -8,24 +8,41 @@
 //! - Streaming responses (when enabled)
 //! - Automatic conversation naming (after first turn)
 
+use std::future::Future;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use futures::StreamExt;
 use serde_json::Value;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
-use crate::core::{FrameworkResult, InputMessage};
-use crate::helpers::{process_attachments, ConversationNamer, Debugger};
+use crate::core::{FrameworkError, FrameworkResult, InputMessage, InputOptions, OutputChunk, ToolCallSummary};
+use crate::helpers::{
+    inject_system_reminder, load_attachments, parse_structured_result, schema_instructions, ConversationNamer, ConversationSummarizer, SummaryStyle, Debugger, FileChangeJournal, FileTracker, ProjectMemory, LoopDetector, LoopStatus, TodoItem,
+    TodoListManager, STRUCTURED_RESULT_METADATA_KEY,
+};
 use crate::hooks::HookContext;
 use crate::llm::{
-    CacheControl, ContentBlock, ContentBlockStart, ContentDelta, LlmProvider, Message,
-    StopReason, StreamEvent, SystemBlock, SystemPrompt,
+    estimate_message_tokens, estimate_request_tokens, CacheControl, ContentBlock, ContentBlockStart, ContentDelta, LlmProvider, Message,
+    MessageContent, ResponseFormat, StopReason, StreamEvent, SystemBlock, SystemPrompt, ToolChoice, Usage,
+    WebSearchToolResultContent,
 };
-use crate::runtime::AgentInternals;
+use crate::llm::stream_blocks::BlockAccumulator;
+use crate::runtime::{AgentFuture, AgentInternals, SpawnableAgent};
+use crate::{metrics, telemetry};
+use crate::session::SessionSummary;
+use crate::tools::common::todo::todos_from_input;
 use crate::tools::{ToolResult, ToolResultData};
 
 use super::config::AgentConfig;
-use super::executor::ToolExecutor;
+use super::context_window::{
+    choose_cut, input_budget, is_cut_point, output_budget, summary_messages, tokens_from, ContextOverflow, DroppedMessages,
+    CONTEXT_SUMMARY_METADATA_KEY, SUMMARY_MAX_TOKENS,
+};
+use super::executor::{ToolExecutor, PERMISSION_TIMEOUT_ABORT};
+use super::task_tool::ForwardMode;
 
 /// Standard agent that handles the full agent loop
 ///
@@ -46,17 +63,101 @@ pub struct StandardAgent {
     llm: Arc<dyn LlmProvider>,
 }
 
+/// Tool calls and token usage collected during a turn, for `TurnComplete`
+#[derive(Default)]
+struct TurnRecord {
+    tool_calls: Vec<ToolCallSummary>,
+    usage: Usage,
+}
+
+/// Result of a tool call skipped because the turn ran out of time
+const TURN_TIMED_OUT: &str = "Not run: the turn timed out";
+
+/// When the running turn has to stop (see `AgentConfig::turn_timeout`)
+#[derive(Debug, Clone, Copy)]
+struct TurnDeadline {
+    at: Instant,
+    timeout: Duration,
+}
+
+impl TurnDeadline {
+    fn new(timeout: Duration) -> Self {
+        Self {
+            at: Instant::now() + timeout,
+            timeout,
+        }
+    }
+
+    fn passed(&self) -> bool {
+        Instant::now() >= self.at
+    }
+
+    /// "Turn timed out after 2m 30s"
+    fn message(&self) -> String {
+        let secs = self.timeout.as_secs();
+        let after = match secs {
+            0 => format!("{}ms", self.timeout.as_millis()),
+            1..=59 => format!("{}s", secs),
+            _ if secs.is_multiple_of(60) => format!("{}m", secs / 60),
+            _ => format!("{}m {}s", secs / 60, secs % 60),
+        };
+        format!("Turn timed out after {}", after)
+    }
+}
+
 impl StandardAgent {
     /// Create a new standard agent
+    ///
+    /// Any `Arc<P>` of a concrete provider (e.g. `Arc<AnthropicProvider>`)
+    /// coerces to `Arc<dyn LlmProvider>` at the call, so no conversion is needed.
     pub fn new(config: AgentConfig, llm: Arc<dyn LlmProvider>) -> Self {
         Self { config, llm }
     }
 
+    /// Create a standard agent that owns its provider
+    ///
+    /// Shorthand for `StandardAgent::new(config, Arc::new(provider))`.
+    pub fn from_provider(config: AgentConfig, provider: impl LlmProvider + 'static) -> Self {
+        Self::new(config, Arc::new(provider))
+    }
+
+    /// Turn off config features the model doesn't support
+    ///
+    /// Checked once at spawn against `LlmProvider::capabilities()`, so a
+    /// model without extended thinking or prompt caching runs with them
+    /// disabled (and a warning) rather than failing its first request.
+    fn apply_model_capabilities(&mut self) {
+        let capabilities = self.llm.capabilities();
+        let model = self.llm.model();
+        if self.config.thinking.is_some() && !capabilities.supports_thinking {
+            tracing::warn!(
+                "[StandardAgent] Model {} does not support extended thinking, disabling it",
+                model
+            );
+            self.config.thinking = None;
+        }
+        if self.config.enable_prompt_caching && !capabilities.supports_caching {
+            tracing::warn!(
+                "[StandardAgent] Model {} does not support prompt caching, disabling it",
+                model
+            );
+            self.config.enable_prompt_caching = false;
+        }
+    }
+
     /// Run the agent loop
     ///
     /// This is the main entry point - pass this to `runtime.spawn()`.
-    pub async fn run(self, mut internals: AgentInternals) -> FrameworkResult<()> {
+    /// Everything logged by the agent runs inside an `agent` span carrying
+    /// the `session_id`, so per-session log files pick it up.
+    pub async fn run(self, internals: AgentInternals) -> FrameworkResult<()> {
+        let span = tracing::info_span!("agent", session_id = %internals.session_id());
+        self.run_loop(internals).instrument(span).await
+    }
+
+    async fn run_loop(mut self, mut internals: AgentInternals) -> FrameworkResult<()> {
         tracing::info!("[StandardAgent] Started, waiting for input...");
+        self.apply_model_capabilities();
 
         // Write initial model/provider info into session metadata
         {
@@ -66,6 +167,25 @@ impl StandardAgent {
 
             // Store dangerous_skip_permissions in session metadata for runtime access
             session.set_custom("dangerous_skip_permissions", self.config.dangerous_skip_permissions);
+            if let Some(dry_run) = self.config.dry_run {
+                session.set_custom("dry_run", dry_run);
+            }
+
+            // Carry on the turn count of a resumed session (estimated from
+            // the history for sessions saved before it was recorded)
+            internals.context.current_turn = session
+                .completed_turns()
+                .unwrap_or_else(|| session.history().iter().filter(|m| is_user_prompt(m)).count());
+
+            // Restore the todo list of a resumed session
+            if let Some(todos) = internals.context.get_resource::<TodoListManager>() {
+                if todos.is_empty() && todos.load_from_session(&session) {
+                    tracing::info!(
+                        "[StandardAgent] Restored {} todo item(s) from session",
+                        todos.len()
+                    );
+                }
+            }
         }
 
         // Log warning if dangerous mode is enabled
@@ -75,13 +195,61 @@ impl StandardAgent {
             );
         }
 
+        internals.permissions.set_timeout(self.config.permission_timeout);
+
+        // Track file reads for read-before-write enforcement (keep one the
+        // caller put in this agent's scope; a shared one would mix up agents)
+        if !internals.context.resources.contains::<FileTracker>() {
+            internals
+                .context
+                .insert_resource(FileTracker::new(self.config.file_enforcement));
+        }
+
+        // Journal file changes in the session so a resumed agent can undo them
+        if !internals.context.resources.contains::<FileChangeJournal>() {
+            let path = {
+                let session = internals.session.read().await;
+                session.storage().changes_path(session.session_id())
+            };
+            internals.context.insert_resource(FileChangeJournal::new(path));
+        }
+
+        // Let the executor save long tool output (redacted like the history)
+        if let Some(overflow) = self.config.output_overflow {
+            internals.context.insert_resource(overflow);
+            if let Some(ref redactor) = self.config.tool_result_redactor {
+                internals.context.insert_resource(redactor.clone());
+            }
+        }
+
+        // Read by the executor before each tool call
+        if let Some(ref validation) = self.config.input_validation {
+            internals.context.insert_resource(validation.clone());
+        }
+
+        if let Some(ref memory) = self.config.project_memory {
+            internals.context.insert_resource(ProjectMemory::new(memory.clone()));
+        }
+
+        // Read by TaskTool when it spawns a subagent
+        if self.config.forward_subagent_output != ForwardMode::None {
+            internals.context.insert_resource(self.config.forward_subagent_output);
+        }
+
+        // Subagent spawning runs the hooks from the agent's scope
+        if let Some(ref hooks) = self.config.hooks {
+            internals.context.insert_resource_arc(hooks.clone());
+        }
+
         // Initialize debugger if enabled
-        if self.config.debug_enabled {
+        if let Some(debugger) = self.config.debugger.as_ref().filter(|_| self.config.debug_enabled) {
+            internals.context.insert_resource_arc(debugger.clone());
+        } else if self.config.debug_enabled {
             let session = internals.session.read().await;
             let session_dir = session.storage().session_dir(session.session_id());
             drop(session);
 
-            match Debugger::new(&session_dir) {
+            match Debugger::with_config(&session_dir, self.config.debug_config.clone()) {
                 Ok(debugger) => {
                     tracing::info!(
                         "[StandardAgent] Debug logging enabled at {:?}",
@@ -99,68 +267,45 @@ impl StandardAgent {
             // Signal we're ready for input
             internals.set_idle().await;
 
-            // Wait for next message
-            match internals.receive().await {
+            // Handle messages deferred during the last turn, then wait for the next one
+            let message = match internals.take_deferred() {
+                Some(_) if internals.is_cancelled() => Some(InputMessage::Shutdown),
+                Some(message) => Some(message),
+                None => internals.receive().await,
+            };
+            match message {
                 Some(InputMessage::UserInput(text)) => {
-                    tracing::info!("[StandardAgent] Received: {}", text);
-                    internals.set_processing().await;
-
-                    // Run UserPromptSubmit hooks
-                    let mut current_text = text.clone();
-                    let mut should_process = true;
-
-                    if let Some(ref hooks) = self.config.hooks {
-                        let mut ctx = HookContext::user_prompt_submit(
-                            &mut internals,
-                            &text,
-                            self.config.hook_short_circuit,
-                        );
-                        let result = hooks.run(&mut ctx);
-
-                        // Hook may have modified the prompt
-                        if let Some(modified) = ctx.user_prompt {
-                            current_text = modified;
-                        }
-
-                        // Check if hook denied the prompt
-                        if let Some(crate::hooks::PermissionDecision::Deny) = result.decision {
-                            let reason = result.reason.unwrap_or_else(|| "Blocked by hook".to_string());
-                            tracing::info!("[StandardAgent] UserPromptSubmit hook denied: {}", reason);
-                            internals.send_error(format!("Prompt blocked: {}", reason));
-                            should_process = false;
-                        }
-                    }
+                    self.handle_user_input(&mut internals, text, InputOptions::new()).await;
+                }
 
-                    tracing::info!("[StandardAgent] Should process: {}", should_process);
+                Some(InputMessage::UserInputWithAttachments { text, attachments }) => {
+                    let options = InputOptions::new().with_attachments(attachments);
+                    self.handle_user_input(&mut internals, text, options).await;
+                }
 
-                    // Process the user message (if not blocked by hook)
-                    if should_process {
-                        if let Err(e) = self.process_turn(&mut internals, &current_text).await {
-                            tracing::error!("[StandardAgent] Error processing turn: {}", e);
-                            internals.send_error(format!("Error: {}", e));
-                        }
+                Some(InputMessage::UserInputWithOptions { text, attachments, tool_choice, turn_timeout }) => {
+                    let options = InputOptions {
+                        attachments,
+                        tool_choice,
+                        turn_timeout,
+                    };
+                    self.handle_user_input(&mut internals, text, options).await;
+                }
 
-                        if self.config.auto_name_conversation && internals.context.current_turn == 0
-                        {
-                            let session_id = {
-                                let session = internals.session.read().await;
-                                session.session_id().to_string()
-                            };
-                            let has_name = internals.session.read().await.has_conversation_name();
-                            if !has_name {
-                                self.generate_conversation_name(&mut internals, Some(&session_id)).await;
-                            }
+                Some(InputMessage::RewindAndResend { message_index, new_text }) => {
+                    match self.rewind(&mut internals, message_index).await {
+                        Ok(()) => self.handle_user_input(&mut internals, new_text, InputOptions::new()).await,
+                        Err(e) => {
+                            tracing::error!("[StandardAgent] Rewind failed: {}", e);
+                            internals.send_error(format!("Rewind failed: {}", e));
+                            internals.send_done();
                         }
                     }
-                    // Signal turn complete
-                    internals.send_done();
+                }
 
-                    // Persist session if configured
-                    if self.config.auto_save_session {
-                        if let Err(e) = internals.session.write().await.save() {
-                            tracing::error!("[StandardAgent] Failed to save session: {}", e);
-                        }
-                    }
+                Some(InputMessage::AgentMessage { from_session_id, content }) => {
+                    let text = format_agent_message(&from_session_id, &content);
+                    self.handle_user_input(&mut internals, text, InputOptions::new()).await;
                 }
 
                 Some(InputMessage::Interrupt) => {
@@ -180,10 +325,164 @@ impl StandardAgent {
                     // Ignore other message types
                 }
             }
+        }
+
+        Ok(())
+    }
+
+    /// Run one user turn: hooks, the LLM loop, naming and saving
+    ///
+    /// Options left unset fall back to the config. The turn counter moves on
+    /// once the turn has run (also when it failed partway, since its prompt
+    /// is in the history); a prompt blocked by a hook doesn't count.
+    async fn handle_user_input(&self, internals: &mut AgentInternals, text: String, options: InputOptions) {
+        tracing::info!("[StandardAgent] Received: {}", text);
+        internals.set_processing().await;
+        internals.start_turn();
+
+        // Run UserPromptSubmit hooks
+        let mut current_text = text.clone();
+        let mut should_process = true;
+
+        if let Some(ref hooks) = self.config.hooks {
+            let mut ctx = HookContext::user_prompt_submit(
+                internals,
+                &text,
+            ).await;
+            let result = hooks.run(&mut ctx);
+
+            // Hook may have modified the prompt
+            if let Some(modified) = ctx.user_prompt {
+                current_text = modified;
+            }
+
+            // Check if hook denied the prompt
+            if let Some(crate::hooks::PermissionDecision::Deny) = result.decision {
+                let reason = result.reason.unwrap_or_else(|| "Blocked by hook".to_string());
+                tracing::info!("[StandardAgent] UserPromptSubmit hook denied: {}", reason);
+                internals.send_error(format!("Prompt blocked: {}", reason));
+                should_process = false;
+            }
+        }
+
+        tracing::info!("[StandardAgent] Should process: {}", should_process);
+
+        let history_start = internals.session.read().await.history().len();
+        let mut record = TurnRecord::default();
+
+        // Process the user message (if not blocked by hook)
+        if should_process {
+            let turn_span = telemetry::turn_span(
+                internals.session_id(),
+                internals.context.current_turn,
+            );
+            let turn_started = Instant::now();
+            if let Err(e) = self
+                .process_turn(internals, &current_text, options, &mut record)
+                .instrument(turn_span)
+                .await
+            {
+                if !internals.is_cancelled() {
+                    tracing::error!("[StandardAgent] Error processing turn: {}", e);
+                    internals.send_error(format!("Error: {}", e));
+                }
+            }
+            if internals.is_cancelled() {
+                tracing::info!("[StandardAgent] Turn stopped by shutdown");
+                internals.send_status("Shutting down");
+            }
+            metrics::record_turn(
+                internals.agent_type(),
+                self.llm.provider_name(),
+                &self.llm.model(),
+                turn_started.elapsed(),
+            );
 
+            if self.config.auto_name_conversation && internals.turn() == 0 && !internals.is_cancelled() {
+                let session_id = {
+                    let session = internals.session.read().await;
+                    session.session_id().to_string()
+                };
+                let has_name = internals.session.read().await.has_conversation_name();
+                if !has_name {
+                    self.generate_conversation_name(internals, Some(&session_id)).await;
+                }
+            }
+        }
+        // Signal turn complete
+        let final_text = {
+            let session = internals.session.read().await;
+            last_assistant_text(session.history().get(history_start..).unwrap_or_default())
+        };
+        internals.send(OutputChunk::TurnComplete {
+            turn: internals.context.current_turn,
+            final_text,
+            tool_calls: record.tool_calls,
+            usage: record.usage,
+        });
+        internals.send_done();
+
+        internals.end_turn();
+        if should_process {
             internals.next_turn();
+            internals.session.write().await.set_completed_turns(internals.turn());
+        }
+
+        // Persist session if configured
+        if self.config.auto_save_session {
+            if let Err(e) = internals.session.write().await.save() {
+                tracing::error!("[StandardAgent] Failed to save session: {}", e);
+            }
+        }
+    }
+
+    /// Truncate history to just before the user turn containing `message_index`
+    ///
+    /// The index is snapped back to the prompt that starts its turn so no
+    /// tool call loses its result. State tied to the removed messages is
+    /// rolled back too: the turn counter, the todo list (to the last
+    /// TodoWrite call still in history) and provider caches for the removed
+    /// tool calls.
+    async fn rewind(&self, internals: &mut AgentInternals, message_index: usize) -> Result<()> {
+        let (new_len, removed) = {
+            let mut session = internals.session.write().await;
+            let history = session.history();
+            if message_index >= history.len() {
+                anyhow::bail!(
+                    "message {} is past the end of the history ({} messages)",
+                    message_index,
+                    history.len()
+                );
+            }
+            let new_len = user_turn_start(history, message_index);
+            let removed = history[new_len..].to_vec();
+            session.truncate_after(new_len)?;
+            (new_len, removed)
+        };
+        tracing::info!(
+            "[StandardAgent] Rewound to message {} ({} messages removed)",
+            new_len,
+            removed.len()
+        );
+
+        let history = internals.session.read().await.history().to_vec();
+        internals.context.current_turn = history.iter().filter(|m| is_user_prompt(m)).count();
+        internals.session.write().await.set_completed_turns(internals.turn());
+
+        let removed_calls = tool_calls(&removed);
+        let removed_ids: Vec<String> = removed_calls.iter().map(|(id, _, _)| id.clone()).collect();
+        self.llm.forget_tool_calls(&removed_ids).await;
+
+        let todos_changed = removed_calls.iter().any(|(_, name, _)| name == "TodoWrite");
+        if let (true, Some(manager)) = (todos_changed, internals.context.get_resource::<TodoListManager>()) {
+            let (items, turn) = last_todo_list(&history).unwrap_or_default();
+            manager.restore(items, turn);
+            if let Err(e) = manager.save_to_session(&mut *internals.session.write().await) {
+                tracing::warn!("[StandardAgent] Failed to save rewound todo list: {}", e);
+            }
         }
 
+        internals.send(OutputChunk::HistoryTruncated { new_len });
         Ok(())
     }
 
@@ -193,7 +492,7 @@ impl StandardAgent {
 
         // Use naming LLM if configured, otherwise fall back to main LLM
         let naming_llm = self.config.naming_llm.clone().unwrap_or_else(|| self.llm.clone());
-        let namer = ConversationNamer::new(naming_llm);
+        let namer = ConversationNamer::with_config(naming_llm, self.config.namer.clone());
         let history = {
             let session = internals.session.read().await;
             session.history().to_vec()
@@ -217,43 +516,101 @@ impl StandardAgent {
     }
 
     /// Process a single user turn (may involve multiple LLM calls for tool use)
-    async fn process_turn(&self, internals: &mut AgentInternals, user_input: &str) -> Result<()> {
-        // Check if input contains attachment tags and process them
-        let user_message = if user_input.contains("<vibe-work-attachment>") {
-            tracing::info!("[StandardAgent] Processing attachments in user input");
-
-            // Get base directory from current working directory
-            let base_dir = std::env::current_dir()
-                .unwrap_or_else(|_| std::path::PathBuf::from("."))
-                .to_string_lossy()
-                .to_string();
-
-            // Process attachments
-            let attachment_blocks = process_attachments(user_input, &base_dir);
-
-            // Build message blocks: original text first, then attachments
-            let mut blocks = vec![ContentBlock::Text {
-                text: user_input.to_string(),
-                cache_control: None,
-            }];
-            blocks.extend(attachment_blocks);
+    async fn process_turn(
+        &self,
+        internals: &mut AgentInternals,
+        user_input: &str,
+        options: InputOptions,
+        record: &mut TurnRecord,
+    ) -> Result<()> {
+        // Get base directory from current working directory
+        let base_dir = std::env::current_dir()
+            .unwrap_or_else(|_| std::path::PathBuf::from("."))
+            .to_string_lossy()
+            .to_string();
+
+        // Preprocessed text (attachment tags by default), then attachments sent with it
+        let mut blocks = match &self.config.input_preprocessor {
+            Some(preprocessor) => preprocessor.process(user_input, &base_dir).await,
+            None => vec![ContentBlock::text(user_input)],
+        };
+        if !options.attachments.is_empty() {
+            tracing::info!("[StandardAgent] Loading {} attachment(s)", options.attachments.len());
+            blocks.extend(load_attachments(&options.attachments, &base_dir).await);
+        }
 
-            Message::user_with_blocks(blocks)
-        } else {
-            // No attachments, use simple text message
-            Message::user(user_input)
+        let user_message = match blocks.as_slice() {
+            // Nothing was added, use simple text message
+            [ContentBlock::Text { text, .. }] if text == user_input => Message::user(user_input),
+            _ => Message::user_with_blocks(blocks),
         };
 
         // Add user message to history
         internals.session.write().await.add_message(user_message)?;
 
+        if let Some(memory) = internals.context.get_resource::<ProjectMemory>() {
+            memory.refresh();
+        }
+
+        let tool_choice = options.tool_choice.or_else(|| self.config.tool_choice.clone());
+        let deadline = options.turn_timeout.or(self.config.turn_timeout).map(TurnDeadline::new);
+        let finished = self.run_llm_loop(internals, tool_choice, deadline, record).await?;
+
+        if let Some(ref schema) = self.config.output_schema {
+            if finished {
+                self.finish_structured_output(internals, schema, deadline, record).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Call the LLM and execute tools until the model stops calling them
+    ///
+    /// Returns true if the model ended its turn on its own, false if the loop
+    /// was cut short (interrupt, iteration limit, truncation, loop detection).
+    /// `tool_choice` applies to the first call only; later calls use Auto.
+    /// Past the `deadline`, the loop stops before the next call.
+    async fn run_llm_loop(
+        &self,
+        internals: &mut AgentInternals,
+        tool_choice: Option<ToolChoice>,
+        deadline: Option<TurnDeadline>,
+        record: &mut TurnRecord,
+    ) -> Result<bool> {
         // Get tool definitions
         let tool_definitions = self.config.tool_definitions();
+        let memory = internals.context.get_resource::<ProjectMemory>().and_then(|m| m.text());
 
         let mut iterations = 0;
+        let mut continuations = 0;
+        let mut stream_resumes = 0;
+        // Text not yet sent as TextComplete (carried across continuations)
+        let mut pending_text = String::new();
+        let mut loop_detector = self.config.loop_detection.map(LoopDetector::new);
+        let mut finished = false;
 
         // LLM loop - continues until no more tool calls
         loop {
+            // Shut down: every tool call has its result, so stop here
+            if internals.is_cancelled() {
+                tracing::info!("[StandardAgent] Shut down, ending turn");
+                break;
+            }
+
+            // Out of time: every tool call has its result, so the history can end here
+            if let Some(deadline) = deadline.filter(TurnDeadline::passed) {
+                let message = deadline.message();
+                tracing::warn!("[StandardAgent] {}", message);
+                internals
+                    .session
+                    .write()
+                    .await
+                    .add_message(Message::assistant(format!("[{}. The turn was stopped before finishing.]", message)))?;
+                internals.send_status(message);
+                break;
+            }
+
             iterations += 1;
             if iterations > self.config.max_tool_iterations {
                 tracing::warn!(
@@ -264,21 +621,33 @@ impl StandardAgent {
                 break;
             }
 
+            // Back to waiting on the LLM after executing tools
+            if iterations > 1 {
+                internals.set_processing().await;
+            }
+
             // Get messages from history
             let messages = {
                 let session = internals.session.read().await;
                 session.history().to_vec()
             };
 
+            // Checked on the stored history, before anything is added to it for this call
+            let messages = self
+                .fit_context_window(internals, iterations, &tool_definitions, memory.as_deref(), messages)
+                .await?;
+
             // IMPORTANT: Apply cache control BEFORE injections
             // This ensures we cache the stable message content (without dynamic injections)
             // The injections will be added AFTER the cache breakpoint, so they're sent but not cached
             // This allows the cache to match across turns even though injections are dynamic
             let (tools_with_cache, system_with_cache, mut messages_with_cache) =
-                self.apply_cache_control(tool_definitions.to_vec(), messages);
+                self.apply_cache_control(tool_definitions.to_vec(), memory.as_deref(), messages);
 
             // Apply context injections AFTER cache control
+            internals.context.current_iteration = iterations;
             messages_with_cache = self.config.injections.apply(internals, messages_with_cache);
+            let generation = self.call_generation(iterations, &messages_with_cache, system_with_cache.as_ref(), &tools_with_cache);
 
             // Update session metadata with current model/provider (may change via SwappableLlmProvider)
             {
@@ -334,13 +703,35 @@ impl StandardAgent {
 
             // Choose streaming or non-streaming based on config
             // Pass the already-cache-controlled data
+            let llm_span = telemetry::llm_span(
+                self.llm.provider_name(),
+                &self.llm.model(),
+                internals.session_id(),
+            );
+            let llm_started = Instant::now();
+            let mut stream_dropped = false;
+            let mut dropped_mid_tool_call = false;
+            // Text this response adds to `pending_text` is dropped if it's retried
+            let carried_len = pending_text.len();
+            let tool_choice = match &tool_choice {
+                Some(choice) if iterations == 1 => Some(choice.clone()),
+                Some(_) => Some(ToolChoice::auto()),
+                None => None,
+            };
             let (content_blocks, stop_reason) = if self.config.streaming_enabled {
                 self.call_llm_streaming_with_cache(
                     internals,
                     messages_with_cache,
                     tools_with_cache,
                     system_with_cache,
+                    tool_choice,
+                    generation,
+                    &mut pending_text,
+                    &mut stream_dropped,
+                    &mut dropped_mid_tool_call,
+                    &mut record.usage,
                 )
+                .instrument(llm_span.clone())
                 .await?
             } else {
                 self.call_llm_non_streaming_with_cache(
@@ -348,23 +739,86 @@ impl StandardAgent {
                     messages_with_cache,
                     tools_with_cache,
                     system_with_cache,
+                    tool_choice,
+                    generation,
+                    &mut pending_text,
+                    &mut record.usage,
                 )
+                .instrument(llm_span.clone())
                 .await?
             };
+            telemetry::record_duration(&llm_span, llm_started.elapsed());
+            metrics::record_llm_call(
+                internals.agent_type(),
+                self.llm.provider_name(),
+                &self.llm.model(),
+                llm_started.elapsed(),
+            );
 
             tracing::info!(
                 "[StandardAgent] LLM response: stop_reason={:?}",
                 stop_reason
             );
 
+            // A dropped stream is handled like a max tokens cut, with its own budget
+            let stop_reason = if stream_dropped { Some(StopReason::MaxTokens) } else { stop_reason };
+
+            // Cut off at max tokens: continue from a partial text response, or
+            // retry a response that ended mid tool call (its input is incomplete).
+            // A dropped stream has already discarded the partial call, so only
+            // the flag tells; its complete tool calls are run as usual.
+            let truncated = if stream_dropped {
+                stream_resumes < self.config.max_stream_resumes
+            } else {
+                matches!(stop_reason, Some(StopReason::MaxTokens))
+                    && continuations < self.config.max_continuations
+            };
+            let mid_tool_call = if stream_dropped {
+                dropped_mid_tool_call
+            } else {
+                matches!(content_blocks.last(), Some(ContentBlock::ToolUse { .. }))
+            };
+            if truncated && mid_tool_call {
+                pending_text.truncate(carried_len);
+                if stream_dropped {
+                    stream_resumes += 1;
+                } else {
+                    continuations += 1;
+                }
+                tracing::info!("[StandardAgent] Response truncated mid tool call, retrying");
+                internals.send_status("Response truncated mid tool call, retrying");
+                continue;
+            }
+            let continuing = truncated
+                && matches!(content_blocks.last(), Some(ContentBlock::Text { .. }))
+                && !content_blocks.iter().any(|b| matches!(b, ContentBlock::ToolUse { .. }));
+            if !continuing {
+                flush_pending_text(internals, &mut pending_text);
+            }
+
             // Process tool use blocks and execute tools
             let mut tool_results: Vec<(String, ToolResult)> = Vec::new();
+            // Most severe repeated failing call in this response: (tool, input, status)
+            let mut loop_hit: Option<(String, Value, LoopStatus)> = None;
+            // Whether an unanswered permission request ended the turn
+            let mut permission_timed_out = false;
 
             for (index, block) in content_blocks.iter().enumerate() {
                 if let ContentBlock::ToolUse { id, name, input } = block {
+                    if deadline.is_some_and(|d| d.passed()) {
+                        tracing::info!("[StandardAgent] Turn timed out, skipping the remaining tool calls");
+                        for remaining_block in content_blocks.iter().skip(index) {
+                            if let ContentBlock::ToolUse { id: remaining_id, .. } = remaining_block {
+                                tool_results.push((remaining_id.clone(), ToolResult::error(TURN_TIMED_OUT)));
+                            }
+                        }
+                        break;
+                    }
+
                     tracing::info!("[StandardAgent] Tool use: {} ({})", name, id);
 
                     // Execute tool with permission check (if tools configured)
+                    let tool_started = Instant::now();
                     let result = if let Some(ref tools) = self.config.tools {
                         let hooks = self.config.hooks.as_deref();
                         ToolExecutor::execute_with_permission(
@@ -374,7 +828,6 @@ impl StandardAgent {
                             name,
                             id,
                             input,
-                            self.config.hook_short_circuit,
                         )
                         .await
                     } else {
@@ -384,7 +837,38 @@ impl StandardAgent {
                         ))
                     };
 
-                    tool_results.push((id.clone(), result));
+                    record.tool_calls.push(ToolCallSummary::new(
+                        id,
+                        name,
+                        input,
+                        result.is_error,
+                        tool_started.elapsed(),
+                    ));
+
+                    if let Some(detector) = loop_detector.as_mut() {
+                        let status = detector.record(name, input, result.is_error);
+                        let current = loop_hit.as_ref().map_or(LoopStatus::Ok, |(_, _, s)| *s);
+                        if status.max(current) != current {
+                            loop_hit = Some((name.clone(), input.clone(), status));
+                        }
+                    }
+
+                    permission_timed_out = result.is_error
+                        && matches!(&result.content[..], [ToolResultData::Text(text)] if text == PERMISSION_TIMEOUT_ABORT);
+                    tool_results.push((id.clone(), self.redact_tool_result(result)));
+
+                    if permission_timed_out {
+                        // The remaining tools still need results, but the turn is over
+                        for remaining_block in content_blocks.iter().skip(index + 1) {
+                            if let ContentBlock::ToolUse { id: remaining_id, .. } = remaining_block {
+                                tool_results.push((
+                                    remaining_id.clone(),
+                                    ToolResult::error(PERMISSION_TIMEOUT_ABORT),
+                                ));
+                            }
+                        }
+                        break;
+                    }
 
                     // Check if user interrupted after tool execution (non-blocking check)
                     // Use tokio::select with immediate timeout to check without blocking
@@ -393,13 +877,22 @@ impl StandardAgent {
                         internals.receive()
                     );
 
-                    if let Ok(Some(InputMessage::Interrupt)) = interrupt_check.await {
+                    // A tool cancelled by the interrupt leaves the message queued
+                    let interrupted = match interrupt_check.await {
+                        Ok(Some(message @ InputMessage::AgentMessage { .. })) => {
+                            internals.defer(message);
+                            false
+                        }
+                        Ok(Some(InputMessage::Interrupt)) => true,
+                        _ => internals.is_cancelled(),
+                    };
+                    if interrupted || tool_results.last().is_some_and(|(_, result)| result.is_interrupted()) {
                         tracing::info!("[StandardAgent] Interrupt detected after tool execution");
 
                         // For all remaining tools that haven't executed, add "Interrupted" error
                         for remaining_block in content_blocks.iter().skip(index + 1) {
                             if let ContentBlock::ToolUse { id: remaining_id, .. } = remaining_block {
-                                tool_results.push((remaining_id.clone(), ToolResult::error("Interrupted")));
+                                tool_results.push((remaining_id.clone(), ToolResult::interrupted()));
                             }
                         }
 
@@ -421,32 +914,19 @@ impl StandardAgent {
                     internals,
                     &content_blocks,
                     stop_reason.clone(),
-                    self.config.hook_short_circuit,
-                );
+                ).await;
                 let _result = hooks.run(&mut ctx);
                 // Hook can access/modify history via internals if needed
                 // Primarily used for logging and monitoring
             }
 
             // Check if any tool was interrupted
-            let has_interrupt = tool_results.iter().any(|(_, result)| {
-                result.is_error && matches!(&result.content, ToolResultData::Text(text) if text == "Interrupted")
-            });
+            let has_interrupt = tool_results.iter().any(|(_, result)| result.is_interrupted());
 
             if has_interrupt {
                 tracing::info!("[StandardAgent] Tool execution interrupted, ending turn");
                 // Add the interrupt results to history
-                let tool_result_blocks: Vec<ContentBlock> = tool_results
-                    .into_iter()
-                    .flat_map(|(id, result)| {
-                        match result.content {
-                            ToolResultData::Text(text) => {
-                                vec![ContentBlock::tool_result(&id, &text, result.is_error)]
-                            }
-                            _ => vec![]
-                        }
-                    })
-                    .collect();
+                let tool_result_blocks = results_message_blocks(tool_results);
 
                 internals
                     .session
@@ -459,7 +939,7 @@ impl StandardAgent {
                     .session
                     .write()
                     .await
-                    .add_message(Message::assistant("<vibe-working-agent-system>User interrupted this message</vibe-working-agent-system>"))?;
+                    .add_message(Message::assistant(self.config.interrupt_marker.as_str()))?;
 
                 // Break out of the loop
                 break;
@@ -469,49 +949,25 @@ impl StandardAgent {
             if !tool_results.is_empty() {
                 // Add tool results as a message (WITHOUT cache_control)
                 // Cache control will be applied dynamically in apply_cache_control()
-                let tool_result_blocks: Vec<ContentBlock> = tool_results
-                    .into_iter()
-                    .flat_map(|(id, result)| {
-                        match result.content {
-                            ToolResultData::Text(text) => {
-                                vec![ContentBlock::tool_result(&id, &text, result.is_error)]
-                            }
-                            ToolResultData::Image { data, media_type } => {
-                                // Encode image data to base64
-                                use base64::Engine;
-                                let base64_data = base64::engine::general_purpose::STANDARD.encode(&data);
-
-                                vec![ContentBlock::ToolResult {
-                                    tool_use_id: id,
-                                    content: None,
-                                    is_error: if result.is_error { Some(true) } else { None },
-                                    cache_control: None,
-                                }, ContentBlock::image(base64_data, media_type)]
-                            }
-                            ToolResultData::Document {
-                                data,
-                                media_type,
-                                description,
-                            } => {
-                                // Encode document data to base64
-                                use base64::Engine;
-                                let base64_data = base64::engine::general_purpose::STANDARD.encode(&data);
-
-                                // For PDFs: two separate blocks as per API spec
-                                vec![
-                                    ContentBlock::tool_result(&id, &description, result.is_error),
-                                    ContentBlock::document(base64_data, media_type),
-                                ]
-                            }
-                        }
-                    })
-                    .collect();
+                let tool_result_blocks = results_message_blocks(tool_results);
+
+                let mut tool_result_message = Message::user_with_blocks(tool_result_blocks);
+                let aborted = match loop_hit {
+                    Some((tool, input, status)) => {
+                        self.handle_loop(internals, &mut tool_result_message, &tool, &input, status).await
+                    }
+                    None => false,
+                };
 
                 internals
                     .session
                     .write()
                     .await
-                    .add_message(Message::user_with_blocks(tool_result_blocks))?;
+                    .add_message(tool_result_message)?;
+
+                if aborted || permission_timed_out {
+                    break;
+                }
 
                 // Continue to next LLM call
                 continue;
@@ -521,12 +977,46 @@ impl StandardAgent {
             match stop_reason {
                 Some(StopReason::EndTurn) | Some(StopReason::StopSequence) | None => {
                     // Done with this turn
+                    finished = true;
                     break;
                 }
                 Some(StopReason::ToolUse) => {
                     // Shouldn't happen if tool_results is empty, but continue just in case
                     continue;
                 }
+                Some(StopReason::MaxTokens) if continuing && stream_dropped => {
+                    stream_resumes += 1;
+                    tracing::info!(
+                        "[StandardAgent] Stream dropped, resuming ({}/{})",
+                        stream_resumes,
+                        self.config.max_stream_resumes
+                    );
+                    internals.send_status("Stream dropped, resuming");
+                    internals
+                        .session
+                        .write()
+                        .await
+                        .add_message(Message::user(self.config.continue_prompt.clone()))?;
+                    continue;
+                }
+                Some(StopReason::MaxTokens) if continuing => {
+                    continuations += 1;
+                    tracing::info!(
+                        "[StandardAgent] Response truncated, continuing ({}/{})",
+                        continuations,
+                        self.config.max_continuations
+                    );
+                    internals
+                        .session
+                        .write()
+                        .await
+                        .add_message(Message::user(self.config.continue_prompt.clone()))?;
+                    continue;
+                }
+                Some(StopReason::MaxTokens) if stream_dropped => {
+                    internals.send_status("Stream dropped, partial response kept");
+                    break;
+                }
                 Some(StopReason::MaxTokens) => {
                     internals.send_status("Response truncated (max tokens)");
                     break;
@@ -542,64 +1032,396 @@ impl StandardAgent {
             }
         }
 
-        Ok(())
+        Ok(finished)
     }
 
-    /// Apply cache control to tools, system prompt, and messages (if enabled)
-    fn apply_cache_control(
+    /// Validate the final answer against the output schema
+    ///
+    /// Asks the model to fix an invalid answer up to `max_output_retries`
+    /// times. A valid result is sent as `StructuredResult` and stored in the
+    /// session metadata; if the model never gets it right, an error is sent.
+    async fn finish_structured_output(
         &self,
-        mut tool_definitions: Vec<crate::llm::ToolDefinition>,
-        mut messages: Vec<Message>,
-    ) -> (Vec<crate::llm::ToolDefinition>, Option<SystemPrompt>, Vec<Message>) {
-        if !self.config.enable_prompt_caching {
-            // Caching disabled - return system prompt as simple text
-            return (
-                tool_definitions,
-                Some(SystemPrompt::Text(self.config.system_prompt.clone())),
-                messages,
-            );
-        }
+        internals: &mut AgentInternals,
+        schema: &Value,
+        deadline: Option<TurnDeadline>,
+        record: &mut TurnRecord,
+    ) -> Result<()> {
+        let mut retries = 0;
+        loop {
+            let text = {
+                let session = internals.session.read().await;
+                last_assistant_text(session.history())
+            };
 
-        // IMPORTANT: Strip ALL existing cache_control from messages first
-        // This ensures we don't accidentally create duplicate cache breakpoints
-        for message in &mut messages {
-            if let crate::llm::MessageContent::Blocks(blocks) = &mut message.content {
-                for block in blocks {
-                    match block {
-                        ContentBlock::Text { cache_control, .. } => {
-                            *cache_control = None;
-                        }
-                        ContentBlock::ToolResult { cache_control, .. } => {
-                            *cache_control = None;
-                        }
-                        _ => {}
+            let reason = match parse_structured_result(&text, schema) {
+                Ok(value) => {
+                    tracing::info!("[StandardAgent] Structured result validated");
+                    {
+                        let mut session = internals.session.write().await;
+                        session.set_custom(STRUCTURED_RESULT_METADATA_KEY, value.clone());
+                        session.save_metadata()?;
                     }
+                    internals.send(OutputChunk::StructuredResult(value));
+                    return Ok(());
                 }
+                Err(reason) => reason,
+            };
+
+            if retries >= self.config.max_output_retries {
+                tracing::warn!("[StandardAgent] Structured result invalid, giving up: {}", reason);
+                internals.send_error(format!("Structured output failed validation: {}", reason));
+                return Ok(());
+            }
+            retries += 1;
+            tracing::info!(
+                "[StandardAgent] Structured result invalid, retrying ({}/{}): {}",
+                retries,
+                self.config.max_output_retries,
+                reason
+            );
+
+            internals.session.write().await.add_message(Message::user(format!(
+                "Your final answer is not valid. {}. Reply with only the corrected JSON, \
+                 with no other text.",
+                reason
+            )))?;
+            if !self.run_llm_loop(internals, None, deadline, record).await? {
+                return Ok(());
             }
         }
+    }
 
-        // 1. Add cache control to last tool definition (caches all tools)
-        if let Some(last_tool) = tool_definitions.last_mut() {
-            *last_tool = last_tool.clone().with_cache_control(CacheControl::ephemeral());
+    /// Mask secrets in a tool result before it is stored (if enabled)
+    fn redact_tool_result(&self, mut result: ToolResult) -> ToolResult {
+        let Some(ref redactor) = self.config.tool_result_redactor else {
+            return result;
+        };
+        for item in &mut result.content {
+            let text = match item {
+                ToolResultData::Text(text) => text,
+                ToolResultData::Document { description, .. } => description,
+                ToolResultData::Image { .. } => continue,
+            };
+            if let std::borrow::Cow::Owned(redacted) = redactor.redact(text) {
+                *text = redacted;
+            }
         }
+        result
+    }
 
-        // 2. Create system prompt with cache control
-        let system_prompt = Some(SystemPrompt::Blocks(vec![SystemBlock::new(
-            self.config.system_prompt.clone(),
-        )
-        .with_cache_control(CacheControl::ephemeral())]));
+    /// System prompt, followed by the available subagent types and the
+    /// output schema (if any)
+    fn system_prompt_text(&self) -> String {
+        let mut prompt = self.config.system_prompt.clone();
+        if let Some(section) = self.config.subagents.as_ref().and_then(|subagents| {
+            subagents.reload_if_changed();
+            subagents.prompt_section()
+        }) {
+            prompt = format!("{}\n\n{}", prompt, section);
+        }
+        if let Some(ref schema) = self.config.output_schema {
+            prompt = format!("{}\n\n{}", prompt, schema_instructions(schema));
+        }
+        prompt
+    }
 
-        // 3. Add cache control to the last content block of the LAST message
-        // This caches everything including the current user input, creating a stable growing cache
-        // Next request will have this content cached, allowing prefix matching
-        if let Some(last_message) = messages.last_mut() {
-            match &mut last_message.content {
-                crate::llm::MessageContent::Text(text) => {
-                    // Convert to blocks format with cache control on the text
-                    last_message.content = crate::llm::MessageContent::Blocks(vec![
-                        ContentBlock::Text {
-                            text: text.clone(),
+    /// React to a repeated failing tool call
+    ///
+    /// Fires the `LoopDetected` hook, then either appends a reminder to the
+    /// tool result message (warning) or reports that the turn is ending.
+    /// Returns true if the turn should end.
+    async fn handle_loop(
+        &self,
+        internals: &mut AgentInternals,
+        tool_result_message: &mut Message,
+        tool: &str,
+        input: &Value,
+        status: LoopStatus,
+    ) -> bool {
+        let (repeats, aborted) = match status {
+            LoopStatus::Ok => return false,
+            LoopStatus::Warn { repeats } => (repeats, false),
+            LoopStatus::Abort { repeats } => (repeats, true),
+        };
+        tracing::warn!(
+            "[StandardAgent] Loop detected: {} failed {} times with the same input",
+            tool,
+            repeats
+        );
+
+        if let Some(ref hooks) = self.config.hooks {
+            let mut ctx = HookContext::loop_detected(
+                internals,
+                tool,
+                input,
+                repeats,
+                aborted,
+            ).await;
+            hooks.run(&mut ctx);
+        }
+
+        if aborted {
+            internals.send_status(format!(
+                "Stopped: {} failed {} times with the same input",
+                tool, repeats
+            ));
+        } else {
+            let reminder = format!(
+                "You have called {} with the same input {} times and it failed every time. \
+                 You are stuck in a loop. Do not repeat this call; change your approach \
+                 (different arguments, a different tool, or ask the user).",
+                tool, repeats
+            );
+            inject_system_reminder(std::slice::from_mut(tool_result_message), &reminder);
+        }
+        aborted
+    }
+
+    /// Configured sampling parameters (None = provider defaults)
+    ///
+    /// A JSON response format without a schema takes the output schema.
+    fn generation_params(&self) -> Option<crate::llm::GenerationParams> {
+        let mut generation = self.config.generation.clone();
+        if let (Some(ResponseFormat::Json { schema: schema @ None }), Some(output_schema)) =
+            (&mut generation.response_format, &self.config.output_schema)
+        {
+            *schema = Some(output_schema.clone());
+        }
+        (!generation.is_empty()).then_some(generation)
+    }
+
+    /// Sampling parameters for a call, with max tokens cut to fit the window
+    ///
+    /// The reply gets the configured max tokens unless the estimated request
+    /// leaves less room in the context window, and never fewer than
+    /// `AgentConfig::min_output_tokens`.
+    fn call_generation(
+        &self,
+        iteration: usize,
+        messages: &[Message],
+        system: Option<&SystemPrompt>,
+        tools: &[crate::llm::ToolDefinition],
+    ) -> Option<crate::llm::GenerationParams> {
+        let mut generation = self.generation_params();
+        let (Some(window), Some(max_tokens)) = (self.context_window(), self.max_output_tokens()) else {
+            return generation;
+        };
+        let prompt = estimate_request_tokens(messages, system, tools);
+        let thinking = self.config.thinking_for(iteration).map(|thinking| thinking.budget_tokens);
+        let reply = output_budget(window, prompt, max_tokens, self.config.min_output_tokens, thinking);
+        if reply < max_tokens {
+            tracing::info!(
+                "[StandardAgent] Reducing max tokens from {} to {}: the request is about {} of {} context tokens",
+                max_tokens,
+                reply,
+                prompt,
+                window
+            );
+            generation.get_or_insert_with(Default::default).max_tokens = Some(reply);
+        }
+        generation
+    }
+
+    /// Context window requests are checked against (None = not checked)
+    fn context_window(&self) -> Option<u32> {
+        self.config.context_window.or(self.llm.capabilities().max_context_tokens)
+    }
+
+    /// Configured max tokens of a reply, before any cut for the window
+    fn max_output_tokens(&self) -> Option<u32> {
+        self.config.generation.max_tokens.or_else(|| self.llm.max_tokens())
+    }
+
+    /// Fit the history into the context window before a call
+    ///
+    /// Returns the messages to send: the whole history, or (per
+    /// `AgentConfig::context_overflow`) the history with its oldest turns
+    /// left out or summarized. Runs every iteration, so tool results added
+    /// during the turn are counted. Fails with
+    /// `FrameworkError::ContextOverflow` when the request can't fit.
+    async fn fit_context_window(
+        &self,
+        internals: &mut AgentInternals,
+        iteration: usize,
+        tool_definitions: &[crate::llm::ToolDefinition],
+        memory: Option<&str>,
+        mut history: Vec<Message>,
+    ) -> Result<Vec<Message>> {
+        let Some(window) = self.context_window() else {
+            return Ok(history);
+        };
+        let thinking = self.config.thinking_for(iteration).map(|thinking| thinking.budget_tokens);
+        // The reply's max tokens are cut to fit later, down to the floor
+        let reply = self.max_output_tokens().map(|max| max.min(self.config.min_output_tokens));
+        let budget = input_budget(window, reply, thinking);
+        let system = match memory {
+            Some(memory) => format!("{}\n\n{}", self.system_prompt_text(), memory),
+            None => self.system_prompt_text(),
+        };
+        let fixed = estimate_request_tokens(&[], Some(&SystemPrompt::Text(system)), tool_definitions);
+        // The smallest request that could be sent: the current turn alone
+        let current_turn = user_turn_start(&history, history.len().saturating_sub(1));
+        let overflow = |prefix: u64| FrameworkError::ContextOverflow {
+            estimated: fixed + prefix + tokens_from(&history, current_turn),
+            limit: budget,
+        };
+
+        match self.config.context_overflow {
+            ContextOverflow::Fail => {
+                let estimated = fixed + tokens_from(&history, 0);
+                if estimated > budget {
+                    return Err(FrameworkError::ContextOverflow { estimated, limit: budget }.into());
+                }
+                Ok(history)
+            }
+            ContextOverflow::DropOldest => {
+                let previous = internals
+                    .context
+                    .get_resource::<DroppedMessages>()
+                    .map(|dropped| dropped.0)
+                    .filter(|&start| is_cut_point(&history, start))
+                    .unwrap_or(0);
+                let start = if fixed + tokens_from(&history, previous) <= budget {
+                    previous
+                } else {
+                    let start = choose_cut(&history, previous, fixed, 0, budget).ok_or_else(|| overflow(0))?;
+                    tracing::warn!(
+                        "[StandardAgent] Request over the context window ({} tokens available), leaving out the first {} messages",
+                        budget,
+                        start
+                    );
+                    internals.send_status(format!("Context window full, left out the {} oldest messages", start));
+                    internals.context.insert_resource(DroppedMessages(start));
+                    start
+                };
+                Ok(history.split_off(start))
+            }
+            ContextOverflow::Compact => {
+                let stored = {
+                    let session = internals.session.read().await;
+                    session
+                        .get_custom(CONTEXT_SUMMARY_METADATA_KEY)
+                        .and_then(|value| serde_json::from_value::<SessionSummary>(value.clone()).ok())
+                        .filter(|summary| summary.message_count > 0 && is_cut_point(&history, summary.message_count))
+                };
+                let prefix = |text: &str| summary_messages(text).iter().map(estimate_message_tokens).sum::<u64>();
+                let previous = stored.as_ref().map(|summary| summary.message_count).unwrap_or(0);
+                let stored_prefix = stored.as_ref().map(|summary| prefix(&summary.text)).unwrap_or(0);
+                if fixed + stored_prefix + tokens_from(&history, previous) <= budget {
+                    let mut messages = match &stored {
+                        Some(summary) => summary_messages(&summary.text).to_vec(),
+                        None => Vec::new(),
+                    };
+                    messages.extend(history.split_off(previous));
+                    return Ok(messages);
+                }
+
+                let summary_size = prefix("") + u64::from(SUMMARY_MAX_TOKENS);
+                let start = choose_cut(&history, previous, fixed, summary_size, budget)
+                    .ok_or_else(|| overflow(summary_size))?;
+                tracing::warn!(
+                    "[StandardAgent] Request over the context window ({} tokens available), compacting the first {} messages",
+                    budget,
+                    start
+                );
+                internals.send_status("Context window full, compacting the conversation");
+
+                let summarizer = ConversationSummarizer::new(self.llm.clone()).with_max_tokens(SUMMARY_MAX_TOKENS);
+                let text = match &stored {
+                    Some(summary) => {
+                        summarizer
+                            .summarize_incremental(&summary.text, &history[summary.message_count..start])
+                            .await
+                    }
+                    None => summarizer.summarize(&history[..start], SummaryStyle::Bullet, SUMMARY_MAX_TOKENS).await,
+                }
+                .map_err(|e| anyhow::anyhow!("Compacting the conversation failed: {}", e))?;
+
+                let summary = SessionSummary {
+                    text,
+                    message_count: start,
+                    updated_at: chrono::Utc::now(),
+                };
+                {
+                    let mut session = internals.session.write().await;
+                    session.set_custom(CONTEXT_SUMMARY_METADATA_KEY, serde_json::to_value(&summary)?);
+                    session.save_metadata()?;
+                }
+                let mut messages = summary_messages(&summary.text).to_vec();
+                messages.extend(history.split_off(start));
+                Ok(messages)
+            }
+        }
+    }
+
+    /// Apply cache control to tools, system prompt, and messages (if enabled)
+    ///
+    /// `memory` is the project memory text, sent after the system prompt.
+    fn apply_cache_control(
+        &self,
+        mut tool_definitions: Vec<crate::llm::ToolDefinition>,
+        memory: Option<&str>,
+        mut messages: Vec<Message>,
+    ) -> (Vec<crate::llm::ToolDefinition>, Option<SystemPrompt>, Vec<Message>) {
+        // Checked per call: a swappable provider may have moved to a model
+        // without cache control since spawn
+        if !self.config.enable_prompt_caching || !self.llm.capabilities().supports_caching {
+            // Caching disabled - return system prompt as simple text
+            let prompt = match memory {
+                Some(memory) => format!("{}\n\n{}", self.system_prompt_text(), memory),
+                None => self.system_prompt_text(),
+            };
+            return (tool_definitions, Some(SystemPrompt::Text(prompt)), messages);
+        }
+
+        // IMPORTANT: Strip ALL existing cache_control from messages first
+        // This ensures we don't accidentally create duplicate cache breakpoints
+        for message in &mut messages {
+            if let crate::llm::MessageContent::Blocks(blocks) = &mut message.content {
+                for block in blocks {
+                    match block {
+                        ContentBlock::Text { cache_control, .. } => {
+                            *cache_control = None;
+                        }
+                        ContentBlock::ToolResult { cache_control, .. } => {
+                            *cache_control = None;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        // 1. Add cache control to last tool definition (caches all tools)
+        if let Some(last_tool) = tool_definitions.last_mut() {
+            *last_tool = last_tool.clone().with_cache_control(CacheControl::ephemeral());
+        }
+
+        // 2. Create system prompt with cache control. Project memory gets a
+        // block of its own after it; the breakpoint on the last block caches
+        // both without using another one
+        let mut system_blocks = vec![SystemBlock::new(self.system_prompt_text())];
+        if let Some(memory) = memory {
+            system_blocks.push(SystemBlock::new(memory));
+        }
+        if let Some(last) = system_blocks.pop() {
+            system_blocks.push(last.with_cache_control(CacheControl::ephemeral()));
+        }
+        let system_prompt = Some(SystemPrompt::Blocks(system_blocks));
+
+        // 3. Add cache control to the last content block of the LAST message
+        // This caches everything including the current user input, creating a stable growing cache
+        // Next request will have this content cached, allowing prefix matching
+        if let Some(last_message) = messages.last_mut() {
+            match &mut last_message.content {
+                crate::llm::MessageContent::Text(text) => {
+                    // Convert to blocks format with cache control on the text
+                    last_message.content = crate::llm::MessageContent::Blocks(vec![
+                        ContentBlock::Text {
+                            text: text.clone(),
                             cache_control: Some(CacheControl::ephemeral()),
+                            citations: None,
                         },
                     ]);
                 }
@@ -616,12 +1438,22 @@ impl StandardAgent {
     }
 
     /// Call LLM without streaming (with pre-applied cache control)
+    ///
+    /// `pending_text` is text whose `TextComplete` hasn't been sent yet. Text
+    /// carried over from a truncated response is joined with this response's
+    /// first text block, and a trailing text block is left in `pending_text`
+    /// for the caller to complete (or carry into a continuation).
+    #[allow(clippy::too_many_arguments)]
     async fn call_llm_non_streaming_with_cache(
         &self,
         internals: &mut AgentInternals,
         messages: Vec<Message>,
         tools: Vec<crate::llm::ToolDefinition>,
         system: Option<SystemPrompt>,
+        tool_choice: Option<ToolChoice>,
+        generation: Option<crate::llm::GenerationParams>,
+        pending_text: &mut String,
+        usage: &mut Usage,
     ) -> Result<(Vec<ContentBlock>, Option<StopReason>)> {
         // Get session ID
         let session_id = {
@@ -629,17 +1461,22 @@ impl StandardAgent {
             session.session_id().to_string()
         };
 
-        let response = self
-            .llm
-            .send_with_tools_and_system(
-                messages,
-                system,
-                tools,
-                None,
-                self.config.thinking.clone(),
-                Some(&session_id),
-            )
-            .await?;
+        let call = self.llm.send_with_tools_and_system(
+            messages,
+            system,
+            tools,
+            tool_choice,
+            self.config.thinking_for(internals.context.current_iteration),
+            generation,
+            Some(&session_id),
+        );
+        let response = unless_cancelled(internals.cancellation_token(), call).await?;
+
+        usage.add(&response.usage);
+        let span = tracing::Span::current();
+        telemetry::record_llm_usage(&span, &response.usage);
+        metrics::record_llm_usage(internals.agent_type(), self.llm.provider_name(), &self.llm.model(), &response.usage);
+        telemetry::record_llm_response(&span, Some(&response.model), response.stop_reason.as_ref());
 
         // Log API response if debugger is enabled
         if let Some(debugger) = internals.context.get_resource::<Debugger>() {
@@ -651,16 +1488,27 @@ impl StandardAgent {
         }
 
         // Send text and thinking content to output
+        let mut carried = !pending_text.is_empty();
         for block in &response.content {
+            if !(carried && matches!(block, ContentBlock::Text { .. })) {
+                flush_pending_text(internals, pending_text);
+            }
+            carried = false;
             match block {
-                ContentBlock::Text { text, .. } => {
+                ContentBlock::Text { text, citations, .. } => {
                     internals.send_text(text);
-                    internals.send_text_complete(text);
+                    pending_text.push_str(text);
+                    if let Some(citations) = citations {
+                        internals.send_citations(text.clone(), citations.clone());
+                    }
                 }
                 ContentBlock::Thinking { thinking, .. } => {
                     internals.send_thinking(thinking);
                     internals.send_thinking_complete(thinking);
                 }
+                ContentBlock::WebSearchToolResult { tool_use_id, content } => {
+                    send_web_search(internals, &response.content, tool_use_id, content);
+                }
                 _ => {}
             }
         }
@@ -669,12 +1517,24 @@ impl StandardAgent {
     }
 
     /// Call LLM with streaming (with pre-applied cache control) - sends deltas in real-time
+    ///
+    /// Handles `pending_text` like [`Self::call_llm_non_streaming_with_cache`].
+    /// If the stream fails after text or a complete tool call arrived, the
+    /// content so far is returned and `dropped` is set instead of failing;
+    /// `mid_tool_call` is set too if a tool call was cut off (and discarded).
+    #[allow(clippy::too_many_arguments)]
     async fn call_llm_streaming_with_cache(
         &self,
         internals: &mut AgentInternals,
         messages: Vec<Message>,
         tools: Vec<crate::llm::ToolDefinition>,
         system: Option<SystemPrompt>,
+        tool_choice: Option<ToolChoice>,
+        generation: Option<crate::llm::GenerationParams>,
+        pending_text: &mut String,
+        dropped: &mut bool,
+        mid_tool_call: &mut bool,
+        turn_usage: &mut Usage,
     ) -> Result<(Vec<ContentBlock>, Option<StopReason>)> {
         // Get session ID
         let session_id = {
@@ -682,38 +1542,36 @@ impl StandardAgent {
             session.session_id().to_string()
         };
 
-        let mut stream = self
-            .llm
-            .stream_with_tools_and_system(
-                messages,
-                system,
-                tools,
-                None,
-                self.config.thinking.clone(),
-                Some(&session_id),
-            )
-            .await?;
-
-        // Track content blocks as they're built
-        let mut content_blocks: Vec<ContentBlock> = Vec::new();
-        let mut current_block_index: Option<usize> = None;
+        let call = self.llm.stream_with_tools_and_system(
+            messages,
+            system,
+            tools,
+            tool_choice,
+            self.config.thinking_for(internals.context.current_iteration),
+            generation,
+            Some(&session_id),
+        );
+        let mut stream = unless_cancelled(internals.cancellation_token(), call).await?;
+
+        // Content blocks by index, as they're built
+        let mut blocks = BlockAccumulator::new();
         let mut stop_reason: Option<StopReason> = None;
+        // How the stream ended early, if it did
+        let mut interrupted = false;
+        let mut salvaged = false;
 
         // Track message metadata for logging
         let mut message_id: Option<String> = None;
         let mut model: Option<String> = None;
-        let mut initial_usage: Option<crate::llm::Usage> = None;
+        // Usage from MessageStart, updated by the final MessageDelta
+        let mut stream_usage: Option<crate::llm::Usage> = None;
         let mut output_tokens: u32 = 0;
-
-        // Accumulators for building content blocks
-        let mut text_accum = String::new();
-        let mut thinking_accum = String::new();
-        let mut thinking_signature = String::new();
-        let mut tool_input_accum = String::new();
-        let mut current_tool_id = String::new();
-        let mut current_tool_name = String::new();
+        let mut carried = !pending_text.is_empty();
 
         loop {
+            // Let blocking subscribers catch up before reading further
+            internals.wait_for_subscribers().await;
+
             tokio::select! {
                 event_result = stream.next() => {
                     let event_result = match event_result {
@@ -729,85 +1587,71 @@ impl StandardAgent {
                                     // Capture message metadata for logging
                                     message_id = Some(msg_start.message.id.clone());
                                     model = Some(msg_start.message.model.clone());
-                                    initial_usage = Some(msg_start.message.usage.clone());
+                                    stream_usage = Some(msg_start.message.usage.clone());
                                 }
 
                         StreamEvent::ContentBlockStart(block_start) => {
-                            current_block_index = Some(block_start.index);
+                            // Complete the previous text block, unless this text
+                            // continues a truncated response
+                            let is_text = matches!(block_start.content_block, ContentBlockStart::Text { .. });
+                            if !(carried && is_text) {
+                                flush_pending_text(internals, pending_text);
+                            }
+                            carried = false;
 
                             match &block_start.content_block {
-                                ContentBlockStart::Text { .. } => {
-                                    text_accum.clear();
-                                }
-                                ContentBlockStart::Thinking { .. } => {
-                                    thinking_accum.clear();
-                                    thinking_signature.clear();
+                                ContentBlockStart::Text { text } if !text.is_empty() => {
+                                    internals.send_text(text);
                                 }
                                 ContentBlockStart::ToolUse { id, name, .. } => {
-                                    tool_input_accum.clear();
-                                    current_tool_id = id.clone();
-                                    current_tool_name = name.clone();
+                                    // Announce the call now; the final input follows
+                                    // in ToolStart before the tool runs
+                                    internals.send_tool_stream_start(id, name);
                                 }
+                                _ => {}
                             }
+                            blocks.start(block_start.index, &block_start.content_block);
                         }
 
                         StreamEvent::ContentBlockDelta(delta) => {
-                            match &delta.delta {
-                                ContentDelta::TextDelta { text } => {
-                                    text_accum.push_str(text);
-                                    // Stream text to output immediately
-                                    internals.send_text(text);
-                                }
-                                ContentDelta::ThinkingDelta { thinking } => {
-                                    thinking_accum.push_str(thinking);
-                                    // Stream thinking to output immediately
-                                    internals.send_thinking(thinking);
-                                }
-                                ContentDelta::SignatureDelta { signature } => {
-                                    thinking_signature.push_str(signature);
-                                }
-                                ContentDelta::InputJsonDelta { partial_json } => {
-                                    tool_input_accum.push_str(partial_json);
+                            if blocks.delta(delta.index, &delta.delta) {
+                                match &delta.delta {
+                                    ContentDelta::TextDelta { text } => {
+                                        // Stream text to output immediately
+                                        internals.send_text(text);
+                                    }
+                                    ContentDelta::ThinkingDelta { thinking } => {
+                                        // Stream thinking to output immediately
+                                        internals.send_thinking(thinking);
+                                    }
+                                    ContentDelta::SignatureDelta { .. } | ContentDelta::CitationsDelta { .. } => {}
+                                    ContentDelta::InputJsonDelta { partial_json } => {
+                                        // Forwarded as-is: partial JSON is only parsed at the stop
+                                        if let Some(id) = blocks.tool_use_id(delta.index) {
+                                            internals.send_tool_input_delta(id, partial_json);
+                                        }
+                                    }
                                 }
                             }
                         }
 
                         StreamEvent::ContentBlockStop(block_stop) => {
-                            if current_block_index == Some(block_stop.index) {
-                                // Finalize the content block
-                                if !text_accum.is_empty() {
-                                    // Send text complete signal to CLI
-                                    internals.send_text_complete(&text_accum);
-                                    content_blocks.push(ContentBlock::Text {
-                                        text: text_accum.clone(),
-                                        cache_control: None,
-                                    });
-                                    text_accum.clear();
-                                } else if !thinking_accum.is_empty() {
+                            match blocks.stop(block_stop.index) {
+                                Some(ContentBlock::Text { text, citations, .. }) => {
+                                    // TextComplete is sent once we know what follows
+                                    pending_text.push_str(&text);
+                                    if let Some(citations) = citations {
+                                        internals.send_citations(text, citations);
+                                    }
+                                }
+                                Some(ContentBlock::Thinking { thinking, .. }) => {
                                     // Send thinking complete signal to CLI
-                                    internals.send_thinking_complete(&thinking_accum);
-                                    content_blocks.push(ContentBlock::Thinking {
-                                        thinking: thinking_accum.clone(),
-                                        signature: thinking_signature.clone(),
-                                    });
-                                    thinking_accum.clear();
-                                    thinking_signature.clear();
-                                } else if !tool_input_accum.is_empty()
-                                    || !current_tool_name.is_empty()
-                                {
-                                    // Parse accumulated JSON
-                                    let input: Value =
-                                        serde_json::from_str(&tool_input_accum).unwrap_or_default();
-                                    content_blocks.push(ContentBlock::ToolUse {
-                                        id: current_tool_id.clone(),
-                                        name: current_tool_name.clone(),
-                                        input,
-                                    });
-                                    tool_input_accum.clear();
-                                    current_tool_id.clear();
-                                    current_tool_name.clear();
+                                    internals.send_thinking_complete(&thinking);
+                                }
+                                Some(ContentBlock::WebSearchToolResult { tool_use_id, content }) => {
+                                    send_web_search(internals, blocks.blocks(), &tool_use_id, &content);
                                 }
-                                current_block_index = None;
+                                _ => {}
                             }
                         }
 
@@ -815,6 +1659,9 @@ impl StandardAgent {
                             stop_reason = msg_delta.delta.stop_reason;
                             // Capture final output tokens
                             output_tokens = msg_delta.usage.output_tokens;
+                            if let Some(usage) = &mut stream_usage {
+                                msg_delta.usage.apply_to(usage);
+                            }
                         }
 
                         StreamEvent::MessageStop => {
@@ -839,42 +1686,72 @@ impl StandardAgent {
                     }
                 }
                 Err(e) => {
-                    tracing::error!("[StandardAgent] Stream error: {}", e);
-                    return Err(e);
+                    let salvageable = blocks.has_open_text()
+                        || blocks
+                            .blocks()
+                            .any(|block| matches!(block, ContentBlock::Text { .. } | ContentBlock::ToolUse { .. }));
+                    if !salvageable {
+                        tracing::error!("[StandardAgent] Stream error: {}", e);
+                        flush_pending_text(internals, pending_text);
+                        return Err(e);
+                    }
+
+                    // Keep what arrived; partial thinking and tool input are discarded
+                    tracing::warn!("[StandardAgent] Stream dropped mid-response: {}", e);
+                    *mid_tool_call = blocks.has_open_tool_use();
+                    pending_text.push_str(&blocks.close_open_text());
+                    salvaged = true;
+                    break;
                 }
             }
                 }
 
-                // Check for interrupt messages
+                // Check for interrupt messages (shutdown stops the stream the same way)
                 msg = internals.receive() => {
-                    if let Some(InputMessage::Interrupt) = msg {
+                    if let Some(message @ InputMessage::AgentMessage { .. }) = msg {
+                        internals.defer(message);
+                    } else if let Some(InputMessage::Interrupt | InputMessage::Shutdown) | None = msg {
                         tracing::info!("[StandardAgent] Interrupt received");
+                        flush_pending_text(internals, pending_text);
 
-                        // Finalize any in-progress text content block
-                        if !text_accum.is_empty() {
-                            content_blocks.push(ContentBlock::Text {
-                                text: text_accum.clone(),
-                                cache_control: None,
-                            });
-                        }
-                        // Discard incomplete thinking blocks (signature may be incomplete)
-                        // Discard partial tool calls (don't add them to content_blocks)
-
-                        // Remove all ToolUse blocks from content_blocks (discard all tool calls)
-                        content_blocks.retain(|block| !matches!(block, ContentBlock::ToolUse { .. }));
-
-                        // Append interrupt notification to the assistant's content blocks
-                        content_blocks.push(ContentBlock::Text {
-                            text: "<vibe-working-agent-system>User interrupted this message</vibe-working-agent-system>".to_string(),
-                            cache_control: None,
-                        });
-
+                        // Keep in-progress text; incomplete thinking (its signature
+                        // may be incomplete) and partial tool calls are discarded
+                        blocks.close_open_text();
+                        interrupted = true;
                         break;
                     }
                 }
             }
         }
 
+        let mut content_blocks = blocks.into_blocks();
+        if salvaged {
+            retain_answered_server_tools(&mut content_blocks);
+            *dropped = true;
+        }
+        if interrupted {
+            // Discard all tool calls
+            content_blocks.retain(|block| !matches!(block, ContentBlock::ToolUse { .. }));
+            retain_answered_server_tools(&mut content_blocks);
+
+            // Append interrupt notification to the assistant's content blocks
+            content_blocks.push(ContentBlock::Text {
+                text: self.config.interrupt_marker.clone(),
+                cache_control: None,
+                citations: None,
+            });
+        }
+
+        let span = tracing::Span::current();
+        if let Some(ref usage) = stream_usage {
+            let mut usage = usage.clone();
+            usage.output_tokens = output_tokens;
+            turn_usage.add(&usage);
+            telemetry::record_llm_usage(&span, &usage);
+            metrics::record_llm_usage(internals.agent_type(), self.llm.provider_name(), &self.llm.model(), &usage);
+        }
+        telemetry::record_llm_response(&span, model.as_deref(), stop_reason.as_ref());
+
         // Log the assembled response if debugger is enabled
         if let Some(debugger) = internals.context.get_resource::<Debugger>() {
             // Construct a response object similar to MessageResponse for logging
@@ -888,7 +1765,7 @@ impl StandardAgent {
             });
 
             // Add usage information if we captured it
-            if let Some(usage) = initial_usage {
+            if let Some(usage) = stream_usage {
                 let usage_obj = serde_json::json!({
                     "input_tokens": usage.input_tokens,
                     "output_tokens": output_tokens,
@@ -906,3 +1783,2141 @@ impl StandardAgent {
         Ok((content_blocks, stop_reason))
     }
 }
+
+impl SpawnableAgent for StandardAgent {
+    fn run(self, internals: AgentInternals) -> AgentFuture {
+        Box::pin(StandardAgent::run(self, internals))
+    }
+}
+
+/// The user message for a message from another agent
+fn format_agent_message(from_session_id: &str, content: &str) -> String {
+    format!(
+        "<agent-message from=\"{}\">\n{}\n</agent-message>",
+        from_session_id.replace('"', "'"),
+        content
+    )
+}
+
+/// Whether a message is a prompt that starts a turn (rather than tool results)
+pub(super) fn is_user_prompt(message: &Message) -> bool {
+    message.role == "user"
+        && !message.blocks().is_some_and(|blocks| {
+            blocks
+                .iter()
+                .any(|block| matches!(block, ContentBlock::ToolResult { .. }))
+        })
+}
+
+/// Index of the prompt that starts the turn containing `index`
+fn user_turn_start(history: &[Message], index: usize) -> usize {
+    (0..=index)
+        .rev()
+        .find(|&i| is_user_prompt(&history[i]))
+        .unwrap_or(0)
+}
+
+/// (id, name, input) of every tool call in `messages`
+fn tool_calls(messages: &[Message]) -> Vec<(String, String, Value)> {
+    messages
+        .iter()
+        .filter_map(Message::blocks)
+        .flatten()
+        .filter_map(|block| match block {
+            ContentBlock::ToolUse { id, name, input } => {
+                Some((id.clone(), name.clone(), input.clone()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The list set by the last TodoWrite call in `history`, and its turn
+fn last_todo_list(history: &[Message]) -> Option<(Vec<TodoItem>, usize)> {
+    let mut turn = 0usize;
+    let mut last = None;
+    for message in history {
+        if is_user_prompt(message) {
+            turn += 1;
+        }
+        for (_, name, input) in tool_calls(std::slice::from_ref(message)) {
+            if name == "TodoWrite" {
+                if let Some(items) = todos_from_input(&input) {
+                    // Turns are counted from 0
+                    last = Some((items, turn.saturating_sub(1)));
+                }
+            }
+        }
+    }
+    last
+}
+
+/// Text of the most recent assistant message
+fn last_assistant_text(history: &[Message]) -> String {
+    let Some(message) = history.iter().rev().find(|m| m.role == "assistant") else {
+        return String::new();
+    };
+    match &message.content {
+        MessageContent::Text(text) => text.clone(),
+        MessageContent::Blocks(blocks) => blocks
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(""),
+    }
+}
+
+/// Send a web search result as `OutputChunk::WebSearch`, with the query
+/// from its `server_tool_use` block earlier in the response
+fn send_web_search<'a>(
+    internals: &AgentInternals,
+    blocks: impl IntoIterator<Item = &'a ContentBlock>,
+    tool_use_id: &str,
+    content: &WebSearchToolResultContent,
+) {
+    let query = blocks
+        .into_iter()
+        .find_map(|block| match block {
+            ContentBlock::ServerToolUse { id, input, .. } if id == tool_use_id => {
+                input.get("query").and_then(Value::as_str)
+            }
+            _ => None,
+        })
+        .unwrap_or_default();
+    internals.send_web_search(tool_use_id, query, content.clone());
+}
+
+/// Drop server tool calls without a result (a call is only valid next to it)
+fn retain_answered_server_tools(content_blocks: &mut Vec<ContentBlock>) {
+    let answered: Vec<String> = content_blocks
+        .iter()
+        .filter_map(|block| match block {
+            ContentBlock::WebSearchToolResult { tool_use_id, .. } => Some(tool_use_id.clone()),
+            _ => None,
+        })
+        .collect();
+    content_blocks.retain(|block| match block {
+        ContentBlock::ServerToolUse { id, .. } => answered.contains(id),
+        _ => true,
+    });
+}
+
+/// The blocks for one tool result
+///
+/// The blocks of a tool results message: every `tool_result` in call
+/// order, then the images and documents of all of them. Providers expect
+/// the tool results to come first, so media never sit between two of them.
+fn results_message_blocks(results: Vec<(String, ToolResult)>) -> Vec<ContentBlock> {
+    let mut blocks = Vec::new();
+    let mut media = Vec::new();
+    for (id, result) in results {
+        let mut call_blocks = result_blocks(id, result).into_iter();
+        blocks.extend(call_blocks.next());
+        media.extend(call_blocks);
+    }
+    blocks.extend(media);
+    blocks
+}
+
+/// A `tool_result` block with the text items and document descriptions,
+/// followed by the images and documents in order. `ContentBlock::ToolResult`
+/// holds text only, so the media go after it.
+fn result_blocks(id: String, result: ToolResult) -> Vec<ContentBlock> {
+    use base64::Engine;
+
+    let mut texts = Vec::new();
+    let mut media = Vec::new();
+    for item in result.content {
+        match item {
+            ToolResultData::Text(text) => texts.push(text),
+            ToolResultData::Image { data, media_type } => {
+                let base64_data = base64::engine::general_purpose::STANDARD.encode(&data);
+                media.push(ContentBlock::image(base64_data, media_type));
+            }
+            ToolResultData::Document { data, media_type, description } => {
+                // For PDFs: two separate blocks as per API spec
+                let base64_data = base64::engine::general_purpose::STANDARD.encode(&data);
+                texts.push(description);
+                media.push(ContentBlock::document(base64_data, media_type));
+            }
+        }
+    }
+
+    let mut blocks = vec![ContentBlock::ToolResult {
+        tool_use_id: id,
+        content: (!texts.is_empty()).then(|| texts.join("\n\n")),
+        is_error: if result.is_error { Some(true) } else { None },
+        cache_control: None,
+    }];
+    blocks.extend(media);
+    blocks
+}
+
+/// Await an LLM call, giving up with `FrameworkError::Shutdown` on shutdown
+async fn unless_cancelled<T>(cancel: &CancellationToken, call: impl Future<Output = Result<T>>) -> Result<T> {
+    tokio::select! {
+        biased;
+        _ = cancel.cancelled() => Err(FrameworkError::Shutdown.into()),
+        result = call => result,
+    }
+}
+
+/// Send any pending text as a `TextComplete`
+fn flush_pending_text(internals: &AgentInternals, pending_text: &mut String) {
+    if !pending_text.is_empty() {
+        internals.send_text_complete(std::mem::take(pending_text));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use crate::cli::{run_print_mode_with_writer, OutputFormat, PrintModeOptions};
+    use crate::helpers::Attachment;
+    use crate::llm::MockLlmProvider;
+    use crate::agent::ThinkingPolicy;
+    use crate::permissions::TimeoutDecision;
+
+    use super::super::context_window::SUMMARY_PREFIX;
+    use super::super::executor::PERMISSION_TIMED_OUT;
+    use crate::runtime::AgentRuntime;
+    use crate::session::{AgentSession, SessionStorage};
+
+    async fn spawn_agent(
+        runtime: &AgentRuntime,
+        dir: &tempfile::TempDir,
+        config: AgentConfig,
+        llm: &MockLlmProvider,
+    ) -> crate::runtime::AgentHandle {
+        let storage = SessionStorage::with_dir(dir.path());
+        let session =
+            AgentSession::new_with_storage("loop-test", "test", "Test", "Loop test", storage)
+                .unwrap();
+        let agent = StandardAgent::new(config.with_auto_name(false), Arc::new(llm.clone()));
+        runtime
+            .spawn(session, move |internals| agent.run(internals))
+            .await
+    }
+
+    /// Run one print-mode turn and return the output chunks
+    async fn run_turn(config: AgentConfig, llm: &MockLlmProvider) -> Vec<Value> {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let handle = spawn_agent(&runtime, &dir, config, llm).await;
+
+        let mut out = Vec::new();
+        let options = PrintModeOptions::new().with_output_format(OutputFormat::Json);
+        run_print_mode_with_writer(&handle, "Write it", options, &mut out)
+            .await
+            .unwrap();
+        runtime.shutdown_all().await;
+
+        let json: Value = serde_json::from_slice(&out).unwrap();
+        json["chunks"].as_array().unwrap().clone()
+    }
+
+    /// Values of the chunks of one kind (e.g. "TextComplete")
+    fn chunks_of(chunks: &[Value], kind: &str) -> Vec<String> {
+        chunks
+            .iter()
+            .filter_map(|c| c[kind].as_str().map(str::to_string))
+            .collect()
+    }
+
+    fn truncated(content: Vec<ContentBlock>) -> crate::llm::MessageResponse {
+        MockLlmProvider::response(content, StopReason::MaxTokens)
+    }
+
+    #[tokio::test]
+    async fn test_auto_continue_stitches_text() {
+        for streaming in [false, true] {
+            let llm = MockLlmProvider::new()
+                .with_response(truncated(vec![ContentBlock::text("fn main() {")]))
+                .with_text(" println!(\"hi\"); }");
+            let config = AgentConfig::new("Test")
+                .with_streaming(streaming)
+                .with_auto_continue(2)
+                .with_continue_prompt("Go on");
+
+            let completes = chunks_of(&run_turn(config, &llm).await, "TextComplete");
+            assert_eq!(completes, vec!["fn main() { println!(\"hi\"); }".to_string()]);
+
+            let requests = llm.requests();
+            assert_eq!(requests.len(), 2);
+            let messages = &requests[1].messages;
+            assert_eq!(messages.len(), 3);
+            assert_eq!(messages[1].role, "assistant");
+            // Prompt caching may have turned the text into blocks
+            assert_eq!(messages[2].role, "user");
+            assert!(serde_json::to_string(&messages[2]).unwrap().contains("Go on"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_web_search_blocks_are_stored_and_surfaced() {
+        let search_result: WebSearchToolResultContent = serde_json::from_value(serde_json::json!([{
+            "type": "web_search_result",
+            "url": "https://docs.rs/tokio",
+            "title": "tokio - Rust",
+            "encrypted_content": "abc123",
+        }]))
+        .unwrap();
+
+        for streaming in [false, true] {
+            let llm = MockLlmProvider::new().with_response(MockLlmProvider::response(
+                vec![
+                    ContentBlock::ServerToolUse {
+                        id: "srvtoolu_1".to_string(),
+                        name: "web_search".to_string(),
+                        input: serde_json::json!({"query": "tokio docs"}),
+                    },
+                    ContentBlock::WebSearchToolResult {
+                        tool_use_id: "srvtoolu_1".to_string(),
+                        content: search_result.clone(),
+                    },
+                    ContentBlock::text("Tokio is an async runtime."),
+                ],
+                StopReason::EndTurn,
+            ));
+            let config = AgentConfig::new("Test")
+                .with_streaming(streaming)
+                .with_web_search(crate::llm::WebSearchTool::new().with_max_uses(2));
+
+            let dir = tempfile::tempdir().unwrap();
+            let runtime = AgentRuntime::new();
+            let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+            let mut out = Vec::new();
+            let options = PrintModeOptions::new().with_output_format(OutputFormat::Json);
+            run_print_mode_with_writer(&handle, "Search", options, &mut out).await.unwrap();
+            runtime.shutdown_all().await;
+
+            // Offered as a tool, answered without running anything locally
+            assert_eq!(llm.call_count(), 1);
+            let tools = serde_json::to_value(&llm.requests()[0].tools).unwrap();
+            assert_eq!(tools[0]["type"], "web_search_20250305");
+            assert_eq!(tools[0]["max_uses"], 2);
+
+            let json: Value = serde_json::from_slice(&out).unwrap();
+            let search = json["chunks"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find_map(|c| c.get("WebSearch"))
+                .expect("WebSearch chunk");
+            assert_eq!(search["id"], "srvtoolu_1");
+            assert_eq!(search["query"], "tokio docs");
+            assert_eq!(search["result"][0]["url"], "https://docs.rs/tokio");
+
+            let session = AgentSession::load_with_storage("loop-test", SessionStorage::with_dir(dir.path())).unwrap();
+            let assistant = &session.history()[1];
+            let MessageContent::Blocks(blocks) = &assistant.content else {
+                panic!("Expected blocks");
+            };
+            assert!(matches!(blocks[0], ContentBlock::ServerToolUse { .. }));
+            assert!(matches!(blocks[1], ContentBlock::WebSearchToolResult { .. }));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_citations_are_stored_and_surfaced() {
+        let citations: Vec<crate::llm::Citation> = serde_json::from_value(serde_json::json!([{
+            "type": "search_result_location",
+            "cited_text": "Tokens expire after 1 hour.",
+            "source": "kb://auth",
+            "title": "Auth guide",
+            "search_result_index": 0,
+            "start_block_index": 0,
+            "end_block_index": 0
+        }]))
+        .unwrap();
+
+        for streaming in [false, true] {
+            let llm = MockLlmProvider::new().with_response(MockLlmProvider::response(
+                vec![ContentBlock::Text {
+                    text: "Tokens last an hour.".to_string(),
+                    cache_control: None,
+                    citations: Some(citations.clone()),
+                }],
+                StopReason::EndTurn,
+            ));
+            let config = AgentConfig::new("Test").with_streaming(streaming);
+
+            let dir = tempfile::tempdir().unwrap();
+            let runtime = AgentRuntime::new();
+            let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+            let mut out = Vec::new();
+            let options = PrintModeOptions::new().with_output_format(OutputFormat::Json);
+            run_print_mode_with_writer(&handle, "How long do tokens last?", options, &mut out)
+                .await
+                .unwrap();
+            runtime.shutdown_all().await;
+
+            let json: Value = serde_json::from_slice(&out).unwrap();
+            let chunks = json["chunks"].as_array().unwrap();
+            let sent = chunks.iter().find_map(|c| c.get("Citations")).expect("Citations chunk");
+            assert_eq!(sent["text"], "Tokens last an hour.");
+            assert_eq!(sent["citations"][0]["source"], "kb://auth");
+            assert_eq!(sent["citations"][0]["cited_text"], "Tokens expire after 1 hour.");
+
+            // Text, then its citations, then TextComplete, in both modes
+            let kinds: Vec<&str> = chunks
+                .iter()
+                .filter_map(|c| c.as_object()?.keys().next().map(String::as_str))
+                .filter(|kind| kind.starts_with("Text") || *kind == "Citations")
+                .collect();
+            assert_eq!(kinds, vec!["TextDelta", "Citations", "TextComplete"], "streaming: {}", streaming);
+
+            let session = AgentSession::load_with_storage("loop-test", SessionStorage::with_dir(dir.path())).unwrap();
+            let stored = serde_json::to_value(&session.history()[1]).unwrap();
+            assert_eq!(stored["content"][0]["citations"][0]["title"], "Auth guide", "streaming: {}", streaming);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tool_start_is_sent_before_input_completes() {
+        use crate::tools::{BashTool, ToolRegistry};
+
+        let input = serde_json::json!({"command": "echo streamed"});
+        let llm = MockLlmProvider::new()
+            .with_input_chunk_size(4)
+            .with_response(MockLlmProvider::tool_use_response("toolu_1", "Bash", input.clone()))
+            .with_text("done");
+        let mut registry = ToolRegistry::new();
+        registry.register(BashTool::new().unwrap());
+        let config = AgentConfig::new("Test")
+            .with_streaming(true)
+            .with_tools(Arc::new(registry));
+
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+        let mut out = Vec::new();
+        let options = PrintModeOptions::new()
+            .with_output_format(OutputFormat::Json)
+            .with_allowed_tools(["*"]);
+        run_print_mode_with_writer(&handle, "Run it", options, &mut out).await.unwrap();
+        runtime.shutdown_all().await;
+
+        let json: Value = serde_json::from_slice(&out).unwrap();
+        let tool_chunks: Vec<(&str, &Value)> = json["chunks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|c| c.as_object()?.iter().next())
+            .map(|(kind, value)| (kind.as_str(), value))
+            .filter(|(kind, _)| kind.starts_with("Tool"))
+            .collect();
+
+        // Announced before any of the input has streamed
+        assert_eq!(tool_chunks[0].0, "ToolStreamStart");
+        assert_eq!(tool_chunks[0].1["id"], "toolu_1");
+        assert_eq!(tool_chunks[0].1["name"], "Bash");
+
+        // Then the raw fragments, which only parse once joined
+        let deltas: Vec<&str> = tool_chunks[1..]
+            .iter()
+            .take_while(|(kind, _)| *kind == "ToolInputDelta")
+            .map(|(_, value)| value["partial_json"].as_str().unwrap())
+            .collect();
+        assert!(deltas.len() > 1);
+        assert!(serde_json::from_str::<Value>(deltas[0]).is_err());
+        assert_eq!(serde_json::from_str::<Value>(&deltas.concat()).unwrap(), input);
+
+        // Then the final input right before the tool runs, and its result
+        let rest = &tool_chunks[1 + deltas.len()..];
+        assert_eq!(rest[0].0, "ToolStart");
+        assert_eq!(rest[0].1["id"], "toolu_1");
+        assert_eq!(rest[0].1["input"], input);
+        assert_eq!(rest[1].0, "ToolEnd");
+        assert_eq!(rest[1].1["id"], "toolu_1");
+    }
+
+    #[tokio::test]
+    async fn test_truncated_tool_call_is_retried() {
+        let llm = MockLlmProvider::new()
+            .with_response(truncated(vec![
+                ContentBlock::text("Writing the file"),
+                ContentBlock::tool_use("tool_1", "Write", serde_json::json!({"file_path": "a.rs"})),
+            ]))
+            .with_text("Done");
+        let config = AgentConfig::new("Test").with_auto_continue(1);
+
+        let completes = chunks_of(&run_turn(config, &llm).await, "TextComplete");
+        assert_eq!(completes.last().map(String::as_str), Some("Done"));
+
+        // The partial response never reached the history
+        let requests = llm.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].messages.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_stream_resumes_from_partial_text() {
+        // MessageStart, ContentBlockStart and one text delta arrive
+        let llm = MockLlmProvider::new()
+            .with_dropped_stream(MockLlmProvider::text_response("fn main() {"), 3)
+            .with_text(" }");
+        let config = AgentConfig::new("Test").with_streaming(true).with_continue_prompt("Go on");
+
+        let chunks = run_turn(config, &llm).await;
+        assert_eq!(chunks_of(&chunks, "TextComplete"), vec!["fn main() { }".to_string()]);
+        assert!(chunks_of(&chunks, "Status").contains(&"Stream dropped, resuming".to_string()));
+
+        let requests = llm.requests();
+        assert_eq!(requests.len(), 2);
+        let messages = &requests[1].messages;
+        assert_eq!(messages[1].role, "assistant");
+        assert!(serde_json::to_string(&messages[1]).unwrap().contains("fn main() {"));
+        assert!(serde_json::to_string(&messages[2]).unwrap().contains("Go on"));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_stream_keeps_partial_response_without_resumes() {
+        let llm = MockLlmProvider::new()
+            .with_dropped_stream(MockLlmProvider::text_response("Partial"), 3)
+            .with_text("Never sent");
+        let config = AgentConfig::new("Test").with_streaming(true).with_stream_resumes(0);
+
+        let chunks = run_turn(config, &llm).await;
+        assert_eq!(chunks_of(&chunks, "TextComplete"), vec!["Partial".to_string()]);
+        assert!(chunks_of(&chunks, "Status").contains(&"Stream dropped, partial response kept".to_string()));
+        assert_eq!(llm.call_count(), 1);
+    }
+
+    /// A text block then a Read of notes.txt, run in a workdir that has it
+    async fn run_dropped_read(llm: &MockLlmProvider) -> Vec<String> {
+        let dir = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        std::fs::write(work.path().join("notes.txt"), "Ship it").unwrap();
+        let mut registry = crate::tools::ToolRegistry::new();
+        registry.register(crate::tools::ReadTool::with_base_dir(work.path().to_string_lossy()));
+        let config = AgentConfig::new("Test").with_streaming(true).with_tools(Arc::new(registry));
+        let runtime = AgentRuntime::new();
+        let handle = spawn_agent(&runtime, &dir, config, llm).await;
+        handle.add_session_rule(crate::permissions::PermissionRule::allow_tool("Read"));
+
+        let mut rx = handle.subscribe();
+        handle.send_input("Check the notes").await.unwrap();
+        let chunks = until_done(&mut rx).await;
+        runtime.shutdown_all().await;
+
+        chunks
+            .into_iter()
+            .filter_map(|chunk| match chunk {
+                OutputChunk::TextComplete(text) => Some(text),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn read_notes_response() -> crate::llm::MessageResponse {
+        MockLlmProvider::response(
+            vec![
+                ContentBlock::text("Reading"),
+                ContentBlock::tool_use("call_1", "Read", serde_json::json!({"file_path": "notes.txt"})),
+            ],
+            StopReason::ToolUse,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_dropped_stream_after_a_tool_call_runs_it() {
+        // Everything up to the tool call's ContentBlockStop arrives
+        let llm = MockLlmProvider::new()
+            .with_dropped_stream(read_notes_response(), 7)
+            .with_text("Looks good");
+
+        let completes = run_dropped_read(&llm).await;
+        assert_eq!(completes, vec!["Reading".to_string(), "Looks good".to_string()]);
+
+        // The call ran instead of the response being retried
+        let requests = llm.requests();
+        assert_eq!(requests.len(), 2);
+        let results = serde_json::to_string(requests[1].messages.last().unwrap()).unwrap();
+        assert!(results.contains("call_1") && results.contains("Ship it"), "{}", results);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_stream_mid_tool_call_is_retried() {
+        // The tool call starts but its ContentBlockStop never arrives
+        let llm = MockLlmProvider::new()
+            .with_dropped_stream(read_notes_response(), 6)
+            .with_text("Looks good");
+
+        let completes = run_dropped_read(&llm).await;
+        assert_eq!(completes.last().map(String::as_str), Some("Looks good"));
+
+        // The partial response never reached the history
+        let requests = llm.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].messages.len(), 1);
+    }
+
+    #[test]
+    fn test_unsupported_features_are_disabled_at_spawn() {
+        let config = || AgentConfig::new("Test").with_thinking(1024).with_prompt_caching(true);
+
+        let llm = MockLlmProvider::new().with_model("claude-3-5-haiku-20241022");
+        let mut agent = StandardAgent::new(config(), Arc::new(llm));
+        agent.apply_model_capabilities();
+        assert!(agent.config.thinking.is_none());
+        assert!(agent.config.enable_prompt_caching);
+
+        let llm = MockLlmProvider::new().with_model("gemini-2.0-flash");
+        let mut agent = StandardAgent::new(config(), Arc::new(llm));
+        agent.apply_model_capabilities();
+        assert!(!agent.config.enable_prompt_caching);
+
+        // Unknown models keep everything
+        let mut agent = StandardAgent::new(config(), Arc::new(MockLlmProvider::new()));
+        agent.apply_model_capabilities();
+        assert!(agent.config.thinking.is_some());
+        assert!(agent.config.enable_prompt_caching);
+    }
+
+    #[test]
+    fn test_new_takes_concrete_provider_arcs() {
+        use crate::llm::AnthropicProvider;
+
+        let anthropic: Arc<AnthropicProvider> = Arc::new(AnthropicProvider::new("key").unwrap());
+        let agent = StandardAgent::new(AgentConfig::new("Test"), anthropic.clone());
+        assert_eq!(agent.llm.model(), anthropic.model());
+
+        let mock = Arc::new(MockLlmProvider::new());
+        let agent = StandardAgent::new(AgentConfig::new("Test"), mock.clone());
+        assert_eq!(Arc::strong_count(&mock), 2);
+        drop(agent);
+        assert_eq!(Arc::strong_count(&mock), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cache_control_follows_swapped_provider() {
+        use crate::llm::SwappableLlmProvider;
+
+        let swappable = SwappableLlmProvider::new(Arc::new(MockLlmProvider::new()));
+        let handle = swappable.handle();
+        let agent = StandardAgent::new(AgentConfig::new("Test"), Arc::new(swappable));
+
+        let (_, system, _) = agent.apply_cache_control(vec![], None, vec![Message::user("Hi")]);
+        assert!(matches!(system, Some(SystemPrompt::Blocks(_))));
+
+        handle.set_provider(Arc::new(MockLlmProvider::new().with_model("gemini-2.0-flash"))).await;
+        let (_, system, _) = agent.apply_cache_control(vec![], None, vec![Message::user("Hi")]);
+        assert!(matches!(system, Some(SystemPrompt::Text(_))));
+    }
+
+    #[tokio::test]
+    async fn test_thinking_is_not_sent_to_models_without_it() {
+        let llm = MockLlmProvider::new().with_model("claude-3-5-sonnet-20241022").with_text("Hi");
+        run_turn(AgentConfig::new("Test").with_thinking(1024), &llm).await;
+        assert!(llm.requests()[0].thinking.is_none());
+    }
+
+    #[test]
+    fn test_mixed_tool_result_blocks() {
+        let result = ToolResult::success("Two screenshots")
+            .with_content(ToolResultData::Image { data: vec![1], media_type: "image/png".into() })
+            .with_content(ToolResultData::Document {
+                data: vec![2],
+                media_type: "application/pdf".into(),
+                description: "report.pdf".into(),
+            })
+            .with_content(ToolResultData::Image { data: vec![3], media_type: "image/jpeg".into() });
+        let blocks = result_blocks("call_1".into(), result);
+
+        assert_eq!(blocks.len(), 4);
+        assert!(matches!(
+            &blocks[0],
+            ContentBlock::ToolResult { tool_use_id, content: Some(text), is_error: None, .. }
+                if tool_use_id == "call_1" && text == "Two screenshots\n\nreport.pdf"
+        ));
+        let kinds: Vec<_> = blocks[1..].iter().map(|b| serde_json::to_value(b).unwrap()["type"].clone()).collect();
+        assert_eq!(kinds, vec!["image", "document", "image"]);
+
+        // Images alone leave the tool_result empty
+        let blocks = result_blocks("call_2".into(), ToolResult::image(vec![1], "image/png"));
+        assert!(matches!(&blocks[0], ContentBlock::ToolResult { content: None, .. }));
+        assert_eq!(blocks.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_parallel_tool_results_come_before_their_media() {
+        let dir = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        std::fs::write(work.path().join("shot.png"), [0x89, b'P', b'N', b'G']).unwrap();
+        std::fs::write(work.path().join("notes.txt"), "Ship it").unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new()
+            .with_response(MockLlmProvider::response(
+                vec![
+                    ContentBlock::tool_use("call_1", "Read", serde_json::json!({"file_path": "shot.png"})),
+                    ContentBlock::tool_use("call_2", "Read", serde_json::json!({"file_path": "notes.txt"})),
+                ],
+                StopReason::ToolUse,
+            ))
+            .with_text("Looks good");
+        let mut registry = crate::tools::ToolRegistry::new();
+        registry.register(crate::tools::ReadTool::with_base_dir(work.path().to_string_lossy()));
+        let config = AgentConfig::new("Test").with_tools(Arc::new(registry));
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+        handle.add_session_rule(crate::permissions::PermissionRule::allow_tool("Read"));
+
+        let mut rx = handle.subscribe();
+        handle.send_input("Check both").await.unwrap();
+        until_done(&mut rx).await;
+        runtime.shutdown_all().await;
+
+        let results = llm.requests()[1].messages.last().cloned().unwrap();
+        let kinds: Vec<_> = results
+            .blocks()
+            .unwrap()
+            .iter()
+            .map(|block| match block {
+                ContentBlock::ToolResult { tool_use_id, .. } => tool_use_id.clone(),
+                other => serde_json::to_value(other).unwrap()["type"].as_str().unwrap().to_string(),
+            })
+            .collect();
+        assert_eq!(kinds, vec!["call_1", "call_2", "image"]);
+    }
+
+    #[tokio::test]
+    async fn test_thinking_policy_varies_budget_per_iteration() {
+        let call = |id| MockLlmProvider::tool_use_response(id, "Missing", serde_json::json!({}));
+        let llm = MockLlmProvider::new()
+            .with_response(call("call_1"))
+            .with_response(call("call_2"))
+            .with_response(call("call_3"))
+            .with_text("done");
+        let policy = ThinkingPolicy::new(8000).with_tool_followup_budget(1024).disable_after(3);
+        run_turn(AgentConfig::new("Test").with_thinking_policy(policy), &llm).await;
+
+        let budgets: Vec<_> = llm.requests().iter().map(|r| r.thinking.as_ref().map(|t| t.budget_tokens)).collect();
+        assert_eq!(budgets, vec![Some(8000), Some(1024), Some(1024), None]);
+    }
+
+    /// Run one turn per prompt on one session; returns the chunks of each turn and the stored history
+    async fn run_turns(config: AgentConfig, llm: &MockLlmProvider, prompts: &[String]) -> (Vec<Vec<OutputChunk>>, AgentSession) {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let handle = spawn_agent(&runtime, &dir, config, llm).await;
+        let mut rx = handle.subscribe();
+        let mut turns = Vec::new();
+        for prompt in prompts {
+            handle.send_input(prompt.as_str()).await.unwrap();
+            turns.push(until_done(&mut rx).await);
+        }
+        runtime.shutdown_all().await;
+        let session = AgentSession::load_with_storage("loop-test", SessionStorage::with_dir(dir.path())).unwrap();
+        (turns, session)
+    }
+
+    /// Prompts and replies of about 1000 tokens each, against a 5900-token budget
+    fn overflow_setup(replies: &[&str]) -> (AgentConfig, MockLlmProvider, Vec<String>) {
+        let llm = replies
+            .iter()
+            .fold(MockLlmProvider::new().with_max_tokens(100), |llm, reply| llm.with_text(reply.repeat(4000)));
+        let prompts = ["a", "b", "c", "d", "e"].iter().map(|p| p.repeat(4000)).collect();
+        (AgentConfig::new("Test").with_context_window(6000), llm, prompts)
+    }
+
+    fn overflow_error(chunks: &[OutputChunk]) -> Option<&str> {
+        chunks.iter().find_map(|c| match c {
+            OutputChunk::Error(e) if e.contains("Context window exceeded") => Some(e.as_str()),
+            _ => None,
+        })
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_debugger_live_sink_streams_the_turn() {
+        use tokio::io::AsyncBufReadExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("debug.sock");
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new().with_response(bash_call("toolu_1")).with_text("done");
+        let mut registry = crate::tools::ToolRegistry::new();
+        registry.register(crate::tools::BashTool::new().unwrap());
+        let config = AgentConfig::new("Test")
+            .with_tools(Arc::new(registry))
+            .with_dangerous_skip_permissions(true)
+            .with_debug(true)
+            .with_debug_config(crate::helpers::DebuggerConfig::new().with_live_sink(crate::helpers::DebugSink::Unix(socket.clone())));
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+
+        let stream = loop {
+            match tokio::net::UnixStream::connect(&socket).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        let mut lines = tokio::io::BufReader::new(stream).lines();
+        let greeting: Value = serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(greeting["event_type"], "connected");
+
+        let mut rx = handle.subscribe();
+        handle.send_input("Look it up").await.unwrap();
+        until_done(&mut rx).await;
+
+        let mut events = Vec::new();
+        for _ in 0..6 {
+            let line = tokio::time::timeout(Duration::from_secs(5), lines.next_line()).await.unwrap().unwrap().unwrap();
+            let event: Value = serde_json::from_str(&line).unwrap();
+            events.push((event["sequence"].as_u64().unwrap(), event["event_type"].as_str().unwrap().to_string()));
+        }
+        let expected = ["api_request", "api_response", "tool_call", "tool_result", "api_request", "api_response"];
+        assert_eq!(
+            events,
+            expected.iter().enumerate().map(|(i, kind)| (i as u64, kind.to_string())).collect::<Vec<_>>()
+        );
+        runtime.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_caller_debugger_gets_the_events() {
+        let logs = tempfile::tempdir().unwrap();
+        let debugger = Arc::new(Debugger::new(logs.path()).unwrap());
+        let mut events = debugger.subscribe();
+        let llm = MockLlmProvider::new().with_text("done");
+        let config = AgentConfig::new("Test").with_debugger(debugger.clone());
+
+        run_turn(config, &llm).await;
+        let kinds: Vec<_> = std::iter::from_fn(|| events.try_recv().ok()).map(|e| e.event_type).collect();
+        assert_eq!(
+            kinds,
+            vec![crate::helpers::EventType::ApiRequest, crate::helpers::EventType::ApiResponse]
+        );
+        assert!(debugger.dir().join("000000_api_request.json").exists());
+    }
+
+    #[tokio::test]
+    async fn test_context_overflow_fails_before_sending() {
+        let (config, llm, prompts) = overflow_setup(&["1", "2", "3", "4"]);
+        let (turns, session) = run_turns(config, &llm, &prompts[..4]).await;
+
+        assert!(turns[..3].iter().all(|turn| overflow_error(turn).is_none()));
+        let error = overflow_error(&turns[3]).expect("fourth turn overflows");
+        assert!(error.contains("7029 tokens, 5900 are available"), "{}", error);
+        assert_eq!(llm.call_count(), 3);
+        assert_eq!(session.history().len(), 7);
+
+        // A thinking budget larger than max_tokens is reserved instead
+        let (config, llm, prompts) = overflow_setup(&["1"]);
+        let (turns, _) = run_turns(config.with_thinking(5500), &llm, &prompts[..1]).await;
+        assert!(overflow_error(&turns[0]).unwrap().contains("500 are available"));
+        assert_eq!(llm.call_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_context_overflow_counts_tool_results_of_the_turn() {
+        let llm = MockLlmProvider::new()
+            .with_max_tokens(100)
+            .with_response(MockLlmProvider::tool_use_response(
+                "toolu_1",
+                "Missing",
+                serde_json::json!({"query": "x".repeat(24000)}),
+            ))
+            .with_text("Never sent");
+        let config = AgentConfig::new("Test").with_context_window(6000);
+        let (turns, _) = run_turns(config, &llm, &["Look it up".to_string()]).await;
+
+        assert!(overflow_error(&turns[0]).is_some(), "{:?}", turns[0]);
+        assert_eq!(llm.call_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_context_overflow_drops_oldest_turns() {
+        let (config, llm, prompts) = overflow_setup(&["1", "2", "3", "4"]);
+        let config = config.with_context_overflow(ContextOverflow::DropOldest);
+        let (turns, session) = run_turns(config, &llm, &prompts[..4]).await;
+
+        assert!(turns.iter().all(|turn| overflow_error(turn).is_none()));
+        assert!(turns[3]
+            .iter()
+            .any(|c| matches!(c, OutputChunk::Status(s) if s.contains("left out the 4 oldest messages"))));
+        let sent = &llm.requests()[3].messages;
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[0].text(), Some("c".repeat(4000).as_str()));
+        // The session keeps every message
+        assert_eq!(session.history().len(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_context_overflow_compacts_oldest_turns() {
+        let (config, llm, prompts) = overflow_setup(&["1", "2", "3"]);
+        let llm = llm
+            .with_text("- The user sent a and b")
+            .with_text("4".repeat(4000))
+            .with_text("5".repeat(4000));
+        let config = config.with_context_overflow(ContextOverflow::Compact);
+        let (turns, session) = run_turns(config, &llm, &prompts).await;
+
+        assert!(turns.iter().all(|turn| overflow_error(turn).is_none()));
+        let requests = llm.requests();
+        assert_eq!(requests.len(), 6);
+        let summary_request = &requests[3];
+        assert!(summary_request.messages[0].text().unwrap().contains(&"a".repeat(4000)));
+        assert!(!summary_request.messages[0].text().unwrap().contains(&"c".repeat(4000)));
+
+        let sent = &requests[4].messages;
+        assert_eq!(sent.len(), 5);
+        assert_eq!(sent[0].text(), Some(format!("{}\n- The user sent a and b", SUMMARY_PREFIX).as_str()));
+        assert_eq!(sent[2].text(), Some("c".repeat(4000).as_str()));
+
+        // The stored summary is reused by the next turn
+        assert_eq!(requests[5].messages.len(), 7);
+        assert_eq!(requests[5].messages[0].text(), sent[0].text());
+        let stored: SessionSummary =
+            serde_json::from_value(session.get_custom(CONTEXT_SUMMARY_METADATA_KEY).unwrap().clone()).unwrap();
+        assert_eq!(stored.message_count, 4);
+        assert_eq!(session.history().len(), 10);
+    }
+
+    #[tokio::test]
+    async fn test_max_tokens_cut_to_fit_the_context_window() {
+        let llm = MockLlmProvider::new().with_max_tokens(4000).with_text("Short").with_text("Short");
+        let config = AgentConfig::new("Test").with_context_window(6000);
+        let (turns, _) = run_turns(config, &llm, &["hi".to_string(), "a".repeat(16000)]).await;
+
+        assert!(turns.iter().all(|turn| overflow_error(turn).is_none()));
+        let requests = llm.requests();
+        // A small request keeps the configured max tokens
+        assert_eq!(requests[0].max_tokens, 4000);
+        assert!(requests[0].generation.is_none());
+        // A large one gets what the window leaves after it and a 5% margin
+        let prompt = estimate_request_tokens(&requests[1].messages, requests[1].system.as_ref(), &requests[1].tools);
+        assert_eq!(u64::from(requests[1].max_tokens), 6000 - 300 - prompt);
+        assert!(requests[1].max_tokens < 4000);
+
+        // Never below the floor
+        let llm = MockLlmProvider::new().with_max_tokens(4000).with_text("Short");
+        let config = AgentConfig::new("Test").with_context_window(6000).with_min_output_tokens(1900);
+        let (turns, _) = run_turns(config, &llm, &["a".repeat(16000)]).await;
+        assert!(overflow_error(&turns[0]).is_none());
+        assert_eq!(llm.requests()[0].max_tokens, 1900);
+    }
+
+    #[tokio::test]
+    async fn test_max_tokens_ends_turn_without_auto_continue() {
+        let llm = MockLlmProvider::new()
+            .with_response(truncated(vec![ContentBlock::text("Partial")]))
+            .with_text("Never sent");
+
+        let completes = chunks_of(&run_turn(AgentConfig::new("Test"), &llm).await, "TextComplete");
+        assert_eq!(completes, vec!["Partial".to_string()]);
+        assert_eq!(llm.call_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_repeated_failing_call_warns_then_stops() {
+        // No tools are configured, so every call fails the same way
+        let llm = MockLlmProvider::new().with_fallback(MockLlmProvider::tool_use_response(
+            "tool_1",
+            "Grep",
+            serde_json::json!({"pattern": "missing"}),
+        ));
+
+        let detections = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = detections.clone();
+        let mut hooks = crate::hooks::HookRegistry::new();
+        hooks.add(crate::hooks::HookEvent::LoopDetected, move |ctx: &mut HookContext| {
+            seen.lock().unwrap().push((ctx.loop_repeats, ctx.loop_aborted));
+            crate::hooks::HookResult::none()
+        });
+        let config = AgentConfig::new("Test")
+            .with_loop_thresholds(2, 1)
+            .with_hooks(hooks);
+
+        let chunks = run_turn(config, &llm).await;
+        assert!(chunks_of(&chunks, "Status")
+            .contains(&"Stopped: Grep failed 3 times with the same input".to_string()));
+        assert_eq!(llm.call_count(), 3);
+
+        // The warning was added to the second tool result
+        let last = llm.requests()[2].messages.last().cloned().unwrap();
+        assert!(serde_json::to_string(&last).unwrap().contains("stuck in a loop"));
+
+        assert_eq!(*detections.lock().unwrap(), vec![(Some(2), false), (Some(3), true)]);
+    }
+
+    /// Agent with Bash that denies unanswered permission requests after `after`
+    fn permission_timeout_config(after: Duration, decision: TimeoutDecision) -> AgentConfig {
+        let mut registry = crate::tools::ToolRegistry::new();
+        registry.register(crate::tools::BashTool::new().unwrap());
+        AgentConfig::new("Test")
+            .with_tools(Arc::new(registry))
+            .with_permission_timeout(after, decision)
+    }
+
+    fn bash_call(id: &str) -> crate::llm::MessageResponse {
+        MockLlmProvider::tool_use_response(id, "Bash", serde_json::json!({"command": "echo hi"}))
+    }
+
+    /// Text of the tool result for `tool_use_id` in a request's last message
+    fn tool_result_text(request: &crate::llm::MockRequest, tool_use_id: &str) -> String {
+        tool_result_text_in(request.messages.last().unwrap(), tool_use_id)
+    }
+
+    /// Text of the tool result for `tool_use_id` in a message
+    fn tool_result_text_in(message: &Message, tool_use_id: &str) -> String {
+        let last = serde_json::to_value(message).unwrap();
+        last["content"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|block| block["tool_use_id"] == tool_use_id)
+            .map(|block| block["content"].to_string())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_attachments_are_sent_and_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new().with_text("A red square");
+        let handle = spawn_agent(&runtime, &dir, AgentConfig::new("Test"), &llm).await;
+        let mut rx = handle.subscribe();
+
+        let png = vec![0x89, b'P', b'N', b'G'];
+        handle
+            .send_input_with_attachments(
+                "What is this?",
+                vec![Attachment::bytes(png, "image/png").with_name("screen.png")],
+            )
+            .await
+            .unwrap();
+        while !matches!(rx.recv().await.unwrap(), OutputChunk::Done) {}
+
+        let is_image = |block: &ContentBlock| matches!(block, ContentBlock::Image { source, .. } if source.media_type == "image/png");
+        let sent = &llm.requests()[0].messages[0];
+        assert!(matches!(&sent.content, MessageContent::Blocks(b) if b.len() == 2 && is_image(&b[1])));
+
+        runtime.shutdown_all().await;
+        let saved = AgentSession::load_with_storage("loop-test", SessionStorage::with_dir(dir.path())).unwrap();
+        match &saved.history()[0].content {
+            MessageContent::Blocks(blocks) => {
+                assert!(matches!(&blocks[0], ContentBlock::Text { text, .. } if text == "What is this?"));
+                assert!(is_image(&blocks[1]));
+            }
+            other => panic!("expected blocks, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_turn_complete_summarizes_the_turn() {
+        for streaming in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let runtime = AgentRuntime::new();
+            let with_usage = |mut response: crate::llm::MessageResponse, input, output| {
+                response.usage.input_tokens = input;
+                response.usage.output_tokens = output;
+                response
+            };
+            let llm = MockLlmProvider::new()
+                .with_response(with_usage(
+                    MockLlmProvider::response(
+                        vec![
+                            ContentBlock::text("Let me look that up."),
+                            ContentBlock::tool_use("toolu_1", "Lookup", serde_json::json!({"query": "x".repeat(300)})),
+                        ],
+                        StopReason::ToolUse,
+                    ),
+                    10,
+                    5,
+                ))
+                .with_response(with_usage(
+                    MockLlmProvider::response(
+                        vec![ContentBlock::text("The answer"), ContentBlock::text(" is 42.")],
+                        StopReason::EndTurn,
+                    ),
+                    20,
+                    7,
+                ));
+            let config = AgentConfig::new("Test").with_streaming(streaming);
+            let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+            assert!(handle.last_turn().is_none());
+            let mut rx = handle.subscribe();
+
+            handle.send_input("What is the answer?").await.unwrap();
+            let chunks = until_done(&mut rx).await;
+            let n = chunks.len();
+            assert!(matches!(chunks[n - 1], OutputChunk::Done));
+            let summary = chunks[n - 2].turn_summary().expect("TurnComplete right before Done");
+
+            assert_eq!(summary.final_text, "The answer is 42.");
+            assert_eq!(summary.tool_calls.len(), 1);
+            let call = &summary.tool_calls[0];
+            assert_eq!((call.id.as_str(), call.name.as_str(), call.is_error), ("toolu_1", "Lookup", true));
+            assert!(call.input_preview.starts_with("{\"query\":\"xxx") && call.input_preview.ends_with("..."));
+            assert_eq!(call.input_preview.chars().count(), 203);
+            assert_eq!((summary.usage.input_tokens, summary.usage.output_tokens), (30, 12), "streaming: {}", streaming);
+
+            let cached = handle.last_turn().unwrap();
+            assert_eq!((cached.turn, cached.final_text.as_str()), (summary.turn, "The answer is 42."));
+            runtime.shutdown_all().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_disabled_attachment_parsing_leaves_text_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("secret.txt");
+        std::fs::write(&file, "do not read").unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new().with_text("ok");
+        let config = AgentConfig::new("Test").with_attachment_parsing(false);
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+        let mut rx = handle.subscribe();
+
+        let input = format!("Look at <vibe-work-attachment>{}</vibe-work-attachment>", file.display());
+        handle.send_input(input.as_str()).await.unwrap();
+        while !matches!(rx.recv().await.unwrap(), OutputChunk::Done) {}
+
+        let sent = &llm.requests()[0].messages[0];
+        assert!(matches!(&sent.content, MessageContent::Blocks(b) if b.len() == 1 && b[0].as_text() == Some(input.as_str())), "{:?}", sent.content);
+        runtime.shutdown_all().await;
+        let saved = AgentSession::load_with_storage("loop-test", SessionStorage::with_dir(dir.path())).unwrap();
+        assert!(matches!(&saved.history()[0].content, MessageContent::Text(text) if *text == input));
+    }
+
+    #[tokio::test]
+    async fn test_custom_input_preprocessor_runs() {
+        struct Shout;
+
+        #[async_trait::async_trait]
+        impl crate::helpers::InputPreprocessor for Shout {
+            async fn process(&self, input: &str, _base_dir: &str) -> Vec<ContentBlock> {
+                vec![ContentBlock::text(input.to_uppercase()), ContentBlock::text("(preprocessed)")]
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new().with_text("ok");
+        let config = AgentConfig::new("Test").with_input_preprocessor(Shout);
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+        let mut rx = handle.subscribe();
+
+        handle.send_input("hello").await.unwrap();
+        while !matches!(rx.recv().await.unwrap(), OutputChunk::Done) {}
+
+        let sent = &llm.requests()[0].messages[0];
+        match &sent.content {
+            MessageContent::Blocks(blocks) => {
+                assert_eq!(blocks.len(), 2);
+                assert!(matches!(&blocks[0], ContentBlock::Text { text, .. } if text == "HELLO"));
+            }
+            other => panic!("expected blocks, got {:?}", other),
+        }
+        runtime.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_tool_choice_applies_to_the_first_call_only() {
+        let call = |id| MockLlmProvider::tool_use_response(id, "Missing", serde_json::json!({}));
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new()
+            .with_response(call("toolu_1"))
+            .with_text("first")
+            .with_response(call("toolu_2"))
+            .with_text("second");
+        let config = AgentConfig::new("Test").with_tool_choice(ToolChoice::any());
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+        let mut rx = handle.subscribe();
+
+        handle.send_input("plan").await.unwrap();
+        while !matches!(rx.recv().await.unwrap(), OutputChunk::Done) {}
+        handle
+            .send_input_with_options("todo", InputOptions::new().with_tool_choice(ToolChoice::tool("TodoWrite")))
+            .await
+            .unwrap();
+        while !matches!(rx.recv().await.unwrap(), OutputChunk::Done) {}
+
+        let choices: Vec<Option<ToolChoice>> = llm.requests().into_iter().map(|r| r.tool_choice).collect();
+        assert_eq!(choices.len(), 4);
+        assert!(matches!(choices[0], Some(ToolChoice::Any { .. })));
+        assert!(matches!(choices[1], Some(ToolChoice::Auto { .. })));
+        assert!(matches!(&choices[2], Some(ToolChoice::Tool { name, .. }) if name == "TodoWrite"));
+        assert!(matches!(choices[3], Some(ToolChoice::Auto { .. })));
+        runtime.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_tools_subscriber_sees_only_tool_chunks() {
+        let mut registry = crate::tools::ToolRegistry::new();
+        registry.register(crate::tools::BashTool::new().unwrap());
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new()
+            .with_response(bash_call("toolu_1"))
+            .with_response(bash_call("toolu_2"))
+            .with_text("done");
+        let config = AgentConfig::new("Test")
+            .with_tools(Arc::new(registry))
+            .with_dangerous_skip_permissions(true);
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+        let mut all = handle.subscribe();
+        let mut tools = handle.subscribe_tools();
+
+        handle.send_input("Run it twice").await.unwrap();
+        let everything = until_done(&mut all).await;
+        let mut seen = Vec::new();
+        loop {
+            let envelope = tools.recv_envelope().await.unwrap();
+            let done = matches!(envelope.chunk, OutputChunk::Done);
+            seen.push(envelope);
+            if done {
+                break;
+            }
+        }
+
+        let kinds: Vec<&str> = seen
+            .iter()
+            .map(|envelope| match &envelope.chunk {
+                OutputChunk::ToolStart { .. } => "start",
+                OutputChunk::ToolEnd { .. } => "end",
+                OutputChunk::Done => "done",
+                other => panic!("unexpected chunk {:?}", other),
+            })
+            .collect();
+        assert_eq!(kinds, vec!["start", "end", "start", "end", "done"]);
+        assert!(everything.len() > seen.len());
+        // Skipped chunks leave gaps in the sequence numbers
+        assert!(seen.windows(2).all(|pair| pair[0].seq < pair[1].seq));
+        assert!(seen.windows(2).any(|pair| pair[1].seq > pair[0].seq + 1));
+        assert!(matches!(tools.try_recv(), Err(tokio::sync::broadcast::error::TryRecvError::Empty)));
+        runtime.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_turn_timeout_stops_the_loop() {
+        let mut registry = crate::tools::ToolRegistry::new();
+        registry.register(crate::tools::BashTool::new().unwrap());
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        // Every response asks for two more slow calls
+        let llm = MockLlmProvider::new().with_fallback(MockLlmProvider::response(
+            vec![
+                ContentBlock::tool_use("toolu_a", "Bash", serde_json::json!({"command": "sleep 0.1"})),
+                ContentBlock::tool_use("toolu_b", "Bash", serde_json::json!({"command": "sleep 0.1"})),
+            ],
+            StopReason::ToolUse,
+        ));
+        let config = AgentConfig::new("Test")
+            .with_tools(Arc::new(registry))
+            .with_dangerous_skip_permissions(true)
+            .with_turn_timeout(Duration::from_secs(60));
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+        let mut rx = handle.subscribe();
+
+        // The per-message timeout wins over the config's: the deadline passes
+        // while toolu_a runs, so toolu_b is skipped and no second call is made
+        let options = InputOptions::new().with_turn_timeout(Duration::from_millis(50));
+        handle.send_input_with_options("loop forever", options).await.unwrap();
+        let chunks = until_done(&mut rx).await;
+        assert!(chunks
+            .iter()
+            .any(|c| matches!(c, OutputChunk::Status(s) if s == "Turn timed out after 50ms")));
+        assert!(matches!(chunks.last(), Some(OutputChunk::Done)));
+        assert_eq!(llm.call_count(), 1);
+
+        let session = AgentSession::load_with_storage("loop-test", SessionStorage::with_dir(dir.path())).unwrap();
+        let history = session.history();
+        assert_eq!(history.len(), 4);
+        assert!(matches!(&history[3].content, MessageContent::Text(text) if text.contains("Turn timed out")));
+        assert!(tool_result_text_in(&history[2], "toolu_a").contains("exit_code: 0"));
+        assert!(tool_result_text_in(&history[2], "toolu_b").contains(TURN_TIMED_OUT));
+        runtime.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_project_memory_is_a_cached_system_block() {
+        let work = tempfile::tempdir().unwrap();
+        std::fs::write(work.path().join("AGENTS.md"), "Always answer in French\n").unwrap();
+        let memory = crate::helpers::MemoryConfig::new()
+            .with_file_names(["AGENTS.md"])
+            .with_start_dir(work.path());
+
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new().with_text("Bonjour").with_text("Salut");
+        let config = AgentConfig::new("Test").with_project_memory(memory);
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+        let mut rx = handle.subscribe();
+
+        handle.send_input("Hi").await.unwrap();
+        while !matches!(rx.recv().await.unwrap(), OutputChunk::Done) {}
+        // Picked up at the start of the next turn
+        std::fs::write(work.path().join("AGENTS.md"), "Always answer in German\n").unwrap();
+        handle.send_input("Hi again").await.unwrap();
+        while !matches!(rx.recv().await.unwrap(), OutputChunk::Done) {}
+
+        let requests = llm.requests();
+        for (request, language) in requests.iter().zip(["French", "German"]) {
+            let Some(SystemPrompt::Blocks(blocks)) = &request.system else {
+                panic!("expected system blocks, got {:?}", request.system);
+            };
+            assert_eq!(blocks.len(), 2);
+            assert!(blocks[0].text.starts_with("Test") && blocks[0].cache_control.is_none());
+            assert!(blocks[1].text.contains(&format!("Always answer in {}", language)));
+            assert!(blocks[1].cache_control.is_some());
+        }
+        runtime.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_hooks_see_history() {
+        let work = tempfile::tempdir().unwrap();
+        let path = work.path().join("notes.txt").to_string_lossy().to_string();
+        let write = |id: &str| {
+            MockLlmProvider::tool_use_response(
+                id,
+                "Write",
+                serde_json::json!({"file_path": path, "content": id, "overwrite": true}),
+            )
+        };
+        let llm = MockLlmProvider::new()
+            .with_response(write("toolu_1"))
+            .with_response(write("toolu_2"))
+            .with_response(write("toolu_3"))
+            .with_text("done");
+
+        let mut hooks = crate::hooks::HookRegistry::new();
+        hooks.add_with_pattern(crate::hooks::HookEvent::PreToolUse, "Write", |ctx: &mut HookContext| {
+            let target = ctx.tool_input.as_ref().unwrap()["file_path"].clone();
+            let writes = ctx
+                .previous_tool_calls("Write")
+                .iter()
+                .filter(|(_, input)| input["file_path"] == target)
+                .count();
+            if writes >= 2 {
+                crate::hooks::HookResult::deny(format!("already written {} times (turn {})", writes, ctx.current_turn()))
+            } else {
+                crate::hooks::HookResult::none()
+            }
+        })
+        .unwrap();
+        let mut registry = crate::tools::ToolRegistry::new();
+        registry.register(crate::tools::WriteTool::with_base_dir(work.path().to_string_lossy()));
+        let config = AgentConfig::new("Test")
+            .with_tools(Arc::new(registry))
+            .with_dangerous_skip_permissions(true)
+            .with_hooks(hooks);
+
+        run_turn(config, &llm).await;
+        let requests = llm.requests();
+        assert!(!tool_result_text(&requests[2], "toolu_2").contains("already written"));
+        assert!(tool_result_text(&requests[3], "toolu_3").contains("already written 2 times (turn 0)"));
+        assert_eq!(std::fs::read_to_string(work.path().join("notes.txt")).unwrap(), "toolu_2");
+    }
+
+    #[tokio::test]
+    async fn test_chunks_are_numbered_across_text_and_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        let path = work.path().join("notes.txt").to_string_lossy().to_string();
+        for streaming in [false, true] {
+            let llm = MockLlmProvider::new()
+                .with_response(MockLlmProvider::tool_use_response(
+                    "toolu_1",
+                    "Write",
+                    serde_json::json!({"file_path": path, "content": "hi"}),
+                ))
+                .with_text("Written")
+                .with_text("Again");
+            let mut registry = crate::tools::ToolRegistry::new();
+            registry.register(crate::tools::WriteTool::with_base_dir(work.path().to_string_lossy()));
+            let config = AgentConfig::new("Test")
+                .with_streaming(streaming)
+                .with_tools(Arc::new(registry))
+                .with_dangerous_skip_permissions(true);
+            let runtime = AgentRuntime::new();
+            let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+
+            let mut rx = handle.subscribe();
+            let mut envelopes = Vec::new();
+            for prompt in ["Write it", "Once more"] {
+                handle.send_input(prompt).await.unwrap();
+                loop {
+                    let envelope = tokio::time::timeout(Duration::from_secs(5), rx.recv_envelope())
+                        .await
+                        .unwrap()
+                        .unwrap();
+                    let done = matches!(envelope.chunk, OutputChunk::Done);
+                    envelopes.push(envelope);
+                    if done {
+                        break;
+                    }
+                }
+            }
+            runtime.shutdown_all().await;
+
+            assert!(envelopes.iter().any(|e| matches!(e.chunk, OutputChunk::ToolEnd { .. })));
+            assert!(envelopes.iter().any(|e| matches!(e.chunk, OutputChunk::TextComplete(_))));
+            assert!(envelopes.windows(2).all(|pair| pair[1].seq == pair[0].seq + 1));
+            assert!(envelopes.windows(2).all(|pair| pair[1].timestamp >= pair[0].timestamp));
+            let done_turns: Vec<_> = envelopes
+                .iter()
+                .filter(|e| matches!(e.chunk, OutputChunk::Done))
+                .map(|e| e.turn)
+                .collect();
+            assert_eq!(done_turns, vec![Some(0), Some(1)]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_injection_frequency_across_tool_iterations() {
+        use crate::helpers::{inject_system_reminder, FnInjection, InjectionFrequency};
+
+        let work = tempfile::tempdir().unwrap();
+        let path = work.path().join("notes.txt").to_string_lossy().to_string();
+        let write = |id: &str| {
+            MockLlmProvider::tool_use_response(
+                id,
+                "Write",
+                serde_json::json!({"file_path": path, "content": id, "overwrite": true}),
+            )
+        };
+        let llm = MockLlmProvider::new()
+            .with_response(write("toolu_1"))
+            .with_response(write("toolu_2"))
+            .with_text("first done")
+            .with_response(write("toolu_3"))
+            .with_text("second done");
+
+        let mut registry = crate::tools::ToolRegistry::new();
+        registry.register(crate::tools::WriteTool::with_base_dir(work.path().to_string_lossy()));
+        let config = AgentConfig::new("Test")
+            .with_tools(Arc::new(registry))
+            .with_dangerous_skip_permissions(true)
+            .with_injection_fn("iteration", |internals, mut messages| {
+                let note = format!("iteration {}", internals.context.current_iteration);
+                inject_system_reminder(&mut messages, &note);
+                messages
+            })
+            .with_injection(
+                FnInjection::new("turn_reminder", |_, mut messages| {
+                    inject_system_reminder(&mut messages, "TURN REMINDER");
+                    messages
+                })
+                .with_frequency(InjectionFrequency::OncePerTurn),
+            )
+            .with_injection(
+                FnInjection::new("session_note", |_, mut messages| {
+                    inject_system_reminder(&mut messages, "SESSION NOTE");
+                    messages
+                })
+                .with_frequency(InjectionFrequency::OncePerSession)
+                .with_priority(10),
+            );
+
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+        let mut rx = handle.subscribe();
+        handle.send_input("First").await.unwrap();
+        while !matches!(rx.recv().await.unwrap(), OutputChunk::Done) {}
+        handle.send_input("Second").await.unwrap();
+        while !matches!(rx.recv().await.unwrap(), OutputChunk::Done) {}
+        runtime.shutdown_all().await;
+
+        let requests: Vec<String> = llm
+            .requests()
+            .iter()
+            .map(|r| serde_json::to_string(&r.messages).unwrap())
+            .collect();
+        assert_eq!(requests.len(), 5);
+        let with = |needle: &str| -> Vec<usize> {
+            (0..requests.len()).filter(|&i| requests[i].contains(needle)).collect()
+        };
+        // Injections aren't saved, so each reminder is only in the requests it was added to;
+        // a once-per-turn one is in every request of the turn, next to its prompt
+        assert_eq!(with("TURN REMINDER"), vec![0, 1, 2, 3, 4]);
+        let prompt_reminder = |i: usize| {
+            let messages = serde_json::to_value(&llm.requests()[i].messages).unwrap();
+            messages[0].to_string().contains("TURN REMINDER")
+        };
+        assert!(prompt_reminder(1) && prompt_reminder(2));
+        assert_eq!(with("SESSION NOTE"), vec![0]);
+        assert_eq!(with("iteration 1"), vec![0, 3]);
+        assert_eq!(with("iteration 3"), vec![2]);
+        // Higher priority runs first
+        let first = &requests[0];
+        assert!(first.find("SESSION NOTE").unwrap() < first.find("iteration 1").unwrap());
+    }
+
+    /// Spawn an agent with its own session ID
+    async fn spawn_named(
+        runtime: &AgentRuntime,
+        dir: &tempfile::TempDir,
+        session_id: &str,
+        config: AgentConfig,
+        llm: &MockLlmProvider,
+    ) -> crate::runtime::AgentHandle {
+        let storage = SessionStorage::with_dir(dir.path());
+        let session = AgentSession::new_with_storage(session_id, "test", "Test", "Messaging test", storage).unwrap();
+        let agent = StandardAgent::new(config.with_auto_name(false), Arc::new(llm.clone()));
+        runtime.spawn(session, move |internals| agent.run(internals)).await
+    }
+
+    #[tokio::test]
+    async fn test_agent_message_lands_in_recipient_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let coder_llm = MockLlmProvider::new().with_text("Will do");
+        let coder = spawn_named(&runtime, &dir, "coder-1", AgentConfig::new("Coder"), &coder_llm).await;
+        runtime.register_alias("coder", "coder-1").await.unwrap();
+        let mut coder_rx = coder.subscribe();
+
+        let send = |id: &str, to: &str| {
+            MockLlmProvider::tool_use_response(id, "SendToAgent", serde_json::json!({"to": to, "message": "Add a test"}))
+        };
+        let reviewer_llm = MockLlmProvider::new()
+            .with_response(send("toolu_1", "coder"))
+            .with_response(send("toolu_2", "ghost"))
+            .with_text("Sent");
+        let mut registry = crate::tools::ToolRegistry::new();
+        registry.register(crate::tools::SendToAgentTool::new());
+        let config = AgentConfig::new("Reviewer")
+            .with_tools(Arc::new(registry))
+            .with_dangerous_skip_permissions(true);
+        let reviewer = spawn_named(&runtime, &dir, "reviewer-1", config, &reviewer_llm).await;
+        let mut reviewer_rx = reviewer.subscribe();
+        reviewer.send_input("Review the change").await.unwrap();
+        while !matches!(reviewer_rx.recv().await.unwrap(), OutputChunk::Done) {}
+        while !matches!(coder_rx.recv().await.unwrap(), OutputChunk::Done) {}
+
+        let requests = reviewer_llm.requests();
+        assert!(tool_result_text(&requests[1], "toolu_1").contains("Message delivered to coder"));
+        assert!(tool_result_text(&requests[2], "toolu_2").contains("Agent not running: ghost"));
+
+        let saved = AgentSession::load_with_storage("coder-1", SessionStorage::with_dir(dir.path())).unwrap();
+        let history = saved.history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(
+            history[0].text().unwrap(),
+            "<agent-message from=\"reviewer-1\">\nAdd a test\n</agent-message>"
+        );
+        assert_eq!(coder_llm.call_count(), 1);
+
+        runtime.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_agent_message_during_turn_waits_for_it_to_end() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new()
+            .with_response(bash_call("toolu_1"))
+            .with_text("Done with the command")
+            .with_text("Got your note");
+        let config = permission_timeout_config(Duration::from_secs(30), TimeoutDecision::Deny);
+        let handle = spawn_named(&runtime, &dir, "coder-1", config, &llm).await;
+        let mut rx = handle.subscribe();
+        handle.send_input("Run it").await.unwrap();
+
+        let mut done = 0;
+        while done < 2 {
+            match rx.recv().await.unwrap() {
+                OutputChunk::PermissionRequest { request_id, .. } => {
+                    // Arrives while the agent waits for permission
+                    runtime
+                        .send_to("coder-1", InputMessage::agent_message("reviewer-1", "Looks good"))
+                        .await
+                        .unwrap();
+                    handle.send_permission_response_by_id(request_id, true, false).await.unwrap();
+                }
+                OutputChunk::Done => done += 1,
+                _ => {}
+            }
+        }
+
+        let requests = llm.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(!tool_result_text(&requests[1], "toolu_1").contains("Unexpected message"));
+        let last = serde_json::to_string(requests[2].messages.last().unwrap()).unwrap();
+        assert!(last.contains("<agent-message from=\\\"reviewer-1\\\">\\nLooks good"));
+
+        runtime.shutdown_all().await;
+        runtime.wait_for("coder-1").await.unwrap();
+        assert!(matches!(
+            runtime.send_to("coder-1", InputMessage::agent_message("reviewer-1", "Still there?")).await,
+            Err(crate::core::FrameworkError::AgentNotRunning(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_permission_request_is_denied() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new()
+            .with_response(bash_call("toolu_1"))
+            .with_text("ok");
+
+        let failures = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = failures.clone();
+        let mut hooks = crate::hooks::HookRegistry::new();
+        hooks.add(crate::hooks::HookEvent::PostToolUseFailure, move |ctx: &mut HookContext| {
+            seen.lock().unwrap().push(ctx.error.clone().unwrap());
+            crate::hooks::HookResult::none()
+        });
+        let config = permission_timeout_config(Duration::from_millis(50), TimeoutDecision::Deny)
+            .with_hooks(hooks);
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+        let mut rx = handle.subscribe();
+        handle.send_input("Run it").await.unwrap();
+
+        let mut statuses = Vec::new();
+        loop {
+            match rx.recv().await.unwrap() {
+                OutputChunk::Status(status) => statuses.push(status),
+                OutputChunk::Done => break,
+                _ => {}
+            }
+        }
+        assert!(statuses.contains(&"Permission request for Bash timed out, denying".to_string()));
+        assert_eq!(*failures.lock().unwrap(), vec![PERMISSION_TIMED_OUT.to_string()]);
+
+        let requests = llm.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(tool_result_text(&requests[1], "toolu_1").contains("permission request timed out"));
+
+        runtime.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_late_permission_response_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new()
+            .with_response(bash_call("toolu_1"))
+            .with_response(bash_call("toolu_2"))
+            .with_text("done");
+        let config = permission_timeout_config(Duration::from_millis(300), TimeoutDecision::Deny);
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+        let mut rx = handle.subscribe();
+        handle.send_input("Run it twice").await.unwrap();
+
+        let mut request_ids: Vec<String> = Vec::new();
+        loop {
+            match rx.recv().await.unwrap() {
+                // The first request times out, then its answer turns up
+                OutputChunk::Status(status) if status.contains("timed out") => {
+                    handle.send_permission_response_by_id(request_ids[0].clone(), true, false).await.unwrap();
+                }
+                // The second request is answered in time and denied
+                OutputChunk::PermissionRequest { request_id, .. } => {
+                    request_ids.push(request_id.clone());
+                    if request_ids.len() == 2 {
+                        handle.send_permission_response_by_id(request_id, false, false).await.unwrap();
+                    }
+                }
+                OutputChunk::Done => break,
+                _ => {}
+            }
+        }
+
+        let requests = llm.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(tool_result_text(&requests[1], "toolu_1").contains("permission request timed out"));
+        // The late "allow" was not applied to the second call
+        assert!(tool_result_text(&requests[2], "toolu_2").contains("User denied permission"));
+
+        runtime.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_permission_timeout_can_end_turn() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new()
+            .with_response(bash_call("toolu_1"))
+            .with_text("Never sent");
+        let config =
+            permission_timeout_config(Duration::from_millis(50), TimeoutDecision::AbortTurn);
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+        let mut rx = handle.subscribe();
+        handle.send_input("Run it").await.unwrap();
+        while !matches!(rx.recv().await.unwrap(), OutputChunk::Done) {}
+
+        runtime.shutdown_all().await;
+
+        assert_eq!(llm.call_count(), 1);
+        let session =
+            AgentSession::load_with_storage("loop-test", SessionStorage::with_dir(dir.path()))
+                .unwrap();
+        let last = serde_json::to_string(session.history().last().unwrap()).unwrap();
+        assert!(last.contains("turn ended"), "{}", last);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_interrupt_cancels_running_bash() {
+        let dir = tempfile::tempdir().unwrap();
+        let work = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new()
+            .with_response(MockLlmProvider::tool_use_response(
+                "toolu_1",
+                "Bash",
+                serde_json::json!({"command": "(sleep 1; touch late) & touch started; wait"}),
+            ))
+            .with_text("Still here");
+        let mut registry = crate::tools::ToolRegistry::new();
+        registry.register(crate::tools::BashTool::with_working_dir(work.path().to_string_lossy()));
+        let config = AgentConfig::new("Test").with_tools(Arc::new(registry));
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+        handle.add_session_rule(crate::permissions::PermissionRule::allow_tool("Bash"));
+
+        let mut rx = handle.subscribe();
+        handle.send_input("Run it").await.unwrap();
+        while !work.path().join("started").exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        handle.interrupt().await.unwrap();
+        let chunks = tokio::time::timeout(Duration::from_millis(500), until_done(&mut rx))
+            .await
+            .expect("the turn ends without waiting for the command");
+        assert!(chunks.iter().any(|c| matches!(c, OutputChunk::ToolEnd { result, .. } if result.is_interrupted())));
+        assert_eq!(llm.call_count(), 1);
+
+        // The agent took the interrupt as ending the turn, not itself
+        handle.send_input("Again").await.unwrap();
+        let chunks = until_done(&mut rx).await;
+        assert!(chunks.iter().any(|c| matches!(c, OutputChunk::TextComplete(text) if text == "Still here")));
+
+        // The command's background job died with it
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+        assert!(!work.path().join("late").exists());
+        runtime.shutdown_all().await;
+
+        // Recorded like tools skipped after an interrupt
+        let session =
+            AgentSession::load_with_storage("loop-test", SessionStorage::with_dir(dir.path()))
+                .unwrap();
+        let history: Vec<String> = session
+            .history()
+            .iter()
+            .map(|message| serde_json::to_string(message).unwrap())
+            .collect();
+        assert!(history[2].contains("\"is_error\":true") && history[2].contains("Interrupted"), "{}", history[2]);
+        assert!(history[3].contains("User interrupted this message"), "{}", history[3]);
+    }
+
+    /// Receive chunks until the turn is done, keeping them
+    async fn until_done(rx: &mut crate::runtime::OutputReceiver) -> Vec<OutputChunk> {
+        let mut chunks = Vec::new();
+        loop {
+            let chunk = rx.recv().await.unwrap();
+            let done = matches!(chunk, OutputChunk::Done);
+            chunks.push(chunk);
+            if done {
+                return chunks;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rewind_snaps_to_turn_start_and_resends() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        // No tools are configured, so the Grep call just fails
+        let llm = MockLlmProvider::new()
+            .with_text("first answer")
+            .with_response(MockLlmProvider::tool_use_response(
+                "toolu_1",
+                "Grep",
+                serde_json::json!({"pattern": "x"}),
+            ))
+            .with_text("second answer")
+            .with_text("rewritten answer");
+        let handle = spawn_agent(&runtime, &dir, AgentConfig::new("Test"), &llm).await;
+        let mut rx = handle.subscribe();
+        handle.send_input("first").await.unwrap();
+        until_done(&mut rx).await;
+        handle.send_input("second").await.unwrap();
+        until_done(&mut rx).await;
+
+        // Message 4 is the Grep result; the rewind goes back to its prompt
+        handle.rewind_and_send(4, "edited").await.unwrap();
+        let chunks = until_done(&mut rx).await;
+        assert!(chunks
+            .iter()
+            .any(|c| matches!(c, OutputChunk::HistoryTruncated { new_len: 2 })));
+
+        let requests = llm.requests();
+        assert_eq!(requests.len(), 4);
+        let sent = serde_json::to_string(&requests[3].messages).unwrap();
+        assert_eq!(requests[3].messages.len(), 3);
+        assert!(sent.contains("edited") && !sent.contains("second"), "{}", sent);
+
+        runtime.shutdown_all().await;
+        let session =
+            AgentSession::load_with_storage("loop-test", SessionStorage::with_dir(dir.path()))
+                .unwrap();
+        let history = serde_json::to_string(session.history()).unwrap();
+        assert_eq!(session.history().len(), 4);
+        assert!(history.contains("rewritten answer") && !history.contains("toolu_1"));
+    }
+
+    /// Run one turn in the "resume-test" session with a new agent, as after a restart
+    async fn run_restarted(dir: &tempfile::TempDir, config: AgentConfig, llm: &MockLlmProvider) -> Vec<OutputChunk> {
+        let storage = SessionStorage::with_dir(dir.path());
+        let session = match AgentSession::load_with_storage("resume-test", storage.clone()) {
+            Ok(session) => session,
+            Err(_) => AgentSession::new_with_storage("resume-test", "test", "Test", "Resume test", storage).unwrap(),
+        };
+        let runtime = AgentRuntime::new();
+        let agent = StandardAgent::new(config, Arc::new(llm.clone()));
+        let handle = runtime.spawn(session, move |internals| agent.run(internals)).await;
+        let mut rx = handle.subscribe();
+        handle.send_input("Hello").await.unwrap();
+        let chunks = until_done(&mut rx).await;
+        runtime.shutdown_all().await;
+        chunks
+    }
+
+    fn completed_turn(chunks: &[OutputChunk]) -> usize {
+        chunks
+            .iter()
+            .find_map(|c| match c {
+                OutputChunk::TurnComplete { turn, .. } => Some(*turn),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_resumed_session_continues_the_turn_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let namer = MockLlmProvider::new().with_text("Greeting chat");
+        let config = || AgentConfig::new("Test").with_naming_llm(Arc::new(namer.clone()));
+
+        let chunks = run_restarted(&dir, config(), &MockLlmProvider::new().with_text("Hi")).await;
+        assert_eq!(completed_turn(&chunks), 0);
+        assert_eq!(namer.call_count(), 1);
+
+        // A blocked prompt doesn't count as a turn
+        let mut hooks = crate::hooks::HookRegistry::new();
+        hooks.add(crate::hooks::HookEvent::UserPromptSubmit, |_: &mut HookContext| {
+            crate::hooks::HookResult::deny("not now")
+        });
+        run_restarted(&dir, config().with_hooks(hooks), &MockLlmProvider::new()).await;
+
+        // Restarting neither resets the count nor names the conversation again
+        let chunks = run_restarted(&dir, config(), &MockLlmProvider::new().with_text("Hi again")).await;
+        assert_eq!(completed_turn(&chunks), 1);
+        assert_eq!(namer.call_count(), 1);
+
+        let storage = SessionStorage::with_dir(dir.path());
+        let mut session = AgentSession::load_with_storage("resume-test", storage).unwrap();
+        assert_eq!(session.completed_turns(), Some(2));
+        assert_eq!(session.conversation_name(), Some("Greeting chat"));
+
+        // Without a recorded count, the prompts in the history are counted
+        session.set_custom(crate::session::TURNS_METADATA_KEY, serde_json::Value::Null);
+        session.save().unwrap();
+        let chunks = run_restarted(&dir, config(), &MockLlmProvider::new().with_text("Hi")).await;
+        assert_eq!(completed_turn(&chunks), 2);
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_is_kept_unless_the_config_sets_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = || AgentConfig::new("Test").with_auto_name(false);
+        let dry_run = || {
+            let session = AgentSession::load_with_storage("resume-test", SessionStorage::with_dir(dir.path())).unwrap();
+            session.get_custom("dry_run").and_then(|v| v.as_bool())
+        };
+
+        run_restarted(&dir, config(), &MockLlmProvider::new().with_text("Hi")).await;
+        assert_eq!(dry_run(), None);
+        run_restarted(&dir, config().with_dry_run(true), &MockLlmProvider::new().with_text("Hi")).await;
+        assert_eq!(dry_run(), Some(true));
+        run_restarted(&dir, config(), &MockLlmProvider::new().with_text("Hi")).await;
+        assert_eq!(dry_run(), Some(true));
+        run_restarted(&dir, config().with_dry_run(false), &MockLlmProvider::new().with_text("Hi")).await;
+        assert_eq!(dry_run(), Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_turn_gated_injection_across_restarts() {
+        use crate::helpers::{inject_system_reminder, FnInjection, InjectionFrequency};
+
+        let config = || {
+            AgentConfig::new("Test").with_auto_name(false).with_injection(
+                FnInjection::new("welcome", |internals, mut messages| {
+                    if internals.turn() == 0 {
+                        inject_system_reminder(&mut messages, "WELCOME");
+                    }
+                    let note = format!("TURN {}", internals.turn());
+                    inject_system_reminder(&mut messages, &note);
+                    messages
+                })
+                .with_frequency(InjectionFrequency::OncePerTurn),
+            )
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let llm = MockLlmProvider::new().with_text("one").with_text("two");
+        run_restarted(&dir, config(), &llm).await;
+        run_restarted(&dir, config(), &llm).await;
+
+        let requests: Vec<String> = llm
+            .requests()
+            .iter()
+            .map(|r| serde_json::to_string(&r.messages).unwrap())
+            .collect();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].contains("WELCOME") && requests[0].contains("TURN 0"));
+        assert!(!requests[1].contains("WELCOME") && requests[1].contains("TURN 1"), "{}", requests[1]);
+    }
+
+    /// Shut the runtime down while the agent is busy and wait for it to exit
+    async fn shut_down_busy_agent(
+        runtime: &AgentRuntime,
+        handle: &crate::runtime::AgentHandle,
+        rx: &mut crate::runtime::OutputReceiver,
+    ) -> Vec<OutputChunk> {
+        runtime.shutdown_all().await;
+        tokio::time::timeout(Duration::from_secs(5), runtime.wait_for(handle.session_id()))
+            .await
+            .expect("agent didn't stop after shutdown")
+            .unwrap();
+        until_done(rx).await
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_stops_a_hung_llm_call() {
+        for streaming in [false, true] {
+            let dir = tempfile::tempdir().unwrap();
+            let runtime = AgentRuntime::new();
+            let llm = MockLlmProvider::new().with_hang();
+            let config = AgentConfig::new("Test").with_streaming(streaming);
+            let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+            let mut rx = handle.subscribe();
+            handle.send_input("Hello").await.unwrap();
+            while llm.call_count() == 0 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+
+            let chunks = shut_down_busy_agent(&runtime, &handle, &mut rx).await;
+            assert!(chunks
+                .iter()
+                .any(|c| matches!(c, OutputChunk::Status(s) if s == "Shutting down")));
+            assert!(!chunks.iter().any(|c| matches!(c, OutputChunk::Error(_))), "{:?}", chunks);
+
+            let session = AgentSession::load_with_storage("loop-test", SessionStorage::with_dir(dir.path())).unwrap();
+            assert_eq!(session.history().len(), 1);
+            assert_eq!(session.completed_turns(), Some(1));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_keeps_the_text_of_a_stalled_stream() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        // Message start, text block start and the text delta, then nothing
+        let llm = MockLlmProvider::new().with_stalled_stream(MockLlmProvider::text_response("partial answer"), 3);
+        let config = AgentConfig::new("Test").with_streaming(true);
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+        let mut rx = handle.subscribe();
+        handle.send_input("Hello").await.unwrap();
+        while !matches!(rx.recv().await.unwrap(), OutputChunk::TextDelta(_)) {}
+
+        shut_down_busy_agent(&runtime, &handle, &mut rx).await;
+        let session = AgentSession::load_with_storage("loop-test", SessionStorage::with_dir(dir.path())).unwrap();
+        let history = serde_json::to_string(session.history()).unwrap();
+        assert_eq!(session.history().len(), 2);
+        assert!(history.contains("partial answer"), "{}", history);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_cancels_a_running_tool() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new().with_response(MockLlmProvider::tool_use_response(
+            "toolu_1",
+            "Bash",
+            serde_json::json!({"command": "sleep 30"}),
+        ));
+        let mut registry = crate::tools::ToolRegistry::new();
+        registry.register(crate::tools::BashTool::new().unwrap());
+        let config = AgentConfig::new("Test")
+            .with_tools(Arc::new(registry))
+            .with_dangerous_skip_permissions(true);
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+        let mut rx = handle.subscribe();
+        handle.send_input("Wait").await.unwrap();
+        while !matches!(rx.recv().await.unwrap(), OutputChunk::ToolStart { .. }) {}
+
+        let started = Instant::now();
+        shut_down_busy_agent(&runtime, &handle, &mut rx).await;
+        assert!(started.elapsed() < Duration::from_secs(10));
+        // The call is answered like an interrupted one, and no further LLM call was made
+        assert_eq!(llm.call_count(), 1);
+        let session = AgentSession::load_with_storage("loop-test", SessionStorage::with_dir(dir.path())).unwrap();
+        assert_eq!(session.history().len(), 4, "{:?}", session.history());
+        assert_eq!(tool_result_text_in(&session.history()[2], "toolu_1"), "\"Interrupted\"");
+    }
+
+    #[tokio::test]
+    async fn test_rewind_past_end_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new().with_text("answer");
+        let handle = spawn_agent(&runtime, &dir, AgentConfig::new("Test"), &llm).await;
+        let mut rx = handle.subscribe();
+        handle.send_input("hello").await.unwrap();
+        until_done(&mut rx).await;
+
+        handle.rewind_and_send(2, "edited").await.unwrap();
+        let chunks = until_done(&mut rx).await;
+        assert!(chunks
+            .iter()
+            .any(|c| matches!(c, OutputChunk::Error(e) if e.starts_with("Rewind failed"))));
+        assert_eq!(llm.call_count(), 1);
+
+        runtime.shutdown_all().await;
+    }
+
+    #[test]
+    fn test_user_turn_start_skips_tool_results() {
+        let history = vec![
+            Message::user("first"),
+            Message::assistant("answer"),
+            Message::user("second"),
+            Message::assistant_with_blocks(vec![ContentBlock::ToolUse {
+                id: "toolu_1".into(),
+                name: "Grep".into(),
+                input: serde_json::json!({}),
+            }]),
+            Message::user_with_blocks(vec![ContentBlock::tool_result("toolu_1", "ok", false)]),
+        ];
+        assert_eq!(user_turn_start(&history, 1), 0);
+        assert_eq!(user_turn_start(&history, 2), 2);
+        assert_eq!(user_turn_start(&history, 4), 2);
+    }
+
+    #[tokio::test]
+    async fn test_revoked_rule_prompts_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new()
+            .with_response(bash_call("toolu_1"))
+            .with_text("first")
+            .with_response(bash_call("toolu_2"))
+            .with_text("second")
+            .with_response(bash_call("toolu_3"))
+            .with_text("third");
+        let config = permission_timeout_config(Duration::from_secs(30), TimeoutDecision::Deny);
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+        let mut rx = handle.subscribe();
+
+        let mut prompts = Vec::new();
+        for turn in 0..3 {
+            if turn == 2 {
+                // Revoke the "always allow" chosen in the first turn
+                let rules = handle.permission_rules().rules(crate::permissions::PermissionScope::Session);
+                assert_eq!(rules.len(), 1);
+                assert!(handle.remove_rule(rules[0].0));
+            }
+            handle.send_input("Run it").await.unwrap();
+            let mut asked = 0;
+            loop {
+                match rx.recv().await.unwrap() {
+                    OutputChunk::PermissionRequest { request_id, .. } => {
+                        asked += 1;
+                        handle.send_permission_response_by_id(request_id, true, true).await.unwrap();
+                    }
+                    OutputChunk::Done => break,
+                    _ => {}
+                }
+            }
+            prompts.push(asked);
+        }
+        assert_eq!(prompts, vec![1, 0, 1]);
+
+        runtime.shutdown_all().await;
+    }
+
+    fn output_schema() -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {"answer": {"type": "integer"}},
+            "required": ["answer"]
+        })
+    }
+
+    #[tokio::test]
+    async fn test_structured_result_retries_invalid_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new()
+            .with_text("The answer is {\"answer\": \"forty-two\"}")
+            .with_text("```json\n{\"answer\": 42}\n```");
+        let config = AgentConfig::new("Test").with_output_schema(output_schema());
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+
+        let result = handle.wait_for_structured_result("What is it?").await.unwrap();
+        assert_eq!(result, serde_json::json!({"answer": 42}));
+        assert_eq!(
+            handle.get_custom_metadata(STRUCTURED_RESULT_METADATA_KEY).await,
+            Some(result)
+        );
+
+        let requests = llm.requests();
+        assert_eq!(requests.len(), 2);
+        let system = serde_json::to_string(&requests[0].system).unwrap();
+        assert!(system.contains("# Output format"));
+        let correction = serde_json::to_string(requests[1].messages.last().unwrap()).unwrap();
+        assert!(correction.contains("does not match the schema"), "{}", correction);
+
+        runtime.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_json_response_format_takes_the_output_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new().with_text("{\"answer\": 42}");
+        let config = AgentConfig::new("Test")
+            .with_output_schema(output_schema())
+            .with_response_format(ResponseFormat::json());
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+
+        let result = handle.wait_for_structured_result("What is it?").await.unwrap();
+        assert_eq!(result, serde_json::json!({"answer": 42}));
+
+        // Valid JSON on the first try: no correction round
+        let requests = llm.requests();
+        assert_eq!(requests.len(), 1);
+        let format = requests[0].generation.as_ref().and_then(|g| g.response_format.clone());
+        assert_eq!(format, Some(ResponseFormat::json_schema(output_schema())));
+
+        runtime.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_structured_result_gives_up_after_retries() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new().with_fallback(MockLlmProvider::text_response("No JSON"));
+        let config = AgentConfig::new("Test")
+            .with_output_schema(output_schema())
+            .with_output_retries(1);
+        let handle = spawn_agent(&runtime, &dir, config, &llm).await;
+
+        let err = handle.wait_for_structured_result("What is it?").await.unwrap_err();
+        assert!(err.to_string().contains("Structured output failed validation"), "{}", err);
+        assert_eq!(llm.call_count(), 2);
+
+        runtime.shutdown_all().await;
+    }
+}
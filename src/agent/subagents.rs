@@ -0,0 +1,581 @@
+//! Subagent definitions loaded from markdown files
+//!
+//! Each `*.md` file in a directory (e.g. `.claude/agents/`) defines one
+//! subagent type. The frontmatter holds its metadata and the body is its
+//! system prompt:
+//!
+//! ```text
+//! ---
+//! name: code-reviewer
+//! description: Reviews diffs for bugs and style issues
+//! tools: Read, Grep, Glob
+//! model: claude-haiku-4-5-20251001
+//! ---
+//! You are a meticulous code reviewer...
+//! ```
+//!
+//! - `name` and `description` are required
+//! - `tools` is a comma-separated or YAML list; omit it to give the subagent every tool
+//! - `model` is optional; omit it (or use `inherit`) to use the parent's model
+//!
+//! Only flat `key: value` frontmatter is supported; unknown keys are ignored.
+//!
+//! `SubAgentRegistry` holds the definitions, re-reads the directory when files
+//! change, and renders the list of subagent types for the system prompt. The
+//! `TaskTool` uses it to spawn subagents.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let subagents = Arc::new(SubAgentRegistry::from_dir(".claude/agents", tools.clone())?);
+//!
+//! let mut registry = tools::create_registry()?;
+//! registry.register(TaskTool::new(subagents.clone(), llm.clone(), tools.clone()));
+//!
+//! let config = AgentConfig::new(SYSTEM_PROMPT)
+//!     .with_tools(Arc::new(registry))
+//!     .with_subagents(subagents);
+//! ```
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use anyhow::{bail, Context, Result};
+
+use crate::tools::ToolRegistry;
+
+/// A subagent type defined in a markdown file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubAgentDefinition {
+    /// Subagent type name (used as `subagent_type` in the Task tool)
+    pub name: String,
+
+    /// When the main agent should use this subagent
+    pub description: String,
+
+    /// Tools the subagent may use (None = all tools)
+    pub tools: Option<Vec<String>>,
+
+    /// Model override (None = the parent's model)
+    pub model: Option<String>,
+
+    /// System prompt (the markdown body)
+    pub system_prompt: String,
+
+    /// File the definition was loaded from
+    pub source: Option<PathBuf>,
+}
+
+impl SubAgentDefinition {
+    /// Create a definition in code
+    pub fn new(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        system_prompt: impl Into<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            tools: None,
+            model: None,
+            system_prompt: system_prompt.into(),
+            source: None,
+        }
+    }
+
+    /// Restrict the subagent to these tools
+    pub fn with_tools<S: Into<String>>(mut self, tools: impl IntoIterator<Item = S>) -> Self {
+        self.tools = Some(tools.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Run the subagent on a different model
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Parse a definition from markdown with frontmatter
+    pub fn parse(text: &str) -> Result<Self> {
+        let (fields, body) = split_frontmatter(text)?;
+
+        let mut name = None;
+        let mut description = None;
+        let mut tools = None;
+        let mut model = None;
+        for (key, value) in fields {
+            match key.as_str() {
+                "name" => name = value.into_scalar(),
+                "description" => description = value.into_scalar(),
+                "tools" => tools = Some(value.into_list()),
+                "model" => model = value.into_scalar().filter(|m| m != "inherit"),
+                _ => {}
+            }
+        }
+
+        let name = name.filter(|n| !n.is_empty()).context("missing required field 'name'")?;
+        if name.contains(char::is_whitespace) {
+            bail!("name '{}' must not contain whitespace", name);
+        }
+        let description = description
+            .filter(|d| !d.is_empty())
+            .context("missing required field 'description'")?;
+        let system_prompt = body.trim().to_string();
+        if system_prompt.is_empty() {
+            bail!("missing system prompt (the body after the frontmatter is empty)");
+        }
+
+        Ok(Self {
+            name,
+            description,
+            tools,
+            model,
+            system_prompt,
+            source: None,
+        })
+    }
+
+    /// Check that every listed tool exists in the registry
+    pub fn validate(&self, registry: &ToolRegistry) -> Result<()> {
+        for tool in self.tools.iter().flatten() {
+            if registry.get(tool).is_none() {
+                bail!("subagent '{}' lists unknown tool '{}'", self.name, tool);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Load and validate every `*.md` definition in a directory
+///
+/// Files are read in name order. Fails on the first invalid file, naming it.
+pub fn load_subagent_definitions(
+    dir: impl AsRef<Path>,
+    registry: &ToolRegistry,
+) -> Result<Vec<SubAgentDefinition>> {
+    let mut definitions: Vec<SubAgentDefinition> = Vec::new();
+    let mut names = HashSet::new();
+
+    for path in markdown_files(dir.as_ref())? {
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut definition = SubAgentDefinition::parse(&text)
+            .and_then(|d| d.validate(registry).map(|_| d))
+            .with_context(|| format!("Invalid subagent definition {}", path.display()))?;
+
+        if !names.insert(definition.name.clone()) {
+            bail!(
+                "Duplicate subagent name '{}' in {}",
+                definition.name,
+                path.display()
+            );
+        }
+        definition.source = Some(path);
+        definitions.push(definition);
+    }
+
+    Ok(definitions)
+}
+
+// ============================================================================
+// Registry
+// ============================================================================
+
+/// Where definitions are loaded from, and the state of the files last loaded
+struct DirSource {
+    dir: PathBuf,
+    tools: Arc<ToolRegistry>,
+    fingerprint: RwLock<Vec<(PathBuf, Option<SystemTime>, u64)>>,
+}
+
+/// The set of subagent types available to an agent
+///
+/// Backed by a directory (`from_dir`) or filled in code (`new` + `register`).
+/// Directory-backed registries pick up added, edited and removed files via
+/// `reload` or `reload_if_changed`.
+pub struct SubAgentRegistry {
+    definitions: RwLock<Vec<SubAgentDefinition>>,
+    source: Option<DirSource>,
+}
+
+impl SubAgentRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self {
+            definitions: RwLock::new(Vec::new()),
+            source: None,
+        }
+    }
+
+    /// Load all definitions from a directory, validating tools against `tools`
+    pub fn from_dir(dir: impl Into<PathBuf>, tools: Arc<ToolRegistry>) -> Result<Self> {
+        let registry = Self {
+            definitions: RwLock::new(Vec::new()),
+            source: Some(DirSource {
+                dir: dir.into(),
+                tools,
+                fingerprint: RwLock::new(Vec::new()),
+            }),
+        };
+        registry.reload()?;
+        Ok(registry)
+    }
+
+    /// Add or replace a definition
+    pub fn register(&self, definition: SubAgentDefinition) {
+        let mut definitions = self.definitions.write().unwrap();
+        definitions.retain(|d| d.name != definition.name);
+        definitions.push(definition);
+    }
+
+    /// Re-read the directory, returning the number of definitions
+    ///
+    /// On error the previously loaded definitions are kept.
+    pub fn reload(&self) -> Result<usize> {
+        let Some(source) = &self.source else {
+            return Ok(self.len());
+        };
+
+        let fingerprint = fingerprint(&source.dir)?;
+        let definitions = load_subagent_definitions(&source.dir, &source.tools)?;
+        let count = definitions.len();
+
+        *self.definitions.write().unwrap() = definitions;
+        *source.fingerprint.write().unwrap() = fingerprint;
+        tracing::info!(
+            "[SubAgentRegistry] Loaded {} subagent definition(s) from {}",
+            count,
+            source.dir.display()
+        );
+        Ok(count)
+    }
+
+    /// Reload if any file in the directory was added, removed or modified
+    ///
+    /// Returns whether a reload happened. Errors are logged and the previous
+    /// definitions are kept.
+    pub fn reload_if_changed(&self) -> bool {
+        let Some(source) = &self.source else {
+            return false;
+        };
+
+        let Ok(current) = fingerprint(&source.dir) else {
+            return false;
+        };
+        if current == *source.fingerprint.read().unwrap() {
+            return false;
+        }
+        // Remember the new state even if it fails to load, so a broken file
+        // is reported once rather than on every call
+        *source.fingerprint.write().unwrap() = current;
+
+        match self.reload() {
+            Ok(_) => true,
+            Err(e) => {
+                tracing::warn!("[SubAgentRegistry] Reload failed, keeping previous definitions: {:#}", e);
+                false
+            }
+        }
+    }
+
+    /// Get a definition by name
+    pub fn get(&self, name: &str) -> Option<SubAgentDefinition> {
+        self.definitions.read().unwrap().iter().find(|d| d.name == name).cloned()
+    }
+
+    /// All definitions, sorted by name
+    pub fn definitions(&self) -> Vec<SubAgentDefinition> {
+        let mut definitions = self.definitions.read().unwrap().clone();
+        definitions.sort_by(|a, b| a.name.cmp(&b.name));
+        definitions
+    }
+
+    /// Names of all subagent types, sorted
+    pub fn names(&self) -> Vec<String> {
+        self.definitions().into_iter().map(|d| d.name).collect()
+    }
+
+    /// Number of definitions
+    pub fn len(&self) -> usize {
+        self.definitions.read().unwrap().len()
+    }
+
+    /// Whether there are no definitions
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Lines advertising the subagent types (`- name: description`)
+    pub fn describe(&self) -> String {
+        self.definitions()
+            .iter()
+            .map(|d| format!("- {}: {}", d.name, d.description))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// System prompt section listing the subagent types (None if there are none)
+    pub fn prompt_section(&self) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "# Subagents\n\nUse the Task tool to delegate work to one of these subagent types:\n{}",
+            self.describe()
+        ))
+    }
+}
+
+impl Default for SubAgentRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for SubAgentRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SubAgentRegistry")
+            .field("names", &self.names())
+            .field("dir", &self.source.as_ref().map(|s| &s.dir))
+            .finish()
+    }
+}
+
+/// `*.md` files in a directory, sorted by name
+fn markdown_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read subagent directory {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && path.extension().map(|e| e == "md").unwrap_or(false))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Path, modification time and size of every definition file
+fn fingerprint(dir: &Path) -> Result<Vec<(PathBuf, Option<SystemTime>, u64)>> {
+    markdown_files(dir)?
+        .into_iter()
+        .map(|path| {
+            let metadata = std::fs::metadata(&path)?;
+            Ok((path, metadata.modified().ok(), metadata.len()))
+        })
+        .collect()
+}
+
+// ============================================================================
+// Frontmatter
+// ============================================================================
+
+/// A frontmatter value
+enum FieldValue {
+    Scalar(String),
+    List(Vec<String>),
+}
+
+impl FieldValue {
+    fn into_scalar(self) -> Option<String> {
+        match self {
+            FieldValue::Scalar(s) => Some(s),
+            FieldValue::List(_) => None,
+        }
+    }
+
+    /// Lists stay as they are; scalars are split on commas (`Read, Grep`)
+    fn into_list(self) -> Vec<String> {
+        match self {
+            FieldValue::List(items) => items,
+            FieldValue::Scalar(s) => {
+                let s = s.trim();
+                let s = s
+                    .strip_prefix('[')
+                    .and_then(|s| s.strip_suffix(']'))
+                    .unwrap_or(s);
+                s.split(',')
+                    .map(|item| unquote(item.trim()).to_string())
+                    .filter(|item| !item.is_empty())
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Split `---` delimited frontmatter into fields and the remaining body
+fn split_frontmatter(text: &str) -> Result<(Vec<(String, FieldValue)>, &str)> {
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    let Some(rest) = text.strip_prefix("---") else {
+        bail!("missing frontmatter (file must start with '---')");
+    };
+    let rest = rest.trim_start_matches([' ', '\t']);
+    let rest = rest.strip_prefix("\r\n").or_else(|| rest.strip_prefix('\n')).unwrap_or(rest);
+
+    let mut fields: Vec<(String, FieldValue)> = Vec::new();
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        let line = line.trim_end();
+
+        if line == "---" {
+            return Ok((fields, &rest[offset..]));
+        }
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        // Block list item belonging to the previous key
+        if let Some(item) = trimmed.strip_prefix("- ") {
+            match fields.last_mut() {
+                Some((_, FieldValue::List(items))) => items.push(unquote(item.trim()).to_string()),
+                _ => bail!("list item '{}' without a key", trimmed),
+            }
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once(':') else {
+            bail!("invalid frontmatter line '{}'", line);
+        };
+        let value = value.trim();
+        let value = if value.is_empty() {
+            FieldValue::List(Vec::new())
+        } else {
+            FieldValue::Scalar(unquote(value).to_string())
+        };
+        fields.push((key.trim().to_string(), value));
+    }
+
+    bail!("unterminated frontmatter (missing closing '---')")
+}
+
+/// Strip matching single or double quotes
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value.strip_prefix(quote).and_then(|v| v.strip_suffix(quote)) {
+            return inner;
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{GlobTool, GrepTool, ReadTool};
+
+    fn tools() -> Arc<ToolRegistry> {
+        let mut registry = ToolRegistry::new();
+        registry.register(ReadTool::new().unwrap());
+        registry.register(GrepTool::new().unwrap());
+        registry.register(GlobTool::new().unwrap());
+        Arc::new(registry)
+    }
+
+    const REVIEWER: &str = "---
+name: code-reviewer
+description: \"Reviews diffs for bugs\"
+tools: Read, Grep
+model: claude-haiku
+color: blue
+---
+
+You are a code reviewer.
+
+Be thorough.
+";
+
+    #[test]
+    fn test_parse_extracts_fields_and_body() {
+        let definition = SubAgentDefinition::parse(REVIEWER).unwrap();
+        assert_eq!(definition.name, "code-reviewer");
+        assert_eq!(definition.description, "Reviews diffs for bugs");
+        assert_eq!(definition.tools, Some(vec!["Read".to_string(), "Grep".to_string()]));
+        assert_eq!(definition.model.as_deref(), Some("claude-haiku"));
+        assert_eq!(definition.system_prompt, "You are a code reviewer.\n\nBe thorough.");
+    }
+
+    #[test]
+    fn test_parse_list_forms_and_inherit() {
+        let block = "---\nname: a\ndescription: d\ntools:\n  - Read\n  - Glob\nmodel: inherit\n---\nPrompt";
+        let definition = SubAgentDefinition::parse(block).unwrap();
+        assert_eq!(definition.tools, Some(vec!["Read".to_string(), "Glob".to_string()]));
+        assert_eq!(definition.model, None);
+
+        let flow = "---\nname: a\ndescription: d\ntools: [\"Read\", Glob]\n---\nPrompt";
+        let definition = SubAgentDefinition::parse(flow).unwrap();
+        assert_eq!(definition.tools, Some(vec!["Read".to_string(), "Glob".to_string()]));
+
+        let all_tools = "---\nname: a\ndescription: d\n---\nPrompt";
+        assert_eq!(SubAgentDefinition::parse(all_tools).unwrap().tools, None);
+    }
+
+    #[test]
+    fn test_parse_missing_fields() {
+        let cases = [
+            ("---\ndescription: d\n---\nPrompt", "name"),
+            ("---\nname: a\n---\nPrompt", "description"),
+            ("---\nname: a\ndescription: d\n---\n\n", "system prompt"),
+            ("name: a\n", "frontmatter"),
+            ("---\nname: a\ndescription: d\n", "unterminated"),
+        ];
+        for (text, expected) in cases {
+            let err = SubAgentDefinition::parse(text).unwrap_err().to_string();
+            assert!(err.contains(expected), "'{}' should mention '{}'", err, expected);
+        }
+    }
+
+    #[test]
+    fn test_load_dir_rejects_unknown_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("reviewer.md"), REVIEWER).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "ignored").unwrap();
+
+        let definitions = load_subagent_definitions(dir.path(), &tools()).unwrap();
+        assert_eq!(definitions.len(), 1);
+        assert_eq!(definitions[0].source, Some(dir.path().join("reviewer.md")));
+
+        std::fs::write(
+            dir.path().join("writer.md"),
+            "---\nname: writer\ndescription: d\ntools: Read, Write\n---\nPrompt",
+        )
+        .unwrap();
+        let err = load_subagent_definitions(dir.path(), &tools()).unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains("writer.md"), "{}", message);
+        assert!(message.contains("unknown tool 'Write'"), "{}", message);
+    }
+
+    #[test]
+    fn test_registry_reloads_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reviewer.md");
+        std::fs::write(&path, REVIEWER).unwrap();
+
+        let registry = SubAgentRegistry::from_dir(dir.path(), tools()).unwrap();
+        assert_eq!(registry.names(), vec!["code-reviewer"]);
+        assert!(!registry.reload_if_changed());
+        assert!(registry
+            .prompt_section()
+            .unwrap()
+            .contains("- code-reviewer: Reviews diffs for bugs"));
+
+        std::fs::write(&path, REVIEWER.replace("Reviews diffs for bugs", "Reviews everything carefully")).unwrap();
+        std::fs::write(
+            dir.path().join("explorer.md"),
+            "---\nname: explorer\ndescription: Finds files\ntools: Glob\n---\nExplore.",
+        )
+        .unwrap();
+        assert!(registry.reload_if_changed());
+        assert_eq!(registry.names(), vec!["code-reviewer", "explorer"]);
+        assert_eq!(
+            registry.get("code-reviewer").unwrap().description,
+            "Reviews everything carefully"
+        );
+
+        // A broken edit keeps the previous definitions
+        std::fs::write(dir.path().join("explorer.md"), "---\nname: explorer\n---\nx").unwrap();
+        assert!(!registry.reload_if_changed());
+        assert_eq!(registry.len(), 2);
+    }
+}
@@ -0,0 +1,775 @@
+//! Task tool - delegates work to a subagent
+//!
+//! The model picks a `subagent_type` from the `SubAgentRegistry`; the tool
+//! spawns a `StandardAgent` with that definition's prompt, tools and model,
+//! sends it the task and returns its final answer.
+//!
+//! While the subagent runs, its permission requests and questions are asked
+//! through the parent agent, so the user answers them in the usual place.
+//!
+//! The parent's `PreSubAgentSpawn` hooks run (in `spawn_subagent_from`)
+//! before anything is created and may deny the spawn or rewrite the
+//! subagent's prompts, tools and model;
+//! `SubAgentComplete` hooks get its result and token usage.
+//!
+//! `with_spawn_options` gives every subagent the same resources and
+//! permission settings, including resources inherited from the parent.
+//!
+//! With a `ForwardMode` other than `None`, the subagent's output is also
+//! re-emitted on the parent's channel as `OutputChunk::SubAgentOutput`, so
+//! a renderer watching the parent sees the subagent work. Forwarding is a
+//! broadcast send and never waits for the parent's subscribers.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::core::{FrameworkError, FrameworkResult, OutputChunk};
+use crate::hooks::SubAgentSpawn;
+use crate::llm::{define_tool, LlmProvider, ToolDefinition, Usage};
+use crate::runtime::channels::OUTPUT_CHANNEL_SIZE;
+use crate::runtime::{AgentHandle, AgentInternals, OverflowPolicy, SpawnOptions};
+use crate::tools::{Tool, ToolInfo, ToolRegistry, ToolResult};
+
+use super::config::AgentConfig;
+use super::standard_loop::StandardAgent;
+use super::subagents::SubAgentRegistry;
+
+/// Max tokens for subagents that override the model
+const SUBAGENT_MAX_TOKENS: u32 = 32000;
+
+/// How much of a subagent's output is forwarded to the parent's stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ForwardMode {
+    /// Nothing; only `SubAgentSpawned` / `SubAgentComplete` are sent
+    #[default]
+    None,
+    /// Its tool calls (`ToolStart` with the final input, `ToolEnd`) and its
+    /// final text
+    Summary,
+    /// Every chunk, except the permission requests and questions that are
+    /// asked through the parent
+    Full,
+}
+
+/// Input for the Task tool
+#[derive(Debug, Deserialize)]
+struct TaskInput {
+    /// Short description shown to the user
+    description: String,
+    /// The task for the subagent
+    prompt: String,
+    /// Which subagent definition to use
+    subagent_type: String,
+}
+
+/// Tool that runs a task in a subagent
+pub struct TaskTool {
+    subagents: Arc<SubAgentRegistry>,
+    llm: Arc<dyn LlmProvider>,
+    tools: Arc<ToolRegistry>,
+    /// Output forwarding (falls back to the agent's `ForwardMode` resource)
+    forward: Option<ForwardMode>,
+    /// Resources and permission settings for every subagent
+    spawn_options: SpawnOptions,
+}
+
+impl TaskTool {
+    /// Create a Task tool
+    ///
+    /// `llm` is used for subagents without a model override and as the base
+    /// for variants; `tools` is the registry that subagent tool lists are
+    /// resolved against (usually the parent's tools, without Task itself).
+    pub fn new(
+        subagents: Arc<SubAgentRegistry>,
+        llm: Arc<dyn LlmProvider>,
+        tools: Arc<ToolRegistry>,
+    ) -> Self {
+        Self {
+            subagents,
+            llm,
+            tools,
+            forward: None,
+            spawn_options: SpawnOptions::new(),
+        }
+    }
+
+    /// Spawn subagents with these options
+    ///
+    /// Use `SpawnOptions::inherit` to share some of the parent's resources:
+    ///
+    /// ```ignore
+    /// let task = TaskTool::new(subagents, llm, tools)
+    ///     .with_spawn_options(SpawnOptions::new().inherit::<TodoListManager>());
+    /// ```
+    pub fn with_spawn_options(mut self, options: SpawnOptions) -> Self {
+        self.spawn_options = options;
+        self
+    }
+
+    /// Forward the subagent's output to the parent's stream
+    ///
+    /// Without this, the parent agent's `ForwardMode` resource is used
+    /// (`AgentConfig::with_forward_subagent_output` sets it).
+    pub fn with_forward_subagent_output(mut self, mode: ForwardMode) -> Self {
+        self.forward = Some(mode);
+        self
+    }
+
+    /// Drive the subagent until it finishes its turn, returning its final text
+    ///
+    /// The tokens it used are added to `usage`.
+    async fn run_subagent(
+        handle: &AgentHandle,
+        prompt: &str,
+        forward: ForwardMode,
+        internals: &mut AgentInternals,
+        usage: &mut Usage,
+    ) -> Result<Option<String>, String> {
+        // Missing a permission request or the final chunk would hang the tool
+        let mut rx = handle.subscribe_reliable(OUTPUT_CHANNEL_SIZE, OverflowPolicy::Block);
+        handle.send_input(prompt).await.map_err(|e| e.to_string())?;
+
+        let session_id = handle.session_id().to_string();
+        let forward_chunk = |internals: &AgentInternals, chunk: OutputChunk| {
+            internals.send(OutputChunk::SubAgentOutput {
+                session_id: session_id.clone(),
+                chunk: Box::new(chunk),
+            });
+        };
+        let mut final_text = None;
+        let mut error = None;
+        loop {
+            let Some(chunk) = rx.recv().await else {
+                break;
+            };
+
+            match (forward, &chunk) {
+                (_, OutputChunk::PermissionRequest { .. } | OutputChunk::AskUserQuestion { .. }) => {}
+                (ForwardMode::Full, _) => forward_chunk(internals, chunk.clone()),
+                (ForwardMode::Summary, OutputChunk::ToolStart { .. } | OutputChunk::ToolEnd { .. }) => {
+                    forward_chunk(internals, chunk.clone())
+                }
+                _ => {}
+            }
+
+            match chunk {
+                OutputChunk::TextComplete(text) => final_text = Some(text),
+                OutputChunk::TurnComplete { usage: turn, .. } => usage.add(&turn),
+                OutputChunk::PermissionRequest {
+                    request_id,
+                    tool_name,
+                    action,
+                    input,
+                    ..
+                } => {
+                    let allowed = internals
+                        .request_permission(&tool_name, &action, &input)
+                        .await
+                        .unwrap_or(false);
+                    handle
+                        .send_permission_response_by_id(request_id, allowed, false)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                }
+                OutputChunk::AskUserQuestion {
+                    request_id,
+                    questions,
+                } => {
+                    let answers = internals
+                        .ask_user_question(&request_id, questions)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    handle
+                        .send_question_response(request_id, answers)
+                        .await
+                        .map_err(|e| e.to_string())?;
+                }
+                OutputChunk::Error(e) => error = Some(e),
+                OutputChunk::Done => break,
+                _ => {}
+            }
+        }
+
+        if forward == ForwardMode::Summary {
+            if let Some(text) = &final_text {
+                forward_chunk(internals, OutputChunk::TextComplete(text.clone()));
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(final_text),
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for TaskTool {
+    fn name(&self) -> &str {
+        "Task"
+    }
+
+    fn description(&self) -> &str {
+        "Launch a subagent to handle a task autonomously."
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        self.subagents.reload_if_changed();
+
+        define_tool(
+            "Task",
+            format!(
+                "Launch a subagent to handle a complex, multi-step task autonomously. \
+                The subagent starts with a fresh context, so include everything it needs in the prompt. \
+                Its final message is returned as the tool result.\n\n\
+                Available subagent types:\n{}",
+                self.subagents.describe()
+            ),
+            json!({
+                "description": {
+                    "type": "string",
+                    "description": "A short (3-5 word) description of the task"
+                },
+                "prompt": {
+                    "type": "string",
+                    "description": "The task for the subagent to perform"
+                },
+                "subagent_type": {
+                    "type": "string",
+                    "enum": self.subagents.names(),
+                    "description": "The type of subagent to use"
+                }
+            }),
+            vec![
+                "description".to_string(),
+                "prompt".to_string(),
+                "subagent_type".to_string(),
+            ],
+        )
+    }
+
+    fn get_info(&self, input: &Value) -> ToolInfo {
+        let subagent_type = input
+            .get("subagent_type")
+            .and_then(|v| v.as_str())
+            .unwrap_or("subagent");
+        let description = input
+            .get("description")
+            .and_then(|v| v.as_str())
+            .unwrap_or("task");
+
+        ToolInfo {
+            name: "Task".to_string(),
+            action_description: format!("Run {}: {}", subagent_type, description),
+            details: input.get("prompt").and_then(|v| v.as_str()).map(str::to_string),
+            read_only: false,
+        }
+    }
+
+    async fn execute(&self, input: &Value, internals: &mut AgentInternals) -> Result<ToolResult> {
+        let input: TaskInput = serde_json::from_value(input.clone())
+            .map_err(|e| anyhow::anyhow!("Invalid Task input: {}", e))?;
+
+        self.subagents.reload_if_changed();
+        let Some(definition) = self.subagents.get(&input.subagent_type) else {
+            return Ok(ToolResult::error(format!(
+                "Unknown subagent type '{}'. Available: {}",
+                input.subagent_type,
+                self.subagents.names().join(", ")
+            )));
+        };
+
+        let spawn = SubAgentSpawn {
+            agent_type: definition.name.clone(),
+            system_prompt: definition.system_prompt.clone(),
+            prompt: input.prompt.clone(),
+            tools: definition.tools.clone(),
+            model: definition.model.clone(),
+        };
+        // Built from the spawn as the PreSubAgentSpawn hooks left it
+        let build = |spawn: &SubAgentSpawn| -> FrameworkResult<StandardAgent> {
+            let tools = match &spawn.tools {
+                Some(names) => Arc::new(self.tools.subset(names).map_err(|e| FrameworkError::other(e.to_string()))?),
+                None => self.tools.clone(),
+            };
+            let llm = match &spawn.model {
+                Some(model) => self.llm.create_variant(model, SUBAGENT_MAX_TOKENS),
+                None => self.llm.clone(),
+            };
+            let config = AgentConfig::new(spawn.system_prompt.clone())
+                .with_tools(tools)
+                .with_auto_name(false);
+            Ok(StandardAgent::new(config, llm))
+        };
+
+        let session_id = format!(
+            "{}-{}-{}",
+            internals.session_id(),
+            definition.name,
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        );
+        let tool_use_id = internals.context.current_tool_use_id.clone().unwrap_or_default();
+        let spawned = internals
+            .spawn_subagent_from(
+                &session_id,
+                spawn,
+                &definition.name,
+                &input.description,
+                &tool_use_id,
+                build,
+                self.spawn_options.clone(),
+            )
+            .await;
+        let (handle, spawn) = match spawned {
+            Ok(spawned) => spawned,
+            Err(FrameworkError::PermissionDenied(reason)) => {
+                return Ok(ToolResult::error(format!(
+                    "Spawning subagent '{}' was denied: {}",
+                    definition.name, reason
+                )));
+            }
+            Err(FrameworkError::LimitExceeded(reason)) => {
+                return Ok(ToolResult::error(format!(
+                    "Could not start subagent '{}': {}. Do this task yourself instead of delegating it.",
+                    definition.name, reason
+                )));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        internals.set_waiting_for_subagent(&session_id).await;
+        let forward = self
+            .forward
+            .or_else(|| internals.context.get_resource::<ForwardMode>().map(|mode| *mode))
+            .unwrap_or_default();
+        let mut usage = Usage::default();
+        let outcome = Self::run_subagent(&handle, &spawn.prompt, forward, internals, &mut usage).await;
+        let _ = handle.shutdown().await;
+        internals.set_executing_tool("Task", &tool_use_id).await;
+
+        match outcome {
+            Ok(text) => {
+                let text = text.unwrap_or_else(|| "(subagent returned no text)".to_string());
+                internals.run_subagent_complete_hooks(&spawn, &session_id, Some(&text), None, Some(&usage)).await;
+                internals.mark_subagent_completed(&session_id, Some(text.clone()), true, None);
+                Ok(ToolResult::success(text))
+            }
+            Err(e) => {
+                internals.run_subagent_complete_hooks(&spawn, &session_id, None, Some(&e), Some(&usage)).await;
+                internals.mark_subagent_completed(&session_id, None, false, Some(e.clone()));
+                Ok(ToolResult::error(format!("Subagent '{}' failed: {}", definition.name, e)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::SubAgentDefinition;
+    use crate::hooks::{HookContext, HookEvent, HookRegistry, HookResult};
+    use crate::cli::print_mode::{run_mock_agent, run_mock_agent_on};
+    use crate::cli::{run_print_mode_with_writer, PrintModeOptions};
+    use crate::llm::MockLlmProvider;
+    use crate::runtime::AgentRuntime;
+    use crate::session::{AgentSession, SessionStorage};
+    use crate::tools::ReadTool;
+
+    #[tokio::test]
+    async fn test_task_runs_subagent() {
+        let mut child_tools = ToolRegistry::new();
+        child_tools.register(ReadTool::new().unwrap());
+        let child_tools = Arc::new(child_tools);
+
+        let subagents = Arc::new(SubAgentRegistry::new());
+        subagents.register(
+            SubAgentDefinition::new("explorer", "Finds files", "You explore.")
+                .with_tools(["Read"]),
+        );
+
+        // Parent calls Task, the subagent answers, then the parent finishes
+        let llm = MockLlmProvider::new()
+            .with_response(MockLlmProvider::tool_use_response(
+                "call_1",
+                "Task",
+                json!({"description": "find", "prompt": "Find main.rs", "subagent_type": "explorer"}),
+            ))
+            .with_text("src/main.rs")
+            .with_text("It is in src/main.rs");
+
+        let mut tools = ToolRegistry::new();
+        tools.register(TaskTool::new(subagents.clone(), Arc::new(llm.clone()), child_tools));
+        let config = AgentConfig::new("You are the main agent.")
+            .with_tools(Arc::new(tools))
+            .with_subagents(subagents)
+            .with_auto_name(false);
+
+        let dir = tempfile::tempdir().unwrap();
+        let session = AgentSession::new_with_storage(
+            "parent",
+            "test",
+            "Test",
+            "",
+            SessionStorage::with_dir(dir.path()),
+        )
+        .unwrap();
+        let result = run_mock_agent(config, Arc::new(llm.clone()), session, "where is main?").await;
+        assert_eq!(result.result, "It is in src/main.rs");
+
+        let requests = llm.requests();
+        assert_eq!(requests.len(), 3);
+
+        // The parent's system prompt advertises the subagent types
+        let parent_system = format!("{:?}", requests[0].system);
+        assert!(parent_system.contains("- explorer: Finds files"));
+
+        // The subagent got its own prompt, the task and only its tools
+        let child = &requests[1];
+        assert!(format!("{:?}", child.system).contains("You explore."));
+        assert_eq!(child.tools.len(), 1);
+
+        // The subagent's answer came back as the tool result
+        let last = format!("{:?}", requests[2].messages.last().unwrap());
+        assert!(last.contains("src/main.rs"));
+
+        let children = AgentSession::load_with_storage("parent", SessionStorage::with_dir(dir.path()))
+            .unwrap()
+            .child_session_ids()
+            .to_vec();
+        assert_eq!(children.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_depth_limit_becomes_tool_error() {
+        let subagents = Arc::new(SubAgentRegistry::new());
+        subagents.register(SubAgentDefinition::new("explorer", "Finds files", "You explore."));
+
+        let llm = MockLlmProvider::new()
+            .with_response(MockLlmProvider::tool_use_response(
+                "call_1",
+                "Task",
+                json!({"description": "find", "prompt": "Find main.rs", "subagent_type": "explorer"}),
+            ))
+            .with_text("I'll look myself");
+
+        let mut tools = ToolRegistry::new();
+        tools.register(TaskTool::new(
+            subagents,
+            Arc::new(llm.clone()),
+            Arc::new(ToolRegistry::new()),
+        ));
+        let config = AgentConfig::new("You are the main agent.")
+            .with_tools(Arc::new(tools))
+            .with_auto_name(false);
+
+        let dir = tempfile::tempdir().unwrap();
+        let session = AgentSession::new_with_storage(
+            "no-subagents",
+            "test",
+            "Test",
+            "",
+            SessionStorage::with_dir(dir.path()),
+        )
+        .unwrap();
+        // Depth 0: this agent may not spawn subagents at all
+        let runtime = AgentRuntime::with_limits(4, 0);
+        run_mock_agent_on(&runtime, SpawnOptions::new(), config, Arc::new(llm.clone()), session, "where is main?").await;
+
+        // The model got a readable error instead of the turn failing
+        let requests = llm.requests();
+        assert_eq!(requests.len(), 2);
+        let tool_result = format!("{:?}", requests[1].messages.last().unwrap());
+        assert!(tool_result.contains("Could not start subagent 'explorer'"));
+        assert!(tool_result.contains("max_subagent_depth"));
+        assert_eq!(runtime.metrics().await.running_subagents, 0);
+
+        runtime.shutdown_all().await;
+    }
+
+    /// Parent calls Task with `hooks`, the subagent (if spawned) answers;
+    /// returns the LLM requests and the parent's child session IDs
+    async fn run_with_hooks(hooks: HookRegistry) -> (Vec<crate::llm::MockRequest>, Vec<String>) {
+        let subagents = Arc::new(SubAgentRegistry::new());
+        subagents.register(SubAgentDefinition::new("explorer", "Finds files", "You explore."));
+
+        let llm = MockLlmProvider::new()
+            .with_response(MockLlmProvider::tool_use_response(
+                "call_1",
+                "Task",
+                json!({"description": "find", "prompt": "Find main.rs", "subagent_type": "explorer"}),
+            ))
+            .with_text("src/main.rs")
+            .with_text("It is in src/main.rs");
+
+        let mut tools = ToolRegistry::new();
+        tools.register(TaskTool::new(subagents, Arc::new(llm.clone()), Arc::new(ToolRegistry::new())));
+        let config = AgentConfig::new("You are the main agent.")
+            .with_tools(Arc::new(tools))
+            .with_hooks(hooks)
+            .with_auto_name(false);
+
+        let dir = tempfile::tempdir().unwrap();
+        let storage = SessionStorage::with_dir(dir.path());
+        let session = AgentSession::new_with_storage("parent", "test", "Test", "", storage.clone()).unwrap();
+        run_mock_agent(config, Arc::new(llm.clone()), session, "where is main?").await;
+
+        let children = AgentSession::load_with_storage("parent", storage.clone())
+            .unwrap()
+            .child_session_ids()
+            .to_vec();
+        // Nothing but the parent and its children was written
+        let sessions = storage.list_sessions().unwrap();
+        assert_eq!(sessions.len(), children.len() + 1, "{:?}", sessions);
+        (llm.requests(), children)
+    }
+
+    #[tokio::test]
+    async fn test_spawn_hook_can_deny() {
+        let mut hooks = HookRegistry::new();
+        hooks
+            .add_with_pattern(HookEvent::PreSubAgentSpawn, "explorer", |ctx: &mut HookContext| {
+                assert_eq!(ctx.session_id(), "parent");
+                assert_eq!(ctx.subagent.as_ref().unwrap().prompt, "Find main.rs");
+                HookResult::deny("no subagents in untrusted sessions")
+            })
+            .unwrap();
+
+        let (requests, children) = run_with_hooks(hooks).await;
+        assert!(children.is_empty());
+        // The parent model got the reason instead of the subagent's answer
+        assert_eq!(requests.len(), 2);
+        let tool_result = format!("{:?}", requests[1].messages.last().unwrap());
+        assert!(tool_result.contains("Spawning subagent 'explorer' was denied: no subagents in untrusted sessions"));
+    }
+
+    #[tokio::test]
+    async fn test_spawn_hook_can_modify_and_complete_hook_sees_result() {
+        let completed = Arc::new(std::sync::Mutex::new(None));
+        let mut hooks = HookRegistry::new();
+        hooks.add(HookEvent::PreSubAgentSpawn, |ctx: &mut HookContext| {
+            let spawn = ctx.subagent.as_mut().unwrap();
+            spawn.system_prompt = format!("{} Never edit files.", spawn.system_prompt);
+            HookResult::none()
+        });
+        let seen = completed.clone();
+        hooks.add(HookEvent::SubAgentComplete, move |ctx: &mut HookContext| {
+            *seen.lock().unwrap() = Some((
+                ctx.subagent.as_ref().unwrap().agent_type.clone(),
+                ctx.subagent_session_id.clone().unwrap(),
+                ctx.subagent_result.clone(),
+                ctx.subagent_usage.is_some(),
+            ));
+            HookResult::none()
+        });
+
+        let (requests, children) = run_with_hooks(hooks).await;
+        assert_eq!(requests.len(), 3);
+        assert!(format!("{:?}", requests[1].system).contains("You explore. Never edit files."));
+
+        let (agent_type, session_id, result, has_usage) = completed.lock().unwrap().clone().unwrap();
+        assert_eq!(agent_type, "explorer");
+        assert_eq!(children, vec![session_id]);
+        assert_eq!(result.as_deref(), Some("src/main.rs"));
+        assert!(has_usage);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_hook_cannot_change_the_agent_type() {
+        let mut hooks = HookRegistry::new();
+        hooks.add(HookEvent::PreSubAgentSpawn, |ctx: &mut HookContext| {
+            ctx.subagent.as_mut().unwrap().agent_type = "admin".to_string();
+            HookResult::none()
+        });
+
+        let (requests, children) = run_with_hooks(hooks).await;
+        assert!(children.is_empty());
+        let tool_result = format!("{:?}", requests[1].messages.last().unwrap());
+        assert!(
+            tool_result.contains("changed agent_type from 'explorer' to 'admin'"),
+            "{}",
+            tool_result
+        );
+    }
+
+    /// Parent calls Task, the subagent reads a file, calls the no-argument
+    /// Project tool and answers; returns the subagent's session ID and what
+    /// it forwarded to the parent's stream
+    async fn forwarded_chunks(config_mode: ForwardMode, tool_mode: Option<ForwardMode>) -> (String, Vec<OutputChunk>) {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("main.rs");
+        std::fs::write(&file, "fn main() {}").unwrap();
+
+        let mut child_tools = ToolRegistry::new();
+        child_tools.register(ReadTool::new().unwrap());
+        child_tools.register(ProjectTool);
+        let subagents = Arc::new(SubAgentRegistry::new());
+        subagents.register(SubAgentDefinition::new("explorer", "Finds files", "You explore.").with_tools(["Read", "Project"]));
+
+        let llm = MockLlmProvider::new()
+            .with_response(MockLlmProvider::tool_use_response(
+                "call_1",
+                "Task",
+                json!({"description": "find", "prompt": "Find main.rs", "subagent_type": "explorer"}),
+            ))
+            .with_response(MockLlmProvider::tool_use_response(
+                "call_2",
+                "Read",
+                json!({"file_path": file.to_string_lossy()}),
+            ))
+            .with_response(MockLlmProvider::tool_use_response("call_3", "Project", json!({})))
+            .with_text("src/main.rs")
+            .with_text("It is in src/main.rs");
+
+        let mut task = TaskTool::new(subagents, Arc::new(llm.clone()), Arc::new(child_tools));
+        if let Some(mode) = tool_mode {
+            task = task.with_forward_subagent_output(mode);
+        }
+        let mut tools = ToolRegistry::new();
+        tools.register(task);
+        let config = AgentConfig::new("You are the main agent.")
+            .with_tools(Arc::new(tools))
+            .with_forward_subagent_output(config_mode)
+            .with_auto_name(false);
+        let agent = StandardAgent::new(config, Arc::new(llm));
+
+        let session = AgentSession::new_with_storage("parent", "test", "Test", "", SessionStorage::with_dir(dir.path())).unwrap();
+        let runtime = AgentRuntime::new();
+        let handle = runtime.spawn(session, move |internals| agent.run(internals)).await;
+        let mut rx = handle.subscribe_reliable(4096, OverflowPolicy::Block);
+        let options = PrintModeOptions::new().with_allowed_tools(["*"]);
+        run_print_mode_with_writer(&handle, "where is main?", options, &mut Vec::new()).await.unwrap();
+        runtime.shutdown_all().await;
+
+        let mut child = String::new();
+        let mut forwarded = Vec::new();
+        while let Some(chunk) = rx.try_recv() {
+            match chunk {
+                OutputChunk::SubAgentSpawned { session_id, .. } => child = session_id,
+                OutputChunk::SubAgentOutput { session_id, chunk } => {
+                    assert_eq!(session_id, child);
+                    forwarded.push(*chunk);
+                }
+                _ => {}
+            }
+        }
+        assert!(child.starts_with("parent-explorer-"), "{}", child);
+        (child, forwarded)
+    }
+
+    #[tokio::test]
+    async fn test_full_forwarding_wraps_every_chunk() {
+        let (_, forwarded) = forwarded_chunks(ForwardMode::Full, None).await;
+        assert!(matches!(&forwarded[..], [.., OutputChunk::Done]), "{:?}", forwarded);
+        assert!(forwarded.iter().any(|c| matches!(c, OutputChunk::ToolStart { name, .. } if name == "Read")));
+        assert!(forwarded.iter().any(|c| matches!(c, OutputChunk::ToolEnd { result, .. } if result.text().contains("fn main"))));
+        assert!(forwarded.iter().any(|c| matches!(c, OutputChunk::TextComplete(t) if t == "src/main.rs")));
+        assert!(forwarded.iter().any(|c| matches!(c, OutputChunk::StateChange(_))));
+    }
+
+    #[tokio::test]
+    async fn test_summary_forwarding_and_tool_override() {
+        let (_, forwarded) = forwarded_chunks(ForwardMode::Summary, None).await;
+        let kinds: Vec<_> = forwarded
+            .iter()
+            .map(|c| match c {
+                OutputChunk::ToolStart { name, .. } => format!("start {}", name),
+                OutputChunk::ToolEnd { .. } => "end".to_string(),
+                OutputChunk::TextComplete(text) => format!("text {}", text),
+                other => format!("{:?}", other),
+            })
+            .collect();
+        assert_eq!(kinds, vec!["start Read", "end", "start Project", "end", "text src/main.rs"]);
+
+        // The tool's own mode wins over the agent's
+        let (_, forwarded) = forwarded_chunks(ForwardMode::Full, Some(ForwardMode::None)).await;
+        assert!(forwarded.is_empty(), "{:?}", forwarded);
+    }
+
+    struct Project(&'static str);
+    struct Secret;
+
+    /// A tool that reports the `Project` and `Secret` resources it can see
+    struct ProjectTool;
+
+    #[async_trait]
+    impl Tool for ProjectTool {
+        fn name(&self) -> &str {
+            "Project"
+        }
+
+        fn description(&self) -> &str {
+            "Names the project"
+        }
+
+        fn definition(&self) -> ToolDefinition {
+            define_tool("Project", "Names the project", json!({}), vec![])
+        }
+
+        fn get_info(&self, _input: &Value) -> ToolInfo {
+            ToolInfo {
+                name: "Project".to_string(),
+                action_description: "Name the project".to_string(),
+                details: None,
+                read_only: true,
+            }
+        }
+
+        async fn execute(&self, _input: &Value, internals: &mut AgentInternals) -> Result<ToolResult> {
+            let project = internals.context.get_resource::<Project>().map_or("none", |p| p.0);
+            let secret = internals.context.get_resource::<Secret>().is_some();
+            Ok(ToolResult::success(format!("project={} secret={}", project, secret)))
+        }
+
+        fn requires_permission(&self) -> bool {
+            false
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spawn_options_reach_parent_and_subagent_tools() {
+        let mut child_tools = ToolRegistry::new();
+        child_tools.register(ProjectTool);
+        let subagents = Arc::new(SubAgentRegistry::new());
+        subagents.register(SubAgentDefinition::new("explorer", "Finds files", "You explore."));
+
+        // Parent and subagent each call Project once
+        let llm = MockLlmProvider::new()
+            .with_response(MockLlmProvider::tool_use_response("call_1", "Project", json!({})))
+            .with_response(MockLlmProvider::tool_use_response(
+                "call_2",
+                "Task",
+                json!({"description": "find", "prompt": "Which project?", "subagent_type": "explorer"}),
+            ))
+            .with_response(MockLlmProvider::tool_use_response("call_3", "Project", json!({})))
+            .with_text("alpha")
+            .with_text("It is alpha");
+
+        // Only `Project` is handed down to subagents
+        let task = TaskTool::new(subagents, Arc::new(llm.clone()), Arc::new(child_tools))
+            .with_spawn_options(SpawnOptions::new().inherit::<Project>());
+        let mut tools = ToolRegistry::new();
+        tools.register(task);
+        tools.register(ProjectTool);
+        let config = AgentConfig::new("You are the main agent.")
+            .with_tools(Arc::new(tools))
+            .with_auto_name(false);
+
+        let dir = tempfile::tempdir().unwrap();
+        let session = AgentSession::new_with_storage("parent", "test", "Test", "", SessionStorage::with_dir(dir.path())).unwrap();
+        let runtime = AgentRuntime::new();
+        let options = SpawnOptions::new().with_resource(Project("alpha")).with_resource(Secret);
+        let result = run_mock_agent_on(&runtime, options, config, Arc::new(llm.clone()), session, "which project?").await;
+        assert_eq!(result.result, "It is alpha");
+        runtime.shutdown_all().await;
+
+        let requests = llm.requests();
+        assert_eq!(requests.len(), 5);
+        let parent_result = format!("{:?}", requests[1].messages.last().unwrap());
+        assert!(parent_result.contains("project=alpha secret=true"), "{}", parent_result);
+        let child_result = format!("{:?}", requests[3].messages.last().unwrap());
+        assert!(child_result.contains("project=alpha secret=false"), "{}", child_result);
+    }
+}
@@ -1,10 +1,18 @@
 use colored::*;
+use std::collections::HashMap;
 use std::io::{self, Write};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
+use crate::core::output::UserQuestion;
 use crate::helpers::{TodoItem, TodoListManager, TodoStatus};
 use crate::permissions::{PermissionDecision, PermissionRequest};
 
+use super::diff::{DiffLine, FileDiff, DIFF_TOOLS};
+use super::input::{parse_question_answer, ConsoleInput, InputConfig, LineEditor};
+
+/// Diff lines shown before the rest collapse (unless diffs are expanded)
+const DIFF_PREVIEW_LINES: usize = 40;
+
 /// Console handles all terminal I/O with colored formatting
 pub struct Console {
     user_color: Color,
@@ -12,6 +20,12 @@ pub struct Console {
     tool_color: Color,
     /// Optional todo list manager for display
     todo_manager: Option<Arc<TodoListManager>>,
+    /// Configuration for the line editor
+    input_config: InputConfig,
+    /// Line editor, created on first read so history is only loaded when needed
+    editor: Mutex<Option<LineEditor>>,
+    /// Show diffs in full instead of collapsing long ones
+    expand_diffs: bool,
 }
 
 impl Console {
@@ -22,6 +36,9 @@ impl Console {
             assistant_color: Color::Green,
             tool_color: Color::Magenta,
             todo_manager: None,
+            input_config: InputConfig::default(),
+            editor: Mutex::new(None),
+            expand_diffs: false,
         }
     }
 
@@ -32,6 +49,9 @@ impl Console {
             assistant_color: Color::Green,
             tool_color: Color::Magenta,
             todo_manager: Some(manager),
+            input_config: InputConfig::default(),
+            editor: Mutex::new(None),
+            expand_diffs: false,
         }
     }
 
@@ -42,6 +62,9 @@ impl Console {
             assistant_color,
             tool_color,
             todo_manager: None,
+            input_config: InputConfig::default(),
+            editor: Mutex::new(None),
+            expand_diffs: false,
         }
     }
 
@@ -50,6 +73,28 @@ impl Console {
         self.todo_manager = Some(manager);
     }
 
+    /// Set whether long diffs are shown in full
+    pub fn set_expand_diffs(&mut self, expand: bool) {
+        self.expand_diffs = expand;
+    }
+
+    /// Set the input configuration (multiline sentinel, history file)
+    ///
+    /// Takes effect on the next read if the editor has already been created.
+    pub fn set_input_config(&mut self, config: InputConfig) {
+        self.input_config = config;
+        *self.editor.lock().unwrap() = None;
+    }
+
+    /// Run a closure with the line editor, creating it on first use
+    fn with_editor<T>(&self, f: impl FnOnce(&mut LineEditor) -> io::Result<T>) -> io::Result<T> {
+        let mut guard = self.editor.lock().unwrap();
+        if guard.is_none() {
+            *guard = Some(LineEditor::new(self.input_config.clone())?);
+        }
+        f(guard.as_mut().expect("editor initialized above"))
+    }
+
     /// Print a user message with colored formatting
     pub fn print_user(&self, message: &str) {
         println!("{} {}", "User:".color(self.user_color).bold(), message);
@@ -91,14 +136,16 @@ impl Console {
         eprintln!("{} {}", "Error:".red().bold(), error);
     }
 
-    /// Read a line of input from the user
-    pub fn read_input(&self) -> io::Result<String> {
-        print!("{} ", ">".color(self.user_color).bold());
+    /// Read a message from the user
+    ///
+    /// Supports multiline input, history, and bracketed paste (see
+    /// [`cli::input`](super::input)). Ctrl+C returns `ConsoleInput::Cancelled`
+    /// and Ctrl+D returns `ConsoleInput::Exit`.
+    pub fn read_input(&self) -> io::Result<ConsoleInput> {
         io::stdout().flush()?;
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        Ok(input.trim().to_string())
+        let prompt = format!("{} ", ">".color(self.user_color).bold());
+        let continuation = format!("{} ", ".".color(self.user_color));
+        self.with_editor(|editor| editor.read_message(&prompt, &continuation))
     }
 
     /// Print a welcome banner
@@ -111,6 +158,11 @@ impl Console {
         println!("{}", "=".repeat(60).bright_blue());
         println!();
         println!("Type your message and press Enter. Type 'exit' or 'quit' to end the session.");
+        println!("Type /permissions to list permission rules, /permissions revoke <id> to remove one.");
+        println!(
+            "Use Ctrl+J or a trailing \\ for a new line, or wrap a block in {}. Ctrl+C cancels, Ctrl+D exits.",
+            self.input_config.sentinel
+        );
         println!();
     }
 
@@ -129,6 +181,39 @@ impl Console {
         );
     }
 
+    /// Print the start of a tool call whose input streams after it
+    pub fn print_tool_input_prefix(&self, tool_name: &str) {
+        print!(
+            "{} {} ",
+            "Tool:".color(self.tool_color).bold(),
+            format!("[{}]", tool_name).color(self.tool_color)
+        );
+        io::stdout().flush().unwrap();
+    }
+
+    /// Print a chunk of streaming tool input (raw JSON)
+    pub fn print_tool_input_chunk(&self, chunk: &str) {
+        print!("{}", chunk.bright_black());
+        io::stdout().flush().unwrap();
+    }
+
+    /// Print a file the agent presented: its title, then indented details
+    pub fn print_file_card(&self, title: &str, details: &[String]) {
+        println!("{} {}", "File:".color(self.tool_color).bold(), title.bold());
+        for line in details {
+            println!("  {}", line.bright_black());
+        }
+    }
+
+    /// Print a line of forwarded subagent output, indented by nesting depth
+    pub fn print_subagent(&self, depth: usize, agent_type: &str, message: &str) {
+        let indent = "  ".repeat(depth);
+        let prefix = format!("[sub:{}]", agent_type).color(self.tool_color);
+        for line in message.lines() {
+            println!("{}{} {}", indent, prefix, line);
+        }
+    }
+
     /// Print a tool result
     pub fn print_tool_result(&self, result: &str, is_error: bool) {
         if is_error {
@@ -144,8 +229,41 @@ impl Console {
         }
     }
 
+    /// Print a file diff: the path, the colored hunks and a `+N -M` line
+    pub fn print_file_diff(&self, diff: &FileDiff) {
+        println!("{}", diff.path.bold());
+        let limit = (!self.expand_diffs).then_some(DIFF_PREVIEW_LINES);
+        for line in diff.lines(limit) {
+            let text = line.to_string();
+            let styled = match line {
+                DiffLine::Hunk(_) => text.cyan(),
+                DiffLine::Context(_) => text.normal(),
+                DiffLine::Added(_) => text.green(),
+                DiffLine::Removed(_) => text.red(),
+                DiffLine::Collapsed(_) => text.bright_black(),
+            };
+            println!("  {}", styled);
+        }
+        println!("  {}", self.diff_counts(diff));
+    }
+
+    /// Print a diff as just its path and `+N -M`
+    pub fn print_diff_summary(&self, diff: &FileDiff) {
+        println!("{} {}", diff.path.bold(), self.diff_counts(diff));
+    }
+
+    fn diff_counts(&self, diff: &FileDiff) -> String {
+        format!(
+            "{} {}",
+            format!("+{}", diff.added).green(),
+            format!("-{}", diff.removed).red()
+        )
+    }
+
     /// Ask for permission to execute a tool
     ///
+    /// Edit, MultiEdit and Write calls show a diff of the change.
+    ///
     /// Returns the user's decision: Allow, Deny, AlwaysAllow, or AlwaysDeny
     pub fn ask_permission(&self, request: &PermissionRequest) -> io::Result<PermissionDecision> {
         println!();
@@ -160,6 +278,10 @@ impl Console {
         if let Some(ref details) = request.details {
             println!("  {}", details.bright_black());
         }
+        if let Some(diff) = permission_diff(request) {
+            println!();
+            self.print_file_diff(&diff);
+        }
         println!();
         println!("{}", "Options:".yellow());
         println!("  [y] Allow this action");
@@ -167,12 +289,12 @@ impl Console {
         println!("  [a] Always allow this tool");
         println!("  [d] Always deny this tool");
         println!("{}", "─".repeat(60).yellow());
-        print!("{} ", "Your choice (y/n/a/d):".yellow().bold());
-        io::stdout().flush()?;
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        let input = input.trim().to_lowercase();
+        let prompt = format!("{} ", "Your choice (y/n/a/d):".yellow().bold());
+        let input = self
+            .with_editor(|editor| editor.read_line(&prompt))?
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
 
         let decision = match input.as_str() {
             "y" | "yes" => PermissionDecision::Allow,
@@ -211,6 +333,70 @@ impl Console {
         Ok(decision)
     }
 
+    /// Ask a set of multiple-choice questions and collect the answers
+    ///
+    /// Each question lists its numbered options plus an "Other" entry for
+    /// free text. Answers are keyed by question header. Ctrl+C / Ctrl+D picks
+    /// the first option for the remaining questions.
+    pub fn ask_questions(&self, questions: &[UserQuestion]) -> io::Result<HashMap<String, String>> {
+        let mut answers = HashMap::new();
+
+        println!();
+        println!("{}", "─".repeat(60).yellow());
+        for q in questions {
+            println!("{} {}", format!("[{}]", q.header).yellow().bold(), q.question);
+            for (i, opt) in q.options.iter().enumerate() {
+                if opt.description.is_empty() {
+                    println!("  {}. {}", i + 1, opt.label.bold());
+                } else {
+                    println!(
+                        "  {}. {} - {}",
+                        i + 1,
+                        opt.label.bold(),
+                        opt.description.bright_black()
+                    );
+                }
+            }
+            println!("  {}. Other (type your own answer)", q.options.len() + 1);
+
+            let prompt = if q.multi_select {
+                format!("{} ", "Your choice (e.g. 1,3 or text):".yellow().bold())
+            } else {
+                format!("{} ", "Your choice (number or text):".yellow().bold())
+            };
+            let answer = loop {
+                let Some(input) = self.with_editor(|editor| editor.read_line(&prompt))? else {
+                    break q.options.first().map(|opt| opt.label.clone()).unwrap_or_default();
+                };
+                if let Some(answer) = parse_question_answer(q, &input) {
+                    break answer;
+                }
+                if input.trim() == (q.options.len() + 1).to_string() {
+                    let other_prompt = format!("{} ", "Your answer:".yellow().bold());
+                    let text = self
+                        .with_editor(|editor| editor.read_line(&other_prompt))?
+                        .unwrap_or_default();
+                    if !text.trim().is_empty() {
+                        break text.trim().to_string();
+                    }
+                    continue;
+                }
+                if q.multi_select {
+                    println!("{}", "Enter option numbers separated by commas, or your own answer.".red());
+                } else {
+                    println!("{}", "Pick one option number, or type your own answer.".red());
+                }
+            };
+
+            println!("{}", format!("✓ {}", answer).green());
+            answers.insert(q.header.clone(), answer);
+        }
+        println!("{}", "─".repeat(60).yellow());
+        println!();
+
+        Ok(answers)
+    }
+
     /// Print a thinking indicator
     pub fn print_thinking(&self) {
         print!("{}", "Thinking...".bright_black());
@@ -346,6 +532,25 @@ impl Console {
     }
 }
 
+/// The diff a permission request for a file-editing tool would make
+fn permission_diff(request: &PermissionRequest) -> Option<FileDiff> {
+    if !DIFF_TOOLS.contains(&request.tool_name.as_str()) {
+        return None;
+    }
+    let input: serde_json::Value = serde_json::from_str(&request.input).ok()?;
+    let current = current_contents(&request.tool_name, &input);
+    FileDiff::from_tool_input(&request.tool_name, &input, current.as_deref())
+}
+
+/// What a Write call's file holds now, before the call runs
+pub(crate) fn current_contents(tool_name: &str, input: &serde_json::Value) -> Option<String> {
+    if tool_name != "Write" {
+        return None;
+    }
+    let path = input.get("file_path")?.as_str()?;
+    std::fs::read_to_string(path).ok()
+}
+
 impl Default for Console {
     fn default() -> Self {
         Self::new()
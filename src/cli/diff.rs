@@ -0,0 +1,324 @@
+//! Diffs of file-editing tool calls for the console
+//!
+//! `FileDiff::from_tool_input` turns the input of an Edit, MultiEdit or
+//! Write call into a unified diff: Edit's `old_string`/`new_string` (each
+//! of MultiEdit's `edits`), or a Write's new `content` against what the
+//! file holds before it runs. The console shows it after the call succeeds
+//! and in the permission prompt.
+//!
+//! ```ignore
+//! let diff = FileDiff::new("src/main.rs", old, new);
+//! for line in diff.lines(Some(40)) {
+//!     println!("{}", line);
+//! }
+//! println!("{}", diff.summary()); // "+12 -3"
+//! ```
+
+use std::fmt;
+
+use serde_json::Value;
+
+/// Tools whose calls are shown as diffs
+pub const DIFF_TOOLS: &[&str] = &["Edit", "MultiEdit", "Write"];
+
+/// Unchanged lines kept around each change
+const CONTEXT_LINES: usize = 3;
+
+/// Past this many line pairs, a changed region is shown as replaced
+/// outright rather than diffed line by line
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// One line of a unified diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    /// `@@ -old_start,old_len +new_start,new_len @@`
+    Hunk(String),
+    /// An unchanged line
+    Context(String),
+    /// A line only in the new text
+    Added(String),
+    /// A line only in the old text
+    Removed(String),
+    /// Footer for the lines a collapsed diff leaves out
+    Collapsed(usize),
+}
+
+impl fmt::Display for DiffLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffLine::Hunk(header) => write!(f, "{}", header),
+            DiffLine::Context(line) => write!(f, " {}", line),
+            DiffLine::Added(line) => write!(f, "+{}", line),
+            DiffLine::Removed(line) => write!(f, "-{}", line),
+            DiffLine::Collapsed(count) => write!(f, "… {} more lines", count),
+        }
+    }
+}
+
+/// The changes a tool call makes to one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDiff {
+    /// The file being changed
+    pub path: String,
+    /// Hunks of the unified diff
+    pub lines: Vec<DiffLine>,
+    /// Number of added lines
+    pub added: usize,
+    /// Number of removed lines
+    pub removed: usize,
+}
+
+impl FileDiff {
+    /// Diff two versions of a file
+    pub fn new(path: impl Into<String>, old: &str, new: &str) -> Self {
+        let mut diff = Self {
+            path: path.into(),
+            lines: Vec::new(),
+            added: 0,
+            removed: 0,
+        };
+        diff.push(old, new);
+        diff
+    }
+
+    /// The diff for an Edit, MultiEdit or Write call
+    ///
+    /// `current` is what the file holds now, used for Write (None if it
+    /// doesn't exist yet). None if the tool isn't one of `DIFF_TOOLS` or
+    /// its input doesn't have the expected fields.
+    pub fn from_tool_input(tool_name: &str, input: &Value, current: Option<&str>) -> Option<Self> {
+        let path = input.get("file_path")?.as_str()?;
+        let text = |value: &Value, key: &str| value.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        let pairs = match tool_name {
+            "Edit" => vec![(text(input, "old_string")?, text(input, "new_string")?)],
+            "MultiEdit" => input
+                .get("edits")?
+                .as_array()?
+                .iter()
+                .map(|edit| Some((text(edit, "old_string")?, text(edit, "new_string")?)))
+                .collect::<Option<Vec<_>>>()?,
+            "Write" => vec![(current.unwrap_or_default().to_string(), text(input, "content")?)],
+            _ => return None,
+        };
+        let mut diff = Self::new(path, "", "");
+        for (old, new) in pairs {
+            diff.push(&old, &new);
+        }
+        Some(diff)
+    }
+
+    /// `+added -removed`
+    pub fn summary(&self) -> String {
+        format!("+{} -{}", self.added, self.removed)
+    }
+
+    /// The lines to show, at most `max_lines` of them plus a footer
+    pub fn lines(&self, max_lines: Option<usize>) -> Vec<DiffLine> {
+        match max_lines {
+            Some(max) if self.lines.len() > max => {
+                let mut lines = self.lines[..max].to_vec();
+                lines.push(DiffLine::Collapsed(self.lines.len() - max));
+                lines
+            }
+            _ => self.lines.clone(),
+        }
+    }
+
+    fn push(&mut self, old: &str, new: &str) {
+        let lines = diff_lines(old, new, CONTEXT_LINES);
+        for line in &lines {
+            match line {
+                DiffLine::Added(_) => self.added += 1,
+                DiffLine::Removed(_) => self.removed += 1,
+                _ => {}
+            }
+        }
+        self.lines.extend(lines);
+    }
+}
+
+/// The unified diff of two texts, with `context` unchanged lines around
+/// each change
+pub fn diff_lines(old: &str, new: &str, context: usize) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let ops = line_ops(&old, &new);
+
+    // Indices into `ops` of the changes, grouped into hunks that are
+    // separated by more than twice the context
+    let changes: Vec<usize> = ops.iter().enumerate().filter(|(_, op)| !matches!(op, Op::Same)).map(|(i, _)| i).collect();
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for &i in &changes {
+        match groups.last_mut() {
+            Some((_, end)) if i - *end <= 2 * context + 1 => *end = i,
+            _ => groups.push((i, i)),
+        }
+    }
+
+    let mut lines = Vec::new();
+    for (first, last) in groups {
+        let start = first.saturating_sub(context);
+        let end = (last + context + 1).min(ops.len());
+        // Line numbers where the hunk starts, counted from the ops before it
+        let (mut old_line, mut new_line) = (0, 0);
+        for op in &ops[..start] {
+            match op {
+                Op::Same => (old_line, new_line) = (old_line + 1, new_line + 1),
+                Op::Removed => old_line += 1,
+                Op::Added => new_line += 1,
+            }
+        }
+        let (mut old_index, mut new_index) = (old_line, new_line);
+        let mut body = Vec::new();
+        for op in &ops[start..end] {
+            match op {
+                Op::Same => {
+                    body.push(DiffLine::Context(old[old_index].to_string()));
+                    old_index += 1;
+                    new_index += 1;
+                }
+                Op::Removed => {
+                    body.push(DiffLine::Removed(old[old_index].to_string()));
+                    old_index += 1;
+                }
+                Op::Added => {
+                    body.push(DiffLine::Added(new[new_index].to_string()));
+                    new_index += 1;
+                }
+            }
+        }
+        let range = |start: usize, len: usize| if len == 0 { format!("{},0", start) } else { format!("{},{}", start + 1, len) };
+        lines.push(DiffLine::Hunk(format!(
+            "@@ -{} +{} @@",
+            range(old_line, old_index - old_line),
+            range(new_line, new_index - new_line)
+        )));
+        lines.extend(body);
+    }
+    lines
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Same,
+    Removed,
+    Added,
+}
+
+/// Edit script from `old` to `new`: the longest common subsequence of lines
+/// between the common prefix and suffix, removals before additions
+fn line_ops(old: &[&str], new: &[&str]) -> Vec<Op> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let (old_mid, new_mid) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let mut ops = vec![Op::Same; prefix];
+    if old_mid.len() * new_mid.len() > MAX_DIFF_CELLS {
+        ops.extend(std::iter::repeat_n(Op::Removed, old_mid.len()));
+        ops.extend(std::iter::repeat_n(Op::Added, new_mid.len()));
+    } else {
+        // lcs[i][j]: common lines of old_mid[i..] and new_mid[j..]
+        let (n, m) = (old_mid.len(), new_mid.len());
+        let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if old_mid[i] == new_mid[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && old_mid[i] == new_mid[j] {
+                ops.push(Op::Same);
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                ops.push(Op::Removed);
+                i += 1;
+            } else {
+                ops.push(Op::Added);
+                j += 1;
+            }
+        }
+    }
+    ops.extend(std::iter::repeat_n(Op::Same, suffix));
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn render(lines: &[DiffLine]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_multi_hunk_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+        assert_eq!(
+            render(&diff_lines(old, new, 2)),
+            vec![
+                "@@ -1,4 +1,4 @@", " a", "-b", "+B", " c", " d",
+                "@@ -11,2 +11,3 @@", " k", " l", "+m",
+            ]
+        );
+
+        // Changes closer than twice the context share a hunk
+        let new = "a\nB\nc\nd\nE\nf\ng\nh\ni\nj\nk\nl\n";
+        assert_eq!(
+            render(&diff_lines(old, new, 2)),
+            vec!["@@ -1,7 +1,7 @@", " a", "-b", "+B", " c", " d", "-e", "+E", " f", " g"]
+        );
+    }
+
+    #[test]
+    fn test_edge_cases() {
+        assert!(diff_lines("same\n", "same\n", 3).is_empty());
+        assert_eq!(render(&diff_lines("", "new\nfile", 3)), vec!["@@ -0,0 +1,2 @@", "+new", "+file"]);
+        assert_eq!(render(&diff_lines("gone", "", 3)), vec!["@@ -1,1 +0,0 @@", "-gone"]);
+        // Moved lines: one side is kept, the other removed and re-added
+        assert_eq!(
+            render(&diff_lines("x\ny\nz", "y\nz\nx", 3)),
+            vec!["@@ -1,3 +1,3 @@", "-x", " y", " z", "+x"]
+        );
+    }
+
+    #[test]
+    fn test_from_tool_input_and_collapse() {
+        let edit = FileDiff::from_tool_input(
+            "MultiEdit",
+            &json!({"file_path": "lib.rs", "edits": [
+                {"old_string": "fn a() {}", "new_string": "fn a() {\n    todo!()\n}"},
+                {"old_string": "use x;\nuse y;", "new_string": "use y;"}
+            ]}),
+            None,
+        )
+        .unwrap();
+        assert_eq!(edit.path, "lib.rs");
+        assert_eq!(edit.summary(), "+3 -2");
+        assert_eq!(
+            render(&edit.lines),
+            vec!["@@ -1,1 +1,3 @@", "-fn a() {}", "+fn a() {", "+    todo!()", "+}", "@@ -1,2 +1,1 @@", "-use x;", " use y;"]
+        );
+
+        let content: String = (1..=100).map(|i| format!("line {}\n", i)).collect();
+        let write = FileDiff::from_tool_input("Write", &json!({"file_path": "new.txt", "content": content}), None).unwrap();
+        assert_eq!(write.summary(), "+100 -0");
+        let shown = write.lines(Some(20));
+        assert_eq!(shown.len(), 21);
+        assert_eq!(shown[20].to_string(), "… 81 more lines");
+        assert_eq!(write.lines(None).len(), 101);
+
+        let overwrite = FileDiff::from_tool_input("Write", &json!({"file_path": "f", "content": "a\nc"}), Some("a\nb")).unwrap();
+        assert_eq!(overwrite.summary(), "+1 -1");
+        assert!(FileDiff::from_tool_input("Read", &json!({"file_path": "f"}), None).is_none());
+        assert!(FileDiff::from_tool_input("Edit", &json!({"file_path": "f"}), None).is_none());
+    }
+}
@@ -0,0 +1,465 @@
+//! Line editing for console input
+//!
+//! Wraps a `rustyline` editor so the console can accept multi-line messages
+//! without sending every pasted line as a separate turn.
+//!
+//! - **Multiline blocks**: a line ending with `\` continues onto the next line,
+//!   and a line consisting of only the sentinel (default `"""`) opens a block.
+//!   A continued line is submitted by an empty line or the sentinel; a
+//!   sentinel block only by the closing sentinel, so it can hold blank lines.
+//! - **Ctrl+J** inserts a newline into the current buffer without submitting.
+//! - **Bracketed paste** keeps a pasted block (with its newlines) as one message.
+//! - **History** is persisted to a dot-file in the working directory
+//!   (`.shadow_agent_history` by default).
+//! - **Ctrl+C** discards the current input, **Ctrl+D** exits.
+//! - **Question answers**: `parse_question_answer` turns `1`, `1,3` or free
+//!   text into the answer for an `AskUserQuestion` prompt.
+//!
+//! # Manual testing
+//!
+//! The terminal behaviour can't be exercised from unit tests. To check it,
+//! run `cargo run --example test_agent` and verify:
+//!
+//! 1. Pasting a multi-line snippet produces a single `User:` turn.
+//! 2. Ctrl+J moves to a new line; Enter then submits everything typed.
+//! 3. Typing `"""`, several lines (including blank ones), then `"""` submits
+//!    one message.
+//! 4. Ctrl+C while typing clears the buffer and shows a fresh prompt.
+//! 5. Ctrl+D on an empty prompt shuts the agent down.
+//! 6. Up-arrow recalls messages from a previous run in the same directory.
+//! 7. The prompt is only redrawn after the streamed response has finished.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let mut editor = LineEditor::new(InputConfig::default())?;
+//! match editor.read_message("> ", "... ")? {
+//!     ConsoleInput::Message(text) => handle.send_input(&text).await?,
+//!     ConsoleInput::Cancelled => {}
+//!     ConsoleInput::Exit => return Ok(()),
+//! }
+//! ```
+
+use std::io;
+use std::path::PathBuf;
+
+use rustyline::config::Configurer;
+use rustyline::error::ReadlineError;
+use rustyline::{Cmd, DefaultEditor, KeyEvent};
+
+use crate::core::output::UserQuestion;
+
+/// Default sentinel that opens and closes a multiline block
+pub const DEFAULT_SENTINEL: &str = "\"\"\"";
+
+/// Default history file name, created in the working directory
+pub const DEFAULT_HISTORY_FILE: &str = ".shadow_agent_history";
+
+/// Maximum number of history entries kept by default
+const DEFAULT_MAX_HISTORY: usize = 1000;
+
+/// Configuration for console input
+#[derive(Debug, Clone)]
+pub struct InputConfig {
+    /// Line that opens and closes a multiline block
+    pub sentinel: String,
+
+    /// File used to persist history (None disables persistence)
+    pub history_file: Option<PathBuf>,
+
+    /// Maximum number of history entries
+    pub max_history: usize,
+}
+
+impl InputConfig {
+    /// Create a config with the default sentinel and history file
+    pub fn new() -> Self {
+        Self {
+            sentinel: DEFAULT_SENTINEL.to_string(),
+            history_file: Some(PathBuf::from(DEFAULT_HISTORY_FILE)),
+            max_history: DEFAULT_MAX_HISTORY,
+        }
+    }
+
+    /// Set the multiline sentinel
+    pub fn with_sentinel(mut self, sentinel: impl Into<String>) -> Self {
+        self.sentinel = sentinel.into();
+        self
+    }
+
+    /// Set the history file path
+    pub fn with_history_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.history_file = Some(path.into());
+        self
+    }
+
+    /// Disable history persistence
+    pub fn without_history(mut self) -> Self {
+        self.history_file = None;
+        self
+    }
+
+    /// Set the maximum number of history entries
+    pub fn with_max_history(mut self, max_history: usize) -> Self {
+        self.max_history = max_history;
+        self
+    }
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Result of reading input from the console
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConsoleInput {
+    /// A complete message to send to the agent
+    Message(String),
+    /// The user cancelled the current input (Ctrl+C)
+    Cancelled,
+    /// The user asked to exit (Ctrl+D)
+    Exit,
+}
+
+// ============================================================================
+// Input assembly
+// ============================================================================
+
+/// State machine that assembles editor lines into complete messages
+///
+/// Each line returned by the editor is fed in with [`push_line`](Self::push_line).
+/// A line may already contain newlines (from Ctrl+J or a bracketed paste),
+/// in which case it is still treated as a single line of input.
+#[derive(Debug, Clone)]
+pub struct InputAssembler {
+    sentinel: String,
+    buffer: Vec<String>,
+    block: Option<BlockKind>,
+}
+
+/// How the current multiline block was opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockKind {
+    /// Opened by the sentinel line
+    Sentinel,
+    /// Opened by a trailing backslash
+    Continuation,
+}
+
+impl InputAssembler {
+    /// Create a new assembler using the given sentinel
+    pub fn new(sentinel: impl Into<String>) -> Self {
+        Self {
+            sentinel: sentinel.into(),
+            buffer: Vec::new(),
+            block: None,
+        }
+    }
+
+    /// Feed a line from the editor
+    ///
+    /// Returns `Some(message)` when the input is complete, or `None` if more
+    /// lines are needed.
+    pub fn push_line(&mut self, line: &str) -> Option<String> {
+        let is_sentinel = line.trim() == self.sentinel;
+
+        match self.block {
+            None => {
+                if is_sentinel {
+                    self.block = Some(BlockKind::Sentinel);
+                    None
+                } else if let Some(stripped) = line.strip_suffix('\\') {
+                    self.buffer.push(stripped.to_string());
+                    self.block = Some(BlockKind::Continuation);
+                    None
+                } else {
+                    Some(line.to_string())
+                }
+            }
+            Some(kind) => {
+                if is_sentinel || (kind == BlockKind::Continuation && line.is_empty()) {
+                    return Some(self.finish());
+                }
+                match (kind, line.strip_suffix('\\')) {
+                    (BlockKind::Continuation, Some(stripped)) => {
+                        self.buffer.push(stripped.to_string())
+                    }
+                    _ => self.buffer.push(line.to_string()),
+                }
+                None
+            }
+        }
+    }
+
+    /// Whether a multiline block is in progress
+    pub fn is_pending(&self) -> bool {
+        self.block.is_some()
+    }
+
+    /// Discard any partially assembled input
+    pub fn cancel(&mut self) {
+        self.buffer.clear();
+        self.block = None;
+    }
+
+    /// Complete the current block and return its contents
+    fn finish(&mut self) -> String {
+        let message = self.buffer.join("\n");
+        self.cancel();
+        message
+    }
+}
+
+impl Default for InputAssembler {
+    fn default() -> Self {
+        Self::new(DEFAULT_SENTINEL)
+    }
+}
+
+// ============================================================================
+// Line editor
+// ============================================================================
+
+/// Line editor backed by rustyline
+pub struct LineEditor {
+    editor: DefaultEditor,
+    assembler: InputAssembler,
+    config: InputConfig,
+}
+
+impl LineEditor {
+    /// Create a new line editor and load any persisted history
+    pub fn new(config: InputConfig) -> io::Result<Self> {
+        let mut editor = DefaultEditor::new().map_err(to_io_error)?;
+        editor
+            .set_max_history_size(config.max_history)
+            .map_err(to_io_error)?;
+        editor.set_auto_add_history(false);
+        editor.bind_sequence(KeyEvent::ctrl('J'), Cmd::Newline);
+
+        if let Some(ref path) = config.history_file {
+            if path.exists() {
+                if let Err(e) = editor.load_history(path) {
+                    tracing::warn!("[LineEditor] Failed to load history from {:?}: {}", path, e);
+                }
+            }
+        }
+
+        Ok(Self {
+            assembler: InputAssembler::new(config.sentinel.clone()),
+            editor,
+            config,
+        })
+    }
+
+    /// Read a complete message, prompting for continuation lines as needed
+    pub fn read_message(&mut self, prompt: &str, continuation: &str) -> io::Result<ConsoleInput> {
+        loop {
+            let current = if self.assembler.is_pending() {
+                continuation
+            } else {
+                prompt
+            };
+
+            match self.editor.readline(current) {
+                Ok(line) => {
+                    if let Some(message) = self.assembler.push_line(&line) {
+                        let message = message.trim().to_string();
+                        if !message.is_empty() {
+                            self.record_history(&message);
+                        }
+                        return Ok(ConsoleInput::Message(message));
+                    }
+                }
+                Err(ReadlineError::Interrupted) => {
+                    self.assembler.cancel();
+                    return Ok(ConsoleInput::Cancelled);
+                }
+                Err(ReadlineError::Eof) => {
+                    self.assembler.cancel();
+                    return Ok(ConsoleInput::Exit);
+                }
+                Err(e) => return Err(to_io_error(e)),
+            }
+        }
+    }
+
+    /// Read a single line without multiline assembly or history
+    ///
+    /// Returns `None` if the user pressed Ctrl+C or Ctrl+D.
+    pub fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        match self.editor.readline(prompt) {
+            Ok(line) => Ok(Some(line)),
+            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => Ok(None),
+            Err(e) => Err(to_io_error(e)),
+        }
+    }
+
+    /// Add a message to history and persist it
+    fn record_history(&mut self, message: &str) {
+        if let Err(e) = self.editor.add_history_entry(message) {
+            tracing::warn!("[LineEditor] Failed to add history entry: {}", e);
+            return;
+        }
+        if let Some(ref path) = self.config.history_file {
+            if let Err(e) = self.editor.save_history(path) {
+                tracing::warn!("[LineEditor] Failed to save history to {:?}: {}", path, e);
+            }
+        }
+    }
+}
+
+/// Interpret a typed answer to a multiple-choice question
+///
+/// Options are numbered from 1, with one extra number for "Other":
+/// - `2` selects option 2; `1,3` (or `1 3`) selects several when the
+///   question allows multi-select. Selected labels are joined with `", "`.
+/// - Any input that isn't a list of option numbers is a free-text answer.
+///
+/// Returns `None` when the input is empty, picks several options on a
+/// single-select question, or is just the "Other" number - the caller should
+/// then prompt again (asking for the free text in the last case).
+pub fn parse_question_answer(question: &UserQuestion, input: &str) -> Option<String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+
+    let numbers: Option<Vec<usize>> = input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse::<usize>().ok())
+        .collect();
+    let Some(numbers) = numbers else {
+        return Some(input.to_string());
+    };
+
+    let other = question.options.len() + 1;
+    if numbers.iter().any(|&n| n == 0 || n > other) {
+        return Some(input.to_string());
+    }
+    if numbers.contains(&other) || (numbers.len() > 1 && !question.multi_select) {
+        return None;
+    }
+
+    let mut labels: Vec<&str> = Vec::new();
+    for n in numbers {
+        let label = question.options[n - 1].label.as_str();
+        if !labels.contains(&label) {
+            labels.push(label);
+        }
+    }
+    Some(labels.join(", "))
+}
+
+fn to_io_error(e: ReadlineError) -> io::Error {
+    match e {
+        ReadlineError::Io(e) => e,
+        other => io::Error::other(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_line_completes_immediately() {
+        let mut assembler = InputAssembler::default();
+        assert_eq!(assembler.push_line("hello"), Some("hello".to_string()));
+        assert!(!assembler.is_pending());
+    }
+
+    #[test]
+    fn test_embedded_newlines_stay_one_message() {
+        // Bracketed paste and Ctrl+J both deliver newlines inside one line
+        let mut assembler = InputAssembler::default();
+        let pasted = "fn main() {\n    println!(\"hi\");\n}";
+        assert_eq!(assembler.push_line(pasted), Some(pasted.to_string()));
+    }
+
+    #[test]
+    fn test_sentinel_block() {
+        let mut assembler = InputAssembler::default();
+        assert_eq!(assembler.push_line("\"\"\""), None);
+        assert!(assembler.is_pending());
+        assert_eq!(assembler.push_line("line one"), None);
+        assert_eq!(assembler.push_line("  line two"), None);
+        assert_eq!(
+            assembler.push_line("\"\"\""),
+            Some("line one\n  line two".to_string())
+        );
+        assert!(!assembler.is_pending());
+    }
+
+    #[test]
+    fn test_sentinel_block_keeps_empty_lines() {
+        let mut assembler = InputAssembler::new("EOF");
+        assert_eq!(assembler.push_line("EOF"), None);
+        assert_eq!(assembler.push_line("a"), None);
+        assert_eq!(assembler.push_line(""), None);
+        assert_eq!(assembler.push_line("b"), None);
+        assert!(assembler.is_pending());
+        assert_eq!(assembler.push_line("EOF"), Some("a\n\nb".to_string()));
+    }
+
+    #[test]
+    fn test_backslash_continuation() {
+        let mut assembler = InputAssembler::default();
+        assert_eq!(assembler.push_line("first\\"), None);
+        assert_eq!(assembler.push_line("second\\"), None);
+        assert_eq!(assembler.push_line("third"), None);
+        assert_eq!(
+            assembler.push_line(""),
+            Some("first\nsecond\nthird".to_string())
+        );
+    }
+
+    fn question(multi_select: bool) -> UserQuestion {
+        use crate::core::output::QuestionOption;
+
+        UserQuestion {
+            question: "Which approach?".to_string(),
+            header: "Approach".to_string(),
+            options: ["A", "B", "C"]
+                .iter()
+                .map(|label| QuestionOption {
+                    label: label.to_string(),
+                    description: String::new(),
+                })
+                .collect(),
+            multi_select,
+        }
+    }
+
+    #[test]
+    fn test_parse_question_answer() {
+        let single = question(false);
+        assert_eq!(parse_question_answer(&single, " 2 "), Some("B".to_string()));
+        assert_eq!(parse_question_answer(&single, "1,3"), None);
+        assert_eq!(parse_question_answer(&single, "4"), None);
+        assert_eq!(parse_question_answer(&single, ""), None);
+        assert_eq!(
+            parse_question_answer(&single, "something else"),
+            Some("something else".to_string())
+        );
+        // Out of range numbers read as free text
+        assert_eq!(parse_question_answer(&single, "42"), Some("42".to_string()));
+
+        let multi = question(true);
+        assert_eq!(parse_question_answer(&multi, "1,3"), Some("A, C".to_string()));
+        assert_eq!(parse_question_answer(&multi, "3 1 3"), Some("C, A".to_string()));
+    }
+
+    #[test]
+    fn test_cancel_discards_buffer() {
+        let mut assembler = InputAssembler::default();
+        assembler.push_line("\"\"\"");
+        assembler.push_line("draft");
+        assembler.cancel();
+        assert!(!assembler.is_pending());
+        assert_eq!(assembler.push_line("fresh"), Some("fresh".to_string()));
+    }
+}
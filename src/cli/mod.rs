@@ -1,5 +1,19 @@
 pub mod console;
+pub mod diff;
+pub mod input;
+pub mod picker;
+pub mod print_mode;
 pub mod renderer;
 
 pub use console::Console;
+pub use diff::{diff_lines, DiffLine, FileDiff};
+pub use input::{parse_question_answer, ConsoleInput, InputAssembler, InputConfig, LineEditor};
+pub use picker::{
+    filter_entries, format_age, list_session_entries, pick_session, PickOutcome, PickerKey, PickerTerminal,
+    SessionEntry, SessionPicker,
+};
+pub use print_mode::{
+    run_print_mode, run_print_mode_with_writer, OutputFormat, PrintModeOptions, PrintModeResult,
+    PrintModeStatus,
+};
 pub use renderer::ConsoleRenderer;
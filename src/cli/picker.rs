@@ -0,0 +1,662 @@
+//! Interactive session picker
+//!
+//! Lists the top-level sessions in a `SessionStorage`, most recently updated
+//! first, so a console app can offer to resume one without the user knowing
+//! its ID. Each row shows the conversation name (or the agent name), the
+//! agent type, the number of messages and how long ago it was updated.
+//!
+//! - **Up/Down** move the selection; **Enter** opens the selected row.
+//! - **Typing** filters the list with a fuzzy match on name, agent type and
+//!   session ID (the letters must appear in order); **Backspace** undoes it.
+//! - The first row, or **Ctrl+N**, starts a new session.
+//! - **Delete** (or Ctrl+D) deletes the selected session and its subagent
+//!   sessions after a `y` confirmation.
+//! - **Esc** or **Ctrl+C** cancels.
+//!
+//! The picker reads keys and draws through a `PickerTerminal`, so the
+//! selection logic can be driven from tests; `pick_session` uses the real
+//! terminal (in raw mode on Unix; elsewhere keys arrive when Enter is pressed).
+//!
+//! # Manual testing
+//!
+//! Run `cargo run --example test_agent -- --pick` in a directory with a few
+//! sessions and verify:
+//!
+//! 1. The newest session is listed first and selected.
+//! 2. Typing part of a name narrows the list; Backspace widens it again.
+//! 3. Enter on a session resumes it; Enter on the first row starts a new one.
+//! 4. Delete asks for confirmation and `y` removes the row.
+//! 5. Esc leaves the terminal in its normal mode.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let storage = SessionStorage::with_dir("./sessions");
+//! let session = match pick_session(&storage) {
+//!     Some(id) => AgentSession::load_with_storage(&id, storage)?,
+//!     None => AgentSession::new_with_storage(&new_id, "coder", "Coder", "", storage)?,
+//! };
+//! ```
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+
+use chrono::{DateTime, Utc};
+use colored::*;
+
+use crate::core::FrameworkResult;
+use crate::session::{SessionMetadata, SessionStorage};
+
+/// Session rows shown at once
+const MAX_ROWS: usize = 15;
+
+/// A session as listed by the picker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionEntry {
+    /// Session ID
+    pub session_id: String,
+    /// Conversation name, or the agent name for unnamed sessions
+    pub title: String,
+    /// Agent type
+    pub agent_type: String,
+    /// Messages in the history
+    pub message_count: usize,
+    /// When the session was last updated
+    pub updated_at: DateTime<Utc>,
+}
+
+impl SessionEntry {
+    /// Build an entry from a session's metadata
+    pub fn from_metadata(metadata: &SessionMetadata, message_count: usize) -> Self {
+        Self {
+            session_id: metadata.session_id.clone(),
+            title: metadata
+                .conversation_name
+                .clone()
+                .unwrap_or_else(|| metadata.name.clone()),
+            agent_type: metadata.agent_type.clone(),
+            message_count,
+            updated_at: metadata.updated_at,
+        }
+    }
+}
+
+/// The top-level sessions in a storage, most recently updated first
+pub fn list_session_entries(storage: &SessionStorage) -> FrameworkResult<Vec<SessionEntry>> {
+    let mut entries: Vec<SessionEntry> = storage
+        .list_sessions_with_metadata(true)?
+        .iter()
+        .map(|(id, metadata)| SessionEntry::from_metadata(metadata, storage.message_count(id)))
+        .collect();
+    sort_entries(&mut entries);
+    Ok(entries)
+}
+
+/// Sort entries newest first (by ID for equal times)
+fn sort_entries(entries: &mut [SessionEntry]) {
+    entries.sort_by(|a, b| {
+        b.updated_at
+            .cmp(&a.updated_at)
+            .then_with(|| a.session_id.cmp(&b.session_id))
+    });
+}
+
+/// Entries matching a fuzzy query, best match first
+///
+/// An entry matches when the query's letters appear in order (ignoring case)
+/// in its title, agent type or session ID. Matches with fewer gaps rank
+/// higher; equal matches keep their order. An empty query matches all.
+pub fn filter_entries<'a>(entries: &'a [SessionEntry], query: &str) -> Vec<&'a SessionEntry> {
+    let query = query.trim();
+    if query.is_empty() {
+        return entries.iter().collect();
+    }
+    let mut matches: Vec<(usize, &SessionEntry)> = entries
+        .iter()
+        .filter_map(|entry| {
+            [&entry.title, &entry.agent_type, &entry.session_id]
+                .into_iter()
+                .filter_map(|text| fuzzy_score(query, text))
+                .min()
+                .map(|score| (score, entry))
+        })
+        .collect();
+    matches.sort_by_key(|(score, _)| *score);
+    matches.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// Characters skipped between the matched letters, or `None` for no match
+///
+/// Tries every place the first letter occurs and keeps the tightest match.
+fn fuzzy_score(query: &str, text: &str) -> Option<usize> {
+    let query: Vec<char> = query.chars().flat_map(char::to_lowercase).collect();
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let (first, rest) = query.split_first()?;
+    (0..text.len())
+        .filter(|&start| text[start] == *first)
+        .filter_map(|start| {
+            let mut score = 0;
+            let mut candidates = text[start + 1..].iter();
+            for wanted in rest {
+                score += candidates.position(|c| c == wanted)?;
+            }
+            Some(score)
+        })
+        .min()
+}
+
+/// How long ago something happened, e.g. `5m ago`
+pub fn format_age(now: DateTime<Utc>, then: DateTime<Utc>) -> String {
+    let elapsed = now.signed_duration_since(then);
+    if elapsed.num_minutes() < 1 {
+        "just now".to_string()
+    } else if elapsed.num_hours() < 1 {
+        format!("{}m ago", elapsed.num_minutes())
+    } else if elapsed.num_days() < 1 {
+        format!("{}h ago", elapsed.num_hours())
+    } else {
+        format!("{}d ago", elapsed.num_days())
+    }
+}
+
+/// A key the picker reacts to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickerKey {
+    Up,
+    Down,
+    Enter,
+    Backspace,
+    /// Delete the selected session (asks first)
+    Delete,
+    /// Start a new session
+    NewSession,
+    /// Leave without choosing
+    Cancel,
+    /// A typed character (filter text, or the answer to a confirmation)
+    Char(char),
+}
+
+/// What the user chose
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PickOutcome {
+    /// Resume the session with this ID
+    Resume(String),
+    /// Start a new session
+    New,
+    /// Cancelled without choosing
+    Cancelled,
+}
+
+/// Where the picker reads keys from and draws to
+pub trait PickerTerminal {
+    /// Wait for the next key
+    fn read_key(&mut self) -> io::Result<PickerKey>;
+
+    /// Replace what is shown with these lines
+    ///
+    /// The selected row starts with `> `.
+    fn draw(&mut self, lines: &[String]) -> io::Result<()>;
+}
+
+/// Interactive session picker (see the module docs for the keys)
+pub struct SessionPicker {
+    storage: SessionStorage,
+    entries: Vec<SessionEntry>,
+    query: String,
+    /// Selected row: 0 is "start a new session", then the filtered entries
+    selected: usize,
+    /// Session waiting for a delete confirmation
+    confirm_delete: Option<SessionEntry>,
+    /// Result of the last action, shown under the list
+    status: Option<String>,
+}
+
+impl SessionPicker {
+    /// Create a picker over the top-level sessions in `storage`
+    pub fn new(storage: SessionStorage) -> FrameworkResult<Self> {
+        let entries = list_session_entries(&storage)?;
+        Ok(Self::with_entries(storage, entries))
+    }
+
+    /// Create a picker over the given entries (deletes still go to `storage`)
+    pub fn with_entries(storage: SessionStorage, mut entries: Vec<SessionEntry>) -> Self {
+        sort_entries(&mut entries);
+        Self {
+            selected: usize::from(!entries.is_empty()),
+            storage,
+            entries,
+            query: String::new(),
+            confirm_delete: None,
+            status: None,
+        }
+    }
+
+    /// Show the picker until the user chooses or cancels
+    pub fn run(&mut self, terminal: &mut dyn PickerTerminal) -> io::Result<PickOutcome> {
+        loop {
+            terminal.draw(&self.render(Utc::now()))?;
+            let key = terminal.read_key()?;
+            if let Some(outcome) = self.handle_key(key) {
+                return Ok(outcome);
+            }
+        }
+    }
+
+    /// Entries matching the current filter, in display order
+    pub fn visible(&self) -> Vec<&SessionEntry> {
+        filter_entries(&self.entries, &self.query)
+    }
+
+    /// The entry under the selection (`None` on the new session row)
+    fn selected_entry(&self) -> Option<&SessionEntry> {
+        let index = self.selected.checked_sub(1)?;
+        self.visible().get(index).copied()
+    }
+
+    /// Apply a key, returning the outcome once the user has chosen
+    pub fn handle_key(&mut self, key: PickerKey) -> Option<PickOutcome> {
+        if let Some(entry) = self.confirm_delete.take() {
+            self.status = Some(match key {
+                PickerKey::Char('y' | 'Y') => self.delete(&entry),
+                _ => format!("Kept \"{}\"", entry.title),
+            });
+            return None;
+        }
+
+        self.status = None;
+        match key {
+            PickerKey::Up => self.selected = self.selected.saturating_sub(1),
+            PickerKey::Down => self.selected = (self.selected + 1).min(self.visible().len()),
+            PickerKey::Enter => {
+                return Some(match self.selected_entry() {
+                    Some(entry) => PickOutcome::Resume(entry.session_id.clone()),
+                    None => PickOutcome::New,
+                });
+            }
+            PickerKey::Delete => self.confirm_delete = self.selected_entry().cloned(),
+            PickerKey::NewSession => return Some(PickOutcome::New),
+            PickerKey::Cancel => return Some(PickOutcome::Cancelled),
+            PickerKey::Char(c) => {
+                self.query.push(c);
+                self.select_first_match();
+            }
+            PickerKey::Backspace => {
+                self.query.pop();
+                self.select_first_match();
+            }
+        }
+        None
+    }
+
+    /// Select the best match after the filter changed
+    fn select_first_match(&mut self) {
+        self.selected = usize::from(!self.visible().is_empty());
+    }
+
+    /// Delete a session and its subagents, returning the status line
+    fn delete(&mut self, entry: &SessionEntry) -> String {
+        match self.storage.delete_session_tree(&entry.session_id) {
+            Ok(deleted) => {
+                self.entries.retain(|e| !deleted.contains(&e.session_id));
+                self.selected = self.selected.min(self.visible().len());
+                match deleted.len() - 1 {
+                    0 => format!("Deleted \"{}\"", entry.title),
+                    subagents => format!("Deleted \"{}\" and {} subagent session(s)", entry.title, subagents),
+                }
+            }
+            Err(e) => {
+                tracing::warn!("[SessionPicker] Failed to delete {}: {}", entry.session_id, e);
+                format!("Failed to delete \"{}\": {}", entry.title, e)
+            }
+        }
+    }
+
+    /// The lines to show, with ages relative to `now`
+    pub fn render(&self, now: DateTime<Utc>) -> Vec<String> {
+        let visible = self.visible();
+        let marker = |row: usize| if row == self.selected { "> " } else { "  " };
+
+        let mut lines = vec![
+            "Pick a session (type to filter, Enter to open, Del to delete, Esc to cancel)".to_string(),
+            format!("Filter: {}", self.query),
+            String::new(),
+            format!("{}+ Start a new session", marker(0)),
+        ];
+        if visible.is_empty() && !self.entries.is_empty() {
+            lines.push("  (no sessions match)".to_string());
+        }
+
+        // Scroll so the selection stays in view
+        let first = self.selected.saturating_sub(MAX_ROWS).min(visible.len().saturating_sub(MAX_ROWS));
+        for (offset, entry) in visible.iter().skip(first).take(MAX_ROWS).enumerate() {
+            lines.push(format!(
+                "{}{:<40} {:<14} {:>5} msgs  {}",
+                marker(first + offset + 1),
+                truncate(&entry.title, 40),
+                truncate(&entry.agent_type, 14),
+                entry.message_count,
+                format_age(now, entry.updated_at)
+            ));
+        }
+        if visible.len() > first + MAX_ROWS {
+            lines.push(format!("  ... {} more", visible.len() - first - MAX_ROWS));
+        }
+
+        if let Some(ref entry) = self.confirm_delete {
+            lines.push(String::new());
+            lines.push(format!("Delete \"{}\" and its subagent sessions? (y/n)", entry.title));
+        } else if let Some(ref status) = self.status {
+            lines.push(String::new());
+            lines.push(status.clone());
+        }
+        lines
+    }
+}
+
+/// Shorten text to `max` characters, ending with `…` when cut
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(max - 1).collect();
+    short.push('…');
+    short
+}
+
+/// Let the user pick a session to resume from the terminal
+///
+/// Returns the chosen session's ID, or `None` when they chose to start a new
+/// session, cancelled, or the terminal couldn't be used (logged).
+pub fn pick_session(storage: &SessionStorage) -> Option<String> {
+    let mut picker = match SessionPicker::new(storage.clone()) {
+        Ok(picker) => picker,
+        Err(e) => {
+            tracing::warn!("[SessionPicker] Failed to list sessions: {}", e);
+            return None;
+        }
+    };
+    let outcome = StdTerminal::new().and_then(|mut terminal| picker.run(&mut terminal));
+    match outcome {
+        Ok(PickOutcome::Resume(session_id)) => Some(session_id),
+        Ok(_) => None,
+        Err(e) => {
+            tracing::warn!("[SessionPicker] Terminal error: {}", e);
+            None
+        }
+    }
+}
+
+/// Decode the bytes of one read from the terminal into keys
+fn parse_keys(bytes: &[u8]) -> Vec<PickerKey> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut chars = text.chars().peekable();
+    let mut keys = Vec::new();
+    while let Some(c) = chars.next() {
+        let key = match c {
+            '\x1b' => match (chars.peek().copied(), chars.clone().nth(1)) {
+                (Some('[' | 'O'), Some(code)) => {
+                    chars.nth(1);
+                    match code {
+                        'A' => Some(PickerKey::Up),
+                        'B' => Some(PickerKey::Down),
+                        '3' if chars.peek() == Some(&'~') => {
+                            chars.next();
+                            Some(PickerKey::Delete)
+                        }
+                        _ => None,
+                    }
+                }
+                _ => Some(PickerKey::Cancel),
+            },
+            '\r' | '\n' => Some(PickerKey::Enter),
+            '\x7f' | '\x08' => Some(PickerKey::Backspace),
+            '\x03' => Some(PickerKey::Cancel),
+            '\x04' => Some(PickerKey::Delete),
+            '\x0e' => Some(PickerKey::NewSession),
+            c if c.is_control() => None,
+            c => Some(PickerKey::Char(c)),
+        };
+        keys.extend(key);
+    }
+    keys
+}
+
+/// The process's terminal, in raw mode while the picker runs
+struct StdTerminal {
+    pending: VecDeque<PickerKey>,
+    #[cfg(unix)]
+    original: libc::termios,
+}
+
+impl StdTerminal {
+    #[cfg(unix)]
+    fn new() -> io::Result<Self> {
+        // SAFETY: termios is plain data; tcgetattr fills it in before it is read
+        unsafe {
+            let mut original: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut raw = original;
+            libc::cfmakeraw(&mut raw);
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self {
+                pending: VecDeque::new(),
+                original,
+            })
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn new() -> io::Result<Self> {
+        Ok(Self {
+            pending: VecDeque::new(),
+        })
+    }
+}
+
+impl PickerTerminal for StdTerminal {
+    fn read_key(&mut self) -> io::Result<PickerKey> {
+        loop {
+            if let Some(key) = self.pending.pop_front() {
+                return Ok(key);
+            }
+            let mut buf = [0u8; 64];
+            let n = io::stdin().read(&mut buf)?;
+            if n == 0 {
+                return Ok(PickerKey::Cancel);
+            }
+            self.pending.extend(parse_keys(&buf[..n]));
+        }
+    }
+
+    fn draw(&mut self, lines: &[String]) -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        // Raw mode doesn't turn \n into \r\n
+        write!(out, "\x1b[H\x1b[2J")?;
+        for line in lines {
+            if line.starts_with("> ") {
+                write!(out, "{}\r\n", line.bold())?;
+            } else {
+                write!(out, "{}\r\n", line)?;
+            }
+        }
+        out.flush()
+    }
+}
+
+impl Drop for StdTerminal {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        // SAFETY: restores the settings read in `new`
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+        print!("\x1b[H\x1b[2J");
+        let _ = io::stdout().flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn entry(id: &str, title: &str, agent_type: &str, updated_hours_ago: i64) -> SessionEntry {
+        SessionEntry {
+            session_id: id.to_string(),
+            title: title.to_string(),
+            agent_type: agent_type.to_string(),
+            message_count: 4,
+            updated_at: Utc::now() - Duration::hours(updated_hours_ago),
+        }
+    }
+
+    fn fixtures() -> Vec<SessionEntry> {
+        vec![
+            entry("s-old", "Fix login bug", "coder", 48),
+            entry("s-new", "Refactor parser", "coder", 1),
+            entry("s-mid", "Research caching", "researcher", 5),
+        ]
+    }
+
+    fn ids(entries: &[&SessionEntry]) -> Vec<String> {
+        entries.iter().map(|e| e.session_id.clone()).collect()
+    }
+
+    /// A terminal that plays back keys and keeps the last frame
+    struct ScriptedTerminal {
+        keys: VecDeque<PickerKey>,
+        frame: Vec<String>,
+    }
+
+    impl PickerTerminal for ScriptedTerminal {
+        fn read_key(&mut self) -> io::Result<PickerKey> {
+            Ok(self.keys.pop_front().unwrap_or(PickerKey::Cancel))
+        }
+
+        fn draw(&mut self, lines: &[String]) -> io::Result<()> {
+            self.frame = lines.to_vec();
+            Ok(())
+        }
+    }
+
+    fn run(picker: &mut SessionPicker, keys: Vec<PickerKey>) -> (PickOutcome, Vec<String>) {
+        let mut terminal = ScriptedTerminal {
+            keys: keys.into(),
+            frame: Vec::new(),
+        };
+        let outcome = picker.run(&mut terminal).unwrap();
+        (outcome, terminal.frame)
+    }
+
+    fn typed(text: &str) -> Vec<PickerKey> {
+        text.chars().map(PickerKey::Char).collect()
+    }
+
+    #[test]
+    fn test_sorting_and_fuzzy_filtering() {
+        let mut entries = fixtures();
+        sort_entries(&mut entries);
+        assert_eq!(ids(&filter_entries(&entries, "")), vec!["s-new", "s-mid", "s-old"]);
+
+        // Letters in order, case-insensitive, across title, type and ID
+        assert_eq!(ids(&filter_entries(&entries, "FXbug")), vec!["s-old"]);
+        assert_eq!(ids(&filter_entries(&entries, "research")), vec!["s-mid"]);
+        assert!(filter_entries(&entries, "zzz").is_empty());
+
+        // A tighter match ranks first; equal ones stay newest first
+        assert_eq!(ids(&filter_entries(&entries, "rc")), vec!["s-mid", "s-new"]);
+        assert_eq!(ids(&filter_entries(&entries, "coder")), vec!["s-new", "s-old"]);
+    }
+
+    #[test]
+    fn test_format_age() {
+        let now = Utc::now();
+        assert_eq!(format_age(now, now - Duration::seconds(20)), "just now");
+        assert_eq!(format_age(now, now - Duration::minutes(5)), "5m ago");
+        assert_eq!(format_age(now, now - Duration::hours(3)), "3h ago");
+        assert_eq!(format_age(now, now - Duration::days(2)), "2d ago");
+    }
+
+    #[test]
+    fn test_navigation_and_selection() {
+        let storage = SessionStorage::with_dir(tempfile::tempdir().unwrap().path());
+        let mut picker = SessionPicker::with_entries(storage.clone(), fixtures());
+
+        // The newest session starts selected
+        let (outcome, frame) = run(&mut picker, vec![PickerKey::Enter]);
+        assert_eq!(outcome, PickOutcome::Resume("s-new".to_string()));
+        assert!(frame[4].starts_with("> Refactor parser"), "{:?}", frame);
+        assert!(frame[4].ends_with("1h ago"), "{:?}", frame);
+
+        let mut picker = SessionPicker::with_entries(storage.clone(), fixtures());
+        let keys = vec![PickerKey::Down, PickerKey::Down, PickerKey::Down, PickerKey::Up, PickerKey::Enter];
+        assert_eq!(run(&mut picker, keys).0, PickOutcome::Resume("s-mid".to_string()));
+
+        // Up past the sessions reaches the new session row
+        let mut picker = SessionPicker::with_entries(storage.clone(), fixtures());
+        assert_eq!(run(&mut picker, vec![PickerKey::Up, PickerKey::Up, PickerKey::Enter]).0, PickOutcome::New);
+
+        // Typing filters and selects the best match
+        let mut picker = SessionPicker::with_entries(storage.clone(), fixtures());
+        let mut keys = typed("logn");
+        keys.extend([PickerKey::Backspace, PickerKey::Char('i'), PickerKey::Enter]);
+        assert_eq!(run(&mut picker, keys).0, PickOutcome::Resume("s-old".to_string()));
+
+        let mut picker = SessionPicker::with_entries(storage, fixtures());
+        let mut keys = typed("zzz");
+        keys.push(PickerKey::Enter);
+        assert_eq!(run(&mut picker, keys).0, PickOutcome::New);
+    }
+
+    #[test]
+    fn test_delete_asks_first_and_cascades() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = SessionStorage::with_dir(dir.path());
+        let mut parent = SessionMetadata::new("s-new", "coder", "Coder", "");
+        parent.conversation_name = Some("Refactor parser".to_string());
+        storage.save_metadata(&parent).unwrap();
+        storage
+            .save_metadata(&SessionMetadata::new_subagent("s-sub", "sub", "Sub", "", "s-new", "toolu_1"))
+            .unwrap();
+        let mut other = SessionMetadata::new("s-other", "coder", "Other", "");
+        other.updated_at -= Duration::hours(1);
+        storage.save_metadata(&other).unwrap();
+
+        let mut picker = SessionPicker::new(storage.clone()).unwrap();
+        assert_eq!(picker.visible().len(), 2);
+
+        // Anything but "y" keeps the session
+        assert_eq!(picker.handle_key(PickerKey::Delete), None);
+        assert!(picker.render(Utc::now()).last().unwrap().contains("? (y/n)"));
+        picker.handle_key(PickerKey::Char('n'));
+        assert!(storage.session_exists("s-new"));
+
+        let (outcome, frame) = run(&mut picker, vec![PickerKey::Delete, PickerKey::Char('y')]);
+        assert_eq!(outcome, PickOutcome::Cancelled);
+        assert_eq!(frame.last().unwrap(), "Deleted \"Refactor parser\" and 1 subagent session(s)");
+        assert!(!storage.session_exists("s-new") && !storage.session_exists("s-sub"));
+        assert_eq!(ids(&picker.visible()), vec!["s-other"]);
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(
+            parse_keys(b"\x1b[A\x1b[Bab\x7f\r"),
+            vec![
+                PickerKey::Up,
+                PickerKey::Down,
+                PickerKey::Char('a'),
+                PickerKey::Char('b'),
+                PickerKey::Backspace,
+                PickerKey::Enter
+            ]
+        );
+        assert_eq!(parse_keys(b"\x1b"), vec![PickerKey::Cancel]);
+        assert_eq!(parse_keys(b"\x1b[3~\x0e\x03"), vec![PickerKey::Delete, PickerKey::NewSession, PickerKey::Cancel]);
+        assert_eq!(parse_keys("é".as_bytes()), vec![PickerKey::Char('é')]);
+    }
+}
@@ -0,0 +1,568 @@
+//! Print Mode - Run a single prompt non-interactively
+//!
+//! Print mode sends one prompt to an agent, waits for the turn to finish and
+//! writes the result to stdout. It is meant for scripts and CI:
+//!
+//! - **Output formats**: `text` (final assistant text), `json` (one object with
+//!   the result and every serialized `OutputChunk`), or `stream-json` (one
+//!   serialized `ChunkEnvelope` per line as it arrives: `seq`, `timestamp`,
+//!   `turn` and the `chunk`)
+//! - **Permissions**: requests are answered automatically - tools in the allow
+//!   list are permitted, everything else is denied
+//! - **Questions**: `AskUserQuestion` is answered with the first option
+//! - **Limits**: a maximum number of LLM turns and an overall timeout; the agent
+//!   is interrupted when either is hit
+//!
+//! The process exit code is available from [`PrintModeResult::exit_code`].
+//!
+//! # Example
+//!
+//! ```ignore
+//! let handle = runtime.spawn(session, |internals| agent.run(internals)).await;
+//! let options = PrintModeOptions::new()
+//!     .with_output_format(OutputFormat::Json)
+//!     .with_allowed_tools(["Read", "Glob"])
+//!     .with_max_turns(10);
+//! let result = run_print_mode(&handle, "Summarize README.md", options).await?;
+//! std::process::exit(result.exit_code());
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::time::Instant;
+
+use crate::core::{AgentState, OutputChunk};
+use crate::runtime::AgentHandle;
+
+/// Output format for print mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Only the final assistant text
+    #[default]
+    Text,
+    /// A single JSON object with the result and all output chunks
+    Json,
+    /// One JSON-serialized `ChunkEnvelope` per line
+    StreamJson,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "stream-json" => Ok(OutputFormat::StreamJson),
+            other => Err(format!(
+                "Unknown output format '{}' (expected text, json or stream-json)",
+                other
+            )),
+        }
+    }
+}
+
+/// Options for a print mode run
+#[derive(Debug, Clone, Default)]
+pub struct PrintModeOptions {
+    /// How to write the output
+    pub output_format: OutputFormat,
+
+    /// Tools that are allowed when the agent asks for permission ("*" allows all)
+    pub allowed_tools: Vec<String>,
+
+    /// Maximum number of LLM turns before the agent is interrupted
+    pub max_turns: Option<usize>,
+
+    /// Maximum wall-clock time before the agent is interrupted
+    pub timeout: Option<Duration>,
+}
+
+impl PrintModeOptions {
+    /// Create options with text output and no limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the output format
+    pub fn with_output_format(mut self, format: OutputFormat) -> Self {
+        self.output_format = format;
+        self
+    }
+
+    /// Set the tools that are allowed without asking
+    pub fn with_allowed_tools<I, S>(mut self, tools: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allowed_tools = tools.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the maximum number of LLM turns
+    pub fn with_max_turns(mut self, max_turns: usize) -> Self {
+        self.max_turns = Some(max_turns);
+        self
+    }
+
+    /// Set the overall timeout
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Whether a tool is in the allow list
+    pub fn is_tool_allowed(&self, tool_name: &str) -> bool {
+        self.allowed_tools
+            .iter()
+            .any(|t| t == "*" || t == tool_name)
+    }
+}
+
+/// How a print mode run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrintModeStatus {
+    /// The turn completed without errors
+    Success,
+    /// The agent reported an error
+    Error,
+    /// The maximum number of turns was reached
+    MaxTurns,
+    /// The timeout elapsed
+    Timeout,
+}
+
+impl PrintModeStatus {
+    /// Process exit code for this status
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            PrintModeStatus::Success => 0,
+            PrintModeStatus::Error => 1,
+            PrintModeStatus::MaxTurns => 2,
+            PrintModeStatus::Timeout => 124,
+        }
+    }
+}
+
+impl fmt::Display for PrintModeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrintModeStatus::Success => write!(f, "success"),
+            PrintModeStatus::Error => write!(f, "error"),
+            PrintModeStatus::MaxTurns => write!(f, "max_turns"),
+            PrintModeStatus::Timeout => write!(f, "timeout"),
+        }
+    }
+}
+
+/// Result of a print mode run
+#[derive(Debug, Clone, Serialize)]
+pub struct PrintModeResult {
+    /// Session ID of the agent
+    pub session_id: String,
+
+    /// How the run ended
+    pub status: PrintModeStatus,
+
+    /// Final assistant text (from the last LLM turn)
+    pub result: String,
+
+    /// Number of LLM turns that were started
+    pub num_turns: usize,
+
+    /// Error messages reported by the agent
+    pub errors: Vec<String>,
+
+    /// Every output chunk received, in order
+    pub chunks: Vec<OutputChunk>,
+}
+
+impl PrintModeResult {
+    /// Whether the run did not complete successfully
+    pub fn is_error(&self) -> bool {
+        self.status != PrintModeStatus::Success
+    }
+
+    /// Process exit code (non-zero on errors, max turns, or timeout)
+    pub fn exit_code(&self) -> i32 {
+        self.status.exit_code()
+    }
+}
+
+/// Run a single prompt and write the result to stdout
+pub async fn run_print_mode(
+    handle: &AgentHandle,
+    prompt: &str,
+    options: PrintModeOptions,
+) -> io::Result<PrintModeResult> {
+    let mut stdout = io::stdout();
+    run_print_mode_with_writer(handle, prompt, options, &mut stdout).await
+}
+
+/// Run a single prompt and write the result to the given writer
+pub async fn run_print_mode_with_writer<W: Write>(
+    handle: &AgentHandle,
+    prompt: &str,
+    options: PrintModeOptions,
+    writer: &mut W,
+) -> io::Result<PrintModeResult> {
+    // Subscribe before sending so no output is missed
+    let mut rx = handle.subscribe();
+    let deadline = options.timeout.map(|t| Instant::now() + t);
+
+    handle.send_input(prompt).await.map_err(io::Error::other)?;
+
+    let mut result = PrintModeResult {
+        session_id: handle.session_id().to_string(),
+        status: PrintModeStatus::Success,
+        result: String::new(),
+        num_turns: 0,
+        errors: Vec::new(),
+        chunks: Vec::new(),
+    };
+    let mut turn_text: Vec<String> = Vec::new();
+    let mut blocked_on_user = false;
+
+    loop {
+        let received = match deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline, rx.recv_envelope()).await {
+                Ok(received) => received,
+                Err(_) => {
+                    tracing::warn!("[PrintMode] Timed out after {:?}", options.timeout);
+                    result.status = PrintModeStatus::Timeout;
+                    let _ = handle.interrupt().await;
+                    break;
+                }
+            },
+            None => rx.recv_envelope().await,
+        };
+
+        let envelope = match received {
+            Ok(envelope) => envelope,
+            Err(_) => {
+                result.status = PrintModeStatus::Error;
+                result.errors.push("Agent output channel closed".to_string());
+                break;
+            }
+        };
+
+        // Kept in the output so the gap is visible
+        if let OutputChunk::Dropped { count } = envelope.chunk {
+            tracing::warn!("[PrintMode] Output lagged, skipped {} chunks", count);
+        }
+
+        // Each LLM call starts with a Processing state change, but so does
+        // the return from a permission prompt or question in the middle of one
+        let resumed = blocked_on_user;
+        if let OutputChunk::StateChange(state) = &envelope.chunk {
+            blocked_on_user = state.is_blocked_on_user();
+        }
+        if let (OutputChunk::StateChange(AgentState::Processing), false) = (&envelope.chunk, resumed) {
+            result.num_turns += 1;
+            if let Some(max_turns) = options.max_turns {
+                if result.num_turns > max_turns {
+                    tracing::info!("[PrintMode] Max turns ({}) reached", max_turns);
+                    result.num_turns = max_turns;
+                    result.status = PrintModeStatus::MaxTurns;
+                    let _ = handle.interrupt().await;
+                    break;
+                }
+            }
+            turn_text.clear();
+        }
+
+        if options.output_format == OutputFormat::StreamJson {
+            writeln!(writer, "{}", serde_json::to_string(&envelope)?)?;
+            writer.flush()?;
+        }
+        let chunk = envelope.chunk;
+
+        match &chunk {
+            OutputChunk::TextComplete(text) => turn_text.push(text.clone()),
+            OutputChunk::PermissionRequest { request_id, tool_name, .. } => {
+                let allowed = options.is_tool_allowed(tool_name);
+                tracing::info!(
+                    "[PrintMode] {} {}",
+                    if allowed { "Allowing" } else { "Denying" },
+                    tool_name
+                );
+                let _ = handle
+                    .send_permission_response_by_id(request_id.clone(), allowed, false)
+                    .await;
+            }
+            OutputChunk::AskUserQuestion {
+                request_id,
+                questions,
+            } => {
+                // No one to ask - pick the first option for each question
+                let answers: HashMap<String, String> = questions
+                    .iter()
+                    .filter_map(|q| {
+                        q.options
+                            .first()
+                            .map(|opt| (q.header.clone(), opt.label.clone()))
+                    })
+                    .collect();
+                let _ = handle
+                    .send_question_response(request_id.clone(), answers)
+                    .await;
+            }
+            OutputChunk::Error(message) => {
+                result.status = PrintModeStatus::Error;
+                result.errors.push(message.clone());
+            }
+            _ => {}
+        }
+
+        let finished = matches!(
+            chunk,
+            OutputChunk::Done | OutputChunk::StateChange(AgentState::Done)
+        );
+        result.chunks.push(chunk);
+        if finished {
+            break;
+        }
+    }
+
+    result.result = turn_text.join("\n");
+
+    match options.output_format {
+        OutputFormat::Text => {
+            if !result.result.is_empty() {
+                writeln!(writer, "{}", result.result)?;
+            }
+            for error in &result.errors {
+                eprintln!("Error: {}", error);
+            }
+            if matches!(
+                result.status,
+                PrintModeStatus::MaxTurns | PrintModeStatus::Timeout
+            ) {
+                eprintln!("Stopped: {}", result.status);
+            }
+        }
+        OutputFormat::Json => {
+            writeln!(writer, "{}", serde_json::to_string(&result)?)?;
+        }
+        OutputFormat::StreamJson => {
+            // Chunks were already streamed; finish with a summary line
+            let summary = serde_json::json!({
+                "session_id": result.session_id,
+                "status": result.status,
+                "result": result.result,
+                "num_turns": result.num_turns,
+                "errors": result.errors,
+            });
+            writeln!(writer, "{}", summary)?;
+        }
+    }
+    writer.flush()?;
+
+    Ok(result)
+}
+
+/// Run `prompt` in print mode on a `StandardAgent`, with every tool allowed
+///
+/// The agent runs in `session` on a runtime of its own, which is shut down
+/// before returning.
+#[cfg(test)]
+pub(crate) async fn run_mock_agent(
+    config: crate::agent::AgentConfig,
+    llm: std::sync::Arc<dyn crate::llm::LlmProvider>,
+    session: crate::session::AgentSession,
+    prompt: &str,
+) -> PrintModeResult {
+    let runtime = crate::runtime::AgentRuntime::new();
+    let options = crate::runtime::SpawnOptions::new();
+    let result = run_mock_agent_on(&runtime, options, config, llm, session, prompt).await;
+    runtime.shutdown_all().await;
+    result
+}
+
+/// `run_mock_agent` on the caller's runtime, spawned with `options`; the
+/// runtime keeps running
+#[cfg(test)]
+pub(crate) async fn run_mock_agent_on(
+    runtime: &crate::runtime::AgentRuntime,
+    options: crate::runtime::SpawnOptions,
+    config: crate::agent::AgentConfig,
+    llm: std::sync::Arc<dyn crate::llm::LlmProvider>,
+    session: crate::session::AgentSession,
+    prompt: &str,
+) -> PrintModeResult {
+    let agent = crate::agent::StandardAgent::new(config, llm);
+    let handle = runtime.spawn_with(session, agent, options).await;
+    let options = PrintModeOptions::new().with_allowed_tools(["*"]);
+    run_print_mode_with_writer(&handle, prompt, options, &mut Vec::new())
+        .await
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use crate::agent::{AgentConfig, StandardAgent};
+    use crate::llm::MockLlmProvider;
+    use crate::runtime::AgentRuntime;
+    use crate::session::{AgentSession, SessionStorage};
+
+    async fn spawn_agent(
+        runtime: &AgentRuntime,
+        dir: &tempfile::TempDir,
+        llm: MockLlmProvider,
+    ) -> AgentHandle {
+        let storage = SessionStorage::with_dir(dir.path());
+        let session =
+            AgentSession::new_with_storage("print-test", "test", "Test", "Print mode test", storage)
+                .unwrap();
+        let config = AgentConfig::new("You are a test agent").with_auto_name(false);
+        let agent = StandardAgent::new(config, Arc::new(llm));
+        runtime
+            .spawn(session, move |internals| agent.run(internals))
+            .await
+    }
+
+    #[test]
+    fn test_output_format_from_str() {
+        assert_eq!("text".parse::<OutputFormat>(), Ok(OutputFormat::Text));
+        assert_eq!("json".parse::<OutputFormat>(), Ok(OutputFormat::Json));
+        assert_eq!(
+            "stream-json".parse::<OutputFormat>(),
+            Ok(OutputFormat::StreamJson)
+        );
+        assert!("yaml".parse::<OutputFormat>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_json_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let handle = spawn_agent(
+            &runtime,
+            &dir,
+            MockLlmProvider::new().with_text("Hello from the mock"),
+        )
+        .await;
+
+        let mut out = Vec::new();
+        let options = PrintModeOptions::new().with_output_format(OutputFormat::Json);
+        let result = run_print_mode_with_writer(&handle, "Hi", options, &mut out)
+            .await
+            .unwrap();
+
+        assert_eq!(result.exit_code(), 0);
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["status"], "success");
+        assert_eq!(json["result"], "Hello from the mock");
+        assert_eq!(json["num_turns"], 1);
+        let chunks = json["chunks"].as_array().unwrap();
+        assert!(chunks
+            .iter()
+            .any(|c| c["TextComplete"] == "Hello from the mock"));
+        assert_eq!(chunks.last().unwrap(), "Done");
+
+        runtime.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_max_turns_cutoff() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        // The model keeps calling tools forever
+        let llm = MockLlmProvider::new().with_fallback(MockLlmProvider::tool_use_response(
+            "tool_1",
+            "Echo",
+            serde_json::json!({}),
+        ));
+        let handle = spawn_agent(&runtime, &dir, llm.clone()).await;
+
+        let mut out = Vec::new();
+        let options = PrintModeOptions::new()
+            .with_output_format(OutputFormat::StreamJson)
+            .with_max_turns(2)
+            .with_timeout(Duration::from_secs(10));
+        let result = run_print_mode_with_writer(&handle, "Loop", options, &mut out)
+            .await
+            .unwrap();
+
+        assert_eq!(result.status, PrintModeStatus::MaxTurns);
+        assert_ne!(result.exit_code(), 0);
+        assert_eq!(result.num_turns, 2);
+
+        // Every line is valid JSON, ending with the summary
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.last().unwrap()["status"], "max_turns");
+
+        runtime.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_permission_prompt_is_not_a_turn() {
+        use crate::tools::common::BashTool;
+        use crate::tools::ToolRegistry;
+
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let mut registry = ToolRegistry::new();
+        registry.register(BashTool::with_working_dir(dir.path().to_string_lossy()));
+        let config = AgentConfig::new("You are a test agent")
+            .with_tools(Arc::new(registry))
+            .with_auto_name(false);
+        let llm = MockLlmProvider::new()
+            .with_response(MockLlmProvider::tool_use_response(
+                "call_1",
+                "Bash",
+                serde_json::json!({"command": "echo hi"}),
+            ))
+            .with_text("done");
+        let storage = SessionStorage::with_dir(dir.path().join("sessions"));
+        let session = AgentSession::new_with_storage("print-test", "test", "Test", "", storage).unwrap();
+        let agent = StandardAgent::new(config, Arc::new(llm));
+        let handle = runtime.spawn(session, move |internals| agent.run(internals)).await;
+
+        let options = PrintModeOptions::new().with_allowed_tools(["Bash"]).with_max_turns(2);
+        let result = run_print_mode_with_writer(&handle, "Go", options, &mut Vec::new())
+            .await
+            .unwrap();
+
+        assert!(result.chunks.iter().any(|c| matches!(c, OutputChunk::PermissionRequest { .. })));
+        assert_eq!(result.status, PrintModeStatus::Success);
+        assert_eq!(result.num_turns, 2);
+        assert_eq!(result.result, "done");
+
+        runtime.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_error_chunk_sets_exit_code() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        // No scripted responses, so the LLM call fails
+        let handle = spawn_agent(&runtime, &dir, MockLlmProvider::new()).await;
+
+        let mut out = Vec::new();
+        let options = PrintModeOptions::new().with_output_format(OutputFormat::Json);
+        let result = run_print_mode_with_writer(&handle, "Hi", options, &mut out)
+            .await
+            .unwrap();
+
+        assert_eq!(result.status, PrintModeStatus::Error);
+        assert_eq!(result.exit_code(), 1);
+        assert!(!result.errors.is_empty());
+
+        runtime.shutdown_all().await;
+    }
+}
@@ -7,16 +7,31 @@
 //! - Is completely decoupled from the agent logic
 //!
 //! This can be replaced with other renderers (Tauri UI, Web UI, etc.)
+//!
+//! The renderer works with any `AgentConnection`, so the same code renders
+//! a local `AgentHandle` or a `remote::RemoteAgentHandle`.
 
+use std::collections::{HashMap, HashSet};
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use crate::core::{InputMessage, OutputChunk};
+use crate::core::{OutputChunk, PresentedFile};
 use crate::helpers::TodoListManager;
-use crate::permissions::PermissionDecision;
-use crate::runtime::AgentHandle;
+use crate::permissions::{PermissionDecision, PermissionScope, RuleId};
+use crate::runtime::{AgentConnection, AgentHandle};
+
+use super::console::{current_contents, Console};
+use super::diff::{FileDiff, DIFF_TOOLS};
+use super::input::{ConsoleInput, InputConfig};
+
+/// Tools whose input is printed live as the model writes it
+///
+/// File edits aren't: they're shown as a diff once they run.
+const LIVE_INPUT_TOOLS: &[&str] = &["Bash"];
 
-use super::console::Console;
+/// Lines of a presented text file shown in its card
+const FILE_PREVIEW_LINES: usize = 10;
 
 /// Console renderer that subscribes to an agent and handles terminal I/O
 ///
@@ -27,9 +42,9 @@ use super::console::Console;
 /// let renderer = ConsoleRenderer::new(handle);
 /// renderer.run().await?;
 /// ```
-pub struct ConsoleRenderer {
+pub struct ConsoleRenderer<H = AgentHandle> {
     /// The agent handle to communicate with
-    handle: AgentHandle,
+    handle: H,
 
     /// The console for formatted output
     console: Console,
@@ -41,9 +56,16 @@ pub struct ConsoleRenderer {
     show_tools: bool,
 }
 
-impl ConsoleRenderer {
+/// A running file edit, shown as a diff when it finishes
+struct PendingDiff {
+    diff: FileDiff,
+    /// Already shown in the permission prompt
+    previewed: bool,
+}
+
+impl<H: AgentConnection> ConsoleRenderer<H> {
     /// Create a new console renderer for an agent
-    pub fn new(handle: AgentHandle) -> Self {
+    pub fn new(handle: H) -> Self {
         Self {
             handle,
             console: Console::new(),
@@ -53,7 +75,7 @@ impl ConsoleRenderer {
     }
 
     /// Create a renderer with a custom console
-    pub fn with_console(handle: AgentHandle, console: Console) -> Self {
+    pub fn with_console(handle: H, console: Console) -> Self {
         Self {
             handle,
             console,
@@ -74,6 +96,20 @@ impl ConsoleRenderer {
         self
     }
 
+    /// Set whether long Edit/Write diffs are shown in full
+    ///
+    /// By default they collapse after 40 lines with a "… N more lines" footer.
+    pub fn expand_diffs(mut self, expand: bool) -> Self {
+        self.console.set_expand_diffs(expand);
+        self
+    }
+
+    /// Set the input configuration (multiline sentinel, history file)
+    pub fn with_input_config(mut self, config: InputConfig) -> Self {
+        self.console.set_input_config(config);
+        self
+    }
+
     /// Set the todo manager for displaying task progress
     pub fn with_todo_manager(mut self, manager: Arc<TodoListManager>) -> Self {
         self.console.set_todo_manager(manager);
@@ -88,13 +124,23 @@ impl ConsoleRenderer {
     /// 3. Renders streaming output
     /// 4. Handles permission requests
     ///
-    /// Returns when the user types "exit" or the agent shuts down.
+    /// Returns when the user types "exit", presses Ctrl+D, or the agent shuts down.
+    /// Ctrl+C discards the current input and shows a fresh prompt.
     pub async fn run(&self) -> io::Result<()> {
         self.console.print_banner();
 
         loop {
-            // Read user input
-            let input = self.console.read_input()?;
+            // Read user input (the prompt is only drawn once the previous
+            // response has finished rendering)
+            let input = match self.console.read_input()? {
+                ConsoleInput::Message(input) => input,
+                ConsoleInput::Cancelled => continue,
+                ConsoleInput::Exit => {
+                    self.console.print_system("Shutting down...");
+                    let _ = self.handle.shutdown().await;
+                    break;
+                }
+            };
 
             // Check for exit commands
             if input.eq_ignore_ascii_case("exit") || input.eq_ignore_ascii_case("quit") {
@@ -108,8 +154,13 @@ impl ConsoleRenderer {
                 continue;
             }
 
+            if let Some(args) = input.trim().strip_prefix("/permissions") {
+                self.permissions_command(args.trim());
+                continue;
+            }
+
             // Send input to agent
-            if let Err(e) = self.handle.send_input(&input).await {
+            if let Err(e) = self.handle.send_input(input.as_str()).await {
                 self.console.print_error(&format!("Failed to send input: {}", e));
                 continue;
             }
@@ -125,6 +176,44 @@ impl ConsoleRenderer {
         Ok(())
     }
 
+    /// `/permissions` lists rules per scope, `/permissions revoke <id>` removes one
+    fn permissions_command(&self, args: &str) {
+        let Some(rules) = self.handle.permission_rules() else {
+            self.console.print_error("Permission rules are not available for this agent");
+            return;
+        };
+        match args.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [] | ["list"] => {
+                for (label, scope) in [
+                    ("Session", PermissionScope::Session),
+                    ("Local", PermissionScope::Local),
+                    ("Global", PermissionScope::Global),
+                ] {
+                    let scoped = rules.rules(scope);
+                    self.console.print_system(&format!("{} rules:", label));
+                    if scoped.is_empty() {
+                        self.console.print_system("  (none)");
+                    }
+                    for (id, rule) in scoped {
+                        self.console.print_system(&format!("  #{} {}", id, rule));
+                    }
+                }
+            }
+            ["revoke", id] => match id.parse::<RuleId>() {
+                Ok(id) => match rules.remove_rule(id) {
+                    Some((scope, rule)) => self
+                        .console
+                        .print_system(&format!("Revoked #{} ({:?}): {}", id, scope, rule)),
+                    None => self.console.print_error(&format!("No rule #{}", id)),
+                },
+                Err(_) => self.console.print_error(&format!("Not a rule id: {}", id)),
+            },
+            _ => self
+                .console
+                .print_error("Usage: /permissions [list] | /permissions revoke <id>"),
+        }
+    }
+
     /// Run a single turn - send input and render response
     ///
     /// Use this for programmatic interaction instead of the full loop.
@@ -144,24 +233,61 @@ impl ConsoleRenderer {
         let mut rx = self.handle.subscribe();
         let mut in_text = false;
         let mut in_thinking = false;
+        // Sources cited by the text being printed
+        let mut sources: Vec<String> = Vec::new();
+        // The streamed tool call whose input is being printed on the current line
+        let mut live_input: Option<String> = None;
+        // File edits by tool call ID, and the inputs of the ones whose diff
+        // was in a permission prompt
+        let mut pending_diffs: HashMap<String, PendingDiff> = HashMap::new();
+        let mut previewed: HashSet<String> = HashSet::new();
+        // Subagent types by session ID, for the prefix of forwarded output
+        let mut agent_types: HashMap<String, String> = HashMap::new();
+        // Sequence number of the last chunk shown; a relay can repeat one
+        let mut last_seq = 0;
 
         loop {
-            match rx.recv().await {
-                Ok(chunk) => {
-                    match chunk {
+            match rx.recv_envelope().await {
+                Ok(envelope) => {
+                    if envelope.seq <= last_seq {
+                        tracing::debug!("Skipping repeated chunk {} (shown up to {})", envelope.seq, last_seq);
+                        continue;
+                    }
+                    last_seq = envelope.seq;
+                    match envelope.chunk {
                         // Text streaming
                         OutputChunk::TextDelta(text) => {
+                            if live_input.take().is_some() {
+                                self.console.println();
+                            }
                             if !in_text {
                                 self.console.print_assistant_prefix();
                                 in_text = true;
                             }
                             self.console.print_assistant_chunk(&text);
                         }
+                        OutputChunk::Citations { citations, .. } => {
+                            for citation in &citations {
+                                let source = match (citation.title(), citation.source()) {
+                                    (Some(title), Some(source)) => format!("{} ({})", title, source),
+                                    (Some(title), None) => title.to_string(),
+                                    (None, Some(source)) => source.to_string(),
+                                    (None, None) => continue,
+                                };
+                                if !sources.contains(&source) {
+                                    sources.push(source);
+                                }
+                            }
+                        }
                         OutputChunk::TextComplete(_) => {
                             if in_text {
                                 self.console.println();
                                 in_text = false;
                             }
+                            if !sources.is_empty() {
+                                self.console.print_system(&format!("Sources: {}", sources.join(", ")));
+                                sources.clear();
+                            }
                         }
 
                         // Thinking - stream in real-time
@@ -182,47 +308,109 @@ impl ConsoleRenderer {
                         }
 
                         // Tool execution
-                        OutputChunk::ToolStart { name, .. } => {
+                        OutputChunk::ToolStreamStart { id, name } => {
                             if in_text {
                                 self.console.println();
                                 in_text = false;
                             }
+                            if live_input.take().is_some() {
+                                self.console.println();
+                            }
+                            // The input follows as deltas
+                            if self.show_tools && LIVE_INPUT_TOOLS.contains(&name.as_str()) {
+                                self.console.print_tool_input_prefix(&name);
+                                live_input = Some(id);
+                            }
+                        }
+                        OutputChunk::ToolStart { id, name, input } => {
+                            if in_text {
+                                self.console.println();
+                                in_text = false;
+                            }
+                            if live_input.take().is_some() {
+                                self.console.println();
+                            }
                             if self.show_tools {
+                                // Final input, right before the tool runs: diff
+                                // it now, while a Write's file is as it was
+                                if DIFF_TOOLS.contains(&name.as_str()) {
+                                    let current = current_contents(&name, &input);
+                                    if let Some(diff) = FileDiff::from_tool_input(&name, &input, current.as_deref()) {
+                                        let previewed = previewed.remove(&input.to_string());
+                                        pending_diffs.insert(id.clone(), PendingDiff { diff, previewed });
+                                    }
+                                }
                                 self.console.print_tool_action(&name, "executing...");
                             }
                         }
+                        OutputChunk::ToolInputDelta { id, partial_json } => {
+                            if live_input.as_deref() == Some(id.as_str()) {
+                                self.console.print_tool_input_chunk(&partial_json);
+                            }
+                        }
                         OutputChunk::ToolProgress { output, .. } => {
                             if self.show_tools {
                                 print!("{}", output);
                                 io::stdout().flush()?;
                             }
                         }
-                        OutputChunk::ToolEnd { result, .. } => {
+                        OutputChunk::ToolEnd { id, result } => {
+                            if live_input.take().is_some() {
+                                self.console.println();
+                            }
                             if self.show_tools {
-                                use crate::tools::ToolResultData;
-                                let output_text = match &result.content {
-                                    ToolResultData::Text(text) => text.clone(),
-                                    ToolResultData::Image { data, media_type } => {
-                                        format!("Image ({}, {} bytes)", media_type, data.len())
+                                match pending_diffs.remove(&id) {
+                                    Some(pending) if !result.is_error => {
+                                        if pending.previewed {
+                                            self.console.print_diff_summary(&pending.diff);
+                                        } else {
+                                            self.console.print_file_diff(&pending.diff);
+                                        }
                                     }
-                                    ToolResultData::Document { description, data, media_type } => {
-                                        format!("{} ({}, {} bytes)", description, media_type, data.len())
+                                    _ => {}
+                                }
+                                self.console.print_tool_result(&result.summary(), result.is_error);
+                            }
+                        }
+
+                        OutputChunk::WebSearch { query, result, .. } => {
+                            if self.show_tools {
+                                use crate::llm::WebSearchToolResultContent;
+                                let summary = match &result {
+                                    WebSearchToolResultContent::Results(results) => {
+                                        format!("{} results", results.len())
+                                    }
+                                    WebSearchToolResultContent::Error(error) => {
+                                        format!("failed: {}", error.error_code)
                                     }
                                 };
-                                self.console.print_tool_result(&output_text, result.is_error);
+                                self.console.print_tool_action("WebSearch", &format!("\"{}\" ({})", query, summary));
                             }
                         }
 
+                        OutputChunk::FilePresented(file) => {
+                            if in_text {
+                                self.console.println();
+                                in_text = false;
+                            }
+                            self.console.print_file_card(&file.title, &file_card(&file));
+                        }
+
                         // Permission requests
-                        OutputChunk::PermissionRequest { tool_name, action, input, details } => {
+                        OutputChunk::PermissionRequest { request_id, tool_name, action, input, details } => {
                             if in_text {
                                 self.console.println();
                                 in_text = false;
                             }
 
+                            if DIFF_TOOLS.contains(&tool_name.as_str()) {
+                                // The prompt shows the diff, so ToolEnd needn't again
+                                previewed.insert(input.clone());
+                            }
+
                             // Create a permission request for the console
                             let request = crate::permissions::PermissionRequest {
-                                tool_name: tool_name.clone(),
+                                tool_name,
                                 action_description: action,
                                 input,
                                 details,
@@ -240,7 +428,7 @@ impl ConsoleRenderer {
                             };
 
                             // Send response back to agent
-                            let _ = self.handle.send_permission_response(&tool_name, allowed, remember).await;
+                            let _ = self.handle.send_permission_response_by_id(request_id, allowed, remember).await;
                         }
 
                         // User questions
@@ -250,37 +438,42 @@ impl ConsoleRenderer {
                                 in_text = false;
                             }
 
-                            // Display questions and collect answers
-                            let mut answers = std::collections::HashMap::new();
-                            for q in &questions {
-                                self.console.print_system(&format!("[{}] {}", q.header, q.question));
-                                for (i, opt) in q.options.iter().enumerate() {
-                                    self.console.print_system(&format!("  {}. {} - {}", i + 1, opt.label, opt.description));
-                                }
-                                // For CLI, just use first option as default for now
-                                // A full implementation would prompt user for input
-                                if let Some(first_option) = q.options.first() {
-                                    answers.insert(q.header.clone(), first_option.label.clone());
-                                }
-                            }
-
-                            // Send response back to agent
-                            let _ = self.handle.send(InputMessage::UserQuestionResponse {
-                                request_id,
-                                answers,
-                            }).await;
+                            let answers = self.console.ask_questions(&questions)?;
+                            let _ = self.handle.send_question_response(request_id, answers).await;
                         }
 
                         // Status updates
                         OutputChunk::Status(status) => {
                             self.console.print_system(&status);
                         }
+                        OutputChunk::HistoryTruncated { new_len } => {
+                            self.console.print_system(&format!(
+                                "[History rewound to {} messages]", new_len
+                            ));
+                        }
+                        OutputChunk::Dropped { count } => {
+                            self.console.print_system(&format!(
+                                "[{} output chunks dropped, display fell behind]", count
+                            ));
+                        }
+                        OutputChunk::StructuredResult(_) => {
+                            // The JSON was already printed as assistant text
+                            self.console.print_system("Structured result validated");
+                        }
                         OutputChunk::StateChange(state) => {
                             // Could show state changes if desired
                             tracing::debug!("Agent state: {:?}", state);
                         }
 
                         // Completion
+                        OutputChunk::TurnComplete { tool_calls, usage, .. } => {
+                            tracing::debug!(
+                                "Turn complete: {} tool call(s), {} input / {} output tokens",
+                                tool_calls.len(),
+                                usage.input_tokens,
+                                usage.output_tokens
+                            );
+                        }
                         OutputChunk::Done => {
                             if in_text {
                                 self.console.println();
@@ -300,20 +493,26 @@ impl ConsoleRenderer {
                             self.console.print_system(&format!(
                                 "Spawned subagent: {} ({})", agent_type, session_id
                             ));
+                            agent_types.insert(session_id, agent_type);
                         }
                         OutputChunk::SubAgentComplete { session_id, result } => {
                             self.console.print_system(&format!(
                                 "Subagent {} completed: {:?}", session_id, result
                             ));
                         }
-                        OutputChunk::SubAgentOutput { chunk, .. } => {
-                            // Could recursively render subagent output
-                            tracing::debug!("Subagent output: {:?}", chunk);
+                        OutputChunk::SubAgentOutput { session_id, chunk } => {
+                            if in_text {
+                                self.console.println();
+                                in_text = false;
+                            }
+                            if self.show_tools {
+                                self.render_subagent_chunk(&mut agent_types, &session_id, &chunk, 1);
+                            }
                         }
                     }
                 }
                 Err(e) => {
-                    // Channel closed or lagged
+                    // Channel closed
                     tracing::warn!("Output channel error: {}", e);
                     break;
                 }
@@ -323,8 +522,47 @@ impl ConsoleRenderer {
         Ok(())
     }
 
+    /// Render a chunk forwarded from a subagent (see `ForwardMode`)
+    ///
+    /// Shows its final texts, tool calls and errors, prefixed with
+    /// `[sub:agent-type]` and indented one step per level of nesting.
+    fn render_subagent_chunk(
+        &self,
+        agent_types: &mut HashMap<String, String>,
+        session_id: &str,
+        chunk: &OutputChunk,
+        depth: usize,
+    ) {
+        let line = match chunk {
+            OutputChunk::TextComplete(text) => text.clone(),
+            OutputChunk::ToolStart { name, .. } => {
+                format!("Tool: [{}] executing...", name)
+            }
+            OutputChunk::ToolEnd { result, .. } => {
+                let summary = result.summary();
+                let first = summary.lines().next().unwrap_or_default();
+                if result.is_error {
+                    format!("Tool Error: {}", first)
+                } else {
+                    first.to_string()
+                }
+            }
+            OutputChunk::Error(e) => format!("Error: {}", e),
+            OutputChunk::SubAgentSpawned { session_id, agent_type } => {
+                agent_types.insert(session_id.clone(), agent_type.clone());
+                format!("Spawned subagent: {}", agent_type)
+            }
+            OutputChunk::SubAgentOutput { session_id, chunk } => {
+                return self.render_subagent_chunk(agent_types, session_id, chunk, depth + 1);
+            }
+            _ => return,
+        };
+        let agent_type = agent_types.get(session_id).map(String::as_str).unwrap_or("agent");
+        self.console.print_subagent(depth, agent_type, &line);
+    }
+
     /// Get the underlying agent handle
-    pub fn handle(&self) -> &AgentHandle {
+    pub fn handle(&self) -> &H {
         &self.handle
     }
 
@@ -333,3 +571,45 @@ impl ConsoleRenderer {
         &self.console
     }
 }
+
+/// Details of a presented file: where it is, its type and size, the
+/// description, and a preview of text content
+///
+/// Binary content sent inline is written to a temporary file, since the
+/// agent's path may be on another machine.
+fn file_card(file: &PresentedFile) -> Vec<String> {
+    let mut details = vec![file.path.clone(), format!("{}, {} bytes", file.media_type, file.size)];
+    if let Some(description) = &file.description {
+        details.push(description.clone());
+    }
+    let content = file.content.as_deref();
+    match content.map(std::str::from_utf8) {
+        Some(Ok(text)) if is_text(&file.media_type) => {
+            details.extend(text.lines().take(FILE_PREVIEW_LINES).map(|line| format!("| {}", line)));
+            if text.lines().count() > FILE_PREVIEW_LINES {
+                details.push("| ...".to_string());
+            }
+        }
+        Some(_) => match save_presented(&file.path, content.unwrap_or_default()) {
+            Ok(saved) => details.push(format!("Saved a copy to {}", saved.display())),
+            Err(e) => details.push(format!("Couldn't save a copy: {}", e)),
+        },
+        None => {}
+    }
+    details
+}
+
+/// Whether a media type is shown as text
+fn is_text(media_type: &str) -> bool {
+    media_type.starts_with("text/") || matches!(media_type, "application/json" | "application/xml" | "application/toml")
+}
+
+/// Write presented bytes to the temp directory, under the file's name
+fn save_presented(path: &str, content: &[u8]) -> io::Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("shadow-agent-files-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let name = Path::new(path).file_name().map(PathBuf::from).unwrap_or_else(|| PathBuf::from("file"));
+    let saved = dir.join(name);
+    std::fs::write(&saved, content)?;
+    Ok(saved)
+}
@@ -7,6 +7,7 @@ use std::collections::HashMap;
 use std::fmt;
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
 
 // ============================================================================
 // DangerousSkipPermissions - Runtime-changeable permission bypass flag
@@ -55,18 +56,21 @@ impl DangerousSkipPermissions {
 /// let todo = ctx.resources.get::<TodoManager>()
 ///     .expect("TodoManager not available");
 /// todo.add_task("Fix bug");
+///
+/// // Several instances of one type, told apart by key
+/// ctx.resources.insert_named("frontend", TodoManager::new());
+/// let frontend = ctx.resources.get_named::<TodoManager>("frontend");
 /// ```
 #[derive(Default, Clone)]
 pub struct ResourceMap {
     map: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+    named: HashMap<(TypeId, String), Arc<dyn Any + Send + Sync>>,
 }
 
 impl ResourceMap {
     /// Create a new empty resource map
     pub fn new() -> Self {
-        Self {
-            map: HashMap::new(),
-        }
+        Self::default()
     }
 
     /// Insert a resource by type
@@ -106,19 +110,49 @@ impl ResourceMap {
             .and_then(|arc| arc.downcast::<T>().ok())
     }
 
-    /// Get the number of resources stored
+    /// Insert a resource under a key
+    ///
+    /// Named resources live beside the by-type ones, so several instances of
+    /// the same type can be stored. Replaces any resource of this type with
+    /// the same key.
+    pub fn insert_named<T: Send + Sync + 'static>(&mut self, key: impl Into<String>, value: T) {
+        self.named.insert((TypeId::of::<T>(), key.into()), Arc::new(value));
+    }
+
+    /// Get a resource by type and key
+    pub fn get_named<T: Send + Sync + 'static>(&self, key: &str) -> Option<Arc<T>> {
+        self.named
+            .get(&(TypeId::of::<T>(), key.to_string()))
+            .and_then(|arc| arc.clone().downcast::<T>().ok())
+    }
+
+    /// Remove a resource by type and key
+    pub fn remove_named<T: Send + Sync + 'static>(&mut self, key: &str) -> Option<Arc<T>> {
+        self.named
+            .remove(&(TypeId::of::<T>(), key.to_string()))
+            .and_then(|arc| arc.downcast::<T>().ok())
+    }
+
+    /// Add every resource of `other`, replacing ones of the same type and key
+    pub fn extend(&mut self, other: &ResourceMap) {
+        self.map.extend(other.map.iter().map(|(k, v)| (*k, v.clone())));
+        self.named.extend(other.named.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+
+    /// Get the number of resources stored (by type and named)
     pub fn len(&self) -> usize {
-        self.map.len()
+        self.map.len() + self.named.len()
     }
 
     /// Check if the resource map is empty
     pub fn is_empty(&self) -> bool {
-        self.map.is_empty()
+        self.map.is_empty() && self.named.is_empty()
     }
 
     /// Clear all resources
     pub fn clear(&mut self) {
         self.map.clear();
+        self.named.clear();
     }
 }
 
@@ -126,6 +160,90 @@ impl fmt::Debug for ResourceMap {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("ResourceMap")
             .field("count", &self.map.len())
+            .field("named", &self.named.len())
+            .finish()
+    }
+}
+
+// ============================================================================
+// SharedResources - Runtime-level resource layer
+// ============================================================================
+
+/// Resources shared by every agent of an `AgentRuntime`
+///
+/// The runtime hands the same layer to each agent it spawns, and
+/// `AgentContext::get_resource` falls back to it when the agent's own
+/// `resources` don't have the type. Use it for things that really are
+/// global (an HTTP client, a database pool); per-agent state like a
+/// `TodoListManager` belongs in the agent layer, where a subagent's copy
+/// can't collide with its parent's.
+///
+/// Unlike the agent layer this one is behind a lock, so resources can be
+/// added after agents are running.
+#[derive(Clone, Default)]
+pub struct SharedResources(Arc<std::sync::RwLock<ResourceMap>>);
+
+impl SharedResources {
+    /// Create an empty shared layer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert a resource by type
+    pub fn insert<T: Send + Sync + 'static>(&self, value: T) {
+        self.0.write().unwrap().insert(value);
+    }
+
+    /// Insert an Arc-wrapped resource by type
+    pub fn insert_arc<T: Send + Sync + 'static>(&self, value: Arc<T>) {
+        self.0.write().unwrap().insert_arc(value);
+    }
+
+    /// Insert a resource under a key
+    pub fn insert_named<T: Send + Sync + 'static>(&self, key: impl Into<String>, value: T) {
+        self.0.write().unwrap().insert_named(key, value);
+    }
+
+    /// Get a resource by type
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.0.read().unwrap().get::<T>()
+    }
+
+    /// Get a resource by type and key
+    pub fn get_named<T: Send + Sync + 'static>(&self, key: &str) -> Option<Arc<T>> {
+        self.0.read().unwrap().get_named::<T>(key)
+    }
+
+    /// Check if a resource of the given type exists
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.0.read().unwrap().contains::<T>()
+    }
+
+    /// Remove a resource by type
+    pub fn remove<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.0.write().unwrap().remove::<T>()
+    }
+
+    /// Remove a resource by type and key
+    pub fn remove_named<T: Send + Sync + 'static>(&self, key: &str) -> Option<Arc<T>> {
+        self.0.write().unwrap().remove_named::<T>(key)
+    }
+
+    /// Get the number of resources stored
+    pub fn len(&self) -> usize {
+        self.0.read().unwrap().len()
+    }
+
+    /// Check if the layer is empty
+    pub fn is_empty(&self) -> bool {
+        self.0.read().unwrap().is_empty()
+    }
+}
+
+impl fmt::Debug for SharedResources {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedResources")
+            .field("count", &self.len())
             .finish()
     }
 }
@@ -138,7 +256,11 @@ impl fmt::Debug for ResourceMap {
 ///
 /// This context is NOT exposed in the tool's JSON schema to the LLM.
 /// It provides tools with access to agent state, session info, lineage,
-/// and agent-specific resources.
+/// and resources.
+///
+/// Resources come in two scopes: the agent's own `resources`, and the
+/// runtime's `shared_resources`. Lookups check the agent scope first, so
+/// an agent can shadow a shared resource with its own instance.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct AgentContext {
     // --- Identity ---
@@ -162,12 +284,27 @@ pub struct AgentContext {
     pub parent_tool_use_id: Option<String>,
 
     // --- Current Execution State ---
-    /// Current turn number (increments each LLM call)
+    /// Current turn number: the number of user turns completed before it
+    /// (restored from the session when an agent resumes it)
     pub current_turn: usize,
 
+    /// LLM call number within the current turn (1 for the first call;
+    /// set by `StandardAgent` before each call)
+    #[serde(default)]
+    pub current_iteration: usize,
+
     /// Current tool_use_id being executed (set during tool execution)
     pub current_tool_use_id: Option<String>,
 
+    /// Cancelled when the user interrupts the current tool call or the
+    /// agent is shut down
+    ///
+    /// Set by the executor for each call. Tools that run for a while, or
+    /// leave work behind when dropped (child processes, remote requests),
+    /// should watch it and clean up.
+    #[serde(skip)]
+    pub cancellation: CancellationToken,
+
     // --- Extensible Metadata (JSON-serializable) ---
     /// Custom metadata that can be set by agent logic (JSON values only)
     #[serde(default)]
@@ -178,6 +315,10 @@ pub struct AgentContext {
     /// These are NOT serialized - they exist only at runtime.
     #[serde(skip)]
     pub resources: ResourceMap,
+
+    /// Resources shared by all agents of the runtime (set at spawn)
+    #[serde(skip)]
+    pub shared_resources: SharedResources,
 }
 
 impl fmt::Debug for AgentContext {
@@ -190,9 +331,11 @@ impl fmt::Debug for AgentContext {
             .field("parent_session_id", &self.parent_session_id)
             .field("parent_tool_use_id", &self.parent_tool_use_id)
             .field("current_turn", &self.current_turn)
+            .field("current_iteration", &self.current_iteration)
             .field("current_tool_use_id", &self.current_tool_use_id)
             .field("metadata", &self.metadata)
             .field("resources", &self.resources)
+            .field("shared_resources", &self.shared_resources)
             .finish()
     }
 }
@@ -213,9 +356,12 @@ impl AgentContext {
             parent_session_id: None,
             parent_tool_use_id: None,
             current_turn: 0,
+            current_iteration: 0,
             current_tool_use_id: None,
+            cancellation: CancellationToken::new(),
             metadata: HashMap::new(),
             resources: ResourceMap::new(),
+            shared_resources: SharedResources::new(),
         }
     }
 
@@ -236,9 +382,12 @@ impl AgentContext {
             parent_session_id: Some(parent_session_id.into()),
             parent_tool_use_id: Some(parent_tool_use_id.into()),
             current_turn: 0,
+            current_iteration: 0,
             current_tool_use_id: None,
+            cancellation: CancellationToken::new(),
             metadata: HashMap::new(),
             resources: ResourceMap::new(),
+            shared_resources: SharedResources::new(),
         }
     }
 
@@ -296,7 +445,7 @@ impl AgentContext {
 
     // --- Resource Methods (runtime objects) ---
 
-    /// Insert a resource by type
+    /// Insert a resource by type into the agent scope
     ///
     /// # Example
     /// ```ignore
@@ -306,7 +455,7 @@ impl AgentContext {
         self.resources.insert(value);
     }
 
-    /// Insert an already Arc-wrapped resource by type
+    /// Insert an already Arc-wrapped resource by type into the agent scope
     ///
     /// Use this when the resource is already wrapped in Arc (e.g., shared with other components).
     ///
@@ -319,25 +468,52 @@ impl AgentContext {
         self.resources.insert_arc(value);
     }
 
-    /// Get a resource by type
+    /// Get a resource by type, from the agent scope or else the shared scope
     ///
     /// # Example
     /// ```ignore
     /// let todo = ctx.get_resource::<TodoManager>();
     /// ```
     pub fn get_resource<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
-        self.resources.get::<T>()
+        self.resources
+            .get::<T>()
+            .or_else(|| self.shared_resources.get::<T>())
     }
 
-    /// Check if a resource of the given type exists
+    /// Check if a resource of the given type exists in either scope
     pub fn has_resource<T: Send + Sync + 'static>(&self) -> bool {
-        self.resources.contains::<T>()
+        self.resources.contains::<T>() || self.shared_resources.contains::<T>()
     }
 
-    /// Remove a resource by type
+    /// Remove a resource by type from the agent scope
+    ///
+    /// A shared resource of the same type becomes visible again.
     pub fn remove_resource<T: Send + Sync + 'static>(&mut self) -> Option<Arc<T>> {
         self.resources.remove::<T>()
     }
+
+    /// Insert a resource under a key into the agent scope
+    ///
+    /// # Example
+    /// ```ignore
+    /// ctx.insert_named_resource("frontend", TodoManager::new());
+    /// ctx.insert_named_resource("backend", TodoManager::new());
+    /// ```
+    pub fn insert_named_resource<T: Send + Sync + 'static>(&mut self, key: impl Into<String>, value: T) {
+        self.resources.insert_named(key, value);
+    }
+
+    /// Get a resource by type and key, from the agent scope or else the shared scope
+    pub fn get_named_resource<T: Send + Sync + 'static>(&self, key: &str) -> Option<Arc<T>> {
+        self.resources
+            .get_named::<T>(key)
+            .or_else(|| self.shared_resources.get_named::<T>(key))
+    }
+
+    /// Remove a resource by type and key from the agent scope
+    pub fn remove_named_resource<T: Send + Sync + 'static>(&mut self, key: &str) -> Option<Arc<T>> {
+        self.resources.remove_named::<T>(key)
+    }
 }
 
 impl Default for AgentContext {
@@ -543,4 +719,60 @@ mod tests {
         assert!(!ctx2.has_resource::<TestResource>());
         assert!(ctx2.resources.is_empty());
     }
+
+    #[test]
+    fn test_shared_resource_fallback() {
+        let shared = SharedResources::new();
+        shared.insert(TestResource { value: 1 });
+
+        let mut ctx = AgentContext::new("s", "t", "n", "d");
+        ctx.shared_resources = shared.clone();
+        assert!(ctx.has_resource::<TestResource>());
+        assert_eq!(ctx.get_resource::<TestResource>().unwrap().value, 1);
+        assert!(ctx.resources.is_empty());
+
+        // Added after the agent got the layer, still visible
+        shared.insert(AnotherResource { name: "late".into() });
+        assert_eq!(ctx.get_resource::<AnotherResource>().unwrap().name, "late");
+    }
+
+    #[test]
+    fn test_agent_resource_shadows_shared() {
+        let shared = SharedResources::new();
+        shared.insert(TestResource { value: 1 });
+
+        let mut parent = AgentContext::new("parent", "t", "n", "d");
+        let mut child = AgentContext::new("child", "t", "n", "d");
+        parent.shared_resources = shared.clone();
+        child.shared_resources = shared.clone();
+
+        child.insert_resource(TestResource { value: 2 });
+        assert_eq!(child.get_resource::<TestResource>().unwrap().value, 2);
+        assert_eq!(parent.get_resource::<TestResource>().unwrap().value, 1);
+
+        // Removing the agent's copy uncovers the shared one
+        assert_eq!(child.remove_resource::<TestResource>().unwrap().value, 2);
+        assert_eq!(child.get_resource::<TestResource>().unwrap().value, 1);
+        assert!(child.remove_resource::<TestResource>().is_none());
+        assert!(shared.contains::<TestResource>());
+    }
+
+    #[test]
+    fn test_named_resources() {
+        let mut ctx = AgentContext::new("s", "t", "n", "d");
+        ctx.insert_named_resource("a", TestResource { value: 1 });
+        ctx.insert_named_resource("b", TestResource { value: 2 });
+        ctx.shared_resources.insert_named("c", TestResource { value: 3 });
+
+        assert_eq!(ctx.get_named_resource::<TestResource>("a").unwrap().value, 1);
+        assert_eq!(ctx.get_named_resource::<TestResource>("b").unwrap().value, 2);
+        assert_eq!(ctx.get_named_resource::<TestResource>("c").unwrap().value, 3);
+        // Named entries are separate from the by-type one
+        assert!(!ctx.has_resource::<TestResource>());
+        assert!(ctx.get_named_resource::<AnotherResource>("a").is_none());
+
+        assert_eq!(ctx.remove_named_resource::<TestResource>("a").unwrap().value, 1);
+        assert!(ctx.get_named_resource::<TestResource>("a").is_none());
+        assert_eq!(ctx.resources.len(), 1);
+    }
 }
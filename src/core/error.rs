@@ -49,6 +49,14 @@ pub enum FrameworkError {
     #[error("Invalid configuration: {0}")]
     InvalidConfig(String),
 
+    /// A runtime limit (concurrency or subagent depth) was hit
+    #[error("Limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    /// A request doesn't fit the model's context window
+    #[error("Context window exceeded: the request is about {estimated} tokens, {limit} are available")]
+    ContextOverflow { estimated: u64, limit: u64 },
+
     /// Agent was interrupted
     #[error("Agent interrupted")]
     Interrupted,
@@ -88,6 +96,12 @@ mod tests {
 
         let err = FrameworkError::ChannelClosed;
         assert_eq!(err.to_string(), "Channel closed");
+
+        let err = FrameworkError::ContextOverflow { estimated: 250_000, limit: 192_000 };
+        assert_eq!(
+            err.to_string(),
+            "Context window exceeded: the request is about 250000 tokens, 192000 are available"
+        );
     }
 
     #[test]
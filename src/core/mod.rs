@@ -4,6 +4,7 @@
 //! - `AgentContext` - Hidden state passed to tools
 //! - `AgentState` - Current state of an agent
 //! - `OutputChunk` / `InputMessage` - Communication types
+//! - `ChunkEnvelope` - An output chunk with its sequence number and timestamp
 //! - `FrameworkError` - Error types
 
 pub mod context;
@@ -11,7 +12,7 @@ pub mod error;
 pub mod output;
 pub mod state;
 
-pub use context::{AgentContext, DangerousSkipPermissions, ResourceMap};
+pub use context::{AgentContext, DangerousSkipPermissions, ResourceMap, SharedResources};
 pub use error::{FrameworkError, FrameworkResult};
-pub use output::{InputMessage, OutputChunk};
+pub use output::{ChunkEnvelope, ChunkFilter, InputMessage, InputOptions, OutputChunk, PresentedFile, ToolCallSummary, TurnSummary};
 pub use state::AgentState;
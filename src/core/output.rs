@@ -1,10 +1,14 @@
 //! Input and output message types for agent communication
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
+use std::time::Duration;
 
 use super::state::AgentState;
+use crate::helpers::Attachment;
+use crate::llm::{Citation, ToolChoice, Usage, WebSearchToolResultContent};
 use crate::tools::ToolResult;
 
 /// A single question option
@@ -23,12 +27,124 @@ pub struct UserQuestion {
     pub multi_select: bool,
 }
 
+/// Maximum characters of tool input kept in a `ToolCallSummary`
+const TOOL_INPUT_PREVIEW_CHARS: usize = 200;
+
+/// One tool call made during a turn
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCallSummary {
+    /// Tool use ID
+    pub id: String,
+    /// Tool name
+    pub name: String,
+    /// The input as JSON, cut to 200 characters
+    pub input_preview: String,
+    /// Whether the tool returned an error
+    pub is_error: bool,
+    /// How long the call took, including the permission check
+    pub duration: Duration,
+}
+
+impl ToolCallSummary {
+    /// Summarize a tool call
+    pub fn new(id: impl Into<String>, name: impl Into<String>, input: &Value, is_error: bool, duration: Duration) -> Self {
+        let json = input.to_string();
+        let input_preview = match json.char_indices().nth(TOOL_INPUT_PREVIEW_CHARS) {
+            Some((cut, _)) => format!("{}...", &json[..cut]),
+            None => json,
+        };
+        Self {
+            id: id.into(),
+            name: name.into(),
+            input_preview,
+            is_error,
+            duration,
+        }
+    }
+}
+
+/// What a finished turn produced (see `OutputChunk::TurnComplete`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TurnSummary {
+    /// Turn number (`AgentContext::current_turn`)
+    pub turn: usize,
+    /// Text of the turn's last assistant message
+    pub final_text: String,
+    /// Tool calls executed during the turn, in order
+    pub tool_calls: Vec<ToolCallSummary>,
+    /// Tokens used by all LLM calls of the turn
+    pub usage: Usage,
+}
+
+/// Per-turn options for `AgentHandle::send_input_with_options`
+#[derive(Debug, Clone, Default)]
+pub struct InputOptions {
+    /// Files sent along with the text
+    pub attachments: Vec<Attachment>,
+    /// Tool choice for the turn's first LLM call (None = `AgentConfig::tool_choice`)
+    pub tool_choice: Option<ToolChoice>,
+    /// Longest the turn may run (None = `AgentConfig::turn_timeout`)
+    pub turn_timeout: Option<Duration>,
+}
+
+impl InputOptions {
+    /// Options with nothing set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Force how the model uses tools on the first LLM call of the turn
+    pub fn with_tool_choice(mut self, choice: ToolChoice) -> Self {
+        self.tool_choice = Some(choice);
+        self
+    }
+
+    /// Stop the turn after this long, overriding `AgentConfig::turn_timeout`
+    pub fn with_turn_timeout(mut self, timeout: Duration) -> Self {
+        self.turn_timeout = Some(timeout);
+        self
+    }
+
+    /// Send files along with the text
+    pub fn with_attachments(mut self, attachments: Vec<Attachment>) -> Self {
+        self.attachments = attachments;
+        self
+    }
+}
+
 /// Messages that can be sent TO an agent
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum InputMessage {
     /// User input text
     UserInput(String),
 
+    /// User input with files attached
+    ///
+    /// `StandardAgent` sends the attachments to the model as image, document
+    /// or text blocks after the text.
+    UserInputWithAttachments {
+        /// The message text
+        text: String,
+        /// Files sent along with it
+        attachments: Vec<Attachment>,
+    },
+
+    /// User input with per-turn options (see [`InputOptions`])
+    UserInputWithOptions {
+        /// The message text
+        text: String,
+        /// Files sent along with it
+        #[serde(default)]
+        attachments: Vec<Attachment>,
+        /// Tool choice for the turn's first LLM call, overriding
+        /// `AgentConfig::tool_choice`
+        #[serde(default)]
+        tool_choice: Option<ToolChoice>,
+        /// Longest the turn may run, overriding `AgentConfig::turn_timeout`
+        #[serde(default)]
+        turn_timeout: Option<Duration>,
+    },
+
     /// Result from an async tool execution
     ToolResult {
         /// ID of the tool use this result is for
@@ -38,9 +154,17 @@ pub enum InputMessage {
     },
 
     /// Permission response from user
+    ///
+    /// Matched to the request with `request_id`. Responses without one (the
+    /// deprecated name-based form) answer the oldest open request for
+    /// `tool_name`. Responses matching no open request are ignored.
     PermissionResponse {
+        /// ID of the `PermissionRequest` being answered
+        #[serde(default)]
+        request_id: Option<String>,
         /// Tool that was requesting permission
-        tool_name: String,
+        #[serde(default)]
+        tool_name: Option<String>,
         /// Whether permission was granted
         allowed: bool,
         /// Whether to remember this decision
@@ -55,6 +179,17 @@ pub enum InputMessage {
         result: Option<String>,
     },
 
+    /// Message from another running agent (see `AgentRuntime::send_to`)
+    ///
+    /// `StandardAgent` handles it as a turn whose user message is the
+    /// content wrapped in `<agent-message from="...">` tags.
+    AgentMessage {
+        /// Session ID of the sending agent
+        from_session_id: String,
+        /// The message text
+        content: String,
+    },
+
     /// Response to an AskUserQuestion request
     UserQuestionResponse {
         /// Unique ID matching the request
@@ -63,6 +198,18 @@ pub enum InputMessage {
         answers: HashMap<String, String>,
     },
 
+    /// Rewind the conversation to a previous user turn and send it again
+    ///
+    /// History is truncated to just before the user message that starts the
+    /// turn containing `message_index`, then `new_text` runs as a new turn.
+    /// Used for "retry" and "edit my message".
+    RewindAndResend {
+        /// Index into the history of the message to rewind to
+        message_index: usize,
+        /// Text sent in place of the original message
+        new_text: String,
+    },
+
     /// Request graceful interrupt
     Interrupt,
 
@@ -80,6 +227,18 @@ pub enum OutputChunk {
     /// Complete text block
     TextComplete(String),
 
+    /// Sources cited by a text block, sent when the block is complete
+    ///
+    /// Comes after the block's text deltas and before the `TextComplete`
+    /// that includes it. Only for models that cite documents or search
+    /// results (Anthropic).
+    Citations {
+        /// The text block that cites them
+        text: String,
+        /// The cited sources
+        citations: Vec<Citation>,
+    },
+
     // --- Thinking Streaming ---
     /// Incremental thinking output
     ThinkingDelta(String),
@@ -88,7 +247,20 @@ pub enum OutputChunk {
     ThinkingComplete(String),
 
     // --- Tool Execution ---
-    /// Tool execution starting
+    /// The model started a tool call (streaming only)
+    ///
+    /// Its input follows as `ToolInputDelta` fragments, then `ToolStart`
+    /// with the parsed input once the tool is about to run.
+    ToolStreamStart {
+        /// Tool use ID
+        id: String,
+        /// Tool name
+        name: String,
+    },
+
+    /// Tool execution starting, with the final input
+    ///
+    /// Sent once per call, right before the tool runs.
     ToolStart {
         /// Tool use ID
         id: String,
@@ -98,6 +270,17 @@ pub enum OutputChunk {
         input: Value,
     },
 
+    /// Incremental tool input as the model streams it
+    ///
+    /// The fragments only form valid JSON once complete; the parsed input
+    /// arrives in `ToolStart`.
+    ToolInputDelta {
+        /// Tool use ID
+        id: String,
+        /// Next fragment of the input JSON
+        partial_json: String,
+    },
+
     /// Incremental tool output (for long-running tools)
     ToolProgress {
         /// Tool use ID
@@ -114,9 +297,26 @@ pub enum OutputChunk {
         result: ToolResult,
     },
 
+    // --- Server Tools ---
+    /// Web search run by the API (see `AgentConfig::with_web_search`)
+    WebSearch {
+        /// Server tool use ID
+        id: String,
+        /// The search query
+        query: String,
+        /// Results, or the error the search failed with
+        result: WebSearchToolResultContent,
+    },
+
+    // --- Files ---
+    /// A file for the UI to show the user (sent by `PresentFileTool`)
+    FilePresented(Box<PresentedFile>),
+
     // --- Permission ---
     /// Requesting permission from user
     PermissionRequest {
+        /// Unique ID to answer with (`send_permission_response_by_id`)
+        request_id: String,
         /// Tool name
         tool_name: String,
         /// Human-readable action description
@@ -161,6 +361,10 @@ pub enum OutputChunk {
         questions: Vec<UserQuestion>,
     },
 
+    // --- Structured Output ---
+    /// Final answer validated against the configured output schema
+    StructuredResult(Value),
+
     // --- State & Status ---
     /// Agent state changed
     StateChange(AgentState),
@@ -168,10 +372,41 @@ pub enum OutputChunk {
     /// Status update (for progress indicators)
     Status(String),
 
+    /// History was truncated by a rewind; `new_len` messages remain
+    HistoryTruncated {
+        /// Number of messages left in the history
+        new_len: usize,
+    },
+
+    /// Output this subscriber missed because it fell behind
+    ///
+    /// Never sent by the agent: each subscriber gets its own marker in place
+    /// of the chunks it lost (see `runtime::channels`).
+    Dropped {
+        /// Number of chunks missed
+        count: u64,
+    },
+
     // --- Completion ---
     /// Error occurred
     Error(String),
 
+    /// Summary of the turn, sent right before `Done`
+    ///
+    /// `final_text` holds only the text blocks of the last assistant
+    /// message, not text the model wrote before its tool calls. The latest
+    /// one is also kept by `AgentHandle::last_turn`.
+    TurnComplete {
+        /// Turn number (`AgentContext::current_turn`)
+        turn: usize,
+        /// Text of the turn's last assistant message
+        final_text: String,
+        /// Tool calls executed during the turn, in order
+        tool_calls: Vec<ToolCallSummary>,
+        /// Tokens used by all LLM calls of the turn
+        usage: Usage,
+    },
+
     /// Agent completed this turn
     Done,
 }
@@ -187,6 +422,14 @@ impl OutputChunk {
         OutputChunk::ThinkingDelta(text.into())
     }
 
+    /// Create a tool stream start chunk
+    pub fn tool_stream_start(id: impl Into<String>, name: impl Into<String>) -> Self {
+        OutputChunk::ToolStreamStart {
+            id: id.into(),
+            name: name.into(),
+        }
+    }
+
     /// Create a tool start chunk
     pub fn tool_start(id: impl Into<String>, name: impl Into<String>, input: Value) -> Self {
         OutputChunk::ToolStart {
@@ -209,6 +452,19 @@ impl OutputChunk {
         OutputChunk::Error(msg.into())
     }
 
+    /// The turn summary carried by a `TurnComplete` chunk
+    pub fn turn_summary(&self) -> Option<TurnSummary> {
+        match self {
+            OutputChunk::TurnComplete { turn, final_text, tool_calls, usage } => Some(TurnSummary {
+                turn: *turn,
+                final_text: final_text.clone(),
+                tool_calls: tool_calls.clone(),
+                usage: usage.clone(),
+            }),
+            _ => None,
+        }
+    }
+
     /// Check if this is a terminal chunk
     pub fn is_terminal(&self) -> bool {
         matches!(self, OutputChunk::Done | OutputChunk::Error(_))
@@ -218,7 +474,7 @@ impl OutputChunk {
     pub fn is_text(&self) -> bool {
         matches!(
             self,
-            OutputChunk::TextDelta(_) | OutputChunk::TextComplete(_)
+            OutputChunk::TextDelta(_) | OutputChunk::TextComplete(_) | OutputChunk::Citations { .. }
         )
     }
 
@@ -234,23 +490,204 @@ impl OutputChunk {
     pub fn is_tool(&self) -> bool {
         matches!(
             self,
-            OutputChunk::ToolStart { .. }
+            OutputChunk::ToolStreamStart { .. }
+                | OutputChunk::ToolStart { .. }
+                | OutputChunk::ToolInputDelta { .. }
                 | OutputChunk::ToolProgress { .. }
                 | OutputChunk::ToolEnd { .. }
+                | OutputChunk::WebSearch { .. }
         )
     }
 }
 
+/// A file presented to the user (`OutputChunk::FilePresented`)
+///
+/// `content` is only set for files up to the tool's inline limit; larger
+/// ones are sent by path. It is base64 when serialized.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresentedFile {
+    /// ID of the PresentFile call that sent it
+    #[serde(default)]
+    pub tool_use_id: String,
+    /// Path of the file
+    pub path: String,
+    /// Media type guessed from the extension (e.g. `text/markdown`)
+    pub media_type: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Name to show
+    pub title: String,
+    /// What the file contains
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The file's bytes, if small enough to inline
+    #[serde(default, with = "base64_bytes", skip_serializing_if = "Option::is_none")]
+    pub content: Option<Vec<u8>>,
+}
+
+/// Serde for optional bytes as a base64 string
+mod base64_bytes {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(bytes) => serializer.serialize_str(&STANDARD.encode(bytes)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|encoded| STANDARD.decode(encoded).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+/// Categories of output chunks a subscriber wants (see `AgentHandle::subscribe_filtered`)
+///
+/// Combine categories with `|`, e.g. `ChunkFilter::TOOLS | ChunkFilter::PERMISSIONS`.
+/// `Done` and `Dropped` match every filter, so loops that stop at `Done`
+/// still stop and lag is still reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkFilter(u8);
+
+impl ChunkFilter {
+    /// Nothing but `Done` and `Dropped`
+    pub const NONE: Self = Self(0);
+    /// `TextDelta`, `TextComplete`, `Citations` and `StructuredResult`
+    pub const TEXT: Self = Self(1);
+    /// `ThinkingDelta` and `ThinkingComplete`
+    pub const THINKING: Self = Self(1 << 1);
+    /// `ToolStreamStart`, `ToolStart`, `ToolInputDelta`, `ToolProgress`, `ToolEnd`, `WebSearch` and `FilePresented`
+    pub const TOOLS: Self = Self(1 << 2);
+    /// `PermissionRequest` and `AskUserQuestion`
+    pub const PERMISSIONS: Self = Self(1 << 3);
+    /// `StateChange`, `Status`, `HistoryTruncated`, `Error` and `TurnComplete`
+    pub const LIFECYCLE: Self = Self(1 << 4);
+    /// `SubAgentSpawned`, `SubAgentOutput` and `SubAgentComplete`
+    pub const SUBAGENTS: Self = Self(1 << 5);
+    /// Every chunk
+    pub const ALL: Self = Self((1 << 6) - 1);
+
+    /// Whether every category of `other` is in this filter
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether a subscriber with this filter receives `chunk`
+    pub fn matches(self, chunk: &OutputChunk) -> bool {
+        let category = match chunk {
+            OutputChunk::Done | OutputChunk::Dropped { .. } => return true,
+            OutputChunk::TextDelta(_)
+            | OutputChunk::TextComplete(_)
+            | OutputChunk::Citations { .. }
+            | OutputChunk::StructuredResult(_) => Self::TEXT,
+            OutputChunk::ThinkingDelta(_) | OutputChunk::ThinkingComplete(_) => Self::THINKING,
+            OutputChunk::ToolStreamStart { .. }
+            | OutputChunk::ToolStart { .. }
+            | OutputChunk::ToolInputDelta { .. }
+            | OutputChunk::ToolProgress { .. }
+            | OutputChunk::ToolEnd { .. }
+            | OutputChunk::WebSearch { .. }
+            | OutputChunk::FilePresented(_) => Self::TOOLS,
+            OutputChunk::PermissionRequest { .. } | OutputChunk::AskUserQuestion { .. } => Self::PERMISSIONS,
+            OutputChunk::StateChange(_)
+            | OutputChunk::Status(_)
+            | OutputChunk::HistoryTruncated { .. }
+            | OutputChunk::Error(_)
+            | OutputChunk::TurnComplete { .. } => Self::LIFECYCLE,
+            OutputChunk::SubAgentSpawned { .. }
+            | OutputChunk::SubAgentOutput { .. }
+            | OutputChunk::SubAgentComplete { .. } => Self::SUBAGENTS,
+        };
+        self.contains(category)
+    }
+}
+
+impl Default for ChunkFilter {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl std::ops::BitOr for ChunkFilter {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl std::ops::BitOrAssign for ChunkFilter {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+/// An output chunk as subscribers receive it, with its place in the stream
+///
+/// The agent's `OutputSender` stamps every chunk it sends, so subscribers
+/// and transcript writers can order and deduplicate chunks. `seq` starts at
+/// 1 for each agent and increases by one per chunk; a `Dropped` marker
+/// carries the sequence number of the last chunk it stands for, so the next
+/// chunk always follows it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkEnvelope {
+    /// Position in the agent's output
+    pub seq: u64,
+    /// When the chunk was sent
+    pub timestamp: DateTime<Utc>,
+    /// Turn the agent was on (`AgentContext::current_turn`), if an agent sent it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turn: Option<usize>,
+    /// The chunk itself
+    pub chunk: OutputChunk,
+}
+
+impl ChunkEnvelope {
+    /// Stamp a chunk with the current time
+    pub fn new(seq: u64, turn: Option<usize>, chunk: OutputChunk) -> Self {
+        Self {
+            seq,
+            timestamp: Utc::now(),
+            turn,
+            chunk,
+        }
+    }
+}
+
 impl InputMessage {
     /// Create a user input message
     pub fn user_input(text: impl Into<String>) -> Self {
         InputMessage::UserInput(text.into())
     }
 
-    /// Create a permission response
+    /// Create a message from another agent
+    pub fn agent_message(from_session_id: impl Into<String>, content: impl Into<String>) -> Self {
+        InputMessage::AgentMessage {
+            from_session_id: from_session_id.into(),
+            content: content.into(),
+        }
+    }
+
+    /// Create a response to the permission request with this ID
+    pub fn permission_for(request_id: impl Into<String>, allowed: bool, remember: bool) -> Self {
+        InputMessage::PermissionResponse {
+            request_id: Some(request_id.into()),
+            tool_name: None,
+            allowed,
+            remember,
+        }
+    }
+
+    /// Create a permission response addressed by tool name
+    #[deprecated(note = "answers the oldest request for the tool; use `permission_for` with the request ID")]
     pub fn permission(tool_name: impl Into<String>, allowed: bool, remember: bool) -> Self {
         InputMessage::PermissionResponse {
-            tool_name: tool_name.into(),
+            request_id: None,
+            tool_name: Some(tool_name.into()),
             allowed,
             remember,
         }
@@ -275,19 +712,71 @@ mod tests {
         assert!(!OutputChunk::text("hello").is_thinking());
     }
 
+    #[test]
+    fn test_chunk_filter_matches() {
+        let filter = ChunkFilter::TOOLS | ChunkFilter::PERMISSIONS;
+        assert!(filter.contains(ChunkFilter::TOOLS));
+        assert!(!filter.contains(ChunkFilter::TOOLS | ChunkFilter::TEXT));
+
+        assert!(filter.matches(&OutputChunk::tool_start("toolu_1", "Bash", Value::Null)));
+        assert!(!filter.matches(&OutputChunk::text("hello")));
+        assert!(!filter.matches(&OutputChunk::Status("working".into())));
+        // Done and Dropped always get through
+        assert!(ChunkFilter::NONE.matches(&OutputChunk::Done));
+        assert!(ChunkFilter::NONE.matches(&OutputChunk::Dropped { count: 3 }));
+        assert!(ChunkFilter::ALL.matches(&OutputChunk::thinking("hmm")));
+        assert_eq!(ChunkFilter::default(), ChunkFilter::ALL);
+    }
+
+    #[test]
+    fn test_file_presented_content_is_base64() {
+        let chunk = OutputChunk::FilePresented(Box::new(PresentedFile {
+            tool_use_id: "toolu_1".into(),
+            path: "/tmp/out.bin".into(),
+            media_type: "application/octet-stream".into(),
+            size: 3,
+            title: "out.bin".into(),
+            description: None,
+            content: Some(vec![0, 159, 255]),
+        }));
+        let json = serde_json::to_value(&chunk).unwrap();
+        assert_eq!(json["FilePresented"]["content"], "AJ//");
+        assert!(json["FilePresented"].get("description").is_none());
+        let back: OutputChunk = serde_json::from_value(json).unwrap();
+        assert!(matches!(back, OutputChunk::FilePresented(file) if file.content == Some(vec![0, 159, 255])));
+
+        // Path only
+        let json = serde_json::json!({"FilePresented": {
+            "path": "/tmp/big.bin", "media_type": "application/octet-stream", "size": 10, "title": "big.bin"
+        }});
+        let chunk: OutputChunk = serde_json::from_value(json).unwrap();
+        assert!(matches!(chunk, OutputChunk::FilePresented(file) if file.content.is_none() && file.size == 10));
+    }
+
     #[test]
     fn test_input_message_creation() {
         let msg = InputMessage::user_input("hello");
         assert!(matches!(msg, InputMessage::UserInput(s) if s == "hello"));
 
-        let msg = InputMessage::permission("Bash", true, false);
+        let msg = InputMessage::permission_for("req-1", true, false);
         assert!(matches!(
             msg,
             InputMessage::PermissionResponse {
+                request_id: Some(ref id),
+                tool_name: None,
                 allowed: true,
                 remember: false,
-                ..
-            }
+            } if id == "req-1"
+        ));
+
+        // Name-only responses from older clients still deserialize
+        let old: InputMessage = serde_json::from_str(
+            r#"{"PermissionResponse":{"tool_name":"Bash","allowed":true,"remember":false}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            old,
+            InputMessage::PermissionResponse { request_id: None, tool_name: Some(ref t), .. } if t == "Bash"
         ));
     }
 }
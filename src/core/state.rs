@@ -3,16 +3,20 @@
 use serde::{Deserialize, Serialize};
 
 /// Current state of an agent
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub enum AgentState {
     /// Agent is idle, waiting for input
+    #[default]
     Idle,
 
     /// Agent is processing input (calling LLM, thinking)
     Processing,
 
     /// Agent is waiting for user permission decision
-    WaitingForPermission,
+    WaitingForPermission {
+        /// Name of the tool waiting to run
+        tool_name: String,
+    },
 
     /// Agent is waiting for user to answer questions
     WaitingForUserInput {
@@ -64,7 +68,18 @@ impl AgentState {
     pub fn is_waiting(&self) -> bool {
         matches!(
             self,
-            AgentState::Idle | AgentState::WaitingForPermission | AgentState::WaitingForUserInput { .. }
+            AgentState::Idle | AgentState::WaitingForPermission { .. } | AgentState::WaitingForUserInput { .. }
+        )
+    }
+
+    /// Check if agent is blocked until a person answers (permission or question)
+    ///
+    /// Unlike `is_waiting`, this excludes `Idle`: an idle agent has finished
+    /// its turn, a blocked one is stuck mid-turn.
+    pub fn is_blocked_on_user(&self) -> bool {
+        matches!(
+            self,
+            AgentState::WaitingForPermission { .. } | AgentState::WaitingForUserInput { .. }
         )
     }
 
@@ -90,6 +105,13 @@ impl AgentState {
         }
     }
 
+    /// Create a waiting for permission state
+    pub fn waiting_for_permission(tool_name: impl Into<String>) -> Self {
+        AgentState::WaitingForPermission {
+            tool_name: tool_name.into(),
+        }
+    }
+
     /// Create a waiting for user input state
     pub fn waiting_for_user_input(request_id: impl Into<String>) -> Self {
         AgentState::WaitingForUserInput {
@@ -98,18 +120,14 @@ impl AgentState {
     }
 }
 
-impl Default for AgentState {
-    fn default() -> Self {
-        AgentState::Idle
-    }
-}
-
 impl std::fmt::Display for AgentState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             AgentState::Idle => write!(f, "Idle"),
             AgentState::Processing => write!(f, "Processing"),
-            AgentState::WaitingForPermission => write!(f, "Waiting for permission"),
+            AgentState::WaitingForPermission { tool_name } => {
+                write!(f, "Waiting for permission: {}", tool_name)
+            }
             AgentState::WaitingForUserInput { request_id } => {
                 write!(f, "Waiting for user input: {}", request_id)
             }
@@ -140,8 +158,12 @@ mod tests {
         assert!(!AgentState::Idle.is_active());
 
         assert!(AgentState::Idle.is_waiting());
-        assert!(AgentState::WaitingForPermission.is_waiting());
+        assert!(AgentState::waiting_for_permission("Bash").is_waiting());
         assert!(!AgentState::Processing.is_waiting());
+
+        assert!(AgentState::waiting_for_permission("Bash").is_blocked_on_user());
+        assert!(AgentState::waiting_for_user_input("ask_1").is_blocked_on_user());
+        assert!(!AgentState::Idle.is_blocked_on_user());
     }
 
     #[test]
@@ -1,13 +1,17 @@
 //! Attachment processing for user messages
 //!
-//! This module handles parsing and processing of attachment tags in user input.
-//! Attachments are specified using `<vibe-work-attachment>path</vibe-work-attachment>` tags.
+//! Attachments arrive either as [`Attachment`] values sent with
+//! `AgentHandle::send_input_with_attachments`, or as
+//! `<vibe-work-attachment>path</vibe-work-attachment>` tags in the input text.
+//! Both are turned into content blocks by the same code.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use crate::llm::ContentBlock;
 
@@ -19,6 +23,108 @@ const MAX_PDF_SIZE: u64 = 32 * 1024 * 1024;
 const DEFAULT_LINE_LIMIT: usize = 2000;
 /// Maximum characters per line before truncation
 const MAX_LINE_LENGTH: usize = 2000;
+/// Maximum size of a downloaded attachment (the largest per-type limit)
+const MAX_DOWNLOAD_SIZE: u64 = MAX_PDF_SIZE;
+/// Timeout for downloading an attachment
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Tag name scanned for by [`process_attachments`]
+pub const DEFAULT_ATTACHMENT_TAG: &str = "vibe-work-attachment";
+
+/// A file sent along with user input
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Attachment {
+    /// A file or directory on disk (relative paths resolve against the
+    /// current directory)
+    Path(PathBuf),
+    /// In-memory content, e.g. a screenshot
+    Bytes {
+        /// Raw file content
+        data: Vec<u8>,
+        /// MIME type (`image/png`, `application/pdf`, `text/plain`, ...)
+        media_type: String,
+        /// Name shown to the model
+        name: Option<String>,
+    },
+    /// A file to download (http or https)
+    Url(String),
+}
+
+impl Attachment {
+    /// Attach a file or directory on disk
+    pub fn path(path: impl Into<PathBuf>) -> Self {
+        Attachment::Path(path.into())
+    }
+
+    /// Attach in-memory content
+    pub fn bytes(data: impl Into<Vec<u8>>, media_type: impl Into<String>) -> Self {
+        Attachment::Bytes {
+            data: data.into(),
+            media_type: media_type.into(),
+            name: None,
+        }
+    }
+
+    /// Attach a file downloaded from `url`
+    pub fn url(url: impl Into<String>) -> Self {
+        Attachment::Url(url.into())
+    }
+
+    /// Set the name of a `Bytes` attachment (no-op for other kinds)
+    pub fn with_name(mut self, new_name: impl Into<String>) -> Self {
+        if let Attachment::Bytes { name, .. } = &mut self {
+            *name = Some(new_name.into());
+        }
+        self
+    }
+
+    /// How the attachment is referred to in notes and errors
+    fn label(&self) -> String {
+        match self {
+            Attachment::Path(path) => path.display().to_string(),
+            Attachment::Bytes { name, media_type, .. } => {
+                name.clone().unwrap_or_else(|| format!("attachment ({})", media_type))
+            }
+            Attachment::Url(url) => url.clone(),
+        }
+    }
+}
+
+/// Convert attachments into content blocks, in order
+///
+/// Paths are read like attachment tags (deduplicated, directories listed),
+/// URLs are downloaded, and size limits apply to every kind. An attachment
+/// that can't be used becomes a text block describing the error, so the
+/// model knows it was not attached.
+pub async fn load_attachments(attachments: &[Attachment], base_dir: &str) -> Vec<ContentBlock> {
+    let mut blocks = Vec::new();
+    let mut processed_paths: HashSet<String> = HashSet::new();
+
+    for attachment in attachments {
+        let result = match attachment {
+            Attachment::Path(path) => {
+                add_path(&path.to_string_lossy(), base_dir, &mut processed_paths, &mut blocks);
+                continue;
+            }
+            Attachment::Bytes { data, media_type, .. } => {
+                content_blocks(data, media_type, &attachment.label())
+            }
+            Attachment::Url(url) => {
+                tracing::info!("[Attachments] Downloading attachment: {}", url);
+                match download(url).await {
+                    Ok((data, media_type)) => content_blocks(&data, &media_type, url),
+                    Err(e) => Err(e),
+                }
+            }
+        };
+        match result {
+            Ok(mut content_blocks) => blocks.append(&mut content_blocks),
+            Err(e) => blocks.push(error_block(&attachment.label(), &e)),
+        }
+    }
+
+    blocks
+}
 
 /// Process attachments from user input
 ///
@@ -37,11 +143,19 @@ const MAX_LINE_LENGTH: usize = 2000;
 /// # Returns
 /// A vector of ContentBlocks, one for each attachment found (in order)
 pub fn process_attachments(input: &str, base_dir: &str) -> Vec<ContentBlock> {
+    process_attachments_with_tag(input, DEFAULT_ATTACHMENT_TAG, base_dir)
+}
+
+/// Process attachments marked with a custom tag
+///
+/// Same as [`process_attachments`], but scans for `<tag>path</tag>`.
+pub fn process_attachments_with_tag(input: &str, tag: &str, base_dir: &str) -> Vec<ContentBlock> {
     let mut blocks = Vec::new();
     let mut processed_paths: HashSet<String> = HashSet::new();
 
     // Parse attachment tags using regex
-    let re = match Regex::new(r"<vibe-work-attachment>(.*?)</vibe-work-attachment>") {
+    let tag = regex::escape(tag);
+    let re = match Regex::new(&format!(r"<{tag}>(.*?)</{tag}>")) {
         Ok(r) => r,
         Err(e) => {
             tracing::warn!("[Attachments] Failed to compile regex: {}", e);
@@ -52,44 +166,54 @@ pub fn process_attachments(input: &str, base_dir: &str) -> Vec<ContentBlock> {
     // Extract all attachment paths in order
     for cap in re.captures_iter(input) {
         if let Some(path_match) = cap.get(1) {
-            let file_path = path_match.as_str().trim();
-            let resolved_path = resolve_path(file_path, base_dir);
-
-            // Check if we've already processed this file
-            if processed_paths.contains(&resolved_path) {
-                tracing::debug!("[Attachments] Skipping duplicate: {}", file_path);
-                blocks.push(ContentBlock::Text {
-                    text: format!("Note: File {} was already attached above", file_path),
-                    cache_control: None,
-                });
-                continue;
-            }
+            add_path(path_match.as_str().trim(), base_dir, &mut processed_paths, &mut blocks);
+        }
+    }
 
-            tracing::info!("[Attachments] Processing attachment: {}", file_path);
+    blocks
+}
 
-            // Read the file and convert to content blocks
-            match read_attachment(file_path, base_dir) {
-                Ok(mut content_blocks) => {
-                    processed_paths.insert(resolved_path);
-                    blocks.append(&mut content_blocks);
-                }
-                Err(e) => {
-                    // On error, add a text block describing the error
-                    let error_text = format!(
-                        "Error: Cannot read file {} - {}",
-                        file_path, e
-                    );
-                    tracing::warn!("[Attachments] {}", error_text);
-                    blocks.push(ContentBlock::Text {
-                        text: error_text,
-                        cache_control: None,
-                    });
-                }
-            }
+/// Read one path attachment into `blocks`, noting repeats of earlier paths
+fn add_path(
+    file_path: &str,
+    base_dir: &str,
+    processed_paths: &mut HashSet<String>,
+    blocks: &mut Vec<ContentBlock>,
+) {
+    let resolved_path = resolve_path(file_path, base_dir);
+
+    // Check if we've already processed this file
+    if processed_paths.contains(&resolved_path) {
+        tracing::debug!("[Attachments] Skipping duplicate: {}", file_path);
+        blocks.push(ContentBlock::Text {
+            text: format!("Note: File {} was already attached above", file_path),
+            cache_control: None,
+            citations: None,
+        });
+        return;
+    }
+
+    tracing::info!("[Attachments] Processing attachment: {}", file_path);
+
+    // Read the file and convert to content blocks
+    match read_attachment(file_path, base_dir) {
+        Ok(mut content_blocks) => {
+            processed_paths.insert(resolved_path);
+            blocks.append(&mut content_blocks);
         }
+        Err(e) => blocks.push(error_block(file_path, &e)),
     }
+}
 
-    blocks
+/// A text block telling the model an attachment could not be read
+fn error_block(label: &str, error: &anyhow::Error) -> ContentBlock {
+    let error_text = format!("Error: Cannot read file {} - {}", label, error);
+    tracing::warn!("[Attachments] {}", error_text);
+    ContentBlock::Text {
+        text: error_text,
+        cache_control: None,
+        citations: None,
+    }
 }
 
 /// Read a single attachment file and convert to ContentBlocks
@@ -136,7 +260,11 @@ fn resolve_path(path: &str, base_dir: &str) -> String {
 /// Read a text file with line numbers
 fn read_text_file(resolved_path: &str, original_path: &str) -> Result<Vec<ContentBlock>> {
     let content = fs::read_to_string(resolved_path)?;
+    Ok(vec![text_block(&content, original_path)])
+}
 
+/// Format text content with line numbers
+fn text_block(content: &str, original_path: &str) -> ContentBlock {
     let lines: Vec<&str> = content.lines().collect();
     let total_lines = lines.len();
     let end = total_lines.min(DEFAULT_LINE_LIMIT);
@@ -161,90 +289,143 @@ fn read_text_file(resolved_path: &str, original_path: &str) -> Result<Vec<Conten
         ));
     }
 
-    Ok(vec![ContentBlock::Text {
+    ContentBlock::Text {
         text: result,
         cache_control: None,
-    }])
+        citations: None,
+    }
 }
 
 /// Read an image file
 fn read_image(resolved_path: &str, original_path: &str) -> Result<Vec<ContentBlock>> {
-    // Check file size first
+    let media_type = media_type_from_path(resolved_path).unwrap_or("application/octet-stream");
+    read_binary(resolved_path, original_path, media_type)
+}
+
+/// Read a PDF file
+fn read_pdf(resolved_path: &str, original_path: &str) -> Result<Vec<ContentBlock>> {
+    read_binary(resolved_path, original_path, "application/pdf")
+}
+
+/// Read an image or PDF, checking its size before reading it
+fn read_binary(resolved_path: &str, original_path: &str, media_type: &str) -> Result<Vec<ContentBlock>> {
     let metadata = fs::metadata(resolved_path)?;
+    check_size(metadata.len(), media_type)?;
+    let data = fs::read(resolved_path)?;
+    content_blocks(&data, media_type, original_path)
+}
 
-    if metadata.len() > MAX_IMAGE_SIZE {
-        anyhow::bail!(
-            "Image file too large: {} bytes (max: {} bytes)",
-            metadata.len(),
-            MAX_IMAGE_SIZE
-        );
+/// Reject content over the limit for its type
+fn check_size(len: u64, media_type: &str) -> Result<()> {
+    let (kind, max) = if media_type == "application/pdf" {
+        ("PDF", MAX_PDF_SIZE)
+    } else if media_type.starts_with("image/") {
+        ("Image", MAX_IMAGE_SIZE)
+    } else {
+        ("File", MAX_DOWNLOAD_SIZE)
+    };
+    if len > max {
+        anyhow::bail!("{} file too large: {} bytes (max: {} bytes)", kind, len, max);
     }
+    Ok(())
+}
 
-    // Read the file as bytes
-    let data = fs::read(resolved_path)?;
+/// Convert raw content into blocks according to its media type
+fn content_blocks(data: &[u8], media_type: &str, original_path: &str) -> Result<Vec<ContentBlock>> {
+    check_size(data.len() as u64, media_type)?;
 
-    // Determine media type from extension
-    let extension = Path::new(resolved_path)
+    use base64::Engine;
+    match media_type {
+        "image/png" | "image/jpeg" | "image/gif" | "image/webp" => {
+            tracing::info!(
+                "[Attachments] Read image: {} ({} bytes, type: {})",
+                original_path,
+                data.len(),
+                media_type
+            );
+            let base64_data = base64::engine::general_purpose::STANDARD.encode(data);
+            Ok(vec![ContentBlock::image(base64_data, media_type.to_string())])
+        }
+        "application/pdf" => {
+            tracing::info!(
+                "[Attachments] Read PDF: {} ({} bytes)",
+                original_path,
+                data.len()
+            );
+            let base64_data = base64::engine::general_purpose::STANDARD.encode(data);
+            Ok(vec![ContentBlock::document(base64_data, media_type.to_string())])
+        }
+        _ if media_type.starts_with("text/")
+            || matches!(media_type, "application/json" | "application/xml" | "application/toml" | "image/svg+xml") =>
+        {
+            let content = std::str::from_utf8(data).context("Text attachment is not valid UTF-8")?;
+            Ok(vec![text_block(content, original_path)])
+        }
+        _ => anyhow::bail!("Unsupported attachment type: {}", media_type),
+    }
+}
+
+/// Download a URL attachment, returning its content and media type
+///
+/// The body is read in chunks and abandoned once it passes
+/// `MAX_DOWNLOAD_SIZE`, so a huge file is never held in memory.
+async fn download(url: &str) -> Result<(Vec<u8>, String)> {
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        anyhow::bail!("Only http and https URLs can be attached");
+    }
+    let client = reqwest::Client::builder().timeout(DOWNLOAD_TIMEOUT).build()?;
+    let mut response = client.get(url).send().await?.error_for_status()?;
+
+    let header_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_lowercase())
+        .filter(|v| !v.is_empty() && v != "application/octet-stream");
+    let media_type = header_type.unwrap_or_else(|| media_type_from_path(url).unwrap_or("text/plain").to_string());
+
+    if let Some(len) = response.content_length() {
+        check_size(len, &media_type)?;
+    }
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        data.extend_from_slice(&chunk);
+        if data.len() as u64 > MAX_DOWNLOAD_SIZE {
+            anyhow::bail!("File too large: over {} bytes", MAX_DOWNLOAD_SIZE);
+        }
+    }
+    Ok((data, media_type))
+}
+
+/// Guess a media type from a file name or URL path (None for unknown extensions)
+pub(crate) fn media_type_from_path(path: &str) -> Option<&'static str> {
+    let path = path.split(['?', '#']).next().unwrap_or(path);
+    let extension = Path::new(path)
         .extension()
         .and_then(|e| e.to_str())
         .map(|s| s.to_lowercase())
         .unwrap_or_default();
-
     let media_type = match extension.as_str() {
+        "txt" | "log" => "text/plain",
+        "md" | "markdown" => "text/markdown",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "rs" | "py" | "js" | "ts" | "go" | "java" | "c" | "h" | "cpp" | "sh" | "yaml" | "yml" => "text/plain",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "toml" => "application/toml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
         "png" => "image/png",
         "jpg" | "jpeg" => "image/jpeg",
         "gif" => "image/gif",
         "webp" => "image/webp",
-        _ => "application/octet-stream",
+        "svg" => "image/svg+xml",
+        _ => return None,
     };
-
-    // Encode to base64
-    use base64::Engine;
-    let base64_data = base64::engine::general_purpose::STANDARD.encode(&data);
-
-    tracing::info!(
-        "[Attachments] Read image: {} ({} bytes, type: {})",
-        original_path,
-        data.len(),
-        media_type
-    );
-
-    Ok(vec![ContentBlock::image(
-        base64_data,
-        media_type.to_string(),
-    )])
-}
-
-/// Read a PDF file
-fn read_pdf(resolved_path: &str, original_path: &str) -> Result<Vec<ContentBlock>> {
-    // Check file size first
-    let metadata = fs::metadata(resolved_path)?;
-
-    if metadata.len() > MAX_PDF_SIZE {
-        anyhow::bail!(
-            "PDF file too large: {} bytes (max: {} bytes)",
-            metadata.len(),
-            MAX_PDF_SIZE
-        );
-    }
-
-    // Read the file as bytes
-    let data = fs::read(resolved_path)?;
-
-    // Encode to base64
-    use base64::Engine;
-    let base64_data = base64::engine::general_purpose::STANDARD.encode(&data);
-
-    tracing::info!(
-        "[Attachments] Read PDF: {} ({} bytes)",
-        original_path,
-        data.len()
-    );
-
-    Ok(vec![ContentBlock::document(
-        base64_data,
-        "application/pdf".to_string(),
-    )])
+    Some(media_type)
 }
 
 /// Read a directory and list its contents
@@ -307,6 +488,7 @@ fn read_directory(resolved_path: &str, original_path: &str) -> Result<Vec<Conten
     Ok(vec![ContentBlock::Text {
         text: result,
         cache_control: None,
+        citations: None,
     }])
 }
 
@@ -340,6 +522,59 @@ mod tests {
         assert_eq!(paths, vec!["file1.txt", "file2.png"]);
     }
 
+    #[tokio::test]
+    async fn test_bytes_attachments() {
+        let png = vec![0x89, b'P', b'N', b'G'];
+        let blocks = load_attachments(
+            &[
+                Attachment::bytes(png, "image/png").with_name("screenshot.png"),
+                Attachment::bytes("fn main() {}", "text/x-rust").with_name("main.rs"),
+                Attachment::bytes(vec![0u8; 4], "application/zip"),
+            ],
+            "/",
+        )
+        .await;
+
+        assert_eq!(blocks.len(), 3);
+        assert!(matches!(
+            &blocks[0],
+            ContentBlock::Image { source, .. } if source.media_type == "image/png" && source.data == "iVBORw=="
+        ));
+        assert!(matches!(&blocks[1], ContentBlock::Text { text, .. } if text.contains("File: main.rs") && text.contains("fn main() {}")));
+        assert!(matches!(
+            &blocks[2],
+            ContentBlock::Text { text, .. } if text.contains("Unsupported attachment type: application/zip")
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_attachments_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let big = dir.path().join("big.png");
+        fs::write(&big, vec![0u8; MAX_IMAGE_SIZE as usize + 1]).unwrap();
+        let base_dir = dir.path().to_string_lossy().to_string();
+
+        let blocks = load_attachments(
+            &[
+                Attachment::path(&big),
+                Attachment::bytes(vec![0u8; MAX_IMAGE_SIZE as usize + 1], "image/jpeg"),
+            ],
+            &base_dir,
+        )
+        .await;
+        assert_eq!(blocks.len(), 2);
+        for block in &blocks {
+            assert!(matches!(block, ContentBlock::Text { text, .. } if text.contains("Image file too large")), "{block:?}");
+        }
+
+        // The legacy tags go through the same checks
+        let tagged = process_attachments("<vibe-work-attachment>big.png</vibe-work-attachment>", &base_dir);
+        assert!(matches!(&tagged[0], ContentBlock::Text { text, .. } if text.contains("Image file too large")));
+
+        let url = load_attachments(&[Attachment::url("file:///etc/passwd")], &base_dir).await;
+        assert!(matches!(&url[0], ContentBlock::Text { text, .. } if text.contains("Only http and https")));
+    }
+
     #[test]
     fn test_resolve_path() {
         // Absolute path
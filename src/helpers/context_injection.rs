@@ -20,7 +20,7 @@
 //! let todo_injection = FnInjection::new("todo_reminder", |internals, mut messages| {
 //!     let manager = internals.context.get_resource::<TodoListManager>();
 //!     if let Some(manager) = manager {
-//!         let turns_since_update = internals.context.current_turn - manager.last_updated_turn();
+//!         let turns_since_update = internals.turn() - manager.last_updated_turn();
 //!         if turns_since_update > 20 {
 //!             // Inject a reminder into the last message
 //!             inject_system_reminder(&mut messages, "Consider updating your todo list");
@@ -32,10 +32,48 @@
 //! // Add to agent's injection list
 //! agent.add_injection(todo_injection);
 //! ```
+//!
+//! # Ordering and frequency
+//!
+//! Injections run by priority (highest first, ties in the order they were
+//! added). By default an injection runs before every LLM call, including
+//! each tool iteration of a turn, and adds to the messages as they are then.
+//! `InjectionFrequency::OncePerTurn` applies it to the messages of the
+//! turn's first call on every request of the turn, so a reminder stays where
+//! it was put (next to the prompt) instead of being repeated after each tool
+//! result:
+//!
+//! ```ignore
+//! let reminder = FnInjection::new("todo_reminder", |_, mut messages| {
+//!     inject_system_reminder(&mut messages, "Keep the todo list current");
+//!     messages
+//! })
+//! .with_frequency(InjectionFrequency::OncePerTurn)
+//! .with_priority(10);
+//! ```
+//!
+//! `internals.context.current_iteration` is the number of the LLM call
+//! within the turn (1 for the first), for injections that branch on it.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
 use crate::llm::Message;
 use crate::runtime::AgentInternals;
 
+/// How often an injection runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InjectionFrequency {
+    /// Before every LLM call, including tool iterations (default)
+    #[default]
+    EveryCall,
+    /// Before every LLM call of a turn, on the messages of its first call
+    OncePerTurn,
+    /// Before the first LLM call of the session
+    OncePerSession,
+}
+
 /// Trait for context injection implementations
 ///
 /// Implement this trait to create reusable, stateful injections.
@@ -56,6 +94,16 @@ pub trait ContextInjection: Send + Sync {
     /// # Returns
     /// The modified message history to send to the LLM
     fn inject(&self, internals: &AgentInternals, messages: Vec<Message>) -> Vec<Message>;
+
+    /// Order among injections in a chain (higher runs first, default 0)
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// How often this injection runs (default: every call)
+    fn frequency(&self) -> InjectionFrequency {
+        InjectionFrequency::EveryCall
+    }
 }
 
 /// A context injection created from a closure
@@ -74,6 +122,8 @@ where
 {
     name: String,
     func: F,
+    priority: i32,
+    frequency: InjectionFrequency,
 }
 
 impl<F> FnInjection<F>
@@ -85,8 +135,22 @@ where
         Self {
             name: name.into(),
             func,
+            priority: 0,
+            frequency: InjectionFrequency::EveryCall,
         }
     }
+
+    /// Set the priority (higher runs first, default 0)
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Set how often the injection runs
+    pub fn with_frequency(mut self, frequency: InjectionFrequency) -> Self {
+        self.frequency = frequency;
+        self
+    }
 }
 
 impl<F> ContextInjection for FnInjection<F>
@@ -100,13 +164,77 @@ where
     fn inject(&self, internals: &AgentInternals, messages: Vec<Message>) -> Vec<Message> {
         (self.func)(internals, messages)
     }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn frequency(&self) -> InjectionFrequency {
+        self.frequency
+    }
 }
 
 /// Boxed context injection for storing in collections
 pub type BoxedInjection = Box<dyn ContextInjection>;
 
 /// Arc-wrapped injection for sharing across threads
-pub type SharedInjection = std::sync::Arc<dyn ContextInjection>;
+pub type SharedInjection = Arc<dyn ContextInjection>;
+
+/// An injection in a chain, with its run state
+struct ChainEntry {
+    injection: SharedInjection,
+    priority: i32,
+    enabled: AtomicBool,
+    /// Session id -> turn of the first run in it and the number of messages
+    /// it saw (for once-per-turn/session)
+    last_run: Mutex<HashMap<String, (usize, usize)>>,
+}
+
+impl ChainEntry {
+    /// Apply the injection if it's due, recording the run
+    fn apply(&self, internals: &AgentInternals, mut messages: Vec<Message>) -> Vec<Message> {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return messages;
+        }
+        let frequency = self.injection.frequency();
+        if frequency == InjectionFrequency::EveryCall {
+            return self.inject(internals, messages);
+        }
+        let turn = internals.turn();
+        let first_call_len = {
+            let mut last_run = self.last_run.lock().unwrap_or_else(|e| e.into_inner());
+            let previous = last_run.get(&internals.context.session_id).copied();
+            match (frequency, previous) {
+                (InjectionFrequency::OncePerTurn, Some((run_turn, len)))
+                    if run_turn == turn && len <= messages.len() =>
+                {
+                    Some(len)
+                }
+                (InjectionFrequency::OncePerSession, Some(_)) => return messages,
+                _ => {
+                    last_run.insert(internals.context.session_id.clone(), (turn, messages.len()));
+                    None
+                }
+            }
+        };
+        match first_call_len {
+            // A later call of the turn: inject into the messages the first
+            // call had, then add the ones since
+            Some(len) => {
+                let since = messages.split_off(len);
+                let mut messages = self.inject(internals, messages);
+                messages.extend(since);
+                messages
+            }
+            None => self.inject(internals, messages),
+        }
+    }
+
+    fn inject(&self, internals: &AgentInternals, messages: Vec<Message>) -> Vec<Message> {
+        tracing::debug!("Applying context injection: {}", self.injection.name());
+        self.injection.inject(internals, messages)
+    }
+}
 
 /// A chain of context injections that are applied in order
 ///
@@ -114,8 +242,14 @@ pub type SharedInjection = std::sync::Arc<dyn ContextInjection>;
 /// `messages = injection1(messages)`
 /// `messages = injection2(messages)`
 /// etc.
+///
+/// Injections run by priority (highest first), then in the order they were
+/// added. Clones share the enabled flags and run state of the injections
+/// already in the chain, so a clone kept by the application can switch
+/// injections on and off while an agent uses the chain.
+#[derive(Clone)]
 pub struct InjectionChain {
-    injections: Vec<SharedInjection>,
+    injections: Vec<Arc<ChainEntry>>,
 }
 
 impl InjectionChain {
@@ -128,12 +262,19 @@ impl InjectionChain {
 
     /// Add an injection to the chain
     pub fn add<I: ContextInjection + 'static>(&mut self, injection: I) {
-        self.injections.push(std::sync::Arc::new(injection));
+        self.add_shared(Arc::new(injection));
     }
 
     /// Add a shared injection to the chain
     pub fn add_shared(&mut self, injection: SharedInjection) {
-        self.injections.push(injection);
+        let entry = ChainEntry {
+            priority: injection.priority(),
+            injection,
+            enabled: AtomicBool::new(true),
+            last_run: Mutex::new(HashMap::new()),
+        };
+        let index = self.injections.partition_point(|e| e.priority >= entry.priority);
+        self.injections.insert(index, Arc::new(entry));
     }
 
     /// Add a function-based injection to the chain
@@ -144,17 +285,39 @@ impl InjectionChain {
         self.add(FnInjection::new(name, func));
     }
 
-    /// Apply all injections in order
+    /// Apply the enabled injections in priority order
     ///
-    /// Each injection receives the output of the previous one.
+    /// Each injection receives the output of the previous one. Once-per-turn
+    /// injections see the messages of the turn's first call on later calls,
+    /// and once-per-session injections are skipped after the first call of the
+    /// session.
     pub fn apply(&self, internals: &AgentInternals, mut messages: Vec<Message>) -> Vec<Message> {
-        for injection in &self.injections {
-            tracing::debug!("Applying context injection: {}", injection.name());
-            messages = injection.inject(internals, messages);
+        for entry in &self.injections {
+            messages = entry.apply(internals, messages);
         }
         messages
     }
 
+    /// Enable or disable the injections with this name
+    ///
+    /// Returns false if no injection has the name.
+    pub fn set_enabled(&self, name: &str, enabled: bool) -> bool {
+        let mut found = false;
+        for entry in self.injections.iter().filter(|e| e.injection.name() == name) {
+            entry.enabled.store(enabled, Ordering::Relaxed);
+            found = true;
+        }
+        found
+    }
+
+    /// Check if an injection is enabled (None if no injection has the name)
+    pub fn is_enabled(&self, name: &str) -> Option<bool> {
+        self.injections
+            .iter()
+            .find(|e| e.injection.name() == name)
+            .map(|e| e.enabled.load(Ordering::Relaxed))
+    }
+
     /// Get the number of injections in the chain
     pub fn len(&self) -> usize {
         self.injections.len()
@@ -165,9 +328,9 @@ impl InjectionChain {
         self.injections.is_empty()
     }
 
-    /// Get the names of all injections in the chain
+    /// Get the names of all injections in the chain, in the order they run
     pub fn names(&self) -> Vec<&str> {
-        self.injections.iter().map(|i| i.name()).collect()
+        self.injections.iter().map(|e| e.injection.name()).collect()
     }
 }
 
@@ -190,7 +353,7 @@ impl Default for InjectionChain {
 /// ```ignore
 /// inject_system_reminder(&mut messages, "Remember to update your todo list");
 /// ```
-pub fn inject_system_reminder(messages: &mut Vec<Message>, reminder: &str) {
+pub fn inject_system_reminder(messages: &mut [Message], reminder: &str) {
     if let Some(last_msg) = messages.last_mut() {
         let reminder_text = format!("\n<vibe-working-agent-systemreminder>\n{}\n</vibe-working-agent-systemreminder>", reminder);
         last_msg.append_text(&reminder_text);
@@ -205,7 +368,7 @@ pub fn inject_system_reminder(messages: &mut Vec<Message>, reminder: &str) {
 /// ```ignore
 /// prepend_to_first_user_message(&mut messages, "Context: You are working on project X\n\n");
 /// ```
-pub fn prepend_to_first_user_message(messages: &mut Vec<Message>, text: &str) {
+pub fn prepend_to_first_user_message(messages: &mut [Message], text: &str) {
     for msg in messages.iter_mut() {
         if msg.role.as_str() == "user" {
             msg.prepend_text(text);
@@ -220,7 +383,7 @@ pub fn prepend_to_first_user_message(messages: &mut Vec<Message>, text: &str) {
 /// ```ignore
 /// append_to_last_message(&mut messages, "\n\nRemember: Be concise.");
 /// ```
-pub fn append_to_last_message(messages: &mut Vec<Message>, text: &str) {
+pub fn append_to_last_message(messages: &mut [Message], text: &str) {
     if let Some(last_msg) = messages.last_mut() {
         last_msg.append_text(text);
     }
@@ -246,4 +409,28 @@ mod tests {
         assert_eq!(chain.len(), 2);
         assert_eq!(chain.names(), vec!["first", "second"]);
     }
+
+    #[test]
+    fn test_injection_chain_priority_order() {
+        let mut chain = InjectionChain::new();
+        chain.add_fn("default", |_, m| m);
+        chain.add(FnInjection::new("low", |_, m| m).with_priority(-5));
+        chain.add(FnInjection::new("high", |_, m| m).with_priority(10));
+        chain.add_fn("default_later", |_, m| m);
+
+        assert_eq!(chain.names(), vec!["high", "default", "default_later", "low"]);
+    }
+
+    #[test]
+    fn test_set_enabled_is_shared_with_clones() {
+        let mut chain = InjectionChain::new();
+        chain.add_fn("reminder", |_, m| m);
+        let handle = chain.clone();
+
+        assert_eq!(chain.is_enabled("reminder"), Some(true));
+        assert!(handle.set_enabled("reminder", false));
+        assert_eq!(chain.is_enabled("reminder"), Some(false));
+        assert!(!handle.set_enabled("missing", false));
+        assert_eq!(chain.is_enabled("missing"), None);
+    }
 }
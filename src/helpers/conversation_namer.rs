@@ -10,19 +10,28 @@
 //! use shadow_agent_sdk::helpers::ConversationNamer;
 //!
 //! // Create namer with an existing LLM provider
-//! let namer = ConversationNamer::new(&llm);
+//! let namer = ConversationNamer::new(llm.clone());
 //!
 //! // Generate a name from session messages
-//! let name = namer.generate_name(session.history()).await?;
+//! let name = namer.generate_name(session.history(), None).await?;
 //!
 //! // Set the name on the session
 //! session.set_conversation_name(&name)?;
+//!
+//! // Or: a cheap variant of the same provider, and regenerate the name in
+//! // place (e.g. after compacting the history)
+//! let namer = ConversationNamer::with_config(
+//!     llm.clone(),
+//!     NamerConfig::new().with_model("claude-haiku-4-5").with_max_length(40),
+//! );
+//! namer.rename(&mut session).await?;
 //! ```
 
 use anyhow::Result;
 use std::sync::Arc;
 
 use crate::llm::{ContentBlock, LlmProvider, Message, MessageContent};
+use crate::session::AgentSession;
 
 /// System prompt for generating conversation names
 const NAMING_SYSTEM_PROMPT: &str = r#"You are a conversation naming assistant. Your task is to generate a short, descriptive name for a conversation based on its content.
@@ -39,9 +48,75 @@ Respond with ONLY the conversation name, nothing else.
 
 The text that will follow will always be the conversation history. Assume the text is the conversation history."#;
 
+/// Settings for conversation naming
+#[derive(Debug, Clone)]
+pub struct NamerConfig {
+    /// Model to name with, as a `create_variant` of the provider (None = the
+    /// provider's own model)
+    pub model: Option<String>,
+    /// Max tokens for the variant created for `model`
+    pub max_tokens: u32,
+    /// System prompt sent with the conversation text
+    pub prompt: String,
+    /// Longest name kept, in characters (cut at a word boundary)
+    pub max_length: usize,
+    /// How many of the most recent messages are sent
+    pub max_messages: usize,
+}
+
+impl Default for NamerConfig {
+    fn default() -> Self {
+        Self {
+            model: None,
+            max_tokens: 256,
+            prompt: NAMING_SYSTEM_PROMPT.to_string(),
+            max_length: 60,
+            max_messages: 20,
+        }
+    }
+}
+
+impl NamerConfig {
+    /// Create a config with the default prompt and limits
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Name with a variant of the provider using this model
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Set the max tokens of the naming variant (default: 256)
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Replace the naming system prompt
+    pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+
+    /// Set the longest name kept, in characters (default: 60)
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length.max(1);
+        self
+    }
+
+    /// Set how many recent messages are sent (default: 20)
+    pub fn with_max_messages(mut self, max_messages: usize) -> Self {
+        self.max_messages = max_messages.max(1);
+        self
+    }
+}
+
 /// Helper for generating conversation names
 pub struct ConversationNamer {
     llm: Arc<dyn LlmProvider>,
+    config: NamerConfig,
 }
 
 impl ConversationNamer {
@@ -50,7 +125,30 @@ impl ConversationNamer {
     /// The caller is responsible for providing an appropriate provider
     /// (e.g., a lightweight model for fast, cheap naming).
     pub fn new(llm: Arc<dyn LlmProvider>) -> Self {
-        Self { llm }
+        Self::with_config(llm, NamerConfig::default())
+    }
+
+    /// Create a namer with custom settings
+    ///
+    /// If the config names a model, a variant of `llm` with that model is
+    /// used, so any provider (or a swappable one) can name conversations.
+    pub fn with_config(llm: Arc<dyn LlmProvider>, config: NamerConfig) -> Self {
+        let llm = match config.model {
+            Some(ref model) => llm.create_variant(model, config.max_tokens),
+            None => llm,
+        };
+        Self { llm, config }
+    }
+
+    /// Generate a name from the session's history and set it
+    ///
+    /// Replaces any existing name, e.g. to rename a conversation after its
+    /// history was compacted.
+    pub async fn rename(&self, session: &mut AgentSession) -> Result<String> {
+        let session_id = session.session_id().to_string();
+        let name = self.generate_name(session.history(), Some(&session_id)).await?;
+        session.set_conversation_name(&name)?;
+        Ok(name)
     }
 
     /// Generate a conversation name from a list of messages
@@ -61,8 +159,9 @@ impl ConversationNamer {
             anyhow::bail!("Cannot name an empty conversation");
         }
 
-        // Format messages into a readable text
-        let formatted = Self::format_messages(messages);
+        // Format the most recent messages into a readable text
+        let recent = &messages[messages.len().saturating_sub(self.config.max_messages)..];
+        let formatted = Self::format_messages(recent);
 
         tracing::debug!(
             "[ConversationNamer] Generating name for {} messages",
@@ -76,11 +175,13 @@ impl ConversationNamer {
         // Call the LLM to generate a name
         let response = self
             .llm
-            .send_message(&formatted, &[], Some(NAMING_SYSTEM_PROMPT), Some(&session_id))
+            .send_message(&formatted, &[], Some(&self.config.prompt), Some(&session_id))
             .await?;
 
-        // Clean up the response (remove any extra whitespace or quotes)
-        let name = response.trim().trim_matches('"').trim().to_string();
+        let name = sanitize_name(&response, self.config.max_length);
+        if name.is_empty() {
+            anyhow::bail!("Naming model returned an empty name");
+        }
 
         tracing::info!("[ConversationNamer] Generated name: {}", name);
 
@@ -128,7 +229,7 @@ impl ConversationNamer {
                         ContentBlock::Text { text, .. } => {
                             text_parts.push(text.clone());
                         }
-                        ContentBlock::ToolUse { name, .. } => {
+                        ContentBlock::ToolUse { name, .. } | ContentBlock::ServerToolUse { name, .. } => {
                             // Include tool name to give context
                             text_parts.push(format!("[Using tool: {}]", name));
                         }
@@ -153,6 +254,12 @@ impl ConversationNamer {
                         ContentBlock::Document { .. } => {
                             // Skip document blocks - not useful for naming
                         }
+                        ContentBlock::WebSearchToolResult { .. } => {
+                            // Skip search results - encrypted page content
+                        }
+                        ContentBlock::SearchResult { title, .. } => {
+                            text_parts.push(format!("[Search result: {}]", title));
+                        }
                     }
                 }
 
@@ -162,6 +269,40 @@ impl ConversationNamer {
     }
 }
 
+/// Clean up a model-written name
+///
+/// Keeps the first non-empty line, drops a `Name:`/`Title:` label,
+/// surrounding quotes or markdown and a trailing period, collapses
+/// whitespace and cuts the result to `max_length` characters at a word
+/// boundary where possible.
+fn sanitize_name(raw: &str, max_length: usize) -> String {
+    let is_decoration = |c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '`' | '*' | '#');
+    let line = raw.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("");
+    let line = line.trim_matches(is_decoration);
+    let line = ["name:", "title:"]
+        .iter()
+        .find_map(|label| {
+            line.get(..label.len())
+                .filter(|prefix| prefix.eq_ignore_ascii_case(label))
+                .map(|_| &line[label.len()..])
+        })
+        .unwrap_or(line);
+    let line = line
+        .trim_start_matches(is_decoration)
+        .trim_end_matches(|c: char| is_decoration(c) || c == '.');
+    let name = line.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if name.chars().count() <= max_length {
+        return name;
+    }
+    let cut: String = name.chars().take(max_length).collect();
+    let at_word = name.chars().nth(max_length) == Some(' ');
+    match cut.rfind(' ') {
+        Some(space) if !at_word => cut[..space].to_string(),
+        _ => cut.trim_end().to_string(),
+    }
+}
+
 /// Convenience function to generate a conversation name
 ///
 /// # Example
@@ -233,4 +374,60 @@ mod tests {
         let text = ConversationNamer::extract_text_content(&content);
         assert_eq!(text, "First part Second part");
     }
+
+    #[test]
+    fn test_sanitize_name() {
+        assert_eq!(sanitize_name("  \"Fixing the  build\".\n", 60), "Fixing the build");
+        assert_eq!(sanitize_name("\n**Title: Rust trait help**\nExtra line", 60), "Rust trait help");
+        assert_eq!(sanitize_name("Debugging a flaky integration test", 20), "Debugging a flaky");
+        assert_eq!(sanitize_name("Supercalifragilistic", 5), "Super");
+    }
+
+    #[tokio::test]
+    async fn test_generate_name_with_config() {
+        use crate::llm::MockLlmProvider;
+
+        let llm = MockLlmProvider::new().with_text("\"Naming   Rust traits for a parser.\"");
+        let namer = ConversationNamer::with_config(
+            Arc::new(llm.clone()),
+            NamerConfig::new()
+                .with_model("cheap-model")
+                .with_prompt("Name it")
+                .with_max_length(20)
+                .with_max_messages(2),
+        );
+
+        let messages = vec![
+            Message::user("Old question about Python"),
+            Message::assistant("Old answer"),
+            Message::user("How do I implement traits?"),
+            Message::assistant("Use impl Trait for Type"),
+        ];
+        let name = namer.generate_name(&messages, Some("s1")).await.unwrap();
+        assert_eq!(name, "Naming Rust traits");
+
+        let request = &llm.requests()[0];
+        assert_eq!(request.model, "cheap-model");
+        let prompt = serde_json::to_string(&request.messages).unwrap();
+        assert!(prompt.contains("User: How do I implement traits?"), "{prompt}");
+        assert!(prompt.contains("Assistant: Use impl Trait for Type"));
+        assert!(!prompt.contains("Python"));
+        assert!(serde_json::to_string(&request.system).unwrap().contains("Name it"));
+    }
+
+    #[tokio::test]
+    async fn test_rename_replaces_existing_name() {
+        use crate::llm::MockLlmProvider;
+        use crate::session::SessionStorage;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut session =
+            AgentSession::new_with_storage("s1", "test", "Test", "", SessionStorage::with_dir(dir.path())).unwrap();
+        session.add_message(Message::user("Compacted summary of the work")).unwrap();
+        session.set_conversation_name("Old name").unwrap();
+
+        let namer = ConversationNamer::new(Arc::new(MockLlmProvider::new().with_text("New name")));
+        assert_eq!(namer.rename(&mut session).await.unwrap(), "New name");
+        assert_eq!(session.conversation_name(), Some("New name"));
+    }
 }
@@ -0,0 +1,340 @@
+//! Conversation summarizer helper
+//!
+//! Writes on-demand summaries of a conversation, for a sidebar, a session
+//! list or a rolling summary kept next to the history. This is separate
+//! from any history compaction: nothing in the history is replaced.
+//!
+//! Tool calls and results are described rather than pasted ("Called Bash:
+//! cargo test", "Bash failed: 3 tests failed"), so large outputs don't
+//! crowd out the conversation.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use shadow_agent_sdk::helpers::{ConversationSummarizer, SummaryStyle};
+//!
+//! // A cheap variant of the agent's provider
+//! let summarizer = ConversationSummarizer::with_model(llm.clone(), "claude-haiku-4-5");
+//!
+//! let title = summarizer.summarize(session.history(), SummaryStyle::Title, 64).await?;
+//!
+//! // Rolling summary: only the messages since the stored summary are sent
+//! let summary = summarizer.update_session(&mut session).await?;
+//! ```
+
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::llm::{ContentBlock, LlmProvider, Message, MessageContent};
+use crate::session::AgentSession;
+
+/// Longest tool detail or result line kept, in characters
+const MAX_DETAIL_CHARS: usize = 120;
+
+/// Words that make a line of tool output worth quoting
+const TELLING_WORDS: [&str; 5] = ["error", "fail", "passed", "test result", "warning"];
+
+/// System prompt shared by every style
+const SUMMARY_SYSTEM_PROMPT: &str = r#"You are a conversation summarizer. The text that follows is a conversation between a user and an AI assistant that can call tools; tool calls and their results are shown in square brackets.
+
+Summarize what the user asked for, what was done and found, and what is still open. Mention files, commands and decisions by name. Do not invent details and do not address the user.
+
+Respond with ONLY the summary."#;
+
+/// How a summary is written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SummaryStyle {
+    /// A short bulleted list (the default)
+    #[default]
+    Bullet,
+    /// One or two paragraphs of prose
+    Paragraph,
+    /// A title of a few words
+    Title,
+}
+
+impl SummaryStyle {
+    /// Instruction for the style, appended to the system prompt
+    fn instruction(self) -> &'static str {
+        match self {
+            SummaryStyle::Bullet => "Write 3-8 short bullet points starting with \"- \".",
+            SummaryStyle::Paragraph => "Write one or two short paragraphs.",
+            SummaryStyle::Title => "Write a title of 3-8 words in sentence case, without quotes or a trailing period.",
+        }
+    }
+}
+
+/// Helper for summarizing conversations
+pub struct ConversationSummarizer {
+    llm: Arc<dyn LlmProvider>,
+    style: SummaryStyle,
+    max_tokens: u32,
+}
+
+impl ConversationSummarizer {
+    /// Create a summarizer using the provider's own model
+    pub fn new(llm: Arc<dyn LlmProvider>) -> Self {
+        Self {
+            llm,
+            style: SummaryStyle::default(),
+            max_tokens: 1024,
+        }
+    }
+
+    /// Create a summarizer using a variant of the provider with `model`
+    pub fn with_model(llm: Arc<dyn LlmProvider>, model: &str) -> Self {
+        let llm = llm.create_variant(model, 1024);
+        Self::new(llm)
+    }
+
+    /// Set the style of incremental and session summaries (default: bullets)
+    pub fn with_style(mut self, style: SummaryStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Set the max tokens of incremental and session summaries (default: 1024)
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens.max(1);
+        self
+    }
+
+    /// Summarize a list of messages
+    pub async fn summarize(&self, messages: &[Message], style: SummaryStyle, max_tokens: u32) -> Result<String> {
+        if messages.is_empty() {
+            anyhow::bail!("Cannot summarize an empty conversation");
+        }
+        tracing::debug!("[ConversationSummarizer] Summarizing {} messages", messages.len());
+        self.complete(&format_messages(messages), style, max_tokens).await
+    }
+
+    /// Update a summary with the messages that came after it
+    ///
+    /// Only `new_messages` are sent, with the previous summary, so a rolling
+    /// summary stays cheap however long the conversation gets.
+    pub async fn summarize_incremental(&self, prev_summary: &str, new_messages: &[Message]) -> Result<String> {
+        if new_messages.is_empty() {
+            return Ok(prev_summary.to_string());
+        }
+        tracing::debug!(
+            "[ConversationSummarizer] Updating a summary with {} new messages",
+            new_messages.len()
+        );
+        let text = format!(
+            "Summary of the conversation so far:\n{}\n\nNew messages since that summary:\n{}\n\
+            Rewrite the summary so it also covers the new messages.",
+            prev_summary.trim(),
+            format_messages(new_messages)
+        );
+        self.complete(&text, self.style, self.max_tokens).await
+    }
+
+    /// Bring the session's stored summary up to date and return it
+    ///
+    /// Summarizes only the messages added since the stored summary, or the
+    /// whole history when there is none (or the history got shorter, e.g.
+    /// after a rewind). The result is saved with `AgentSession::set_summary`.
+    pub async fn update_session(&self, session: &mut AgentSession) -> Result<String> {
+        let history = session.history();
+        let text = match session.summary() {
+            Some(summary) if summary.message_count == history.len() => return Ok(summary.text),
+            Some(summary) if summary.message_count < history.len() => {
+                self.summarize_incremental(&summary.text, &history[summary.message_count..])
+                    .await?
+            }
+            _ => self.summarize(history, self.style, self.max_tokens).await?,
+        };
+        let message_count = session.history().len();
+        session.set_summary(&text, message_count)?;
+        Ok(text)
+    }
+
+    async fn complete(&self, text: &str, style: SummaryStyle, max_tokens: u32) -> Result<String> {
+        let system = format!("{}\n\n{}", SUMMARY_SYSTEM_PROMPT, style.instruction());
+        let llm = self.llm.create_variant(&self.llm.model(), max_tokens);
+        let summary = llm.send_message(text, &[], Some(&system), None).await?;
+        let summary = summary.trim();
+        if summary.is_empty() {
+            anyhow::bail!("Summarizing model returned an empty summary");
+        }
+        Ok(summary.to_string())
+    }
+}
+
+/// Format messages as a transcript, describing tool calls and results
+fn format_messages(messages: &[Message]) -> String {
+    // Tool names by call ID, so a result can say which tool it came from
+    let mut tool_names: HashMap<&str, &str> = HashMap::new();
+    let mut formatted = String::new();
+
+    for message in messages {
+        let role = if message.role == "user" { "User" } else { "Assistant" };
+        let parts: Vec<String> = match &message.content {
+            MessageContent::Text(text) => vec![text.clone()],
+            MessageContent::Blocks(blocks) => blocks
+                .iter()
+                .filter_map(|block| match block {
+                    ContentBlock::Text { text, .. } => Some(text.clone()),
+                    ContentBlock::ToolUse { id, name, input } | ContentBlock::ServerToolUse { id, name, input } => {
+                        tool_names.insert(id, name);
+                        Some(describe_call(name, input))
+                    }
+                    ContentBlock::ToolResult {
+                        tool_use_id,
+                        content,
+                        is_error,
+                        ..
+                    } => {
+                        let name = tool_names.get(tool_use_id.as_str()).copied().unwrap_or("Tool");
+                        Some(describe_result(name, content.as_deref(), is_error.unwrap_or(false)))
+                    }
+                    ContentBlock::Image { .. } => Some("[Image]".to_string()),
+                    ContentBlock::Document { .. } => Some("[Document]".to_string()),
+                    ContentBlock::WebSearchToolResult { .. } => Some("[Web search results]".to_string()),
+                    ContentBlock::SearchResult { title, .. } => Some(format!("[Search result: {}]", title)),
+                    ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } => None,
+                })
+                .collect(),
+        };
+        let content = parts.join(" ");
+        if !content.trim().is_empty() {
+            formatted.push_str(&format!("{}: {}\n", role, content));
+        }
+    }
+
+    formatted
+}
+
+/// "[Called Bash: cargo test]"
+fn describe_call(name: &str, input: &Value) -> String {
+    let detail = ["command", "file_path", "path", "pattern", "url", "description", "query"]
+        .iter()
+        .find_map(|key| input.get(key).and_then(Value::as_str));
+    match detail {
+        Some(detail) => format!("[Called {}: {}]", name, shorten(detail)),
+        None => format!("[Called {}]", name),
+    }
+}
+
+/// "[Bash returned 42 lines: test result: ok. 12 passed]"
+///
+/// Quotes one line: the first that mentions errors, failures or test
+/// results, else the first non-empty one.
+fn describe_result(name: &str, content: Option<&str>, is_error: bool) -> String {
+    let content = content.unwrap_or("");
+    let lines: Vec<&str> = content.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    let telling = lines
+        .iter()
+        .find(|line| {
+            let lower = line.to_lowercase();
+            TELLING_WORDS.iter().any(|word| lower.contains(word))
+        })
+        .or(lines.first());
+    let outcome = if is_error { "failed" } else { "returned" };
+    match telling {
+        None => format!("[{} {} nothing]", name, outcome),
+        Some(line) if lines.len() == 1 => format!("[{} {}: {}]", name, outcome, shorten(line)),
+        Some(line) => format!("[{} {} {} lines: {}]", name, outcome, lines.len(), shorten(line)),
+    }
+}
+
+/// Cut text to `MAX_DETAIL_CHARS`, on one line
+fn shorten(text: &str) -> String {
+    let line = text.lines().next().unwrap_or("").trim();
+    match line.char_indices().nth(MAX_DETAIL_CHARS) {
+        Some((cut, _)) => format!("{}...", &line[..cut]),
+        None if line.len() < text.trim().len() => format!("{}...", line),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::MockLlmProvider;
+    use crate::session::SessionStorage;
+    use serde_json::json;
+
+    fn conversation() -> Vec<Message> {
+        vec![
+            Message::user("Run the tests"),
+            Message::assistant_with_blocks(vec![
+                ContentBlock::text("Running them."),
+                ContentBlock::ToolUse {
+                    id: "call_1".to_string(),
+                    name: "Bash".to_string(),
+                    input: json!({"command": "cargo test"}),
+                },
+            ]),
+            Message::user_with_blocks(vec![ContentBlock::tool_result(
+                "call_1",
+                "Compiling app\nrunning 40 tests\nlong output line\ntest result: FAILED. 37 passed; 3 failed",
+                true,
+            )]),
+            Message::assistant("3 tests fail in parser.rs"),
+        ]
+    }
+
+    fn prompt(request: &crate::llm::MockRequest) -> String {
+        serde_json::to_string(&request.messages).unwrap()
+    }
+
+    #[test]
+    fn test_tools_are_described() {
+        let formatted = format_messages(&conversation());
+        assert!(formatted.contains("Assistant: Running them. [Called Bash: cargo test]"), "{formatted}");
+        assert!(formatted.contains("[Bash failed 4 lines: test result: FAILED. 37 passed; 3 failed]"));
+        assert!(!formatted.contains("long output line"));
+        assert_eq!(describe_result("Read", Some(""), false), "[Read returned nothing]");
+    }
+
+    #[tokio::test]
+    async fn test_summarize_sends_tool_descriptions() {
+        let llm = MockLlmProvider::new().with_text("  Fixing parser test failures\n");
+        let summarizer = ConversationSummarizer::new(Arc::new(llm.clone()));
+
+        let title = summarizer.summarize(&conversation(), SummaryStyle::Title, 64).await.unwrap();
+        assert_eq!(title, "Fixing parser test failures");
+
+        let request = &llm.requests()[0];
+        assert_eq!(request.max_tokens, 64);
+        assert!(prompt(request).contains("[Called Bash: cargo test]"));
+        assert!(serde_json::to_string(&request.system).unwrap().contains("title of 3-8 words"));
+    }
+
+    #[tokio::test]
+    async fn test_incremental_summary_sends_only_new_messages() {
+        let llm = MockLlmProvider::new()
+            .with_text("- Ran the tests, 3 failed")
+            .with_text("- Ran the tests, 3 failed\n- Fixed the parser");
+        let summarizer = ConversationSummarizer::new(Arc::new(llm.clone()));
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut session =
+            AgentSession::new_with_storage("s1", "test", "Test", "", SessionStorage::with_dir(dir.path())).unwrap();
+        for message in conversation() {
+            session.add_message(message).unwrap();
+        }
+        summarizer.update_session(&mut session).await.unwrap();
+        assert_eq!(session.summary().unwrap().message_count, 4);
+
+        session.add_message(Message::user("Fix the parser")).unwrap();
+        session.add_message(Message::assistant("Fixed it")).unwrap();
+        let text = summarizer.update_session(&mut session).await.unwrap();
+        assert!(text.ends_with("- Fixed the parser"));
+
+        let requests = llm.requests();
+        let delta = prompt(&requests[1]);
+        assert!(delta.contains("- Ran the tests, 3 failed"), "{delta}");
+        assert!(delta.contains("User: Fix the parser"));
+        assert!(!delta.contains("cargo test"));
+
+        // Stored in the metadata, and nothing to do until the history grows
+        let stored = AgentSession::load_with_storage("s1", SessionStorage::with_dir(dir.path())).unwrap();
+        assert_eq!(stored.summary().unwrap().message_count, 6);
+        summarizer.update_session(&mut session).await.unwrap();
+        assert_eq!(llm.call_count(), 2);
+    }
+}
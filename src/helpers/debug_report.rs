@@ -0,0 +1,301 @@
+//! HTML report of a debugger session
+//!
+//! Renders the events written by `Debugger` into one self-contained HTML
+//! file: a summary header (tokens, tool calls, duration) and a timeline of
+//! API requests/responses and tool calls, each with its raw JSON in a
+//! collapsible block.
+//!
+//! Lines that aren't valid JSON (such as the last line of a crashed run)
+//! are skipped and counted in the header.
+//!
+//! ```ignore
+//! let path = debugger.generate_report()?;   // debugger/report.html
+//!
+//! // Or from a saved events.jsonl
+//! let html = render_report(&fs::read_to_string("events.jsonl")?);
+//! ```
+
+use std::fmt::Write;
+
+use chrono::{DateTime, FixedOffset};
+use serde_json::Value;
+
+/// Name of the report written by `Debugger::generate_report`
+pub const REPORT_HTML: &str = "report.html";
+
+const STYLE: &str = "\
+body { font-family: -apple-system, 'Segoe UI', sans-serif; margin: 2em; color: #222; background: #fafafa; }
+h1 { font-size: 1.4em; }
+.summary { display: flex; flex-wrap: wrap; gap: 1em; margin-bottom: 1.5em; }
+.stat { background: #fff; border: 1px solid #ddd; border-radius: 6px; padding: 0.6em 1em; }
+.stat b { display: block; font-size: 1.3em; }
+.warning { background: #fff4e0; border: 1px solid #f0b45a; border-radius: 6px; padding: 0.6em 1em; margin-bottom: 1em; }
+.event { background: #fff; border-left: 4px solid #999; border-radius: 4px; margin: 0.5em 0; padding: 0.5em 1em; }
+.api_request { border-color: #3b7dd8; }
+.api_response { border-color: #2e9e5b; }
+.tool_call { border-color: #8a5cd1; }
+.tool_result { border-color: #2e9e5b; }
+.tool_result.error { border-color: #d33; background: #fff0f0; }
+.http_request, .http_response { border-color: #888; }
+.head { display: flex; gap: 1em; align-items: baseline; }
+.seq { color: #888; font-family: monospace; }
+.delta { color: #888; margin-left: auto; font-size: 0.9em; }
+.tokens span { display: inline-block; margin-right: 1em; }
+.tokens .cache { color: #2e9e5b; font-weight: bold; }
+pre { background: #f4f4f4; padding: 0.6em; overflow-x: auto; white-space: pre-wrap; word-break: break-word; }
+";
+
+/// Render an `events.jsonl` stream as an HTML report
+pub fn render_report(jsonl: &str) -> String {
+    render_events(jsonl.lines())
+}
+
+/// Render events, one JSON document per item
+pub(crate) fn render_events<'a, I>(events: I) -> String
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut skipped = 0;
+    let mut parsed = Vec::new();
+    for line in events {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Value>(line) {
+            Ok(event) => parsed.push(event),
+            Err(_) => skipped += 1,
+        }
+    }
+
+    let mut body = String::new();
+    write_summary(&mut body, &parsed, skipped);
+    body.push_str("<div class=\"timeline\">\n");
+    let mut previous: Option<DateTime<FixedOffset>> = None;
+    for event in &parsed {
+        let time = timestamp(event);
+        let delta = match (previous, time) {
+            (Some(previous), Some(time)) => Some(format_duration((time - previous).num_milliseconds())),
+            _ => None,
+        };
+        if time.is_some() {
+            previous = time;
+        }
+        write_event(&mut body, event, delta.as_deref());
+    }
+    body.push_str("</div>\n");
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Debugger report</title>\n<style>\n{}</style>\n</head>\n<body>\n<h1>Debugger report</h1>\n{}</body>\n</html>\n",
+        STYLE, body
+    )
+}
+
+fn write_summary(out: &mut String, events: &[Value], skipped: usize) {
+    let mut usage = [0u64; 4];
+    let mut requests = 0;
+    let mut tools = 0;
+    let mut tool_errors = 0;
+    for event in events {
+        match event_type(event) {
+            "api_request" => requests += 1,
+            "api_response" => {
+                for (total, value) in usage.iter_mut().zip(token_counts(event)) {
+                    *total += value;
+                }
+            }
+            "tool_call" => tools += 1,
+            "tool_result" if event["is_error"].as_bool() == Some(true) => tool_errors += 1,
+            _ => {}
+        }
+    }
+    let times: Vec<_> = events.iter().filter_map(timestamp).collect();
+    let duration = match (times.first(), times.last()) {
+        (Some(first), Some(last)) => format_duration((*last - *first).num_milliseconds()),
+        _ => "unknown".to_string(),
+    };
+
+    if skipped > 0 {
+        let _ = writeln!(
+            out,
+            "<div class=\"warning\">Skipped {} unreadable line(s); the log may be truncated.</div>",
+            skipped
+        );
+    }
+    out.push_str("<div class=\"summary\">\n");
+    let stats = [
+        ("Events", events.len().to_string()),
+        ("API requests", requests.to_string()),
+        ("Input tokens", usage[0].to_string()),
+        ("Output tokens", usage[1].to_string()),
+        ("Cache read", usage[2].to_string()),
+        ("Cache write", usage[3].to_string()),
+        ("Tool calls", tools.to_string()),
+        ("Tool errors", tool_errors.to_string()),
+        ("Duration", duration),
+    ];
+    for (label, value) in stats {
+        let _ = writeln!(out, "<div class=\"stat\"><b>{}</b>{}</div>", escape(&value), label);
+    }
+    out.push_str("</div>\n");
+}
+
+fn write_event(out: &mut String, event: &Value, delta: Option<&str>) {
+    let kind = event_type(event);
+    let error = kind == "tool_result" && event["is_error"].as_bool() == Some(true);
+    let (title, detail) = describe(kind, event);
+
+    let _ = writeln!(out, "<div class=\"event {}{}\">", escape(kind), if error { " error" } else { "" });
+    let _ = write!(
+        out,
+        "<div class=\"head\"><span class=\"seq\">#{}</span><b>{}</b>",
+        event["sequence"],
+        escape(&title)
+    );
+    if let Some(delta) = delta {
+        let _ = write!(out, "<span class=\"delta\">+{}</span>", delta);
+    }
+    out.push_str("</div>\n");
+    out.push_str(&detail);
+    let raw = serde_json::to_string_pretty(event).unwrap_or_default();
+    let _ = writeln!(out, "<details><summary>Raw JSON</summary><pre>{}</pre></details>", escape(&raw));
+    out.push_str("</div>\n");
+}
+
+/// Title and HTML body of an event
+fn describe(kind: &str, event: &Value) -> (String, String) {
+    match kind {
+        "api_request" => {
+            let messages = event["messages"].as_array().map(|m| m.len()).unwrap_or(0);
+            let tools = event["tool_definitions"].as_array().map(|t| t.len()).unwrap_or(0);
+            (
+                "API request".to_string(),
+                format!("<div>{} message(s), {} tool(s)</div>\n", messages, tools),
+            )
+        }
+        "api_response" => {
+            let response = &event["response"];
+            let [input, output, cache_read, cache_write] = token_counts(event);
+            let detail = format!(
+                "<div>{} &middot; stop: {}</div>\n<div class=\"tokens\"><span>in: {}</span><span>out: {}</span><span class=\"cache\">cache read: {}</span><span class=\"cache\">cache write: {}</span></div>\n",
+                escape(response["model"].as_str().unwrap_or("unknown model")),
+                escape(response["stop_reason"].as_str().unwrap_or("none")),
+                input,
+                output,
+                cache_read,
+                cache_write
+            );
+            ("API response".to_string(), detail)
+        }
+        "tool_call" => {
+            let input = serde_json::to_string_pretty(&event["input"]).unwrap_or_default();
+            (
+                format!("Tool call: {}", event["tool_name"].as_str().unwrap_or("?")),
+                format!(
+                    "<div class=\"seq\">{}</div>\n<pre>{}</pre>\n",
+                    escape(event["tool_id"].as_str().unwrap_or("")),
+                    escape(&input)
+                ),
+            )
+        }
+        "tool_result" => {
+            let status = if event["is_error"].as_bool() == Some(true) { "error" } else { "ok" };
+            (
+                format!("Tool result: {} ({})", event["tool_name"].as_str().unwrap_or("?"), status),
+                format!("<pre>{}</pre>\n", escape(event["output"].as_str().unwrap_or(""))),
+            )
+        }
+        "http_request" => (
+            format!(
+                "HTTP {} {}",
+                event["method"].as_str().unwrap_or("?"),
+                event["url"].as_str().unwrap_or("")
+            ),
+            String::new(),
+        ),
+        "http_response" => (format!("HTTP response {}", event["status"]), String::new()),
+        other => (format!("Event: {}", other), String::new()),
+    }
+}
+
+fn event_type(event: &Value) -> &str {
+    event["event_type"].as_str().unwrap_or("unknown")
+}
+
+/// Input, output, cache read and cache creation tokens of a response
+fn token_counts(event: &Value) -> [u64; 4] {
+    let usage = &event["response"]["usage"];
+    [
+        "input_tokens",
+        "output_tokens",
+        "cache_read_input_tokens",
+        "cache_creation_input_tokens",
+    ]
+    .map(|key| usage[key].as_u64().unwrap_or(0))
+}
+
+fn timestamp(event: &Value) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(event["timestamp"].as_str()?).ok()
+}
+
+fn format_duration(ms: i64) -> String {
+    if ms < 1000 {
+        format!("{}ms", ms)
+    } else if ms < 60_000 {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    } else {
+        format!("{}m {}s", ms / 60_000, (ms % 60_000) / 1000)
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/debugger").join(name)
+    }
+
+    /// Set UPDATE_GOLDEN=1 to rewrite report.html after an intended change
+    #[test]
+    fn test_report_matches_golden_file() {
+        let events = std::fs::read_to_string(fixture("events.jsonl")).unwrap();
+        let html = render_report(&events);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(fixture("report.html"), &html).unwrap();
+        }
+        let golden = std::fs::read_to_string(fixture("report.html")).unwrap();
+        assert_eq!(html, golden);
+    }
+
+    #[test]
+    fn test_partial_lines_are_skipped() {
+        let html = render_report("{\"event_type\":\"tool_call\",\"sequence\":0,\"tool_name\":\"Bash\"}\n{\"event_type\":\"tool_res");
+        assert!(html.contains("Skipped 1 unreadable line(s)"));
+        assert!(html.contains("Tool call: Bash"));
+        assert!(html.contains("<b>unknown</b>Duration"));
+    }
+
+    #[test]
+    fn test_content_is_escaped() {
+        let html = render_report(r#"{"event_type":"tool_result","sequence":1,"tool_name":"Bash","output":"<script>alert(1)</script>","is_error":true}"#);
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(html.contains("class=\"event tool_result error\""));
+    }
+}
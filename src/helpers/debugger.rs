@@ -3,6 +3,24 @@
 //! When enabled, logs all LLM interactions and tool calls to a `debugger/`
 //! folder within the session directory.
 //!
+//! By default every event is written to its own pretty-printed JSON file and
+//! nothing is ever rotated. `DebuggerConfig` adds limits for long-running agents:
+//!
+//! - **Segments**: after N events, start a new `segment_NNNNNN/` directory
+//! - **Size cap**: delete the oldest closed segments once the total exceeds a limit
+//! - **Output**: per-event files, an `events.jsonl` stream, or both
+//! - **Compression**: gzip the files of each segment once it is closed
+//! - **Report**: render all events into one HTML file (`generate_report`),
+//!   on demand or when the debugger is dropped
+//! - **Live sink**: stream every event as one JSON line to clients of a TCP
+//!   or Unix domain socket, for tail tools and monitors in other processes
+//!
+//! In-process consumers (a TUI, say) can `subscribe()` to the events instead.
+//!
+//! Every event is passed through a `Redactor` before it is written, so API
+//! keys and tokens that show up in messages or tool output never reach disk.
+//! Use `DebuggerConfig::without_redaction()` to write events verbatim.
+//!
 //! # Example
 //!
 //! ```ignore
@@ -11,38 +29,240 @@
 //! debugger.log_api_response(&response)?;
 //! debugger.log_tool_call("Read", &input)?;
 //! debugger.log_tool_result("Read", &result)?;
+//!
+//! // Bounded: 1000 events per segment, 100 MB total, jsonl only, gzipped
+//! let config = DebuggerConfig::new()
+//!     .with_max_events_per_segment(1000)
+//!     .with_max_total_bytes(100 * 1024 * 1024)
+//!     .with_output(DebuggerOutput::Jsonl)
+//!     .with_compression(true);
+//! let debugger = Debugger::with_config(session_dir, config)?;
+//!
+//! // Live: `nc 127.0.0.1 9229` prints each event as it happens
+//! let config = DebuggerConfig::new().with_live_sink(DebugSink::Tcp("127.0.0.1:9229".parse()?));
 //! ```
 
-use std::fs::{self, File};
-use std::io::BufWriter;
+use std::borrow::Cow;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
 
 use anyhow::Result;
 use serde::Serialize;
 use serde_json::Value;
+use tokio::sync::broadcast;
 
 use crate::llm::{Message, SystemPrompt};
 use crate::tools::ToolResult;
 
+use super::debug_report::{render_events, REPORT_HTML};
+use super::redactor::Redactor;
+
+/// Name of the JSON Lines event stream inside each segment
+pub const EVENTS_JSONL: &str = "events.jsonl";
+
+/// Events queued for each live sink client; newer events are dropped while it is full
+pub const LIVE_QUEUE_EVENTS: usize = 1024;
+
+/// Events buffered for each `subscribe()` receiver before it lags
+const SUBSCRIBER_CAPACITY: usize = 256;
+
+/// Where debug events are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DebuggerOutput {
+    /// One pretty-printed JSON file per event
+    #[default]
+    Files,
+    /// Only the `events.jsonl` stream
+    Jsonl,
+    /// Both per-event files and `events.jsonl`
+    Both,
+}
+
+impl DebuggerOutput {
+    fn writes_files(&self) -> bool {
+        matches!(self, DebuggerOutput::Files | DebuggerOutput::Both)
+    }
+
+    fn writes_jsonl(&self) -> bool {
+        matches!(self, DebuggerOutput::Jsonl | DebuggerOutput::Both)
+    }
+}
+
+/// Limits and output options for the debugger
+///
+/// The default matches the original behavior: per-event files directly in
+/// `debugger/`, no segments, no size cap, no compression. Redaction with the
+/// built-in patterns is on by default.
+#[derive(Debug, Clone)]
+pub struct DebuggerConfig {
+    /// Start a new segment directory after this many events (None = single directory)
+    pub max_events_per_segment: Option<u64>,
+
+    /// Delete the oldest closed segments once the total size exceeds this
+    ///
+    /// Only closed segments are deleted, so this needs `max_events_per_segment`.
+    pub max_total_bytes: Option<u64>,
+
+    /// Which files to write
+    pub output: DebuggerOutput,
+
+    /// Gzip the files of a segment once it is closed
+    pub compress_closed_segments: bool,
+
+    /// Masks secrets in each serialized event (None = write verbatim)
+    pub redactor: Option<Redactor>,
+
+    /// Write `report.html` when the debugger is dropped
+    pub report_on_drop: bool,
+
+    /// Socket to stream events to as they happen (None = no live sink)
+    pub live_sink: Option<DebugSink>,
+}
+
+/// Socket the debugger listens on for live event clients
+///
+/// Each client gets one line per event, redacted like `events.jsonl`,
+/// after a first `{"event_type":"connected",...}` line. A client that reads
+/// too slowly misses events (see `Debugger::dropped_live_events`) rather
+/// than slowing the agent down.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugSink {
+    /// A TCP address (port 0 picks a free port, see `Debugger::live_addr`)
+    Tcp(SocketAddr),
+    /// A Unix domain socket at this path (a stale socket file is replaced)
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl Default for DebuggerConfig {
+    fn default() -> Self {
+        Self {
+            max_events_per_segment: None,
+            max_total_bytes: None,
+            output: DebuggerOutput::default(),
+            compress_closed_segments: false,
+            redactor: Some(Redactor::new()),
+            report_on_drop: false,
+            live_sink: None,
+        }
+    }
+}
+
+impl DebuggerConfig {
+    /// Create the default config
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of events per segment
+    pub fn with_max_events_per_segment(mut self, max_events: u64) -> Self {
+        self.max_events_per_segment = Some(max_events);
+        self
+    }
+
+    /// Set the total size cap in bytes
+    pub fn with_max_total_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_total_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Set which files to write
+    pub fn with_output(mut self, output: DebuggerOutput) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Enable or disable gzip compression of closed segments
+    pub fn with_compression(mut self, enabled: bool) -> Self {
+        self.compress_closed_segments = enabled;
+        self
+    }
+
+    /// Use a custom redactor
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Write events without redacting secrets
+    pub fn without_redaction(mut self) -> Self {
+        self.redactor = None;
+        self
+    }
+
+    /// Generate the HTML report when the debugger is dropped
+    pub fn with_report_on_drop(mut self, enabled: bool) -> Self {
+        self.report_on_drop = enabled;
+        self
+    }
+
+    /// Stream events to clients of a socket as they happen
+    pub fn with_live_sink(mut self, sink: DebugSink) -> Self {
+        self.live_sink = Some(sink);
+        self
+    }
+}
+
 /// Debugger for logging API calls and tool executions
 pub struct Debugger {
     /// Directory where debug logs are stored
     dir: PathBuf,
-    /// Sequence counter for ordering events
-    sequence: AtomicU64,
     /// Whether debugging is enabled
     enabled: bool,
+    /// Limits and output options
+    config: DebuggerConfig,
+    /// Current segment and size bookkeeping
+    state: Mutex<SegmentState>,
+    /// Socket clients receiving events as JSON lines
+    live: Option<LiveSink>,
+    /// In-process subscribers
+    subscribers: broadcast::Sender<DebugEvent>,
+}
+
+/// Bookkeeping for the segment being written and the closed ones
+#[derive(Default)]
+struct SegmentState {
+    /// Sequence number of the next event
+    sequence: u64,
+    /// Index of the current segment
+    index: u64,
+    /// Events written to the current segment
+    events: u64,
+    /// Bytes written to the current segment
+    bytes: u64,
+    /// Closed segments still on disk: (index, bytes), oldest first
+    closed: Vec<(u64, u64)>,
+    /// Open `events.jsonl` of the current segment
+    jsonl: Option<BufWriter<File>>,
 }
 
 /// Types of debug events
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum EventType {
     ApiRequest,
     ApiResponse,
     ToolCall,
     ToolResult,
+    HttpRequest,
+    HttpResponse,
+}
+
+/// An event as sent to `Debugger::subscribe` receivers
+#[derive(Debug, Clone)]
+pub struct DebugEvent {
+    pub event_type: EventType,
+    pub sequence: u64,
+    /// The whole event, redacted like `events.jsonl`
+    pub data: Value,
 }
 
 /// API request event
@@ -50,6 +270,8 @@ pub enum EventType {
 pub struct ApiRequestEvent {
     pub event_type: EventType,
     pub sequence: u64,
+    /// When the event was logged (RFC 3339, UTC)
+    pub timestamp: String,
     pub system_prompt: Option<String>,
     pub messages: Vec<Message>,
     pub tool_definitions: Option<Vec<Value>>,
@@ -60,6 +282,8 @@ pub struct ApiRequestEvent {
 pub struct ApiRequestEventFull {
     pub event_type: EventType,
     pub sequence: u64,
+    /// When the event was logged (RFC 3339, UTC)
+    pub timestamp: String,
     pub system: Option<SystemPrompt>,
     pub messages: Vec<Message>,
     pub tool_definitions: Option<Vec<Value>>,
@@ -70,14 +294,42 @@ pub struct ApiRequestEventFull {
 pub struct ApiResponseEvent {
     pub event_type: EventType,
     pub sequence: u64,
+    /// When the event was logged (RFC 3339, UTC)
+    pub timestamp: String,
     pub response: Value,
 }
 
+/// Raw HTTP request event (from `PayloadCapture`)
+#[derive(Debug, Serialize)]
+pub struct HttpRequestEvent {
+    pub event_type: EventType,
+    pub sequence: u64,
+    /// When the event was logged (RFC 3339, UTC)
+    pub timestamp: String,
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Value,
+}
+
+/// Raw HTTP response event (from `PayloadCapture`)
+#[derive(Debug, Serialize)]
+pub struct HttpResponseEvent {
+    pub event_type: EventType,
+    pub sequence: u64,
+    /// When the event was logged (RFC 3339, UTC)
+    pub timestamp: String,
+    pub status: u16,
+    pub body: Value,
+}
+
 /// Tool call event
 #[derive(Debug, Serialize)]
 pub struct ToolCallEvent {
     pub event_type: EventType,
     pub sequence: u64,
+    /// When the event was logged (RFC 3339, UTC)
+    pub timestamp: String,
     pub tool_name: String,
     pub tool_id: String,
     pub input: Value,
@@ -88,6 +340,8 @@ pub struct ToolCallEvent {
 pub struct ToolResultEvent {
     pub event_type: EventType,
     pub sequence: u64,
+    /// When the event was logged (RFC 3339, UTC)
+    pub timestamp: String,
     pub tool_name: String,
     pub tool_id: String,
     pub output: String,
@@ -100,24 +354,51 @@ impl Debugger {
     /// # Arguments
     /// * `session_dir` - The session directory where `debugger/` will be created
     pub fn new(session_dir: impl AsRef<Path>) -> Result<Self> {
+        Self::with_config(session_dir, DebuggerConfig::default())
+    }
+
+    /// Create a debugger with segment, size and output options
+    ///
+    /// Existing segments in the directory are picked up, so a resumed session
+    /// continues after the last one and counts it towards the size cap.
+    pub fn with_config(session_dir: impl AsRef<Path>, config: DebuggerConfig) -> Result<Self> {
         let dir = session_dir.as_ref().join("debugger");
         fs::create_dir_all(&dir)?;
 
         tracing::info!("[Debugger] Created debug directory: {:?}", dir);
 
-        Ok(Self {
+        let mut state = SegmentState::default();
+        if config.max_events_per_segment.is_some() {
+            let existing = existing_segments(&dir)?;
+            // Start fresh after the last segment rather than appending to it
+            if let Some((last, _)) = existing.last() {
+                state.index = last + 1;
+            }
+            state.closed = existing;
+        }
+
+        let live = config.live_sink.as_ref().map(LiveSink::bind).transpose()?;
+        let debugger = Self {
             dir,
-            sequence: AtomicU64::new(0),
             enabled: true,
-        })
+            config,
+            state: Mutex::new(state),
+            live,
+            subscribers: broadcast::channel(SUBSCRIBER_CAPACITY).0,
+        };
+        fs::create_dir_all(debugger.segment_dir(debugger.lock_state().index))?;
+        Ok(debugger)
     }
 
     /// Create a disabled debugger (no-op for all operations)
     pub fn disabled() -> Self {
         Self {
             dir: PathBuf::new(),
-            sequence: AtomicU64::new(0),
             enabled: false,
+            config: DebuggerConfig::default(),
+            state: Mutex::new(SegmentState::default()),
+            live: None,
+            subscribers: broadcast::channel(1).0,
         }
     }
 
@@ -131,9 +412,181 @@ impl Debugger {
         &self.dir
     }
 
-    /// Get the next sequence number
-    fn next_sequence(&self) -> u64 {
-        self.sequence.fetch_add(1, Ordering::SeqCst)
+    /// Get the config in use
+    pub fn config(&self) -> &DebuggerConfig {
+        &self.config
+    }
+
+    /// Get the directory events are currently written to
+    ///
+    /// This is `dir()` itself unless segments are enabled.
+    pub fn current_segment_dir(&self) -> PathBuf {
+        self.segment_dir(self.lock_state().index)
+    }
+
+    /// Receive every event from now on, as it is logged
+    ///
+    /// A receiver that falls more than 256 events behind skips the oldest
+    /// (`RecvError::Lagged`). A disabled debugger sends nothing.
+    pub fn subscribe(&self) -> broadcast::Receiver<DebugEvent> {
+        self.subscribers.subscribe()
+    }
+
+    /// Address the live sink listens on (None without one)
+    ///
+    /// For `DebugSink::Tcp` with port 0, this has the port that was picked.
+    pub fn live_addr(&self) -> Option<&DebugSink> {
+        self.live.as_ref().map(|live| &live.addr)
+    }
+
+    /// Events not sent to live sink clients because they read too slowly
+    pub fn dropped_live_events(&self) -> u64 {
+        self.live.as_ref().map(|live| live.dropped.load(Ordering::Relaxed)).unwrap_or(0)
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, SegmentState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn segment_dir(&self, index: u64) -> PathBuf {
+        if self.config.max_events_per_segment.is_some() {
+            self.dir.join(segment_name(index))
+        } else {
+            self.dir.clone()
+        }
+    }
+
+    /// Write one event to the current segment, rolling over and enforcing
+    /// the size cap as configured, then pass it to live clients and subscribers
+    ///
+    /// The event gets its sequence number under the state lock, so numbers
+    /// follow the order events are written in. Returns the number.
+    fn write_event<T: Serialize>(
+        &self,
+        event_type: EventType,
+        file_name: impl FnOnce(u64) -> String,
+        event: impl FnOnce(u64) -> T,
+    ) -> Result<u64> {
+        let mut state = self.lock_state();
+        let sequence = state.sequence;
+        state.sequence += 1;
+        let event = &event(sequence);
+        let file_name = file_name(sequence);
+
+        if let Some(max_events) = self.config.max_events_per_segment {
+            if state.events >= max_events {
+                self.rollover(&mut state)?;
+            }
+        }
+
+        let segment_dir = self.segment_dir(state.index);
+        let mut written = 0u64;
+
+        if self.config.output.writes_files() {
+            let bytes = self.redact(serde_json::to_vec_pretty(event)?);
+            let mut writer = BufWriter::new(File::create(segment_dir.join(&file_name))?);
+            writer.write_all(&bytes)?;
+            writer.flush()?;
+            written += bytes.len() as u64;
+        }
+
+        if self.config.output.writes_jsonl() {
+            if state.jsonl.is_none() {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(segment_dir.join(EVENTS_JSONL))?;
+                state.jsonl = Some(BufWriter::new(file));
+            }
+            let mut line = self.redact(serde_json::to_vec(event)?);
+            line.push(b'\n');
+            if let Some(jsonl) = state.jsonl.as_mut() {
+                jsonl.write_all(&line)?;
+                jsonl.flush()?;
+            }
+            written += line.len() as u64;
+        }
+
+        state.events += 1;
+        state.bytes += written;
+
+        self.enforce_size_cap(&mut state);
+
+        // Still under the lock, so clients get events in the order they were written
+        let streamed = self.live.as_ref().is_some_and(|live| live.has_clients());
+        if streamed || self.subscribers.receiver_count() > 0 {
+            let line = self.redact(serde_json::to_vec(event)?);
+            if self.subscribers.receiver_count() > 0 {
+                let data = serde_json::from_slice(&line)?;
+                let _ = self.subscribers.send(DebugEvent { event_type, sequence, data });
+            }
+            if let Some(live) = self.live.as_ref().filter(|_| streamed) {
+                live.send(line);
+            }
+        }
+        Ok(sequence)
+    }
+
+    /// Mask secrets in a serialized event
+    fn redact(&self, bytes: Vec<u8>) -> Vec<u8> {
+        let Some(redactor) = &self.config.redactor else {
+            return bytes;
+        };
+        match String::from_utf8(bytes) {
+            Ok(text) => match redactor.redact(&text) {
+                Cow::Borrowed(_) => text.into_bytes(),
+                Cow::Owned(redacted) => redacted.into_bytes(),
+            },
+            Err(e) => e.into_bytes(),
+        }
+    }
+
+    /// Close the current segment and start the next one
+    fn rollover(&self, state: &mut SegmentState) -> Result<()> {
+        if let Some(mut jsonl) = state.jsonl.take() {
+            jsonl.flush()?;
+        }
+
+        let closed_dir = self.segment_dir(state.index);
+        let mut bytes = state.bytes;
+        if self.config.compress_closed_segments {
+            match compress_dir(&closed_dir) {
+                Ok(compressed) => bytes = compressed,
+                Err(e) => tracing::warn!(
+                    "[Debugger] Failed to compress segment {:?}: {}",
+                    closed_dir,
+                    e
+                ),
+            }
+        }
+        state.closed.push((state.index, bytes));
+
+        state.index += 1;
+        state.events = 0;
+        state.bytes = 0;
+        fs::create_dir_all(self.segment_dir(state.index))?;
+
+        tracing::debug!("[Debugger] Started segment {}", state.index);
+        Ok(())
+    }
+
+    /// Delete the oldest closed segments while over the size cap
+    fn enforce_size_cap(&self, state: &mut SegmentState) {
+        let Some(max_bytes) = self.config.max_total_bytes else {
+            return;
+        };
+
+        let mut total: u64 = state.bytes + state.closed.iter().map(|(_, b)| b).sum::<u64>();
+        while total > max_bytes && !state.closed.is_empty() {
+            let (index, bytes) = state.closed.remove(0);
+            let dir = self.segment_dir(index);
+            if let Err(e) = fs::remove_dir_all(&dir) {
+                tracing::warn!("[Debugger] Failed to delete segment {:?}: {}", dir, e);
+            } else {
+                tracing::debug!("[Debugger] Deleted segment {} to stay under size cap", index);
+            }
+            total -= bytes;
+        }
     }
 
     /// Log an API request (messages sent to LLM)
@@ -147,20 +600,18 @@ impl Debugger {
             return Ok(());
         }
 
-        let seq = self.next_sequence();
-        let event = ApiRequestEvent {
-            event_type: EventType::ApiRequest,
-            sequence: seq,
-            system_prompt: system_prompt.map(|s| s.to_string()),
-            messages: messages.to_vec(),
-            tool_definitions: tool_definitions.map(|t| t.to_vec()),
-        };
-
-        let filename = format!("{:06}_api_request.json", seq);
-        let path = self.dir.join(&filename);
-        let file = File::create(&path)?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, &event)?;
+        let seq = self.write_event(
+            EventType::ApiRequest,
+            |seq| format!("{:06}_api_request.json", seq),
+            |sequence| ApiRequestEvent {
+                event_type: EventType::ApiRequest,
+                sequence,
+                timestamp: now(),
+                system_prompt: system_prompt.map(|s| s.to_string()),
+                messages: messages.to_vec(),
+                tool_definitions: tool_definitions.map(|t| t.to_vec()),
+            },
+        )?;
 
         tracing::debug!("[Debugger] Logged API request #{}", seq);
         Ok(())
@@ -177,20 +628,18 @@ impl Debugger {
             return Ok(());
         }
 
-        let seq = self.next_sequence();
-        let event = ApiRequestEventFull {
-            event_type: EventType::ApiRequest,
-            sequence: seq,
-            system,
-            messages: messages.to_vec(),
-            tool_definitions: tool_definitions.map(|t| t.to_vec()),
-        };
-
-        let filename = format!("{:06}_api_request.json", seq);
-        let path = self.dir.join(&filename);
-        let file = File::create(&path)?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, &event)?;
+        let seq = self.write_event(
+            EventType::ApiRequest,
+            |seq| format!("{:06}_api_request.json", seq),
+            |sequence| ApiRequestEventFull {
+                event_type: EventType::ApiRequest,
+                sequence,
+                timestamp: now(),
+                system,
+                messages: messages.to_vec(),
+                tool_definitions: tool_definitions.map(|t| t.to_vec()),
+            },
+        )?;
 
         tracing::debug!("[Debugger] Logged API request #{} (with cache_control)", seq);
         Ok(())
@@ -202,43 +651,91 @@ impl Debugger {
             return Ok(());
         }
 
-        let seq = self.next_sequence();
-        let event = ApiResponseEvent {
-            event_type: EventType::ApiResponse,
-            sequence: seq,
-            response: response.clone(),
-        };
-
-        let filename = format!("{:06}_api_response.json", seq);
-        let path = self.dir.join(&filename);
-        let file = File::create(&path)?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, &event)?;
+        let seq = self.write_event(
+            EventType::ApiResponse,
+            |seq| format!("{:06}_api_response.json", seq),
+            |sequence| ApiResponseEvent {
+                event_type: EventType::ApiResponse,
+                sequence,
+                timestamp: now(),
+                response: response.clone(),
+            },
+        )?;
 
         tracing::debug!("[Debugger] Logged API response #{}", seq);
         Ok(())
     }
 
+    /// Log a provider HTTP request exactly as sent
+    pub fn log_http_request(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &[(String, String)],
+        body: &Value,
+    ) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let seq = self.write_event(
+            EventType::HttpRequest,
+            |seq| format!("{:06}_http_request.json", seq),
+            |sequence| HttpRequestEvent {
+                event_type: EventType::HttpRequest,
+                sequence,
+                timestamp: now(),
+                method: method.to_string(),
+                url: url.to_string(),
+                headers: headers.to_vec(),
+                body: body.clone(),
+            },
+        )?;
+
+        tracing::debug!("[Debugger] Logged HTTP request #{}", seq);
+        Ok(())
+    }
+
+    /// Log a provider HTTP response status and body
+    pub fn log_http_response(&self, status: u16, body: &Value) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let seq = self.write_event(
+            EventType::HttpResponse,
+            |seq| format!("{:06}_http_response.json", seq),
+            |sequence| HttpResponseEvent {
+                event_type: EventType::HttpResponse,
+                sequence,
+                timestamp: now(),
+                status,
+                body: body.clone(),
+            },
+        )?;
+
+        tracing::debug!("[Debugger] Logged HTTP response #{}", seq);
+        Ok(())
+    }
+
     /// Log a tool call
     pub fn log_tool_call(&self, tool_name: &str, tool_id: &str, input: &Value) -> Result<()> {
         if !self.enabled {
             return Ok(());
         }
 
-        let seq = self.next_sequence();
-        let event = ToolCallEvent {
-            event_type: EventType::ToolCall,
-            sequence: seq,
-            tool_name: tool_name.to_string(),
-            tool_id: tool_id.to_string(),
-            input: input.clone(),
-        };
-
-        let filename = format!("{:06}_tool_call_{}.json", seq, tool_name);
-        let path = self.dir.join(&filename);
-        let file = File::create(&path)?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, &event)?;
+        let seq = self.write_event(
+            EventType::ToolCall,
+            |seq| format!("{:06}_tool_call_{}.json", seq, tool_name),
+            |sequence| ToolCallEvent {
+                event_type: EventType::ToolCall,
+                sequence,
+                timestamp: now(),
+                tool_name: tool_name.to_string(),
+                tool_id: tool_id.to_string(),
+                input: input.clone(),
+            },
+        )?;
 
         tracing::debug!("[Debugger] Logged tool call #{}: {}", seq, tool_name);
         Ok(())
@@ -250,34 +747,23 @@ impl Debugger {
             return Ok(());
         }
 
-        let seq = self.next_sequence();
-
-        // Convert ToolResultData to string for logging
-        use crate::tools::ToolResultData;
-        let output_text = match &result.content {
-            ToolResultData::Text(text) => text.clone(),
-            ToolResultData::Image { data, media_type } => {
-                format!("Image ({}, {} bytes)", media_type, data.len())
-            }
-            ToolResultData::Document { description, data, media_type } => {
-                format!("{} ({}, {} bytes)", description, media_type, data.len())
-            }
-        };
 
-        let event = ToolResultEvent {
-            event_type: EventType::ToolResult,
-            sequence: seq,
-            tool_name: tool_name.to_string(),
-            tool_id: tool_id.to_string(),
-            output: output_text,
-            is_error: result.is_error,
-        };
+        // Convert the result content to a string for logging
+        let output_text = result.summary();
 
-        let filename = format!("{:06}_tool_result_{}.json", seq, tool_name);
-        let path = self.dir.join(&filename);
-        let file = File::create(&path)?;
-        let writer = BufWriter::new(file);
-        serde_json::to_writer_pretty(writer, &event)?;
+        let seq = self.write_event(
+            EventType::ToolResult,
+            |seq| format!("{:06}_tool_result_{}.json", seq, tool_name),
+            |sequence| ToolResultEvent {
+                event_type: EventType::ToolResult,
+                sequence,
+                timestamp: now(),
+                tool_name: tool_name.to_string(),
+                tool_id: tool_id.to_string(),
+                output: output_text,
+                is_error: result.is_error,
+            },
+        )?;
 
         tracing::debug!("[Debugger] Logged tool result #{}: {}", seq, tool_name);
         Ok(())
@@ -289,16 +775,642 @@ impl Debugger {
             return Ok(());
         }
 
+        let mut state = self.lock_state();
+        state.jsonl = None;
+
         for entry in fs::read_dir(&self.dir)? {
             let entry = entry?;
             let path = entry.path();
-            if path.extension().map(|e| e == "json").unwrap_or(false) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if path.is_dir() && name.starts_with(SEGMENT_PREFIX) {
+                fs::remove_dir_all(path)?;
+            } else if is_event_file(&name) {
                 fs::remove_file(path)?;
             }
         }
 
-        self.sequence.store(0, Ordering::SeqCst);
+        *state = SegmentState::default();
+        fs::create_dir_all(self.segment_dir(state.index))?;
         tracing::info!("[Debugger] Cleared debug logs");
         Ok(())
     }
+
+    /// Render every event logged so far into `report.html`
+    ///
+    /// Reads the `events.jsonl` of each segment (or the per-event files
+    /// where there is no stream) and returns the report's path.
+    pub fn generate_report(&self) -> Result<PathBuf> {
+        if !self.enabled {
+            anyhow::bail!("Debugger is disabled");
+        }
+
+        let mut events = Vec::new();
+        {
+            let mut state = self.lock_state();
+            if let Some(jsonl) = state.jsonl.as_mut() {
+                jsonl.flush()?;
+            }
+        }
+        let mut dirs: Vec<PathBuf> = existing_segments(&self.dir)?
+            .into_iter()
+            .map(|(index, _)| self.dir.join(segment_name(index)))
+            .collect();
+        dirs.push(self.dir.clone());
+        for dir in dirs {
+            read_events(&dir, &mut events)?;
+        }
+
+        let html = render_events(events.iter().map(String::as_str));
+        let path = self.dir.join(REPORT_HTML);
+        fs::write(&path, html)?;
+        tracing::info!("[Debugger] Wrote report: {:?}", path);
+        Ok(path)
+    }
+}
+
+impl Drop for Debugger {
+    fn drop(&mut self) {
+        if self.enabled && self.config.report_on_drop {
+            if let Err(e) = self.generate_report() {
+                tracing::warn!("[Debugger] Failed to write report: {}", e);
+            }
+        }
+    }
+}
+
+/// Current time for event timestamps
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+// ============================================================================
+// Live sink
+// ============================================================================
+
+/// Queues of the connected live clients
+type LiveClients = Arc<Mutex<Vec<SyncSender<Arc<[u8]>>>>>;
+
+/// A listening socket and the clients connected to it
+///
+/// A thread accepts clients; each client has a thread writing its queue to
+/// the socket, so a slow client only ever fills its own queue.
+struct LiveSink {
+    /// The address actually bound
+    addr: DebugSink,
+    clients: LiveClients,
+    dropped: AtomicU64,
+    /// Tells the accept thread to exit once it wakes up
+    stop: Arc<AtomicBool>,
+}
+
+impl LiveSink {
+    fn bind(sink: &DebugSink) -> Result<Self> {
+        let clients = LiveClients::default();
+        let stop = Arc::new(AtomicBool::new(false));
+        let addr = match sink {
+            DebugSink::Tcp(addr) => {
+                let listener = TcpListener::bind(addr)?;
+                let addr = listener.local_addr()?;
+                spawn_accept_loop(move || listener.accept().map(|(stream, _)| stream), clients.clone(), stop.clone())?;
+                DebugSink::Tcp(addr)
+            }
+            #[cfg(unix)]
+            DebugSink::Unix(path) => {
+                remove_stale_socket(path)?;
+                let listener = std::os::unix::net::UnixListener::bind(path)?;
+                spawn_accept_loop(move || listener.accept().map(|(stream, _)| stream), clients.clone(), stop.clone())?;
+                DebugSink::Unix(path.clone())
+            }
+        };
+        tracing::info!("[Debugger] Live sink listening on {:?}", addr);
+        Ok(Self {
+            addr,
+            clients,
+            dropped: AtomicU64::new(0),
+            stop,
+        })
+    }
+
+    fn lock_clients(&self) -> std::sync::MutexGuard<'_, Vec<SyncSender<Arc<[u8]>>>> {
+        self.clients.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn has_clients(&self) -> bool {
+        !self.lock_clients().is_empty()
+    }
+
+    /// Queue a line for every client, dropping it for clients that are full
+    fn send(&self, mut line: Vec<u8>) {
+        line.push(b'\n');
+        let line: Arc<[u8]> = line.into();
+        self.lock_clients().retain(|client| match client.try_send(line.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+    }
+}
+
+impl Drop for LiveSink {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // Ending the queues ends the writer threads; a connection wakes the accept thread
+        self.lock_clients().clear();
+        match &self.addr {
+            DebugSink::Tcp(addr) => {
+                let _ = TcpStream::connect(addr);
+            }
+            #[cfg(unix)]
+            DebugSink::Unix(path) => {
+                let _ = std::os::unix::net::UnixStream::connect(path);
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+/// Remove a socket file left behind by a debugger that is gone
+///
+/// Fails if another debugger still listens on it or the path isn't a socket.
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if !metadata.file_type().is_socket() {
+        anyhow::bail!("Live sink path {:?} exists and is not a socket", path);
+    }
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        anyhow::bail!("Live sink socket {:?} is in use by another debugger", path);
+    }
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+/// Accept clients until `stop` is set
+fn spawn_accept_loop<S, F>(mut accept: F, clients: LiveClients, stop: Arc<AtomicBool>) -> io::Result<()>
+where
+    S: Write + Send + 'static,
+    F: FnMut() -> io::Result<S> + Send + 'static,
+{
+    std::thread::Builder::new()
+        .name("debugger-live-accept".to_string())
+        .spawn(move || loop {
+            let accepted = accept();
+            if stop.load(Ordering::SeqCst) {
+                break;
+            }
+            match accepted {
+                Ok(stream) => add_client(stream, &clients),
+                Err(e) => {
+                    tracing::warn!("[Debugger] Live sink failed to accept a client: {}", e);
+                    std::thread::sleep(std::time::Duration::from_millis(100));
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// Register a client and start the thread that writes its queue
+fn add_client<S: Write + Send + 'static>(mut stream: S, clients: &LiveClients) {
+    let (sender, receiver) = mpsc::sync_channel::<Arc<[u8]>>(LIVE_QUEUE_EVENTS);
+    let greeting = serde_json::json!({"event_type": "connected", "timestamp": now()});
+    let mut greeting = greeting.to_string().into_bytes();
+    greeting.push(b'\n');
+    let mut clients = clients.lock().unwrap_or_else(|e| e.into_inner());
+    // Queued before any event, so a client that has read it gets every later event
+    if sender.try_send(greeting.into()).is_err() {
+        return;
+    }
+    let spawned = std::thread::Builder::new()
+        .name("debugger-live-client".to_string())
+        .spawn(move || {
+            for line in receiver {
+                if stream.write_all(&line).and_then(|_| stream.flush()).is_err() {
+                    break;
+                }
+            }
+        });
+    match spawned {
+        Ok(_) => {
+            clients.push(sender);
+            tracing::debug!("[Debugger] Live sink client connected");
+        }
+        Err(e) => tracing::warn!("[Debugger] Failed to start a live sink writer: {}", e),
+    }
+}
+
+// ============================================================================
+// Segment helpers
+// ============================================================================
+
+/// Prefix of segment directory names
+const SEGMENT_PREFIX: &str = "segment_";
+
+fn segment_name(index: u64) -> String {
+    format!("{}{:06}", SEGMENT_PREFIX, index)
+}
+
+/// Whether a file name is one the debugger writes
+fn is_event_file(name: &str) -> bool {
+    name.ends_with(".json")
+        || name.ends_with(".json.gz")
+        || name == EVENTS_JSONL
+        || name == format!("{}.gz", EVENTS_JSONL)
+}
+
+/// Read the events of one directory, one JSON document per entry
+///
+/// Uses `events.jsonl` (plain or gzipped) if present, else the per-event
+/// files in sequence order.
+fn read_events(dir: &Path, events: &mut Vec<String>) -> io::Result<()> {
+    let jsonl = dir.join(EVENTS_JSONL);
+    let gz = dir.join(format!("{}.gz", EVENTS_JSONL));
+    if jsonl.exists() || gz.exists() {
+        let text = if jsonl.exists() { read_lossy(&jsonl)? } else { read_lossy(&gz)? };
+        events.extend(text.lines().map(str::to_string));
+        return Ok(());
+    }
+
+    let mut names: Vec<String> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|n| n.ends_with(".json") || n.ends_with(".json.gz"))
+        .collect();
+    names.sort();
+    for name in names {
+        let text = read_lossy(&dir.join(&name))?;
+        // Flatten pretty-printed files to one line (invalid ones stay as they are)
+        match serde_json::from_str::<Value>(&text) {
+            Ok(value) => events.push(value.to_string()),
+            Err(_) => events.push(text.replace('\n', " ")),
+        }
+    }
+    Ok(())
+}
+
+/// Read a file as text, gunzipping `.gz` files
+///
+/// Invalid UTF-8 is replaced; a gzip stream cut short yields what was
+/// decoded before the cut.
+fn read_lossy(path: &Path) -> io::Result<String> {
+    let mut bytes = Vec::new();
+    if path.extension().map(|e| e == "gz").unwrap_or(false) {
+        let mut decoder = flate2::read::GzDecoder::new(File::open(path)?);
+        if let Err(e) = io::Read::read_to_end(&mut decoder, &mut bytes) {
+            tracing::warn!("[Debugger] {:?} is truncated: {}", path, e);
+        }
+    } else {
+        bytes = fs::read(path)?;
+    }
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Find existing segments and their sizes, oldest first
+fn existing_segments(dir: &Path) -> io::Result<Vec<(u64, u64)>> {
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Some(index) = name
+            .strip_prefix(SEGMENT_PREFIX)
+            .and_then(|n| n.parse::<u64>().ok())
+        {
+            if entry.path().is_dir() {
+                segments.push((index, dir_size(&entry.path())?));
+            }
+        }
+    }
+    segments.sort_by_key(|(index, _)| *index);
+    Ok(segments)
+}
+
+fn dir_size(dir: &Path) -> io::Result<u64> {
+    let mut total = 0;
+    for entry in fs::read_dir(dir)? {
+        let metadata = entry?.metadata()?;
+        if metadata.is_file() {
+            total += metadata.len();
+        }
+    }
+    Ok(total)
+}
+
+/// Gzip every uncompressed file in a directory, returning the new total size
+fn compress_dir(dir: &Path) -> io::Result<u64> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().map(|e| e == "gz").unwrap_or(false) {
+            continue;
+        }
+
+        let mut gz_name = path.clone().into_os_string();
+        gz_name.push(".gz");
+
+        let mut input = File::open(&path)?;
+        let mut encoder = GzEncoder::new(File::create(&gz_name)?, Compression::default());
+        io::copy(&mut input, &mut encoder)?;
+        encoder.finish()?;
+        fs::remove_file(&path)?;
+    }
+    dir_size(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn log_events(debugger: &Debugger, count: usize) {
+        for i in 0..count {
+            debugger
+                .log_tool_call("Read", &format!("tool_{}", i), &serde_json::json!({"n": i}))
+                .unwrap();
+        }
+    }
+
+    fn segment_dirs(debugger: &Debugger) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(debugger.dir())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|n| n.starts_with(SEGMENT_PREFIX))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_default_config_writes_flat_files() {
+        let session = tempfile::tempdir().unwrap();
+        let debugger = Debugger::new(session.path()).unwrap();
+        log_events(&debugger, 3);
+
+        assert!(debugger.dir().join("000000_tool_call_Read.json").exists());
+        assert!(debugger.dir().join("000002_tool_call_Read.json").exists());
+        assert!(!debugger.dir().join(EVENTS_JSONL).exists());
+        assert!(segment_dirs(&debugger).is_empty());
+    }
+
+    #[test]
+    fn test_segment_rollover() {
+        let session = tempfile::tempdir().unwrap();
+        let config = DebuggerConfig::new()
+            .with_max_events_per_segment(500)
+            .with_output(DebuggerOutput::Both);
+        let debugger = Debugger::with_config(session.path(), config).unwrap();
+        log_events(&debugger, 3000);
+
+        let segments = segment_dirs(&debugger);
+        assert_eq!(segments.len(), 6);
+        for segment in &segments {
+            let dir = debugger.dir().join(segment);
+            let jsonl = fs::read_to_string(dir.join(EVENTS_JSONL)).unwrap();
+            assert_eq!(jsonl.lines().count(), 500);
+            // 500 event files + events.jsonl
+            assert_eq!(fs::read_dir(&dir).unwrap().count(), 501);
+        }
+
+        // Sequence numbers continue across segments
+        assert!(debugger
+            .dir()
+            .join("segment_000005/002999_tool_call_Read.json")
+            .exists());
+    }
+
+    #[test]
+    fn test_size_cap_deletes_oldest_segments() {
+        let session = tempfile::tempdir().unwrap();
+        let max_bytes = 64 * 1024;
+        let config = DebuggerConfig::new()
+            .with_max_events_per_segment(200)
+            .with_max_total_bytes(max_bytes)
+            .with_output(DebuggerOutput::Jsonl);
+        let debugger = Debugger::with_config(session.path(), config).unwrap();
+        log_events(&debugger, 4000);
+
+        let segments = segment_dirs(&debugger);
+        assert!(!segments.contains(&"segment_000000".to_string()));
+        assert_eq!(segments.last().unwrap(), "segment_000019");
+
+        let total: u64 = segments
+            .iter()
+            .map(|s| dir_size(&debugger.dir().join(s)).unwrap())
+            .sum();
+        assert!(total <= max_bytes, "total {} exceeds cap {}", total, max_bytes);
+    }
+
+    #[test]
+    fn test_closed_segments_are_compressed() {
+        let session = tempfile::tempdir().unwrap();
+        let config = DebuggerConfig::new()
+            .with_max_events_per_segment(100)
+            .with_output(DebuggerOutput::Jsonl)
+            .with_compression(true);
+        let debugger = Debugger::with_config(session.path(), config).unwrap();
+        log_events(&debugger, 250);
+
+        let closed = debugger.dir().join("segment_000000");
+        assert!(!closed.join(EVENTS_JSONL).exists());
+
+        let mut decoder =
+            flate2::read::GzDecoder::new(File::open(closed.join("events.jsonl.gz")).unwrap());
+        let mut contents = String::new();
+        decoder.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents.lines().count(), 100);
+
+        // The open segment stays uncompressed
+        assert!(debugger.current_segment_dir().join(EVENTS_JSONL).exists());
+    }
+
+    #[test]
+    fn test_resume_continues_after_existing_segments() {
+        let session = tempfile::tempdir().unwrap();
+        let config = DebuggerConfig::new().with_max_events_per_segment(10);
+        {
+            let debugger = Debugger::with_config(session.path(), config.clone()).unwrap();
+            log_events(&debugger, 25);
+        }
+
+        let debugger = Debugger::with_config(session.path(), config).unwrap();
+        assert!(debugger.current_segment_dir().ends_with("segment_000003"));
+    }
+
+    #[test]
+    fn test_tool_results_are_redacted() {
+        let session = tempfile::tempdir().unwrap();
+        let config = DebuggerConfig::new().with_output(DebuggerOutput::Both);
+        let debugger = Debugger::with_config(session.path(), config).unwrap();
+        debugger
+            .log_tool_result("Bash", "tool_1", &ToolResult::success("ANTHROPIC_KEY=sk-ant-api03-secret"))
+            .unwrap();
+
+        let file = fs::read_to_string(debugger.dir().join("000000_tool_result_Bash.json")).unwrap();
+        let jsonl = fs::read_to_string(debugger.dir().join(EVENTS_JSONL)).unwrap();
+        for contents in [file, jsonl] {
+            assert!(!contents.contains("sk-ant-api03-secret"));
+            assert!(contents.contains("[REDACTED:anthropic_api_key]"));
+            serde_json::from_str::<Value>(contents.trim()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_report_covers_all_segments() {
+        let session = tempfile::tempdir().unwrap();
+        let config = DebuggerConfig::new()
+            .with_max_events_per_segment(4)
+            .with_output(DebuggerOutput::Jsonl)
+            .with_compression(true)
+            .with_report_on_drop(true);
+        let debugger = Debugger::with_config(session.path(), config).unwrap();
+        log_events(&debugger, 9);
+        debugger
+            .log_tool_result("Read", "tool_9", &ToolResult::error("No such file"))
+            .unwrap();
+
+        let html = fs::read_to_string(debugger.generate_report().unwrap()).unwrap();
+        assert_eq!(html.matches("Tool call: Read").count(), 9);
+        assert!(html.contains("Tool result: Read (error)"));
+        assert!(html.contains("<b>1</b>Tool errors"));
+
+        // Written again on drop, including events logged since
+        let dir = debugger.dir().to_path_buf();
+        log_events(&debugger, 1);
+        drop(debugger);
+        let html = fs::read_to_string(dir.join(REPORT_HTML)).unwrap();
+        assert_eq!(html.matches("Tool call: Read").count(), 10);
+    }
+
+    fn live_client(debugger: &Debugger) -> io::BufReader<TcpStream> {
+        let Some(DebugSink::Tcp(addr)) = debugger.live_addr() else {
+            panic!("no TCP live sink");
+        };
+        let mut client = io::BufReader::new(TcpStream::connect(addr).unwrap());
+        let mut greeting = String::new();
+        io::BufRead::read_line(&mut client, &mut greeting).unwrap();
+        assert!(greeting.starts_with("{\"event_type\":\"connected\""), "{}", greeting);
+        client
+    }
+
+    #[test]
+    fn test_live_sink_streams_events_in_order() {
+        let session = tempfile::tempdir().unwrap();
+        let config = DebuggerConfig::new().with_live_sink(DebugSink::Tcp("127.0.0.1:0".parse().unwrap()));
+        let debugger = Debugger::with_config(session.path(), config).unwrap();
+        let client = live_client(&debugger);
+
+        log_events(&debugger, 3);
+        debugger
+            .log_tool_result("Bash", "tool_3", &ToolResult::success("ANTHROPIC_KEY=sk-ant-api03-secret"))
+            .unwrap();
+
+        let lines: Vec<Value> = io::BufRead::lines(client)
+            .take(4)
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        let sequences: Vec<u64> = lines.iter().map(|e| e["sequence"].as_u64().unwrap()).collect();
+        assert_eq!(sequences, vec![0, 1, 2, 3]);
+        assert_eq!(lines[0]["event_type"], "tool_call");
+        assert_eq!(lines[3]["output"], "ANTHROPIC_KEY=[REDACTED:anthropic_api_key]");
+        assert_eq!(debugger.dropped_live_events(), 0);
+    }
+
+    #[test]
+    fn test_live_sink_drops_events_for_slow_clients() {
+        let session = tempfile::tempdir().unwrap();
+        let config = DebuggerConfig::new()
+            .with_output(DebuggerOutput::Jsonl)
+            .without_redaction()
+            .with_live_sink(DebugSink::Tcp("127.0.0.1:0".parse().unwrap()));
+        let debugger = Debugger::with_config(session.path(), config).unwrap();
+        // Connected but never read past the greeting
+        let _client = live_client(&debugger);
+
+        let input = serde_json::json!({"content": "x".repeat(8 * 1024)});
+        for i in 0..4000 {
+            debugger.log_tool_call("Write", &format!("tool_{}", i), &input).unwrap();
+        }
+        assert!(debugger.dropped_live_events() > 0);
+        // Every event still reaches the file
+        let jsonl = fs::read_to_string(debugger.dir().join(EVENTS_JSONL)).unwrap();
+        assert_eq!(jsonl.lines().count(), 4000);
+    }
+
+    #[test]
+    fn test_sequence_numbers_follow_write_order() {
+        let session = tempfile::tempdir().unwrap();
+        let config = DebuggerConfig::new().with_output(DebuggerOutput::Jsonl);
+        let debugger = Arc::new(Debugger::with_config(session.path(), config).unwrap());
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let debugger = debugger.clone();
+                std::thread::spawn(move || log_events(&debugger, 50))
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let jsonl = fs::read_to_string(debugger.dir().join(EVENTS_JSONL)).unwrap();
+        let sequences: Vec<u64> = jsonl
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap()["sequence"].as_u64().unwrap())
+            .collect();
+        assert_eq!(sequences, (0..200).collect::<Vec<_>>());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_live_sink_replaces_only_stale_sockets() {
+        let session = tempfile::tempdir().unwrap();
+        let path = session.path().join("debug.sock");
+        let unix = || DebuggerConfig::new().with_live_sink(DebugSink::Unix(path.clone()));
+
+        // Not a socket: left alone
+        fs::write(&path, "notes").unwrap();
+        assert!(Debugger::with_config(session.path(), unix()).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "notes");
+        fs::remove_file(&path).unwrap();
+
+        // Left behind by a listener that is gone: replaced
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        let debugger = Debugger::with_config(session.path(), unix()).unwrap();
+
+        // Still listened on: refused, and the first debugger keeps it
+        let e = Debugger::with_config(session.path(), unix()).err().unwrap();
+        assert!(e.to_string().contains("in use"), "{}", e);
+        assert!(std::os::unix::net::UnixStream::connect(&path).is_ok());
+        drop(debugger);
+    }
+
+    #[test]
+    fn test_subscribers_receive_events() {
+        let session = tempfile::tempdir().unwrap();
+        let debugger = Debugger::new(session.path()).unwrap();
+        log_events(&debugger, 1);
+        let mut events = debugger.subscribe();
+        log_events(&debugger, 2);
+
+        let event = events.try_recv().unwrap();
+        assert_eq!((event.event_type, event.sequence), (EventType::ToolCall, 1));
+        assert_eq!(event.data["tool_id"], "tool_0");
+        assert_eq!(events.try_recv().unwrap().sequence, 2);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_report_from_per_event_files() {
+        let session = tempfile::tempdir().unwrap();
+        let debugger = Debugger::new(session.path()).unwrap();
+        log_events(&debugger, 2);
+
+        let html = fs::read_to_string(debugger.generate_report().unwrap()).unwrap();
+        assert_eq!(html.matches("Tool call: Read").count(), 2);
+        assert!(!html.contains("unreadable"));
+    }
 }
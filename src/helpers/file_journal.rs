@@ -0,0 +1,266 @@
+//! File Change Journal
+//!
+//! Records every change the write tools make to a file, so a turn's edits
+//! can be undone: the path, what the file hashed to before and after, the
+//! copy of its previous contents, and which tool call and turn made it.
+//! `StandardAgent` stores the journal in the ResourceMap; WriteTool and
+//! EditTool record into it, and UndoTool (or `AgentHandle::rollback_turn`)
+//! rolls it back.
+//!
+//! The journal lives in the session directory (`changes.jsonl`, one change
+//! per line), so a resumed session can still roll back earlier turns.
+//!
+//! Rolling back turn N undoes every change made in turn N and after, newest
+//! first, leaving the files as they were when turn N started. A file whose
+//! contents no longer match what the agent last wrote was changed by
+//! someone else; it is left alone and reported.
+//!
+//! Usage:
+//! ```ignore
+//! let report = handle.rollback_turn(3).await?;
+//! for (path, reason) in &report.skipped {
+//!     println!("kept {}: {}", path.display(), reason);
+//! }
+//! ```
+
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use super::file_tracker::content_hash;
+
+/// One change a tool made to a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChange {
+    /// The file that changed
+    pub path: PathBuf,
+    /// `content_hash` of the file before the change (None if it was created)
+    pub before_hash: Option<String>,
+    /// `content_hash` of the file after the change
+    pub after_hash: String,
+    /// Copy of the previous contents (None if created, or backups are off)
+    pub backup: Option<PathBuf>,
+    /// The tool call that made the change
+    pub tool_use_id: String,
+    /// The turn it was made in
+    pub turn: usize,
+}
+
+/// What a rollback did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RollbackReport {
+    /// Files put back to their earlier contents
+    pub restored: Vec<PathBuf>,
+    /// Files deleted because the rolled-back turns created them
+    pub removed: Vec<PathBuf>,
+    /// Files left alone, with why
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+impl RollbackReport {
+    /// A summary for a tool result or a log line
+    pub fn describe(&self) -> String {
+        if self.restored.is_empty() && self.removed.is_empty() && self.skipped.is_empty() {
+            return "No file changes to undo".to_string();
+        }
+        let mut lines = Vec::new();
+        for path in &self.restored {
+            lines.push(format!("Restored {}", path.display()));
+        }
+        for path in &self.removed {
+            lines.push(format!("Removed {}", path.display()));
+        }
+        for (path, reason) in &self.skipped {
+            lines.push(format!("Skipped {}: {}", path.display(), reason));
+        }
+        lines.join("\n")
+    }
+}
+
+/// The changes tools made to files in a session
+#[derive(Debug)]
+pub struct FileChangeJournal {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileChangeJournal {
+    /// A journal kept in the file at `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// The file the journal is kept in
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append a change
+    pub fn record(&self, change: &FileChange) -> io::Result<()> {
+        let _guard = self.lock.lock().unwrap();
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(change)?)
+    }
+
+    /// All recorded changes, oldest first
+    pub fn changes(&self) -> Vec<FileChange> {
+        let _guard = self.lock.lock().unwrap();
+        self.read()
+    }
+
+    /// The latest turn that changed a file
+    pub fn last_turn(&self) -> Option<usize> {
+        self.changes().iter().map(|change| change.turn).max()
+    }
+
+    /// Undo the changes made in `turn` and every turn after it
+    ///
+    /// The undone changes leave the journal, including those of skipped
+    /// files.
+    pub fn rollback_turn(&self, turn: usize) -> io::Result<RollbackReport> {
+        let _guard = self.lock.lock().unwrap();
+        let (undone, kept): (Vec<_>, Vec<_>) = self.read().into_iter().partition(|change| change.turn >= turn);
+
+        let mut report = RollbackReport::default();
+        let mut skipped = HashSet::new();
+        for change in undone.iter().rev() {
+            if skipped.contains(&change.path) {
+                continue;
+            }
+            match undo(change) {
+                Ok(Undone::Restored) => {
+                    report.removed.retain(|path| path != &change.path);
+                    if !report.restored.contains(&change.path) {
+                        report.restored.push(change.path.clone());
+                    }
+                }
+                Ok(Undone::Removed) => {
+                    report.restored.retain(|path| path != &change.path);
+                    report.removed.push(change.path.clone());
+                }
+                Err(reason) => {
+                    // Earlier changes to it stay too, they'd need this one undone
+                    tracing::warn!("[FileChangeJournal] Not rolling back {:?}: {}", change.path, reason);
+                    report.skipped.push((change.path.clone(), reason));
+                    skipped.insert(change.path.clone());
+                }
+            }
+        }
+
+        let mut lines = String::new();
+        for change in &kept {
+            lines.push_str(&serde_json::to_string(change)?);
+            lines.push('\n');
+        }
+        if self.path.exists() {
+            fs::write(&self.path, lines)?;
+        }
+        Ok(report)
+    }
+
+    fn read(&self) -> Vec<FileChange> {
+        let Ok(text) = fs::read_to_string(&self.path) else {
+            return Vec::new();
+        };
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(change) => Some(change),
+                Err(e) => {
+                    tracing::warn!("[FileChangeJournal] Skipping bad entry: {}", e);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+enum Undone {
+    Restored,
+    Removed,
+}
+
+/// Put one file back as it was before `change`
+fn undo(change: &FileChange) -> Result<Undone, String> {
+    let current = match fs::read(&change.path) {
+        Ok(current) => Some(content_hash(&current)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("cannot read it: {}", e)),
+    };
+    if current.as_deref() != Some(change.after_hash.as_str()) {
+        return Err("modified since the agent changed it".to_string());
+    }
+
+    match (&change.before_hash, &change.backup) {
+        (None, _) => {
+            fs::remove_file(&change.path).map_err(|e| format!("cannot remove it: {}", e))?;
+            Ok(Undone::Removed)
+        }
+        (Some(_), None) => Err("no backup of its previous contents".to_string()),
+        (Some(before), Some(backup)) => {
+            let previous = fs::read(backup).map_err(|e| format!("cannot read backup {}: {}", backup.display(), e))?;
+            if &content_hash(&previous) != before {
+                return Err(format!("backup {} doesn't match", backup.display()));
+            }
+            fs::write(&change.path, previous).map_err(|e| format!("cannot write it: {}", e))?;
+            Ok(Undone::Restored)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollback_restores_in_reverse_and_skips_changed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = FileChangeJournal::new(dir.path().join("changes.jsonl"));
+        let (kept, created, changed) = (dir.path().join("kept"), dir.path().join("new"), dir.path().join("changed"));
+        let backup = |name: &str, content: &str| {
+            let path = dir.path().join(format!("{}.bak", name));
+            fs::write(&path, content).unwrap();
+            Some(path)
+        };
+        let change = |path: &Path, before: Option<&str>, after: &str, backup, turn| FileChange {
+            path: path.to_path_buf(),
+            before_hash: before.map(|b| content_hash(b.as_bytes())),
+            after_hash: content_hash(after.as_bytes()),
+            backup,
+            tool_use_id: format!("toolu_{}", turn),
+            turn,
+        };
+
+        // Turn 1 edits "kept" twice and creates "new"; turn 0 edited "changed"
+        fs::write(&kept, "v2").unwrap();
+        fs::write(&created, "fresh").unwrap();
+        fs::write(&changed, "edited by someone").unwrap();
+        journal.record(&change(&changed, Some("c0"), "c1", backup("changed", "c0"), 0)).unwrap();
+        journal.record(&change(&kept, Some("v0"), "v1", backup("kept0", "v0"), 1)).unwrap();
+        journal.record(&change(&created, None, "fresh", None, 1)).unwrap();
+        journal.record(&change(&kept, Some("v1"), "v2", backup("kept1", "v1"), 1)).unwrap();
+        assert_eq!(journal.last_turn(), Some(1));
+
+        let report = journal.rollback_turn(1).unwrap();
+        assert_eq!(report.restored, vec![kept.clone()]);
+        assert_eq!(report.removed, vec![created.clone()]);
+        assert_eq!(fs::read_to_string(&kept).unwrap(), "v0");
+        assert!(!created.exists());
+        assert_eq!(journal.last_turn(), Some(0));
+
+        let report = FileChangeJournal::new(journal.path()).rollback_turn(0).unwrap();
+        assert_eq!(report.skipped, vec![(changed.clone(), "modified since the agent changed it".to_string())]);
+        assert_eq!(fs::read_to_string(&changed).unwrap(), "edited by someone");
+        assert!(journal.changes().is_empty());
+    }
+}
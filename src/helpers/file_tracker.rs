@@ -0,0 +1,270 @@
+//! File Tracker
+//!
+//! Remembers which files the agent has read so EditTool and WriteTool can
+//! refuse to modify a file the model has never seen, or one that changed on
+//! disk after it was read. This is stored in the ResourceMap; ReadTool
+//! records every read and the write tools consult it.
+//!
+//! Usage:
+//! ```ignore
+//! // Opt in through the agent config (StandardAgent inserts the tracker)
+//! let config = AgentConfig::new(prompt).with_file_enforcement(FileEnforcement::Strict);
+//!
+//! // A PreToolUse hook can relax the rule for a single path
+//! if let Some(tracker) = ctx.internals.context.get_resource::<FileTracker>() {
+//!     tracker.set_path_enforcement("/tmp/scratch.txt", FileEnforcement::Off);
+//! }
+//! ```
+//!
+//! Files that don't exist yet can always be written.
+//!
+//! Files the framework writes for the agent (tool output saved by the
+//! executor when it is too long for the tool result) are granted with
+//! `grant_read`: Read opens them without a permission prompt and outside
+//! the `PathPolicy` roots.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+/// How strictly read-before-write is enforced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileEnforcement {
+    /// Don't check anything
+    #[default]
+    Off,
+    /// Allow the write but add a warning to the tool result
+    Warn,
+    /// Refuse the write with an error
+    Strict,
+}
+
+/// What the tracker saw when a file was last read or written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileSnapshot {
+    modified: Option<SystemTime>,
+    hash: u64,
+}
+
+impl FileSnapshot {
+    fn capture(path: &Path) -> Option<Self> {
+        let content = fs::read(path).ok()?;
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        Some(Self {
+            modified: fs::metadata(path).and_then(|m| m.modified()).ok(),
+            hash: hasher.finish(),
+        })
+    }
+}
+
+/// A stable hash of file contents, in hex
+///
+/// FNV-1a, so unlike the tracker's own snapshots it stays the same across
+/// runs and Rust versions, and can be shown to the model or saved.
+pub fn content_hash(content: &[u8]) -> String {
+    let hash = content.iter().fold(0xcbf29ce484222325, |hash: u64, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Outcome of checking a write against the tracker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteCheck {
+    /// The write may go ahead
+    Allowed,
+    /// The write may go ahead, but the model should be told this
+    Warning(String),
+    /// The write must not happen
+    Denied(String),
+}
+
+/// Tracks file reads for read-before-write enforcement
+#[derive(Debug, Default)]
+pub struct FileTracker {
+    enforcement: FileEnforcement,
+    reads: RwLock<HashMap<PathBuf, FileSnapshot>>,
+    overrides: RwLock<HashMap<PathBuf, FileEnforcement>>,
+    granted: RwLock<HashSet<PathBuf>>,
+}
+
+impl FileTracker {
+    /// Create a tracker with the given enforcement level
+    pub fn new(enforcement: FileEnforcement) -> Self {
+        Self {
+            enforcement,
+            ..Default::default()
+        }
+    }
+
+    /// The default enforcement level
+    pub fn enforcement(&self) -> FileEnforcement {
+        self.enforcement
+    }
+
+    /// The enforcement level that applies to `path`
+    pub fn enforcement_for(&self, path: impl AsRef<Path>) -> FileEnforcement {
+        self.overrides
+            .read()
+            .unwrap()
+            .get(&normalize(path.as_ref()))
+            .copied()
+            .unwrap_or(self.enforcement)
+    }
+
+    /// Use a different enforcement level for one path
+    pub fn set_path_enforcement(&self, path: impl AsRef<Path>, enforcement: FileEnforcement) {
+        self.overrides
+            .write()
+            .unwrap()
+            .insert(normalize(path.as_ref()), enforcement);
+    }
+
+    /// Go back to the default enforcement level for a path
+    pub fn clear_path_enforcement(&self, path: impl AsRef<Path>) {
+        self.overrides.write().unwrap().remove(&normalize(path.as_ref()));
+    }
+
+    /// Record that the agent has seen the current contents of `path`
+    ///
+    /// Called by ReadTool after a successful read, and by the write tools
+    /// after they change a file (the agent knows what it just wrote).
+    pub fn record_read(&self, path: impl AsRef<Path>) {
+        let path = normalize(path.as_ref());
+        if let Some(snapshot) = FileSnapshot::capture(&path) {
+            self.reads.write().unwrap().insert(path, snapshot);
+        }
+    }
+
+    /// Let the agent Read a file the framework wrote for it
+    ///
+    /// The file counts as read, and ReadTool skips the permission check
+    /// and the path policy for it.
+    pub fn grant_read(&self, path: impl AsRef<Path>) {
+        let path = normalize(path.as_ref());
+        self.record_read(&path);
+        self.granted.write().unwrap().insert(path);
+    }
+
+    /// Whether `path` was granted with `grant_read`
+    pub fn is_granted(&self, path: impl AsRef<Path>) -> bool {
+        self.granted.read().unwrap().contains(&normalize(path.as_ref()))
+    }
+
+    /// Whether `path` has been read this session
+    pub fn has_read(&self, path: impl AsRef<Path>) -> bool {
+        self.reads.read().unwrap().contains_key(&normalize(path.as_ref()))
+    }
+
+    /// Check whether the agent may modify `path`
+    pub fn check_write(&self, path: impl AsRef<Path>) -> WriteCheck {
+        let path = normalize(path.as_ref());
+        let enforcement = self.enforcement_for(&path);
+        if enforcement == FileEnforcement::Off || !path.exists() {
+            return WriteCheck::Allowed;
+        }
+
+        let problem = match self.reads.read().unwrap().get(&path) {
+            None => Some("You must Read the file first before modifying it"),
+            Some(seen) => {
+                let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+                // A changed mtime alone (e.g. `touch`) doesn't count
+                if modified != seen.modified
+                    && FileSnapshot::capture(&path).map(|now| now.hash) != Some(seen.hash)
+                {
+                    Some("File changed on disk since it was read. Read it again before modifying it")
+                } else {
+                    None
+                }
+            }
+        };
+
+        match (problem, enforcement) {
+            (None, _) => WriteCheck::Allowed,
+            (Some(problem), FileEnforcement::Strict) => {
+                WriteCheck::Denied(format!("{}: {}", problem, path.display()))
+            }
+            (Some(problem), _) => WriteCheck::Warning(format!("{}: {}", problem, path.display())),
+        }
+    }
+}
+
+/// Key paths by their canonical form when the file exists
+fn normalize(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_unread_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "a = 1").unwrap();
+
+        let tracker = FileTracker::new(FileEnforcement::Strict);
+        match tracker.check_write(&path) {
+            WriteCheck::Denied(msg) => assert!(msg.contains("You must Read the file first"), "{}", msg),
+            other => panic!("expected denial, got {:?}", other),
+        }
+
+        // New files can always be written
+        assert_eq!(tracker.check_write(dir.path().join("new.txt")), WriteCheck::Allowed);
+
+        let tracker = FileTracker::new(FileEnforcement::Warn);
+        assert!(matches!(tracker.check_write(&path), WriteCheck::Warning(_)));
+
+        let tracker = FileTracker::new(FileEnforcement::Off);
+        assert_eq!(tracker.check_write(&path), WriteCheck::Allowed);
+    }
+
+    #[test]
+    fn test_stale_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "a = 1").unwrap();
+
+        let tracker = FileTracker::new(FileEnforcement::Strict);
+        tracker.record_read(&path);
+        assert_eq!(tracker.check_write(&path), WriteCheck::Allowed);
+
+        // Touching the file without changing it is fine
+        let later = SystemTime::now() + Duration::from_secs(60);
+        fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        assert_eq!(tracker.check_write(&path), WriteCheck::Allowed);
+
+        // Changing it behind the agent's back is not
+        fs::write(&path, "a = 2").unwrap();
+        let later = later + Duration::from_secs(60);
+        fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        match tracker.check_write(&path) {
+            WriteCheck::Denied(msg) => assert!(msg.contains("File changed on disk"), "{}", msg),
+            other => panic!("expected denial, got {:?}", other),
+        }
+
+        tracker.record_read(&path);
+        assert_eq!(tracker.check_write(&path), WriteCheck::Allowed);
+    }
+
+    #[test]
+    fn test_path_override() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scratch.txt");
+        fs::write(&path, "x").unwrap();
+
+        let tracker = FileTracker::new(FileEnforcement::Strict);
+        tracker.set_path_enforcement(&path, FileEnforcement::Off);
+        assert_eq!(tracker.check_write(&path), WriteCheck::Allowed);
+
+        tracker.clear_path_enforcement(&path);
+        assert!(matches!(tracker.check_write(&path), WriteCheck::Denied(_)));
+    }
+}
@@ -0,0 +1,98 @@
+//! Input preprocessing
+//!
+//! An [`InputPreprocessor`] turns the text of a user turn into the content
+//! blocks sent to the model. The default, [`AttachmentTags`], keeps the text
+//! and appends a block for each `<vibe-work-attachment>path</vibe-work-attachment>`
+//! tag in it.
+//!
+//! # Example
+//!
+//! ```ignore
+//! struct Uppercase;
+//!
+//! #[async_trait]
+//! impl InputPreprocessor for Uppercase {
+//!     async fn process(&self, input: &str, _base_dir: &str) -> Vec<ContentBlock> {
+//!         vec![ContentBlock::text(input.to_uppercase())]
+//!     }
+//! }
+//!
+//! let config = AgentConfig::new("...").with_input_preprocessor(Uppercase);
+//! ```
+
+use async_trait::async_trait;
+
+use super::attachments::{process_attachments_with_tag, DEFAULT_ATTACHMENT_TAG};
+use crate::llm::ContentBlock;
+
+/// Turns user input into the content blocks of a user message
+///
+/// Attachments sent with `AgentHandle::send_input_with_attachments` are
+/// appended after the returned blocks.
+#[async_trait]
+pub trait InputPreprocessor: Send + Sync {
+    /// Build the content blocks for `input`
+    ///
+    /// `base_dir` is the directory relative paths resolve against.
+    async fn process(&self, input: &str, base_dir: &str) -> Vec<ContentBlock>;
+}
+
+/// The default preprocessor: the input text followed by the files referenced
+/// by `<tag>path</tag>` attachment tags
+#[derive(Debug, Clone)]
+pub struct AttachmentTags {
+    tag: String,
+}
+
+impl AttachmentTags {
+    /// Scan for `<tag>path</tag>`
+    pub fn new(tag: impl Into<String>) -> Self {
+        Self { tag: tag.into() }
+    }
+
+    /// The tag name scanned for
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+}
+
+impl Default for AttachmentTags {
+    fn default() -> Self {
+        Self::new(DEFAULT_ATTACHMENT_TAG)
+    }
+}
+
+#[async_trait]
+impl InputPreprocessor for AttachmentTags {
+    async fn process(&self, input: &str, base_dir: &str) -> Vec<ContentBlock> {
+        let mut blocks = vec![ContentBlock::text(input)];
+        if input.contains(&format!("<{}>", self.tag)) {
+            blocks.extend(process_attachments_with_tag(input, &self.tag, base_dir));
+        }
+        blocks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_custom_tag() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("notes.txt"), "remember the milk").unwrap();
+        let base_dir = dir.path().to_string_lossy().to_string();
+
+        let input = "See <file>notes.txt</file> and <vibe-work-attachment>missing.txt</vibe-work-attachment>";
+        let blocks = AttachmentTags::new("file").process(input, &base_dir).await;
+
+        assert_eq!(blocks.len(), 2);
+        assert!(matches!(&blocks[0], ContentBlock::Text { text, .. } if text == input));
+        assert!(matches!(&blocks[1], ContentBlock::Text { text, .. } if text.contains("remember the milk")));
+
+        // Text without the tag is passed through as-is
+        let plain = AttachmentTags::default().process("no tags here", &base_dir).await;
+        assert_eq!(plain.len(), 1);
+    }
+}
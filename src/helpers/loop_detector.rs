@@ -0,0 +1,232 @@
+//! Loop Detector
+//!
+//! Tracks the tool calls made during a turn and notices when the model keeps
+//! repeating the same failing call (or alternating between a few of them).
+//!
+//! Calls are compared by tool name and canonicalized input, so `{"a":1,"b":2}`
+//! and `{"b":2,"a":1}` count as the same call. Only failing calls count
+//! towards a loop; repeating a successful call is often legitimate.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let mut detector = LoopDetector::new(LoopDetectionConfig::default());
+//! match detector.record("Grep", &input, result.is_error) {
+//!     LoopStatus::Ok => {}
+//!     LoopStatus::Warn { repeats } => { /* remind the model */ }
+//!     LoopStatus::Abort { repeats } => { /* end the turn */ }
+//! }
+//! ```
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+
+use serde_json::Value;
+
+/// Thresholds for loop detection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopDetectionConfig {
+    /// Number of recent calls remembered
+    pub window: usize,
+    /// Repeats of the same failing call before the model is warned (N)
+    pub warn_after: usize,
+    /// Further repeats after the warning before the turn is ended (M)
+    pub abort_after: usize,
+}
+
+impl Default for LoopDetectionConfig {
+    fn default() -> Self {
+        Self {
+            window: 20,
+            warn_after: 3,
+            abort_after: 2,
+        }
+    }
+}
+
+/// What the detector concluded after a call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopStatus {
+    /// No loop
+    Ok,
+    /// The call has failed `repeats` times; the model should change approach
+    Warn { repeats: usize },
+    /// The call has failed `repeats` times despite the warning; stop the turn
+    Abort { repeats: usize },
+}
+
+impl LoopStatus {
+    /// Keep the more severe of two statuses
+    pub fn max(self, other: LoopStatus) -> LoopStatus {
+        fn rank(status: &LoopStatus) -> (u8, usize) {
+            match *status {
+                LoopStatus::Ok => (0, 0),
+                LoopStatus::Warn { repeats } => (1, repeats),
+                LoopStatus::Abort { repeats } => (2, repeats),
+            }
+        }
+        if rank(&other) > rank(&self) {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+/// A remembered call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CallRecord {
+    tool_hash: u64,
+    is_error: bool,
+}
+
+/// Rolling window of recent tool calls for one turn
+#[derive(Debug, Clone)]
+pub struct LoopDetector {
+    config: LoopDetectionConfig,
+    recent: VecDeque<CallRecord>,
+}
+
+impl LoopDetector {
+    /// Create a detector with the given thresholds
+    pub fn new(config: LoopDetectionConfig) -> Self {
+        Self {
+            config,
+            recent: VecDeque::with_capacity(config.window),
+        }
+    }
+
+    /// Record a tool call and check whether it is looping
+    pub fn record(&mut self, tool_name: &str, input: &Value, is_error: bool) -> LoopStatus {
+        let record = CallRecord {
+            tool_hash: call_hash(tool_name, input),
+            is_error,
+        };
+        if self.recent.len() >= self.config.window.max(1) {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(record);
+
+        if !is_error || self.config.warn_after == 0 {
+            return LoopStatus::Ok;
+        }
+
+        let repeats = self.recent.iter().filter(|r| **r == record).count();
+        if repeats >= self.config.warn_after + self.config.abort_after {
+            LoopStatus::Abort { repeats }
+        } else if repeats >= self.config.warn_after {
+            LoopStatus::Warn { repeats }
+        } else {
+            LoopStatus::Ok
+        }
+    }
+}
+
+/// Hash a tool name and its canonicalized input
+fn call_hash(tool_name: &str, input: &Value) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    tool_name.hash(&mut hasher);
+    canonical_json(input).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Serialize JSON with object keys sorted, so key order doesn't matter
+fn canonical_json(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            let fields: Vec<String> = entries
+                .into_iter()
+                .map(|(k, v)| format!("{}:{}", Value::String(k.clone()), canonical_json(v)))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(canonical_json).collect();
+            format!("[{}]", items.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn detector() -> LoopDetector {
+        LoopDetector::new(LoopDetectionConfig {
+            window: 10,
+            warn_after: 3,
+            abort_after: 2,
+        })
+    }
+
+    #[test]
+    fn test_repeated_failing_call() {
+        let mut detector = detector();
+        let input = json!({"pattern": "foo", "path": "src"});
+
+        assert_eq!(detector.record("Grep", &input, true), LoopStatus::Ok);
+        assert_eq!(detector.record("Grep", &input, true), LoopStatus::Ok);
+        assert_eq!(detector.record("Grep", &input, true), LoopStatus::Warn { repeats: 3 });
+        assert_eq!(detector.record("Grep", &input, true), LoopStatus::Warn { repeats: 4 });
+        assert_eq!(detector.record("Grep", &input, true), LoopStatus::Abort { repeats: 5 });
+    }
+
+    #[test]
+    fn test_alternating_calls_and_key_order() {
+        let mut detector = detector();
+        let a = json!({"file_path": "a.rs", "old_string": "x", "new_string": "y"});
+        let a_reordered = json!({"new_string": "y", "old_string": "x", "file_path": "a.rs"});
+        let b = json!({"file_path": "a.rs", "old_string": "y", "new_string": "x"});
+
+        assert_eq!(detector.record("Edit", &a, true), LoopStatus::Ok);
+        assert_eq!(detector.record("Edit", &b, true), LoopStatus::Ok);
+        assert_eq!(detector.record("Edit", &a_reordered, true), LoopStatus::Ok);
+        assert_eq!(detector.record("Edit", &b, true), LoopStatus::Ok);
+        assert_eq!(detector.record("Edit", &a, true), LoopStatus::Warn { repeats: 3 });
+    }
+
+    #[test]
+    fn test_successes_and_other_calls_dont_count() {
+        let mut detector = detector();
+        let input = json!({"file_path": "a.rs"});
+
+        for _ in 0..5 {
+            assert_eq!(detector.record("Read", &input, false), LoopStatus::Ok);
+        }
+        // Same input, different tool
+        assert_eq!(detector.record("Read", &input, true), LoopStatus::Ok);
+        assert_eq!(detector.record("Write", &input, true), LoopStatus::Ok);
+        assert_eq!(detector.record("Read", &input, true), LoopStatus::Ok);
+    }
+
+    #[test]
+    fn test_window_forgets_old_calls() {
+        let mut detector = LoopDetector::new(LoopDetectionConfig {
+            window: 3,
+            warn_after: 2,
+            abort_after: 1,
+        });
+        let input = json!({"command": "make"});
+
+        assert_eq!(detector.record("Bash", &input, true), LoopStatus::Ok);
+        detector.record("Read", &json!({"file_path": "1"}), false);
+        detector.record("Read", &json!({"file_path": "2"}), false);
+        // The first failure has left the window
+        assert_eq!(detector.record("Bash", &input, true), LoopStatus::Ok);
+        assert_eq!(detector.record("Bash", &input, true), LoopStatus::Warn { repeats: 2 });
+    }
+
+    #[test]
+    fn test_status_max() {
+        let warn = LoopStatus::Warn { repeats: 3 };
+        let abort = LoopStatus::Abort { repeats: 5 };
+        assert_eq!(LoopStatus::Ok.max(warn), warn);
+        assert_eq!(abort.max(warn), abort);
+        assert_eq!(warn.max(LoopStatus::Warn { repeats: 4 }), LoopStatus::Warn { repeats: 4 });
+    }
+}
@@ -0,0 +1,473 @@
+//! Long-term notes the model keeps with the Memory tool
+//!
+//! A [`MemoryStore`] is a small key-value file. Its [`MemoryScope`] decides
+//! who shares it: one session, every session of an agent type (the
+//! default), or every agent. Stores live next to the sessions in
+//! `SessionStorage`, so they survive restarts.
+//!
+//! Writes are capped per key and in total ([`MemoryLimits`]). When a write
+//! takes the store over its total, the least recently written entries are
+//! evicted.
+//!
+//! Every change holds an exclusive lock on a `.lock` file next to the store
+//! while it reads, updates and replaces the file, so sessions writing the
+//! same store at once don't lose each other's notes. The methods do blocking
+//! file I/O; call them from `spawn_blocking` in async code.
+//!
+//! ```ignore
+//! registry.register(MemoryTool::new().with_scope(MemoryScope::AgentType));
+//! config = config.with_injection(MemoryInjection::new(MemoryScope::AgentType));
+//! ```
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::context_injection::{inject_system_reminder, ContextInjection, InjectionFrequency};
+use crate::core::{FrameworkError, FrameworkResult};
+use crate::llm::Message;
+use crate::runtime::AgentInternals;
+use crate::session::SessionStorage;
+
+/// Who shares a memory store
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryScope {
+    /// Only the current session
+    Session,
+    /// Every session of the same agent type
+    #[default]
+    AgentType,
+    /// Every agent using the same session storage
+    Global,
+}
+
+impl MemoryScope {
+    /// Short description used in tool output and the injected section
+    pub fn label(&self) -> &'static str {
+        match self {
+            MemoryScope::Session => "this session",
+            MemoryScope::AgentType => "all sessions of this agent",
+            MemoryScope::Global => "all agents",
+        }
+    }
+}
+
+/// Size caps for a memory store, in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryLimits {
+    /// Largest value a single key may hold
+    pub max_value_bytes: usize,
+    /// Largest size of all keys and values together
+    pub max_total_bytes: usize,
+}
+
+impl Default for MemoryLimits {
+    fn default() -> Self {
+        Self {
+            max_value_bytes: 4 * 1024,
+            max_total_bytes: 64 * 1024,
+        }
+    }
+}
+
+/// One stored note
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryEntry {
+    pub key: String,
+    pub value: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl MemoryEntry {
+    /// Bytes this entry counts against the total
+    pub fn size(&self) -> usize {
+        self.key.len() + self.value.len()
+    }
+}
+
+/// A key-value file of notes
+///
+/// Every operation reads the file and writes it back, so stores shared by
+/// several sessions see each other's changes.
+#[derive(Debug, Clone)]
+pub struct MemoryStore {
+    path: PathBuf,
+    limits: MemoryLimits,
+}
+
+impl MemoryStore {
+    /// Open the store saved at `path` (created on the first write)
+    pub fn open(path: impl Into<PathBuf>, limits: MemoryLimits) -> Self {
+        Self {
+            path: path.into(),
+            limits,
+        }
+    }
+
+    /// Open the store for `scope` as seen from a session
+    pub fn for_scope(
+        storage: &SessionStorage,
+        scope: MemoryScope,
+        session_id: &str,
+        agent_type: &str,
+        limits: MemoryLimits,
+    ) -> Self {
+        let path = match scope {
+            MemoryScope::Session => storage.session_memory_path(session_id),
+            MemoryScope::AgentType => storage.agent_memory_path(agent_type),
+            MemoryScope::Global => storage.global_memory_path(),
+        };
+        Self::open(path, limits)
+    }
+
+    /// The file the store is saved in
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The size caps of the store
+    pub fn limits(&self) -> MemoryLimits {
+        self.limits
+    }
+
+    /// All entries, least recently written first
+    pub fn entries(&self) -> FrameworkResult<Vec<MemoryEntry>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&self.path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// The value stored under `key`
+    pub fn get(&self, key: &str) -> FrameworkResult<Option<String>> {
+        Ok(self.entries()?.into_iter().find(|e| e.key == key).map(|e| e.value))
+    }
+
+    /// Store `value` under `key`, replacing any previous value
+    ///
+    /// Returns the keys evicted to stay under the total cap.
+    pub fn set(&self, key: &str, value: &str) -> FrameworkResult<Vec<String>> {
+        let _lock = self.lock()?;
+        let mut entries = self.entries()?;
+        entries.retain(|e| e.key != key);
+        self.write_entry(entries, key, value.to_string())
+    }
+
+    /// Add `text` on a new line after the value stored under `key`
+    ///
+    /// Creates the key if it doesn't exist. Returns the evicted keys.
+    pub fn append(&self, key: &str, text: &str) -> FrameworkResult<Vec<String>> {
+        let _lock = self.lock()?;
+        let mut entries = self.entries()?;
+        let value = match entries.iter().position(|e| e.key == key) {
+            Some(index) => {
+                let mut value = entries.remove(index).value;
+                if !value.is_empty() {
+                    value.push('\n');
+                }
+                value.push_str(text);
+                value
+            }
+            None => text.to_string(),
+        };
+        self.write_entry(entries, key, value)
+    }
+
+    /// Remove `key`, returning whether it existed
+    pub fn delete(&self, key: &str) -> FrameworkResult<bool> {
+        let _lock = self.lock()?;
+        let mut entries = self.entries()?;
+        let before = entries.len();
+        entries.retain(|e| e.key != key);
+        if entries.len() == before {
+            return Ok(false);
+        }
+        self.save(&entries)?;
+        Ok(true)
+    }
+
+    /// Add an entry as the most recent one, evicting the oldest over the cap
+    fn write_entry(&self, mut entries: Vec<MemoryEntry>, key: &str, value: String) -> FrameworkResult<Vec<String>> {
+        if key.trim().is_empty() {
+            return Err(FrameworkError::other("memory key must not be empty"));
+        }
+        if value.len() > self.limits.max_value_bytes {
+            return Err(FrameworkError::LimitExceeded(format!(
+                "value for '{}' is {} bytes, over the {} byte limit per key",
+                key,
+                value.len(),
+                self.limits.max_value_bytes
+            )));
+        }
+        let entry = MemoryEntry {
+            key: key.to_string(),
+            value,
+            updated_at: Utc::now(),
+        };
+        if entry.size() > self.limits.max_total_bytes {
+            return Err(FrameworkError::LimitExceeded(format!(
+                "'{}' is {} bytes, over the {} byte limit for the whole store",
+                key,
+                entry.size(),
+                self.limits.max_total_bytes
+            )));
+        }
+
+        let mut total: usize = entries.iter().map(MemoryEntry::size).sum::<usize>() + entry.size();
+        let mut evicted = Vec::new();
+        while total > self.limits.max_total_bytes && !entries.is_empty() {
+            let oldest = entries.remove(0);
+            total -= oldest.size();
+            evicted.push(oldest.key);
+        }
+        if !evicted.is_empty() {
+            tracing::info!("[MemoryStore] Evicted to stay under the size cap: {}", evicted.join(", "));
+        }
+
+        entries.push(entry);
+        self.save(&entries)?;
+        Ok(evicted)
+    }
+
+    /// Take the store's write lock, held until the returned file is dropped
+    fn lock(&self) -> FrameworkResult<File> {
+        let parent = self.dir();
+        fs::create_dir_all(parent)?;
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.path.with_extension("lock"))?;
+        file.lock()?;
+        Ok(file)
+    }
+
+    /// Write the entries through a temporary file so readers never see half a store
+    fn save(&self, entries: &[MemoryEntry]) -> FrameworkResult<()> {
+        let mut tmp = tempfile::NamedTempFile::new_in(self.dir())?;
+        tmp.write_all(serde_json::to_string_pretty(entries)?.as_bytes())?;
+        tmp.persist(&self.path).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    /// The directory the store file is in
+    fn dir(&self) -> &Path {
+        match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        }
+    }
+}
+
+/// Lists the stored keys in a "Memory" system reminder once per turn
+///
+/// Values up to `value_preview` bytes are shown inline; longer ones only
+/// show their size, and the model reads them with the Memory tool. Nothing is
+/// injected while the store is empty.
+pub struct MemoryInjection {
+    scope: MemoryScope,
+    value_preview: usize,
+    priority: i32,
+}
+
+impl MemoryInjection {
+    /// List the store for `scope` (use the same scope as the `MemoryTool`)
+    pub fn new(scope: MemoryScope) -> Self {
+        Self {
+            scope,
+            value_preview: 200,
+            priority: 0,
+        }
+    }
+
+    /// Show values up to this many bytes inline (0 = keys only)
+    pub fn with_value_preview(mut self, bytes: usize) -> Self {
+        self.value_preview = bytes;
+        self
+    }
+
+    /// Set the order among injections (higher runs first)
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// The section for these entries, or None if there are none
+    pub fn render(&self, entries: &[MemoryEntry]) -> Option<String> {
+        if entries.is_empty() {
+            return None;
+        }
+        let mut text = format!(
+            "# Memory\nNotes you saved with the Memory tool (shared with {}):\n",
+            self.scope.label()
+        );
+        for entry in entries {
+            if entry.value.len() <= self.value_preview && !entry.value.contains('\n') {
+                text.push_str(&format!("- {}: {}\n", entry.key, entry.value));
+            } else {
+                text.push_str(&format!("- {} ({} bytes)\n", entry.key, entry.value.len()));
+            }
+        }
+        Some(text.trim_end().to_string())
+    }
+}
+
+impl ContextInjection for MemoryInjection {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    fn inject(&self, internals: &AgentInternals, mut messages: Vec<Message>) -> Vec<Message> {
+        let Ok(session) = internals.session.try_read() else {
+            return messages;
+        };
+        let store = MemoryStore::for_scope(
+            session.storage(),
+            self.scope,
+            session.session_id(),
+            session.agent_type(),
+            MemoryLimits::default(),
+        );
+        drop(session);
+
+        match store.entries() {
+            Ok(entries) => {
+                if let Some(text) = self.render(&entries) {
+                    inject_system_reminder(&mut messages, &text);
+                }
+            }
+            Err(e) => tracing::warn!("[MemoryStore] Failed to read {}: {}", store.path().display(), e),
+        }
+        messages
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn frequency(&self) -> InjectionFrequency {
+        InjectionFrequency::OncePerTurn
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(dir: &Path, limits: MemoryLimits) -> MemoryStore {
+        MemoryStore::open(dir.join("memory.json"), limits)
+    }
+
+    #[test]
+    fn test_crud() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = store(dir.path(), MemoryLimits::default());
+        assert!(store.entries().unwrap().is_empty());
+
+        store.set("build", "cargo build --release").unwrap();
+        store.append("build", "then run ./deploy.sh").unwrap();
+        store.append("style", "tabs").unwrap();
+        assert_eq!(store.get("build").unwrap().unwrap(), "cargo build --release\nthen run ./deploy.sh");
+        assert_eq!(store.get("style").unwrap().unwrap(), "tabs");
+
+        store.set("style", "spaces").unwrap();
+        let keys: Vec<String> = store.entries().unwrap().into_iter().map(|e| e.key).collect();
+        assert_eq!(keys, ["build", "style"]);
+        assert_eq!(store.get("style").unwrap().unwrap(), "spaces");
+
+        assert!(store.delete("build").unwrap());
+        assert!(!store.delete("build").unwrap());
+        assert!(store.get("build").unwrap().is_none());
+        assert!(store.set(" ", "x").is_err());
+    }
+
+    #[test]
+    fn test_limits_and_eviction() {
+        let dir = tempfile::tempdir().unwrap();
+        let limits = MemoryLimits {
+            max_value_bytes: 10,
+            max_total_bytes: 20,
+        };
+        let store = store(dir.path(), limits);
+
+        assert!(matches!(store.set("k", "01234567890"), Err(FrameworkError::LimitExceeded(_))));
+
+        // a, b and c are 6 bytes each; d takes the store to 24 bytes
+        assert!(store.set("a", "aaaaa").unwrap().is_empty());
+        assert!(store.set("b", "bbbbb").unwrap().is_empty());
+        assert!(store.set("c", "ccccc").unwrap().is_empty());
+        // Rewriting a makes b the oldest
+        assert!(store.set("a", "AAAAA").unwrap().is_empty());
+        assert_eq!(store.set("d", "ddddd").unwrap(), ["b"]);
+
+        let keys: Vec<String> = store.entries().unwrap().into_iter().map(|e| e.key).collect();
+        assert_eq!(keys, ["c", "a", "d"]);
+
+        // Appending past the per-key cap fails and leaves the value alone
+        assert!(store.append("d", "dddddd").is_err());
+        assert_eq!(store.get("d").unwrap().unwrap(), "ddddd");
+    }
+
+    #[test]
+    fn test_concurrent_writers_keep_every_note() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("memory.json");
+        let writers: Vec<_> = (0..4)
+            .map(|writer| {
+                let store = MemoryStore::open(&path, MemoryLimits::default());
+                std::thread::spawn(move || {
+                    for note in 0..10 {
+                        store.append("log", &format!("{}-{}", writer, note)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let store = MemoryStore::open(&path, MemoryLimits::default());
+        assert_eq!(store.get("log").unwrap().unwrap().lines().count(), 40);
+    }
+
+    #[test]
+    fn test_scopes_are_isolated() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = SessionStorage::with_dir(dir.path());
+        let open = |scope, session, agent| MemoryStore::for_scope(&storage, scope, session, agent, MemoryLimits::default());
+
+        open(MemoryScope::AgentType, "s1", "coder").set("lang", "rust").unwrap();
+        open(MemoryScope::Session, "s1", "coder").set("task", "fix bug").unwrap();
+        open(MemoryScope::Global, "s1", "coder").set("user", "Ada").unwrap();
+
+        // Same agent type, different session
+        assert_eq!(open(MemoryScope::AgentType, "s2", "coder").get("lang").unwrap().unwrap(), "rust");
+        assert!(open(MemoryScope::Session, "s2", "coder").get("task").unwrap().is_none());
+        // Different agent type
+        assert!(open(MemoryScope::AgentType, "s3", "reviewer").get("lang").unwrap().is_none());
+        assert_eq!(open(MemoryScope::Global, "s3", "reviewer").get("user").unwrap().unwrap(), "Ada");
+    }
+
+    #[test]
+    fn test_injection_render() {
+        let injection = MemoryInjection::new(MemoryScope::AgentType).with_value_preview(8);
+        assert!(injection.render(&[]).is_none());
+
+        let entry = |key: &str, value: &str| MemoryEntry {
+            key: key.to_string(),
+            value: value.to_string(),
+            updated_at: Utc::now(),
+        };
+        let text = injection.render(&[entry("lang", "rust"), entry("notes", "a long note")]).unwrap();
+        assert_eq!(
+            text,
+            "# Memory\nNotes you saved with the Memory tool (shared with all sessions of this agent):\n\
+             - lang: rust\n- notes (11 bytes)"
+        );
+    }
+}
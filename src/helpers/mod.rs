@@ -3,23 +3,65 @@
 //! This module provides reusable components that agents can opt-in to:
 //! - `TodoListManager` - Tracks tasks and which turn they were last updated
 //! - `ContextInjection` - Modify messages before each LLM call
-//! - `Debugger` - Log API calls and tool executions for debugging
+//! - `Debugger` - Log API calls and tool executions for debugging (with an HTML report)
 //! - `ConversationNamer` - Generate descriptive names for conversations
+//! - `ConversationSummarizer` - On-demand and rolling summaries of a conversation
 //! - `Attachments` - Process file attachments in user messages
+//! - `InputPreprocessor` - Turn user input into message content (attachment tags by default)
+//! - `Redactor` - Mask secrets before they are written to disk
+//! - `FileTracker` - Make the write tools refuse files the agent hasn't read
+//! - `FileChangeJournal` - Record the write tools' changes so a turn can be undone
+//! - `OutputOverflow` - Save long tool output to a file and send a preview
+//! - `LoopDetector` - Notice when the model keeps repeating a failing tool call
+//! - `PromptTemplate` - System prompts with variables, partials and conditional sections
+//! - `ProjectMemory` - Load project instructions from AGENTS.md / CLAUDE.md files
+//! - `MemoryStore` - Notes the Memory tool keeps across sessions
+//! - `structured_output` - Validate a final answer against a JSON Schema
 
 mod attachments;
 mod context_injection;
 mod conversation_namer;
+mod conversation_summarizer;
+mod debug_report;
 mod debugger;
+mod file_journal;
+mod file_tracker;
+mod input_preprocessor;
+mod loop_detector;
+mod memory_store;
+mod output_overflow;
+mod project_memory;
+mod prompt_template;
+mod redactor;
+mod structured_output;
 mod todo_manager;
 
-pub use attachments::process_attachments;
+pub use attachments::{
+    load_attachments, process_attachments, process_attachments_with_tag, Attachment,
+    DEFAULT_ATTACHMENT_TAG,
+};
+pub(crate) use attachments::media_type_from_path;
 pub use context_injection::{
     append_to_last_message, inject_system_reminder, prepend_to_first_user_message,
-    BoxedInjection, ContextInjection, FnInjection, InjectionChain, SharedInjection,
+    BoxedInjection, ContextInjection, FnInjection, InjectionChain, InjectionFrequency, SharedInjection,
 };
-pub use conversation_namer::{generate_conversation_name, ConversationNamer};
+pub use conversation_namer::{generate_conversation_name, ConversationNamer, NamerConfig};
+pub use conversation_summarizer::{ConversationSummarizer, SummaryStyle};
+pub use debug_report::{render_report, REPORT_HTML};
 pub use debugger::{
-    ApiRequestEvent, ApiResponseEvent, Debugger, EventType, ToolCallEvent, ToolResultEvent,
+    ApiRequestEvent, ApiResponseEvent, DebugEvent, DebugSink, Debugger, DebuggerConfig, DebuggerOutput, EventType,
+    HttpRequestEvent, HttpResponseEvent, ToolCallEvent, ToolResultEvent, LIVE_QUEUE_EVENTS,
+};
+pub use file_journal::{FileChange, FileChangeJournal, RollbackReport};
+pub use file_tracker::{content_hash, FileEnforcement, FileTracker, WriteCheck};
+pub use input_preprocessor::{AttachmentTags, InputPreprocessor};
+pub use loop_detector::{LoopDetectionConfig, LoopDetector, LoopStatus};
+pub use memory_store::{MemoryEntry, MemoryInjection, MemoryLimits, MemoryScope, MemoryStore};
+pub use output_overflow::{OutputOverflow, DEFAULT_OVERFLOW_THRESHOLD};
+pub use project_memory::{load_memory_files, render_memory, MemoryConfig, MemoryFile, ProjectMemory};
+pub use prompt_template::{PromptTemplate, TemplateError, TemplateInjection, TemplateVars};
+pub use redactor::Redactor;
+pub use structured_output::{
+    parse_structured_result, schema_instructions, STRUCTURED_RESULT_METADATA_KEY,
 };
-pub use todo_manager::{TodoItem, TodoListManager, TodoStatus};
+pub use todo_manager::{TodoItem, TodoListManager, TodoStatus, TODOS_METADATA_KEY};
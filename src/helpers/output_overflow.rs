@@ -0,0 +1,193 @@
+//! Tool Output Overflow
+//!
+//! A Grep across a monorepo or a verbose test run can produce hundreds of
+//! KB, more than is worth sending back to the model in one tool result, and
+//! cutting it off loses what the model may need later. Instead, when a
+//! result's text is over the threshold, the executor saves the whole output
+//! in the session's `outputs/` directory and sends the first and last lines
+//! with a note pointing at the file:
+//!
+//! ```text
+//! src/a.rs:12: fn parse(
+//! ...
+//! ... (3913 lines omitted) ...
+//! ...
+//! [full output saved to /app/sessions/abc/outputs/toolu_42.txt (4213 lines) — use Read with offset/limit to inspect]
+//! ```
+//!
+//! The file is granted on the agent's `FileTracker`, so Read can open it
+//! without a permission prompt. It is part of the session directory and is
+//! deleted or archived with the session.
+//!
+//! Usage:
+//! ```ignore
+//! // On by default; tune it or turn it off through the agent config
+//! let config = AgentConfig::new(prompt)
+//!     .with_output_overflow_config(OutputOverflow::new(20_000).with_lines(50, 20));
+//! let config = AgentConfig::new(prompt).with_output_overflow(false);
+//! ```
+
+use std::path::Path;
+
+/// Default size above which tool output is saved to a file (bytes)
+pub const DEFAULT_OVERFLOW_THRESHOLD: usize = 50_000;
+
+/// Longest preview line; longer lines are cut
+const MAX_PREVIEW_LINE_CHARS: usize = 500;
+
+/// When and how tool output is moved out of the tool result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputOverflow {
+    /// Save output longer than this many bytes
+    pub threshold_bytes: usize,
+    /// Lines kept from the start of the output
+    pub head_lines: usize,
+    /// Lines kept from the end of the output
+    pub tail_lines: usize,
+}
+
+impl Default for OutputOverflow {
+    fn default() -> Self {
+        Self {
+            threshold_bytes: DEFAULT_OVERFLOW_THRESHOLD,
+            head_lines: 100,
+            tail_lines: 50,
+        }
+    }
+}
+
+impl OutputOverflow {
+    /// Save output longer than `threshold_bytes`, with the default preview
+    pub fn new(threshold_bytes: usize) -> Self {
+        Self {
+            threshold_bytes,
+            ..Default::default()
+        }
+    }
+
+    /// Keep `head` lines from the start and `tail` from the end
+    pub fn with_lines(mut self, head: usize, tail: usize) -> Self {
+        self.head_lines = head;
+        self.tail_lines = tail;
+        self
+    }
+
+    /// Whether `text` should be saved to a file
+    pub fn exceeds(&self, text: &str) -> bool {
+        text.len() > self.threshold_bytes
+    }
+
+    /// Most bytes of output kept in a preview, half the threshold
+    ///
+    /// Lines are cut at 500 characters, so line counts alone could let a
+    /// preview grow past the threshold it replaces.
+    pub fn preview_bytes(&self) -> usize {
+        self.threshold_bytes / 2
+    }
+
+    /// The tool result text for output saved at `path`
+    pub fn preview(&self, text: &str, path: &Path) -> String {
+        let lines: Vec<String> = text.lines().map(preview_line).collect();
+        let total = lines.len();
+        let budget = self.preview_bytes();
+
+        // The head gets its share of the byte budget, the tail whatever is left
+        let wanted = self.head_lines + self.tail_lines;
+        let head_budget = if wanted >= total {
+            budget
+        } else {
+            budget * self.head_lines / wanted.max(1)
+        };
+        let head_limit = if wanted >= total { total } else { self.head_lines };
+        let mut used = 0;
+        let head = fit(lines.iter().take(head_limit), head_budget, &mut used);
+        let tail_limit = if wanted >= total { 0 } else { self.tail_lines };
+        let tail = fit(lines[head..].iter().rev().take(tail_limit), budget, &mut used);
+
+        let mut preview = String::new();
+        for line in &lines[..head] {
+            preview.push_str(line);
+        }
+        if head + tail < total {
+            preview.push_str(&format!("... ({} lines omitted) ...\n", total - head - tail));
+        }
+        for line in &lines[total - tail..] {
+            preview.push_str(line);
+        }
+
+        preview.push_str(&format!(
+            "\n[full output saved to {} ({} lines) — use Read with offset/limit to inspect]",
+            path.display(),
+            total
+        ));
+        preview
+    }
+}
+
+/// A preview line, cut at `MAX_PREVIEW_LINE_CHARS`, with its newline
+fn preview_line(line: &str) -> String {
+    match line.char_indices().nth(MAX_PREVIEW_LINE_CHARS) {
+        Some((end, _)) => format!("{}...\n", &line[..end]),
+        None => format!("{}\n", line),
+    }
+}
+
+/// How many of `lines` fit before `used` passes `budget`
+fn fit<'a>(lines: impl Iterator<Item = &'a String>, budget: usize, used: &mut usize) -> usize {
+    let mut count = 0;
+    for line in lines {
+        if *used + line.len() > budget {
+            break;
+        }
+        *used += line.len();
+        count += 1;
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_keeps_head_and_tail() {
+        let text: String = (1..=100).map(|i| format!("line {}\n", i)).collect();
+        let overflow = OutputOverflow::new(100).with_lines(3, 2);
+        assert!(overflow.exceeds(&text));
+
+        let preview = overflow.preview(&text, Path::new("/s/outputs/t1.txt"));
+        assert_eq!(
+            preview,
+            "line 1\nline 2\nline 3\n... (95 lines omitted) ...\nline 99\nline 100\n\n\
+             [full output saved to /s/outputs/t1.txt (100 lines) — use Read with offset/limit to inspect]"
+        );
+    }
+
+    #[test]
+    fn test_preview_of_few_long_lines() {
+        let text = "x".repeat(2000);
+        let overflow = OutputOverflow::new(1100);
+        let preview = overflow.preview(&text, Path::new("out.txt"));
+
+        assert!(preview.starts_with(&format!("{}...\n", "x".repeat(MAX_PREVIEW_LINE_CHARS))));
+        assert!(!preview.contains("omitted"));
+        assert!(preview.ends_with("(1 lines) — use Read with offset/limit to inspect]"));
+        assert!(!overflow.exceeds("short"));
+    }
+
+    #[test]
+    fn test_preview_is_capped_by_bytes() {
+        let line = "y".repeat(1000);
+        let text: String = (0..300).map(|_| format!("{}\n", line)).collect();
+        let overflow = OutputOverflow::default();
+        assert!(overflow.exceeds(&text));
+
+        let preview = overflow.preview(&text, Path::new("out.txt"));
+        assert!(preview.len() < overflow.preview_bytes() + 200);
+        // 503 bytes per cut line: 33 head lines, then the tail fills the rest
+        let kept = preview.lines().filter(|l| l.starts_with('y')).count();
+        assert_eq!(kept, overflow.preview_bytes() / 503);
+        assert!(preview.contains(&format!("... ({} lines omitted) ...", 300 - kept)));
+        assert!(preview.ends_with(&format!("{}...\n\n[full output saved to out.txt (300 lines) — use Read with offset/limit to inspect]", "y".repeat(MAX_PREVIEW_LINE_CHARS))));
+    }
+}
@@ -0,0 +1,327 @@
+//! Project memory files
+//!
+//! Loads persistent project instructions from files such as `AGENTS.md` and
+//! `CLAUDE.md`, found in the working directory and every directory above
+//! it. A line consisting of `@path/to/other.md` imports that file in its
+//! place (relative to the importing file; `~/` is the home directory).
+//!
+//! ```ignore
+//! let config = AgentConfig::new("You are a coding assistant")
+//!     .with_project_memory(MemoryConfig::new().with_file_names(["AGENTS.md"]));
+//! ```
+//!
+//! `StandardAgent` sends the files as a cached system block and checks
+//! their modification times at the start of each turn.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+/// File names looked for by default
+const DEFAULT_FILE_NAMES: &[&str] = &["AGENTS.md", "CLAUDE.md"];
+/// How deep `@` imports may nest by default
+const DEFAULT_MAX_IMPORT_DEPTH: usize = 5;
+
+/// Where to look for memory files
+#[derive(Debug, Clone)]
+pub struct MemoryConfig {
+    /// File names to collect in each directory, in order
+    pub file_names: Vec<String>,
+    /// Directory to start from (None = the current directory)
+    pub start_dir: Option<PathBuf>,
+    /// How deep `@` imports may nest; deeper imports are left as text
+    pub max_import_depth: usize,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            file_names: DEFAULT_FILE_NAMES.iter().map(|s| s.to_string()).collect(),
+            start_dir: None,
+            max_import_depth: DEFAULT_MAX_IMPORT_DEPTH,
+        }
+    }
+}
+
+impl MemoryConfig {
+    /// Look for `AGENTS.md` and `CLAUDE.md` from the current directory up
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the file names to look for
+    pub fn with_file_names<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.file_names = names.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Start from this directory instead of the current one
+    pub fn with_start_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.start_dir = Some(dir.into());
+        self
+    }
+
+    /// Set how deep `@` imports may nest
+    pub fn with_max_import_depth(mut self, depth: usize) -> Self {
+        self.max_import_depth = depth;
+        self
+    }
+
+    /// Load the memory files for this config
+    pub fn load(&self) -> Vec<MemoryFile> {
+        load_with(&self.start(), self).0
+    }
+
+    fn start(&self) -> PathBuf {
+        self.start_dir
+            .clone()
+            .unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")))
+    }
+}
+
+/// A memory file, with its imports expanded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryFile {
+    /// Where the file was found
+    pub path: PathBuf,
+    /// Content, with `@` imports replaced by the imported files
+    pub content: String,
+    /// Files imported (directly or indirectly), in order
+    pub imports: Vec<PathBuf>,
+}
+
+/// Collect memory files from `start_dir` and every directory above it
+///
+/// Uses the default file names. Files nearer the root come first, so more
+/// specific instructions come last.
+pub fn load_memory_files(start_dir: impl AsRef<Path>) -> Vec<MemoryFile> {
+    load_with(start_dir.as_ref(), &MemoryConfig::default()).0
+}
+
+/// Render memory files as one block of text (None if there are none)
+pub fn render_memory(files: &[MemoryFile]) -> Option<String> {
+    if files.is_empty() {
+        return None;
+    }
+    let sections: Vec<String> = files
+        .iter()
+        .map(|file| format!("Contents of {} (project instructions):\n\n{}", file.path.display(), file.content.trim_end()))
+        .collect();
+    Some(format!(
+        "Project instructions are shown below. Follow them; they override default behavior.\n\n{}",
+        sections.join("\n\n")
+    ))
+}
+
+/// Files and their modification times (None = missing)
+type Stamps = Vec<(PathBuf, Option<SystemTime>)>;
+
+/// Load files, also returning the stamps of every path that was checked
+fn load_with(start_dir: &Path, config: &MemoryConfig) -> (Vec<MemoryFile>, Stamps) {
+    let mut stamps = Vec::new();
+    let mut files = Vec::new();
+    let mut dirs: Vec<&Path> = start_dir.ancestors().collect();
+    dirs.reverse();
+
+    for dir in dirs {
+        for name in &config.file_names {
+            let path = dir.join(name);
+            stamps.push((path.clone(), modified(&path)));
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let mut imports = Vec::new();
+            let mut stack = vec![canonical(&path)];
+            let content = expand_imports(&content, &path, config.max_import_depth, &mut stack, &mut imports);
+            for import in &imports {
+                stamps.push((import.clone(), modified(import)));
+            }
+            tracing::debug!("[ProjectMemory] Loaded {}", path.display());
+            files.push(MemoryFile { path, content, imports });
+        }
+    }
+    (files, stamps)
+}
+
+/// Replace `@path` lines with the files they name
+///
+/// `stack` holds the files being expanded, to break import cycles.
+fn expand_imports(
+    content: &str,
+    file: &Path,
+    depth_left: usize,
+    stack: &mut Vec<PathBuf>,
+    imports: &mut Vec<PathBuf>,
+) -> String {
+    let base = file.parent().unwrap_or(Path::new("."));
+    let mut out = String::new();
+    let mut in_code_block = false;
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        let target = trimmed
+            .strip_prefix('@')
+            .filter(|t| !in_code_block && !t.is_empty() && !t.contains(char::is_whitespace));
+        let Some(target) = target else {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        };
+
+        let path = resolve_import(target, base);
+        let key = canonical(&path);
+        if depth_left == 0 {
+            tracing::warn!("[ProjectMemory] Import depth limit reached at {}", path.display());
+        } else if stack.contains(&key) {
+            tracing::warn!("[ProjectMemory] Skipping import cycle through {}", path.display());
+        } else {
+            match fs::read_to_string(&path) {
+                Ok(imported) => {
+                    if !imports.contains(&path) {
+                        imports.push(path.clone());
+                    }
+                    stack.push(key);
+                    out.push_str(&expand_imports(&imported, &path, depth_left - 1, stack, imports));
+                    stack.pop();
+                    continue;
+                }
+                Err(e) => tracing::warn!("[ProjectMemory] Cannot import {}: {}", path.display(), e),
+            }
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+fn resolve_import(target: &str, base: &Path) -> PathBuf {
+    if let Some(rest) = target.strip_prefix("~/") {
+        if let Some(home) = std::env::var_os("HOME") {
+            return PathBuf::from(home).join(rest);
+        }
+    }
+    base.join(target)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Memory files kept up to date for an agent
+///
+/// Stored as an agent resource by `StandardAgent`.
+pub struct ProjectMemory {
+    config: MemoryConfig,
+    start_dir: PathBuf,
+    state: RwLock<(Option<String>, Stamps)>,
+}
+
+impl ProjectMemory {
+    /// Load the files for `config`
+    ///
+    /// The start directory is fixed now, so a later change of the
+    /// process's current directory doesn't move it.
+    pub fn new(config: MemoryConfig) -> Self {
+        let start_dir = config.start();
+        let (files, stamps) = load_with(&start_dir, &config);
+        Self {
+            config,
+            start_dir,
+            state: RwLock::new((render_memory(&files), stamps)),
+        }
+    }
+
+    /// The rendered memory text (None if no files were found)
+    pub fn text(&self) -> Option<String> {
+        self.state.read().unwrap_or_else(|e| e.into_inner()).0.clone()
+    }
+
+    /// Reload if any file was created, changed or deleted
+    ///
+    /// Returns true if the files were reloaded.
+    pub fn refresh(&self) -> bool {
+        let changed = {
+            let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+            state.1.iter().any(|(path, stamp)| modified(path) != *stamp)
+        };
+        if changed {
+            let (files, stamps) = load_with(&self.start_dir, &self.config);
+            tracing::info!("[ProjectMemory] Reloaded {} memory file(s)", files.len());
+            *self.state.write().unwrap_or_else(|e| e.into_inner()) = (render_memory(&files), stamps);
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_files_are_collected_root_first_with_imports() {
+        let root = tempfile::tempdir().unwrap();
+        let project = root.path().join("project");
+        fs::create_dir_all(project.join("docs")).unwrap();
+        fs::write(root.path().join("AGENTS.md"), "Outer rules\n").unwrap();
+        fs::write(project.join("CLAUDE.md"), "Inner rules\n@docs/style.md\n```\n@not-an-import\n```\n").unwrap();
+        fs::write(project.join("docs/style.md"), "Use tabs\n@nested.md\n").unwrap();
+        fs::write(project.join("docs/nested.md"), "Nested rule\n").unwrap();
+
+        let files = load_memory_files(&project);
+        let outer = files.iter().position(|f| f.path == root.path().join("AGENTS.md")).unwrap();
+        let inner = files.iter().position(|f| f.path == project.join("CLAUDE.md")).unwrap();
+        assert!(outer < inner);
+
+        let inner = &files[inner];
+        assert_eq!(inner.content, "Inner rules\nUse tabs\nNested rule\n```\n@not-an-import\n```\n");
+        assert_eq!(inner.imports, vec![project.join("docs/style.md"), project.join("docs/nested.md")]);
+
+        // Too deep: the second-level import is left as written
+        let shallow = MemoryConfig::new()
+            .with_file_names(["CLAUDE.md"])
+            .with_start_dir(&project)
+            .with_max_import_depth(1)
+            .load();
+        let shallow = shallow.last().unwrap();
+        assert!(shallow.content.contains("Use tabs\n@nested.md\n"));
+    }
+
+    #[test]
+    fn test_import_cycles_are_broken() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("AGENTS.md"), "A\n@b.md\n").unwrap();
+        fs::write(dir.path().join("b.md"), "B\n@AGENTS.md\n@b.md\n").unwrap();
+
+        let config = MemoryConfig::new().with_file_names(["AGENTS.md"]).with_start_dir(dir.path());
+        let files = config.load();
+        let file = files.last().unwrap();
+        assert_eq!(file.content, "A\nB\n@AGENTS.md\n@b.md\n");
+    }
+
+    #[test]
+    fn test_refresh_picks_up_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = MemoryConfig::new().with_file_names(["AGENTS.md"]).with_start_dir(dir.path());
+        let memory = ProjectMemory::new(config);
+        let before = memory.text();
+        assert!(!memory.refresh());
+
+        fs::write(dir.path().join("AGENTS.md"), "Run cargo fmt\n").unwrap();
+        assert!(memory.refresh());
+        let text = memory.text().unwrap();
+        assert!(text.contains("Run cargo fmt"));
+        assert_ne!(before, Some(text));
+    }
+}
@@ -0,0 +1,511 @@
+//! Prompt templates
+//!
+//! Lets agents share pieces of their system prompts instead of copying them.
+//! A template is plain text with `{{tags}}`:
+//!
+//! - `{{variable}}` - replaced with the variable's value
+//! - `{{> partial_name}}` - replaced with another template, looked up in the
+//!   template directory (`partial_name`, then `partial_name.md`) or among the
+//!   partials registered with [`PromptTemplate::with_partial`]
+//! - `{{#if variable}}...{{else}}...{{/if}}` - kept when the variable is set
+//!   to something other than `""`, `"false"` or `"0"` (`{{else}}` is optional)
+//!
+//! Block tags on a line of their own don't leave a blank line behind.
+//! In strict mode an unset variable is an error; otherwise it renders as
+//! nothing. Partials see the same variables as the template including them.
+//!
+//! # Example
+//!
+//! ```ignore
+//! // prompts/coder.md:
+//! //   You are {{name}}.
+//! //   {{> tone}}
+//! //   {{#if tools_enabled}}
+//! //   {{> tool_guidance}}
+//! //   {{/if}}
+//! let template = PromptTemplate::from_file("prompts/coder.md")?.with_strict(true);
+//! let vars = TemplateVars::from([
+//!     ("name".to_string(), "Coder".to_string()),
+//!     ("tools_enabled".to_string(), "true".to_string()),
+//! ]);
+//! let config = AgentConfig::new("").with_system_prompt_template(&template, &vars)?;
+//! ```
+//!
+//! [`TemplateInjection`] renders a template before each turn with runtime
+//! variables (`date`, `cwd`, `turn`, `todos`) and sends it as a system reminder.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use super::context_injection::{inject_system_reminder, ContextInjection, InjectionFrequency};
+use super::todo_manager::TodoListManager;
+use crate::llm::Message;
+use crate::runtime::AgentInternals;
+
+/// Variable values for rendering a template
+pub type TemplateVars = HashMap<String, String>;
+
+/// Errors from loading or rendering a template
+#[derive(Error, Debug)]
+pub enum TemplateError {
+    /// A template or partial file could not be read
+    #[error("Failed to read template {path}: {source}")]
+    Io {
+        /// The file that could not be read
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+
+    /// Malformed template text
+    #[error("Template syntax error: {0}")]
+    Syntax(String),
+
+    /// A variable with no value, in strict mode
+    #[error("Unresolved template variable: {0}")]
+    MissingVariable(String),
+
+    /// A partial that isn't registered or in the template directory
+    #[error("Partial not found: {0}")]
+    MissingPartial(String),
+
+    /// A partial that includes itself, directly or through others
+    #[error("Partial include cycle: {0}")]
+    IncludeCycle(String),
+}
+
+/// A prompt template with variables, partials and conditional sections
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    source: String,
+    partials_dir: Option<PathBuf>,
+    partials: HashMap<String, String>,
+    strict: bool,
+}
+
+impl PromptTemplate {
+    /// Create a template from its text
+    pub fn new(source: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            partials_dir: None,
+            partials: HashMap::new(),
+            strict: false,
+        }
+    }
+
+    /// Load a template file; partials are looked up in the file's directory
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, TemplateError> {
+        let path = path.as_ref();
+        let source = read_file(path)?;
+        let mut template = Self::new(source);
+        template.partials_dir = path.parent().map(Path::to_path_buf);
+        Ok(template)
+    }
+
+    /// Look up partials in `dir` (replaces the template file's directory)
+    pub fn with_partials_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.partials_dir = Some(dir.into());
+        self
+    }
+
+    /// Register a partial by name (takes precedence over the directory)
+    pub fn with_partial(mut self, name: impl Into<String>, source: impl Into<String>) -> Self {
+        self.partials.insert(name.into(), source.into());
+        self
+    }
+
+    /// Make unset variables an error instead of rendering them as nothing
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// The template text
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Render the template with `vars`
+    ///
+    /// Partials are read when they are included, so edits to partial files
+    /// show up on the next render.
+    pub fn render(&self, vars: &TemplateVars) -> Result<String, TemplateError> {
+        let nodes = parse(&self.source)?;
+        let mut output = String::new();
+        let mut stack = Vec::new();
+        self.render_nodes(&nodes, vars, &mut stack, &mut output)?;
+        Ok(output)
+    }
+
+    fn render_nodes(
+        &self,
+        nodes: &[Node],
+        vars: &TemplateVars,
+        stack: &mut Vec<String>,
+        output: &mut String,
+    ) -> Result<(), TemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+                Node::Var(name) => match vars.get(name) {
+                    Some(value) => output.push_str(value),
+                    None if self.strict => return Err(TemplateError::MissingVariable(name.clone())),
+                    None => tracing::debug!("[PromptTemplate] Unset variable: {}", name),
+                },
+                Node::If { name, then, otherwise } => {
+                    let branch = if is_truthy(vars.get(name)) { then } else { otherwise };
+                    self.render_nodes(branch, vars, stack, output)?;
+                }
+                Node::Partial(name) => {
+                    if stack.contains(name) {
+                        let mut chain = stack.clone();
+                        chain.push(name.clone());
+                        return Err(TemplateError::IncludeCycle(chain.join(" -> ")));
+                    }
+                    let nodes = parse(&self.partial_source(name)?)?;
+                    stack.push(name.clone());
+                    self.render_nodes(&nodes, vars, stack, output)?;
+                    stack.pop();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Find a partial's text: registered partials first, then the directory
+    fn partial_source(&self, name: &str) -> Result<String, TemplateError> {
+        if let Some(source) = self.partials.get(name) {
+            return Ok(source.clone());
+        }
+        let dir = self
+            .partials_dir
+            .as_ref()
+            .ok_or_else(|| TemplateError::MissingPartial(name.to_string()))?;
+        for candidate in [dir.join(name), dir.join(format!("{}.md", name))] {
+            if candidate.is_file() {
+                return read_file(&candidate);
+            }
+        }
+        Err(TemplateError::MissingPartial(name.to_string()))
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, TemplateError> {
+    std::fs::read_to_string(path).map_err(|source| TemplateError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Whether a conditional's variable counts as set
+fn is_truthy(value: Option<&String>) -> bool {
+    value.is_some_and(|v| !matches!(v.trim(), "" | "false" | "0"))
+}
+
+/// A parsed piece of a template
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Var(String),
+    Partial(String),
+    If {
+        name: String,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+/// An `{{#if}}` being parsed
+struct OpenIf {
+    name: String,
+    then: Vec<Node>,
+    otherwise: Vec<Node>,
+    in_else: bool,
+}
+
+impl OpenIf {
+    fn nodes(&mut self) -> &mut Vec<Node> {
+        if self.in_else {
+            &mut self.otherwise
+        } else {
+            &mut self.then
+        }
+    }
+}
+
+fn parse(source: &str) -> Result<Vec<Node>, TemplateError> {
+    let mut root = Vec::new();
+    let mut open: Vec<OpenIf> = Vec::new();
+    let mut pos = 0;
+
+    fn push(node: Node, root: &mut Vec<Node>, open: &mut [OpenIf]) {
+        match open.last_mut() {
+            Some(block) => block.nodes().push(node),
+            None => root.push(node),
+        }
+    }
+
+    while let Some(found) = source[pos..].find("{{") {
+        let start = pos + found;
+        let end = source[start..]
+            .find("}}")
+            .map(|i| start + i)
+            .ok_or_else(|| TemplateError::Syntax(format!("unclosed tag at `{}`", preview(&source[start..]))))?;
+        let tag = source[start + 2..end].trim();
+        let mut text_end = start;
+        let mut next = end + 2;
+
+        // A block tag alone on its line takes the line with it
+        if tag.starts_with('#') || tag.starts_with('/') || tag == "else" {
+            let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = source[next..].find('\n').map_or(source.len(), |i| next + i);
+            if source[line_start..start].trim().is_empty() && source[next..line_end].trim().is_empty() {
+                text_end = line_start.max(pos);
+                next = (line_end + 1).min(source.len());
+            }
+        }
+
+        if text_end > pos {
+            push(Node::Text(source[pos..text_end].to_string()), &mut root, &mut open);
+        }
+
+        if let Some(name) = tag.strip_prefix('>') {
+            push(Node::Partial(tag_name(name, tag)?), &mut root, &mut open);
+        } else if let Some(name) = tag.strip_prefix("#if") {
+            open.push(OpenIf {
+                name: tag_name(name, tag)?,
+                then: Vec::new(),
+                otherwise: Vec::new(),
+                in_else: false,
+            });
+        } else if tag == "else" {
+            match open.last_mut() {
+                Some(block) if !block.in_else => block.in_else = true,
+                _ => return Err(TemplateError::Syntax("`{{else}}` outside `{{#if}}`".to_string())),
+            }
+        } else if tag == "/if" {
+            let block = open
+                .pop()
+                .ok_or_else(|| TemplateError::Syntax("`{{/if}}` without `{{#if}}`".to_string()))?;
+            let node = Node::If {
+                name: block.name,
+                then: block.then,
+                otherwise: block.otherwise,
+            };
+            push(node, &mut root, &mut open);
+        } else if tag.starts_with('#') || tag.starts_with('/') {
+            return Err(TemplateError::Syntax(format!("unknown block tag `{{{{{}}}}}`", tag)));
+        } else {
+            push(Node::Var(tag_name(tag, tag)?), &mut root, &mut open);
+        }
+        pos = next;
+    }
+
+    if let Some(block) = open.last() {
+        return Err(TemplateError::Syntax(format!("`{{{{#if {}}}}}` is never closed", block.name)));
+    }
+    if pos < source.len() {
+        push(Node::Text(source[pos..].to_string()), &mut root, &mut open);
+    }
+    Ok(root)
+}
+
+/// Validate the name in a tag
+fn tag_name(name: &str, tag: &str) -> Result<String, TemplateError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().any(char::is_whitespace) {
+        return Err(TemplateError::Syntax(format!("invalid tag `{{{{{}}}}}`", tag)));
+    }
+    Ok(name.to_string())
+}
+
+/// The start of some text, for error messages
+fn preview(text: &str) -> String {
+    text.chars().take(30).collect()
+}
+
+/// Renders a template before each turn and sends it as a system reminder
+///
+/// Besides the variables given with [`with_var`](Self::with_var), the
+/// template can use:
+///
+/// - `date` - today's local date (`YYYY-MM-DD`)
+/// - `cwd` - the current working directory
+/// - `turn` - the current turn number
+/// - `todos` - the formatted todo list (unset when there is no
+///   `TodoListManager` or it is empty)
+///
+/// A render error is logged and the messages are left as they are.
+pub struct TemplateInjection {
+    name: String,
+    template: PromptTemplate,
+    vars: TemplateVars,
+    priority: i32,
+    frequency: InjectionFrequency,
+}
+
+impl TemplateInjection {
+    /// Create an injection rendering `template` once per turn
+    pub fn new(name: impl Into<String>, template: PromptTemplate) -> Self {
+        Self {
+            name: name.into(),
+            template,
+            vars: TemplateVars::new(),
+            priority: 0,
+            frequency: InjectionFrequency::OncePerTurn,
+        }
+    }
+
+    /// Set a variable (overrides a runtime variable of the same name)
+    pub fn with_var(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.vars.insert(name.into(), value.into());
+        self
+    }
+
+    /// Set the priority (higher runs first, default 0)
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Set how often the injection runs (default: once per turn)
+    pub fn with_frequency(mut self, frequency: InjectionFrequency) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    /// The variables the template is rendered with
+    pub fn vars(&self, internals: &AgentInternals) -> TemplateVars {
+        let mut vars = runtime_vars(internals);
+        vars.extend(self.vars.clone());
+        vars
+    }
+}
+
+/// The runtime variables available to a `TemplateInjection`
+fn runtime_vars(internals: &AgentInternals) -> TemplateVars {
+    let mut vars = TemplateVars::new();
+    vars.insert("date".to_string(), chrono::Local::now().format("%Y-%m-%d").to_string());
+    if let Ok(cwd) = std::env::current_dir() {
+        vars.insert("cwd".to_string(), cwd.to_string_lossy().to_string());
+    }
+    vars.insert("turn".to_string(), internals.turn().to_string());
+    if let Some(todos) = internals.context.get_resource::<TodoListManager>() {
+        if !todos.is_empty() {
+            vars.insert("todos".to_string(), todos.format());
+        }
+    }
+    vars
+}
+
+impl ContextInjection for TemplateInjection {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn inject(&self, internals: &AgentInternals, mut messages: Vec<Message>) -> Vec<Message> {
+        match self.template.render(&self.vars(internals)) {
+            Ok(text) if !text.trim().is_empty() => inject_system_reminder(&mut messages, text.trim()),
+            Ok(_) => {}
+            Err(e) => tracing::warn!("[PromptTemplate] Failed to render {}: {}", self.name, e),
+        }
+        messages
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn frequency(&self) -> InjectionFrequency {
+        self.frequency
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn vars(pairs: &[(&str, &str)]) -> TemplateVars {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_variables_and_conditionals() {
+        let template = PromptTemplate::new("Hi {{ name }}!{{#if admin}} You are an admin.{{else}} Read only.{{/if}}");
+        assert_eq!(
+            template.render(&vars(&[("name", "Ada"), ("admin", "true")])).unwrap(),
+            "Hi Ada! You are an admin."
+        );
+        assert_eq!(template.render(&vars(&[("name", "Bob"), ("admin", "false")])).unwrap(), "Hi Bob! Read only.");
+    }
+
+    #[test]
+    fn test_nesting_and_standalone_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("main.md"), "Intro\n{{#if tools}}\n{{> tools}}\n{{/if}}\nEnd\n").unwrap();
+        fs::write(dir.path().join("tools.md"), "Tools for {{name}}:\n{{#if bash}}\n- bash\n{{/if}}\n{{> footer}}").unwrap();
+        let template = PromptTemplate::from_file(dir.path().join("main.md"))
+            .unwrap()
+            .with_partial("footer", "({{name}})");
+
+        let all = vars(&[("name", "coder"), ("tools", "1"), ("bash", "yes")]);
+        assert_eq!(template.render(&all).unwrap(), "Intro\nTools for coder:\n- bash\n(coder)\nEnd\n");
+        assert_eq!(template.render(&vars(&[("name", "coder")])).unwrap(), "Intro\nEnd\n");
+    }
+
+    #[test]
+    fn test_missing_variables_strict_and_lenient() {
+        let template = PromptTemplate::new("Today is {{date}}.");
+        assert_eq!(template.render(&TemplateVars::new()).unwrap(), "Today is .");
+
+        let strict = template.with_strict(true);
+        assert!(matches!(strict.render(&TemplateVars::new()), Err(TemplateError::MissingVariable(name)) if name == "date"));
+        // A conditional on an unset variable is just false
+        let conditional = PromptTemplate::new("{{#if beta}}beta{{/if}}").with_strict(true);
+        assert_eq!(conditional.render(&TemplateVars::new()).unwrap(), "");
+    }
+
+    #[test]
+    fn test_include_cycles_and_errors() {
+        let template = PromptTemplate::new("{{> a}}")
+            .with_partial("a", "A {{> b}}")
+            .with_partial("b", "B {{> a}}");
+        match template.render(&TemplateVars::new()) {
+            Err(TemplateError::IncludeCycle(chain)) => assert_eq!(chain, "a -> b -> a"),
+            other => panic!("expected a cycle, got {:?}", other),
+        }
+
+        // Including the same partial twice in a row is fine
+        let twice = PromptTemplate::new("{{> x}}{{> x}}").with_partial("x", "x");
+        assert_eq!(twice.render(&TemplateVars::new()).unwrap(), "xx");
+
+        let missing = PromptTemplate::new("{{> nope}}").render(&TemplateVars::new());
+        assert!(matches!(missing, Err(TemplateError::MissingPartial(_))));
+        for bad in ["{{#if a}}open", "{{/if}}", "{{name", "{{#each x}}{{/each}}"] {
+            assert!(matches!(PromptTemplate::new(bad).render(&TemplateVars::new()), Err(TemplateError::Syntax(_))), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_injection_renders_runtime_vars() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut internals = AgentInternals::for_test(dir.path());
+        internals.context.current_turn = 3;
+        let todos = TodoListManager::new();
+        todos.set_todos(vec![crate::helpers::TodoItem::new("Write tests", "Writing tests")], 1);
+        internals.context.insert_resource(todos);
+
+        let template = PromptTemplate::new("Turn {{turn}} on {{date}} for {{who}}\n{{#if todos}}{{todos}}{{/if}}");
+        let injection = TemplateInjection::new("status", template).with_var("who", "Ada");
+        let messages = injection.inject(&internals, vec![Message::user("hi")]);
+        let text = serde_json::to_string(&messages[0]).unwrap();
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        assert!(text.contains(&format!("Turn 3 on {} for Ada", today)), "{}", text);
+        assert!(text.contains("Write tests"), "{}", text);
+        assert_eq!(injection.frequency(), InjectionFrequency::OncePerTurn);
+    }
+}
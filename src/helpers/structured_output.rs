@@ -0,0 +1,137 @@
+//! Structured Output
+//!
+//! Helpers for agents whose final answer must be JSON matching a schema
+//! (see `AgentConfig::with_output_schema`):
+//! - `schema_instructions` - system prompt section describing the schema
+//! - `parse_structured_result` - extract JSON from a reply and validate it
+
+use serde_json::Value;
+
+/// Session custom metadata key the validated result is stored under
+pub const STRUCTURED_RESULT_METADATA_KEY: &str = "structured_result";
+
+/// Maximum number of schema errors reported back to the model
+const MAX_REPORTED_ERRORS: usize = 5;
+
+/// System prompt section telling the model how to format its final answer
+pub fn schema_instructions(schema: &Value) -> String {
+    let schema = serde_json::to_string_pretty(schema).unwrap_or_else(|_| schema.to_string());
+    format!(
+        "# Output format\n\n\
+         When you have finished, your final reply must be only a JSON value matching \
+         this JSON Schema, with no other text before or after it:\n\n```json\n{}\n```",
+        schema
+    )
+}
+
+/// Extract the JSON value from a reply and validate it against `schema`
+///
+/// Accepts bare JSON, JSON in a code fence, or JSON surrounded by prose.
+/// The error describes what is wrong, in a form that can be sent back to
+/// the model.
+pub fn parse_structured_result(text: &str, schema: &Value) -> Result<Value, String> {
+    let validator =
+        jsonschema::validator_for(schema).map_err(|e| format!("Invalid output schema: {}", e))?;
+
+    let value = extract_json(text).ok_or_else(|| {
+        if text.trim().is_empty() {
+            "The reply is empty".to_string()
+        } else {
+            "The reply does not contain a valid JSON value".to_string()
+        }
+    })?;
+
+    let errors: Vec<String> = validator
+        .iter_errors(&value)
+        .take(MAX_REPORTED_ERRORS)
+        .map(|e| {
+            let path = e.instance_path().to_string();
+            if path.is_empty() {
+                e.to_string()
+            } else {
+                format!("{} (at {})", e, path)
+            }
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(value)
+    } else {
+        Err(format!(
+            "The JSON does not match the schema: {}",
+            errors.join("; ")
+        ))
+    }
+}
+
+/// Find a JSON value in a reply
+fn extract_json(text: &str) -> Option<Value> {
+    let text = text.trim();
+    if let Ok(value) = serde_json::from_str(text) {
+        return Some(value);
+    }
+
+    // ```json ... ``` (or a bare ``` fence)
+    if let Some(start) = text.find("```") {
+        let after = &text[start + 3..];
+        let body_start = after.find('\n').map(|i| i + 1).unwrap_or(0);
+        if let Some(end) = after[body_start..].find("```") {
+            if let Ok(value) = serde_json::from_str(after[body_start..body_start + end].trim()) {
+                return Some(value);
+            }
+        }
+    }
+
+    // Outermost object or array surrounded by prose
+    for (open, close) in [('{', '}'), ('[', ']')] {
+        if let (Some(start), Some(end)) = (text.find(open), text.rfind(close)) {
+            if start < end {
+                if let Ok(value) = serde_json::from_str(&text[start..=end]) {
+                    return Some(value);
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string"},
+                "count": {"type": "integer"}
+            },
+            "required": ["name", "count"]
+        })
+    }
+
+    #[test]
+    fn test_extracts_fenced_and_embedded_json() {
+        let expected = json!({"name": "a", "count": 1});
+        for text in [
+            r#"{"name": "a", "count": 1}"#,
+            "```json\n{\"name\": \"a\", \"count\": 1}\n```",
+            "Here you go:\n{\"name\": \"a\", \"count\": 1}\nDone.",
+        ] {
+            assert_eq!(parse_structured_result(text, &schema()), Ok(expected.clone()));
+        }
+    }
+
+    #[test]
+    fn test_reports_schema_errors() {
+        let err = parse_structured_result(r#"{"name": "a", "count": "one"}"#, &schema()).unwrap_err();
+        assert!(err.contains("does not match the schema"), "{}", err);
+        assert!(err.contains("/count"), "{}", err);
+
+        let err = parse_structured_result("no json here", &schema()).unwrap_err();
+        assert!(err.contains("does not contain a valid JSON value"));
+
+        let err = parse_structured_result("{}", &json!({"type": 5})).unwrap_err();
+        assert!(err.starts_with("Invalid output schema"));
+    }
+}
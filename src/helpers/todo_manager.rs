@@ -10,10 +10,21 @@
 //!
 //! // TodoWriteTool will automatically find and update it
 //! ```
+//!
+//! The list is saved into the session's custom metadata (under
+//! `TODOS_METADATA_KEY`) whenever TodoWriteTool changes it. When a resumed
+//! session is spawned with a TodoListManager resource, `StandardAgent` calls
+//! `load_from_session` so the plan survives the restart.
 
 use serde::{Deserialize, Serialize};
 use std::sync::RwLock;
 
+use crate::core::FrameworkResult;
+use crate::session::AgentSession;
+
+/// Session custom-metadata key the todo list is stored under
+pub const TODOS_METADATA_KEY: &str = "todos";
+
 /// Status of a todo item
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
@@ -43,6 +54,9 @@ pub struct TodoItem {
     /// The present continuous form shown during execution (e.g., "Running tests")
     #[serde(rename = "activeForm")]
     pub active_form: String,
+    /// The turn in which this item was added or last changed
+    #[serde(rename = "updatedTurn", default)]
+    pub updated_turn: usize,
 }
 
 impl TodoItem {
@@ -52,6 +66,7 @@ impl TodoItem {
             content: content.into(),
             status: TodoStatus::Pending,
             active_form: active_form.into(),
+            updated_turn: 0,
         }
     }
 
@@ -65,11 +80,13 @@ impl TodoItem {
             content: content.into(),
             status,
             active_form: active_form.into(),
+            updated_turn: 0,
         }
     }
 }
 
 /// Internal state protected by RwLock
+#[derive(Serialize, Deserialize)]
 struct TodoListState {
     /// The list of todo items
     items: Vec<TodoItem>,
@@ -102,12 +119,88 @@ impl TodoListManager {
     }
 
     /// Set the todo list and update the turn number
-    pub fn set_todos(&self, items: Vec<TodoItem>, turn: usize) {
+    ///
+    /// Items whose content, status and active form are unchanged keep their
+    /// `updated_turn`; new or changed items are stamped with `turn`.
+    pub fn set_todos(&self, mut items: Vec<TodoItem>, turn: usize) {
         let mut state = self.state.write().unwrap();
+        for item in &mut items {
+            item.updated_turn = state
+                .items
+                .iter()
+                .find(|old| {
+                    old.content == item.content
+                        && old.status == item.status
+                        && old.active_form == item.active_form
+                })
+                .map(|old| old.updated_turn)
+                .unwrap_or(turn);
+        }
         state.items = items;
         state.last_updated_turn = turn;
     }
 
+    /// Replace the list with one as it was at `turn`
+    ///
+    /// Used when history is rewound past later updates; every item is
+    /// stamped with `turn`.
+    pub fn restore(&self, mut items: Vec<TodoItem>, turn: usize) {
+        for item in &mut items {
+            item.updated_turn = turn;
+        }
+        *self.state.write().unwrap() = TodoListState {
+            items,
+            last_updated_turn: turn,
+        };
+    }
+
+    /// Unfinished items that haven't changed for more than `max_age` turns
+    ///
+    /// Useful for a "stale todo" reminder injection, which can name the items
+    /// and the turn they last changed in.
+    pub fn stale_items(&self, current_turn: usize, max_age: usize) -> Vec<TodoItem> {
+        self.state
+            .read()
+            .unwrap()
+            .items
+            .iter()
+            .filter(|t| t.status != TodoStatus::Completed)
+            .filter(|t| current_turn.saturating_sub(t.updated_turn) > max_age)
+            .cloned()
+            .collect()
+    }
+
+    /// Save the todo list into the session's custom metadata
+    pub fn save_to_session(&self, session: &mut AgentSession) -> FrameworkResult<()> {
+        let value = serde_json::to_value(&*self.state.read().unwrap())?;
+        session.set_custom(TODOS_METADATA_KEY, value);
+        session.save_metadata()
+    }
+
+    /// Replace the todo list with the one saved in `session`
+    ///
+    /// Returns `true` if the session had a saved list. A missing or malformed
+    /// entry leaves the manager unchanged.
+    pub fn load_from_session(&self, session: &AgentSession) -> bool {
+        let Some(value) = session.get_custom(TODOS_METADATA_KEY) else {
+            return false;
+        };
+        match serde_json::from_value::<TodoListState>(value.clone()) {
+            Ok(saved) => {
+                *self.state.write().unwrap() = saved;
+                true
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "[TodoListManager] Ignoring malformed todo list in session {}: {}",
+                    session.session_id(),
+                    e
+                );
+                false
+            }
+        }
+    }
+
     /// Get the turn number when todos were last updated
     pub fn last_updated_turn(&self) -> usize {
         self.state.read().unwrap().last_updated_turn
@@ -238,6 +331,20 @@ mod tests {
         assert_eq!(retrieved[1].status, TodoStatus::Completed);
     }
 
+    #[test]
+    fn test_restore_replaces_list() {
+        let manager = TodoListManager::new();
+        manager.set_todos(vec![TodoItem::new("Later task", "Working")], 7);
+
+        manager.restore(vec![TodoItem::new("Earlier task", "Working")], 2);
+
+        assert_eq!(manager.last_updated_turn(), 2);
+        let todos = manager.get_todos();
+        assert_eq!(todos.len(), 1);
+        assert_eq!(todos[0].content, "Earlier task");
+        assert_eq!(todos[0].updated_turn, 2);
+    }
+
     #[test]
     fn test_counts() {
         let manager = TodoListManager::new();
@@ -275,4 +382,76 @@ mod tests {
         assert!(current.is_some());
         assert_eq!(current.unwrap().content, "Task 2");
     }
+
+    #[test]
+    fn test_updated_turn_tracks_changes() {
+        let manager = TodoListManager::new();
+        manager.set_todos(
+            vec![TodoItem::new("Task 1", "Working"), TodoItem::new("Task 2", "Working")],
+            1,
+        );
+        manager.set_todos(
+            vec![
+                TodoItem::new("Task 1", "Working"),
+                TodoItem::with_status("Task 2", "Working", TodoStatus::InProgress),
+            ],
+            4,
+        );
+
+        let todos = manager.get_todos();
+        assert_eq!(todos[0].updated_turn, 1);
+        assert_eq!(todos[1].updated_turn, 4);
+
+        let stale = manager.stale_items(6, 3);
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].content, "Task 1");
+    }
+
+    #[test]
+    fn test_save_and_load_from_session() {
+        use crate::session::SessionStorage;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut session = AgentSession::new_with_storage(
+            "todo-session",
+            "test",
+            "Test",
+            "",
+            SessionStorage::with_dir(dir.path()),
+        )
+        .unwrap();
+
+        let manager = TodoListManager::new();
+        manager.set_todos(
+            vec![
+                TodoItem::with_status("Write code", "Writing code", TodoStatus::Completed),
+                TodoItem::with_status("Run tests", "Running tests", TodoStatus::InProgress),
+            ],
+            3,
+        );
+        manager.save_to_session(&mut session).unwrap();
+
+        let reloaded =
+            AgentSession::load_with_storage("todo-session", SessionStorage::with_dir(dir.path()))
+                .unwrap();
+        let restored = TodoListManager::new();
+        assert!(restored.load_from_session(&reloaded));
+
+        assert_eq!(restored.len(), 2);
+        assert_eq!(restored.last_updated_turn(), 3);
+        assert_eq!(restored.current_task().unwrap().content, "Run tests");
+        assert_eq!(restored.format(), manager.format());
+
+        // A session without a saved list leaves the manager alone
+        let fresh = AgentSession::new_with_storage(
+            "other-session",
+            "test",
+            "Test",
+            "",
+            SessionStorage::with_dir(dir.path()),
+        )
+        .unwrap();
+        assert!(!restored.load_from_session(&fresh));
+        assert_eq!(restored.len(), 2);
+    }
 }
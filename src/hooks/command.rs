@@ -0,0 +1,492 @@
+//! Command Hooks
+//!
+//! Run an external program as a hook, so approval logic can live in a
+//! script (Python, shell, ...) instead of a Rust closure.
+//!
+//! # Contract
+//!
+//! The command is run through the shell (`sh -c` on Unix, `cmd /C` on
+//! Windows). It receives a [`CommandHookInput`] as JSON on stdin and may
+//! print a [`CommandHookOutput`] as JSON on stdout:
+//!
+//! ```text
+//! stdin:  {"event": "PreToolUse", "session_id": "...", "tool_name": "Bash",
+//!          "tool_input": {"command": "rm -rf build"}, ...}
+//! stdout: {"decision": "deny", "reason": "rm is not allowed"}
+//! ```
+//!
+//! - Exit code 0: stdout is parsed; empty stdout means "no decision"
+//! - Exit code 2: deny, with stderr (or stdout) as the reason
+//! - Any other exit code, a timeout, or unparseable stdout is a failure:
+//!   fail-open (the default) continues as if the hook weren't there,
+//!   fail-closed denies
+//!
+//! The timeout covers the output too: a background process still holding
+//! stdout open when it runs out is killed with the rest of the command's
+//! process group.
+//!
+//! stderr is always logged.
+
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::runtime::RuntimeFlavor;
+
+use crate::tools::common::bash::ProcessGroup;
+
+use super::registry::Hook;
+use super::types::{HookContext, HookResult, PermissionDecision};
+
+/// Default time a command hook may run
+pub const DEFAULT_COMMAND_HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Exit code that means "deny"
+const DENY_EXIT_CODE: i32 = 2;
+
+/// JSON sent to a command hook on stdin
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandHookInput {
+    /// Hook event name (`PreToolUse`, `UserPromptSubmit`, ...)
+    pub event: String,
+    /// Session the hook fired in
+    pub session_id: String,
+    /// Type of the agent
+    pub agent_type: String,
+    /// Tool being called (tool events)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    /// Tool input (tool events)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_input: Option<Value>,
+    /// Tool use ID (tool events)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_use_id: Option<String>,
+    /// Error message (PostToolUseFailure)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// User prompt (UserPromptSubmit)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_prompt: Option<String>,
+}
+
+impl CommandHookInput {
+    /// Build the input from a hook context
+    pub fn from_context(ctx: &HookContext<'_>) -> Self {
+        Self {
+            event: ctx.event.to_string(),
+            session_id: ctx.session_id().to_string(),
+            agent_type: ctx.agent_type().to_string(),
+            tool_name: ctx.tool_name.clone(),
+            tool_input: ctx.tool_input.clone(),
+            tool_use_id: ctx.tool_use_id.clone(),
+            error: ctx.error.clone(),
+            user_prompt: ctx.user_prompt.clone(),
+        }
+    }
+}
+
+/// Decision a command hook can print
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandDecision {
+    Allow,
+    Deny,
+    Ask,
+}
+
+impl From<CommandDecision> for PermissionDecision {
+    fn from(decision: CommandDecision) -> Self {
+        match decision {
+            CommandDecision::Allow => PermissionDecision::Allow,
+            CommandDecision::Deny => PermissionDecision::Deny,
+            CommandDecision::Ask => PermissionDecision::Ask,
+        }
+    }
+}
+
+/// JSON a command hook may print on stdout (every field is optional)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommandHookOutput {
+    /// Permission decision
+    #[serde(default)]
+    pub decision: Option<CommandDecision>,
+    /// Reason for the decision
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Replacement tool input (PreToolUse)
+    #[serde(default)]
+    pub modified_input: Option<Value>,
+    /// Replacement user prompt (UserPromptSubmit)
+    #[serde(default)]
+    pub modified_prompt: Option<String>,
+}
+
+/// A hook that runs an external command
+///
+/// # Example
+///
+/// ```ignore
+/// hooks.add_matcher(
+///     HookEvent::PreToolUse,
+///     HookMatcher::with_pattern(
+///         "Bash",
+///         CommandHook::new("python3 policy/approve.py")
+///             .with_timeout(Duration::from_secs(5))
+///             .with_fail_closed(true),
+///     )?,
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct CommandHook {
+    command: String,
+    timeout: Duration,
+    fail_closed: bool,
+}
+
+impl CommandHook {
+    /// Create a fail-open command hook with the default timeout
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            timeout: DEFAULT_COMMAND_HOOK_TIMEOUT,
+            fail_closed: false,
+        }
+    }
+
+    /// Set how long the command may run
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Deny when the command fails, times out or prints garbage
+    pub fn with_fail_closed(mut self, fail_closed: bool) -> Self {
+        self.fail_closed = fail_closed;
+        self
+    }
+
+    /// Run the command and interpret what it printed
+    pub fn evaluate(&self, input: &CommandHookInput) -> CommandHookOutput {
+        match self.run(input) {
+            Ok(output) => output,
+            Err(e) if self.fail_closed => {
+                tracing::warn!("[CommandHook] '{}' failed, denying: {}", self.command, e);
+                CommandHookOutput {
+                    decision: Some(CommandDecision::Deny),
+                    reason: Some(format!("Hook command failed: {}", e)),
+                    ..Default::default()
+                }
+            }
+            Err(e) => {
+                tracing::warn!("[CommandHook] '{}' failed, ignoring: {}", self.command, e);
+                CommandHookOutput::default()
+            }
+        }
+    }
+
+    fn run(&self, input: &CommandHookInput) -> Result<CommandHookOutput, String> {
+        // Hooks are called synchronously from the agent loop; on a
+        // multi-threaded runtime, let its other tasks move off this worker
+        // while the command runs
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                tokio::task::block_in_place(|| self.run_blocking(input))
+            }
+            _ => self.run_blocking(input),
+        }
+    }
+
+    fn run_blocking(&self, input: &CommandHookInput) -> Result<CommandHookOutput, String> {
+        let stdin = serde_json::to_vec(input).map_err(|e| e.to_string())?;
+
+        #[cfg(windows)]
+        let mut command = {
+            let mut command = Command::new("cmd");
+            command.arg("/C").arg(&self.command);
+            command
+        };
+        #[cfg(not(windows))]
+        let mut command = {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(&self.command);
+            command
+        };
+        // Its own process group, so a timeout reaches what the shell started
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("could not start: {}", e))?;
+        let group = ProcessGroup(Some(child.id()));
+
+        // Feed stdin and drain the pipes on threads so a chatty command can't
+        // block; the writer isn't waited for, it ends when the pipe closes
+        let mut child_stdin = child.stdin.take();
+        std::thread::spawn(move || {
+            if let Some(pipe) = child_stdin.as_mut() {
+                let _ = pipe.write_all(&stdin);
+            }
+        });
+        let stdout = read_pipe(child.stdout.take());
+        let stderr = read_pipe(child.stderr.take());
+
+        let deadline = Instant::now() + self.timeout;
+        let timed_out = || format!("timed out after {:?}", self.timeout);
+        let status = loop {
+            match child.try_wait().map_err(|e| e.to_string())? {
+                Some(status) => break status,
+                None if Instant::now() >= deadline => {
+                    let _ = child.kill();
+                    drop(group);
+                    let _ = child.wait();
+                    return Err(timed_out());
+                }
+                None => std::thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        // A background process that kept the pipes open holds them until the
+        // deadline, then goes with the rest of the group (dropping `group`)
+        let remaining = || deadline.saturating_duration_since(Instant::now());
+        let stdout = stdout.recv_timeout(remaining()).map_err(|_| timed_out())?;
+        let stderr = stderr.recv_timeout(remaining()).map_err(|_| timed_out())?;
+        group.release();
+
+        if !stderr.trim().is_empty() {
+            tracing::info!("[CommandHook] '{}' stderr: {}", self.command, stderr.trim());
+        }
+
+        match status.code() {
+            Some(0) if stdout.trim().is_empty() => Ok(CommandHookOutput::default()),
+            Some(0) => serde_json::from_str(stdout.trim())
+                .map_err(|e| format!("invalid JSON on stdout: {}", e)),
+            Some(DENY_EXIT_CODE) => {
+                let reason = if stderr.trim().is_empty() { stdout.trim() } else { stderr.trim() };
+                Ok(CommandHookOutput {
+                    decision: Some(CommandDecision::Deny),
+                    reason: Some(if reason.is_empty() {
+                        "Denied by hook command".to_string()
+                    } else {
+                        reason.to_string()
+                    }),
+                    ..Default::default()
+                })
+            }
+            Some(code) => Err(format!("exited with status {}", code)),
+            None => Err("terminated by a signal".to_string()),
+        }
+    }
+}
+
+/// Read a pipe to the end on a separate thread
+fn read_pipe<R: Read + Send + 'static>(pipe: Option<R>) -> mpsc::Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut output = String::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_string(&mut output);
+        }
+        let _ = tx.send(output);
+    });
+    rx
+}
+
+impl Hook for CommandHook {
+    fn call(&self, ctx: &mut HookContext<'_>) -> HookResult {
+        let output = self.evaluate(&CommandHookInput::from_context(ctx));
+
+        if let Some(input) = output.modified_input {
+            if ctx.tool_input.is_some() {
+                ctx.tool_input = Some(input);
+            }
+        }
+        if let Some(prompt) = output.modified_prompt {
+            if ctx.user_prompt.is_some() {
+                ctx.user_prompt = Some(prompt);
+            }
+        }
+
+        HookResult {
+            decision: output.decision.map(Into::into),
+            reason: output.reason,
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A policy script that denies commands containing "rm"
+    fn policy_script(dir: &std::path::Path) -> String {
+        let path = dir.join("policy.sh");
+        std::fs::write(
+            &path,
+            r#"input=$(cat)
+echo "checking" >&2
+case "$input" in
+  *'"command":"rm'*|*' rm '*) echo '{"decision": "deny", "reason": "rm is not allowed"}' ;;
+  *'"command":"ls'*) echo '{"decision": "allow", "modified_input": {"command": "ls -la"}}' ;;
+  *) ;;
+esac
+"#,
+        )
+        .unwrap();
+        format!("sh {}", path.display())
+    }
+
+    fn bash_input(command: &str) -> CommandHookInput {
+        CommandHookInput {
+            event: "PreToolUse".into(),
+            session_id: "s".into(),
+            agent_type: "test".into(),
+            tool_name: Some("Bash".into()),
+            tool_input: Some(json!({"command": command})),
+            tool_use_id: Some("call_1".into()),
+            error: None,
+            user_prompt: None,
+        }
+    }
+
+    #[test]
+    fn test_decisions_from_stdout() {
+        let dir = tempfile::tempdir().unwrap();
+        let hook = CommandHook::new(policy_script(dir.path()));
+
+        let output = hook.evaluate(&bash_input("rm -rf build"));
+        assert_eq!(output.decision, Some(CommandDecision::Deny));
+        assert_eq!(output.reason.as_deref(), Some("rm is not allowed"));
+
+        let output = hook.evaluate(&bash_input("ls"));
+        assert_eq!(output.decision, Some(CommandDecision::Allow));
+        assert_eq!(output.modified_input, Some(json!({"command": "ls -la"})));
+
+        // No output: no decision
+        assert_eq!(hook.evaluate(&bash_input("cargo build")), CommandHookOutput::default());
+    }
+
+    #[test]
+    fn test_exit_codes_and_failures() {
+        let input = bash_input("echo hi");
+
+        let output = CommandHook::new("echo 'not today' >&2; exit 2").evaluate(&input);
+        assert_eq!(output.decision, Some(CommandDecision::Deny));
+        assert_eq!(output.reason.as_deref(), Some("not today"));
+
+        for command in ["exit 1", "echo '{not json'", "sleep 5"] {
+            let open = CommandHook::new(command).with_timeout(Duration::from_millis(200));
+            assert_eq!(open.evaluate(&input), CommandHookOutput::default(), "{}", command);
+
+            let closed = open.with_fail_closed(true);
+            let output = closed.evaluate(&input);
+            assert_eq!(output.decision, Some(CommandDecision::Deny), "{}", command);
+            assert!(output.reason.unwrap().starts_with("Hook command failed"));
+        }
+    }
+
+    #[test]
+    fn test_timeout_reaches_background_processes() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("marker");
+        let input = bash_input("echo hi");
+
+        // Exits at once, but a background child keeps stdout open
+        let started = Instant::now();
+        let hook = CommandHook::new("sleep 5 & echo '{}'").with_timeout(Duration::from_millis(300));
+        assert_eq!(hook.evaluate(&input), CommandHookOutput::default());
+        assert!(started.elapsed() < Duration::from_secs(3), "{:?}", started.elapsed());
+
+        // What the shell started dies with it on a timeout
+        let command = format!("(sleep 1; touch {}) & sleep 5", marker.display());
+        let hook = CommandHook::new(command).with_timeout(Duration::from_millis(200));
+        assert_eq!(hook.evaluate(&input), CommandHookOutput::default());
+        std::thread::sleep(Duration::from_millis(1500));
+        assert!(!marker.exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn test_slow_command_leaves_the_runtime_running() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let ticker = tokio::spawn({
+            let ticks = ticks.clone();
+            async move {
+                loop {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    ticks.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        });
+        let hook = tokio::spawn(async { CommandHook::new("sleep 0.5").evaluate(&bash_input("ls")) });
+        assert_eq!(hook.await.unwrap(), CommandHookOutput::default());
+        ticker.abort();
+        assert!(ticks.load(Ordering::SeqCst) >= 10, "{}", ticks.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_input_contract() {
+        let json = serde_json::to_value(bash_input("ls")).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "event": "PreToolUse",
+                "session_id": "s",
+                "agent_type": "test",
+                "tool_name": "Bash",
+                "tool_input": {"command": "ls"},
+                "tool_use_id": "call_1"
+            })
+        );
+        let output: CommandHookOutput = serde_json::from_str(r#"{"decision": "ask"}"#).unwrap();
+        assert_eq!(output.decision, Some(CommandDecision::Ask));
+    }
+
+    #[tokio::test]
+    async fn test_command_hook_denies_tool_call() {
+        use crate::agent::AgentConfig;
+        use crate::cli::print_mode::run_mock_agent;
+        use crate::hooks::{HookEvent, HookRegistry};
+        use crate::llm::MockLlmProvider;
+        use crate::session::{AgentSession, SessionStorage};
+        use crate::tools::common::BashTool;
+        use crate::tools::ToolRegistry;
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut hooks = HookRegistry::new();
+        hooks
+            .add_command(HookEvent::PreToolUse, "Bash", policy_script(dir.path()))
+            .unwrap();
+
+        let mut registry = ToolRegistry::new();
+        registry.register(BashTool::with_working_dir(dir.path().to_string_lossy()));
+        let config = AgentConfig::new("test")
+            .with_tools(Arc::new(registry))
+            .with_hooks(hooks)
+            .with_auto_name(false);
+        let llm = MockLlmProvider::new()
+            .with_response(MockLlmProvider::tool_use_response(
+                "call_1",
+                "Bash",
+                json!({"command": "rm -rf build"}),
+            ))
+            .with_text("done");
+
+        let storage = SessionStorage::with_dir(dir.path().join("sessions"));
+        let session = AgentSession::new_with_storage("hook-test", "test", "Test", "", storage).unwrap();
+        run_mock_agent(config, Arc::new(llm.clone()), session, "go").await;
+
+        let result = serde_json::to_string(llm.requests()[1].messages.last().unwrap()).unwrap();
+        assert!(result.contains("Hook denied: rm is not allowed"), "{}", result);
+    }
+}
@@ -11,8 +11,9 @@
 //! - Log and audit tool calls
 //! - Filter or modify conversation history
 //!
-//! **Important:** ALL matching hooks run to completion (no short-circuiting).
-//! This ensures security hooks can't be bypassed and monitoring hooks always fire.
+//! Matching hooks run in priority order (higher first, ties in registration
+//! order), so a policy hook with a high priority runs before an auto-approve
+//! hook no matter where each was registered.
 //!
 //! # Example
 //!
@@ -67,6 +68,11 @@
 //! | `PostToolUseFailure` | After tool fails | messages (for logging) |
 //! | `UserPromptSubmit` | When user sends prompt | `user_prompt`, messages |
 //! | `PostAssistantResponse` | After assistant generates response | messages (for logging) |
+//! | `LoopDetected` | Same failing tool call repeats in a turn | nothing (`loop_repeats`, `loop_aborted` for logging) |
+//! | `PreSubAgentSpawn` | Before a subagent is spawned | `subagent` (prompts, tools, model), deny |
+//! | `SubAgentComplete` | After a subagent finishes | nothing (`subagent_result`, `subagent_usage` for logging) |
+//!
+//! Subagent hooks match their pattern against the subagent type.
 //!
 //! # HookResult
 //!
@@ -81,17 +87,43 @@
 //!
 //! # Result Combination
 //!
-//! When multiple hooks run, results are combined with priority: **Deny > Allow > Ask > None**
-//!
-//! - If ANY hook returns `Deny` → Tool is blocked (most restrictive wins)
+//! - The first hook to return `Deny` wins → Tool is blocked, later hooks don't run
 //! - Else if ANY hook returns `Allow` → Skip permissions
 //! - Else if ANY hook returns `Ask` → Use normal permission flow
 //! - Else (all returned `None`) → Continue normal flow
 //!
-//! This ensures security hooks can't be bypassed by earlier allow hooks.
+//! Modifications to the context compose in execution order.
+//!
+//! ```ignore
+//! hooks.add_matcher(
+//!     HookEvent::PreToolUse,
+//!     HookMatcher::new(security_policy).with_priority(100),
+//! );
+//! // Inspect the order hooks will run in
+//! for matcher in hooks.list(HookEvent::PreToolUse) {
+//!     println!("{:?} {}", matcher.pattern(), matcher.priority());
+//! }
+//! ```
+//!
+//! # Command Hooks
+//!
+//! A hook can also be an external program, so policy can live in a script:
+//!
+//! ```ignore
+//! hooks.add_command(HookEvent::PreToolUse, "Bash", "python3 hooks/check_bash.py")?;
+//! ```
+//!
+//! The command gets the context as JSON on stdin (`event`, `session_id`,
+//! `agent_type`, `tool_name`, `tool_input`, `tool_use_id`, `error`,
+//! `user_prompt`) and may print `{"decision": "allow"|"deny"|"ask",
+//! "reason": "...", "modified_input": {...}}` on stdout. Exit code 2 denies
+//! with stderr as the reason. Failures and timeouts are ignored unless the
+//! hook is fail-closed (`CommandHook::with_fail_closed`).
 
+pub mod command;
 mod registry;
 mod types;
 
+pub use command::{CommandDecision, CommandHook, CommandHookInput, CommandHookOutput};
 pub use registry::{ArcHook, Hook, HookMatcher, HookRegistry};
-pub use types::{HookContext, HookEvent, HookResult, PermissionDecision};
+pub use types::{HookContext, HookEvent, HookResult, PermissionDecision, SubAgentSpawn};
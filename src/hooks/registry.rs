@@ -10,6 +10,7 @@ use std::sync::Arc;
 
 use regex::Regex;
 
+use super::command::CommandHook;
 use super::types::{HookContext, HookEvent, HookResult, PermissionDecision};
 
 /// Trait for hook implementations
@@ -19,6 +20,14 @@ use super::types::{HookContext, HookEvent, HookResult, PermissionDecision};
 pub trait Hook: Send + Sync {
     /// Execute the hook with the given context
     fn call(&self, ctx: &mut HookContext<'_>) -> HookResult;
+
+    /// Order among hooks for the same event (higher runs first)
+    ///
+    /// Used when the hook is added to a registry; `HookMatcher::with_priority`
+    /// overrides it.
+    fn priority(&self) -> i32 {
+        0
+    }
 }
 
 /// Implement Hook for closures
@@ -44,6 +53,9 @@ pub struct HookMatcher {
 
     /// The hook to execute
     hook: ArcHook,
+
+    /// Order among matchers for the same event (higher runs first)
+    priority: i32,
 }
 
 impl HookMatcher {
@@ -51,6 +63,7 @@ impl HookMatcher {
     pub fn new<H: Hook + 'static>(hook: H) -> Self {
         Self {
             pattern: None,
+            priority: hook.priority(),
             hook: Arc::new(hook),
         }
     }
@@ -64,10 +77,27 @@ impl HookMatcher {
     pub fn with_pattern<H: Hook + 'static>(pattern: &str, hook: H) -> Result<Self, regex::Error> {
         Ok(Self {
             pattern: Some(Regex::new(pattern)?),
+            priority: hook.priority(),
             hook: Arc::new(hook),
         })
     }
 
+    /// Set the priority (higher runs first, default 0)
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// The priority of this matcher
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// The tool name pattern (None = match all)
+    pub fn pattern(&self) -> Option<&str> {
+        self.pattern.as_ref().map(|r| r.as_str())
+    }
+
     /// Check if this matcher applies to a tool name
     pub fn matches(&self, tool_name: &str) -> bool {
         match &self.pattern {
@@ -85,7 +115,8 @@ impl HookMatcher {
 impl std::fmt::Debug for HookMatcher {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("HookMatcher")
-            .field("pattern", &self.pattern.as_ref().map(|r| r.as_str()))
+            .field("pattern", &self.pattern())
+            .field("priority", &self.priority)
             .finish()
     }
 }
@@ -135,11 +166,7 @@ impl HookRegistry {
 
     /// Add a hook that matches all tools
     pub fn add<H: Hook + 'static>(&mut self, event: HookEvent, hook: H) -> &mut Self {
-        self.hooks
-            .entry(event)
-            .or_default()
-            .push(HookMatcher::new(hook));
-        self
+        self.add_matcher(event, HookMatcher::new(hook))
     }
 
     /// Add a hook with a tool name pattern
@@ -149,19 +176,50 @@ impl HookRegistry {
         pattern: &str,
         hook: H,
     ) -> Result<&mut Self, regex::Error> {
-        self.hooks
-            .entry(event)
-            .or_default()
-            .push(HookMatcher::with_pattern(pattern, hook)?);
-        Ok(self)
+        Ok(self.add_matcher(event, HookMatcher::with_pattern(pattern, hook)?))
+    }
+
+    /// Add an external command as a hook for tools matching `pattern`
+    ///
+    /// Shortcut for a fail-open [`CommandHook`] with the default timeout; build
+    /// the hook yourself and use [`add_with_pattern`](Self::add_with_pattern)
+    /// to change those. See the [`command`](super::command) module for the
+    /// stdin/stdout contract.
+    pub fn add_command(
+        &mut self,
+        event: HookEvent,
+        pattern: &str,
+        command: impl Into<String>,
+    ) -> Result<&mut Self, regex::Error> {
+        self.add_with_pattern(event, pattern, CommandHook::new(command))
     }
 
     /// Add a pre-built matcher
+    ///
+    /// Matchers are kept in execution order: by priority (highest first),
+    /// then by registration order.
     pub fn add_matcher(&mut self, event: HookEvent, matcher: HookMatcher) -> &mut Self {
-        self.hooks.entry(event).or_default().push(matcher);
+        let matchers = self.hooks.entry(event).or_default();
+        let index = matchers.partition_point(|m| m.priority >= matcher.priority);
+        matchers.insert(index, matcher);
         self
     }
 
+    /// Move every matcher of `other` into this registry, keeping its priority
+    pub fn merge(&mut self, other: HookRegistry) -> &mut Self {
+        for (event, matchers) in other.hooks {
+            for matcher in matchers {
+                self.add_matcher(event, matcher);
+            }
+        }
+        self
+    }
+
+    /// The matchers for an event, in the order they run
+    pub fn list(&self, event: HookEvent) -> &[HookMatcher] {
+        self.hooks.get(&event).map(|v| v.as_slice()).unwrap_or(&[])
+    }
+
     /// Check if there are any hooks for an event
     pub fn has_hooks(&self, event: HookEvent) -> bool {
         self.hooks
@@ -177,28 +235,25 @@ impl HookRegistry {
 
     /// Run all matching hooks for an event
     ///
-    /// For tool hooks, filters by tool name.
-    /// For non-tool hooks (like UserPromptSubmit), runs all hooks.
-    ///
-    /// **Hook execution behavior:**
-    /// - By default (short_circuit_on_deny = false): ALL hooks run to completion
-    ///   - Security hooks can't be bypassed
-    ///   - Logging/monitoring hooks always fire
-    ///   - Multiple hooks compose properly
+    /// For tool hooks, filters by tool name; for subagent hooks, by
+    /// subagent type. For other hooks (like UserPromptSubmit), runs all hooks.
     ///
-    /// - When short_circuit_on_deny = true: Stops on first Deny
-    ///   - Performance optimization
-    ///   - Later hooks may not run
+    /// Hooks run in priority order (highest first, ties in registration
+    /// order) and share the context, so modifications compose in that order.
     ///
-    /// Results are combined with priority:
-    /// - If ANY hook said Deny → DENY (most restrictive wins)
+    /// Results are combined as:
+    /// - The first hook to say Deny → DENY, and later hooks don't run
     /// - Else if ANY hook said Allow → ALLOW
     /// - Else if ANY hook said Ask → ASK
     /// - Else (all said None) → NONE (continue normal flow)
     pub fn run(&self, ctx: &mut HookContext<'_>) -> HookResult {
         let event = ctx.event;
-        let tool_name = ctx.tool_name.clone();
-        let short_circuit = ctx.short_circuit_on_deny;
+        let tool_name = match event {
+            HookEvent::PreSubAgentSpawn | HookEvent::SubAgentComplete => {
+                ctx.subagent.as_ref().map(|s| s.agent_type.clone())
+            }
+            _ => ctx.tool_name.clone(),
+        };
 
         let matchers = match self.hooks.get(&event) {
             Some(matchers) => matchers,
@@ -208,11 +263,16 @@ impl HookRegistry {
         let mut combined = HookResult::none();
 
         for matcher in matchers {
-            // For tool hooks, check if matcher applies to this tool
+            // For tool and subagent hooks, check if matcher applies to this tool or subagent
             let should_run = match (&tool_name, event) {
                 (
                     Some(name),
-                    HookEvent::PreToolUse | HookEvent::PostToolUse | HookEvent::PostToolUseFailure,
+                    HookEvent::PreToolUse
+                    | HookEvent::PostToolUse
+                    | HookEvent::PostToolUseFailure
+                    | HookEvent::LoopDetected
+                    | HookEvent::PreSubAgentSpawn
+                    | HookEvent::SubAgentComplete,
                 ) => matcher.matches(name),
                 _ => true, // Non-tool hooks always run
             };
@@ -227,9 +287,9 @@ impl HookRegistry {
             // Combine results (Deny > Allow > Ask > None)
             combined = combine_results(combined, result);
 
-            // Optionally short-circuit on Deny (if enabled in config)
-            if short_circuit && combined.decision == Some(PermissionDecision::Deny) {
-                tracing::debug!("[HookRegistry] Short-circuiting on Deny (remaining hooks skipped)");
+            // A Deny is final; lower-priority hooks can't override it
+            if combined.decision == Some(PermissionDecision::Deny) {
+                tracing::debug!("[HookRegistry] Hook denied (remaining hooks skipped)");
                 break;
             }
         }
@@ -264,7 +324,7 @@ impl std::fmt::Debug for HookRegistry {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut map = f.debug_map();
         for (event, matchers) in &self.hooks {
-            map.entry(event, &matchers.len());
+            map.entry(event, matchers);
         }
         map.finish()
     }
@@ -382,4 +442,81 @@ mod tests {
             Some(PermissionDecision::Allow)
         );
     }
+
+    /// A hook that records its name when it runs
+    fn recording(
+        name: &'static str,
+        ran: &Arc<std::sync::Mutex<Vec<&'static str>>>,
+        result: fn() -> HookResult,
+    ) -> impl Hook {
+        let ran = ran.clone();
+        move |_ctx: &mut HookContext<'_>| {
+            ran.lock().unwrap().push(name);
+            result()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_priority_ordering() {
+        let ran = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut registry = HookRegistry::new();
+        registry.add(HookEvent::PreToolUse, recording("first", &ran, HookResult::none));
+        registry.add_matcher(
+            HookEvent::PreToolUse,
+            HookMatcher::new(recording("urgent", &ran, HookResult::none)).with_priority(10),
+        );
+        registry.add(HookEvent::PreToolUse, recording("second", &ran, HookResult::none));
+        registry.add_matcher(
+            HookEvent::PreToolUse,
+            HookMatcher::new(recording("late", &ran, HookResult::none)).with_priority(-5),
+        );
+
+        let priorities: Vec<i32> = registry.list(HookEvent::PreToolUse).iter().map(|m| m.priority()).collect();
+        assert_eq!(priorities, vec![10, 0, 0, -5]);
+        assert!(registry.list(HookEvent::PostToolUse).is_empty());
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut internals = crate::runtime::AgentInternals::for_test(dir.path());
+        let mut ctx = HookContext::pre_tool_use(&mut internals, "Bash", &serde_json::json!({}), "call_1").await;
+        registry.run(&mut ctx);
+        assert_eq!(*ran.lock().unwrap(), vec!["urgent", "first", "second", "late"]);
+    }
+
+    #[test]
+    fn test_merge_keeps_priorities() {
+        let mut registry = HookRegistry::new();
+        registry.add(HookEvent::PreToolUse, |_ctx: &mut HookContext| HookResult::none());
+        let mut other = HookRegistry::new();
+        other.add_matcher(
+            HookEvent::PreToolUse,
+            HookMatcher::new(|_ctx: &mut HookContext| HookResult::none()).with_priority(5),
+        );
+        other.add(HookEvent::PostToolUse, |_ctx: &mut HookContext| HookResult::none());
+
+        registry.merge(other);
+        let priorities: Vec<i32> = registry.list(HookEvent::PreToolUse).iter().map(|m| m.priority()).collect();
+        assert_eq!(priorities, vec![5, 0]);
+        assert_eq!(registry.hook_count(HookEvent::PostToolUse), 1);
+    }
+
+    #[tokio::test]
+    async fn test_deny_short_circuits() {
+        let ran = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut registry = HookRegistry::new();
+        // Registered first, but the deny outranks it
+        registry.add(HookEvent::PreToolUse, recording("approve", &ran, HookResult::allow));
+        registry.add_matcher(
+            HookEvent::PreToolUse,
+            HookMatcher::new(recording("policy", &ran, || HookResult::deny("blocked"))).with_priority(100),
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut internals = crate::runtime::AgentInternals::for_test(dir.path());
+        let mut ctx = HookContext::pre_tool_use(&mut internals, "Bash", &serde_json::json!({}), "call_1").await;
+        let result = registry.run(&mut ctx);
+
+        assert_eq!(result.decision, Some(PermissionDecision::Deny));
+        assert_eq!(result.reason.as_deref(), Some("blocked"));
+        assert_eq!(*ran.lock().unwrap(), vec!["policy"]);
+    }
 }
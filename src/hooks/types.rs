@@ -5,12 +5,14 @@
 //! - `HookContext` - Mutable context passed to hooks
 //! - `HookResult` - Result returned from hooks
 //! - `PermissionDecision` - Permission decision for PreToolUse hooks
+//! - `SubAgentSpawn` - The subagent a PreSubAgentSpawn hook can change
 
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use serde_json::Value;
 
-use crate::llm::{ContentBlock, Message, StopReason};
+use crate::llm::{ContentBlock, Message, MessageContent, StopReason, Usage};
 use crate::runtime::AgentInternals;
 use crate::tools::ToolResult;
 
@@ -27,6 +29,12 @@ pub enum HookEvent {
     UserPromptSubmit,
     /// After assistant generates a response
     PostAssistantResponse,
+    /// When the same failing tool call keeps repeating within a turn
+    LoopDetected,
+    /// Before a subagent is spawned - can deny, or modify its prompts and model
+    PreSubAgentSpawn,
+    /// After a spawned subagent finishes
+    SubAgentComplete,
 }
 
 impl std::fmt::Display for HookEvent {
@@ -37,6 +45,9 @@ impl std::fmt::Display for HookEvent {
             HookEvent::PostToolUseFailure => write!(f, "PostToolUseFailure"),
             HookEvent::UserPromptSubmit => write!(f, "UserPromptSubmit"),
             HookEvent::PostAssistantResponse => write!(f, "PostAssistantResponse"),
+            HookEvent::LoopDetected => write!(f, "LoopDetected"),
+            HookEvent::PreSubAgentSpawn => write!(f, "PreSubAgentSpawn"),
+            HookEvent::SubAgentComplete => write!(f, "SubAgentComplete"),
         }
     }
 }
@@ -55,8 +66,12 @@ pub struct HookContext<'a> {
     /// Full agent internals - access to session, context, permissions
     pub internals: &'a mut AgentInternals,
 
-    /// Whether to short-circuit on Deny (from agent config)
-    pub(crate) short_circuit_on_deny: bool,
+    /// Session history when the hook was called (read-only snapshot)
+    ///
+    /// Taken without holding the session lock during the hook. The response
+    /// whose tools are running is added to the history after they finish, so
+    /// tool hooks see earlier responses only.
+    pub history: Arc<[Message]>,
 
     // === Tool-specific (populated for tool hooks) ===
     /// Tool name being called
@@ -85,21 +100,42 @@ pub struct HookContext<'a> {
 
     /// Stop reason for the assistant's response
     pub stop_reason: Option<StopReason>,
+
+    // === Loop detection (for LoopDetected) ===
+    /// How many times the failing call has been repeated
+    pub loop_repeats: Option<usize>,
+
+    /// Whether the turn is being ended because of the loop
+    pub loop_aborted: bool,
+
+    // === Subagents (for PreSubAgentSpawn and SubAgentComplete) ===
+    /// The subagent being spawned - can be modified by PreSubAgentSpawn hooks
+    ///
+    /// The spawning (parent) agent is the one in `internals`.
+    pub subagent: Option<SubAgentSpawn>,
+
+    /// Session ID of the finished subagent (SubAgentComplete)
+    pub subagent_session_id: Option<String>,
+
+    /// Final text of the finished subagent (SubAgentComplete; failures go in `error`)
+    pub subagent_result: Option<String>,
+
+    /// Tokens the finished subagent used (SubAgentComplete)
+    pub subagent_usage: Option<Usage>,
 }
 
 impl<'a> HookContext<'a> {
     /// Create context for PreToolUse hook
-    pub fn pre_tool_use(
+    pub async fn pre_tool_use(
         internals: &'a mut AgentInternals,
         tool_name: &str,
         tool_input: &Value,
         tool_use_id: &str,
-        short_circuit_on_deny: bool,
     ) -> Self {
         Self {
+            history: history_snapshot(internals).await,
             event: HookEvent::PreToolUse,
             internals,
-            short_circuit_on_deny,
             tool_name: Some(tool_name.to_string()),
             tool_input: Some(tool_input.clone()),
             tool_use_id: Some(tool_use_id.to_string()),
@@ -108,22 +144,27 @@ impl<'a> HookContext<'a> {
             user_prompt: None,
             assistant_content: None,
             stop_reason: None,
+            loop_repeats: None,
+            loop_aborted: false,
+            subagent: None,
+            subagent_session_id: None,
+            subagent_result: None,
+            subagent_usage: None,
         }
     }
 
     /// Create context for PostToolUse hook
-    pub fn post_tool_use(
+    pub async fn post_tool_use(
         internals: &'a mut AgentInternals,
         tool_name: &str,
         tool_input: &Value,
         tool_use_id: &str,
         result: &ToolResult,
-        short_circuit_on_deny: bool,
     ) -> Self {
         Self {
+            history: history_snapshot(internals).await,
             event: HookEvent::PostToolUse,
             internals,
-            short_circuit_on_deny,
             tool_name: Some(tool_name.to_string()),
             tool_input: Some(tool_input.clone()),
             tool_use_id: Some(tool_use_id.to_string()),
@@ -132,22 +173,27 @@ impl<'a> HookContext<'a> {
             user_prompt: None,
             assistant_content: None,
             stop_reason: None,
+            loop_repeats: None,
+            loop_aborted: false,
+            subagent: None,
+            subagent_session_id: None,
+            subagent_result: None,
+            subagent_usage: None,
         }
     }
 
     /// Create context for PostToolUseFailure hook
-    pub fn post_tool_use_failure(
+    pub async fn post_tool_use_failure(
         internals: &'a mut AgentInternals,
         tool_name: &str,
         tool_input: &Value,
         tool_use_id: &str,
         error: &str,
-        short_circuit_on_deny: bool,
     ) -> Self {
         Self {
+            history: history_snapshot(internals).await,
             event: HookEvent::PostToolUseFailure,
             internals,
-            short_circuit_on_deny,
             tool_name: Some(tool_name.to_string()),
             tool_input: Some(tool_input.clone()),
             tool_use_id: Some(tool_use_id.to_string()),
@@ -156,19 +202,24 @@ impl<'a> HookContext<'a> {
             user_prompt: None,
             assistant_content: None,
             stop_reason: None,
+            loop_repeats: None,
+            loop_aborted: false,
+            subagent: None,
+            subagent_session_id: None,
+            subagent_result: None,
+            subagent_usage: None,
         }
     }
 
     /// Create context for UserPromptSubmit hook
-    pub fn user_prompt_submit(
+    pub async fn user_prompt_submit(
         internals: &'a mut AgentInternals,
         prompt: &str,
-        short_circuit_on_deny: bool,
     ) -> Self {
         Self {
+            history: history_snapshot(internals).await,
             event: HookEvent::UserPromptSubmit,
             internals,
-            short_circuit_on_deny,
             tool_name: None,
             tool_input: None,
             tool_use_id: None,
@@ -177,20 +228,25 @@ impl<'a> HookContext<'a> {
             user_prompt: Some(prompt.to_string()),
             assistant_content: None,
             stop_reason: None,
+            loop_repeats: None,
+            loop_aborted: false,
+            subagent: None,
+            subagent_session_id: None,
+            subagent_result: None,
+            subagent_usage: None,
         }
     }
 
     /// Create context for PostAssistantResponse hook
-    pub fn post_assistant_response(
+    pub async fn post_assistant_response(
         internals: &'a mut AgentInternals,
         content_blocks: &[ContentBlock],
         stop_reason: Option<StopReason>,
-        short_circuit_on_deny: bool,
     ) -> Self {
         Self {
+            history: history_snapshot(internals).await,
             event: HookEvent::PostAssistantResponse,
             internals,
-            short_circuit_on_deny,
             tool_name: None,
             tool_input: None,
             tool_use_id: None,
@@ -199,6 +255,99 @@ impl<'a> HookContext<'a> {
             user_prompt: None,
             assistant_content: Some(content_blocks.to_vec()),
             stop_reason,
+            loop_repeats: None,
+            loop_aborted: false,
+            subagent: None,
+            subagent_session_id: None,
+            subagent_result: None,
+            subagent_usage: None,
+        }
+    }
+
+    /// Create context for LoopDetected hook
+    ///
+    /// `aborted` is true when the turn is about to end, false when the model
+    /// is only being warned.
+    pub async fn loop_detected(
+        internals: &'a mut AgentInternals,
+        tool_name: &str,
+        tool_input: &Value,
+        repeats: usize,
+        aborted: bool,
+    ) -> Self {
+        Self {
+            history: history_snapshot(internals).await,
+            event: HookEvent::LoopDetected,
+            internals,
+            tool_name: Some(tool_name.to_string()),
+            tool_input: Some(tool_input.clone()),
+            tool_use_id: None,
+            tool_result: None,
+            error: None,
+            user_prompt: None,
+            assistant_content: None,
+            stop_reason: None,
+            loop_repeats: Some(repeats),
+            loop_aborted: aborted,
+            subagent: None,
+            subagent_session_id: None,
+            subagent_result: None,
+            subagent_usage: None,
+        }
+    }
+
+    /// Create context for PreSubAgentSpawn hook
+    pub async fn pre_subagent_spawn(internals: &'a mut AgentInternals, spawn: &SubAgentSpawn) -> Self {
+        Self {
+            history: history_snapshot(internals).await,
+            event: HookEvent::PreSubAgentSpawn,
+            internals,
+            tool_name: None,
+            tool_input: None,
+            tool_use_id: None,
+            tool_result: None,
+            error: None,
+            user_prompt: None,
+            assistant_content: None,
+            stop_reason: None,
+            loop_repeats: None,
+            loop_aborted: false,
+            subagent: Some(spawn.clone()),
+            subagent_session_id: None,
+            subagent_result: None,
+            subagent_usage: None,
+        }
+    }
+
+    /// Create context for SubAgentComplete hook
+    ///
+    /// `spawn` is the subagent as it was spawned (after PreSubAgentSpawn hooks).
+    pub async fn subagent_complete(
+        internals: &'a mut AgentInternals,
+        spawn: &SubAgentSpawn,
+        session_id: &str,
+        result: Option<&str>,
+        error: Option<&str>,
+        usage: Option<&Usage>,
+    ) -> Self {
+        Self {
+            history: history_snapshot(internals).await,
+            event: HookEvent::SubAgentComplete,
+            internals,
+            tool_name: None,
+            tool_input: None,
+            tool_use_id: None,
+            tool_result: None,
+            error: error.map(str::to_string),
+            user_prompt: None,
+            assistant_content: None,
+            stop_reason: None,
+            loop_repeats: None,
+            loop_aborted: false,
+            subagent: Some(spawn.clone()),
+            subagent_session_id: Some(session_id.to_string()),
+            subagent_result: result.map(str::to_string),
+            subagent_usage: usage.cloned(),
         }
     }
 
@@ -206,22 +355,38 @@ impl<'a> HookContext<'a> {
 
     /// Get conversation history
     ///
-    /// Note: This returns a cloned copy of the messages since the session
-    /// is now behind an Arc<RwLock>. If you need to modify messages, use
-    /// the session directly through async access.
+    /// Returns a copy of the [`history`](Self::history) snapshot.
     pub fn messages(&self) -> Vec<Message> {
-        let session = self.internals.session.blocking_read();
-        session.history().to_vec()
+        self.history.to_vec()
+    }
+
+    /// The last `n` messages of the history snapshot
+    pub fn recent_messages(&self, n: usize) -> &[Message] {
+        &self.history[self.history.len().saturating_sub(n)..]
+    }
+
+    /// Calls to `tool_name` in the history snapshot, as (tool use id, input)
+    pub fn previous_tool_calls(&self, tool_name: &str) -> Vec<(&str, &Value)> {
+        self.history
+            .iter()
+            .filter_map(|message| match &message.content {
+                MessageContent::Blocks(blocks) => Some(blocks),
+                MessageContent::Text(_) => None,
+            })
+            .flatten()
+            .filter_map(|block| match block {
+                ContentBlock::ToolUse { id, name, input } if name == tool_name => Some((id.as_str(), input)),
+                _ => None,
+            })
+            .collect()
     }
 
     /// Get conversation history (deprecated - hooks should not mutate history directly)
     ///
-    /// This method is kept for backwards compatibility but will block.
-    /// Consider using async access to the session if you need to modify history.
+    /// Returns a copy of the [`history`](Self::history) snapshot.
     #[deprecated(note = "Use async session access instead of synchronous mutation")]
     pub fn messages_mut(&mut self) -> Vec<Message> {
-        let session = self.internals.session.blocking_read();
-        session.history().to_vec()
+        self.history.to_vec()
     }
 
     /// Get session ID
@@ -234,9 +399,9 @@ impl<'a> HookContext<'a> {
         self.internals.agent_type()
     }
 
-    /// Get current turn number
+    /// Get current turn number (see `AgentInternals::turn`)
     pub fn current_turn(&self) -> usize {
-        self.internals.context.current_turn
+        self.internals.turn()
     }
 
     /// Get metadata value
@@ -255,6 +420,48 @@ impl<'a> HookContext<'a> {
     }
 }
 
+/// Snapshot the session history for one hook run
+///
+/// Waits for the session lock once; the hooks of the run share the snapshot.
+async fn history_snapshot(internals: &AgentInternals) -> Arc<[Message]> {
+    internals.session.read().await.history().into()
+}
+
+/// A subagent about to be spawned (see `HookEvent::PreSubAgentSpawn`)
+///
+/// Hooks may change anything but `agent_type` (a changed type denies the
+/// spawn); the subagent is spawned with what's left after all of them ran.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubAgentSpawn {
+    /// Subagent type (the definition name)
+    pub agent_type: String,
+    /// System prompt the subagent runs with
+    pub system_prompt: String,
+    /// Task sent to the subagent as its first message
+    pub prompt: String,
+    /// Tools it may use (None = all tools available to subagents)
+    pub tools: Option<Vec<String>>,
+    /// Model override (None = the parent's provider)
+    pub model: Option<String>,
+}
+
+impl SubAgentSpawn {
+    /// A subagent of `agent_type` with a system prompt and a task
+    pub fn new(
+        agent_type: impl Into<String>,
+        system_prompt: impl Into<String>,
+        prompt: impl Into<String>,
+    ) -> Self {
+        Self {
+            agent_type: agent_type.into(),
+            system_prompt: system_prompt.into(),
+            prompt: prompt.into(),
+            tools: None,
+            model: None,
+        }
+    }
+}
+
 /// Permission decision for PreToolUse hooks
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PermissionDecision {
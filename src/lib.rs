@@ -1,3 +1,6 @@
+// Lets `#[derive(ToolInput)]` name this crate from inside it too
+extern crate self as shadow_agent_sdk;
+
 pub mod core;
 pub mod runtime;
 pub mod session;
@@ -8,6 +11,8 @@ pub mod tools;
 pub mod cli;
 pub mod llm;
 pub mod logging;
+pub mod metrics;
+pub mod telemetry;
 
 // Useful helpers for agent implementations
 pub mod helpers;
@@ -20,3 +25,10 @@ pub mod hooks;
 
 // MCP (Model Context Protocol) support
 pub mod mcp;
+
+// Ready-made agent setups
+pub mod presets;
+
+// Remote agent handles over WebSocket
+#[cfg(feature = "remote")]
+pub mod remote;
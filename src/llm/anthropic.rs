@@ -29,18 +29,30 @@ use std::env;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::AsyncBufReadExt;
 use tokio_util::io::StreamReader;
 
 use super::auth::{auth_provider, AuthConfig, AuthProvider, AuthSource};
+use super::batch::AnthropicBatchClient;
+use super::interceptor::{notify_received, prepare_request, HttpParts, RequestInterceptor};
+use super::json_tool;
 use super::provider::LlmProvider;
+use super::stats::{ErrorClass, ProviderStats, StatsRecorder};
 use super::types::{
-    Message, MessageRequest, MessageResponse, RawStreamEvent, StreamEvent, SystemPrompt,
+    GenerationParams, Message, MessageRequest, MessageResponse, RawStreamEvent, StreamEvent, SystemPrompt,
     ThinkingConfig, ToolChoice, ToolDefinition,
 };
 
-const DEFAULT_API_URL: &str = "https://api.anthropic.com/v1/messages";
-const ANTHROPIC_VERSION: &str = "2023-06-01";
+pub(super) const DEFAULT_API_URL: &str = "https://api.anthropic.com/v1/messages";
+pub(super) const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// Beta sent with thinking requests unless `ThinkingConfig::interleaved` is off
+pub(super) const INTERLEAVED_THINKING_BETA: &str = "interleaved-thinking-2025-05-14";
+
+/// Whether a request wants the interleaved-thinking beta
+pub(super) fn wants_interleaved_thinking(request: &MessageRequest) -> bool {
+    request.thinking.as_ref().is_some_and(|thinking| thinking.interleaved)
+}
 
 /// Anthropic LLM provider using direct HTTP calls
 ///
@@ -53,6 +65,8 @@ pub struct AnthropicProvider {
     auth: AuthSource,
     model: String,
     max_tokens: u32,
+    interceptors: Vec<Arc<dyn RequestInterceptor>>,
+    stats: Arc<StatsRecorder>,
 }
 
 impl AnthropicProvider {
@@ -95,6 +109,8 @@ impl AnthropicProvider {
             }),
             model,
             max_tokens,
+            interceptors: Vec::new(),
+            stats: Arc::new(StatsRecorder::new("anthropic")),
         })
     }
 
@@ -107,6 +123,8 @@ impl AnthropicProvider {
             auth: AuthSource::Static(AuthConfig::new(api_key)),
             model: "".to_string(),
             max_tokens: 32000,
+            interceptors: Vec::new(),
+            stats: Arc::new(StatsRecorder::new("anthropic")),
         })
     }
 
@@ -136,6 +154,8 @@ impl AnthropicProvider {
             auth: AuthSource::Dynamic(Arc::new(auth_provider(provider))),
             model: "".to_string(),
             max_tokens: 32000,
+            interceptors: Vec::new(),
+            stats: Arc::new(StatsRecorder::new("anthropic")),
         }
     }
 
@@ -148,6 +168,8 @@ impl AnthropicProvider {
             auth: AuthSource::Dynamic(provider),
             model: "".to_string(),
             max_tokens: 32000,
+            interceptors: Vec::new(),
+            stats: Arc::new(StatsRecorder::new("anthropic")),
         }
     }
 
@@ -163,6 +185,24 @@ impl AnthropicProvider {
         self
     }
 
+    /// Add a request interceptor
+    ///
+    /// Interceptors run in the order they were added, on every request.
+    /// Variants created with `with_model_override` keep them.
+    pub fn with_interceptor(mut self, interceptor: Arc<dyn RequestInterceptor>) -> Self {
+        self.interceptors.push(interceptor);
+        self
+    }
+
+    /// Log a stats snapshot at info level at most once per `interval`
+    ///
+    /// See `LlmProvider::stats`. Variants keep the setting but count their
+    /// own requests.
+    pub fn with_stats_log(mut self, interval: Duration) -> Self {
+        self.stats = Arc::new(StatsRecorder::new("anthropic").with_log_interval(interval));
+        self
+    }
+
     /// Get the current model
     pub fn model(&self) -> &str {
         &self.model
@@ -190,6 +230,8 @@ impl AnthropicProvider {
             auth: self.auth.clone(),
             model: model.into(),
             max_tokens: self.max_tokens,
+            interceptors: self.interceptors.clone(),
+            stats: Arc::new(self.stats.fresh()),
         }
     }
 
@@ -204,9 +246,17 @@ impl AnthropicProvider {
             auth: self.auth.clone(),
             model: model.into(),
             max_tokens,
+            interceptors: self.interceptors.clone(),
+            stats: Arc::new(self.stats.fresh()),
         }
     }
 
+    /// Create a Message Batches client sharing this provider's auth, model
+    /// and max tokens
+    pub fn batch_client(&self) -> AnthropicBatchClient {
+        AnthropicBatchClient::from_auth(self.auth.clone(), self.model.clone(), self.max_tokens)
+    }
+
     /// Send a message and get a complete response (no tool calling)
     ///
     /// This is a simple method for basic conversations without tools.
@@ -238,6 +288,8 @@ impl AnthropicProvider {
             tool_choice: None,
             thinking: None,
             temperature: None,
+            top_p: None,
+            stop_sequences: None,
             stream: None,
         };
 
@@ -262,20 +314,9 @@ impl AnthropicProvider {
         tracing::debug!("Tools count: {}", tools.len());
         tracing::debug!("Thinking enabled: {}", thinking.is_some());
 
-        // When thinking is enabled, temperature must be 1 (required by Anthropic API)
-        let temperature = if thinking.is_some() { Some(1.0) } else { None };
-
-        let request = MessageRequest {
-            model: self.model.clone(),
-            max_tokens: self.max_tokens,
-            messages,
-            system: system_prompt.map(|s| SystemPrompt::Text(s.to_string())),
-            tools: if tools.is_empty() { None } else { Some(tools) },
-            tool_choice,
-            thinking,
-            temperature,
-            stream: None,
-        };
+        let system = system_prompt.map(|s| SystemPrompt::Text(s.to_string()));
+        let request =
+            self.build_tools_request(messages, system, tools, tool_choice, thinking, None, false);
 
         self.send_request(&request, None).await
     }
@@ -284,6 +325,7 @@ impl AnthropicProvider {
     ///
     /// This variant accepts `Option<SystemPrompt>` instead of `Option<&str>`,
     /// allowing for prompt caching via SystemPrompt::Blocks.
+    #[allow(clippy::too_many_arguments)]
     pub async fn send_with_tools_and_system(
         &self,
         messages: Vec<Message>,
@@ -291,6 +333,7 @@ impl AnthropicProvider {
         tools: Vec<ToolDefinition>,
         tool_choice: Option<ToolChoice>,
         thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
         session_id: Option<&str>,
     ) -> Result<MessageResponse> {
         tracing::info!("Sending message with tools to Anthropic API");
@@ -298,25 +341,49 @@ impl AnthropicProvider {
         tracing::debug!("Tools count: {}", tools.len());
         tracing::debug!("Thinking enabled: {}", thinking.is_some());
 
-        // When thinking is enabled, temperature must be 1 (required by Anthropic API)
-        let temperature = if thinking.is_some() { Some(1.0) } else { None };
+        let format = generation.as_ref().and_then(|g| g.response_format.clone());
+        let request = self.build_tools_request(
+            messages, system, tools, tool_choice, thinking, generation, false,
+        );
 
-        let request = MessageRequest {
-            model: self.model.clone(),
-            max_tokens: self.max_tokens,
-            messages,
-            system,
-            tools: if tools.is_empty() { None } else { Some(tools) },
-            tool_choice,
-            thinking,
-            temperature,
-            stream: None,
-        };
+        let mut response = self.send_request(&request, session_id).await?;
+        if let Some(format) = format {
+            json_tool::unwrap_response(&format, &mut response);
+        }
+        Ok(response)
+    }
+
+    /// Build a tool-calling request, applying sampling parameters
+    #[allow(clippy::too_many_arguments)]
+    fn build_tools_request(
+        &self,
+        messages: Vec<Message>,
+        system: Option<SystemPrompt>,
+        tools: Vec<ToolDefinition>,
+        tool_choice: Option<ToolChoice>,
+        thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
+        stream: bool,
+    ) -> MessageRequest {
+        build_tools_request(
+            &self.model, self.max_tokens, messages, system, tools, tool_choice, thinking, generation, stream,
+        )
+    }
 
-        self.send_request(&request, session_id).await
+    /// Method, URL and standard headers for a Messages API request
+    fn http_parts(&self, api_url: &str, api_key: &str, request: &MessageRequest) -> Result<HttpParts> {
+        let mut parts = HttpParts::post(api_url);
+        parts.set_header("Content-Type", "application/json")?;
+        parts.set_header("x-api-key", api_key)?;
+        parts.set_header("anthropic-version", ANTHROPIC_VERSION)?;
+        if wants_interleaved_thinking(request) {
+            parts.set_header("anthropic-beta", INTERLEAVED_THINKING_BETA)?;
+        }
+        Ok(parts)
     }
 
     /// Send a raw request to the Anthropic API
+    #[tracing::instrument(name = "llm_request", skip_all, fields(provider = "anthropic", model = %request.model, session_id = session_id.unwrap_or_default()))]
     async fn send_request(&self, request: &MessageRequest, session_id: Option<&str>) -> Result<MessageResponse> {
         tracing::debug!("Model: {}", request.model);
         tracing::debug!("Max tokens: {}", request.max_tokens);
@@ -326,45 +393,43 @@ impl AnthropicProvider {
             .context("Failed to get authentication credentials")?;
         let api_url = auth_config.base_url.as_deref().unwrap_or(DEFAULT_API_URL);
 
-        let request_json = serde_json::to_string(request)
-            .context("Failed to serialize request")?;
-        tracing::debug!("Request JSON: {}", request_json);
-
-        let mut request_builder = self
-            .client
-            .post(api_url)
-            .header("Content-Type", "application/json")
-            .header("x-api-key", &auth_config.api_key)
-            .header("anthropic-version", ANTHROPIC_VERSION)
-            .header("anthropic-beta", "interleaved-thinking-2025-05-14");
-
+        let mut parts = self.http_parts(api_url, &auth_config.api_key, request)?;
         // Add agent-session-id header if session_id is provided
         if let Some(sid) = session_id {
-            request_builder = request_builder.header("agent-session-id", sid);
+            parts.set_header("agent-session-id", sid)?;
         }
 
+        let (request_builder, request_json) = prepare_request(&self.client, &self.interceptors, parts, request)?;
+        tracing::debug!("Request JSON: {}", request_json);
+
+        let started = self.stats.begin();
         let response = request_builder
-            .body(request_json)
             .send()
             .await
+            .inspect_err(|_| self.stats.record_error(ErrorClass::Network))
             .context("Failed to send request to Anthropic API")?;
 
         let status = response.status();
         let response_text = response
             .text()
             .await
+            .inspect_err(|_| self.stats.record_error(ErrorClass::Network))
             .context("Failed to read response body")?;
+        notify_received(&self.interceptors, status, Some(&response_text));
 
         tracing::debug!("Response status: {}", status);
         tracing::debug!("Response body: {}", response_text);
 
         if !status.is_success() {
             tracing::error!("API error: {} - {}", status, response_text);
+            self.stats.record_error(ErrorClass::from_status(status));
             anyhow::bail!("Anthropic API error ({}): {}", status, response_text);
         }
 
         let response: MessageResponse = serde_json::from_str(&response_text)
+            .inspect_err(|_| self.stats.record_error(ErrorClass::Parse))
             .context("Failed to parse API response")?;
+        self.stats.record_success(started, response.usage.input_tokens, response.usage.output_tokens);
 
         tracing::info!("Received response from Anthropic API");
         tracing::debug!("Response ID: {}", response.id);
@@ -419,6 +484,8 @@ impl AnthropicProvider {
             tool_choice: None,
             thinking: None,
             temperature: None,
+            top_p: None,
+            stop_sequences: None,
             stream: Some(true),
         };
 
@@ -441,20 +508,9 @@ impl AnthropicProvider {
         tracing::debug!("Tools count: {}", tools.len());
         tracing::debug!("Thinking enabled: {}", thinking.is_some());
 
-        // When thinking is enabled, temperature must be 1 (required by Anthropic API)
-        let temperature = if thinking.is_some() { Some(1.0) } else { None };
-
-        let request = MessageRequest {
-            model: self.model.clone(),
-            max_tokens: self.max_tokens,
-            messages,
-            system: system_prompt.map(|s| SystemPrompt::Text(s.to_string())),
-            tools: if tools.is_empty() { None } else { Some(tools) },
-            tool_choice,
-            thinking,
-            temperature,
-            stream: Some(true),
-        };
+        let system = system_prompt.map(|s| SystemPrompt::Text(s.to_string()));
+        let request =
+            self.build_tools_request(messages, system, tools, tool_choice, thinking, None, true);
 
         self.send_streaming_request(&request, None).await
     }
@@ -463,6 +519,7 @@ impl AnthropicProvider {
     ///
     /// This variant accepts `Option<SystemPrompt>` instead of `Option<&str>`,
     /// allowing for prompt caching via SystemPrompt::Blocks.
+    #[allow(clippy::too_many_arguments)]
     pub async fn stream_with_tools_and_system(
         &self,
         messages: Vec<Message>,
@@ -470,6 +527,7 @@ impl AnthropicProvider {
         tools: Vec<ToolDefinition>,
         tool_choice: Option<ToolChoice>,
         thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
         session_id: Option<&str>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>> {
         tracing::info!("Streaming message with tools from Anthropic API");
@@ -477,25 +535,20 @@ impl AnthropicProvider {
         tracing::debug!("Tools count: {}", tools.len());
         tracing::debug!("Thinking enabled: {}", thinking.is_some());
 
-        // When thinking is enabled, temperature must be 1 (required by Anthropic API)
-        let temperature = if thinking.is_some() { Some(1.0) } else { None };
-
-        let request = MessageRequest {
-            model: self.model.clone(),
-            max_tokens: self.max_tokens,
-            messages,
-            system,
-            tools: if tools.is_empty() { None } else { Some(tools) },
-            tool_choice,
-            thinking,
-            temperature,
-            stream: Some(true),
-        };
+        let format = generation.as_ref().and_then(|g| g.response_format.clone());
+        let request = self.build_tools_request(
+            messages, system, tools, tool_choice, thinking, generation, true,
+        );
 
-        self.send_streaming_request(&request, session_id).await
+        let stream = self.send_streaming_request(&request, session_id).await?;
+        Ok(match format {
+            Some(format) => json_tool::unwrap_stream(format, stream),
+            None => stream,
+        })
     }
 
     /// Send a streaming request to the Anthropic API
+    #[tracing::instrument(name = "llm_stream", skip_all, fields(provider = "anthropic", model = %request.model, session_id = session_id.unwrap_or_default()))]
     async fn send_streaming_request(
         &self,
         request: &MessageRequest,
@@ -509,27 +562,20 @@ impl AnthropicProvider {
             .context("Failed to get authentication credentials")?;
         let api_url = auth_config.base_url.as_deref().unwrap_or(DEFAULT_API_URL);
 
-        let request_json =
-            serde_json::to_string(request).context("Failed to serialize request")?;
-        tracing::debug!("Request JSON: {}", request_json);
-
-        let mut request_builder = self
-            .client
-            .post(api_url)
-            .header("Content-Type", "application/json")
-            .header("x-api-key", &auth_config.api_key)
-            .header("anthropic-version", ANTHROPIC_VERSION)
-            .header("anthropic-beta", "interleaved-thinking-2025-05-14");
-
+        let mut parts = self.http_parts(api_url, &auth_config.api_key, request)?;
         // Add agent-session-id header if session_id is provided
         if let Some(sid) = session_id {
-            request_builder = request_builder.header("X-Agent-Session-Id", sid);
+            parts.set_header("X-Agent-Session-Id", sid)?;
         }
 
+        let (request_builder, request_json) = prepare_request(&self.client, &self.interceptors, parts, request)?;
+        tracing::debug!("Request JSON: {}", request_json);
+
+        let started = self.stats.begin();
         let response = request_builder
-            .body(request_json)
             .send()
             .await
+            .inspect_err(|_| self.stats.record_error(ErrorClass::Network))
             .context("Failed to send streaming request to Anthropic API")?;
 
         let status = response.status();
@@ -539,10 +585,14 @@ impl AnthropicProvider {
                 .text()
                 .await
                 .unwrap_or_else(|_| "Failed to read error body".to_string());
+            notify_received(&self.interceptors, status, Some(&error_text));
             tracing::error!("API error: {} - {}", status, error_text);
+            self.stats.record_error(ErrorClass::from_status(status));
             anyhow::bail!("Anthropic API error ({}): {}", status, error_text);
         }
 
+        self.stats.record_started(started);
+        notify_received(&self.interceptors, status, None);
         tracing::info!("Streaming response started from Anthropic API");
 
         // Convert the response body stream to an async reader
@@ -559,11 +609,11 @@ impl AnthropicProvider {
             let mut current_data = String::new();
 
             while let Some(line) = lines.next_line().await? {
-                if line.starts_with("event: ") {
-                    current_event = Some(line[7..].to_string());
+                if let Some(event) = line.strip_prefix("event: ") {
+                    current_event = Some(event.to_string());
                     current_data.clear();
-                } else if line.starts_with("data: ") {
-                    current_data.push_str(&line[6..]);
+                } else if let Some(data) = line.strip_prefix("data: ") {
+                    current_data.push_str(data);
                 } else if line.is_empty() && current_event.is_some() {
                     // Empty line signals end of event
                     if let Some(ref event_type) = current_event {
@@ -586,7 +636,59 @@ impl AnthropicProvider {
             }
         };
 
-        Ok(Box::pin(stream))
+        Ok(self.stats.track_stream(Box::pin(stream)))
+    }
+}
+
+/// Build a tool-calling request, applying sampling parameters
+///
+/// When thinking is enabled, temperature is forced to 1 (required by the
+/// Anthropic API) and a configured temperature is ignored. A JSON response
+/// format adds the hidden tool from `json_tool` (callers unwrap the
+/// response). Shared with the Bedrock provider, which sends the same body.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn build_tools_request(
+    model: &str,
+    max_tokens: u32,
+    messages: Vec<Message>,
+    system: Option<SystemPrompt>,
+    tools: Vec<ToolDefinition>,
+    tool_choice: Option<ToolChoice>,
+    thinking: Option<ThinkingConfig>,
+    generation: Option<GenerationParams>,
+    stream: bool,
+) -> MessageRequest {
+    let generation = generation.unwrap_or_default();
+    let mut tools = tools;
+    let tool_choice = match &generation.response_format {
+        Some(format) => json_tool::apply(format, &mut tools, tool_choice, thinking.is_some()),
+        None => tool_choice,
+    };
+    let temperature = if thinking.is_some() {
+        if generation.temperature.is_some_and(|t| t != 1.0) {
+            tracing::warn!("Ignoring temperature {:?}: thinking requires temperature 1", generation.temperature);
+        }
+        Some(1.0)
+    } else {
+        generation.temperature
+    };
+
+    MessageRequest {
+        model: model.to_string(),
+        max_tokens: generation.max_tokens.unwrap_or(max_tokens),
+        messages,
+        system,
+        tools: if tools.is_empty() { None } else { Some(tools) },
+        tool_choice,
+        thinking,
+        temperature,
+        top_p: generation.top_p,
+        stop_sequences: if generation.stop_sequences.is_empty() {
+            None
+        } else {
+            Some(generation.stop_sequences)
+        },
+        stream: stream.then_some(true),
     }
 }
 
@@ -626,10 +728,13 @@ impl LlmProvider for AnthropicProvider {
         tools: Vec<ToolDefinition>,
         tool_choice: Option<ToolChoice>,
         thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
         session_id: Option<&str>,
     ) -> Result<MessageResponse> {
-        self.send_with_tools_and_system(messages, system, tools, tool_choice, thinking, session_id)
-            .await
+        self.send_with_tools_and_system(
+            messages, system, tools, tool_choice, thinking, generation, session_id,
+        )
+        .await
     }
 
     async fn stream_with_tools_and_system(
@@ -639,20 +744,35 @@ impl LlmProvider for AnthropicProvider {
         tools: Vec<ToolDefinition>,
         tool_choice: Option<ToolChoice>,
         thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
         session_id: Option<&str>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>> {
-        self.stream_with_tools_and_system(messages, system, tools, tool_choice, thinking, session_id)
-            .await
+        self.stream_with_tools_and_system(
+            messages, system, tools, tool_choice, thinking, generation, session_id,
+        )
+        .await
     }
 
     fn model(&self) -> String {
         self.model.clone()
     }
 
+    fn max_tokens(&self) -> Option<u32> {
+        Some(self.max_tokens)
+    }
+
     fn provider_name(&self) -> &str {
         "anthropic"
     }
 
+    fn stats(&self) -> Option<ProviderStats> {
+        Some(self.stats.snapshot())
+    }
+
+    fn reset_stats(&self) {
+        self.stats.reset();
+    }
+
     fn create_variant(&self, model: &str, max_tokens: u32) -> Arc<dyn LlmProvider> {
         Arc::new(self.with_model_and_tokens_override(model, max_tokens))
     }
@@ -679,3 +799,364 @@ pub fn define_tool(
         cache_control: None,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{ContentBlock, ResponseFormat, StopReason};
+
+    fn request_json(thinking: Option<ThinkingConfig>, generation: Option<GenerationParams>) -> serde_json::Value {
+        let provider = AnthropicProvider::new("test-key").unwrap();
+        let request = provider.build_tools_request(
+            vec![Message::user("Hi")],
+            None,
+            vec![],
+            None,
+            thinking,
+            generation,
+            false,
+        );
+        serde_json::to_value(&request).unwrap()
+    }
+
+    #[test]
+    fn test_generation_params_serialization() {
+        let json = request_json(None, None);
+        assert!(json.get("temperature").is_none());
+        assert!(json.get("top_p").is_none());
+        assert!(json.get("stop_sequences").is_none());
+        assert_eq!(json["max_tokens"], 32000);
+
+        let generation = GenerationParams::new()
+            .with_temperature(0.25)
+            .with_top_p(0.5)
+            .with_max_tokens(1500)
+            .with_stop_sequences(vec!["END".to_string()]);
+        let json = request_json(None, Some(generation));
+        assert_eq!(json["max_tokens"], 1500);
+        assert_eq!(json["temperature"], 0.25);
+        assert_eq!(json["top_p"], 0.5);
+        assert_eq!(json["stop_sequences"], serde_json::json!(["END"]));
+    }
+
+    #[test]
+    fn test_thinking_forces_temperature_one() {
+        let generation = GenerationParams::new().with_temperature(0.25).with_top_p(0.5);
+        let json = request_json(Some(ThinkingConfig::enabled(2048)), Some(generation));
+        assert_eq!(json["temperature"], 1.0);
+        assert_eq!(json["top_p"], 0.5);
+        assert_eq!(json["thinking"]["budget_tokens"], 2048);
+    }
+
+    #[test]
+    fn test_interleaved_beta_follows_thinking_config() {
+        let provider = AnthropicProvider::new("test-key").unwrap();
+        let beta = |thinking: Option<ThinkingConfig>| {
+            let request = provider.build_tools_request(vec![], None, vec![], None, thinking, None, false);
+            let parts = provider.http_parts(DEFAULT_API_URL, "test-key", &request).unwrap();
+            parts.headers.get("anthropic-beta").map(|v| v.to_str().unwrap().to_string())
+        };
+        assert_eq!(beta(Some(ThinkingConfig::enabled(2048))).as_deref(), Some(INTERLEAVED_THINKING_BETA));
+        assert_eq!(beta(Some(ThinkingConfig::enabled(2048).with_interleaved(false))), None);
+        assert_eq!(beta(None), None);
+    }
+
+    fn provider_for(port: u16) -> AnthropicProvider {
+        AnthropicProvider::with_auth_provider(move || async move {
+            Ok(AuthConfig::with_base_url("test-key", format!("http://127.0.0.1:{port}/v1/messages")))
+        })
+        .with_model("test-model")
+    }
+
+    #[tokio::test]
+    async fn test_interceptors_reach_the_wire() {
+        use crate::llm::interceptor::tests::{capture_server, RecordStatus, TagRequests};
+
+        let body = serde_json::json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "content": [{"type": "text", "text": "hi"}],
+            "model": "test-model",
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": {"input_tokens": 1, "output_tokens": 1}
+        });
+        let (port, captured) = capture_server("application/json", body.to_string()).await;
+        let statuses = Arc::new(RecordStatus::default());
+        let provider = provider_for(port)
+            .with_interceptor(Arc::new(TagRequests))
+            .with_interceptor(statuses.clone());
+
+        let response = provider
+            .send_with_tools_and_system(vec![Message::user("Hi")], None, vec![], None, None, None, Some("s1"))
+            .await
+            .unwrap();
+        assert_eq!(response.text(), "hi");
+
+        let (head, request_body) = captured.lock().unwrap()[0].clone();
+        assert!(head.contains("x-gateway-team: evals"), "{head}");
+        assert!(head.contains("x-api-key: test-key"));
+        assert!(head.contains("agent-session-id: s1"));
+        let request_body: serde_json::Value = serde_json::from_str(&request_body).unwrap();
+        assert_eq!(request_body["metadata"]["team"], "evals");
+        assert_eq!(request_body["model"], "test-model");
+
+        let statuses = statuses.0.lock().unwrap();
+        assert_eq!(statuses[0].0, 200);
+        assert_eq!(statuses[0].1["id"], "msg_1");
+    }
+
+    #[tokio::test]
+    async fn test_interceptors_apply_to_streaming_requests() {
+        use crate::llm::interceptor::tests::{capture_server, TagRequests};
+
+        let sse = "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n".to_string();
+        let (port, captured) = capture_server("text/event-stream", sse).await;
+        let provider = provider_for(port).with_interceptor(Arc::new(TagRequests));
+
+        let stream = provider
+            .stream_with_tools_and_system(vec![Message::user("Hi")], None, vec![], None, None, None, None)
+            .await
+            .unwrap();
+        let events: Vec<_> = stream.collect().await;
+        assert!(events.iter().all(|e| e.is_ok()));
+
+        let (head, request_body) = captured.lock().unwrap()[0].clone();
+        assert!(head.contains("x-gateway-team: evals"), "{head}");
+        let request_body: serde_json::Value = serde_json::from_str(&request_body).unwrap();
+        assert_eq!(request_body["metadata"]["team"], "evals");
+        assert_eq!(request_body["stream"], true);
+    }
+
+    fn person_format() -> ResponseFormat {
+        ResponseFormat::json_schema(serde_json::json!({
+            "type": "object",
+            "properties": {"name": {"type": "string"}, "age": {"type": "integer"}},
+            "required": ["name"]
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_json_response_format_uses_a_hidden_tool() {
+        use crate::llm::interceptor::tests::capture_server;
+
+        let body = serde_json::json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "content": [{
+                "type": "tool_use",
+                "id": "toolu_1",
+                "name": "json_response",
+                "input": {"name": "Ada", "age": 36}
+            }],
+            "model": "test-model",
+            "stop_reason": "tool_use",
+            "stop_sequence": null,
+            "usage": {"input_tokens": 1, "output_tokens": 1}
+        });
+        let (port, captured) = capture_server("application/json", body.to_string()).await;
+        let generation = GenerationParams::new().with_response_format(person_format());
+
+        let response = provider_for(port)
+            .send_with_tools_and_system(vec![Message::user("Who?")], None, vec![], None, None, Some(generation), None)
+            .await
+            .unwrap();
+        assert_eq!(response.text(), r#"{"age":36,"name":"Ada"}"#);
+        assert_eq!(response.stop_reason, Some(StopReason::EndTurn));
+        assert!(!response.content.iter().any(|b| matches!(b, ContentBlock::ToolUse { .. })));
+
+        let (_, request_body) = captured.lock().unwrap()[0].clone();
+        let request_body: serde_json::Value = serde_json::from_str(&request_body).unwrap();
+        assert_eq!(request_body["tools"][0]["name"], "json_response");
+        assert_eq!(request_body["tools"][0]["input_schema"]["required"], serde_json::json!(["name"]));
+        assert_eq!(request_body["tool_choice"], serde_json::json!({"type": "tool", "name": "json_response"}));
+    }
+
+    #[tokio::test]
+    async fn test_streamed_json_response_becomes_text() {
+        use crate::llm::interceptor::tests::capture_server;
+        use crate::llm::types::{ContentDelta, MessageDeltaEvent};
+
+        let events = [
+            r#"{"type":"message_start","message":{"id":"msg_1","type":"message","role":"assistant","content":[],"model":"test-model","stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":1,"output_tokens":0}}}"#,
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"tool_use","id":"toolu_1","name":"json_response","input":{}}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"input_json_delta","partial_json":"{\"name\": \"A"}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"input_json_delta","partial_json":"da\"}"}}"#,
+            r#"{"type":"content_block_stop","index":0}"#,
+            r#"{"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":5}}"#,
+            r#"{"type":"message_stop"}"#,
+        ];
+        let sse: String = events
+            .iter()
+            .map(|data| {
+                let kind: serde_json::Value = serde_json::from_str(data).unwrap();
+                format!("event: {}\ndata: {}\n\n", kind["type"].as_str().unwrap(), data)
+            })
+            .collect();
+        let (port, _) = capture_server("text/event-stream", sse).await;
+        let generation = GenerationParams::new().with_response_format(person_format());
+
+        let stream = provider_for(port)
+            .stream_with_tools_and_system(vec![Message::user("Who?")], None, vec![], None, None, Some(generation), None)
+            .await
+            .unwrap();
+        let events: Vec<StreamEvent> = stream.map(|e| e.unwrap()).collect().await;
+
+        assert!(!events.iter().any(|e| matches!(
+            e,
+            StreamEvent::ContentBlockStart(start) if !matches!(start.content_block, crate::llm::ContentBlockStart::Text { .. })
+        )));
+        let text: String = events
+            .iter()
+            .filter_map(|e| match e {
+                StreamEvent::ContentBlockDelta(delta) => match &delta.delta {
+                    ContentDelta::TextDelta { text } => Some(text.as_str()),
+                    other => panic!("unexpected delta {:?}", other),
+                },
+                _ => None,
+            })
+            .collect();
+        assert_eq!(text, r#"{"name":"Ada"}"#);
+        assert!(events.iter().any(|e| matches!(
+            e,
+            StreamEvent::MessageDelta(MessageDeltaEvent { delta, .. }) if delta.stop_reason == Some(StopReason::EndTurn)
+        )));
+    }
+
+    fn message_body(input_tokens: u32, output_tokens: u32) -> String {
+        serde_json::json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "content": [{"type": "text", "text": "hi"}],
+            "model": "test-model",
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": {"input_tokens": input_tokens, "output_tokens": output_tokens}
+        })
+        .to_string()
+    }
+
+    async fn send_hi(provider: &dyn LlmProvider) -> Result<MessageResponse> {
+        provider
+            .send_with_tools_and_system(vec![Message::user("Hi")], None, vec![], None, None, None, None)
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_stats_count_mixed_outcomes() {
+        use crate::llm::interceptor::tests::{scripted_server, ScriptedResponse};
+        use std::time::Duration;
+
+        let json = "application/json";
+        let (port, _) = scripted_server(vec![
+            ScriptedResponse::ok(json, message_body(10, 4)),
+            ScriptedResponse::status(429, json, r#"{"type":"error"}"#.to_string()),
+            ScriptedResponse::status(500, json, r#"{"type":"error"}"#.to_string()),
+            ScriptedResponse::status(529, json, r#"{"type":"error"}"#.to_string()),
+            ScriptedResponse::ok(json, message_body(5, 2)).delayed(Duration::from_millis(300)),
+            ScriptedResponse::ok(json, "not json".to_string()),
+        ])
+        .await;
+        let provider = provider_for(port);
+
+        let mut outcomes = Vec::new();
+        for _ in 0..6 {
+            outcomes.push(send_hi(&provider).await.is_ok());
+        }
+        assert_eq!(outcomes, [true, false, false, false, true, false]);
+
+        let stats = LlmProvider::stats(&provider).unwrap();
+        assert_eq!(stats.requests, 6);
+        assert_eq!(stats.errors.rate_limited, 1);
+        assert_eq!(stats.errors.server, 1);
+        assert_eq!(stats.errors.overloaded, 1);
+        assert_eq!(stats.errors.parse, 1);
+        assert_eq!(stats.errors.total(), 4);
+        assert_eq!(stats.consecutive_failures, 1);
+        assert_eq!((stats.input_tokens, stats.output_tokens), (15, 6));
+        // One fast success and one above the 250ms bound
+        assert_eq!(stats.latency_buckets.iter().sum::<u64>(), 2);
+        assert_eq!(stats.latency_buckets[3..].iter().sum::<u64>(), 1);
+        assert!(stats.max_latency_ms >= 300);
+        assert!(stats.latency_p95().unwrap() >= Duration::from_millis(300));
+
+        provider.reset_stats();
+        assert_eq!(LlmProvider::stats(&provider).unwrap(), ProviderStats::default());
+    }
+
+    #[tokio::test]
+    async fn test_stats_track_streams() {
+        use crate::llm::interceptor::tests::{scripted_server, ScriptedResponse};
+
+        let events = [
+            r#"{"type":"message_start","message":{"id":"msg_1","type":"message","role":"assistant","content":[],"model":"test-model","stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":7,"output_tokens":1}}}"#,
+            r#"{"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":null},"usage":{"output_tokens":5}}"#,
+            r#"{"type":"message_stop"}"#,
+        ];
+        let sse: String = events
+            .iter()
+            .map(|data| {
+                let kind: serde_json::Value = serde_json::from_str(data).unwrap();
+                format!("event: {}\ndata: {}\n\n", kind["type"].as_str().unwrap(), data)
+            })
+            .collect();
+        let overloaded = r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#;
+        let (port, _) = scripted_server(vec![
+            ScriptedResponse::ok("text/event-stream", sse),
+            ScriptedResponse::status(529, "application/json", overloaded.to_string()),
+        ])
+        .await;
+        let provider = provider_for(port);
+        let stream = || {
+            provider.stream_with_tools_and_system(vec![Message::user("Hi")], None, vec![], None, None, None, None)
+        };
+
+        let events: Vec<_> = stream().await.unwrap().collect().await;
+        assert!(events.iter().all(|e| e.is_ok()));
+        assert!(stream().await.is_err());
+
+        let stats = LlmProvider::stats(&provider).unwrap();
+        assert_eq!(stats.requests, 2);
+        assert_eq!(stats.errors.overloaded, 1);
+        assert_eq!(stats.errors.total(), 1);
+        assert_eq!(stats.consecutive_failures, 1);
+        assert_eq!((stats.input_tokens, stats.output_tokens), (7, 5));
+        assert_eq!(stats.latency_buckets.iter().sum::<u64>(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_swappable_adds_up_its_providers() {
+        use crate::llm::interceptor::tests::{scripted_server, ScriptedResponse};
+        use crate::llm::SwappableLlmProvider;
+
+        let json = "application/json";
+        let (first_port, _) = scripted_server(vec![
+            ScriptedResponse::ok(json, message_body(10, 4)),
+            ScriptedResponse::status(503, json, "{}".to_string()),
+        ])
+        .await;
+        let (second_port, _) = scripted_server(vec![ScriptedResponse::ok(json, message_body(3, 1))]).await;
+
+        let swappable = SwappableLlmProvider::new(Arc::new(provider_for(first_port)));
+        let handle = swappable.handle();
+        send_hi(&swappable).await.unwrap();
+        send_hi(&swappable).await.unwrap_err();
+        assert_eq!(swappable.stats().unwrap().consecutive_failures, 1);
+
+        handle.set_provider(Arc::new(provider_for(second_port))).await;
+        send_hi(&swappable).await.unwrap();
+
+        let stats = swappable.stats().unwrap();
+        assert_eq!(stats.requests, 3);
+        assert_eq!(stats.errors.overloaded, 1);
+        assert_eq!(stats.consecutive_failures, 0);
+        assert_eq!((stats.input_tokens, stats.output_tokens), (13, 5));
+        assert_eq!(stats.latency_buckets.iter().sum::<u64>(), 2);
+
+        swappable.reset_stats();
+        assert_eq!(swappable.stats().unwrap(), ProviderStats::default());
+    }
+}
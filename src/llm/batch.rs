@@ -0,0 +1,601 @@
+//! Anthropic Message Batches API
+//!
+//! Submits many `MessageRequest`s at once for asynchronous processing, at a
+//! lower cost than the interactive endpoint. Batches usually finish within
+//! minutes but may take up to 24 hours, so this is meant for offline work
+//! such as nightly evaluations rather than interactive agents.
+//!
+//! # Lifecycle
+//!
+//! ```ignore
+//! let client = AnthropicBatchClient::from_env()?;
+//!
+//! let id = client.submit(requests).await?;
+//! let status = client.wait(&id, Duration::from_secs(30)).await?;
+//! println!("{} succeeded, {} errored", status.request_counts.succeeded, status.request_counts.errored);
+//!
+//! // One entry per submitted request, in submission order
+//! for result in client.results(&id).await? {
+//!     match result {
+//!         Ok(response) => println!("{}", response.text()),
+//!         Err(e) => eprintln!("{}", e),
+//!     }
+//! }
+//! ```
+
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::anthropic::{wants_interleaved_thinking, ANTHROPIC_VERSION, DEFAULT_API_URL, INTERLEAVED_THINKING_BETA};
+use super::auth::{auth_provider, AuthConfig, AuthProvider, AuthSource};
+use super::types::{MessageRequest, MessageResponse};
+
+/// Identifier of a submitted batch (e.g. `msgbatch_01...`)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BatchId(pub String);
+
+impl BatchId {
+    /// The raw batch id
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for BatchId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Processing state of a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessingStatus {
+    /// Requests are still being processed
+    InProgress,
+    /// A cancellation was requested and is being applied
+    Canceling,
+    /// Every request has a result
+    Ended,
+}
+
+/// Number of requests in each state
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestCounts {
+    #[serde(default)]
+    pub processing: u32,
+    #[serde(default)]
+    pub succeeded: u32,
+    #[serde(default)]
+    pub errored: u32,
+    #[serde(default)]
+    pub canceled: u32,
+    #[serde(default)]
+    pub expired: u32,
+}
+
+impl RequestCounts {
+    /// Total number of requests in the batch
+    pub fn total(&self) -> u32 {
+        self.processing + self.succeeded + self.errored + self.canceled + self.expired
+    }
+}
+
+/// Status of a batch, as returned by `poll`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchStatus {
+    pub id: BatchId,
+    pub processing_status: ProcessingStatus,
+    pub request_counts: RequestCounts,
+    /// Where results can be downloaded (set once the batch has ended)
+    #[serde(default)]
+    pub results_url: Option<String>,
+}
+
+impl BatchStatus {
+    /// Whether every request has a result
+    pub fn is_ended(&self) -> bool {
+        self.processing_status == ProcessingStatus::Ended
+    }
+}
+
+/// Client for the Anthropic Message Batches API
+///
+/// Uses the same authentication as `AnthropicProvider` (static key or an
+/// `AuthProvider` callback). A custom `base_url` is the messages URL; the
+/// batches endpoint is derived from it by appending `/batches`.
+#[derive(Clone)]
+pub struct AnthropicBatchClient {
+    client: Client,
+    auth: AuthSource,
+    model: String,
+    max_tokens: u32,
+}
+
+impl AnthropicBatchClient {
+    /// Create a batch client from environment variables
+    ///
+    /// Reads the same variables as `AnthropicProvider::from_env`:
+    /// `ANTHROPIC_API_KEY` (required), `ANTHROPIC_MODEL`, `ANTHROPIC_BASE_URL`
+    /// and `ANTHROPIC_MAX_TOKENS` (optional).
+    pub fn from_env() -> Result<Self> {
+        let api_key = env::var("ANTHROPIC_API_KEY")
+            .context("ANTHROPIC_API_KEY environment variable not set")?;
+        let base_url = env::var("ANTHROPIC_BASE_URL").ok();
+        let model = env::var("ANTHROPIC_MODEL").unwrap_or_default();
+        let max_tokens = env::var("ANTHROPIC_MAX_TOKENS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(32000);
+
+        Ok(Self::from_auth(
+            AuthSource::Static(AuthConfig { api_key, base_url }),
+            model,
+            max_tokens,
+        ))
+    }
+
+    /// Create a batch client with a specific API key
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self::from_auth(AuthSource::Static(AuthConfig::new(api_key)), String::new(), 32000)
+    }
+
+    /// Create a batch client with an auth provider callback
+    pub fn with_auth_provider<F, Fut>(provider: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<AuthConfig>> + Send + 'static,
+    {
+        Self::with_auth_provider_boxed(Arc::new(auth_provider(provider)))
+    }
+
+    /// Create a batch client with a trait object auth provider
+    pub fn with_auth_provider_boxed(provider: Arc<dyn AuthProvider>) -> Self {
+        Self::from_auth(AuthSource::Dynamic(provider), String::new(), 32000)
+    }
+
+    pub(crate) fn from_auth(auth: AuthSource, model: String, max_tokens: u32) -> Self {
+        Self {
+            client: Client::new(),
+            auth,
+            model,
+            max_tokens,
+        }
+    }
+
+    /// Set the model used by requests built from this client
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Set the max tokens used by requests built from this client
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// The model used by requests built from this client
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// The max tokens used by requests built from this client
+    pub fn max_tokens(&self) -> u32 {
+        self.max_tokens
+    }
+
+    /// Submit requests as a new batch
+    ///
+    /// Streaming is not supported in batches, so `stream` is cleared on every
+    /// request. Results are matched back to the order of `requests`.
+    pub async fn submit(&self, requests: Vec<MessageRequest>) -> Result<BatchId> {
+        if requests.is_empty() {
+            anyhow::bail!("Cannot submit an empty batch");
+        }
+        let interleaved = requests.iter().any(wants_interleaved_thinking);
+        let entries: Vec<Value> = requests
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut request)| {
+                request.stream = None;
+                serde_json::json!({ "custom_id": custom_id(i), "params": request })
+            })
+            .collect();
+        let count = entries.len();
+        let body = serde_json::json!({ "requests": entries });
+
+        let auth = self.auth().await?;
+        let url = batches_url(&auth);
+        tracing::info!("[BatchClient] Submitting batch of {} requests", count);
+        let mut builder = self.request(self.client.post(&url), &auth);
+        if interleaved {
+            builder = builder.header("anthropic-beta", INTERLEAVED_THINKING_BETA);
+        }
+        let response = builder
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&body).context("Failed to serialize batch")?)
+            .send()
+            .await
+            .context("Failed to submit batch")?;
+        let status: BatchStatus = parse_json(response).await?;
+        tracing::info!("[BatchClient] Submitted batch {}", status.id);
+        Ok(status.id)
+    }
+
+    /// Fetch the current status of a batch
+    pub async fn poll(&self, id: &BatchId) -> Result<BatchStatus> {
+        let auth = self.auth().await?;
+        let url = format!("{}/{}", batches_url(&auth), id);
+        let response = self
+            .request(self.client.get(&url), &auth)
+            .send()
+            .await
+            .context("Failed to poll batch")?;
+        parse_json(response).await
+    }
+
+    /// Poll until the batch has ended, sleeping `interval` between polls
+    pub async fn wait(&self, id: &BatchId, interval: Duration) -> Result<BatchStatus> {
+        loop {
+            let status = self.poll(id).await?;
+            if status.is_ended() {
+                return Ok(status);
+            }
+            tracing::debug!(
+                "[BatchClient] Batch {} still processing ({} of {} done)",
+                id,
+                status.request_counts.total() - status.request_counts.processing,
+                status.request_counts.total()
+            );
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Download the results of an ended batch
+    ///
+    /// Returns one entry per submitted request, in submission order. Errored,
+    /// canceled and expired requests are `Err` entries; the batch is still
+    /// `Ok` as long as the results could be downloaded.
+    pub async fn results(&self, id: &BatchId) -> Result<Vec<Result<MessageResponse>>> {
+        let status = self.poll(id).await?;
+        if !status.is_ended() {
+            anyhow::bail!("Batch {} has not ended yet", id);
+        }
+        let url = status
+            .results_url
+            .clone()
+            .with_context(|| format!("Batch {} has no results URL", id))?;
+
+        let auth = self.auth().await?;
+        let response = self
+            .request(self.client.get(&url), &auth)
+            .send()
+            .await
+            .context("Failed to download batch results")?;
+        let code = response.status();
+        let text = response.text().await.context("Failed to read batch results")?;
+        if !code.is_success() {
+            anyhow::bail!("Anthropic API error ({}): {}", code, text);
+        }
+
+        let total = status.request_counts.total() as usize;
+        let mut slots: Vec<Option<Result<MessageResponse>>> = (0..total).map(|_| None).collect();
+        for line in text.lines().filter(|l| !l.trim().is_empty()) {
+            let entry: ResultLine =
+                serde_json::from_str(line).context("Failed to parse batch result line")?;
+            let Some(index) = parse_custom_id(&entry.custom_id) else {
+                tracing::warn!("[BatchClient] Ignoring result with unknown id {}", entry.custom_id);
+                continue;
+            };
+            if index >= slots.len() {
+                slots.resize_with(index + 1, || None);
+            }
+            slots[index] = Some(entry.into_result());
+        }
+
+        Ok(slots
+            .into_iter()
+            .enumerate()
+            .map(|(i, slot)| {
+                slot.unwrap_or_else(|| Err(anyhow::anyhow!("No result for request {}", custom_id(i))))
+            })
+            .collect())
+    }
+
+    async fn auth(&self) -> Result<AuthConfig> {
+        self.auth
+            .get_auth()
+            .await
+            .context("Failed to get authentication credentials")
+    }
+
+    fn request(&self, builder: reqwest::RequestBuilder, auth: &AuthConfig) -> reqwest::RequestBuilder {
+        builder
+            .header("x-api-key", &auth.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+    }
+}
+
+/// One line of the results JSONL file
+#[derive(Deserialize)]
+struct ResultLine {
+    custom_id: String,
+    result: Value,
+}
+
+impl ResultLine {
+    fn into_result(self) -> Result<MessageResponse> {
+        match self.result.get("type").and_then(Value::as_str) {
+            Some("succeeded") => {
+                let message = self.result.get("message").cloned().unwrap_or(Value::Null);
+                serde_json::from_value(message).context("Failed to parse batch message")
+            }
+            Some("errored") => {
+                // {"type":"errored","error":{"type":"error","error":{"type":..,"message":..}}}
+                let error = self.result.get("error");
+                let detail = error.and_then(|e| e.get("error")).or(error);
+                let kind = detail.and_then(|d| d.get("type")).and_then(Value::as_str).unwrap_or("error");
+                let message = detail.and_then(|d| d.get("message")).and_then(Value::as_str).unwrap_or("");
+                Err(anyhow::anyhow!("Request {} errored ({}): {}", self.custom_id, kind, message))
+            }
+            Some(other) => Err(anyhow::anyhow!("Request {} {}", self.custom_id, other)),
+            None => Err(anyhow::anyhow!("Request {} has no result type", self.custom_id)),
+        }
+    }
+}
+
+fn custom_id(index: usize) -> String {
+    format!("req-{}", index)
+}
+
+fn parse_custom_id(id: &str) -> Option<usize> {
+    id.strip_prefix("req-")?.parse().ok()
+}
+
+fn batches_url(auth: &AuthConfig) -> String {
+    let messages_url = auth.base_url.as_deref().unwrap_or(DEFAULT_API_URL);
+    format!("{}/batches", messages_url.trim_end_matches('/'))
+}
+
+async fn parse_json<T: serde::de::DeserializeOwned>(response: reqwest::Response) -> Result<T> {
+    let status = response.status();
+    let text = response.text().await.context("Failed to read response body")?;
+    if !status.is_success() {
+        tracing::error!("[BatchClient] API error: {} - {}", status, text);
+        anyhow::bail!("Anthropic API error ({}): {}", status, text);
+    }
+    serde_json::from_str(&text).context("Failed to parse API response")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::llm::Message;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// A fake batches endpoint
+    pub(crate) struct MockBatchApi {
+        pub port: u16,
+        /// Body of every submit request
+        pub submitted: Arc<Mutex<Vec<Value>>>,
+    }
+
+    impl MockBatchApi {
+        pub fn client(&self) -> AnthropicBatchClient {
+            let port = self.port;
+            AnthropicBatchClient::with_auth_provider(move || async move {
+                Ok(AuthConfig::with_base_url("test-key", format!("http://127.0.0.1:{port}/v1/messages")))
+            })
+            .with_model("test-model")
+        }
+    }
+
+    /// Serve a batch that stays in progress for `polls_before_end` polls and
+    /// then ends with `results` (JSONL) for `total` requests
+    pub(crate) async fn mock_batch_api(polls_before_end: usize, total: u32, results: String) -> MockBatchApi {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let submitted = Arc::new(Mutex::new(Vec::new()));
+        let polls = Arc::new(AtomicUsize::new(0));
+        let results = Arc::new(results);
+        let bodies = submitted.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let (bodies, polls, results) = (bodies.clone(), polls.clone(), results.clone());
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 4096];
+                    let header_end = loop {
+                        if let Some(i) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                            break i + 4;
+                        }
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    };
+                    let head = String::from_utf8_lossy(&request[..header_end]).to_ascii_lowercase();
+                    let length: usize = head
+                        .lines()
+                        .find_map(|l| l.strip_prefix("content-length:"))
+                        .and_then(|l| l.trim().parse().ok())
+                        .unwrap_or(0);
+                    while request.len() < header_end + length {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => break,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    assert!(head.contains("x-api-key: test-key"));
+
+                    let status = |ended: bool| {
+                        let processing = if ended { 0 } else { total };
+                        serde_json::json!({
+                            "id": "msgbatch_1",
+                            "type": "message_batch",
+                            "processing_status": if ended { "ended" } else { "in_progress" },
+                            "request_counts": {
+                                "processing": processing,
+                                "succeeded": total - processing,
+                                "errored": 0,
+                                "canceled": 0,
+                                "expired": 0
+                            },
+                            "results_url": ended
+                                .then(|| format!("http://127.0.0.1:{port}/v1/messages/batches/msgbatch_1/results")),
+                        })
+                        .to_string()
+                    };
+                    let (content_type, body) = if head.starts_with("post /v1/messages/batches ") {
+                        let body: Value = serde_json::from_slice(&request[header_end..]).unwrap();
+                        bodies.lock().unwrap().push(body);
+                        ("application/json", status(false))
+                    } else if head.starts_with("get /v1/messages/batches/msgbatch_1/results ") {
+                        ("application/binary", results.to_string())
+                    } else if head.starts_with("get /v1/messages/batches/msgbatch_1 ") {
+                        let ended = polls.fetch_add(1, Ordering::SeqCst) >= polls_before_end;
+                        ("application/json", status(ended))
+                    } else {
+                        panic!("unexpected request: {head}");
+                    };
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+                        Connection: close\r\n\r\n{}",
+                        content_type,
+                        body.len(),
+                        body
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        MockBatchApi { port, submitted }
+    }
+
+    pub(crate) fn succeeded_line(custom_id: &str, text: &str) -> String {
+        serde_json::json!({
+            "custom_id": custom_id,
+            "result": {
+                "type": "succeeded",
+                "message": {
+                    "id": format!("msg_{custom_id}"),
+                    "type": "message",
+                    "role": "assistant",
+                    "content": [{"type": "text", "text": text}],
+                    "model": "test-model",
+                    "stop_reason": "end_turn",
+                    "stop_sequence": null,
+                    "usage": {"input_tokens": 10, "output_tokens": 5}
+                }
+            }
+        })
+        .to_string()
+    }
+
+    pub(crate) fn errored_line(custom_id: &str, message: &str) -> String {
+        serde_json::json!({
+            "custom_id": custom_id,
+            "result": {
+                "type": "errored",
+                "error": {"type": "error", "error": {"type": "invalid_request_error", "message": message}}
+            }
+        })
+        .to_string()
+    }
+
+    fn request(prompt: &str) -> MessageRequest {
+        MessageRequest {
+            model: "test-model".to_string(),
+            max_tokens: 100,
+            messages: vec![Message::user(prompt)],
+            system: None,
+            tools: None,
+            tool_choice: None,
+            thinking: None,
+            temperature: None,
+            top_p: None,
+            stop_sequences: None,
+            stream: Some(true),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_submit_poll_results_lifecycle() {
+        // Results arrive out of order, with one failure and one expiry
+        let results = [
+            errored_line("req-1", "prompt is too long"),
+            succeeded_line("req-2", "third"),
+            serde_json::json!({"custom_id": "req-3", "result": {"type": "expired"}}).to_string(),
+            succeeded_line("req-0", "first"),
+        ]
+        .join("\n");
+        let api = mock_batch_api(2, 4, results).await;
+        let client = api.client();
+
+        let id = client
+            .submit(vec![request("a"), request("b"), request("c"), request("d")])
+            .await
+            .unwrap();
+        assert_eq!(id, BatchId("msgbatch_1".to_string()));
+
+        let submitted = api.submitted.lock().unwrap()[0].clone();
+        let entries = submitted["requests"].as_array().unwrap();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[1]["custom_id"], "req-1");
+        assert_eq!(entries[1]["params"]["messages"][0]["content"], "b");
+        assert!(entries[1]["params"].get("stream").is_none());
+
+        let status = client.poll(&id).await.unwrap();
+        assert_eq!(status.processing_status, ProcessingStatus::InProgress);
+        assert!(client.results(&id).await.is_err(), "results before the batch ended");
+
+        let status = client.wait(&id, Duration::from_millis(1)).await.unwrap();
+        assert!(status.is_ended());
+        assert_eq!(status.request_counts.total(), 4);
+
+        let results = client.results(&id).await.unwrap();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap().text(), "first");
+        let err = results[1].as_ref().unwrap_err().to_string();
+        assert!(err.contains("invalid_request_error") && err.contains("prompt is too long"), "{err}");
+        assert_eq!(results[2].as_ref().unwrap().text(), "third");
+        assert!(results[3].as_ref().unwrap_err().to_string().contains("expired"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_results_are_errors() {
+        let api = mock_batch_api(0, 2, succeeded_line("req-1", "second")).await;
+        let client = api.client();
+        let id = client.submit(vec![request("a"), request("b")]).await.unwrap();
+
+        let results = client.results(&id).await.unwrap();
+        assert!(results[0].as_ref().unwrap_err().to_string().contains("No result for request req-0"));
+        assert_eq!(results[1].as_ref().unwrap().text(), "second");
+    }
+
+    #[tokio::test]
+    async fn test_empty_batch_is_rejected() {
+        let client = AnthropicBatchClient::new("key");
+        assert!(client.submit(Vec::new()).await.is_err());
+    }
+
+    #[test]
+    fn test_batches_url() {
+        assert_eq!(batches_url(&AuthConfig::new("k")), "https://api.anthropic.com/v1/messages/batches");
+        assert_eq!(
+            batches_url(&AuthConfig::with_base_url("k", "https://proxy.example/v1/messages/")),
+            "https://proxy.example/v1/messages/batches"
+        );
+    }
+}
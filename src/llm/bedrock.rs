@@ -0,0 +1,471 @@
+//! AWS Bedrock client for Anthropic models
+//!
+//! Calls `bedrock-runtime` InvokeModel / InvokeModelWithResponseStream with
+//! `anthropic.claude-*` model ids (or inference profiles such as
+//! `us.anthropic.claude-*`). Bedrock takes nearly the same JSON body as the
+//! Anthropic Messages API, so the request and response types are shared with
+//! `AnthropicProvider`; the differences are handled here:
+//! - The body carries `anthropic_version` instead of a header, and no `model`
+//!   or `stream` field (the model id and streaming are part of the call)
+//! - Beta flags go in an `anthropic_beta` body field
+//! - Stream events arrive as event-stream chunks holding one JSON event each,
+//!   instead of SSE
+//!
+//! Requires the `bedrock` feature.
+//!
+//! # Authentication
+//!
+//! Credentials and region come from the default AWS chain (environment,
+//! profile files, SSO, instance metadata), optionally with an explicit
+//! region or profile:
+//!
+//! ```ignore
+//! // Default chain, model from BEDROCK_MODEL
+//! let llm = BedrockProvider::from_env().await?;
+//!
+//! // Explicit region and profile
+//! let llm = BedrockProvider::load(Some("us-west-2"), Some("prod"))
+//!     .await
+//!     .with_model("us.anthropic.claude-sonnet-4-5-20250929-v1:0");
+//! ```
+
+use anyhow::{Context, Result};
+use aws_sdk_bedrockruntime::error::DisplayErrorContext;
+use aws_sdk_bedrockruntime::primitives::Blob;
+use aws_sdk_bedrockruntime::types::ResponseStream;
+use futures::stream::Stream;
+use serde_json::Value;
+use std::env;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use super::anthropic::{build_tools_request, wants_interleaved_thinking, INTERLEAVED_THINKING_BETA};
+use super::json_tool;
+use super::provider::LlmProvider;
+use super::types::{
+    GenerationParams, Message, MessageRequest, MessageResponse, RawStreamEvent, StreamEvent,
+    SystemPrompt, ThinkingConfig, ToolChoice, ToolDefinition,
+};
+
+/// `anthropic_version` Bedrock expects in the request body
+const BEDROCK_ANTHROPIC_VERSION: &str = "bedrock-2023-05-31";
+
+/// Anthropic models on AWS Bedrock
+pub struct BedrockProvider {
+    client: aws_sdk_bedrockruntime::Client,
+    model: String,
+    max_tokens: u32,
+}
+
+impl BedrockProvider {
+    /// Create a new Bedrock provider from environment variables
+    ///
+    /// Reads from:
+    /// - `BEDROCK_MODEL` (required, e.g. `anthropic.claude-sonnet-4-5-20250929-v1:0`)
+    /// - `BEDROCK_MAX_TOKENS` (optional, defaults to 32000)
+    /// - `AWS_REGION` / `AWS_PROFILE` and the rest of the default AWS chain
+    pub async fn from_env() -> Result<Self> {
+        tracing::info!("Creating Bedrock provider from environment");
+
+        let model = env::var("BEDROCK_MODEL")
+            .context("BEDROCK_MODEL environment variable not set")?;
+
+        let max_tokens = env::var("BEDROCK_MAX_TOKENS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(32000); // Must be > thinking.budget_tokens (16000)
+
+        tracing::info!("Using model: {}", model);
+        tracing::info!("Max tokens: {}", max_tokens);
+
+        Ok(Self::load(None, None).await.with_model(model).with_max_tokens(max_tokens))
+    }
+
+    /// Create a provider from the default AWS chain, overriding the region
+    /// and/or profile
+    pub async fn load(region: Option<&str>, profile: Option<&str>) -> Self {
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+        if let Some(region) = region {
+            loader = loader.region(aws_config::Region::new(region.to_string()));
+        }
+        if let Some(profile) = profile {
+            loader = loader.profile_name(profile);
+        }
+        Self::from_sdk_config(&loader.load().await)
+    }
+
+    /// Create a provider from an already loaded AWS config
+    pub fn from_sdk_config(config: &aws_config::SdkConfig) -> Self {
+        Self::from_client(aws_sdk_bedrockruntime::Client::new(config))
+    }
+
+    /// Create a provider from a Bedrock runtime client
+    pub fn from_client(client: aws_sdk_bedrockruntime::Client) -> Self {
+        Self {
+            client,
+            model: "".to_string(),
+            max_tokens: 32000,
+        }
+    }
+
+    /// Set the model id to use
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Set the max tokens for responses
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Create a variant with different model/tokens, sharing the same client
+    fn create_variant_impl(&self, model: &str, max_tokens: u32) -> Self {
+        Self {
+            client: self.client.clone(),
+            model: model.to_string(),
+            max_tokens,
+        }
+    }
+
+    /// Send a request with InvokeModel
+    #[tracing::instrument(name = "llm_request", skip_all, fields(provider = "bedrock", model = %self.model))]
+    async fn invoke(&self, request: &MessageRequest) -> Result<MessageResponse> {
+        let body = bedrock_body(request)?;
+        tracing::debug!("[Bedrock] Request JSON: {}", body);
+
+        let output = self
+            .client
+            .invoke_model()
+            .model_id(&self.model)
+            .content_type("application/json")
+            .accept("application/json")
+            .body(Blob::new(body.into_bytes()))
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Bedrock API error: {}", DisplayErrorContext(e)))?;
+
+        let response_text = String::from_utf8_lossy(output.body().as_ref());
+        tracing::debug!("[Bedrock] Response body: {}", response_text);
+
+        serde_json::from_str(&response_text).context("Failed to parse Bedrock response")
+    }
+
+    /// Send a request with InvokeModelWithResponseStream
+    #[tracing::instrument(name = "llm_stream", skip_all, fields(provider = "bedrock", model = %self.model))]
+    async fn invoke_streaming(
+        &self,
+        request: &MessageRequest,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>> {
+        let body = bedrock_body(request)?;
+        tracing::debug!("[Bedrock] Streaming request JSON: {}", body);
+
+        let output = self
+            .client
+            .invoke_model_with_response_stream()
+            .model_id(&self.model)
+            .content_type("application/json")
+            .accept("application/json")
+            .body(Blob::new(body.into_bytes()))
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Bedrock API error: {}", DisplayErrorContext(e)))?;
+
+        tracing::info!("[Bedrock] Streaming response started");
+
+        let mut receiver = output.body;
+        let stream = async_stream::try_stream! {
+            loop {
+                let chunk = receiver
+                    .recv()
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Bedrock stream error: {}", DisplayErrorContext(e)))?;
+                let Some(chunk) = chunk else { break };
+                let ResponseStream::Chunk(part) = chunk else {
+                    tracing::debug!("[Bedrock] Ignoring unknown stream event");
+                    continue;
+                };
+                let Some(bytes) = part.bytes() else { continue };
+                if let Some(event) = parse_chunk(bytes.as_ref())? {
+                    yield event;
+                }
+            }
+        };
+
+        Ok(Box::pin(stream))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn build_request(
+        &self,
+        messages: Vec<Message>,
+        system: Option<SystemPrompt>,
+        tools: Vec<ToolDefinition>,
+        tool_choice: Option<ToolChoice>,
+        thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
+    ) -> MessageRequest {
+        // Server-side web search is not available on Bedrock
+        let tools: Vec<ToolDefinition> = tools
+            .into_iter()
+            .filter(|tool| {
+                let keep = !matches!(tool, ToolDefinition::WebSearch(_));
+                if !keep {
+                    tracing::warn!("[Bedrock] Web search is not supported; dropping the tool");
+                }
+                keep
+            })
+            .collect();
+        build_tools_request(
+            &self.model, self.max_tokens, messages, system, tools, tool_choice, thinking, generation, false,
+        )
+    }
+}
+
+/// Turn a Messages API request into a Bedrock InvokeModel body
+fn bedrock_body(request: &MessageRequest) -> Result<String> {
+    let mut body = serde_json::to_value(request).context("Failed to serialize request")?;
+    let map = body.as_object_mut().context("Request did not serialize to an object")?;
+    map.remove("model");
+    map.remove("stream");
+    map.insert("anthropic_version".to_string(), Value::from(BEDROCK_ANTHROPIC_VERSION));
+    if wants_interleaved_thinking(request) {
+        map.insert("anthropic_beta".to_string(), serde_json::json!([INTERLEAVED_THINKING_BETA]));
+    }
+    serde_json::to_string(&body).context("Failed to serialize request")
+}
+
+/// Parse one stream chunk (a single Messages API event as JSON)
+///
+/// Bedrock adds fields such as `amazon-bedrock-invocationMetrics` to the
+/// final event; they are ignored. Unknown event types are skipped.
+fn parse_chunk(bytes: &[u8]) -> Result<Option<StreamEvent>> {
+    let value: Value = serde_json::from_slice(bytes).context("Failed to parse Bedrock stream chunk")?;
+    let event_type = value.get("type").and_then(Value::as_str).unwrap_or_default().to_string();
+    match serde_json::from_value::<RawStreamEvent>(value) {
+        Ok(raw) => Ok(Some(raw.into_stream_event())),
+        Err(e) if matches!(
+            event_type.as_str(),
+            "message_start" | "content_block_start" | "content_block_delta" | "content_block_stop"
+                | "message_delta" | "message_stop" | "ping" | "error"
+        ) => {
+            Err(anyhow::anyhow!("Failed to parse Bedrock {} event: {}", event_type, e))
+        }
+        Err(_) => {
+            tracing::debug!("[Bedrock] Unknown stream event type: {}", event_type);
+            Ok(None)
+        }
+    }
+}
+
+// ============================================================================
+// LlmProvider implementation
+// ============================================================================
+
+#[async_trait::async_trait]
+impl LlmProvider for BedrockProvider {
+    async fn send_message(
+        &self,
+        user_message: &str,
+        conversation_history: &[Message],
+        system_prompt: Option<&str>,
+        _session_id: Option<&str>,
+    ) -> Result<String> {
+        tracing::info!("[Bedrock] Sending message");
+
+        let mut messages: Vec<Message> = conversation_history.to_vec();
+        messages.push(Message::user(user_message));
+
+        let system = system_prompt.map(|s| SystemPrompt::Text(s.to_string()));
+        let request = self.build_request(messages, system, Vec::new(), None, None, None);
+        Ok(self.invoke(&request).await?.text())
+    }
+
+    async fn send_with_tools_and_system(
+        &self,
+        messages: Vec<Message>,
+        system: Option<SystemPrompt>,
+        tools: Vec<ToolDefinition>,
+        tool_choice: Option<ToolChoice>,
+        thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
+        _session_id: Option<&str>,
+    ) -> Result<MessageResponse> {
+        tracing::info!("[Bedrock] Sending message with tools");
+        tracing::debug!("[Bedrock] Messages count: {}", messages.len());
+        tracing::debug!("[Bedrock] Tools count: {}", tools.len());
+
+        let format = generation.as_ref().and_then(|g| g.response_format.clone());
+        let request = self.build_request(messages, system, tools, tool_choice, thinking, generation);
+        let mut response = self.invoke(&request).await?;
+        if let Some(format) = format {
+            json_tool::unwrap_response(&format, &mut response);
+        }
+        Ok(response)
+    }
+
+    async fn stream_with_tools_and_system(
+        &self,
+        messages: Vec<Message>,
+        system: Option<SystemPrompt>,
+        tools: Vec<ToolDefinition>,
+        tool_choice: Option<ToolChoice>,
+        thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
+        _session_id: Option<&str>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>> {
+        tracing::info!("[Bedrock] Streaming message with tools");
+
+        let format = generation.as_ref().and_then(|g| g.response_format.clone());
+        let request = self.build_request(messages, system, tools, tool_choice, thinking, generation);
+        let stream = self.invoke_streaming(&request).await?;
+        Ok(match format {
+            Some(format) => json_tool::unwrap_stream(format, stream),
+            None => stream,
+        })
+    }
+
+    fn model(&self) -> String {
+        self.model.clone()
+    }
+
+    fn max_tokens(&self) -> Option<u32> {
+        Some(self.max_tokens)
+    }
+
+    fn provider_name(&self) -> &str {
+        "bedrock"
+    }
+
+    fn create_variant(&self, model: &str, max_tokens: u32) -> Arc<dyn LlmProvider> {
+        Arc::new(self.create_variant_impl(model, max_tokens))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{ContentBlockStart, ContentDelta, StopReason, WebSearchTool};
+    use std::path::Path;
+
+    fn fixture(name: &str) -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/bedrock").join(name);
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    fn provider() -> BedrockProvider {
+        let config = aws_config::SdkConfig::builder()
+            .behavior_version(aws_config::BehaviorVersion::latest())
+            .region(aws_config::Region::new("us-east-1"))
+            .build();
+        BedrockProvider::from_sdk_config(&config)
+            .with_model("anthropic.claude-sonnet-4-5-20250929-v1:0")
+            .with_max_tokens(1024)
+    }
+
+    fn body(request: &MessageRequest) -> Value {
+        serde_json::from_str(&bedrock_body(request).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_body_construction() {
+        let request = provider().build_request(
+            vec![Message::user("Hi")],
+            Some(SystemPrompt::Text("Be brief".to_string())),
+            vec![
+                crate::llm::define_tool("Read", "Read a file", serde_json::json!({}), vec![]),
+                ToolDefinition::WebSearch(WebSearchTool::new()),
+            ],
+            None,
+            None,
+            Some(GenerationParams::new().with_temperature(0.5)),
+        );
+        let body = body(&request);
+
+        assert_eq!(body["anthropic_version"], BEDROCK_ANTHROPIC_VERSION);
+        assert!(body.get("model").is_none());
+        assert!(body.get("stream").is_none());
+        assert!(body.get("anthropic_beta").is_none());
+        assert_eq!(body["max_tokens"], 1024);
+        assert_eq!(body["system"], "Be brief");
+        assert_eq!(body["temperature"], 0.5);
+        assert_eq!(body["messages"][0]["content"], "Hi");
+        // Web search is dropped; custom tools pass through unchanged
+        assert_eq!(body["tools"].as_array().unwrap().len(), 1);
+        assert_eq!(body["tools"][0]["name"], "Read");
+    }
+
+    #[test]
+    fn test_thinking_enables_beta_and_forces_temperature() {
+        let request = provider().build_request(
+            vec![Message::user("Hi")],
+            None,
+            Vec::new(),
+            None,
+            Some(ThinkingConfig::enabled(512)),
+            Some(GenerationParams::new().with_temperature(0.2)),
+        );
+        let body = body(&request);
+        assert_eq!(body["anthropic_beta"][0], INTERLEAVED_THINKING_BETA);
+        assert_eq!(body["thinking"]["budget_tokens"], 512);
+        assert_eq!(body["temperature"], 1.0);
+    }
+
+    #[test]
+    fn test_interleaved_beta_can_be_turned_off() {
+        let thinking = ThinkingConfig::enabled(512).with_interleaved(false);
+        let request = provider().build_request(vec![Message::user("Hi")], None, Vec::new(), None, Some(thinking), None);
+        let body = body(&request);
+        assert!(body.get("anthropic_beta").is_none());
+        assert_eq!(body["thinking"], serde_json::json!({"type": "enabled", "budget_tokens": 512}));
+    }
+
+    #[test]
+    fn test_response_fixture_parses() {
+        let response: MessageResponse = serde_json::from_str(&fixture("response_text.json")).unwrap();
+        assert_eq!(response.text(), "Hello from Bedrock.");
+        assert_eq!(response.stop_reason, Some(StopReason::EndTurn));
+        assert_eq!(response.usage.input_tokens, 21);
+    }
+
+    #[test]
+    fn test_stream_chunks_reframe_into_events() {
+        let events: Vec<StreamEvent> = fixture("stream_tool_use.jsonl")
+            .lines()
+            .filter_map(|line| parse_chunk(line.as_bytes()).unwrap())
+            .collect();
+        assert_eq!(events.len(), 12);
+
+        let StreamEvent::MessageStart(ref start) = events[0] else { panic!("{:?}", events[0]) };
+        assert_eq!(start.message.usage.input_tokens, 412);
+
+        let text: String = events
+            .iter()
+            .filter_map(|e| match e {
+                StreamEvent::ContentBlockDelta(d) => match &d.delta {
+                    ContentDelta::TextDelta { text } => Some(text.as_str()),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+        assert_eq!(text, "I'll read the file.");
+
+        let StreamEvent::ContentBlockStart(ref tool) = events[5] else { panic!("{:?}", events[5]) };
+        assert!(matches!(&tool.content_block, ContentBlockStart::ToolUse { name, .. } if name == "Read"));
+
+        let StreamEvent::MessageDelta(ref delta) = events[10] else { panic!("{:?}", events[10]) };
+        assert_eq!(delta.delta.stop_reason, Some(StopReason::ToolUse));
+        assert_eq!(delta.usage.output_tokens, 58);
+        // The final event carries Bedrock's invocation metrics, which are ignored
+        assert!(matches!(events[11], StreamEvent::MessageStop));
+    }
+
+    #[test]
+    fn test_unknown_and_malformed_chunks() {
+        assert!(parse_chunk(br#"{"type":"future_event","x":1}"#).unwrap().is_none());
+        assert!(parse_chunk(br#"{"type":"content_block_stop"}"#).is_err());
+        assert!(parse_chunk(b"not json").is_err());
+    }
+}
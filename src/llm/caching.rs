@@ -0,0 +1,573 @@
+//! Response caching for development
+//!
+//! `CachingProvider` wraps a provider and keeps its responses in a local
+//! directory, one JSON file per request. Re-running a prompt that was asked
+//! before returns the stored response at once and for free:
+//!
+//! ```ignore
+//! let llm = CachingProvider::new(Arc::new(AnthropicProvider::from_env()?), ".llm-cache")
+//!     .with_ttl(Duration::from_secs(60 * 60));
+//! let agent = StandardAgent::new(config, Arc::new(llm));
+//! ```
+//!
+//! Requests are keyed by a hash of the model, system prompt, messages,
+//! tools, tool choice, thinking and sampling parameters, with prompt caching
+//! markers left out. Streaming and non-streaming calls share entries: a hit
+//! on a streaming call is replayed as the events of the stored response.
+//! Hits report zero usage, so cost tracking only counts real calls.
+//!
+//! Set `SHADOW_LLM_CACHE_BYPASS=1` to send everything to the provider
+//! without reading or writing the cache, or pass
+//! `GenerationParams::with_no_cache(true)` to refresh the entry for one
+//! request.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use futures::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::capabilities::ModelCapabilities;
+use super::provider::LlmProvider;
+use super::replay::{request_json, send_message_json, MatchOptions};
+use super::stats::ProviderStats;
+use super::stream_blocks::BlockAccumulator;
+use super::types::{
+    GenerationParams, Message, MessageResponse, StreamEvent, SystemPrompt, ThinkingConfig, ToolChoice,
+    ToolDefinition, Usage,
+};
+
+/// Environment variable that turns the cache off (`1`, `true`, `yes` or `on`)
+pub const CACHE_BYPASS_ENV: &str = "SHADOW_LLM_CACHE_BYPASS";
+
+/// How long a stored response is used by default
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+type EventStream = Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>;
+
+/// A cache file
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    stored_at: DateTime<Utc>,
+    response: CachedResponse,
+}
+
+/// A stored response, by the kind of call that produced it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CachedResponse {
+    /// `send_message` text
+    Text(String),
+    /// `send_with_tools_and_system` or `stream_with_tools_and_system` response
+    Message(MessageResponse),
+}
+
+/// Wraps a provider and serves repeated requests from a local cache
+///
+/// Failed calls and streams that end early are never stored. Variants
+/// created with `create_variant` use the same cache directory.
+#[derive(Clone)]
+pub struct CachingProvider {
+    inner: Arc<dyn LlmProvider>,
+    dir: PathBuf,
+    ttl: Duration,
+    options: MatchOptions,
+    bypass: bool,
+}
+
+impl CachingProvider {
+    /// Cache `inner`'s responses in `dir`
+    ///
+    /// The cache is bypassed when `SHADOW_LLM_CACHE_BYPASS` is set.
+    pub fn new(inner: Arc<dyn LlmProvider>, dir: impl Into<PathBuf>) -> Self {
+        let bypass = std::env::var(CACHE_BYPASS_ENV)
+            .map(|value| matches!(value.to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+            .unwrap_or(false);
+        Self {
+            inner,
+            dir: dir.into(),
+            ttl: DEFAULT_CACHE_TTL,
+            options: MatchOptions::new().ignore_cache_control(),
+            bypass,
+        }
+    }
+
+    /// Set how long a stored response is used (default 24 hours)
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Relax which requests count as the same
+    ///
+    /// E.g. `replace_text` for temp directories that differ between runs.
+    /// Prompt caching markers are always ignored.
+    pub fn with_match_options(mut self, options: MatchOptions) -> Self {
+        self.options = options.ignore_cache_control();
+        self
+    }
+
+    /// Send every request to the provider, overriding the environment
+    pub fn with_bypass(mut self, bypass: bool) -> Self {
+        self.bypass = bypass;
+        self
+    }
+
+    /// The cache directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Delete every stored response, returning how many there were
+    pub fn clear(&self) -> Result<usize> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", self.dir.display())),
+        };
+        let mut removed = 0;
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                std::fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// The key of a request, with the provider name added
+    fn key(&self, mut request: Value) -> String {
+        request["provider"] = json!(self.inner.provider_name());
+        self.options.key(&request)
+    }
+
+    /// The key of a `send_with_tools_and_system` or streaming request
+    fn message_key(
+        &self,
+        messages: &[Message],
+        system: &Option<SystemPrompt>,
+        tools: &[ToolDefinition],
+        tool_choice: &Option<ToolChoice>,
+        thinking: &Option<ThinkingConfig>,
+        generation: &Option<GenerationParams>,
+    ) -> String {
+        // The no-cache flag doesn't change the response
+        let generation = generation
+            .clone()
+            .map(|generation| generation.with_no_cache(false))
+            .filter(|generation| !generation.is_empty());
+        self.key(request_json(
+            "message", &self.inner.model(), messages, system, tools, tool_choice, thinking, &generation,
+        ))
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// The stored response for a key, unless it's missing or expired
+    async fn lookup(&self, key: &str) -> Option<CachedResponse> {
+        let content = tokio::fs::read_to_string(self.path(key)).await.ok()?;
+        let entry: CacheEntry = match serde_json::from_str(&content) {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::warn!("[CachingProvider] Ignoring unreadable cache entry {}: {}", key, e);
+                return None;
+            }
+        };
+        let age = (Utc::now() - entry.stored_at).to_std().unwrap_or_default();
+        if age >= self.ttl {
+            tracing::debug!("[CachingProvider] Cache entry {} expired", key);
+            return None;
+        }
+        tracing::info!(cache_hit = true, "[CachingProvider] Cache hit {}", key);
+        Some(entry.response)
+    }
+
+    async fn store(&self, key: &str, response: CachedResponse) {
+        let entry = CacheEntry {
+            stored_at: Utc::now(),
+            response,
+        };
+        let content = serde_json::to_string_pretty(&entry).unwrap_or_default();
+        let result = match tokio::fs::create_dir_all(&self.dir).await {
+            Ok(()) => tokio::fs::write(self.path(key), content).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            tracing::warn!("[CachingProvider] Failed to store cache entry {}: {}", key, e);
+        }
+    }
+
+    /// The stored message response for a key, with zero usage
+    async fn cached_message(&self, key: &str) -> Option<MessageResponse> {
+        match self.lookup(key).await? {
+            CachedResponse::Message(mut response) => {
+                response.usage = Usage::default();
+                Some(response)
+            }
+            CachedResponse::Text(_) => None,
+        }
+    }
+}
+
+/// Whether a request asked to skip the cache
+fn no_cache(generation: &Option<GenerationParams>) -> bool {
+    generation.as_ref().is_some_and(|generation| generation.no_cache)
+}
+
+/// Pass a stream through, storing the response once it completes
+fn store_when_complete(provider: CachingProvider, key: String, mut inner: EventStream) -> EventStream {
+    Box::pin(async_stream::try_stream! {
+        let mut blocks = BlockAccumulator::new();
+        let mut response: Option<MessageResponse> = None;
+        let mut complete = false;
+
+        while let Some(event) = inner.next().await {
+            let event = event?;
+            match &event {
+                StreamEvent::MessageStart(start) => {
+                    let message = &start.message;
+                    response = Some(MessageResponse {
+                        id: message.id.clone(),
+                        response_type: message.message_type.clone(),
+                        role: message.role.clone(),
+                        content: Vec::new(),
+                        model: message.model.clone(),
+                        stop_reason: None,
+                        stop_sequence: None,
+                        usage: message.usage.clone(),
+                    });
+                }
+                StreamEvent::ContentBlockStart(start) => blocks.start(start.index, &start.content_block),
+                StreamEvent::ContentBlockDelta(delta) => {
+                    blocks.delta(delta.index, &delta.delta);
+                }
+                StreamEvent::ContentBlockStop(stop) => {
+                    blocks.stop(stop.index);
+                }
+                StreamEvent::MessageDelta(delta) => {
+                    if let Some(response) = &mut response {
+                        response.stop_reason = delta.delta.stop_reason.clone();
+                        response.stop_sequence = delta.delta.stop_sequence.clone();
+                        delta.usage.apply_to(&mut response.usage);
+                    }
+                }
+                StreamEvent::MessageStop => complete = true,
+                StreamEvent::Error(_) => response = None,
+                StreamEvent::Ping => {}
+            }
+            yield event;
+        }
+
+        if let (true, Some(mut response)) = (complete, response) {
+            response.content = blocks.into_blocks();
+            provider.store(&key, CachedResponse::Message(response)).await;
+        }
+    })
+}
+
+#[async_trait::async_trait]
+impl LlmProvider for CachingProvider {
+    async fn send_message(
+        &self,
+        user_message: &str,
+        conversation_history: &[Message],
+        system_prompt: Option<&str>,
+        session_id: Option<&str>,
+    ) -> Result<String> {
+        if self.bypass {
+            return self.inner.send_message(user_message, conversation_history, system_prompt, session_id).await;
+        }
+        let key = self.key(send_message_json(
+            &self.inner.model(),
+            user_message,
+            conversation_history,
+            system_prompt,
+        ));
+        if let Some(CachedResponse::Text(text)) = self.lookup(&key).await {
+            return Ok(text);
+        }
+        let text = self
+            .inner
+            .send_message(user_message, conversation_history, system_prompt, session_id)
+            .await?;
+        self.store(&key, CachedResponse::Text(text.clone())).await;
+        Ok(text)
+    }
+
+    async fn send_with_tools_and_system(
+        &self,
+        messages: Vec<Message>,
+        system: Option<SystemPrompt>,
+        tools: Vec<ToolDefinition>,
+        tool_choice: Option<ToolChoice>,
+        thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
+        session_id: Option<&str>,
+    ) -> Result<MessageResponse> {
+        if self.bypass {
+            return self
+                .inner
+                .send_with_tools_and_system(messages, system, tools, tool_choice, thinking, generation, session_id)
+                .await;
+        }
+        let key = self.message_key(&messages, &system, &tools, &tool_choice, &thinking, &generation);
+        if !no_cache(&generation) {
+            if let Some(response) = self.cached_message(&key).await {
+                return Ok(response);
+            }
+        }
+        let response = self
+            .inner
+            .send_with_tools_and_system(messages, system, tools, tool_choice, thinking, generation, session_id)
+            .await?;
+        self.store(&key, CachedResponse::Message(response.clone())).await;
+        Ok(response)
+    }
+
+    async fn stream_with_tools_and_system(
+        &self,
+        messages: Vec<Message>,
+        system: Option<SystemPrompt>,
+        tools: Vec<ToolDefinition>,
+        tool_choice: Option<ToolChoice>,
+        thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
+        session_id: Option<&str>,
+    ) -> Result<EventStream> {
+        if self.bypass {
+            return self
+                .inner
+                .stream_with_tools_and_system(messages, system, tools, tool_choice, thinking, generation, session_id)
+                .await;
+        }
+        let key = self.message_key(&messages, &system, &tools, &tool_choice, &thinking, &generation);
+        if !no_cache(&generation) {
+            if let Some(response) = self.cached_message(&key).await {
+                return Ok(Box::pin(futures::stream::iter(response.into_stream_events().into_iter().map(Ok))));
+            }
+        }
+        let stream = self
+            .inner
+            .stream_with_tools_and_system(messages, system, tools, tool_choice, thinking, generation, session_id)
+            .await?;
+        Ok(store_when_complete(self.clone(), key, stream))
+    }
+
+    fn model(&self) -> String {
+        self.inner.model()
+    }
+
+    fn capabilities(&self) -> ModelCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn max_tokens(&self) -> Option<u32> {
+        self.inner.max_tokens()
+    }
+
+    fn stats(&self) -> Option<ProviderStats> {
+        self.inner.stats()
+    }
+
+    fn reset_stats(&self) {
+        self.inner.reset_stats();
+    }
+
+    fn provider_name(&self) -> &str {
+        self.inner.provider_name()
+    }
+
+    fn create_variant(&self, model: &str, max_tokens: u32) -> Arc<dyn LlmProvider> {
+        Arc::new(Self {
+            inner: self.inner.create_variant(model, max_tokens),
+            ..self.clone()
+        })
+    }
+
+    async fn forget_tool_calls(&self, tool_use_ids: &[String]) {
+        self.inner.forget_tool_calls(tool_use_ids).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{ContentBlock, MockLlmProvider, StopReason};
+
+    fn provider(mock: &MockLlmProvider, dir: &tempfile::TempDir) -> CachingProvider {
+        CachingProvider::new(Arc::new(mock.clone()), dir.path()).with_bypass(false)
+    }
+
+    async fn send(llm: &CachingProvider, prompt: &str, generation: Option<GenerationParams>) -> MessageResponse {
+        llm.send_with_tools_and_system(vec![Message::user(prompt)], None, vec![], None, None, generation, None)
+            .await
+            .unwrap()
+    }
+
+    async fn stream(llm: &CachingProvider, prompt: &str) -> Vec<StreamEvent> {
+        llm.stream_with_tools_and_system(vec![Message::user(prompt)], None, vec![], None, None, None, None)
+            .await
+            .unwrap()
+            .map(|event| event.unwrap())
+            .collect()
+            .await
+    }
+
+    /// Assemble stream events the way the agent loop does
+    fn assemble(events: &[StreamEvent]) -> Vec<ContentBlock> {
+        let mut blocks = BlockAccumulator::new();
+        for event in events {
+            match event {
+                StreamEvent::ContentBlockStart(e) => blocks.start(e.index, &e.content_block),
+                StreamEvent::ContentBlockDelta(e) => {
+                    blocks.delta(e.index, &e.delta);
+                }
+                StreamEvent::ContentBlockStop(e) => {
+                    blocks.stop(e.index);
+                }
+                _ => {}
+            }
+        }
+        blocks.into_blocks()
+    }
+
+    fn thinking_and_tool_response() -> MessageResponse {
+        let mut response = MockLlmProvider::tool_use_response("toolu_1", "Read", json!({"file_path": "a.rs"}));
+        response.content.insert(
+            0,
+            ContentBlock::Thinking {
+                thinking: "Read it first".into(),
+                signature: "sig".into(),
+            },
+        );
+        response.content.insert(1, ContentBlock::text("Reading."));
+        response.usage.input_tokens = 120;
+        response.usage.output_tokens = 30;
+        response
+    }
+
+    #[tokio::test]
+    async fn test_hits_misses_and_no_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let mock = MockLlmProvider::new()
+            .with_text("first")
+            .with_text("other")
+            .with_text("refreshed")
+            .with_text("live");
+        let llm = provider(&mock, &dir);
+
+        assert_eq!(send(&llm, "hello", None).await.text(), "first");
+
+        // Same request: served from the cache with zero usage
+        let cached = send(&llm, "hello", None).await;
+        assert_eq!(cached.text(), "first");
+        assert_eq!((cached.usage.input_tokens, cached.usage.output_tokens), (0, 0));
+        assert_eq!(mock.call_count(), 1);
+
+        // A different request misses
+        assert_eq!(send(&llm, "something else", None).await.text(), "other");
+        assert_eq!(mock.call_count(), 2);
+
+        // No-cache goes to the provider and refreshes the entry
+        let generation = Some(GenerationParams::new().with_no_cache(true));
+        assert_eq!(send(&llm, "hello", generation).await.text(), "refreshed");
+        assert_eq!(send(&llm, "hello", None).await.text(), "refreshed");
+        assert_eq!(mock.call_count(), 3);
+
+        // Bypassing neither reads nor writes
+        let bypassed = provider(&mock, &dir).with_bypass(true);
+        assert_eq!(send(&bypassed, "hello", None).await.text(), "live");
+        assert_eq!(send(&llm, "hello", None).await.text(), "refreshed");
+        assert_eq!(mock.call_count(), 4);
+        assert_eq!(llm.clear().unwrap(), 2);
+        assert_eq!(llm.clear().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cache_control_and_ttl() {
+        let dir = tempfile::tempdir().unwrap();
+        let mock = MockLlmProvider::new().with_text("first").with_text("second");
+        let llm = provider(&mock, &dir);
+        let message = |cache_control| Message {
+            role: "user".into(),
+            content: crate::llm::MessageContent::Blocks(vec![ContentBlock::Text {
+                text: "hello".into(),
+                cache_control,
+                citations: None,
+            }]),
+        };
+        llm.send_with_tools_and_system(vec![message(None)], None, vec![], None, None, None, None)
+            .await
+            .unwrap();
+
+        // Prompt caching markers don't change the key
+        let marked = message(Some(crate::llm::CacheControl::ephemeral()));
+        let response = llm
+            .send_with_tools_and_system(vec![marked], None, vec![], None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(response.text(), "first");
+        assert_eq!(mock.call_count(), 1);
+
+        // An expired entry is a miss
+        let expired = provider(&mock, &dir).with_ttl(Duration::ZERO);
+        let response = expired
+            .send_with_tools_and_system(vec![message(None)], None, vec![], None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(response.text(), "second");
+        assert_eq!(mock.call_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_streamed_hit_matches_the_stored_response() {
+        let dir = tempfile::tempdir().unwrap();
+        let mock = MockLlmProvider::new()
+            .with_input_chunk_size(5)
+            .with_response(thinking_and_tool_response());
+        let llm = provider(&mock, &dir);
+
+        // A streamed miss is passed through and stored
+        let live = stream(&llm, "read a.rs").await;
+        let stored = send(&llm, "read a.rs", None).await;
+        assert_eq!(mock.call_count(), 1);
+        assert_eq!(format!("{:?}", assemble(&live)), format!("{:?}", stored.content));
+        assert_eq!(stored.stop_reason, Some(StopReason::ToolUse));
+
+        // A streamed hit assembles to the non-streaming result
+        let replayed = stream(&llm, "read a.rs").await;
+        assert_eq!(mock.call_count(), 1);
+        assert_eq!(format!("{:?}", assemble(&replayed)), format!("{:?}", stored.content));
+        assert!(matches!(replayed.last(), Some(StreamEvent::MessageStop)));
+        assert!(replayed.iter().any(|event| matches!(
+            event,
+            StreamEvent::MessageDelta(delta)
+                if delta.delta.stop_reason == Some(StopReason::ToolUse) && delta.usage.output_tokens == 0
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_incomplete_streams_are_not_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let mock = MockLlmProvider::new()
+            .with_dropped_stream(MockLlmProvider::text_response("partial"), 3)
+            .with_text("whole");
+        let llm = provider(&mock, &dir);
+
+        let mut events = llm
+            .stream_with_tools_and_system(vec![Message::user("hi")], None, vec![], None, None, None, None)
+            .await
+            .unwrap();
+        while let Some(Ok(_)) = events.next().await {}
+        drop(events);
+        assert_eq!(send(&llm, "hi", None).await.text(), "whole");
+        assert_eq!(mock.call_count(), 2);
+    }
+}
@@ -0,0 +1,160 @@
+//! Model capabilities
+//!
+//! What a model supports (extended thinking, prompt caching, images) and its
+//! token limits, so an agent can turn off features a model would reject
+//! instead of failing with a 400 mid-conversation.
+//!
+//! Known Anthropic and Gemini models are built in. Others can be registered:
+//!
+//! ```ignore
+//! register_model_capabilities("my-finetune", ModelCapabilities {
+//!     supports_thinking: false,
+//!     supports_caching: false,
+//!     supports_images: true,
+//!     max_context_tokens: Some(32_768),
+//!     max_output_tokens: Some(4096),
+//! });
+//! ```
+//!
+//! Entries match by substring of the model id, longest first, so
+//! `claude-opus-4-5` wins over `claude-opus-4` and Bedrock ids such as
+//! `us.anthropic.claude-sonnet-4-5-20250929-v1:0` match too.
+
+use std::sync::{OnceLock, RwLock};
+
+/// Features and limits of a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelCapabilities {
+    /// Accepts a `thinking` config
+    pub supports_thinking: bool,
+    /// Honours `cache_control` breakpoints
+    pub supports_caching: bool,
+    /// Accepts image content
+    pub supports_images: bool,
+    /// Context window in tokens (None = unknown)
+    pub max_context_tokens: Option<u32>,
+    /// Maximum output tokens (None = unknown)
+    pub max_output_tokens: Option<u32>,
+}
+
+impl Default for ModelCapabilities {
+    /// Capabilities assumed for unknown models: everything is allowed
+    fn default() -> Self {
+        Self {
+            supports_thinking: true,
+            supports_caching: true,
+            supports_images: true,
+            max_context_tokens: None,
+            max_output_tokens: None,
+        }
+    }
+}
+
+impl ModelCapabilities {
+    /// Look up a model, falling back to the permissive default
+    pub fn for_model(model: &str) -> Self {
+        Self::lookup(model).unwrap_or_default()
+    }
+
+    /// Look up a model in the registered and built-in tables
+    ///
+    /// Registered entries are checked first.
+    pub fn lookup(model: &str) -> Option<Self> {
+        let registered = registry().read().unwrap_or_else(|e| e.into_inner());
+        longest_match(registered.iter().map(|(key, caps)| (key.as_str(), *caps)), model)
+            .or_else(|| longest_match(BUILT_IN.iter().map(|(key, caps)| (*key, *caps)), model))
+    }
+}
+
+/// Register capabilities for models whose id contains `pattern`
+///
+/// Replaces an earlier registration of the same pattern.
+pub fn register_model_capabilities(pattern: impl Into<String>, capabilities: ModelCapabilities) {
+    let pattern = pattern.into();
+    let mut registered = registry().write().unwrap_or_else(|e| e.into_inner());
+    registered.retain(|(key, _)| *key != pattern);
+    registered.push((pattern, capabilities));
+}
+
+fn registry() -> &'static RwLock<Vec<(String, ModelCapabilities)>> {
+    static REGISTRY: OnceLock<RwLock<Vec<(String, ModelCapabilities)>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(Vec::new()))
+}
+
+fn longest_match<'a>(
+    entries: impl Iterator<Item = (&'a str, ModelCapabilities)>,
+    model: &str,
+) -> Option<ModelCapabilities> {
+    entries
+        .filter(|(key, _)| model.contains(key))
+        .max_by_key(|(key, _)| key.len())
+        .map(|(_, caps)| caps)
+}
+
+const fn caps(thinking: bool, caching: bool, context: u32, output: u32) -> ModelCapabilities {
+    ModelCapabilities {
+        supports_thinking: thinking,
+        supports_caching: caching,
+        supports_images: true,
+        max_context_tokens: Some(context),
+        max_output_tokens: Some(output),
+    }
+}
+
+/// Known models, matched by substring of the model id
+const BUILT_IN: &[(&str, ModelCapabilities)] = &[
+    // Anthropic
+    ("claude-opus-4-5", caps(true, true, 200_000, 64_000)),
+    ("claude-opus-4-1", caps(true, true, 200_000, 32_000)),
+    ("claude-opus-4", caps(true, true, 200_000, 32_000)),
+    ("claude-sonnet-4-5", caps(true, true, 200_000, 64_000)),
+    ("claude-sonnet-4", caps(true, true, 200_000, 64_000)),
+    ("claude-haiku-4-5", caps(true, true, 200_000, 64_000)),
+    ("claude-3-7-sonnet", caps(true, true, 200_000, 64_000)),
+    ("claude-3-5-sonnet", caps(false, true, 200_000, 8192)),
+    ("claude-3-5-haiku", caps(false, true, 200_000, 8192)),
+    ("claude-3-opus", caps(false, true, 200_000, 4096)),
+    ("claude-3-haiku", caps(false, true, 200_000, 4096)),
+    // Gemini (caching is implicit; cache_control breakpoints are not used)
+    ("gemini-3-pro", caps(true, false, 1_048_576, 65_536)),
+    ("gemini-2.5-pro", caps(true, false, 1_048_576, 65_536)),
+    ("gemini-2.5-flash", caps(true, false, 1_048_576, 65_536)),
+    ("gemini-2.0-flash", caps(false, false, 1_048_576, 8192)),
+    ("gemini-1.5-pro", caps(false, false, 2_097_152, 8192)),
+    ("gemini-1.5-flash", caps(false, false, 1_048_576, 8192)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_longest_match_wins() {
+        let opus = ModelCapabilities::for_model("claude-opus-4-5-20251101");
+        assert_eq!(opus.max_output_tokens, Some(64_000));
+        let older = ModelCapabilities::for_model("claude-opus-4-20250514");
+        assert_eq!(older.max_output_tokens, Some(32_000));
+
+        let bedrock = ModelCapabilities::for_model("us.anthropic.claude-3-5-haiku-20241022-v1:0");
+        assert!(!bedrock.supports_thinking);
+
+        let unknown = ModelCapabilities::for_model("some-local-model");
+        assert_eq!(unknown, ModelCapabilities::default());
+        assert!(ModelCapabilities::lookup("some-local-model").is_none());
+    }
+
+    #[test]
+    fn test_registered_entries_take_precedence() {
+        let custom = ModelCapabilities {
+            supports_thinking: false,
+            supports_caching: false,
+            supports_images: false,
+            max_context_tokens: Some(32_768),
+            max_output_tokens: Some(4096),
+        };
+        register_model_capabilities("gemini-2.5-flash-capstest", custom);
+        assert_eq!(ModelCapabilities::for_model("gemini-2.5-flash-capstest-001"), custom);
+        // Other models are unaffected
+        assert!(ModelCapabilities::for_model("gemini-2.5-flash").supports_thinking);
+    }
+}
@@ -15,6 +15,36 @@
 //! // With explicit API key
 //! let llm = GeminiProvider::new("AIza...")?;
 //! ```
+//!
+//! # Images and Documents
+//!
+//! Images (PNG, JPEG, WebP, HEIC, HEIF) and documents (PDF and plain-text
+//! formats) are sent as inline data, right after the tool result they belong
+//! to. Other media types fail the request with an error naming the type.
+//!
+//! Large attachments can go through the Files API instead, so they are
+//! uploaded once rather than resent inline on every turn:
+//!
+//! ```ignore
+//! // Upload anything over 1 MB
+//! let llm = GeminiProvider::from_env()?.with_files_api(1024 * 1024);
+//! ```
+//!
+//! # Gemini Options
+//!
+//! Settings only Gemini has (safety thresholds, top-k, candidate count,
+//! response MIME type) go in `GeminiOptions`:
+//!
+//! ```ignore
+//! let llm = GeminiProvider::from_env()?.with_options(
+//!     GeminiOptions::new()
+//!         .with_safety_setting(HarmCategory::DangerousContent, HarmBlockThreshold::BlockOnlyHigh)
+//!         .with_top_k(40),
+//! );
+//! ```
+//!
+//! A response blocked by the safety filters ends with `StopReason::Refusal`
+//! and a text block naming the categories that fired.
 
 use anyhow::{Context, Result};
 use futures::stream::Stream;
@@ -27,21 +57,38 @@ use std::env;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::AsyncBufReadExt;
 use tokio::sync::Mutex;
 use tokio_util::io::StreamReader;
 
 use super::auth::{auth_provider, AuthConfig, AuthProvider, AuthSource};
+use super::interceptor::{notify_received, prepare_request, HttpParts, RequestInterceptor};
+use super::json_tool;
 use super::provider::LlmProvider;
+use super::stats::{ErrorClass, ProviderStats, StatsRecorder};
 use super::types::{
     ContentBlock, ContentBlockDeltaEvent, ContentBlockStart, ContentBlockStartEvent,
-    ContentBlockStopEvent, ContentDelta, DeltaUsage, Message, MessageContent,
-    MessageDeltaData, MessageDeltaEvent, MessageResponse, MessageStartData, MessageStartEvent,
+    ContentBlockStopEvent, ContentDelta, DeltaUsage, GenerationParams, Message, MessageContent,
+    MessageDeltaData, MessageDeltaEvent, MessageResponse, MessageStartData, MessageStartEvent, ResponseFormat,
     StopReason, StreamEvent, SystemPrompt, ThinkingConfig, ToolChoice, ToolDefinition, Usage,
 };
 
 const DEFAULT_API_BASE: &str = "https://generativelanguage.googleapis.com/v1beta";
 
+/// Image types Gemini accepts
+const GEMINI_IMAGE_TYPES: &[&str] = &["image/png", "image/jpeg", "image/webp", "image/heic", "image/heif"];
+
+/// Document types Gemini accepts
+const GEMINI_DOCUMENT_TYPES: &[&str] = &[
+    "application/pdf",
+    "text/plain",
+    "text/html",
+    "text/csv",
+    "text/markdown",
+    "text/xml",
+];
+
 // ============================================================================
 // Gemini-specific request/response types
 // ============================================================================
@@ -58,6 +105,14 @@ struct GeminiRequest {
     tool_config: Option<GeminiToolConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     generation_config: Option<GeminiGenerationConfig>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    safety_settings: Vec<GeminiSafetySetting>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct GeminiSafetySetting {
+    category: HarmCategory,
+    threshold: HarmBlockThreshold,
 }
 
 #[derive(Debug, Serialize)]
@@ -72,7 +127,7 @@ struct GeminiContent {
     parts: Vec<GeminiPart>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct GeminiPart {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -84,24 +139,13 @@ struct GeminiPart {
     #[serde(skip_serializing_if = "Option::is_none")]
     inline_data: Option<GeminiInlineData>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    file_data: Option<GeminiFileData>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     thought_signature: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     thought: Option<bool>,
 }
 
-impl Default for GeminiPart {
-    fn default() -> Self {
-        Self {
-            text: None,
-            function_call: None,
-            function_response: None,
-            inline_data: None,
-            thought_signature: None,
-            thought: None,
-        }
-    }
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct GeminiFunctionCall {
     name: String,
@@ -121,6 +165,25 @@ struct GeminiInlineData {
     data: String,
 }
 
+/// A file uploaded through the Files API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiFileData {
+    mime_type: String,
+    file_uri: String,
+}
+
+/// Response of a finished Files API upload
+#[derive(Debug, Deserialize)]
+struct GeminiUploadResponse {
+    file: GeminiUploadedFile,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeminiUploadedFile {
+    uri: String,
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct GeminiTool {
@@ -142,8 +205,11 @@ struct GeminiToolConfig {
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 struct GeminiFunctionCallingConfig {
     mode: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allowed_function_names: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize)]
@@ -154,6 +220,18 @@ struct GeminiGenerationConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    candidate_count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_schema: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop_sequences: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     thinking_config: Option<GeminiThinkingConfig>,
 }
 
@@ -177,6 +255,8 @@ struct GeminiThinkingConfig {
 struct GeminiResponse {
     candidates: Option<Vec<GeminiCandidate>>,
     usage_metadata: Option<GeminiUsageMetadata>,
+    #[serde(default)]
+    prompt_feedback: Option<GeminiPromptFeedback>,
     #[allow(dead_code)]
     model_version: Option<String>,
     #[allow(dead_code)]
@@ -188,12 +268,74 @@ struct GeminiResponse {
 struct GeminiCandidate {
     content: Option<GeminiContent>,
     finish_reason: Option<String>,
-    #[allow(dead_code)]
+    #[serde(default)]
+    safety_ratings: Vec<GeminiSafetyRating>,
     index: Option<u32>,
 }
 
+/// Why the prompt itself was blocked (no candidates are returned then)
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
+struct GeminiPromptFeedback {
+    block_reason: Option<String>,
+    #[serde(default)]
+    safety_ratings: Vec<GeminiSafetyRating>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GeminiSafetyRating {
+    category: String,
+    probability: Option<String>,
+    #[serde(default)]
+    blocked: bool,
+}
+
+/// Describe the safety ratings that blocked a response or prompt
+///
+/// Lists the ratings marked as blocked, or else every rating above
+/// `NEGLIGIBLE`, e.g. `HARM_CATEGORY_DANGEROUS_CONTENT (HIGH)`.
+fn describe_safety_ratings(ratings: &[GeminiSafetyRating]) -> String {
+    let blocked: Vec<&GeminiSafetyRating> = ratings.iter().filter(|r| r.blocked).collect();
+    let fired = if blocked.is_empty() {
+        ratings
+            .iter()
+            .filter(|r| r.probability.as_deref().is_some_and(|p| p != "NEGLIGIBLE"))
+            .collect()
+    } else {
+        blocked
+    };
+    fired
+        .iter()
+        .map(|r| match &r.probability {
+            Some(probability) => format!("{} ({})", r.category, probability),
+            None => r.category.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Text added to a response the safety filters stopped
+fn safety_block_notice(ratings: &[GeminiSafetyRating]) -> String {
+    match describe_safety_ratings(ratings) {
+        fired if fired.is_empty() => "[Response blocked by Gemini safety filters]".to_string(),
+        fired => format!("[Response blocked by Gemini safety filters: {}]", fired),
+    }
+}
+
+/// Error for a prompt Gemini refused to answer at all
+fn prompt_blocked_error(feedback: &GeminiPromptFeedback) -> Option<anyhow::Error> {
+    let reason = feedback.block_reason.as_deref()?;
+    let fired = describe_safety_ratings(&feedback.safety_ratings);
+    Some(if fired.is_empty() {
+        anyhow::anyhow!("Gemini blocked the prompt ({})", reason)
+    } else {
+        anyhow::anyhow!("Gemini blocked the prompt ({}): {}", reason, fired)
+    })
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
 struct GeminiUsageMetadata {
     #[serde(default)]
@@ -206,6 +348,122 @@ struct GeminiUsageMetadata {
     thoughts_token_count: Option<u32>,
 }
 
+impl GeminiUsageMetadata {
+    /// Fold in the metadata of a later streamed chunk
+    ///
+    /// Counts are cumulative, but a chunk may leave some out, so each keeps
+    /// the highest value seen.
+    fn update(&mut self, later: &GeminiUsageMetadata) {
+        self.prompt_token_count = self.prompt_token_count.max(later.prompt_token_count);
+        self.candidates_token_count = self.candidates_token_count.max(later.candidates_token_count);
+        self.total_token_count = self.total_token_count.max(later.total_token_count);
+        self.thoughts_token_count = self.thoughts_token_count.max(later.thoughts_token_count);
+    }
+
+    fn to_usage(&self) -> Usage {
+        Usage {
+            input_tokens: self.prompt_token_count,
+            output_tokens: self.candidates_token_count,
+            cache_creation_input_tokens: None,
+            cache_read_input_tokens: None,
+            thoughts_token_count: self.thoughts_token_count,
+        }
+    }
+}
+
+// ============================================================================
+// Gemini options
+// ============================================================================
+
+/// Harm categories for `GeminiOptions::with_safety_setting`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HarmCategory {
+    #[serde(rename = "HARM_CATEGORY_HARASSMENT")]
+    Harassment,
+    #[serde(rename = "HARM_CATEGORY_HATE_SPEECH")]
+    HateSpeech,
+    #[serde(rename = "HARM_CATEGORY_SEXUALLY_EXPLICIT")]
+    SexuallyExplicit,
+    #[serde(rename = "HARM_CATEGORY_DANGEROUS_CONTENT")]
+    DangerousContent,
+    #[serde(rename = "HARM_CATEGORY_CIVIC_INTEGRITY")]
+    CivicIntegrity,
+}
+
+/// How likely content has to be harmful before Gemini blocks it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum HarmBlockThreshold {
+    /// Block when the probability is low, medium or high
+    BlockLowAndAbove,
+    /// Block when the probability is medium or high
+    BlockMediumAndAbove,
+    /// Block only when the probability is high
+    BlockOnlyHigh,
+    /// Never block (ratings are still returned)
+    BlockNone,
+    /// Turn the filter off
+    Off,
+}
+
+/// Gemini-specific request settings (see `GeminiProvider::with_options`)
+///
+/// `top_p` here is the provider default; a `GenerationParams::top_p` passed
+/// with the request wins over it.
+#[derive(Debug, Clone, Default)]
+pub struct GeminiOptions {
+    /// Blocking threshold per harm category (categories left out use Gemini's default)
+    pub safety_settings: Vec<(HarmCategory, HarmBlockThreshold)>,
+    /// Nucleus sampling threshold
+    pub top_p: Option<f32>,
+    /// Sample from the k most likely tokens
+    pub top_k: Option<u32>,
+    /// Candidates to generate (only the first one is used)
+    pub candidate_count: Option<u32>,
+    /// Response MIME type, e.g. `application/json` for JSON output
+    pub response_mime_type: Option<String>,
+}
+
+impl GeminiOptions {
+    /// Options with nothing set (Gemini's defaults)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the blocking threshold for a harm category
+    pub fn with_safety_setting(mut self, category: HarmCategory, threshold: HarmBlockThreshold) -> Self {
+        self.safety_settings.retain(|(c, _)| *c != category);
+        self.safety_settings.push((category, threshold));
+        self
+    }
+
+    /// Set the default nucleus sampling threshold
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    /// Set top-k sampling
+    pub fn with_top_k(mut self, top_k: u32) -> Self {
+        self.top_k = Some(top_k);
+        self
+    }
+
+    /// Set how many candidates Gemini generates
+    ///
+    /// Only the first candidate becomes the response.
+    pub fn with_candidate_count(mut self, count: u32) -> Self {
+        self.candidate_count = Some(count);
+        self
+    }
+
+    /// Set the response MIME type (`application/json` for JSON mode)
+    pub fn with_response_mime_type(mut self, mime_type: impl Into<String>) -> Self {
+        self.response_mime_type = Some(mime_type.into());
+        self
+    }
+}
+
 // ============================================================================
 // GeminiProvider
 // ============================================================================
@@ -239,6 +497,13 @@ pub struct GeminiProvider {
     /// Maps tool_use_id -> thought_signature.
     /// Gemini 3 requires thought signatures to be sent back with function calls.
     thought_signatures: Arc<Mutex<HashMap<String, String>>>,
+    /// Attachments larger than this many bytes go through the Files API
+    files_api_threshold: Option<usize>,
+    /// Files already uploaded, by hash of (media type, data) -> file URI
+    uploaded_files: Arc<Mutex<HashMap<u64, String>>>,
+    interceptors: Vec<Arc<dyn RequestInterceptor>>,
+    options: GeminiOptions,
+    stats: Arc<StatsRecorder>,
 }
 
 impl GeminiProvider {
@@ -272,6 +537,11 @@ impl GeminiProvider {
             max_tokens,
             api_base: DEFAULT_API_BASE.to_string(),
             thought_signatures: Arc::new(Mutex::new(HashMap::new())),
+            files_api_threshold: None,
+            uploaded_files: Arc::new(Mutex::new(HashMap::new())),
+            interceptors: Vec::new(),
+            options: GeminiOptions::default(),
+            stats: Arc::new(StatsRecorder::new("gemini")),
         })
     }
 
@@ -284,6 +554,11 @@ impl GeminiProvider {
             max_tokens: 8192,
             api_base: DEFAULT_API_BASE.to_string(),
             thought_signatures: Arc::new(Mutex::new(HashMap::new())),
+            files_api_threshold: None,
+            uploaded_files: Arc::new(Mutex::new(HashMap::new())),
+            interceptors: Vec::new(),
+            options: GeminiOptions::default(),
+            stats: Arc::new(StatsRecorder::new("gemini")),
         })
     }
 
@@ -318,6 +593,11 @@ impl GeminiProvider {
             max_tokens: 8192,
             api_base: DEFAULT_API_BASE.to_string(),
             thought_signatures: Arc::new(Mutex::new(HashMap::new())),
+            files_api_threshold: None,
+            uploaded_files: Arc::new(Mutex::new(HashMap::new())),
+            interceptors: Vec::new(),
+            options: GeminiOptions::default(),
+            stats: Arc::new(StatsRecorder::new("gemini")),
         }
     }
 
@@ -332,6 +612,11 @@ impl GeminiProvider {
             max_tokens: 8192,
             api_base: DEFAULT_API_BASE.to_string(),
             thought_signatures: Arc::new(Mutex::new(HashMap::new())),
+            files_api_threshold: None,
+            uploaded_files: Arc::new(Mutex::new(HashMap::new())),
+            interceptors: Vec::new(),
+            options: GeminiOptions::default(),
+            stats: Arc::new(StatsRecorder::new("gemini")),
         }
     }
 
@@ -347,6 +632,42 @@ impl GeminiProvider {
         self
     }
 
+    /// Upload images and documents larger than `threshold_bytes` through the
+    /// Files API instead of sending them inline
+    ///
+    /// Inline data counts against the request size limit and is resent on
+    /// every turn; an uploaded file is sent once and then referenced by URI.
+    /// Uploads are cached for the lifetime of the provider (Gemini keeps
+    /// files for 48 hours).
+    pub fn with_files_api(mut self, threshold_bytes: usize) -> Self {
+        self.files_api_threshold = Some(threshold_bytes);
+        self
+    }
+
+    /// Add a request interceptor
+    ///
+    /// Interceptors run in the order they were added and see the request in
+    /// Gemini's format. Files API uploads are not intercepted.
+    pub fn with_interceptor(mut self, interceptor: Arc<dyn RequestInterceptor>) -> Self {
+        self.interceptors.push(interceptor);
+        self
+    }
+
+    /// Set Gemini-specific request settings (safety, sampling, response type)
+    pub fn with_options(mut self, options: GeminiOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Log a stats snapshot at info level at most once per `interval`
+    ///
+    /// See `LlmProvider::stats`. Variants keep the setting but count their
+    /// own requests.
+    pub fn with_stats_log(mut self, interval: Duration) -> Self {
+        self.stats = Arc::new(StatsRecorder::new("gemini").with_log_interval(interval));
+        self
+    }
+
     /// Create a variant with different model/tokens, sharing the same auth config
     fn create_variant_impl(&self, model: &str, max_tokens: u32) -> Self {
         Self {
@@ -356,6 +677,11 @@ impl GeminiProvider {
             max_tokens,
             api_base: self.api_base.clone(),
             thought_signatures: Arc::new(Mutex::new(HashMap::new())),
+            files_api_threshold: self.files_api_threshold,
+            uploaded_files: self.uploaded_files.clone(),
+            interceptors: self.interceptors.clone(),
+            options: self.options.clone(),
+            stats: Arc::new(self.stats.fresh()),
         }
     }
 
@@ -364,9 +690,24 @@ impl GeminiProvider {
     // ========================================================================
 
     /// Convert internal messages to Gemini format
-    async fn convert_messages(&self, messages: &[Message]) -> Vec<GeminiContent> {
+    async fn convert_messages(&self, messages: &[Message]) -> Result<Vec<GeminiContent>> {
         let mut gemini_contents: Vec<GeminiContent> = Vec::new();
 
+        // functionResponse needs the tool name, but a tool_result only carries
+        // the id, and it lives in the message after its tool_use
+        let tool_use_names: HashMap<String, String> = messages
+            .iter()
+            .filter_map(|msg| match &msg.content {
+                MessageContent::Blocks(blocks) => Some(blocks),
+                MessageContent::Text(_) => None,
+            })
+            .flatten()
+            .filter_map(|block| match block {
+                ContentBlock::ToolUse { id, name, .. } => Some((id.clone(), name.clone())),
+                _ => None,
+            })
+            .collect();
+
         for msg in messages {
             let gemini_role = match msg.role.as_str() {
                 "user" => "user",
@@ -374,50 +715,37 @@ impl GeminiProvider {
                 _ => "user",
             };
 
-            let parts = self.convert_content_to_parts(&msg.content, &msg.role).await;
+            let parts = self.convert_content_to_parts(&msg.content, &tool_use_names).await?;
+            if parts.is_empty() {
+                continue;
+            }
 
-            // Check if parts contain function responses - those should be role "user"
-            let has_function_response = parts.iter().any(|p| p.function_response.is_some());
-            let role = if has_function_response {
-                "user".to_string()
+            // User messages keep their parts in order, so a tool result stays
+            // next to the image or PDF that belongs to it
+            if gemini_role == "user" || parts.iter().all(|p| p.function_response.is_none()) {
+                gemini_contents.push(GeminiContent {
+                    role: gemini_role.to_string(),
+                    parts,
+                });
             } else {
-                gemini_role.to_string()
-            };
-
-            // Split: function responses go as separate "user" content,
-            // function calls stay with "model" content
-            if has_function_response && parts.iter().any(|p| p.function_response.is_none()) {
-                // Mixed content - separate function responses from other parts
-                let fn_parts: Vec<GeminiPart> = parts.iter()
-                    .filter(|p| p.function_response.is_some())
-                    .cloned()
-                    .collect();
-                let other_parts: Vec<GeminiPart> = parts.iter()
-                    .filter(|p| p.function_response.is_none())
-                    .cloned()
-                    .collect();
-
+                // Function responses can only be sent by the user
+                let (fn_parts, other_parts): (Vec<GeminiPart>, Vec<GeminiPart>) =
+                    parts.into_iter().partition(|p| p.function_response.is_some());
                 if !other_parts.is_empty() {
                     gemini_contents.push(GeminiContent {
                         role: gemini_role.to_string(),
                         parts: other_parts,
                     });
                 }
-                if !fn_parts.is_empty() {
-                    gemini_contents.push(GeminiContent {
-                        role: "user".to_string(),
-                        parts: fn_parts,
-                    });
-                }
-            } else {
-                if !parts.is_empty() {
-                    gemini_contents.push(GeminiContent { role, parts });
-                }
+                gemini_contents.push(GeminiContent {
+                    role: "user".to_string(),
+                    parts: fn_parts,
+                });
             }
         }
 
         // Gemini requires alternating user/model turns - merge consecutive same-role messages
-        self.merge_consecutive_roles(gemini_contents)
+        Ok(self.merge_consecutive_roles(gemini_contents))
     }
 
     /// Merge consecutive messages with the same role (Gemini requires alternation)
@@ -439,30 +767,24 @@ impl GeminiProvider {
     }
 
     /// Convert internal content to Gemini parts
-    async fn convert_content_to_parts(&self, content: &MessageContent, _role: &str) -> Vec<GeminiPart> {
+    ///
+    /// `tool_use_names` maps every tool_use id in the conversation to its tool name.
+    async fn convert_content_to_parts(
+        &self,
+        content: &MessageContent,
+        tool_use_names: &HashMap<String, String>,
+    ) -> Result<Vec<GeminiPart>> {
         match content {
             MessageContent::Text(text) => {
-                vec![GeminiPart {
+                Ok(vec![GeminiPart {
                     text: Some(text.clone()),
                     ..Default::default()
-                }]
+                }])
             }
             MessageContent::Blocks(blocks) => {
                 let mut parts = Vec::new();
-                // Track tool names for tool_result -> functionResponse mapping
-                // We need to find the corresponding tool_use name for each tool_result
-                let tool_use_names: std::collections::HashMap<String, String> = blocks
-                    .iter()
-                    .filter_map(|b| {
-                        if let ContentBlock::ToolUse { id, name, .. } = b {
-                            Some((id.clone(), name.clone()))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
 
-                for block in blocks {
+                for (index, block) in blocks.iter().enumerate() {
                     match block {
                         ContentBlock::Text { text, .. } => {
                             // Skip empty text blocks
@@ -495,19 +817,35 @@ impl GeminiProvider {
                             is_error,
                             ..
                         } => {
-                            // Find the tool name from our context or from the history
-                            let tool_name = tool_use_names
-                                .get(tool_use_id)
-                                .cloned()
-                                .unwrap_or_else(|| {
-                                    // Try to find from previous messages stored in session
-                                    // Fallback: use the tool_use_id as a stand-in
-                                    tool_use_id.clone()
-                                });
+                            // Image results have no text; the image is the next block
+                            let attachment_follows = matches!(
+                                blocks.get(index + 1),
+                                Some(ContentBlock::Image { .. } | ContentBlock::Document { .. })
+                            );
+                            let result_content = content.clone().unwrap_or_else(|| {
+                                if attachment_follows {
+                                    "The result is attached.".to_string()
+                                } else {
+                                    "No output".to_string()
+                                }
+                            });
 
-                            let result_content = content
-                                .clone()
-                                .unwrap_or_else(|| "No output".to_string());
+                            // A result whose call is gone from the history (e.g. trimmed
+                            // by compaction) can't be a functionResponse; keep it as text
+                            let Some(tool_name) = tool_use_names.get(tool_use_id).cloned() else {
+                                tracing::warn!(
+                                    "[GeminiProvider] No tool_use found for tool_result {}; sending it as text",
+                                    tool_use_id
+                                );
+                                parts.push(GeminiPart {
+                                    text: Some(format!(
+                                        "Result of tool call {}:\n{}",
+                                        tool_use_id, result_content
+                                    )),
+                                    ..Default::default()
+                                });
+                                continue;
+                            };
 
                             let response = if is_error.unwrap_or(false) {
                                 serde_json::json!({
@@ -538,34 +876,124 @@ impl GeminiProvider {
                         ContentBlock::RedactedThinking { .. } => {
                             // Skip redacted thinking
                         }
-                        ContentBlock::Image { source, .. } => {
+                        ContentBlock::ServerToolUse { .. } | ContentBlock::WebSearchToolResult { .. } => {
+                            // Anthropic server tools have no Gemini equivalent - skip them
+                        }
+                        ContentBlock::SearchResult { .. } => {
+                            // No citations in Gemini: send the result as text
                             parts.push(GeminiPart {
-                                inline_data: Some(GeminiInlineData {
-                                    mime_type: source.media_type.clone(),
-                                    data: source.data.clone(),
-                                }),
+                                text: block.search_result_text(),
                                 ..Default::default()
                             });
                         }
+                        ContentBlock::Image { source, .. } => {
+                            let mime_type = gemini_media_type(&source.media_type, GEMINI_IMAGE_TYPES, "image")?;
+                            parts.push(self.media_part(mime_type, &source.data).await?);
+                        }
                         ContentBlock::Document { source, .. } => {
-                            parts.push(GeminiPart {
-                                inline_data: Some(GeminiInlineData {
-                                    mime_type: source.media_type.clone(),
-                                    data: source.data.clone(),
-                                }),
-                                ..Default::default()
-                            });
+                            let mime_type =
+                                gemini_media_type(&source.media_type, GEMINI_DOCUMENT_TYPES, "document")?;
+                            parts.push(self.media_part(mime_type, &source.data).await?);
                         }
                     }
                 }
 
-                // For user messages with tool results, we need to map tool_use_ids to names.
-                // The tool_use_names map above handles this for blocks within the same message.
-                // For cross-message lookups, we rely on the session history being passed correctly.
+                Ok(parts)
+            }
+        }
+    }
 
-                parts
+    /// Build the part for base64 media: inline, or a Files API reference when
+    /// it's over the upload threshold
+    async fn media_part(&self, mime_type: String, data: &str) -> Result<GeminiPart> {
+        // Decoded size of the base64 payload
+        let size = data.len() / 4 * 3;
+        if let Some(threshold) = self.files_api_threshold {
+            if size > threshold {
+                let file_uri = self.upload_file(&mime_type, data).await?;
+                return Ok(GeminiPart {
+                    file_data: Some(GeminiFileData { mime_type, file_uri }),
+                    ..Default::default()
+                });
             }
         }
+        Ok(GeminiPart {
+            inline_data: Some(GeminiInlineData {
+                mime_type,
+                data: data.to_string(),
+            }),
+            ..Default::default()
+        })
+    }
+
+    /// Upload base64 data through the Files API, returning the file URI
+    ///
+    /// Uses the resumable protocol in two requests: one that announces the
+    /// file and returns an upload URL, and one that sends the bytes.
+    async fn upload_file(&self, mime_type: &str, data: &str) -> Result<String> {
+        use base64::Engine;
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        (mime_type, data).hash(&mut hasher);
+        let key = hasher.finish();
+        if let Some(uri) = self.uploaded_files.lock().await.get(&key) {
+            return Ok(uri.clone());
+        }
+
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .context("Attachment is not valid base64")?;
+        let auth_config = self.auth.get_auth().await
+            .context("Failed to get authentication credentials")?;
+        let api_base = auth_config.base_url.as_deref().unwrap_or(&self.api_base);
+        tracing::info!("[Gemini] Uploading {} attachment ({} bytes) to the Files API", mime_type, bytes.len());
+
+        let metadata = serde_json::json!({"file": {"display_name": format!("attachment-{:016x}", key)}});
+        let start = self
+            .client
+            .post(format!("{}/files", upload_base(api_base)))
+            .header("x-goog-api-key", &auth_config.api_key)
+            .header("X-Goog-Upload-Protocol", "resumable")
+            .header("X-Goog-Upload-Command", "start")
+            .header("X-Goog-Upload-Header-Content-Length", bytes.len())
+            .header("X-Goog-Upload-Header-Content-Type", mime_type)
+            .header("Content-Type", "application/json")
+            .body(metadata.to_string())
+            .send()
+            .await
+            .context("Failed to start Gemini file upload")?;
+        let status = start.status();
+        if !status.is_success() {
+            let body = start.text().await.unwrap_or_default();
+            anyhow::bail!("Gemini file upload failed ({}): {}", status, body);
+        }
+        let upload_url = start
+            .headers()
+            .get("x-goog-upload-url")
+            .and_then(|v| v.to_str().ok())
+            .context("Gemini file upload response has no upload URL")?
+            .to_string();
+
+        let finish = self
+            .client
+            .post(&upload_url)
+            .header("X-Goog-Upload-Offset", "0")
+            .header("X-Goog-Upload-Command", "upload, finalize")
+            .body(bytes)
+            .send()
+            .await
+            .context("Failed to upload file to Gemini")?;
+        let status = finish.status();
+        let body = finish.text().await.unwrap_or_default();
+        if !status.is_success() {
+            anyhow::bail!("Gemini file upload failed ({}): {}", status, body);
+        }
+        let uploaded: GeminiUploadResponse =
+            serde_json::from_str(&body).context("Failed to parse Gemini file upload response")?;
+
+        self.uploaded_files.lock().await.insert(key, uploaded.file.uri.clone());
+        Ok(uploaded.file.uri)
     }
 
     /// Convert internal tool definitions to Gemini function declarations
@@ -605,7 +1033,9 @@ impl GeminiProvider {
                         })
                     }
                     // Built-in Anthropic tools don't map to Gemini - skip them
-                    ToolDefinition::Bash(_) | ToolDefinition::TextEditor(_) => None,
+                    ToolDefinition::Bash(_)
+                    | ToolDefinition::TextEditor(_)
+                    | ToolDefinition::WebSearch(_) => None,
                 }
             })
             .collect();
@@ -648,7 +1078,7 @@ impl GeminiProvider {
                 Value::Object(cleaned)
             }
             Value::Array(arr) => {
-                Value::Array(arr.iter().map(|v| Self::clean_schema_for_gemini(v)).collect())
+                Value::Array(arr.iter().map(Self::clean_schema_for_gemini).collect())
             }
             other => other.clone(),
         }
@@ -679,35 +1109,20 @@ impl GeminiProvider {
 
     /// Convert tool choice to Gemini format
     fn convert_tool_config(&self, tool_choice: &Option<ToolChoice>) -> Option<GeminiToolConfig> {
-        match tool_choice {
-            Some(ToolChoice::Auto { .. }) | None => {
-                // AUTO is the default, only set if tools are present
-                Some(GeminiToolConfig {
-                    function_calling_config: GeminiFunctionCallingConfig {
-                        mode: "AUTO".to_string(),
-                    },
-                })
-            }
-            Some(ToolChoice::Any { .. }) => Some(GeminiToolConfig {
-                function_calling_config: GeminiFunctionCallingConfig {
-                    mode: "ANY".to_string(),
-                },
-            }),
-            Some(ToolChoice::None) => Some(GeminiToolConfig {
-                function_calling_config: GeminiFunctionCallingConfig {
-                    mode: "NONE".to_string(),
-                },
-            }),
-            Some(ToolChoice::Tool { .. }) => {
-                // Gemini doesn't have a direct equivalent of "must use specific tool"
-                // Fall back to ANY mode
-                Some(GeminiToolConfig {
-                    function_calling_config: GeminiFunctionCallingConfig {
-                        mode: "ANY".to_string(),
-                    },
-                })
-            }
-        }
+        let (mode, allowed_function_names) = match tool_choice {
+            // AUTO is the default, only set if tools are present
+            Some(ToolChoice::Auto { .. }) | None => ("AUTO", None),
+            Some(ToolChoice::Any { .. }) => ("ANY", None),
+            Some(ToolChoice::None) => ("NONE", None),
+            // "Must use this tool" is ANY restricted to that one function
+            Some(ToolChoice::Tool { name, .. }) => ("ANY", Some(vec![name.clone()])),
+        };
+        Some(GeminiToolConfig {
+            function_calling_config: GeminiFunctionCallingConfig {
+                mode: mode.to_string(),
+                allowed_function_names,
+            },
+        })
     }
 
     /// Convert internal ThinkingConfig to Gemini format
@@ -754,15 +1169,25 @@ impl GeminiProvider {
 
     /// Convert Gemini response to internal MessageResponse format
     async fn convert_response(&self, gemini_resp: GeminiResponse) -> Result<MessageResponse> {
+        if let Some(error) = gemini_resp.prompt_feedback.as_ref().and_then(prompt_blocked_error) {
+            return Err(error);
+        }
         let candidate = gemini_resp
             .candidates
             .as_ref()
             .and_then(|c| c.first())
             .context("No candidates in Gemini response")?;
 
-        let content_blocks = self.convert_gemini_parts_to_blocks(
+        let mut content_blocks = self.convert_gemini_parts_to_blocks(
             candidate.content.as_ref().map(|c| &c.parts[..]).unwrap_or(&[]),
         ).await;
+        if candidate.finish_reason.as_deref() == Some("SAFETY") {
+            content_blocks.push(ContentBlock::Text {
+                text: safety_block_notice(&candidate.safety_ratings),
+                cache_control: None,
+                citations: None,
+            });
+        }
 
         let stop_reason = candidate.finish_reason.as_deref().map(|r| match r {
             "STOP" => StopReason::EndTurn,
@@ -782,19 +1207,7 @@ impl GeminiProvider {
             stop_reason
         };
 
-        let usage = gemini_resp.usage_metadata.as_ref().map(|u| Usage {
-            input_tokens: u.prompt_token_count,
-            output_tokens: u.candidates_token_count,
-            cache_creation_input_tokens: None,
-            cache_read_input_tokens: None,
-            thoughts_token_count: u.thoughts_token_count,
-        }).unwrap_or(Usage {
-            input_tokens: 0,
-            output_tokens: 0,
-            cache_creation_input_tokens: None,
-            cache_read_input_tokens: None,
-            thoughts_token_count: None,
-        });
+        let usage = gemini_resp.usage_metadata.unwrap_or_default().to_usage();
 
         Ok(MessageResponse {
             id: gemini_resp.response_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
@@ -825,6 +1238,7 @@ impl GeminiProvider {
                     blocks.push(ContentBlock::Text {
                         text: text.clone(),
                         cache_control: None,
+                        citations: None,
                     });
                 }
             }
@@ -862,6 +1276,7 @@ impl GeminiProvider {
     }
 
     /// Send a non-streaming request to the Gemini API
+    #[tracing::instrument(name = "llm_request", skip_all, fields(provider = "gemini", model = %self.model, session_id = session_id.unwrap_or_default()))]
     async fn send_gemini_request(&self, request: &GeminiRequest, session_id: Option<&str>) -> Result<GeminiResponse> {
         // Get auth credentials (static or from provider)
         let auth_config = self.auth.get_auth().await
@@ -869,48 +1284,54 @@ impl GeminiProvider {
         let api_base = auth_config.base_url.as_deref().unwrap_or(&self.api_base);
         let url = self.api_url_with_base(api_base, "generateContent");
 
-        let request_json = serde_json::to_string(request)
-            .context("Failed to serialize Gemini request")?;
-        tracing::debug!("[Gemini] Request JSON: {}", request_json);
-
-        let mut request_builder = self
-            .client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .header("x-goog-api-key", &auth_config.api_key);
-
+        let mut parts = http_parts(&url, &auth_config.api_key)?;
         // Add agent-session-id header if session_id is provided
         if let Some(sid) = session_id {
-            request_builder = request_builder.header("agent-session-id", sid);
+            parts.set_header("agent-session-id", sid)?;
         }
 
+        let (request_builder, request_json) = prepare_request(&self.client, &self.interceptors, parts, request)
+            .context("Failed to prepare Gemini request")?;
+        tracing::debug!("[Gemini] Request JSON: {}", request_json);
+
+        let started = self.stats.begin();
         let response = request_builder
-            .body(request_json)
             .send()
             .await
+            .inspect_err(|_| self.stats.record_error(ErrorClass::Network))
             .context("Failed to send request to Gemini API")?;
 
         let status = response.status();
         let response_text = response
             .text()
             .await
+            .inspect_err(|_| self.stats.record_error(ErrorClass::Network))
             .context("Failed to read Gemini response body")?;
+        notify_received(&self.interceptors, status, Some(&response_text));
 
         tracing::debug!("[Gemini] Response status: {}", status);
         tracing::debug!("[Gemini] Response body: {}", response_text);
 
         if !status.is_success() {
             tracing::error!("[Gemini] API error: {} - {}", status, response_text);
+            self.stats.record_error(ErrorClass::from_status(status));
             anyhow::bail!("Gemini API error ({}): {}", status, response_text);
         }
 
         let gemini_response: GeminiResponse = serde_json::from_str(&response_text)
+            .inspect_err(|_| self.stats.record_error(ErrorClass::Parse))
             .context("Failed to parse Gemini API response")?;
+        let (input_tokens, output_tokens) = gemini_response
+            .usage_metadata
+            .as_ref()
+            .map_or((0, 0), |u| (u.prompt_token_count, u.candidates_token_count));
+        self.stats.record_success(started, input_tokens, output_tokens);
 
         Ok(gemini_response)
     }
 
     /// Send a streaming request to the Gemini API
+    #[tracing::instrument(name = "llm_stream", skip_all, fields(provider = "gemini", model = %self.model, session_id = session_id.unwrap_or_default()))]
     async fn send_gemini_streaming_request(
         &self,
         request: &GeminiRequest,
@@ -922,25 +1343,21 @@ impl GeminiProvider {
         let api_base = auth_config.base_url.as_deref().unwrap_or(&self.api_base);
         let url = format!("{}?alt=sse", self.api_url_with_base(api_base, "streamGenerateContent"));
 
-        let request_json = serde_json::to_string(request)
-            .context("Failed to serialize Gemini streaming request")?;
-        tracing::debug!("[Gemini] Streaming request JSON: {}", request_json);
-
-        let mut request_builder = self
-            .client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .header("x-goog-api-key", &auth_config.api_key);
-
+        let mut parts = http_parts(&url, &auth_config.api_key)?;
         // Add agent-session-id header if session_id is provided
         if let Some(sid) = session_id {
-            request_builder = request_builder.header("X-Agent-Session-Id", sid);
+            parts.set_header("X-Agent-Session-Id", sid)?;
         }
 
+        let (request_builder, request_json) = prepare_request(&self.client, &self.interceptors, parts, request)
+            .context("Failed to prepare Gemini streaming request")?;
+        tracing::debug!("[Gemini] Streaming request JSON: {}", request_json);
+
+        let started = self.stats.begin();
         let response = request_builder
-            .body(request_json)
             .send()
             .await
+            .inspect_err(|_| self.stats.record_error(ErrorClass::Network))
             .context("Failed to send streaming request to Gemini API")?;
 
         let status = response.status();
@@ -950,10 +1367,14 @@ impl GeminiProvider {
                 .text()
                 .await
                 .unwrap_or_else(|_| "Failed to read error body".to_string());
+            notify_received(&self.interceptors, status, Some(&error_text));
             tracing::error!("[Gemini] Streaming API error: {} - {}", status, error_text);
+            self.stats.record_error(ErrorClass::from_status(status));
             anyhow::bail!("Gemini API error ({}): {}", status, error_text);
         }
 
+        self.stats.record_started(started);
+        notify_received(&self.interceptors, status, None);
         tracing::info!("[Gemini] Streaming response started");
 
         // Parse SSE stream and convert to internal StreamEvent format
@@ -972,6 +1393,8 @@ impl GeminiProvider {
             let mut prev_had_text = false;
             let mut prev_had_function = false;
             let mut finished = false;
+            // Usage so far; later chunks repeat and update it
+            let mut usage_metadata = GeminiUsageMetadata::default();
 
             tracing::info!("[Gemini] Stream: starting to read lines");
 
@@ -1000,22 +1423,16 @@ impl GeminiProvider {
                         continue;
                     }
                 };
+                if let Some(error) = gemini_resp.prompt_feedback.as_ref().and_then(prompt_blocked_error) {
+                    Err(error)?;
+                }
+                if let Some(metadata) = &gemini_resp.usage_metadata {
+                    usage_metadata.update(metadata);
+                }
 
                 // First chunk - emit MessageStart
                 if chunk_index == 0 {
-                    let usage = gemini_resp.usage_metadata.as_ref().map(|u| Usage {
-                        input_tokens: u.prompt_token_count,
-                        output_tokens: u.candidates_token_count,
-                        cache_creation_input_tokens: None,
-                        cache_read_input_tokens: None,
-                        thoughts_token_count: u.thoughts_token_count,
-                    }).unwrap_or(Usage {
-                        input_tokens: 0,
-                        output_tokens: 0,
-                        cache_creation_input_tokens: None,
-                        cache_read_input_tokens: None,
-                        thoughts_token_count: None,
-                    });
+                    let usage = usage_metadata.to_usage();
 
                     yield StreamEvent::MessageStart(MessageStartEvent {
                         message: MessageStartData {
@@ -1031,9 +1448,10 @@ impl GeminiProvider {
                     });
                 }
 
-                // Process candidate content
+                // Process the first candidate's content; with a candidate
+                // count above 1 the others would interleave into its blocks
                 if let Some(candidates) = &gemini_resp.candidates {
-                    for candidate in candidates {
+                    for candidate in candidates.iter().filter(|c| c.index.unwrap_or(0) == 0).take(1) {
                         if let Some(content) = &candidate.content {
                             for part in &content.parts {
                                 // Handle text parts
@@ -1169,6 +1587,20 @@ impl GeminiProvider {
                                 content_block_started = false;
                             }
 
+                            if reason == "SAFETY" {
+                                yield StreamEvent::ContentBlockStart(ContentBlockStartEvent {
+                                    index: 0,
+                                    content_block: ContentBlockStart::Text { text: String::new() },
+                                });
+                                yield StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent {
+                                    index: 0,
+                                    delta: ContentDelta::TextDelta {
+                                        text: safety_block_notice(&candidate.safety_ratings),
+                                    },
+                                });
+                                yield StreamEvent::ContentBlockStop(ContentBlockStopEvent { index: 0 });
+                            }
+
                             let stop_reason = match reason.as_str() {
                                 "STOP" => StopReason::EndTurn,
                                 "MAX_TOKENS" => StopReason::MaxTokens,
@@ -1183,16 +1615,12 @@ impl GeminiProvider {
                                 stop_reason
                             };
 
-                            let output_tokens = gemini_resp.usage_metadata.as_ref()
-                                .map(|u| u.candidates_token_count)
-                                .unwrap_or(0);
-
                             yield StreamEvent::MessageDelta(MessageDeltaEvent {
                                 delta: MessageDeltaData {
                                     stop_reason: Some(stop_reason),
                                     stop_sequence: None,
                                 },
-                                usage: DeltaUsage { output_tokens },
+                                usage: DeltaUsage::from_usage(&usage_metadata.to_usage()),
                             });
 
                             // Mark as finished - don't wait for more data
@@ -1220,7 +1648,7 @@ impl GeminiProvider {
             yield StreamEvent::MessageStop;
         };
 
-        Ok(Box::pin(stream))
+        Ok(self.stats.track_stream(Box::pin(stream)))
     }
 
     /// Build a GeminiRequest from internal types
@@ -1231,32 +1659,119 @@ impl GeminiProvider {
         tools: &[ToolDefinition],
         tool_choice: &Option<ToolChoice>,
         thinking: &Option<ThinkingConfig>,
-    ) -> GeminiRequest {
-        let contents = self.convert_messages(messages).await;
+        generation: &Option<GenerationParams>,
+    ) -> Result<GeminiRequest> {
+        let contents = self.convert_messages(messages).await?;
         let system_instruction = self.convert_system_prompt(system);
-        let gemini_tools = self.convert_tools(tools);
+        let generation = generation.clone().unwrap_or_default();
+        let mut tools = tools.to_vec();
+        let mut tool_choice = tool_choice.clone();
+        // JSON mode is native: a MIME type and a schema in Gemini's dialect.
+        // Gemini refuses it next to function declarations, so with tools the
+        // response comes through the hidden tool, as on the Messages API.
+        let (response_mime_type, response_schema) = match &generation.response_format {
+            Some(format) if !tools.is_empty() => {
+                tool_choice = json_tool::apply(format, &mut tools, tool_choice, thinking.is_some());
+                (None, None)
+            }
+            Some(format) => (
+                Some("application/json".to_string()),
+                format.schema().map(Self::clean_schema_for_gemini),
+            ),
+            None if tools.is_empty() => (self.options.response_mime_type.clone(), None),
+            None => {
+                if let Some(mime_type) = &self.options.response_mime_type {
+                    tracing::warn!("[Gemini] Not sending response MIME type {} with tools", mime_type);
+                }
+                (None, None)
+            }
+        };
+        let gemini_tools = self.convert_tools(&tools);
         let tool_config = if gemini_tools.is_some() {
-            self.convert_tool_config(tool_choice)
+            self.convert_tool_config(&tool_choice)
         } else {
             None
         };
 
         let thinking_config = self.convert_thinking_config(thinking);
 
-        GeminiRequest {
+        Ok(GeminiRequest {
             contents,
             system_instruction,
             tools: gemini_tools,
             tool_config,
             generation_config: Some(GeminiGenerationConfig {
-                max_output_tokens: Some(self.max_tokens),
-                temperature: Some(1.0),
+                max_output_tokens: Some(generation.max_tokens.unwrap_or(self.max_tokens)),
+                // Gemini recommends temperature 1.0, so that stays the default
+                temperature: Some(generation.temperature.unwrap_or(1.0)),
+                top_p: generation.top_p.or(self.options.top_p),
+                top_k: self.options.top_k,
+                candidate_count: self.options.candidate_count,
+                response_mime_type,
+                response_schema,
+                stop_sequences: if generation.stop_sequences.is_empty() {
+                    None
+                } else {
+                    Some(generation.stop_sequences)
+                },
                 thinking_config,
             }),
-        }
+            safety_settings: self
+                .options
+                .safety_settings
+                .iter()
+                .map(|&(category, threshold)| GeminiSafetySetting { category, threshold })
+                .collect(),
+        })
+    }
+}
+
+/// The response format a request answers through the hidden JSON tool
+///
+/// That is the case when it asks for JSON and also sends tools (see `build_request`).
+fn json_tool_format(tools: &[ToolDefinition], generation: &Option<GenerationParams>) -> Option<ResponseFormat> {
+    generation
+        .as_ref()
+        .and_then(|g| g.response_format.clone())
+        .filter(|_| !tools.is_empty())
+}
+
+/// Check a media type against what Gemini accepts
+fn gemini_media_type(media_type: &str, supported: &[&str], kind: &str) -> Result<String> {
+    let normalized = match media_type.to_ascii_lowercase().as_str() {
+        "image/jpg" => "image/jpeg".to_string(),
+        other => other.to_string(),
+    };
+    if supported.contains(&normalized.as_str()) {
+        Ok(normalized)
+    } else {
+        anyhow::bail!(
+            "Gemini does not accept {} attachments of type {} (supported: {})",
+            kind,
+            media_type,
+            supported.join(", ")
+        )
     }
 }
 
+/// Method, URL and standard headers for a generateContent request
+fn http_parts(url: &str, api_key: &str) -> Result<HttpParts> {
+    let mut parts = HttpParts::post(url);
+    parts.set_header("Content-Type", "application/json")?;
+    parts.set_header("x-goog-api-key", api_key)?;
+    Ok(parts)
+}
+
+/// The Files API upload endpoint for an API base (`.../v1beta` -> `.../upload/v1beta`)
+fn upload_base(api_base: &str) -> String {
+    let api_base = api_base.trim_end_matches('/');
+    let path_start = api_base
+        .find("://")
+        .and_then(|scheme| api_base[scheme + 3..].find('/').map(|i| scheme + 3 + i))
+        .unwrap_or(api_base.len());
+    format!("{}/upload{}", &api_base[..path_start], &api_base[path_start..])
+}
+
 // ============================================================================
 // LlmProvider implementation
 // ============================================================================
@@ -1276,7 +1791,7 @@ impl LlmProvider for GeminiProvider {
         messages.push(Message::user(user_message));
 
         let system = system_prompt.map(|s| SystemPrompt::Text(s.to_string()));
-        let request = self.build_request(&messages, &system, &[], &None, &None).await;
+        let request = self.build_request(&messages, &system, &[], &None, &None, &None).await?;
 
         let gemini_response = self.send_gemini_request(&request, session_id).await?;
         let response = self.convert_response(gemini_response).await?;
@@ -1291,15 +1806,24 @@ impl LlmProvider for GeminiProvider {
         tools: Vec<ToolDefinition>,
         tool_choice: Option<ToolChoice>,
         thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
         session_id: Option<&str>,
     ) -> Result<MessageResponse> {
         tracing::info!("[Gemini] Sending message with tools");
         tracing::debug!("[Gemini] Messages count: {}", messages.len());
         tracing::debug!("[Gemini] Tools count: {}", tools.len());
 
-        let request = self.build_request(&messages, &system, &tools, &tool_choice, &thinking).await;
+        let format = json_tool_format(&tools, &generation);
+        let request = self.build_request(
+            &messages, &system, &tools, &tool_choice, &thinking, &generation,
+        )
+        .await?;
         let gemini_response = self.send_gemini_request(&request, session_id).await?;
-        self.convert_response(gemini_response).await
+        let mut response = self.convert_response(gemini_response).await?;
+        if let Some(format) = format {
+            json_tool::unwrap_response(&format, &mut response);
+        }
+        Ok(response)
     }
 
     async fn stream_with_tools_and_system(
@@ -1309,25 +1833,743 @@ impl LlmProvider for GeminiProvider {
         tools: Vec<ToolDefinition>,
         tool_choice: Option<ToolChoice>,
         thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
         session_id: Option<&str>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>> {
         tracing::info!("[Gemini] Streaming message with tools");
         tracing::debug!("[Gemini] Messages count: {}", messages.len());
         tracing::debug!("[Gemini] Tools count: {}", tools.len());
 
-        let request = self.build_request(&messages, &system, &tools, &tool_choice, &thinking).await;
-        self.send_gemini_streaming_request(&request, session_id).await
+        let format = json_tool_format(&tools, &generation);
+        let request = self.build_request(
+            &messages, &system, &tools, &tool_choice, &thinking, &generation,
+        )
+        .await?;
+        let stream = self.send_gemini_streaming_request(&request, session_id).await?;
+        Ok(match format {
+            Some(format) => json_tool::unwrap_stream(format, stream),
+            None => stream,
+        })
     }
 
     fn model(&self) -> String {
         self.model.clone()
     }
 
+    fn max_tokens(&self) -> Option<u32> {
+        Some(self.max_tokens)
+    }
+
     fn provider_name(&self) -> &str {
         "gemini"
     }
 
+    fn stats(&self) -> Option<ProviderStats> {
+        Some(self.stats.snapshot())
+    }
+
+    fn reset_stats(&self) {
+        self.stats.reset();
+    }
+
     fn create_variant(&self, model: &str, max_tokens: u32) -> Arc<dyn LlmProvider> {
         Arc::new(self.create_variant_impl(model, max_tokens))
     }
+
+    async fn forget_tool_calls(&self, tool_use_ids: &[String]) {
+        let mut sigs = self.thought_signatures.lock().await;
+        for id in tool_use_ids {
+            sigs.remove(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn generation_config_json(generation: Option<GenerationParams>) -> serde_json::Value {
+        let provider = GeminiProvider::new("test-key").unwrap();
+        let request = provider
+            .build_request(&[Message::user("Hi")], &None, &[], &None, &None, &generation)
+            .await
+            .unwrap();
+        serde_json::to_value(&request).unwrap()["generationConfig"].clone()
+    }
+
+    #[tokio::test]
+    async fn test_generation_params_serialization() {
+        let config = generation_config_json(None).await;
+        assert_eq!(config["temperature"], 1.0);
+        assert!(config.get("topP").is_none());
+        assert!(config.get("stopSequences").is_none());
+
+        let generation = GenerationParams::new()
+            .with_temperature(0.25)
+            .with_top_p(0.5)
+            .with_stop_sequences(vec!["END".to_string()]);
+        let config = generation_config_json(Some(generation)).await;
+        assert_eq!(config["temperature"], 0.25);
+        assert_eq!(config["topP"], 0.5);
+        assert_eq!(config["stopSequences"], serde_json::json!(["END"]));
+    }
+
+    #[tokio::test]
+    async fn test_gemini_options_serialization() {
+        let provider = GeminiProvider::new("test-key").unwrap().with_options(
+            GeminiOptions::new()
+                .with_safety_setting(HarmCategory::DangerousContent, HarmBlockThreshold::BlockLowAndAbove)
+                .with_safety_setting(HarmCategory::Harassment, HarmBlockThreshold::Off)
+                .with_safety_setting(HarmCategory::DangerousContent, HarmBlockThreshold::BlockOnlyHigh)
+                .with_top_p(0.9)
+                .with_top_k(40)
+                .with_candidate_count(1)
+                .with_response_mime_type("application/json"),
+        );
+        let request = |generation| {
+            let provider = &provider;
+            async move {
+                let request = provider
+                    .build_request(&[Message::user("Hi")], &None, &[], &None, &None, &generation)
+                    .await
+                    .unwrap();
+                serde_json::to_value(&request).unwrap()
+            }
+        };
+
+        let body = request(None).await;
+        assert_eq!(
+            body["safetySettings"],
+            serde_json::json!([
+                {"category": "HARM_CATEGORY_HARASSMENT", "threshold": "OFF"},
+                {"category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "BLOCK_ONLY_HIGH"}
+            ])
+        );
+        let config = &body["generationConfig"];
+        assert_eq!(config["topP"], 0.9_f32);
+        assert_eq!(config["topK"], 40);
+        assert_eq!(config["candidateCount"], 1);
+        assert_eq!(config["responseMimeType"], "application/json");
+
+        // Per-request parameters win over the provider's defaults
+        let body = request(Some(GenerationParams::new().with_top_p(0.5).with_temperature(0.2))).await;
+        assert_eq!(body["generationConfig"]["topP"], 0.5);
+        assert_eq!(body["generationConfig"]["temperature"], 0.2_f32);
+
+        // Nothing Gemini-specific is sent by default
+        let body = serde_json::to_value(
+            GeminiProvider::new("test-key")
+                .unwrap()
+                .build_request(&[Message::user("Hi")], &None, &[], &None, &None, &None)
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        assert!(body.get("safetySettings").is_none());
+        assert!(body["generationConfig"].get("topK").is_none());
+        assert!(body["generationConfig"].get("responseMimeType").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_json_response_format_is_native() {
+        use crate::llm::interceptor::tests::capture_server;
+        use crate::llm::ResponseFormat;
+
+        let body = serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{"text": "{\"name\": \"Ada\"}"}]},
+                "finishReason": "STOP"
+            }]
+        });
+        let (port, captured) = capture_server("application/json", body.to_string()).await;
+        let provider = GeminiProvider::with_auth_provider(move || async move {
+            Ok(AuthConfig::with_base_url("test-key", format!("http://127.0.0.1:{port}/v1beta")))
+        })
+        .with_model("gemini-test");
+
+        let format = ResponseFormat::json_schema(serde_json::json!({
+            "type": "object",
+            "properties": {"name": {"type": "string"}},
+            "required": ["name"],
+            "additionalProperties": false
+        }));
+        let generation = GenerationParams::new().with_response_format(format);
+        let response = provider
+            .send_with_tools_and_system(vec![Message::user("Who?")], None, vec![], None, None, Some(generation), None)
+            .await
+            .unwrap();
+        assert_eq!(response.text(), r#"{"name": "Ada"}"#);
+
+        let (_, request_body) = captured.lock().unwrap()[0].clone();
+        let request_body: serde_json::Value = serde_json::from_str(&request_body).unwrap();
+        let config = &request_body["generationConfig"];
+        assert_eq!(config["responseMimeType"], "application/json");
+        // Cleaned like tool schemas
+        assert_eq!(
+            config["responseSchema"],
+            serde_json::json!({"type": "object", "properties": {"name": {"type": "string"}}, "required": ["name"]})
+        );
+        assert!(request_body.get("tools").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_json_response_format_with_tools_uses_the_hidden_tool() {
+        use crate::llm::interceptor::tests::capture_server;
+
+        let body = serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{"functionCall": {"name": "json_response", "args": {"name": "Ada"}}}]},
+                "finishReason": "STOP"
+            }]
+        });
+        let (port, captured) = capture_server("application/json", body.to_string()).await;
+        let provider = GeminiProvider::with_auth_provider(move || async move {
+            Ok(AuthConfig::with_base_url("test-key", format!("http://127.0.0.1:{port}/v1beta")))
+        })
+        .with_model("gemini-test");
+
+        let lookup = ToolDefinition::Custom(crate::llm::types::CustomTool {
+            name: "Lookup".to_string(),
+            description: None,
+            input_schema: crate::llm::ToolInputSchema::new(),
+            tool_type: None,
+            cache_control: None,
+        });
+        let format = ResponseFormat::json_schema(serde_json::json!({
+            "type": "object",
+            "properties": {"name": {"type": "string"}},
+            "required": ["name"]
+        }));
+        let generation = GenerationParams::new().with_response_format(format);
+        let response = provider
+            .send_with_tools_and_system(vec![Message::user("Who?")], None, vec![lookup], None, None, Some(generation), None)
+            .await
+            .unwrap();
+        assert_eq!(response.text(), r#"{"name":"Ada"}"#);
+        assert_eq!(response.stop_reason, Some(StopReason::EndTurn));
+
+        let (_, request_body) = captured.lock().unwrap()[0].clone();
+        let request_body: serde_json::Value = serde_json::from_str(&request_body).unwrap();
+        assert!(request_body["generationConfig"].get("responseMimeType").is_none());
+        assert!(request_body["generationConfig"].get("responseSchema").is_none());
+        let names: Vec<_> = request_body["tools"][0]["functionDeclarations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|declaration| declaration["name"].clone())
+            .collect();
+        assert_eq!(names, vec!["Lookup", "json_response"]);
+        assert_eq!(request_body["toolConfig"]["functionCallingConfig"]["mode"], "ANY");
+    }
+
+    /// A response stopped by the safety filters
+    const BLOCKED_RESPONSE: &str = r#"{
+        "candidates": [{
+            "content": {"role": "model", "parts": []},
+            "finishReason": "SAFETY",
+            "index": 0,
+            "safetyRatings": [
+                {"category": "HARM_CATEGORY_HATE_SPEECH", "probability": "NEGLIGIBLE"},
+                {"category": "HARM_CATEGORY_DANGEROUS_CONTENT", "probability": "HIGH", "blocked": true},
+                {"category": "HARM_CATEGORY_HARASSMENT", "probability": "LOW"}
+            ]
+        }],
+        "usageMetadata": {"promptTokenCount": 12, "totalTokenCount": 12}
+    }"#;
+
+    #[tokio::test]
+    async fn test_safety_block_is_explained() {
+        let provider = GeminiProvider::new("test-key").unwrap();
+        let response = provider
+            .convert_response(serde_json::from_str(BLOCKED_RESPONSE).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.stop_reason, Some(StopReason::Refusal));
+        assert!(matches!(
+            &response.content[..],
+            [ContentBlock::Text { text, .. }]
+                if text == "[Response blocked by Gemini safety filters: HARM_CATEGORY_DANGEROUS_CONTENT (HIGH)]"
+        ), "{:?}", response.content);
+
+        // Without a blocked flag, every rating above NEGLIGIBLE is named
+        let ratings: Vec<GeminiSafetyRating> = serde_json::from_str(
+            r#"[{"category": "HARM_CATEGORY_HATE_SPEECH", "probability": "NEGLIGIBLE"},
+                {"category": "HARM_CATEGORY_HARASSMENT", "probability": "MEDIUM"}]"#,
+        )
+        .unwrap();
+        assert_eq!(describe_safety_ratings(&ratings), "HARM_CATEGORY_HARASSMENT (MEDIUM)");
+        assert_eq!(safety_block_notice(&[]), "[Response blocked by Gemini safety filters]");
+
+        // A blocked prompt has no candidates, only feedback
+        let blocked_prompt = r#"{
+            "promptFeedback": {
+                "blockReason": "SAFETY",
+                "safetyRatings": [{"category": "HARM_CATEGORY_SEXUALLY_EXPLICIT", "probability": "HIGH", "blocked": true}]
+            }
+        }"#;
+        let error = provider
+            .convert_response(serde_json::from_str(blocked_prompt).unwrap())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Gemini blocked the prompt (SAFETY): HARM_CATEGORY_SEXUALLY_EXPLICIT (HIGH)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streamed_safety_block_is_explained() {
+        use crate::llm::interceptor::tests::capture_server;
+
+        let chunk: Value = serde_json::from_str(BLOCKED_RESPONSE).unwrap();
+        let (port, _) = capture_server("text/event-stream", format!("data: {}\r\n\r\n", chunk)).await;
+        let provider = GeminiProvider::with_auth_provider(move || async move {
+            Ok(AuthConfig::with_base_url("test-key", format!("http://127.0.0.1:{port}/v1beta")))
+        })
+        .with_model("gemini-test");
+
+        let stream = provider
+            .stream_with_tools_and_system(vec![Message::user("Hi")], None, vec![], None, None, None, None)
+            .await
+            .unwrap();
+        let events: Vec<StreamEvent> = stream.map(|e| e.unwrap()).collect().await;
+        let text: String = events
+            .iter()
+            .filter_map(|e| match e {
+                StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent {
+                    delta: ContentDelta::TextDelta { text },
+                    ..
+                }) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(text, "[Response blocked by Gemini safety filters: HARM_CATEGORY_DANGEROUS_CONTENT (HIGH)]");
+        assert!(events.iter().any(|e| matches!(
+            e,
+            StreamEvent::MessageDelta(MessageDeltaEvent {
+                delta: MessageDeltaData { stop_reason: Some(StopReason::Refusal), .. },
+                ..
+            })
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_stream_reads_only_the_first_candidate() {
+        use crate::llm::interceptor::tests::capture_server;
+
+        let chunks = [
+            serde_json::json!({"candidates": [
+                {"index": 0, "content": {"role": "model", "parts": [{"text": "one"}]}},
+                {"index": 1, "content": {"role": "model", "parts": [{"text": "uno"}]}}
+            ]}),
+            serde_json::json!({"candidates": [
+                {"index": 1, "content": {"role": "model", "parts": [{"text": " dos"}]}, "finishReason": "STOP"}
+            ]}),
+            serde_json::json!({"candidates": [
+                {"index": 0, "content": {"role": "model", "parts": [{"text": " two"}]}, "finishReason": "STOP"}
+            ]}),
+        ];
+        let sse: String = chunks.iter().map(|chunk| format!("data: {}\r\n\r\n", chunk)).collect();
+        let (port, _) = capture_server("text/event-stream", sse).await;
+        let provider = GeminiProvider::with_auth_provider(move || async move {
+            Ok(AuthConfig::with_base_url("test-key", format!("http://127.0.0.1:{port}/v1beta")))
+        })
+        .with_model("gemini-test")
+        .with_options(GeminiOptions::new().with_candidate_count(2));
+
+        let stream = provider
+            .stream_with_tools_and_system(vec![Message::user("Count")], None, vec![], None, None, None, None)
+            .await
+            .unwrap();
+        let events: Vec<StreamEvent> = stream.map(|e| e.unwrap()).collect().await;
+        let text: String = events
+            .iter()
+            .filter_map(|e| match e {
+                StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent {
+                    delta: ContentDelta::TextDelta { text },
+                    ..
+                }) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(text, "one two");
+    }
+
+    #[test]
+    fn test_forced_tool_choice_restricts_function_names() {
+        let provider = GeminiProvider::new("test-key").unwrap();
+        let config = |choice| serde_json::to_value(provider.convert_tool_config(&choice)).unwrap();
+        assert_eq!(
+            config(Some(ToolChoice::tool("TodoWrite"))),
+            serde_json::json!({"functionCallingConfig": {"mode": "ANY", "allowedFunctionNames": ["TodoWrite"]}})
+        );
+        assert_eq!(config(Some(ToolChoice::auto())), serde_json::json!({"functionCallingConfig": {"mode": "AUTO"}}));
+    }
+
+    #[tokio::test]
+    async fn test_forget_tool_calls_drops_thought_signatures() {
+        let provider = GeminiProvider::new("test-key").unwrap();
+        {
+            let mut sigs = provider.thought_signatures.lock().await;
+            sigs.insert("gemini_tool_1".to_string(), "sig-1".to_string());
+            sigs.insert("gemini_tool_2".to_string(), "sig-2".to_string());
+        }
+
+        provider.forget_tool_calls(&["gemini_tool_2".to_string()]).await;
+
+        let sigs = provider.thought_signatures.lock().await;
+        assert_eq!(sigs.get("gemini_tool_1").map(String::as_str), Some("sig-1"));
+        assert!(!sigs.contains_key("gemini_tool_2"));
+    }
+
+    #[tokio::test]
+    async fn test_tool_result_uses_tool_name_from_previous_message() {
+        let provider = GeminiProvider::new("test-key").unwrap();
+        let messages = vec![
+            Message::user("List the files"),
+            Message::assistant_with_blocks(vec![ContentBlock::tool_use(
+                "gemini_tool_3",
+                "Glob",
+                serde_json::json!({"pattern": "*.rs"}),
+            )]),
+            Message::user_with_blocks(vec![ContentBlock::tool_result("gemini_tool_3", "main.rs", false)]),
+        ];
+
+        let contents = provider.convert_messages(&messages).await.unwrap();
+        let response = contents
+            .iter()
+            .flat_map(|c| &c.parts)
+            .find_map(|p| p.function_response.as_ref())
+            .expect("functionResponse part");
+        assert_eq!(response.name, "Glob");
+        assert_eq!(response.response, serde_json::json!({"result": "main.rs"}));
+
+        // Without its tool_use the result goes as text instead of a bogus name
+        let contents = provider.convert_messages(&messages[2..]).await.unwrap();
+        let parts = &contents[0].parts;
+        assert!(parts.iter().all(|p| p.function_response.is_none()));
+        assert!(parts[0].text.as_deref().unwrap().contains("main.rs"));
+    }
+
+    #[tokio::test]
+    async fn test_search_results_are_sent_as_text() {
+        let provider = GeminiProvider::new("test-key").unwrap();
+        let messages = vec![Message::user_with_blocks(vec![
+            ContentBlock::search_result("kb://auth", "Auth guide", "Tokens expire after 1 hour."),
+            ContentBlock::text("How long do tokens last?"),
+        ])];
+
+        let contents = provider.convert_messages(&messages).await.unwrap();
+        let texts: Vec<&str> = contents[0].parts.iter().filter_map(|p| p.text.as_deref()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "[Search result: Auth guide (kb://auth)]\nTokens expire after 1 hour.",
+                "How long do tokens last?"
+            ]
+        );
+    }
+
+    /// An assistant turn that read an image and a PDF, and the user message
+    /// carrying both results and their attachments
+    fn media_tool_turn(image_type: &str) -> Vec<Message> {
+        vec![
+            Message::user("Look at these"),
+            Message::assistant_with_blocks(vec![
+                ContentBlock::tool_use("t1", "Read", serde_json::json!({"file_path": "a.png"})),
+                ContentBlock::tool_use("t2", "Read", serde_json::json!({"file_path": "b.pdf"})),
+            ]),
+            Message::user_with_blocks(vec![
+                ContentBlock::ToolResult {
+                    tool_use_id: "t1".to_string(),
+                    content: None,
+                    is_error: None,
+                    cache_control: None,
+                },
+                ContentBlock::image("aW1hZ2U=".to_string(), image_type.to_string()),
+                ContentBlock::tool_result("t2", "PDF document: b.pdf", false),
+                ContentBlock::document("cGRm".to_string(), "application/pdf".to_string()),
+            ]),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_image_tool_result_is_next_to_its_image() {
+        let provider = GeminiProvider::new("test-key").unwrap();
+        let contents = provider.convert_messages(&media_tool_turn("image/png")).await.unwrap();
+
+        assert_eq!(contents.len(), 3);
+        let parts = &contents[2].parts;
+        assert_eq!(contents[2].role, "user");
+        assert_eq!(parts.len(), 4);
+
+        let response = parts[0].function_response.as_ref().unwrap();
+        assert_eq!(response.name, "Read");
+        assert_eq!(response.response, serde_json::json!({"result": "The result is attached."}));
+        let image = parts[1].inline_data.as_ref().unwrap();
+        assert_eq!(image.mime_type, "image/png");
+        assert_eq!(image.data, "aW1hZ2U=");
+    }
+
+    #[tokio::test]
+    async fn test_pdf_tool_result_is_next_to_its_document() {
+        let provider = GeminiProvider::new("test-key").unwrap();
+        let contents = provider.convert_messages(&media_tool_turn("image/jpg")).await.unwrap();
+        let parts = &contents[2].parts;
+
+        // image/jpg is accepted as image/jpeg
+        assert_eq!(parts[1].inline_data.as_ref().unwrap().mime_type, "image/jpeg");
+
+        let response = parts[2].function_response.as_ref().unwrap();
+        assert_eq!(response.response, serde_json::json!({"result": "PDF document: b.pdf"}));
+        let document = parts[3].inline_data.as_ref().unwrap();
+        assert_eq!(document.mime_type, "application/pdf");
+        assert_eq!(document.data, "cGRm");
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_media_type_is_rejected() {
+        let provider = GeminiProvider::new("test-key").unwrap();
+        let err = provider
+            .convert_messages(&media_tool_turn("image/gif"))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Gemini does not accept image attachments of type image/gif"), "{err}");
+        assert!(err.contains("image/png"), "{err}");
+    }
+
+    #[test]
+    fn test_upload_base() {
+        assert_eq!(
+            upload_base("https://generativelanguage.googleapis.com/v1beta"),
+            "https://generativelanguage.googleapis.com/upload/v1beta"
+        );
+        assert_eq!(upload_base("http://127.0.0.1:8080/v1beta/"), "http://127.0.0.1:8080/upload/v1beta");
+    }
+
+    #[tokio::test]
+    async fn test_large_attachments_go_through_files_api() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let counter = counter.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    let header_end = loop {
+                        if let Some(i) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                            break i + 4;
+                        }
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    };
+                    let head = String::from_utf8_lossy(&request[..header_end]).to_ascii_lowercase();
+                    let length: usize = head
+                        .lines()
+                        .find_map(|l| l.strip_prefix("content-length:"))
+                        .and_then(|l| l.trim().parse().ok())
+                        .unwrap_or(0);
+                    while request.len() < header_end + length {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => break,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    counter.fetch_add(1, Ordering::SeqCst);
+
+                    let response = if head.starts_with("post /upload/v1beta/files ") {
+                        assert!(head.contains("x-goog-upload-command: start"));
+                        assert!(head.contains("x-goog-upload-header-content-type: image/png"));
+                        format!(
+                            "HTTP/1.1 200 OK\r\nx-goog-upload-url: http://127.0.0.1:{port}/session\r\n\
+                            Content-Length: 0\r\nConnection: close\r\n\r\n"
+                        )
+                    } else {
+                        assert!(head.starts_with("post /session "));
+                        assert_eq!(&request[header_end..], b"image");
+                        let body = r#"{"file":{"uri":"https://files.example/abc"}}"#;
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                            Connection: close\r\n\r\n{}",
+                            body.len(),
+                            body
+                        )
+                    };
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+
+        let provider = GeminiProvider::with_auth_provider(move || async move {
+            Ok(AuthConfig::with_base_url("test-key", format!("http://127.0.0.1:{port}/v1beta")))
+        })
+        .with_files_api(4);
+
+        let messages = media_tool_turn("image/png");
+        for _ in 0..2 {
+            let contents = provider.convert_messages(&messages).await.unwrap();
+            let parts = &contents[2].parts;
+            let file = parts[1].file_data.as_ref().expect("image uploaded");
+            assert_eq!(file.mime_type, "image/png");
+            assert_eq!(file.file_uri, "https://files.example/abc");
+            assert!(parts[1].inline_data.is_none());
+            // The 3-byte PDF stays under the threshold
+            assert!(parts[3].inline_data.is_some());
+        }
+        // Uploaded once, then served from the cache
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_interceptors_reach_the_wire() {
+        use crate::llm::interceptor::tests::{capture_server, TagRequests};
+
+        let body = serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{"text": "hi"}]},
+                "finishReason": "STOP"
+            }]
+        });
+        let (port, captured) = capture_server("application/json", body.to_string()).await;
+        let provider = GeminiProvider::with_auth_provider(move || async move {
+            Ok(AuthConfig::with_base_url("test-key", format!("http://127.0.0.1:{port}/v1beta")))
+        })
+        .with_model("gemini-test")
+        .with_interceptor(Arc::new(TagRequests));
+
+        let reply = provider.send_message("Hi", &[], None, None).await.unwrap();
+        assert_eq!(reply, "hi");
+
+        let (head, request_body) = captured.lock().unwrap()[0].clone();
+        assert!(head.starts_with("post /v1beta/models/gemini-test:generatecontent "), "{head}");
+        assert!(head.contains("x-gateway-team: evals"), "{head}");
+        assert!(head.contains("x-goog-api-key: test-key"));
+        let request_body: serde_json::Value = serde_json::from_str(&request_body).unwrap();
+        assert_eq!(request_body["metadata"]["team"], "evals");
+        assert!(request_body["contents"].is_array());
+    }
+
+    #[tokio::test]
+    async fn test_stats_count_usage_and_errors() {
+        use crate::llm::interceptor::tests::{scripted_server, ScriptedResponse};
+
+        let body = serde_json::json!({
+            "candidates": [{
+                "content": {"role": "model", "parts": [{"text": "hi"}]},
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": {"promptTokenCount": 12, "candidatesTokenCount": 3, "totalTokenCount": 15}
+        });
+        let json = "application/json";
+        let (port, _) = scripted_server(vec![
+            ScriptedResponse::status(401, json, "{}".to_string()),
+            ScriptedResponse::status(400, json, "{}".to_string()),
+            ScriptedResponse::ok(json, body.to_string()),
+        ])
+        .await;
+        let provider = GeminiProvider::with_auth_provider(move || async move {
+            Ok(AuthConfig::with_base_url("test-key", format!("http://127.0.0.1:{port}/v1beta")))
+        })
+        .with_model("gemini-test");
+
+        for _ in 0..3 {
+            let _ = provider
+                .send_with_tools_and_system(vec![Message::user("Hi")], None, vec![], None, None, None, None)
+                .await;
+        }
+
+        let stats = LlmProvider::stats(&provider).unwrap();
+        assert_eq!(stats.requests, 3);
+        assert_eq!(stats.errors.auth, 1);
+        assert_eq!(stats.errors.invalid_request, 1);
+        assert_eq!(stats.consecutive_failures, 0);
+        assert_eq!((stats.input_tokens, stats.output_tokens), (12, 3));
+        assert_eq!(stats.latency_buckets.iter().sum::<u64>(), 1);
+    }
+
+    fn fixture(name: &str) -> String {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/gemini").join(name);
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_streamed_usage_matches_non_streaming() {
+        use crate::llm::interceptor::tests::capture_server;
+
+        let provider = GeminiProvider::new("test-key").unwrap();
+        let response = provider
+            .convert_response(serde_json::from_str(&fixture("response_thinking.json")).unwrap())
+            .await
+            .unwrap();
+
+        let sse: String = fixture("stream_thinking.jsonl")
+            .lines()
+            .map(|chunk| format!("data: {}\r\n\r\n", chunk))
+            .collect();
+        let (port, _) = capture_server("text/event-stream", sse).await;
+        let provider = GeminiProvider::with_auth_provider(move || async move {
+            Ok(AuthConfig::with_base_url("test-key", format!("http://127.0.0.1:{port}/v1beta")))
+        })
+        .with_model("gemini-test");
+        let stream = provider
+            .stream_with_tools_and_system(vec![Message::user("2 + 2?")], None, vec![], None, None, None, None)
+            .await
+            .unwrap();
+        let events: Vec<StreamEvent> = stream.map(|e| e.unwrap()).collect().await;
+
+        // The first chunk only had the prompt count
+        let mut usage = match &events[0] {
+            StreamEvent::MessageStart(start) => start.message.usage.clone(),
+            other => panic!("unexpected first event {:?}", other),
+        };
+        assert_eq!((usage.input_tokens, usage.output_tokens, usage.thoughts_token_count), (42, 0, None));
+        let delta = events
+            .iter()
+            .find_map(|e| match e {
+                StreamEvent::MessageDelta(delta) => Some(delta),
+                _ => None,
+            })
+            .unwrap();
+        delta.usage.apply_to(&mut usage);
+
+        assert_eq!(serde_json::to_value(&usage).unwrap(), serde_json::to_value(&response.usage).unwrap());
+        assert_eq!((usage.input_tokens, usage.output_tokens, usage.thoughts_token_count), (42, 5, Some(28)));
+        assert_eq!(LlmProvider::stats(&provider).unwrap().output_tokens, 5);
+
+        let text: String = events
+            .iter()
+            .filter_map(|e| match e {
+                StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent {
+                    delta: ContentDelta::TextDelta { text },
+                    ..
+                }) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(text, response.text());
+    }
+
+    #[test]
+    fn test_usage_metadata_keeps_the_highest_counts() {
+        let mut usage = GeminiUsageMetadata::default();
+        for chunk in fixture("stream_thinking.jsonl").lines() {
+            let chunk: GeminiResponse = serde_json::from_str(chunk).unwrap();
+            usage.update(chunk.usage_metadata.as_ref().unwrap());
+        }
+        // A chunk that leaves counts out doesn't reset them
+        usage.update(&GeminiUsageMetadata { prompt_token_count: 42, ..Default::default() });
+        assert_eq!(usage.candidates_token_count, 5);
+        assert_eq!(usage.thoughts_token_count, Some(28));
+        assert_eq!(usage.total_token_count, 75);
+    }
 }
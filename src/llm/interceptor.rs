@@ -0,0 +1,377 @@
+//! Request middleware for HTTP-based providers
+//!
+//! A `RequestInterceptor` sees every API request just before it is sent and
+//! every response status and body after it arrives. Use it to add tracing
+//! headers, rewrite requests for a gateway, or capture exact payloads,
+//! without forking a provider.
+//!
+//! ```ignore
+//! let llm = AnthropicProvider::from_env()?
+//!     .with_interceptor(Arc::new(
+//!         HeaderInterceptor::new().with_header("x-gateway-team", "evals"),
+//!     ))
+//!     .with_interceptor(Arc::new(PayloadCapture::new(debugger.clone())));
+//! ```
+//!
+//! Interceptors run in the order they were added. `AnthropicProvider` and
+//! `GeminiProvider` support them.
+
+use anyhow::{Context, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Method, RequestBuilder, StatusCode};
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::helpers::Debugger;
+
+/// Headers whose values are never captured
+const SENSITIVE_HEADERS: &[&str] = &["x-api-key", "x-goog-api-key", "authorization"];
+
+/// Method, URL and headers of an outgoing request
+#[derive(Debug, Clone)]
+pub struct HttpParts {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+}
+
+impl HttpParts {
+    /// A POST request to `url` with no headers
+    pub fn post(url: impl Into<String>) -> Self {
+        Self {
+            method: Method::POST,
+            url: url.into(),
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// Set a header, replacing any previous value
+    pub fn set_header(&mut self, name: &str, value: &str) -> Result<()> {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("Invalid header name: {}", name))?;
+        let value = HeaderValue::from_str(value)
+            .with_context(|| format!("Invalid value for header {}", name))?;
+        self.headers.insert(name, value);
+        Ok(())
+    }
+}
+
+/// Hooks around each provider HTTP request
+///
+/// Both methods have no-op defaults, so implement only the one you need.
+pub trait RequestInterceptor: Send + Sync {
+    /// Inspect or modify a request before it is sent
+    ///
+    /// Returning an error aborts the request with that error.
+    fn before_send(&self, _parts: &mut HttpParts, _body: &mut Value) -> Result<()> {
+        Ok(())
+    }
+
+    /// Inspect a response
+    ///
+    /// `body` is the parsed JSON body (a JSON string if it wasn't JSON). For
+    /// successful streaming requests the body is consumed as a stream, so
+    /// it is `Value::Null` here.
+    fn after_receive(&self, _status: StatusCode, _body: &Value) {}
+}
+
+/// Build a request, running each interceptor's `before_send` in order
+///
+/// Without interceptors the body is serialized directly, so the wire format
+/// is unchanged.
+pub(crate) fn prepare_request<T: Serialize>(
+    client: &Client,
+    interceptors: &[Arc<dyn RequestInterceptor>],
+    mut parts: HttpParts,
+    body: &T,
+) -> Result<(RequestBuilder, String)> {
+    let body = if interceptors.is_empty() {
+        serde_json::to_string(body).context("Failed to serialize request")?
+    } else {
+        let mut value = serde_json::to_value(body).context("Failed to serialize request")?;
+        for interceptor in interceptors {
+            interceptor.before_send(&mut parts, &mut value)?;
+        }
+        serde_json::to_string(&value).context("Failed to serialize request")?
+    };
+    let builder = client
+        .request(parts.method, parts.url)
+        .headers(parts.headers)
+        .body(body.clone());
+    Ok((builder, body))
+}
+
+/// Run each interceptor's `after_receive` in order
+pub(crate) fn notify_received(interceptors: &[Arc<dyn RequestInterceptor>], status: StatusCode, body: Option<&str>) {
+    if interceptors.is_empty() {
+        return;
+    }
+    let value = match body {
+        Some(text) => serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string())),
+        None => Value::Null,
+    };
+    for interceptor in interceptors {
+        interceptor.after_receive(status, &value);
+    }
+}
+
+/// Source of per-request headers
+type HeaderFn = dyn Fn() -> Vec<(String, String)> + Send + Sync;
+
+/// Adds headers to every request
+///
+/// Static headers are set once; `from_fn` computes them per request (e.g. a
+/// fresh `traceparent`). Both kinds replace headers the provider set.
+#[derive(Default)]
+pub struct HeaderInterceptor {
+    headers: Vec<(String, String)>,
+    dynamic: Option<Arc<HeaderFn>>,
+}
+
+impl HeaderInterceptor {
+    /// Create an interceptor that adds no headers yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compute headers for each request
+    pub fn from_fn<F>(headers: F) -> Self
+    where
+        F: Fn() -> Vec<(String, String)> + Send + Sync + 'static,
+    {
+        Self {
+            headers: Vec::new(),
+            dynamic: Some(Arc::new(headers)),
+        }
+    }
+
+    /// Add a header to every request
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+impl RequestInterceptor for HeaderInterceptor {
+    fn before_send(&self, parts: &mut HttpParts, _body: &mut Value) -> Result<()> {
+        for (name, value) in &self.headers {
+            parts.set_header(name, value)?;
+        }
+        if let Some(dynamic) = &self.dynamic {
+            for (name, value) in dynamic() {
+                parts.set_header(&name, &value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes exact request and response payloads to a `Debugger`
+///
+/// Unlike the agent's own debugger events, this records the request as it
+/// goes over the wire: after provider conversion (e.g. Gemini's format) and
+/// after earlier interceptors. Credential headers are masked.
+pub struct PayloadCapture {
+    debugger: Arc<Debugger>,
+}
+
+impl PayloadCapture {
+    /// Capture payloads into `debugger`
+    pub fn new(debugger: Arc<Debugger>) -> Self {
+        Self { debugger }
+    }
+}
+
+impl RequestInterceptor for PayloadCapture {
+    fn before_send(&self, parts: &mut HttpParts, body: &mut Value) -> Result<()> {
+        let headers: Vec<(String, String)> = parts
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                let value = if SENSITIVE_HEADERS.contains(&name.as_str()) {
+                    "[REDACTED]".to_string()
+                } else {
+                    String::from_utf8_lossy(value.as_bytes()).into_owned()
+                };
+                (name.to_string(), value)
+            })
+            .collect();
+        if let Err(e) = self.debugger.log_http_request(parts.method.as_str(), &parts.url, &headers, body) {
+            tracing::warn!("[PayloadCapture] Failed to log request: {}", e);
+        }
+        Ok(())
+    }
+
+    fn after_receive(&self, status: StatusCode, body: &Value) {
+        if let Err(e) = self.debugger.log_http_response(status.as_u16(), body) {
+            tracing::warn!("[PayloadCapture] Failed to log response: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Requests seen by `capture_server`: (lowercased head, body)
+    pub(crate) type Captured = Arc<Mutex<Vec<(String, String)>>>;
+
+    /// Serve `body` with `content_type` to every request, keeping each request
+    pub(crate) async fn capture_server(content_type: &'static str, body: String) -> (u16, Captured) {
+        scripted_server(vec![ScriptedResponse::ok(content_type, body)]).await
+    }
+
+    /// One response from `scripted_server`
+    #[derive(Clone)]
+    pub(crate) struct ScriptedResponse {
+        pub status: u16,
+        pub content_type: &'static str,
+        pub body: String,
+        /// Wait this long before answering
+        pub delay: std::time::Duration,
+    }
+
+    impl ScriptedResponse {
+        pub(crate) fn ok(content_type: &'static str, body: String) -> Self {
+            Self::status(200, content_type, body)
+        }
+
+        pub(crate) fn status(status: u16, content_type: &'static str, body: String) -> Self {
+            Self {
+                status,
+                content_type,
+                body,
+                delay: std::time::Duration::ZERO,
+            }
+        }
+
+        pub(crate) fn delayed(mut self, delay: std::time::Duration) -> Self {
+            self.delay = delay;
+            self
+        }
+    }
+
+    /// Answer requests with `responses` in order (repeating the last one),
+    /// keeping each request
+    pub(crate) async fn scripted_server(responses: Vec<ScriptedResponse>) -> (u16, Captured) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let captured: Captured = Arc::new(Mutex::new(Vec::new()));
+        let seen = captured.clone();
+        tokio::spawn(async move {
+            let mut next = 0;
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let seen = seen.clone();
+                let reply = responses[next.min(responses.len() - 1)].clone();
+                next += 1;
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    let header_end = loop {
+                        if let Some(i) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                            break i + 4;
+                        }
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    };
+                    let head = String::from_utf8_lossy(&request[..header_end]).to_ascii_lowercase();
+                    let length: usize = head
+                        .lines()
+                        .find_map(|l| l.strip_prefix("content-length:"))
+                        .and_then(|l| l.trim().parse().ok())
+                        .unwrap_or(0);
+                    while request.len() < header_end + length {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => break,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let request_body = String::from_utf8_lossy(&request[header_end..]).into_owned();
+                    seen.lock().unwrap().push((head, request_body));
+
+                    tokio::time::sleep(reply.delay).await;
+                    let response = format!(
+                        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+                        Connection: close\r\n\r\n{}",
+                        reply.status,
+                        StatusCode::from_u16(reply.status).ok().and_then(|s| s.canonical_reason()).unwrap_or("Status"),
+                        reply.content_type,
+                        reply.body.len(),
+                        reply.body
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        (port, captured)
+    }
+
+    /// Adds a header and a body field, to check interceptors reach the wire
+    pub(crate) struct TagRequests;
+
+    impl RequestInterceptor for TagRequests {
+        fn before_send(&self, parts: &mut HttpParts, body: &mut Value) -> Result<()> {
+            parts.set_header("x-gateway-team", "evals")?;
+            body["metadata"] = serde_json::json!({"team": "evals"});
+            Ok(())
+        }
+    }
+
+    /// Records response statuses
+    #[derive(Default)]
+    pub(crate) struct RecordStatus(pub Mutex<Vec<(u16, Value)>>);
+
+    impl RequestInterceptor for RecordStatus {
+        fn after_receive(&self, status: StatusCode, body: &Value) {
+            self.0.lock().unwrap().push((status.as_u16(), body.clone()));
+        }
+    }
+
+    #[test]
+    fn test_header_interceptor() {
+        let mut parts = HttpParts::post("http://example.test");
+        parts.set_header("x-team", "old").unwrap();
+        let interceptor = HeaderInterceptor::new().with_header("x-team", "evals");
+        interceptor.before_send(&mut parts, &mut Value::Null).unwrap();
+        assert_eq!(parts.headers["x-team"], "evals");
+
+        let dynamic = HeaderInterceptor::from_fn(|| vec![("traceparent".to_string(), "00-abc".to_string())]);
+        dynamic.before_send(&mut parts, &mut Value::Null).unwrap();
+        assert_eq!(parts.headers["traceparent"], "00-abc");
+
+        let invalid = HeaderInterceptor::new().with_header("bad header", "x");
+        assert!(invalid.before_send(&mut parts, &mut Value::Null).is_err());
+    }
+
+    #[test]
+    fn test_payload_capture_masks_credentials() {
+        let dir = tempfile::tempdir().unwrap();
+        let debugger = Arc::new(Debugger::new(dir.path()).unwrap());
+        let capture = PayloadCapture::new(debugger.clone());
+
+        let mut parts = HttpParts::post("http://example.test/v1/messages");
+        parts.set_header("x-api-key", "sk-secret").unwrap();
+        parts.set_header("anthropic-version", "2023-06-01").unwrap();
+        capture.before_send(&mut parts, &mut serde_json::json!({"model": "m"})).unwrap();
+        capture.after_receive(StatusCode::OK, &serde_json::json!({"id": "msg_1"}));
+
+        let mut files: Vec<_> = std::fs::read_dir(debugger.dir())
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        files.sort();
+        assert_eq!(files.len(), 2);
+        let request = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(request.contains("http_request") && request.contains("\"model\": \"m\""), "{request}");
+        assert!(request.contains("2023-06-01"));
+        assert!(!request.contains("sk-secret"));
+        let response = std::fs::read_to_string(&files[1]).unwrap();
+        assert!(response.contains("\"status\": 200") && response.contains("msg_1"), "{response}");
+    }
+}
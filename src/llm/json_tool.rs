@@ -0,0 +1,287 @@
+//! JSON response mode through a forced tool call
+//!
+//! The Messages API (Anthropic and Bedrock) has no JSON mode, so
+//! `ResponseFormat::Json` is implemented with a hidden tool whose input is
+//! the schema. The request makes the model call it, and the call is turned
+//! back into a text block holding the JSON, both in whole responses and in
+//! streams, so callers never see the tool.
+
+use std::pin::Pin;
+
+use anyhow::Result;
+use futures::stream::Stream;
+use futures::StreamExt;
+use serde_json::{json, Value};
+
+use super::types::{
+    ContentBlock, ContentBlockDeltaEvent, ContentBlockStart, ContentBlockStartEvent, ContentBlockStopEvent,
+    ContentDelta, CustomTool, MessageResponse, ResponseFormat, StopReason, StreamEvent, ToolChoice,
+    ToolDefinition, ToolInputSchema,
+};
+
+/// Name of the hidden tool
+pub(super) const JSON_RESPONSE_TOOL: &str = "json_response";
+
+/// Property that holds the response when the schema isn't an object
+const WRAPPED_PROPERTY: &str = "response";
+
+type EventStream = Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>;
+
+/// Whether the schema describes something other than an object
+///
+/// Tool input has to be an object, so such responses are wrapped in one.
+fn is_wrapped(schema: Option<&Value>) -> bool {
+    schema.is_some_and(|s| s.get("type").and_then(Value::as_str) != Some("object") && s.get("properties").is_none())
+}
+
+/// How deep references are followed; deeper (recursive) ones accept anything
+const MAX_REF_DEPTH: usize = 16;
+
+/// Replace local references (`#/$defs/..`, `#/definitions/..`) with what they name
+///
+/// Tool input schemas only keep `properties` and `required`, so the
+/// definitions wouldn't survive next to them.
+fn inline_refs(value: &Value, root: &Value, depth: usize) -> Value {
+    match value {
+        Value::Object(map) => {
+            if let Some(target) = map.get("$ref").and_then(Value::as_str) {
+                let resolved = target.strip_prefix('#').and_then(|pointer| root.pointer(pointer));
+                return match resolved {
+                    Some(resolved) if depth < MAX_REF_DEPTH => inline_refs(resolved, root, depth + 1),
+                    _ => json!({}),
+                };
+            }
+            map.iter()
+                .filter(|(key, _)| !matches!(key.as_str(), "$defs" | "definitions"))
+                .map(|(key, val)| (key.clone(), inline_refs(val, root, depth)))
+                .collect()
+        }
+        Value::Array(items) => Value::Array(items.iter().map(|item| inline_refs(item, root, depth)).collect()),
+        other => other.clone(),
+    }
+}
+
+/// The hidden tool for a response format
+fn json_tool(format: &ResponseFormat) -> ToolDefinition {
+    let schema = format.schema().map(|schema| inline_refs(schema, schema, 0));
+    let schema = schema.as_ref();
+    let input_schema = match schema {
+        None => ToolInputSchema::new(),
+        Some(schema) if is_wrapped(Some(schema)) => ToolInputSchema {
+            schema_type: "object".to_string(),
+            properties: Some(json!({ WRAPPED_PROPERTY: schema })),
+            required: Some(vec![WRAPPED_PROPERTY.to_string()]),
+        },
+        Some(schema) => ToolInputSchema {
+            schema_type: "object".to_string(),
+            properties: schema.get("properties").cloned(),
+            required: schema
+                .get("required")
+                .and_then(Value::as_array)
+                .map(|names| names.iter().filter_map(Value::as_str).map(str::to_string).collect()),
+        },
+    };
+    ToolDefinition::Custom(CustomTool {
+        name: JSON_RESPONSE_TOOL.to_string(),
+        description: Some(
+            "Give your final response by calling this tool. Its input is the whole response; \
+            don't answer with plain text."
+                .to_string(),
+        ),
+        input_schema,
+        tool_type: None,
+        cache_control: None,
+    })
+}
+
+/// Add the hidden tool to a request and pick a tool choice that makes the model use it
+///
+/// Without other tools the model must call it; with other tools it must call
+/// some tool, so it can still use them before answering. A specific tool
+/// choice is kept. Thinking doesn't allow forced tool use, so then the tool
+/// choice is left alone and only the tool description asks for it.
+pub(super) fn apply(
+    format: &ResponseFormat,
+    tools: &mut Vec<ToolDefinition>,
+    tool_choice: Option<ToolChoice>,
+    thinking: bool,
+) -> Option<ToolChoice> {
+    let has_tools = !tools.is_empty();
+    tools.push(json_tool(format));
+    if thinking {
+        return tool_choice;
+    }
+    match tool_choice {
+        Some(choice @ (ToolChoice::Any { .. } | ToolChoice::Tool { .. })) if has_tools => Some(choice),
+        _ if has_tools => Some(ToolChoice::any()),
+        _ => Some(ToolChoice::tool(JSON_RESPONSE_TOOL)),
+    }
+}
+
+/// The JSON text for the hidden tool's input
+fn response_text(format: &ResponseFormat, input: &Value) -> String {
+    let value = if is_wrapped(format.schema()) {
+        input.get(WRAPPED_PROPERTY).unwrap_or(&Value::Null)
+    } else {
+        input
+    };
+    value.to_string()
+}
+
+/// Turn the hidden tool call in a response into a text block
+pub(super) fn unwrap_response(format: &ResponseFormat, response: &mut MessageResponse) {
+    for block in &mut response.content {
+        if let ContentBlock::ToolUse { name, input, .. } = block {
+            if name == JSON_RESPONSE_TOOL {
+                *block = ContentBlock::text(response_text(format, input));
+            }
+        }
+    }
+    let other_tools = response.content.iter().any(|b| matches!(b, ContentBlock::ToolUse { .. }));
+    if !other_tools && response.stop_reason == Some(StopReason::ToolUse) {
+        response.stop_reason = Some(StopReason::EndTurn);
+    }
+}
+
+/// Turn the hidden tool call in a stream into a text block
+///
+/// The tool's input is collected and sent as one text delta when its block
+/// ends, so the text is exactly what `unwrap_response` would produce.
+pub(super) fn unwrap_stream(format: ResponseFormat, mut inner: EventStream) -> EventStream {
+    Box::pin(async_stream::try_stream! {
+        // Index and input of the hidden tool's block while it streams
+        let mut json_block: Option<(usize, String)> = None;
+        let mut other_tools = false;
+
+        while let Some(event) = inner.next().await {
+            match event? {
+                StreamEvent::ContentBlockStart(ContentBlockStartEvent {
+                    index,
+                    content_block: ContentBlockStart::ToolUse { name, .. },
+                }) if name == JSON_RESPONSE_TOOL => {
+                    json_block = Some((index, String::new()));
+                    yield StreamEvent::ContentBlockStart(ContentBlockStartEvent {
+                        index,
+                        content_block: ContentBlockStart::Text { text: String::new() },
+                    });
+                }
+                StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent {
+                    index,
+                    delta: ContentDelta::InputJsonDelta { partial_json },
+                }) if json_block.as_ref().is_some_and(|(i, _)| *i == index) => {
+                    if let Some((_, json)) = json_block.as_mut() {
+                        json.push_str(&partial_json);
+                    }
+                }
+                StreamEvent::ContentBlockStop(ContentBlockStopEvent { index })
+                    if json_block.as_ref().is_some_and(|(i, _)| *i == index) =>
+                {
+                    let (_, json) = json_block.take().unwrap_or_default();
+                    let input = if json.trim().is_empty() {
+                        json!({})
+                    } else {
+                        serde_json::from_str(&json).unwrap_or(Value::String(json))
+                    };
+                    yield StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent {
+                        index,
+                        delta: ContentDelta::TextDelta { text: response_text(&format, &input) },
+                    });
+                    yield StreamEvent::ContentBlockStop(ContentBlockStopEvent { index });
+                }
+                StreamEvent::MessageDelta(mut delta) => {
+                    if !other_tools && delta.delta.stop_reason == Some(StopReason::ToolUse) {
+                        delta.delta.stop_reason = Some(StopReason::EndTurn);
+                    }
+                    yield StreamEvent::MessageDelta(delta);
+                }
+                event => {
+                    if let StreamEvent::ContentBlockStart(ContentBlockStartEvent {
+                        content_block: ContentBlockStart::ToolUse { .. },
+                        ..
+                    }) = &event
+                    {
+                        other_tools = true;
+                    }
+                    yield event;
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_picks_a_tool_choice() {
+        let format = ResponseFormat::json();
+        let mut tools = Vec::new();
+        assert_eq!(apply(&format, &mut tools, None, false), Some(ToolChoice::tool(JSON_RESPONSE_TOOL)));
+        assert_eq!(tools.len(), 1);
+
+        // With other tools the model may still use them first
+        let mut tools = vec![json_tool(&format)];
+        assert_eq!(apply(&format, &mut tools, Some(ToolChoice::auto()), false), Some(ToolChoice::any()));
+        let forced = Some(ToolChoice::tool("Read"));
+        assert_eq!(apply(&format, &mut tools, forced.clone(), false), forced);
+
+        // Thinking can't force tool use
+        assert_eq!(apply(&format, &mut Vec::new(), None, true), None);
+    }
+
+    #[test]
+    fn test_non_object_schemas_are_wrapped() {
+        let format = ResponseFormat::json_schema(json!({"type": "array", "items": {"type": "string"}}));
+        let ToolDefinition::Custom(tool) = json_tool(&format) else {
+            panic!("expected a custom tool");
+        };
+        assert_eq!(tool.input_schema.required, Some(vec!["response".to_string()]));
+        assert_eq!(response_text(&format, &json!({"response": ["a", "b"]})), r#"["a","b"]"#);
+
+        let format = ResponseFormat::json_schema(json!({
+            "type": "object",
+            "properties": {"name": {"type": "string"}},
+            "required": ["name"]
+        }));
+        let ToolDefinition::Custom(tool) = json_tool(&format) else {
+            panic!("expected a custom tool");
+        };
+        assert_eq!(tool.input_schema.properties, Some(json!({"name": {"type": "string"}})));
+        assert_eq!(response_text(&format, &json!({"name": "x"})), r#"{"name":"x"}"#);
+    }
+
+    #[test]
+    fn test_references_are_inlined() {
+        // As schemars derives it
+        let format = ResponseFormat::json_schema(json!({
+            "type": "object",
+            "properties": {
+                "owner": {"$ref": "#/$defs/Person"},
+                "tree": {"$ref": "#/definitions/Node"}
+            },
+            "required": ["owner"],
+            "$defs": {
+                "Person": {"type": "object", "properties": {"name": {"type": "string"}}, "additionalProperties": false}
+            },
+            "definitions": {
+                "Node": {"type": "object", "properties": {"child": {"$ref": "#/definitions/Node"}}}
+            }
+        }));
+        let ToolDefinition::Custom(tool) = json_tool(&format) else {
+            panic!("expected a custom tool");
+        };
+        let properties = tool.input_schema.properties.unwrap();
+        assert_eq!(
+            properties["owner"],
+            json!({"type": "object", "properties": {"name": {"type": "string"}}, "additionalProperties": false})
+        );
+        // A recursive definition stops at the depth limit
+        let mut node = &properties["tree"];
+        for _ in 0..MAX_REF_DEPTH - 1 {
+            node = &node["properties"]["child"];
+        }
+        assert_eq!(node["properties"]["child"], json!({}));
+        assert!(!properties.to_string().contains("$ref"));
+    }
+}
@@ -0,0 +1,374 @@
+//! Mock LLM provider for tests
+//!
+//! `MockLlmProvider` returns scripted responses in order and records every
+//! request it receives, so agent loops can be exercised without network access.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let llm = Arc::new(
+//!     MockLlmProvider::new()
+//!         .with_response(MockLlmProvider::tool_use_response("t1", "Read", json!({"file_path": "a.txt"})))
+//!         .with_response(MockLlmProvider::text_response("Done!")),
+//! );
+//! let agent = StandardAgent::new(config, llm.clone());
+//! // ... run the agent ...
+//! assert_eq!(llm.call_count(), 2);
+//! ```
+
+use anyhow::Result;
+use futures::stream::Stream;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use super::provider::LlmProvider;
+use super::types::{
+    response_events, ContentBlock, GenerationParams, Message, MessageResponse, StopReason, StreamEvent,
+    SystemPrompt, ThinkingConfig, ToolChoice, ToolDefinition, Usage,
+};
+
+/// A request recorded by `MockLlmProvider`
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub model: String,
+    pub max_tokens: u32,
+    pub messages: Vec<Message>,
+    pub system: Option<SystemPrompt>,
+    pub tools: Vec<ToolDefinition>,
+    pub tool_choice: Option<ToolChoice>,
+    pub thinking: Option<ThinkingConfig>,
+    pub generation: Option<GenerationParams>,
+}
+
+/// How a scripted response is delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Delivery {
+    /// The whole response
+    Whole,
+    /// The stream fails after this many events
+    DropAfter(usize),
+    /// The stream stops sending after this many events but never ends;
+    /// non-streaming calls never return
+    StallAfter(usize),
+    /// The call never returns
+    Hang,
+}
+
+/// Shared state so variants created with `create_variant` use the same script
+struct MockState {
+    responses: VecDeque<(MessageResponse, Delivery)>,
+    fallback: Option<MessageResponse>,
+    requests: Vec<MockRequest>,
+}
+
+/// LLM provider that replays scripted responses
+///
+/// Responses are returned in the order they were added. Once the queue is
+/// empty, the fallback response (if set) is returned for every further call;
+/// otherwise an error is returned.
+#[derive(Clone)]
+pub struct MockLlmProvider {
+    state: Arc<Mutex<MockState>>,
+    model: String,
+    max_tokens: u32,
+    input_chunk_size: Option<usize>,
+}
+
+impl MockLlmProvider {
+    /// Create a mock with no scripted responses
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(MockState {
+                responses: VecDeque::new(),
+                fallback: None,
+                requests: Vec::new(),
+            })),
+            model: "mock-model".to_string(),
+            max_tokens: 4096,
+            input_chunk_size: None,
+        }
+    }
+
+    /// Queue a response
+    pub fn with_response(self, response: MessageResponse) -> Self {
+        self.push_response(response);
+        self
+    }
+
+    /// Queue a response whose stream fails after `events` events
+    ///
+    /// Simulates a connection dropping mid-response. Non-streaming calls
+    /// return the whole response.
+    pub fn with_dropped_stream(self, response: MessageResponse, events: usize) -> Self {
+        self.state.lock().unwrap().responses.push_back((response, Delivery::DropAfter(events)));
+        self
+    }
+
+    /// Queue a response whose stream goes quiet after `events` events
+    ///
+    /// The stream never ends, like a stalled connection. Non-streaming calls
+    /// never return.
+    pub fn with_stalled_stream(self, response: MessageResponse, events: usize) -> Self {
+        self.state.lock().unwrap().responses.push_back((response, Delivery::StallAfter(events)));
+        self
+    }
+
+    /// Queue a call that never returns, like a provider that stopped answering
+    pub fn with_hang(self) -> Self {
+        let response = Self::text_response("");
+        self.state.lock().unwrap().responses.push_back((response, Delivery::Hang));
+        self
+    }
+
+    /// Queue a plain text response
+    pub fn with_text(self, text: impl Into<String>) -> Self {
+        self.with_response(Self::text_response(text))
+    }
+
+    /// Set the response returned once the queue is exhausted
+    pub fn with_fallback(self, response: MessageResponse) -> Self {
+        self.state.lock().unwrap().fallback = Some(response);
+        self
+    }
+
+    /// Set the model name reported by the provider
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Set the max tokens reported in recorded requests
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Stream tool input JSON in pieces of `chars` characters
+    ///
+    /// By default each tool call's input arrives in a single delta. Real APIs
+    /// split it, so most deltas are not valid JSON on their own.
+    pub fn with_input_chunk_size(mut self, chars: usize) -> Self {
+        self.input_chunk_size = Some(chars.max(1));
+        self
+    }
+
+    /// Queue a response on an existing (possibly shared) mock
+    pub fn push_response(&self, response: MessageResponse) {
+        self.state.lock().unwrap().responses.push_back((response, Delivery::Whole));
+    }
+
+    /// Get all requests received so far
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Get the number of requests received so far
+    pub fn call_count(&self) -> usize {
+        self.state.lock().unwrap().requests.len()
+    }
+
+    /// Build a response from content blocks and a stop reason
+    pub fn response(content: Vec<ContentBlock>, stop_reason: StopReason) -> MessageResponse {
+        MessageResponse {
+            id: format!("mock_{}", uuid::Uuid::new_v4()),
+            response_type: "message".to_string(),
+            role: "assistant".to_string(),
+            content,
+            model: "mock-model".to_string(),
+            stop_reason: Some(stop_reason),
+            stop_sequence: None,
+            usage: Usage {
+                input_tokens: 0,
+                output_tokens: 0,
+                cache_creation_input_tokens: None,
+                cache_read_input_tokens: None,
+                thoughts_token_count: None,
+            },
+        }
+    }
+
+    /// Build a text response that ends the turn
+    pub fn text_response(text: impl Into<String>) -> MessageResponse {
+        Self::response(vec![ContentBlock::text(text)], StopReason::EndTurn)
+    }
+
+    /// Build a response containing a single tool call
+    pub fn tool_use_response(
+        id: impl Into<String>,
+        name: impl Into<String>,
+        input: Value,
+    ) -> MessageResponse {
+        Self::response(vec![ContentBlock::tool_use(id, name, input)], StopReason::ToolUse)
+    }
+
+    /// Record a request and pop the next response
+    fn next_response(&self, request: MockRequest) -> Result<(MessageResponse, Delivery)> {
+        let mut state = self.state.lock().unwrap();
+        state.requests.push(request);
+        match state.responses.pop_front() {
+            Some(scripted) => Ok(scripted),
+            None => state
+                .fallback
+                .clone()
+                .map(|response| (response, Delivery::Whole))
+                .ok_or_else(|| anyhow::anyhow!("MockLlmProvider: no scripted response left")),
+        }
+    }
+}
+
+impl Default for MockLlmProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmProvider for MockLlmProvider {
+    async fn send_message(
+        &self,
+        user_message: &str,
+        conversation_history: &[Message],
+        system_prompt: Option<&str>,
+        _session_id: Option<&str>,
+    ) -> Result<String> {
+        let mut messages = conversation_history.to_vec();
+        messages.push(Message::user(user_message));
+        let (response, _) = self.next_response(MockRequest {
+            model: self.model.clone(),
+            max_tokens: self.max_tokens,
+            messages,
+            system: system_prompt.map(|s| SystemPrompt::Text(s.to_string())),
+            tools: vec![],
+            tool_choice: None,
+            thinking: None,
+            generation: None,
+        })?;
+        Ok(response.text())
+    }
+
+    async fn send_with_tools_and_system(
+        &self,
+        messages: Vec<Message>,
+        system: Option<SystemPrompt>,
+        tools: Vec<ToolDefinition>,
+        tool_choice: Option<ToolChoice>,
+        thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
+        _session_id: Option<&str>,
+    ) -> Result<MessageResponse> {
+        let (response, delivery) = self.next_response(MockRequest {
+            model: self.model.clone(),
+            max_tokens: generation.as_ref().and_then(|g| g.max_tokens).unwrap_or(self.max_tokens),
+            messages,
+            system,
+            tools,
+            tool_choice,
+            thinking,
+            generation,
+        })?;
+        if matches!(delivery, Delivery::StallAfter(_) | Delivery::Hang) {
+            std::future::pending::<()>().await;
+        }
+        Ok(response)
+    }
+
+    async fn stream_with_tools_and_system(
+        &self,
+        messages: Vec<Message>,
+        system: Option<SystemPrompt>,
+        tools: Vec<ToolDefinition>,
+        tool_choice: Option<ToolChoice>,
+        thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
+        _session_id: Option<&str>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>> {
+        let (response, delivery) = self.next_response(MockRequest {
+            model: self.model.clone(),
+            max_tokens: generation.as_ref().and_then(|g| g.max_tokens).unwrap_or(self.max_tokens),
+            messages,
+            system,
+            tools,
+            tool_choice,
+            thinking,
+            generation,
+        })?;
+        let events = response_events(response, self.input_chunk_size);
+        match delivery {
+            Delivery::Whole => Ok(Box::pin(futures::stream::iter(events.into_iter().map(Ok)))),
+            Delivery::DropAfter(count) => {
+                let events: Vec<Result<StreamEvent>> = events
+                    .into_iter()
+                    .take(count)
+                    .map(Ok)
+                    .chain(std::iter::once(Err(anyhow::anyhow!("MockLlmProvider: connection reset"))))
+                    .collect();
+                Ok(Box::pin(futures::stream::iter(events)))
+            }
+            Delivery::StallAfter(count) => {
+                let events = futures::stream::iter(events.into_iter().take(count).map(Ok));
+                Ok(Box::pin(futures::StreamExt::chain(events, futures::stream::pending())))
+            }
+            Delivery::Hang => std::future::pending().await,
+        }
+    }
+
+    fn model(&self) -> String {
+        self.model.clone()
+    }
+
+    fn max_tokens(&self) -> Option<u32> {
+        Some(self.max_tokens)
+    }
+
+    fn provider_name(&self) -> &str {
+        "mock"
+    }
+
+    fn create_variant(&self, model: &str, max_tokens: u32) -> Arc<dyn LlmProvider> {
+        Arc::new(self.clone().with_model(model).with_max_tokens(max_tokens))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use crate::llm::{ContentBlockStart, ContentBlockStartEvent};
+
+    #[tokio::test]
+    async fn test_scripted_responses_in_order() {
+        let llm = MockLlmProvider::new().with_text("first").with_text("second");
+
+        assert_eq!(llm.send_message("a", &[], None, None).await.unwrap(), "first");
+        assert_eq!(llm.send_message("b", &[], None, None).await.unwrap(), "second");
+        assert!(llm.send_message("c", &[], None, None).await.is_err());
+        assert_eq!(llm.call_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_and_streaming() {
+        let llm = MockLlmProvider::new().with_fallback(MockLlmProvider::tool_use_response(
+            "t1",
+            "Read",
+            serde_json::json!({"file_path": "a.txt"}),
+        ));
+
+        let stream = llm
+            .stream_with_tools_and_system(vec![], None, vec![], None, None, None, None)
+            .await
+            .unwrap();
+        let events: Vec<StreamEvent> = stream.map(|e| e.unwrap()).collect().await;
+
+        assert!(matches!(events.first(), Some(StreamEvent::MessageStart(_))));
+        assert!(events.iter().any(|e| matches!(
+            e,
+            StreamEvent::ContentBlockStart(ContentBlockStartEvent {
+                content_block: ContentBlockStart::ToolUse { .. },
+                ..
+            })
+        )));
+        assert!(matches!(events.last(), Some(StreamEvent::MessageStop)));
+    }
+}
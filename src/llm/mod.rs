@@ -1,19 +1,44 @@
 pub mod anthropic;
 pub mod auth;
+pub mod batch;
+pub mod caching;
+pub mod capabilities;
+#[cfg(feature = "bedrock")]
+pub mod bedrock;
 pub mod gemini;
+pub mod interceptor;
+mod json_tool;
+pub mod mock;
+pub mod ollama;
 pub mod provider;
+pub mod replay;
+pub mod stats;
+pub(crate) mod stream_blocks;
 pub mod swappable;
+pub mod tokens;
 pub mod types;
 
 pub use anthropic::{define_tool, AnthropicProvider};
 pub use auth::{auth_provider, AuthConfig, AuthProvider};
-pub use gemini::GeminiProvider;
+pub use batch::{AnthropicBatchClient, BatchId, BatchStatus, ProcessingStatus, RequestCounts};
+pub use caching::{CachingProvider, CACHE_BYPASS_ENV, DEFAULT_CACHE_TTL};
+pub use capabilities::{register_model_capabilities, ModelCapabilities};
+#[cfg(feature = "bedrock")]
+pub use bedrock::BedrockProvider;
+pub use gemini::{GeminiOptions, GeminiProvider, HarmBlockThreshold, HarmCategory};
+pub use interceptor::{HeaderInterceptor, HttpParts, PayloadCapture, RequestInterceptor};
+pub use mock::{MockLlmProvider, MockRequest};
+pub use ollama::OllamaProvider;
 pub use provider::LlmProvider;
+pub use replay::{Cassette, Interaction, MatchOptions, RecordedResponse, RecordingProvider, ReplayProvider};
+pub use stats::{ErrorClass, ErrorCounts, ProviderStats, StatsRecorder, LATENCY_BUCKETS_MS};
 pub use swappable::{LlmProviderHandle, SwappableLlmProvider};
+pub use tokens::{estimate_message_tokens, estimate_request_tokens, estimate_text_tokens};
 pub use types::{
-    CacheControl, ContentBlock, ContentBlockDeltaEvent, ContentBlockStart, ContentBlockStartEvent,
-    ContentBlockStopEvent, ContentDelta, DeltaUsage, Message, MessageContent,
+    CacheControl, Citation, CitationsConfig, ContentBlock, ContentBlockDeltaEvent, ContentBlockStart, ContentBlockStartEvent,
+    ContentBlockStopEvent, ContentDelta, DeltaUsage, GenerationParams, Message, MessageContent,
     MessageDeltaData, MessageDeltaEvent, MessageRequest, MessageResponse, MessageStartData,
-    MessageStartEvent, RawStreamEvent, StopReason, StreamError, StreamErrorDetails, StreamEvent,
+    MessageStartEvent, RawStreamEvent, ResponseFormat, StopReason, StreamError, StreamErrorDetails, StreamEvent,
     SystemBlock, SystemPrompt, ThinkingConfig, ToolChoice, ToolDefinition, ToolInputSchema, Usage,
+    UserLocation, WebSearchResult, WebSearchTool, WebSearchToolError, WebSearchToolResultContent,
 };
@@ -0,0 +1,1183 @@
+//! Ollama API client
+//!
+//! This module provides a direct HTTP client for a local Ollama daemon
+//! (`/api/chat`), translating between the framework's internal message types
+//! (Anthropic format) and Ollama's chat format.
+//!
+//! # Configuration
+//!
+//! No API key is needed. The host and model come from the constructor or the
+//! environment:
+//!
+//! ```ignore
+//! // OLLAMA_HOST (default http://localhost:11434) and OLLAMA_MODEL
+//! let llm = OllamaProvider::from_env()?;
+//!
+//! // Explicit host and model
+//! let llm = OllamaProvider::new("http://gpu-box:11434")?.with_model("qwen3:8b");
+//! ```
+//!
+//! # Tool Calling
+//!
+//! Tools are sent in Ollama's native `tools` format by default. For models
+//! without native tool support, `with_prompt_tools(true)` describes the tools
+//! in the system prompt instead and parses fenced `tool_call` JSON blocks out
+//! of the reply.
+//!
+//! # Limitations
+//!
+//! - Extended thinking is not supported: thinking config and thinking blocks
+//!   in the history are dropped with a warning
+//! - Only images are sent as attachments; documents are replaced with a note
+//! - `tool_choice` other than `none` is ignored (Ollama can't force a call)
+
+use anyhow::{Context, Result};
+use futures::stream::Stream;
+use futures::StreamExt;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::env;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::io::AsyncBufReadExt;
+use tokio_util::io::StreamReader;
+
+use super::capabilities::ModelCapabilities;
+use super::provider::LlmProvider;
+use super::types::{
+    ContentBlock, ContentBlockDeltaEvent, ContentBlockStart, ContentBlockStartEvent,
+    ContentBlockStopEvent, ContentDelta, DeltaUsage, GenerationParams, Message, MessageContent,
+    MessageDeltaData, MessageDeltaEvent, MessageResponse, MessageStartData, MessageStartEvent,
+    StopReason, StreamEvent, SystemPrompt, ThinkingConfig, ToolChoice, ToolDefinition, Usage,
+};
+
+const DEFAULT_HOST: &str = "http://localhost:11434";
+
+/// Info string of the fenced block used by the prompt-based tool protocol
+const TOOL_CALL_FENCE: &str = "```tool_call";
+
+// ============================================================================
+// Ollama API types
+// ============================================================================
+
+#[derive(Debug, Serialize)]
+struct OllamaRequest {
+    model: String,
+    messages: Vec<OllamaMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<OllamaTool>>,
+    stream: bool,
+    /// `"json"` or a JSON Schema (structured outputs)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<Value>,
+    options: OllamaOptions,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct OllamaMessage {
+    role: String,
+    #[serde(default)]
+    content: String,
+    /// Base64-encoded images
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tool_calls: Vec<OllamaToolCall>,
+    /// Name of the tool a `tool` message is the result of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tool_name: Option<String>,
+}
+
+impl OllamaMessage {
+    fn new(role: &str, content: impl Into<String>) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.into(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OllamaToolCall {
+    function: OllamaFunctionCall,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OllamaFunctionCall {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaTool {
+    #[serde(rename = "type")]
+    tool_type: &'static str,
+    function: OllamaFunction,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaFunction {
+    name: String,
+    description: String,
+    parameters: Value,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct OllamaOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_predict: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+}
+
+/// A `/api/chat` response, or one line of a streamed response
+#[derive(Debug, Deserialize)]
+struct OllamaResponse {
+    #[serde(default)]
+    model: String,
+    #[serde(default)]
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    done_reason: Option<String>,
+    #[serde(default)]
+    prompt_eval_count: Option<u32>,
+    #[serde(default)]
+    eval_count: Option<u32>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+// ============================================================================
+// OllamaProvider
+// ============================================================================
+
+/// Ollama LLM provider for local models
+///
+/// ```ignore
+/// let llm = OllamaProvider::from_env()?;
+///
+/// // A model without native tool support
+/// let llm = OllamaProvider::new("http://localhost:11434")?
+///     .with_model("gemma2:9b")
+///     .with_prompt_tools(true);
+/// ```
+pub struct OllamaProvider {
+    client: Client,
+    host: String,
+    model: String,
+    max_tokens: u32,
+    /// Describe tools in the system prompt instead of using native tool calling
+    prompt_tools: bool,
+}
+
+impl OllamaProvider {
+    /// Create a new Ollama provider from environment variables
+    ///
+    /// Reads from:
+    /// - `OLLAMA_HOST` (optional, defaults to http://localhost:11434)
+    /// - `OLLAMA_MODEL` (required)
+    /// - `OLLAMA_MAX_TOKENS` (optional, defaults to 8192)
+    pub fn from_env() -> Result<Self> {
+        tracing::info!("Creating Ollama provider from environment");
+
+        let host = env::var("OLLAMA_HOST").unwrap_or_else(|_| DEFAULT_HOST.to_string());
+
+        let model = env::var("OLLAMA_MODEL")
+            .context("OLLAMA_MODEL environment variable not set")?;
+
+        let max_tokens = env::var("OLLAMA_MAX_TOKENS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(8192);
+
+        tracing::info!("Using model: {}", model);
+        tracing::info!("Max tokens: {}", max_tokens);
+
+        Ok(Self::new(host)?.with_model(model).with_max_tokens(max_tokens))
+    }
+
+    /// Create a new Ollama provider for a daemon at `host`
+    ///
+    /// `host` may omit the scheme (`localhost:11434`), like `OLLAMA_HOST`.
+    pub fn new(host: impl Into<String>) -> Result<Self> {
+        Ok(Self {
+            client: Client::new(),
+            host: normalize_host(&host.into()),
+            model: "".to_string(),
+            max_tokens: 8192,
+            prompt_tools: false,
+        })
+    }
+
+    /// Set the model to use
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Set the max tokens for responses
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Use a JSON-in-prompt protocol for tools instead of native tool calling
+    ///
+    /// For models that reject or ignore Ollama's `tools` field. Tools are
+    /// described in the system prompt and calls are parsed from fenced
+    /// `tool_call` blocks in the reply. Streaming requests are answered in
+    /// one piece in this mode, since a call can't be told apart from text
+    /// until the block is complete.
+    pub fn with_prompt_tools(mut self, enabled: bool) -> Self {
+        self.prompt_tools = enabled;
+        self
+    }
+
+    /// Create a variant with different model/tokens, sharing the same host
+    fn create_variant_impl(&self, model: &str, max_tokens: u32) -> Self {
+        Self {
+            client: Client::new(),
+            host: self.host.clone(),
+            model: model.to_string(),
+            max_tokens,
+            prompt_tools: self.prompt_tools,
+        }
+    }
+
+    // ========================================================================
+    // Format conversion: Internal (Anthropic) -> Ollama
+    // ========================================================================
+
+    /// Build an `/api/chat` request from internal types
+    #[allow(clippy::too_many_arguments)]
+    fn build_request(
+        &self,
+        messages: &[Message],
+        system: &Option<SystemPrompt>,
+        tools: &[ToolDefinition],
+        tool_choice: &Option<ToolChoice>,
+        thinking: &Option<ThinkingConfig>,
+        generation: &Option<GenerationParams>,
+        stream: bool,
+    ) -> OllamaRequest {
+        if thinking.is_some() {
+            tracing::warn!("[Ollama] Extended thinking is not supported; ignoring thinking config");
+        }
+
+        let tools: &[ToolDefinition] = if matches!(tool_choice, Some(ToolChoice::None)) {
+            &[]
+        } else {
+            tools
+        };
+
+        let mut system_text = system.as_ref().map(system_prompt_text).unwrap_or_default();
+        let native_tools = if self.prompt_tools {
+            if let Some(section) = prompt_tools_section(tools) {
+                system_text = if system_text.is_empty() {
+                    section
+                } else {
+                    format!("{}\n\n{}", system_text, section)
+                };
+            }
+            None
+        } else {
+            convert_tools(tools)
+        };
+
+        let mut ollama_messages = Vec::new();
+        if !system_text.is_empty() {
+            ollama_messages.push(OllamaMessage::new("system", system_text));
+        }
+        ollama_messages.extend(convert_messages(messages, self.prompt_tools));
+
+        let generation = generation.clone().unwrap_or_default();
+        OllamaRequest {
+            model: self.model.clone(),
+            messages: ollama_messages,
+            tools: native_tools,
+            stream,
+            format: generation.response_format.as_ref().map(|format| {
+                format.schema().cloned().unwrap_or_else(|| Value::from("json"))
+            }),
+            options: OllamaOptions {
+                num_predict: Some(generation.max_tokens.unwrap_or(self.max_tokens)),
+                temperature: generation.temperature,
+                top_p: generation.top_p,
+                stop: if generation.stop_sequences.is_empty() {
+                    None
+                } else {
+                    Some(generation.stop_sequences)
+                },
+            },
+        }
+    }
+
+    // ========================================================================
+    // Format conversion: Ollama -> Internal (Anthropic)
+    // ========================================================================
+
+    /// Convert an Ollama response to internal MessageResponse format
+    fn convert_response(&self, response: OllamaResponse) -> MessageResponse {
+        let message = response.message.unwrap_or_default();
+        let mut content = Vec::new();
+        if self.prompt_tools {
+            content.extend(parse_prompt_tool_calls(&message.content));
+        } else if !message.content.is_empty() {
+            content.push(ContentBlock::text(message.content));
+        }
+        content.extend(message.tool_calls.into_iter().map(tool_use_block));
+
+        let has_tool_use = content.iter().any(|b| matches!(b, ContentBlock::ToolUse { .. }));
+        MessageResponse {
+            id: format!("ollama_{}", uuid::Uuid::new_v4()),
+            response_type: "message".to_string(),
+            role: "assistant".to_string(),
+            content,
+            model: if response.model.is_empty() { self.model.clone() } else { response.model },
+            stop_reason: Some(stop_reason(response.done_reason.as_deref(), has_tool_use)),
+            stop_sequence: None,
+            usage: usage(response.prompt_eval_count, response.eval_count),
+        }
+    }
+
+    // ========================================================================
+    // API methods
+    // ========================================================================
+
+    fn chat_url(&self) -> String {
+        format!("{}/api/chat", self.host)
+    }
+
+    /// Send a non-streaming request to the Ollama API
+    #[tracing::instrument(name = "llm_request", skip_all, fields(provider = "ollama", model = %self.model, session_id = session_id.unwrap_or_default()))]
+    async fn send_ollama_request(&self, request: &OllamaRequest, session_id: Option<&str>) -> Result<OllamaResponse> {
+        let request_json = serde_json::to_string(request)
+            .context("Failed to serialize Ollama request")?;
+        tracing::debug!("[Ollama] Request JSON: {}", request_json);
+
+        let response = self
+            .client
+            .post(self.chat_url())
+            .header("Content-Type", "application/json")
+            .body(request_json)
+            .send()
+            .await
+            .context("Failed to send request to Ollama")?;
+
+        let status = response.status();
+        let response_text = response
+            .text()
+            .await
+            .context("Failed to read Ollama response body")?;
+
+        tracing::debug!("[Ollama] Response status: {}", status);
+        tracing::debug!("[Ollama] Response body: {}", response_text);
+
+        if !status.is_success() {
+            tracing::error!("[Ollama] API error: {} - {}", status, response_text);
+            anyhow::bail!("Ollama API error ({}): {}", status, response_text);
+        }
+
+        let response: OllamaResponse = serde_json::from_str(&response_text)
+            .context("Failed to parse Ollama API response")?;
+        if let Some(error) = response.error {
+            anyhow::bail!("Ollama API error: {}", error);
+        }
+        Ok(response)
+    }
+
+    /// Send a streaming request to the Ollama API (NDJSON)
+    #[tracing::instrument(name = "llm_stream", skip_all, fields(provider = "ollama", model = %self.model, session_id = session_id.unwrap_or_default()))]
+    async fn send_ollama_streaming_request(
+        &self,
+        request: &OllamaRequest,
+        session_id: Option<&str>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>> {
+        let request_json = serde_json::to_string(request)
+            .context("Failed to serialize Ollama streaming request")?;
+        tracing::debug!("[Ollama] Streaming request JSON: {}", request_json);
+
+        let response = self
+            .client
+            .post(self.chat_url())
+            .header("Content-Type", "application/json")
+            .body(request_json)
+            .send()
+            .await
+            .context("Failed to send streaming request to Ollama")?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Failed to read error body".to_string());
+            tracing::error!("[Ollama] Streaming API error: {} - {}", status, error_text);
+            anyhow::bail!("Ollama API error ({}): {}", status, error_text);
+        }
+
+        let byte_stream = response.bytes_stream();
+        let stream_reader = StreamReader::new(
+            byte_stream.map(|result| result.map_err(|e| std::io::Error::other(e.to_string()))),
+        );
+        let lines = tokio::io::BufReader::new(stream_reader).lines();
+        Ok(Box::pin(ndjson_events(lines, self.model.clone())))
+    }
+}
+
+/// The `MessageStart` of a stream, with the prompt count if Ollama sent one
+fn message_start(model: &str, prompt_eval_count: Option<u32>) -> StreamEvent {
+    StreamEvent::MessageStart(MessageStartEvent {
+        message: MessageStartData {
+            id: format!("ollama_{}", uuid::Uuid::new_v4()),
+            message_type: "message".to_string(),
+            role: "assistant".to_string(),
+            content: vec![],
+            model: model.to_string(),
+            stop_reason: None,
+            stop_sequence: None,
+            usage: usage(prompt_eval_count, None),
+        },
+    })
+}
+
+/// Convert NDJSON chat lines into internal stream events
+///
+/// Ollama reports the prompt count with the final chunk, so the final
+/// `MessageDelta` carries it too (`MessageStart` only has it when the first
+/// chunk is also the last).
+fn ndjson_events<R>(mut lines: tokio::io::Lines<R>, model: String) -> impl Stream<Item = Result<StreamEvent>> + Send
+where
+    R: tokio::io::AsyncBufRead + Unpin + Send + 'static,
+{
+    async_stream::try_stream! {
+        let mut started = false;
+        let mut index = 0;
+        let mut text_open = false;
+        let mut has_tool_use = false;
+        let mut finished = false;
+
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let chunk: OllamaResponse = serde_json::from_str(&line)
+                .context("Failed to parse Ollama stream line")?;
+            if let Some(error) = chunk.error {
+                Err(anyhow::anyhow!("Ollama API error: {}", error))?;
+            }
+            if !started {
+                yield message_start(&model, chunk.prompt_eval_count);
+                started = true;
+            }
+
+            if let Some(message) = chunk.message {
+                if !message.content.is_empty() {
+                    if !text_open {
+                        yield StreamEvent::ContentBlockStart(ContentBlockStartEvent {
+                            index,
+                            content_block: ContentBlockStart::Text { text: String::new() },
+                        });
+                        text_open = true;
+                    }
+                    yield StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent {
+                        index,
+                        delta: ContentDelta::TextDelta { text: message.content },
+                    });
+                }
+
+                // Tool calls arrive whole, not as deltas
+                for call in message.tool_calls {
+                    if text_open {
+                        yield StreamEvent::ContentBlockStop(ContentBlockStopEvent { index });
+                        index += 1;
+                        text_open = false;
+                    }
+                    if let ContentBlock::ToolUse { id, name, input } = tool_use_block(call) {
+                        yield StreamEvent::ContentBlockStart(ContentBlockStartEvent {
+                            index,
+                            content_block: ContentBlockStart::ToolUse {
+                                id,
+                                name,
+                                input: Value::Object(Default::default()),
+                            },
+                        });
+                        yield StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent {
+                            index,
+                            delta: ContentDelta::InputJsonDelta { partial_json: input.to_string() },
+                        });
+                        yield StreamEvent::ContentBlockStop(ContentBlockStopEvent { index });
+                        index += 1;
+                        has_tool_use = true;
+                    }
+                }
+            }
+
+            if chunk.done {
+                if text_open {
+                    yield StreamEvent::ContentBlockStop(ContentBlockStopEvent { index });
+                    text_open = false;
+                }
+                yield StreamEvent::MessageDelta(MessageDeltaEvent {
+                    delta: MessageDeltaData {
+                        stop_reason: Some(stop_reason(chunk.done_reason.as_deref(), has_tool_use)),
+                        stop_sequence: None,
+                    },
+                    usage: DeltaUsage::from_usage(&usage(chunk.prompt_eval_count, chunk.eval_count)),
+                });
+                finished = true;
+                break;
+            }
+        }
+
+        if !started {
+            yield message_start(&model, None);
+        }
+        if text_open {
+            yield StreamEvent::ContentBlockStop(ContentBlockStopEvent { index });
+        }
+        if !finished {
+            tracing::warn!("[Ollama] Stream ended without a final message");
+        }
+        yield StreamEvent::MessageStop;
+    }
+}
+
+/// Events a streaming request would have produced for a complete response
+fn response_events(response: MessageResponse) -> Vec<StreamEvent> {
+    let mut events = vec![StreamEvent::MessageStart(MessageStartEvent {
+        message: MessageStartData {
+            id: response.id,
+            message_type: "message".to_string(),
+            role: "assistant".to_string(),
+            content: vec![],
+            model: response.model,
+            stop_reason: None,
+            stop_sequence: None,
+            usage: response.usage.clone(),
+        },
+    })];
+    for (index, block) in response.content.into_iter().enumerate() {
+        let (start, delta) = match block {
+            ContentBlock::Text { text, .. } => (
+                ContentBlockStart::Text { text: String::new() },
+                ContentDelta::TextDelta { text },
+            ),
+            ContentBlock::ToolUse { id, name, input } => (
+                ContentBlockStart::ToolUse { id, name, input: Value::Object(Default::default()) },
+                ContentDelta::InputJsonDelta { partial_json: input.to_string() },
+            ),
+            _ => continue,
+        };
+        events.push(StreamEvent::ContentBlockStart(ContentBlockStartEvent { index, content_block: start }));
+        events.push(StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent { index, delta }));
+        events.push(StreamEvent::ContentBlockStop(ContentBlockStopEvent { index }));
+    }
+    events.push(StreamEvent::MessageDelta(MessageDeltaEvent {
+        delta: MessageDeltaData {
+            stop_reason: response.stop_reason,
+            stop_sequence: None,
+        },
+        usage: DeltaUsage::from_usage(&response.usage),
+    }));
+    events.push(StreamEvent::MessageStop);
+    events
+}
+
+/// Add a scheme to hosts given as `host:port`, and drop a trailing slash
+fn normalize_host(host: &str) -> String {
+    let host = host.trim().trim_end_matches('/');
+    if host.is_empty() {
+        DEFAULT_HOST.to_string()
+    } else if host.starts_with("http://") || host.starts_with("https://") {
+        host.to_string()
+    } else {
+        format!("http://{}", host)
+    }
+}
+
+fn system_prompt_text(system: &SystemPrompt) -> String {
+    match system {
+        SystemPrompt::Text(text) => text.clone(),
+        SystemPrompt::Blocks(blocks) => blocks
+            .iter()
+            .map(|b| b.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+    }
+}
+
+/// Convert custom tools to Ollama's `tools` format
+///
+/// Built-in Anthropic tools (bash, text editor, web search) don't map to
+/// Ollama and are skipped.
+fn convert_tools(tools: &[ToolDefinition]) -> Option<Vec<OllamaTool>> {
+    let tools: Vec<OllamaTool> = tools
+        .iter()
+        .filter_map(|tool| match tool {
+            ToolDefinition::Custom(custom) => Some(OllamaTool {
+                tool_type: "function",
+                function: OllamaFunction {
+                    name: custom.name.clone(),
+                    description: custom.description.clone().unwrap_or_default(),
+                    parameters: serde_json::to_value(&custom.input_schema).unwrap_or(Value::Null),
+                },
+            }),
+            ToolDefinition::Bash(_) | ToolDefinition::TextEditor(_) | ToolDefinition::WebSearch(_) => None,
+        })
+        .collect();
+    (!tools.is_empty()).then_some(tools)
+}
+
+/// System prompt section describing tools for the prompt-based protocol
+fn prompt_tools_section(tools: &[ToolDefinition]) -> Option<String> {
+    let tools = convert_tools(tools)?;
+    let mut section = String::from("# Tools\n\nYou can call these tools:\n");
+    for tool in &tools {
+        section.push_str(&format!(
+            "\n## {}\n{}\nParameters (JSON Schema): {}\n",
+            tool.function.name, tool.function.description, tool.function.parameters
+        ));
+    }
+    section.push_str(&format!(
+        "\nTo call a tool, reply with a fenced block like this, one block per call, \
+         and stop after the last block:\n\n{}\n{{\"name\": \"<tool name>\", \"arguments\": {{...}}}}\n```\n\n\
+         Tool results are sent back to you in <tool_result> tags.",
+        TOOL_CALL_FENCE
+    ));
+    Some(section)
+}
+
+/// Render a tool call as a fenced block for the prompt-based protocol
+fn prompt_tool_call(name: &str, input: &Value) -> String {
+    format!(
+        "{}\n{}\n```",
+        TOOL_CALL_FENCE,
+        serde_json::json!({ "name": name, "arguments": input })
+    )
+}
+
+/// Split a reply into text and the tool calls in its fenced `tool_call` blocks
+///
+/// Blocks that aren't valid calls are left in the text.
+fn parse_prompt_tool_calls(reply: &str) -> Vec<ContentBlock> {
+    let mut blocks = Vec::new();
+    let mut text = String::new();
+    let mut rest = reply;
+
+    while let Some(start) = rest.find(TOOL_CALL_FENCE) {
+        let body_start = start + TOOL_CALL_FENCE.len();
+        let Some(len) = rest[body_start..].find("```") else {
+            break;
+        };
+        let body = &rest[body_start..body_start + len];
+        let call = serde_json::from_str::<Value>(body.trim()).ok().and_then(|v| {
+            let name = v.get("name")?.as_str()?.to_string();
+            let arguments = v.get("arguments").cloned().unwrap_or_else(|| serde_json::json!({}));
+            Some(OllamaToolCall { function: OllamaFunctionCall { name, arguments } })
+        });
+        let end = body_start + len + 3;
+        match call {
+            Some(call) => {
+                text.push_str(&rest[..start]);
+                blocks.push(tool_use_block(call));
+            }
+            None => text.push_str(&rest[..end]),
+        }
+        rest = &rest[end..];
+    }
+    text.push_str(rest);
+
+    let text = text.trim();
+    if !text.is_empty() {
+        blocks.insert(0, ContentBlock::text(text));
+    }
+    blocks
+}
+
+/// Convert an Ollama tool call into a ToolUse block
+///
+/// Ollama calls have no id, so one is generated. Some models send the
+/// arguments as a JSON string rather than an object.
+fn tool_use_block(call: OllamaToolCall) -> ContentBlock {
+    let input = match call.function.arguments {
+        Value::String(s) => serde_json::from_str(&s).unwrap_or(Value::String(s)),
+        Value::Null => serde_json::json!({}),
+        other => other,
+    };
+    ContentBlock::ToolUse {
+        id: format!("ollama_tool_{}", uuid::Uuid::new_v4().simple()),
+        name: call.function.name,
+        input,
+    }
+}
+
+/// Convert internal messages to Ollama format
+///
+/// Tool results become `tool` messages (or `<tool_result>` text in prompt
+/// mode), placed before the rest of the user message so they directly
+/// follow the assistant message that made the calls.
+fn convert_messages(messages: &[Message], prompt_tools: bool) -> Vec<OllamaMessage> {
+    // Tool results only carry the id; the name is on the tool_use
+    let tool_names: HashMap<&str, &str> = messages
+        .iter()
+        .filter_map(|m| m.blocks())
+        .flatten()
+        .filter_map(|b| b.as_tool_use())
+        .map(|(id, name, _)| (id, name))
+        .collect();
+
+    let mut dropped_thinking = false;
+    let mut converted = Vec::new();
+    for message in messages {
+        let blocks = match &message.content {
+            MessageContent::Text(text) => {
+                converted.push(OllamaMessage::new(&message.role, text.clone()));
+                continue;
+            }
+            MessageContent::Blocks(blocks) => blocks,
+        };
+
+        let mut main = OllamaMessage::new(&message.role, String::new());
+        let mut texts = Vec::new();
+        for block in blocks {
+            match block {
+                ContentBlock::Text { text, .. } => texts.push(text.clone()),
+                ContentBlock::Image { source, .. } => main.images.push(source.data.clone()),
+                ContentBlock::Document { source, .. } => {
+                    tracing::warn!("[Ollama] Documents are not supported; omitting {}", source.media_type);
+                    texts.push(format!("[{} document omitted: not supported by this model]", source.media_type));
+                }
+                ContentBlock::ToolUse { name, input, .. } => {
+                    if prompt_tools {
+                        texts.push(prompt_tool_call(name, input));
+                    } else {
+                        main.tool_calls.push(OllamaToolCall {
+                            function: OllamaFunctionCall { name: name.clone(), arguments: input.clone() },
+                        });
+                    }
+                }
+                ContentBlock::ToolResult { tool_use_id, content, is_error, .. } => {
+                    let name = tool_names.get(tool_use_id.as_str()).copied().unwrap_or("unknown");
+                    let mut result = content.clone().unwrap_or_default();
+                    if *is_error == Some(true) {
+                        result = format!("Error: {}", result);
+                    }
+                    if prompt_tools {
+                        converted.push(OllamaMessage::new(
+                            "user",
+                            format!("<tool_result name=\"{}\">\n{}\n</tool_result>", name, result),
+                        ));
+                    } else {
+                        let mut tool = OllamaMessage::new("tool", result);
+                        tool.tool_name = Some(name.to_string());
+                        converted.push(tool);
+                    }
+                }
+                ContentBlock::Thinking { .. } | ContentBlock::RedactedThinking { .. } => {
+                    dropped_thinking = true;
+                }
+                ContentBlock::SearchResult { .. } => texts.extend(block.search_result_text()),
+                // Server-side tools have no Ollama equivalent
+                ContentBlock::ServerToolUse { .. } | ContentBlock::WebSearchToolResult { .. } => {}
+            }
+        }
+
+        main.content = texts.join("\n\n");
+        if !main.content.is_empty() || !main.images.is_empty() || !main.tool_calls.is_empty() {
+            converted.push(main);
+        }
+    }
+
+    if dropped_thinking {
+        tracing::warn!("[Ollama] Thinking blocks are not supported; dropped them from the history");
+    }
+    converted
+}
+
+fn stop_reason(done_reason: Option<&str>, has_tool_use: bool) -> StopReason {
+    if has_tool_use {
+        return StopReason::ToolUse;
+    }
+    match done_reason {
+        Some("length") => StopReason::MaxTokens,
+        _ => StopReason::EndTurn,
+    }
+}
+
+fn usage(prompt_eval_count: Option<u32>, eval_count: Option<u32>) -> Usage {
+    Usage {
+        input_tokens: prompt_eval_count.unwrap_or(0),
+        output_tokens: eval_count.unwrap_or(0),
+        cache_creation_input_tokens: None,
+        cache_read_input_tokens: None,
+        thoughts_token_count: None,
+    }
+}
+
+// ============================================================================
+// LlmProvider implementation
+// ============================================================================
+
+#[async_trait::async_trait]
+impl LlmProvider for OllamaProvider {
+    async fn send_message(
+        &self,
+        user_message: &str,
+        conversation_history: &[Message],
+        system_prompt: Option<&str>,
+        session_id: Option<&str>,
+    ) -> Result<String> {
+        tracing::info!("[Ollama] Sending message");
+
+        let mut messages: Vec<Message> = conversation_history.to_vec();
+        messages.push(Message::user(user_message));
+
+        let system = system_prompt.map(|s| SystemPrompt::Text(s.to_string()));
+        let request = self.build_request(&messages, &system, &[], &None, &None, &None, false);
+
+        let response = self.send_ollama_request(&request, session_id).await?;
+        Ok(self.convert_response(response).text())
+    }
+
+    async fn send_with_tools_and_system(
+        &self,
+        messages: Vec<Message>,
+        system: Option<SystemPrompt>,
+        tools: Vec<ToolDefinition>,
+        tool_choice: Option<ToolChoice>,
+        thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
+        session_id: Option<&str>,
+    ) -> Result<MessageResponse> {
+        tracing::info!("[Ollama] Sending message with tools");
+        tracing::debug!("[Ollama] Messages count: {}", messages.len());
+        tracing::debug!("[Ollama] Tools count: {}", tools.len());
+
+        let request = self.build_request(
+            &messages, &system, &tools, &tool_choice, &thinking, &generation, false,
+        );
+        let response = self.send_ollama_request(&request, session_id).await?;
+        Ok(self.convert_response(response))
+    }
+
+    async fn stream_with_tools_and_system(
+        &self,
+        messages: Vec<Message>,
+        system: Option<SystemPrompt>,
+        tools: Vec<ToolDefinition>,
+        tool_choice: Option<ToolChoice>,
+        thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
+        session_id: Option<&str>,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>> {
+        tracing::info!("[Ollama] Streaming message with tools");
+
+        if self.prompt_tools {
+            let response = self
+                .send_with_tools_and_system(
+                    messages, system, tools, tool_choice, thinking, generation, session_id,
+                )
+                .await?;
+            return Ok(Box::pin(futures::stream::iter(
+                response_events(response).into_iter().map(Ok),
+            )));
+        }
+
+        let request = self.build_request(
+            &messages, &system, &tools, &tool_choice, &thinking, &generation, true,
+        );
+        self.send_ollama_streaming_request(&request, session_id).await
+    }
+
+    fn model(&self) -> String {
+        self.model.clone()
+    }
+
+    fn max_tokens(&self) -> Option<u32> {
+        Some(self.max_tokens)
+    }
+
+    fn capabilities(&self) -> ModelCapabilities {
+        // Thinking and cache control are dropped by this provider
+        ModelCapabilities {
+            supports_thinking: false,
+            supports_caching: false,
+            ..ModelCapabilities::lookup(&self.model).unwrap_or_default()
+        }
+    }
+
+    fn provider_name(&self) -> &str {
+        "ollama"
+    }
+
+    fn create_variant(&self, model: &str, max_tokens: u32) -> Arc<dyn LlmProvider> {
+        Arc::new(self.create_variant_impl(model, max_tokens))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider() -> OllamaProvider {
+        OllamaProvider::new("localhost:11434").unwrap().with_model("qwen3:8b")
+    }
+
+    fn read_tool() -> ToolDefinition {
+        crate::llm::define_tool(
+            "Read",
+            "Read a file",
+            serde_json::json!({"path": {"type": "string"}}),
+            vec!["path".to_string()],
+        )
+    }
+
+    fn tool_turn() -> Vec<Message> {
+        vec![
+            Message::user("What's in a.txt?"),
+            Message::assistant_with_blocks(vec![
+                ContentBlock::Thinking { thinking: "hmm".to_string(), signature: "sig".to_string() },
+                ContentBlock::text("Reading it"),
+                ContentBlock::tool_use("call_1", "Read", serde_json::json!({"path": "a.txt"})),
+            ]),
+            Message::user_with_blocks(vec![
+                ContentBlock::tool_result("call_1", "hello", false),
+                ContentBlock::text("Summarize it"),
+            ]),
+        ]
+    }
+
+    #[test]
+    fn test_normalize_host() {
+        assert_eq!(normalize_host("localhost:11434"), "http://localhost:11434");
+        assert_eq!(normalize_host("https://ollama.internal/"), "https://ollama.internal");
+        assert_eq!(normalize_host(""), DEFAULT_HOST);
+    }
+
+    #[test]
+    fn test_request_conversion_with_native_tools() {
+        let request = provider().build_request(
+            &tool_turn(),
+            &Some(SystemPrompt::Text("Be brief".to_string())),
+            &[read_tool()],
+            &None,
+            &Some(ThinkingConfig::enabled(1024)),
+            &Some(GenerationParams::new().with_temperature(0.3)),
+            false,
+        );
+        let json = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(json["model"], "qwen3:8b");
+        assert_eq!(json["stream"], false);
+        assert_eq!(json["options"]["num_predict"], 8192);
+        assert!((json["options"]["temperature"].as_f64().unwrap() - 0.3).abs() < 1e-6);
+        assert_eq!(json["tools"][0]["type"], "function");
+        assert_eq!(json["tools"][0]["function"]["name"], "Read");
+        assert_eq!(json["tools"][0]["function"]["parameters"]["required"][0], "path");
+
+        let messages = json["messages"].as_array().unwrap();
+        let roles: Vec<&str> = messages.iter().map(|m| m["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, ["system", "user", "assistant", "tool", "user"]);
+        assert_eq!(messages[0]["content"], "Be brief");
+        // Thinking dropped, text kept, call converted
+        assert_eq!(messages[2]["content"], "Reading it");
+        assert_eq!(messages[2]["tool_calls"][0]["function"]["name"], "Read");
+        assert_eq!(messages[2]["tool_calls"][0]["function"]["arguments"]["path"], "a.txt");
+        // The tool result comes right after the call, before the user's text
+        assert_eq!(messages[3]["content"], "hello");
+        assert_eq!(messages[3]["tool_name"], "Read");
+        assert_eq!(messages[4]["content"], "Summarize it");
+    }
+
+    #[test]
+    fn test_tool_choice_none_omits_tools() {
+        let request = provider().build_request(
+            &[Message::user("hi")], &None, &[read_tool()], &Some(ToolChoice::none()), &None, &None, false,
+        );
+        assert!(request.tools.is_none());
+    }
+
+    #[test]
+    fn test_images_are_attached_and_documents_omitted() {
+        let messages = vec![Message::user_with_blocks(vec![
+            ContentBlock::text("Look"),
+            ContentBlock::image("aW1n".to_string(), "image/png".to_string()),
+            ContentBlock::document("cGRm".to_string(), "application/pdf".to_string()),
+        ])];
+        let converted = convert_messages(&messages, false);
+        assert_eq!(converted.len(), 1);
+        assert_eq!(converted[0].images, ["aW1n"]);
+        assert!(converted[0].content.starts_with("Look\n\n[application/pdf document omitted"));
+    }
+
+    #[test]
+    fn test_response_conversion() {
+        let response: OllamaResponse = serde_json::from_value(serde_json::json!({
+            "model": "qwen3:8b",
+            "message": {
+                "role": "assistant",
+                "content": "Let me check",
+                "tool_calls": [
+                    {"function": {"name": "Read", "arguments": {"path": "a.txt"}}},
+                    {"function": {"name": "Read", "arguments": "{\"path\": \"b.txt\"}"}}
+                ]
+            },
+            "done": true,
+            "done_reason": "stop",
+            "prompt_eval_count": 42,
+            "eval_count": 7
+        }))
+        .unwrap();
+        let response = provider().convert_response(response);
+
+        assert_eq!(response.stop_reason, Some(StopReason::ToolUse));
+        assert_eq!(response.usage.input_tokens, 42);
+        assert_eq!(response.usage.output_tokens, 7);
+        assert_eq!(response.text(), "Let me check");
+        let calls = response.tool_uses();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1].2["path"], "b.txt");
+        assert_ne!(calls[0].0, calls[1].0, "each call gets its own id");
+    }
+
+    #[test]
+    fn test_length_maps_to_max_tokens() {
+        let response: OllamaResponse = serde_json::from_value(serde_json::json!({
+            "message": {"role": "assistant", "content": "trunc"},
+            "done": true,
+            "done_reason": "length"
+        }))
+        .unwrap();
+        let response = provider().convert_response(response);
+        assert_eq!(response.stop_reason, Some(StopReason::MaxTokens));
+        assert_eq!(response.model, "qwen3:8b");
+    }
+
+    #[test]
+    fn test_prompt_tools_protocol() {
+        let provider = provider().with_prompt_tools(true);
+        let request = provider.build_request(
+            &tool_turn(),
+            &Some(SystemPrompt::Text("Be brief".to_string())),
+            &[read_tool()],
+            &None,
+            &None,
+            &None,
+            false,
+        );
+        assert!(request.tools.is_none());
+        let system = &request.messages[0].content;
+        assert!(system.starts_with("Be brief\n\n# Tools"), "{system}");
+        assert!(system.contains("## Read") && system.contains(TOOL_CALL_FENCE));
+
+        // History calls and results are rendered as text
+        assert!(request.messages[2].tool_calls.is_empty());
+        assert!(request.messages[2].content.contains("{\"arguments\":{\"path\":\"a.txt\"},\"name\":\"Read\"}"));
+        assert_eq!(request.messages[3].role, "user");
+        assert_eq!(request.messages[3].content, "<tool_result name=\"Read\">\nhello\n</tool_result>");
+
+        let blocks = parse_prompt_tool_calls(
+            "I'll read both.\n```tool_call\n{\"name\": \"Read\", \"arguments\": {\"path\": \"a.txt\"}}\n```\n\
+             ```tool_call\nnot json\n```\n```tool_call\n{\"name\": \"Read\"}\n```",
+        );
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].as_text().unwrap(), "I'll read both.\n\n```tool_call\nnot json\n```");
+        assert_eq!(blocks[1].as_tool_use().unwrap().2["path"], "a.txt");
+        assert_eq!(blocks[2].as_tool_use().unwrap().2, &serde_json::json!({}));
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_stream_conversion() {
+        let body = [
+            r#"{"model":"qwen3:8b","message":{"role":"assistant","content":"Hel"},"done":false}"#,
+            r#"{"model":"qwen3:8b","message":{"role":"assistant","content":"lo"},"done":false}"#,
+            r#"{"model":"qwen3:8b","message":{"role":"assistant","content":"","tool_calls":[{"function":{"name":"Read","arguments":{"path":"a.txt"}}}]},"done":false}"#,
+            r#"{"model":"qwen3:8b","message":{"role":"assistant","content":""},"done":true,"done_reason":"stop","prompt_eval_count":12,"eval_count":5}"#,
+        ]
+        .join("\n");
+        let lines = tokio::io::BufReader::new(std::io::Cursor::new(body.into_bytes())).lines();
+        let events: Vec<StreamEvent> = ndjson_events(lines, "qwen3:8b".to_string())
+            .map(|e| e.unwrap())
+            .collect()
+            .await;
+
+        let mut text = String::new();
+        let mut tool = None;
+        let mut input = String::new();
+        let mut stop = None;
+        let mut usage = Usage::default();
+        for event in &events {
+            match event {
+                StreamEvent::MessageStart(start) => usage = start.message.usage.clone(),
+                StreamEvent::ContentBlockStart(start) => {
+                    if let ContentBlockStart::ToolUse { name, .. } = &start.content_block {
+                        tool = Some((start.index, name.clone()));
+                    }
+                }
+                StreamEvent::ContentBlockDelta(delta) => match &delta.delta {
+                    ContentDelta::TextDelta { text: t } => text.push_str(t),
+                    ContentDelta::InputJsonDelta { partial_json } => input.push_str(partial_json),
+                    _ => {}
+                },
+                StreamEvent::MessageDelta(delta) => {
+                    stop = delta.delta.stop_reason.clone();
+                    delta.usage.apply_to(&mut usage);
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(text, "Hello");
+        // The text block is closed before the tool block opens
+        assert_eq!(tool, Some((1, "Read".to_string())));
+        assert_eq!(serde_json::from_str::<Value>(&input).unwrap()["path"], "a.txt");
+        assert_eq!(stop, Some(StopReason::ToolUse));
+        // The counts from the final chunk, as a non-streaming call reports them
+        assert_eq!((usage.input_tokens, usage.output_tokens), (12, 5));
+        assert!(matches!(events.last(), Some(StreamEvent::MessageStop)));
+    }
+
+    #[tokio::test]
+    async fn test_stream_error_line_is_an_error() {
+        let body = r#"{"error":"model 'nope' not found"}"#;
+        let lines = tokio::io::BufReader::new(std::io::Cursor::new(body.as_bytes().to_vec())).lines();
+        let events: Vec<Result<StreamEvent>> = ndjson_events(lines, "nope".to_string()).collect().await;
+        assert!(events.iter().any(|e| e.as_ref().is_err_and(|e| e.to_string().contains("not found"))));
+    }
+
+    /// Requires a local daemon: `OLLAMA_MODEL=qwen3:8b cargo test -- --ignored ollama`
+    #[tokio::test]
+    #[ignore]
+    async fn test_ollama_daemon_roundtrip() {
+        let provider = OllamaProvider::from_env().unwrap();
+        let reply = provider
+            .send_message("Reply with the single word: pong", &[], None, None)
+            .await
+            .unwrap();
+        assert!(reply.to_lowercase().contains("pong"), "{reply}");
+
+        let response = provider
+            .send_with_tools_and_system(
+                vec![Message::user("Read the file a.txt")],
+                None,
+                vec![read_tool()],
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(response.usage.output_tokens > 0);
+    }
+}
@@ -8,8 +8,10 @@ use futures::stream::Stream;
 use std::pin::Pin;
 use std::sync::Arc;
 
+use super::capabilities::ModelCapabilities;
+use super::stats::ProviderStats;
 use super::types::{
-    Message, MessageResponse, StreamEvent, SystemPrompt, ThinkingConfig, ToolChoice,
+    GenerationParams, Message, MessageResponse, StreamEvent, SystemPrompt, ThinkingConfig, ToolChoice,
     ToolDefinition,
 };
 
@@ -37,6 +39,8 @@ pub trait LlmProvider: Send + Sync {
     /// Send a request with tools and system prompt, returning the full response.
     ///
     /// This is the primary method used by the agent loop for non-streaming requests.
+    /// `generation` carries sampling parameters; `None` leaves them to the provider.
+    #[allow(clippy::too_many_arguments)]
     async fn send_with_tools_and_system(
         &self,
         messages: Vec<Message>,
@@ -44,6 +48,7 @@ pub trait LlmProvider: Send + Sync {
         tools: Vec<ToolDefinition>,
         tool_choice: Option<ToolChoice>,
         thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
         session_id: Option<&str>,
     ) -> Result<MessageResponse>;
 
@@ -51,6 +56,7 @@ pub trait LlmProvider: Send + Sync {
     ///
     /// Returns an async stream of StreamEvent that yields events as they arrive.
     /// This is the primary method used by the agent loop for streaming requests.
+    #[allow(clippy::too_many_arguments)]
     async fn stream_with_tools_and_system(
         &self,
         messages: Vec<Message>,
@@ -58,6 +64,7 @@ pub trait LlmProvider: Send + Sync {
         tools: Vec<ToolDefinition>,
         tool_choice: Option<ToolChoice>,
         thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
         session_id: Option<&str>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>>;
 
@@ -72,4 +79,37 @@ pub trait LlmProvider: Send + Sync {
     /// Used by ConversationNamer to create a Haiku-based namer that shares
     /// the same authentication configuration.
     fn create_variant(&self, model: &str, max_tokens: u32) -> Arc<dyn LlmProvider>;
+
+    /// Get what the current model supports.
+    ///
+    /// Defaults to the capabilities table entry for `model()`; unknown
+    /// models are assumed to support everything.
+    fn capabilities(&self) -> ModelCapabilities {
+        ModelCapabilities::for_model(&self.model())
+    }
+
+    /// Get the max tokens requested for each response, if the provider sets one.
+    ///
+    /// StandardAgent keeps this much of the context window free for the
+    /// response. Defaults to None.
+    fn max_tokens(&self) -> Option<u32> {
+        None
+    }
+
+    /// Get request, error, latency and token counts, if the provider keeps them.
+    ///
+    /// The Anthropic and Gemini providers do; wrappers report their inner
+    /// provider's. Defaults to None.
+    fn stats(&self) -> Option<ProviderStats> {
+        None
+    }
+
+    /// Zero the counts returned by `stats`.
+    fn reset_stats(&self) {}
+
+    /// Drop any state cached for these tool calls.
+    ///
+    /// Called when the calls are removed from the history (e.g. by a rewind).
+    /// Providers that keep nothing per call don't need to override this.
+    async fn forget_tool_calls(&self, _tool_use_ids: &[String]) {}
 }
@@ -0,0 +1,492 @@
+//! Provider health and latency stats
+//!
+//! HTTP providers (Anthropic, Gemini) keep a [`StatsRecorder`] and update it
+//! around every API request: a few atomic adds, no locks on the hot path.
+//! [`LlmProvider::stats`](super::LlmProvider::stats) returns a
+//! [`ProviderStats`] snapshot, for routing traffic or alerting on a provider
+//! that starts failing.
+//!
+//! ```ignore
+//! let llm = AnthropicProvider::from_env()?.with_stats_log(Duration::from_secs(60));
+//!
+//! if let Some(stats) = llm.stats() {
+//!     if stats.consecutive_failures >= 3 {
+//!         handle.set_provider(fallback.clone()).await;
+//!     }
+//!     println!("p95 {:?}", stats.latency_p95());
+//! }
+//! ```
+//!
+//! Latency is the time until the full response for non-streaming calls and
+//! the time until the response headers (first byte) for streaming calls.
+
+use futures::stream::Stream;
+use futures::StreamExt;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::types::StreamEvent;
+
+/// Upper bounds of the latency histogram buckets, in milliseconds
+///
+/// `ProviderStats::latency_buckets` has one more bucket than this, for
+/// latencies above the last bound.
+pub const LATENCY_BUCKETS_MS: [u64; 10] = [50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 30_000, 60_000];
+
+const BUCKET_COUNT: usize = LATENCY_BUCKETS_MS.len() + 1;
+
+/// Why a request failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    /// 429, or a `rate_limit_error` in a stream
+    RateLimited,
+    /// 503 or 529, or an `overloaded_error` in a stream
+    Overloaded,
+    /// Any other 5xx
+    Server,
+    /// 401 or 403
+    Auth,
+    /// Any other 4xx
+    InvalidRequest,
+    /// The request could not be sent, or the response broke off
+    Network,
+    /// The response body could not be parsed
+    Parse,
+}
+
+impl ErrorClass {
+    /// Classify an HTTP error status
+    pub fn from_status(status: StatusCode) -> Self {
+        match status.as_u16() {
+            429 => Self::RateLimited,
+            503 | 529 => Self::Overloaded,
+            401 | 403 => Self::Auth,
+            500..=599 => Self::Server,
+            _ => Self::InvalidRequest,
+        }
+    }
+
+    /// Classify the `type` of an error event in an Anthropic stream
+    pub fn from_stream_error(error_type: &str) -> Self {
+        match error_type {
+            "rate_limit_error" => Self::RateLimited,
+            "overloaded_error" => Self::Overloaded,
+            "authentication_error" | "permission_error" => Self::Auth,
+            "invalid_request_error" | "not_found_error" | "request_too_large" => Self::InvalidRequest,
+            _ => Self::Server,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Failed requests by class
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorCounts {
+    pub rate_limited: u64,
+    pub overloaded: u64,
+    pub server: u64,
+    pub auth: u64,
+    pub invalid_request: u64,
+    pub network: u64,
+    pub parse: u64,
+}
+
+impl ErrorCounts {
+    /// Count for one class
+    pub fn get(&self, class: ErrorClass) -> u64 {
+        match class {
+            ErrorClass::RateLimited => self.rate_limited,
+            ErrorClass::Overloaded => self.overloaded,
+            ErrorClass::Server => self.server,
+            ErrorClass::Auth => self.auth,
+            ErrorClass::InvalidRequest => self.invalid_request,
+            ErrorClass::Network => self.network,
+            ErrorClass::Parse => self.parse,
+        }
+    }
+
+    /// All errors
+    pub fn total(&self) -> u64 {
+        self.rate_limited + self.overloaded + self.server + self.auth + self.invalid_request + self.network + self.parse
+    }
+
+    fn get_mut(&mut self, class: ErrorClass) -> &mut u64 {
+        match class {
+            ErrorClass::RateLimited => &mut self.rate_limited,
+            ErrorClass::Overloaded => &mut self.overloaded,
+            ErrorClass::Server => &mut self.server,
+            ErrorClass::Auth => &mut self.auth,
+            ErrorClass::InvalidRequest => &mut self.invalid_request,
+            ErrorClass::Network => &mut self.network,
+            ErrorClass::Parse => &mut self.parse,
+        }
+    }
+}
+
+const ERROR_CLASSES: [ErrorClass; 7] = [
+    ErrorClass::RateLimited,
+    ErrorClass::Overloaded,
+    ErrorClass::Server,
+    ErrorClass::Auth,
+    ErrorClass::InvalidRequest,
+    ErrorClass::Network,
+    ErrorClass::Parse,
+];
+
+/// Snapshot of a provider's request counts, errors, latency and token use
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProviderStats {
+    /// Requests sent to the API
+    pub requests: u64,
+    /// Failed requests by class
+    ///
+    /// A stream that breaks off after it started counts as a request that
+    /// succeeded (for latency) and as an error.
+    pub errors: ErrorCounts,
+    /// Input tokens reported by the API
+    pub input_tokens: u64,
+    /// Output tokens reported by the API
+    pub output_tokens: u64,
+    /// Failures since the last successful request
+    pub consecutive_failures: u64,
+    /// Successful requests per latency bucket
+    ///
+    /// Bucket `i` counts latencies up to `LATENCY_BUCKETS_MS[i]` (and above
+    /// the previous bound); the last bucket counts the rest.
+    pub latency_buckets: [u64; BUCKET_COUNT],
+    /// Highest latency seen, in milliseconds
+    pub max_latency_ms: u64,
+}
+
+impl ProviderStats {
+    /// Median latency of successful requests, to bucket precision
+    pub fn latency_p50(&self) -> Option<Duration> {
+        self.latency_percentile(0.50)
+    }
+
+    /// 95th percentile latency of successful requests, to bucket precision
+    pub fn latency_p95(&self) -> Option<Duration> {
+        self.latency_percentile(0.95)
+    }
+
+    /// Latency below which a `fraction` (0.0 to 1.0) of successful requests fell
+    ///
+    /// Returns the upper bound of the bucket holding that request, capped at
+    /// the highest latency seen. None until a request succeeds.
+    pub fn latency_percentile(&self, fraction: f64) -> Option<Duration> {
+        let total: u64 = self.latency_buckets.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((fraction.clamp(0.0, 1.0) * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, count) in self.latency_buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let bound = LATENCY_BUCKETS_MS.get(i).copied().unwrap_or(u64::MAX);
+                return Some(Duration::from_millis(bound.min(self.max_latency_ms)));
+            }
+        }
+        None
+    }
+
+    /// Add another provider's counts to these
+    ///
+    /// Consecutive failures take the higher of the two.
+    pub fn merge(&mut self, other: &ProviderStats) {
+        self.requests += other.requests;
+        for class in ERROR_CLASSES {
+            *self.errors.get_mut(class) += other.errors.get(class);
+        }
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.consecutive_failures = self.consecutive_failures.max(other.consecutive_failures);
+        for (bucket, count) in self.latency_buckets.iter_mut().zip(other.latency_buckets) {
+            *bucket += count;
+        }
+        self.max_latency_ms = self.max_latency_ms.max(other.max_latency_ms);
+    }
+}
+
+/// Atomic counters behind `ProviderStats`
+///
+/// Shared behind an `Arc` so that streams can keep recording after the
+/// call that started them returns.
+pub struct StatsRecorder {
+    provider: &'static str,
+    requests: AtomicU64,
+    errors: [AtomicU64; ERROR_CLASSES.len()],
+    input_tokens: AtomicU64,
+    output_tokens: AtomicU64,
+    consecutive_failures: AtomicU64,
+    latency_buckets: [AtomicU64; BUCKET_COUNT],
+    max_latency_ms: AtomicU64,
+    log_interval: Option<Duration>,
+    last_log: Mutex<Instant>,
+}
+
+impl StatsRecorder {
+    /// Empty counters for `provider` (the name used in log lines)
+    pub fn new(provider: &'static str) -> Self {
+        Self {
+            provider,
+            requests: AtomicU64::new(0),
+            errors: Default::default(),
+            input_tokens: AtomicU64::new(0),
+            output_tokens: AtomicU64::new(0),
+            consecutive_failures: AtomicU64::new(0),
+            latency_buckets: Default::default(),
+            max_latency_ms: AtomicU64::new(0),
+            log_interval: None,
+            last_log: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Log a snapshot at info level at most once per `interval`
+    ///
+    /// The log line is written by the first request recorded after the
+    /// interval has passed, so an idle provider logs nothing.
+    pub fn with_log_interval(mut self, interval: Duration) -> Self {
+        self.log_interval = Some(interval);
+        self
+    }
+
+    /// Empty counters with the same name and log interval, for provider variants
+    pub fn fresh(&self) -> Self {
+        Self {
+            log_interval: self.log_interval,
+            ..Self::new(self.provider)
+        }
+    }
+
+    /// Count a request about to be sent; pass the result to `record_*`
+    pub fn begin(&self) -> Instant {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        Instant::now()
+    }
+
+    /// Record a successful response to a request from `begin`
+    pub fn record_success(&self, started: Instant, input_tokens: u32, output_tokens: u32) {
+        self.add_tokens(input_tokens, output_tokens);
+        self.record_started(started);
+    }
+
+    /// Record a stream whose response headers arrived
+    ///
+    /// Tokens follow as the stream is read, see `track_stream`.
+    pub fn record_started(&self, started: Instant) {
+        let latency_ms = started.elapsed().as_millis() as u64;
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|bound| latency_ms <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.max_latency_ms.fetch_max(latency_ms, Ordering::Relaxed);
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+        self.maybe_log();
+    }
+
+    /// Record a failure
+    pub fn record_error(&self, class: ErrorClass) {
+        self.errors[class.index()].fetch_add(1, Ordering::Relaxed);
+        self.consecutive_failures.fetch_add(1, Ordering::Relaxed);
+        self.maybe_log();
+    }
+
+    fn add_tokens(&self, input_tokens: u32, output_tokens: u32) {
+        self.input_tokens.fetch_add(input_tokens as u64, Ordering::Relaxed);
+        self.output_tokens.fetch_add(output_tokens as u64, Ordering::Relaxed);
+    }
+
+    /// Record token usage and errors from a stream as it is read
+    ///
+    /// Counts come from `MessageStart`, then from the last `MessageDelta`
+    /// (whose counts are cumulative). Events pass through unchanged.
+    pub fn track_stream(
+        self: &Arc<Self>,
+        stream: Pin<Box<dyn Stream<Item = anyhow::Result<StreamEvent>> + Send>>,
+    ) -> Pin<Box<dyn Stream<Item = anyhow::Result<StreamEvent>> + Send>> {
+        let stats = self.clone();
+        let (mut input_tokens, mut output_tokens) = (0, 0);
+        Box::pin(stream.inspect(move |event| match event {
+            Ok(StreamEvent::MessageStart(start)) => {
+                let usage = &start.message.usage;
+                stats.add_tokens(usage.input_tokens, usage.output_tokens);
+                (input_tokens, output_tokens) = (usage.input_tokens, usage.output_tokens);
+            }
+            Ok(StreamEvent::MessageDelta(delta)) => {
+                let input_total = delta.usage.input_tokens.unwrap_or(input_tokens);
+                let output_total = delta.usage.output_tokens;
+                stats.add_tokens(
+                    input_total.saturating_sub(input_tokens),
+                    output_total.saturating_sub(output_tokens),
+                );
+                input_tokens = input_tokens.max(input_total);
+                output_tokens = output_tokens.max(output_total);
+            }
+            Ok(StreamEvent::Error(error)) => {
+                stats.record_error(ErrorClass::from_stream_error(&error.error.error_type));
+            }
+            Ok(_) => {}
+            Err(_) => stats.record_error(ErrorClass::Network),
+        }))
+    }
+
+    /// Current counts
+    pub fn snapshot(&self) -> ProviderStats {
+        let mut errors = ErrorCounts::default();
+        for class in ERROR_CLASSES {
+            *errors.get_mut(class) = self.errors[class.index()].load(Ordering::Relaxed);
+        }
+        ProviderStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            errors,
+            input_tokens: self.input_tokens.load(Ordering::Relaxed),
+            output_tokens: self.output_tokens.load(Ordering::Relaxed),
+            consecutive_failures: self.consecutive_failures.load(Ordering::Relaxed),
+            latency_buckets: std::array::from_fn(|i| self.latency_buckets[i].load(Ordering::Relaxed)),
+            max_latency_ms: self.max_latency_ms.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Zero every counter
+    pub fn reset(&self) {
+        let counters = [
+            &self.requests,
+            &self.input_tokens,
+            &self.output_tokens,
+            &self.consecutive_failures,
+            &self.max_latency_ms,
+        ];
+        for counter in counters.into_iter().chain(&self.errors).chain(&self.latency_buckets) {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn maybe_log(&self) {
+        let Some(interval) = self.log_interval else {
+            return;
+        };
+        {
+            let Ok(mut last_log) = self.last_log.try_lock() else {
+                return;
+            };
+            if last_log.elapsed() < interval {
+                return;
+            }
+            *last_log = Instant::now();
+        }
+        let stats = self.snapshot();
+        tracing::info!(
+            provider = self.provider,
+            requests = stats.requests,
+            errors = stats.errors.total(),
+            rate_limited = stats.errors.rate_limited,
+            overloaded = stats.errors.overloaded,
+            consecutive_failures = stats.consecutive_failures,
+            p50_ms = stats.latency_p50().map(|d| d.as_millis() as u64),
+            p95_ms = stats.latency_p95().map(|d| d.as_millis() as u64),
+            input_tokens = stats.input_tokens,
+            output_tokens = stats.output_tokens,
+            "Provider stats"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_latencies(latencies_ms: &[u64]) -> ProviderStats {
+        let mut stats = ProviderStats::default();
+        for &ms in latencies_ms {
+            let bucket = LATENCY_BUCKETS_MS.iter().position(|b| ms <= *b).unwrap_or(LATENCY_BUCKETS_MS.len());
+            stats.latency_buckets[bucket] += 1;
+            stats.max_latency_ms = stats.max_latency_ms.max(ms);
+        }
+        stats
+    }
+
+    #[test]
+    fn test_percentiles_use_bucket_bounds() {
+        assert_eq!(ProviderStats::default().latency_p50(), None);
+
+        let mut latencies = vec![30; 18];
+        latencies.extend([700, 3_000]);
+        let stats = with_latencies(&latencies);
+        assert_eq!(stats.latency_p50(), Some(Duration::from_millis(50)));
+        assert_eq!(stats.latency_p95(), Some(Duration::from_millis(1_000)));
+        assert_eq!(stats.latency_percentile(1.0), Some(Duration::from_millis(3_000)));
+
+        // The overflow bucket reports the highest latency seen
+        let stats = with_latencies(&[90_000]);
+        assert_eq!(stats.latency_p50(), Some(Duration::from_millis(90_000)));
+    }
+
+    #[test]
+    fn test_error_classes() {
+        assert_eq!(ErrorClass::from_status(StatusCode::TOO_MANY_REQUESTS), ErrorClass::RateLimited);
+        assert_eq!(ErrorClass::from_status(StatusCode::from_u16(529).unwrap()), ErrorClass::Overloaded);
+        assert_eq!(ErrorClass::from_status(StatusCode::SERVICE_UNAVAILABLE), ErrorClass::Overloaded);
+        assert_eq!(ErrorClass::from_status(StatusCode::INTERNAL_SERVER_ERROR), ErrorClass::Server);
+        assert_eq!(ErrorClass::from_status(StatusCode::UNAUTHORIZED), ErrorClass::Auth);
+        assert_eq!(ErrorClass::from_status(StatusCode::BAD_REQUEST), ErrorClass::InvalidRequest);
+        assert_eq!(ErrorClass::from_stream_error("overloaded_error"), ErrorClass::Overloaded);
+        assert_eq!(ErrorClass::from_stream_error("api_error"), ErrorClass::Server);
+    }
+
+    #[test]
+    fn test_recorder_counts_and_resets() {
+        let recorder = StatsRecorder::new("test");
+        let started = recorder.begin();
+        recorder.record_success(started, 10, 4);
+        recorder.begin();
+        recorder.record_error(ErrorClass::RateLimited);
+        recorder.begin();
+        recorder.record_error(ErrorClass::Network);
+
+        let stats = recorder.snapshot();
+        assert_eq!(stats.requests, 3);
+        assert_eq!(stats.errors.total(), 2);
+        assert_eq!(stats.errors.get(ErrorClass::RateLimited), 1);
+        assert_eq!(stats.errors.network, 1);
+        assert_eq!(stats.consecutive_failures, 2);
+        assert_eq!((stats.input_tokens, stats.output_tokens), (10, 4));
+        assert_eq!(stats.latency_buckets[0], 1);
+
+        recorder.record_started(recorder.begin());
+        assert_eq!(recorder.snapshot().consecutive_failures, 0);
+
+        recorder.reset();
+        assert_eq!(recorder.snapshot(), ProviderStats::default());
+    }
+
+    #[test]
+    fn test_merge_sums_counts() {
+        let mut a = with_latencies(&[30, 700]);
+        a.requests = 3;
+        a.errors.overloaded = 1;
+        a.consecutive_failures = 1;
+        let mut b = with_latencies(&[30]);
+        b.requests = 4;
+        b.errors.overloaded = 2;
+        b.errors.auth = 1;
+        b.consecutive_failures = 3;
+
+        a.merge(&b);
+        assert_eq!(a.requests, 7);
+        assert_eq!(a.errors.overloaded, 3);
+        assert_eq!(a.errors.total(), 4);
+        assert_eq!(a.consecutive_failures, 3);
+        assert_eq!(a.latency_buckets[0], 2);
+        assert_eq!(a.max_latency_ms, 700);
+    }
+}
@@ -0,0 +1,407 @@
+//! Assembly of streamed content blocks
+//!
+//! Every event of a streamed response names the block it belongs to by
+//! index: `content_block_start` opens it, deltas extend it and
+//! `content_block_stop` closes it. `BlockAccumulator` keeps one in-progress
+//! block per index, so a block's state never leaks into the next one and
+//! interleaved events are still assembled correctly. Finished blocks keep
+//! the order of their indices; blocks that share an index (Gemini numbers
+//! every block 0) keep the order they finished in.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use super::{Citation, ContentBlock, ContentBlockStart, ContentDelta};
+
+/// A content block whose events are still arriving
+#[derive(Debug)]
+enum PartialBlock {
+    Text { text: String, citations: Vec<Citation> },
+    Thinking { thinking: String, signature: String },
+    /// `input` is the start event's, used when no input deltas arrive
+    ToolUse { id: String, name: String, input: Value, input_json: String, server: bool },
+    /// Arrived whole in its start event (web search results)
+    Complete(ContentBlock),
+}
+
+impl PartialBlock {
+    /// The finished block, or `None` for a block with nothing in it
+    ///
+    /// Empty text blocks are dropped: the API rejects them in the history.
+    fn finish(self) -> Option<ContentBlock> {
+        match self {
+            PartialBlock::Text { text, .. } if text.is_empty() => None,
+            PartialBlock::Text { text, citations } => Some(text_block(text, citations)),
+            PartialBlock::Thinking { thinking, signature } if thinking.is_empty() && signature.is_empty() => None,
+            PartialBlock::Thinking { thinking, signature } => Some(ContentBlock::Thinking { thinking, signature }),
+            PartialBlock::ToolUse { id, name, input, input_json, server } => {
+                let input = if input_json.trim().is_empty() {
+                    input
+                } else {
+                    serde_json::from_str(&input_json).unwrap_or_default()
+                };
+                Some(if server {
+                    ContentBlock::ServerToolUse { id, name, input }
+                } else {
+                    ContentBlock::ToolUse { id, name, input }
+                })
+            }
+            PartialBlock::Complete(block) => Some(block),
+        }
+    }
+}
+
+/// A finished text block, with its citations if it has any
+fn text_block(text: String, mut citations: Vec<Citation>) -> ContentBlock {
+    citations.retain(Citation::is_known);
+    ContentBlock::Text {
+        text,
+        citations: (!citations.is_empty()).then_some(citations),
+        cache_control: None,
+    }
+}
+
+/// Content blocks of one streamed response, keyed by block index
+#[derive(Debug, Default)]
+pub(crate) struct BlockAccumulator {
+    open: BTreeMap<usize, PartialBlock>,
+    /// Finished blocks with their index, sorted by index
+    done: Vec<(usize, ContentBlock)>,
+}
+
+impl BlockAccumulator {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep a finished block, after every block with the same or a lower index
+    fn finished(&mut self, index: usize, block: ContentBlock) {
+        let at = self.done.partition_point(|(done, _)| *done <= index);
+        self.done.insert(at, (index, block));
+    }
+
+    /// Open the block at `index`, replacing one left open at the same index
+    pub(crate) fn start(&mut self, index: usize, start: &ContentBlockStart) {
+        let block = match start {
+            ContentBlockStart::Text { text } => PartialBlock::Text {
+                text: text.clone(),
+                citations: Vec::new(),
+            },
+            ContentBlockStart::Thinking { thinking } => PartialBlock::Thinking {
+                thinking: thinking.clone(),
+                signature: String::new(),
+            },
+            ContentBlockStart::ToolUse { id, name, input } => PartialBlock::ToolUse {
+                id: id.clone(),
+                name: name.clone(),
+                input: input.clone(),
+                input_json: String::new(),
+                server: false,
+            },
+            ContentBlockStart::ServerToolUse { id, name, input } => PartialBlock::ToolUse {
+                id: id.clone(),
+                name: name.clone(),
+                input: input.clone(),
+                input_json: String::new(),
+                server: true,
+            },
+            ContentBlockStart::WebSearchToolResult { tool_use_id, content } => {
+                PartialBlock::Complete(ContentBlock::WebSearchToolResult {
+                    tool_use_id: tool_use_id.clone(),
+                    content: content.clone(),
+                })
+            }
+        };
+        if self.open.insert(index, block).is_some() {
+            tracing::warn!("[StandardAgent] Stream reopened content block {} before it stopped", index);
+        }
+    }
+
+    /// Add a delta to the block at `index`
+    ///
+    /// Returns false (and ignores it) when no block of the right kind is
+    /// open there.
+    pub(crate) fn delta(&mut self, index: usize, delta: &ContentDelta) -> bool {
+        match (self.open.get_mut(&index), delta) {
+            (Some(PartialBlock::Text { text, .. }), ContentDelta::TextDelta { text: more }) => text.push_str(more),
+            (Some(PartialBlock::Text { citations, .. }), ContentDelta::CitationsDelta { citation }) => {
+                citations.push(citation.clone())
+            }
+            (Some(PartialBlock::Thinking { thinking, .. }), ContentDelta::ThinkingDelta { thinking: more }) => {
+                thinking.push_str(more)
+            }
+            (Some(PartialBlock::Thinking { signature, .. }), ContentDelta::SignatureDelta { signature: more }) => {
+                signature.push_str(more)
+            }
+            (Some(PartialBlock::ToolUse { input_json, .. }), ContentDelta::InputJsonDelta { partial_json }) => {
+                input_json.push_str(partial_json)
+            }
+            (block, _) => {
+                tracing::warn!(
+                    "[StandardAgent] Ignoring a delta for content block {} ({})",
+                    index,
+                    if block.is_some() { "wrong block type" } else { "not open" }
+                );
+                return false;
+            }
+        }
+        true
+    }
+
+    /// The ID of the client tool call open at `index`
+    pub(crate) fn tool_use_id(&self, index: usize) -> Option<&str> {
+        match self.open.get(&index) {
+            Some(PartialBlock::ToolUse { id, server: false, .. }) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Close the block at `index`, returning a copy unless it was empty
+    pub(crate) fn stop(&mut self, index: usize) -> Option<ContentBlock> {
+        let block = self.open.remove(&index)?.finish()?;
+        self.finished(index, block.clone());
+        Some(block)
+    }
+
+    /// Whether an unfinished text block has text
+    pub(crate) fn has_open_text(&self) -> bool {
+        self.open
+            .values()
+            .any(|block| matches!(block, PartialBlock::Text { text, .. } if !text.is_empty()))
+    }
+
+    /// Whether a client tool call is unfinished
+    pub(crate) fn has_open_tool_use(&self) -> bool {
+        self.open
+            .values()
+            .any(|block| matches!(block, PartialBlock::ToolUse { server: false, .. }))
+    }
+
+    /// Finished blocks, in index order
+    pub(crate) fn blocks(&self) -> impl Iterator<Item = &ContentBlock> {
+        self.done.iter().map(|(_, block)| block)
+    }
+
+    /// Close the unfinished text blocks and return their text
+    ///
+    /// For a response cut short; unfinished thinking (its signature may be
+    /// missing) and tool calls (their input may be partial) are discarded.
+    pub(crate) fn close_open_text(&mut self) -> String {
+        let mut closed = String::new();
+        for (index, block) in std::mem::take(&mut self.open) {
+            if let PartialBlock::Text { text, citations } = block {
+                if !text.is_empty() {
+                    closed.push_str(&text);
+                    self.finished(index, text_block(text, citations));
+                }
+            }
+        }
+        closed
+    }
+
+    /// The finished blocks, in index order
+    pub(crate) fn into_blocks(self) -> Vec<ContentBlock> {
+        self.done.into_iter().map(|(_, block)| block).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::llm::{ContentBlockDeltaEvent, ContentBlockStartEvent, ContentBlockStopEvent, StreamEvent};
+
+    fn start(index: usize, content_block: ContentBlockStart) -> StreamEvent {
+        StreamEvent::ContentBlockStart(ContentBlockStartEvent { index, content_block })
+    }
+
+    fn delta(index: usize, delta: ContentDelta) -> StreamEvent {
+        StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent { index, delta })
+    }
+
+    fn stop(index: usize) -> StreamEvent {
+        StreamEvent::ContentBlockStop(ContentBlockStopEvent { index })
+    }
+
+    fn text(index: usize, text: &str) -> StreamEvent {
+        delta(index, ContentDelta::TextDelta { text: text.to_string() })
+    }
+
+    fn text_start() -> ContentBlockStart {
+        ContentBlockStart::Text { text: String::new() }
+    }
+
+    fn tool_start(id: &str, name: &str) -> ContentBlockStart {
+        ContentBlockStart::ToolUse {
+            id: id.to_string(),
+            name: name.to_string(),
+            input: json!({}),
+        }
+    }
+
+    fn assemble(events: Vec<StreamEvent>) -> Vec<ContentBlock> {
+        let mut blocks = BlockAccumulator::new();
+        for event in events {
+            match event {
+                StreamEvent::ContentBlockStart(e) => blocks.start(e.index, &e.content_block),
+                StreamEvent::ContentBlockDelta(e) => {
+                    blocks.delta(e.index, &e.delta);
+                }
+                StreamEvent::ContentBlockStop(e) => {
+                    blocks.stop(e.index);
+                }
+                _ => {}
+            }
+        }
+        blocks.into_blocks()
+    }
+
+    fn describe(blocks: &[ContentBlock]) -> Vec<String> {
+        blocks
+            .iter()
+            .map(|block| match block {
+                ContentBlock::Text { text, .. } => format!("text {}", text),
+                ContentBlock::Thinking { thinking, signature } => format!("thinking {} ({})", thinking, signature),
+                ContentBlock::ToolUse { name, input, .. } => format!("tool {} {}", name, input),
+                other => format!("{:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_interleaved_thinking_text_and_tools() {
+        let blocks = assemble(vec![
+            start(0, ContentBlockStart::Thinking { thinking: String::new() }),
+            delta(0, ContentDelta::ThinkingDelta { thinking: "Plan".to_string() }),
+            delta(0, ContentDelta::SignatureDelta { signature: "sig1".to_string() }),
+            stop(0),
+            start(1, text_start()),
+            text(1, "Reading it."),
+            stop(1),
+            start(2, tool_start("call_1", "Read")),
+            delta(2, ContentDelta::InputJsonDelta { partial_json: r#"{"file_path":"#.to_string() }),
+            delta(2, ContentDelta::InputJsonDelta { partial_json: r#""a.rs"}"#.to_string() }),
+            stop(2),
+            start(3, ContentBlockStart::Thinking { thinking: String::new() }),
+            delta(3, ContentDelta::ThinkingDelta { thinking: "Then".to_string() }),
+            stop(3),
+            start(4, text_start()),
+            text(4, "And more text."),
+            stop(4),
+        ]);
+        assert_eq!(
+            describe(&blocks),
+            vec![
+                "thinking Plan (sig1)",
+                "text Reading it.",
+                r#"tool Read {"file_path":"a.rs"}"#,
+                "thinking Then ()",
+                "text And more text.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_blocks_do_not_disturb_their_neighbours() {
+        let blocks = assemble(vec![
+            start(0, tool_start("call_1", "Glob")),
+            stop(0),
+            // An empty text block after a tool call
+            start(1, text_start()),
+            stop(1),
+            start(2, tool_start("call_2", "Read")),
+            delta(2, ContentDelta::InputJsonDelta { partial_json: r#"{"file_path":"b.rs"}"#.to_string() }),
+            stop(2),
+            start(3, text_start()),
+            text(3, "Done"),
+            stop(3),
+        ]);
+        assert_eq!(
+            describe(&blocks),
+            vec!["tool Glob {}", r#"tool Read {"file_path":"b.rs"}"#, "text Done"]
+        );
+    }
+
+    #[test]
+    fn test_overlapping_blocks_are_kept_apart_and_in_order() {
+        let mut blocks = BlockAccumulator::new();
+        blocks.start(0, &text_start());
+        blocks.start(1, &tool_start("call_1", "Bash"));
+        assert!(blocks.delta(1, &ContentDelta::InputJsonDelta { partial_json: r#"{"command":"ls"}"#.to_string() }));
+        assert!(blocks.delta(0, &ContentDelta::TextDelta { text: "Listing".to_string() }));
+        // A delta of the wrong type or for a closed block is ignored
+        assert!(!blocks.delta(0, &ContentDelta::InputJsonDelta { partial_json: "x".to_string() }));
+        assert!(!blocks.delta(7, &ContentDelta::TextDelta { text: "lost".to_string() }));
+        assert_eq!(blocks.tool_use_id(1), Some("call_1"));
+
+        // The tool block stops first but still comes after the text
+        assert!(matches!(blocks.stop(1), Some(ContentBlock::ToolUse { .. })));
+        assert!(blocks.has_open_text());
+        blocks.stop(0);
+        assert_eq!(
+            describe(&blocks.into_blocks()),
+            vec!["text Listing", r#"tool Bash {"command":"ls"}"#]
+        );
+    }
+
+    #[test]
+    fn test_citations_are_kept_with_their_text() {
+        let citation: Citation = serde_json::from_value(json!({
+            "type": "search_result_location",
+            "cited_text": "Tokens expire after 1 hour.",
+            "source": "kb://auth",
+            "title": "Auth guide",
+            "search_result_index": 0,
+            "start_block_index": 0,
+            "end_block_index": 0
+        }))
+        .unwrap();
+        let blocks = assemble(vec![
+            start(0, text_start()),
+            text(0, "Tokens last an hour."),
+            delta(0, ContentDelta::CitationsDelta { citation: citation.clone() }),
+            stop(0),
+            start(1, text_start()),
+            text(1, " Anything else?"),
+            stop(1),
+        ]);
+        assert!(matches!(&blocks[0], ContentBlock::Text { citations: Some(c), .. } if c == &vec![citation]));
+        assert!(matches!(&blocks[1], ContentBlock::Text { citations: None, .. }));
+    }
+
+    #[test]
+    fn test_blocks_sharing_an_index_are_all_kept() {
+        // Gemini's shape: every block at index 0
+        let blocks = assemble(vec![
+            start(0, text_start()),
+            text(0, "Let me look."),
+            stop(0),
+            start(0, tool_start("call_1", "Read")),
+            delta(0, ContentDelta::InputJsonDelta { partial_json: r#"{"file_path":"a.rs"}"#.to_string() }),
+            stop(0),
+            start(0, tool_start("call_2", "Read")),
+            delta(0, ContentDelta::InputJsonDelta { partial_json: r#"{"file_path":"b.rs"}"#.to_string() }),
+            stop(0),
+        ]);
+        assert_eq!(
+            describe(&blocks),
+            vec![
+                "text Let me look.",
+                r#"tool Read {"file_path":"a.rs"}"#,
+                r#"tool Read {"file_path":"b.rs"}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_close_open_text_keeps_only_text() {
+        let mut blocks = BlockAccumulator::new();
+        blocks.start(0, &text_start());
+        blocks.delta(0, &ContentDelta::TextDelta { text: "Partial".to_string() });
+        blocks.start(1, &tool_start("call_1", "Read"));
+        assert_eq!(blocks.close_open_text(), "Partial");
+        assert_eq!(describe(&blocks.into_blocks()), vec!["text Partial"]);
+    }
+}
@@ -24,17 +24,22 @@
 //! let pro = Arc::new(GeminiProvider::new("key")?.with_model("gemini-3-pro-preview"));
 //! handle.set_provider(pro).await;
 //! ```
+//!
+//! `stats()` adds up every provider the swappable has held: the current one's
+//! live counts plus the counts of earlier ones as they were when swapped out.
 
 use anyhow::Result;
 use futures::stream::Stream;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tokio::sync::RwLock;
 
+use super::capabilities::ModelCapabilities;
 use super::provider::LlmProvider;
+use super::stats::ProviderStats;
 use super::types::{
-    Message, MessageResponse, StreamEvent, SystemPrompt, ThinkingConfig, ToolChoice,
-    ToolDefinition,
+    GenerationParams, Message, MessageResponse, StreamEvent, SystemPrompt, ThinkingConfig,
+    ToolChoice, ToolDefinition,
 };
 
 /// A swappable LLM provider that delegates to an inner provider which can be
@@ -45,13 +50,18 @@ use super::types::{
 /// to swap the underlying provider between turns.
 pub struct SwappableLlmProvider {
     inner: Arc<RwLock<Arc<dyn LlmProvider>>>,
+    retired: RetiredStats,
 }
 
+/// Stats of the providers swapped out so far (None if none kept stats)
+type RetiredStats = Arc<Mutex<Option<ProviderStats>>>;
+
 impl SwappableLlmProvider {
     /// Create a new swappable provider wrapping the given initial provider.
     pub fn new(provider: Arc<dyn LlmProvider>) -> Self {
         Self {
             inner: Arc::new(RwLock::new(provider)),
+            retired: Arc::default(),
         }
     }
 
@@ -61,6 +71,7 @@ impl SwappableLlmProvider {
     pub fn handle(&self) -> LlmProviderHandle {
         LlmProviderHandle {
             inner: self.inner.clone(),
+            retired: self.retired.clone(),
         }
     }
 }
@@ -72,6 +83,7 @@ impl SwappableLlmProvider {
 #[derive(Clone)]
 pub struct LlmProviderHandle {
     inner: Arc<RwLock<Arc<dyn LlmProvider>>>,
+    retired: RetiredStats,
 }
 
 impl LlmProviderHandle {
@@ -82,6 +94,11 @@ impl LlmProviderHandle {
     /// complete using the old provider.
     pub async fn set_provider(&self, provider: Arc<dyn LlmProvider>) {
         let mut guard = self.inner.write().await;
+        if let Some(mut stats) = guard.stats() {
+            // Failures of the old provider don't count against the new one
+            stats.consecutive_failures = 0;
+            self.retired.lock().unwrap().get_or_insert_with(ProviderStats::default).merge(&stats);
+        }
         *guard = provider;
     }
 
@@ -94,6 +111,11 @@ impl LlmProviderHandle {
 
 #[async_trait::async_trait]
 impl LlmProvider for SwappableLlmProvider {
+    async fn forget_tool_calls(&self, tool_use_ids: &[String]) {
+        let provider = self.inner.read().await.clone();
+        provider.forget_tool_calls(tool_use_ids).await;
+    }
+
     async fn send_message(
         &self,
         user_message: &str,
@@ -114,11 +136,14 @@ impl LlmProvider for SwappableLlmProvider {
         tools: Vec<ToolDefinition>,
         tool_choice: Option<ToolChoice>,
         thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
         session_id: Option<&str>,
     ) -> Result<MessageResponse> {
         let provider = self.inner.read().await.clone();
         provider
-            .send_with_tools_and_system(messages, system, tools, tool_choice, thinking, session_id)
+            .send_with_tools_and_system(
+                messages, system, tools, tool_choice, thinking, generation, session_id,
+            )
             .await
     }
 
@@ -129,12 +154,13 @@ impl LlmProvider for SwappableLlmProvider {
         tools: Vec<ToolDefinition>,
         tool_choice: Option<ToolChoice>,
         thinking: Option<ThinkingConfig>,
+        generation: Option<GenerationParams>,
         session_id: Option<&str>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>> {
         let provider = self.inner.read().await.clone();
         provider
             .stream_with_tools_and_system(
-                messages, system, tools, tool_choice, thinking, session_id,
+                messages, system, tools, tool_choice, thinking, generation, session_id,
             )
             .await
     }
@@ -146,6 +172,33 @@ impl LlmProvider for SwappableLlmProvider {
         }
     }
 
+    fn capabilities(&self) -> ModelCapabilities {
+        match self.inner.try_read() {
+            Ok(guard) => guard.capabilities(),
+            Err(_) => ModelCapabilities::default(),
+        }
+    }
+
+    fn max_tokens(&self) -> Option<u32> {
+        self.inner.try_read().ok().and_then(|guard| guard.max_tokens())
+    }
+
+    fn stats(&self) -> Option<ProviderStats> {
+        let current = self.inner.try_read().ok().and_then(|guard| guard.stats());
+        let mut stats = self.retired.lock().unwrap().clone();
+        if let Some(current) = current {
+            stats.get_or_insert_with(ProviderStats::default).merge(&current);
+        }
+        stats
+    }
+
+    fn reset_stats(&self) {
+        *self.retired.lock().unwrap() = None;
+        if let Ok(guard) = self.inner.try_read() {
+            guard.reset_stats();
+        }
+    }
+
     fn provider_name(&self) -> &str {
         // Provider names are static strings, so we can match the inner
         // provider's name to a 'static &str to avoid lifetime issues.
@@ -169,6 +222,7 @@ impl LlmProvider for SwappableLlmProvider {
         } else {
             Arc::new(Self {
                 inner: self.inner.clone(),
+                retired: self.retired.clone(),
             })
         }
     }
@@ -0,0 +1,92 @@
+//! Token estimates
+//!
+//! A rough count of the tokens a request takes, for checks made before it
+//! is sent (see `AgentConfig::with_context_overflow`). Text counts one
+//! token per 4 characters, which is close for prose and a little low for
+//! code. Images count as much as the API charges for a full-size image;
+//! PDFs by the size of their data. Treat the numbers as an estimate, not
+//! as what the provider will bill.
+
+use super::types::{ContentBlock, Message, MessageContent, SystemPrompt, ToolDefinition};
+
+/// Characters per token of text
+const CHARS_PER_TOKEN: u64 = 4;
+
+/// Tokens of an image (the API downsizes larger ones to about this)
+const IMAGE_TOKENS: u64 = 1600;
+
+/// Base64 characters per token of a document (roughly 1,500-3,000 tokens per PDF page)
+const DOCUMENT_CHARS_PER_TOKEN: u64 = 40;
+
+/// Tokens added per message for its role and framing
+const MESSAGE_OVERHEAD: u64 = 4;
+
+/// Estimate the tokens of a piece of text
+pub fn estimate_text_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Estimate the tokens of one content block
+pub fn estimate_block_tokens(block: &ContentBlock) -> u64 {
+    match block {
+        ContentBlock::Text { text, .. } => estimate_text_tokens(text),
+        ContentBlock::Thinking { thinking, .. } => estimate_text_tokens(thinking),
+        ContentBlock::ToolUse { name, input, .. } | ContentBlock::ServerToolUse { name, input, .. } => {
+            estimate_text_tokens(name) + estimate_text_tokens(&input.to_string())
+        }
+        ContentBlock::ToolResult { content, .. } => content.as_deref().map(estimate_text_tokens).unwrap_or(0),
+        ContentBlock::Image { .. } => IMAGE_TOKENS,
+        ContentBlock::Document { source, .. } => (source.data.len() as u64).div_ceil(DOCUMENT_CHARS_PER_TOKEN),
+        ContentBlock::SearchResult { title, source, content, .. } => {
+            estimate_text_tokens(title)
+                + estimate_text_tokens(source)
+                + content.iter().map(estimate_block_tokens).sum::<u64>()
+        }
+        other => serde_json::to_string(other).map(|json| estimate_text_tokens(&json)).unwrap_or(0),
+    }
+}
+
+/// Estimate the tokens of a message
+pub fn estimate_message_tokens(message: &Message) -> u64 {
+    let content = match &message.content {
+        MessageContent::Text(text) => estimate_text_tokens(text),
+        MessageContent::Blocks(blocks) => blocks.iter().map(estimate_block_tokens).sum(),
+    };
+    content + MESSAGE_OVERHEAD
+}
+
+/// Estimate the input tokens of a request: its messages, system prompt and tools
+pub fn estimate_request_tokens(messages: &[Message], system: Option<&SystemPrompt>, tools: &[ToolDefinition]) -> u64 {
+    let system = match system {
+        Some(SystemPrompt::Text(text)) => estimate_text_tokens(text),
+        Some(SystemPrompt::Blocks(blocks)) => blocks.iter().map(|block| estimate_text_tokens(&block.text)).sum(),
+        None => 0,
+    };
+    let tools: u64 = tools
+        .iter()
+        .map(|tool| serde_json::to_string(tool).map(|json| estimate_text_tokens(&json)).unwrap_or(0))
+        .sum();
+    messages.iter().map(estimate_message_tokens).sum::<u64>() + system + tools
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimates() {
+        assert_eq!(estimate_text_tokens(""), 0);
+        assert_eq!(estimate_text_tokens("abcde"), 2);
+        assert_eq!(estimate_message_tokens(&Message::user("a".repeat(400))), 104);
+
+        let blocks = Message::user_with_blocks(vec![
+            ContentBlock::tool_result("toolu_1", "x".repeat(80), false),
+            ContentBlock::image("aW1hZ2U=".to_string(), "image/png".to_string()),
+            ContentBlock::document("a".repeat(4000), "application/pdf".to_string()),
+        ]);
+        assert_eq!(estimate_message_tokens(&blocks), 20 + IMAGE_TOKENS + 100 + MESSAGE_OVERHEAD);
+
+        let system = SystemPrompt::Text("s".repeat(40));
+        assert_eq!(estimate_request_tokens(&[Message::user("hi")], Some(&system), &[]), 1 + MESSAGE_OVERHEAD + 10);
+    }
+}
@@ -123,6 +123,14 @@ pub struct MessageRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f32>,
 
+    /// Nucleus sampling threshold (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+
+    /// Sequences that stop generation when produced (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+
     /// Whether to stream the response (optional)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
@@ -137,6 +145,15 @@ pub struct ThinkingConfig {
 
     /// Budget tokens for thinking
     pub budget_tokens: u32,
+
+    /// Send the interleaved-thinking beta, so the model can think between
+    /// tool calls (Anthropic and Bedrock; not part of the request body)
+    #[serde(skip, default = "interleaved_default")]
+    pub interleaved: bool,
+}
+
+fn interleaved_default() -> bool {
+    true
 }
 
 impl ThinkingConfig {
@@ -145,10 +162,133 @@ impl ThinkingConfig {
         Self {
             thinking_type: "enabled".to_string(),
             budget_tokens,
+            interleaved: true,
+        }
+    }
+
+    /// Whether to send the interleaved-thinking beta (default true)
+    ///
+    /// Turn it off for models that reject the beta.
+    pub fn with_interleaved(mut self, interleaved: bool) -> Self {
+        self.interleaved = interleaved;
+        self
+    }
+}
+
+/// Sampling parameters for a request
+///
+/// Unset fields are left to the provider's defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GenerationParams {
+    /// Sampling temperature
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Nucleus sampling threshold
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// Max tokens of the response (None = the provider's max tokens)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    /// Sequences that stop generation when produced
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop_sequences: Vec<String>,
+    /// Constrain the response format (None = free text)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    /// Skip response caches (see `CachingProvider`) for this request
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_cache: bool,
+}
+
+/// Response format enforced by the provider, not just asked for in the prompt
+///
+/// With `Json`, the response text is a JSON value (matching `schema` when
+/// one is given). Gemini and Ollama have native JSON modes; Anthropic and
+/// Bedrock get a hidden tool whose input is the schema, and the tool call is
+/// turned back into a text block, so callers see JSON text either way.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// Respond with JSON
+    Json {
+        /// JSON Schema the response must match
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        schema: Option<Value>,
+    },
+}
+
+impl ResponseFormat {
+    /// Any JSON value
+    pub fn json() -> Self {
+        ResponseFormat::Json { schema: None }
+    }
+
+    /// JSON matching a schema
+    pub fn json_schema(schema: Value) -> Self {
+        ResponseFormat::Json { schema: Some(schema) }
+    }
+
+    /// The schema of a `Json` format, if it has one
+    pub fn schema(&self) -> Option<&Value> {
+        match self {
+            ResponseFormat::Json { schema } => schema.as_ref(),
         }
     }
 }
 
+impl GenerationParams {
+    /// Create empty parameters (provider defaults)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the sampling temperature
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Set the nucleus sampling threshold
+    pub fn with_top_p(mut self, top_p: f32) -> Self {
+        self.top_p = Some(top_p);
+        self
+    }
+
+    /// Set the max tokens of the response, overriding the provider's
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Set the stop sequences
+    pub fn with_stop_sequences(mut self, stop_sequences: Vec<String>) -> Self {
+        self.stop_sequences = stop_sequences;
+        self
+    }
+
+    /// Constrain the response format
+    pub fn with_response_format(mut self, format: ResponseFormat) -> Self {
+        self.response_format = Some(format);
+        self
+    }
+
+    /// Send the request to the provider even when a cached response exists
+    pub fn with_no_cache(mut self, no_cache: bool) -> Self {
+        self.no_cache = no_cache;
+        self
+    }
+
+    /// Whether nothing is set
+    pub fn is_empty(&self) -> bool {
+        self.temperature.is_none()
+            && self.top_p.is_none()
+            && self.max_tokens.is_none()
+            && self.stop_sequences.is_empty()
+            && self.response_format.is_none()
+            && !self.no_cache
+    }
+}
+
 /// A message in the conversation
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
@@ -231,6 +371,7 @@ impl Message {
                 blocks.push(ContentBlock::Text {
                     text: text.to_string(),
                     cache_control: None,
+                    citations: None,
                 });
             }
         }
@@ -251,6 +392,7 @@ impl Message {
                     ContentBlock::Text {
                         text: text.to_string(),
                         cache_control: None,
+                        citations: None,
                     },
                 );
             }
@@ -318,6 +460,11 @@ pub enum ContentBlock {
         text: String,
         #[serde(skip_serializing_if = "Option::is_none")]
         cache_control: Option<CacheControl>,
+        /// Sources the model cited for this text (assistant messages only)
+        ///
+        /// Citations of a type this crate doesn't know are dropped.
+        #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "deserialize_known_citations")]
+        citations: Option<Vec<Citation>>,
     },
 
     /// Tool use request from the model
@@ -368,6 +515,179 @@ pub enum ContentBlock {
         #[serde(skip_serializing_if = "Option::is_none")]
         cache_control: Option<CacheControl>,
     },
+
+    /// Server tool call made by the model (e.g., web search), run by the API
+    #[serde(rename = "server_tool_use")]
+    ServerToolUse {
+        id: String,
+        name: String,
+        input: Value,
+    },
+
+    /// Result of a server-side web search
+    #[serde(rename = "web_search_tool_result")]
+    WebSearchToolResult {
+        tool_use_id: String,
+        content: WebSearchToolResultContent,
+    },
+
+    /// A retrieved document the model can cite (see `ContentBlock::search_result`)
+    #[serde(rename = "search_result")]
+    SearchResult {
+        /// Where the content came from (URL, file path, document ID)
+        source: String,
+        title: String,
+        /// Text blocks
+        content: Vec<ContentBlock>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        citations: Option<CitationsConfig>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+}
+
+/// Whether the model may cite a search result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CitationsConfig {
+    pub enabled: bool,
+}
+
+/// A passage the model cited, and where it is in the source
+///
+/// Attached to the text blocks of a response; `OutputChunk::Citations`
+/// carries them to UIs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Citation {
+    /// Characters of a plain text document
+    CharLocation {
+        cited_text: String,
+        document_index: usize,
+        #[serde(default)]
+        document_title: Option<String>,
+        start_char_index: usize,
+        end_char_index: usize,
+    },
+    /// Pages of a PDF document
+    PageLocation {
+        cited_text: String,
+        document_index: usize,
+        #[serde(default)]
+        document_title: Option<String>,
+        start_page_number: usize,
+        end_page_number: usize,
+    },
+    /// Blocks of a custom content document
+    ContentBlockLocation {
+        cited_text: String,
+        document_index: usize,
+        #[serde(default)]
+        document_title: Option<String>,
+        start_block_index: usize,
+        end_block_index: usize,
+    },
+    /// Blocks of a `search_result` block
+    SearchResultLocation {
+        cited_text: String,
+        source: String,
+        #[serde(default)]
+        title: Option<String>,
+        search_result_index: usize,
+        start_block_index: usize,
+        end_block_index: usize,
+    },
+    /// A page found by the server-side web search
+    WebSearchResultLocation {
+        cited_text: String,
+        url: String,
+        #[serde(default)]
+        title: Option<String>,
+        encrypted_index: String,
+    },
+    /// A citation type this crate doesn't know yet
+    ///
+    /// Only seen in a `citations_delta`; text blocks drop these, since they
+    /// can't be sent back to the API.
+    #[serde(other)]
+    Unknown,
+}
+
+impl Citation {
+    /// The cited passage
+    pub fn cited_text(&self) -> &str {
+        match self {
+            Citation::CharLocation { cited_text, .. }
+            | Citation::PageLocation { cited_text, .. }
+            | Citation::ContentBlockLocation { cited_text, .. }
+            | Citation::SearchResultLocation { cited_text, .. }
+            | Citation::WebSearchResultLocation { cited_text, .. } => cited_text,
+            Citation::Unknown => "",
+        }
+    }
+
+    /// Whether this is a citation type this crate knows
+    pub fn is_known(&self) -> bool {
+        !matches!(self, Citation::Unknown)
+    }
+
+    /// The title of the cited source, if it has one
+    pub fn title(&self) -> Option<&str> {
+        match self {
+            Citation::CharLocation { document_title, .. }
+            | Citation::PageLocation { document_title, .. }
+            | Citation::ContentBlockLocation { document_title, .. } => document_title.as_deref(),
+            Citation::SearchResultLocation { title, .. } | Citation::WebSearchResultLocation { title, .. } => {
+                title.as_deref()
+            }
+            Citation::Unknown => None,
+        }
+    }
+
+    /// The search result source or web page URL (None for documents)
+    pub fn source(&self) -> Option<&str> {
+        match self {
+            Citation::SearchResultLocation { source, .. } => Some(source),
+            Citation::WebSearchResultLocation { url, .. } => Some(url),
+            _ => None,
+        }
+    }
+}
+
+/// Content of a `web_search_tool_result` block: results or an error
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum WebSearchToolResultContent {
+    /// Search results
+    Results(Vec<WebSearchResult>),
+    /// The search failed
+    Error(WebSearchToolError),
+}
+
+/// A single web search result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSearchResult {
+    /// Result type (always "web_search_result")
+    #[serde(rename = "type")]
+    pub result_type: String,
+    /// Page URL
+    pub url: String,
+    /// Page title
+    pub title: String,
+    /// Page content, encrypted; must be sent back unchanged in later turns
+    pub encrypted_content: String,
+    /// How long ago the page was updated (e.g., "2 days ago")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_age: Option<String>,
+}
+
+/// A failed web search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSearchToolError {
+    /// Error type (always "web_search_tool_result_error")
+    #[serde(rename = "type")]
+    pub error_type: String,
+    /// Error code (e.g., "max_uses_exceeded", "too_many_requests")
+    pub error_code: String,
 }
 
 impl ContentBlock {
@@ -376,6 +696,7 @@ impl ContentBlock {
         ContentBlock::Text {
             text: text.into(),
             cache_control: None,
+            citations: None,
         }
     }
 
@@ -384,6 +705,30 @@ impl ContentBlock {
         ContentBlock::Text {
             text: text.into(),
             cache_control: Some(cache_control),
+            citations: None,
+        }
+    }
+
+    /// A search result as plain text, for providers without citations
+    pub fn search_result_text(&self) -> Option<String> {
+        let ContentBlock::SearchResult { source, title, content, .. } = self else {
+            return None;
+        };
+        let text: Vec<&str> = content.iter().filter_map(|block| block.as_text()).collect();
+        Some(format!("[Search result: {} ({})]\n{}", title, source, text.join("\n")))
+    }
+
+    /// Create a search result block with citations enabled
+    ///
+    /// For retrieved documents: put them in a user message and the model
+    /// can cite them by `source`.
+    pub fn search_result(source: impl Into<String>, title: impl Into<String>, text: impl Into<String>) -> Self {
+        ContentBlock::SearchResult {
+            source: source.into(),
+            title: title.into(),
+            content: vec![ContentBlock::text(text)],
+            citations: Some(CitationsConfig { enabled: true }),
+            cache_control: None,
         }
     }
 
@@ -486,6 +831,11 @@ impl ContentBlock {
 pub enum ToolDefinition {
     /// Custom tool with JSON schema
     Custom(CustomTool),
+    /// Server-side web search tool (run by Anthropic, not executed locally)
+    ///
+    /// Listed before `Bash` so untagged deserialization checks its `type` first;
+    /// both are just a name and a type when no options are set.
+    WebSearch(WebSearchTool),
     /// Built-in bash tool
     Bash(BashTool),
     /// Built-in text editor tool
@@ -505,6 +855,9 @@ impl ToolDefinition {
             ToolDefinition::TextEditor(tool) => {
                 tool.cache_control = Some(cache_control);
             }
+            ToolDefinition::WebSearch(tool) => {
+                tool.cache_control = Some(cache_control);
+            }
         }
         self
     }
@@ -627,12 +980,176 @@ impl Default for TextEditorTool {
     }
 }
 
+/// Server-side web search tool (web_search_20250305)
+///
+/// Searches run on Anthropic's side: the response carries `server_tool_use`
+/// and `web_search_tool_result` blocks, and nothing is executed locally.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebSearchTool {
+    /// Tool name (always "web_search")
+    pub name: String,
+
+    /// Tool type (always "web_search_20250305")
+    #[serde(rename = "type", deserialize_with = "deserialize_web_search_type")]
+    pub tool_type: String,
+
+    /// Maximum number of searches per request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_uses: Option<u32>,
+
+    /// Only include results from these domains
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_domains: Option<Vec<String>>,
+
+    /// Never include results from these domains
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocked_domains: Option<Vec<String>>,
+
+    /// Location used to localize results
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_location: Option<UserLocation>,
+
+    /// Cache control (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+impl WebSearchTool {
+    /// Create a web search tool with no limits
+    pub fn new() -> Self {
+        Self {
+            name: "web_search".to_string(),
+            tool_type: "web_search_20250305".to_string(),
+            max_uses: None,
+            allowed_domains: None,
+            blocked_domains: None,
+            user_location: None,
+            cache_control: None,
+        }
+    }
+
+    /// Limit the number of searches per request
+    pub fn with_max_uses(mut self, max_uses: u32) -> Self {
+        self.max_uses = Some(max_uses);
+        self
+    }
+
+    /// Only include results from these domains
+    ///
+    /// The API rejects a tool with both allowed and blocked domains.
+    pub fn with_allowed_domains(mut self, domains: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.allowed_domains = Some(domains.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Never include results from these domains
+    pub fn with_blocked_domains(mut self, domains: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.blocked_domains = Some(domains.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Localize results
+    pub fn with_user_location(mut self, location: UserLocation) -> Self {
+        self.user_location = Some(location);
+        self
+    }
+}
+
+impl Default for WebSearchTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Text block citations, without the ones of unknown types
+fn deserialize_known_citations<'de, D>(deserializer: D) -> std::result::Result<Option<Vec<Citation>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let citations = Option::<Vec<Citation>>::deserialize(deserializer)?;
+    Ok(citations
+        .map(|citations| citations.into_iter().filter(Citation::is_known).collect::<Vec<_>>())
+        .filter(|citations| !citations.is_empty()))
+}
+
+/// Only accept web search tool types, so other built-in tools don't
+/// deserialize as `ToolDefinition::WebSearch`
+fn deserialize_web_search_type<'de, D>(deserializer: D) -> std::result::Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let tool_type = String::deserialize(deserializer)?;
+    if tool_type.starts_with("web_search_") {
+        Ok(tool_type)
+    } else {
+        Err(serde::de::Error::custom(format!("not a web search tool type: {}", tool_type)))
+    }
+}
+
+/// Approximate user location for localizing web search results
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserLocation {
+    /// Location type (always "approximate")
+    #[serde(rename = "type")]
+    pub location_type: String,
+
+    /// City name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+
+    /// Region or state
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+
+    /// Two-letter ISO country code
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+
+    /// IANA timezone (e.g., "America/Los_Angeles")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+}
+
+impl UserLocation {
+    /// Create an empty approximate location
+    pub fn approximate() -> Self {
+        Self {
+            location_type: "approximate".to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Set the city
+    pub fn with_city(mut self, city: impl Into<String>) -> Self {
+        self.city = Some(city.into());
+        self
+    }
+
+    /// Set the region
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.region = Some(region.into());
+        self
+    }
+
+    /// Set the country
+    pub fn with_country(mut self, country: impl Into<String>) -> Self {
+        self.country = Some(country.into());
+        self
+    }
+
+    /// Set the timezone
+    pub fn with_timezone(mut self, timezone: impl Into<String>) -> Self {
+        self.timezone = Some(timezone.into());
+        self
+    }
+}
+
 // ============================================================================
 // Tool Choice
 // ============================================================================
 
 /// How the model should use tools
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ToolChoice {
     /// Model decides whether to use tools
@@ -751,6 +1268,107 @@ impl MessageResponse {
     pub fn is_end_turn(&self) -> bool {
         matches!(self.stop_reason, Some(StopReason::EndTurn))
     }
+
+    /// The events a streaming provider would emit for this response
+    ///
+    /// Each block comes as one start, one delta and one stop event, so
+    /// assembling the stream gives back the same content.
+    pub fn into_stream_events(self) -> Vec<StreamEvent> {
+        response_events(self, None)
+    }
+}
+
+/// Convert a response into stream events
+///
+/// Tool inputs are split into `InputJsonDelta`s of `input_chunk_size`
+/// characters (one delta when `None`).
+pub(crate) fn response_events(response: MessageResponse, input_chunk_size: Option<usize>) -> Vec<StreamEvent> {
+    let input_deltas = |input: &Value| -> Vec<ContentDelta> {
+        let json = input.to_string();
+        let chars: Vec<char> = json.chars().collect();
+        chars
+            .chunks(input_chunk_size.unwrap_or(chars.len().max(1)))
+            .map(|chunk| ContentDelta::InputJsonDelta {
+                partial_json: chunk.iter().collect(),
+            })
+            .collect()
+    };
+
+    let mut events = vec![StreamEvent::MessageStart(MessageStartEvent {
+        message: MessageStartData {
+            id: response.id.clone(),
+            message_type: "message".to_string(),
+            role: "assistant".to_string(),
+            content: vec![],
+            model: response.model.clone(),
+            stop_reason: None,
+            stop_sequence: None,
+            usage: response.usage.clone(),
+        },
+    })];
+
+    for (index, block) in response.content.into_iter().enumerate() {
+        let (start, deltas) = match block {
+            ContentBlock::Text { text, citations, .. } => {
+                let mut deltas = vec![ContentDelta::TextDelta { text }];
+                deltas.extend(
+                    citations
+                        .into_iter()
+                        .flatten()
+                        .map(|citation| ContentDelta::CitationsDelta { citation }),
+                );
+                (ContentBlockStart::Text { text: String::new() }, deltas)
+            }
+            ContentBlock::Thinking { thinking, signature } => {
+                let mut deltas = vec![ContentDelta::ThinkingDelta { thinking }];
+                if !signature.is_empty() {
+                    deltas.push(ContentDelta::SignatureDelta { signature });
+                }
+                (ContentBlockStart::Thinking { thinking: String::new() }, deltas)
+            }
+            ContentBlock::ToolUse { id, name, input } => (
+                ContentBlockStart::ToolUse {
+                    id,
+                    name,
+                    input: Value::Object(Default::default()),
+                },
+                input_deltas(&input),
+            ),
+            ContentBlock::ServerToolUse { id, name, input } => (
+                ContentBlockStart::ServerToolUse {
+                    id,
+                    name,
+                    input: Value::Object(Default::default()),
+                },
+                input_deltas(&input),
+            ),
+            ContentBlock::WebSearchToolResult { tool_use_id, content } => (
+                ContentBlockStart::WebSearchToolResult { tool_use_id, content },
+                vec![],
+            ),
+            // Other block types never appear in assistant responses
+            _ => continue,
+        };
+
+        events.push(StreamEvent::ContentBlockStart(ContentBlockStartEvent {
+            index,
+            content_block: start,
+        }));
+        for delta in deltas {
+            events.push(StreamEvent::ContentBlockDelta(ContentBlockDeltaEvent { index, delta }));
+        }
+        events.push(StreamEvent::ContentBlockStop(ContentBlockStopEvent { index }));
+    }
+
+    events.push(StreamEvent::MessageDelta(MessageDeltaEvent {
+        delta: MessageDeltaData {
+            stop_reason: response.stop_reason,
+            stop_sequence: response.stop_sequence,
+        },
+        usage: DeltaUsage::from_usage(&response.usage),
+    }));
+    events.push(StreamEvent::MessageStop);
+    events
 }
 
 /// Reason why the model stopped generating
@@ -772,7 +1390,7 @@ pub enum StopReason {
 }
 
 /// Token usage information
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Usage {
     /// Input tokens used
     pub input_tokens: u32,
@@ -794,6 +1412,23 @@ pub struct Usage {
     pub thoughts_token_count: Option<u32>,
 }
 
+impl Usage {
+    /// Add another call's usage to this one
+    pub fn add(&mut self, other: &Usage) {
+        fn sum(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+            match (a, b) {
+                (None, None) => None,
+                (a, b) => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+            }
+        }
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens = sum(self.cache_creation_input_tokens, other.cache_creation_input_tokens);
+        self.cache_read_input_tokens = sum(self.cache_read_input_tokens, other.cache_read_input_tokens);
+        self.thoughts_token_count = sum(self.thoughts_token_count, other.thoughts_token_count);
+    }
+}
+
 // ============================================================================
 // Error Types
 // ============================================================================
@@ -846,14 +1481,14 @@ pub enum StreamEvent {
 }
 
 /// Event data for message_start
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageStartEvent {
     /// The message object (with empty content)
     pub message: MessageStartData,
 }
 
 /// Message data in message_start event
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageStartData {
     /// Unique message ID
     pub id: String,
@@ -875,7 +1510,7 @@ pub struct MessageStartData {
 }
 
 /// Event data for content_block_start
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContentBlockStartEvent {
     /// Index of this content block
     pub index: usize,
@@ -884,7 +1519,7 @@ pub struct ContentBlockStartEvent {
 }
 
 /// Content block start data
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ContentBlockStart {
     /// Text block start
@@ -896,10 +1531,19 @@ pub enum ContentBlockStart {
     /// Thinking block start
     #[serde(rename = "thinking")]
     Thinking { thinking: String },
+    /// Server tool use block start (input follows as JSON deltas)
+    #[serde(rename = "server_tool_use")]
+    ServerToolUse { id: String, name: String, input: Value },
+    /// Web search result block (complete in the start event)
+    #[serde(rename = "web_search_tool_result")]
+    WebSearchToolResult {
+        tool_use_id: String,
+        content: WebSearchToolResultContent,
+    },
 }
 
 /// Event data for content_block_delta
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContentBlockDeltaEvent {
     /// Index of the content block being updated
     pub index: usize,
@@ -908,7 +1552,7 @@ pub struct ContentBlockDeltaEvent {
 }
 
 /// Delta types for content block updates
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ContentDelta {
     /// Text delta
@@ -923,17 +1567,20 @@ pub enum ContentDelta {
     /// Signature delta (at end of thinking block)
     #[serde(rename = "signature_delta")]
     SignatureDelta { signature: String },
+    /// A citation for the text block
+    #[serde(rename = "citations_delta")]
+    CitationsDelta { citation: Citation },
 }
 
 /// Event data for content_block_stop
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContentBlockStopEvent {
     /// Index of the content block that stopped
     pub index: usize,
 }
 
 /// Event data for message_delta
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageDeltaEvent {
     /// Delta changes to the message
     pub delta: MessageDeltaData,
@@ -942,7 +1589,7 @@ pub struct MessageDeltaEvent {
 }
 
 /// Delta data in message_delta event
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageDeltaData {
     /// Stop reason
     pub stop_reason: Option<StopReason>,
@@ -951,14 +1598,60 @@ pub struct MessageDeltaData {
 }
 
 /// Usage in delta events (may only have output_tokens)
-#[derive(Debug, Clone, Deserialize)]
+///
+/// The other counts, when present, are final and replace those from
+/// `MessageStart`. Gemini fills them from the last chunk's usage metadata.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DeltaUsage {
     /// Output tokens (cumulative)
     pub output_tokens: u32,
+    /// Input tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_tokens: Option<u32>,
+    /// Cache creation tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_creation_input_tokens: Option<u32>,
+    /// Cache read tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_read_input_tokens: Option<u32>,
+    /// Thinking tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thoughts_token_count: Option<u32>,
+}
+
+impl DeltaUsage {
+    /// Complete usage as a delta, so a stream can end with the same usage as
+    /// the non-streaming response
+    pub fn from_usage(usage: &Usage) -> Self {
+        Self {
+            output_tokens: usage.output_tokens,
+            input_tokens: Some(usage.input_tokens),
+            cache_creation_input_tokens: usage.cache_creation_input_tokens,
+            cache_read_input_tokens: usage.cache_read_input_tokens,
+            thoughts_token_count: usage.thoughts_token_count,
+        }
+    }
+
+    /// Update `usage` (from `MessageStart`) with these counts
+    pub fn apply_to(&self, usage: &mut Usage) {
+        usage.output_tokens = self.output_tokens;
+        if let Some(input_tokens) = self.input_tokens {
+            usage.input_tokens = input_tokens;
+        }
+        if self.cache_creation_input_tokens.is_some() {
+            usage.cache_creation_input_tokens = self.cache_creation_input_tokens;
+        }
+        if self.cache_read_input_tokens.is_some() {
+            usage.cache_read_input_tokens = self.cache_read_input_tokens;
+        }
+        if self.thoughts_token_count.is_some() {
+            usage.thoughts_token_count = self.thoughts_token_count;
+        }
+    }
 }
 
 /// Error in stream
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StreamError {
     /// Error type
     #[serde(rename = "type")]
@@ -968,7 +1661,7 @@ pub struct StreamError {
 }
 
 /// Stream error details
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StreamErrorDetails {
     /// Error type
     #[serde(rename = "type")]
@@ -978,7 +1671,7 @@ pub struct StreamErrorDetails {
 }
 
 /// Raw SSE event data structure for deserialization
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum RawStreamEvent {
     #[serde(rename = "message_start")]
@@ -1028,6 +1721,29 @@ impl RawStreamEvent {
             }
         }
     }
+
+    /// Convert from StreamEvent (the inverse of `into_stream_event`)
+    pub fn from_stream_event(event: StreamEvent) -> Self {
+        match event {
+            StreamEvent::MessageStart(e) => RawStreamEvent::MessageStart { message: e.message },
+            StreamEvent::ContentBlockStart(e) => RawStreamEvent::ContentBlockStart {
+                index: e.index,
+                content_block: e.content_block,
+            },
+            StreamEvent::ContentBlockDelta(e) => RawStreamEvent::ContentBlockDelta {
+                index: e.index,
+                delta: e.delta,
+            },
+            StreamEvent::ContentBlockStop(e) => RawStreamEvent::ContentBlockStop { index: e.index },
+            StreamEvent::MessageDelta(e) => RawStreamEvent::MessageDelta {
+                delta: e.delta,
+                usage: e.usage,
+            },
+            StreamEvent::MessageStop => RawStreamEvent::MessageStop,
+            StreamEvent::Ping => RawStreamEvent::Ping,
+            StreamEvent::Error(e) => RawStreamEvent::Error { error: e.error },
+        }
+    }
 }
 
 #[cfg(test)]
@@ -1080,4 +1796,220 @@ mod tests {
             _ => panic!("Expected ContentBlockDelta"),
         }
     }
+
+    #[test]
+    fn test_web_search_tool_serialization() {
+        // Tool entry from the web search tool docs
+        let expected: Value = serde_json::from_str(
+            r#"{
+                "type": "web_search_20250305",
+                "name": "web_search",
+                "max_uses": 5,
+                "allowed_domains": ["example.com", "trusteddomain.org"],
+                "user_location": {
+                    "type": "approximate",
+                    "city": "San Francisco",
+                    "region": "California",
+                    "country": "US",
+                    "timezone": "America/Los_Angeles"
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let tool = ToolDefinition::WebSearch(
+            WebSearchTool::new()
+                .with_max_uses(5)
+                .with_allowed_domains(["example.com", "trusteddomain.org"])
+                .with_user_location(
+                    UserLocation::approximate()
+                        .with_city("San Francisco")
+                        .with_region("California")
+                        .with_country("US")
+                        .with_timezone("America/Los_Angeles"),
+                ),
+        );
+        assert_eq!(serde_json::to_value(&tool).unwrap(), expected);
+
+        let parsed: ToolDefinition = serde_json::from_value(expected).unwrap();
+        assert!(matches!(parsed, ToolDefinition::WebSearch(ref t) if t.max_uses == Some(5)));
+
+        // A bare bash tool has the same shape but must not parse as web search
+        let bash = serde_json::to_value(ToolDefinition::Bash(BashTool::default())).unwrap();
+        let parsed: ToolDefinition = serde_json::from_value(bash).unwrap();
+        assert!(matches!(parsed, ToolDefinition::Bash(_)));
+    }
+
+    #[test]
+    fn test_web_search_result_blocks_deserialization() {
+        // Response content from the web search tool docs
+        let json = r#"{
+            "id": "msg_01Xyz",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-sonnet-4-20250514",
+            "content": [
+                {"type": "text", "text": "I'll search for when Claude Shannon was born."},
+                {
+                    "type": "server_tool_use",
+                    "id": "srvtoolu_01WYG3ziw53XMcoyKL4XcZmE",
+                    "name": "web_search",
+                    "input": {"query": "claude shannon birth date"}
+                },
+                {
+                    "type": "web_search_tool_result",
+                    "tool_use_id": "srvtoolu_01WYG3ziw53XMcoyKL4XcZmE",
+                    "content": [
+                        {
+                            "type": "web_search_result",
+                            "url": "https://en.wikipedia.org/wiki/Claude_Shannon",
+                            "title": "Claude Shannon - Wikipedia",
+                            "encrypted_content": "EqgfCioIARgBIiQ3YTAwMjY1Mi1mZjM5LTQ1NGUtODgxNC1kNjNjNTk1ZWI3Y...",
+                            "page_age": "April 30, 2025"
+                        }
+                    ]
+                },
+                {
+                    "text": "Claude Shannon was born on April 30, 1916, in Petoskey, Michigan",
+                    "type": "text",
+                    "citations": [
+                        {
+                            "type": "web_search_result_location",
+                            "url": "https://en.wikipedia.org/wiki/Claude_Shannon",
+                            "title": "Claude Shannon - Wikipedia",
+                            "encrypted_index": "Eo8BCioIAhgBIiQyYjQ0OWJmZi1lNm..",
+                            "cited_text": "Claude Elwood Shannon (April 30, 1916 – February 24, 2001) was an American mathematician..."
+                        }
+                    ]
+                }
+            ],
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": {
+                "input_tokens": 6039,
+                "output_tokens": 931,
+                "server_tool_use": {"web_search_requests": 1}
+            }
+        }"#;
+        let response: MessageResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(response.content.len(), 4);
+
+        match &response.content[1] {
+            ContentBlock::ServerToolUse { id, name, input } => {
+                assert_eq!(id, "srvtoolu_01WYG3ziw53XMcoyKL4XcZmE");
+                assert_eq!(name, "web_search");
+                assert_eq!(input["query"], "claude shannon birth date");
+            }
+            other => panic!("Expected ServerToolUse, got {:?}", other),
+        }
+        match &response.content[2] {
+            ContentBlock::WebSearchToolResult {
+                tool_use_id,
+                content: WebSearchToolResultContent::Results(results),
+            } => {
+                assert_eq!(tool_use_id, "srvtoolu_01WYG3ziw53XMcoyKL4XcZmE");
+                assert_eq!(results[0].title, "Claude Shannon - Wikipedia");
+                assert_eq!(results[0].page_age.as_deref(), Some("April 30, 2025"));
+            }
+            other => panic!("Expected WebSearchToolResult, got {:?}", other),
+        }
+
+        // Blocks go back to the API unchanged in later turns
+        let original: Value = serde_json::from_str(json).unwrap();
+        for index in 1..3 {
+            assert_eq!(serde_json::to_value(&response.content[index]).unwrap(), original["content"][index]);
+        }
+    }
+
+    #[test]
+    fn test_web_search_error_deserialization() {
+        let json = r#"{
+            "type": "web_search_tool_result",
+            "tool_use_id": "servertoolu_a93jad",
+            "content": {"type": "web_search_tool_result_error", "error_code": "max_uses_exceeded"}
+        }"#;
+        let block: ContentBlock = serde_json::from_str(json).unwrap();
+        match &block {
+            ContentBlock::WebSearchToolResult {
+                content: WebSearchToolResultContent::Error(error),
+                ..
+            } => assert_eq!(error.error_code, "max_uses_exceeded"),
+            other => panic!("Expected web search error, got {:?}", other),
+        }
+        assert_eq!(serde_json::to_value(&block).unwrap(), serde_json::from_str::<Value>(json).unwrap());
+
+        let start = r#"{"type": "content_block_start", "index": 1, "content_block": {"type": "server_tool_use", "id": "srvtoolu_1", "name": "web_search", "input": {}}}"#;
+        let event: RawStreamEvent = serde_json::from_str(start).unwrap();
+        assert!(matches!(
+            event,
+            RawStreamEvent::ContentBlockStart { content_block: ContentBlockStart::ServerToolUse { .. }, .. }
+        ));
+    }
+
+    #[test]
+    fn test_search_result_serialization() {
+        let block = ContentBlock::search_result("https://docs.example.com/auth", "Auth guide", "Tokens expire after 1 hour.");
+        assert_eq!(
+            serde_json::to_value(&block).unwrap(),
+            serde_json::json!({
+                "type": "search_result",
+                "source": "https://docs.example.com/auth",
+                "title": "Auth guide",
+                "content": [{"type": "text", "text": "Tokens expire after 1 hour."}],
+                "citations": {"enabled": true}
+            })
+        );
+        assert_eq!(
+            block.search_result_text().unwrap(),
+            "[Search result: Auth guide (https://docs.example.com/auth)]\nTokens expire after 1 hour."
+        );
+        assert!(ContentBlock::text("plain").search_result_text().is_none());
+    }
+
+    #[test]
+    fn test_text_citations_deserialization() {
+        let json = r#"{
+            "type": "text",
+            "text": "Tokens last an hour.",
+            "citations": [{
+                "type": "search_result_location",
+                "cited_text": "Tokens expire after 1 hour.",
+                "source": "https://docs.example.com/auth",
+                "title": "Auth guide",
+                "search_result_index": 0,
+                "start_block_index": 0,
+                "end_block_index": 0
+            }]
+        }"#;
+        let block: ContentBlock = serde_json::from_str(json).unwrap();
+        let ContentBlock::Text { citations: Some(citations), .. } = &block else {
+            panic!("Expected text with citations, got {:?}", block);
+        };
+        assert_eq!(citations[0].cited_text(), "Tokens expire after 1 hour.");
+        assert_eq!(citations[0].title(), Some("Auth guide"));
+        assert_eq!(citations[0].source(), Some("https://docs.example.com/auth"));
+        // Sent back unchanged in the history
+        assert_eq!(serde_json::to_value(&block).unwrap(), serde_json::from_str::<Value>(json).unwrap());
+
+        // Text without citations doesn't grow the field
+        let plain = serde_json::to_value(ContentBlock::text("Hi")).unwrap();
+        assert_eq!(plain, serde_json::json!({"type": "text", "text": "Hi"}));
+
+        let delta = r#"{"type": "content_block_delta", "index": 0, "delta": {"type": "citations_delta", "citation": {"type": "char_location", "cited_text": "x", "document_index": 0, "document_title": null, "start_char_index": 0, "end_char_index": 1}}}"#;
+        let event: RawStreamEvent = serde_json::from_str(delta).unwrap();
+        assert!(matches!(
+            event,
+            RawStreamEvent::ContentBlockDelta {
+                delta: ContentDelta::CitationsDelta { citation: Citation::CharLocation { .. } },
+                ..
+            }
+        ));
+
+        // A new citation type doesn't fail the response, and isn't kept
+        let newer = r#"{"type": "text", "text": "Hi", "citations": [{"type": "video_location", "start_ms": 0}]}"#;
+        let block: ContentBlock = serde_json::from_str(newer).unwrap();
+        assert!(matches!(block, ContentBlock::Text { citations: None, .. }));
+        let citation: Citation = serde_json::from_str(r#"{"type": "video_location", "start_ms": 0}"#).unwrap();
+        assert!(!citation.is_known());
+    }
 }
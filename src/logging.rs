@@ -1,47 +1,904 @@
-use anyhow::Result;
-use std::path::Path;
+//! Logging setup
+//!
+//! Configures the global `tracing` subscriber. Logs are written to files only
+//! (no console output) so they never interfere with the terminal UI.
+//!
+//! - **Formats**: human-readable text or one JSON object per line
+//! - **Rotation**: daily, hourly, never, or by file size, with an optional cap on
+//!   the number of rotated files kept
+//! - **Per-session files**: events that carry a `session_id` field (directly or
+//!   from an enclosing span) are also written to `<dir>/<session_id>-<hash>.log`
+//!
+//! # Environment overrides
+//!
+//! `LoggingConfig::from_env()` starts from the defaults and applies:
+//!
+//! | Variable | Example | Effect |
+//! |----------|---------|--------|
+//! | `RUST_LOG` | `shadow_agent_sdk=debug` | Filter directives |
+//! | `SHADOW_LOG_DIR` | `/var/log/agent` | Log directory |
+//! | `SHADOW_LOG_FORMAT` | `json` | `text` or `json` |
+//! | `SHADOW_LOG_ROTATION` | `size:10M` | `daily`, `hourly`, `never`, `size:<bytes>[K\|M\|G]` |
+//! | `SHADOW_LOG_MAX_FILES` | `7` | Rotated files to keep |
+//! | `SHADOW_LOG_PER_SESSION` | `true` | Enable per-session files |
+//!
+//! # Example
+//!
+//! ```ignore
+//! let config = LoggingConfig::new()
+//!     .with_format(LogFormat::Json)
+//!     .with_rotation(LogRotation::Size(10 * 1024 * 1024))
+//!     .with_per_session_files(true);
+//! init_logging_with_config(config)?;
+//! ```
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
+/// Default log directory
+pub const DEFAULT_LOG_DIR: &str = "logs";
+
+/// Default name of the main log file
+pub const DEFAULT_LOG_FILE: &str = "agent.log";
+
+/// Default number of per-session log files kept open at once
+pub const DEFAULT_MAX_OPEN_SESSION_FILES: usize = 64;
+
+// ============================================================================
+// Configuration
+// ============================================================================
+
+/// Output format for log lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Human-readable text
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(anyhow!("Unknown log format '{}'", other)),
+        }
+    }
+}
+
+/// When the main log file is rotated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogRotation {
+    /// New file every day
+    #[default]
+    Daily,
+    /// New file every hour
+    Hourly,
+    /// Single file, never rotated
+    Never,
+    /// Rotate once the file exceeds this many bytes
+    Size(u64),
+}
+
+impl FromStr for LogRotation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let lower = s.to_ascii_lowercase();
+        match lower.as_str() {
+            "daily" => Ok(LogRotation::Daily),
+            "hourly" => Ok(LogRotation::Hourly),
+            "never" => Ok(LogRotation::Never),
+            _ => {
+                let size = lower
+                    .strip_prefix("size:")
+                    .ok_or_else(|| anyhow!("Unknown log rotation '{}'", s))?;
+                let (digits, multiplier) = match size.chars().last() {
+                    Some('k') => (&size[..size.len() - 1], 1024),
+                    Some('m') => (&size[..size.len() - 1], 1024 * 1024),
+                    Some('g') => (&size[..size.len() - 1], 1024 * 1024 * 1024),
+                    _ => (size, 1),
+                };
+                let bytes: u64 = digits
+                    .trim()
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid log rotation size '{}'", s))?;
+                bytes
+                    .checked_mul(multiplier)
+                    .map(LogRotation::Size)
+                    .ok_or_else(|| anyhow!("Log rotation size '{}' is too large", s))
+            }
+        }
+    }
+}
+
+/// Configuration for the logging system
+#[derive(Debug, Clone)]
+pub struct LoggingConfig {
+    /// Directory for all log files
+    pub dir: PathBuf,
+
+    /// Name of the main log file
+    pub file_name: String,
+
+    /// Line format (used for both the main and per-session files)
+    pub format: LogFormat,
+
+    /// Rotation policy for the main log file
+    pub rotation: LogRotation,
+
+    /// Maximum number of rotated main log files to keep (None keeps all)
+    pub max_files: Option<usize>,
+
+    /// Whether to also write per-session log files
+    pub per_session_files: bool,
+
+    /// Filter directives (same syntax as `RUST_LOG`)
+    pub filter: String,
+}
+
+impl LoggingConfig {
+    /// Create a config with the defaults (daily text logs in `logs/agent.log`)
+    pub fn new() -> Self {
+        Self {
+            dir: PathBuf::from(DEFAULT_LOG_DIR),
+            file_name: DEFAULT_LOG_FILE.to_string(),
+            format: LogFormat::Text,
+            rotation: LogRotation::Daily,
+            max_files: None,
+            per_session_files: false,
+            filter: "info".to_string(),
+        }
+    }
+
+    /// Create a config from the defaults plus environment overrides
+    pub fn from_env() -> Result<Self> {
+        let mut config = Self::new();
+
+        if let Ok(filter) = std::env::var("RUST_LOG") {
+            config.filter = filter;
+        }
+        if let Ok(dir) = std::env::var("SHADOW_LOG_DIR") {
+            config.dir = PathBuf::from(dir);
+        }
+        if let Ok(format) = std::env::var("SHADOW_LOG_FORMAT") {
+            config.format = format.parse()?;
+        }
+        if let Ok(rotation) = std::env::var("SHADOW_LOG_ROTATION") {
+            config.rotation = rotation.parse()?;
+        }
+        if let Ok(max_files) = std::env::var("SHADOW_LOG_MAX_FILES") {
+            config.max_files = Some(
+                max_files
+                    .parse()
+                    .map_err(|_| anyhow!("Invalid SHADOW_LOG_MAX_FILES '{}'", max_files))?,
+            );
+        }
+        if let Ok(per_session) = std::env::var("SHADOW_LOG_PER_SESSION") {
+            config.per_session_files = matches!(
+                per_session.to_ascii_lowercase().as_str(),
+                "1" | "true" | "yes" | "on"
+            );
+        }
+
+        Ok(config)
+    }
+
+    /// Set the log directory
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = dir.into();
+        self
+    }
+
+    /// Set the main log file name
+    pub fn with_file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_name = file_name.into();
+        self
+    }
+
+    /// Set the line format
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set the rotation policy
+    pub fn with_rotation(mut self, rotation: LogRotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Set the maximum number of rotated files to keep
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.max_files = Some(max_files);
+        self
+    }
+
+    /// Enable or disable per-session log files
+    pub fn with_per_session_files(mut self, enabled: bool) -> Self {
+        self.per_session_files = enabled;
+        self
+    }
+
+    /// Set the filter directives
+    pub fn with_filter(mut self, filter: impl Into<String>) -> Self {
+        self.filter = filter.into();
+        self
+    }
+
+    /// Build the layers described by this config
+    ///
+    /// Useful for composing with other layers or installing a scoped
+    /// subscriber in tests. `init_logging_with_config` uses this internally.
+    pub fn build_layers<S>(&self) -> Result<Vec<Box<dyn Layer<S> + Send + Sync>>>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        std::fs::create_dir_all(&self.dir)?;
+
+        let mut layers: Vec<Box<dyn Layer<S> + Send + Sync>> = Vec::new();
+
+        let layer = match self.rotation {
+            LogRotation::Size(max_bytes) => {
+                let writer = SizeRotatingAppender::new(
+                    self.dir.join(&self.file_name),
+                    max_bytes,
+                    self.max_files,
+                )?;
+                self.fmt_layer(writer)
+            }
+            rotation => {
+                let rotation = match rotation {
+                    LogRotation::Hourly => Rotation::HOURLY,
+                    LogRotation::Never => Rotation::NEVER,
+                    _ => Rotation::DAILY,
+                };
+                let mut builder = RollingFileAppender::builder()
+                    .rotation(rotation)
+                    .filename_prefix(&self.file_name);
+                if let Some(max_files) = self.max_files {
+                    builder = builder.max_log_files(max_files);
+                }
+                self.fmt_layer(builder.build(&self.dir)?)
+            }
+        };
+        layers.push(layer);
+
+        if self.per_session_files {
+            layers.push(Box::new(SessionFileLayer::new(&self.dir, self.format)));
+        }
+
+        Ok(layers)
+    }
+
+    /// Build the main fmt layer for a writer in the configured format
+    fn fmt_layer<S, W>(&self, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+        W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+    {
+        let layer = fmt::layer()
+            .with_writer(writer)
+            .with_ansi(false)
+            .with_target(true)
+            .with_thread_ids(true)
+            .with_line_number(true);
+
+        match self.format {
+            LogFormat::Text => Box::new(layer),
+            LogFormat::Json => Box::new(layer.json().with_current_span(true).with_span_list(true)),
+        }
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ============================================================================
+// Initialization
+// ============================================================================
+
 /// Initialize the logging system
 /// Logs will be written to the logs/ directory only (no console output)
+///
+/// Uses `LoggingConfig::from_env()`, so the defaults match previous behavior
+/// unless overridden by environment variables.
 pub fn init_logging() -> Result<()> {
-    // Create logs directory if it doesn't exist
-    std::fs::create_dir_all("logs")?;
-
-    // File appender - daily rotation in logs/ folder
-    let file_appender = RollingFileAppender::new(
-        Rotation::DAILY,
-        "logs",
-        "agent.log",
-    );
-
-    // Create file layer
-    let file_layer = fmt::layer()
-        .with_writer(file_appender)
-        .with_ansi(false)
-        .with_target(true)
-        .with_thread_ids(true)
-        .with_line_number(true);
+    init_logging_with_config(LoggingConfig::from_env()?)
+}
 
-    // Set up environment filter
-    // Default to INFO level, can be overridden with RUST_LOG env var
-    let env_filter = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::new("info"));
+/// Initialize the logging system with an explicit config
+pub fn init_logging_with_config(config: LoggingConfig) -> Result<()> {
+    let env_filter = EnvFilter::try_new(&config.filter).unwrap_or_else(|_| EnvFilter::new("info"));
 
-    // Combine layers - only file layer, no stdout
     tracing_subscriber::registry()
         .with(env_filter)
-        .with(file_layer)
-        .init();
+        .with(config.build_layers()?)
+        .try_init()?;
 
     tracing::info!("Logging system initialized");
-    tracing::info!("Log files location: logs/agent.log");
+    tracing::info!(
+        "Log files location: {}",
+        config.dir.join(&config.file_name).display()
+    );
 
     Ok(())
 }
 
 /// Check if logs directory exists
 pub fn logs_dir_exists() -> bool {
-    Path::new("logs").exists()
+    Path::new(DEFAULT_LOG_DIR).exists()
+}
+
+// ============================================================================
+// OpenTelemetry (feature "otel")
+// ============================================================================
+
+/// Keeps the OpenTelemetry exporter alive; flushes pending spans on drop
+#[cfg(feature = "otel")]
+pub struct OtelGuard {
+    provider: opentelemetry_sdk::trace::SdkTracerProvider,
+}
+
+#[cfg(feature = "otel")]
+impl OtelGuard {
+    /// The tracer provider backing the exporter
+    pub fn provider(&self) -> &opentelemetry_sdk::trace::SdkTracerProvider {
+        &self.provider
+    }
+}
+
+#[cfg(feature = "otel")]
+impl Drop for OtelGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Failed to shut down OpenTelemetry exporter: {}", e);
+        }
+    }
+}
+
+/// Create a tracer provider exporting spans via OTLP/HTTP to `endpoint`
+///
+/// Must be called from within a tokio runtime.
+#[cfg(feature = "otel")]
+pub fn otel_tracer_provider(
+    endpoint: &str,
+    service_name: &str,
+) -> Result<opentelemetry_sdk::trace::SdkTracerProvider> {
+    use opentelemetry_otlp::WithExportConfig;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()?;
+
+    Ok(opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            opentelemetry_sdk::Resource::builder()
+                .with_service_name(service_name.to_string())
+                .build(),
+        )
+        .build())
+}
+
+/// Create a layer that exports `tracing` spans through a tracer provider
+#[cfg(feature = "otel")]
+pub fn otel_layer<S>(
+    provider: &opentelemetry_sdk::trace::SdkTracerProvider,
+) -> tracing_opentelemetry::OpenTelemetryLayer<S, opentelemetry_sdk::trace::SdkTracer>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    use opentelemetry::trace::TracerProvider as _;
+
+    tracing_opentelemetry::layer().with_tracer(provider.tracer("shadow-agent-sdk"))
+}
+
+/// Initialize file logging (from `LoggingConfig::from_env()`) plus OTLP export
+///
+/// Keep the returned guard alive for the lifetime of the program.
+///
+/// # Example
+///
+/// ```ignore
+/// let _otel = init_otel("http://localhost:4318/v1/traces", "my-agent")?;
+/// ```
+#[cfg(feature = "otel")]
+pub fn init_otel(endpoint: &str, service_name: &str) -> Result<OtelGuard> {
+    init_otel_with_config(endpoint, service_name, LoggingConfig::from_env()?)
+}
+
+/// Initialize file logging with an explicit config plus OTLP export
+#[cfg(feature = "otel")]
+pub fn init_otel_with_config(
+    endpoint: &str,
+    service_name: &str,
+    config: LoggingConfig,
+) -> Result<OtelGuard> {
+    let provider = otel_tracer_provider(endpoint, service_name)?;
+    let env_filter = EnvFilter::try_new(&config.filter).unwrap_or_else(|_| EnvFilter::new("info"));
+
+    tracing_subscriber::registry()
+        .with(env_filter)
+        .with(config.build_layers()?)
+        .with(otel_layer(&provider))
+        .try_init()?;
+
+    tracing::info!(
+        "OpenTelemetry export enabled: endpoint={}, service={}",
+        endpoint,
+        service_name
+    );
+
+    Ok(OtelGuard { provider })
+}
+
+// ============================================================================
+// Size-based rotation
+// ============================================================================
+
+/// File writer that rotates once the file exceeds a size limit
+///
+/// Rotated files are renamed `<name>.1`, `<name>.2`, ... with `.1` the newest.
+pub struct SizeRotatingAppender {
+    inner: Mutex<SizeRotatingFile>,
+}
+
+struct SizeRotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: Option<usize>,
+    file: File,
+    written: u64,
+}
+
+impl SizeRotatingAppender {
+    /// Open (or create) the log file at `path`
+    pub fn new(path: impl Into<PathBuf>, max_bytes: u64, max_files: Option<usize>) -> io::Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            inner: Mutex::new(SizeRotatingFile {
+                path,
+                max_bytes,
+                max_files,
+                file,
+                written,
+            }),
+        })
+    }
+}
+
+impl SizeRotatingFile {
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        let rotated = |n: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{}", n));
+            PathBuf::from(name)
+        };
+
+        // Without a limit, keep shifting as far as files exist
+        let keep = match self.max_files {
+            Some(max) => max,
+            None => (1..).find(|n| !rotated(*n).exists()).unwrap_or(1),
+        };
+
+        if keep == 0 {
+            let _ = std::fs::remove_file(&self.path);
+        } else {
+            let _ = std::fs::remove_file(rotated(keep));
+            for n in (1..keep).rev() {
+                let from = rotated(n);
+                if from.exists() {
+                    std::fs::rename(&from, rotated(n + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, rotated(1))?;
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+/// Writer handed out by `SizeRotatingAppender` for a single event
+pub struct SizeRotatingWriter<'a>(MutexGuard<'a, SizeRotatingFile>);
+
+impl Write for SizeRotatingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let inner = &mut *self.0;
+        if inner.written > 0 && inner.written + buf.len() as u64 > inner.max_bytes {
+            inner.rotate()?;
+        }
+        let n = inner.file.write(buf)?;
+        inner.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.file.flush()
+    }
+}
+
+impl<'a> MakeWriter<'a> for SizeRotatingAppender {
+    type Writer = SizeRotatingWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        SizeRotatingWriter(self.inner.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+// ============================================================================
+// Per-session files
+// ============================================================================
+
+/// Session ID recorded on a span
+struct SessionId(String);
+
+/// Collects event/span fields, pulling out `session_id` and `message`
+#[derive(Default)]
+struct FieldCollector {
+    session_id: Option<String>,
+    message: Option<String>,
+    fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Visit for FieldCollector {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, serde_json::Value::String(value.to_string()));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.record(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.record(field, value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record(field, serde_json::Value::String(format!("{:?}", value)));
+    }
+}
+
+impl FieldCollector {
+    fn record(&mut self, field: &Field, value: serde_json::Value) {
+        let as_string = || match &value {
+            serde_json::Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        match field.name() {
+            "session_id" => {
+                let id = as_string();
+                if !id.is_empty() {
+                    self.session_id = Some(id);
+                }
+            }
+            "message" => self.message = Some(as_string()),
+            name => {
+                self.fields.insert(name.to_string(), value);
+            }
+        }
+    }
+}
+
+/// Per-session files that are open, with when each was last written to
+#[derive(Default)]
+struct OpenFiles {
+    files: HashMap<String, (File, u64)>,
+    writes: u64,
+}
+
+/// Layer that copies events carrying a `session_id` into per-session files
+///
+/// The session ID is taken from the event itself, or from the nearest
+/// enclosing span that recorded one. At most `max_open_files` files are kept
+/// open; the least recently written one is closed to make room, and reopened
+/// (appending) if its session logs again.
+pub struct SessionFileLayer {
+    dir: PathBuf,
+    format: LogFormat,
+    max_open_files: usize,
+    files: Arc<Mutex<OpenFiles>>,
+}
+
+impl SessionFileLayer {
+    /// Create a layer writing `<dir>/<session_id>-<hash>.log` files
+    pub fn new(dir: impl Into<PathBuf>, format: LogFormat) -> Self {
+        Self {
+            dir: dir.into(),
+            format,
+            max_open_files: DEFAULT_MAX_OPEN_SESSION_FILES,
+            files: Arc::new(Mutex::new(OpenFiles::default())),
+        }
+    }
+
+    /// Set how many session files are kept open at once (at least 1)
+    pub fn with_max_open_files(mut self, max: usize) -> Self {
+        self.max_open_files = max.max(1);
+        self
+    }
+
+    /// Number of session files currently open
+    pub fn open_file_count(&self) -> usize {
+        self.files.lock().unwrap_or_else(|e| e.into_inner()).files.len()
+    }
+
+    /// Path of the log file for a session
+    ///
+    /// Characters that aren't safe in a file name become `_`, so the name ends
+    /// with a short hash of the raw ID to keep e.g. `a/b` and `a_b` apart.
+    pub fn session_log_path(dir: &Path, session_id: &str) -> PathBuf {
+        let safe: String = session_id
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+            .collect();
+        // FNV-1a, so names stay the same across builds and Rust versions
+        let hash = session_id
+            .bytes()
+            .fold(0x811c9dc5u32, |h, b| (h ^ b as u32).wrapping_mul(0x01000193));
+        dir.join(format!("{}-{:08x}.log", safe, hash))
+    }
+
+    fn write_line(&self, session_id: &str, line: &str) {
+        let mut open = self.files.lock().unwrap_or_else(|e| e.into_inner());
+        open.writes += 1;
+        let now = open.writes;
+        if !open.files.contains_key(session_id) {
+            if open.files.len() >= self.max_open_files {
+                let oldest = open
+                    .files
+                    .iter()
+                    .min_by_key(|(_, (_, last))| *last)
+                    .map(|(id, _)| id.clone());
+                if let Some(oldest) = oldest {
+                    open.files.remove(&oldest);
+                }
+            }
+            let path = Self::session_log_path(&self.dir, session_id);
+            match OpenOptions::new().create(true).append(true).open(&path) {
+                Ok(file) => {
+                    open.files.insert(session_id.to_string(), (file, now));
+                }
+                Err(_) => return,
+            }
+        }
+        if let Some((file, last)) = open.files.get_mut(session_id) {
+            *last = now;
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+}
+
+impl<S> Layer<S> for SessionFileLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut collector = FieldCollector::default();
+        attrs.record(&mut collector);
+        if let (Some(session_id), Some(span)) = (collector.session_id, ctx.span(id)) {
+            span.extensions_mut().insert(SessionId(session_id));
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let mut collector = FieldCollector::default();
+        values.record(&mut collector);
+        if let (Some(session_id), Some(span)) = (collector.session_id, ctx.span(id)) {
+            let mut extensions = span.extensions_mut();
+            extensions.remove::<SessionId>();
+            extensions.insert(SessionId(session_id));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut collector = FieldCollector::default();
+        event.record(&mut collector);
+
+        let session_id = collector.session_id.clone().or_else(|| {
+            ctx.event_scope(event)?
+                .find_map(|span| span.extensions().get::<SessionId>().map(|s| s.0.clone()))
+        });
+        let Some(session_id) = session_id else {
+            return;
+        };
+
+        let metadata = event.metadata();
+        let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        let message = collector.message.unwrap_or_default();
+
+        let line = match self.format {
+            LogFormat::Json => serde_json::json!({
+                "timestamp": timestamp,
+                "level": metadata.level().as_str(),
+                "target": metadata.target(),
+                "session_id": session_id,
+                "message": message,
+                "fields": collector.fields,
+            })
+            .to_string(),
+            LogFormat::Text => {
+                let mut line = format!(
+                    "{} {:>5} {}: {}",
+                    timestamp,
+                    metadata.level().as_str(),
+                    metadata.target(),
+                    message
+                );
+                for (key, value) in &collector.fields {
+                    match value {
+                        serde_json::Value::String(s) => line.push_str(&format!(" {}={}", key, s)),
+                        other => line.push_str(&format!(" {}={}", key, other)),
+                    }
+                }
+                line
+            }
+        };
+
+        self.write_line(&session_id, &line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::Instrument;
+
+    #[test]
+    fn test_parse_rotation() {
+        assert_eq!("daily".parse::<LogRotation>().unwrap(), LogRotation::Daily);
+        assert_eq!("NEVER".parse::<LogRotation>().unwrap(), LogRotation::Never);
+        assert_eq!(
+            "size:10M".parse::<LogRotation>().unwrap(),
+            LogRotation::Size(10 * 1024 * 1024)
+        );
+        assert_eq!("size:512".parse::<LogRotation>().unwrap(), LogRotation::Size(512));
+        assert!("weekly".parse::<LogRotation>().is_err());
+        assert!("size:abc".parse::<LogRotation>().is_err());
+        assert!("size:99999999999G".parse::<LogRotation>().is_err());
+        assert_eq!(
+            "size:16777215G".parse::<LogRotation>().unwrap(),
+            LogRotation::Size(16777215 * 1024 * 1024 * 1024)
+        );
+    }
+
+    #[test]
+    fn test_session_log_paths_are_distinct() {
+        let dir = Path::new("/logs");
+        let a = SessionFileLayer::session_log_path(dir, "a/b");
+        let b = SessionFileLayer::session_log_path(dir, "a_b");
+        assert_ne!(a, b);
+        assert_eq!(a.parent(), Some(dir));
+        assert!(a.file_name().unwrap().to_str().unwrap().starts_with("a_b-"));
+        assert_eq!(a, SessionFileLayer::session_log_path(dir, "a/b"));
+    }
+
+    #[test]
+    fn test_size_rotation_keeps_max_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agent.log");
+        let appender = SizeRotatingAppender::new(&path, 10, Some(2)).unwrap();
+
+        for line in ["aaaaaaaa\n", "bbbbbbbb\n", "cccccccc\n", "dddddddd\n"] {
+            appender.make_writer().write_all(line.as_bytes()).unwrap();
+        }
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "dddddddd\n");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("agent.log.1")).unwrap(),
+            "cccccccc\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("agent.log.2")).unwrap(),
+            "bbbbbbbb\n"
+        );
+        assert!(!dir.path().join("agent.log.3").exists());
+    }
+
+    #[test]
+    fn test_concurrent_sessions_get_separate_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = LoggingConfig::new()
+            .with_dir(dir.path())
+            .with_format(LogFormat::Json)
+            .with_rotation(LogRotation::Never)
+            .with_per_session_files(true);
+
+        let subscriber = tracing_subscriber::registry().with(config.build_layers().unwrap());
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let session = |id: &'static str| {
+                async move {
+                    for i in 0..3 {
+                        tracing::info!(step = i, "working in {}", id);
+                        tokio::task::yield_now().await;
+                    }
+                }
+                .instrument(tracing::info_span!("agent", session_id = id))
+            };
+            // Interleave both sessions on the same thread
+            tokio::join!(session("session-a"), session("session-b"));
+            tracing::info!(session_id = "session-c", "event-level session id");
+            tracing::info!("no session");
+        });
+
+        let read_lines = |id: &str| -> Vec<serde_json::Value> {
+            std::fs::read_to_string(SessionFileLayer::session_log_path(dir.path(), id))
+                .unwrap()
+                .lines()
+                .map(|l| serde_json::from_str(l).unwrap())
+                .collect()
+        };
+
+        for id in ["session-a", "session-b"] {
+            let lines = read_lines(id);
+            assert_eq!(lines.len(), 3);
+            for line in &lines {
+                assert_eq!(line["session_id"], id);
+                assert_eq!(line["message"], format!("working in {}", id));
+            }
+        }
+        assert_eq!(read_lines("session-c").len(), 1);
+
+        // The main log has everything, including the event without a session
+        let main = std::fs::read_to_string(dir.path().join(DEFAULT_LOG_FILE)).unwrap();
+        assert_eq!(main.lines().count(), 8);
+    }
+
+    #[test]
+    fn test_open_session_files_are_capped() {
+        let dir = tempfile::tempdir().unwrap();
+        let layer = SessionFileLayer::new(dir.path(), LogFormat::Text).with_max_open_files(2);
+
+        for round in 0..2 {
+            for i in 0..10 {
+                layer.write_line(&format!("session-{}", i), &format!("line {}", round));
+                assert!(layer.open_file_count() <= 2);
+            }
+        }
+        // Closed files are reopened for appending
+        let read = |id: &str| std::fs::read_to_string(SessionFileLayer::session_log_path(dir.path(), id)).unwrap();
+        for i in 0..10 {
+            assert_eq!(read(&format!("session-{}", i)), "line 0\nline 1\n");
+        }
+    }
 }
@@ -7,6 +7,7 @@ use std::time::Duration;
 
 /// Configuration for a single MCP server
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct MCPServerConfig {
     /// Unique identifier for this server (used for namespacing tools)
     pub id: String,
@@ -24,6 +25,13 @@ pub struct MCPServerConfig {
 
     /// Optional health check interval in seconds
     pub health_check_interval_secs: Option<u64>,
+
+    /// Longest a tool call may take, in milliseconds (default two minutes)
+    ///
+    /// A single call can ask for another limit with a `_timeout_ms` field
+    /// in its input, which is removed before the call is sent.
+    #[serde(default)]
+    pub tool_timeout_ms: Option<u64>,
 }
 
 fn default_enabled() -> bool {
@@ -43,6 +51,7 @@ impl MCPServerConfig {
             enabled: true,
             reconnect_attempts: 3,
             health_check_interval_secs: None,
+            tool_timeout_ms: None,
         }
     }
 
@@ -68,12 +77,25 @@ impl MCPServerConfig {
     pub fn health_check_interval(&self) -> Option<Duration> {
         self.health_check_interval_secs.map(Duration::from_secs)
     }
+
+    /// Set how long a tool call may take
+    pub fn with_tool_timeout(mut self, timeout: Duration) -> Self {
+        self.tool_timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    /// Get the tool call timeout as Duration
+    pub fn tool_timeout(&self) -> Option<Duration> {
+        self.tool_timeout_ms.map(Duration::from_millis)
+    }
 }
 
 /// Global MCP configuration
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct MCPConfig {
     /// List of MCP servers to connect to
+    #[serde(default)]
     pub servers: Vec<MCPServerConfig>,
 
     /// Global timeout for MCP tool calls in milliseconds
@@ -12,7 +12,7 @@ use rmcp::transport::StreamableHttpClientTransport;
 use rmcp::{RoleClient, ServiceExt};
 
 use super::config::MCPServerConfig;
-use super::server::MCPServer;
+use super::server::{MCPClientHandler, MCPServer, MCPService};
 
 /// Information about an MCP tool from a specific server
 #[derive(Debug, Clone)]
@@ -59,80 +59,73 @@ impl MCPServerManager {
     /// use std::sync::Arc;
     /// use std::time::{Duration, Instant};
     /// use tokio::sync::RwLock;
+    /// use rmcp::transport::streamable_http_client::StreamableHttpClientTransportConfig;
     /// use rmcp::transport::StreamableHttpClientTransport;
     /// use rmcp::ServiceExt;
+    /// use shadow_agent_sdk::mcp::MCPServerManager;
     ///
-    /// // Cached service with timestamp
-    /// let cached_service = Arc::new(RwLock::new(None));
-    /// let last_refresh = Arc::new(RwLock::new(Instant::now()));
-    /// let jwt_provider = Arc::new(MyJwtProvider::new());
+    /// # async fn fetch_token() -> anyhow::Result<String> { Ok(String::new()) }
+    /// # async fn example() -> anyhow::Result<()> {
+    /// # let manager = MCPServerManager::new();
+    /// // When the current connection was made (its token lasts an hour)
+    /// let connected_at: Arc<RwLock<Option<Instant>>> = Arc::new(RwLock::new(None));
     ///
-    /// let refresher = {
-    ///     let cached = cached_service.clone();
-    ///     let last_refresh = last_refresh.clone();
-    ///     let jwt = jwt_provider.clone();
+    /// let refresher = move || {
+    ///     let connected_at = connected_at.clone();
     ///
-    ///     move || {
-    ///         let cached = cached.clone();
-    ///         let last_refresh = last_refresh.clone();
-    ///         let jwt = jwt.clone();
-    ///
-    ///         async move {
-    ///             // Check if cached service is still valid
-    ///             {
-    ///                 let last = last_refresh.read().await;
-    ///                 if last.elapsed() < Duration::from_secs(50 * 60) {
-    ///                     // Token still fresh, no refresh needed
-    ///                     let cached_guard = cached.read().await;
-    ///                     if cached_guard.is_some() {
-    ///                         return Ok(None); // Keep using cached
-    ///                     }
-    ///                 }
+    ///     async move {
+    ///         // Token still fresh: keep the service the server already has
+    ///         if let Some(at) = *connected_at.read().await {
+    ///             if at.elapsed() < Duration::from_secs(50 * 60) {
+    ///                 return Ok(None);
     ///             }
+    ///         }
     ///
-    ///             // Create new service
-    ///             let token = jwt.get_fresh_token().await?;
-    ///             let transport = StreamableHttpClientTransport::from_uri("https://backend/mcp")
-    ///                 .with_header("Authorization", format!("Bearer {}", token));
-    ///             let service = ().serve(transport).await?;
-    ///
-    ///             // Cache it
-    ///             *cached.write().await = Some(service);
-    ///             *last_refresh.write().await = Instant::now();
+    ///         // Connect again with a new token
+    ///         let token = fetch_token().await?;
+    ///         let config = StreamableHttpClientTransportConfig::with_uri("https://backend/mcp").auth_header(token);
+    ///         let service = ().serve(StreamableHttpClientTransport::from_config(config)).await?;
+    ///         *connected_at.write().await = Some(Instant::now());
     ///
-    ///             // Return the cached service as Option
-    ///             Ok(cached.read().await.clone())
-    ///         }
+    ///         Ok(Some(service))
     ///     }
     /// };
     ///
     /// manager.add_server_with_refresher("my-server", refresher).await?;
+    /// # Ok(())
+    /// # }
     /// ```
-    pub async fn add_server_with_refresher<F, Fut>(
+    pub async fn add_server_with_refresher<F, Fut, S>(
         &self,
         id: impl Into<String>,
         refresher: F,
     ) -> Result<()>
     where
         F: Fn() -> Fut + Send + Sync + 'static,
-        Fut: std::future::Future<Output = Result<Option<RunningService<RoleClient, ()>>>> + Send + 'static,
+        Fut: std::future::Future<Output = Result<Option<S>>> + Send + 'static,
+        S: MCPService,
     {
-        let id = id.into();
+        self.add_mcp_server(MCPServer::new(id, refresher)).await
+    }
+
+    /// Add an MCP server built by the caller (e.g. with a tool timeout)
+    ///
+    /// ```ignore
+    /// let server = MCPServer::new("search", refresher).with_tool_timeout(Duration::from_secs(600));
+    /// manager.add_mcp_server(server).await?;
+    /// ```
+    pub async fn add_mcp_server(&self, server: MCPServer) -> Result<()> {
+        let id = server.id().to_string();
 
         // Check if server already exists
         if self.servers.read().await.contains_key(&id) {
             return Err(anyhow!("MCP server '{}' already exists", id));
         }
 
-        let server = Arc::new(MCPServer::new(id.clone(), refresher));
-
         // Add to map
-        self.servers.write().await.insert(id.clone(), server);
+        self.servers.write().await.insert(id.clone(), Arc::new(server));
 
-        tracing::debug!(
-            "[MCPServerManager] Added MCP server '{}' with refresher",
-            id
-        );
+        tracing::debug!("[MCPServerManager] Added MCP server '{}'", id);
 
         Ok(())
     }
@@ -160,7 +153,7 @@ impl MCPServerManager {
 
         // Create a refresher with simple caching
         // Cache service for 5 minutes to avoid unnecessary reconnections
-        let cached_service: Arc<RwLock<Option<RunningService<RoleClient, ()>>>> =
+        let cached_service: Arc<RwLock<Option<RunningService<RoleClient, MCPClientHandler>>>> =
             Arc::new(RwLock::new(None));
         let last_refresh = Arc::new(RwLock::new(Instant::now() - Duration::from_secs(999)));
 
@@ -189,7 +182,7 @@ impl MCPServerManager {
 
                     // Create new service
                     let transport = StreamableHttpClientTransport::from_uri(uri.as_str());
-                    let service = ().serve(transport).await?;
+                    let service = MCPClientHandler::new().serve(transport).await?;
 
                     // Cache it (just for tracking the timestamp)
                     // We don't actually return from cache since RunningService doesn't impl Clone
@@ -202,14 +195,11 @@ impl MCPServerManager {
             }
         };
 
-        let server = Arc::new(MCPServer::new(id.clone(), refresher));
-
-        // Add to map
-        self.servers.write().await.insert(id.clone(), server);
-
-        tracing::debug!("[MCPServerManager] Added MCP server '{}'", id);
-
-        Ok(())
+        let mut server = MCPServer::new(id, refresher);
+        if let Some(timeout) = config.tool_timeout() {
+            server = server.with_tool_timeout(timeout);
+        }
+        self.add_mcp_server(server).await
     }
 
     /// Get a server by ID
@@ -130,5 +130,8 @@ mod tool_adapter;
 pub use config::{MCPConfig, MCPServerConfig};
 pub use manager::{MCPServerManager, MCPToolInfo};
 pub use provider::MCPToolProvider;
-pub use server::{service_refresher, MCPServer, ServiceRefreshFuture, ServiceRefresher};
+pub use server::{
+    service_refresher, MCPClientHandler, MCPProgress, MCPServer, MCPService, ServiceRefreshFuture, ServiceRefresher,
+    DEFAULT_TOOL_TIMEOUT,
+};
 pub use tool_adapter::MCPToolAdapter;
@@ -1,20 +1,150 @@
 //! MCP Server wrapper
 //!
 //! Wraps rmcp service to manage connections to individual MCP servers
+//!
+//! # Timeouts and Progress
+//!
+//! A tool call runs for at most the server's tool timeout (two minutes
+//! unless set with `MCPServer::with_tool_timeout`). When it runs out, the
+//! server is sent an MCP cancellation notification for the request, so it
+//! can stop the work, and the call fails with a timeout error. A call
+//! interrupted by the user (`call_tool_cancellable`) is cancelled the same way.
+//!
+//! Servers may report progress on a call. The notifications only reach
+//! the framework when the service was created with `MCPClientHandler`
+//! (`MCPClientHandler::new().serve(transport)`); a service created with
+//! `().serve(transport)` works the same otherwise, without progress.
 
 use anyhow::{anyhow, Result};
-use rmcp::model::{CallToolRequestParams, CallToolResult, Tool};
-use rmcp::service::RunningService;
-use rmcp::RoleClient;
+use futures::{FutureExt, StreamExt};
+use rmcp::handler::client::progress::ProgressDispatcher;
+use rmcp::model::{
+    CallToolRequest, CallToolRequestParams, CallToolResult, ClientInfo, ClientRequest, Meta, NumberOrString,
+    ProgressNotificationParam, ProgressToken, ServerResult, Tool,
+};
+use rmcp::service::{NotificationContext, PeerRequestOptions, RunningService};
+use rmcp::{ClientHandler, Peer, RoleClient};
 use serde_json::{Map, Value};
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+
+/// Default time a tool call may take
+pub const DEFAULT_TOOL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// A running rmcp client service
+///
+/// Implemented for services created with `().serve(transport)` and with
+/// `MCPClientHandler::new().serve(transport)`; only the latter receives
+/// progress notifications.
+pub trait MCPService: Send + Sync + 'static {
+    /// The connection to the server
+    fn peer(&self) -> &Peer<RoleClient>;
+
+    /// Where the service's progress notifications go, if it receives them
+    fn progress(&self) -> Option<&ProgressDispatcher> {
+        None
+    }
+}
+
+impl MCPService for RunningService<RoleClient, ()> {
+    fn peer(&self) -> &Peer<RoleClient> {
+        RunningService::peer(self)
+    }
+}
+
+impl MCPService for RunningService<RoleClient, MCPClientHandler> {
+    fn peer(&self) -> &Peer<RoleClient> {
+        RunningService::peer(self)
+    }
+
+    fn progress(&self) -> Option<&ProgressDispatcher> {
+        Some(&self.service().progress)
+    }
+}
+
+/// Client handler that passes progress notifications on to tool calls
+///
+/// ```ignore
+/// let service = MCPClientHandler::new().serve(transport).await?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MCPClientHandler {
+    info: ClientInfo,
+    progress: ProgressDispatcher,
+}
+
+impl MCPClientHandler {
+    /// A handler with the default client info
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A handler that introduces itself with `info`
+    pub fn with_info(info: ClientInfo) -> Self {
+        Self {
+            info,
+            progress: ProgressDispatcher::new(),
+        }
+    }
+}
+
+impl ClientHandler for MCPClientHandler {
+    fn get_info(&self) -> ClientInfo {
+        self.info.clone()
+    }
+
+    async fn on_progress(&self, params: ProgressNotificationParam, _context: NotificationContext<RoleClient>) {
+        self.progress.handle_notification(params).await;
+    }
+}
+
+/// Progress reported by a server during a tool call
+#[derive(Debug, Clone, PartialEq)]
+pub struct MCPProgress {
+    /// Progress so far, in the server's own units
+    pub progress: f64,
+    /// Progress at completion, if known
+    pub total: Option<f64>,
+    /// What the server is doing
+    pub message: Option<String>,
+}
+
+impl MCPProgress {
+    /// Percentage done, when the server reports a total
+    pub fn percent(&self) -> Option<f64> {
+        self.total
+            .filter(|total| *total > 0.0)
+            .map(|total| (self.progress / total * 100.0).clamp(0.0, 100.0))
+    }
+
+    /// One-line description, such as "40% Indexing files"
+    pub fn describe(&self) -> String {
+        match (self.percent(), &self.message) {
+            (Some(percent), Some(message)) => format!("{:.0}% {}", percent, message),
+            (Some(percent), None) => format!("{:.0}%", percent),
+            (None, Some(message)) => message.clone(),
+            (None, None) => format!("Progress: {}", self.progress),
+        }
+    }
+}
+
+impl From<ProgressNotificationParam> for MCPProgress {
+    fn from(params: ProgressNotificationParam) -> Self {
+        Self {
+            progress: params.progress,
+            total: params.total,
+            message: params.message,
+        }
+    }
+}
 
 /// Type alias for service refresher callback future
-pub type ServiceRefreshFuture =
-    Pin<Box<dyn Future<Output = Result<Option<RunningService<RoleClient, ()>>>> + Send>>;
+pub type ServiceRefreshFuture = Pin<Box<dyn Future<Output = Result<Option<Box<dyn MCPService>>>> + Send>>;
 
 /// Trait for providing MCP service with refresh/reconnection logic
 ///
@@ -42,21 +172,24 @@ pub struct FnServiceRefresher<F> {
     func: F,
 }
 
-impl<F, Fut> ServiceRefresher for FnServiceRefresher<F>
+impl<F, Fut, S> ServiceRefresher for FnServiceRefresher<F>
 where
     F: Fn() -> Fut + Send + Sync,
-    Fut: Future<Output = Result<Option<RunningService<RoleClient, ()>>>> + Send + 'static,
+    Fut: Future<Output = Result<Option<S>>> + Send + 'static,
+    S: MCPService,
 {
     fn refresh(&self) -> ServiceRefreshFuture {
-        Box::pin((self.func)())
+        let refreshed = (self.func)();
+        Box::pin(async move { Ok(refreshed.await?.map(|service| Box::new(service) as Box<dyn MCPService>)) })
     }
 }
 
 /// Create a service refresher from an async closure
-pub fn service_refresher<F, Fut>(func: F) -> FnServiceRefresher<F>
+pub fn service_refresher<F, Fut, S>(func: F) -> FnServiceRefresher<F>
 where
     F: Fn() -> Fut + Send + Sync,
-    Fut: Future<Output = Result<Option<RunningService<RoleClient, ()>>>> + Send + 'static,
+    Fut: Future<Output = Result<Option<S>>> + Send + 'static,
+    S: MCPService,
 {
     FnServiceRefresher { func }
 }
@@ -67,16 +200,20 @@ pub struct MCPServer {
     id: String,
 
     /// The underlying rmcp service (None if not connected)
-    service: Arc<RwLock<Option<RunningService<RoleClient, ()>>>>,
+    service: Arc<RwLock<Option<Box<dyn MCPService>>>>,
 
     /// Service refresher callback (REQUIRED - handles both JWT refresh and reconnection)
     refresher: Arc<dyn ServiceRefresher>,
+
+    /// Longest a tool call may take
+    tool_timeout: Duration,
 }
 
 impl std::fmt::Debug for MCPServer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("MCPServer")
             .field("id", &self.id)
+            .field("tool_timeout", &self.tool_timeout)
             .finish()
     }
 }
@@ -95,58 +232,46 @@ impl MCPServer {
     /// use std::sync::Arc;
     /// use std::time::{Duration, Instant};
     /// use tokio::sync::RwLock;
+    /// use rmcp::transport::streamable_http_client::StreamableHttpClientTransportConfig;
     /// use rmcp::transport::StreamableHttpClientTransport;
     /// use rmcp::ServiceExt;
+    /// use shadow_agent_sdk::mcp::MCPServer;
     ///
-    /// // Cached service with timestamp
-    /// let cached_service = Arc::new(RwLock::new(None));
-    /// let last_refresh = Arc::new(RwLock::new(Instant::now()));
-    /// let jwt_provider = Arc::new(MyJwtProvider::new());
-    ///
-    /// let refresher = {
-    ///     let cached = cached_service.clone();
-    ///     let last_refresh = last_refresh.clone();
-    ///     let jwt = jwt_provider.clone();
+    /// # async fn fetch_token() -> anyhow::Result<String> { Ok(String::new()) }
+    /// # async fn example() -> anyhow::Result<()> {
+    /// // When the current connection was made (its token lasts an hour)
+    /// let connected_at: Arc<RwLock<Option<Instant>>> = Arc::new(RwLock::new(None));
     ///
-    ///     move || {
-    ///         let cached = cached.clone();
-    ///         let last_refresh = last_refresh.clone();
-    ///         let jwt = jwt.clone();
+    /// let refresher = move || {
+    ///     let connected_at = connected_at.clone();
     ///
-    ///         async move {
-    ///             // Check if cached service is still valid
-    ///             {
-    ///                 let last = last_refresh.read().await;
-    ///                 if last.elapsed() < Duration::from_secs(50 * 60) {
-    ///                     // Token still fresh, return cached service
-    ///                     let cached_guard = cached.read().await;
-    ///                     if let Some(service) = cached_guard.as_ref() {
-    ///                         return Ok(service.clone()); // Reuse cached
-    ///                     }
-    ///                 }
+    ///     async move {
+    ///         // Token still fresh: keep the service the server already has
+    ///         if let Some(at) = *connected_at.read().await {
+    ///             if at.elapsed() < Duration::from_secs(50 * 60) {
+    ///                 return Ok(None);
     ///             }
+    ///         }
     ///
-    ///             // Create new service
-    ///             let token = jwt.get_fresh_token().await?;
-    ///             let transport = StreamableHttpClientTransport::from_uri("https://backend/mcp")
-    ///                 .with_header("Authorization", format!("Bearer {}", token));
-    ///             let service = ().serve(transport).await?;
-    ///
-    ///             // Cache it
-    ///             *cached.write().await = Some(service.clone());
-    ///             *last_refresh.write().await = Instant::now();
+    ///         // Connect again with a new token
+    ///         let token = fetch_token().await?;
+    ///         let config = StreamableHttpClientTransportConfig::with_uri("https://backend/mcp").auth_header(token);
+    ///         let service = ().serve(StreamableHttpClientTransport::from_config(config)).await?;
+    ///         *connected_at.write().await = Some(Instant::now());
     ///
-    ///             Ok(service)
-    ///         }
+    ///         Ok(Some(service))
     ///     }
     /// };
     ///
     /// let server = MCPServer::new("my-server", refresher);
+    /// # Ok(())
+    /// # }
     /// ```
-    pub fn new<F, Fut>(id: impl Into<String>, refresher: F) -> Self
+    pub fn new<F, Fut, S>(id: impl Into<String>, refresher: F) -> Self
     where
         F: Fn() -> Fut + Send + Sync + 'static,
-        Fut: Future<Output = Result<Option<RunningService<RoleClient, ()>>>> + Send + 'static,
+        Fut: Future<Output = Result<Option<S>>> + Send + 'static,
+        S: MCPService,
     {
         let id = id.into();
         tracing::debug!("[MCPServer] Created MCP server '{}'", id);
@@ -155,14 +280,26 @@ impl MCPServer {
             id,
             service: Arc::new(RwLock::new(None)),
             refresher: Arc::new(service_refresher(refresher)),
+            tool_timeout: DEFAULT_TOOL_TIMEOUT,
         }
     }
 
+    /// Give tool calls this long before cancelling them
+    pub fn with_tool_timeout(mut self, timeout: Duration) -> Self {
+        self.tool_timeout = timeout;
+        self
+    }
+
     /// Get the server ID
     pub fn id(&self) -> &str {
         &self.id
     }
 
+    /// Longest a tool call may take, unless the call says otherwise
+    pub fn tool_timeout(&self) -> Duration {
+        self.tool_timeout
+    }
+
     /// Check if the server is connected
     pub async fn is_connected(&self) -> bool {
         self.service.read().await.is_some()
@@ -260,7 +397,7 @@ impl MCPServer {
                 timeout_duration.as_secs()
             );
 
-            let list_future = service.list_tools(Default::default());
+            let list_future = service.peer().list_tools(Default::default());
 
             match tokio::time::timeout(timeout_duration, list_future).await {
                 Ok(Ok(result)) => {
@@ -415,10 +552,41 @@ impl MCPServer {
     ///
     /// This method first checks server connectivity by calling list_tools()
     /// to ensure the server is alive before executing the actual tool call.
+    /// The call is cancelled after the server's tool timeout.
     pub async fn call_tool(
         &self,
         name: &str,
         arguments: Option<Map<String, Value>>,
+    ) -> Result<CallToolResult> {
+        self.call_tool_with_progress(name, arguments, self.tool_timeout, |_| {}).await
+    }
+
+    /// Call a tool, passing the server's progress notifications to `on_progress`
+    ///
+    /// If the call takes longer than `timeout`, the server is sent a
+    /// cancellation notification and the call fails.
+    pub async fn call_tool_with_progress(
+        &self,
+        name: &str,
+        arguments: Option<Map<String, Value>>,
+        timeout: Duration,
+        on_progress: impl FnMut(MCPProgress) + Send,
+    ) -> Result<CallToolResult> {
+        self.call_tool_cancellable(name, arguments, timeout, &CancellationToken::new(), on_progress)
+            .await
+    }
+
+    /// Call a tool until it finishes, times out or `cancel` fires
+    ///
+    /// On timeout or cancellation the server is sent a cancellation
+    /// notification and the call fails.
+    pub async fn call_tool_cancellable(
+        &self,
+        name: &str,
+        arguments: Option<Map<String, Value>>,
+        timeout: Duration,
+        cancel: &CancellationToken,
+        mut on_progress: impl FnMut(MCPProgress) + Send,
     ) -> Result<CallToolResult> {
         // Health check: call list_tools() to verify server is up
         // This will automatically retry and reconnect if the server crashed
@@ -453,10 +621,14 @@ impl MCPServer {
             }
         }
 
-        let service_guard = self.service.read().await;
-        let service = service_guard
-            .as_ref()
-            .ok_or_else(|| anyhow!("MCP server '{}' is not connected", self.id))?;
+        // Take what the call needs, so a long call doesn't hold the lock
+        let (peer, dispatcher) = {
+            let service_guard = self.service.read().await;
+            let service = service_guard
+                .as_ref()
+                .ok_or_else(|| anyhow!("MCP server '{}' is not connected", self.id))?;
+            (service.peer().clone(), service.progress().cloned())
+        };
 
         tracing::info!(
             "[MCPServer] Calling tool '{}' on server '{}'",
@@ -464,31 +636,88 @@ impl MCPServer {
             self.id
         );
         tracing::debug!("[MCPServer] Tool arguments: {:?}", arguments);
+        tracing::info!("[MCPServer] Executing '{}' with {:?} timeout...", name, timeout);
 
-        // Add timeout to tool call as well
-        let timeout_duration = std::time::Duration::from_secs(120); // 2 minutes for tool execution
-        tracing::info!(
-            "[MCPServer] Executing '{}' with {}s timeout...",
-            name,
-            timeout_duration.as_secs()
-        );
-
-        let call_future = service.call_tool(CallToolRequestParams {
-            meta: None,
-            name: name.to_string().into(),
-            arguments,
-            task: None,
+        // Subscribe before sending, so no early notification is missed
+        let token = next_progress_token();
+        let mut progress = match &dispatcher {
+            Some(dispatcher) => Some(dispatcher.subscribe(token.clone()).await),
+            None => None,
+        };
+        let mut meta = Meta::new();
+        meta.set_progress_token(token);
+
+        let request = ClientRequest::CallToolRequest(CallToolRequest {
+            method: Default::default(),
+            params: CallToolRequestParams {
+                meta: None,
+                name: name.to_string().into(),
+                arguments,
+                task: None,
+            },
+            extensions: Default::default(),
         });
+        let options = PeerRequestOptions { timeout: None, meta: Some(meta) };
+        let mut handle = peer.send_cancellable_request(request, options).await?;
+
+        let deadline = tokio::time::sleep(timeout);
+        tokio::pin!(deadline);
+        let response = loop {
+            tokio::select! {
+                response = &mut handle.rx => break response,
+                Some(update) = async {
+                    match progress.as_mut() {
+                        Some(progress) => progress.next().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    tracing::debug!("[MCPServer] Progress from '{}': {:?}", name, update);
+                    on_progress(update.into());
+                }
+                _ = &mut deadline => {
+                    tracing::error!(
+                        "[MCPServer] TIMEOUT executing tool '{}' on '{}' after {:?}, cancelling",
+                        name,
+                        self.id,
+                        timeout
+                    );
+                    if let Err(e) = handle.cancel(Some(format!("timed out after {:?}", timeout))).await {
+                        tracing::warn!("[MCPServer] Could not cancel '{}' on '{}': {}", name, self.id, e);
+                    }
+                    return Err(anyhow!(
+                        "Timeout calling tool '{}' on '{}' after {:?}",
+                        name,
+                        self.id,
+                        timeout
+                    ));
+                }
+                _ = cancel.cancelled() => {
+                    tracing::info!("[MCPServer] Tool '{}' on '{}' interrupted, cancelling", name, self.id);
+                    if let Err(e) = handle.cancel(Some("interrupted".to_string())).await {
+                        tracing::warn!("[MCPServer] Could not cancel '{}' on '{}': {}", name, self.id, e);
+                    }
+                    return Err(anyhow!("Call to '{}' on '{}' was interrupted", name, self.id));
+                }
+            }
+        };
+
+        // Progress that arrived together with the response
+        if let Some(progress) = progress.as_mut() {
+            while let Some(Some(update)) = progress.next().now_or_never() {
+                on_progress(update.into());
+            }
+        }
 
-        let result = match tokio::time::timeout(timeout_duration, call_future).await {
-            Ok(Ok(result)) => {
+        match response {
+            Ok(Ok(ServerResult::CallToolResult(result))) => {
                 tracing::info!(
                     "[MCPServer] Tool '{}' executed successfully on '{}'",
                     name,
                     self.id
                 );
-                result
+                Ok(result)
             }
+            Ok(Ok(other)) => Err(anyhow!("Unexpected response to '{}' from '{}': {:?}", name, self.id, other)),
             Ok(Err(e)) => {
                 tracing::error!(
                     "[MCPServer] Tool '{}' FAILED on '{}': {}",
@@ -496,25 +725,10 @@ impl MCPServer {
                     self.id,
                     e
                 );
-                return Err(e.into());
-            }
-            Err(_) => {
-                tracing::error!(
-                    "[MCPServer] TIMEOUT executing tool '{}' on '{}' after {}s",
-                    name,
-                    self.id,
-                    timeout_duration.as_secs()
-                );
-                return Err(anyhow!(
-                    "Timeout calling tool '{}' on '{}' after {}s",
-                    name,
-                    self.id,
-                    timeout_duration.as_secs()
-                ));
+                Err(e.into())
             }
-        };
-
-        Ok(result)
+            Err(_) => Err(anyhow!("MCP server '{}' closed the connection during '{}'", self.id, name)),
+        }
     }
 
     /// Health check - try to list tools to verify connection
@@ -524,6 +738,13 @@ impl MCPServer {
     }
 }
 
+/// A progress token no other call of this process uses
+fn next_progress_token() -> ProgressToken {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    ProgressToken(NumberOrString::String(format!("shadow-{}", n).into()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
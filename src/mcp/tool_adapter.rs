@@ -1,18 +1,28 @@
 //! MCP Tool Adapter
 //!
 //! Adapts MCP tools to implement the framework's Tool trait
+//!
+//! Progress the server reports while a call runs is sent as
+//! `OutputChunk::ToolProgress` ("40% Indexing files"). A call may set its
+//! own timeout with a `_timeout_ms` input field; the field is removed
+//! before the input is sent to the server.
 
 use anyhow::Result;
 use async_trait::async_trait;
 use serde_json::Value;
 use std::sync::Arc;
+use std::time::Duration;
 
+use crate::core::OutputChunk;
 use crate::llm::{ToolDefinition, ToolInputSchema};
 use crate::runtime::AgentInternals;
-use crate::tools::{Tool, ToolInfo, ToolResult};
+use crate::tools::{Tool, ToolInfo, ToolResult, ToolResultData};
 
 use super::server::MCPServer;
 
+/// Input field that sets the timeout of a single call, in milliseconds
+pub const TIMEOUT_FIELD: &str = "_timeout_ms";
+
 /// Adapter that wraps an MCP tool to implement the Tool trait
 pub struct MCPToolAdapter {
     /// ID of the server this tool belongs to
@@ -88,46 +98,44 @@ impl MCPToolAdapter {
     }
 
     /// Convert rmcp CallToolResult to framework ToolResult
-    fn convert_mcp_result(&self, rmcp_result: rmcp::model::CallToolResult) -> Result<ToolResult> {
+    fn convert_mcp_result(rmcp_result: rmcp::model::CallToolResult) -> Result<ToolResult> {
         use rmcp::model::RawContent;
 
         let is_error = rmcp_result.is_error.unwrap_or(false);
 
-        // Aggregate all content
-        let mut text_parts = Vec::new();
+        // One item per content part
+        let mut content = Vec::new();
 
-        for content in rmcp_result.content {
+        for part in rmcp_result.content {
             // Extract the raw content from the annotated wrapper
-            match &content.raw {
-                RawContent::Text(text_content) => {
-                    text_parts.push(text_content.text.clone());
-                }
+            let item = match &part.raw {
+                RawContent::Text(text_content) => ToolResultData::Text(text_content.text.clone()),
                 RawContent::Image(image_content) => {
-                    // Return image directly
                     use base64::Engine;
-                    let decoded = base64::engine::general_purpose::STANDARD
+                    let data = base64::engine::general_purpose::STANDARD
                         .decode(&image_content.data)
                         .map_err(|e| anyhow::anyhow!("Failed to decode base64 image: {}", e))?;
-                    return Ok(ToolResult::image(decoded, image_content.mime_type.clone()));
+                    ToolResultData::Image {
+                        data,
+                        media_type: image_content.mime_type.clone(),
+                    }
                 }
                 RawContent::Resource(resource_content) => {
                     // Serialize resource as JSON
-                    text_parts.push(serde_json::to_string_pretty(&resource_content.resource)?);
+                    ToolResultData::Text(serde_json::to_string_pretty(&resource_content.resource)?)
                 }
                 _ => {
                     // Handle other content types (Audio, ResourceLink) as JSON
-                    text_parts.push(serde_json::to_string_pretty(&content)?);
+                    ToolResultData::Text(serde_json::to_string_pretty(&part)?)
                 }
-            }
+            };
+            content.push(item);
         }
 
-        let output = text_parts.join("\n\n");
-
-        if is_error {
-            Ok(ToolResult::error(output))
-        } else {
-            Ok(ToolResult::success(output))
+        if content.is_empty() {
+            content.push(ToolResultData::Text(String::new()));
         }
+        Ok(ToolResult::new(content, is_error))
     }
 }
 
@@ -159,10 +167,11 @@ impl Tool for MCPToolAdapter {
                 self.tool_name, self.server_id
             ),
             details: Some(format!("Input: {}", input)),
+            read_only: false,
         }
     }
 
-    async fn execute(&self, input: &Value, _internals: &mut AgentInternals) -> Result<ToolResult> {
+    async fn execute(&self, input: &Value, internals: &mut AgentInternals) -> Result<ToolResult> {
         tracing::info!(
             "[MCPToolAdapter] Executing '{}' on server '{}'",
             self.tool_name,
@@ -170,14 +179,31 @@ impl Tool for MCPToolAdapter {
         );
         tracing::debug!("[MCPToolAdapter] Input: {}", input);
 
-        // Convert JSON Value to Map<String, Value> for rmcp
-        let arguments = input.as_object().cloned();
+        // Convert JSON Value to Map<String, Value> for rmcp, without our own field
+        let mut arguments = input.as_object().cloned();
+        let timeout = arguments
+            .as_mut()
+            .and_then(|args| args.remove(TIMEOUT_FIELD))
+            .and_then(|ms| ms.as_u64())
+            .map(Duration::from_millis)
+            .unwrap_or_else(|| self.server.tool_timeout());
 
         // Call the MCP server with the ORIGINAL tool name (not namespaced)
-        let rmcp_result = self.server.call_tool(&self.tool_name, arguments).await?;
+        let tool_use_id = internals.context.current_tool_use_id.clone().unwrap_or_default();
+        // Cancelled on interrupt; the server is told to stop
+        let cancel = internals.context.cancellation.clone();
+        let rmcp_result = self
+            .server
+            .call_tool_cancellable(&self.tool_name, arguments, timeout, &cancel, |progress| {
+                internals.send(OutputChunk::ToolProgress {
+                    id: tool_use_id.clone(),
+                    output: progress.describe(),
+                });
+            })
+            .await?;
 
         // Convert rmcp result to framework ToolResult
-        let result = self.convert_mcp_result(rmcp_result)?;
+        let result = Self::convert_mcp_result(rmcp_result)?;
 
         tracing::debug!(
             "[MCPToolAdapter] Tool '{}' completed. Is error: {}",
@@ -198,7 +224,191 @@ impl Tool for MCPToolAdapter {
 mod tests {
     use super::*;
     use serde_json::json;
-    use std::sync::Arc;
+    use std::sync::{Arc, Mutex};
+
+    use rmcp::service::{RoleClient, RxJsonRpcMessage, TxJsonRpcMessage};
+    use rmcp::transport::Transport;
+    use rmcp::ServiceExt;
+    use tokio::sync::mpsc;
+
+    use crate::mcp::MCPClientHandler;
+    use crate::runtime::OutputReceiver;
+
+    /// In-process MCP server with two tools: "fast" reports progress and
+    /// answers, "slow" reports progress and then never answers
+    struct FixtureServer {
+        replies: mpsc::UnboundedSender<RxJsonRpcMessage<RoleClient>>,
+        inbox: mpsc::UnboundedReceiver<RxJsonRpcMessage<RoleClient>>,
+        /// Every message the client sent
+        received: Arc<Mutex<Vec<Value>>>,
+    }
+
+    impl FixtureServer {
+        fn new() -> (Self, Arc<Mutex<Vec<Value>>>) {
+            let (replies, inbox) = mpsc::unbounded_channel();
+            let received = Arc::new(Mutex::new(Vec::new()));
+            (Self { replies, inbox, received: received.clone() }, received)
+        }
+
+        fn replies_to(message: &Value) -> Vec<Value> {
+            let id = &message["id"];
+            let result = |result: Value| json!({"jsonrpc": "2.0", "id": id, "result": result});
+            match message["method"].as_str() {
+                Some("initialize") => vec![result(json!({
+                    "protocolVersion": message["params"]["protocolVersion"],
+                    "capabilities": {"tools": {}},
+                    "serverInfo": {"name": "fixture", "version": "1.0"}
+                }))],
+                Some("tools/list") => vec![result(json!({"tools": [
+                    {"name": "fast", "inputSchema": {"type": "object"}},
+                    {"name": "slow", "inputSchema": {"type": "object"}}
+                ]}))],
+                Some("tools/call") => {
+                    let progress = json!({
+                        "jsonrpc": "2.0",
+                        "method": "notifications/progress",
+                        "params": {
+                            "progressToken": message["params"]["_meta"]["progressToken"],
+                            "progress": 1,
+                            "total": 2,
+                            "message": "Halfway"
+                        }
+                    });
+                    match message["params"]["name"].as_str() {
+                        Some("fast") => vec![progress, result(json!({"content": [{"type": "text", "text": "done"}]}))],
+                        _ => vec![progress],
+                    }
+                }
+                _ => Vec::new(),
+            }
+        }
+    }
+
+    impl Transport<RoleClient> for FixtureServer {
+        type Error = std::io::Error;
+
+        fn send(
+            &mut self,
+            item: TxJsonRpcMessage<RoleClient>,
+        ) -> impl std::future::Future<Output = Result<(), Self::Error>> + Send + 'static {
+            let message = serde_json::to_value(&item).unwrap();
+            let replies = Self::replies_to(&message);
+            self.received.lock().unwrap().push(message);
+            let sender = self.replies.clone();
+            async move {
+                // Like a real server, answer a little after reporting progress
+                for reply in replies {
+                    let _ = sender.send(serde_json::from_value(reply).unwrap());
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                Ok(())
+            }
+        }
+
+        async fn receive(&mut self) -> Option<RxJsonRpcMessage<RoleClient>> {
+            self.inbox.recv().await
+        }
+
+        async fn close(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    async fn fixture_server() -> (Arc<MCPServer>, Arc<Mutex<Vec<Value>>>) {
+        let (transport, received) = FixtureServer::new();
+        let service = Arc::new(Mutex::new(Some(MCPClientHandler::new().serve(transport).await.unwrap())));
+        let server = MCPServer::new("fixture", move || {
+            let service = service.clone();
+            async move { Ok(service.lock().unwrap().take()) }
+        });
+        (Arc::new(server), received)
+    }
+
+    async fn adapter(server: &Arc<MCPServer>, name: &str) -> MCPToolAdapter {
+        let tool = server.list_tools().await.unwrap().into_iter().find(|t| t.name == name).unwrap();
+        MCPToolAdapter::new("fixture".into(), server.clone(), tool)
+    }
+
+    fn internals(dir: &std::path::Path) -> (AgentInternals, OutputReceiver) {
+        let mut internals = AgentInternals::for_test(dir);
+        internals.context.current_tool_use_id = Some("toolu_1".into());
+        let output = internals.subscribe_output();
+        (internals, output)
+    }
+
+    fn progress_chunks(output: &mut OutputReceiver) -> Vec<(String, String)> {
+        let mut progress = Vec::new();
+        while let Ok(chunk) = output.try_recv() {
+            if let OutputChunk::ToolProgress { id, output } = chunk {
+                progress.push((id, output));
+            }
+        }
+        progress
+    }
+
+    fn sent_with_method<'a>(received: &'a [Value], method: &str) -> Vec<&'a Value> {
+        received.iter().filter(|m| m["method"] == method).collect()
+    }
+
+    #[tokio::test]
+    async fn test_progress_is_forwarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, _) = fixture_server().await;
+        let fast = adapter(&server, "fast").await;
+        let (mut internals, mut output) = internals(dir.path());
+
+        let result = fast.execute(&json!({}), &mut internals).await.unwrap();
+        assert!(!result.is_error);
+        assert_eq!(progress_chunks(&mut output), vec![("toolu_1".to_string(), "50% Halfway".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_stalled_call_times_out_and_is_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, received) = fixture_server().await;
+        let slow = adapter(&server, "slow").await;
+        let (mut internals, mut output) = internals(dir.path());
+
+        let started = std::time::Instant::now();
+        let input = json!({"query": "x", TIMEOUT_FIELD: 200});
+        let e = slow.execute(&input, &mut internals).await.unwrap_err();
+        assert!(e.to_string().contains("Timeout calling tool 'slow'"), "{}", e);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(progress_chunks(&mut output).len(), 1);
+
+        // The reserved field is not forwarded, and the request is cancelled
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let received = received.lock().unwrap();
+        let call = sent_with_method(&received, "tools/call")[0];
+        assert_eq!(call["params"]["arguments"], json!({"query": "x"}));
+        let cancelled = sent_with_method(&received, "notifications/cancelled");
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(cancelled[0]["params"]["requestId"], call["id"]);
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_call_is_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, received) = fixture_server().await;
+        let slow = adapter(&server, "slow").await;
+        let (mut internals, _output) = internals(dir.path());
+
+        let cancel = internals.context.cancellation.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            cancel.cancel();
+        });
+        let e = slow.execute(&json!({}), &mut internals).await.unwrap_err();
+        assert!(e.to_string().contains("was interrupted"), "{}", e);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let received = received.lock().unwrap();
+        let call = sent_with_method(&received, "tools/call")[0];
+        let cancelled = sent_with_method(&received, "notifications/cancelled");
+        assert_eq!(cancelled.len(), 1);
+        assert_eq!(cancelled[0]["params"]["requestId"], call["id"]);
+        assert_eq!(cancelled[0]["params"]["reason"], "interrupted");
+    }
 
     #[test]
     fn test_tool_definition_conversion() {
@@ -237,4 +447,27 @@ mod tests {
             _ => panic!("Expected CustomTool"),
         }
     }
+
+    #[test]
+    fn test_multi_part_result_maps_one_to_one() {
+        let result = serde_json::from_value(json!({
+            "content": [
+                {"type": "text", "text": "Two screenshots"},
+                {"type": "image", "data": "AQI=", "mimeType": "image/png"},
+                {"type": "image", "data": "Aw==", "mimeType": "image/jpeg"}
+            ],
+            "isError": false
+        }))
+        .unwrap();
+        let result = MCPToolAdapter::convert_mcp_result(result).unwrap();
+        assert_eq!(
+            result.content,
+            vec![
+                ToolResultData::Text("Two screenshots".into()),
+                ToolResultData::Image { data: vec![1, 2], media_type: "image/png".into() },
+                ToolResultData::Image { data: vec![3], media_type: "image/jpeg".into() },
+            ]
+        );
+        assert!(!result.is_error);
+    }
 }
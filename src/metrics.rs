@@ -0,0 +1,272 @@
+//! Metrics for agent activity
+//!
+//! With the `metrics` feature, `StandardAgent`, `ToolExecutor` and
+//! `AgentRuntime` record counters, gauges and histograms through the
+//! [`metrics`](https://docs.rs/metrics) facade. They go to whatever recorder
+//! the application installs; `install_prometheus` serves them for
+//! Prometheus to scrape. Without the feature the helpers here do nothing.
+//!
+//! | Metric | Type | Labels |
+//! |--------|------|--------|
+//! | `shadow_agent_turns_total` | counter | `agent_type`, `provider`, `model` |
+//! | `shadow_agent_turn_duration_seconds` | histogram | `agent_type`, `provider`, `model` |
+//! | `shadow_agent_llm_requests_total` | counter | `agent_type`, `provider`, `model` |
+//! | `shadow_agent_llm_latency_seconds` | histogram | `agent_type`, `provider`, `model` |
+//! | `shadow_agent_tokens_total` | counter | `agent_type`, `provider`, `model`, `kind` (`input`, `output`, `cache_read`, `cache_write`) |
+//! | `shadow_agent_tool_calls_total` | counter | `agent_type`, `tool` |
+//! | `shadow_agent_tool_errors_total` | counter | `agent_type`, `tool` |
+//! | `shadow_agent_tool_duration_seconds` | histogram | `agent_type`, `tool` |
+//! | `shadow_agent_active_agents` | gauge | `agent_type` |
+//! | `shadow_agent_spawned_total` | counter | `agent_type` |
+//! | `shadow_agent_stopped_total` | counter | `agent_type`, `outcome` (`ok`, `error`) |
+//!
+//! ```ignore
+//! // Serve http://0.0.0.0:9100/metrics
+//! shadow_agent_sdk::metrics::install_prometheus("0.0.0.0:9100".parse()?)?;
+//! ```
+
+use std::time::Duration;
+
+use crate::llm::Usage;
+
+/// Turns processed
+pub const TURNS_TOTAL: &str = "shadow_agent_turns_total";
+/// Time from user message to the end of the turn
+pub const TURN_DURATION_SECONDS: &str = "shadow_agent_turn_duration_seconds";
+/// LLM calls made
+pub const LLM_REQUESTS_TOTAL: &str = "shadow_agent_llm_requests_total";
+/// Time an LLM call took, including streaming the response
+pub const LLM_LATENCY_SECONDS: &str = "shadow_agent_llm_latency_seconds";
+/// Tokens used, by `kind`
+pub const TOKENS_TOTAL: &str = "shadow_agent_tokens_total";
+/// Tool calls finished
+pub const TOOL_CALLS_TOTAL: &str = "shadow_agent_tool_calls_total";
+/// Tool calls that returned an error result
+pub const TOOL_ERRORS_TOTAL: &str = "shadow_agent_tool_errors_total";
+/// Time a tool call took, including the permission check
+pub const TOOL_DURATION_SECONDS: &str = "shadow_agent_tool_duration_seconds";
+/// Agents currently running
+pub const ACTIVE_AGENTS: &str = "shadow_agent_active_agents";
+/// Agents spawned
+pub const SPAWNED_TOTAL: &str = "shadow_agent_spawned_total";
+/// Agents whose task ended, by `outcome`
+pub const STOPPED_TOTAL: &str = "shadow_agent_stopped_total";
+
+/// Serve the metrics at `http://{addr}/metrics` for Prometheus
+///
+/// Installs the exporter as the global recorder, so call it once at startup,
+/// from within a tokio runtime.
+#[cfg(feature = "metrics")]
+pub fn install_prometheus(addr: std::net::SocketAddr) -> crate::core::FrameworkResult<()> {
+    metrics_exporter_prometheus::PrometheusBuilder::new()
+        .with_http_listener(addr)
+        .install()
+        .map_err(|e| crate::core::FrameworkError::other(format!("Failed to install Prometheus exporter: {}", e)))?;
+    tracing::info!("[Metrics] Serving Prometheus metrics on http://{}/metrics", addr);
+    Ok(())
+}
+
+/// Record a finished turn
+pub fn record_turn(agent_type: &str, provider: &str, model: &str, elapsed: Duration) {
+    #[cfg(feature = "metrics")]
+    {
+        let labels = llm_labels(agent_type, provider, model);
+        ::metrics::counter!(TURNS_TOTAL, &labels).increment(1);
+        ::metrics::histogram!(TURN_DURATION_SECONDS, &labels).record(elapsed.as_secs_f64());
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (agent_type, provider, model, elapsed);
+}
+
+/// Record a finished LLM call
+pub fn record_llm_call(agent_type: &str, provider: &str, model: &str, elapsed: Duration) {
+    #[cfg(feature = "metrics")]
+    {
+        let labels = llm_labels(agent_type, provider, model);
+        ::metrics::counter!(LLM_REQUESTS_TOTAL, &labels).increment(1);
+        ::metrics::histogram!(LLM_LATENCY_SECONDS, &labels).record(elapsed.as_secs_f64());
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (agent_type, provider, model, elapsed);
+}
+
+/// Record the tokens of one LLM response
+pub fn record_llm_usage(agent_type: &str, provider: &str, model: &str, usage: &Usage) {
+    #[cfg(feature = "metrics")]
+    {
+        let kinds = [
+            ("input", Some(usage.input_tokens)),
+            ("output", Some(usage.output_tokens)),
+            ("cache_read", usage.cache_read_input_tokens),
+            ("cache_write", usage.cache_creation_input_tokens),
+        ];
+        for (kind, tokens) in kinds {
+            let Some(tokens) = tokens.filter(|t| *t > 0) else {
+                continue;
+            };
+            let mut labels = llm_labels(agent_type, provider, model);
+            labels.push(("kind", kind.to_string()));
+            ::metrics::counter!(TOKENS_TOTAL, &labels).increment(tokens as u64);
+        }
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (agent_type, provider, model, usage);
+}
+
+/// Record a finished tool call
+pub fn record_tool_call(agent_type: &str, tool: &str, elapsed: Duration, is_error: bool) {
+    #[cfg(feature = "metrics")]
+    {
+        let labels = [("agent_type", agent_type.to_string()), ("tool", tool.to_string())];
+        ::metrics::counter!(TOOL_CALLS_TOTAL, &labels).increment(1);
+        if is_error {
+            ::metrics::counter!(TOOL_ERRORS_TOTAL, &labels).increment(1);
+        }
+        ::metrics::histogram!(TOOL_DURATION_SECONDS, &labels).record(elapsed.as_secs_f64());
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (agent_type, tool, elapsed, is_error);
+}
+
+/// Record an agent starting to run
+pub fn record_agent_spawned(agent_type: &str) {
+    #[cfg(feature = "metrics")]
+    {
+        let labels = [("agent_type", agent_type.to_string())];
+        ::metrics::counter!(SPAWNED_TOTAL, &labels).increment(1);
+        ::metrics::gauge!(ACTIVE_AGENTS, &labels).increment(1.0);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = agent_type;
+}
+
+/// Record an agent's task ending
+pub fn record_agent_stopped(agent_type: &str, failed: bool) {
+    #[cfg(feature = "metrics")]
+    {
+        let labels = [("agent_type", agent_type.to_string())];
+        ::metrics::gauge!(ACTIVE_AGENTS, &labels).decrement(1.0);
+        let outcome = if failed { "error" } else { "ok" };
+        let labels = [("agent_type", agent_type.to_string()), ("outcome", outcome.to_string())];
+        ::metrics::counter!(STOPPED_TOTAL, &labels).increment(1);
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (agent_type, failed);
+}
+
+#[cfg(feature = "metrics")]
+fn llm_labels(agent_type: &str, provider: &str, model: &str) -> Vec<(&'static str, String)> {
+    vec![
+        ("agent_type", agent_type.to_string()),
+        ("provider", provider.to_string()),
+        ("model", model.to_string()),
+    ]
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use metrics_util::CompositeKey;
+    use serde_json::json;
+
+    use crate::agent::AgentConfig;
+    use crate::cli::print_mode::run_mock_agent_on;
+    use crate::llm::MockLlmProvider;
+    use crate::runtime::{AgentRuntime, SpawnOptions};
+    use crate::session::{AgentSession, SessionStorage};
+    use crate::tools::{ToolRegistry, TodoWriteTool};
+
+    type Snapshot = Vec<(CompositeKey, DebugValue)>;
+
+    fn find<'a>(snapshot: &'a Snapshot, name: &str, labels: &[(&str, &str)]) -> &'a DebugValue {
+        snapshot
+            .iter()
+            .find(|(key, _)| {
+                key.key().name() == name
+                    && labels
+                        .iter()
+                        .all(|(k, v)| key.key().labels().any(|l| l.key() == *k && l.value() == *v))
+            })
+            .map(|(_, value)| value)
+            .unwrap_or_else(|| panic!("missing {} {:?}", name, labels))
+    }
+
+    fn counter(snapshot: &Snapshot, name: &str, labels: &[(&str, &str)]) -> u64 {
+        match find(snapshot, name, labels) {
+            DebugValue::Counter(count) => *count,
+            other => panic!("{} is not a counter: {:?}", name, other),
+        }
+    }
+
+    #[test]
+    fn test_mock_turn_records_metrics() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        ::metrics::with_local_recorder(&recorder, || {
+            tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap().block_on(async {
+                let mut tool_call = MockLlmProvider::tool_use_response(
+                    "call_1",
+                    "TodoWrite",
+                    json!({"todos": [{"content": "x", "status": "pending", "activeForm": "Doing x"}]}),
+                );
+                tool_call.usage.input_tokens = 12;
+                tool_call.usage.output_tokens = 3;
+                tool_call.usage.cache_read_input_tokens = Some(8);
+                let llm = MockLlmProvider::new()
+                    .with_response(tool_call)
+                    .with_response(MockLlmProvider::tool_use_response("call_2", "Missing", json!({})))
+                    .with_text("done");
+
+                let mut registry = ToolRegistry::new();
+                registry.register(TodoWriteTool::new());
+                let config = AgentConfig::new("test").with_tools(Arc::new(registry)).with_auto_name(false);
+
+                let dir = tempfile::tempdir().unwrap();
+                let session = AgentSession::new_with_storage(
+                    "metrics-session",
+                    "metrics-test",
+                    "Test",
+                    "",
+                    SessionStorage::with_dir(dir.path()),
+                )
+                .unwrap();
+                let runtime = AgentRuntime::new();
+                run_mock_agent_on(&runtime, SpawnOptions::new(), config, Arc::new(llm), session, "go").await;
+                runtime.shutdown_all().await;
+                runtime.wait_for("metrics-session").await.unwrap();
+            })
+        });
+
+        let snapshot: Snapshot = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| (key, value))
+            .collect();
+        let llm = [("agent_type", "metrics-test"), ("provider", "mock"), ("model", "mock-model")];
+
+        assert_eq!(counter(&snapshot, TURNS_TOTAL, &llm), 1);
+        assert_eq!(counter(&snapshot, LLM_REQUESTS_TOTAL, &llm), 3);
+        assert!(matches!(find(&snapshot, LLM_LATENCY_SECONDS, &llm), DebugValue::Histogram(v) if v.len() == 3));
+        assert_eq!(counter(&snapshot, TOKENS_TOTAL, &[("kind", "input"), ("agent_type", "metrics-test")]), 12);
+        assert_eq!(counter(&snapshot, TOKENS_TOTAL, &[("kind", "cache_read"), ("agent_type", "metrics-test")]), 8);
+
+        let todo = [("agent_type", "metrics-test"), ("tool", "TodoWrite")];
+        assert_eq!(counter(&snapshot, TOOL_CALLS_TOTAL, &todo), 1);
+        assert!(matches!(find(&snapshot, TOOL_DURATION_SECONDS, &todo), DebugValue::Histogram(v) if v.len() == 1));
+        let missing = [("agent_type", "metrics-test"), ("tool", "Missing")];
+        assert_eq!(counter(&snapshot, TOOL_ERRORS_TOTAL, &missing), 1);
+
+        let agent = [("agent_type", "metrics-test")];
+        assert_eq!(counter(&snapshot, SPAWNED_TOTAL, &agent), 1);
+        assert_eq!(counter(&snapshot, STOPPED_TOTAL, &[("agent_type", "metrics-test"), ("outcome", "ok")]), 1);
+        match find(&snapshot, ACTIVE_AGENTS, &agent) {
+            DebugValue::Gauge(value) => assert_eq!(value.into_inner(), 0.0),
+            other => panic!("not a gauge: {:?}", other),
+        }
+    }
+}
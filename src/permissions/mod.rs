@@ -9,6 +9,20 @@
 //!
 //! - `AllowTool`: Allow an entire tool (e.g., Read is always allowed)
 //! - `AllowPrefix`: Allow commands starting with a prefix (e.g., `cd` for Bash)
+//! - `DenyTool` / `DenyPrefix`: Deny a tool or prefix; deny rules win
+//!
+//! Bash prefixes are checked per command: `analyze_bash` splits a command
+//! line on `&&`, `||`, `;` and `|`, and every command must be allowed, so an
+//! `AllowPrefix("cd")` rule doesn't allow `cd /tmp && rm -rf /`. Commands
+//! with `$(...)` or backticks always ask.
+//!
+//! ## Revoking Rules
+//!
+//! Every rule gets a `RuleId` when added. `GlobalPermissions::rules()` and
+//! `PermissionRules::rules(scope)` list rules with their ids, and
+//! `remove_rule(id)` revokes one; checks read the current rules, so a revoked
+//! "always allow" prompts again at the next check. `subscribe()` returns a
+//! watch channel that changes whenever any rule is added or removed.
 //!
 //! ## Example
 //!
@@ -31,9 +45,13 @@
 //! }
 //! ```
 
+mod bash;
+pub mod bash_safety;
 mod manager;
 
+pub use bash::{analyze_bash, CommandSpec};
 pub use manager::{
     CheckResult, GlobalPermissions, PermissionDecision, PermissionManager, PermissionRequest,
-    PermissionRule, PermissionScope, RuleType,
+    PermissionRule, PermissionRules, PermissionScope, PermissionTimeout, ResponseTarget, RuleId,
+    RuleType, TimeoutDecision,
 };
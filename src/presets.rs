@@ -0,0 +1,425 @@
+//! Ready-made agent setups
+//!
+//! A preset builds what a typical agent needs - an `AgentConfig`, its
+//! `ToolRegistry`, a `HookRegistry` and local permission rules - so an
+//! agent takes a few lines instead of a page of setup:
+//!
+//! ```ignore
+//! let preset = presets::coding_agent(llm, "/work/project").build()?;
+//! let (agent, options) = preset.into_agent();
+//! let handle = runtime.spawn_with(session, agent, options).await;
+//! ```
+//!
+//! | Preset | Tools | Allowed without asking |
+//! |--------|-------|------------------------|
+//! | `coding_agent` | Read, Write, Edit, Glob, Grep, LS, Bash, TodoWrite, AskUserQuestion | Read, Glob, Grep, LS |
+//! | `research_agent` | Read, WebFetch, web search (server-side) | Read, WebFetch |
+//! | `reviewer_agent` | Read, Glob, Grep, LS, Bash | Read, Glob, Grep, LS, `git diff/log/show/status` |
+//!
+//! File tools are confined to the working directory with a `PathPolicy`,
+//! Bash runs in it, and `bash_safety_hook(Severity::High)` refuses Bash
+//! commands the safety check rates high or critical, and asks before other
+//! risky ones. The coding agent also reminds the model of TodoWrite until it has
+//! a todo list. The builder swaps pieces out:
+//!
+//! ```ignore
+//! let preset = presets::coding_agent(llm, ".")
+//!     .without_bash()
+//!     .with_extra_tool(MyDeployTool::new())
+//!     .with_rule(PermissionRule::allow_tool("MyDeploy"))
+//!     .build()?;
+//! ```
+
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+
+use crate::agent::{AgentConfig, AgentFile, StandardAgent};
+use crate::helpers::{inject_system_reminder, TodoListManager};
+use crate::hooks::{HookEvent, HookRegistry};
+use crate::llm::{LlmProvider, WebSearchTool};
+use crate::permissions::bash_safety::{bash_safety_hook, Severity};
+use crate::permissions::PermissionRule;
+use crate::runtime::SpawnOptions;
+use crate::tools::{
+    register_file_tools, AskUserQuestionTool, BashTool, ConflictPolicy, GlobTool, GrepTool, LsTool, PathPolicy,
+    ReadTool, TodoWriteTool, Tool, ToolRegistry, WebFetchTool,
+};
+
+const CODING_PROMPT: &str = r#"You are a coding assistant working in a software project.
+
+Read the relevant code before changing it, keep changes focused on what was asked, and match the style of the surrounding code. Use Bash to build and run tests. Use TodoWrite to track multi-step tasks and show progress.
+
+Be concise in your responses."#;
+
+const RESEARCH_PROMPT: &str = r#"You are a research assistant.
+
+Search the web and read pages and local files to answer questions. Cite the sources you used, say when sources disagree or when you couldn't find an answer, and don't present guesses as facts. You can't change files.
+
+Be concise in your responses."#;
+
+const REVIEWER_PROMPT: &str = r#"You are a code reviewer.
+
+Read the changes (git diff, git log, git show) and the code around them, then report bugs, risky changes, missing tests and unclear code, most important first, with file and line. You can't change files; describe fixes instead of making them.
+
+Be concise in your responses."#;
+
+/// Reminder sent while the todo list is empty (coding agent)
+const TODO_REMINDER: &str = "The TodoWrite tool hasn't been used yet. If you're working on tasks that would benefit from tracking progress, consider using the TodoWrite tool to track progress. Only use it if it's relevant to the current work.";
+
+/// Bash commands the reviewer may run without asking
+const REVIEWER_GIT_PREFIXES: [&str; 4] = ["git diff", "git log", "git show", "git status"];
+
+/// Which preset a builder makes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Coding,
+    Research,
+    Reviewer,
+}
+
+/// Registers one extra tool
+type ToolRegistration = Box<dyn FnOnce(&mut ToolRegistry) + Send>;
+
+/// A coding agent: file tools, Bash, TodoWrite and AskUserQuestion
+pub fn coding_agent(llm: Arc<dyn LlmProvider>, workdir: impl AsRef<Path>) -> PresetBuilder {
+    PresetBuilder::new(Kind::Coding, llm, workdir)
+}
+
+/// A research agent: web search, WebFetch and Read, nothing that changes files
+pub fn research_agent(llm: Arc<dyn LlmProvider>, workdir: impl AsRef<Path>) -> PresetBuilder {
+    PresetBuilder::new(Kind::Research, llm, workdir)
+}
+
+/// A code reviewer: read-only file tools, and Bash for git
+pub fn reviewer_agent(llm: Arc<dyn LlmProvider>, workdir: impl AsRef<Path>) -> PresetBuilder {
+    PresetBuilder::new(Kind::Reviewer, llm, workdir)
+}
+
+/// Builder for a preset; see the module docs for what each one includes
+pub struct PresetBuilder {
+    kind: Kind,
+    llm: Arc<dyn LlmProvider>,
+    workdir: String,
+    system_prompt: Option<String>,
+    bash: bool,
+    extra_tools: Vec<ToolRegistration>,
+    removed_tools: Vec<String>,
+    rules: Vec<PermissionRule>,
+    hooks: HookRegistry,
+}
+
+impl PresetBuilder {
+    fn new(kind: Kind, llm: Arc<dyn LlmProvider>, workdir: impl AsRef<Path>) -> Self {
+        Self {
+            kind,
+            llm,
+            workdir: workdir.as_ref().to_string_lossy().into_owned(),
+            system_prompt: None,
+            bash: kind != Kind::Research,
+            extra_tools: Vec::new(),
+            removed_tools: Vec::new(),
+            rules: Vec::new(),
+            hooks: HookRegistry::new(),
+        }
+    }
+
+    /// Replace the preset's system prompt
+    pub fn with_system_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.system_prompt = Some(prompt.into());
+        self
+    }
+
+    /// Register another tool (it asks for permission unless a rule allows it)
+    ///
+    /// A tool with the name of one of the preset's tools replaces it.
+    pub fn with_extra_tool<T: Tool + 'static>(mut self, tool: T) -> Self {
+        self.extra_tools.push(Box::new(move |registry| registry.register(tool)));
+        self
+    }
+
+    /// Leave out one of the preset's tools
+    pub fn without_tool(mut self, name: impl Into<String>) -> Self {
+        self.removed_tools.push(name.into());
+        self
+    }
+
+    /// Leave out Bash (and its safety hook)
+    pub fn without_bash(mut self) -> Self {
+        self.bash = false;
+        self
+    }
+
+    /// Add a local permission rule to the preset's defaults
+    pub fn with_rule(mut self, rule: PermissionRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Start from these hooks; the preset adds its own
+    pub fn with_hooks(mut self, hooks: HookRegistry) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Build the tools, hooks, rules and config
+    pub fn build(mut self) -> Result<AgentPreset> {
+        let tools = Arc::new(self.build_tools()?);
+        let has = |name: &str| tools.get(name).is_some();
+
+        let mut permission_rules: Vec<PermissionRule> = self
+            .default_allowed()
+            .into_iter()
+            .filter(|rule| has(&rule.tool_name))
+            .collect();
+        permission_rules.extend(self.rules);
+
+        let mut hooks = self.hooks;
+        if has("Bash") {
+            hooks
+                .add_with_pattern(HookEvent::PreToolUse, "^Bash$", bash_safety_hook(Severity::High))
+                .context("Invalid preset hook pattern")?;
+        }
+
+        let prompt = self.system_prompt.unwrap_or_else(|| self.kind.prompt().to_string());
+        let mut config = AgentConfig::new(prompt).with_tools(tools.clone());
+        if self.kind == Kind::Research {
+            config = config.with_web_search(WebSearchTool::new());
+        }
+
+        let todo_manager = Arc::new(TodoListManager::new());
+        if has("TodoWrite") {
+            let todos = todo_manager.clone();
+            config = config.with_injection_fn("todo_status", move |_internals, mut messages| {
+                if todos.is_empty() {
+                    inject_system_reminder(&mut messages, TODO_REMINDER);
+                }
+                messages
+            });
+        }
+
+        Ok(AgentPreset {
+            config,
+            llm: self.llm,
+            tools,
+            hooks,
+            permission_rules,
+            todo_manager,
+        })
+    }
+
+    /// The preset's tools, less the removed ones, plus the extra ones
+    fn build_tools(&mut self) -> Result<ToolRegistry> {
+        let workdir = self.workdir.as_str();
+        let policy = PathPolicy::new().allow_root(workdir);
+        let mut registry = ToolRegistry::new().with_conflict_policy(ConflictPolicy::Replace);
+        match self.kind {
+            Kind::Coding => {
+                register_file_tools(&mut registry, workdir, &policy);
+                registry.register(TodoWriteTool::new());
+                registry.register(AskUserQuestionTool::new());
+            }
+            Kind::Research => {
+                registry.register(ReadTool::with_base_dir(workdir).with_policy(policy));
+                registry.register(WebFetchTool::new()?);
+            }
+            Kind::Reviewer => {
+                registry.register(ReadTool::with_base_dir(workdir).with_policy(policy.clone()));
+                registry.register(GlobTool::with_base_dir(workdir).with_policy(policy.clone()));
+                registry.register(GrepTool::with_base_dir(workdir).with_policy(policy.clone()));
+                registry.register(LsTool::with_base_dir(workdir).with_policy(policy));
+            }
+        }
+        if self.bash {
+            registry.register(BashTool::with_working_dir(workdir));
+        }
+        for register in self.extra_tools.drain(..) {
+            register(&mut registry);
+        }
+
+        let kept: Vec<String> = registry
+            .tool_names()
+            .into_iter()
+            .filter(|name| !self.removed_tools.iter().any(|removed| removed == name))
+            .map(str::to_string)
+            .collect();
+        registry.subset(&kept)
+    }
+
+    /// Rules allowing the preset's read-only tools
+    fn default_allowed(&self) -> Vec<PermissionRule> {
+        let read_only = ["Read", "Glob", "Grep", "LS"].map(PermissionRule::allow_tool);
+        match self.kind {
+            Kind::Coding => read_only.to_vec(),
+            Kind::Research => vec![PermissionRule::allow_tool("Read"), PermissionRule::allow_tool("WebFetch")],
+            Kind::Reviewer => read_only
+                .into_iter()
+                .chain(REVIEWER_GIT_PREFIXES.map(|prefix| PermissionRule::allow_prefix("Bash", prefix)))
+                .collect(),
+        }
+    }
+}
+
+impl Kind {
+    fn prompt(self) -> &'static str {
+        match self {
+            Kind::Coding => CODING_PROMPT,
+            Kind::Research => RESEARCH_PROMPT,
+            Kind::Reviewer => REVIEWER_PROMPT,
+        }
+    }
+}
+
+/// Everything a preset builds
+///
+/// The hooks aren't in `config` yet, so more can be added: `into_agent`
+/// applies them, or use `config.with_hooks(hooks)` yourself.
+pub struct AgentPreset {
+    /// Agent configuration with the prompt, tools and injections applied
+    pub config: AgentConfig,
+    /// The LLM provider
+    pub llm: Arc<dyn LlmProvider>,
+    /// The registered tools (also set on `config`)
+    pub tools: Arc<ToolRegistry>,
+    /// The Bash safety hook, plus any hooks passed to the builder
+    pub hooks: HookRegistry,
+    /// Local permission rules, for `SpawnOptions::with_rules`
+    pub permission_rules: Vec<PermissionRule>,
+    /// Todo list shared with TodoWrite (show it with `ConsoleRenderer::with_todo_manager`)
+    pub todo_manager: Arc<TodoListManager>,
+}
+
+impl AgentPreset {
+    /// Apply an agent definition file (`agent.toml` / JSON) on top of the preset
+    ///
+    /// The file's options override the preset's, its `tools` list narrows
+    /// the preset's tools, and its permission rules and deny hooks are added.
+    pub fn with_agent_file(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = AgentFile::load(path)?;
+        let context = || format!("Invalid agent config {}", path.display());
+
+        self.tools = file.resolve_tools(self.tools).with_context(context)?;
+        self.config = file.apply(self.config).with_tools(self.tools.clone());
+        self.llm = file.apply_llm(self.llm).with_context(context)?;
+        self.permission_rules.extend(file.permission_rules().with_context(context)?);
+        self.hooks.merge(file.deny_hooks().with_context(context)?);
+        Ok(self)
+    }
+
+    /// Options that give a spawned agent the preset's rules and todo list
+    pub fn spawn_options(&self) -> SpawnOptions {
+        SpawnOptions::new()
+            .with_resource_arc(self.todo_manager.clone())
+            .with_rules(self.permission_rules.clone())
+    }
+
+    /// The agent with the hooks applied, and its spawn options
+    pub fn into_agent(self) -> (StandardAgent, SpawnOptions) {
+        let options = self.spawn_options();
+        let agent = StandardAgent::new(self.config.with_hooks(self.hooks), self.llm);
+        (agent, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::MockLlmProvider;
+    use crate::permissions::RuleType;
+
+    fn llm() -> Arc<dyn LlmProvider> {
+        Arc::new(MockLlmProvider::new())
+    }
+
+    fn names(preset: &AgentPreset) -> Vec<&str> {
+        let mut names = preset.tools.tool_names();
+        names.sort();
+        names
+    }
+
+    fn allowed(preset: &AgentPreset) -> Vec<String> {
+        preset
+            .permission_rules
+            .iter()
+            .map(|rule| match &rule.prefix {
+                Some(prefix) => format!("{}({})", rule.tool_name, prefix),
+                None => rule.tool_name.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_coding_agent() {
+        let dir = tempfile::tempdir().unwrap();
+        let preset = coding_agent(llm(), dir.path()).build().unwrap();
+
+        assert_eq!(
+            names(&preset),
+            vec!["AskUserQuestion", "Bash", "Edit", "Glob", "Grep", "LS", "Read", "TodoWrite", "Write"]
+        );
+        assert_eq!(allowed(&preset), vec!["Read", "Glob", "Grep", "LS"]);
+        assert!(preset.permission_rules.iter().all(|rule| rule.rule_type == RuleType::AllowTool));
+        assert_eq!(preset.hooks.hook_count(HookEvent::PreToolUse), 1);
+        assert_eq!(preset.config.injections.names(), vec!["todo_status"]);
+        assert_eq!(preset.config.system_prompt, CODING_PROMPT);
+        assert!(preset.config.web_search.is_none());
+    }
+
+    #[test]
+    fn test_research_agent_cannot_change_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let preset = research_agent(llm(), dir.path()).build().unwrap();
+
+        assert_eq!(names(&preset), vec!["Read", "WebFetch"]);
+        assert_eq!(allowed(&preset), vec!["Read", "WebFetch"]);
+        assert!(preset.config.web_search.is_some());
+        assert_eq!(preset.hooks.hook_count(HookEvent::PreToolUse), 0);
+        assert!(preset.config.injections.names().is_empty());
+    }
+
+    #[test]
+    fn test_reviewer_agent() {
+        let dir = tempfile::tempdir().unwrap();
+        let preset = reviewer_agent(llm(), dir.path()).build().unwrap();
+
+        assert_eq!(names(&preset), vec!["Bash", "Glob", "Grep", "LS", "Read"]);
+        assert_eq!(
+            allowed(&preset),
+            vec![
+                "Read",
+                "Glob",
+                "Grep",
+                "LS",
+                "Bash(git diff)",
+                "Bash(git log)",
+                "Bash(git show)",
+                "Bash(git status)"
+            ]
+        );
+        assert_eq!(preset.hooks.hook_count(HookEvent::PreToolUse), 1);
+    }
+
+    #[test]
+    fn test_builder_swaps_pieces() {
+        let dir = tempfile::tempdir().unwrap();
+        let preset = coding_agent(llm(), dir.path())
+            .without_bash()
+            .without_tool("AskUserQuestion")
+            .with_extra_tool(crate::tools::NotebookReadTool::new().unwrap())
+            .with_rule(PermissionRule::allow_tool("NotebookRead"))
+            .with_system_prompt("Custom")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            names(&preset),
+            vec!["Edit", "Glob", "Grep", "LS", "NotebookRead", "Read", "TodoWrite", "Write"]
+        );
+        assert_eq!(allowed(&preset), vec!["Read", "Glob", "Grep", "LS", "NotebookRead"]);
+        // No Bash, no safety hook
+        assert_eq!(preset.hooks.hook_count(HookEvent::PreToolUse), 0);
+        assert_eq!(preset.config.system_prompt, "Custom");
+    }
+}
@@ -0,0 +1,356 @@
+//! Client side: `RemoteAgentHandle`, an agent handle over WebSocket
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::core::{ChunkEnvelope, FrameworkError, FrameworkResult, InputMessage, OutputChunk};
+use crate::runtime::channels::{INPUT_CHANNEL_SIZE, OUTPUT_CHANNEL_SIZE};
+use crate::runtime::{AgentConnection, OutputReceiver, OutputSender};
+
+use super::protocol::{ClientFrame, ServerFrame};
+use super::server::DEFAULT_PING_INTERVAL;
+use super::ws_error;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Settings for a `RemoteAgentHandle`
+#[derive(Debug, Clone)]
+pub struct RemoteConfig {
+    /// Shared token the bridge was created with
+    pub token: String,
+    /// Wait between reconnection attempts
+    pub reconnect_delay: Duration,
+    /// Give up after this many failed attempts in a row (None = never)
+    pub max_reconnect_attempts: Option<usize>,
+    /// Treat the connection as lost after this long without a frame
+    ///
+    /// The server pings every `BridgeConfig::ping_interval`, so keep this
+    /// well above it.
+    pub idle_timeout: Duration,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            token: String::new(),
+            reconnect_delay: Duration::from_secs(1),
+            max_reconnect_attempts: None,
+            idle_timeout: DEFAULT_PING_INTERVAL * 3,
+        }
+    }
+}
+
+impl RemoteConfig {
+    /// Default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Present this token to the bridge
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = token.into();
+        self
+    }
+
+    /// Wait this long between reconnection attempts
+    pub fn with_reconnect_delay(mut self, delay: Duration) -> Self {
+        self.reconnect_delay = delay;
+        self
+    }
+
+    /// Give up after this many failed attempts in a row
+    pub fn with_max_reconnect_attempts(mut self, attempts: usize) -> Self {
+        self.max_reconnect_attempts = Some(attempts);
+        self
+    }
+
+    /// Treat the connection as lost after this long without a frame
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+}
+
+struct ConnectionTask(JoinHandle<()>);
+
+impl Drop for ConnectionTask {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Handle to an agent served by an `AgentBridge`
+///
+/// Offers the `AgentConnection` surface of `AgentHandle` (send input,
+/// subscribe, answer permission requests and questions), so renderers work
+/// against it unchanged. When the connection drops, the handle reconnects
+/// in the background and the server replays the chunks sent meanwhile;
+/// input sent while disconnected is delivered after reconnecting.
+///
+/// ```ignore
+/// use shadow_agent_sdk::runtime::AgentConnection;
+///
+/// let remote = RemoteAgentHandle::connect("ws://agents.internal:9000", token).await?;
+/// ConsoleRenderer::new(remote).run().await?;
+/// ```
+///
+/// Clones share the connection, which closes when the last clone is dropped.
+#[derive(Clone)]
+pub struct RemoteAgentHandle {
+    session_id: String,
+    outgoing: mpsc::Sender<InputMessage>,
+    output: OutputSender,
+    last_seq: Arc<AtomicU64>,
+    connected: Arc<AtomicBool>,
+    _task: Arc<ConnectionTask>,
+}
+
+impl RemoteAgentHandle {
+    /// Connect with the default settings and the bridge's token
+    pub async fn connect(url: impl Into<String>, token: impl Into<String>) -> FrameworkResult<Self> {
+        Self::connect_with(url, RemoteConfig::default().with_token(token)).await
+    }
+
+    /// Connect to an `AgentBridge` at a `ws://` or `wss://` URL
+    ///
+    /// Fails if the first connection can't be made or the bridge rejects
+    /// `config.token`; later drops are retried as `config` says.
+    pub async fn connect_with(url: impl Into<String>, config: RemoteConfig) -> FrameworkResult<Self> {
+        let url = url.into();
+        let last_seq = Arc::new(AtomicU64::new(0));
+        let (socket, session_id, seq) = open(&url, &config.token, None).await?;
+        tracing::info!("[Remote] Connected to {} ({})", url, session_id);
+        last_seq.store(seq, Ordering::SeqCst);
+
+        let (outgoing, incoming) = mpsc::channel(INPUT_CHANNEL_SIZE);
+        let output = OutputSender::new(OUTPUT_CHANNEL_SIZE);
+        let connected = Arc::new(AtomicBool::new(true));
+        let task = tokio::spawn(run(
+            Connection {
+                url,
+                config,
+                output: output.clone(),
+                last_seq: last_seq.clone(),
+                connected: connected.clone(),
+            },
+            socket,
+            incoming,
+        ));
+
+        Ok(Self {
+            session_id,
+            outgoing,
+            output,
+            last_seq,
+            connected,
+            _task: Arc::new(ConnectionTask(task)),
+        })
+    }
+
+    /// Session ID of the remote agent
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Whether the connection is currently up
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
+
+    /// Sequence number of the last chunk received
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl AgentConnection for RemoteAgentHandle {
+    async fn send(&self, message: InputMessage) -> FrameworkResult<()> {
+        self.outgoing.send(message).await.map_err(|_| FrameworkError::ChannelClosed)
+    }
+
+    fn subscribe(&self) -> OutputReceiver {
+        self.output.subscribe()
+    }
+}
+
+impl std::fmt::Debug for RemoteAgentHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteAgentHandle")
+            .field("session_id", &self.session_id)
+            .field("connected", &self.is_connected())
+            .field("last_seq", &self.last_seq())
+            .finish()
+    }
+}
+
+/// Open a connection and exchange hello/welcome
+///
+/// Returns the socket, the session ID and the server's latest sequence number.
+async fn open(url: &str, token: &str, resume_from: Option<u64>) -> FrameworkResult<(Socket, String, u64)> {
+    let (mut socket, _) = tokio_tungstenite::connect_async(url).await.map_err(ws_error)?;
+    let hello = serde_json::to_string(&ClientFrame::Hello { token: token.to_string(), resume_from })?;
+    socket.send(Message::text(hello)).await.map_err(ws_error)?;
+    loop {
+        match socket.next().await {
+            Some(Ok(Message::Text(text))) => match serde_json::from_str(text.as_str())? {
+                ServerFrame::Welcome { session_id, last_seq } => return Ok((socket, session_id, last_seq)),
+                other => return Err(FrameworkError::other(format!("expected welcome, got {:?}", other))),
+            },
+            Some(Ok(Message::Close(Some(frame)))) => {
+                return Err(FrameworkError::other(format!("bridge closed the connection: {}", frame.reason)));
+            }
+            Some(Ok(Message::Close(None))) | None => return Err(FrameworkError::ChannelClosed),
+            Some(Ok(_)) => {}
+            Some(Err(e)) => return Err(ws_error(e)),
+        }
+    }
+}
+
+struct Connection {
+    url: String,
+    config: RemoteConfig,
+    output: OutputSender,
+    last_seq: Arc<AtomicU64>,
+    connected: Arc<AtomicBool>,
+}
+
+/// How a connection ended
+enum Ended {
+    /// Every handle was dropped
+    Closed,
+    /// The connection was lost; reconnect
+    Lost,
+}
+
+/// Pump frames both ways, reconnecting when the connection drops
+async fn run(conn: Connection, socket: Socket, mut incoming: mpsc::Receiver<InputMessage>) {
+    let mut socket = Some(socket);
+    // Input that couldn't be sent before the connection dropped
+    let mut unsent: Option<InputMessage> = None;
+
+    loop {
+        let current = match socket.take() {
+            Some(current) => current,
+            None => match reconnect(&conn).await {
+                Some(current) => current,
+                None => {
+                    let _ = conn.output.send(OutputChunk::error(format!("Lost connection to {}", conn.url)));
+                    return;
+                }
+            },
+        };
+        conn.connected.store(true, Ordering::SeqCst);
+        let ended = pump(&conn, current, &mut incoming, &mut unsent).await;
+        conn.connected.store(false, Ordering::SeqCst);
+        match ended {
+            Ended::Closed => return,
+            Ended::Lost => tracing::warn!("[Remote] Connection to {} lost, reconnecting", conn.url),
+        }
+    }
+}
+
+async fn reconnect(conn: &Connection) -> Option<Socket> {
+    let mut attempts = 0;
+    loop {
+        tokio::time::sleep(conn.config.reconnect_delay).await;
+        attempts += 1;
+        let resume_from = conn.last_seq.load(Ordering::SeqCst);
+        match open(&conn.url, &conn.config.token, Some(resume_from)).await {
+            Ok((socket, _, last_seq)) => {
+                if last_seq < resume_from {
+                    // A different stream (the server restarted): start over
+                    tracing::warn!("[Remote] Server sequence restarted at {}", last_seq);
+                    conn.last_seq.store(last_seq, Ordering::SeqCst);
+                }
+                tracing::info!("[Remote] Reconnected to {} after {} attempt(s)", conn.url, attempts);
+                return Some(socket);
+            }
+            Err(e) => {
+                tracing::debug!("[Remote] Reconnect attempt {} failed: {}", attempts, e);
+                if conn.config.max_reconnect_attempts.is_some_and(|max| attempts >= max) {
+                    tracing::error!("[Remote] Giving up on {} after {} attempts", conn.url, attempts);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+async fn pump(
+    conn: &Connection,
+    mut socket: Socket,
+    incoming: &mut mpsc::Receiver<InputMessage>,
+    unsent: &mut Option<InputMessage>,
+) -> Ended {
+    if let Some(message) = unsent.take() {
+        if let Err(message) = send_input(&mut socket, message).await {
+            *unsent = Some(message);
+            return Ended::Lost;
+        }
+    }
+
+    loop {
+        tokio::select! {
+            received = tokio::time::timeout(conn.config.idle_timeout, socket.next()) => {
+                let frame = match received {
+                    Ok(Some(Ok(Message::Text(text)))) => text,
+                    Ok(Some(Ok(Message::Close(_)))) | Ok(None) | Ok(Some(Err(_))) => return Ended::Lost,
+                    Ok(Some(Ok(_))) => continue,
+                    Err(_) => {
+                        tracing::warn!("[Remote] No frames from {} for {:?}", conn.url, conn.config.idle_timeout);
+                        return Ended::Lost;
+                    }
+                };
+                match serde_json::from_str(frame.as_str()) {
+                    Ok(ServerFrame::Chunk(envelope)) => {
+                        // Skip anything a resume sent twice
+                        if envelope.seq > conn.last_seq.load(Ordering::SeqCst) {
+                            conn.last_seq.store(envelope.seq, Ordering::SeqCst);
+                            let _ = conn.output.forward(envelope);
+                        }
+                    }
+                    Ok(ServerFrame::Missed { count, seq }) => {
+                        if seq > conn.last_seq.load(Ordering::SeqCst) {
+                            conn.last_seq.store(seq, Ordering::SeqCst);
+                            let _ = conn.output.forward(ChunkEnvelope::new(seq, None, OutputChunk::Dropped { count }));
+                        }
+                    }
+                    Ok(ServerFrame::Welcome { .. }) => {}
+                    Err(e) => tracing::warn!("[Remote] Ignoring bad frame: {}", e),
+                }
+            }
+            message = incoming.recv() => {
+                let Some(message) = message else {
+                    let _ = socket.close(None).await;
+                    return Ended::Closed;
+                };
+                if let Err(message) = send_input(&mut socket, message).await {
+                    *unsent = Some(message);
+                    return Ended::Lost;
+                }
+            }
+        }
+    }
+}
+
+/// Send one input frame, handing the message back if it couldn't be sent
+async fn send_input(socket: &mut Socket, message: InputMessage) -> Result<(), InputMessage> {
+    let text = match serde_json::to_string(&ClientFrame::Input { message: message.clone() }) {
+        Ok(text) => text,
+        Err(e) => {
+            tracing::error!("[Remote] Cannot serialize input: {}", e);
+            return Ok(());
+        }
+    };
+    socket.send(Message::text(text)).await.map_err(|_| message)
+}
@@ -0,0 +1,219 @@
+//! Remote agent handles over WebSocket (`remote` feature)
+//!
+//! Run agents on a server and drive them from another process, such as a
+//! desktop UI:
+//! - `AgentBridge` (server) serves an `AgentHandle` to WebSocket clients:
+//!   `InputMessage` frames in, numbered `OutputChunk` frames out
+//! - `RemoteAgentHandle` (client) implements `AgentConnection`, so
+//!   renderers such as `ConsoleRenderer` work against it unchanged
+//!
+//! ```ignore
+//! // Server
+//! let bridge = AgentBridge::new(runtime.spawn(session, agent_fn).await, token);
+//! tokio::spawn(async move { bridge.listen(TcpListener::bind("127.0.0.1:9000").await?).await });
+//!
+//! // Client
+//! let remote = RemoteAgentHandle::connect("ws://127.0.0.1:9000", token).await?;
+//! ConsoleRenderer::new(remote).run().await?;
+//! ```
+//!
+//! Clients authenticate with a shared token in their hello frame. Anyone
+//! holding it controls the agent, including its permission prompts, so
+//! keep the bridge on loopback unless a TLS proxy fronts it.
+//!
+//! The bridge keeps the most recent chunks (`BridgeConfig::buffer_size`).
+//! A client that loses its connection reconnects with the sequence number
+//! of the last chunk it saw and gets the rest replayed, so subscribers see
+//! an unbroken stream; if the gap is larger than the buffer they get an
+//! `OutputChunk::Dropped` marker for the part that is gone. See `protocol`
+//! for the frames.
+
+pub mod client;
+pub mod protocol;
+pub mod server;
+
+pub use client::{RemoteAgentHandle, RemoteConfig};
+pub use protocol::{ClientFrame, ServerFrame};
+pub use server::{AgentBridge, BridgeConfig};
+
+use crate::core::FrameworkError;
+
+fn ws_error(e: tokio_tungstenite::tungstenite::Error) -> FrameworkError {
+    FrameworkError::other(format!("WebSocket error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::net::TcpListener;
+
+    use crate::agent::{AgentConfig, StandardAgent};
+    use crate::cli::ConsoleRenderer;
+    use crate::core::OutputChunk;
+    use crate::llm::MockLlmProvider;
+    use crate::runtime::{AgentConnection, AgentRuntime};
+    use crate::session::{AgentSession, SessionStorage};
+
+    /// Collect chunks until the end of a turn
+    async fn turn_text(rx: &mut crate::runtime::OutputReceiver) -> String {
+        let mut text = String::new();
+        loop {
+            match tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.unwrap().unwrap() {
+                OutputChunk::TextComplete(complete) => text.push_str(&complete),
+                OutputChunk::Done => return text,
+                OutputChunk::Error(e) => panic!("turn failed: {}", e),
+                _ => {}
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remote_conversation_survives_reconnect() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new()
+            .with_text("Hello from the server")
+            .with_text("Said while you were away");
+        let agent = StandardAgent::new(AgentConfig::new("Remote").with_auto_name(false), Arc::new(llm.clone()));
+        let session =
+            AgentSession::new_with_storage("remote", "test", "Remote", "Remote agent", SessionStorage::with_dir(dir.path()))
+                .unwrap();
+        let handle = runtime.spawn(session, move |internals| agent.run(internals)).await;
+
+        let bridge = AgentBridge::with_config(
+            handle.clone(),
+            "let me in",
+            BridgeConfig::new().with_ping_interval(Duration::from_millis(50)),
+        );
+        let listener = Arc::new(TcpListener::bind("127.0.0.1:0").await.unwrap());
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let serve_next = || {
+            let (listener, bridge) = (listener.clone(), bridge.clone());
+            tokio::spawn(async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                bridge.accept(stream).await
+            })
+        };
+
+        let serving = serve_next();
+        let config = RemoteConfig::new().with_token("let me in").with_reconnect_delay(Duration::from_millis(20));
+        let remote = RemoteAgentHandle::connect_with(&url, config).await.unwrap();
+        assert_eq!(remote.session_id(), "remote");
+
+        // A whole turn through the console renderer, over the socket
+        let renderer = ConsoleRenderer::new(remote.clone()).show_tools(false);
+        renderer.run_turn("Hi").await.unwrap();
+        assert_eq!(llm.call_count(), 1);
+        let seen = remote.last_seq();
+        assert!(seen > 0);
+
+        // Drop the connection, and run a turn while the client is away
+        let mut rx = remote.subscribe();
+        serving.abort();
+        let mut local = handle.subscribe();
+        handle.send_input("Anything new?").await.unwrap();
+        assert_eq!(turn_text(&mut local).await, "Said while you were away");
+
+        // The client reconnects and gets the missed turn replayed
+        serve_next();
+        assert_eq!(turn_text(&mut rx).await, "Said while you were away");
+        assert!(remote.is_connected());
+        assert!(remote.last_seq() > seen);
+
+        // Input still flows after the reconnect
+        remote.send(crate::core::InputMessage::Shutdown).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), runtime.wait_for("remote")).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_connect_fails_without_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        drop(listener);
+        assert!(RemoteAgentHandle::connect(url, "token").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bridge_rejects_a_wrong_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let agent = StandardAgent::new(AgentConfig::new("Remote").with_auto_name(false), Arc::new(MockLlmProvider::new()));
+        let session =
+            AgentSession::new_with_storage("locked", "test", "Remote", "Remote agent", SessionStorage::with_dir(dir.path()))
+                .unwrap();
+        let handle = runtime.spawn(session, move |internals| agent.run(internals)).await;
+
+        let bridge = AgentBridge::new(handle.clone(), "right");
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let serving = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            bridge.accept(stream).await
+        });
+
+        let error = RemoteAgentHandle::connect(&url, "wrong").await.unwrap_err();
+        assert!(error.to_string().contains("invalid token"), "{}", error);
+        assert!(serving.await.unwrap().is_err());
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bridge_waits_one_ping_interval_for_hello() {
+        use futures::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message;
+
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let agent = StandardAgent::new(AgentConfig::new("Remote").with_auto_name(false), Arc::new(MockLlmProvider::new()));
+        let session =
+            AgentSession::new_with_storage("hello", "test", "Remote", "Remote agent", SessionStorage::with_dir(dir.path()))
+                .unwrap();
+        let handle = runtime.spawn(session, move |internals| agent.run(internals)).await;
+
+        let bridge = AgentBridge::with_config(
+            handle.clone(),
+            "right",
+            BridgeConfig::new().with_ping_interval(Duration::from_millis(200)),
+        );
+        let listener = Arc::new(TcpListener::bind("127.0.0.1:0").await.unwrap());
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let serve_next = || {
+            let (listener, bridge) = (listener.clone(), bridge.clone());
+            tokio::spawn(async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                bridge.accept(stream).await
+            })
+        };
+
+        // A client that pings first still gets in
+        let serving = serve_next();
+        let (mut ws, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+        ws.send(Message::Ping(Vec::new().into())).await.unwrap();
+        ws.send(Message::text(r#"{"type": "hello", "token": "right"}"#)).await.unwrap();
+        let welcome = loop {
+            match ws.next().await.unwrap().unwrap() {
+                Message::Text(text) => break text,
+                _ => continue,
+            }
+        };
+        assert!(welcome.as_str().contains(r#""type":"welcome""#), "{}", welcome.as_str());
+        drop(ws);
+        let _ = serving.await.unwrap();
+
+        // A silent one is dropped, even if it keeps pinging
+        let serving = serve_next();
+        let (mut ws, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+        let pinging = tokio::spawn(async move {
+            while ws.send(Message::Ping(Vec::new().into())).await.is_ok() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        });
+        let error = tokio::time::timeout(Duration::from_secs(5), serving).await.unwrap().unwrap().unwrap_err();
+        assert!(error.to_string().contains("no hello"), "{}", error);
+        pinging.abort();
+        handle.shutdown().await.unwrap();
+    }
+}
@@ -0,0 +1,93 @@
+//! Wire format between `AgentBridge` and `RemoteAgentHandle`
+//!
+//! Every frame is a JSON text message with a `type` field. Keepalive uses
+//! WebSocket ping/pong control frames.
+//!
+//! ```text
+//! client                                   server
+//!   ── {"type":"hello","token":"..","resume_from":12} ──▶
+//!   ◀── {"type":"welcome","session_id":"..","last_seq":40}
+//!   ◀── {"type":"chunk","seq":13,"timestamp":"..","turn":2,"chunk":{..}}   (replayed 13..=40, then live)
+//!   ── {"type":"input","message":{..}} ──▶
+//! ```
+//!
+//! The hello frame carries the bridge's shared token; the server closes
+//! connections whose token is missing or wrong before sending anything.
+//!
+//! Chunks carry the agent's `ChunkEnvelope`, numbered from 1. A client
+//! that has seen chunk `n` resumes with `resume_from: n`; the server replays
+//! what it still buffers after `n`, preceded by `missed` if some of it was
+//! already dropped.
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::{ChunkEnvelope, InputMessage};
+
+/// Frames sent by the client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientFrame {
+    /// First frame of every connection
+    Hello {
+        /// Shared token the bridge was created with
+        #[serde(default)]
+        token: String,
+        /// Last chunk seen on an earlier connection (None = new client,
+        /// which only gets chunks from now on)
+        #[serde(default)]
+        resume_from: Option<u64>,
+    },
+    /// Input for the agent
+    Input {
+        /// The message, as it would be passed to `AgentHandle::send`
+        message: InputMessage,
+    },
+}
+
+/// Frames sent by the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerFrame {
+    /// Reply to `hello`
+    Welcome {
+        /// Session ID of the served agent
+        session_id: String,
+        /// Sequence number of the latest chunk (0 = none yet)
+        last_seq: u64,
+    },
+    /// An output chunk, with the sequence number the agent gave it
+    Chunk(ChunkEnvelope),
+    /// Chunks the client asked to resume from are no longer buffered
+    Missed {
+        /// Number of chunks lost
+        count: u64,
+        /// Sequence number of the last chunk lost
+        seq: u64,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_are_tagged() {
+        let frame = ClientFrame::Input { message: InputMessage::user_input("hi") };
+        assert_eq!(
+            serde_json::to_string(&frame).unwrap(),
+            r#"{"type":"input","message":{"UserInput":"hi"}}"#
+        );
+
+        let frame: ClientFrame = serde_json::from_str(r#"{"type":"hello"}"#).unwrap();
+        assert!(matches!(frame, ClientFrame::Hello { ref token, resume_from: None } if token.is_empty()));
+
+        let frame: ServerFrame = serde_json::from_str(
+            r#"{"type":"chunk","seq":3,"timestamp":"2026-01-01T00:00:00Z","turn":1,"chunk":"Done"}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            frame,
+            ServerFrame::Chunk(ChunkEnvelope { seq: 3, turn: Some(1), chunk: crate::core::OutputChunk::Done, .. })
+        ));
+    }
+}
@@ -0,0 +1,394 @@
+//! Server side: serve an `AgentHandle` over WebSocket connections
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use crate::core::{ChunkEnvelope, FrameworkError, FrameworkResult};
+use crate::runtime::{AgentHandle, OverflowPolicy};
+
+use super::protocol::{ClientFrame, ServerFrame};
+use super::ws_error;
+
+/// Default number of recent chunks kept for resuming clients
+pub const DEFAULT_BUFFER_SIZE: usize = 1024;
+/// Default time between pings
+pub const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Settings for an `AgentBridge`
+#[derive(Debug, Clone)]
+pub struct BridgeConfig {
+    /// Recent chunks kept for clients that reconnect
+    pub buffer_size: usize,
+    /// Time between pings; a client silent for three intervals is dropped,
+    /// and one that doesn't say hello within one interval
+    pub ping_interval: Duration,
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        Self {
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            ping_interval: DEFAULT_PING_INTERVAL,
+        }
+    }
+}
+
+impl BridgeConfig {
+    /// Default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep this many recent chunks for reconnecting clients
+    pub fn with_buffer_size(mut self, size: usize) -> Self {
+        self.buffer_size = size.max(1);
+        self
+    }
+
+    /// Ping clients this often
+    pub fn with_ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = interval;
+        self
+    }
+}
+
+/// Recent chunks, in sequence order
+struct ReplayBuffer {
+    chunks: VecDeque<ChunkEnvelope>,
+    capacity: usize,
+    last_seq: u64,
+    /// Sequence number of the newest chunk pushed out of the buffer
+    evicted_seq: u64,
+}
+
+/// Chunks to resend to a client, after the ones it lost
+struct Replay {
+    /// Number of chunks no longer buffered, and the last of them
+    missed: Option<(u64, u64)>,
+    chunks: Vec<ChunkEnvelope>,
+}
+
+impl ReplayBuffer {
+    fn new(capacity: usize) -> Self {
+        Self {
+            chunks: VecDeque::new(),
+            capacity,
+            last_seq: 0,
+            evicted_seq: 0,
+        }
+    }
+
+    fn push(&mut self, envelope: ChunkEnvelope) {
+        self.last_seq = envelope.seq;
+        self.chunks.push_back(envelope);
+        while self.chunks.len() > self.capacity {
+            if let Some(evicted) = self.chunks.pop_front() {
+                self.evicted_seq = evicted.seq;
+            }
+        }
+    }
+
+    /// Chunks after `seq`, and which of those are no longer buffered
+    fn since(&self, seq: u64) -> Replay {
+        let missed = (self.evicted_seq > seq).then(|| (self.evicted_seq - seq, self.evicted_seq));
+        let chunks = self.chunks.iter().filter(|c| c.seq > seq).cloned().collect();
+        Replay { missed, chunks }
+    }
+}
+
+struct BridgeInner {
+    handle: AgentHandle,
+    token: String,
+    config: BridgeConfig,
+    buffer: Mutex<ReplayBuffer>,
+    live: broadcast::Sender<ChunkEnvelope>,
+    pump: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Drop for BridgeInner {
+    fn drop(&mut self) {
+        if let Some(pump) = self.pump.lock().unwrap().take() {
+            pump.abort();
+        }
+    }
+}
+
+/// Serves one agent to any number of WebSocket clients
+///
+/// The bridge numbers the agent's output chunks and keeps the most recent
+/// ones, so a client that reconnects gets what it missed. Clones share
+/// the buffer; create one bridge per agent and keep it while the agent
+/// runs.
+///
+/// Clients must present the bridge's shared token in their hello frame;
+/// connections without it are closed. A connected client can send any
+/// input, including permission answers, so it controls the agent and
+/// whatever its tools can reach. Listen on loopback, and put a TLS
+/// terminating proxy in front before exposing the bridge to a network.
+///
+/// ```ignore
+/// let bridge = AgentBridge::new(handle, std::env::var("BRIDGE_TOKEN")?);
+/// bridge.listen(TcpListener::bind("127.0.0.1:9000").await?).await?;
+/// ```
+#[derive(Clone)]
+pub struct AgentBridge {
+    inner: Arc<BridgeInner>,
+}
+
+impl AgentBridge {
+    /// Bridge an agent with the default settings
+    ///
+    /// Clients must connect with `token`. An empty token admits no one.
+    pub fn new(handle: AgentHandle, token: impl Into<String>) -> Self {
+        Self::with_config(handle, token, BridgeConfig::default())
+    }
+
+    /// Bridge an agent
+    pub fn with_config(handle: AgentHandle, token: impl Into<String>, config: BridgeConfig) -> Self {
+        let (live, _) = broadcast::channel(config.buffer_size);
+        let inner = Arc::new(BridgeInner {
+            token: token.into(),
+            buffer: Mutex::new(ReplayBuffer::new(config.buffer_size)),
+            live,
+            pump: Mutex::new(None),
+            config,
+            handle,
+        });
+
+        // Record every chunk, whether or not a client is connected
+        let mut rx = inner.handle.subscribe_reliable(inner.config.buffer_size, OverflowPolicy::DropOldest);
+        let weak = Arc::downgrade(&inner);
+        let pump = tokio::spawn(async move {
+            while let Some(envelope) = rx.recv_envelope().await {
+                let Some(inner) = weak.upgrade() else {
+                    break;
+                };
+                let mut buffer = inner.buffer.lock().unwrap();
+                buffer.push(envelope.clone());
+                let _ = inner.live.send(envelope);
+            }
+        });
+        *inner.pump.lock().unwrap() = Some(pump);
+        Self { inner }
+    }
+
+    /// The bridged agent's handle
+    pub fn handle(&self) -> &AgentHandle {
+        &self.inner.handle
+    }
+
+    /// Accept WebSocket connections until the listener fails
+    ///
+    /// Each connection is served on its own task.
+    pub async fn listen(&self, listener: TcpListener) -> FrameworkResult<()> {
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            tracing::info!("[Remote] Client connected from {}", peer);
+            let bridge = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = bridge.accept(stream).await {
+                    tracing::warn!("[Remote] Connection from {} ended: {}", peer, e);
+                }
+            });
+        }
+    }
+
+    /// Do the WebSocket handshake on a TCP connection and serve it
+    pub async fn accept(&self, stream: TcpStream) -> FrameworkResult<()> {
+        let ws = tokio_tungstenite::accept_async(stream).await.map_err(ws_error)?;
+        self.serve(ws).await
+    }
+
+    /// Serve an established WebSocket connection until it closes
+    pub async fn serve<S>(&self, mut ws: WebSocketStream<S>) -> FrameworkResult<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        // A client has one ping interval to say hello; pings don't count
+        let ping_interval = self.inner.config.ping_interval;
+        let hello_deadline = Instant::now() + ping_interval;
+        let hello = loop {
+            match tokio::time::timeout_at(hello_deadline, ws.next()).await {
+                Ok(Some(Ok(Message::Ping(_) | Message::Pong(_)))) => continue,
+                Ok(message) => break message,
+                Err(_) => {
+                    let frame = CloseFrame { code: CloseCode::Policy, reason: "no hello".into() };
+                    let _ = ws.close(Some(frame)).await;
+                    return Err(FrameworkError::other(format!("client sent no hello within {:?}", ping_interval)));
+                }
+            }
+        };
+        let resume_from = match hello {
+            Some(Ok(Message::Text(text))) => match serde_json::from_str(text.as_str())? {
+                ClientFrame::Hello { token, resume_from } => {
+                    if !token_matches(&self.inner.token, &token) {
+                        let frame = CloseFrame { code: CloseCode::Policy, reason: "invalid token".into() };
+                        let _ = ws.close(Some(frame)).await;
+                        return Err(FrameworkError::other("client sent an invalid token"));
+                    }
+                    resume_from
+                }
+                ClientFrame::Input { .. } => {
+                    return Err(FrameworkError::other("expected a hello frame first"));
+                }
+            },
+            Some(Ok(other)) => {
+                return Err(FrameworkError::other(format!("expected a hello frame, got {:?}", other)));
+            }
+            Some(Err(e)) => return Err(ws_error(e)),
+            None => return Ok(()),
+        };
+
+        // Subscribe before reading the buffer so no chunk falls in between
+        let (mut live, last_seq, replay) = {
+            let buffer = self.inner.buffer.lock().unwrap();
+            let live = self.inner.live.subscribe();
+            let replay = match resume_from {
+                Some(seq) if seq <= buffer.last_seq => buffer.since(seq),
+                _ => Replay { missed: None, chunks: Vec::new() },
+            };
+            (live, buffer.last_seq, replay)
+        };
+
+        send_frame(
+            &mut ws,
+            &ServerFrame::Welcome {
+                session_id: self.inner.handle.session_id().to_string(),
+                last_seq,
+            },
+        )
+        .await?;
+        if let Some((count, _)) = replay.missed {
+            tracing::warn!("[Remote] Client resumed {} chunks too late", count);
+        }
+        if !replay.chunks.is_empty() {
+            tracing::debug!("[Remote] Replaying {} chunks", replay.chunks.len());
+        }
+        send_replay(&mut ws, replay).await?;
+        let mut sent = last_seq;
+
+        let mut ping = tokio::time::interval_at(Instant::now() + ping_interval, ping_interval);
+        let mut last_heard = Instant::now();
+
+        loop {
+            tokio::select! {
+                received = live.recv() => match received {
+                    Ok(envelope) => {
+                        if envelope.seq > sent {
+                            sent = envelope.seq;
+                            send_frame(&mut ws, &ServerFrame::Chunk(envelope)).await?;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(count)) => {
+                        // Catch up from the buffer, which holds at least as much
+                        tracing::debug!("[Remote] Client lagged {} chunks, catching up", count);
+                        let replay = self.inner.buffer.lock().unwrap().since(sent);
+                        if let Some(last) = replay.chunks.last() {
+                            sent = last.seq;
+                        }
+                        send_replay(&mut ws, replay).await?;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                message = ws.next() => {
+                    last_heard = Instant::now();
+                    match message {
+                        Some(Ok(Message::Text(text))) => match serde_json::from_str(text.as_str()) {
+                            Ok(ClientFrame::Input { message }) => self.inner.handle.send(message).await?,
+                            Ok(ClientFrame::Hello { .. }) => {}
+                            Err(e) => tracing::warn!("[Remote] Ignoring bad frame: {}", e),
+                        },
+                        Some(Ok(Message::Close(_))) | None => break,
+                        Some(Ok(_)) => {}
+                        Some(Err(e)) => return Err(ws_error(e)),
+                    }
+                }
+                _ = ping.tick() => {
+                    if last_heard.elapsed() > ping_interval * 3 {
+                        tracing::warn!("[Remote] Client stopped answering pings, closing");
+                        break;
+                    }
+                    ws.send(Message::Ping(Default::default())).await.map_err(ws_error)?;
+                }
+            }
+        }
+        let _ = ws.close(None).await;
+        Ok(())
+    }
+}
+
+/// Compare tokens in time independent of where they differ
+fn token_matches(expected: &str, given: &str) -> bool {
+    !expected.is_empty()
+        && expected.len() == given.len()
+        && expected.bytes().zip(given.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+async fn send_replay<S>(ws: &mut WebSocketStream<S>, replay: Replay) -> FrameworkResult<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    if let Some((count, seq)) = replay.missed {
+        send_frame(ws, &ServerFrame::Missed { count, seq }).await?;
+    }
+    for envelope in replay.chunks {
+        send_frame(ws, &ServerFrame::Chunk(envelope)).await?;
+    }
+    Ok(())
+}
+
+async fn send_frame<S>(ws: &mut WebSocketStream<S>, frame: &ServerFrame) -> FrameworkResult<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let text = serde_json::to_string(frame)?;
+    ws.send(Message::text(text)).await.map_err(ws_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::core::OutputChunk;
+
+    #[test]
+    fn test_replay_buffer_counts_missed_chunks() {
+        let mut buffer = ReplayBuffer::new(3);
+        for seq in 1..=5 {
+            buffer.push(ChunkEnvelope::new(seq, Some(0), OutputChunk::text(seq.to_string())));
+        }
+        // Holds 3..=5
+        let replay = buffer.since(1);
+        assert_eq!(replay.missed, Some((1, 2)));
+        assert_eq!(replay.chunks.iter().map(|c| c.seq).collect::<Vec<_>>(), vec![3, 4, 5]);
+
+        let replay = buffer.since(4);
+        assert_eq!(replay.missed, None);
+        assert_eq!(replay.chunks.len(), 1);
+
+        let replay = buffer.since(5);
+        assert_eq!(replay.missed, None);
+        assert!(replay.chunks.is_empty());
+    }
+
+    #[test]
+    fn test_token_matches() {
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secret", "secreT"));
+        assert!(!token_matches("secret", "secret2"));
+        assert!(!token_matches("secret", ""));
+        assert!(!token_matches("", ""));
+    }
+}
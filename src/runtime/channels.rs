@@ -3,10 +3,55 @@
 //! Agents communicate via two channel types:
 //! - **Input channel** (mpsc): Single-producer, single-consumer for sending commands to the agent
 //! - **Output channel** (broadcast): Multi-consumer for streaming output to multiple subscribers
+//!
+//! The agent's current state is shared through a **state channel** (watch),
+//! which always holds the latest `AgentState`.
+//!
+//! # Slow Subscribers
+//!
+//! The broadcast channel never waits for subscribers: one that falls more
+//! than the channel capacity behind loses the oldest chunks, and receives an
+//! `OutputChunk::Dropped` marker with how many it missed. That keeps the
+//! agent fast no matter who is listening, and suits UIs that only show the
+//! latest output.
+//!
+//! Subscribers that must see everything (transcript writers, network
+//! forwarders) use `OutputSender::subscribe_reliable` instead, which gives
+//! them their own bounded buffer with an `OverflowPolicy`:
+//! - `Block` holds the agent at its next await point until the subscriber
+//!   catches up, so nothing is lost but a stuck subscriber stalls the agent.
+//!   The buffer can briefly exceed its capacity by the chunks the agent sends
+//!   between await points.
+//! - `DropOldest` never slows the agent; the oldest buffered chunks are
+//!   discarded and replaced by a `Dropped` marker, as with `subscribe`.
+//!
+//! # Ordering
+//!
+//! The sender numbers every chunk it sends, starting at 1 for each agent,
+//! and records when it was sent; `recv_envelope` returns the chunk in a
+//! `ChunkEnvelope` with both. Clones of a sender share the numbering, and
+//! every subscriber sees chunks in sequence order. `recv` returns the bare
+//! chunk.
+//!
+//! # Filtered Subscribers
+//!
+//! `OutputReceiver::filtered` wraps a subscriber so it only returns the
+//! chunk categories of a `ChunkFilter`, skipping the rest as they arrive.
+//! The skipped chunks keep their sequence numbers, so a filtered
+//! subscriber sees gaps in `seq`; a gap means filtered out, not lost. Lost
+//! chunks still arrive as a `Dropped` marker, whose count includes chunks
+//! the filter would have skipped. Skipped chunks still take room in the
+//! channel, so a filtered subscriber lags no later than any other.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::broadcast::error::{RecvError, SendError, TryRecvError};
+use tokio::sync::{broadcast, mpsc, watch, Notify};
+use tokio_util::sync::CancellationToken;
 
-use crate::core::{InputMessage, OutputChunk};
+use crate::core::{AgentState, ChunkEnvelope, ChunkFilter, InputMessage, OutputChunk, TurnSummary};
 
 /// Default buffer size for input channel
 pub const INPUT_CHANNEL_SIZE: usize = 32;
@@ -24,11 +69,504 @@ pub type InputSender = mpsc::Sender<InputMessage>;
 /// Receiver half of the input channel (used by AgentInternals)
 pub type InputReceiver = mpsc::Receiver<InputMessage>;
 
-/// Sender half of the output broadcast channel (used by AgentInternals)
-pub type OutputSender = broadcast::Sender<OutputChunk>;
+/// Sender half of the state channel (shared by AgentInternals and AgentHandle)
+pub type StateSender = watch::Sender<AgentState>;
+
+/// Receiver half of the state channel (see `AgentHandle::subscribe_state`)
+pub type StateReceiver = watch::Receiver<AgentState>;
+
+// ============================================================================
+// Output Channel
+// ============================================================================
+
+/// Sender half of the output channel (used by AgentInternals)
+///
+/// Chunks go to every broadcast subscriber and every reliable subscriber.
+/// Clones share the same subscribers and sequence numbers, and the summary
+/// of the last `TurnComplete` sent.
+#[derive(Clone)]
+pub struct OutputSender {
+    broadcast: broadcast::Sender<ChunkEnvelope>,
+    reliable: Arc<ReliableSubscribers>,
+    last_seq: Arc<AtomicU64>,
+    last_turn: Arc<Mutex<Option<TurnSummary>>>,
+}
+
+impl OutputSender {
+    /// Create a sender whose broadcast subscribers can lag `capacity` chunks
+    pub fn new(capacity: usize) -> Self {
+        let (broadcast, _) = broadcast::channel(capacity);
+        Self {
+            broadcast,
+            reliable: Arc::new(ReliableSubscribers::default()),
+            last_seq: Arc::new(AtomicU64::new(0)),
+            last_turn: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Send a chunk to all subscribers
+    ///
+    /// Returns the number of subscribers, or an error if there are none.
+    /// Never waits; see [`wait_for_capacity`](Self::wait_for_capacity).
+    pub fn send(&self, chunk: OutputChunk) -> Result<usize, SendError<OutputChunk>> {
+        self.stamp_and_send(None, chunk)
+    }
+
+    /// Send a chunk recording the agent's current turn
+    pub fn send_in_turn(&self, turn: usize, chunk: OutputChunk) -> Result<usize, SendError<OutputChunk>> {
+        self.stamp_and_send(Some(turn), chunk)
+    }
+
+    /// Pass on a chunk another sender stamped, keeping its sequence number
+    ///
+    /// For relays such as `RemoteAgentHandle`. Chunks sent afterwards are
+    /// numbered from the highest sequence number forwarded so far.
+    pub fn forward(&self, envelope: ChunkEnvelope) -> Result<usize, SendError<OutputChunk>> {
+        let mut queues = self.reliable.queues.lock().unwrap();
+        self.last_seq.fetch_max(envelope.seq, Ordering::SeqCst);
+        self.deliver(&mut queues, envelope)
+    }
+
+    /// Sequence number of the last chunk sent (0 before the first)
+    pub fn last_seq(&self) -> u64 {
+        self.last_seq.load(Ordering::SeqCst)
+    }
+
+    /// Summary of the last `TurnComplete` chunk sent or forwarded
+    pub fn last_turn(&self) -> Option<TurnSummary> {
+        self.last_turn.lock().unwrap().clone()
+    }
+
+    fn stamp_and_send(&self, turn: Option<usize>, chunk: OutputChunk) -> Result<usize, SendError<OutputChunk>> {
+        // Numbered under the lock so every subscriber gets them in order
+        let mut queues = self.reliable.queues.lock().unwrap();
+        let seq = self.last_seq.fetch_add(1, Ordering::SeqCst) + 1;
+        self.deliver(&mut queues, ChunkEnvelope::new(seq, turn, chunk))
+    }
+
+    fn deliver(
+        &self,
+        queues: &mut Vec<Arc<ReliableQueue>>,
+        envelope: ChunkEnvelope,
+    ) -> Result<usize, SendError<OutputChunk>> {
+        if let Some(summary) = envelope.chunk.turn_summary() {
+            *self.last_turn.lock().unwrap() = Some(summary);
+        }
+        queues.retain(|queue| queue.push(envelope.clone()));
+        let reliable = queues.len();
+        match self.broadcast.send(envelope) {
+            Ok(count) => Ok(count + reliable),
+            Err(_) if reliable > 0 => Ok(reliable),
+            Err(SendError(envelope)) => Err(SendError(envelope.chunk)),
+        }
+    }
+
+    /// Subscribe to output from this point forward
+    pub fn subscribe(&self) -> OutputReceiver {
+        let _sending = self.reliable.queues.lock().unwrap();
+        OutputReceiver {
+            inner: self.broadcast.subscribe(),
+            last_seq: self.last_seq(),
+            pending: None,
+        }
+    }
+
+    /// Subscribe to the chunks `filter` matches from this point forward
+    pub fn subscribe_filtered(&self, filter: ChunkFilter) -> FilteredReceiver {
+        self.subscribe().filtered(filter)
+    }
+
+    /// Subscribe with a buffer of `capacity` chunks that only this subscriber uses
+    ///
+    /// See the module docs for how `policy` trades completeness for speed.
+    pub fn subscribe_reliable(&self, capacity: usize, policy: OverflowPolicy) -> ReliableReceiver {
+        let queue = Arc::new(ReliableQueue {
+            capacity: capacity.max(1),
+            policy,
+            state: Mutex::new(QueueState::default()),
+            readable: Notify::new(),
+            writable: Notify::new(),
+        });
+        self.reliable.queues.lock().unwrap().push(queue.clone());
+        ReliableReceiver { queue }
+    }
+
+    /// Number of current subscribers (broadcast and reliable)
+    pub fn receiver_count(&self) -> usize {
+        let reliable = self
+            .reliable
+            .queues
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|queue| !queue.state.lock().unwrap().abandoned)
+            .count();
+        self.broadcast.receiver_count() + reliable
+    }
+
+    /// Wait until every `Block` subscriber has room in its buffer
+    ///
+    /// Returns immediately when there are none, or they're keeping up.
+    pub async fn wait_for_capacity(&self) {
+        let blocking: Vec<Arc<ReliableQueue>> = self
+            .reliable
+            .queues
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|queue| queue.policy == OverflowPolicy::Block)
+            .cloned()
+            .collect();
+        for queue in blocking {
+            queue.wait_for_room().await;
+        }
+    }
+}
+
+/// Subscriber to an agent's output (see `AgentHandle::subscribe`)
+///
+/// A subscriber that falls more than the channel capacity behind misses the
+/// oldest chunks. Instead of an error it then receives one
+/// `OutputChunk::Dropped` with how many it missed, and continues with the
+/// oldest chunk still buffered.
+pub struct OutputReceiver {
+    inner: broadcast::Receiver<ChunkEnvelope>,
+    /// Sequence number of the last chunk received
+    last_seq: u64,
+    /// Chunk received while working out where a gap ended
+    pending: Option<ChunkEnvelope>,
+}
+
+impl OutputReceiver {
+    /// Receive the next chunk
+    ///
+    /// Only fails with `RecvError::Closed`, once the agent and every handle
+    /// to it are gone.
+    pub async fn recv(&mut self) -> Result<OutputChunk, RecvError> {
+        self.recv_envelope().await.map(|envelope| envelope.chunk)
+    }
+
+    /// Receive the next chunk without waiting
+    pub fn try_recv(&mut self) -> Result<OutputChunk, TryRecvError> {
+        self.try_recv_envelope().map(|envelope| envelope.chunk)
+    }
+
+    /// Receive the next chunk with its sequence number and timestamp
+    pub async fn recv_envelope(&mut self) -> Result<ChunkEnvelope, RecvError> {
+        if let Some(envelope) = self.pending.take() {
+            return Ok(self.received(envelope));
+        }
+        match self.inner.recv().await {
+            Err(RecvError::Lagged(count)) => {
+                // The chunk after the gap tells where it ended
+                let next = self.inner.recv().await.ok();
+                Ok(self.dropped(count, next))
+            }
+            received => received.map(|envelope| self.received(envelope)),
+        }
+    }
+
+    /// Receive the next chunk with its sequence number and timestamp, without waiting
+    pub fn try_recv_envelope(&mut self) -> Result<ChunkEnvelope, TryRecvError> {
+        if let Some(envelope) = self.pending.take() {
+            return Ok(self.received(envelope));
+        }
+        match self.inner.try_recv() {
+            Err(TryRecvError::Lagged(count)) => {
+                let next = self.inner.try_recv().ok();
+                Ok(self.dropped(count, next))
+            }
+            received => received.map(|envelope| self.received(envelope)),
+        }
+    }
+
+    /// Create another subscriber starting from the next chunk sent
+    pub fn resubscribe(&self) -> Self {
+        Self {
+            inner: self.inner.resubscribe(),
+            last_seq: self.last_seq,
+            pending: None,
+        }
+    }
+
+    /// Only receive the chunks `filter` matches
+    pub fn filtered(self, filter: ChunkFilter) -> FilteredReceiver {
+        FilteredReceiver { inner: self, filter }
+    }
+
+    fn received(&mut self, envelope: ChunkEnvelope) -> ChunkEnvelope {
+        self.last_seq = envelope.seq;
+        envelope
+    }
+
+    /// The marker for `count` missed chunks, keeping `next` for the next call
+    fn dropped(&mut self, count: u64, next: Option<ChunkEnvelope>) -> ChunkEnvelope {
+        let seq = match &next {
+            Some(next) => next.seq - 1,
+            None => self.last_seq + count,
+        };
+        self.pending = next;
+        self.last_seq = seq;
+        ChunkEnvelope::new(seq, None, OutputChunk::Dropped { count })
+    }
+}
+
+/// Subscriber that skips chunks its filter doesn't match
+/// (see `AgentHandle::subscribe_filtered`)
+///
+/// Skipped chunks are dropped as they're received, without being returned.
+/// See the module docs for what that means for sequence numbers.
+pub struct FilteredReceiver {
+    inner: OutputReceiver,
+    filter: ChunkFilter,
+}
+
+impl FilteredReceiver {
+    /// Receive the next matching chunk
+    pub async fn recv(&mut self) -> Result<OutputChunk, RecvError> {
+        self.recv_envelope().await.map(|envelope| envelope.chunk)
+    }
+
+    /// Receive the next matching chunk without waiting
+    pub fn try_recv(&mut self) -> Result<OutputChunk, TryRecvError> {
+        self.try_recv_envelope().map(|envelope| envelope.chunk)
+    }
+
+    /// Receive the next matching chunk with its sequence number and timestamp
+    pub async fn recv_envelope(&mut self) -> Result<ChunkEnvelope, RecvError> {
+        loop {
+            let envelope = self.inner.recv_envelope().await?;
+            if self.filter.matches(&envelope.chunk) {
+                return Ok(envelope);
+            }
+        }
+    }
+
+    /// Receive the next matching chunk with its sequence number and timestamp, without waiting
+    pub fn try_recv_envelope(&mut self) -> Result<ChunkEnvelope, TryRecvError> {
+        loop {
+            let envelope = self.inner.try_recv_envelope()?;
+            if self.filter.matches(&envelope.chunk) {
+                return Ok(envelope);
+            }
+        }
+    }
+
+    /// The categories this subscriber receives
+    pub fn filter(&self) -> ChunkFilter {
+        self.filter
+    }
+}
+
+/// What a reliable subscriber's buffer does when it is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Hold the agent until the subscriber catches up
+    #[default]
+    Block,
+    /// Discard the oldest chunks and send a `Dropped` marker in their place
+    DropOldest,
+}
+
+/// Subscriber with its own buffer (see `OutputSender::subscribe_reliable`)
+pub struct ReliableReceiver {
+    queue: Arc<ReliableQueue>,
+}
+
+impl ReliableReceiver {
+    /// Receive the next chunk
+    ///
+    /// Returns `None` once the agent and every handle to it are gone and
+    /// everything buffered has been received.
+    pub async fn recv(&mut self) -> Option<OutputChunk> {
+        self.recv_envelope().await.map(|envelope| envelope.chunk)
+    }
+
+    /// Receive the next chunk without waiting
+    pub fn try_recv(&mut self) -> Option<OutputChunk> {
+        self.try_recv_envelope().map(|envelope| envelope.chunk)
+    }
+
+    /// Receive the next chunk with its sequence number and timestamp
+    pub async fn recv_envelope(&mut self) -> Option<ChunkEnvelope> {
+        loop {
+            match self.queue.pop() {
+                Next::Chunk(envelope) => return Some(envelope),
+                Next::Closed => return None,
+                Next::Empty => self.queue.readable.notified().await,
+            }
+        }
+    }
 
-/// Receiver half of the output broadcast channel (used by subscribers)
-pub type OutputReceiver = broadcast::Receiver<OutputChunk>;
+    /// Receive the next chunk with its sequence number and timestamp, without waiting
+    pub fn try_recv_envelope(&mut self) -> Option<ChunkEnvelope> {
+        match self.queue.pop() {
+            Next::Chunk(envelope) => Some(envelope),
+            Next::Empty | Next::Closed => None,
+        }
+    }
+}
+
+impl Drop for ReliableReceiver {
+    fn drop(&mut self) {
+        {
+            let mut state = self.queue.state.lock().unwrap();
+            state.abandoned = true;
+            state.chunks.clear();
+        }
+        self.queue.writable.notify_waiters();
+    }
+}
+
+/// Reliable subscribers shared by all clones of an `OutputSender`
+#[derive(Default)]
+struct ReliableSubscribers {
+    queues: Mutex<Vec<Arc<ReliableQueue>>>,
+}
+
+impl Drop for ReliableSubscribers {
+    /// The last sender is gone: let receivers finish what's buffered
+    fn drop(&mut self) {
+        let queues = self.queues.get_mut().unwrap();
+        for queue in queues.drain(..) {
+            queue.state.lock().unwrap().closed = true;
+            queue.readable.notify_one();
+        }
+    }
+}
+
+/// Buffer between the sender and one reliable subscriber
+struct ReliableQueue {
+    capacity: usize,
+    policy: OverflowPolicy,
+    state: Mutex<QueueState>,
+    /// Wakes the receiver when a chunk arrives or the senders are gone
+    readable: Notify,
+    /// Wakes waiting senders when the receiver takes a chunk or goes away
+    writable: Notify,
+}
+
+#[derive(Default)]
+struct QueueState {
+    chunks: VecDeque<ChunkEnvelope>,
+    /// Chunks discarded since the receiver last got a `Dropped` marker
+    dropped: u64,
+    /// Sequence number of the last chunk discarded
+    dropped_seq: u64,
+    /// All senders are gone
+    closed: bool,
+    /// The receiver was dropped
+    abandoned: bool,
+}
+
+/// Result of taking from a reliable queue
+enum Next {
+    Chunk(ChunkEnvelope),
+    Empty,
+    Closed,
+}
+
+impl ReliableQueue {
+    /// Buffer a chunk; returns false if the receiver is gone
+    fn push(&self, envelope: ChunkEnvelope) -> bool {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.abandoned {
+                return false;
+            }
+            if self.policy == OverflowPolicy::DropOldest && state.chunks.len() >= self.capacity {
+                if let Some(oldest) = state.chunks.pop_front() {
+                    state.dropped += 1;
+                    state.dropped_seq = oldest.seq;
+                }
+            }
+            state.chunks.push_back(envelope);
+        }
+        self.readable.notify_one();
+        true
+    }
+
+    fn pop(&self) -> Next {
+        let next = {
+            let mut state = self.state.lock().unwrap();
+            if state.dropped > 0 {
+                let count = std::mem::take(&mut state.dropped);
+                return Next::Chunk(ChunkEnvelope::new(state.dropped_seq, None, OutputChunk::Dropped { count }));
+            }
+            match state.chunks.pop_front() {
+                Some(chunk) => Next::Chunk(chunk),
+                None if state.closed => return Next::Closed,
+                None => return Next::Empty,
+            }
+        };
+        self.writable.notify_waiters();
+        next
+    }
+
+    async fn wait_for_room(&self) {
+        loop {
+            // Register before checking so a pop in between isn't missed
+            let writable = self.writable.notified();
+            {
+                let state = self.state.lock().unwrap();
+                if state.abandoned || state.chunks.len() < self.capacity {
+                    return;
+                }
+            }
+            writable.await;
+        }
+    }
+}
+
+// ============================================================================
+// Interrupt Signal
+// ============================================================================
+
+/// Cancels the agent's running tool call when an interrupt is sent
+///
+/// `InputMessage::Interrupt` waits in the input channel until the agent
+/// reads it, which it doesn't do while a tool runs. `AgentHandle` also
+/// trips this signal, and the executor watches it while a tool runs.
+/// Clones share the signal.
+#[derive(Clone, Default)]
+pub struct InterruptSignal {
+    current: Arc<Mutex<Option<CancellationToken>>>,
+}
+
+impl InterruptSignal {
+    /// A signal with no call running
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token for the call about to run, cancelled by `interrupt`
+    pub fn arm(&self) -> CancellationToken {
+        self.arm_child(&CancellationToken::new())
+    }
+
+    /// Like `arm`, with a token that is also cancelled along with `parent`
+    pub fn arm_child(&self, parent: &CancellationToken) -> CancellationToken {
+        let token = parent.child_token();
+        *self.current.lock().unwrap() = Some(token.clone());
+        token
+    }
+
+    /// Stop watching the call that just finished
+    pub fn disarm(&self) {
+        self.current.lock().unwrap().take();
+    }
+
+    /// Cancel the running call, returning false if none was running
+    pub fn interrupt(&self) -> bool {
+        match self.current.lock().unwrap().take() {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
 
 // ============================================================================
 // Channel Creation
@@ -47,8 +585,14 @@ pub fn create_input_channel() -> (InputSender, InputReceiver) {
 /// Returns the sender. Receivers are created by calling `sender.subscribe()`.
 /// Multiple subscribers can receive the same output chunks.
 pub fn create_output_channel() -> OutputSender {
-    let (tx, _) = broadcast::channel(OUTPUT_CHANNEL_SIZE);
-    tx
+    OutputSender::new(OUTPUT_CHANNEL_SIZE)
+}
+
+/// Create a new state channel, starting at `AgentState::Idle`
+///
+/// Receivers are created by calling `sender.subscribe()`.
+pub fn create_state_channel() -> StateSender {
+    watch::Sender::new(AgentState::Idle)
 }
 
 /// Create both input and output channels
@@ -178,6 +722,160 @@ mod tests {
         assert!(matches!(chunk, OutputChunk::TextDelta(s) if s == "Late"));
     }
 
+    fn texts(chunks: &[OutputChunk]) -> Vec<String> {
+        chunks
+            .iter()
+            .map(|chunk| match chunk {
+                OutputChunk::TextDelta(text) => text.clone(),
+                other => format!("{:?}", other),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscriber_gets_dropped_marker() {
+        let tx = OutputSender::new(4);
+        let mut rx = tx.subscribe();
+
+        for i in 0..10 {
+            tx.send(OutputChunk::TextDelta(i.to_string())).unwrap();
+        }
+
+        let mut received = vec![rx.recv().await.unwrap()];
+        while let Ok(chunk) = rx.try_recv() {
+            received.push(chunk);
+        }
+        assert!(matches!(received[0], OutputChunk::Dropped { count: 6 }));
+        assert_eq!(texts(&received[1..]), vec!["6", "7", "8", "9"]);
+    }
+
+    #[tokio::test]
+    async fn test_filtered_subscriber_skips_other_chunks() {
+        let tx = OutputSender::new(4);
+        let mut rx = tx.subscribe_filtered(ChunkFilter::TOOLS);
+
+        tx.send(OutputChunk::text("a")).unwrap();
+        tx.send(OutputChunk::tool_start("toolu_1", "Bash", serde_json::json!({}))).unwrap();
+        tx.send(OutputChunk::text("b")).unwrap();
+        tx.send(OutputChunk::Done).unwrap();
+
+        let start = rx.recv_envelope().await.unwrap();
+        assert!(matches!(start.chunk, OutputChunk::ToolStart { .. }));
+        assert_eq!(start.seq, 2);
+        // seq 3 was skipped
+        let done = rx.recv_envelope().await.unwrap();
+        assert!(matches!(done.chunk, OutputChunk::Done));
+        assert_eq!(done.seq, 4);
+        assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+
+        // Lag is still reported, counting skipped chunks
+        for i in 0..10 {
+            tx.send(OutputChunk::text(i.to_string())).unwrap();
+        }
+        assert!(matches!(rx.recv().await.unwrap(), OutputChunk::Dropped { count: 6 }));
+        assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+    }
+
+    #[tokio::test]
+    async fn test_reliable_block_loses_nothing() {
+        let tx = OutputSender::new(4);
+        let mut reliable = tx.subscribe_reliable(2, OverflowPolicy::Block);
+        let mut lossy = tx.subscribe();
+
+        let producer = tokio::spawn(async move {
+            for i in 0..40 {
+                tx.send(OutputChunk::TextDelta(i.to_string())).unwrap();
+                tx.wait_for_capacity().await;
+            }
+        });
+
+        // A consumer much slower than the producer
+        let mut received = Vec::new();
+        while let Some(chunk) = reliable.recv().await {
+            received.push(chunk);
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+        }
+        producer.await.unwrap();
+
+        let expected: Vec<String> = (0..40).map(|i| i.to_string()).collect();
+        assert_eq!(texts(&received), expected);
+
+        // The broadcast subscriber that never read fell behind
+        assert!(matches!(lossy.recv().await.unwrap(), OutputChunk::Dropped { count: 36 }));
+    }
+
+    #[tokio::test]
+    async fn test_reliable_block_waits_for_receiver() {
+        let tx = OutputSender::new(4);
+        let rx = tx.subscribe_reliable(2, OverflowPolicy::Block);
+        tx.send(OutputChunk::TextDelta("one".into())).unwrap();
+        tx.send(OutputChunk::TextDelta("two".into())).unwrap();
+        assert_eq!(tx.receiver_count(), 1);
+
+        let wait = std::time::Duration::from_millis(50);
+        assert!(tokio::time::timeout(wait, tx.wait_for_capacity()).await.is_err());
+
+        // A receiver that goes away no longer holds the sender up
+        drop(rx);
+        assert!(tokio::time::timeout(wait, tx.wait_for_capacity()).await.is_ok());
+        assert_eq!(tx.receiver_count(), 0);
+        assert!(tx.send(OutputChunk::Done).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reliable_drop_oldest() {
+        let tx = OutputSender::new(4);
+        let mut rx = tx.subscribe_reliable(3, OverflowPolicy::DropOldest);
+
+        for i in 0..10 {
+            tx.send(OutputChunk::TextDelta(i.to_string())).unwrap();
+        }
+        // Never waits with DropOldest
+        tx.wait_for_capacity().await;
+        drop(tx);
+
+        let mut received = Vec::new();
+        while let Some(chunk) = rx.recv().await {
+            received.push(chunk);
+        }
+        assert!(matches!(received[0], OutputChunk::Dropped { count: 7 }));
+        assert_eq!(texts(&received[1..]), vec!["7", "8", "9"]);
+    }
+
+    #[tokio::test]
+    async fn test_sequence_numbers_stay_in_order() {
+        let tx = OutputSender::new(4);
+        let mut lossy = tx.subscribe();
+        let mut reliable = tx.subscribe_reliable(3, OverflowPolicy::DropOldest);
+
+        let clone = tx.clone();
+        for i in 0..10 {
+            let sender = if i % 2 == 0 { &tx } else { &clone };
+            sender.send_in_turn(2, OutputChunk::TextDelta(i.to_string())).unwrap();
+        }
+        assert_eq!(tx.last_seq(), 10);
+
+        // Markers take the number of the last chunk they stand for
+        let seqs = |received: Vec<ChunkEnvelope>| received.iter().map(|e| e.seq).collect::<Vec<_>>();
+        let mut received = vec![lossy.recv_envelope().await.unwrap()];
+        received.extend(std::iter::from_fn(|| lossy.try_recv_envelope().ok()));
+        assert!(matches!(received[0].chunk, OutputChunk::Dropped { count: 6 }));
+        assert_eq!(received[1].turn, Some(2));
+        assert_eq!(seqs(received), vec![6, 7, 8, 9, 10]);
+
+        let received: Vec<_> = std::iter::from_fn(|| reliable.try_recv_envelope()).collect();
+        assert!(matches!(received[0].chunk, OutputChunk::Dropped { count: 7 }));
+        assert_eq!(seqs(received), vec![7, 8, 9, 10]);
+
+        // A relay keeps the numbers it is given and continues after them
+        let relay = OutputSender::new(4);
+        let mut rx = relay.subscribe();
+        relay.forward(ChunkEnvelope::new(41, Some(3), OutputChunk::Done)).unwrap();
+        relay.send(OutputChunk::error("lost")).unwrap();
+        assert_eq!(rx.recv_envelope().await.unwrap().seq, 41);
+        assert_eq!(rx.recv_envelope().await.unwrap().seq, 42);
+    }
+
     #[tokio::test]
     async fn test_send_without_subscribers() {
         let tx = create_output_channel();
@@ -186,4 +884,20 @@ mod tests {
         let result = tx.send(OutputChunk::TextDelta("Nobody listening".into()));
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_interrupt_signal() {
+        let signal = InterruptSignal::new();
+        assert!(!signal.interrupt());
+
+        let token = signal.arm();
+        assert!(signal.clone().interrupt());
+        assert!(token.is_cancelled());
+
+        // A finished call is out of reach
+        let token = signal.arm();
+        signal.disarm();
+        assert!(!signal.interrupt());
+        assert!(!token.is_cancelled());
+    }
 }
@@ -7,14 +7,22 @@
 //! - Check agent state
 //! - Request interrupt or shutdown
 
+use std::collections::HashMap;
 use std::sync::Arc;
+use async_trait::async_trait;
 use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
 
-use crate::core::{AgentState, FrameworkError, FrameworkResult, InputMessage};
+use crate::core::{AgentState, ChunkFilter, FrameworkError, FrameworkResult, InputMessage, InputOptions, OutputChunk, TurnSummary};
+use crate::helpers::{Attachment, FileChangeJournal, RollbackReport};
+use crate::permissions::{PermissionRule, PermissionRules, PermissionScope, RuleId};
 use crate::session::AgentSession;
 use crate::tools::ToolResult;
 
-use super::channels::{InputSender, OutputReceiver, OutputSender};
+use super::channels::{
+    FilteredReceiver, InputSender, InterruptSignal, OutputReceiver, OutputSender, OverflowPolicy, ReliableReceiver,
+    StateReceiver, StateSender, OUTPUT_CHANNEL_SIZE,
+};
 
 /// Handle for interacting with a running agent
 ///
@@ -35,7 +43,16 @@ pub struct AgentHandle {
     output_tx: OutputSender,
 
     /// Current agent state
-    state: Arc<RwLock<AgentState>>,
+    state: StateSender,
+
+    /// The agent's permission rules (shared with its PermissionManager)
+    permissions: PermissionRules,
+
+    /// Cancels the agent's running tool call (shared with its AgentInternals)
+    interrupt: InterruptSignal,
+
+    /// Cancelled on shutdown (shared with its AgentInternals)
+    cancel: CancellationToken,
 }
 
 impl AgentHandle {
@@ -47,7 +64,8 @@ impl AgentHandle {
         session: Arc<RwLock<AgentSession>>,
         input_tx: InputSender,
         output_tx: OutputSender,
-        state: Arc<RwLock<AgentState>>,
+        state: StateSender,
+        permissions: PermissionRules,
     ) -> Self {
         Self {
             session_id: session_id.into(),
@@ -55,9 +73,30 @@ impl AgentHandle {
             input_tx,
             output_tx,
             state,
+            permissions,
+            interrupt: InterruptSignal::new(),
+            cancel: CancellationToken::new(),
         }
     }
 
+    /// Cancel the agent's running tool call through `signal` on interrupt
+    ///
+    /// Pass the agent's `AgentInternals::interrupt_signal`; `AgentRuntime`
+    /// does this for the agents it spawns.
+    pub fn with_interrupt_signal(mut self, signal: InterruptSignal) -> Self {
+        self.interrupt = signal;
+        self
+    }
+
+    /// Cancel `token` on shutdown
+    ///
+    /// Pass the agent's `AgentInternals::cancellation_token`; `AgentRuntime`
+    /// does this for the agents it spawns.
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
     /// Get the session ID
     pub fn session_id(&self) -> &str {
         &self.session_id
@@ -72,6 +111,49 @@ impl AgentHandle {
         self.send(InputMessage::UserInput(input.into())).await
     }
 
+    /// Send user input with files attached
+    ///
+    /// ```ignore
+    /// handle.send_input_with_attachments("What's wrong here?", vec![
+    ///     Attachment::bytes(screenshot_png, "image/png").with_name("screen.png"),
+    ///     Attachment::path("logs/error.log"),
+    /// ]).await?;
+    /// ```
+    pub async fn send_input_with_attachments(
+        &self,
+        text: impl Into<String>,
+        attachments: Vec<Attachment>,
+    ) -> FrameworkResult<()> {
+        self.send(InputMessage::UserInputWithAttachments {
+            text: text.into(),
+            attachments,
+        })
+        .await
+    }
+
+    /// Send user input with per-turn options
+    ///
+    /// ```ignore
+    /// // Make the model plan before doing anything else
+    /// handle.send_input_with_options(
+    ///     "Refactor the parser",
+    ///     InputOptions::new().with_tool_choice(ToolChoice::tool("TodoWrite")),
+    /// ).await?;
+    /// ```
+    pub async fn send_input_with_options(
+        &self,
+        text: impl Into<String>,
+        options: InputOptions,
+    ) -> FrameworkResult<()> {
+        self.send(InputMessage::UserInputWithOptions {
+            text: text.into(),
+            attachments: options.attachments,
+            tool_choice: options.tool_choice,
+            turn_timeout: options.turn_timeout,
+        })
+        .await
+    }
+
     /// Send a tool result to the agent
     ///
     /// Used when tools execute asynchronously and need to report back.
@@ -87,17 +169,65 @@ impl AgentHandle {
         .await
     }
 
-    /// Send a permission response to the agent
+    /// Answer the `PermissionRequest` with this `request_id`
+    ///
+    /// Requests can be answered in any order. Answers to requests that are
+    /// unknown or already timed out are ignored.
+    pub async fn send_permission_response_by_id(
+        &self,
+        request_id: impl Into<String>,
+        allowed: bool,
+        remember: bool,
+    ) -> FrameworkResult<()> {
+        self.send(InputMessage::permission_for(request_id, allowed, remember)).await
+    }
+
+    /// Send a permission response to the agent, addressed by tool name
+    ///
+    /// Answers the oldest open request for `tool_name`, which is ambiguous
+    /// when several are pending.
+    #[deprecated(note = "use `send_permission_response_by_id` with the request's `request_id`")]
     pub async fn send_permission_response(
         &self,
         tool_name: impl Into<String>,
         allowed: bool,
         remember: bool,
     ) -> FrameworkResult<()> {
-        self.send(InputMessage::PermissionResponse {
-            tool_name: tool_name.into(),
-            allowed,
-            remember,
+        #[allow(deprecated)]
+        self.send(InputMessage::permission(tool_name, allowed, remember)).await
+    }
+
+    /// Send the answers to an `AskUserQuestion` request
+    ///
+    /// `answers` maps each question's header to the chosen option label(s)
+    /// or free text. The agent's `AskUserQuestion` tool is blocked until this
+    /// arrives.
+    pub async fn send_question_response(
+        &self,
+        request_id: impl Into<String>,
+        answers: HashMap<String, String>,
+    ) -> FrameworkResult<()> {
+        self.send(InputMessage::UserQuestionResponse {
+            request_id: request_id.into(),
+            answers,
+        })
+        .await
+    }
+
+    /// Rewind to a previous user turn and send `text` in its place
+    ///
+    /// `message_index` is an index into the history; it is snapped back to
+    /// the user message that starts its turn, so a tool call is never
+    /// separated from its result. The agent sends `HistoryTruncated` and
+    /// then runs `text` as a new turn. Only handled while the agent is idle.
+    pub async fn rewind_and_send(
+        &self,
+        message_index: usize,
+        text: impl Into<String>,
+    ) -> FrameworkResult<()> {
+        self.send(InputMessage::RewindAndResend {
+            message_index,
+            new_text: text.into(),
         })
         .await
     }
@@ -117,30 +247,47 @@ impl AgentHandle {
 
     /// Request graceful interrupt
     ///
-    /// The agent should stop at the next safe point.
+    /// The agent should stop at the next safe point. A running tool call is
+    /// cancelled and recorded as interrupted.
     pub async fn interrupt(&self) -> FrameworkResult<()> {
         self.send(InputMessage::Interrupt).await
     }
 
     /// Request shutdown
     ///
-    /// The agent should terminate as soon as possible.
+    /// Cancels the agent's cancellation token, so a running LLM call,
+    /// permission wait or tool call stops too; the agent saves its session
+    /// and sends `Done` before it exits.
     pub async fn shutdown(&self) -> FrameworkResult<()> {
         self.send(InputMessage::Shutdown).await
     }
 
     /// Send any input message to the agent
     pub async fn send(&self, message: InputMessage) -> FrameworkResult<()> {
+        let interrupt = matches!(message, InputMessage::Interrupt);
+        // Before queueing, so a full channel can't hold it up
+        if matches!(message, InputMessage::Shutdown) {
+            self.cancel.cancel();
+        }
         self.input_tx
             .send(message)
             .await
-            .map_err(|_| FrameworkError::ChannelClosed)
+            .map_err(|_| FrameworkError::ChannelClosed)?;
+        // Queued first, so the agent finds it once the tool has stopped
+        if interrupt {
+            self.interrupt.interrupt();
+        }
+        Ok(())
     }
 
     /// Try to send input without waiting (non-blocking)
     ///
     /// Returns an error if the channel is full or closed.
     pub fn try_send(&self, message: InputMessage) -> FrameworkResult<()> {
+        let interrupt = matches!(message, InputMessage::Interrupt);
+        if matches!(message, InputMessage::Shutdown) {
+            self.cancel.cancel();
+        }
         self.input_tx
             .try_send(message)
             .map_err(|e| match e {
@@ -148,7 +295,11 @@ impl AgentHandle {
                     FrameworkError::SendError("Channel full".into())
                 }
                 tokio::sync::mpsc::error::TrySendError::Closed(_) => FrameworkError::ChannelClosed,
-            })
+            })?;
+        if interrupt {
+            self.interrupt.interrupt();
+        }
+        Ok(())
     }
 
     // =========================================================================
@@ -158,11 +309,60 @@ impl AgentHandle {
     /// Subscribe to agent output
     ///
     /// Returns a receiver that will get all output chunks from this point forward.
-    /// Multiple subscribers can exist simultaneously.
+    /// Multiple subscribers can exist simultaneously. A subscriber that falls
+    /// behind gets an `OutputChunk::Dropped` in place of the chunks it missed.
     pub fn subscribe(&self) -> OutputReceiver {
         self.output_tx.subscribe()
     }
 
+    /// Subscribe to some categories of output only
+    ///
+    /// Other chunks are skipped as they arrive, so sequence numbers have
+    /// gaps; `Done` and `Dropped` always get through. See
+    /// `runtime::channels`.
+    ///
+    /// ```ignore
+    /// let mut rx = handle.subscribe_filtered(ChunkFilter::TOOLS | ChunkFilter::PERMISSIONS);
+    /// ```
+    pub fn subscribe_filtered(&self, filter: ChunkFilter) -> FilteredReceiver {
+        self.output_tx.subscribe_filtered(filter)
+    }
+
+    /// Subscribe to tool chunks (`ToolStart`, `ToolEnd`, ...) and `Done`
+    pub fn subscribe_tools(&self) -> FilteredReceiver {
+        self.subscribe_filtered(ChunkFilter::TOOLS)
+    }
+
+    /// Subscribe to state changes, status, errors, `TurnComplete` and `Done`
+    pub fn subscribe_lifecycle(&self) -> FilteredReceiver {
+        self.subscribe_filtered(ChunkFilter::LIFECYCLE)
+    }
+
+    /// Subscribe with a buffer of its own, for subscribers that can't lose output
+    ///
+    /// With `OverflowPolicy::Block` the agent waits for this subscriber when
+    /// its `capacity` chunks are all unread; with `DropOldest` the oldest
+    /// are replaced by a `Dropped` marker instead. See `runtime::channels`
+    /// for the tradeoffs.
+    ///
+    /// ```ignore
+    /// let mut rx = handle.subscribe_reliable(1024, OverflowPolicy::Block);
+    /// while let Some(chunk) = rx.recv().await {
+    ///     transcript.write(&chunk)?;
+    /// }
+    /// ```
+    pub fn subscribe_reliable(&self, capacity: usize, policy: OverflowPolicy) -> ReliableReceiver {
+        self.output_tx.subscribe_reliable(capacity, policy)
+    }
+
+    /// Summary of the agent's most recent turn (None before the first ends)
+    ///
+    /// Kept from the last `OutputChunk::TurnComplete`, so it's available
+    /// without subscribing before the turn started.
+    pub fn last_turn(&self) -> Option<TurnSummary> {
+        self.output_tx.last_turn()
+    }
+
     /// Get the number of current subscribers
     pub fn subscriber_count(&self) -> usize {
         self.output_tx.receiver_count()
@@ -174,33 +374,46 @@ impl AgentHandle {
 
     /// Get the current agent state
     pub async fn state(&self) -> AgentState {
-        self.state.read().await.clone()
+        self.state.borrow().clone()
+    }
+
+    /// Subscribe to state changes
+    ///
+    /// The receiver always holds the latest state, so unlike `StateChange`
+    /// chunks on the output stream, a slow reader never misses where the
+    /// agent is now.
+    ///
+    /// ```ignore
+    /// let mut states = handle.subscribe_state();
+    /// states.wait_for(|s| s.is_blocked_on_user()).await?;
+    /// ```
+    pub fn subscribe_state(&self) -> StateReceiver {
+        self.state.subscribe()
     }
 
     /// Check if the agent is idle (waiting for input)
     pub async fn is_idle(&self) -> bool {
-        matches!(*self.state.read().await, AgentState::Idle)
+        matches!(*self.state.borrow(), AgentState::Idle)
     }
 
     /// Check if the agent is processing
     pub async fn is_processing(&self) -> bool {
-        matches!(*self.state.read().await, AgentState::Processing)
+        matches!(*self.state.borrow(), AgentState::Processing)
     }
 
     /// Check if the agent is done
     pub async fn is_done(&self) -> bool {
-        matches!(*self.state.read().await, AgentState::Done)
+        matches!(*self.state.borrow(), AgentState::Done)
     }
 
     /// Check if the agent has errored
     pub async fn is_error(&self) -> bool {
-        matches!(*self.state.read().await, AgentState::Error { .. })
+        matches!(*self.state.borrow(), AgentState::Error { .. })
     }
 
     /// Check if the agent is still running (not done and not errored)
     pub async fn is_running(&self) -> bool {
-        let state = self.state.read().await;
-        !matches!(*state, AgentState::Done | AgentState::Error { .. })
+        !matches!(*self.state.borrow(), AgentState::Done | AgentState::Error { .. })
     }
 
     /// Wait until the agent reaches a terminal state (Done or Error)
@@ -216,6 +429,57 @@ impl AgentHandle {
         }
     }
 
+    /// Send input and collect the output of the resulting turn
+    ///
+    /// Returns every chunk up to and including `Done`. Permission requests and
+    /// questions are not answered here, so use this with tools that don't need
+    /// approval (or with `dangerous_skip_permissions`), or answer them from
+    /// another subscriber.
+    pub async fn send_and_wait(
+        &self,
+        input: impl Into<String>,
+    ) -> FrameworkResult<Vec<OutputChunk>> {
+        // Subscribe before sending, and reliably, so no output is missed
+        let mut rx = self.subscribe_reliable(OUTPUT_CHANNEL_SIZE, OverflowPolicy::Block);
+        self.send_input(input).await?;
+
+        let mut chunks = Vec::new();
+        while let Some(chunk) = rx.recv().await {
+            let done = matches!(
+                chunk,
+                OutputChunk::Done | OutputChunk::StateChange(AgentState::Done)
+            );
+            chunks.push(chunk);
+            if done {
+                return Ok(chunks);
+            }
+        }
+        Err(FrameworkError::ChannelClosed)
+    }
+
+    /// Send input and wait for the turn's structured result
+    ///
+    /// The agent must be configured with `AgentConfig::with_output_schema`.
+    /// Fails with the agent's error if the answer never matched the schema.
+    pub async fn wait_for_structured_result(
+        &self,
+        input: impl Into<String>,
+    ) -> FrameworkResult<serde_json::Value> {
+        let chunks = self.send_and_wait(input).await?;
+
+        let mut error = None;
+        for chunk in chunks.into_iter().rev() {
+            match chunk {
+                OutputChunk::StructuredResult(value) => return Ok(value),
+                OutputChunk::Error(e) if error.is_none() => error = Some(e),
+                _ => {}
+            }
+        }
+        Err(FrameworkError::Other(
+            error.unwrap_or_else(|| "Turn ended without a structured result".to_string()),
+        ))
+    }
+
     // =========================================================================
     // Session Metadata Methods
     // =========================================================================
@@ -247,6 +511,28 @@ impl AgentHandle {
         session.get_custom(key).cloned()
     }
 
+    /// Undo the file changes the agent made in `turn` and every turn after
+    ///
+    /// Files are put back as they were when `turn` started, newest change
+    /// first. A file changed outside the agent since is left alone and
+    /// listed in the report's `skipped`. Call it while the agent is idle.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let report = handle.rollback_turn(2).await?;
+    /// println!("{}", report.describe());
+    /// ```
+    pub async fn rollback_turn(&self, turn: usize) -> FrameworkResult<RollbackReport> {
+        let path = {
+            let session = self.session.read().await;
+            session.storage().changes_path(session.session_id())
+        };
+        let report = FileChangeJournal::new(path).rollback_turn(turn)?;
+        tracing::info!("[AgentHandle] Rolled back turn {} for '{}'", turn, self.session_id);
+        Ok(report)
+    }
+
     /// **DANGEROUS:** Enable or disable permission checks at runtime
     ///
     /// When enabled, tools execute without asking for user permission.
@@ -287,6 +573,48 @@ impl AgentHandle {
             .unwrap_or(false)
     }
 
+    /// Turn dry-run mode on or off
+    ///
+    /// In dry-run mode, tools that aren't read-only (or exempt through
+    /// `Tool::runs_in_dry_run`) are described to the model instead of run (see `AgentConfig::with_dry_run`). Takes effect
+    /// from the next tool call.
+    pub async fn set_dry_run(&self, enabled: bool) -> FrameworkResult<()> {
+        tracing::info!(
+            "[AgentHandle] Dry run {} for '{}'",
+            if enabled { "enabled" } else { "disabled" },
+            self.session_id
+        );
+        self.set_custom_metadata("dry_run", enabled).await
+    }
+
+    /// Check if dry-run mode is currently enabled
+    pub async fn is_dry_run_enabled(&self) -> bool {
+        self.get_custom_metadata("dry_run")
+            .await
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+    }
+
+    /// The agent's permission rules at every scope
+    ///
+    /// Use this to list rules with their ids, add rules, or revoke them while
+    /// the agent runs; changes apply from the agent's next check.
+    pub fn permission_rules(&self) -> &PermissionRules {
+        &self.permissions
+    }
+
+    /// Add a rule for this session only
+    pub fn add_session_rule(&self, rule: PermissionRule) -> RuleId {
+        self.permissions.add_rule(rule, PermissionScope::Session)
+    }
+
+    /// Revoke a rule by id, at whichever scope holds it
+    ///
+    /// Returns false if no rule has the id.
+    pub fn remove_rule(&self, id: RuleId) -> bool {
+        self.permissions.remove_rule(id).is_some()
+    }
+
     /// Get the conversation name
     pub async fn conversation_name(&self) -> Option<String> {
         let session = self.session.read().await;
@@ -301,6 +629,74 @@ impl AgentHandle {
     }
 }
 
+/// The part of a handle renderers use: send input, subscribe to output
+///
+/// Implemented by `AgentHandle` and, with the `remote` feature, by
+/// `remote::RemoteAgentHandle`, so a renderer written against this trait
+/// (like `ConsoleRenderer`) works with local and remote agents alike.
+#[async_trait]
+pub trait AgentConnection: Send + Sync {
+    /// Send any input message to the agent
+    async fn send(&self, message: InputMessage) -> FrameworkResult<()>;
+
+    /// Subscribe to agent output from this point forward
+    fn subscribe(&self) -> OutputReceiver;
+
+    /// The agent's permission rules, if they are reachable from here
+    fn permission_rules(&self) -> Option<&PermissionRules> {
+        None
+    }
+
+    /// Send user input to the agent
+    async fn send_input(&self, input: &str) -> FrameworkResult<()> {
+        self.send(InputMessage::UserInput(input.to_string())).await
+    }
+
+    /// Answer the `PermissionRequest` with this `request_id`
+    async fn send_permission_response_by_id(
+        &self,
+        request_id: String,
+        allowed: bool,
+        remember: bool,
+    ) -> FrameworkResult<()> {
+        self.send(InputMessage::permission_for(request_id, allowed, remember)).await
+    }
+
+    /// Send the answers to an `AskUserQuestion` request
+    async fn send_question_response(
+        &self,
+        request_id: String,
+        answers: HashMap<String, String>,
+    ) -> FrameworkResult<()> {
+        self.send(InputMessage::UserQuestionResponse { request_id, answers }).await
+    }
+
+    /// Request graceful interrupt
+    async fn interrupt(&self) -> FrameworkResult<()> {
+        self.send(InputMessage::Interrupt).await
+    }
+
+    /// Request shutdown
+    async fn shutdown(&self) -> FrameworkResult<()> {
+        self.send(InputMessage::Shutdown).await
+    }
+}
+
+#[async_trait]
+impl AgentConnection for AgentHandle {
+    async fn send(&self, message: InputMessage) -> FrameworkResult<()> {
+        AgentHandle::send(self, message).await
+    }
+
+    fn subscribe(&self) -> OutputReceiver {
+        AgentHandle::subscribe(self)
+    }
+
+    fn permission_rules(&self) -> Option<&PermissionRules> {
+        Some(AgentHandle::permission_rules(self))
+    }
+}
+
 impl std::fmt::Debug for AgentHandle {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("AgentHandle")
@@ -314,7 +710,7 @@ impl std::fmt::Debug for AgentHandle {
 mod tests {
     use super::*;
     use crate::core::OutputChunk;
-    use crate::runtime::channels::create_agent_channels;
+    use crate::runtime::channels::{create_agent_channels, create_state_channel};
     use crate::session::{AgentSession, SessionStorage};
     use tempfile::TempDir;
 
@@ -332,8 +728,7 @@ mod tests {
         let session = Arc::new(RwLock::new(session));
 
         let (input_tx, input_rx, output_tx) = create_agent_channels();
-        let state = Arc::new(RwLock::new(AgentState::Idle));
-        let handle = AgentHandle::new("test-session", session, input_tx, output_tx, state);
+        let handle = AgentHandle::new("test-session", session, input_tx, output_tx, create_state_channel(), PermissionRules::default());
         (handle, input_rx, temp_dir)
     }
 
@@ -407,17 +802,20 @@ mod tests {
         let session = Arc::new(RwLock::new(session));
 
         let (input_tx, _input_rx, output_tx) = create_agent_channels();
-        let state = Arc::new(RwLock::new(AgentState::Idle));
-        let handle = AgentHandle::new("test", session, input_tx, output_tx, state.clone());
+        let state = create_state_channel();
+        let handle = AgentHandle::new("test", session, input_tx, output_tx, state.clone(), PermissionRules::default());
+        let mut states = handle.subscribe_state();
 
         assert!(handle.is_idle().await);
         assert!(handle.is_running().await);
 
         // Change state
-        *state.write().await = AgentState::Processing;
+        state.send_replace(AgentState::Processing);
         assert!(handle.is_processing().await);
+        assert!(states.has_changed().unwrap());
+        assert_eq!(*states.borrow_and_update(), AgentState::Processing);
 
-        *state.write().await = AgentState::Done;
+        state.send_replace(AgentState::Done);
         assert!(handle.is_done().await);
         assert!(!handle.is_running().await);
     }
@@ -433,7 +831,7 @@ mod tests {
         let (handle, mut rx, _temp) = create_test_handle();
 
         handle
-            .send_permission_response("Bash", true, false)
+            .send_permission_response_by_id("req-1", true, false)
             .await
             .unwrap();
 
@@ -441,10 +839,22 @@ mod tests {
         assert!(matches!(
             msg,
             InputMessage::PermissionResponse {
-                tool_name,
+                request_id: Some(id),
+                tool_name: None,
                 allowed: true,
                 remember: false,
-            } if tool_name == "Bash"
+            } if id == "req-1"
+        ));
+
+        #[allow(deprecated)]
+        handle
+            .send_permission_response("Bash", true, false)
+            .await
+            .unwrap();
+        let msg = rx.recv().await.unwrap();
+        assert!(matches!(
+            msg,
+            InputMessage::PermissionResponse { request_id: None, tool_name: Some(t), .. } if t == "Bash"
         ));
     }
 
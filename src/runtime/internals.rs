@@ -9,15 +9,37 @@
 
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 use crate::core::{AgentContext, AgentState, FrameworkError, FrameworkResult, InputMessage, OutputChunk};
 use crate::core::output::UserQuestion;
-use crate::permissions::{CheckResult, PermissionManager, PermissionRule, PermissionScope};
+use crate::hooks::{HookContext, HookRegistry, PermissionDecision, SubAgentSpawn};
+use crate::llm::Usage;
+use crate::permissions::{
+    CheckResult, PermissionManager, PermissionRule, PermissionScope, PermissionTimeout, ResponseTarget,
+    RuleId, TimeoutDecision,
+};
 use crate::session::AgentSession;
 
-use super::channels::{InputReceiver, OutputSender};
+use super::channels::{InputReceiver, InterruptSignal, OutputSender, StateSender};
+
+/// Outcome of waiting for a permission response
+#[derive(Debug)]
+pub enum PermissionWait {
+    /// The request was answered
+    Answered {
+        /// Whether permission was granted
+        allowed: bool,
+        /// Whether to remember the decision
+        remember: bool,
+    },
+    /// Another message arrived (or the channel closed) first
+    Received(Option<InputMessage>),
+    /// Nobody answered in time; the timeout's decision applies
+    TimedOut(PermissionTimeout),
+}
 
 /// Internal state and channels for an agent
 ///
@@ -41,7 +63,19 @@ pub struct AgentInternals {
     output_tx: OutputSender,
 
     /// Current agent state (shared with AgentHandle)
-    state: Arc<RwLock<AgentState>>,
+    state: StateSender,
+
+    /// Messages set aside to handle after the current turn
+    deferred: VecDeque<InputMessage>,
+
+    /// Tripped by the handle to cancel the running tool call
+    interrupt: InterruptSignal,
+
+    /// Cancelled when the agent is shut down (shared with the handle)
+    cancel: CancellationToken,
+
+    /// Child of `cancel` for the current turn, cancelled when it ends
+    turn_cancel: CancellationToken,
 }
 
 impl AgentInternals {
@@ -54,8 +88,9 @@ impl AgentInternals {
         permissions: PermissionManager,
         input_rx: InputReceiver,
         output_tx: OutputSender,
-        state: Arc<RwLock<AgentState>>,
+        state: StateSender,
     ) -> Self {
+        let cancel = CancellationToken::new();
         Self {
             session,
             context,
@@ -63,9 +98,78 @@ impl AgentInternals {
             input_rx,
             output_tx,
             state,
+            deferred: VecDeque::new(),
+            interrupt: InterruptSignal::new(),
+            turn_cancel: cancel.child_token(),
+            cancel,
         }
     }
 
+    /// The signal that cancels the running tool call
+    ///
+    /// `AgentRuntime::spawn` gives it to the agent's handle (see
+    /// `AgentHandle::with_interrupt_signal`).
+    pub fn interrupt_signal(&self) -> &InterruptSignal {
+        &self.interrupt
+    }
+
+    // =========================================================================
+    // Cancellation
+    // =========================================================================
+
+    /// The token cancelled when this agent is shut down
+    ///
+    /// `AgentHandle::shutdown` and `AgentRuntime::shutdown_all` cancel it
+    /// (`AgentRuntime::spawn` gives it to the handle, see
+    /// `AgentHandle::with_cancellation_token`), so an agent stuck waiting on
+    /// a provider or a tool still stops. `receive` returns
+    /// `InputMessage::Shutdown` once it is cancelled.
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    /// Whether the agent has been shut down
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Wait until the agent is shut down
+    ///
+    /// For custom agents and tools doing long work:
+    ///
+    /// ```ignore
+    /// tokio::select! {
+    ///     result = slow_work() => result,
+    ///     _ = internals.cancelled() => return Err(FrameworkError::Shutdown),
+    /// }
+    /// ```
+    pub async fn cancelled(&self) {
+        self.cancel.cancelled().await
+    }
+
+    /// A token for work in the current turn
+    ///
+    /// Cancelled when the turn ends (for agents that call `start_turn` and
+    /// `end_turn`, as `StandardAgent` does) or the agent shuts down. Tool
+    /// calls get a child of it as `context.cancellation`.
+    pub fn turn_token(&self) -> &CancellationToken {
+        &self.turn_cancel
+    }
+
+    /// Start a turn, with a new `turn_token`
+    pub fn start_turn(&mut self) {
+        self.turn_cancel = self.cancel.child_token();
+    }
+
+    /// End the turn, cancelling whatever still watches its `turn_token`
+    ///
+    /// The next `turn_token` is a fresh one, so work started between turns
+    /// isn't cancelled before it begins.
+    pub fn end_turn(&mut self) {
+        self.turn_cancel.cancel();
+        self.turn_cancel = self.cancel.child_token();
+    }
+
     // =========================================================================
     // Input Methods
     // =========================================================================
@@ -74,23 +178,131 @@ impl AgentInternals {
     ///
     /// Blocks until an input message is available.
     /// Returns `None` if the input channel is closed (handle dropped).
+    /// Permission responses to requests that aren't open (timed out,
+    /// already answered or never sent) are dropped here.
     pub async fn receive(&mut self) -> Option<InputMessage> {
-        self.input_rx.recv().await
+        loop {
+            let message = tokio::select! {
+                biased;
+                _ = self.cancel.cancelled() => return Some(InputMessage::Shutdown),
+                message = self.input_rx.recv() => message,
+            };
+            if !self.is_stale_response(&message) {
+                return message;
+            }
+        }
     }
 
     /// Receive the next input message, returning an error if channel closed
     pub async fn receive_or_err(&mut self) -> FrameworkResult<InputMessage> {
-        self.input_rx
-            .recv()
-            .await
-            .ok_or(FrameworkError::ChannelClosed)
+        self.receive().await.ok_or(FrameworkError::ChannelClosed)
     }
 
     /// Try to receive input without blocking
     ///
     /// Returns `None` if no message is available.
     pub fn try_receive(&mut self) -> Option<InputMessage> {
-        self.input_rx.try_recv().ok()
+        loop {
+            let message = self.input_rx.try_recv().ok();
+            if !self.is_stale_response(&message) {
+                return message;
+            }
+        }
+    }
+
+    /// Set a message aside to handle once the current turn is over
+    ///
+    /// For messages (such as `AgentMessage`) that arrive while the agent is
+    /// busy and shouldn't be lost. `receive` doesn't return them; the
+    /// agent's main loop takes them with `take_deferred`.
+    pub fn defer(&mut self, message: InputMessage) {
+        self.deferred.push_back(message);
+    }
+
+    /// Take the oldest message set aside with `defer`
+    pub fn take_deferred(&mut self) -> Option<InputMessage> {
+        self.deferred.pop_front()
+    }
+
+    /// Whether a message answers a permission request that is no longer open
+    ///
+    /// Answers to requests that timed out, or that were never sent, are
+    /// dropped so they can't be applied to a later request.
+    fn is_stale_response(&mut self, message: &Option<InputMessage>) -> bool {
+        let Some(InputMessage::PermissionResponse { request_id, tool_name, .. }) = message else {
+            return false;
+        };
+        let target = request_id.as_deref().or(tool_name.as_deref()).unwrap_or("?");
+        match self.permissions.resolve_response(request_id.as_deref(), tool_name.as_deref()) {
+            ResponseTarget::Pending(_) => false,
+            ResponseTarget::Expired => {
+                tracing::info!("[AgentInternals] Dropping late permission response for {}", target);
+                true
+            }
+            ResponseTarget::Unknown => {
+                tracing::warn!("[AgentInternals] Ignoring permission response for unknown request {}", target);
+                true
+            }
+        }
+    }
+
+    /// Wait for the response to the permission request `request_id`
+    ///
+    /// Answers to other open requests that arrive first are kept for them,
+    /// so requests can be answered in any order; answers to unknown requests
+    /// are ignored and messages from other agents are deferred. Any other
+    /// message ends the wait and is returned.
+    ///
+    /// Waits at most the permission manager's timeout. When it runs out, the
+    /// request is marked expired so a late answer is dropped instead of
+    /// being applied to a later request, and the caller gets the timeout's
+    /// decision to apply.
+    pub async fn receive_permission_response(&mut self, request_id: &str) -> PermissionWait {
+        let timeout = self.permissions.timeout();
+        let deadline = timeout.map(|t| tokio::time::Instant::now() + t.after);
+        loop {
+            if let Some((allowed, remember)) = self.permissions.take_answer(request_id) {
+                return PermissionWait::Answered { allowed, remember };
+            }
+            let message = match (timeout, deadline) {
+                (Some(timeout), Some(deadline)) => {
+                    match tokio::time::timeout_at(deadline, self.receive()).await {
+                        Ok(message) => message,
+                        Err(_) => {
+                            tracing::warn!(
+                                "[AgentInternals] Permission request {} timed out after {:?}",
+                                request_id,
+                                timeout.after
+                            );
+                            self.permissions.expire_request(request_id);
+                            return PermissionWait::TimedOut(timeout);
+                        }
+                    }
+                }
+                _ => self.receive().await,
+            };
+            match message {
+                Some(InputMessage::PermissionResponse {
+                    request_id: response_id,
+                    tool_name,
+                    allowed,
+                    remember,
+                }) => {
+                    // `receive` already dropped answers to requests that aren't open
+                    if let ResponseTarget::Pending(id) =
+                        self.permissions.resolve_response(response_id.as_deref(), tool_name.as_deref())
+                    {
+                        self.permissions.store_answer(&id, allowed, remember);
+                    }
+                }
+                // Another agent's message waits for the end of the turn
+                Some(message @ InputMessage::AgentMessage { .. }) => self.defer(message),
+                other => {
+                    self.permissions.close_request(request_id);
+                    return PermissionWait::Received(other);
+                }
+            }
+        }
     }
 
     // =========================================================================
@@ -101,8 +313,12 @@ impl AgentInternals {
     ///
     /// Returns the number of subscribers that received the message.
     /// Returns 0 if there are no subscribers (which is not an error).
+    /// The chunk is numbered and stamped with the time and the current turn
+    /// (see `ChunkEnvelope`).
     pub fn send(&self, chunk: OutputChunk) -> usize {
-        self.output_tx.send(chunk).unwrap_or(0)
+        self.output_tx
+            .send_in_turn(self.context.current_turn, chunk)
+            .unwrap_or(0)
     }
 
     /// Send a text delta
@@ -125,6 +341,14 @@ impl AgentInternals {
         self.send(OutputChunk::TextComplete(full_text.into()))
     }
 
+    /// Send the citations of a complete text block
+    pub fn send_citations(&self, text: impl Into<String>, citations: Vec<crate::llm::Citation>) -> usize {
+        self.send(OutputChunk::Citations {
+            text: text.into(),
+            citations,
+        })
+    }
+
     /// Send a status update
     pub fn send_status(&self, status: impl Into<String>) -> usize {
         self.send(OutputChunk::Status(status.into()))
@@ -140,6 +364,14 @@ impl AgentInternals {
         self.send(OutputChunk::Done)
     }
 
+    /// Announce a tool call whose input is still streaming
+    pub fn send_tool_stream_start(&self, id: impl Into<String>, name: impl Into<String>) -> usize {
+        self.send(OutputChunk::ToolStreamStart {
+            id: id.into(),
+            name: name.into(),
+        })
+    }
+
     /// Send a tool start notification
     pub fn send_tool_start(
         &self,
@@ -154,6 +386,14 @@ impl AgentInternals {
         })
     }
 
+    /// Send a fragment of a tool call's input as it streams
+    pub fn send_tool_input_delta(&self, id: impl Into<String>, partial_json: impl Into<String>) -> usize {
+        self.send(OutputChunk::ToolInputDelta {
+            id: id.into(),
+            partial_json: partial_json.into(),
+        })
+    }
+
     /// Send a tool end notification
     pub fn send_tool_end(&self, id: impl Into<String>, result: crate::tools::ToolResult) -> usize {
         self.send(OutputChunk::ToolEnd {
@@ -162,20 +402,41 @@ impl AgentInternals {
         })
     }
 
-    /// Send a permission request
-    pub fn send_permission_request(
+    /// Send a completed server-side web search
+    pub fn send_web_search(
         &self,
+        id: impl Into<String>,
+        query: impl Into<String>,
+        result: crate::llm::WebSearchToolResultContent,
+    ) -> usize {
+        self.send(OutputChunk::WebSearch {
+            id: id.into(),
+            query: query.into(),
+            result,
+        })
+    }
+
+    /// Send a permission request, returning its ID
+    ///
+    /// Wait for the answer with
+    /// [`receive_permission_response`](Self::receive_permission_response).
+    pub fn send_permission_request(
+        &mut self,
         tool_name: impl Into<String>,
         action: impl Into<String>,
         input: impl Into<String>,
         details: Option<String>,
-    ) -> usize {
+    ) -> String {
+        let tool_name = tool_name.into();
+        let request_id = self.permissions.open_request(tool_name.clone());
         self.send(OutputChunk::PermissionRequest {
-            tool_name: tool_name.into(),
+            request_id: request_id.clone(),
+            tool_name,
             action: action.into(),
             input: input.into(),
             details,
-        })
+        });
+        request_id
     }
 
     /// Get the number of current subscribers
@@ -189,21 +450,28 @@ impl AgentInternals {
 
     /// Set the current agent state
     pub async fn set_state(&self, new_state: AgentState) {
-        let mut state = self.state.write().await;
-        *state = new_state.clone();
+        self.state.send_replace(new_state.clone());
         // Notify subscribers of state change
-        let _ = self.output_tx.send(OutputChunk::StateChange(new_state));
+        self.send(OutputChunk::StateChange(new_state));
+        self.wait_for_subscribers().await;
+    }
+
+    /// Wait until subscribers with `OverflowPolicy::Block` have room again
+    ///
+    /// Sending output never waits, so loops that produce a lot of it call
+    /// this between chunks to apply backpressure. `set_state` calls it too.
+    pub async fn wait_for_subscribers(&self) {
+        self.output_tx.wait_for_capacity().await;
     }
 
     /// Set state without notifying subscribers
     pub async fn set_state_silent(&self, new_state: AgentState) {
-        let mut state = self.state.write().await;
-        *state = new_state;
+        self.state.send_replace(new_state);
     }
 
     /// Get the current agent state
     pub async fn state(&self) -> AgentState {
-        self.state.read().await.clone()
+        self.state.borrow().clone()
     }
 
     /// Set state to Idle
@@ -230,8 +498,8 @@ impl AgentInternals {
     }
 
     /// Set state to WaitingForPermission
-    pub async fn set_waiting_for_permission(&self) {
-        self.set_state(AgentState::WaitingForPermission).await;
+    pub async fn set_waiting_for_permission(&self, tool_name: impl Into<String>) {
+        self.set_state(AgentState::waiting_for_permission(tool_name)).await;
     }
 
     /// Set state to ExecutingTool
@@ -273,6 +541,14 @@ impl AgentInternals {
         &self.context.agent_type
     }
 
+    /// The current turn number, counting from 0
+    ///
+    /// This is the number of user turns completed before the current one,
+    /// so it carries on where a resumed session left off.
+    pub fn turn(&self) -> usize {
+        self.context.current_turn
+    }
+
     /// Increment the turn counter
     pub fn next_turn(&mut self) {
         self.context.next_turn();
@@ -305,7 +581,8 @@ impl AgentInternals {
     /// 3. Processes the response (adding rules if "Always" was selected)
     ///
     /// Returns `Ok(true)` if allowed, `Ok(false)` if denied, or an error if
-    /// the channel closed while waiting.
+    /// the channel closed while waiting. If the request times out, the
+    /// timeout's decision applies (`AbortTurn` returns `PermissionDenied`).
     pub async fn request_permission(
         &mut self,
         tool_name: &str,
@@ -317,39 +594,38 @@ impl AgentInternals {
             CheckResult::Denied => Ok(false),
             CheckResult::AskUser => {
                 // Send permission request
-                self.send_permission_request(tool_name, action_description, input, None);
-                self.set_waiting_for_permission().await;
+                let request_id = self.send_permission_request(tool_name, action_description, input, None);
+                let previous = self.state().await;
+                self.set_waiting_for_permission(tool_name).await;
 
                 // Wait for response
-                match self.receive().await {
-                    Some(InputMessage::PermissionResponse {
-                        tool_name: resp_tool,
-                        allowed,
-                        remember,
-                    }) => {
-                        if resp_tool == tool_name {
-                            if remember && allowed {
-                                // Add to session rules (could also be global based on UI)
-                                self.permissions.add_rule(
-                                    PermissionRule::allow_tool(tool_name),
-                                    PermissionScope::Session,
-                                );
-                            }
-                            Ok(allowed)
-                        } else {
-                            // Mismatched tool name - shouldn't happen
-                            tracing::warn!(
-                                "Permission response for {} but expected {}",
-                                resp_tool,
-                                tool_name
+                let response = self.receive_permission_response(&request_id).await;
+                self.set_state(previous).await;
+                match response {
+                    PermissionWait::Answered { allowed, remember } => {
+                        if remember && allowed {
+                            // Add to session rules (could also be global based on UI)
+                            self.permissions.add_rule(
+                                PermissionRule::allow_tool(tool_name),
+                                PermissionScope::Session,
                             );
-                            Ok(false)
+                        }
+                        Ok(allowed)
+                    }
+                    PermissionWait::TimedOut(timeout) => {
+                        self.send_status(format!("Permission request for {} timed out", tool_name));
+                        match timeout.decision {
+                            TimeoutDecision::Allow => Ok(true),
+                            TimeoutDecision::Deny => Ok(false),
+                            TimeoutDecision::AbortTurn => Err(FrameworkError::PermissionDenied(
+                                "permission request timed out".into(),
+                            )),
                         }
                     }
-                    Some(InputMessage::Shutdown) => Err(FrameworkError::Shutdown),
-                    Some(InputMessage::Interrupt) => Err(FrameworkError::Interrupted),
-                    None => Err(FrameworkError::ChannelClosed),
-                    _ => {
+                    PermissionWait::Received(Some(InputMessage::Shutdown)) => Err(FrameworkError::Shutdown),
+                    PermissionWait::Received(Some(InputMessage::Interrupt)) => Err(FrameworkError::Interrupted),
+                    PermissionWait::Received(None) => Err(FrameworkError::ChannelClosed),
+                    PermissionWait::Received(Some(_)) => {
                         // Unexpected message while waiting for permission
                         Ok(false)
                     }
@@ -361,8 +637,8 @@ impl AgentInternals {
     /// Add a permission rule
     ///
     /// Use this to programmatically add rules (e.g., from configuration).
-    pub fn add_permission_rule(&mut self, rule: PermissionRule, scope: PermissionScope) {
-        self.permissions.add_rule(rule, scope);
+    pub fn add_permission_rule(&mut self, rule: PermissionRule, scope: PermissionScope) -> RuleId {
+        self.permissions.add_rule(rule, scope)
     }
 
     // =========================================================================
@@ -392,10 +668,18 @@ impl AgentInternals {
         });
 
         // Set state to waiting for user input
+        let previous = self.state().await;
         self.set_waiting_for_user_input(&request_id).await;
 
-        // Wait for response
-        match self.receive().await {
+        // Wait for response (messages from other agents wait for the turn to end)
+        let response = loop {
+            match self.receive().await {
+                Some(message @ InputMessage::AgentMessage { .. }) => self.defer(message),
+                other => break other,
+            }
+        };
+        self.set_state(previous).await;
+        match response {
             Some(InputMessage::UserQuestionResponse { request_id: resp_id, answers }) => {
                 if resp_id == request_id {
                     Ok(answers)
@@ -439,9 +723,14 @@ impl AgentInternals {
     ///
     /// This is the preferred way to spawn subagents from within an agent,
     /// as it automatically:
-    /// 1. Creates the subagent with proper parent linkage
-    /// 2. Registers the handle with this agent's SubAgentManager
-    /// 3. Sends a SubAgentSpawned notification to subscribers
+    /// 1. Runs the parent's PreSubAgentSpawn hooks
+    /// 2. Creates the subagent with proper parent linkage, in this agent's session storage
+    /// 3. Registers the handle with this agent's SubAgentManager
+    /// 4. Sends a SubAgentSpawned notification to subscribers
+    ///
+    /// Fails with `FrameworkError::PermissionDenied` when a hook denies the
+    /// spawn, and with `FrameworkError::LimitExceeded` when the runtime's
+    /// depth or concurrency limits don't allow another subagent.
     ///
     /// # Example
     ///
@@ -459,7 +748,7 @@ impl AgentInternals {
     /// ).await?;
     /// ```
     pub async fn spawn_subagent<F, Fut>(
-        &self,
+        &mut self,
         session_id: impl Into<String>,
         agent_type: impl Into<String>,
         name: impl Into<String>,
@@ -471,8 +760,62 @@ impl AgentInternals {
         F: FnOnce(AgentInternals) -> Fut + Send + 'static,
         Fut: std::future::Future<Output = FrameworkResult<()>> + Send + 'static,
     {
+        self.spawn_subagent_with(
+            session_id,
+            agent_type,
+            name,
+            description,
+            tool_use_id,
+            agent_fn,
+            super::SpawnOptions::new(),
+        )
+        .await
+    }
+
+    /// Spawn a subagent with `SpawnOptions`, like `spawn_subagent`
+    ///
+    /// Resource types the options `inherit` are shared from this agent's
+    /// context. Hooks see a `SubAgentSpawn` with only `agent_type` filled
+    /// in; use `spawn_subagent_from` to let them rewrite the agent.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn spawn_subagent_with(
+        &mut self,
+        session_id: impl Into<String>,
+        agent_type: impl Into<String>,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        tool_use_id: impl Into<String>,
+        agent: impl super::SpawnableAgent,
+        options: super::SpawnOptions,
+    ) -> FrameworkResult<super::AgentHandle> {
+        let spawn = SubAgentSpawn::new(agent_type, "", "");
+        let (handle, _) = self
+            .spawn_subagent_from(session_id, spawn, name, description, tool_use_id, |_| Ok(agent), options)
+            .await?;
+        Ok(handle)
+    }
+
+    /// Spawn the subagent `build` makes from `spawn`, once hooks have seen it
+    ///
+    /// PreSubAgentSpawn hooks may rewrite `spawn` (all but `agent_type`) or
+    /// deny it; `build` gets what they left. Returns the handle and the
+    /// final `spawn`, for the subagent's first message and the
+    /// SubAgentComplete hooks. Nothing is written before the hooks ran.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn spawn_subagent_from<A: super::SpawnableAgent>(
+        &mut self,
+        session_id: impl Into<String>,
+        mut spawn: SubAgentSpawn,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        tool_use_id: impl Into<String>,
+        build: impl FnOnce(&SubAgentSpawn) -> FrameworkResult<A>,
+        options: super::SpawnOptions,
+    ) -> FrameworkResult<(super::AgentHandle, SubAgentSpawn)> {
+        self.run_subagent_spawn_hooks(&mut spawn).await.map_err(FrameworkError::PermissionDenied)?;
+
         let session_id = session_id.into();
-        let agent_type = agent_type.into();
+        let agent_type = spawn.agent_type.clone();
         let name_str = name.into();
         let description_str = description.into();
 
@@ -482,18 +825,24 @@ impl AgentInternals {
             .get_resource::<super::AgentRuntime>()
             .ok_or_else(|| FrameworkError::Other("Runtime not found in context".into()))?;
 
-        // Spawn the subagent
-        let handle = runtime
-            .spawn_subagent(
-                &session_id,
-                &agent_type,
-                &name_str,
-                &description_str,
-                self.session_id(),
-                tool_use_id,
-                agent_fn,
-            )
-            .await?;
+        // Check the depth limit before anything is written to disk
+        let storage = self.session.read().await.storage().clone();
+        let depth = runtime.depth_of(self.session_id(), &storage).await + 1;
+        runtime.check_depth(&session_id, depth)?;
+
+        // Spawn the subagent, stored next to this agent's session
+        let session = crate::session::AgentSession::new_subagent_with_storage(
+            &session_id,
+            &agent_type,
+            &name_str,
+            &description_str,
+            self.session_id(),
+            tool_use_id,
+            storage,
+        )?;
+        self.session.write().await.add_child(&session_id)?;
+        let options = options.inherit_from(&self.context.resources);
+        let handle = runtime.try_spawn_with(session, build(&spawn)?, options).await?;
 
         // Register with our SubAgentManager
         if let Some(manager) = self.context.get_resource::<super::SubAgentManager>() {
@@ -513,7 +862,7 @@ impl AgentInternals {
             agent_type
         );
 
-        Ok(handle)
+        Ok((handle, spawn))
     }
 
     /// Get the SubAgentManager for this agent
@@ -562,6 +911,91 @@ impl AgentInternals {
             });
         }
     }
+
+    /// Run PreSubAgentSpawn hooks for a subagent about to be spawned
+    ///
+    /// Uses the agent's `HookRegistry` resource (`StandardAgent` sets it up
+    /// from `AgentConfig::with_hooks`). Hooks may change `spawn`; if one
+    /// denies, or changes `agent_type`, the reason is returned and nothing
+    /// should be spawned.
+    async fn run_subagent_spawn_hooks(&mut self, spawn: &mut SubAgentSpawn) -> Result<(), String> {
+        let Some(hooks) = self.context.get_resource::<HookRegistry>() else {
+            return Ok(());
+        };
+        let mut ctx = HookContext::pre_subagent_spawn(self, spawn).await;
+        let result = hooks.run(&mut ctx);
+        if let Some(modified) = ctx.subagent.take() {
+            if modified.agent_type != spawn.agent_type {
+                let reason = format!(
+                    "a PreSubAgentSpawn hook changed agent_type from '{}' to '{}', which hooks can't do",
+                    spawn.agent_type, modified.agent_type
+                );
+                tracing::warn!("[{}] {}", self.session_id(), reason);
+                return Err(reason);
+            }
+            *spawn = modified;
+        }
+
+        if result.decision == Some(PermissionDecision::Deny) {
+            let reason = result.reason.unwrap_or_else(|| "Blocked by hook".to_string());
+            tracing::info!(
+                "[{}] Subagent {} denied by hook: {}",
+                self.session_id(),
+                spawn.agent_type,
+                reason
+            );
+            return Err(reason);
+        }
+        Ok(())
+    }
+
+    /// Run SubAgentComplete hooks for a subagent that finished
+    ///
+    /// `result` is its final text when it succeeded, `error` why it failed.
+    pub async fn run_subagent_complete_hooks(
+        &mut self,
+        spawn: &SubAgentSpawn,
+        session_id: &str,
+        result: Option<&str>,
+        error: Option<&str>,
+        usage: Option<&Usage>,
+    ) {
+        if let Some(hooks) = self.context.get_resource::<HookRegistry>() {
+            let mut ctx = HookContext::subagent_complete(self, spawn, session_id, result, error, usage).await;
+            hooks.run(&mut ctx);
+        }
+    }
+}
+
+#[cfg(test)]
+impl AgentInternals {
+    /// Internals for unit tests: session "s" of agent type "test", stored in `dir`
+    pub(crate) fn for_test(dir: &std::path::Path) -> Self {
+        Self::for_test_session(dir, "s", "test")
+    }
+
+    /// Like `for_test`, with another session id and agent type
+    pub(crate) fn for_test_session(dir: &std::path::Path, session_id: &str, agent_type: &str) -> Self {
+        use crate::permissions::GlobalPermissions;
+        use crate::session::SessionStorage;
+
+        let (_input_tx, input_rx, output_tx) = super::channels::create_agent_channels();
+        let storage = SessionStorage::with_dir(dir);
+        let session = AgentSession::new_with_storage(session_id, agent_type, "Test", "", storage).unwrap();
+        Self::new(
+            Arc::new(RwLock::new(session)),
+            AgentContext::new(session_id, agent_type, "Test", ""),
+            PermissionManager::new(Arc::new(GlobalPermissions::new()), agent_type),
+            input_rx,
+            output_tx,
+            super::channels::create_state_channel(),
+        )
+    }
+
+    /// A receiver for the chunks this agent sends
+    pub(crate) fn subscribe_output(&self) -> super::channels::OutputReceiver {
+        self.output_tx.subscribe()
+    }
 }
 
 impl std::fmt::Debug for AgentInternals {
@@ -578,14 +1012,14 @@ impl std::fmt::Debug for AgentInternals {
 mod tests {
     use super::*;
     use crate::permissions::GlobalPermissions;
-    use crate::runtime::channels::create_agent_channels;
+    use crate::runtime::channels::{create_agent_channels, create_state_channel};
     use crate::session::SessionStorage;
     use tempfile::TempDir;
 
     fn create_test_internals() -> (AgentInternals, super::super::channels::InputSender, super::super::channels::OutputReceiver) {
         let (input_tx, input_rx, output_tx) = create_agent_channels();
         let output_rx = output_tx.subscribe();
-        let state = Arc::new(RwLock::new(AgentState::Idle));
+        let state = create_state_channel();
 
         let temp_dir = TempDir::new().unwrap();
         let storage = SessionStorage::with_dir(temp_dir.path());
@@ -688,6 +1122,34 @@ mod tests {
         assert_eq!(internals.context.current_turn, 1);
     }
 
+    #[tokio::test]
+    async fn test_cancellation_ends_receive_and_turn_tokens() {
+        let (mut internals, _input_tx, _output_rx) = create_test_internals();
+
+        internals.start_turn();
+        let turn = internals.turn_token().clone();
+        internals.end_turn();
+        assert!(turn.is_cancelled() && !internals.is_cancelled());
+        assert!(!internals.turn_token().is_cancelled());
+
+        // Without start_turn, the token still follows shutdown
+        let (internals, _input_tx, _output_rx) = create_test_internals();
+        let turn = internals.turn_token().clone();
+        internals.cancellation_token().cancel();
+        assert!(turn.is_cancelled());
+
+        let (mut internals, input_tx, _output_rx) = create_test_internals();
+
+        internals.start_turn();
+        let turn = internals.turn_token().clone();
+        input_tx.send(InputMessage::UserInput("queued".into())).await.unwrap();
+        internals.cancellation_token().cancel();
+        assert!(turn.is_cancelled());
+        internals.cancelled().await;
+        // Shutdown wins over queued input
+        assert!(matches!(internals.receive().await, Some(InputMessage::Shutdown)));
+    }
+
     #[tokio::test]
     async fn test_send_done() {
         let (internals, _input_tx, mut output_rx) = create_test_internals();
@@ -698,6 +1160,38 @@ mod tests {
         assert!(matches!(chunk, OutputChunk::Done));
     }
 
+    #[tokio::test]
+    async fn test_permission_responses_out_of_order() {
+        let (mut internals, input_tx, mut output_rx) = create_test_internals();
+
+        let first = internals.send_permission_request("Bash", "Run ls", "ls", None);
+        let second = internals.send_permission_request("Bash", "Run rm", "rm x", None);
+        assert_ne!(first, second);
+        for expected in [&first, &second] {
+            let chunk = output_rx.recv().await.unwrap();
+            assert!(matches!(chunk, OutputChunk::PermissionRequest { request_id, .. } if request_id == *expected));
+        }
+
+        // Answered in reverse order, with a stray answer in between
+        input_tx.send(InputMessage::permission_for(&second, false, false)).await.unwrap();
+        input_tx.send(InputMessage::permission_for("unknown", true, true)).await.unwrap();
+        input_tx.send(InputMessage::permission_for(&first, true, false)).await.unwrap();
+
+        assert!(matches!(
+            internals.receive_permission_response(&first).await,
+            PermissionWait::Answered { allowed: true, remember: false }
+        ));
+        assert!(matches!(
+            internals.receive_permission_response(&second).await,
+            PermissionWait::Answered { allowed: false, remember: false }
+        ));
+
+        // Both are settled; a repeated answer is ignored
+        input_tx.send(InputMessage::permission_for(&first, false, false)).await.unwrap();
+        input_tx.send(InputMessage::user_input("next")).await.unwrap();
+        assert!(matches!(internals.receive().await, Some(InputMessage::UserInput(_))));
+    }
+
     #[tokio::test]
     async fn test_channel_close() {
         let (mut internals, input_tx, _output_rx) = create_test_internals();
@@ -1,7 +1,10 @@
 //! Agent runtime and communication
 //!
 //! This module provides the infrastructure for running agents:
-//! - `AgentRuntime` - Spawns and manages agent tasks
+//! - `AgentRuntime` - Spawns and manages agent tasks, with optional concurrency
+//!   and subagent depth limits
+//! - `scheduler` - Prompts sent to agents on interval or cron triggers
+//! - `spawn` - `SpawnOptions` and the `SpawnableAgent` trait for `spawn_with`
 //! - `AgentHandle` - External interface for communicating with a running agent
 //! - `AgentInternals` - Internal state passed to agent functions
 //! - Channel types for input/output communication
@@ -12,11 +15,19 @@
 pub mod channels;
 pub mod handle;
 pub mod internals;
+#[allow(clippy::module_inception)]
 pub mod runtime;
+pub mod scheduler;
+pub mod spawn;
 pub mod subagent_manager;
 
-pub use channels::{InputReceiver, InputSender, OutputReceiver, OutputSender};
-pub use handle::AgentHandle;
-pub use internals::AgentInternals;
-pub use runtime::AgentRuntime;
+pub use channels::{
+    FilteredReceiver, InputReceiver, InputSender, InterruptSignal, OutputReceiver, OutputSender,
+    OverflowPolicy, ReliableReceiver, StateReceiver, StateSender,
+};
+pub use handle::{AgentConnection, AgentHandle};
+pub use internals::{AgentInternals, PermissionWait};
+pub use runtime::{AgentInfo, AgentRuntime, LimitBehavior, RuntimeMetrics, WaitingTimeout};
+pub use scheduler::{BusyPolicy, CronExpr, Schedule, Trigger};
+pub use spawn::{AgentFuture, PermissionMode, SpawnOptions, SpawnableAgent};
 pub use subagent_manager::{CompletedSubAgent, SubAgentManager};
@@ -6,21 +6,160 @@
 //! - Tracking running agents
 //! - Providing shutdown methods
 //! - Sharing global permissions across all agents
+//! - Enforcing concurrency and subagent depth limits
+//!
+//! # Limits
+//!
+//! `AgentRuntime::with_limits(max_concurrent_agents, max_subagent_depth)`
+//! guards against runaway recursive subagents:
+//! - **Depth**: a top-level agent has depth 0, its subagents depth 1, and so
+//!   on (derived from the session's parent chain). Spawning deeper than
+//!   `max_subagent_depth` fails with `FrameworkError::LimitExceeded`.
+//! - **Concurrency**: every running agent holds one of
+//!   `max_concurrent_agents` slots. When none are free, subagent spawns wait
+//!   for one by default, or fail with `LimitExceeded` under
+//!   `LimitBehavior::Error`.
+//!
+//! Limits are enforced by `try_spawn`, `spawn_subagent` and
+//! `AgentInternals::spawn_subagent`. `spawn` never fails: it waits for a free
+//! slot and does not check depth, so it is meant for top-level agents.
+//!
+//! With `LimitBehavior::Wait`, agents that wait on their subagents while
+//! holding a slot can deadlock once every slot is taken; keep the cap above
+//! the deepest chain you expect, or use `LimitBehavior::Error`.
+//!
+//! # Waiting Timeouts
+//!
+//! `on_waiting_timeout` registers a callback for agents stuck on a person:
+//! it fires once per wait when an agent stays in `WaitingForPermission` or
+//! `WaitingForUserInput` longer than the given duration.
+//!
+//! ```ignore
+//! let runtime = AgentRuntime::new().on_waiting_timeout(Duration::from_secs(600), |timeout| {
+//!     alert(format!("{} is waiting: {}", timeout.session_id, timeout.state));
+//! });
+//! ```
+//!
+//! # Agent Messaging
+//!
+//! Running agents can message each other: `send_to` delivers an
+//! `InputMessage` (usually `InputMessage::AgentMessage`) by session ID or by
+//! an alias set with `register_alias`. `SendToAgentTool` lets the model do
+//! the same.
+//!
+//! ```ignore
+//! runtime.register_alias("coder", coder.session_id()).await?;
+//! runtime.send_to("coder", InputMessage::agent_message("reviewer-1", "Add a test")).await?;
+//! ```
+//!
+//! # Scheduled Turns
+//!
+//! `schedule` sends an agent a prompt on an interval or cron trigger (see
+//! the `scheduler` module). Schedules are saved in the session and re-armed
+//! when the session is spawned again.
+//!
+//! ```ignore
+//! let id = runtime.schedule("monitor", Trigger::Interval(Duration::from_secs(300)), "Check the build").await?;
+//! runtime.cancel_schedule(&id).await?;
+//! ```
+//!
+//! # Tool Concurrency
+//!
+//! Every runtime has a `ToolConcurrency` shared resource, so a tool's
+//! `max_concurrency` holds across all of its agents.
+//! `with_max_concurrent_tools` also caps tool calls of any kind; see
+//! `tools::concurrency`.
+//!
+//! # Session Cleanup
+//!
+//! `spawn_session_cleanup` runs `SessionStorage::cleanup` periodically in the
+//! background. Sessions of running agents are never removed.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, RwLock, Semaphore};
 
-use crate::core::{AgentContext, AgentState, FrameworkError, FrameworkResult};
+use crate::core::{AgentContext, AgentState, FrameworkError, FrameworkResult, InputMessage, SharedResources};
 use crate::permissions::{GlobalPermissions, PermissionManager, PermissionRule};
-use crate::session::AgentSession;
+use crate::session::{AgentSession, CleanupPolicy, SessionStorage};
+use crate::tools::ToolConcurrency;
 
-use super::channels::create_agent_channels;
+use super::channels::{
+    create_input_channel, create_state_channel, OutputSender, StateReceiver, OUTPUT_CHANNEL_SIZE,
+};
 use super::handle::AgentHandle;
 use super::internals::AgentInternals;
+use super::scheduler::{saved_schedules, BusyPolicy, Schedule, Scheduler, Trigger};
+use super::spawn::{PermissionMode, SpawnOptions, SpawnableAgent};
 use super::subagent_manager::SubAgentManager;
 
+/// Upper bound on parent-chain walks, in case stored metadata forms a cycle
+const MAX_PARENT_CHAIN: usize = 64;
+
+/// What a spawn does when all concurrency slots are taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LimitBehavior {
+    /// Wait until a running agent finishes
+    #[default]
+    Wait,
+    /// Fail immediately with `FrameworkError::LimitExceeded`
+    Error,
+}
+
+/// Snapshot of the runtime's agent counts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeMetrics {
+    /// Agents currently running (including subagents)
+    pub running_agents: usize,
+    /// Running agents that are subagents (depth > 0)
+    pub running_subagents: usize,
+    /// Depth of the deepest running agent
+    pub max_running_depth: usize,
+    /// Spawns waiting for a free concurrency slot
+    pub waiting_spawns: usize,
+    /// Free concurrency slots (`None` when there is no cap)
+    pub available_slots: Option<usize>,
+    /// Configured concurrency cap
+    pub max_concurrent_agents: Option<usize>,
+    /// Configured subagent depth limit
+    pub max_subagent_depth: Option<usize>,
+}
+
+/// A running agent as reported by `AgentRuntime::list`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentInfo {
+    /// Session ID of the agent
+    pub session_id: String,
+    /// Subagent depth (0 for top-level agents)
+    pub depth: usize,
+    /// Current state
+    pub state: AgentState,
+}
+
+/// Passed to the `on_waiting_timeout` callback
+#[derive(Debug, Clone)]
+pub struct WaitingTimeout {
+    /// Session ID of the waiting agent
+    pub session_id: String,
+    /// The waiting state (`WaitingForPermission` or `WaitingForUserInput`)
+    pub state: AgentState,
+    /// How long the agent has been waiting
+    pub waited: Duration,
+}
+
+/// Callback for agents that wait on a person too long
+type WaitingTimeoutCallback = Arc<dyn Fn(&WaitingTimeout) + Send + Sync>;
+
+/// A registered running agent
+#[derive(Clone)]
+struct RunningAgent {
+    handle: AgentHandle,
+    depth: usize,
+}
+
 /// Runtime for spawning and managing agents
 ///
 /// The runtime maintains a registry of running agents and provides
@@ -30,10 +169,30 @@ use super::subagent_manager::SubAgentManager;
 /// so permission rules added to global scope are immediately visible to all agents.
 #[derive(Clone)]
 pub struct AgentRuntime {
-    /// Map of session_id -> AgentHandle for running agents
-    agents: Arc<RwLock<HashMap<String, AgentHandle>>>,
+    /// Map of session_id -> running agent
+    agents: Arc<RwLock<HashMap<String, RunningAgent>>>,
     /// Shared global permissions for all agents
     global_permissions: Arc<GlobalPermissions>,
+    /// Concurrency slots (`None` when unlimited)
+    slots: Option<Arc<Semaphore>>,
+    /// Configured concurrency cap
+    max_concurrent_agents: Option<usize>,
+    /// Maximum subagent depth (`None` when unlimited)
+    max_subagent_depth: Option<usize>,
+    /// What to do when no slot is free
+    limit_behavior: LimitBehavior,
+    /// Number of spawns currently waiting for a slot
+    waiting: Arc<AtomicUsize>,
+    /// Resource layer shared by every spawned agent
+    shared_resources: SharedResources,
+    /// How long an agent may wait on a person, and what to call after
+    waiting_timeout: Option<(Duration, WaitingTimeoutCallback)>,
+    /// How many chunks a broadcast subscriber may fall behind
+    output_capacity: usize,
+    /// Alias -> session ID, for addressing agents by name
+    aliases: Arc<RwLock<HashMap<String, String>>>,
+    /// Armed schedules of running agents
+    scheduler: Scheduler,
 }
 
 impl AgentRuntime {
@@ -42,17 +201,94 @@ impl AgentRuntime {
         Self {
             agents: Arc::new(RwLock::new(HashMap::new())),
             global_permissions: Arc::new(GlobalPermissions::new()),
+            slots: None,
+            max_concurrent_agents: None,
+            max_subagent_depth: None,
+            limit_behavior: LimitBehavior::default(),
+            waiting: Arc::new(AtomicUsize::new(0)),
+            shared_resources: SharedResources::new(),
+            waiting_timeout: None,
+            output_capacity: OUTPUT_CHANNEL_SIZE,
+            aliases: Arc::new(RwLock::new(HashMap::new())),
+            scheduler: Scheduler::default(),
         }
+        .with_tool_slots(None)
+    }
+
+    fn with_tool_slots(self, max_concurrent_tools: Option<usize>) -> Self {
+        self.shared_resources.insert(ToolConcurrency::new(max_concurrent_tools));
+        self
     }
 
     /// Create a runtime with initial global permission rules
     pub fn with_global_rules(rules: Vec<PermissionRule>) -> Self {
         Self {
-            agents: Arc::new(RwLock::new(HashMap::new())),
             global_permissions: Arc::new(GlobalPermissions::with_rules(rules)),
+            ..Self::new()
+        }
+    }
+
+    /// Create a runtime that caps running agents and subagent depth
+    ///
+    /// See the module docs for how the limits are enforced.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// // At most 8 agents at once; subagents may not spawn their own subagents
+    /// let runtime = AgentRuntime::with_limits(8, 1).on_concurrency_limit(LimitBehavior::Error);
+    /// ```
+    pub fn with_limits(max_concurrent_agents: usize, max_subagent_depth: usize) -> Self {
+        Self {
+            slots: Some(Arc::new(Semaphore::new(max_concurrent_agents))),
+            max_concurrent_agents: Some(max_concurrent_agents),
+            max_subagent_depth: Some(max_subagent_depth),
+            ..Self::new()
         }
     }
 
+    /// Set what limited spawns do when all concurrency slots are taken
+    pub fn on_concurrency_limit(mut self, behavior: LimitBehavior) -> Self {
+        self.limit_behavior = behavior;
+        self
+    }
+
+    /// Call `callback` when an agent waits on a permission prompt or a
+    /// question for longer than `after`
+    ///
+    /// Fires once per wait, from a task watching the agent's state. Applies
+    /// to agents spawned after this is set.
+    pub fn on_waiting_timeout<F>(mut self, after: Duration, callback: F) -> Self
+    where
+        F: Fn(&WaitingTimeout) + Send + Sync + 'static,
+    {
+        self.waiting_timeout = Some((after, Arc::new(callback)));
+        self
+    }
+
+    /// Set how many output chunks a subscriber may fall behind (default 256)
+    ///
+    /// A subscriber further behind gets an `OutputChunk::Dropped` in place
+    /// of what it missed; see `runtime::channels`. Applies to agents spawned
+    /// after this is set, so a clone can give one agent a larger buffer:
+    ///
+    /// ```ignore
+    /// let handle = runtime.clone().with_output_capacity(4096).spawn(session, agent_fn).await;
+    /// ```
+    pub fn with_output_capacity(mut self, capacity: usize) -> Self {
+        self.output_capacity = capacity;
+        self
+    }
+
+    /// Allow at most `max` tool calls at once across all agents
+    ///
+    /// Calls beyond the cap wait for a slot (see `tools::concurrency`).
+    /// Applies to the runtime and all its clones, including agents that are
+    /// already running.
+    pub fn with_max_concurrent_tools(self, max: usize) -> Self {
+        self.with_tool_slots(Some(max))
+    }
+
     /// Get a reference to the global permissions
     ///
     /// This can be used to add rules that apply to all agents.
@@ -60,6 +296,20 @@ impl AgentRuntime {
         &self.global_permissions
     }
 
+    /// Get the resource layer shared by all agents
+    ///
+    /// Every agent's `AgentContext::get_resource` falls back to these when
+    /// the agent has no resource of its own of that type. Resources added
+    /// here are visible to agents that are already running.
+    pub fn shared_resources(&self) -> &SharedResources {
+        &self.shared_resources
+    }
+
+    /// Add a resource for all agents of this runtime
+    pub fn insert_shared_resource<T: Send + Sync + 'static>(&self, value: T) {
+        self.shared_resources.insert(value);
+    }
+
     /// Spawn a new agent task
     ///
     /// The `agent_fn` receives `AgentInternals` and runs the agent logic.
@@ -90,7 +340,32 @@ impl AgentRuntime {
         F: FnOnce(AgentInternals) -> Fut + Send + 'static,
         Fut: Future<Output = FrameworkResult<()>> + Send + 'static,
     {
-        self.spawn_with_local_rules(session, Vec::new(), agent_fn).await
+        self.spawn_with(session, agent_fn, SpawnOptions::new()).await
+    }
+
+    /// Spawn an agent with resources, permission settings and buffer size
+    ///
+    /// Takes a `StandardAgent` (or any `SpawnableAgent`) directly. Like
+    /// `spawn`, it waits for a free slot and does not check depth.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let options = SpawnOptions::new().with_resource_arc(todo_manager.clone());
+    /// let handle = runtime.spawn_with(session, agent, options).await;
+    /// ```
+    pub async fn spawn_with(
+        &self,
+        session: AgentSession,
+        agent: impl SpawnableAgent,
+        options: SpawnOptions,
+    ) -> AgentHandle {
+        let depth = self.session_depth(&session).await;
+        let permit = match &self.slots {
+            Some(slots) => Some(self.wait_for_slot(slots).await),
+            None => None,
+        };
+        self.start(session, options, agent, depth, permit).await
     }
 
     /// Spawn a new agent task with local permission rules
@@ -106,17 +381,155 @@ impl AgentRuntime {
         F: FnOnce(AgentInternals) -> Fut + Send + 'static,
         Fut: Future<Output = FrameworkResult<()>> + Send + 'static,
     {
+        self.spawn_with(session, agent_fn, SpawnOptions::new().with_rules(local_rules))
+            .await
+    }
+
+    /// Spawn a new agent task, enforcing the runtime limits
+    ///
+    /// Fails with `FrameworkError::LimitExceeded` if the session is deeper
+    /// than `max_subagent_depth`, or if no concurrency slot is free under
+    /// `LimitBehavior::Error`.
+    pub async fn try_spawn<F, Fut>(
+        &self,
+        session: AgentSession,
+        agent_fn: F,
+    ) -> FrameworkResult<AgentHandle>
+    where
+        F: FnOnce(AgentInternals) -> Fut + Send + 'static,
+        Fut: Future<Output = FrameworkResult<()>> + Send + 'static,
+    {
+        self.try_spawn_with_local_rules(session, Vec::new(), agent_fn)
+            .await
+    }
+
+    /// Spawn a new agent task with local permission rules, enforcing the runtime limits
+    pub async fn try_spawn_with_local_rules<F, Fut>(
+        &self,
+        session: AgentSession,
+        local_rules: Vec<PermissionRule>,
+        agent_fn: F,
+    ) -> FrameworkResult<AgentHandle>
+    where
+        F: FnOnce(AgentInternals) -> Fut + Send + 'static,
+        Fut: Future<Output = FrameworkResult<()>> + Send + 'static,
+    {
+        self.try_spawn_with(session, agent_fn, SpawnOptions::new().with_rules(local_rules))
+            .await
+    }
+
+    /// Spawn an agent with `SpawnOptions`, enforcing the runtime limits
+    pub async fn try_spawn_with(
+        &self,
+        session: AgentSession,
+        agent: impl SpawnableAgent,
+        options: SpawnOptions,
+    ) -> FrameworkResult<AgentHandle> {
+        let depth = self.session_depth(&session).await;
+        self.check_depth(session.session_id(), depth)?;
+
+        let permit = match &self.slots {
+            Some(slots) => match self.limit_behavior {
+                LimitBehavior::Wait => Some(self.wait_for_slot(slots).await),
+                LimitBehavior::Error => {
+                    Some(slots.clone().try_acquire_owned().map_err(|_| {
+                        FrameworkError::LimitExceeded(format!(
+                            "cannot spawn '{}': all {} agent slots are in use (max_concurrent_agents)",
+                            session.session_id(),
+                            self.max_concurrent_agents.unwrap_or_default()
+                        ))
+                    })?)
+                }
+            },
+            None => None,
+        };
+
+        Ok(self.start(session, options, agent, depth, permit).await)
+    }
+
+    /// Check that an agent at `depth` is allowed to spawn
+    pub(crate) fn check_depth(&self, session_id: &str, depth: usize) -> FrameworkResult<()> {
+        match self.max_subagent_depth {
+            Some(max) if depth > max => Err(FrameworkError::LimitExceeded(format!(
+                "cannot spawn '{}': subagent depth {} exceeds max_subagent_depth = {}",
+                session_id, depth, max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Depth of a session: 0 for top-level agents, parent depth + 1 for subagents
+    ///
+    /// Uses the recorded depth of running agents and falls back to walking
+    /// the parent chain in storage.
+    pub(crate) async fn depth_of(&self, session_id: &str, storage: &SessionStorage) -> usize {
+        let mut depth = 0;
+        let mut current = session_id.to_string();
+        while depth < MAX_PARENT_CHAIN {
+            if let Some(agent) = self.agents.read().await.get(&current) {
+                return depth + agent.depth;
+            }
+            match AgentSession::get_metadata_with_storage(&current, storage) {
+                Ok(metadata) => match metadata.parent_session_id {
+                    Some(parent) => {
+                        depth += 1;
+                        current = parent;
+                    }
+                    None => return depth,
+                },
+                Err(_) => return depth,
+            }
+        }
+        depth
+    }
+
+    /// Depth of a session about to be spawned
+    async fn session_depth(&self, session: &AgentSession) -> usize {
+        match session.parent_session_id() {
+            Some(parent) => self.depth_of(parent, session.storage()).await + 1,
+            None => 0,
+        }
+    }
+
+    /// Wait for a free concurrency slot
+    async fn wait_for_slot(&self, slots: &Arc<Semaphore>) -> OwnedSemaphorePermit {
+        if let Ok(permit) = slots.clone().try_acquire_owned() {
+            return permit;
+        }
+
+        tracing::info!("[AgentRuntime] All agent slots are taken, waiting for one to free up");
+        self.waiting.fetch_add(1, Ordering::SeqCst);
+        let permit = slots
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("agent slot semaphore is never closed");
+        self.waiting.fetch_sub(1, Ordering::SeqCst);
+        permit
+    }
+
+    /// Create the agent's channels and internals and start its task
+    async fn start(
+        &self,
+        session: AgentSession,
+        mut options: SpawnOptions,
+        agent: impl SpawnableAgent,
+        depth: usize,
+        permit: Option<OwnedSemaphorePermit>,
+    ) -> AgentHandle {
         let session_id = session.session_id().to_string();
         let agent_type = session.agent_type().to_string();
+        let schedules = saved_schedules(&session);
 
         // Wrap session in Arc<RwLock> for shared access
         let session = Arc::new(RwLock::new(session));
 
         // Create channels
-        let (input_tx, input_rx, output_tx) = create_agent_channels();
+        let (input_tx, input_rx) = create_input_channel();
+        let output_tx = OutputSender::new(options.output_capacity().unwrap_or(self.output_capacity));
 
         // Create shared state
-        let state = Arc::new(RwLock::new(AgentState::Idle));
+        let state = create_state_channel();
 
         // Create context from session
         let session_read = session.read().await;
@@ -127,6 +540,7 @@ impl AgentRuntime {
             session_read.description(),
         );
         drop(session_read); // Release the lock
+        context.shared_resources = self.shared_resources.clone();
 
         // Add SubAgentManager to context for tracking spawned subagents
         context.insert_resource(SubAgentManager::new());
@@ -134,12 +548,19 @@ impl AgentRuntime {
         // Store runtime reference so agents can spawn subagents
         context.insert_resource(self.clone());
 
+        // Resources the caller gave this agent
+        context.resources.extend(options.resources());
+
         // Create permission manager with shared global + local rules
-        let permissions = PermissionManager::with_local_rules(
+        let mut permissions = PermissionManager::with_local_rules(
             self.global_permissions.clone(),
             &agent_type,
-            local_rules,
+            options.take_rules(),
         );
+        if let Some(mode) = options.permission_mode() {
+            permissions.set_interactive(mode == PermissionMode::Interactive);
+        }
+        let rules = permissions.shared_rules().clone();
 
         // Create internals for the agent
         let internals = AgentInternals::new(
@@ -158,30 +579,65 @@ impl AgentRuntime {
             input_tx,
             output_tx,
             state,
-        );
+            rules,
+        )
+        .with_interrupt_signal(internals.interrupt_signal().clone())
+        .with_cancellation_token(internals.cancellation_token().clone());
 
         // Store handle in registry
         {
             let mut agents = self.agents.write().await;
-            agents.insert(session_id.clone(), handle.clone());
+            agents.insert(
+                session_id.clone(),
+                RunningAgent {
+                    handle: handle.clone(),
+                    depth,
+                },
+            );
         }
 
+        crate::metrics::record_agent_spawned(&agent_type);
+
+        // Re-arm schedules saved by an earlier run
+        for schedule in schedules {
+            self.scheduler.arm(schedule, handle.clone()).await;
+        }
+
+        // Watch for the agent waiting on a person too long
+        let watcher = self.waiting_timeout.clone().map(|(after, callback)| {
+            tokio::spawn(watch_waiting(session_id.clone(), handle.subscribe_state(), after, callback))
+        });
+
         // Spawn the agent task
         let agents_ref = self.agents.clone();
+        let aliases_ref = self.aliases.clone();
+        let scheduler = self.scheduler.clone();
         let session_id_clone = session_id.clone();
 
         tokio::spawn(async move {
+            // Hold the concurrency slot for as long as the agent runs
+            let _permit = permit;
+
             // Run the agent function
-            let result = agent_fn(internals).await;
+            let result = agent.run(internals).await;
+            if let Some(watcher) = watcher {
+                watcher.abort();
+            }
 
             // Log errors (but don't panic)
+            crate::metrics::record_agent_stopped(&agent_type, result.is_err());
             if let Err(e) = result {
                 tracing::error!(session_id = %session_id_clone, error = %e, "Agent task errored");
             }
 
-            // Remove from registry when done
+            // Stop its schedules (they stay saved for the next spawn)
+            scheduler.disarm_session(&session_id_clone).await;
+
+            // Remove from registry when done (the slot is released on drop)
             let mut agents = agents_ref.write().await;
             agents.remove(&session_id_clone);
+            drop(agents);
+            aliases_ref.write().await.retain(|_, id| *id != session_id_clone);
 
             tracing::debug!(session_id = %session_id_clone, "Agent task completed");
         });
@@ -191,7 +647,9 @@ impl AgentRuntime {
 
     /// Spawn a subagent
     ///
-    /// Similar to `spawn`, but creates a subagent session linked to a parent.
+    /// Similar to `spawn`, but creates a subagent session linked to a parent
+    /// and enforces the runtime limits like `try_spawn`.
+    #[allow(clippy::too_many_arguments)]
     pub async fn spawn_subagent<F, Fut>(
         &self,
         session_id: impl Into<String>,
@@ -215,13 +673,13 @@ impl AgentRuntime {
             parent_tool_use_id,
         )?;
 
-        Ok(self.spawn(session, agent_fn).await)
+        self.try_spawn(session, agent_fn).await
     }
 
     /// Get a handle to a running agent
     pub async fn get(&self, session_id: &str) -> Option<AgentHandle> {
         let agents = self.agents.read().await;
-        agents.get(session_id).cloned()
+        agents.get(session_id).map(|agent| agent.handle.clone())
     }
 
     /// Check if an agent is running
@@ -236,20 +694,185 @@ impl AgentRuntime {
         agents.len()
     }
 
+    /// Current agent counts and limits
+    pub async fn metrics(&self) -> RuntimeMetrics {
+        let agents = self.agents.read().await;
+        RuntimeMetrics {
+            running_agents: agents.len(),
+            running_subagents: agents.values().filter(|a| a.depth > 0).count(),
+            max_running_depth: agents.values().map(|a| a.depth).max().unwrap_or(0),
+            waiting_spawns: self.waiting.load(Ordering::SeqCst),
+            available_slots: self.slots.as_ref().map(|s| s.available_permits()),
+            max_concurrent_agents: self.max_concurrent_agents,
+            max_subagent_depth: self.max_subagent_depth,
+        }
+    }
+
     /// List all running session IDs
     pub async fn list_running(&self) -> Vec<String> {
         let agents = self.agents.read().await;
         agents.keys().cloned().collect()
     }
 
+    /// Clean up `storage` with `policy` every `interval`, starting now
+    ///
+    /// Sessions of agents running in this runtime (and the sessions grouped
+    /// with them) are kept. Errors are logged and retried next time. Abort
+    /// the returned handle to stop.
+    pub fn spawn_session_cleanup(
+        &self,
+        storage: SessionStorage,
+        policy: CleanupPolicy,
+        interval: Duration,
+    ) -> tokio::task::JoinHandle<()> {
+        let agents = self.agents.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let running: HashSet<String> = agents.read().await.keys().cloned().collect();
+                let (storage, policy) = (storage.clone(), policy.clone());
+                let result =
+                    tokio::task::spawn_blocking(move || storage.cleanup_except(&policy, &running))
+                        .await;
+                match result {
+                    Ok(Ok(report)) if !report.removed.is_empty() => {
+                        tracing::info!(
+                            "[AgentRuntime] Session cleanup removed {} sessions ({} bytes)",
+                            report.removed.len(),
+                            report.bytes_freed
+                        );
+                    }
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => tracing::warn!("[AgentRuntime] Session cleanup failed: {}", e),
+                    Err(e) => tracing::warn!("[AgentRuntime] Session cleanup panicked: {}", e),
+                }
+            }
+        })
+    }
+
+    /// List running agents with their current state, sorted by session ID
+    pub async fn list(&self) -> Vec<AgentInfo> {
+        let agents = self.agents.read().await;
+        let mut list: Vec<AgentInfo> = agents
+            .iter()
+            .map(|(session_id, agent)| AgentInfo {
+                session_id: session_id.clone(),
+                depth: agent.depth,
+                state: agent.handle.subscribe_state().borrow().clone(),
+            })
+            .collect();
+        list.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+        list
+    }
+
+    /// Register an alias for a running agent
+    ///
+    /// Other agents can then address it by alias (e.g. "reviewer") with
+    /// `send_to` or `SendToAgentTool`. Replaces an existing alias of the
+    /// same name; removed when the agent stops.
+    pub async fn register_alias(
+        &self,
+        alias: impl Into<String>,
+        session_id: impl Into<String>,
+    ) -> FrameworkResult<()> {
+        let session_id = session_id.into();
+        if !self.is_running(&session_id).await {
+            return Err(FrameworkError::AgentNotRunning(session_id));
+        }
+        self.aliases.write().await.insert(alias.into(), session_id);
+        Ok(())
+    }
+
+    /// Remove an alias, returning true if it existed
+    pub async fn remove_alias(&self, alias: &str) -> bool {
+        self.aliases.write().await.remove(alias).is_some()
+    }
+
+    /// Session ID of a running agent, by session ID or alias
+    pub async fn resolve(&self, name: &str) -> Option<String> {
+        if self.is_running(name).await {
+            return Some(name.to_string());
+        }
+        let session_id = self.aliases.read().await.get(name).cloned()?;
+        self.is_running(&session_id).await.then_some(session_id)
+    }
+
+    /// Send a message to a running agent, by session ID or alias
+    ///
+    /// Fails with `AgentNotRunning` if no such agent is running or it has
+    /// finished.
+    pub async fn send_to(&self, session_id: &str, message: InputMessage) -> FrameworkResult<()> {
+        let not_running = || FrameworkError::AgentNotRunning(session_id.to_string());
+        let target = self.resolve(session_id).await.ok_or_else(not_running)?;
+        let handle = self.get(&target).await.ok_or_else(not_running)?;
+        if handle.state().await.is_terminal() {
+            return Err(not_running());
+        }
+        handle.send(message).await.map_err(|_| not_running())
+    }
+
+    /// Send a running agent a prompt whenever `trigger` fires
+    ///
+    /// `{timestamp}` in the template is replaced with the firing time. Ticks
+    /// that find the agent busy are skipped; see `schedule_with_policy`.
+    /// Returns the schedule ID, for `cancel_schedule`.
+    pub async fn schedule(
+        &self,
+        session_id: &str,
+        trigger: Trigger,
+        prompt_template: impl Into<String>,
+    ) -> FrameworkResult<String> {
+        self.schedule_with_policy(session_id, trigger, prompt_template, BusyPolicy::Skip)
+            .await
+    }
+
+    /// Like `schedule`, choosing what happens when the agent is busy
+    pub async fn schedule_with_policy(
+        &self,
+        session_id: &str,
+        trigger: Trigger,
+        prompt_template: impl Into<String>,
+        busy_policy: BusyPolicy,
+    ) -> FrameworkResult<String> {
+        let handle = self
+            .get(session_id)
+            .await
+            .ok_or_else(|| FrameworkError::AgentNotRunning(session_id.to_string()))?;
+        let schedule = Schedule {
+            id: uuid::Uuid::new_v4().to_string(),
+            session_id: session_id.to_string(),
+            trigger,
+            prompt_template: prompt_template.into(),
+            busy_policy,
+        };
+        let id = schedule.id.clone();
+        self.scheduler.add(schedule, handle).await?;
+        Ok(id)
+    }
+
+    /// Cancel a schedule, returning false if no such schedule is armed
+    ///
+    /// The schedule is also removed from the agent's saved session.
+    pub async fn cancel_schedule(&self, id: &str) -> FrameworkResult<bool> {
+        let Some(session_id) = self.scheduler.session_of(id).await else {
+            return Ok(false);
+        };
+        let handle = self.get(&session_id).await;
+        Ok(self.scheduler.remove(id, handle).await?.is_some())
+    }
+
+    /// The armed schedules of an agent
+    pub async fn schedules(&self, session_id: &str) -> Vec<Schedule> {
+        self.scheduler.list(session_id).await
+    }
+
     /// Shutdown a specific agent
     ///
     /// Sends a shutdown message to the agent.
     pub async fn shutdown(&self, session_id: &str) -> FrameworkResult<()> {
-        let handle = {
-            let agents = self.agents.read().await;
-            agents.get(session_id).cloned()
-        };
+        let handle = self.get(session_id).await;
 
         match handle {
             Some(h) => h.shutdown().await,
@@ -261,10 +884,7 @@ impl AgentRuntime {
     ///
     /// Sends an interrupt message to the agent.
     pub async fn interrupt(&self, session_id: &str) -> FrameworkResult<()> {
-        let handle = {
-            let agents = self.agents.read().await;
-            agents.get(session_id).cloned()
-        };
+        let handle = self.get(session_id).await;
 
         match handle {
             Some(h) => h.interrupt().await,
@@ -306,6 +926,40 @@ impl AgentRuntime {
     }
 }
 
+/// Fire `callback` each time the agent stays blocked on a person for `after`
+async fn watch_waiting(
+    session_id: String,
+    mut states: StateReceiver,
+    after: Duration,
+    callback: WaitingTimeoutCallback,
+) {
+    loop {
+        let state = states.borrow_and_update().clone();
+        if state.is_blocked_on_user() {
+            match tokio::time::timeout(after, states.changed()).await {
+                Ok(Ok(())) => continue,
+                Ok(Err(_)) => return,
+                Err(_) => {
+                    tracing::warn!(
+                        "[AgentRuntime] Agent {} has been waiting for {:?}: {}",
+                        session_id,
+                        after,
+                        state
+                    );
+                    callback(&WaitingTimeout {
+                        session_id: session_id.clone(),
+                        state,
+                        waited: after,
+                    });
+                }
+            }
+        }
+        if states.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
 impl Default for AgentRuntime {
     fn default() -> Self {
         Self::new()
@@ -314,14 +968,22 @@ impl Default for AgentRuntime {
 
 impl std::fmt::Debug for AgentRuntime {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("AgentRuntime").finish()
+        f.debug_struct("AgentRuntime")
+            .field("max_concurrent_agents", &self.max_concurrent_agents)
+            .field("max_subagent_depth", &self.max_subagent_depth)
+            .field("limit_behavior", &self.limit_behavior)
+            .field("waiting_timeout", &self.waiting_timeout.as_ref().map(|(after, _)| after))
+            .field("output_capacity", &self.output_capacity)
+            .finish()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::{InputMessage, OutputChunk};
+    use crate::core::OutputChunk;
+    use crate::permissions::CheckResult;
+    use crate::runtime::OverflowPolicy;
     use crate::session::SessionStorage;
     use tempfile::TempDir;
 
@@ -358,6 +1020,182 @@ mod tests {
         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
     }
 
+    #[tokio::test]
+    async fn test_send_to_by_alias() {
+        let runtime = AgentRuntime::new();
+        let (session, _temp) = create_test_session("alias-test");
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        runtime
+            .spawn(session, |mut internals| async move {
+                let _ = tx.send(internals.receive().await);
+                Ok(())
+            })
+            .await;
+
+        assert!(runtime.register_alias("helper", "missing").await.is_err());
+        runtime.register_alias("helper", "alias-test").await.unwrap();
+        assert_eq!(runtime.resolve("helper").await.as_deref(), Some("alias-test"));
+        assert_eq!(runtime.resolve("alias-test").await.as_deref(), Some("alias-test"));
+
+        runtime
+            .send_to("helper", InputMessage::agent_message("other", "hi"))
+            .await
+            .unwrap();
+        let received = rx.await.unwrap();
+        assert!(matches!(
+            received,
+            Some(InputMessage::AgentMessage { from_session_id, content }) if from_session_id == "other" && content == "hi"
+        ));
+
+        // The agent has finished: its alias is gone and sends fail
+        runtime.wait_for("alias-test").await.unwrap();
+        assert_eq!(runtime.resolve("helper").await, None);
+        let result = runtime.send_to("helper", InputMessage::agent_message("other", "again")).await;
+        assert!(matches!(result, Err(FrameworkError::AgentNotRunning(name)) if name == "helper"));
+    }
+
+    #[tokio::test]
+    async fn test_shared_resources_reach_agents() {
+        #[derive(Debug)]
+        struct Client(&'static str);
+
+        let runtime = AgentRuntime::new();
+        runtime.insert_shared_resource(Client("shared"));
+        let (session, _temp) = create_test_session("shared-test");
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let handle = runtime
+            .spawn(session, |mut internals| async move {
+                let seen = internals.context.get_resource::<Client>().unwrap().0;
+                internals.context.insert_resource(Client("own"));
+                let shadowed = internals.context.get_resource::<Client>().unwrap().0;
+                let _ = tx.send((seen, shadowed));
+                internals.set_done().await;
+                Ok(())
+            })
+            .await;
+
+        assert_eq!(rx.await.unwrap(), ("shared", "own"));
+        // The agent's own copy didn't leak into the shared layer
+        assert_eq!(runtime.shared_resources().get::<Client>().unwrap().0, "shared");
+        drop(handle);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_with_options() {
+        struct Marker(u32);
+
+        let runtime = AgentRuntime::new();
+        let (session, _temp_dir) = create_test_session("spawn-with");
+        let options = SpawnOptions::new()
+            .with_resource(Marker(7))
+            .with_rule(PermissionRule::allow_tool("Read"))
+            .with_permission_mode(PermissionMode::NonInteractive);
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let handle = runtime
+            .spawn_with(
+                session,
+                move |internals: AgentInternals| async move {
+                    let marker = internals.context.get_resource::<Marker>().map(|m| m.0);
+                    let read = internals.permissions.check("Read", "a.txt");
+                    let bash = internals.permissions.check("Bash", "ls");
+                    let _ = tx.send((marker, read, bash));
+                    Ok(())
+                },
+                options,
+            )
+            .await;
+
+        let (marker, read, bash) = rx.await.unwrap();
+        assert_eq!(marker, Some(7));
+        assert_eq!(read, CheckResult::Allowed);
+        assert_eq!(bash, CheckResult::Denied);
+        runtime.wait_for(handle.session_id()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_session_cleanup_keeps_running_agents() {
+        let runtime = AgentRuntime::new();
+        let (session, temp) = create_test_session("running");
+        let storage = SessionStorage::with_dir(temp.path());
+        AgentSession::new_with_storage("idle", "test-agent", "Idle", "Idle", storage.clone())
+            .unwrap();
+
+        let handle = runtime
+            .spawn(session, |mut internals| async move {
+                while internals.receive().await.is_some() {}
+                Ok(())
+            })
+            .await;
+
+        let task = runtime.spawn_session_cleanup(
+            storage.clone(),
+            CleanupPolicy::new().with_max_sessions(0),
+            Duration::from_millis(10),
+        );
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while storage.session_exists("idle") {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        task.abort();
+
+        assert_eq!(storage.list_sessions().unwrap(), vec!["running".to_string()]);
+        handle.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscribers() {
+        let runtime = AgentRuntime::new().with_output_capacity(8);
+        let (session, _temp) = create_test_session("slow-test");
+
+        let handle = runtime
+            .spawn(session, |mut internals| async move {
+                internals.receive().await;
+                for i in 0..100 {
+                    internals.send_text(i.to_string());
+                    internals.wait_for_subscribers().await;
+                }
+                internals.send_done();
+                Ok(())
+            })
+            .await;
+
+        let mut lossy = handle.subscribe();
+        let mut reliable = handle.subscribe_reliable(8, OverflowPolicy::Block);
+        handle.send_input("go").await.unwrap();
+
+        let mut texts = Vec::new();
+        while let Some(chunk) = reliable.recv().await {
+            match chunk {
+                OutputChunk::TextDelta(text) => texts.push(text),
+                OutputChunk::Done => break,
+                _ => {}
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        let expected: Vec<String> = (0..100).map(|i| i.to_string()).collect();
+        assert_eq!(texts, expected);
+
+        // The broadcast subscriber hasn't read anything and fell behind
+        let mut dropped = 0;
+        let mut kept = 0;
+        while let Ok(chunk) = lossy.try_recv() {
+            match chunk {
+                OutputChunk::Dropped { count } => dropped += count,
+                _ => kept += 1,
+            }
+        }
+        assert_eq!(kept, 8);
+        assert!(dropped > 0);
+
+        runtime.shutdown_all().await;
+    }
+
     #[tokio::test]
     async fn test_agent_communication() {
         let runtime = AgentRuntime::new();
@@ -453,6 +1291,75 @@ mod tests {
         assert_eq!(runtime.count().await, 0);
     }
 
+    #[tokio::test]
+    async fn test_permission_wait_states() {
+        use crate::agent::{AgentConfig, StandardAgent};
+        use crate::llm::MockLlmProvider;
+        use crate::tools::{BashTool, ToolRegistry};
+
+        let timeouts = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = timeouts.clone();
+        let runtime = AgentRuntime::new().on_waiting_timeout(Duration::from_millis(50), move |timeout| {
+            recorded.lock().unwrap().push(timeout.clone());
+        });
+
+        let llm = MockLlmProvider::new()
+            .with_response(MockLlmProvider::tool_use_response(
+                "toolu_1",
+                "Bash",
+                serde_json::json!({"command": "echo hi"}),
+            ))
+            .with_text("done");
+        let mut registry = ToolRegistry::new();
+        registry.register(BashTool::new().unwrap());
+        let config = AgentConfig::new("Test")
+            .with_tools(Arc::new(registry))
+            .with_auto_name(false);
+        let agent = StandardAgent::new(config, Arc::new(llm));
+
+        let (session, _temp) = create_test_session("wait-states");
+        let handle = runtime.spawn(session, move |internals| agent.run(internals)).await;
+        let mut output = handle.subscribe();
+        let mut states = handle.subscribe_state();
+        handle.send_input("Run it").await.unwrap();
+
+        // Blocked on the permission prompt, not "processing"
+        let waiting = AgentState::waiting_for_permission("Bash");
+        states.wait_for(|s| *s == waiting).await.unwrap();
+        assert_eq!(handle.state().await, waiting);
+        assert_eq!(runtime.list().await[0].state, waiting);
+
+        // Left waiting past the timeout: the callback fires once
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        {
+            let timeouts = timeouts.lock().unwrap();
+            assert_eq!(timeouts.len(), 1);
+            assert_eq!(timeouts[0].session_id, "wait-states");
+            assert_eq!(timeouts[0].state, waiting);
+        }
+
+        #[allow(deprecated)]
+        handle.send_permission_response("Bash", true, false).await.unwrap();
+        let mut transitions = Vec::new();
+        loop {
+            match output.recv().await.unwrap() {
+                OutputChunk::StateChange(state) => transitions.push(state),
+                OutputChunk::Done => break,
+                _ => {}
+            }
+        }
+
+        let position = |state: &AgentState| transitions.iter().position(|s| s == state).unwrap();
+        let processing = position(&AgentState::Processing);
+        let asked = position(&waiting);
+        let executing = position(&AgentState::executing_tool("Bash", "toolu_1"));
+        assert!(processing < asked && asked < executing);
+        assert_eq!(states.borrow().clone(), AgentState::Idle);
+        assert_eq!(timeouts.lock().unwrap().len(), 1);
+
+        runtime.shutdown_all().await;
+    }
+
     #[tokio::test]
     async fn test_shutdown_nonexistent() {
         let runtime = AgentRuntime::new();
@@ -567,4 +1474,112 @@ mod tests {
         // Shutdown from runtime2
         runtime2.shutdown("clone-test").await.unwrap();
     }
+
+    /// Agent that runs until it is shut down
+    async fn wait_for_shutdown(mut internals: AgentInternals) -> FrameworkResult<()> {
+        loop {
+            match internals.receive().await {
+                Some(InputMessage::Shutdown) | None => break,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_subagent_depth_limit() {
+        let runtime = AgentRuntime::with_limits(10, 1);
+        let (session, _temp) = create_test_session("depth-root");
+        let (result_tx, mut result_rx) = tokio::sync::mpsc::unbounded_channel();
+
+        // root -> child (depth 1, allowed) -> grandchild (depth 2, refused)
+        let _handle = runtime
+            .spawn(session, move |mut internals| async move {
+                let child_tx = result_tx.clone();
+                let child_fn = move |mut internals: AgentInternals| async move {
+                    let grandchild = internals
+                        .spawn_subagent(
+                            "depth-grandchild",
+                            "test",
+                            "Grandchild",
+                            "",
+                            "tool_2",
+                            wait_for_shutdown,
+                        )
+                        .await;
+                    let _ = child_tx.send(grandchild.map(|_| ()));
+                    wait_for_shutdown(internals).await
+                };
+                let child = internals
+                    .spawn_subagent("depth-child", "test", "Child", "", "tool_1", child_fn)
+                    .await;
+                let _ = result_tx.send(child.map(|_| ()));
+                wait_for_shutdown(internals).await
+            })
+            .await;
+
+        let first = result_rx.recv().await.unwrap();
+        let second = result_rx.recv().await.unwrap();
+        let (child, grandchild) = if first.is_ok() {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        assert!(child.is_ok());
+        assert!(matches!(grandchild, Err(FrameworkError::LimitExceeded(_))));
+
+        let metrics = runtime.metrics().await;
+        assert_eq!(metrics.running_agents, 2);
+        assert_eq!(metrics.running_subagents, 1);
+        assert_eq!(metrics.max_running_depth, 1);
+        assert!(!runtime.is_running("depth-grandchild").await);
+
+        runtime.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit_error() {
+        let runtime = AgentRuntime::with_limits(2, 5).on_concurrency_limit(LimitBehavior::Error);
+        let (session1, _temp1) = create_test_session("limit-1");
+        let (session2, _temp2) = create_test_session("limit-2");
+        let (session3, _temp3) = create_test_session("limit-3");
+
+        runtime.try_spawn(session1, wait_for_shutdown).await.unwrap();
+        runtime.try_spawn(session2, wait_for_shutdown).await.unwrap();
+        let third = runtime.try_spawn(session3, wait_for_shutdown).await;
+        assert!(matches!(third, Err(FrameworkError::LimitExceeded(_))));
+
+        let metrics = runtime.metrics().await;
+        assert_eq!(metrics.running_agents, 2);
+        assert_eq!(metrics.available_slots, Some(0));
+        assert_eq!(metrics.max_concurrent_agents, Some(2));
+
+        runtime.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit_waits_for_slot() {
+        let runtime = AgentRuntime::with_limits(1, 5);
+        let (session1, _temp1) = create_test_session("wait-slot-1");
+        let (session2, _temp2) = create_test_session("wait-slot-2");
+
+        runtime.try_spawn(session1, wait_for_shutdown).await.unwrap();
+
+        let waiting_runtime = runtime.clone();
+        let second = tokio::spawn(async move {
+            waiting_runtime.try_spawn(session2, wait_for_shutdown).await
+        });
+
+        // The second spawn is parked until the first agent exits
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert!(!second.is_finished());
+        assert_eq!(runtime.metrics().await.waiting_spawns, 1);
+
+        runtime.shutdown("wait-slot-1").await.unwrap();
+        let handle = second.await.unwrap().unwrap();
+        assert_eq!(handle.session_id(), "wait-slot-2");
+        assert_eq!(runtime.metrics().await.waiting_spawns, 0);
+
+        runtime.shutdown_all().await;
+    }
 }
@@ -0,0 +1,607 @@
+//! Scheduled agent turns
+//!
+//! A schedule sends a running agent a prompt whenever its trigger fires, so
+//! an agent can check on something without anyone typing input:
+//!
+//! ```ignore
+//! let id = runtime
+//!     .schedule("monitor", Trigger::Interval(Duration::from_secs(300)), "Check the build ({timestamp})")
+//!     .await?;
+//!
+//! // Every weekday at 09:00 UTC; wait for a busy agent instead of skipping
+//! runtime
+//!     .schedule_with_policy("monitor", Trigger::Cron("0 9 * * 1-5".into()), "Daily summary", BusyPolicy::Queue)
+//!     .await?;
+//!
+//! runtime.cancel_schedule(&id).await?;
+//! ```
+//!
+//! `{timestamp}` in the prompt is replaced with the time the trigger fired
+//! (RFC 3339, UTC). When the agent isn't idle at that moment the tick is
+//! skipped, or with `BusyPolicy::Queue` sent once the agent is idle again.
+//!
+//! Schedules are saved in the session's custom metadata (under
+//! `SCHEDULES_METADATA_KEY`) and armed again when the runtime spawns the
+//! session after a restart. They stop firing when the agent stops.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
+
+use crate::core::{AgentState, FrameworkError, FrameworkResult};
+use crate::session::AgentSession;
+
+use super::handle::AgentHandle;
+
+/// Session custom metadata key the schedules are saved under
+pub const SCHEDULES_METADATA_KEY: &str = "schedules";
+
+/// Placeholder in prompt templates for the time the trigger fired
+const TIMESTAMP_PLACEHOLDER: &str = "{timestamp}";
+
+/// When a schedule fires
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Trigger {
+    /// Every interval, starting one interval after the schedule is armed
+    Interval(Duration),
+    /// A five-field cron expression (`minute hour day month weekday`, UTC)
+    ///
+    /// Fields take `*`, numbers, ranges (`1-5`), lists (`1,15`) and steps
+    /// (`*/10`). `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly`
+    /// are accepted as shorthands.
+    Cron(String),
+}
+
+impl Trigger {
+    /// Check that the trigger can fire
+    pub fn validate(&self) -> FrameworkResult<()> {
+        match self {
+            Trigger::Interval(interval) if interval.is_zero() => {
+                Err(FrameworkError::InvalidConfig("schedule interval must be greater than zero".into()))
+            }
+            Trigger::Interval(_) => Ok(()),
+            Trigger::Cron(expr) => CronExpr::parse(expr)
+                .map(|_| ())
+                .map_err(|e| FrameworkError::InvalidConfig(format!("invalid cron expression '{}': {}", expr, e))),
+        }
+    }
+}
+
+/// What to do when a trigger fires while the agent is busy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BusyPolicy {
+    /// Drop the tick (default)
+    #[default]
+    Skip,
+    /// Send the prompt once the agent is idle again
+    Queue,
+}
+
+/// A prompt sent to an agent when a trigger fires
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Schedule {
+    /// Unique ID (used to cancel the schedule)
+    pub id: String,
+    /// Session ID of the agent the prompt goes to
+    pub session_id: String,
+    /// When to fire
+    pub trigger: Trigger,
+    /// Prompt to send; `{timestamp}` is replaced with the firing time
+    pub prompt_template: String,
+    /// What to do when the agent is busy
+    #[serde(default)]
+    pub busy_policy: BusyPolicy,
+}
+
+impl Schedule {
+    /// The prompt for a tick at `now`
+    pub fn render_prompt(&self, now: DateTime<Utc>) -> String {
+        self.prompt_template
+            .replace(TIMESTAMP_PLACEHOLDER, &now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+    }
+}
+
+/// The schedules saved in a session's metadata
+pub fn saved_schedules(session: &AgentSession) -> Vec<Schedule> {
+    session
+        .get_custom(SCHEDULES_METADATA_KEY)
+        .and_then(|value| serde_json::from_value(value.clone()).ok())
+        .unwrap_or_default()
+}
+
+async fn saved_in(handle: &AgentHandle) -> Vec<Schedule> {
+    handle
+        .get_custom_metadata(SCHEDULES_METADATA_KEY)
+        .await
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+/// An armed schedule and the task that fires it
+struct Armed {
+    schedule: Schedule,
+    task: JoinHandle<()>,
+}
+
+/// The schedules armed in a runtime
+#[derive(Clone, Default)]
+pub(crate) struct Scheduler {
+    armed: Arc<RwLock<HashMap<String, Armed>>>,
+    /// Held while a session's saved schedules are rewritten
+    saving: Arc<Mutex<()>>,
+}
+
+impl Scheduler {
+    /// Save a new schedule in the agent's session and arm it
+    pub(crate) async fn add(&self, schedule: Schedule, handle: AgentHandle) -> FrameworkResult<()> {
+        schedule.trigger.validate()?;
+        {
+            let _saving = self.saving.lock().await;
+            let mut saved = saved_in(&handle).await;
+            saved.push(schedule.clone());
+            handle
+                .set_custom_metadata(SCHEDULES_METADATA_KEY, serde_json::to_value(&saved)?)
+                .await?;
+        }
+        self.arm(schedule, handle).await;
+        Ok(())
+    }
+
+    /// Cancel a schedule and remove it from the agent's session
+    pub(crate) async fn remove(&self, id: &str, handle: Option<AgentHandle>) -> FrameworkResult<Option<Schedule>> {
+        let Some(schedule) = self.cancel(id).await else {
+            return Ok(None);
+        };
+        if let Some(handle) = handle {
+            let _saving = self.saving.lock().await;
+            let mut saved = saved_in(&handle).await;
+            saved.retain(|s| s.id != id);
+            handle
+                .set_custom_metadata(SCHEDULES_METADATA_KEY, serde_json::to_value(&saved)?)
+                .await?;
+        }
+        tracing::info!("[Scheduler] Cancelled schedule {}", id);
+        Ok(Some(schedule))
+    }
+
+    /// Start firing a schedule at `handle`'s agent
+    pub(crate) async fn arm(&self, schedule: Schedule, handle: AgentHandle) {
+        tracing::info!(
+            "[Scheduler] Armed schedule {} for {} ({:?})",
+            schedule.id,
+            schedule.session_id,
+            schedule.trigger
+        );
+        let task = tokio::spawn(fire(schedule.clone(), handle));
+        if let Some(previous) = self.armed.write().await.insert(schedule.id.clone(), Armed { schedule, task }) {
+            previous.task.abort();
+        }
+    }
+
+    /// Stop and forget a schedule
+    async fn cancel(&self, id: &str) -> Option<Schedule> {
+        let armed = self.armed.write().await.remove(id)?;
+        armed.task.abort();
+        Some(armed.schedule)
+    }
+
+    /// Stop the schedules of an agent that stopped (they stay saved)
+    pub(crate) async fn disarm_session(&self, session_id: &str) {
+        self.armed.write().await.retain(|_, armed| {
+            let keep = armed.schedule.session_id != session_id;
+            if !keep {
+                armed.task.abort();
+            }
+            keep
+        });
+    }
+
+    /// Session ID of an armed schedule
+    pub(crate) async fn session_of(&self, id: &str) -> Option<String> {
+        self.armed.read().await.get(id).map(|armed| armed.schedule.session_id.clone())
+    }
+
+    /// The armed schedules of an agent
+    pub(crate) async fn list(&self, session_id: &str) -> Vec<Schedule> {
+        let mut schedules: Vec<Schedule> = self
+            .armed
+            .read()
+            .await
+            .values()
+            .filter(|armed| armed.schedule.session_id == session_id)
+            .map(|armed| armed.schedule.clone())
+            .collect();
+        schedules.sort_by(|a, b| a.id.cmp(&b.id));
+        schedules
+    }
+}
+
+/// Send the schedule's prompt every time the trigger fires
+async fn fire(schedule: Schedule, handle: AgentHandle) {
+    let mut ticker = match &schedule.trigger {
+        Trigger::Interval(interval) => {
+            let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + *interval, *interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            Some(ticker)
+        }
+        Trigger::Cron(_) => None,
+    };
+    let cron = match &schedule.trigger {
+        Trigger::Cron(expr) => match CronExpr::parse(expr) {
+            Ok(cron) => Some(cron),
+            Err(e) => {
+                tracing::warn!("[Scheduler] Schedule {} has an invalid cron expression: {}", schedule.id, e);
+                return;
+            }
+        },
+        Trigger::Interval(_) => None,
+    };
+
+    loop {
+        if let Some(ticker) = ticker.as_mut() {
+            ticker.tick().await;
+        } else if let Some(cron) = &cron {
+            let now = Utc::now();
+            let Some(next) = cron.next_after(now) else {
+                tracing::warn!("[Scheduler] Schedule {} never fires again", schedule.id);
+                return;
+            };
+            tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+        }
+
+        let state = handle.state().await;
+        if state.is_terminal() {
+            return;
+        }
+        if state != AgentState::Idle {
+            match schedule.busy_policy {
+                BusyPolicy::Skip => {
+                    tracing::info!("[Scheduler] {} is busy ({}), skipping schedule {}", schedule.session_id, state, schedule.id);
+                    continue;
+                }
+                BusyPolicy::Queue => {
+                    let mut states = handle.subscribe_state();
+                    let idle = states
+                        .wait_for(|s| *s == AgentState::Idle || s.is_terminal())
+                        .await
+                        .map(|s| *s == AgentState::Idle);
+                    if !matches!(idle, Ok(true)) {
+                        return;
+                    }
+                }
+            }
+        }
+
+        tracing::info!("[Scheduler] Firing schedule {} for {}", schedule.id, schedule.session_id);
+        if handle.send_input(schedule.render_prompt(Utc::now())).await.is_err() {
+            return;
+        }
+    }
+}
+
+// ============================================================================
+// Cron expressions
+// ============================================================================
+
+/// A parsed five-field cron expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day-of-month / day-of-week fields were `*`
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// How far ahead `next_after` looks before giving up
+const CRON_SEARCH_DAYS: i64 = 366 * 5;
+
+impl CronExpr {
+    /// Parse `minute hour day month weekday` (or an `@` shorthand)
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let expr = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("expected 5 fields, got {}", fields.len()));
+        };
+
+        let mut weekdays = parse_field(weekday, 0, 7)?;
+        // 7 is Sunday too
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    /// The first matching minute strictly after `after`
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+        let limit = start + ChronoDuration::days(CRON_SEARCH_DAYS);
+        let mut t = start;
+        while t < limit {
+            if !bit(self.months, t.month()) {
+                let (year, month) = if t.month() == 12 { (t.year() + 1, 1) } else { (t.year(), t.month() + 1) };
+                t = t.with_day(1)?.with_hour(0)?.with_minute(0)?.with_month(month)?.with_year(year)?;
+                continue;
+            }
+            if !self.day_matches(&t) {
+                t = (t + ChronoDuration::days(1)).with_hour(0)?.with_minute(0)?;
+                continue;
+            }
+            if !bit(self.hours, t.hour()) {
+                t = (t + ChronoDuration::hours(1)).with_minute(0)?;
+                continue;
+            }
+            if !bit(self.minutes, t.minute()) {
+                t += ChronoDuration::minutes(1);
+                continue;
+            }
+            return Some(t);
+        }
+        None
+    }
+
+    /// Day-of-month and day-of-week match like cron: if both are
+    /// restricted, either one matching is enough
+    fn day_matches(&self, t: &DateTime<Utc>) -> bool {
+        let day = bit(self.days, t.day());
+        let weekday = bit(self.weekdays, t.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+}
+
+fn bit(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Parse one field into a bit set of the allowed values
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut set = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| format!("bad step in '{}'", part))?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(format!("step must be positive in '{}'", part));
+        }
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (parse_value(a, part)?, parse_value(b, part)?)
+        } else {
+            let value = parse_value(range, part)?;
+            // `5/15` means from 5 to the end in steps of 15
+            (value, if part.contains('/') { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            return Err(format!("'{}' is outside {}-{}", part, min, max));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+fn parse_value(value: &str, part: &str) -> Result<u32, String> {
+    value.parse().map_err(|_| format!("bad value in '{}'", part))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::agent::{AgentConfig, StandardAgent};
+    use crate::core::{InputMessage, OutputChunk};
+    use crate::llm::MockLlmProvider;
+    use crate::runtime::AgentRuntime;
+    use crate::session::SessionStorage;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_cron_next_after() {
+        let every_ten = CronExpr::parse("*/10 * * * *").unwrap();
+        assert_eq!(every_ten.next_after(at("2025-03-01T10:03:30Z")), Some(at("2025-03-01T10:10:00Z")));
+        assert_eq!(every_ten.next_after(at("2025-03-01T10:50:00Z")), Some(at("2025-03-01T11:00:00Z")));
+
+        // 2025-03-01 is a Saturday
+        let weekdays = CronExpr::parse("0 9 * * 1-5").unwrap();
+        assert_eq!(weekdays.next_after(at("2025-03-01T08:00:00Z")), Some(at("2025-03-03T09:00:00Z")));
+
+        let new_year = CronExpr::parse("@yearly").unwrap();
+        assert_eq!(new_year.next_after(at("2025-03-01T00:00:00Z")), Some(at("2026-01-01T00:00:00Z")));
+
+        // Day of month or Sunday (7 = 0)
+        let either = CronExpr::parse("30 12 15 * 7").unwrap();
+        assert_eq!(either.next_after(at("2025-03-01T13:00:00Z")), Some(at("2025-03-02T12:30:00Z")));
+        assert_eq!(either.next_after(at("2025-03-10T00:00:00Z")), Some(at("2025-03-15T12:30:00Z")));
+
+        let never = CronExpr::parse("0 0 31 2 *").unwrap();
+        assert_eq!(never.next_after(at("2025-03-01T00:00:00Z")), None);
+
+        for bad in ["* * * *", "60 * * * *", "*/0 * * * *", "5-1 * * * *", "a * * * *"] {
+            assert!(CronExpr::parse(bad).is_err(), "{} should not parse", bad);
+        }
+    }
+
+    fn storage(dir: &tempfile::TempDir) -> SessionStorage {
+        SessionStorage::with_dir(dir.path())
+    }
+
+    async fn spawn_standard(runtime: &AgentRuntime, session: AgentSession, llm: &MockLlmProvider) -> AgentHandle {
+        let agent = StandardAgent::new(AgentConfig::new("Monitor").with_auto_name(false), Arc::new(llm.clone()));
+        runtime.spawn(session, move |internals| agent.run(internals)).await
+    }
+
+    #[tokio::test]
+    async fn test_interval_schedule_fires_and_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let llm = MockLlmProvider::new().with_fallback(MockLlmProvider::text_response("All good"));
+        let session = AgentSession::new_with_storage("monitor", "test", "Monitor", "Scheduled", storage(&dir)).unwrap();
+        let handle = spawn_standard(&runtime, session, &llm).await;
+        let mut rx = handle.subscribe();
+
+        assert!(runtime
+            .schedule("monitor", Trigger::Interval(Duration::ZERO), "tick")
+            .await
+            .is_err());
+        assert!(runtime.schedule("ghost", Trigger::Interval(Duration::from_secs(1)), "tick").await.is_err());
+        let id = runtime
+            .schedule("monitor", Trigger::Interval(Duration::from_millis(50)), "Check at {timestamp}")
+            .await
+            .unwrap();
+        for _ in 0..2 {
+            while !matches!(rx.recv().await.unwrap(), OutputChunk::Done) {}
+        }
+        let prompt = serde_json::to_string(&llm.requests()[0].messages[0]).unwrap();
+        assert!(prompt.contains("Check at 20"), "{}", prompt);
+
+        // Saved in the session and armed again when it is spawned after a restart
+        runtime.shutdown("monitor").await.unwrap();
+        runtime.wait_for("monitor").await.unwrap();
+        assert!(runtime.schedules("monitor").await.is_empty());
+        let calls = llm.call_count();
+
+        let session = AgentSession::load_with_storage("monitor", storage(&dir)).unwrap();
+        assert_eq!(saved_schedules(&session).len(), 1);
+        let handle = spawn_standard(&runtime, session, &llm).await;
+        let mut rx = handle.subscribe();
+        assert_eq!(runtime.schedules("monitor").await[0].id, id);
+        while !matches!(rx.recv().await.unwrap(), OutputChunk::Done) {}
+        assert!(llm.call_count() > calls);
+
+        // Cancelled: stops firing and is no longer saved
+        assert!(runtime.cancel_schedule(&id).await.unwrap());
+        assert!(!runtime.cancel_schedule(&id).await.unwrap());
+        let session = AgentSession::load_with_storage("monitor", storage(&dir)).unwrap();
+        assert!(saved_schedules(&session).is_empty());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let calls = llm.call_count();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(llm.call_count(), calls);
+
+        runtime.shutdown_all().await;
+    }
+
+    /// Spawn an agent that stays Processing after each prompt until released
+    async fn spawn_busy_agent(
+        runtime: &AgentRuntime,
+        dir: &tempfile::TempDir,
+        prompts: Arc<AtomicUsize>,
+        release: Arc<tokio::sync::Notify>,
+    ) {
+        let session = AgentSession::new_with_storage("busy", "test", "Busy", "Scheduled", storage(dir)).unwrap();
+        runtime
+            .spawn(session, move |mut internals| async move {
+                loop {
+                    internals.set_idle().await;
+                    match internals.receive().await {
+                        Some(InputMessage::UserInput(_)) => {
+                            prompts.fetch_add(1, Ordering::SeqCst);
+                            internals.set_processing().await;
+                            release.notified().await;
+                        }
+                        _ => break,
+                    }
+                }
+                internals.set_done().await;
+                Ok(())
+            })
+            .await;
+    }
+
+    async fn wait_for_prompts(prompts: &AtomicUsize, count: usize) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while prompts.load(Ordering::SeqCst) < count {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_busy_agent_skips_ticks() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let prompts = Arc::new(AtomicUsize::new(0));
+        let release = Arc::new(tokio::sync::Notify::new());
+        spawn_busy_agent(&runtime, &dir, prompts.clone(), release.clone()).await;
+
+        runtime
+            .schedule("busy", Trigger::Interval(Duration::from_millis(20)), "tick")
+            .await
+            .unwrap();
+        wait_for_prompts(&prompts, 1).await;
+
+        // Many ticks pass while the agent is busy; none are delivered
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(prompts.load(Ordering::SeqCst), 1);
+
+        release.notify_one();
+        wait_for_prompts(&prompts, 2).await;
+        runtime.shutdown_all().await;
+        release.notify_one();
+    }
+
+    #[tokio::test]
+    async fn test_queue_policy_waits_for_idle() {
+        let dir = tempfile::tempdir().unwrap();
+        let runtime = AgentRuntime::new();
+        let prompts = Arc::new(AtomicUsize::new(0));
+        let release = Arc::new(tokio::sync::Notify::new());
+        spawn_busy_agent(&runtime, &dir, prompts.clone(), release.clone()).await;
+
+        runtime
+            .schedule_with_policy("busy", Trigger::Interval(Duration::from_millis(300)), "tick", BusyPolicy::Queue)
+            .await
+            .unwrap();
+        wait_for_prompts(&prompts, 1).await;
+
+        // The tick at ~600ms finds the agent busy and is sent as soon as it's idle,
+        // well before the next tick at ~900ms
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(prompts.load(Ordering::SeqCst), 1);
+        let released = tokio::time::Instant::now();
+        release.notify_one();
+        wait_for_prompts(&prompts, 2).await;
+        assert!(released.elapsed() < Duration::from_millis(150), "{:?}", released.elapsed());
+
+        runtime.shutdown_all().await;
+        release.notify_one();
+    }
+}
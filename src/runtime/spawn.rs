@@ -0,0 +1,173 @@
+//! Spawn options and spawnable agents
+//!
+//! `AgentRuntime::spawn_with` takes a `SpawnableAgent` (a `StandardAgent`
+//! or any `FnOnce(AgentInternals) -> Future` closure) and a `SpawnOptions`
+//! describing what the agent starts with: resources added to its context,
+//! a permission mode, local permission rules and an output buffer size.
+//! That replaces wrapping the agent in a closure just to insert resources:
+//!
+//! ```ignore
+//! let options = SpawnOptions::new()
+//!     .with_resource_arc(todo_manager.clone())
+//!     .with_rules(vec![PermissionRule::allow_tool("Read")]);
+//! let handle = runtime.spawn_with(session, agent, options).await;
+//! ```
+//!
+//! Subagents spawned through `AgentInternals::spawn_subagent_with` (as
+//! `TaskTool` does) can also `inherit` resources of the parent agent; the
+//! parent's instance is shared, not copied.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::core::{FrameworkResult, ResourceMap};
+use crate::permissions::PermissionRule;
+
+use super::internals::AgentInternals;
+
+/// The future an agent runs as
+pub type AgentFuture = Pin<Box<dyn Future<Output = FrameworkResult<()>> + Send>>;
+
+/// Something the runtime can run as an agent task
+///
+/// Implemented for `StandardAgent` and for closures taking `AgentInternals`.
+pub trait SpawnableAgent: Send + 'static {
+    /// Run the agent until it stops
+    fn run(self, internals: AgentInternals) -> AgentFuture;
+}
+
+impl<F, Fut> SpawnableAgent for F
+where
+    F: FnOnce(AgentInternals) -> Fut + Send + 'static,
+    Fut: Future<Output = FrameworkResult<()>> + Send + 'static,
+{
+    fn run(self, internals: AgentInternals) -> AgentFuture {
+        Box::pin(self(internals))
+    }
+}
+
+/// How an agent handles permission checks that no rule answers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PermissionMode {
+    /// Ask the user (the default)
+    #[default]
+    Interactive,
+    /// Deny without asking, for agents nobody is watching
+    NonInteractive,
+}
+
+/// Copies one resource type from the parent's resources to the child's
+type Inherit = fn(&ResourceMap, &mut ResourceMap);
+
+/// What an agent is given when it is spawned
+#[derive(Clone, Default)]
+pub struct SpawnOptions {
+    /// Added to the agent's context before it runs
+    resources: ResourceMap,
+    /// Resource types shared from the parent agent
+    inherited: Vec<Inherit>,
+    /// Permission mode (`None` keeps the default)
+    permission_mode: Option<PermissionMode>,
+    /// Local permission rules
+    rules: Vec<PermissionRule>,
+    /// Output buffer size (`None` uses the runtime's)
+    output_capacity: Option<usize>,
+}
+
+impl SpawnOptions {
+    /// Options that spawn the agent like `AgentRuntime::spawn`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a resource to the agent's context
+    pub fn with_resource<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.resources.insert(value);
+        self
+    }
+
+    /// Add an already Arc-wrapped resource, shared with whoever else holds it
+    pub fn with_resource_arc<T: Send + Sync + 'static>(mut self, value: Arc<T>) -> Self {
+        self.resources.insert_arc(value);
+        self
+    }
+
+    /// Share the parent agent's resource of type `T` with a subagent
+    ///
+    /// Only applies to subagents spawned through `AgentInternals`; a
+    /// resource added with `with_resource` takes precedence.
+    pub fn inherit<T: Send + Sync + 'static>(mut self) -> Self {
+        self.inherited.push(|parent, child| {
+            if let Some(resource) = parent.get::<T>() {
+                child.insert_arc(resource);
+            }
+        });
+        self
+    }
+
+    /// Set how permission checks without a matching rule are handled
+    pub fn with_permission_mode(mut self, mode: PermissionMode) -> Self {
+        self.permission_mode = Some(mode);
+        self
+    }
+
+    /// Set the agent's local permission rules
+    pub fn with_rules(mut self, rules: Vec<PermissionRule>) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Add a local permission rule
+    pub fn with_rule(mut self, rule: PermissionRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Set how many output chunks a subscriber may fall behind
+    ///
+    /// Overrides `AgentRuntime::with_output_capacity` for this agent.
+    pub fn with_output_capacity(mut self, capacity: usize) -> Self {
+        self.output_capacity = Some(capacity);
+        self
+    }
+
+    /// Resolve the inherited resource types against the parent's resources
+    pub(crate) fn inherit_from(mut self, parent: &ResourceMap) -> Self {
+        let mut resources = ResourceMap::new();
+        for inherit in self.inherited.drain(..) {
+            inherit(parent, &mut resources);
+        }
+        resources.extend(&self.resources);
+        self.resources = resources;
+        self
+    }
+
+    pub(crate) fn resources(&self) -> &ResourceMap {
+        &self.resources
+    }
+
+    pub(crate) fn permission_mode(&self) -> Option<PermissionMode> {
+        self.permission_mode
+    }
+
+    pub(crate) fn take_rules(&mut self) -> Vec<PermissionRule> {
+        std::mem::take(&mut self.rules)
+    }
+
+    pub(crate) fn output_capacity(&self) -> Option<usize> {
+        self.output_capacity
+    }
+}
+
+impl std::fmt::Debug for SpawnOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpawnOptions")
+            .field("resources", &self.resources)
+            .field("inherited", &self.inherited.len())
+            .field("permission_mode", &self.permission_mode)
+            .field("rules", &self.rules)
+            .field("output_capacity", &self.output_capacity)
+            .finish()
+    }
+}
@@ -198,8 +198,7 @@ impl std::fmt::Debug for SubAgentManager {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::AgentState;
-    use crate::runtime::channels::create_agent_channels;
+    use crate::runtime::channels::{create_agent_channels, create_state_channel};
     use crate::session::{AgentSession, SessionStorage};
     use std::sync::Arc;
     use tempfile::TempDir;
@@ -219,8 +218,14 @@ mod tests {
         let session = Arc::new(TokioRwLock::new(session));
 
         let (input_tx, _input_rx, output_tx) = create_agent_channels();
-        let state = Arc::new(TokioRwLock::new(AgentState::Idle));
-        let handle = AgentHandle::new(session_id.to_string(), session, input_tx, output_tx, state);
+        let handle = AgentHandle::new(
+            session_id.to_string(),
+            session,
+            input_tx,
+            output_tx,
+            create_state_channel(),
+            crate::permissions::PermissionRules::default(),
+        );
         (handle, temp_dir)
     }
 
@@ -0,0 +1,548 @@
+//! Session cleanup and archiving
+//!
+//! `SessionStorage::cleanup` removes old sessions according to a
+//! `CleanupPolicy`: by age, by count, by total size, or any combination.
+//! Subagent sessions are always handled together with their top-level
+//! session, so a cleanup never leaves children without a parent (or a
+//! parent pointing at deleted children).
+//!
+//! In archive mode, a session and its subagents are packed into
+//! `<base_dir>/.archive/<session_id>.tar.gz` instead of being deleted, and
+//! can be brought back with `SessionStorage::restore_archived`.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use shadow_agent_sdk::session::{CleanupPolicy, SessionStorage};
+//! use std::time::Duration;
+//!
+//! let storage = SessionStorage::new();
+//! let policy = CleanupPolicy::new()
+//!     .with_max_age(Duration::from_secs(30 * 24 * 3600))
+//!     .with_max_bytes(1 << 30)
+//!     .archive();
+//!
+//! let report = storage.cleanup(&policy)?;
+//! println!("Archived {} sessions", report.removed.len());
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::core::error::FrameworkError;
+use crate::core::FrameworkResult;
+
+use super::metadata::SessionMetadata;
+use super::storage::SessionStorage;
+
+/// Directory (under the storage base dir) holding archived sessions
+const ARCHIVE_DIR: &str = ".archive";
+
+/// What happens to sessions a cleanup removes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CleanupMode {
+    /// Delete the session directories
+    #[default]
+    Delete,
+    /// Pack each session (with its subagents) into a `.tar.gz` first
+    Archive,
+}
+
+/// Which sessions a cleanup removes
+///
+/// Limits apply to top-level sessions; subagent sessions count towards their
+/// parent's age and size and are removed with it. A session's age is the time
+/// since it (or any of its subagents) was last updated. With no limits set,
+/// nothing is removed.
+///
+/// Sessions matching the exclude filter (by default, pinned sessions - see
+/// `SessionMetadata::is_pinned`) are never removed but still count towards
+/// `max_sessions` and `max_bytes`.
+#[derive(Clone)]
+pub struct CleanupPolicy {
+    /// Remove sessions not updated for this long
+    pub max_age: Option<Duration>,
+    /// Keep at most this many top-level sessions, removing the oldest
+    pub max_sessions: Option<usize>,
+    /// Keep the total size on disk under this, removing the oldest sessions
+    pub max_bytes: Option<u64>,
+    /// Delete or archive
+    pub mode: CleanupMode,
+    exclude: Arc<dyn Fn(&SessionMetadata) -> bool + Send + Sync>,
+}
+
+impl CleanupPolicy {
+    /// A policy with no limits that keeps pinned sessions
+    pub fn new() -> Self {
+        Self {
+            max_age: None,
+            max_sessions: None,
+            max_bytes: None,
+            mode: CleanupMode::Delete,
+            exclude: Arc::new(SessionMetadata::is_pinned),
+        }
+    }
+
+    /// Remove sessions not updated for `max_age`
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Keep at most `max_sessions` top-level sessions
+    pub fn with_max_sessions(mut self, max_sessions: usize) -> Self {
+        self.max_sessions = Some(max_sessions);
+        self
+    }
+
+    /// Keep the total size of all sessions under `max_bytes`
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Keep sessions for which `exclude` returns true
+    ///
+    /// Replaces the default filter, so include `is_pinned` to keep pinned
+    /// sessions too:
+    ///
+    /// ```rust,ignore
+    /// policy.with_exclude(|m| m.is_pinned() || m.conversation_name.is_some())
+    /// ```
+    pub fn with_exclude<F>(mut self, exclude: F) -> Self
+    where
+        F: Fn(&SessionMetadata) -> bool + Send + Sync + 'static,
+    {
+        self.exclude = Arc::new(exclude);
+        self
+    }
+
+    /// Archive removed sessions instead of deleting them
+    pub fn archive(mut self) -> Self {
+        self.mode = CleanupMode::Archive;
+        self
+    }
+
+    /// Whether `metadata` matches the exclude filter
+    pub fn excludes(&self, metadata: &SessionMetadata) -> bool {
+        (self.exclude)(metadata)
+    }
+}
+
+impl Default for CleanupPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for CleanupPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CleanupPolicy")
+            .field("max_age", &self.max_age)
+            .field("max_sessions", &self.max_sessions)
+            .field("max_bytes", &self.max_bytes)
+            .field("mode", &self.mode)
+            .finish_non_exhaustive()
+    }
+}
+
+/// What a cleanup removed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CleanupReport {
+    /// Removed (or archived) session IDs, subagents included
+    pub removed: Vec<String>,
+    /// Size on disk of the removed sessions
+    pub bytes_freed: u64,
+}
+
+/// A top-level session and all its subagent sessions
+struct SessionGroup {
+    root: String,
+    members: Vec<String>,
+    updated_at: DateTime<Utc>,
+    bytes: u64,
+    keep: bool,
+}
+
+impl SessionStorage {
+    /// Remove sessions according to `policy`
+    pub fn cleanup(&self, policy: &CleanupPolicy) -> FrameworkResult<CleanupReport> {
+        self.cleanup_except(policy, &HashSet::new())
+    }
+
+    /// Like `cleanup`, but never removes the sessions in `protected` (or the
+    /// sessions grouped with them), e.g. sessions of running agents
+    pub fn cleanup_except(
+        &self,
+        policy: &CleanupPolicy,
+        protected: &HashSet<String>,
+    ) -> FrameworkResult<CleanupReport> {
+        let mut groups = self.session_groups(policy, protected)?;
+        // Oldest first
+        groups.sort_by_key(|g| g.updated_at);
+
+        let mut remove = vec![false; groups.len()];
+        let cutoff = policy.max_age.and_then(|max_age| {
+            Utc::now().checked_sub_signed(chrono::Duration::from_std(max_age).ok()?)
+        });
+        if let Some(cutoff) = cutoff {
+            for (i, group) in groups.iter().enumerate() {
+                remove[i] = !group.keep && group.updated_at < cutoff;
+            }
+        }
+
+        let mut count = remove.iter().filter(|r| !**r).count();
+        let mut bytes: u64 = groups
+            .iter()
+            .zip(&remove)
+            .filter(|(_, r)| !**r)
+            .map(|(g, _)| g.bytes)
+            .sum();
+        for (i, group) in groups.iter().enumerate() {
+            let over_count = policy.max_sessions.is_some_and(|max| count > max);
+            let over_bytes = policy.max_bytes.is_some_and(|max| bytes > max);
+            if !over_count && !over_bytes {
+                break;
+            }
+            if !remove[i] && !group.keep {
+                remove[i] = true;
+                count -= 1;
+                bytes -= group.bytes;
+            }
+        }
+
+        let mut report = CleanupReport::default();
+        for (group, _) in groups.iter().zip(&remove).filter(|(_, r)| **r) {
+            if policy.mode == CleanupMode::Archive {
+                self.archive_group(group)?;
+            }
+            for member in &group.members {
+                self.delete_session(member)?;
+            }
+            tracing::info!(
+                "[SessionStorage] {} session {} ({} subagents, {} bytes)",
+                if policy.mode == CleanupMode::Archive { "Archived" } else { "Deleted" },
+                group.root,
+                group.members.len() - 1,
+                group.bytes
+            );
+            report.removed.extend(group.members.iter().cloned());
+            report.bytes_freed += group.bytes;
+        }
+
+        Ok(report)
+    }
+
+    /// Restore an archived session (and its subagents)
+    ///
+    /// `session_id` is the top-level session that was archived. Returns the
+    /// restored session IDs.
+    pub fn restore_archived(&self, session_id: &str) -> FrameworkResult<Vec<String>> {
+        let path = self.archive_path(session_id);
+        if !path.exists() {
+            return Err(FrameworkError::SessionNotFound(session_id.to_string()));
+        }
+        if self.session_exists(session_id) {
+            return Err(FrameworkError::Other(format!(
+                "Cannot restore {}: the session already exists",
+                session_id
+            )));
+        }
+
+        let mut archive = tar::Archive::new(GzDecoder::new(File::open(&path)?));
+        let mut restored = HashSet::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if let Some(id) = entry.path()?.components().next() {
+                restored.insert(id.as_os_str().to_string_lossy().into_owned());
+            }
+            entry.unpack_in(self.base_dir())?;
+        }
+        fs::remove_file(&path)?;
+
+        let mut restored: Vec<String> = restored.into_iter().collect();
+        restored.sort();
+        tracing::info!("[SessionStorage] Restored archived session {}", session_id);
+        Ok(restored)
+    }
+
+    /// IDs of the archived top-level sessions
+    pub fn list_archived(&self) -> FrameworkResult<Vec<String>> {
+        let dir = self.base_dir().join(ARCHIVE_DIR);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut archived = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let name = entry?.file_name();
+            if let Some(id) = name.to_str().and_then(|n| n.strip_suffix(".tar.gz")) {
+                archived.push(id.to_string());
+            }
+        }
+        archived.sort();
+        Ok(archived)
+    }
+
+    /// Path of the archive for a top-level session
+    pub fn archive_path(&self, session_id: &str) -> PathBuf {
+        self.base_dir()
+            .join(ARCHIVE_DIR)
+            .join(format!("{}.tar.gz", session_id))
+    }
+
+    /// Group sessions under their top-level session
+    fn session_groups(
+        &self,
+        policy: &CleanupPolicy,
+        protected: &HashSet<String>,
+    ) -> FrameworkResult<Vec<SessionGroup>> {
+        let sessions: HashMap<String, SessionMetadata> =
+            self.list_sessions_with_metadata(false)?.into_iter().collect();
+
+        let mut groups: HashMap<String, SessionGroup> = HashMap::new();
+        for (id, metadata) in &sessions {
+            // Walk up to the topmost parent still on disk
+            let mut root = id;
+            let mut seen = HashSet::from([id]);
+            while let Some(parent) = sessions[root].parent_session_id.as_ref() {
+                if !sessions.contains_key(parent) || !seen.insert(parent) {
+                    break;
+                }
+                root = parent;
+            }
+
+            let group = groups.entry(root.clone()).or_insert_with(|| SessionGroup {
+                root: root.clone(),
+                members: Vec::new(),
+                updated_at: DateTime::<Utc>::MIN_UTC,
+                bytes: 0,
+                keep: false,
+            });
+            group.members.push(id.clone());
+            group.updated_at = group.updated_at.max(metadata.updated_at);
+            group.bytes += dir_size(&self.session_dir(id));
+            group.keep |= policy.excludes(metadata) || protected.contains(id);
+        }
+
+        Ok(groups
+            .into_values()
+            .map(|mut group| {
+                // Root first, so archives list the parent before its children
+                let root = group.root.clone();
+                group.members.sort_by_key(|m| (*m != root, m.clone()));
+                group
+            })
+            .collect())
+    }
+
+    fn archive_group(&self, group: &SessionGroup) -> FrameworkResult<()> {
+        let path = self.archive_path(&group.root);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut builder = tar::Builder::new(GzEncoder::new(File::create(&path)?, Compression::default()));
+        for member in &group.members {
+            builder.append_dir_all(member, self.session_dir(member))?;
+        }
+        builder.into_inner()?.finish()?;
+        Ok(())
+    }
+}
+
+/// Total size of the files under `path`
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&entry.path()),
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Save a session last updated `days` ago, with some history
+    fn save_session(storage: &SessionStorage, meta: SessionMetadata, days: i64) {
+        let mut meta = meta;
+        meta.updated_at = Utc::now() - chrono::Duration::days(days);
+        storage.save_metadata(&meta).unwrap();
+        storage
+            .append_message(&meta.session_id, &crate::llm::Message::user("x".repeat(1000)))
+            .unwrap();
+    }
+
+    fn root(id: &str) -> SessionMetadata {
+        SessionMetadata::new(id, "coder", "Test", "Testing")
+    }
+
+    fn child(id: &str, parent: &str) -> SessionMetadata {
+        SessionMetadata::new_subagent(id, "helper", "Sub", "Sub", parent, "tool_1")
+    }
+
+    fn remaining(storage: &SessionStorage) -> Vec<String> {
+        let mut sessions = storage.list_sessions().unwrap();
+        sessions.sort();
+        sessions
+    }
+
+    #[test]
+    fn test_max_age() {
+        let temp = TempDir::new().unwrap();
+        let storage = SessionStorage::with_dir(temp.path());
+        save_session(&storage, root("old"), 40);
+        save_session(&storage, root("new"), 1);
+
+        let policy = CleanupPolicy::new().with_max_age(Duration::from_secs(30 * 24 * 3600));
+        let report = storage.cleanup(&policy).unwrap();
+
+        assert_eq!(report.removed, vec!["old".to_string()]);
+        assert!(report.bytes_freed > 1000);
+        assert_eq!(remaining(&storage), vec!["new"]);
+    }
+
+    #[test]
+    fn test_max_sessions_removes_oldest() {
+        let temp = TempDir::new().unwrap();
+        let storage = SessionStorage::with_dir(temp.path());
+        for (id, days) in [("a", 3), ("b", 1), ("c", 2)] {
+            save_session(&storage, root(id), days);
+        }
+
+        let report = storage.cleanup(&CleanupPolicy::new().with_max_sessions(1)).unwrap();
+
+        assert_eq!(report.removed, vec!["a".to_string(), "c".to_string()]);
+        assert_eq!(remaining(&storage), vec!["b"]);
+    }
+
+    #[test]
+    fn test_max_bytes() {
+        let temp = TempDir::new().unwrap();
+        let storage = SessionStorage::with_dir(temp.path());
+        for (id, days) in [("a", 3), ("b", 2), ("c", 1)] {
+            save_session(&storage, root(id), days);
+        }
+        let each = dir_size(&storage.session_dir("a"));
+
+        let policy = CleanupPolicy::new().with_max_bytes(each * 2);
+        let report = storage.cleanup(&policy).unwrap();
+
+        assert_eq!(report.removed, vec!["a".to_string()]);
+        assert_eq!(report.bytes_freed, each);
+        assert_eq!(remaining(&storage), vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_no_limits_removes_nothing() {
+        let temp = TempDir::new().unwrap();
+        let storage = SessionStorage::with_dir(temp.path());
+        save_session(&storage, root("old"), 400);
+
+        assert_eq!(storage.cleanup(&CleanupPolicy::new()).unwrap(), CleanupReport::default());
+        assert_eq!(remaining(&storage), vec!["old"]);
+    }
+
+    #[test]
+    fn test_excluded_sessions_are_kept() {
+        let temp = TempDir::new().unwrap();
+        let storage = SessionStorage::with_dir(temp.path());
+        let mut pinned = root("pinned");
+        pinned.set_custom("pinned", true);
+        save_session(&storage, pinned, 50);
+        let mut named = root("named");
+        named.conversation_name = Some("Fix the build".into());
+        save_session(&storage, named, 50);
+        save_session(&storage, root("plain"), 50);
+
+        let policy = CleanupPolicy::new().with_max_age(Duration::from_secs(24 * 3600));
+        let keep_named = policy
+            .clone()
+            .with_exclude(|m| m.is_pinned() || m.conversation_name.is_some());
+        storage.cleanup(&keep_named).unwrap();
+        assert_eq!(remaining(&storage), vec!["named", "pinned"]);
+
+        // The default filter keeps only pinned sessions
+        storage.cleanup(&policy).unwrap();
+        assert_eq!(remaining(&storage), vec!["pinned"]);
+
+        // Protected sessions are kept whatever the filter says
+        let protected = HashSet::from(["pinned".to_string()]);
+        let policy = CleanupPolicy::new().with_max_sessions(0).with_exclude(|_| false);
+        storage.cleanup_except(&policy, &protected).unwrap();
+        assert_eq!(remaining(&storage), vec!["pinned"]);
+    }
+
+    #[test]
+    fn test_subagents_go_with_their_parent() {
+        let temp = TempDir::new().unwrap();
+        let storage = SessionStorage::with_dir(temp.path());
+        save_session(&storage, root("parent"), 40);
+        // A recently updated child keeps the whole group
+        save_session(&storage, child("child", "parent"), 40);
+        save_session(&storage, child("grandchild", "child"), 1);
+        save_session(&storage, root("old"), 40);
+        save_session(&storage, child("old-child", "old"), 40);
+
+        let policy = CleanupPolicy::new().with_max_age(Duration::from_secs(30 * 24 * 3600));
+        let report = storage.cleanup(&policy).unwrap();
+        assert_eq!(report.removed, vec!["old".to_string(), "old-child".to_string()]);
+        assert_eq!(remaining(&storage), vec!["child", "grandchild", "parent"]);
+
+        // A pinned child keeps its parent
+        let mut pinned = child("pinned-child", "other");
+        pinned.set_custom("pinned", true);
+        save_session(&storage, root("other"), 40);
+        save_session(&storage, pinned, 40);
+        storage.cleanup(&CleanupPolicy::new().with_max_sessions(0)).unwrap();
+        assert_eq!(remaining(&storage), vec!["other", "pinned-child"]);
+    }
+
+    #[test]
+    fn test_archive_and_restore() {
+        let temp = TempDir::new().unwrap();
+        let storage = SessionStorage::with_dir(temp.path());
+        save_session(&storage, root("parent"), 40);
+        save_session(&storage, child("child", "parent"), 40);
+        let history = storage.load_messages("child").unwrap();
+
+        let policy = CleanupPolicy::new().with_max_sessions(0).archive();
+        let report = storage.cleanup(&policy).unwrap();
+        assert_eq!(report.removed, vec!["parent".to_string(), "child".to_string()]);
+        assert!(remaining(&storage).is_empty());
+        assert_eq!(storage.list_archived().unwrap(), vec!["parent"]);
+        assert!(storage.archive_path("parent").exists());
+
+        let restored = storage.restore_archived("parent").unwrap();
+        assert_eq!(restored, vec!["child", "parent"]);
+        assert_eq!(remaining(&storage), vec!["child", "parent"]);
+        assert_eq!(storage.load_messages("child").unwrap().len(), history.len());
+        assert_eq!(
+            storage.load_metadata("child").unwrap().parent_session_id.as_deref(),
+            Some("parent")
+        );
+        assert!(storage.list_archived().unwrap().is_empty());
+
+        assert!(matches!(
+            storage.restore_archived("parent"),
+            Err(FrameworkError::SessionNotFound(_))
+        ));
+    }
+}
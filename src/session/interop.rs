@@ -0,0 +1,500 @@
+//! Transcript interop with the Claude Code JSONL format
+//!
+//! Claude Code keeps a conversation as one JSON record per line. Each
+//! record has a `uuid`, the `parentUuid` of the record before it, a
+//! `timestamp`, a `type` (`user`, `assistant`, `summary`, ...) and, for
+//! conversation turns, the API `message`:
+//!
+//! ```text
+//! {"parentUuid":null,"type":"user","message":{"role":"user","content":"Fix the build"},"uuid":"a1","timestamp":"2025-06-01T12:00:00Z","sessionId":"..."}
+//! {"parentUuid":"a1","type":"assistant","message":{"id":"msg_1","role":"assistant","model":"claude-sonnet-4-5","content":[...]},"uuid":"b2",...}
+//! ```
+//!
+//! `import_claude_jsonl` turns such a transcript into a session that can be
+//! resumed; `export_claude_jsonl` writes a session out the same way.
+//!
+//! On import, the conversation is the chain of `parentUuid` links ending at
+//! the last record, so branches left behind by a rewind are skipped.
+//! Assistant records that share a message `id` (Claude Code writes one per
+//! content block) become one message. Images inside a `tool_result` go
+//! right after it, as this crate sends them. Records and blocks this crate
+//! has no type for are dropped with a warning.
+//!
+//! ```ignore
+//! let storage = SessionStorage::new();
+//! let session = import_claude_jsonl("~/.claude/projects/app/4f1c.jsonl", "imported-4f1c", storage)?;
+//!
+//! let mut out = std::fs::File::create("export.jsonl")?;
+//! export_claude_jsonl(session.session_id(), &mut out)?;
+//! ```
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+use crate::core::{FrameworkError, FrameworkResult};
+use crate::llm::{ContentBlock, Message, MessageContent};
+
+use super::{AgentSession, SessionMetadata, SessionStorage};
+
+/// Agent type given to imported sessions
+pub const IMPORTED_AGENT_TYPE: &str = "imported";
+
+/// Custom metadata key holding the transcript's own session ID
+pub const SOURCE_SESSION_ID_KEY: &str = "claude_session_id";
+
+/// Import a Claude Code transcript as a new session
+///
+/// The session's timestamps, model and conversation name come from the
+/// transcript. Fails if `session_id` is already taken.
+pub fn import_claude_jsonl(
+    path: impl AsRef<Path>,
+    session_id: &str,
+    storage: SessionStorage,
+) -> FrameworkResult<AgentSession> {
+    if storage.session_exists(session_id) {
+        return Err(FrameworkError::other(format!("Session {} already exists", session_id)));
+    }
+    let text = fs::read_to_string(path.as_ref())?;
+    let transcript = Transcript::parse(&text)?;
+
+    let mut metadata = SessionMetadata::new(session_id, IMPORTED_AGENT_TYPE, "Imported session", "");
+    if let Some(model) = &transcript.model {
+        metadata.model = model.clone();
+        metadata.provider = "anthropic".to_string();
+    }
+    if let Some(summary) = &transcript.summary {
+        metadata.set_conversation_name(summary.clone());
+    }
+    if let Some(source) = &transcript.session_id {
+        metadata.set_custom(SOURCE_SESSION_ID_KEY, source.clone());
+    }
+    if let Some(first) = transcript.records.first().and_then(|r| r.timestamp) {
+        metadata.created_at = first;
+    }
+    if let Some(last) = transcript.records.last().and_then(|r| r.timestamp) {
+        metadata.updated_at = last;
+    }
+
+    let messages: Vec<Message> = transcript.records.into_iter().map(|r| r.message).collect();
+    storage.save_metadata(&metadata)?;
+    storage.save_messages(session_id, &messages)?;
+    tracing::info!("[Interop] Imported {} messages into {}", messages.len(), session_id);
+    AgentSession::load_with_storage(session_id, storage)
+}
+
+/// Export a session in the Claude Code JSONL format
+pub fn export_claude_jsonl(session_id: &str, writer: &mut impl Write) -> FrameworkResult<()> {
+    export_claude_jsonl_with_storage(session_id, &SessionStorage::new(), writer)
+}
+
+/// Export a session from custom storage in the Claude Code JSONL format
+///
+/// Records get fresh UUIDs, chained through `parentUuid`. Timestamps are
+/// spread evenly between the session's creation and last update, since
+/// the history doesn't keep one per message.
+pub fn export_claude_jsonl_with_storage(
+    session_id: &str,
+    storage: &SessionStorage,
+    writer: &mut impl Write,
+) -> FrameworkResult<()> {
+    let metadata = storage.load_metadata(session_id)?;
+    let messages = storage.load_messages(session_id)?;
+    let source_id = metadata
+        .get_custom(SOURCE_SESSION_ID_KEY)
+        .and_then(|v| v.as_str())
+        .unwrap_or(session_id)
+        .to_string();
+
+    let span = metadata.updated_at - metadata.created_at;
+    let steps = messages.len().saturating_sub(1).max(1) as i32;
+    let mut parent: Option<String> = None;
+    for (i, message) in messages.iter().enumerate() {
+        let uuid = uuid::Uuid::new_v4().to_string();
+        let timestamp = metadata.created_at + span * i as i32 / steps;
+        let mut api_message = json!({
+            "role": message.role,
+            "content": export_content(&message.content),
+        });
+        if message.role == "assistant" && !metadata.model.is_empty() {
+            api_message["model"] = Value::from(metadata.model.clone());
+        }
+        let record = json!({
+            "parentUuid": parent,
+            "isSidechain": false,
+            "userType": "external",
+            "sessionId": source_id,
+            "type": message.role,
+            "message": api_message,
+            "uuid": uuid,
+            "timestamp": timestamp.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        });
+        writeln!(writer, "{}", serde_json::to_string(&record)?)?;
+        parent = Some(uuid);
+    }
+    if let Some(name) = metadata.conversation_name() {
+        let record = json!({"type": "summary", "summary": name, "leafUuid": parent});
+        writeln!(writer, "{}", serde_json::to_string(&record)?)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// One conversation record of a transcript
+struct Record {
+    uuid: Option<String>,
+    /// Message ID, shared by the records of one assistant response
+    message_id: Option<String>,
+    timestamp: Option<DateTime<Utc>>,
+    message: Message,
+}
+
+/// The parts of a transcript an import keeps
+#[derive(Default)]
+struct Transcript {
+    records: Vec<Record>,
+    model: Option<String>,
+    summary: Option<String>,
+    session_id: Option<String>,
+}
+
+impl Transcript {
+    fn parse(text: &str) -> FrameworkResult<Self> {
+        let mut transcript = Transcript::default();
+        let mut records = Vec::new();
+        let mut links = HashMap::new();
+        let mut dropped: BTreeMap<String, usize> = BTreeMap::new();
+
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let value: Value = serde_json::from_str(line)
+                .map_err(|e| FrameworkError::other(format!("Line {}: {}", number + 1, e)))?;
+            let kind = value["type"].as_str().unwrap_or_default();
+            if let Some(uuid) = value["uuid"].as_str() {
+                links.insert(uuid.to_string(), value["parentUuid"].as_str().map(str::to_string));
+            }
+            match kind {
+                "user" | "assistant" => {}
+                "summary" => {
+                    transcript.summary = value["summary"].as_str().map(str::to_string);
+                    continue;
+                }
+                _ => {
+                    *dropped.entry(format!("{} record", kind)).or_default() += 1;
+                    continue;
+                }
+            }
+            if transcript.session_id.is_none() {
+                transcript.session_id = value["sessionId"].as_str().map(str::to_string);
+            }
+            if kind == "assistant" {
+                if let Some(model) = value["message"]["model"].as_str() {
+                    transcript.model = Some(model.to_string());
+                }
+            }
+            let content = import_content(&value["message"]["content"], &mut dropped);
+            records.push(Record {
+                uuid: value["uuid"].as_str().map(str::to_string),
+                message_id: value["message"]["id"].as_str().map(str::to_string),
+                timestamp: value["timestamp"].as_str().and_then(|t| t.parse().ok()),
+                message: Message {
+                    role: kind.to_string(),
+                    content,
+                },
+            });
+        }
+
+        for (what, count) in &dropped {
+            tracing::warn!("[Interop] Dropped {} unsupported {}(s)", count, what);
+        }
+        transcript.records = merge_responses(current_branch(records, &links));
+        if transcript.records.is_empty() {
+            return Err(FrameworkError::other("Transcript has no messages"));
+        }
+        Ok(transcript)
+    }
+}
+
+/// The records on the chain ending at the last one, oldest first
+///
+/// `links` maps every record's UUID to its parent, including records that
+/// aren't messages, since the chain runs through those too.
+fn current_branch(records: Vec<Record>, links: &HashMap<String, Option<String>>) -> Vec<Record> {
+    let Some(leaf) = records.last().and_then(|r| r.uuid.clone()) else {
+        return records;
+    };
+    let mut chain = HashSet::new();
+    let mut next = Some(leaf);
+    while let Some(uuid) = next {
+        if !chain.insert(uuid.clone()) {
+            break;
+        }
+        next = links.get(&uuid).cloned().flatten();
+    }
+    records
+        .into_iter()
+        .filter(|r| r.uuid.as_ref().is_none_or(|uuid| chain.contains(uuid)))
+        .collect()
+}
+
+/// Join the records Claude Code writes per block of one response, and the
+/// records it writes per result of parallel tool calls
+///
+/// Joined tool results come first, then the images and documents they held.
+fn merge_responses(records: Vec<Record>) -> Vec<Record> {
+    let mut merged: Vec<Record> = Vec::new();
+    for record in records {
+        if let Some(last) = merged.last_mut() {
+            let same_response = record.message.role == "assistant"
+                && last.message.role == "assistant"
+                && record.message_id.is_some()
+                && record.message_id == last.message_id;
+            let same_results = record.message.role == "user"
+                && last.message.role == "user"
+                && is_tool_results(&record.message.content)
+                && is_tool_results(&last.message.content);
+            if same_response || same_results {
+                let mut blocks = into_blocks(std::mem::replace(&mut last.message.content, MessageContent::Blocks(Vec::new())));
+                blocks.extend(into_blocks(record.message.content));
+                last.message.content = MessageContent::Blocks(blocks);
+                last.uuid = record.uuid;
+                continue;
+            }
+        }
+        merged.push(record);
+    }
+    for record in &mut merged {
+        if let MessageContent::Blocks(blocks) = &mut record.message.content {
+            if is_tool_results_blocks(blocks) {
+                blocks.sort_by_key(|block| !matches!(block, ContentBlock::ToolResult { .. }));
+            }
+        }
+    }
+    merged
+}
+
+/// Whether content is tool results and the media they held, and nothing else
+fn is_tool_results(content: &MessageContent) -> bool {
+    matches!(content, MessageContent::Blocks(blocks) if is_tool_results_blocks(blocks))
+}
+
+fn is_tool_results_blocks(blocks: &[ContentBlock]) -> bool {
+    matches!(blocks.first(), Some(ContentBlock::ToolResult { .. }))
+        && blocks.iter().all(|block| {
+            matches!(block, ContentBlock::ToolResult { .. } | ContentBlock::Image { .. } | ContentBlock::Document { .. })
+        })
+}
+
+fn into_blocks(content: MessageContent) -> Vec<ContentBlock> {
+    match content {
+        MessageContent::Text(text) => vec![ContentBlock::text(text)],
+        MessageContent::Blocks(blocks) => blocks,
+    }
+}
+
+/// Convert a transcript message's content
+fn import_content(content: &Value, dropped: &mut BTreeMap<String, usize>) -> MessageContent {
+    let Some(items) = content.as_array() else {
+        return MessageContent::Text(content.as_str().unwrap_or_default().to_string());
+    };
+    let mut blocks = Vec::new();
+    for item in items {
+        if item["type"] == "tool_result" {
+            import_tool_result(item, &mut blocks, dropped);
+            continue;
+        }
+        match serde_json::from_value::<ContentBlock>(strip_cache_control(item)) {
+            Ok(block) => blocks.push(block),
+            Err(_) => {
+                let kind = item["type"].as_str().unwrap_or("untyped");
+                *dropped.entry(format!("{} block", kind)).or_default() += 1;
+            }
+        }
+    }
+    MessageContent::Blocks(blocks)
+}
+
+/// A `tool_result` block, with any images it holds moved after it (and
+/// after the message's other results, by `merge_responses`)
+fn import_tool_result(item: &Value, blocks: &mut Vec<ContentBlock>, dropped: &mut BTreeMap<String, usize>) {
+    let mut texts = Vec::new();
+    let mut media = Vec::new();
+    match &item["content"] {
+        Value::String(text) => texts.push(text.clone()),
+        Value::Array(parts) => {
+            for part in parts {
+                if part["type"] == "text" {
+                    texts.push(part["text"].as_str().unwrap_or_default().to_string());
+                    continue;
+                }
+                match serde_json::from_value::<ContentBlock>(strip_cache_control(part)) {
+                    Ok(block @ (ContentBlock::Image { .. } | ContentBlock::Document { .. })) => media.push(block),
+                    _ => {
+                        let kind = part["type"].as_str().unwrap_or("untyped");
+                        *dropped.entry(format!("{} block in a tool result", kind)).or_default() += 1;
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+    blocks.push(ContentBlock::ToolResult {
+        tool_use_id: item["tool_use_id"].as_str().unwrap_or_default().to_string(),
+        content: (!texts.is_empty()).then(|| texts.join("\n\n")),
+        is_error: item["is_error"].as_bool().filter(|&e| e),
+        cache_control: None,
+    });
+    blocks.extend(media);
+}
+
+fn strip_cache_control(item: &Value) -> Value {
+    let mut item = item.clone();
+    if let Some(map) = item.as_object_mut() {
+        map.remove("cache_control");
+    }
+    item
+}
+
+/// Convert message content to the transcript form, folding the images and
+/// documents after a `tool_result` back into it
+fn export_content(content: &MessageContent) -> Value {
+    let blocks = match content {
+        MessageContent::Text(text) => return Value::from(text.clone()),
+        MessageContent::Blocks(blocks) => blocks,
+    };
+    let mut items: Vec<Value> = Vec::new();
+    let mut in_tool_result = false;
+    for block in blocks {
+        let mut value = strip_cache_control(&serde_json::to_value(block).unwrap_or_default());
+        match block {
+            ContentBlock::ToolResult { content, .. } => {
+                let parts: Vec<Value> = content.iter().map(|text| json!({"type": "text", "text": text})).collect();
+                value["content"] = Value::Array(parts);
+                in_tool_result = true;
+            }
+            ContentBlock::Image { .. } | ContentBlock::Document { .. } if in_tool_result => {
+                if let Some(parts) = items.last_mut().and_then(|last| last["content"].as_array_mut()) {
+                    parts.push(value);
+                    continue;
+                }
+            }
+            _ => in_tool_result = false,
+        }
+        items.push(value);
+    }
+    Value::Array(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/interop").join(name)
+    }
+
+    fn kinds(message: &Message) -> Vec<String> {
+        match &message.content {
+            MessageContent::Text(_) => vec!["string".to_string()],
+            MessageContent::Blocks(blocks) => blocks
+                .iter()
+                .map(|b| serde_json::to_value(b).unwrap()["type"].as_str().unwrap().to_string())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_import_follows_the_current_branch() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = SessionStorage::with_dir(dir.path());
+        let session = import_claude_jsonl(fixture("claude_code.jsonl"), "imported", storage).unwrap();
+
+        let history = session.history();
+        let kinds: Vec<_> = history.iter().map(kinds).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                vec!["string"],
+                vec!["thinking", "text", "tool_use"],
+                vec!["tool_result", "image"],
+                vec!["text"],
+            ]
+        );
+        // The abandoned branch and the unknown records are gone
+        assert!(!serde_json::to_string(history).unwrap().contains("abandoned"));
+        let MessageContent::Blocks(blocks) = &history[2].content else { panic!() };
+        assert!(matches!(&blocks[0], ContentBlock::ToolResult { content: Some(t), .. } if t == "2 screenshots"));
+
+        assert_eq!(session.model(), "claude-sonnet-4-5");
+        assert_eq!(session.conversation_name(), Some("Screenshot the login page"));
+        let metadata = SessionStorage::with_dir(dir.path()).load_metadata("imported").unwrap();
+        assert_eq!(metadata.created_at.to_rfc3339(), "2025-06-01T12:00:00+00:00");
+        assert_eq!(metadata.get_custom(SOURCE_SESSION_ID_KEY), Some(&json!("4f1c")));
+
+        let storage = SessionStorage::with_dir(dir.path());
+        assert!(import_claude_jsonl(fixture("claude_code.jsonl"), "imported", storage).is_err());
+    }
+
+    #[test]
+    fn test_parallel_tool_results_are_joined() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = SessionStorage::with_dir(dir.path());
+        let session = import_claude_jsonl(fixture("parallel_tools.jsonl"), "imported", storage).unwrap();
+
+        let history = session.history();
+        let kinds: Vec<_> = history.iter().map(kinds).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                vec!["string"],
+                vec!["text", "tool_use", "tool_use"],
+                vec!["tool_result", "tool_result", "image"],
+                vec!["text"],
+            ]
+        );
+        let MessageContent::Blocks(blocks) = &history[2].content else { panic!() };
+        let ids: Vec<_> = blocks
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::ToolResult { tool_use_id, .. } => Some(tool_use_id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ids, vec!["toolu_1", "toolu_2"]);
+    }
+
+    #[test]
+    fn test_export_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = SessionStorage::with_dir(dir.path());
+        let first = import_claude_jsonl(fixture("claude_code.jsonl"), "first", storage.clone()).unwrap();
+
+        let mut exported = Vec::new();
+        export_claude_jsonl_with_storage("first", &storage, &mut exported).unwrap();
+        let text = String::from_utf8(exported).unwrap();
+        let records: Vec<Value> = text.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[0]["parentUuid"], Value::Null);
+        assert_eq!(records[1]["parentUuid"], records[0]["uuid"]);
+        assert_eq!(records[3]["timestamp"], "2025-06-01T12:00:09.000Z");
+        assert_eq!(records[4]["summary"], "Screenshot the login page");
+        // The image is back inside the tool result
+        let tool_result = &records[2]["message"]["content"][0];
+        assert_eq!(tool_result["content"][1]["type"], "image");
+
+        let path = dir.path().join("export.jsonl");
+        fs::write(&path, &text).unwrap();
+        let second = import_claude_jsonl(&path, "second", storage).unwrap();
+        assert_eq!(
+            serde_json::to_value(second.history()).unwrap(),
+            serde_json::to_value(first.history()).unwrap()
+        );
+        assert_eq!(second.conversation_name(), first.conversation_name());
+    }
+}
@@ -5,6 +5,23 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
 
+/// Custom metadata key holding the latest conversation summary
+pub const SUMMARY_METADATA_KEY: &str = "summary";
+
+/// Custom metadata key holding the number of completed turns
+pub const TURNS_METADATA_KEY: &str = "turns";
+
+/// A summary of the conversation, stored in the session's custom metadata
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionSummary {
+    /// The summary text
+    pub text: String,
+    /// How many history messages it covers (the first `message_count`)
+    pub message_count: usize,
+    /// When it was written
+    pub updated_at: DateTime<Utc>,
+}
+
 /// Metadata for an agent session
 ///
 /// This is persisted separately from the message history for quick access.
@@ -171,6 +188,13 @@ impl SessionMetadata {
     pub fn get_custom(&self, key: &str) -> Option<&Value> {
         self.custom.get(key)
     }
+
+    /// Whether the `pinned` custom metadata flag is set
+    ///
+    /// Pinned sessions are kept by session cleanup (see `CleanupPolicy`).
+    pub fn is_pinned(&self) -> bool {
+        self.custom.get("pinned") == Some(&Value::Bool(true))
+    }
 }
 
 #[cfg(test)]
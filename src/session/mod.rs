@@ -5,11 +5,17 @@
 //!
 //! Each agent has its own session with a unique session_id. Sessions can
 //! be linked via parent/child relationships for subagent tracking.
+//! The `interop` module imports and exports Claude Code transcripts.
 
+pub mod cleanup;
+pub mod interop;
 pub mod metadata;
+#[allow(clippy::module_inception)]
 pub mod session;
 pub mod storage;
 
-pub use metadata::SessionMetadata;
+pub use cleanup::{CleanupMode, CleanupPolicy, CleanupReport};
+pub use interop::{export_claude_jsonl, export_claude_jsonl_with_storage, import_claude_jsonl};
+pub use metadata::{SessionMetadata, SessionSummary, SUMMARY_METADATA_KEY, TURNS_METADATA_KEY};
 pub use session::AgentSession;
 pub use storage::SessionStorage;
@@ -6,7 +6,7 @@
 use crate::core::FrameworkResult;
 use crate::llm::Message;
 
-use super::metadata::SessionMetadata;
+use super::metadata::{SessionMetadata, SessionSummary, SUMMARY_METADATA_KEY, TURNS_METADATA_KEY};
 use super::storage::SessionStorage;
 
 /// An agent session that tracks conversation history and metadata
@@ -199,6 +199,18 @@ impl AgentSession {
         &self.metadata.child_session_ids
     }
 
+    /// Record a child session on this session and persist the metadata
+    ///
+    /// Creating a subagent session updates the parent's metadata on disk; a
+    /// parent that is already loaded calls this so its next save keeps the link.
+    pub fn add_child(&mut self, child_session_id: impl Into<String>) -> FrameworkResult<()> {
+        let child_session_id = child_session_id.into();
+        if !self.metadata.child_session_ids.contains(&child_session_id) {
+            self.metadata.add_child(child_session_id);
+        }
+        self.storage.save_metadata(&self.metadata)
+    }
+
     /// Add a message to the conversation history
     ///
     /// The message is immediately persisted to disk.
@@ -216,6 +228,18 @@ impl AgentSession {
         &self.messages
     }
 
+    /// Remove every message after the first `message_index`
+    ///
+    /// Keeps `history()[..message_index]` and rewrites the history file to
+    /// match. Does nothing if the history isn't that long.
+    pub fn truncate_after(&mut self, message_index: usize) -> FrameworkResult<()> {
+        if message_index >= self.messages.len() {
+            return Ok(());
+        }
+        self.messages.truncate(message_index);
+        self.save()
+    }
+
     /// Get a mutable reference to the conversation history
     ///
     /// Note: Changes made directly to this vector are not automatically persisted.
@@ -304,11 +328,53 @@ impl AgentSession {
         self.metadata.set_custom(key, value);
     }
 
+    /// Persist the metadata without rewriting the history
+    ///
+    /// Use after `set_custom` when the value must survive a crash before the
+    /// next message is added.
+    pub fn save_metadata(&self) -> FrameworkResult<()> {
+        self.storage.save_metadata(&self.metadata)
+    }
+
     /// Get custom metadata
     pub fn get_custom(&self, key: &str) -> Option<&serde_json::Value> {
         self.metadata.get_custom(key)
     }
 
+    /// Store a summary of the first `message_count` history messages
+    ///
+    /// Replaces the previous summary and saves the metadata.
+    pub fn set_summary(&mut self, text: impl Into<String>, message_count: usize) -> FrameworkResult<()> {
+        let summary = SessionSummary {
+            text: text.into(),
+            message_count,
+            updated_at: chrono::Utc::now(),
+        };
+        let value = serde_json::to_value(&summary)?;
+        self.metadata.set_custom(SUMMARY_METADATA_KEY, value);
+        self.save_metadata()
+    }
+
+    /// The latest summary stored with `set_summary`
+    pub fn summary(&self) -> Option<SessionSummary> {
+        self.get_custom(SUMMARY_METADATA_KEY)
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+    }
+
+    /// Number of turns completed, as recorded by the agent
+    ///
+    /// `None` for sessions saved before the count was kept.
+    pub fn completed_turns(&self) -> Option<usize> {
+        self.get_custom(TURNS_METADATA_KEY)
+            .and_then(|value| value.as_u64())
+            .map(|turns| turns as usize)
+    }
+
+    /// Record the number of completed turns (saved with the metadata)
+    pub fn set_completed_turns(&mut self, turns: usize) {
+        self.metadata.set_custom(TURNS_METADATA_KEY, turns);
+    }
+
     /// List all sessions in storage
     pub fn list_all() -> FrameworkResult<Vec<String>> {
         SessionStorage::new().list_sessions()
@@ -481,6 +547,29 @@ mod tests {
         assert_eq!(reloaded.history().len(), 2);
     }
 
+    #[test]
+    fn test_truncate_after() {
+        let (storage, _temp) = create_test_storage();
+
+        let mut session =
+            AgentSession::new_with_storage("truncate_test", "coder", "Test", "Testing", storage.clone())
+                .unwrap();
+        session.add_message(Message::user("One")).unwrap();
+        session.add_message(Message::assistant("Two")).unwrap();
+        session.add_message(Message::user("Three")).unwrap();
+
+        session.truncate_after(1).unwrap();
+        assert_eq!(session.history().len(), 1);
+        session.truncate_after(5).unwrap();
+        assert_eq!(session.history().len(), 1);
+
+        // Appending after a truncate lands after the kept messages on disk
+        session.add_message(Message::assistant("Four")).unwrap();
+        let reloaded = AgentSession::load_with_storage("truncate_test", storage).unwrap();
+        let texts: Vec<_> = reloaded.history().iter().map(|m| m.text().unwrap()).collect();
+        assert_eq!(texts, vec!["One", "Four"]);
+    }
+
     #[test]
     fn test_save_and_reload() {
         let (storage, _temp) = create_test_storage();
@@ -51,6 +51,79 @@ impl SessionStorage {
         self.session_dir(session_id).join("history.jsonl")
     }
 
+    /// Get the directory for tool output saved outside the history
+    ///
+    /// Lives inside the session directory, so it is deleted or archived
+    /// with the session by `SessionCleanup`.
+    pub fn outputs_dir(&self, session_id: &str) -> PathBuf {
+        self.session_dir(session_id).join("outputs")
+    }
+
+    /// Save the full output of a tool call to the outputs directory
+    ///
+    /// Returns the path of the file, named after the tool call ID.
+    pub fn save_tool_output(&self, session_id: &str, tool_use_id: &str, content: &str) -> FrameworkResult<PathBuf> {
+        let dir = self.outputs_dir(session_id);
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.txt", file_name_safe(tool_use_id)));
+        fs::write(&path, content)?;
+        Ok(path)
+    }
+
+    /// Get the path of the journal of file changes (see `FileChangeJournal`)
+    pub fn changes_path(&self, session_id: &str) -> PathBuf {
+        self.session_dir(session_id).join("changes.jsonl")
+    }
+
+    /// Get the directory for copies of files the agent replaced
+    ///
+    /// Lives inside the session directory, like `outputs_dir`.
+    pub fn backups_dir(&self, session_id: &str) -> PathBuf {
+        self.session_dir(session_id).join("backups")
+    }
+
+    /// Keep a copy of a file's contents before a tool call replaces them
+    ///
+    /// Returns the path of the copy, `backups/<tool_use_id>/<file name>`.
+    pub fn save_backup(
+        &self,
+        session_id: &str,
+        tool_use_id: &str,
+        file: &Path,
+        content: &[u8],
+    ) -> FrameworkResult<PathBuf> {
+        let dir = self.backups_dir(session_id).join(file_name_safe(tool_use_id));
+        fs::create_dir_all(&dir)?;
+        let name = file
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "file".to_string());
+        let path = dir.join(name);
+        fs::write(&path, content)?;
+        Ok(path)
+    }
+
+    /// Get the path of the notes the Memory tool keeps for one session
+    pub fn session_memory_path(&self, session_id: &str) -> PathBuf {
+        self.session_dir(session_id).join("memory.json")
+    }
+
+    /// Get the path of the notes shared by all sessions of an agent type
+    ///
+    /// Shared notes live under `.memory/`, outside any session directory, so
+    /// deleting a session keeps them.
+    pub fn agent_memory_path(&self, agent_type: &str) -> PathBuf {
+        self.base_dir
+            .join(".memory")
+            .join("agents")
+            .join(format!("{}.json", file_name_safe(agent_type)))
+    }
+
+    /// Get the path of the notes shared by every agent
+    pub fn global_memory_path(&self) -> PathBuf {
+        self.base_dir.join(".memory").join("global.json")
+    }
+
     /// Create the session directory if it doesn't exist
     pub fn ensure_session_dir(&self, session_id: &str) -> FrameworkResult<PathBuf> {
         let dir = self.session_dir(session_id);
@@ -236,6 +309,40 @@ impl SessionStorage {
         Ok(())
     }
 
+    /// Delete a session and its subagent sessions, recursively
+    ///
+    /// Returns the deleted session IDs, the session itself first.
+    pub fn delete_session_tree(&self, session_id: &str) -> FrameworkResult<Vec<String>> {
+        let sessions = self.list_sessions_with_metadata(false)?;
+        let mut deleted = vec![session_id.to_string()];
+        let mut next = 0;
+        while next < deleted.len() {
+            let parent = deleted[next].clone();
+            for (id, metadata) in &sessions {
+                if metadata.parent_session_id.as_deref() == Some(parent.as_str()) && !deleted.contains(id) {
+                    deleted.push(id.clone());
+                }
+            }
+            next += 1;
+        }
+        for id in &deleted {
+            self.delete_session(id)?;
+        }
+        Ok(deleted)
+    }
+
+    /// Number of messages in a session's history, without parsing them
+    pub fn message_count(&self, session_id: &str) -> usize {
+        let Ok(file) = File::open(self.history_path(session_id)) else {
+            return 0;
+        };
+        BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter(|line| !line.trim().is_empty())
+            .count()
+    }
+
     /// Get the base directory
     pub fn base_dir(&self) -> &Path {
         &self.base_dir
@@ -248,6 +355,13 @@ impl Default for SessionStorage {
     }
 }
 
+/// A tool call ID made safe to use as a file name
+fn file_name_safe(id: &str) -> String {
+    id.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -328,6 +442,27 @@ mod tests {
         assert!(!storage.session_exists("to_delete"));
     }
 
+    #[test]
+    fn test_delete_session_tree() {
+        let (storage, _temp) = create_test_storage();
+        let subagent = |id: &str, parent: &str| SessionMetadata::new_subagent(id, "sub", "Sub", "Sub", parent, "tool_1");
+        storage.save_metadata(&SessionMetadata::new("parent", "main", "P", "P")).unwrap();
+        storage.save_metadata(&subagent("child", "parent")).unwrap();
+        storage.save_metadata(&subagent("grandchild", "child")).unwrap();
+        storage.save_metadata(&SessionMetadata::new("other", "main", "O", "O")).unwrap();
+        storage.save_metadata(&subagent("other-child", "other")).unwrap();
+        storage.append_message("parent", &Message::user("Hello")).unwrap();
+        storage.append_message("parent", &Message::assistant("Hi")).unwrap();
+        assert_eq!(storage.message_count("parent"), 2);
+        assert_eq!(storage.message_count("child"), 0);
+
+        let deleted = storage.delete_session_tree("parent").unwrap();
+        assert_eq!(deleted, vec!["parent", "child", "grandchild"]);
+        let mut remaining = storage.list_sessions().unwrap();
+        remaining.sort();
+        assert_eq!(remaining, vec!["other", "other-child"]);
+    }
+
     #[test]
     fn test_list_sessions_filtered() {
         let (storage, _temp) = create_test_storage();
@@ -0,0 +1,251 @@
+//! Telemetry spans for agent activity
+//!
+//! Helpers that create the `tracing` spans recorded by `StandardAgent` and
+//! `ToolExecutor`. The spans are always recorded; they only leave the process
+//! when an exporter is installed (see `logging::init_otel`, behind the `otel`
+//! feature).
+//!
+//! Attribute names follow the OpenTelemetry GenAI semantic conventions where
+//! they exist:
+//!
+//! - **Turn** (`invoke_agent`): `gen_ai.conversation.id`, `turn.index`
+//! - **LLM call** (`chat {model}`): `gen_ai.provider.name`, `gen_ai.request.model`,
+//!   `gen_ai.response.model`, `gen_ai.usage.input_tokens`,
+//!   `gen_ai.usage.output_tokens`, `gen_ai.usage.cache_read.input_tokens`,
+//!   `gen_ai.usage.cache_creation.input_tokens`, `gen_ai.response.finish_reasons`,
+//!   `duration_ms`
+//! - **Tool execution** (`execute_tool {name}`): `gen_ai.tool.name`,
+//!   `gen_ai.tool.call.id`, `is_error`, `duration_ms`
+//!
+//! Every span also carries `session_id`, which routes its events to the
+//! per-session log files.
+
+use std::time::Duration;
+
+use tracing::field::Empty;
+use tracing::Span;
+
+use crate::llm::{StopReason, Usage};
+
+/// Span for one user turn
+pub fn turn_span(session_id: &str, turn_index: usize) -> Span {
+    tracing::info_span!(
+        "agent_turn",
+        otel.name = "invoke_agent",
+        gen_ai.operation.name = "invoke_agent",
+        gen_ai.conversation.id = %session_id,
+        session_id = %session_id,
+        turn.index = turn_index as i64,
+    )
+}
+
+/// Span for one LLM call
+///
+/// Usage, finish reason and latency are filled in with the `record_*` helpers.
+pub fn llm_span(provider: &str, model: &str, session_id: &str) -> Span {
+    tracing::info_span!(
+        "llm_call",
+        otel.name = %format!("chat {}", model),
+        gen_ai.operation.name = "chat",
+        gen_ai.provider.name = %provider,
+        gen_ai.request.model = %model,
+        gen_ai.response.model = Empty,
+        gen_ai.usage.input_tokens = Empty,
+        gen_ai.usage.output_tokens = Empty,
+        gen_ai.usage.cache_read.input_tokens = Empty,
+        gen_ai.usage.cache_creation.input_tokens = Empty,
+        gen_ai.response.finish_reasons = Empty,
+        duration_ms = Empty,
+        session_id = %session_id,
+    )
+}
+
+/// Span for one tool execution
+pub fn tool_span(session_id: &str, tool_name: &str, tool_id: &str) -> Span {
+    tracing::info_span!(
+        "tool_execution",
+        otel.name = %format!("execute_tool {}", tool_name),
+        gen_ai.operation.name = "execute_tool",
+        gen_ai.tool.name = %tool_name,
+        gen_ai.tool.call.id = %tool_id,
+        is_error = Empty,
+        duration_ms = Empty,
+        session_id = %session_id,
+    )
+}
+
+/// Record token usage on an LLM span
+pub fn record_llm_usage(span: &Span, usage: &Usage) {
+    span.record("gen_ai.usage.input_tokens", usage.input_tokens as i64);
+    span.record("gen_ai.usage.output_tokens", usage.output_tokens as i64);
+    if let Some(cache_read) = usage.cache_read_input_tokens {
+        span.record("gen_ai.usage.cache_read.input_tokens", cache_read as i64);
+    }
+    if let Some(cache_creation) = usage.cache_creation_input_tokens {
+        span.record("gen_ai.usage.cache_creation.input_tokens", cache_creation as i64);
+    }
+}
+
+/// Record the response model and stop reason on an LLM span
+pub fn record_llm_response(span: &Span, model: Option<&str>, stop_reason: Option<&StopReason>) {
+    if let Some(model) = model {
+        span.record("gen_ai.response.model", model);
+    }
+    if let Some(reason) = stop_reason.and_then(stop_reason_name) {
+        span.record("gen_ai.response.finish_reasons", reason.as_str());
+    }
+}
+
+/// Record the outcome of a tool execution
+pub fn record_tool_result(span: &Span, is_error: bool) {
+    span.record("is_error", is_error);
+}
+
+/// Record the elapsed time on an LLM or tool span
+pub fn record_duration(span: &Span, elapsed: Duration) {
+    span.record("duration_ms", elapsed.as_millis() as i64);
+}
+
+/// Wire name of a stop reason (e.g. `end_turn`)
+fn stop_reason_name(reason: &StopReason) -> Option<String> {
+    serde_json::to_value(reason)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+}
+
+#[cfg(all(test, feature = "otel"))]
+mod tests {
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use opentelemetry::Value;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+    use serde_json::json;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::agent::AgentConfig;
+    use crate::cli::print_mode::run_mock_agent;
+    use crate::llm::{define_tool, MockLlmProvider, ToolDefinition};
+    use crate::logging::otel_layer;
+    use crate::runtime::AgentInternals;
+    use crate::session::{AgentSession, SessionStorage};
+    use crate::tools::{Tool, ToolInfo, ToolRegistry, ToolResult};
+
+    struct EchoTool;
+
+    #[async_trait]
+    impl Tool for EchoTool {
+        fn name(&self) -> &str {
+            "Echo"
+        }
+
+        fn description(&self) -> &str {
+            "Echo the input"
+        }
+
+        fn definition(&self) -> ToolDefinition {
+            define_tool("Echo", "Echo the input", json!({}), vec![])
+        }
+
+        fn get_info(&self, _input: &serde_json::Value) -> ToolInfo {
+            ToolInfo {
+                name: "Echo".to_string(),
+                action_description: "Echo".to_string(),
+                details: None,
+                read_only: false,
+            }
+        }
+
+        async fn execute(
+            &self,
+            input: &serde_json::Value,
+            _internals: &mut AgentInternals,
+        ) -> anyhow::Result<ToolResult> {
+            Ok(ToolResult::success(input.to_string()))
+        }
+    }
+
+    fn attribute(span: &SpanData, key: &str) -> Option<Value> {
+        span.attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.clone())
+    }
+
+    fn find<'a>(spans: &'a [SpanData], name: &str) -> &'a SpanData {
+        spans
+            .iter()
+            .find(|s| s.name == name)
+            .unwrap_or_else(|| panic!("missing span '{}'", name))
+    }
+
+    #[tokio::test]
+    async fn test_spans_exported() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry().with(otel_layer(&provider));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let mut tool_call = MockLlmProvider::tool_use_response("call_1", "Echo", json!({"x": 1}));
+        tool_call.usage.input_tokens = 12;
+        tool_call.usage.output_tokens = 3;
+        tool_call.usage.cache_read_input_tokens = Some(8);
+        let llm = MockLlmProvider::new()
+            .with_response(tool_call)
+            .with_text("done");
+
+        let mut registry = ToolRegistry::new();
+        registry.register(EchoTool);
+        let config = AgentConfig::new("test")
+            .with_tools(Arc::new(registry))
+            .with_auto_name(false);
+
+        let dir = tempfile::tempdir().unwrap();
+        let session = AgentSession::new_with_storage(
+            "otel-session",
+            "test",
+            "Test",
+            "",
+            SessionStorage::with_dir(dir.path()),
+        )
+        .unwrap();
+        let result = run_mock_agent(config, Arc::new(llm), session, "go").await;
+        assert_eq!(result.exit_code(), 0);
+
+        provider.force_flush().unwrap();
+        let spans = exporter.get_finished_spans().unwrap();
+
+        let turn = find(&spans, "invoke_agent");
+        assert_eq!(attribute(turn, "gen_ai.conversation.id"), Some("otel-session".into()));
+        assert_eq!(attribute(turn, "turn.index"), Some(Value::I64(0)));
+
+        let chat = spans
+            .iter()
+            .find(|s| {
+                s.name == "chat mock-model"
+                    && attribute(s, "gen_ai.usage.input_tokens") == Some(Value::I64(12))
+            })
+            .expect("missing chat span with usage");
+        assert_eq!(attribute(chat, "gen_ai.provider.name"), Some("mock".into()));
+        assert_eq!(attribute(chat, "gen_ai.request.model"), Some("mock-model".into()));
+        assert_eq!(attribute(chat, "gen_ai.usage.output_tokens"), Some(Value::I64(3)));
+        assert_eq!(
+            attribute(chat, "gen_ai.usage.cache_read.input_tokens"),
+            Some(Value::I64(8))
+        );
+        assert_eq!(
+            attribute(chat, "gen_ai.response.finish_reasons"),
+            Some("tool_use".into())
+        );
+        assert!(attribute(chat, "duration_ms").is_some());
+
+        let tool = find(&spans, "execute_tool Echo");
+        assert_eq!(attribute(tool, "gen_ai.tool.name"), Some("Echo".into()));
+        assert_eq!(attribute(tool, "gen_ai.tool.call.id"), Some("call_1".into()));
+        assert_eq!(attribute(tool, "is_error"), Some(Value::Bool(false)));
+        assert!(attribute(tool, "duration_ms").is_some());
+        assert_eq!(tool.parent_span_id, turn.span_context.span_id());
+    }
+}
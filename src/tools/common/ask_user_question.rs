@@ -2,7 +2,12 @@
 //!
 //! This tool allows the agent to ask the user questions and wait for responses.
 //! Questions are sent via `OutputChunk::AskUserQuestion`, and the tool waits
-//! for the user's answers via `InputMessage::UserQuestionResponse`.
+//! for the user's answers via `InputMessage::UserQuestionResponse`
+//! (`AgentHandle::send_question_response`).
+//!
+//! Each question has 2-4 options and may allow multiple selections. Renderers
+//! show the options as a menu plus an "Other" free-text entry; answers are
+//! keyed by question header.
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -160,6 +165,7 @@ impl Tool for AskUserQuestionTool {
             name: "AskUserQuestion".to_string(),
             action_description: format!("Ask user {} question(s)", question_count),
             details: None,
+            read_only: false,
         }
     }
 
@@ -243,4 +249,104 @@ impl Tool for AskUserQuestionTool {
     fn requires_permission(&self) -> bool {
         false // Questions ARE the user interaction, no additional permission needed
     }
+
+    fn runs_in_dry_run(&self) -> bool {
+        true // Asking changes nothing outside the conversation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::agent::{AgentConfig, StandardAgent};
+    use crate::core::OutputChunk;
+    use crate::llm::MockLlmProvider;
+    use crate::runtime::AgentRuntime;
+    use crate::session::{AgentSession, SessionStorage};
+    use crate::tools::ToolRegistry;
+
+    #[tokio::test]
+    async fn test_question_response_reaches_tool() {
+        let llm = MockLlmProvider::new()
+            .with_response(MockLlmProvider::tool_use_response(
+                "call_1",
+                "AskUserQuestion",
+                json!({"questions": [{
+                    "question": "Which approach?",
+                    "header": "Approach",
+                    "options": [
+                        {"label": "A", "description": "First"},
+                        {"label": "B", "description": "Second"},
+                        {"label": "C", "description": "Third"}
+                    ],
+                    "multiSelect": true
+                }]}),
+            ))
+            .with_text("done");
+
+        let mut registry = ToolRegistry::new();
+        registry.register(AskUserQuestionTool::new());
+        let config = AgentConfig::new("test")
+            .with_tools(Arc::new(registry))
+            .with_auto_name(false);
+        let agent = StandardAgent::new(config, Arc::new(llm.clone()));
+
+        let dir = tempfile::tempdir().unwrap();
+        let session = AgentSession::new_with_storage(
+            "question-session",
+            "test",
+            "Test",
+            "",
+            SessionStorage::with_dir(dir.path()),
+        )
+        .unwrap();
+        let runtime = AgentRuntime::new();
+        let handle = runtime
+            .spawn(session, move |internals| agent.run(internals))
+            .await;
+
+        let mut rx = handle.subscribe();
+        handle.send_input("pick one").await.unwrap();
+
+        let mut asked = None;
+        loop {
+            match rx.recv().await.unwrap() {
+                OutputChunk::PermissionRequest { request_id, .. } => {
+                    handle
+                        .send_permission_response_by_id(request_id, true, false)
+                        .await
+                        .unwrap();
+                }
+                OutputChunk::AskUserQuestion {
+                    request_id,
+                    questions,
+                } => {
+                    asked = Some(questions.clone());
+                    let answers =
+                        HashMap::from([("Approach".to_string(), "A, C".to_string())]);
+                    handle
+                        .send_question_response(request_id, answers)
+                        .await
+                        .unwrap();
+                }
+                OutputChunk::Done => break,
+                _ => {}
+            }
+        }
+
+        let questions = asked.expect("question was asked");
+        assert_eq!(questions[0].options.len(), 3);
+        assert!(questions[0].multi_select);
+
+        // The scripted answer came back to the model as the tool result
+        let requests = llm.requests();
+        assert_eq!(requests.len(), 2);
+        let tool_result = format!("{:?}", requests[1].messages.last().unwrap());
+        assert!(tool_result.contains("A, C"));
+
+        runtime.shutdown_all().await;
+    }
 }
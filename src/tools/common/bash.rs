@@ -1,6 +1,26 @@
 //! Bash tool for executing shell commands
 //!
 //! This tool executes bash commands with optional timeout and description.
+//!
+//! The result keeps the exit status and the two output streams apart, so
+//! the model can tell warnings on a successful command from a failure:
+//!
+//! ```text
+//! exit_code: 0
+//! --- stdout ---
+//! Compiled 12 files
+//! --- stderr ---
+//! warning: unused variable `x`
+//! ```
+//!
+//! A command killed by a signal reports `signal: SIGKILL` (or `timed_out`
+//! after its timeout) instead of an exit code. The result is an error only
+//! when the exit code is non-zero or the command was killed. With
+//! `combine_output`, stderr is sent into stdout and the streams appear
+//! interleaved in one `--- output ---` section.
+//!
+//! The command runs in its own process group. On timeout or interrupt the
+//! whole group is killed, so nothing it started outlives the call.
 
 use anyhow::Result;
 use async_trait::async_trait;
@@ -10,6 +30,7 @@ use std::process::Stdio;
 use std::time::Duration;
 use tokio::process::Command;
 use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
 
 use super::super::tool::{Tool, ToolInfo, ToolResult};
 use crate::llm::{ToolDefinition, ToolInputSchema};
@@ -19,7 +40,7 @@ use crate::runtime::AgentInternals;
 const DEFAULT_TIMEOUT_MS: u64 = 120000;
 /// Maximum timeout in milliseconds (10 minutes)
 const MAX_TIMEOUT_MS: u64 = 600000;
-/// Maximum output length in characters
+/// Maximum length of each output section in bytes
 const MAX_OUTPUT_LENGTH: usize = 30000;
 
 /// Bash tool for executing shell commands
@@ -37,6 +58,94 @@ struct BashInput {
     timeout: Option<u64>,
     /// Optional description of what this command does
     description: Option<String>,
+    /// Send stderr into stdout, keeping the order in which lines were written
+    #[serde(default)]
+    combine_output: bool,
+}
+
+/// How a command ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Termination {
+    /// Exited with a status code
+    Exited(i32),
+    /// Killed by a signal
+    Signaled(i32),
+    /// Killed after running out of time
+    TimedOut(u64),
+}
+
+/// What a command printed and how it ended
+#[derive(Debug)]
+struct CommandOutput {
+    termination: Termination,
+    stdout: String,
+    stderr: String,
+    /// Whether stderr was sent into stdout
+    combined: bool,
+}
+
+impl CommandOutput {
+    fn is_error(&self) -> bool {
+        self.termination != Termination::Exited(0)
+    }
+
+    /// The tool result text
+    fn render(&self) -> String {
+        let mut text = match self.termination {
+            Termination::Exited(code) => format!("exit_code: {}\n", code),
+            Termination::Signaled(signal) => format!("signal: {}\n", signal_name(signal)),
+            Termination::TimedOut(ms) => format!("timed_out: killed after {}ms\n", ms),
+        };
+        if self.combined {
+            push_section(&mut text, "output", &self.stdout);
+        } else {
+            push_section(&mut text, "stdout", &self.stdout);
+            push_section(&mut text, "stderr", &self.stderr);
+        }
+        text.truncate(text.trim_end().len());
+        text
+    }
+}
+
+/// Append a `--- name ---` section, cut at MAX_OUTPUT_LENGTH bytes
+fn push_section(text: &mut String, name: &str, content: &str) {
+    text.push_str(&format!("--- {} ---\n", name));
+    if content.is_empty() {
+        text.push_str("(empty)\n");
+        return;
+    }
+    if content.len() > MAX_OUTPUT_LENGTH {
+        let mut end = MAX_OUTPUT_LENGTH;
+        while !content.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.push_str(&content[..end]);
+        text.push_str(&format!("\n... ({} more bytes truncated)\n", content.len() - end));
+    } else {
+        text.push_str(content);
+        if !content.ends_with('\n') {
+            text.push('\n');
+        }
+    }
+}
+
+/// Conventional name of a signal, such as "SIGKILL"
+fn signal_name(signal: i32) -> String {
+    let name = match signal {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        3 => "SIGQUIT",
+        4 => "SIGILL",
+        6 => "SIGABRT",
+        8 => "SIGFPE",
+        9 => "SIGKILL",
+        11 => "SIGSEGV",
+        13 => "SIGPIPE",
+        14 => "SIGALRM",
+        15 => "SIGTERM",
+        _ => return format!("signal {}", signal),
+    };
+    name.to_string()
 }
 
 impl BashTool {
@@ -57,61 +166,116 @@ impl BashTool {
     }
 
     /// Execute a bash command with optional timeout
-    async fn run_command(&self, command: &str, timeout_ms: u64) -> Result<(String, i32)> {
+    async fn run_command(&self, command: &str, timeout_ms: u64, combine_output: bool) -> Result<CommandOutput> {
         tracing::info!("Executing bash command: {}", command);
         tracing::debug!("Working directory: {}", self.working_dir);
         tracing::debug!("Timeout: {}ms", timeout_ms);
 
-        let duration = Duration::from_millis(timeout_ms.min(MAX_TIMEOUT_MS));
+        let timeout_ms = timeout_ms.min(MAX_TIMEOUT_MS);
+        let duration = Duration::from_millis(timeout_ms);
 
-        let output_future = Command::new("bash")
+        // Both streams on one pipe keeps their relative order
+        let script = if combine_output {
+            format!("exec 2>&1\n{}", command)
+        } else {
+            command.to_string()
+        };
+        let mut command = Command::new("bash");
+        command
             .arg("-c")
-            .arg(command)
+            .arg(script)
             .current_dir(&self.working_dir)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .output();
+            .kill_on_drop(true);
+        #[cfg(unix)]
+        command.process_group(0);
+        let child = command.spawn()?;
+        let group = ProcessGroup(child.id());
 
-        let output = match timeout(duration, output_future).await {
+        let output = match timeout(duration, child.wait_with_output()).await {
             Ok(result) => result?,
             Err(_) => {
-                return Ok((
-                    format!("Command timed out after {}ms", timeout_ms),
-                    -1,
-                ));
+                return Ok(CommandOutput {
+                    termination: Termination::TimedOut(timeout_ms),
+                    stdout: String::new(),
+                    stderr: String::new(),
+                    combined: combine_output,
+                });
             }
         };
+        // Leave anything it started in the background running
+        group.release();
 
-        let exit_code = output.status.code().unwrap_or(-1);
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
+        let termination = match output.status.code() {
+            Some(code) => Termination::Exited(code),
+            None => Termination::Signaled(exit_signal(&output.status)),
+        };
+        tracing::debug!("Command ended: {:?}", termination);
+        tracing::debug!("Output length: {} + {} bytes", output.stdout.len(), output.stderr.len());
+
+        Ok(CommandOutput {
+            termination,
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            combined: combine_output,
+        })
+    }
 
-        // Combine stdout and stderr
-        let mut result = String::new();
-        if !stdout.is_empty() {
-            result.push_str(&stdout);
-        }
-        if !stderr.is_empty() {
-            if !result.is_empty() {
-                result.push('\n');
+    /// Run a command until it ends or `cancel` fires
+    async fn run(&self, input: &BashInput, cancel: &CancellationToken) -> ToolResult {
+        let timeout_ms = input.timeout.unwrap_or(DEFAULT_TIMEOUT_MS);
+        let outcome = tokio::select! {
+            outcome = self.run_command(&input.command, timeout_ms, input.combine_output) => outcome,
+            // Dropping the command kills its process group
+            _ = cancel.cancelled() => {
+                tracing::info!("Bash command interrupted: {}", input.command);
+                return ToolResult::interrupted();
             }
-            result.push_str("STDERR:\n");
-            result.push_str(&stderr);
+        };
+        match outcome {
+            Ok(output) if output.is_error() => ToolResult::error(output.render()),
+            Ok(output) => ToolResult::success(output.render()),
+            Err(e) => ToolResult::error(format!("Failed to execute command: {}", e)),
         }
+    }
+}
 
-        // Truncate if too long
-        if result.len() > MAX_OUTPUT_LENGTH {
-            result.truncate(MAX_OUTPUT_LENGTH);
-            result.push_str("\n... (output truncated)");
-        }
+/// Kills a command's process group when dropped, unless released
+///
+/// `kill_on_drop` only reaches bash itself; what it started (a `sleep`, a
+/// test runner) would otherwise keep running after a timeout or interrupt.
+pub(crate) struct ProcessGroup(pub(crate) Option<u32>);
 
-        tracing::debug!("Command exit code: {}", exit_code);
-        tracing::debug!("Output length: {} chars", result.len());
+impl ProcessGroup {
+    pub(crate) fn release(mut self) {
+        self.0 = None;
+    }
+}
 
-        Ok((result, exit_code))
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pgid) = self.0.and_then(|pid| i32::try_from(pid).ok()) {
+            // SAFETY: kill(2) touches no memory; the group was created for this command
+            unsafe {
+                libc::kill(-pgid, libc::SIGKILL);
+            }
+        }
     }
 }
 
+#[cfg(unix)]
+fn exit_signal(status: &std::process::ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal().unwrap_or(-1)
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: &std::process::ExitStatus) -> i32 {
+    -1
+}
+
 impl Default for BashTool {
     fn default() -> Self {
         Self::with_working_dir(".")
@@ -153,6 +317,10 @@ impl Tool for BashTool {
                     "description": {
                         "type": "string",
                         "description": "Clear, concise description of what this command does in 5-10 words, in active voice."
+                    },
+                    "combine_output": {
+                        "type": "boolean",
+                        "description": "Send stderr into stdout so both appear interleaved, in the order they were written. Default false: stdout and stderr are reported separately."
                     }
                 })),
                 required: Some(vec!["command".to_string()]),
@@ -179,36 +347,26 @@ impl Tool for BashTool {
             name: "Bash".to_string(),
             action_description: action,
             details: Some(format!("Command: {}", command)),
+            read_only: false,
+        }
+    }
+
+    fn describe_effect(&self, input: &Value) -> String {
+        match serde_json::from_value::<BashInput>(input.clone()) {
+            Ok(input) => format!("Run in {}:\n{}", self.working_dir, input.command),
+            Err(_) => input.to_string(),
         }
     }
 
-    async fn execute(&self, input: &Value, _internals: &mut AgentInternals) -> Result<ToolResult> {
+    async fn execute(&self, input: &Value, internals: &mut AgentInternals) -> Result<ToolResult> {
         let bash_input: BashInput = serde_json::from_value(input.clone())
             .map_err(|e| anyhow::anyhow!("Invalid bash input: {}", e))?;
 
-        let timeout_ms = bash_input.timeout.unwrap_or(DEFAULT_TIMEOUT_MS);
-
         if let Some(ref desc) = bash_input.description {
             tracing::info!("Command description: {}", desc);
         }
 
-        match self.run_command(&bash_input.command, timeout_ms).await {
-            Ok((output, exit_code)) => {
-                if exit_code == 0 {
-                    if output.is_empty() {
-                        Ok(ToolResult::success("Command completed successfully (no output)"))
-                    } else {
-                        Ok(ToolResult::success(output))
-                    }
-                } else {
-                    Ok(ToolResult::error(format!(
-                        "Command failed with exit code {}\n{}",
-                        exit_code, output
-                    )))
-                }
-            }
-            Err(e) => Ok(ToolResult::error(format!("Failed to execute command: {}", e))),
-        }
+        Ok(self.run(&bash_input, &internals.context.cancellation).await)
     }
 
     fn requires_permission(&self) -> bool {
@@ -216,5 +374,99 @@ impl Tool for BashTool {
     }
 }
 
-// Tests temporarily disabled - require AgentInternals test helper
-// TODO: Create test infrastructure for tools that need AgentInternals
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn run(command: &str, combine_output: bool) -> ToolResult {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = BashTool::with_working_dir(dir.path().to_string_lossy());
+        let input = BashInput {
+            command: command.to_string(),
+            timeout: Some(5000),
+            description: None,
+            combine_output,
+        };
+        tool.run(&input, &CancellationToken::new()).await
+    }
+
+    fn text(result: &ToolResult) -> &str {
+        match &result.content[..] {
+            [super::super::super::ToolResultData::Text(text)] => text,
+            other => panic!("expected text, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streams_are_reported_separately() {
+        let result = run("echo built; echo 'warning: unused' >&2; echo done", false).await;
+        assert!(!result.is_error);
+        assert_eq!(
+            text(&result),
+            "exit_code: 0\n--- stdout ---\nbuilt\ndone\n--- stderr ---\nwarning: unused"
+        );
+
+        let result = run("echo built; echo 'warning: unused' >&2; echo done", true).await;
+        assert_eq!(text(&result), "exit_code: 0\n--- output ---\nbuilt\nwarning: unused\ndone");
+    }
+
+    #[tokio::test]
+    async fn test_non_zero_exit_is_an_error() {
+        let result = run("echo partial; exit 3", false).await;
+        assert!(result.is_error);
+        assert_eq!(text(&result), "exit_code: 3\n--- stdout ---\npartial\n--- stderr ---\n(empty)");
+    }
+
+    #[tokio::test]
+    async fn test_killed_by_signal() {
+        let result = run("echo before; kill -9 $$", false).await;
+        assert!(result.is_error);
+        assert!(text(&result).starts_with("signal: SIGKILL\n--- stdout ---\nbefore\n"), "{}", text(&result));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_timeout_and_cancel_kill_the_process_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = BashTool::with_working_dir(dir.path().to_string_lossy());
+        let input = |timeout| BashInput {
+            command: "(sleep 1; touch late) & touch started; wait".to_string(),
+            timeout: Some(timeout),
+            description: None,
+            combine_output: false,
+        };
+
+        let result = tool.run(&input(200), &CancellationToken::new()).await;
+        assert!(text(&result).starts_with("timed_out: killed after 200ms"), "{}", text(&result));
+
+        let cancel = CancellationToken::new();
+        let started = dir.path().join("started");
+        std::fs::remove_file(&started).unwrap();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            while !started.exists() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            trigger.cancel();
+        });
+        let result = tool.run(&input(5000), &cancel).await;
+        assert!(result.is_interrupted());
+
+        // The backgrounded subshell died with the command both times
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+        assert!(!dir.path().join("late").exists());
+    }
+
+    #[test]
+    fn test_sections_are_capped_separately() {
+        let output = CommandOutput {
+            termination: Termination::Exited(0),
+            stdout: "é".repeat(MAX_OUTPUT_LENGTH),
+            stderr: "short".to_string(),
+            combined: false,
+        };
+        let text = output.render();
+        assert!(text.contains(&format!("... ({} more bytes truncated)", MAX_OUTPUT_LENGTH)));
+        assert!(text.ends_with("--- stderr ---\nshort"));
+    }
+}
@@ -0,0 +1,425 @@
+//! Relaxed matching for EditTool (`EditMatching::Lenient`)
+//!
+//! When `old_string` isn't in the file exactly, two relaxations are tried
+//! in turn:
+//! 1. **Whitespace**: every run of whitespace (spaces, tabs, newlines)
+//!    compares equal to any other, so trailing spaces, tabs vs spaces and
+//!    re-wrapped lines still match.
+//! 2. **Fuzzy**: whole lines whose text, whitespace normalized, is within a
+//!    small edit distance of `old_string`'s lines. Only for strings of at
+//!    least `FUZZY_MIN_CHARS`, so short strings never match something else.
+//!
+//! A relaxation is only used when it finds exactly one place. If it finds
+//! several, matching stops there and the caller reports their line numbers,
+//! so the model can pick one with more context.
+
+/// Shortest `old_string` (whitespace normalized, in chars) matched fuzzily
+const FUZZY_MIN_CHARS: usize = 20;
+
+/// Longest `old_string` (whitespace normalized, in chars) matched fuzzily
+const FUZZY_MAX_CHARS: usize = 2000;
+
+/// Edit distance allowed, as a percentage of `old_string`'s length
+const FUZZY_DISTANCE_PERCENT: usize = 10;
+
+/// Edit distance allowed, at most
+const FUZZY_MAX_DISTANCE: usize = 40;
+
+/// How a relaxed match was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Relaxation {
+    /// Equal once whitespace differences are ignored
+    Whitespace,
+    /// Whole lines this many characters away
+    Fuzzy { distance: usize },
+}
+
+/// Result of relaxed matching
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Relaxed {
+    /// The one place found: replace `content[start..end]`
+    Found { start: usize, end: usize, relaxation: Relaxation },
+    /// Several places match equally well (1-based first lines)
+    Ambiguous { relaxation: Relaxation, lines: Vec<usize> },
+    /// Nothing close enough
+    NotFound,
+}
+
+/// Find `old` in `content` despite whitespace differences or small typos
+pub(super) fn find_relaxed(content: &str, old: &str) -> Relaxed {
+    match find_ignoring_whitespace(content, old) {
+        Relaxed::NotFound => find_fuzzy(content, old),
+        found => found,
+    }
+}
+
+/// Text with each whitespace run collapsed to one space, and for every
+/// byte of it the range of the original text it stands for
+struct Collapsed {
+    text: String,
+    spans: Vec<(usize, usize)>,
+}
+
+fn collapse(original: &str) -> Collapsed {
+    let mut text = String::with_capacity(original.len());
+    let mut spans = Vec::with_capacity(original.len());
+    let mut chars = original.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+        if c.is_whitespace() {
+            while let Some(&(next, n)) = chars.peek() {
+                if !n.is_whitespace() {
+                    break;
+                }
+                end = next + n.len_utf8();
+                chars.next();
+            }
+            text.push(' ');
+            spans.push((start, end));
+        } else {
+            text.push(c);
+            spans.extend(std::iter::repeat_n((start, end), c.len_utf8()));
+        }
+    }
+    Collapsed { text, spans }
+}
+
+/// 1-based line of a byte offset
+fn line_of(content: &str, offset: usize) -> usize {
+    content[..offset].matches('\n').count() + 1
+}
+
+fn find_ignoring_whitespace(content: &str, old: &str) -> Relaxed {
+    let needle = collapse(old.trim()).text;
+    if needle.is_empty() {
+        return Relaxed::NotFound;
+    }
+    let leading = &old[..old.len() - old.trim_start().len()];
+    let trailing = &old[old.trim_end().len()..];
+    let haystack = collapse(content);
+
+    // Whitespace around `old` is a word boundary: "foo " doesn't match "foobar"
+    let matches: Vec<(usize, usize)> = haystack
+        .text
+        .match_indices(&needle)
+        .map(|(at, _)| (at, at + needle.len()))
+        .filter(|&(start, end)| {
+            (leading.is_empty() || start == 0 || haystack.text.as_bytes()[start - 1] == b' ')
+                && (trailing.is_empty() || end == haystack.text.len() || haystack.text.as_bytes()[end] == b' ')
+        })
+        .map(|(start, end)| (haystack.spans[start].0, haystack.spans[end - 1].1))
+        .collect();
+
+    match matches.as_slice() {
+        [] => Relaxed::NotFound,
+        [(start, end)] => Relaxed::Found {
+            start: extend_back(content, *start, leading),
+            end: extend_forward(content, *end, trailing),
+            relaxation: Relaxation::Whitespace,
+        },
+        _ => Relaxed::Ambiguous {
+            relaxation: Relaxation::Whitespace,
+            lines: matches.iter().map(|&(start, _)| line_of(content, start)).collect(),
+        },
+    }
+}
+
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t' || c == '\r'
+}
+
+/// Take in the whitespace before a match that `old`'s leading whitespace
+/// stands for, so `new_string`'s indentation replaces the file's instead of
+/// adding to it
+fn extend_back(content: &str, mut start: usize, leading: &str) -> usize {
+    if leading.is_empty() {
+        return start;
+    }
+    let back_over_blanks = |mut at: usize| {
+        while let Some(c) = content[..at].chars().next_back().filter(|&c| is_blank(c)) {
+            at -= c.len_utf8();
+        }
+        at
+    };
+    let indent = &leading[leading.rfind('\n').map_or(0, |i| i + 1)..];
+    if !indent.is_empty() {
+        let line_start = back_over_blanks(start);
+        if line_start == 0 || content[..line_start].ends_with('\n') {
+            start = line_start;
+        }
+    }
+    for _ in 0..leading.matches('\n').count() {
+        if !content[..start].ends_with('\n') {
+            break;
+        }
+        start = back_over_blanks(start - 1);
+    }
+    start
+}
+
+/// Take in the whitespace after a match that `old`'s trailing whitespace
+/// stands for, up to and including as many newlines as it has
+fn extend_forward(content: &str, mut end: usize, trailing: &str) -> usize {
+    if trailing.is_empty() {
+        return end;
+    }
+    let forward_over_blanks = |mut at: usize| {
+        while let Some(c) = content[at..].chars().next().filter(|&c| is_blank(c)) {
+            at += c.len_utf8();
+        }
+        at
+    };
+    let newlines = trailing.matches('\n').count();
+    end = forward_over_blanks(end);
+    for crossed in 0..newlines {
+        if !content[end..].starts_with('\n') {
+            break;
+        }
+        end += 1;
+        if crossed + 1 < newlines {
+            end = forward_over_blanks(end);
+        }
+    }
+    end
+}
+
+/// A line with whitespace runs collapsed and the ends trimmed
+fn normalize_line(line: &str) -> String {
+    line.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn find_fuzzy(content: &str, old: &str) -> Relaxed {
+    let old_lines: Vec<String> = old
+        .lines()
+        .map(normalize_line)
+        .skip_while(String::is_empty)
+        .collect();
+    let blank_tail = old_lines.iter().rev().take_while(|line| line.is_empty()).count();
+    let old_lines = &old_lines[..old_lines.len() - blank_tail];
+    let needle: Vec<char> = old_lines.join("\n").chars().collect();
+    if needle.len() < FUZZY_MIN_CHARS || needle.len() > FUZZY_MAX_CHARS {
+        return Relaxed::NotFound;
+    }
+    let allowed = (needle.len() * FUZZY_DISTANCE_PERCENT / 100).clamp(1, FUZZY_MAX_DISTANCE);
+
+    // Byte range of every line, without its line break
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let text = line.strip_suffix('\n').unwrap_or(line);
+        lines.push((offset, offset + text.strip_suffix('\r').unwrap_or(text).len()));
+        offset += line.len();
+    }
+    let normalized: Vec<String> = lines.iter().map(|&(start, end)| normalize_line(&content[start..end])).collect();
+
+    let count = old_lines.len();
+    let candidates: Vec<(usize, usize)> = (0..normalized.len().saturating_sub(count - 1))
+        .filter_map(|first| {
+            let window: Vec<char> = normalized[first..first + count].join("\n").chars().collect();
+            bounded_distance(&needle, &window, allowed).map(|distance| (first, distance))
+        })
+        .collect();
+
+    match candidates.as_slice() {
+        [] => Relaxed::NotFound,
+        [(first, distance)] => {
+            let (line_start, _) = lines[*first];
+            let (_, mut end) = lines[first + count - 1];
+            // Keep the indentation if `old` didn't include any
+            let first_line = old.trim_start_matches(['\n', '\r']);
+            let start = if first_line.starts_with(is_blank) {
+                line_start
+            } else {
+                line_start + content[line_start..].len() - content[line_start..].trim_start_matches(is_blank).len()
+            };
+            if old.ends_with('\n') && content[end..].starts_with(['\r', '\n']) {
+                end += if content[end..].starts_with("\r\n") { 2 } else { 1 };
+            }
+            Relaxed::Found { start, end, relaxation: Relaxation::Fuzzy { distance: *distance } }
+        }
+        _ => Relaxed::Ambiguous {
+            relaxation: Relaxation::Fuzzy {
+                distance: candidates.iter().map(|&(_, distance)| distance).min().unwrap_or(0),
+            },
+            lines: candidates.iter().map(|&(first, _)| first + 1).collect(),
+        },
+    }
+}
+
+/// Levenshtein distance between `a` and `b`, if it is at most `limit`
+///
+/// Only cells within `limit` of the diagonal are computed, and it gives up
+/// as soon as a whole row is over the limit.
+fn bounded_distance(a: &[char], b: &[char], limit: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > limit {
+        return None;
+    }
+    let over = limit + 1;
+    let mut previous: Vec<usize> = (0..=b.len()).map(|j| j.min(over)).collect();
+    let mut current = vec![over; b.len() + 1];
+    for i in 1..=a.len() {
+        let low = i.saturating_sub(limit).max(1);
+        let high = (i + limit).min(b.len());
+        current[0] = i.min(over);
+        if low > 1 {
+            current[low - 1] = over;
+        }
+        let mut row_min = current[0];
+        for j in low..=high {
+            let substitute = previous[j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let value = substitute.min(previous[j] + 1).min(current[j - 1] + 1).min(over);
+            current[j] = value;
+            row_min = row_min.min(value);
+        }
+        if high < b.len() {
+            current[high + 1] = over;
+        }
+        if row_min > limit {
+            return None;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    Some(previous[b.len()]).filter(|&distance| distance <= limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apply a relaxed match, or None if there was none
+    fn apply(content: &str, old: &str, new: &str) -> Option<(String, Relaxation)> {
+        match find_relaxed(content, old) {
+            Relaxed::Found { start, end, relaxation } => {
+                Some((format!("{}{}{}", &content[..start], new, &content[end..]), relaxation))
+            }
+            _ => None,
+        }
+    }
+
+    fn distance(a: &str, b: &str, limit: usize) -> Option<usize> {
+        let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+        bounded_distance(&a, &b, limit)
+    }
+
+    #[test]
+    fn test_bounded_distance() {
+        assert_eq!(distance("kitten", "sitting", 3), Some(3));
+        assert_eq!(distance("kitten", "sitting", 2), None);
+        assert_eq!(distance("", "abc", 3), Some(3));
+        assert_eq!(distance("same", "same", 0), Some(0));
+        assert_eq!(distance("short", "a much longer string", 5), None);
+        assert_eq!(distance("héllo wörld", "hello world", 2), Some(2));
+    }
+
+    #[test]
+    fn test_trailing_whitespace_and_tabs() {
+        let content = "fn main() {\n\tlet x = 1;   \n\tprintln!(\"{}\", x);\n}\n";
+        let (edited, how) = apply(
+            content,
+            "    let x = 1;\n    println!(\"{}\", x);\n",
+            "    let x = 2;\n    println!(\"{}\", x);\n",
+        )
+        .unwrap();
+        assert_eq!(how, Relaxation::Whitespace);
+        // The model's indentation replaces the file's, nothing is doubled
+        assert_eq!(edited, "fn main() {\n    let x = 2;\n    println!(\"{}\", x);\n}\n");
+    }
+
+    #[test]
+    fn test_rewrapped_line() {
+        let content = "let total = first_value\n    + second_value;\nnext();\n";
+        let (edited, how) = apply(content, "let total = first_value + second_value;", "let total = sum;").unwrap();
+        assert_eq!(how, Relaxation::Whitespace);
+        assert_eq!(edited, "let total = sum;\nnext();\n");
+    }
+
+    #[test]
+    fn test_mid_line_match_keeps_surroundings() {
+        let content = "call(alpha,  beta) + 1\n";
+        let (edited, _) = apply(content, "alpha, beta", "gamma").unwrap();
+        assert_eq!(edited, "call(gamma) + 1\n");
+    }
+
+    #[test]
+    fn test_whitespace_is_a_boundary() {
+        // "foo " ends where a word ends, so it doesn't match "foobar"
+        assert_eq!(find_relaxed("foobar\n", "foo "), Relaxed::NotFound);
+        assert_eq!(find_relaxed("let x = value;\n", "let x = value; "), Relaxed::Found {
+            start: 0,
+            end: 14,
+            relaxation: Relaxation::Whitespace
+        });
+        assert_eq!(find_relaxed("let x = values;\n", "let x = value "), Relaxed::NotFound);
+        // Whitespace never matches nothing
+        assert_eq!(find_relaxed("a+b\n", "a + b"), Relaxed::NotFound);
+    }
+
+    #[test]
+    fn test_whitespace_matches_in_several_places() {
+        let content = "if x {\n    reset();\n}\nif y {\n\treset();\n}\n";
+        assert_eq!(
+            find_relaxed(content, "  reset();  "),
+            Relaxed::Ambiguous { relaxation: Relaxation::Whitespace, lines: vec![2, 5] }
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_fixes_a_typo() {
+        let content = "fn area(width: u32, height: u32) -> u32 {\n    width * height\n}\n";
+        let (edited, how) = apply(
+            content,
+            "fn area(widht: u32, height: u32) -> u32 {\n",
+            "fn area(width: u64, height: u64) -> u64 {\n",
+        )
+        .unwrap();
+        assert_eq!(how, Relaxation::Fuzzy { distance: 2 });
+        assert_eq!(edited, "fn area(width: u64, height: u64) -> u64 {\n    width * height\n}\n");
+    }
+
+    #[test]
+    fn test_fuzzy_keeps_indentation_old_string_left_out() {
+        let content = "impl Shape {\n        let ratio = numerator / denominator;\n}\n";
+        let (edited, _) = apply(content, "let ratio = numerator / denomintor;", "let ratio = 0.5;").unwrap();
+        assert_eq!(edited, "impl Shape {\n        let ratio = 0.5;\n}\n");
+    }
+
+    #[test]
+    fn test_fuzzy_ties_are_reported() {
+        let content = "log_message(\"starting up\");\nwork();\nlog_message(\"starting up\");\n";
+        assert_eq!(
+            find_relaxed(content, "log_mesage(\"starting up\");"),
+            Relaxed::Ambiguous { relaxation: Relaxation::Fuzzy { distance: 1 }, lines: vec![1, 3] }
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_never_matches_different_code() {
+        let content = "let result = compute_total(items);\nlet other = compute_average(items);\n";
+        // Too short to match fuzzily at all
+        assert_eq!(find_relaxed("let a = 1;\n", "let b = 1;"), Relaxed::NotFound);
+        // Different enough to be another line, not a typo
+        assert_eq!(find_relaxed(content, "let result = compute_median(values);"), Relaxed::NotFound);
+        // Two lines where the file has one don't match that line
+        assert_eq!(
+            find_relaxed(content, "let result = compute_total(items);\nprintln!(\"{}\", result);"),
+            Relaxed::NotFound
+        );
+        assert_eq!(find_relaxed("", "let result = compute_total(items);"), Relaxed::NotFound);
+    }
+
+    #[test]
+    fn test_fuzzy_allows_only_a_small_distance() {
+        let line = "the quick brown fox jumps over the lazy dog";
+        // 40 chars allow 4 edits
+        assert!(matches!(find_relaxed(line, "the quick brwn fox jumps ovr the lazy dg"), Relaxed::Found { .. }));
+        assert_eq!(find_relaxed(line, "the quack brwn fix jumps ovr the lazy dg"), Relaxed::NotFound);
+    }
+
+    #[test]
+    fn test_crlf_lines() {
+        let content = "first line of the file\r\nsecond line of the file\r\n";
+        let (edited, how) = apply(content, "second line of teh file\n", "replaced\r\n").unwrap();
+        assert_eq!(how, Relaxation::Fuzzy { distance: 2 });
+        assert_eq!(edited, "first line of the file\r\nreplaced\r\n");
+    }
+}
@@ -1,34 +1,65 @@
 //! Edit tool for modifying files
 //!
-//! Performs exact string replacements in files.
+//! Performs exact string replacements in files. With
+//! `EditMatching::Lenient`, an `old_string` that isn't in the file exactly
+//! may still match despite whitespace differences or a small typo, when
+//! only one place matches (see `edit_matching`).
+//!
+//! Like WriteTool, each edit keeps a copy of the file's previous contents in
+//! the session's `backups/` directory and is recorded in the agent's
+//! `FileChangeJournal`, so UndoTool can reverse it.
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use serde::Deserialize;
-use serde_json::{json, Value};
+use serde_json::Value;
 use std::fs;
 use std::path::Path;
+use std::sync::Arc;
 
+use super::edit_matching::{find_relaxed, Relaxation, Relaxed};
+use super::file_changes::{record_change, save_backup};
+use super::path_policy::{effective_policy, PathPolicy};
 use super::super::tool::{Tool, ToolInfo, ToolResult};
-use crate::llm::{ToolDefinition, ToolInputSchema};
+use super::super::ToolInput;
+use crate::helpers::{FileTracker, WriteCheck};
+use crate::llm::ToolDefinition;
 use crate::runtime::AgentInternals;
 
+/// How EditTool finds `old_string` in the file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EditMatching {
+    /// Only exact matches (the default)
+    #[default]
+    Exact,
+    /// When there's no exact match, ignore whitespace differences, then
+    /// allow a close match of whole lines. A relaxed match is only used
+    /// when it is the only one, and never with `replace_all`.
+    Lenient,
+}
+
 /// Edit tool for string replacement in files
 pub struct EditTool {
     /// Base directory for file operations
     base_dir: String,
+    /// Paths this tool may touch (falls back to the agent's `PathPolicy` resource)
+    policy: Option<Arc<PathPolicy>>,
+    /// Keep a copy of each edited file in the session directory
+    backups: bool,
+    /// How `old_string` is matched
+    matching: EditMatching,
 }
 
 /// Input for the edit tool
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToolInput)]
 struct EditInput {
-    /// The absolute path to the file to modify (required)
+    /// The absolute path to the file to modify
     file_path: String,
-    /// The text to replace (required)
+    /// The text to replace
     old_string: String,
-    /// The text to replace it with (required)
+    /// The text to replace it with (must be different from old_string)
     new_string: String,
-    /// Replace all occurrences (default false)
+    /// Replace all occurrences of old_string (default false)
     #[serde(default)]
     replace_all: bool,
 }
@@ -40,16 +71,37 @@ impl EditTool {
             .to_string_lossy()
             .to_string();
 
-        Ok(Self { base_dir })
+        Ok(Self::with_base_dir(base_dir))
     }
 
     /// Create a new Edit tool with a specific base directory
     pub fn with_base_dir(base_dir: impl Into<String>) -> Self {
         Self {
             base_dir: base_dir.into(),
+            policy: None,
+            backups: true,
+            matching: EditMatching::Exact,
         }
     }
 
+    /// Confine this tool to the paths `policy` allows
+    pub fn with_policy(mut self, policy: PathPolicy) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Whether to keep a copy of each edited file (default true)
+    pub fn with_backups(mut self, enabled: bool) -> Self {
+        self.backups = enabled;
+        self
+    }
+
+    /// How to match `old_string` (default `EditMatching::Exact`)
+    pub fn with_matching(mut self, matching: EditMatching) -> Self {
+        self.matching = matching;
+        self
+    }
+
     /// Resolve a path (handle both absolute and relative)
     fn resolve_path(&self, path: &str) -> String {
         let path = Path::new(path);
@@ -84,6 +136,9 @@ impl EditTool {
         let occurrences = content.matches(old_str).count();
 
         if occurrences == 0 {
+            if self.matching == EditMatching::Lenient && !replace_all {
+                return self.relaxed_replace(&resolved_path, file_path, &content, old_str, new_str);
+            }
             anyhow::bail!(
                 "String not found in file. Make sure to include exact text including whitespace."
             );
@@ -115,6 +170,57 @@ impl EditTool {
             Ok(format!("Successfully replaced text in {}", file_path))
         }
     }
+
+    /// Replace the one place `old_str` matches after relaxing the match
+    fn relaxed_replace(
+        &self,
+        resolved_path: &str,
+        file_path: &str,
+        content: &str,
+        old_str: &str,
+        new_str: &str,
+    ) -> Result<String> {
+        let (start, end, relaxation) = match find_relaxed(content, old_str) {
+            Relaxed::Found { start, end, relaxation } => (start, end, relaxation),
+            Relaxed::Ambiguous { relaxation, lines } => {
+                let lines = lines.iter().map(usize::to_string).collect::<Vec<_>>().join(", ");
+                let how = match relaxation {
+                    Relaxation::Whitespace => "Ignoring whitespace, it matches",
+                    Relaxation::Fuzzy { .. } => "It is close to",
+                };
+                anyhow::bail!(
+                    "String not found in file exactly. {} several places, starting at lines {}. \
+                    Include more surrounding text, copied exactly from the file, to pick one.",
+                    how,
+                    lines
+                );
+            }
+            Relaxed::NotFound => anyhow::bail!(
+                "String not found in file. Make sure to include exact text including whitespace."
+            ),
+        };
+
+        let new_content = format!("{}{}{}", &content[..start], new_str, &content[end..]);
+        fs::write(resolved_path, &new_content)
+            .with_context(|| format!("Failed to write file: {}", resolved_path))?;
+
+        let first = content[..start].matches('\n').count() + 1;
+        let last = first + content[start..end].trim_end_matches('\n').matches('\n').count();
+        let lines = if first == last { format!("line {}", first) } else { format!("lines {}-{}", first, last) };
+        let how = match relaxation {
+            Relaxation::Whitespace => "ignoring whitespace differences".to_string(),
+            Relaxation::Fuzzy { distance } => format!(
+                "using the closest match, {} character{} different",
+                distance,
+                if distance == 1 { "" } else { "s" }
+            ),
+        };
+        tracing::info!("No exact match in {}, replaced {} {}", resolved_path, lines, how);
+        Ok(format!(
+            "Successfully replaced text in {} at {} (no exact match; matched {}). Read the file to check the result.",
+            file_path, lines, how
+        ))
+    }
 }
 
 impl Default for EditTool {
@@ -144,33 +250,7 @@ impl Tool for EditTool {
                 Use replace_all for replacing and renaming strings across the file."
                     .to_string(),
             ),
-            input_schema: ToolInputSchema {
-                schema_type: "object".to_string(),
-                properties: Some(json!({
-                    "file_path": {
-                        "type": "string",
-                        "description": "The absolute path to the file to modify"
-                    },
-                    "old_string": {
-                        "type": "string",
-                        "description": "The text to replace"
-                    },
-                    "new_string": {
-                        "type": "string",
-                        "description": "The text to replace it with (must be different from old_string)"
-                    },
-                    "replace_all": {
-                        "type": "boolean",
-                        "default": false,
-                        "description": "Replace all occurrences of old_string (default false)"
-                    }
-                })),
-                required: Some(vec![
-                    "file_path".to_string(),
-                    "old_string".to_string(),
-                    "new_string".to_string(),
-                ]),
-            },
+            input_schema: EditInput::schema(),
             tool_type: None,
             cache_control: None,
         })
@@ -186,20 +266,64 @@ impl Tool for EditTool {
             name: "Edit".to_string(),
             action_description: format!("Edit file: {}", file_path),
             details: None,
+            read_only: false,
         }
     }
 
-    async fn execute(&self, input: &Value, _internals: &mut AgentInternals) -> Result<ToolResult> {
-        let edit_input: EditInput = serde_json::from_value(input.clone())
+    fn describe_effect(&self, input: &Value) -> String {
+        let Ok(input) = EditInput::from_value(input.clone()) else {
+            return input.to_string();
+        };
+        let what = if input.replace_all { "replace every occurrence of" } else { "replace" };
+        format!(
+            "In {}, {}:\n{}\nwith:\n{}",
+            self.resolve_path(&input.file_path),
+            what,
+            input.old_string,
+            input.new_string
+        )
+    }
+
+    async fn execute(&self, input: &Value, internals: &mut AgentInternals) -> Result<ToolResult> {
+        let edit_input = EditInput::from_value(input.clone())
             .map_err(|e| anyhow::anyhow!("Invalid edit input: {}", e))?;
 
+        let mut resolved_path = self.resolve_path(&edit_input.file_path);
+        if let Some(policy) = effective_policy(&self.policy, internals) {
+            match policy.check(&resolved_path) {
+                Ok(checked) => resolved_path = checked.to_string_lossy().into_owned(),
+                Err(e) => return Ok(ToolResult::error(e.to_string())),
+            }
+        }
+        let tracker = internals.context.get_resource::<FileTracker>();
+        let warning = match tracker.as_ref().map(|t| t.check_write(&resolved_path)) {
+            Some(WriteCheck::Denied(reason)) => return Ok(ToolResult::error(reason)),
+            Some(WriteCheck::Warning(warning)) => Some(warning),
+            _ => None,
+        };
+
+        let previous = fs::read(&resolved_path).ok();
         match self.str_replace(
-            &edit_input.file_path,
+            &resolved_path,
             &edit_input.old_string,
             &edit_input.new_string,
             edit_input.replace_all,
         ) {
-            Ok(output) => Ok(ToolResult::success(output)),
+            Ok(output) => {
+                if let Some(tracker) = &tracker {
+                    tracker.record_read(&resolved_path);
+                }
+                let path = Path::new(&resolved_path);
+                let backup = match &previous {
+                    Some(previous) if self.backups => save_backup(internals, path, previous).await,
+                    _ => None,
+                };
+                record_change(internals, path, previous.as_deref(), backup);
+                match warning {
+                    Some(warning) => Ok(ToolResult::success(format!("{}\n\nWarning: {}", output, warning))),
+                    None => Ok(ToolResult::success(output)),
+                }
+            }
             Err(e) => Ok(ToolResult::error(format!("{}", e))),
         }
     }
@@ -209,5 +333,140 @@ impl Tool for EditTool {
     }
 }
 
-// Tests temporarily disabled - require AgentInternals test helper
-// TODO: Create test infrastructure for tools that need AgentInternals
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::agent::AgentConfig;
+    use crate::cli::print_mode::run_mock_agent;
+    use crate::helpers::FileEnforcement;
+    use crate::llm::MockLlmProvider;
+    use crate::session::{AgentSession, SessionStorage};
+    use crate::tools::common::ReadTool;
+    use crate::tools::ToolRegistry;
+
+    /// Run one turn with Read and Edit under strict read-before-write
+    async fn run_turn(dir: &std::path::Path, llm: &MockLlmProvider) {
+        let mut registry = ToolRegistry::new();
+        registry.register(ReadTool::with_base_dir(dir.to_string_lossy()));
+        registry.register(EditTool::with_base_dir(dir.to_string_lossy()));
+        let config = AgentConfig::new("test")
+            .with_tools(Arc::new(registry))
+            .with_file_enforcement(FileEnforcement::Strict)
+            .with_auto_name(false);
+
+        let storage = SessionStorage::with_dir(dir.join("sessions"));
+        let session = AgentSession::new_with_storage("edit-test", "test", "Test", "", storage).unwrap();
+        run_mock_agent(config, Arc::new(llm.clone()), session, "go").await;
+    }
+
+    fn edit_call(id: &str) -> crate::llm::MessageResponse {
+        MockLlmProvider::tool_use_response(
+            id,
+            "Edit",
+            json!({"file_path": "config.toml", "old_string": "a = 1", "new_string": "a = 2"}),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_edit_requires_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "a = 1").unwrap();
+
+        let llm = MockLlmProvider::new().with_response(edit_call("call_1")).with_text("done");
+        run_turn(dir.path(), &llm).await;
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a = 1");
+        let result = serde_json::to_string(llm.requests()[1].messages.last().unwrap()).unwrap();
+        assert!(result.contains("You must Read the file first"), "{}", result);
+    }
+
+    #[tokio::test]
+    async fn test_edit_after_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "a = 1").unwrap();
+
+        let llm = MockLlmProvider::new()
+            .with_response(MockLlmProvider::tool_use_response(
+                "call_1",
+                "Read",
+                json!({"file_path": "config.toml"}),
+            ))
+            .with_response(edit_call("call_2"))
+            .with_text("done");
+        run_turn(dir.path(), &llm).await;
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a = 2");
+    }
+
+    #[test]
+    fn test_lenient_matching() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lib.rs");
+        let original = "fn one() {\n\treturn 1;  \n}\n\nfn two() {\n\treturn 2;\n}\n";
+        fs::write(&path, original).unwrap();
+        let exact = EditTool::with_base_dir(dir.path().to_string_lossy());
+        let lenient = EditTool::with_base_dir(dir.path().to_string_lossy()).with_matching(EditMatching::Lenient);
+
+        // Exact matching is the default
+        let error = exact.str_replace("lib.rs", "    return 1;\n", "    return 10;\n", false).unwrap_err();
+        assert!(error.to_string().starts_with("String not found"), "{}", error);
+
+        let output = lenient.str_replace("lib.rs", "    return 1;\n", "    return 10;\n", false).unwrap();
+        assert_eq!(
+            output,
+            "Successfully replaced text in lib.rs at line 2 (no exact match; matched ignoring whitespace differences). \
+            Read the file to check the result."
+        );
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "fn one() {\n    return 10;\n}\n\nfn two() {\n\treturn 2;\n}\n"
+        );
+
+        // Several places: nothing changes and the lines are listed
+        let before = fs::read_to_string(&path).unwrap();
+        let error = lenient.str_replace("lib.rs", "}\n\nfn  two", "x", true).unwrap_err();
+        assert!(error.to_string().starts_with("String not found"), "{}", error);
+        let error = lenient.str_replace("lib.rs", "}  ", "};", false).unwrap_err();
+        assert!(error.to_string().contains("starting at lines 3, 7"), "{}", error);
+        assert_eq!(fs::read_to_string(&path).unwrap(), before);
+
+        let output = lenient
+            .str_replace("lib.rs", "fn tow() {\n\treturn 2;", "fn two() {\n\treturn 20;", false)
+            .unwrap();
+        assert!(output.contains("at lines 5-6 (no exact match; matched using the closest match, 2 characters different)"), "{}", output);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "fn one() {\n    return 10;\n}\n\nfn two() {\n\treturn 20;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_schema_matches_input() {
+        let schema = serde_json::to_value(EditInput::schema()).unwrap();
+        assert_eq!(
+            schema,
+            json!({
+                "type": "object",
+                "properties": {
+                    "file_path": {"type": "string", "description": "The absolute path to the file to modify"},
+                    "old_string": {"type": "string", "description": "The text to replace"},
+                    "new_string": {
+                        "type": "string",
+                        "description": "The text to replace it with (must be different from old_string)"
+                    },
+                    "replace_all": {"type": "boolean", "description": "Replace all occurrences of old_string (default false)"}
+                },
+                "required": ["file_path", "old_string", "new_string"]
+            })
+        );
+
+        let error = EditInput::from_value(json!({"file_path": "a", "old_string": 1})).unwrap_err();
+        assert!(error.to_string().starts_with("old_string: invalid type"), "{}", error);
+    }
+}
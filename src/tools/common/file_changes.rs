@@ -0,0 +1,68 @@
+//! Backups and journal entries for the tools that change files
+//!
+//! WriteTool and EditTool keep a copy of what they replace in the
+//! session's `backups/` directory, and record each change in the agent's
+//! `FileChangeJournal` (if it has one) so UndoTool can reverse it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::helpers::{content_hash, FileChange, FileChangeJournal, FileTracker};
+use crate::runtime::AgentInternals;
+
+/// Save the contents a tool call is replacing, returning where
+///
+/// The copy is granted on the `FileTracker` so Read can open it. None if
+/// it couldn't be saved.
+pub(crate) async fn save_backup(internals: &AgentInternals, path: &Path, previous: &[u8]) -> Option<PathBuf> {
+    let tool_use_id = internals.context.current_tool_use_id.as_deref().unwrap_or("write");
+    let saved = {
+        let session = internals.session.read().await;
+        session.storage().save_backup(session.session_id(), tool_use_id, path, previous)
+    };
+    match saved {
+        Ok(backup) => {
+            let backup = fs::canonicalize(&backup).unwrap_or(backup);
+            if let Some(tracker) = internals.context.get_resource::<FileTracker>() {
+                tracker.grant_read(&backup);
+            }
+            Some(backup)
+        }
+        Err(e) => {
+            tracing::warn!("Failed to back up {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Record a change a tool call just made to `path`
+///
+/// `previous` is what the file held before (None if the call created it).
+pub(crate) fn record_change(
+    internals: &AgentInternals,
+    path: &Path,
+    previous: Option<&[u8]>,
+    backup: Option<PathBuf>,
+) {
+    let Some(journal) = internals.context.get_resource::<FileChangeJournal>() else {
+        return;
+    };
+    let after_hash = match fs::read(path) {
+        Ok(current) => content_hash(&current),
+        Err(e) => {
+            tracing::warn!("Cannot journal change to {}: {}", path.display(), e);
+            return;
+        }
+    };
+    let change = FileChange {
+        path: fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+        before_hash: previous.map(content_hash),
+        after_hash,
+        backup,
+        tool_use_id: internals.context.current_tool_use_id.clone().unwrap_or_default(),
+        turn: internals.context.current_turn,
+    };
+    if let Err(e) = journal.record(&change) {
+        tracing::warn!("Cannot journal change to {}: {}", path.display(), e);
+    }
+}
@@ -1,22 +1,50 @@
 //! Glob tool for file pattern matching
 //!
 //! Fast file pattern matching tool that works with any codebase size.
+//!
+//! Files ignored by `.gitignore`/`.ignore` and common build/dependency
+//! directories (`DEFAULT_IGNORED_DIRS`) are skipped unless `no_ignore` is
+//! set. Hidden files are skipped unless the pattern names one (a pattern
+//! component starting with a dot, like `.github/**` or `**/.env`).
 
 use anyhow::Result;
 use async_trait::async_trait;
-use glob::glob;
+use globset::GlobBuilder;
+use ignore::WalkBuilder;
 use serde::Deserialize;
 use serde_json::{json, Value};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
 
+use super::path_policy::{effective_policy, PathPolicy};
 use super::super::tool::{Tool, ToolInfo, ToolResult};
 use crate::llm::{ToolDefinition, ToolInputSchema};
 use crate::runtime::AgentInternals;
 
+/// Maximum number of paths listed in a result
+const MAX_RESULTS: usize = 100;
+
+/// Directories skipped even without an ignore file
+pub const DEFAULT_IGNORED_DIRS: &[&str] = &[
+    ".git",
+    ".hg",
+    ".svn",
+    "node_modules",
+    "target",
+    "__pycache__",
+    ".venv",
+    ".mypy_cache",
+    ".pytest_cache",
+    ".tox",
+];
+
 /// Glob tool for file pattern matching
 pub struct GlobTool {
     /// Base directory for searches
     base_dir: String,
+    /// Paths this tool may touch (falls back to the agent's `PathPolicy` resource)
+    policy: Option<Arc<PathPolicy>>,
 }
 
 /// Input for the glob tool
@@ -26,6 +54,9 @@ struct GlobInput {
     pattern: String,
     /// The directory to search in (optional)
     path: Option<String>,
+    /// Include ignored files and directories
+    #[serde(default)]
+    no_ignore: bool,
 }
 
 impl GlobTool {
@@ -35,31 +66,90 @@ impl GlobTool {
             .to_string_lossy()
             .to_string();
 
-        Ok(Self { base_dir })
+        Ok(Self::with_base_dir(base_dir))
     }
 
     /// Create a new Glob tool with a specific base directory
     pub fn with_base_dir(base_dir: impl Into<String>) -> Self {
         Self {
             base_dir: base_dir.into(),
+            policy: None,
         }
     }
 
-    /// Search for files matching the glob pattern
-    fn search(&self, pattern: &str, search_dir: Option<&str>) -> Result<Vec<String>> {
-        let base = search_dir.unwrap_or(&self.base_dir);
+    /// Confine this tool to the paths `policy` allows
+    pub fn with_policy(mut self, policy: PathPolicy) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
 
-        let full_pattern = if Path::new(pattern).is_absolute() {
-            pattern.to_string()
-        } else {
-            format!("{}/{}", base, pattern)
+    /// Search for files matching the glob pattern, most recently modified first
+    ///
+    /// With a policy, searching outside the allowed paths is an error and
+    /// matches the policy refuses (such as `.env` files) are left out.
+    fn search(
+        &self,
+        pattern: &str,
+        search_dir: Option<&str>,
+        no_ignore: bool,
+        policy: Option<&PathPolicy>,
+    ) -> Result<Vec<String>> {
+        let base = match search_dir {
+            Some(dir) => Path::new(&self.base_dir).join(dir),
+            None => PathBuf::from(&self.base_dir),
         };
+        let full_pattern = base.join(pattern);
+        let (mut root, relative_pattern) = split_pattern(&full_pattern);
+        if let Some(policy) = policy {
+            root = policy.check(&root)?;
+        }
+
+        tracing::info!(
+            "Searching with glob pattern: {} (in {})",
+            relative_pattern,
+            root.display()
+        );
 
-        tracing::info!("Searching with glob pattern: {}", full_pattern);
+        let matcher = GlobBuilder::new(&relative_pattern)
+            .literal_separator(true)
+            .build()?
+            .compile_matcher();
+        let include_hidden = relative_pattern
+            .split('/')
+            .any(|part| part.starts_with('.') && part != "." && part != "..");
 
-        let mut entries: Vec<(String, std::time::SystemTime)> = glob(&full_pattern)?
+        let mut walker = WalkBuilder::new(&root);
+        walker
+            .hidden(!include_hidden)
+            .ignore(!no_ignore)
+            .git_ignore(!no_ignore)
+            .git_global(!no_ignore)
+            .git_exclude(!no_ignore)
+            .parents(!no_ignore)
+            .require_git(false);
+        if !no_ignore {
+            walker.filter_entry(|entry| {
+                let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+                !(is_dir
+                    && entry.depth() > 0
+                    && DEFAULT_IGNORED_DIRS.contains(&entry.file_name().to_string_lossy().as_ref()))
+            });
+        }
+
+        let mut entries: Vec<(String, SystemTime)> = walker
+            .build()
             .filter_map(|entry| entry.ok())
-            .filter_map(|path| {
+            .filter(|entry| entry.file_type().map(|t| t.is_file()).unwrap_or(false))
+            .filter(|entry| policy.map(|p| p.allows(entry.path())).unwrap_or(true))
+            .filter(|entry| {
+                entry
+                    .path()
+                    .strip_prefix(&root)
+                    .map(|rel| matcher.is_match(rel))
+                    .unwrap_or(false)
+            })
+            .filter_map(|entry| {
+                let path = entry.path();
                 let mtime = path.metadata().ok()?.modified().ok()?;
                 let display_path = path
                     .strip_prefix(&self.base_dir)
@@ -69,13 +159,57 @@ impl GlobTool {
             })
             .collect();
 
-        // Sort by modification time (most recent first)
-        entries.sort_by(|a, b| b.1.cmp(&a.1));
+        // Sort by modification time (most recent first), then by path
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
 
         Ok(entries.into_iter().map(|(path, _)| path).collect())
     }
 }
 
+/// Split a pattern into the directory to walk and the glob relative to it
+///
+/// `/repo/src/**/*.rs` becomes (`/repo/src`, `**/*.rs`).
+fn split_pattern(pattern: &Path) -> (PathBuf, String) {
+    let mut root = PathBuf::new();
+    let mut rest = Vec::new();
+    for component in pattern.components() {
+        let part = component.as_os_str().to_string_lossy();
+        if rest.is_empty() && !part.contains(['*', '?', '[', '{']) {
+            root.push(component);
+        } else {
+            rest.push(part.into_owned());
+        }
+    }
+    if rest.is_empty() {
+        // No wildcards: match the file itself
+        let name = root
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        root.pop();
+        rest.push(name);
+    }
+    (root, rest.join("/"))
+}
+
+/// Format search results, capped at `MAX_RESULTS`
+fn format_results(pattern: &str, entries: &[String]) -> String {
+    if entries.is_empty() {
+        return format!("No files found matching pattern: {}", pattern);
+    }
+    let mut result = format!("Found {} files matching '{}':\n", entries.len(), pattern);
+    for entry in entries.iter().take(MAX_RESULTS) {
+        result.push_str(&format!("{}\n", entry));
+    }
+    if entries.len() > MAX_RESULTS {
+        result.push_str(&format!(
+            "({} more matches omitted; use a more specific pattern or path)\n",
+            entries.len() - MAX_RESULTS
+        ));
+    }
+    result
+}
+
 impl Default for GlobTool {
     fn default() -> Self {
         Self::with_base_dir(".")
@@ -100,7 +234,10 @@ impl Tool for GlobTool {
             description: Some(
                 "Fast file pattern matching tool that works with any codebase size. \
                 Supports glob patterns like \"**/*.js\" or \"src/**/*.ts\". \
-                Returns matching file paths sorted by modification time."
+                Returns matching file paths sorted by modification time (most recent first). \
+                Files ignored by .gitignore, common build and dependency directories, and hidden \
+                files are skipped; start a pattern component with a dot to match hidden files, \
+                or set no_ignore to search everything."
                     .to_string(),
             ),
             input_schema: ToolInputSchema {
@@ -113,6 +250,10 @@ impl Tool for GlobTool {
                     "path": {
                         "type": "string",
                         "description": "The directory to search in. If not specified, the current working directory will be used."
+                    },
+                    "no_ignore": {
+                        "type": "boolean",
+                        "description": "Include files ignored by .gitignore and the default ignore list (default false)"
                     }
                 })),
                 required: Some(vec!["pattern".to_string()]),
@@ -132,35 +273,22 @@ impl Tool for GlobTool {
             name: "Glob".to_string(),
             action_description: format!("Search files: {}", pattern),
             details: None,
+            read_only: true,
         }
     }
 
-    async fn execute(&self, input: &Value, _internals: &mut AgentInternals) -> Result<ToolResult> {
+    async fn execute(&self, input: &Value, internals: &mut AgentInternals) -> Result<ToolResult> {
         let glob_input: GlobInput = serde_json::from_value(input.clone())
             .map_err(|e| anyhow::anyhow!("Invalid glob input: {}", e))?;
 
-        match self.search(&glob_input.pattern, glob_input.path.as_deref()) {
-            Ok(entries) => {
-                if entries.is_empty() {
-                    Ok(ToolResult::success(format!(
-                        "No files found matching pattern: {}",
-                        glob_input.pattern
-                    )))
-                } else {
-                    let mut result = format!(
-                        "Found {} files matching '{}':\n",
-                        entries.len(),
-                        glob_input.pattern
-                    );
-                    for entry in entries.iter().take(100) {
-                        result.push_str(&format!("{}\n", entry));
-                    }
-                    if entries.len() > 100 {
-                        result.push_str(&format!("... and {} more\n", entries.len() - 100));
-                    }
-                    Ok(ToolResult::success(result))
-                }
-            }
+        let policy = effective_policy(&self.policy, internals);
+        match self.search(
+            &glob_input.pattern,
+            glob_input.path.as_deref(),
+            glob_input.no_ignore,
+            policy.as_deref(),
+        ) {
+            Ok(entries) => Ok(ToolResult::success(format_results(&glob_input.pattern, &entries))),
             Err(e) => Ok(ToolResult::error(format!("Glob search failed: {}", e))),
         }
     }
@@ -170,5 +298,99 @@ impl Tool for GlobTool {
     }
 }
 
-// Tests temporarily disabled - require AgentInternals test helper
-// TODO: Create test infrastructure for tools that need AgentInternals
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Duration;
+
+    fn touch(path: &Path, age_secs: u64) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, "x").unwrap();
+        let mtime = SystemTime::now() - Duration::from_secs(age_secs);
+        fs::File::options().write(true).open(path).unwrap().set_modified(mtime).unwrap();
+    }
+
+    /// A project with a root .gitignore, a nested one and the usual clutter
+    fn project() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join(".gitignore"), "*.log\nbuild/\n").unwrap();
+        fs::create_dir_all(root.join("src/gen")).unwrap();
+        fs::write(root.join("src/.gitignore"), "gen/\n").unwrap();
+        touch(&root.join("src/old.rs"), 300);
+        touch(&root.join("src/new.rs"), 10);
+        touch(&root.join("src/mid/lib.rs"), 100);
+        touch(&root.join("src/gen/bindings.rs"), 0);
+        touch(&root.join("build/out.rs"), 0);
+        touch(&root.join("target/debug/build.rs"), 0);
+        touch(&root.join("node_modules/pkg/index.rs"), 0);
+        touch(&root.join("debug.log"), 0);
+        touch(&root.join(".config/settings.rs"), 0);
+        dir
+    }
+
+    #[test]
+    fn test_respects_ignores_and_sorts_by_mtime() {
+        let dir = project();
+        let tool = GlobTool::with_base_dir(dir.path().to_string_lossy());
+
+        let found = tool.search("**/*.rs", None, false, None).unwrap();
+        assert_eq!(found, vec!["src/new.rs", "src/mid/lib.rs", "src/old.rs"]);
+
+        assert!(tool.search("*.log", None, false, None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_no_ignore_and_hidden() {
+        let dir = project();
+        let tool = GlobTool::with_base_dir(dir.path().to_string_lossy());
+
+        let all = tool.search("**/*.rs", None, true, None).unwrap();
+        for path in ["build/out.rs", "src/gen/bindings.rs", "target/debug/build.rs", "node_modules/pkg/index.rs"] {
+            assert!(all.contains(&path.to_string()), "{} missing from {:?}", path, all);
+        }
+        assert!(!all.iter().any(|p| p.starts_with(".config")));
+
+        let hidden = tool.search(".config/*.rs", None, false, None).unwrap();
+        assert_eq!(hidden, vec![".config/settings.rs"]);
+    }
+
+    #[test]
+    fn test_path_and_absolute_patterns() {
+        let dir = project();
+        let tool = GlobTool::with_base_dir(dir.path().to_string_lossy());
+
+        assert_eq!(tool.search("*.rs", Some("src"), false, None).unwrap(), vec!["src/new.rs", "src/old.rs"]);
+
+        let absolute = format!("{}/src/mid/*.rs", dir.path().display());
+        assert_eq!(tool.search(&absolute, None, false, None).unwrap(), vec!["src/mid/lib.rs"]);
+    }
+
+    #[test]
+    fn test_path_policy() {
+        let dir = project();
+        fs::write(dir.path().join("src/.env"), "SECRET=1").unwrap();
+        let policy = PathPolicy::new().allow_root(dir.path().join("src")).deny_glob("**/.env");
+        let tool = GlobTool::with_base_dir(dir.path().to_string_lossy());
+
+        let found = tool.search("src/.e*", None, false, Some(&policy)).unwrap();
+        assert!(found.is_empty(), "{:?}", found);
+        assert_eq!(tool.search("src/*.rs", None, false, Some(&policy)).unwrap().len(), 2);
+
+        let err = tool.search("**/*.rs", None, false, Some(&policy)).unwrap_err();
+        assert!(err.to_string().contains("outside the allowed directories"), "{}", err);
+    }
+
+    #[test]
+    fn test_result_cap() {
+        let entries: Vec<String> = (0..105).map(|i| format!("f{}.rs", i)).collect();
+        let output = format_results("*.rs", &entries);
+        assert!(output.starts_with("Found 105 files"));
+        assert!(output.contains("f99.rs"));
+        assert!(!output.contains("f100.rs"));
+        assert!(output.contains("(5 more matches omitted"));
+    }
+}
@@ -0,0 +1,417 @@
+//! Grep tool for content search
+//!
+//! Searches with an `rg` (ripgrep) binary when one is found on the PATH or
+//! configured, and with a built-in search otherwise. Both print results the
+//! same way and take the same Rust regex syntax, so the model's experience
+//! doesn't depend on what's installed (see [`search`]).
+
+pub mod search;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use self::search::{format_results, ResultFormat, SearchOptions};
+use super::path_policy::{effective_policy, PathPolicy};
+use super::super::tool::{Tool, ToolInfo, ToolResult};
+use super::super::ToolInput;
+use crate::llm::ToolDefinition;
+use crate::runtime::AgentInternals;
+
+/// Which search implementation the Grep tool uses
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum GrepBackend {
+    /// ripgrep if `rg` is on the PATH, the built-in search otherwise (default)
+    #[default]
+    Auto,
+    /// The ripgrep binary at this path
+    Ripgrep(PathBuf),
+    /// The built-in search (`regex` and `ignore` crates)
+    Builtin,
+}
+
+/// Grep tool for content search
+pub struct GrepTool {
+    /// Base directory for searches
+    base_dir: String,
+    /// Paths this tool may touch (falls back to the agent's `PathPolicy` resource)
+    policy: Option<Arc<PathPolicy>>,
+    /// Search implementation
+    backend: GrepBackend,
+}
+
+/// Output mode for grep results
+#[derive(Debug, Default, Deserialize, ToolInput, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum OutputMode {
+    Content,
+    #[default]
+    FilesWithMatches,
+    Count,
+}
+
+/// Input for the grep tool
+#[derive(Debug, Deserialize, ToolInput)]
+struct GrepInput {
+    /// The regular expression pattern to search for in file contents (Rust regex syntax; no look-around or backreferences)
+    pattern: String,
+    /// File or directory to search in. Defaults to current working directory.
+    path: Option<String>,
+    /// Glob pattern to filter files (e.g. "*.js", "*.{ts,tsx}")
+    glob: Option<String>,
+    /// Output mode: 'content', 'files_with_matches' (default), or 'count'
+    output_mode: Option<OutputMode>,
+    /// Number of lines to show before each match
+    #[serde(rename = "-B")]
+    before_context: Option<u32>,
+    /// Number of lines to show after each match
+    #[serde(rename = "-A")]
+    after_context: Option<u32>,
+    /// Number of lines to show before and after each match
+    #[serde(rename = "-C")]
+    context: Option<u32>,
+    /// Show line numbers in output. Defaults to true.
+    #[serde(rename = "-n")]
+    line_numbers: Option<bool>,
+    /// Case insensitive search
+    #[serde(rename = "-i")]
+    case_insensitive: Option<bool>,
+    /// File type to search (e.g. 'js', 'py', 'rust')
+    #[serde(rename = "type")]
+    file_type: Option<String>,
+    /// Limit output to first N lines/entries
+    head_limit: Option<usize>,
+    /// Skip first N lines/entries
+    offset: Option<usize>,
+    /// Enable multiline mode where . matches newlines
+    multiline: Option<bool>,
+}
+
+impl GrepTool {
+    /// Create a new Grep tool with the current directory as base
+    pub fn new() -> Result<Self> {
+        let base_dir = std::env::current_dir()?
+            .to_string_lossy()
+            .to_string();
+
+        Ok(Self::with_base_dir(base_dir))
+    }
+
+    /// Create a new Grep tool with a specific base directory
+    pub fn with_base_dir(base_dir: impl Into<String>) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+            policy: None,
+            backend: GrepBackend::Auto,
+        }
+    }
+
+    /// Confine this tool to the paths `policy` allows
+    pub fn with_policy(mut self, policy: PathPolicy) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Choose the search implementation (default: `GrepBackend::Auto`)
+    pub fn with_backend(mut self, backend: GrepBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Search with the ripgrep binary at `path`
+    pub fn with_ripgrep_path(self, path: impl Into<PathBuf>) -> Self {
+        self.with_backend(GrepBackend::Ripgrep(path.into()))
+    }
+
+    /// Run the search and format the results
+    ///
+    /// With a policy, the search path must be allowed and files it refuses
+    /// are left out of the results.
+    async fn search(&self, input: &GrepInput, policy: Option<&PathPolicy>) -> Result<String> {
+        let search_path = input.path.as_deref().unwrap_or(&self.base_dir);
+        let mut resolved = Path::new(&self.base_dir).join(search_path);
+        if let Some(policy) = policy {
+            resolved = policy.check(&resolved)?;
+        }
+        if !resolved.exists() {
+            return Ok(format!("Path not found: {}", search_path));
+        }
+        let output_mode = input.output_mode.unwrap_or_default();
+
+        // Context only applies to content mode; -A/-B override -C
+        let context = |specific: Option<u32>| match output_mode {
+            OutputMode::Content => specific.or(input.context).unwrap_or(0) as usize,
+            _ => 0,
+        };
+        // Denied globs relative to the search root are skipped while walking;
+        // every match is still checked against the policy below
+        let mut globs: Vec<String> = input.glob.iter().cloned().collect();
+        for denied in policy.map(|p| p.denied_globs.as_slice()).unwrap_or_default() {
+            globs.push(format!("!{}", denied));
+        }
+        let options = SearchOptions {
+            pattern: input.pattern.clone(),
+            case_insensitive: input.case_insensitive.unwrap_or(false),
+            multiline: input.multiline.unwrap_or(false),
+            before: context(input.before_context),
+            after: context(input.after_context),
+            file_type: input.file_type.clone(),
+            globs,
+        };
+
+        let rg = match &self.backend {
+            GrepBackend::Auto => search::find_ripgrep(),
+            GrepBackend::Ripgrep(path) => Some(path.clone()),
+            GrepBackend::Builtin => None,
+        };
+        let mut results = match rg {
+            Some(rg) => search::search_ripgrep(&rg, &resolved, search_path, &options).await?,
+            None => {
+                let (root, search_path, options) = (resolved.clone(), search_path.to_string(), options.clone());
+                tokio::task::spawn_blocking(move || search::search_builtin(&root, &search_path, &options)).await??
+            }
+        };
+        if let Some(policy) = policy {
+            results.retain(|matches| policy.allows(&matches.file));
+        }
+
+        let format = match output_mode {
+            OutputMode::Content => ResultFormat::Content,
+            OutputMode::FilesWithMatches => ResultFormat::Files,
+            OutputMode::Count => ResultFormat::Count,
+        };
+        let mut lines = format_results(
+            results,
+            format,
+            input.line_numbers.unwrap_or(true),
+            !resolved.is_file(),
+            options.has_context(),
+        );
+
+        // Apply offset
+        if let Some(offset) = input.offset {
+            if offset < lines.len() {
+                lines = lines[offset..].to_vec();
+            } else {
+                lines.clear();
+            }
+        }
+
+        // Apply head limit
+        if let Some(limit) = input.head_limit {
+            lines.truncate(limit);
+        }
+
+        Ok(lines.join("\n"))
+    }
+}
+
+impl Default for GrepTool {
+    fn default() -> Self {
+        Self::with_base_dir(".")
+    }
+}
+
+#[async_trait]
+impl Tool for GrepTool {
+    fn name(&self) -> &str {
+        "Grep"
+    }
+
+    fn description(&self) -> &str {
+        "Search file contents using regex patterns. Uses ripgrep when it is installed."
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        use crate::llm::types::CustomTool;
+
+        ToolDefinition::Custom(CustomTool {
+            name: "Grep".to_string(),
+            description: Some(
+                "A powerful search tool with ripgrep's options. \
+                Supports full Rust regex syntax (look-around and backreferences are not supported). \
+                Output modes: 'content' shows matching lines, 'files_with_matches' shows only file paths (default), 'count' shows match counts."
+                    .to_string(),
+            ),
+            input_schema: GrepInput::schema(),
+            tool_type: None,
+            cache_control: None,
+        })
+    }
+
+    fn get_info(&self, input: &Value) -> ToolInfo {
+        let pattern = input
+            .get("pattern")
+            .and_then(|v| v.as_str())
+            .unwrap_or("?");
+
+        ToolInfo {
+            name: "Grep".to_string(),
+            action_description: format!("Search for: {}", pattern),
+            details: None,
+            read_only: true,
+        }
+    }
+
+    async fn execute(&self, input: &Value, internals: &mut AgentInternals) -> Result<ToolResult> {
+        let grep_input = GrepInput::from_value(input.clone())
+            .map_err(|e| anyhow::anyhow!("Invalid grep input: {}", e))?;
+
+        let policy = effective_policy(&self.policy, internals);
+        match self.search(&grep_input, policy.as_deref()).await {
+            Ok(output) => {
+                if output.is_empty() {
+                    Ok(ToolResult::success(format!(
+                        "No matches found for pattern: {}",
+                        grep_input.pattern
+                    )))
+                } else {
+                    Ok(ToolResult::success(output))
+                }
+            }
+            Err(e) => Ok(ToolResult::error(format!("Search failed: {}", e))),
+        }
+    }
+
+    fn requires_permission(&self) -> bool {
+        false // Read-only operation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+
+    /// A small tree with ignored, hidden and binary files
+    fn fixture() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str, content: &[u8]| {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        };
+        write("src/main.rs", b"fn main() {\n    let total = add(1, 2);\n    println!(\"{}\", total);\n}\n");
+        write(
+            "src/lib.rs",
+            b"/// Adds numbers\npub fn add(a: i32, b: i32) -> i32 {\n    a + b\n}\n\npub fn sub(a: i32, b: i32) -> i32 {\n    a - b\n}\n",
+        );
+        write("docs/guide.md", b"# Guide\n\nCall `add` to add.\nTODO: document sub\n");
+        write(".ignore", b"generated.rs\n");
+        write("generated.rs", b"pub fn add_generated() {}\n");
+        write(".hidden/secret.rs", b"pub fn add_secret() {}\n");
+        write("data.bin", b"add\0binary");
+        dir
+    }
+
+    async fn run(tool: &GrepTool, input: Value) -> String {
+        let input = GrepInput::from_value(input).unwrap();
+        tool.search(&input, None).await.unwrap()
+    }
+
+    fn queries() -> Vec<Value> {
+        vec![
+            json!({"pattern": "add", "path": "."}),
+            json!({"pattern": "add", "path": ".", "output_mode": "count"}),
+            json!({"pattern": "fn \\w+", "path": "src", "output_mode": "content"}),
+            json!({"pattern": "a [+-] b", "path": "src", "output_mode": "content", "-C": 1}),
+            json!({"pattern": "total", "path": "src/main.rs", "output_mode": "content", "-n": false}),
+            json!({"pattern": "TODO", "path": ".", "output_mode": "content", "-i": true, "glob": "*.md"}),
+            json!({"pattern": "pub fn", "path": ".", "output_mode": "content", "type": "rust", "-A": 1}),
+            json!({"pattern": "i32 \\{.*?\\}", "path": "src", "output_mode": "content", "multiline": true}),
+            json!({"pattern": "fn", "path": ".", "output_mode": "content", "head_limit": 2, "offset": 1}),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_builtin_backend_output() {
+        let dir = fixture();
+        let tool = GrepTool::with_base_dir(dir.path().to_string_lossy()).with_backend(GrepBackend::Builtin);
+        let outputs: Vec<String> = futures::future::join_all(queries().into_iter().map(|q| run(&tool, q))).await;
+
+        assert_eq!(outputs[0], "./docs/guide.md\n./src/lib.rs\n./src/main.rs");
+        assert_eq!(outputs[1], "./docs/guide.md:1\n./src/lib.rs:1\n./src/main.rs:1");
+        assert_eq!(outputs[2], "src/lib.rs:2:pub fn add(a: i32, b: i32) -> i32 {\nsrc/lib.rs:6:pub fn sub(a: i32, b: i32) -> i32 {\nsrc/main.rs:1:fn main() {");
+        assert_eq!(
+            outputs[3],
+            "src/lib.rs-2-pub fn add(a: i32, b: i32) -> i32 {\nsrc/lib.rs:3:    a + b\nsrc/lib.rs-4-}\n--\n\
+             src/lib.rs-6-pub fn sub(a: i32, b: i32) -> i32 {\nsrc/lib.rs:7:    a - b\nsrc/lib.rs-8-}"
+        );
+        assert_eq!(outputs[4], "    let total = add(1, 2);\n    println!(\"{}\", total);");
+        assert_eq!(outputs[5], "./docs/guide.md:4:TODO: document sub");
+        assert_eq!(
+            outputs[6],
+            "./src/lib.rs:2:pub fn add(a: i32, b: i32) -> i32 {\n./src/lib.rs-3-    a + b\n--\n\
+             ./src/lib.rs:6:pub fn sub(a: i32, b: i32) -> i32 {\n./src/lib.rs-7-    a - b"
+        );
+        assert_eq!(
+            outputs[7],
+            "src/lib.rs:2:pub fn add(a: i32, b: i32) -> i32 {\nsrc/lib.rs:3:    a + b\nsrc/lib.rs:4:}\n\
+             src/lib.rs:6:pub fn sub(a: i32, b: i32) -> i32 {\nsrc/lib.rs:7:    a - b\nsrc/lib.rs:8:}"
+        );
+        assert_eq!(outputs[8], "./src/lib.rs:6:pub fn sub(a: i32, b: i32) -> i32 {\n./src/main.rs:1:fn main() {");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs an rg binary on PATH; run with --ignored"]
+    async fn test_backends_produce_identical_output() {
+        let rg = search::find_ripgrep().expect("rg not found on PATH");
+        let dir = fixture();
+        let base = dir.path().to_string_lossy().to_string();
+        let builtin = GrepTool::with_base_dir(&base).with_backend(GrepBackend::Builtin);
+        let ripgrep = GrepTool::with_base_dir(&base).with_ripgrep_path(rg);
+        for query in queries() {
+            assert_eq!(run(&builtin, query.clone()).await, run(&ripgrep, query.clone()).await, "{}", query);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_path_policy_denies_absolute_globs() {
+        let dir = fixture();
+        fs::create_dir_all(dir.path().join("secrets")).unwrap();
+        fs::write(dir.path().join("secrets/key.txt"), "pub fn add_key() {} // TOPSECRET\n").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let policy = PathPolicy::new()
+            .allow_root(&root)
+            .deny_glob(format!("{}/secrets/**", root.display()));
+        let tool = GrepTool::with_base_dir(root.to_string_lossy()).with_backend(GrepBackend::Builtin);
+
+        let input = GrepInput::from_value(json!({"pattern": "add", "path": "."})).unwrap();
+        let found = tool.search(&input, Some(&policy)).await.unwrap();
+        assert_eq!(found, "./docs/guide.md\n./src/lib.rs\n./src/main.rs");
+        let input = GrepInput::from_value(json!({"pattern": "TOPSECRET", "path": "secrets/key.txt"})).unwrap();
+        assert!(tool.search(&input, Some(&policy)).await.unwrap_err().to_string().contains("Path policy violation"));
+        // The directory itself is allowed, but not the files in it
+        for path in ["secrets", "."] {
+            let input = GrepInput::from_value(json!({"pattern": "TOPSECRET", "path": path, "output_mode": "content"})).unwrap();
+            assert_eq!(tool.search(&input, Some(&policy)).await.unwrap(), "", "{}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_patterns_are_explained() {
+        let dir = fixture();
+        let tool = GrepTool::with_base_dir(dir.path().to_string_lossy());
+        for (pattern, expected) in [
+            ("add(?=\\()", "look-around"),
+            ("(a)\\1", "backreferences"),
+            ("fn\\nmain", "multiline"),
+            ("[\\r\\n]+", "multiline"),
+            ("(unclosed", "invalid pattern"),
+        ] {
+            let input = GrepInput::from_value(json!({"pattern": pattern})).unwrap();
+            let error = tool.search(&input, None).await.unwrap_err().to_string();
+            assert!(error.contains(expected), "{}: {}", pattern, error);
+        }
+
+        // An escaped backslash before `n` matches the text `\n`, not a line break
+        assert_eq!(run(&tool, json!({"pattern": "\\\\n|\\s[+-]\\s", "path": "."})).await, "./src/lib.rs");
+        let input = GrepInput::from_value(json!({"pattern": "x", "type": "nope"})).unwrap();
+        assert!(tool.search(&input, None).await.unwrap_err().to_string().contains("unrecognized file type"));
+        assert_eq!(run(&tool, json!({"pattern": "x", "path": "missing"})).await, "Path not found: missing");
+    }
+}
@@ -0,0 +1,461 @@
+//! Search backends for the Grep tool
+//!
+//! Both backends return [`FileMatches`], which [`format_results`] turns into
+//! the tool's output, so the model sees the same text whichever one ran:
+//!
+//! - [`search_builtin`] - walks the tree with the `ignore` crate (honoring
+//!   `.gitignore`/`.ignore`, skipping hidden and binary files like ripgrep)
+//!   and matches with the `regex` crate
+//! - [`search_ripgrep`] - runs an `rg` binary with `--json`
+//!
+//! Patterns use Rust regex syntax (<https://docs.rs/regex/#syntax>), which
+//! is also ripgrep's default engine. [`build_regex`] checks the pattern
+//! before either backend runs, so features it lacks (look-around,
+//! backreferences) fail with an explanation instead of matching nothing.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use anyhow::{anyhow, bail, Result};
+use base64::Engine;
+use ignore::overrides::OverrideBuilder;
+use ignore::types::TypesBuilder;
+use ignore::WalkBuilder;
+use regex::bytes::{Regex, RegexBuilder};
+use regex_syntax::hir::{Class, Hir, HirKind};
+use serde::Deserialize;
+use tokio::process::Command;
+
+/// What to search for, shared by both backends
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    /// The regex pattern
+    pub pattern: String,
+    /// Match case-insensitively
+    pub case_insensitive: bool,
+    /// Let matches span lines (`.` matches newlines too)
+    pub multiline: bool,
+    /// Context lines before each match
+    pub before: usize,
+    /// Context lines after each match
+    pub after: usize,
+    /// ripgrep file type to restrict the search to (`rust`, `py`, ...)
+    pub file_type: Option<String>,
+    /// ripgrep-style globs; a leading `!` excludes
+    pub globs: Vec<String>,
+}
+
+impl SearchOptions {
+    /// Whether context lines were asked for
+    pub fn has_context(&self) -> bool {
+        self.before > 0 || self.after > 0
+    }
+}
+
+/// A line in the results
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResultLine {
+    /// Line number, from 1
+    pub number: u64,
+    /// The line, without its line break
+    pub text: String,
+    /// Whether the line matched (false for context lines)
+    pub is_match: bool,
+}
+
+/// The matching and context lines of one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMatches {
+    /// Path as the search printed it (the search path joined with the file)
+    pub path: String,
+    /// The file on disk
+    pub file: PathBuf,
+    /// Lines in order
+    pub lines: Vec<ResultLine>,
+}
+
+impl FileMatches {
+    /// Number of matching lines
+    pub fn match_count(&self) -> usize {
+        self.lines.iter().filter(|line| line.is_match).count()
+    }
+}
+
+/// How results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultFormat {
+    /// Matching lines (with context)
+    Content,
+    /// Paths of the files with a match
+    Files,
+    /// Matching lines per file
+    Count,
+}
+
+/// Compile the pattern, explaining features the regex syntax lacks
+pub fn build_regex(options: &SearchOptions) -> Result<Regex> {
+    if !options.multiline && matches_line_break(&options.pattern) {
+        bail!("the pattern matches a line break, which a line-by-line search never sees; set multiline to true");
+    }
+    RegexBuilder::new(&options.pattern)
+        .case_insensitive(options.case_insensitive)
+        .multi_line(true)
+        .dot_matches_new_line(options.multiline)
+        .build()
+        .map_err(|e| {
+            let message = e.to_string();
+            if message.contains("look-around") {
+                anyhow!(
+                    "look-around ((?=...), (?!...), (?<=...), (?<!...)) is not supported. \
+                     Patterns use Rust regex syntax; match the surrounding text instead"
+                )
+            } else if message.contains("backreferences") {
+                anyhow!("backreferences (\\1) are not supported. Patterns use Rust regex syntax")
+            } else {
+                anyhow!("invalid pattern: {}", message)
+            }
+        })
+}
+
+/// Whether the parsed pattern names a line break: a literal `\n` or a class
+/// made only of line-break characters (`[\r\n]`)
+///
+/// Broad classes that merely include `\n` (`\s`, `[^a]`) don't count, since
+/// ripgrep searches them line by line too. A pattern that doesn't parse is
+/// left to [`build_regex`] to report.
+fn matches_line_break(pattern: &str) -> bool {
+    fn walk(hir: &Hir) -> bool {
+        match hir.kind() {
+            HirKind::Literal(literal) => literal.0.contains(&b'\n'),
+            HirKind::Class(Class::Unicode(class)) => {
+                class.ranges().iter().any(|r| r.start() <= '\n' && '\n' <= r.end())
+                    && class.ranges().iter().all(|r| '\t' <= r.start() && r.end() <= '\r')
+            }
+            HirKind::Class(Class::Bytes(class)) => {
+                class.ranges().iter().any(|r| r.start() <= b'\n' && b'\n' <= r.end())
+                    && class.ranges().iter().all(|r| b'\t' <= r.start() && r.end() <= b'\r')
+            }
+            HirKind::Repetition(repetition) => walk(&repetition.sub),
+            HirKind::Capture(capture) => walk(&capture.sub),
+            HirKind::Concat(hirs) | HirKind::Alternation(hirs) => hirs.iter().any(walk),
+            HirKind::Empty | HirKind::Look(_) => false,
+        }
+    }
+    regex_syntax::ParserBuilder::new()
+        .multi_line(true)
+        .build()
+        .parse(pattern)
+        .is_ok_and(|hir| walk(&hir))
+}
+
+/// Check a file type name against ripgrep's built-in list
+fn check_file_type(file_type: &str) -> Result<()> {
+    let mut types = TypesBuilder::new();
+    types.add_defaults();
+    if types.definitions().iter().any(|def| def.name() == file_type) {
+        Ok(())
+    } else {
+        bail!("unrecognized file type: {}", file_type)
+    }
+}
+
+/// Check what both backends would otherwise report differently
+pub fn validate(options: &SearchOptions) -> Result<Regex> {
+    if let Some(ref file_type) = options.file_type {
+        check_file_type(file_type)?;
+    }
+    build_regex(options)
+}
+
+/// The path to print for `file`, found under `root`, which prints as `display`
+fn display_path(root: &Path, display: &str, file: &Path) -> String {
+    let path = match file.strip_prefix(root) {
+        Ok(relative) if relative.as_os_str().is_empty() => PathBuf::from(display),
+        Ok(relative) => Path::new(display).join(relative),
+        Err(_) => file.to_path_buf(),
+    };
+    path.to_string_lossy().to_string()
+}
+
+/// Search with the `regex` and `ignore` crates
+///
+/// Searches `root` and prints paths under it starting with `display` (the
+/// search path as the model gave it).
+pub fn search_builtin(root: &Path, display: &str, options: &SearchOptions) -> Result<Vec<FileMatches>> {
+    let regex = validate(options)?;
+    let root = root.to_path_buf();
+
+    let mut walk = WalkBuilder::new(&root);
+    if let Some(ref file_type) = options.file_type {
+        let mut types = TypesBuilder::new();
+        types.add_defaults();
+        types.select(file_type);
+        walk.types(types.build()?);
+    }
+    if !options.globs.is_empty() {
+        let mut overrides = OverrideBuilder::new(&root);
+        for glob in &options.globs {
+            overrides.add(glob)?;
+        }
+        walk.overrides(overrides.build()?);
+    }
+
+    let mut results = Vec::new();
+    for entry in walk.build() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::debug!("[Grep] Skipping entry: {}", e);
+                continue;
+            }
+        };
+        if !entry.file_type().is_some_and(|t| t.is_file()) {
+            continue;
+        }
+        let Ok(content) = std::fs::read(entry.path()) else {
+            continue;
+        };
+        // Binary files are skipped, as ripgrep does
+        if content.contains(&0) {
+            continue;
+        }
+        let lines = match_lines(&regex, &content, options);
+        if lines.is_empty() {
+            continue;
+        }
+        results.push(FileMatches {
+            path: display_path(&root, display, entry.path()),
+            file: entry.path().to_path_buf(),
+            lines,
+        });
+    }
+    Ok(results)
+}
+
+/// The matching lines of a file, with context
+fn match_lines(regex: &Regex, content: &[u8], options: &SearchOptions) -> Vec<ResultLine> {
+    let mut lines: Vec<&[u8]> = content.split(|&b| b == b'\n').collect();
+    if content.ends_with(b"\n") {
+        lines.pop();
+    }
+
+    let mut matched = vec![false; lines.len()];
+    if options.multiline {
+        // Start offset of each line, to map matches back to lines
+        let starts: Vec<usize> = std::iter::once(0)
+            .chain(content.iter().enumerate().filter(|(_, &b)| b == b'\n').map(|(i, _)| i + 1))
+            .take(lines.len())
+            .collect();
+        let line_of = |offset: usize| starts.partition_point(|&start| start <= offset).saturating_sub(1);
+        for found in regex.find_iter(content) {
+            let first = line_of(found.start());
+            let last = line_of(found.end().saturating_sub(1).max(found.start()));
+            for flag in &mut matched[first..=last.min(lines.len().saturating_sub(1))] {
+                *flag = true;
+            }
+        }
+    } else {
+        for (flag, line) in matched.iter_mut().zip(&lines) {
+            *flag = regex.is_match(line);
+        }
+    }
+
+    let mut shown = vec![false; lines.len()];
+    for (index, _) in matched.iter().enumerate().filter(|(_, &m)| m) {
+        let from = index.saturating_sub(options.before);
+        let to = (index + options.after).min(lines.len() - 1);
+        for flag in &mut shown[from..=to] {
+            *flag = true;
+        }
+    }
+
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| shown[*index])
+        .map(|(index, line)| ResultLine {
+            number: index as u64 + 1,
+            text: String::from_utf8_lossy(line).to_string(),
+            is_match: matched[index],
+        })
+        .collect()
+}
+
+/// Find `rg` on the PATH
+pub fn find_ripgrep() -> Option<PathBuf> {
+    let name = if cfg!(windows) { "rg.exe" } else { "rg" };
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// A line of `rg --json` output
+#[derive(Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "lowercase")]
+enum RgMessage {
+    Match(RgLines),
+    Context(RgLines),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct RgLines {
+    path: RgData,
+    lines: RgData,
+    line_number: Option<u64>,
+}
+
+/// Text or, when it isn't UTF-8, base64-encoded bytes
+#[derive(Deserialize)]
+struct RgData {
+    text: Option<String>,
+    bytes: Option<String>,
+}
+
+impl RgData {
+    fn decode(&self) -> String {
+        match (&self.text, &self.bytes) {
+            (Some(text), _) => text.clone(),
+            (None, Some(bytes)) => base64::engine::general_purpose::STANDARD
+                .decode(bytes)
+                .map(|b| String::from_utf8_lossy(&b).to_string())
+                .unwrap_or_default(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// Search with an `rg` binary
+///
+/// Runs on `root` with `--json` and turns its events into results, printing
+/// paths as [`search_builtin`] does.
+pub async fn search_ripgrep(rg: &Path, root: &Path, display: &str, options: &SearchOptions) -> Result<Vec<FileMatches>> {
+    validate(options)?;
+
+    let mut cmd = Command::new(rg);
+    cmd.arg("--json").arg("--no-config");
+    if options.case_insensitive {
+        cmd.arg("-i");
+    }
+    if options.multiline {
+        cmd.arg("-U").arg("--multiline-dotall");
+    }
+    if options.before > 0 {
+        cmd.arg("-B").arg(options.before.to_string());
+    }
+    if options.after > 0 {
+        cmd.arg("-A").arg(options.after.to_string());
+    }
+    if let Some(ref file_type) = options.file_type {
+        cmd.arg("--type").arg(file_type);
+    }
+    for glob in &options.globs {
+        cmd.arg("--glob").arg(glob);
+    }
+    cmd.arg("-e").arg(&options.pattern).arg("--").arg(root);
+
+    tracing::info!("[Grep] Running ripgrep: {:?}", cmd);
+    let output = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).output().await?;
+
+    // 1 = no matches; 2 = an error, possibly after some results
+    if output.status.code() == Some(2) && output.stdout.is_empty() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    let mut files: BTreeMap<String, BTreeMap<u64, ResultLine>> = BTreeMap::new();
+    for line in output.stdout.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+        let (data, is_match) = match serde_json::from_slice::<RgMessage>(line) {
+            Ok(RgMessage::Match(data)) => (data, true),
+            Ok(RgMessage::Context(data)) => (data, false),
+            Ok(RgMessage::Other) => continue,
+            Err(e) => {
+                tracing::warn!("[Grep] Unreadable ripgrep output: {}", e);
+                continue;
+            }
+        };
+        let Some(first) = data.line_number else {
+            continue;
+        };
+        let entry = files.entry(data.path.decode()).or_default();
+        let text = data.lines.decode();
+        // A multiline match covers several lines
+        for (offset, text) in text.strip_suffix('\n').unwrap_or(&text).split('\n').enumerate() {
+            let number = first + offset as u64;
+            let line = ResultLine {
+                number,
+                text: text.to_string(),
+                is_match,
+            };
+            match entry.get_mut(&number) {
+                Some(existing) => existing.is_match |= is_match,
+                None => {
+                    entry.insert(number, line);
+                }
+            }
+        }
+    }
+
+    Ok(files
+        .into_iter()
+        .map(|(path, lines)| {
+            let file = PathBuf::from(path);
+            FileMatches {
+                path: display_path(root, display, &file),
+                file,
+                lines: lines.into_values().collect(),
+            }
+        })
+        .collect())
+}
+
+/// Print results like ripgrep's default output
+///
+/// Files are sorted by path. `with_filename` is false when a single file
+/// was searched, as ripgrep leaves the name out then. Context groups are
+/// separated by `--`.
+pub fn format_results(
+    mut results: Vec<FileMatches>,
+    format: ResultFormat,
+    line_numbers: bool,
+    with_filename: bool,
+    context: bool,
+) -> Vec<String> {
+    results.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut output = Vec::new();
+    for file in results.iter().filter(|f| f.match_count() > 0) {
+        match format {
+            ResultFormat::Files => output.push(file.path.clone()),
+            ResultFormat::Count if with_filename => {
+                output.push(format!("{}:{}", file.path, file.match_count()));
+            }
+            ResultFormat::Count => output.push(file.match_count().to_string()),
+            ResultFormat::Content => {
+                let mut previous: Option<u64> = None;
+                if context && !output.is_empty() {
+                    output.push("--".to_string());
+                }
+                for line in &file.lines {
+                    if context && previous.is_some_and(|p| line.number != p + 1) {
+                        output.push("--".to_string());
+                    }
+                    previous = Some(line.number);
+                    let separator = if line.is_match { ':' } else { '-' };
+                    let mut text = String::new();
+                    if with_filename {
+                        text.push_str(&file.path);
+                        text.push(separator);
+                    }
+                    if line_numbers {
+                        text.push_str(&line.number.to_string());
+                        text.push(separator);
+                    }
+                    text.push_str(&line.text);
+                    output.push(text);
+                }
+            }
+        }
+    }
+    output
+}
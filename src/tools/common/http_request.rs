@@ -0,0 +1,669 @@
+//! HttpRequest tool for calling REST endpoints
+//!
+//! A generic "call this endpoint" tool, so integrations don't need a
+//! bespoke Tool for every API. Which hosts the model may reach is fixed
+//! when the tool is built:
+//! - `allow_host` - only these hosts (empty = any host)
+//! - `deny_host` - never these hosts; patterns may be CIDR ranges
+//! - loopback, link-local and cloud metadata addresses (`localhost`,
+//!   `127.0.0.1`, `169.254.169.254`, ...) are always refused unless a
+//!   non-wildcard `allow_host` pattern names them
+//!
+//! The host is resolved once and the connection is pinned to the checked
+//! addresses, so DNS tricks can't swap in a denied address afterwards.
+//! Redirects are not followed; the 3xx response (with its `Location`) is
+//! returned instead.
+//!
+//! Auth headers added with `with_header_for` are sent on every request to
+//! matching hosts but never appear in the tool's definition or output.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let tool = HttpRequestTool::new()?
+//!     .allow_host("api.github.com")
+//!     .with_header_for("api.github.com", "Authorization", format!("Bearer {}", token));
+//! registry.register(tool);
+//! ```
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use reqwest::{header, Client, Method, Url};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::super::tool::{Tool, ToolInfo, ToolResult};
+use crate::llm::{define_tool, ToolDefinition};
+use crate::runtime::AgentInternals;
+
+/// Default timeout per request, in seconds
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// Longest timeout the model may ask for, in seconds
+const MAX_TIMEOUT_SECS: u64 = 120;
+
+/// User agent sent with every request
+const USER_AGENT: &str = concat!("shadow-agent-sdk/", env!("CARGO_PKG_VERSION"));
+
+/// Default response body cap
+pub const DEFAULT_MAX_BODY_BYTES: usize = 256 * 1024;
+
+/// Host names refused unless explicitly allowed
+const LOCAL_HOST_NAMES: &[&str] = &["localhost", "*.localhost", "metadata.google.internal"];
+
+/// Response headers worth showing to the model
+const INTERESTING_HEADERS: &[&str] = &[
+    "content-type",
+    "content-length",
+    "location",
+    "retry-after",
+    "etag",
+    "last-modified",
+    "link",
+    "www-authenticate",
+];
+
+/// Request body: a raw string or a JSON value
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RequestBody {
+    Text(String),
+    Json(Value),
+}
+
+/// Input for the HttpRequest tool
+#[derive(Debug, Deserialize)]
+struct HttpRequestInput {
+    /// HTTP method (default GET)
+    #[serde(default = "default_method")]
+    method: String,
+    /// The URL to call
+    url: String,
+    /// Extra request headers
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    /// Request body
+    body: Option<RequestBody>,
+    /// Timeout in seconds
+    timeout: Option<u64>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+/// A host pattern: exact name, `*.domain`, `*`, an IP or a CIDR range
+#[derive(Debug, Clone)]
+enum HostPattern {
+    Any,
+    Name(String),
+    Suffix(String),
+    Network(IpAddr, u8),
+}
+
+impl HostPattern {
+    fn parse(pattern: &str) -> Self {
+        let pattern = pattern.trim().trim_start_matches('[').trim_end_matches(']').to_ascii_lowercase();
+        if pattern == "*" {
+            return HostPattern::Any;
+        }
+        if let Some(suffix) = pattern.strip_prefix("*.") {
+            return HostPattern::Suffix(suffix.to_string());
+        }
+        let (addr, prefix) = match pattern.split_once('/') {
+            Some((addr, prefix)) => (addr, prefix.parse().ok()),
+            None => (pattern.as_str(), None),
+        };
+        match addr.parse::<IpAddr>() {
+            Ok(ip) => {
+                let max = if ip.is_ipv4() { 32 } else { 128 };
+                HostPattern::Network(ip, prefix.unwrap_or(max).min(max))
+            }
+            Err(_) => HostPattern::Name(pattern),
+        }
+    }
+
+    fn matches_name(&self, host: &str) -> bool {
+        match self {
+            HostPattern::Any => true,
+            HostPattern::Name(name) => host == name,
+            HostPattern::Suffix(suffix) => host.ends_with(&format!(".{}", suffix)),
+            HostPattern::Network(ip, prefix) => host
+                .parse::<IpAddr>()
+                .map(|host_ip| ip_in_network(host_ip, *ip, *prefix))
+                .unwrap_or(false),
+        }
+    }
+
+    fn matches_ip(&self, addr: IpAddr) -> bool {
+        match self {
+            HostPattern::Any => true,
+            HostPattern::Network(ip, prefix) => ip_in_network(addr, *ip, *prefix),
+            _ => false,
+        }
+    }
+}
+
+/// Whether `addr` lies in `network/prefix`
+fn ip_in_network(addr: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    let addr = canonical_ip(addr);
+    match (addr, canonical_ip(network)) {
+        (IpAddr::V4(a), IpAddr::V4(n)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(a) & mask == u32::from(n) & mask
+        }
+        (IpAddr::V6(a), IpAddr::V6(n)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(a) & mask == u128::from(n) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Treat IPv4-mapped IPv6 addresses (`::ffff:127.0.0.1`) as IPv4
+fn canonical_ip(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(addr),
+        v4 => v4,
+    }
+}
+
+/// Loopback, link-local (including cloud metadata) and unspecified addresses
+fn is_local_address(addr: IpAddr) -> bool {
+    match canonical_ip(addr) {
+        IpAddr::V4(v4) => v4.is_loopback() || v4.is_link_local() || v4.is_unspecified(),
+        IpAddr::V6(v6) => {
+            v6.is_loopback() || v6.is_unspecified() || (v6.segments()[0] & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+/// A hidden header for hosts matching a pattern
+#[derive(Debug, Clone)]
+struct InjectedHeader {
+    host: HostPattern,
+    name: String,
+    value: String,
+}
+
+/// HttpRequest tool for calling HTTP APIs
+pub struct HttpRequestTool {
+    allowed_hosts: Vec<HostPattern>,
+    denied_hosts: Vec<HostPattern>,
+    injected_headers: Vec<InjectedHeader>,
+    max_body_bytes: usize,
+}
+
+impl HttpRequestTool {
+    /// Create an HttpRequest tool that may call any non-local host
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            allowed_hosts: Vec::new(),
+            denied_hosts: Vec::new(),
+            injected_headers: Vec::new(),
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+        })
+    }
+
+    /// Only allow hosts matching one of the allowed patterns
+    ///
+    /// Patterns are host names (`api.example.com`), subdomain wildcards
+    /// (`*.example.com`), `*`, IP addresses or CIDR ranges. Naming a local
+    /// address here (e.g. `localhost`) lifts the default local-address block
+    /// for it.
+    pub fn allow_host(mut self, pattern: impl AsRef<str>) -> Self {
+        self.allowed_hosts.push(HostPattern::parse(pattern.as_ref()));
+        self
+    }
+
+    /// Never allow hosts matching `pattern` (checked before the allow-list)
+    pub fn deny_host(mut self, pattern: impl AsRef<str>) -> Self {
+        self.denied_hosts.push(HostPattern::parse(pattern.as_ref()));
+        self
+    }
+
+    /// Send a header on every request to hosts matching `pattern`
+    ///
+    /// Use this for auth tokens: the value is never shown to the model and
+    /// overrides a header of the same name the model sets.
+    pub fn with_header_for(
+        mut self,
+        pattern: impl AsRef<str>,
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        self.injected_headers.push(InjectedHeader {
+            host: HostPattern::parse(pattern.as_ref()),
+            name: name.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    /// Set the response body cap
+    pub fn with_max_body_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_body_bytes = max_bytes;
+        self
+    }
+
+    /// Check the URL's host and resolve it to the addresses to connect to
+    async fn check_host(&self, url: &Url) -> std::result::Result<Vec<SocketAddr>, String> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| format!("URL has no host: {}", url))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_ascii_lowercase();
+        let port = url.port_or_known_default().unwrap_or(80);
+
+        if self.denied_hosts.iter().any(|p| p.matches_name(&host)) {
+            return Err(format!("Host '{}' is denied", host));
+        }
+        if !self.allowed_hosts.is_empty() && !self.allowed_hosts.iter().any(|p| p.matches_name(&host)) {
+            return Err(format!("Host '{}' is not in the allowed hosts", host));
+        }
+        let explicitly_allowed = self
+            .allowed_hosts
+            .iter()
+            .any(|p| !matches!(p, HostPattern::Any) && p.matches_name(&host));
+
+        let addrs: Vec<SocketAddr> = match host.parse::<IpAddr>() {
+            Ok(ip) => vec![SocketAddr::new(ip, port)],
+            Err(_) => tokio::net::lookup_host((host.as_str(), port))
+                .await
+                .map_err(|e| format!("Could not resolve '{}': {}", host, e))?
+                .collect(),
+        };
+        if addrs.is_empty() {
+            return Err(format!("Could not resolve '{}'", host));
+        }
+
+        for addr in &addrs {
+            if self.denied_hosts.iter().any(|p| p.matches_ip(addr.ip())) {
+                return Err(format!("Host '{}' resolves to denied address {}", host, addr.ip()));
+            }
+        }
+        if !explicitly_allowed {
+            let local_name = LOCAL_HOST_NAMES
+                .iter()
+                .any(|name| HostPattern::parse(name).matches_name(&host));
+            if let Some(addr) = addrs.iter().find(|a| is_local_address(a.ip())) {
+                return Err(format!(
+                    "Host '{}' is a local or link-local address ({}) and is blocked by default",
+                    host,
+                    addr.ip()
+                ));
+            }
+            if local_name {
+                return Err(format!("Host '{}' is blocked by default", host));
+            }
+        }
+        Ok(addrs)
+    }
+
+    /// Make the request, or explain why it couldn't be made
+    async fn request(&self, input: HttpRequestInput) -> std::result::Result<String, String> {
+        let method = Method::from_bytes(input.method.to_ascii_uppercase().as_bytes())
+            .map_err(|_| format!("Invalid HTTP method '{}'", input.method))?;
+        let url = Url::parse(&input.url).map_err(|e| format!("Invalid URL '{}': {}", input.url, e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(format!("Unsupported URL scheme '{}'", url.scheme()));
+        }
+        let addrs = self.check_host(&url).await?;
+        let host = url.host_str().unwrap_or_default().to_string();
+
+        let client = Client::builder()
+            .user_agent(USER_AGENT)
+            .redirect(reqwest::redirect::Policy::none())
+            .resolve_to_addrs(&host, &addrs)
+            .build()
+            .map_err(|e| e.to_string())?;
+        let timeout = Duration::from_secs(
+            input.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS).clamp(1, MAX_TIMEOUT_SECS),
+        );
+
+        let mut headers = header::HeaderMap::new();
+        for (name, value) in &input.headers {
+            let name = header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("Invalid header name '{}'", name))?;
+            let value = header::HeaderValue::from_str(value)
+                .map_err(|_| format!("Invalid value for header '{}'", name))?;
+            headers.insert(name, value);
+        }
+        let host_lower = host.to_ascii_lowercase();
+        for injected in self.injected_headers.iter().filter(|i| i.host.matches_name(&host_lower)) {
+            // Never echo the value in errors
+            let name = header::HeaderName::from_bytes(injected.name.as_bytes())
+                .map_err(|_| "Invalid configured header name".to_string())?;
+            let mut value = header::HeaderValue::from_str(&injected.value)
+                .map_err(|_| format!("Invalid configured value for header '{}'", name))?;
+            value.set_sensitive(true);
+            headers.insert(name, value);
+        }
+
+        let mut request = client.request(method.clone(), url.clone()).headers(headers).timeout(timeout);
+        match input.body {
+            Some(RequestBody::Text(text)) => request = request.body(text),
+            Some(RequestBody::Json(value)) => request = request.json(&value),
+            None => {}
+        }
+
+        tracing::info!("[HttpRequest] {} {}", method, url);
+        let mut response = request
+            .send()
+            .await
+            .map_err(|e| format!("Request to {} failed: {}", url, e.without_url()))?;
+
+        let status = response.status();
+        let mut output = format!(
+            "HTTP {} {}\n",
+            status.as_u16(),
+            status.canonical_reason().unwrap_or("")
+        );
+        for (name, value) in response.headers() {
+            let name = name.as_str();
+            if INTERESTING_HEADERS.contains(&name) || name.starts_with("x-ratelimit") {
+                output.push_str(&format!("{}: {}\n", name, String::from_utf8_lossy(value.as_bytes())));
+            }
+        }
+        let is_json = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|ct| ct.contains("json"))
+            .unwrap_or(false);
+
+        let mut body = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+            let room = self.max_body_bytes - body.len();
+            if chunk.len() > room {
+                body.extend_from_slice(&chunk[..room]);
+                truncated = true;
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        output.push('\n');
+        let pretty = if is_json && !truncated {
+            serde_json::from_slice::<Value>(&body)
+                .ok()
+                .and_then(|v| serde_json::to_string_pretty(&v).ok())
+        } else {
+            None
+        };
+        match pretty {
+            Some(pretty) => output.push_str(&pretty),
+            None if body.contains(&0) => output.push_str(&format!("(binary body, {} bytes)", body.len())),
+            None => output.push_str(&String::from_utf8_lossy(&body)),
+        }
+        if truncated {
+            output.push_str(&format!("\n\n(Body truncated at {} bytes)", self.max_body_bytes));
+        }
+        Ok(output)
+    }
+}
+
+#[async_trait]
+impl Tool for HttpRequestTool {
+    fn name(&self) -> &str {
+        "HttpRequest"
+    }
+
+    fn description(&self) -> &str {
+        "Make an HTTP request to an API endpoint."
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        define_tool(
+            "HttpRequest",
+            "Makes an HTTP request and returns the status, the main response headers and \
+            the body (JSON is pretty-printed, large bodies are truncated).\n\n\
+            Usage notes:\n\
+            - The URL must be a fully-formed http:// or https:// URL\n\
+            - Only some hosts may be reachable; a refused host is reported as an error\n\
+            - Redirects are not followed; the redirect response is returned\n\
+            - Authentication for known APIs is added automatically, don't ask for tokens",
+            json!({
+                "method": {
+                    "type": "string",
+                    "enum": ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"],
+                    "description": "HTTP method (default: GET)"
+                },
+                "url": {
+                    "type": "string",
+                    "description": "The URL to call"
+                },
+                "headers": {
+                    "type": "object",
+                    "additionalProperties": {"type": "string"},
+                    "description": "Request headers"
+                },
+                "body": {
+                    "description": "Request body: a string is sent as-is, anything else as JSON"
+                },
+                "timeout": {
+                    "type": "number",
+                    "description": "Timeout in seconds (default 30, max 120)"
+                }
+            }),
+            vec!["url".to_string()],
+        )
+    }
+
+    fn get_info(&self, input: &Value) -> ToolInfo {
+        let method = input
+            .get("method")
+            .and_then(|v| v.as_str())
+            .unwrap_or("GET")
+            .to_ascii_uppercase();
+        let url = input.get("url").and_then(|v| v.as_str()).unwrap_or("");
+
+        ToolInfo {
+            name: "HttpRequest".to_string(),
+            action_description: format!("{} {}", method, url),
+            details: input.get("body").map(|body| match body {
+                Value::String(text) => text.clone(),
+                other => other.to_string(),
+            }),
+            read_only: matches!(method.as_str(), "GET" | "HEAD" | "OPTIONS"),
+        }
+    }
+
+    async fn execute(&self, input: &Value, _internals: &mut AgentInternals) -> Result<ToolResult> {
+        let input: HttpRequestInput = serde_json::from_value(input.clone())
+            .map_err(|e| anyhow!("Invalid HttpRequest input: {}", e))?;
+
+        match self.request(input).await {
+            Ok(output) => Ok(ToolResult::success(output)),
+            Err(e) => Ok(ToolResult::error(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve HTTP/1.1 on localhost, answering every request with a JSON echo of it
+    async fn serve() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    break;
+                };
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    let header_end = loop {
+                        if let Some(i) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                            break i + 4;
+                        }
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    };
+                    let head = String::from_utf8_lossy(&request[..header_end]).to_string();
+                    let mut lines = head.lines();
+                    let mut start = lines.next().unwrap_or("").split_whitespace();
+                    let (method, path) = (start.next().unwrap_or(""), start.next().unwrap_or(""));
+                    let headers: HashMap<String, String> = lines
+                        .filter_map(|l| l.split_once(':'))
+                        .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string()))
+                        .collect();
+                    let length: usize = headers.get("content-length").and_then(|l| l.parse().ok()).unwrap_or(0);
+                    while request.len() < header_end + length {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => break,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let body = String::from_utf8_lossy(&request[header_end..]).to_string();
+
+                    let response = if path == "/text" {
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 26\r\n\
+                        Connection: close\r\n\r\nabcdefghijklmnopqrstuvwxyz"
+                            .to_string()
+                    } else {
+                        let echo = json!({
+                            "method": method,
+                            "path": path,
+                            "body": body,
+                            "authorization": headers.get("authorization"),
+                            "x-custom": headers.get("x-custom"),
+                        })
+                        .to_string();
+                        format!(
+                            "HTTP/1.1 201 Created\r\nContent-Type: application/json\r\nX-Request-Id: 1\r\n\
+                            X-RateLimit-Remaining: 9\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            echo.len(),
+                            echo
+                        )
+                    };
+                    let _ = stream.write_all(response.as_bytes()).await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        });
+        port
+    }
+
+    fn input(method: &str, url: String, body: Option<Value>) -> HttpRequestInput {
+        serde_json::from_value(json!({"method": method, "url": url, "body": body})).unwrap()
+    }
+
+    fn local_tool() -> HttpRequestTool {
+        HttpRequestTool::new().unwrap().allow_host("127.0.0.1")
+    }
+
+    fn echoed(output: &str) -> Value {
+        serde_json::from_str(&output[output.find("\n\n").unwrap()..]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_methods_and_bodies() {
+        let port = serve().await;
+        let tool = local_tool();
+        let url = format!("http://127.0.0.1:{}/items", port);
+
+        let output = tool.request(input("get", url.clone(), None)).await.unwrap();
+        assert!(output.starts_with("HTTP 201 Created\n"), "{}", output);
+        assert!(output.contains("content-type: application/json"));
+        assert!(output.contains("x-ratelimit-remaining: 9"));
+        assert!(!output.contains("x-request-id"));
+        // Pretty-printed
+        assert!(output.contains("\n  \"method\": \"GET\""), "{}", output);
+
+        let output = tool
+            .request(input("POST", url.clone(), Some(json!({"name": "a"}))))
+            .await
+            .unwrap();
+        let echo = echoed(&output);
+        assert_eq!(echo["method"], "POST");
+        assert_eq!(echo["body"], r#"{"name":"a"}"#);
+
+        for method in ["PUT", "PATCH", "DELETE"] {
+            let output = tool
+                .request(input(method, url.clone(), Some(json!("raw text"))))
+                .await
+                .unwrap();
+            let echo = echoed(&output);
+            assert_eq!(echo["method"], method);
+            assert_eq!(echo["body"], "raw text");
+        }
+
+        let small = local_tool().with_max_body_bytes(10);
+        let output = small
+            .request(input("GET", format!("http://127.0.0.1:{}/text", port), None))
+            .await
+            .unwrap();
+        assert!(output.contains("abcdefghij\n\n(Body truncated at 10 bytes)"), "{}", output);
+    }
+
+    #[tokio::test]
+    async fn test_injected_headers_are_hidden() {
+        let port = serve().await;
+        let tool = local_tool().with_header_for("127.0.0.1", "Authorization", "Bearer s3cret");
+        let mut request = input("GET", format!("http://127.0.0.1:{}/", port), None);
+        request.headers.insert("Authorization".into(), "Bearer model".into());
+        request.headers.insert("X-Custom".into(), "yes".into());
+
+        let output = tool.request(request).await.unwrap();
+        let echo = echoed(&output);
+        assert_eq!(echo["authorization"], "Bearer s3cret");
+        assert_eq!(echo["x-custom"], "yes");
+
+        assert!(!tool.definition_json().contains("s3cret"));
+    }
+
+    #[tokio::test]
+    async fn test_ssrf_deny_list() {
+        let tool = HttpRequestTool::new().unwrap();
+        for url in [
+            "http://127.0.0.1:1/",
+            "http://localhost:1/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://[::1]:1/",
+            "http://[::ffff:127.0.0.1]:1/",
+            "http://0.0.0.0:1/",
+        ] {
+            let err = tool.request(input("GET", url.to_string(), None)).await.unwrap_err();
+            assert!(err.contains("blocked by default"), "{}: {}", url, err);
+        }
+
+        // A wildcard allow-list doesn't lift the default block
+        let tool = HttpRequestTool::new().unwrap().allow_host("*");
+        assert!(tool.request(input("GET", "http://127.0.0.1:1/".into(), None)).await.is_err());
+
+        let tool = HttpRequestTool::new().unwrap().allow_host("api.example.com");
+        let err = tool.request(input("GET", "http://127.0.0.1:1/".into(), None)).await.unwrap_err();
+        assert!(err.contains("not in the allowed hosts"), "{}", err);
+
+        let tool = local_tool().deny_host("127.0.0.0/8");
+        let err = tool.request(input("GET", "http://127.0.0.1:1/".into(), None)).await.unwrap_err();
+        assert!(err.contains("is denied"), "{}", err);
+    }
+
+    #[test]
+    fn test_read_only_info() {
+        let tool = HttpRequestTool::new().unwrap();
+        assert!(tool.get_info(&json!({"url": "http://x"})).read_only);
+        assert!(!tool.get_info(&json!({"method": "post", "url": "http://x"})).read_only);
+    }
+
+    impl HttpRequestTool {
+        fn definition_json(&self) -> String {
+            serde_json::to_string(&self.definition()).unwrap()
+        }
+    }
+}
@@ -0,0 +1,415 @@
+//! LS tool for listing directories
+//!
+//! A read-only, permission-free replacement for `ls -la` in Bash. Lists a
+//! directory as an indented tree, a configurable number of levels deep.
+//! Directories end with `/` and files show their size. Entries that can't
+//! be read are listed with a note instead of failing the whole call.
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::path_policy::{effective_policy, PathPolicy};
+use super::super::tool::{Tool, ToolInfo, ToolResult};
+use crate::llm::{ToolDefinition, ToolInputSchema};
+use crate::runtime::AgentInternals;
+
+/// Levels listed when no depth is given
+const DEFAULT_DEPTH: usize = 2;
+/// Deepest listing allowed
+const MAX_DEPTH: usize = 5;
+/// Entries listed before the output is truncated
+const DEFAULT_MAX_ENTRIES: usize = 1000;
+
+/// LS tool for directory listings
+pub struct LsTool {
+    /// Base directory for relative paths
+    base_dir: String,
+    /// Directory listings must stay inside (optional)
+    root: Option<PathBuf>,
+    /// Maximum number of entries per listing
+    max_entries: usize,
+    /// Paths this tool may touch (falls back to the agent's `PathPolicy` resource)
+    policy: Option<Arc<PathPolicy>>,
+}
+
+/// Input for the LS tool
+#[derive(Debug, Deserialize)]
+struct LsInput {
+    /// The directory to list (required)
+    path: String,
+    /// Glob patterns of entries to leave out
+    #[serde(default)]
+    ignore: Vec<String>,
+    /// How many levels to list (1 = only the directory's own entries)
+    depth: Option<usize>,
+}
+
+/// State carried through one listing
+struct Listing {
+    ignore: GlobSet,
+    max_entries: usize,
+    entries: usize,
+    truncated: bool,
+    output: String,
+}
+
+impl LsTool {
+    /// Create a new LS tool with the current directory as base
+    pub fn new() -> Result<Self> {
+        let base_dir = std::env::current_dir()?
+            .to_string_lossy()
+            .to_string();
+
+        Ok(Self::with_base_dir(base_dir))
+    }
+
+    /// Create a new LS tool with a specific base directory
+    pub fn with_base_dir(base_dir: impl Into<String>) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+            root: None,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            policy: None,
+        }
+    }
+
+    /// Refuse to list anything outside `root`
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    /// Confine this tool to the paths `policy` allows
+    pub fn with_policy(mut self, policy: PathPolicy) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Set the maximum number of entries per listing
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Resolve a path (handle both absolute and relative)
+    fn resolve_path(&self, path: &str) -> PathBuf {
+        let path = Path::new(path);
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            Path::new(&self.base_dir).join(path)
+        }
+    }
+
+    /// List a directory
+    fn list(&self, input: &LsInput) -> Result<String> {
+        let path = self.resolve_path(&input.path);
+        let path = fs::canonicalize(&path).map_err(|e| anyhow!("Cannot access {}: {}", path.display(), e))?;
+
+        if let Some(root) = &self.root {
+            let root = fs::canonicalize(root).unwrap_or_else(|_| root.clone());
+            if !path.starts_with(&root) {
+                return Err(anyhow!(
+                    "{} is outside the allowed directory {}",
+                    path.display(),
+                    root.display()
+                ));
+            }
+        }
+        if !path.is_dir() {
+            return Err(anyhow!("{} is not a directory", path.display()));
+        }
+
+        let mut ignore = GlobSetBuilder::new();
+        for pattern in &input.ignore {
+            ignore.add(Glob::new(pattern).map_err(|e| anyhow!("Invalid ignore pattern '{}': {}", pattern, e))?);
+        }
+
+        tracing::info!("Listing directory: {}", path.display());
+
+        let mut listing = Listing {
+            ignore: ignore.build()?,
+            max_entries: self.max_entries,
+            entries: 0,
+            truncated: false,
+            output: format!("{}/\n", path.display()),
+        };
+        let depth = input.depth.unwrap_or(DEFAULT_DEPTH).clamp(1, MAX_DEPTH);
+        let children = fs::read_dir(&path).map_err(|e| anyhow!("Cannot read {}: {}", path.display(), e))?;
+        list_entries(&mut listing, &path, Path::new(""), children, 1, depth);
+
+        if listing.truncated {
+            listing.output.push_str(&format!(
+                "\n(Listing truncated at {} entries; list a subdirectory or use ignore patterns to see more)\n",
+                listing.max_entries
+            ));
+        } else if listing.entries == 0 {
+            listing.output.push_str("  (empty)\n");
+        }
+        Ok(listing.output)
+    }
+}
+
+/// Add the entries of one directory to the listing, recursing up to `max_depth`
+fn list_entries(
+    listing: &mut Listing,
+    dir: &Path,
+    relative: &Path,
+    children: fs::ReadDir,
+    depth: usize,
+    max_depth: usize,
+) {
+    let mut entries: Vec<(String, bool)> = children
+        .filter_map(|entry| entry.ok())
+        .map(|entry| {
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            (entry.file_name().to_string_lossy().into_owned(), is_dir)
+        })
+        .filter(|(name, _)| {
+            !listing.ignore.is_match(name) && !listing.ignore.is_match(relative.join(name))
+        })
+        .collect();
+    // Directories first, then files, each alphabetically
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let indent = "  ".repeat(depth);
+    for (name, is_dir) in entries {
+        if listing.entries >= listing.max_entries {
+            listing.truncated = true;
+            return;
+        }
+        listing.entries += 1;
+
+        let path = dir.join(&name);
+        if is_dir {
+            if depth >= max_depth {
+                listing.output.push_str(&format!("{}- {}/\n", indent, name));
+                continue;
+            }
+            match fs::read_dir(&path) {
+                Ok(children) => {
+                    listing.output.push_str(&format!("{}- {}/\n", indent, name));
+                    list_entries(listing, &path, &relative.join(&name), children, depth + 1, max_depth);
+                }
+                Err(e) => {
+                    listing
+                        .output
+                        .push_str(&format!("{}- {}/ ({})\n", indent, name, describe_error(&e)));
+                }
+            }
+        } else {
+            let size = match fs::metadata(&path) {
+                Ok(metadata) => format_size(metadata.len()),
+                Err(e) => describe_error(&e),
+            };
+            listing.output.push_str(&format!("{}- {} ({})\n", indent, name, size));
+        }
+    }
+}
+
+/// Short note for an entry that couldn't be read
+fn describe_error(error: &std::io::Error) -> String {
+    match error.kind() {
+        std::io::ErrorKind::PermissionDenied => "permission denied".to_string(),
+        _ => format!("unreadable: {}", error),
+    }
+}
+
+/// Human-readable file size
+fn format_size(bytes: u64) -> String {
+    let kb = bytes as f64 / 1024.0;
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if kb < 1024.0 {
+        format!("{:.1} KB", kb)
+    } else {
+        format!("{:.1} MB", kb / 1024.0)
+    }
+}
+
+impl Default for LsTool {
+    fn default() -> Self {
+        Self::with_base_dir(".")
+    }
+}
+
+#[async_trait]
+impl Tool for LsTool {
+    fn name(&self) -> &str {
+        "LS"
+    }
+
+    fn description(&self) -> &str {
+        "List the contents of a directory as a tree."
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        use crate::llm::types::CustomTool;
+
+        ToolDefinition::Custom(CustomTool {
+            name: "LS".to_string(),
+            description: Some(
+                "Lists files and directories in a given path as an indented tree. \
+                Directories end with '/' and files show their size. \
+                Lists two levels by default; use depth to change that. \
+                Prefer this over running ls with the Bash tool. \
+                You can leave out entries with ignore glob patterns."
+                    .to_string(),
+            ),
+            input_schema: ToolInputSchema {
+                schema_type: "object".to_string(),
+                properties: Some(json!({
+                    "path": {
+                        "type": "string",
+                        "description": "The directory to list"
+                    },
+                    "ignore": {
+                        "type": "array",
+                        "items": {"type": "string"},
+                        "description": "Glob patterns of entries to leave out (e.g. \"*.log\", \"node_modules\")"
+                    },
+                    "depth": {
+                        "type": "number",
+                        "description": "How many levels to list (default 2, max 5)"
+                    }
+                })),
+                required: Some(vec!["path".to_string()]),
+            },
+            tool_type: None,
+            cache_control: None,
+        })
+    }
+
+    fn get_info(&self, input: &Value) -> ToolInfo {
+        let path = input
+            .get("path")
+            .and_then(|v| v.as_str())
+            .unwrap_or(".");
+
+        ToolInfo {
+            name: "LS".to_string(),
+            action_description: format!("List directory: {}", path),
+            details: None,
+            read_only: true,
+        }
+    }
+
+    async fn execute(&self, input: &Value, internals: &mut AgentInternals) -> Result<ToolResult> {
+        let mut ls_input: LsInput = serde_json::from_value(input.clone())
+            .map_err(|e| anyhow::anyhow!("Invalid LS input: {}", e))?;
+
+        if let Some(policy) = effective_policy(&self.policy, internals) {
+            match policy.check(self.resolve_path(&ls_input.path)) {
+                Ok(checked) => ls_input.path = checked.to_string_lossy().into_owned(),
+                Err(e) => return Ok(ToolResult::error(e.to_string())),
+            }
+        }
+
+        match self.list(&ls_input) {
+            Ok(output) => Ok(ToolResult::success(output)),
+            Err(e) => Ok(ToolResult::error(format!("{}", e))),
+        }
+    }
+
+    fn requires_permission(&self) -> bool {
+        false // Read-only operation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(path: &str, depth: Option<usize>, ignore: &[&str]) -> LsInput {
+        LsInput {
+            path: path.to_string(),
+            ignore: ignore.iter().map(|s| s.to_string()).collect(),
+            depth,
+        }
+    }
+
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/nested/deep")).unwrap();
+        fs::write(root.join("Cargo.toml"), "x".repeat(2048)).unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("src/nested/mod.rs"), "").unwrap();
+        fs::write(root.join("debug.log"), "").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_tree_listing() {
+        let dir = tree();
+        let tool = LsTool::with_base_dir(dir.path().to_string_lossy());
+
+        let output = tool.list(&input(".", None, &["*.log"])).unwrap();
+        let lines: Vec<&str> = output.lines().skip(1).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "  - src/",
+                "    - nested/",
+                "    - main.rs (12 B)",
+                "  - Cargo.toml (2.0 KB)",
+            ]
+        );
+
+        let output = tool.list(&input("src", None, &[])).unwrap();
+        assert!(output.contains("    - deep/\n    - mod.rs (0 B)\n"));
+
+        let output = tool.list(&input("src", Some(1), &[])).unwrap();
+        assert!(output.contains("  - nested/\n"));
+        assert!(!output.contains("deep"));
+    }
+
+    #[test]
+    fn test_entry_cap_and_root() {
+        let dir = tree();
+        let tool = LsTool::with_base_dir(dir.path().to_string_lossy()).with_max_entries(2);
+        let output = tool.list(&input(".", Some(1), &[])).unwrap();
+        assert_eq!(output.lines().filter(|l| l.starts_with("  - ")).count(), 2);
+        assert!(output.contains("Listing truncated at 2 entries"));
+
+        let tool = LsTool::with_base_dir(dir.path().to_string_lossy()).with_root(dir.path().join("src"));
+        assert!(tool.list(&input("src", None, &[])).is_ok());
+        let err = tool.list(&input("src/..", None, &[])).unwrap_err();
+        assert!(err.to_string().contains("outside the allowed directory"), "{}", err);
+
+        let err = tool.list(&input("Cargo.toml", None, &[])).unwrap_err();
+        assert!(err.to_string().contains("outside the allowed directory"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_permission_denied_subdirectory() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tree();
+        let locked = dir.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::write(locked.join("secret"), "").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let readable = fs::read_dir(&locked).is_ok();
+        let tool = LsTool::with_base_dir(dir.path().to_string_lossy());
+        let output = tool.list(&input(".", None, &[]));
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let output = output.unwrap();
+        if readable {
+            // Running as root: permissions aren't enforced
+            assert!(output.contains("  - locked/\n    - secret"));
+        } else {
+            assert!(output.contains("  - locked/ (permission denied)"), "{}", output);
+            assert!(output.contains("  - src/"));
+        }
+    }
+}
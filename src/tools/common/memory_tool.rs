@@ -0,0 +1,270 @@
+//! Memory tool for long-term notes
+//!
+//! Lets the model keep notes that outlive the session: build commands,
+//! user preferences, facts about the project. Notes are stored in a
+//! `MemoryStore` next to the sessions; the scope decides which sessions
+//! share them (all sessions of the agent type by default).
+//!
+//! Pair it with a `MemoryInjection` of the same scope so the model sees
+//! what it has stored at the start of every turn:
+//!
+//! ```ignore
+//! registry.register(MemoryTool::new());
+//! config = config.with_injection(MemoryInjection::new(MemoryScope::AgentType));
+//! ```
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::super::tool::{Tool, ToolInfo, ToolResult};
+use crate::helpers::{MemoryLimits, MemoryScope, MemoryStore};
+use crate::llm::{ToolDefinition, ToolInputSchema};
+use crate::runtime::AgentInternals;
+
+/// Input parameters for the Memory tool
+#[derive(Debug, Deserialize)]
+struct MemoryInput {
+    operation: MemoryOperation,
+    #[serde(default)]
+    key: Option<String>,
+    #[serde(default)]
+    value: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum MemoryOperation {
+    Get,
+    Set,
+    Append,
+    Delete,
+    List,
+}
+
+/// Tool for reading and writing long-term notes
+pub struct MemoryTool {
+    scope: MemoryScope,
+    limits: MemoryLimits,
+}
+
+impl MemoryTool {
+    /// Create a Memory tool shared by all sessions of the agent type
+    pub fn new() -> Self {
+        Self {
+            scope: MemoryScope::default(),
+            limits: MemoryLimits::default(),
+        }
+    }
+
+    /// Set which sessions share the notes
+    pub fn with_scope(mut self, scope: MemoryScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    /// Set the size caps per key and for the whole store
+    pub fn with_limits(mut self, limits: MemoryLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// The store this tool works on, as seen from the current session
+    pub async fn store(&self, internals: &AgentInternals) -> MemoryStore {
+        let session = internals.session.read().await;
+        MemoryStore::for_scope(
+            session.storage(),
+            self.scope,
+            session.session_id(),
+            session.agent_type(),
+            self.limits,
+        )
+    }
+
+    /// Run one operation (blocking file I/O)
+    fn run(store: &MemoryStore, input: MemoryInput) -> Result<ToolResult> {
+        let key = || {
+            input
+                .key
+                .as_deref()
+                .filter(|k| !k.trim().is_empty())
+                .ok_or_else(|| anyhow::anyhow!("'key' is required for this operation"))
+        };
+        let value = || {
+            input
+                .value
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("'value' is required for this operation"))
+        };
+
+        let result = match input.operation {
+            MemoryOperation::Get => match store.get(key()?)? {
+                Some(value) => ToolResult::success(value),
+                None => ToolResult::error(format!("No memory stored under '{}'", key()?)),
+            },
+            MemoryOperation::Set | MemoryOperation::Append => {
+                let (key, value) = (key()?, value()?);
+                let written = if matches!(input.operation, MemoryOperation::Set) {
+                    store.set(key, value)
+                } else {
+                    store.append(key, value)
+                };
+                match written {
+                    Ok(evicted) if evicted.is_empty() => ToolResult::success(format!("Saved '{}'", key)),
+                    Ok(evicted) => ToolResult::success(format!(
+                        "Saved '{}'. Evicted the oldest notes to make room: {}",
+                        key,
+                        evicted.join(", ")
+                    )),
+                    Err(crate::core::FrameworkError::LimitExceeded(reason)) => ToolResult::error(reason),
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            MemoryOperation::Delete => {
+                if store.delete(key()?)? {
+                    ToolResult::success(format!("Deleted '{}'", key()?))
+                } else {
+                    ToolResult::error(format!("No memory stored under '{}'", key()?))
+                }
+            }
+            MemoryOperation::List => {
+                let entries = store.entries()?;
+                if entries.is_empty() {
+                    ToolResult::success("No memories stored")
+                } else {
+                    let lines: Vec<String> = entries
+                        .iter()
+                        .map(|e| format!("{} ({} bytes, updated {})", e.key, e.value.len(), e.updated_at.format("%Y-%m-%d")))
+                        .collect();
+                    ToolResult::success(lines.join("\n"))
+                }
+            }
+        };
+        Ok(result)
+    }
+}
+
+impl Default for MemoryTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Tool for MemoryTool {
+    fn name(&self) -> &str {
+        "Memory"
+    }
+
+    fn description(&self) -> &str {
+        "Read and write long-term notes that persist across sessions."
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        use crate::llm::types::CustomTool;
+
+        ToolDefinition::Custom(CustomTool {
+            name: "Memory".to_string(),
+            description: Some(format!(
+                "Keep long-term notes that persist across conversations (shared with {}). \
+                Save facts worth remembering: user preferences, project conventions, commands \
+                that worked. Operations: get (read a key), set (replace a key's value), \
+                append (add a line to a key), delete (remove a key), list (show all keys). \
+                Values are limited to {} bytes per key; when the store is full the least \
+                recently written notes are dropped.",
+                self.scope.label(),
+                self.limits.max_value_bytes
+            )),
+            input_schema: ToolInputSchema {
+                schema_type: "object".to_string(),
+                properties: Some(json!({
+                    "operation": {
+                        "type": "string",
+                        "enum": ["get", "set", "append", "delete", "list"],
+                        "description": "What to do"
+                    },
+                    "key": {
+                        "type": "string",
+                        "description": "The note's name (required except for list)"
+                    },
+                    "value": {
+                        "type": "string",
+                        "description": "The text to store (required for set and append)"
+                    }
+                })),
+                required: Some(vec!["operation".to_string()]),
+            },
+            tool_type: None,
+            cache_control: None,
+        })
+    }
+
+    fn get_info(&self, input: &Value) -> ToolInfo {
+        let operation = input.get("operation").and_then(|v| v.as_str()).unwrap_or("list");
+        let key = input.get("key").and_then(|v| v.as_str()).unwrap_or("");
+
+        ToolInfo {
+            name: "Memory".to_string(),
+            action_description: format!("Memory {} {}", operation, key).trim_end().to_string(),
+            details: None,
+            read_only: matches!(operation, "get" | "list"),
+        }
+    }
+
+    async fn execute(&self, input: &Value, internals: &mut AgentInternals) -> Result<ToolResult> {
+        let input: MemoryInput = serde_json::from_value(input.clone())
+            .map_err(|e| anyhow::anyhow!("Invalid memory input: {}", e))?;
+        let store = self.store(internals).await;
+        tokio::task::spawn_blocking(move || Self::run(&store, input)).await?
+    }
+
+    fn requires_permission(&self) -> bool {
+        false // Notes stay inside the session storage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_tool_operations() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = MemoryTool::new();
+        let mut first = AgentInternals::for_test_session(dir.path(), "s1", "coder");
+
+        let result = tool
+            .execute(&json!({"operation": "set", "key": "test", "value": "cargo test"}), &mut first)
+            .await
+            .unwrap();
+        assert!(!result.is_error);
+        tool.execute(&json!({"operation": "append", "key": "test", "value": "-- --nocapture"}), &mut first)
+            .await
+            .unwrap();
+
+        // Another session of the same agent type reads it back
+        let mut second = AgentInternals::for_test_session(dir.path(), "s2", "coder");
+        let result = tool.execute(&json!({"operation": "get", "key": "test"}), &mut second).await.unwrap();
+        assert_eq!(result.text(), "cargo test\n-- --nocapture");
+        let result = tool.execute(&json!({"operation": "list"}), &mut second).await.unwrap();
+        assert!(result.text().starts_with("test (25 bytes"));
+
+        let result = tool.execute(&json!({"operation": "delete", "key": "test"}), &mut second).await.unwrap();
+        assert!(!result.is_error);
+        let result = tool.execute(&json!({"operation": "get", "key": "test"}), &mut first).await.unwrap();
+        assert!(result.is_error);
+
+        // Missing arguments and oversized values are reported to the model
+        assert!(tool.execute(&json!({"operation": "set", "key": "x"}), &mut first).await.is_err());
+        let small = MemoryTool::new().with_limits(MemoryLimits {
+            max_value_bytes: 4,
+            max_total_bytes: 100,
+        });
+        let result = small
+            .execute(&json!({"operation": "set", "key": "x", "value": "too long"}), &mut first)
+            .await
+            .unwrap();
+        assert!(result.is_error && result.text().contains("byte limit"));
+    }
+}
@@ -6,27 +6,53 @@
 //! - `ReadTool` - Read file contents
 //! - `WriteTool` - Write files
 //! - `EditTool` - Edit files with string replacement
+//! - `UndoTool` - Undo a turn's file changes
 //! - `GlobTool` - Find files by pattern
 //! - `GrepTool` - Search file contents
+//! - `LsTool` - List directories
+//! - `HttpRequestTool` - Call HTTP APIs on allow-listed hosts
 //! - `TodoWriteTool` - Manage todo lists
+//! - `MemoryTool` - Keep long-term notes across sessions
+//! - `NotebookReadTool` / `NotebookEditTool` - Read and edit Jupyter notebooks
 //! - `PresentFileTool` - Present files to the user
+//! - `SendToAgentTool` - Message another running agent
+//! - `PathPolicy` - Confine the file tools to a set of directories
+//! - `WebFetchTool` - Fetch web pages (plain HTTP, raw, or Firecrawl backends)
 
 pub mod ask_user_question;
 pub mod bash;
+mod edit_matching;
 pub mod edit_tool;
+mod file_changes;
 pub mod glob_tool;
 pub mod grep_tool;
+pub mod http_request;
+pub mod ls_tool;
+pub mod memory_tool;
+pub mod notebook;
+pub mod path_policy;
 pub mod present_file;
 pub mod read_tool;
+pub mod send_to_agent;
 pub mod todo;
+pub mod undo_tool;
+pub mod web_fetch;
 pub mod write_tool;
 
 pub use ask_user_question::AskUserQuestionTool;
 pub use bash::BashTool;
-pub use edit_tool::EditTool;
+pub use edit_tool::{EditMatching, EditTool};
 pub use glob_tool::GlobTool;
-pub use grep_tool::GrepTool;
+pub use grep_tool::{GrepBackend, GrepTool};
+pub use http_request::HttpRequestTool;
+pub use ls_tool::LsTool;
+pub use memory_tool::MemoryTool;
+pub use notebook::{NotebookEditTool, NotebookReadTool};
+pub use path_policy::{register_file_tools, PathPolicy};
 pub use present_file::PresentFileTool;
 pub use read_tool::ReadTool;
+pub use send_to_agent::SendToAgentTool;
 pub use todo::TodoWriteTool;
+pub use undo_tool::UndoTool;
+pub use web_fetch::WebFetchTool;
 pub use write_tool::WriteTool;
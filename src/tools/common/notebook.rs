@@ -0,0 +1,881 @@
+//! Notebook tools for Jupyter notebooks
+//!
+//! Editing an `.ipynb` file with Edit means matching escaped JSON, which
+//! models get wrong. `NotebookReadTool` renders a notebook as text: every
+//! cell with its index, type and source, and outputs truncated (images are
+//! summarized, never dumped as base64). `NotebookEditTool` changes one
+//! cell per call - replace its source, insert a cell, delete one or change
+//! its type - and writes the notebook back in nbformat's layout. Metadata,
+//! execution counts, outputs and fields this module doesn't know about are
+//! kept as they were.
+//!
+//! Like EditTool, each edit keeps a copy of the notebook in the session's
+//! `backups/` directory and is recorded in the agent's `FileChangeJournal`,
+//! so UndoTool can reverse it.
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::file_changes::{record_change, save_backup};
+use super::path_policy::{effective_policy, PathPolicy};
+use super::super::tool::{Tool, ToolInfo, ToolResult};
+use super::super::ToolInput;
+use crate::helpers::{FileTracker, WriteCheck};
+use crate::llm::ToolDefinition;
+use crate::runtime::AgentInternals;
+
+/// Maximum characters of output shown per cell
+const MAX_OUTPUT_CHARS: usize = 2000;
+
+/// A parsed notebook and the layout it was written in
+struct Notebook {
+    json: Value,
+    /// Spaces per indentation level (None for a single-line file)
+    indent: Option<usize>,
+    trailing_newline: bool,
+}
+
+impl Notebook {
+    fn parse(content: &str) -> Result<Self> {
+        let json: Value = serde_json::from_str(content).context("Not a valid notebook (invalid JSON)")?;
+        if !json.get("cells").is_some_and(Value::is_array) {
+            bail!("Not a Jupyter notebook: it has no \"cells\" list");
+        }
+        let indent = content
+            .lines()
+            .nth(1)
+            .map(|line| line.len() - line.trim_start_matches(' ').len())
+            .map(|spaces| spaces.max(1));
+        Ok(Self {
+            json,
+            indent,
+            trailing_newline: content.ends_with('\n'),
+        })
+    }
+
+    fn cells(&self) -> &[Value] {
+        self.json["cells"].as_array().map(Vec::as_slice).unwrap_or_default()
+    }
+
+    fn cells_mut(&mut self) -> &mut Vec<Value> {
+        self.json["cells"].as_array_mut().expect("checked in parse")
+    }
+
+    /// The kernel's language, if the metadata names one
+    fn language(&self) -> Option<&str> {
+        let metadata = &self.json["metadata"];
+        metadata["kernelspec"]["language"]
+            .as_str()
+            .or_else(|| metadata["language_info"]["name"].as_str())
+    }
+
+    /// Whether cells need an `id` (nbformat 4.5 and later)
+    fn uses_cell_ids(&self) -> bool {
+        let major = self.json["nbformat"].as_u64().unwrap_or(4);
+        let minor = self.json["nbformat_minor"].as_u64().unwrap_or(0);
+        major > 4 || (major == 4 && minor >= 5) || self.cells().iter().any(|cell| cell.get("id").is_some())
+    }
+
+    /// An 8-character cell ID no other cell has
+    fn new_cell_id(&self) -> String {
+        loop {
+            let id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+            if !self.cells().iter().any(|cell| cell["id"] == id.as_str()) {
+                return id;
+            }
+        }
+    }
+
+    /// The notebook in the layout it was read in
+    fn to_json_string(&self) -> Result<String> {
+        let mut out = match self.indent {
+            Some(indent) => {
+                let indent = " ".repeat(indent);
+                let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+                let mut buf = Vec::new();
+                let mut serializer = serde_json::Serializer::with_formatter(&mut buf, formatter);
+                self.json.serialize(&mut serializer)?;
+                String::from_utf8(buf)?
+            }
+            None => serde_json::to_string(&self.json)?,
+        };
+        if self.trailing_newline {
+            out.push('\n');
+        }
+        Ok(out)
+    }
+}
+
+/// A multiline string field (`source`, `text`), stored as a string or a list of lines
+fn multiline(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+/// Text as nbformat stores it: lines that keep their newlines
+fn to_lines(text: &str) -> Value {
+    Value::Array(text.split_inclusive('\n').map(|line| json!(line)).collect())
+}
+
+fn cell_type(cell: &Value) -> &str {
+    cell["cell_type"].as_str().unwrap_or("unknown")
+}
+
+/// One output of a code cell as text
+fn render_output(output: &Value) -> String {
+    match output["output_type"].as_str() {
+        Some("stream") => multiline(&output["text"]),
+        Some("error") => format!(
+            "{}: {}",
+            output["ename"].as_str().unwrap_or("Error"),
+            output["evalue"].as_str().unwrap_or("")
+        ),
+        Some("execute_result") | Some("display_data") => {
+            let Some(data) = output["data"].as_object() else {
+                return String::new();
+            };
+            let mut parts = Vec::new();
+            if data.keys().any(|mime| mime.starts_with("image/")) {
+                parts.push("[image output omitted]".to_string());
+            }
+            if let Some(text) = data.get("text/plain") {
+                parts.push(multiline(text));
+            } else if parts.is_empty() {
+                if let Some(mime) = data.keys().next() {
+                    parts.push(format!("[{} output omitted]", mime));
+                }
+            }
+            parts.join("\n")
+        }
+        other => format!("[{} output omitted]", other.unwrap_or("unknown")),
+    }
+}
+
+/// A cell as text: a header line, the source and (for code) the outputs
+fn render_cell(index: usize, cell: &Value) -> String {
+    let kind = cell_type(cell);
+    let mut header = format!("--- Cell {} ({}", index, kind);
+    if let Some(count) = cell["execution_count"].as_u64() {
+        header.push_str(&format!(", execution count {}", count));
+    }
+    header.push_str(") ---");
+
+    let mut text = format!("{}\n{}", header, multiline(&cell["source"]));
+    let outputs: Vec<String> = cell["outputs"]
+        .as_array()
+        .map(|outputs| {
+            outputs
+                .iter()
+                .map(|output| render_output(output).trim_end_matches('\n').to_string())
+                .filter(|output| !output.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    if !outputs.is_empty() {
+        let outputs = outputs.join("\n");
+        let total = outputs.chars().count();
+        text.push_str("\nOutput:\n");
+        if total > MAX_OUTPUT_CHARS {
+            text.extend(outputs.chars().take(MAX_OUTPUT_CHARS));
+            text.push_str(&format!("\n... ({} more characters)", total - MAX_OUTPUT_CHARS));
+        } else {
+            text.push_str(&outputs);
+        }
+    }
+    text
+}
+
+/// Check that `index` names a cell
+fn check_index(index: usize, count: usize) -> Result<()> {
+    match count {
+        _ if index < count => Ok(()),
+        0 => bail!(
+            "Cell index {} is out of range: the notebook has no cells. Use operation \"insert\" with cell_index 0 to add one.",
+            index
+        ),
+        1 => bail!("Cell index {} is out of range: the notebook has 1 cell (index 0).", index),
+        _ => bail!(
+            "Cell index {} is out of range: the notebook has {} cells (indices 0-{}).",
+            index,
+            count,
+            count - 1
+        ),
+    }
+}
+
+/// Resolve a path (handle both absolute and relative)
+fn resolve_path(base_dir: &str, path: &str) -> String {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        path.to_string_lossy().to_string()
+    } else {
+        Path::new(base_dir).join(path).to_string_lossy().to_string()
+    }
+}
+
+fn check_extension(path: &str) -> Result<()> {
+    if !path.to_lowercase().ends_with(".ipynb") {
+        bail!("{} is not a Jupyter notebook (.ipynb). Use Read and Edit for other files.", path);
+    }
+    Ok(())
+}
+
+/// Input for the NotebookRead tool
+#[derive(Debug, Deserialize, ToolInput)]
+struct NotebookReadInput {
+    /// The absolute path to the notebook (.ipynb)
+    notebook_path: String,
+    /// Read only this cell (0-based index)
+    cell_index: Option<usize>,
+}
+
+/// Tool that renders a Jupyter notebook as text
+pub struct NotebookReadTool {
+    /// Base directory for file operations
+    base_dir: String,
+    /// Paths this tool may touch (falls back to the agent's `PathPolicy` resource)
+    policy: Option<Arc<PathPolicy>>,
+}
+
+impl NotebookReadTool {
+    /// Create a new NotebookRead tool with the current directory as base
+    pub fn new() -> Result<Self> {
+        let base_dir = std::env::current_dir()?.to_string_lossy().to_string();
+        Ok(Self::with_base_dir(base_dir))
+    }
+
+    /// Create a new NotebookRead tool with a specific base directory
+    pub fn with_base_dir(base_dir: impl Into<String>) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+            policy: None,
+        }
+    }
+
+    /// Confine this tool to the paths `policy` allows
+    pub fn with_policy(mut self, policy: PathPolicy) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Render the notebook at `resolved_path`, named `notebook_path` in the output
+    fn read(&self, resolved_path: &str, notebook_path: &str, cell_index: Option<usize>) -> Result<String> {
+        check_extension(notebook_path)?;
+        let content = fs::read_to_string(resolved_path)
+            .with_context(|| format!("Failed to read notebook: {}", resolved_path))?;
+        let notebook = Notebook::parse(&content)?;
+        let cells = notebook.cells();
+
+        if let Some(index) = cell_index {
+            check_index(index, cells.len())?;
+            return Ok(render_cell(index, &cells[index]));
+        }
+
+        let mut text = format!(
+            "{}: {} cell{}",
+            notebook_path,
+            cells.len(),
+            if cells.len() == 1 { "" } else { "s" }
+        );
+        if let Some(language) = notebook.language() {
+            text.push_str(&format!(" ({})", language));
+        }
+        for (index, cell) in cells.iter().enumerate() {
+            text.push_str("\n\n");
+            text.push_str(&render_cell(index, cell));
+        }
+        Ok(text)
+    }
+}
+
+impl Default for NotebookReadTool {
+    fn default() -> Self {
+        Self::with_base_dir(".")
+    }
+}
+
+#[async_trait]
+impl Tool for NotebookReadTool {
+    fn name(&self) -> &str {
+        "NotebookRead"
+    }
+
+    fn description(&self) -> &str {
+        "Read a Jupyter notebook's cells and outputs."
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        use crate::llm::types::CustomTool;
+
+        ToolDefinition::Custom(CustomTool {
+            name: "NotebookRead".to_string(),
+            description: Some(
+                "Reads a Jupyter notebook (.ipynb) and shows each cell with its index, type, source and \
+                outputs. Long outputs are truncated and images are not included. \
+                Use the cell indices with NotebookEdit."
+                    .to_string(),
+            ),
+            input_schema: NotebookReadInput::schema(),
+            tool_type: None,
+            cache_control: None,
+        })
+    }
+
+    fn get_info(&self, input: &Value) -> ToolInfo {
+        let notebook_path = input.get("notebook_path").and_then(|v| v.as_str()).unwrap_or("?");
+
+        ToolInfo {
+            name: "NotebookRead".to_string(),
+            action_description: format!("Read notebook: {}", notebook_path),
+            details: None,
+            read_only: true,
+        }
+    }
+
+    async fn execute(&self, input: &Value, internals: &mut AgentInternals) -> Result<ToolResult> {
+        let read_input = NotebookReadInput::from_value(input.clone())
+            .map_err(|e| anyhow::anyhow!("Invalid notebook read input: {}", e))?;
+
+        let mut resolved_path = resolve_path(&self.base_dir, &read_input.notebook_path);
+        if let Some(policy) = effective_policy(&self.policy, internals) {
+            match policy.check(&resolved_path) {
+                Ok(checked) => resolved_path = checked.to_string_lossy().into_owned(),
+                Err(e) => return Ok(ToolResult::error(e.to_string())),
+            }
+        }
+
+        match self.read(&resolved_path, &read_input.notebook_path, read_input.cell_index) {
+            Ok(text) => {
+                if let Some(tracker) = internals.context.get_resource::<FileTracker>() {
+                    tracker.record_read(&resolved_path);
+                }
+                Ok(ToolResult::success(text))
+            }
+            Err(e) => Ok(ToolResult::error(format!("{}", e))),
+        }
+    }
+
+    fn requires_permission(&self) -> bool {
+        false // Read-only operation
+    }
+}
+
+/// What NotebookEdit does to the cell
+#[derive(Debug, Clone, Copy, Deserialize, ToolInput)]
+#[serde(rename_all = "snake_case")]
+enum EditOperation {
+    Replace,
+    Insert,
+    Delete,
+    ChangeType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ToolInput)]
+#[serde(rename_all = "snake_case")]
+enum CellType {
+    Code,
+    Markdown,
+    Raw,
+}
+
+impl CellType {
+    fn as_str(self) -> &'static str {
+        match self {
+            CellType::Code => "code",
+            CellType::Markdown => "markdown",
+            CellType::Raw => "raw",
+        }
+    }
+}
+
+/// Input for the NotebookEdit tool
+#[derive(Debug, Deserialize, ToolInput)]
+struct NotebookEditInput {
+    /// The absolute path to the notebook (.ipynb)
+    notebook_path: String,
+    /// replace: set a cell's source; insert: add a cell; delete: remove a cell; change_type: convert a cell
+    operation: EditOperation,
+    /// The cell (0-based). For insert, the new cell's index: 0 puts it first, the cell count appends it
+    cell_index: usize,
+    /// The cell's new source (replace and insert)
+    source: Option<String>,
+    /// The cell type (insert, default code; change_type)
+    cell_type: Option<CellType>,
+}
+
+impl NotebookEditInput {
+    /// The description of the change, for the permission prompt
+    fn describe(&self) -> String {
+        match self.operation {
+            EditOperation::Replace => format!("replace the source of cell {}", self.cell_index),
+            EditOperation::Insert => format!(
+                "insert a {} cell at index {}",
+                self.cell_type.unwrap_or(CellType::Code).as_str(),
+                self.cell_index
+            ),
+            EditOperation::Delete => format!("delete cell {}", self.cell_index),
+            EditOperation::ChangeType => format!(
+                "change cell {} to {}",
+                self.cell_index,
+                self.cell_type.map(CellType::as_str).unwrap_or("?")
+            ),
+        }
+    }
+}
+
+/// Apply an edit, returning what was done
+fn apply_edit(notebook: &mut Notebook, input: &NotebookEditInput, path: &str) -> Result<String> {
+    let count = notebook.cells().len();
+    let index = input.cell_index;
+    let source = || {
+        input
+            .source
+            .as_deref()
+            .with_context(|| format!("Operation \"{}\" needs \"source\"", operation_name(input.operation)))
+    };
+
+    match input.operation {
+        EditOperation::Replace => {
+            check_index(index, count)?;
+            let source = to_lines(source()?);
+            let cell = &mut notebook.cells_mut()[index];
+            cell["source"] = source;
+            Ok(format!("Replaced the source of cell {} ({}) in {}", index, cell_type(cell), path))
+        }
+        EditOperation::Insert => {
+            if index > count {
+                bail!(
+                    "Cell index {} is out of range for insert: use 0-{} ({} appends the cell).",
+                    index,
+                    count,
+                    count
+                );
+            }
+            let kind = input.cell_type.unwrap_or(CellType::Code);
+            let mut cell = json!({
+                "cell_type": kind.as_str(),
+                "metadata": {},
+                "source": to_lines(source()?),
+            });
+            if kind == CellType::Code {
+                cell["execution_count"] = Value::Null;
+                cell["outputs"] = json!([]);
+            }
+            if notebook.uses_cell_ids() {
+                cell["id"] = json!(notebook.new_cell_id());
+            }
+            notebook.cells_mut().insert(index, cell);
+            Ok(format!(
+                "Inserted a {} cell at index {} in {} ({} cells now)",
+                kind.as_str(),
+                index,
+                path,
+                count + 1
+            ))
+        }
+        EditOperation::Delete => {
+            check_index(index, count)?;
+            let cell = notebook.cells_mut().remove(index);
+            Ok(format!(
+                "Deleted cell {} ({}) from {} ({} cells left)",
+                index,
+                cell_type(&cell),
+                path,
+                count - 1
+            ))
+        }
+        EditOperation::ChangeType => {
+            check_index(index, count)?;
+            let kind = input
+                .cell_type
+                .context("Operation \"change_type\" needs \"cell_type\"")?;
+            let cell = &mut notebook.cells_mut()[index];
+            let previous = cell_type(cell).to_string();
+            if previous == kind.as_str() {
+                bail!("Cell {} is already a {} cell", index, previous);
+            }
+            let Some(fields) = cell.as_object_mut() else {
+                bail!("Cell {} is not a JSON object", index);
+            };
+            fields.insert("cell_type".to_string(), json!(kind.as_str()));
+            if kind == CellType::Code {
+                // Code cells can't have attachments, and must have outputs
+                fields.remove("attachments");
+                fields.insert("execution_count".to_string(), Value::Null);
+                fields.insert("outputs".to_string(), json!([]));
+            } else {
+                fields.remove("execution_count");
+                fields.remove("outputs");
+            }
+            Ok(format!("Changed cell {} from {} to {} in {}", index, previous, kind.as_str(), path))
+        }
+    }
+}
+
+fn operation_name(operation: EditOperation) -> &'static str {
+    match operation {
+        EditOperation::Replace => "replace",
+        EditOperation::Insert => "insert",
+        EditOperation::Delete => "delete",
+        EditOperation::ChangeType => "change_type",
+    }
+}
+
+/// Tool that edits the cells of a Jupyter notebook
+pub struct NotebookEditTool {
+    /// Base directory for file operations
+    base_dir: String,
+    /// Paths this tool may touch (falls back to the agent's `PathPolicy` resource)
+    policy: Option<Arc<PathPolicy>>,
+    /// Keep a copy of each edited notebook in the session directory
+    backups: bool,
+}
+
+impl NotebookEditTool {
+    /// Create a new NotebookEdit tool with the current directory as base
+    pub fn new() -> Result<Self> {
+        let base_dir = std::env::current_dir()?.to_string_lossy().to_string();
+        Ok(Self::with_base_dir(base_dir))
+    }
+
+    /// Create a new NotebookEdit tool with a specific base directory
+    pub fn with_base_dir(base_dir: impl Into<String>) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+            policy: None,
+            backups: true,
+        }
+    }
+
+    /// Confine this tool to the paths `policy` allows
+    pub fn with_policy(mut self, policy: PathPolicy) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Whether to keep a copy of each edited notebook (default true)
+    pub fn with_backups(mut self, enabled: bool) -> Self {
+        self.backups = enabled;
+        self
+    }
+
+    /// Apply `input` to the notebook at `resolved_path`
+    fn edit(&self, resolved_path: &str, input: &NotebookEditInput) -> Result<String> {
+        check_extension(&input.notebook_path)?;
+        let content = fs::read_to_string(resolved_path)
+            .with_context(|| format!("Failed to read notebook: {}", resolved_path))?;
+        let mut notebook = Notebook::parse(&content)?;
+        let output = apply_edit(&mut notebook, input, &input.notebook_path)?;
+        fs::write(resolved_path, notebook.to_json_string()?)
+            .with_context(|| format!("Failed to write notebook: {}", resolved_path))?;
+        Ok(output)
+    }
+}
+
+impl Default for NotebookEditTool {
+    fn default() -> Self {
+        Self::with_base_dir(".")
+    }
+}
+
+#[async_trait]
+impl Tool for NotebookEditTool {
+    fn name(&self) -> &str {
+        "NotebookEdit"
+    }
+
+    fn description(&self) -> &str {
+        "Edit the cells of a Jupyter notebook."
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        use crate::llm::types::CustomTool;
+
+        ToolDefinition::Custom(CustomTool {
+            name: "NotebookEdit".to_string(),
+            description: Some(
+                "Edits one cell of a Jupyter notebook (.ipynb): replace its source, insert a new cell, \
+                delete it, or change its type. Cells are addressed by the 0-based index NotebookRead shows. \
+                Use this instead of Edit or Write for notebooks; the rest of the notebook is kept as it is."
+                    .to_string(),
+            ),
+            input_schema: NotebookEditInput::schema(),
+            tool_type: None,
+            cache_control: None,
+        })
+    }
+
+    fn get_info(&self, input: &Value) -> ToolInfo {
+        let notebook_path = input.get("notebook_path").and_then(|v| v.as_str()).unwrap_or("?");
+
+        ToolInfo {
+            name: "NotebookEdit".to_string(),
+            action_description: format!("Edit notebook: {}", notebook_path),
+            details: None,
+            read_only: false,
+        }
+    }
+
+    fn describe_effect(&self, input: &Value) -> String {
+        let Ok(input) = NotebookEditInput::from_value(input.clone()) else {
+            return input.to_string();
+        };
+        let mut effect = format!(
+            "In {}, {}",
+            resolve_path(&self.base_dir, &input.notebook_path),
+            input.describe()
+        );
+        if let Some(source) = input.source.as_deref().filter(|_| {
+            matches!(input.operation, EditOperation::Replace | EditOperation::Insert)
+        }) {
+            effect.push_str(":\n");
+            effect.push_str(source);
+        }
+        effect
+    }
+
+    async fn execute(&self, input: &Value, internals: &mut AgentInternals) -> Result<ToolResult> {
+        let edit_input = NotebookEditInput::from_value(input.clone())
+            .map_err(|e| anyhow::anyhow!("Invalid notebook edit input: {}", e))?;
+
+        let mut resolved_path = resolve_path(&self.base_dir, &edit_input.notebook_path);
+        if let Some(policy) = effective_policy(&self.policy, internals) {
+            match policy.check(&resolved_path) {
+                Ok(checked) => resolved_path = checked.to_string_lossy().into_owned(),
+                Err(e) => return Ok(ToolResult::error(e.to_string())),
+            }
+        }
+        let tracker = internals.context.get_resource::<FileTracker>();
+        let warning = match tracker.as_ref().map(|t| t.check_write(&resolved_path)) {
+            Some(WriteCheck::Denied(reason)) => return Ok(ToolResult::error(reason)),
+            Some(WriteCheck::Warning(warning)) => Some(warning),
+            _ => None,
+        };
+
+        let previous = fs::read(&resolved_path).ok();
+        match self.edit(&resolved_path, &edit_input) {
+            Ok(output) => {
+                if let Some(tracker) = &tracker {
+                    tracker.record_read(&resolved_path);
+                }
+                let path = Path::new(&resolved_path);
+                let backup = match &previous {
+                    Some(previous) if self.backups => save_backup(internals, path, previous).await,
+                    _ => None,
+                };
+                record_change(internals, path, previous.as_deref(), backup);
+                match warning {
+                    Some(warning) => Ok(ToolResult::success(format!("{}\n\nWarning: {}", output, warning))),
+                    None => Ok(ToolResult::success(output)),
+                }
+            }
+            Err(e) => Ok(ToolResult::error(format!("{}", e))),
+        }
+    }
+
+    fn requires_permission(&self) -> bool {
+        true // Modifies files
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ToolResultData;
+
+    fn fixture() -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/notebook/analysis.ipynb");
+        fs::read_to_string(path).unwrap()
+    }
+
+    fn internals(sessions: &Path) -> AgentInternals {
+        let mut internals = AgentInternals::for_test(sessions);
+        internals.context.current_tool_use_id = Some("toolu_1".to_string());
+        internals
+    }
+
+    fn text(result: &ToolResult) -> &str {
+        match &result.content[..] {
+            [ToolResultData::Text(text)] => text,
+            other => panic!("expected text, got {:?}", other),
+        }
+    }
+
+    /// Run one edit on a copy of the fixture, returning the result and the cells after it
+    async fn edit(input: Value) -> (ToolResult, Vec<Value>) {
+        let (work, sessions) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        fs::write(work.path().join("analysis.ipynb"), fixture()).unwrap();
+        let tool = NotebookEditTool::with_base_dir(work.path().to_string_lossy());
+        let mut input = input;
+        input["notebook_path"] = json!("analysis.ipynb");
+
+        let result = tool.execute(&input, &mut internals(sessions.path())).await.unwrap();
+        let after: Value = serde_json::from_str(&fs::read_to_string(work.path().join("analysis.ipynb")).unwrap()).unwrap();
+        (result, after["cells"].as_array().unwrap().clone())
+    }
+
+    fn original_cells() -> Vec<Value> {
+        let notebook: Value = serde_json::from_str(&fixture()).unwrap();
+        notebook["cells"].as_array().unwrap().clone()
+    }
+
+    #[test]
+    fn test_read_renders_cells_and_summarizes_outputs() {
+        let tool = NotebookReadTool::with_base_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/notebook").to_string_lossy());
+        let read = |cell_index| tool.read(&resolve_path(&tool.base_dir, "analysis.ipynb"), "analysis.ipynb", cell_index);
+        let text = read(None).unwrap();
+
+        assert!(text.starts_with("analysis.ipynb: 6 cells (python)\n\n--- Cell 0 (markdown) ---\n# Sales analysis\n"), "{}", text);
+        assert!(text.contains(
+            "--- Cell 2 (code, execution count 2) ---\ndf = pd.read_csv(\"sales.csv\")\nprint(f\"Loaded {len(df)} rows\")\ndf.head(1)\nOutput:\nLoaded 1200 rows\n  region  revenue\n0  north     1200"
+        ), "{}", text);
+        assert!(text.contains("Output:\n[image output omitted]\n<Figure size 640x480 with 1 Axes>"), "{}", text);
+        assert!(!text.contains("iVBORw0KGgo"));
+        assert!(text.contains("Output:\nKeyError: 'profit'"), "{}", text);
+        assert!(text.ends_with("--- Cell 5 (raw) ---\nExport notes"), "{}", text);
+
+        assert_eq!(read(Some(5)).unwrap(), "--- Cell 5 (raw) ---\nExport notes");
+        let error = read(Some(6)).unwrap_err();
+        assert_eq!(error.to_string(), "Cell index 6 is out of range: the notebook has 6 cells (indices 0-5).");
+    }
+
+    #[test]
+    fn test_long_outputs_are_truncated() {
+        let cell = json!({
+            "cell_type": "code",
+            "execution_count": null,
+            "outputs": [{"output_type": "stream", "name": "stdout", "text": "x".repeat(MAX_OUTPUT_CHARS + 10)}],
+            "source": "print('x' * 2010)"
+        });
+        let text = render_cell(0, &cell);
+        assert!(text.ends_with("x\n... (10 more characters)"), "{}", &text[text.len() - 40..]);
+    }
+
+    #[tokio::test]
+    async fn test_untouched_notebook_round_trips_exactly() {
+        let notebook = Notebook::parse(&fixture()).unwrap();
+        assert_eq!(notebook.to_json_string().unwrap(), fixture());
+
+        // Replacing a source with itself writes the same bytes
+        let work = tempfile::tempdir().unwrap();
+        let sessions = tempfile::tempdir().unwrap();
+        fs::write(work.path().join("analysis.ipynb"), fixture()).unwrap();
+        let tool = NotebookEditTool::with_base_dir(work.path().to_string_lossy());
+        let input = json!({
+            "notebook_path": "analysis.ipynb",
+            "operation": "replace",
+            "cell_index": 3,
+            "source": "df.plot(x=\"month\", y=\"revenue\")"
+        });
+        let result = tool.execute(&input, &mut internals(sessions.path())).await.unwrap();
+        assert!(!result.is_error, "{}", text(&result));
+        assert_eq!(fs::read_to_string(work.path().join("analysis.ipynb")).unwrap(), fixture());
+    }
+
+    #[tokio::test]
+    async fn test_replace_keeps_everything_but_the_source() {
+        let (result, cells) = edit(json!({
+            "operation": "replace",
+            "cell_index": 2,
+            "source": "df = pd.read_csv(\"sales.csv\")\ndf.head(1)"
+        }))
+        .await;
+        assert_eq!(text(&result), "Replaced the source of cell 2 (code) in analysis.ipynb");
+
+        let mut expected = original_cells();
+        expected[2]["source"] = json!(["df = pd.read_csv(\"sales.csv\")\n", "df.head(1)"]);
+        // Metadata, execution count and outputs are untouched, as are the other cells
+        assert_eq!(cells, expected);
+    }
+
+    #[tokio::test]
+    async fn test_insert_cells() {
+        let (result, cells) = edit(json!({
+            "operation": "insert",
+            "cell_index": 1,
+            "cell_type": "markdown",
+            "source": "## Setup"
+        }))
+        .await;
+        assert_eq!(text(&result), "Inserted a markdown cell at index 1 in analysis.ipynb (7 cells now)");
+        assert_eq!(cells.len(), 7);
+        assert_eq!(cells[1]["cell_type"], "markdown");
+        assert_eq!(cells[1]["source"], json!(["## Setup"]));
+        assert_eq!(cells[1]["id"].as_str().unwrap().len(), 8);
+        assert!(cells[1].get("outputs").is_none());
+        assert_eq!(&cells[2..], &original_cells()[1..]);
+
+        // Appending a code cell
+        let (_, cells) = edit(json!({"operation": "insert", "cell_index": 6, "source": "df.describe()"})).await;
+        assert_eq!(cells[6]["cell_type"], "code");
+        assert_eq!(cells[6]["execution_count"], Value::Null);
+        assert_eq!(cells[6]["outputs"], json!([]));
+
+        let (result, cells) = edit(json!({"operation": "insert", "cell_index": 9, "source": "x"})).await;
+        assert!(result.is_error);
+        assert_eq!(text(&result), "Cell index 9 is out of range for insert: use 0-6 (6 appends the cell).");
+        assert_eq!(cells, original_cells());
+
+        let (result, _) = edit(json!({"operation": "insert", "cell_index": 0})).await;
+        assert_eq!(text(&result), "Operation \"insert\" needs \"source\"");
+    }
+
+    #[tokio::test]
+    async fn test_delete_cell() {
+        let (result, cells) = edit(json!({"operation": "delete", "cell_index": 4})).await;
+        assert_eq!(text(&result), "Deleted cell 4 (code) from analysis.ipynb (5 cells left)");
+        let mut expected = original_cells();
+        expected.remove(4);
+        assert_eq!(cells, expected);
+
+        let (result, cells) = edit(json!({"operation": "delete", "cell_index": 6})).await;
+        assert!(result.is_error);
+        assert_eq!(text(&result), "Cell index 6 is out of range: the notebook has 6 cells (indices 0-5).");
+        assert_eq!(cells, original_cells());
+    }
+
+    #[tokio::test]
+    async fn test_change_cell_type() {
+        let (result, cells) = edit(json!({"operation": "change_type", "cell_index": 5, "cell_type": "code"})).await;
+        assert_eq!(text(&result), "Changed cell 5 from raw to code in analysis.ipynb");
+        assert_eq!(
+            cells[5],
+            json!({
+                "cell_type": "code",
+                "execution_count": null,
+                "id": "c1d2e3f4",
+                "metadata": {"format": "text/x-rst"},
+                "outputs": [],
+                "source": ["Export notes"]
+            })
+        );
+
+        // To markdown, the outputs and execution count go
+        let (_, cells) = edit(json!({"operation": "change_type", "cell_index": 2, "cell_type": "markdown"})).await;
+        assert_eq!(cells[2]["cell_type"], "markdown");
+        assert!(cells[2].get("outputs").is_none() && cells[2].get("execution_count").is_none());
+        assert_eq!(cells[2]["metadata"], original_cells()[2]["metadata"]);
+
+        let (result, _) = edit(json!({"operation": "change_type", "cell_index": 0, "cell_type": "markdown"})).await;
+        assert_eq!(text(&result), "Cell 0 is already a markdown cell");
+    }
+
+    #[tokio::test]
+    async fn test_only_notebooks_are_edited() {
+        let (work, sessions) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        fs::write(work.path().join("data.json"), "{\"cells\": []}").unwrap();
+        let tool = NotebookEditTool::with_base_dir(work.path().to_string_lossy());
+        let input = json!({"notebook_path": "data.json", "operation": "delete", "cell_index": 0});
+        let result = tool.execute(&input, &mut internals(sessions.path())).await.unwrap();
+        assert!(result.is_error);
+        assert!(text(&result).starts_with("data.json is not a Jupyter notebook (.ipynb)."), "{}", text(&result));
+    }
+}
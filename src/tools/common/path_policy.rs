@@ -0,0 +1,340 @@
+//! Path policy for the file tools
+//!
+//! Confines Read, Write, Edit, Glob, Grep and LS to a set of directories.
+//! Every path is made absolute and canonicalized (resolving `..` and
+//! symlinks) before it is checked, so `root/../..` and a symlink inside the
+//! root pointing outside it are both caught.
+//!
+//! `check` returns the canonical path; tools open that path rather than the
+//! one they were given, so what was checked is what gets opened.
+//!
+//! A tool uses the policy it was built with (`ReadTool::with_policy`), or
+//! else a `PathPolicy` resource in the agent context, which applies to all
+//! file tools at once:
+//!
+//! ```ignore
+//! let policy = PathPolicy::new()
+//!     .allow_root("/work/project")
+//!     .deny_glob("**/.env");
+//!
+//! // Either build the tools with it...
+//! register_file_tools(&mut registry, "/work/project", &policy);
+//!
+//! // ...or install it for every file tool of one agent
+//! internals.context.insert_resource(policy);
+//! ```
+
+use anyhow::{anyhow, Result};
+use globset::GlobBuilder;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use super::{EditTool, GlobTool, GrepTool, LsTool, ReadTool, WriteTool};
+use crate::runtime::AgentInternals;
+use crate::tools::ToolRegistry;
+
+/// Which paths the file tools may touch
+#[derive(Debug, Clone, Default)]
+pub struct PathPolicy {
+    /// Directories paths must fall under (empty = anywhere)
+    pub allowed_roots: Vec<PathBuf>,
+    /// Glob patterns of paths that are always refused (e.g. `**/.env`)
+    pub denied_globs: Vec<String>,
+    /// Whether paths may go through symlinks inside the allowed roots
+    ///
+    /// Symlinks that lead outside the roots are refused either way.
+    pub follow_symlinks: bool,
+}
+
+impl PathPolicy {
+    /// Create a policy that allows everything and follows symlinks
+    pub fn new() -> Self {
+        Self {
+            follow_symlinks: true,
+            ..Default::default()
+        }
+    }
+
+    /// Allow paths under `root`
+    pub fn allow_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.allowed_roots.push(root.into());
+        self
+    }
+
+    /// Refuse paths matching `glob`
+    pub fn deny_glob(mut self, glob: impl Into<String>) -> Self {
+        self.denied_globs.push(glob.into());
+        self
+    }
+
+    /// Set whether paths may go through symlinks inside the allowed roots
+    pub fn with_follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// Check an absolute path, returning its canonical form
+    ///
+    /// The path doesn't have to exist (Write creates files). The error names
+    /// the rule that was violated.
+    pub fn check(&self, path: impl AsRef<Path>) -> Result<PathBuf> {
+        let absolute = make_absolute(path.as_ref());
+        let lexical = normalize_lexically(&absolute);
+        // Resolved on disk, so `link/..` goes where the OS would take it
+        let canonical = canonicalize_existing(&absolute);
+
+        for pattern in &self.denied_globs {
+            let glob = GlobBuilder::new(pattern)
+                .build()
+                .map_err(|e| anyhow!("Invalid denied glob '{}' in path policy: {}", pattern, e))?
+                .compile_matcher();
+            if glob.is_match(&lexical) || glob.is_match(&canonical) {
+                return Err(anyhow!(
+                    "Path policy violation: {} matches denied pattern '{}'",
+                    lexical.display(),
+                    pattern
+                ));
+            }
+        }
+
+        if !self.allowed_roots.is_empty() {
+            let roots: Vec<PathBuf> = self
+                .allowed_roots
+                .iter()
+                .map(|root| canonicalize_existing(&make_absolute(root)))
+                .collect();
+            if !roots.iter().any(|root| canonical.starts_with(root)) {
+                let escaped_via_link = roots.iter().any(|root| lexical.starts_with(root));
+                let roots: Vec<String> = roots.iter().map(|r| r.display().to_string()).collect();
+                return Err(anyhow!(
+                    "Path policy violation: {} is outside the allowed directories ({}){}",
+                    lexical.display(),
+                    roots.join(", "),
+                    if escaped_via_link {
+                        format!(" - it resolves to {}", canonical.display())
+                    } else {
+                        String::new()
+                    }
+                ));
+            }
+        }
+
+        if !self.follow_symlinks {
+            let lexical_roots: Vec<PathBuf> =
+                self.allowed_roots.iter().map(|r| normalize_lexically(r)).collect();
+            // Every prefix as written, so a link hidden by a later `..` still counts
+            let components: Vec<Component> = absolute.components().collect();
+            for len in (1..=components.len()).rev() {
+                if !matches!(components[len - 1], Component::Normal(_)) {
+                    continue;
+                }
+                let ancestor: PathBuf = components[..len].iter().collect();
+                let ancestor = ancestor.as_path();
+                // Links at or above a root are how the root was named, not an escape
+                if lexical_roots.iter().any(|root| root.starts_with(normalize_lexically(ancestor))) {
+                    break;
+                }
+                let is_link = std::fs::symlink_metadata(ancestor)
+                    .map(|m| m.file_type().is_symlink())
+                    .unwrap_or(false);
+                if is_link {
+                    return Err(anyhow!(
+                        "Path policy violation: {} goes through the symlink {} and symlinks are not followed",
+                        lexical.display(),
+                        ancestor.display()
+                    ));
+                }
+            }
+        }
+
+        Ok(canonical)
+    }
+
+    /// Whether `path` passes the policy
+    pub fn allows(&self, path: impl AsRef<Path>) -> bool {
+        self.check(path).is_ok()
+    }
+}
+
+/// The policy a tool should apply: its own, or the agent's `PathPolicy` resource
+pub(crate) fn effective_policy(
+    own: &Option<Arc<PathPolicy>>,
+    internals: &AgentInternals,
+) -> Option<Arc<PathPolicy>> {
+    own.clone()
+        .or_else(|| internals.context.get_resource::<PathPolicy>())
+}
+
+/// Register Read, Write, Edit, Glob, Grep and LS, all confined by `policy`
+pub fn register_file_tools(registry: &mut ToolRegistry, base_dir: &str, policy: &PathPolicy) {
+    registry.register(ReadTool::with_base_dir(base_dir).with_policy(policy.clone()));
+    registry.register(WriteTool::with_base_dir(base_dir).with_policy(policy.clone()));
+    registry.register(EditTool::with_base_dir(base_dir).with_policy(policy.clone()));
+    registry.register(GlobTool::with_base_dir(base_dir).with_policy(policy.clone()));
+    registry.register(GrepTool::with_base_dir(base_dir).with_policy(policy.clone()));
+    registry.register(LsTool::with_base_dir(base_dir).with_policy(policy.clone()));
+}
+
+/// Make a path absolute against the current directory
+fn make_absolute(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    }
+}
+
+/// Make a path absolute and resolve `.`/`..` without touching the disk
+///
+/// Only for naming paths (messages, globs): `..` after a symlink is
+/// resolved differently on disk, see `canonicalize_existing`.
+fn normalize_lexically(path: &Path) -> PathBuf {
+    push_lexically(PathBuf::new(), make_absolute(path).components())
+}
+
+fn push_lexically<'a>(mut path: PathBuf, components: impl IntoIterator<Item = Component<'a>>) -> PathBuf {
+    for component in components {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                path.pop();
+            }
+            other => path.push(other),
+        }
+    }
+    path
+}
+
+/// How many symlinks `canonicalize_existing` follows by hand before giving up,
+/// like the OS's `SYMLOOP_MAX`
+const MAX_LINK_HOPS: usize = 40;
+
+/// Canonicalize the deepest existing ancestor of `path` and append the rest
+///
+/// The existing part is resolved by the OS (symlinks, then `..`). The first
+/// component past it may still be a dangling symlink, which the OS would
+/// follow to create its target, so that one is resolved by hand; the rest
+/// doesn't exist, so it holds no symlinks and `..` in it is resolved as text.
+fn canonicalize_existing(path: &Path) -> PathBuf {
+    resolve_existing(path, 0)
+}
+
+fn resolve_existing(path: &Path, hops: usize) -> PathBuf {
+    let components: Vec<Component> = path.components().collect();
+    for len in (1..=components.len()).rev() {
+        let existing: PathBuf = components[..len].iter().collect();
+        let Ok(canonical) = std::fs::canonicalize(&existing) else {
+            continue;
+        };
+        if let Some(Component::Normal(name)) = components.get(len) {
+            let next = canonical.join(name);
+            let is_link = std::fs::symlink_metadata(&next).is_ok_and(|m| m.file_type().is_symlink());
+            if is_link && hops < MAX_LINK_HOPS {
+                if let Ok(target) = std::fs::read_link(&next) {
+                    // Relative targets are relative to the link's directory
+                    let mut target = canonical.join(target);
+                    target.extend(&components[len + 1..]);
+                    return resolve_existing(&target, hops + 1);
+                }
+            }
+        }
+        return push_lexically(canonical, components[len..].iter().copied());
+    }
+    normalize_lexically(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn setup() -> (tempfile::TempDir, PathBuf, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("project");
+        let outside = dir.path().join("outside");
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(root.join("src/main.rs"), "").unwrap();
+        fs::write(root.join(".env"), "SECRET=1").unwrap();
+        fs::write(outside.join("id_rsa"), "key").unwrap();
+        (dir, root, outside)
+    }
+
+    #[test]
+    fn test_roots_and_traversal() {
+        let (_dir, root, outside) = setup();
+        let policy = PathPolicy::new().allow_root(&root);
+
+        assert!(policy.allows(root.join("src/main.rs")));
+        // Files that don't exist yet are checked by where they would be
+        assert!(policy.allows(root.join("src/new/file.rs")));
+
+        let err = policy.check(root.join("src/../../outside/id_rsa")).unwrap_err();
+        assert!(err.to_string().contains("outside the allowed directories"), "{}", err);
+        assert!(!policy.allows(&outside));
+        assert!(!policy.allows(root.join("../project-other/file")));
+        assert!(!policy.allows("/etc/passwd"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_escape() {
+        let (_dir, root, outside) = setup();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        std::os::unix::fs::symlink(root.join("src"), root.join("src-link")).unwrap();
+
+        let policy = PathPolicy::new().allow_root(&root);
+        let err = policy.check(root.join("link/id_rsa")).unwrap_err();
+        assert!(err.to_string().contains("it resolves to"), "{}", err);
+        assert!(!policy.allows(root.join("link/new-file")));
+        // A link that stays inside the root is fine...
+        assert!(policy.allows(root.join("src-link/main.rs")));
+
+        // `..` after a link is resolved where the link leads, as the OS does
+        let err = policy.check(root.join("link/../project/src/main.rs"));
+        assert!(err.is_ok(), "{:?}", err);
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        std::os::unix::fs::symlink(outside.join("deep"), root.join("src/nested/up")).unwrap();
+        fs::create_dir_all(outside.join("deep")).unwrap();
+        fs::write(outside.join("secret"), "key").unwrap();
+        let escape = root.join("src/nested/up/../secret");
+        assert!(fs::read_to_string(&escape).is_ok(), "the OS follows the link before `..`");
+        let err = policy.check(&escape).unwrap_err();
+        assert!(err.to_string().contains("it resolves to"), "{}", err);
+
+        // A dangling link is checked by where writing through it would land
+        std::os::unix::fs::symlink(outside.join("new"), root.join("evil")).unwrap();
+        std::os::unix::fs::symlink("../outside/new-dir/file", root.join("evil-relative")).unwrap();
+        std::os::unix::fs::symlink("src/new.rs", root.join("inside")).unwrap();
+        let err = policy.check(root.join("evil")).unwrap_err();
+        assert!(err.to_string().contains("it resolves to"), "{}", err);
+        assert!(!policy.allows(root.join("evil-relative")));
+        assert_eq!(policy.check(root.join("inside")).unwrap(), root.canonicalize().unwrap().join("src/new.rs"));
+        // Links to each other resolve to nowhere in particular, but stay put
+        std::os::unix::fs::symlink("loop-b", root.join("loop-a")).unwrap();
+        std::os::unix::fs::symlink("loop-a", root.join("loop-b")).unwrap();
+        assert!(policy.allows(root.join("loop-a")));
+
+        // ...unless symlinks aren't followed at all
+        let strict = policy.with_follow_symlinks(false);
+        let err = strict.check(root.join("src-link/main.rs")).unwrap_err();
+        assert!(err.to_string().contains("symlinks are not followed"), "{}", err);
+        assert!(strict.allows(root.join("src/main.rs")));
+    }
+
+    #[test]
+    fn test_denied_globs() {
+        let (_dir, root, _outside) = setup();
+        let policy = PathPolicy::new().allow_root(&root).deny_glob("**/.env");
+
+        let err = policy.check(root.join(".env")).unwrap_err();
+        assert!(err.to_string().contains("denied pattern '**/.env'"), "{}", err);
+        assert!(!policy.allows(root.join("src/./../.env")));
+        assert!(policy.allows(root.join(".env.example")));
+
+        let bad = PathPolicy::new().deny_glob("[");
+        assert!(bad.check(root.join("x")).unwrap_err().to_string().contains("Invalid denied glob"));
+    }
+}
@@ -2,23 +2,47 @@
 //!
 //! This tool presents a file to the user with an option to open it.
 //! Use this after creating or modifying a file that the user should access.
+//!
+//! The UI gets an `OutputChunk::FilePresented` with the file's metadata,
+//! and its bytes when it is at most the inline limit. The model only gets a
+//! short confirmation, never the file's contents.
+//!
+//! Relative paths resolve against the tool's base directory, and the
+//! agent's `PathPolicy` applies. Files outside the base directory are
+//! only presented once the user allows it.
 
 use anyhow::Result;
 use async_trait::async_trait;
 use serde::Deserialize;
 use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Arc;
 
+use super::path_policy::{effective_policy, PathPolicy};
 use super::super::tool::{Tool, ToolInfo, ToolResult};
+use crate::core::{OutputChunk, PresentedFile};
+use crate::helpers::media_type_from_path;
 use crate::llm::{ToolDefinition, ToolInputSchema};
 use crate::runtime::AgentInternals;
 
+/// Largest file sent inside `FilePresented` by default (64 KB)
+pub const DEFAULT_INLINE_LIMIT: u64 = 64 * 1024;
+
 /// Present File tool for displaying files to the user
-pub struct PresentFileTool;
+pub struct PresentFileTool {
+    /// Base directory; relative paths resolve against it, and files
+    /// outside it need permission
+    base_dir: String,
+    /// Paths this tool may present (falls back to the agent's `PathPolicy` resource)
+    policy: Option<Arc<PathPolicy>>,
+    /// Files up to this size are sent with their content
+    inline_limit: u64,
+}
 
 /// Input for the present file tool
 #[derive(Debug, Deserialize)]
 struct PresentFileInput {
-    /// Absolute path to the file (required)
+    /// Path to the file, absolute or relative to the base directory (required)
     file_path: String,
     /// Display name for the file (required)
     file_name: String,
@@ -27,9 +51,52 @@ struct PresentFileInput {
 }
 
 impl PresentFileTool {
-    /// Create a new Present File tool
+    /// Create a new Present File tool with the current directory as base
     pub fn new() -> Self {
-        Self
+        let base_dir = std::env::current_dir()
+            .map(|dir| dir.to_string_lossy().to_string())
+            .unwrap_or_else(|_| ".".to_string());
+        Self::with_base_dir(base_dir)
+    }
+
+    /// Create a new Present File tool with a specific base directory
+    pub fn with_base_dir(base_dir: impl Into<String>) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+            policy: None,
+            inline_limit: DEFAULT_INLINE_LIMIT,
+        }
+    }
+
+    /// Confine this tool to the paths `policy` allows
+    pub fn with_policy(mut self, policy: PathPolicy) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Send the content of files up to `bytes` (default `DEFAULT_INLINE_LIMIT`);
+    /// larger files are sent by path only
+    pub fn with_inline_limit(mut self, bytes: u64) -> Self {
+        self.inline_limit = bytes;
+        self
+    }
+
+    /// Resolve a path (handle both absolute and relative)
+    fn resolve_path(&self, path: &str) -> String {
+        let path = Path::new(path);
+        if path.is_absolute() {
+            path.to_string_lossy().to_string()
+        } else {
+            Path::new(&self.base_dir)
+                .join(path)
+                .to_string_lossy()
+                .to_string()
+        }
+    }
+
+    /// Whether `path` is inside the base directory
+    fn in_base_dir(&self, path: &str) -> bool {
+        PathPolicy::new().allow_root(&self.base_dir).allows(path)
     }
 }
 
@@ -64,7 +131,7 @@ impl Tool for PresentFileTool {
                 properties: Some(json!({
                     "file_path": {
                         "type": "string",
-                        "description": "Absolute path to the file"
+                        "description": "Path to the file, absolute or relative to the working directory"
                     },
                     "file_name": {
                         "type": "string",
@@ -92,27 +159,169 @@ impl Tool for PresentFileTool {
             name: "PresentFile".to_string(),
             action_description: format!("Present file: {}", file_name),
             details: None,
+            read_only: false,
         }
     }
 
-    async fn execute(&self, input: &Value, _internals: &mut AgentInternals) -> Result<ToolResult> {
+    async fn execute(&self, input: &Value, internals: &mut AgentInternals) -> Result<ToolResult> {
         let present_input: PresentFileInput = serde_json::from_value(input.clone())
             .map_err(|e| anyhow::anyhow!("Invalid present file input: {}", e))?;
+        let mut path = self.resolve_path(&present_input.file_path);
+        if let Some(policy) = effective_policy(&self.policy, internals) {
+            match policy.check(&path) {
+                Ok(checked) => path = checked.to_string_lossy().into_owned(),
+                Err(e) => return Ok(ToolResult::error(e.to_string())),
+            }
+        }
+        if !self.in_base_dir(&path) {
+            let action = format!("Present {} (outside {})", path, self.base_dir);
+            match internals.request_permission(self.name(), &action, &path).await {
+                Ok(true) => {}
+                Ok(false) => return Ok(ToolResult::error(format!("Permission denied to present {}", path))),
+                Err(e) => return Ok(ToolResult::error(format!("Cannot present {}: {}", path, e))),
+            }
+        }
+
+        let size = match tokio::fs::metadata(&path).await {
+            Ok(metadata) if metadata.is_file() => metadata.len(),
+            Ok(_) => return Ok(ToolResult::error(format!("Not a file: {}", path))),
+            Err(e) => return Ok(ToolResult::error(format!("Cannot present {}: {}", path, e))),
+        };
+        let content = if size <= self.inline_limit {
+            match tokio::fs::read(&path).await {
+                Ok(content) => Some(content),
+                Err(e) => return Ok(ToolResult::error(format!("Cannot read {}: {}", path, e))),
+            }
+        } else {
+            None
+        };
+        let media_type = media_type_from_path(&path).unwrap_or("application/octet-stream");
 
-        let message = format!(
-            "File ready: {} at {}",
-            present_input.file_name, present_input.file_path
-        );
+        tracing::info!("Presenting file: {} ({}, {} bytes)", path, media_type, size);
+        internals.send(OutputChunk::FilePresented(Box::new(PresentedFile {
+            tool_use_id: internals.context.current_tool_use_id.clone().unwrap_or_default(),
+            path: path.clone(),
+            media_type: media_type.to_string(),
+            size,
+            title: present_input.file_name.clone(),
+            description: present_input.description,
+            content,
+        })));
 
-        tracing::info!("{}", message);
-        if let Some(desc) = &present_input.description {
-            tracing::info!("Description: {}", desc);
+        Ok(ToolResult::success(format!(
+            "Presented {} to the user ({}, {} bytes).",
+            present_input.file_name, path, size
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::permissions::{PermissionRule, PermissionScope};
+    use crate::runtime::OutputReceiver;
+
+    fn internals(dir: &Path) -> (AgentInternals, OutputReceiver) {
+        let internals = AgentInternals::for_test(dir);
+        let rx = internals.subscribe_output();
+        (internals, rx)
+    }
+
+    #[tokio::test]
+    async fn test_small_text_file_is_inlined() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.md");
+        std::fs::write(&path, "# Notes\n\nAll done.\n").unwrap();
+        let (mut internals, mut rx) = internals(dir.path());
+        internals.context.current_tool_use_id = Some("toolu_1".to_string());
+        let path = path.to_string_lossy().to_string();
+
+        let input = json!({"file_path": "notes.md", "file_name": "Notes", "description": "Summary of the work"});
+        let result = PresentFileTool::with_base_dir(dir.path().to_string_lossy()).execute(&input, &mut internals).await.unwrap();
+
+        assert!(!result.is_error);
+        assert_eq!(result.text(), format!("Presented Notes to the user ({}, 19 bytes).", path));
+        match rx.try_recv().unwrap() {
+            OutputChunk::FilePresented(file) => assert_eq!(
+                *file,
+                PresentedFile {
+                    tool_use_id: "toolu_1".into(),
+                    path,
+                    media_type: "text/markdown".into(),
+                    size: 19,
+                    title: "Notes".into(),
+                    description: Some("Summary of the work".into()),
+                    content: Some(b"# Notes\n\nAll done.\n".to_vec()),
+                }
+            ),
+            other => panic!("unexpected chunk {:?}", other),
         }
+    }
+
+    #[tokio::test]
+    async fn test_large_binary_is_sent_by_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("model.bin");
+        std::fs::write(&path, vec![0xAB; 4096]).unwrap();
+        let (mut internals, mut rx) = internals(dir.path());
+        let path = path.to_string_lossy().to_string();
+
+        let tool = PresentFileTool::with_base_dir(dir.path().to_string_lossy()).with_inline_limit(1024);
+        let result = tool.execute(&json!({"file_path": path, "file_name": "model.bin"}), &mut internals).await.unwrap();
 
-        Ok(ToolResult::success(message))
+        // The model only gets a confirmation
+        assert!(result.text().len() < 200, "{}", result.text());
+        match rx.try_recv().unwrap() {
+            OutputChunk::FilePresented(file) => {
+                assert_eq!(file.media_type, "application/octet-stream");
+                assert_eq!(file.size, 4096);
+                assert!(file.description.is_none());
+                assert!(file.content.is_none());
+            }
+            other => panic!("unexpected chunk {:?}", other),
+        }
     }
 
-    fn requires_permission(&self) -> bool {
-        false // Read-only presentation doesn't need permission
+    #[tokio::test]
+    async fn test_missing_file_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut internals, mut rx) = internals(dir.path());
+        let missing = dir.path().join("missing.txt").to_string_lossy().to_string();
+
+        let tool = PresentFileTool::with_base_dir(dir.path().to_string_lossy());
+        let result = tool
+            .execute(&json!({"file_path": missing, "file_name": "missing"}), &mut internals)
+            .await
+            .unwrap();
+        assert!(result.is_error);
+        let result = tool
+            .execute(&json!({"file_path": dir.path().to_string_lossy(), "file_name": "dir"}), &mut internals)
+            .await
+            .unwrap();
+        assert!(result.text().starts_with("Not a file"));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_files_outside_the_base_dir_need_permission() {
+        let dir = tempfile::tempdir().unwrap();
+        let work = dir.path().join("work");
+        std::fs::create_dir(&work).unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "key").unwrap();
+        let (mut internals, mut rx) = internals(dir.path());
+        internals.add_permission_rule(PermissionRule::deny_tool("PresentFile"), PermissionScope::Session);
+
+        let tool = PresentFileTool::with_base_dir(work.to_string_lossy());
+        let input = json!({"file_path": "../secret.txt", "file_name": "secret"});
+        let result = tool.execute(&input, &mut internals).await.unwrap();
+        assert!(result.is_error);
+        assert!(result.text().starts_with("Permission denied"), "{}", result.text());
+        assert!(rx.try_recv().is_err());
+
+        // The agent's path policy applies too
+        let tool = PresentFileTool::with_base_dir(work.to_string_lossy()).with_policy(PathPolicy::new().allow_root(&work));
+        let result = tool.execute(&input, &mut internals).await.unwrap();
+        assert!(result.text().contains("outside"), "{}", result.text());
     }
 }
@@ -8,8 +8,11 @@ use serde::Deserialize;
 use serde_json::{json, Value};
 use std::fs;
 use std::path::Path;
+use std::sync::Arc;
 
+use super::path_policy::{effective_policy, PathPolicy};
 use super::super::tool::{Tool, ToolInfo, ToolResult};
+use crate::helpers::{media_type_from_path, FileTracker};
 use crate::llm::{ToolDefinition, ToolInputSchema};
 use crate::runtime::AgentInternals;
 
@@ -26,6 +29,8 @@ const MAX_PDF_SIZE: u64 = 32 * 1024 * 1024;
 pub struct ReadTool {
     /// Base directory for file operations
     base_dir: String,
+    /// Paths this tool may touch (falls back to the agent's `PathPolicy` resource)
+    policy: Option<Arc<PathPolicy>>,
 }
 
 /// Input for the read tool
@@ -46,16 +51,23 @@ impl ReadTool {
             .to_string_lossy()
             .to_string();
 
-        Ok(Self { base_dir })
+        Ok(Self::with_base_dir(base_dir))
     }
 
     /// Create a new Read tool with a specific base directory
     pub fn with_base_dir(base_dir: impl Into<String>) -> Self {
         Self {
             base_dir: base_dir.into(),
+            policy: None,
         }
     }
 
+    /// Confine this tool to the paths `policy` allows
+    pub fn with_policy(mut self, policy: PathPolicy) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
     /// Resolve a path (handle both absolute and relative)
     fn resolve_path(&self, path: &str) -> String {
         let path = Path::new(path);
@@ -156,19 +168,7 @@ impl ReadTool {
             .with_context(|| format!("Failed to read image file: {}", resolved_path))?;
 
         // Determine media type from extension
-        let extension = Path::new(resolved_path)
-            .extension()
-            .and_then(|e| e.to_str())
-            .map(|s| s.to_lowercase())
-            .unwrap_or_default();
-
-        let media_type = match extension.as_str() {
-            "png" => "image/png",
-            "jpg" | "jpeg" => "image/jpeg",
-            "gif" => "image/gif",
-            "webp" => "image/webp",
-            _ => "application/octet-stream",
-        };
+        let media_type = media_type_from_path(resolved_path).unwrap_or("application/octet-stream");
 
         tracing::info!(
             "Read image: {} ({} bytes, type: {})",
@@ -289,15 +289,35 @@ impl Tool for ReadTool {
             name: "Read".to_string(),
             action_description: format!("Read file: {}", file_path),
             details: None,
+            read_only: true,
         }
     }
 
-    async fn execute(&self, input: &Value, _internals: &mut AgentInternals) -> Result<ToolResult> {
+    async fn execute(&self, input: &Value, internals: &mut AgentInternals) -> Result<ToolResult> {
         let read_input: ReadInput = serde_json::from_value(input.clone())
             .map_err(|e| anyhow::anyhow!("Invalid read input: {}", e))?;
 
-        match self.read_file(&read_input.file_path, read_input.offset, read_input.limit) {
-            Ok(result) => Ok(result),
+        let mut file_path = self.resolve_path(&read_input.file_path);
+        let granted = internals
+            .context
+            .get_resource::<FileTracker>()
+            .is_some_and(|tracker| tracker.is_granted(&file_path));
+        if let Some(policy) = effective_policy(&self.policy, internals).filter(|_| !granted) {
+            match policy.check(&file_path) {
+                Ok(checked) => file_path = checked.to_string_lossy().into_owned(),
+                Err(e) => return Ok(ToolResult::error(e.to_string())),
+            }
+        }
+
+        match self.read_file(&file_path, read_input.offset, read_input.limit) {
+            Ok(result) => {
+                if !result.is_error {
+                    if let Some(tracker) = internals.context.get_resource::<FileTracker>() {
+                        tracker.record_read(&file_path);
+                    }
+                }
+                Ok(result)
+            }
             Err(e) => Ok(ToolResult::error(format!("{}", e))),
         }
     }
@@ -0,0 +1,109 @@
+//! SendToAgent tool - message another running agent
+//!
+//! Delivers the model's message to an agent running in the same
+//! `AgentRuntime`, addressed by session ID or by an alias registered with
+//! `AgentRuntime::register_alias`. The recipient sees it as a user message
+//! wrapped in `<agent-message from="...">` tags.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::super::tool::{Tool, ToolInfo, ToolResult};
+use crate::core::InputMessage;
+use crate::llm::{define_tool, ToolDefinition};
+use crate::runtime::{AgentInternals, AgentRuntime};
+
+/// Input for the SendToAgent tool
+#[derive(Debug, Deserialize)]
+struct SendToAgentInput {
+    /// Session ID or alias of the recipient
+    to: String,
+    /// The message text
+    message: String,
+}
+
+/// Tool that sends a message to another running agent
+pub struct SendToAgentTool;
+
+impl SendToAgentTool {
+    /// Create a SendToAgent tool
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for SendToAgentTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Tool for SendToAgentTool {
+    fn name(&self) -> &str {
+        "SendToAgent"
+    }
+
+    fn description(&self) -> &str {
+        "Send a message to another running agent."
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        define_tool(
+            "SendToAgent",
+            "Send a message to another agent that is running alongside you, \
+            addressed by its session ID or alias. The message is queued and \
+            handled by the other agent as its next turn; this tool does not wait \
+            for a reply. Fails if no such agent is running.",
+            json!({
+                "to": {
+                    "type": "string",
+                    "description": "Session ID or alias of the agent to message"
+                },
+                "message": {
+                    "type": "string",
+                    "description": "The message to send"
+                }
+            }),
+            vec!["to".to_string(), "message".to_string()],
+        )
+    }
+
+    fn get_info(&self, input: &Value) -> ToolInfo {
+        let to = input.get("to").and_then(|v| v.as_str()).unwrap_or("?");
+
+        ToolInfo {
+            name: "SendToAgent".to_string(),
+            action_description: format!("Send a message to agent {}", to),
+            details: input.get("message").and_then(|v| v.as_str()).map(str::to_string),
+            read_only: false,
+        }
+    }
+
+    async fn execute(&self, input: &Value, internals: &mut AgentInternals) -> Result<ToolResult> {
+        let input: SendToAgentInput = serde_json::from_value(input.clone())
+            .map_err(|e| anyhow::anyhow!("Invalid SendToAgent input: {}", e))?;
+
+        let Some(runtime) = internals.context.get_resource::<AgentRuntime>() else {
+            return Ok(ToolResult::error("SendToAgent is only available to agents spawned by an AgentRuntime"));
+        };
+        let own_id = internals.session_id().to_string();
+        if runtime.resolve(&input.to).await.as_deref() == Some(own_id.as_str()) {
+            return Ok(ToolResult::error("Cannot send a message to yourself"));
+        }
+
+        let message = InputMessage::agent_message(&own_id, input.message);
+        match runtime.send_to(&input.to, message).await {
+            Ok(()) => {
+                tracing::info!("[SendToAgent] {} -> {}", own_id, input.to);
+                Ok(ToolResult::success(format!("Message delivered to {}", input.to)))
+            }
+            Err(e) => Ok(ToolResult::error(format!(
+                "Could not deliver message to '{}': {}",
+                input.to, e
+            ))),
+        }
+    }
+}
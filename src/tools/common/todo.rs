@@ -5,7 +5,8 @@
 //!
 //! The tool looks for a `TodoListManager` in the agent's ResourceMap.
 //! If found, it updates the manager; if not found, it returns an error
-//! prompting the agent to ensure TodoListManager is configured.
+//! prompting the agent to ensure TodoListManager is configured. Every update
+//! is also saved into the session metadata so it survives a resume.
 //!
 //! Usage:
 //! ```ignore
@@ -54,6 +55,14 @@ impl TodoItemInput {
     }
 }
 
+/// The todo list a TodoWrite call sets, if its input is well-formed
+///
+/// Lets callers rebuild the list from the calls in a history.
+pub fn todos_from_input(input: &Value) -> Option<Vec<TodoItem>> {
+    let input: TodoInput = serde_json::from_value(input.clone()).ok()?;
+    Some(input.todos.into_iter().map(|i| i.into_todo_item()).collect())
+}
+
 /// TodoWrite tool for managing tasks
 ///
 /// This tool reads from and writes to a `TodoListManager` stored in the
@@ -145,6 +154,7 @@ impl Tool for TodoWriteTool {
             name: "TodoWrite".to_string(),
             action_description: format!("Update todo list ({} items)", todo_count),
             details: None,
+            read_only: false,
         }
     }
 
@@ -174,8 +184,11 @@ impl Tool for TodoWriteTool {
         // Get current turn from context
         let current_turn = internals.context.current_turn;
 
-        // Update the manager
+        // Update the manager and persist it so a resumed session keeps the plan
         manager.set_todos(items, current_turn);
+        if let Err(e) = manager.save_to_session(&mut *internals.session.write().await) {
+            tracing::warn!("[TodoWrite] Failed to save todo list to session: {}", e);
+        }
 
         // Return the formatted list
         let output = manager.format();
@@ -185,4 +198,69 @@ impl Tool for TodoWriteTool {
     fn requires_permission(&self) -> bool {
         false // Todo updates don't need permission
     }
+
+    fn runs_in_dry_run(&self) -> bool {
+        true // The todo list is the plan
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::agent::AgentConfig;
+    use crate::cli::print_mode::run_mock_agent_on;
+    use crate::llm::MockLlmProvider;
+    use crate::runtime::{AgentRuntime, SpawnOptions};
+    use crate::session::{AgentSession, SessionStorage};
+    use crate::tools::ToolRegistry;
+
+    async fn run_turn(
+        session: AgentSession,
+        llm: MockLlmProvider,
+        todos: Arc<TodoListManager>,
+    ) {
+        let mut registry = ToolRegistry::new();
+        registry.register(TodoWriteTool::new());
+        let config = AgentConfig::new("test")
+            .with_tools(Arc::new(registry))
+            .with_auto_name(false);
+
+        let runtime = AgentRuntime::new();
+        let options = SpawnOptions::new().with_resource_arc(todos);
+        run_mock_agent_on(&runtime, options, config, Arc::new(llm), session, "go").await;
+        runtime.shutdown_all().await;
+    }
+
+    #[tokio::test]
+    async fn test_todos_survive_session_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = || SessionStorage::with_dir(dir.path());
+
+        let llm = MockLlmProvider::new()
+            .with_response(MockLlmProvider::tool_use_response(
+                "call_1",
+                "TodoWrite",
+                json!({"todos": [
+                    {"content": "Read code", "status": "completed", "activeForm": "Reading code"},
+                    {"content": "Fix bug", "status": "in_progress", "activeForm": "Fixing bug"}
+                ]}),
+            ))
+            .with_text("working on it");
+        let first = Arc::new(TodoListManager::new());
+        let session = AgentSession::new_with_storage("todo-resume", "test", "Test", "", storage())
+            .unwrap();
+        run_turn(session, llm, first.clone()).await;
+        assert_eq!(first.len(), 2);
+
+        // Resume with a fresh manager, as a restarted process would
+        let resumed = Arc::new(TodoListManager::new());
+        let session = AgentSession::load_with_storage("todo-resume", storage()).unwrap();
+        run_turn(session, MockLlmProvider::new().with_text("back"), resumed.clone()).await;
+
+        assert_eq!(resumed.len(), 2);
+        assert_eq!(resumed.current_task().unwrap().active_form, "Fixing bug");
+        assert_eq!(resumed.format(), first.format());
+    }
 }
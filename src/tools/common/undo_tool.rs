@@ -0,0 +1,196 @@
+//! Undo tool for reversing file changes
+//!
+//! Rolls back the changes WriteTool and EditTool made, using the agent's
+//! `FileChangeJournal` resource (which `StandardAgent` sets up). Without a
+//! turn, it undoes the most recent turn that changed files. Files changed
+//! by someone else since are left alone and reported.
+//!
+//! Usage:
+//! ```ignore
+//! registry.register(UndoTool::new());
+//! ```
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::super::tool::{Tool, ToolInfo, ToolResult};
+use crate::helpers::FileChangeJournal;
+use crate::llm::{ToolDefinition, ToolInputSchema};
+use crate::runtime::AgentInternals;
+
+/// Input for the undo tool
+#[derive(Debug, Deserialize)]
+struct UndoInput {
+    /// Undo this turn and every turn after it (default: the latest)
+    #[serde(default)]
+    turn: Option<usize>,
+}
+
+/// Undo tool for rolling back a turn's file changes
+pub struct UndoTool;
+
+impl UndoTool {
+    /// Create a new Undo tool
+    ///
+    /// The tool will look for FileChangeJournal in the agent's resources.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for UndoTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Tool for UndoTool {
+    fn name(&self) -> &str {
+        "Undo"
+    }
+
+    fn description(&self) -> &str {
+        "Undo the file changes made in a turn."
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        use crate::llm::types::CustomTool;
+
+        ToolDefinition::Custom(CustomTool {
+            name: "Undo".to_string(),
+            description: Some(
+                "Restores the files changed by Write and Edit to how they were before a turn. \
+                Undoes that turn and every later one; without a turn, undoes the most recent turn that changed files. \
+                Files modified by someone else since are left unchanged and reported."
+                    .to_string(),
+            ),
+            input_schema: ToolInputSchema {
+                schema_type: "object".to_string(),
+                properties: Some(json!({
+                    "turn": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "The turn to undo, along with every turn after it. Default: the most recent turn that changed files."
+                    }
+                })),
+                required: None,
+            },
+            tool_type: None,
+            cache_control: None,
+        })
+    }
+
+    fn get_info(&self, input: &Value) -> ToolInfo {
+        let action_description = match input.get("turn").and_then(|v| v.as_u64()) {
+            Some(turn) => format!("Undo file changes since turn {}", turn),
+            None => "Undo the last turn's file changes".to_string(),
+        };
+
+        ToolInfo {
+            name: "Undo".to_string(),
+            action_description,
+            details: None,
+            read_only: false,
+        }
+    }
+
+    async fn execute(&self, input: &Value, internals: &mut AgentInternals) -> Result<ToolResult> {
+        let undo_input: UndoInput = serde_json::from_value(input.clone())
+            .map_err(|e| anyhow::anyhow!("Invalid undo input: {}", e))?;
+
+        let journal = match internals.context.get_resource::<FileChangeJournal>() {
+            Some(journal) => journal,
+            None => {
+                return Ok(ToolResult::error(
+                    "FileChangeJournal not found in agent resources. \
+                    Ensure FileChangeJournal is added to context before using UndoTool.",
+                ));
+            }
+        };
+        let Some(turn) = undo_input.turn.or_else(|| journal.last_turn()) else {
+            return Ok(ToolResult::error("No file changes to undo"));
+        };
+
+        match journal.rollback_turn(turn) {
+            Ok(report) => Ok(ToolResult::success(report.describe())),
+            Err(e) => Ok(ToolResult::error(format!("Failed to undo turn {}: {}", turn, e))),
+        }
+    }
+
+    fn requires_permission(&self) -> bool {
+        true // Modifies files
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+    use crate::session::SessionStorage;
+    use crate::tools::common::{EditTool, WriteTool};
+    use crate::tools::ToolResultData;
+
+    fn internals(sessions: &Path) -> AgentInternals {
+        let mut internals = AgentInternals::for_test(sessions);
+        let journal = FileChangeJournal::new(SessionStorage::with_dir(sessions).changes_path("s"));
+        internals.context.insert_resource(journal);
+        internals
+    }
+
+    async fn run(tool: &dyn Tool, internals: &mut AgentInternals, turn: usize, id: &str, input: Value) -> String {
+        internals.context.current_turn = turn;
+        internals.context.current_tool_use_id = Some(id.to_string());
+        let result = tool.execute(&input, internals).await.unwrap();
+        let [ToolResultData::Text(text)] = &result.content[..] else {
+            panic!("expected text");
+        };
+        assert!(!result.is_error, "{}", text);
+        text.clone()
+    }
+
+    #[tokio::test]
+    async fn test_undo_restores_the_last_turn_and_keeps_external_changes() {
+        let (work, sessions) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let (a, b) = (work.path().join("a.txt"), work.path().join("b.txt"));
+        fs::write(&a, "a = 1").unwrap();
+        let base = work.path().to_string_lossy();
+        let (write, edit) = (WriteTool::with_base_dir(base.clone()), EditTool::with_base_dir(base));
+        let mut internals = internals(sessions.path());
+
+        // Turn 1 edits a, turn 2 edits a again and creates b
+        let replace = |old: &str, new: &str| json!({"file_path": "a.txt", "old_string": old, "new_string": new});
+        run(&edit, &mut internals, 1, "toolu_1", replace("a = 1", "a = 2")).await;
+        run(&edit, &mut internals, 2, "toolu_2", replace("a = 2", "a = 3")).await;
+        run(&write, &mut internals, 2, "toolu_3", json!({"file_path": "b.txt", "content": "b"})).await;
+        fs::write(&b, "b, edited by hand").unwrap();
+
+        let report = run(&UndoTool::new(), &mut internals, 3, "toolu_4", json!({})).await;
+        assert_eq!(fs::read_to_string(&a).unwrap(), "a = 2");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "b, edited by hand");
+        let a = fs::canonicalize(&a).unwrap();
+        let b = fs::canonicalize(&b).unwrap();
+        assert_eq!(
+            report,
+            format!(
+                "Restored {}\nSkipped {}: modified since the agent changed it",
+                a.display(),
+                b.display()
+            )
+        );
+
+        // The journal is in the session directory, so a resumed agent can go on
+        let journal = FileChangeJournal::new(SessionStorage::with_dir(sessions.path()).changes_path("s"));
+        assert_eq!(journal.last_turn(), Some(1));
+        let report = journal.rollback_turn(1).unwrap();
+        assert_eq!(report.restored, vec![a.clone()]);
+        assert_eq!(fs::read_to_string(&a).unwrap(), "a = 1");
+
+        let result = UndoTool::new().execute(&json!({}), &mut internals).await.unwrap();
+        assert!(result.is_error);
+    }
+}
@@ -0,0 +1,322 @@
+//! Fetch backends for the WebFetch tool
+//!
+//! - `HttpBackend` - plain HTTP, converts HTML to markdown/text (default)
+//! - `RawBackend` - plain HTTP, returns status, headers and body untouched
+//! - `FirecrawlBackend` - the Firecrawl scrape API (needs an API key)
+
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use html2text::render::TrivialDecorator;
+use reqwest::{header, Client, Url};
+use serde::Deserialize;
+
+/// Maximum number of redirects followed before giving up
+const MAX_REDIRECTS: usize = 10;
+
+/// Width HTML is wrapped to when converted to text
+const TEXT_WIDTH: usize = 100;
+
+/// User agent sent with every request (also matched against robots.txt)
+pub(crate) const USER_AGENT: &str = concat!("shadow-agent-sdk/", env!("CARGO_PKG_VERSION"));
+
+/// Output format for fetched pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FetchFormat {
+    /// HTML converted to markdown-style text (headings, links, lists)
+    #[default]
+    Markdown,
+    /// HTML converted to plain text
+    Text,
+    /// The HTML source
+    Html,
+}
+
+/// What to fetch and how
+#[derive(Debug, Clone)]
+pub struct FetchRequest {
+    /// The URL to fetch
+    pub url: String,
+    /// Requested output format (only applies to HTML pages)
+    pub format: FetchFormat,
+    /// Timeout for each HTTP request
+    pub timeout: Duration,
+    /// Maximum number of body bytes to download
+    pub max_bytes: usize,
+}
+
+/// One hop of a redirect chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    /// URL that answered with a redirect
+    pub from: String,
+    /// URL it redirected to
+    pub to: String,
+    /// HTTP status of the redirect (301, 302, ...)
+    pub status: u16,
+}
+
+impl Redirect {
+    /// Whether this hop moves to a different host
+    pub fn crosses_domain(&self) -> bool {
+        let host = |url: &str| Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_string));
+        host(&self.from) != host(&self.to)
+    }
+}
+
+/// The result of a fetch
+#[derive(Debug, Clone)]
+pub struct FetchResponse {
+    /// URL the content was finally served from
+    pub url: String,
+    /// HTTP status of the final response
+    pub status: u16,
+    /// Content type of the final response
+    pub content_type: Option<String>,
+    /// Redirects followed to reach `url`
+    pub redirects: Vec<Redirect>,
+    /// The extracted content
+    pub content: String,
+    /// Whether the body was cut off at `max_bytes`
+    pub truncated: bool,
+}
+
+/// A way of turning a URL into content for the model
+#[async_trait]
+pub trait FetchBackend: Send + Sync {
+    /// Short backend name, used in logs
+    fn name(&self) -> &str;
+
+    /// Fetch `request.url`
+    async fn fetch(&self, request: &FetchRequest) -> Result<FetchResponse>;
+}
+
+// ============================================================================
+// Shared HTTP fetching
+// ============================================================================
+
+/// A response body and everything needed to render it
+pub(crate) struct HttpFetch {
+    pub url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub content_type: Option<String>,
+    pub redirects: Vec<Redirect>,
+    pub body: Vec<u8>,
+    pub truncated: bool,
+}
+
+/// Build a client that leaves redirects to `fetch_http`
+pub(crate) fn http_client() -> Result<Client> {
+    Ok(Client::builder()
+        .user_agent(USER_AGENT)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?)
+}
+
+/// GET a URL, following redirects by hand so each hop can be reported
+pub(crate) async fn fetch_http(client: &Client, request: &FetchRequest) -> Result<HttpFetch> {
+    let mut url = Url::parse(&request.url).map_err(|e| anyhow!("Invalid URL '{}': {}", request.url, e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(anyhow!("Unsupported URL scheme '{}'", url.scheme()));
+    }
+
+    let mut redirects = Vec::new();
+    let mut response = loop {
+        let response = client
+            .get(url.clone())
+            .timeout(request.timeout)
+            .send()
+            .await
+            .map_err(|e| anyhow!("Request to {} failed: {}", url, e))?;
+
+        if !response.status().is_redirection() {
+            break response;
+        }
+        let Some(location) = response.headers().get(header::LOCATION) else {
+            break response;
+        };
+        if redirects.len() >= MAX_REDIRECTS {
+            return Err(anyhow!("Too many redirects (more than {})", MAX_REDIRECTS));
+        }
+
+        let location = location.to_str().map_err(|_| anyhow!("Invalid redirect location"))?;
+        let next = url
+            .join(location)
+            .map_err(|e| anyhow!("Invalid redirect location '{}': {}", location, e))?;
+        redirects.push(Redirect {
+            from: url.to_string(),
+            to: next.to_string(),
+            status: response.status().as_u16(),
+        });
+        url = next;
+    };
+
+    let status = response.status().as_u16();
+    let headers = response
+        .headers()
+        .iter()
+        .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+        .collect();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    let mut body = Vec::new();
+    let mut truncated = false;
+    while let Some(chunk) = response.chunk().await? {
+        let room = request.max_bytes - body.len();
+        if chunk.len() > room {
+            body.extend_from_slice(&chunk[..room]);
+            truncated = true;
+            break;
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    Ok(HttpFetch {
+        url: url.to_string(),
+        status,
+        headers,
+        content_type,
+        redirects,
+        body,
+        truncated,
+    })
+}
+
+/// Whether a content type is HTML
+fn is_html(content_type: Option<&str>) -> bool {
+    content_type
+        .map(|ct| {
+            let ct = ct.to_ascii_lowercase();
+            ct.contains("text/html") || ct.contains("application/xhtml")
+        })
+        .unwrap_or(false)
+}
+
+/// Decode a body for display, summarizing binary content
+fn body_text(body: &[u8]) -> String {
+    if body.contains(&0) {
+        format!("(binary body, {} bytes)", body.len())
+    } else {
+        String::from_utf8_lossy(body).into_owned()
+    }
+}
+
+/// Render status, headers and body
+fn render_raw(fetch: &HttpFetch) -> String {
+    let mut output = format!("HTTP {}\n", fetch.status);
+    for (name, value) in &fetch.headers {
+        output.push_str(&format!("{}: {}\n", name, value));
+    }
+    output.push('\n');
+    output.push_str(&body_text(&fetch.body));
+    output
+}
+
+/// Convert an HTML page to the requested format
+pub(crate) fn convert_html(html: &[u8], format: FetchFormat) -> Result<String> {
+    let converted = match format {
+        FetchFormat::Html => return Ok(String::from_utf8_lossy(html).into_owned()),
+        FetchFormat::Markdown => html2text::config::plain()
+            .allow_width_overflow()
+            .string_from_read(html, TEXT_WIDTH),
+        FetchFormat::Text => html2text::config::with_decorator(TrivialDecorator::new())
+            .allow_width_overflow()
+            .string_from_read(html, TEXT_WIDTH),
+    };
+    converted.map_err(|e| anyhow!("Failed to convert HTML: {}", e))
+}
+
+// ============================================================================
+// HttpBackend
+// ============================================================================
+
+/// Plain HTTP backend, no API key needed
+///
+/// HTML pages are converted to markdown or text (scripts and styles are
+/// dropped); other content types are returned raw, with their headers.
+pub struct HttpBackend {
+    client: Client,
+}
+
+impl HttpBackend {
+    /// Create an HTTP backend
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            client: http_client()?,
+        })
+    }
+}
+
+#[async_trait]
+impl FetchBackend for HttpBackend {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    async fn fetch(&self, request: &FetchRequest) -> Result<FetchResponse> {
+        let fetch = fetch_http(&self.client, request).await?;
+        let content = if is_html(fetch.content_type.as_deref()) {
+            convert_html(&fetch.body, request.format)?
+        } else {
+            render_raw(&fetch)
+        };
+
+        Ok(FetchResponse {
+            url: fetch.url,
+            status: fetch.status,
+            content_type: fetch.content_type,
+            redirects: fetch.redirects,
+            content,
+            truncated: fetch.truncated,
+        })
+    }
+}
+
+// ============================================================================
+// RawBackend
+// ============================================================================
+
+/// Plain HTTP backend that never converts anything
+///
+/// Returns the status line, headers and body as-is, which is what the model
+/// wants for APIs, feeds and other non-HTML content.
+pub struct RawBackend {
+    client: Client,
+}
+
+impl RawBackend {
+    /// Create a raw HTTP backend
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            client: http_client()?,
+        })
+    }
+}
+
+#[async_trait]
+impl FetchBackend for RawBackend {
+    fn name(&self) -> &str {
+        "raw"
+    }
+
+    async fn fetch(&self, request: &FetchRequest) -> Result<FetchResponse> {
+        let fetch = fetch_http(&self.client, request).await?;
+        let content = render_raw(&fetch);
+
+        Ok(FetchResponse {
+            url: fetch.url,
+            status: fetch.status,
+            content_type: fetch.content_type,
+            redirects: fetch.redirects,
+            content,
+            truncated: fetch.truncated,
+        })
+    }
+}
@@ -0,0 +1,152 @@
+//! Firecrawl fetch backend
+//!
+//! Uses the Firecrawl scrape API, which renders JavaScript-heavy pages and
+//! returns clean markdown. Needs an API key (`FIRECRAWL_API_KEY`).
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::backend::{FetchBackend, FetchFormat, FetchRequest, FetchResponse};
+
+/// Default Firecrawl API endpoint
+const DEFAULT_BASE_URL: &str = "https://api.firecrawl.dev";
+
+/// Environment variable the API key is read from
+const API_KEY_ENV: &str = "FIRECRAWL_API_KEY";
+
+#[derive(Debug, Deserialize)]
+struct ScrapeResponse {
+    success: bool,
+    #[serde(default)]
+    data: Option<ScrapeData>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScrapeData {
+    #[serde(default)]
+    markdown: Option<String>,
+    #[serde(default)]
+    html: Option<String>,
+    #[serde(default)]
+    metadata: Option<ScrapeMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScrapeMetadata {
+    #[serde(default, rename = "sourceURL")]
+    source_url: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    status_code: Option<u16>,
+    #[serde(default)]
+    content_type: Option<String>,
+}
+
+/// Fetch backend backed by the Firecrawl scrape API
+pub struct FirecrawlBackend {
+    client: Client,
+    api_key: String,
+    base_url: String,
+}
+
+impl FirecrawlBackend {
+    /// Create a Firecrawl backend with an API key
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            client: Client::new(),
+            api_key: api_key.into(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+        }
+    }
+
+    /// Create a Firecrawl backend from `FIRECRAWL_API_KEY`
+    pub fn from_env() -> Result<Self> {
+        let api_key = std::env::var(API_KEY_ENV)
+            .map_err(|_| anyhow!("{} environment variable not set", API_KEY_ENV))?;
+        Ok(Self::new(api_key))
+    }
+
+    /// Use a different API endpoint (self-hosted Firecrawl)
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+}
+
+#[async_trait]
+impl FetchBackend for FirecrawlBackend {
+    fn name(&self) -> &str {
+        "firecrawl"
+    }
+
+    async fn fetch(&self, request: &FetchRequest) -> Result<FetchResponse> {
+        let format = match request.format {
+            FetchFormat::Html => "html",
+            FetchFormat::Markdown | FetchFormat::Text => "markdown",
+        };
+        let response = self
+            .client
+            .post(format!("{}/v1/scrape", self.base_url.trim_end_matches('/')))
+            .bearer_auth(&self.api_key)
+            .timeout(request.timeout)
+            .json(&json!({
+                "url": request.url,
+                "formats": [format],
+                "timeout": request.timeout.as_millis() as u64,
+            }))
+            .send()
+            .await
+            .map_err(|e| anyhow!("Firecrawl request failed: {}", e))?;
+
+        let status = response.status();
+        let body: ScrapeResponse = response
+            .json()
+            .await
+            .map_err(|e| anyhow!("Invalid Firecrawl response ({}): {}", status, e))?;
+        if !body.success {
+            return Err(anyhow!(
+                "Firecrawl scrape failed: {}",
+                body.error.unwrap_or_else(|| status.to_string())
+            ));
+        }
+
+        let data = body.data.ok_or_else(|| anyhow!("Firecrawl response has no data"))?;
+        let mut content = match request.format {
+            FetchFormat::Html => data.html,
+            FetchFormat::Markdown | FetchFormat::Text => data.markdown,
+        }
+        .unwrap_or_default();
+
+        let truncated = content.len() > request.max_bytes;
+        if truncated {
+            let mut end = request.max_bytes;
+            while !content.is_char_boundary(end) {
+                end -= 1;
+            }
+            content.truncate(end);
+        }
+
+        let metadata = data.metadata;
+        let url = metadata
+            .as_ref()
+            .and_then(|m| m.url.clone().or_else(|| m.source_url.clone()))
+            .unwrap_or_else(|| request.url.clone());
+
+        Ok(FetchResponse {
+            url,
+            status: metadata.as_ref().and_then(|m| m.status_code).unwrap_or(200),
+            content_type: metadata.and_then(|m| m.content_type),
+            // Firecrawl follows redirects itself and doesn't report the chain
+            redirects: Vec::new(),
+            content,
+            truncated,
+        })
+    }
+}
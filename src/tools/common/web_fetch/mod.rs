@@ -0,0 +1,412 @@
+//! WebFetch tool for reading web pages
+//!
+//! Fetches a URL and returns its content in a model-friendly form. How the
+//! page is fetched is up to a `FetchBackend`:
+//! - `HttpBackend` (default) - plain HTTP with HTML converted to markdown or
+//!   text; no API key needed, works against local servers
+//! - `RawBackend` - status, headers and body as-is
+//! - `FirecrawlBackend` - the Firecrawl API, for JavaScript-rendered pages
+//!
+//! Redirects are followed (up to 10) and reported in the output, with a
+//! warning when one leaves the original host. Downloads stop at `max_bytes`
+//! (10 MB by default).
+//!
+//! # Example
+//!
+//! ```ignore
+//! // No API key needed
+//! registry.register(WebFetchTool::new()?);
+//!
+//! // Firecrawl, honouring robots.txt
+//! registry.register(
+//!     WebFetchTool::with_backend(FirecrawlBackend::from_env()?)?.with_respect_robots_txt(true),
+//! );
+//! ```
+
+mod backend;
+mod firecrawl;
+mod robots;
+
+pub use backend::{
+    FetchBackend, FetchFormat, FetchRequest, FetchResponse, HttpBackend, RawBackend, Redirect,
+};
+pub use firecrawl::FirecrawlBackend;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::super::tool::{Tool, ToolInfo, ToolResult};
+use crate::llm::{define_tool, ToolDefinition};
+use crate::runtime::AgentInternals;
+
+/// Default timeout per request, in seconds
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// Longest timeout the model may ask for, in seconds
+const MAX_TIMEOUT_SECS: u64 = 120;
+
+/// Default download cap
+pub const DEFAULT_MAX_BYTES: usize = 10 * 1024 * 1024;
+
+/// Input for the WebFetch tool
+#[derive(Debug, Deserialize)]
+struct WebFetchInput {
+    /// The URL to fetch
+    url: String,
+    /// Output format for HTML pages
+    #[serde(default)]
+    format: FetchFormat,
+    /// Timeout in seconds
+    timeout: Option<u64>,
+    /// Maximum number of bytes to download
+    max_bytes: Option<usize>,
+}
+
+/// WebFetch tool for reading web pages
+pub struct WebFetchTool {
+    backend: Arc<dyn FetchBackend>,
+    /// Client used for robots.txt lookups
+    client: Client,
+    respect_robots_txt: bool,
+    max_bytes: usize,
+}
+
+impl WebFetchTool {
+    /// Create a WebFetch tool with the plain HTTP backend
+    pub fn new() -> Result<Self> {
+        Self::with_backend(HttpBackend::new()?)
+    }
+
+    /// Create a WebFetch tool with a specific backend
+    pub fn with_backend(backend: impl FetchBackend + 'static) -> Result<Self> {
+        Ok(Self {
+            backend: Arc::new(backend),
+            client: backend::http_client()?,
+            respect_robots_txt: false,
+            max_bytes: DEFAULT_MAX_BYTES,
+        })
+    }
+
+    /// Refuse URLs that the site's robots.txt disallows (off by default)
+    pub fn with_respect_robots_txt(mut self, respect: bool) -> Self {
+        self.respect_robots_txt = respect;
+        self
+    }
+
+    /// Set the download cap; requests may ask for less, never more
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Fetch and render a URL, or explain why it couldn't be fetched
+    async fn fetch(&self, input: WebFetchInput) -> std::result::Result<String, String> {
+        let timeout = Duration::from_secs(
+            input
+                .timeout
+                .unwrap_or(DEFAULT_TIMEOUT_SECS)
+                .clamp(1, MAX_TIMEOUT_SECS),
+        );
+        let request = FetchRequest {
+            url: input.url,
+            format: input.format,
+            timeout,
+            max_bytes: input.max_bytes.unwrap_or(self.max_bytes).min(self.max_bytes),
+        };
+
+        if self.respect_robots_txt {
+            let url = Url::parse(&request.url)
+                .map_err(|e| format!("Invalid URL '{}': {}", request.url, e))?;
+            if !robots::is_allowed(&self.client, &url, backend::USER_AGENT, timeout).await {
+                return Err(format!("Fetching {} is disallowed by the site's robots.txt", url));
+            }
+        }
+
+        tracing::info!("[WebFetch] Fetching {} via {}", request.url, self.backend.name());
+        let response = self
+            .backend
+            .fetch(&request)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(format_response(&response, request.max_bytes))
+    }
+}
+
+/// Render a fetch result as the tool output
+fn format_response(response: &FetchResponse, max_bytes: usize) -> String {
+    let mut output = format!("URL: {}\nStatus: {}\n", response.url, response.status);
+    if let Some(ref content_type) = response.content_type {
+        output.push_str(&format!("Content-Type: {}\n", content_type));
+    }
+
+    if !response.redirects.is_empty() {
+        output.push_str("Redirects:\n");
+        for redirect in &response.redirects {
+            output.push_str(&format!(
+                "  {} {} -> {}{}\n",
+                redirect.status,
+                redirect.from,
+                redirect.to,
+                if redirect.crosses_domain() {
+                    " (different domain)"
+                } else {
+                    ""
+                }
+            ));
+        }
+        if response.redirects.iter().any(Redirect::crosses_domain) {
+            output.push_str("Note: the content was served from a different domain than requested.\n");
+        }
+    }
+    if response.truncated {
+        output.push_str(&format!("Note: content truncated at {} bytes.\n", max_bytes));
+    }
+
+    output.push('\n');
+    output.push_str(&response.content);
+    output
+}
+
+#[async_trait]
+impl Tool for WebFetchTool {
+    fn name(&self) -> &str {
+        "WebFetch"
+    }
+
+    fn description(&self) -> &str {
+        "Fetch a URL and return its content as markdown, text or HTML."
+    }
+
+    fn definition(&self) -> ToolDefinition {
+        define_tool(
+            "WebFetch",
+            "Fetches content from a URL. HTML pages are converted to markdown by default; \
+            other content types (JSON, plain text, ...) are returned with their HTTP headers.\n\n\
+            Usage notes:\n\
+            - The URL must be a fully-formed http:// or https:// URL\n\
+            - Redirects are followed and listed in the output; check it when a redirect \
+            leaves the original domain\n\
+            - Large pages are truncated at max_bytes",
+            json!({
+                "url": {
+                    "type": "string",
+                    "description": "The URL to fetch"
+                },
+                "format": {
+                    "type": "string",
+                    "enum": ["markdown", "text", "html"],
+                    "description": "Output format for HTML pages (default: markdown)"
+                },
+                "timeout": {
+                    "type": "number",
+                    "description": "Timeout in seconds (default 30, max 120)"
+                },
+                "max_bytes": {
+                    "type": "number",
+                    "description": "Maximum number of bytes to download (default 10 MB)"
+                }
+            }),
+            vec!["url".to_string()],
+        )
+    }
+
+    fn get_info(&self, input: &Value) -> ToolInfo {
+        let url = input.get("url").and_then(|v| v.as_str()).unwrap_or("");
+
+        ToolInfo {
+            name: "WebFetch".to_string(),
+            action_description: format!("Fetch: {}", url),
+            details: None,
+            read_only: true,
+        }
+    }
+
+    async fn execute(&self, input: &Value, _internals: &mut AgentInternals) -> Result<ToolResult> {
+        let input: WebFetchInput = serde_json::from_value(input.clone())
+            .map_err(|e| anyhow::anyhow!("Invalid WebFetch input: {}", e))?;
+
+        match self.fetch(input).await {
+            Ok(output) => Ok(ToolResult::success(output)),
+            Err(e) => Ok(ToolResult::error(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve HTTP/1.1 on localhost; `route` maps a request path to a raw response
+    async fn serve(route: fn(&str, u16) -> Vec<u8>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    break;
+                };
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => request.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let request = String::from_utf8_lossy(&request);
+                    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+                    // The client hangs up early when it hits the byte cap
+                    let _ = stream.write_all(&route(&path, port)).await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        });
+        port
+    }
+
+    fn response(status: &str, headers: &[(&str, &str)], body: &[u8]) -> Vec<u8> {
+        let mut out = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n", status, body.len());
+        for (name, value) in headers {
+            out.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        out.push_str("\r\n");
+        let mut out = out.into_bytes();
+        out.extend_from_slice(body);
+        out
+    }
+
+    fn routes(path: &str, port: u16) -> Vec<u8> {
+        match path {
+            "/page" => response(
+                "200 OK",
+                &[("Content-Type", "text/html; charset=utf-8")],
+                b"<html><head><script>var secret = 1;</script><style>p{}</style></head>\
+                <body><h1>Title</h1><p>Hello <a href=\"/x\">world</a></p></body></html>",
+            ),
+            "/json" => response("200 OK", &[("Content-Type", "application/json")], b"{\"ok\":true}"),
+            "/big" => response(
+                "200 OK",
+                &[("Content-Type", "text/plain")],
+                &vec![b'a'; 11 * 1024 * 1024],
+            ),
+            "/a" => response("302 Found", &[("Location", "/b")], b""),
+            "/b" => {
+                let location = format!("http://localhost:{}/page", port);
+                response("301 Moved Permanently", &[("Location", location.as_str())], b"")
+            }
+            "/loop" => response("302 Found", &[("Location", "/loop")], b""),
+            "/robots.txt" => response("200 OK", &[], b"User-agent: *\nDisallow: /private\n"),
+            "/private" => response("200 OK", &[("Content-Type", "text/plain")], b"secret"),
+            _ => response("404 Not Found", &[], b"not found"),
+        }
+    }
+
+    fn input(url: String) -> WebFetchInput {
+        WebFetchInput {
+            url,
+            format: FetchFormat::Markdown,
+            timeout: Some(5),
+            max_bytes: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_html_extraction() {
+        let port = serve(routes).await;
+        let tool = WebFetchTool::new().unwrap();
+
+        let output = tool.fetch(input(format!("http://127.0.0.1:{}/page", port))).await.unwrap();
+        assert!(output.contains("Status: 200"));
+        assert!(output.contains("# Title"));
+        assert!(output.contains("Hello"));
+        assert!(output.contains("world"));
+        assert!(!output.contains("secret"));
+
+        let mut text = input(format!("http://127.0.0.1:{}/page", port));
+        text.format = FetchFormat::Text;
+        let output = tool.fetch(text).await.unwrap();
+        assert!(output.contains("Title"));
+        assert!(!output.contains("# Title"));
+
+        let mut html = input(format!("http://127.0.0.1:{}/page", port));
+        html.format = FetchFormat::Html;
+        assert!(tool.fetch(html).await.unwrap().contains("<h1>Title</h1>"));
+
+        // Non-HTML content comes back raw, with headers
+        let output = tool.fetch(input(format!("http://127.0.0.1:{}/json", port))).await.unwrap();
+        assert!(output.contains("content-type: application/json"));
+        assert!(output.contains("{\"ok\":true}"));
+    }
+
+    #[tokio::test]
+    async fn test_body_cap() {
+        let port = serve(routes).await;
+        let tool = WebFetchTool::with_backend(RawBackend::new().unwrap()).unwrap();
+
+        let request = FetchRequest {
+            url: format!("http://127.0.0.1:{}/big", port),
+            format: FetchFormat::Markdown,
+            timeout: Duration::from_secs(5),
+            max_bytes: DEFAULT_MAX_BYTES,
+        };
+        let response = tool.backend.fetch(&request).await.unwrap();
+        assert!(response.truncated);
+        let body = response.content.split("\n\n").nth(1).unwrap();
+        assert_eq!(body.len(), DEFAULT_MAX_BYTES);
+
+        // The model can ask for less
+        let mut small = input(format!("http://127.0.0.1:{}/big", port));
+        small.max_bytes = Some(100);
+        let output = tool.fetch(small).await.unwrap();
+        assert!(output.contains("Note: content truncated at 100 bytes."));
+    }
+
+    #[tokio::test]
+    async fn test_redirect_chain() {
+        let port = serve(routes).await;
+        let tool = WebFetchTool::new().unwrap();
+
+        let output = tool.fetch(input(format!("http://127.0.0.1:{}/a", port))).await.unwrap();
+        assert!(output.contains(&format!("URL: http://localhost:{}/page", port)));
+        assert!(output.contains(&format!(
+            "302 http://127.0.0.1:{port}/a -> http://127.0.0.1:{port}/b\n"
+        )));
+        assert!(output.contains(&format!(
+            "301 http://127.0.0.1:{port}/b -> http://localhost:{port}/page (different domain)"
+        )));
+        assert!(output.contains("served from a different domain"));
+        assert!(output.contains("# Title"));
+
+        let err = tool
+            .fetch(input(format!("http://127.0.0.1:{}/loop", port)))
+            .await
+            .unwrap_err();
+        assert!(err.contains("Too many redirects"));
+    }
+
+    #[tokio::test]
+    async fn test_robots_txt() {
+        let port = serve(routes).await;
+        let url = format!("http://127.0.0.1:{}/private", port);
+
+        let polite = WebFetchTool::new().unwrap().with_respect_robots_txt(true);
+        let err = polite.fetch(input(url.clone())).await.unwrap_err();
+        assert!(err.contains("disallowed by the site's robots.txt"));
+        assert!(polite
+            .fetch(input(format!("http://127.0.0.1:{}/page", port)))
+            .await
+            .is_ok());
+
+        let impolite = WebFetchTool::new().unwrap();
+        assert!(impolite.fetch(input(url)).await.unwrap().contains("secret"));
+    }
+}
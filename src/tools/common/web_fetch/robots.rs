@@ -0,0 +1,148 @@
+//! Minimal robots.txt support
+//!
+//! Only `User-agent`, `Allow` and `Disallow` are understood. Rules are path
+//! prefixes (a trailing `*` is ignored, `$` anchors the end); the longest
+//! matching rule wins and `Allow` wins ties. A missing or unreadable
+//! robots.txt allows everything.
+
+use std::time::Duration;
+
+use reqwest::{Client, Url};
+
+/// A `User-agent` group and its rules
+#[derive(Default)]
+struct Group {
+    agents: Vec<String>,
+    /// (allow, path pattern)
+    rules: Vec<(bool, String)>,
+}
+
+/// Fetch `robots.txt` for `url`'s origin and check whether `url` may be fetched
+pub(crate) async fn is_allowed(client: &Client, url: &Url, user_agent: &str, timeout: Duration) -> bool {
+    let Ok(robots_url) = url.join("/robots.txt") else {
+        return true;
+    };
+
+    let response = match client.get(robots_url.clone()).timeout(timeout).send().await {
+        Ok(response) if response.status().is_success() => response,
+        _ => return true,
+    };
+    let Ok(text) = response.text().await else {
+        return true;
+    };
+
+    let mut path = url.path().to_string();
+    if let Some(query) = url.query() {
+        path.push('?');
+        path.push_str(query);
+    }
+    robots_allows(&text, user_agent, &path)
+}
+
+/// Check `path` against the rules in `robots_txt` for `user_agent`
+///
+/// Uses the group naming our agent (matched by product token, ignoring case)
+/// if there is one, otherwise the `*` group.
+pub(crate) fn robots_allows(robots_txt: &str, user_agent: &str, path: &str) -> bool {
+    let token = user_agent
+        .split('/')
+        .next()
+        .unwrap_or(user_agent)
+        .to_ascii_lowercase();
+
+    let mut groups: Vec<Group> = Vec::new();
+    let mut in_agent_lines = false;
+    for line in robots_txt.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+
+        match key.as_str() {
+            "user-agent" => {
+                if !in_agent_lines {
+                    groups.push(Group::default());
+                }
+                in_agent_lines = true;
+                if let Some(group) = groups.last_mut() {
+                    group.agents.push(value.to_ascii_lowercase());
+                }
+            }
+            "allow" | "disallow" => {
+                in_agent_lines = false;
+                if let Some(group) = groups.last_mut() {
+                    group.rules.push((key == "allow", value.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let group = groups
+        .iter()
+        .find(|g| g.agents.iter().any(|a| a != "*" && token.contains(a.as_str())))
+        .or_else(|| groups.iter().find(|g| g.agents.iter().any(|a| a == "*")));
+    let Some(group) = group else {
+        return true;
+    };
+
+    let mut best: Option<(usize, bool)> = None;
+    for (allow, pattern) in &group.rules {
+        // An empty Disallow means "allow everything"
+        if pattern.is_empty() {
+            continue;
+        }
+        if !rule_matches(pattern, path) {
+            continue;
+        }
+        let len = pattern.len();
+        best = match best {
+            Some((best_len, best_allow)) if best_len > len || (best_len == len && best_allow) => {
+                Some((best_len, best_allow))
+            }
+            _ => Some((len, *allow)),
+        };
+    }
+    best.map(|(_, allow)| allow).unwrap_or(true)
+}
+
+/// Whether a robots.txt path pattern matches `path`
+fn rule_matches(pattern: &str, path: &str) -> bool {
+    if let Some(exact) = pattern.strip_suffix('$') {
+        return path == exact;
+    }
+    path.starts_with(pattern.trim_end_matches('*'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_robots_rules() {
+        let robots = "\
+# comment
+User-agent: *
+Disallow: /private
+Allow: /private/public
+
+User-agent: shadow-agent-sdk
+Disallow: /no-agents
+";
+        let ua = "shadow-agent-sdk/0.1.0";
+        // Our own group applies, not the * group
+        assert!(!robots_allows(robots, ua, "/no-agents/page"));
+        assert!(robots_allows(robots, ua, "/private"));
+
+        let other = "other-bot/1.0";
+        assert!(!robots_allows(robots, other, "/private/secret"));
+        assert!(robots_allows(robots, other, "/private/public/page"));
+        assert!(robots_allows(robots, other, "/"));
+
+        assert!(robots_allows("User-agent: *\nDisallow:\n", ua, "/anything"));
+        assert!(!robots_allows("User-agent: *\nDisallow: /*\n", ua, "/anything"));
+        assert!(robots_allows("", ua, "/"));
+    }
+}
@@ -1,6 +1,17 @@
 //! Write tool for creating/writing files
 //!
 //! Writes content to files on the local filesystem.
+//!
+//! Replacing a file takes `overwrite: true`, and writing into a directory
+//! that doesn't exist takes `create_dirs: true`; otherwise the call fails
+//! with what to do instead. A replaced file's contents are kept in the
+//! session's `backups/` directory (see `SessionStorage::save_backup`), the
+//! change is recorded in the agent's `FileChangeJournal` for UndoTool, and
+//! the result says what was replaced:
+//!
+//! ```text
+//! File updated successfully: src/lib.rs (replaced 120-line file, previous content hash 9f2c61a0d3e4b5c7; backup at /app/sessions/abc/backups/toolu_7/lib.rs)
+//! ```
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -8,8 +19,12 @@ use serde::Deserialize;
 use serde_json::{json, Value};
 use std::fs;
 use std::path::Path;
+use std::sync::Arc;
 
+use super::file_changes::{record_change, save_backup};
+use super::path_policy::{effective_policy, PathPolicy};
 use super::super::tool::{Tool, ToolInfo, ToolResult};
+use crate::helpers::{content_hash, FileTracker, WriteCheck};
 use crate::llm::{ToolDefinition, ToolInputSchema};
 use crate::runtime::AgentInternals;
 
@@ -17,6 +32,10 @@ use crate::runtime::AgentInternals;
 pub struct WriteTool {
     /// Base directory for file operations
     base_dir: String,
+    /// Paths this tool may touch (falls back to the agent's `PathPolicy` resource)
+    policy: Option<Arc<PathPolicy>>,
+    /// Keep a copy of each replaced file in the session directory
+    backups: bool,
 }
 
 /// Input for the write tool
@@ -26,6 +45,12 @@ struct WriteInput {
     file_path: String,
     /// The content to write to the file (required)
     content: String,
+    /// Replace the file if it already exists
+    #[serde(default)]
+    overwrite: bool,
+    /// Create missing parent directories
+    #[serde(default)]
+    create_dirs: bool,
 }
 
 impl WriteTool {
@@ -35,16 +60,30 @@ impl WriteTool {
             .to_string_lossy()
             .to_string();
 
-        Ok(Self { base_dir })
+        Ok(Self::with_base_dir(base_dir))
     }
 
     /// Create a new Write tool with a specific base directory
     pub fn with_base_dir(base_dir: impl Into<String>) -> Self {
         Self {
             base_dir: base_dir.into(),
+            policy: None,
+            backups: true,
         }
     }
 
+    /// Confine this tool to the paths `policy` allows
+    pub fn with_policy(mut self, policy: PathPolicy) -> Self {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Whether to keep a copy of each replaced file (default true)
+    pub fn with_backups(mut self, enabled: bool) -> Self {
+        self.backups = enabled;
+        self
+    }
+
     /// Resolve a path (handle both absolute and relative)
     fn resolve_path(&self, path: &str) -> String {
         let path = Path::new(path);
@@ -58,28 +97,49 @@ impl WriteTool {
         }
     }
 
-    /// Write content to a file
-    fn write_file(&self, file_path: &str, content: &str) -> Result<String> {
-        let resolved_path = self.resolve_path(file_path);
-        tracing::info!("Writing file: {}", resolved_path);
-
-        // Create parent directories if needed
-        if let Some(parent) = Path::new(&resolved_path).parent() {
-            fs::create_dir_all(parent)
-                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    /// Why a write must not happen, if it must not
+    fn refusal(input: &WriteInput, path: &Path) -> Option<String> {
+        if path.is_dir() {
+            return Some(format!("{} is a directory", input.file_path));
         }
+        if path.exists() && !input.overwrite {
+            return Some(format!(
+                "File already exists: {}. Use Edit to change it, or Read it first and pass \"overwrite\": true to replace it",
+                input.file_path
+            ));
+        }
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() && !parent.exists() && !input.create_dirs => Some(format!(
+                "Directory does not exist: {}. Pass \"create_dirs\": true to create it",
+                parent.display()
+            )),
+            _ => None,
+        }
+    }
 
-        let existed = Path::new(&resolved_path).exists();
-
-        fs::write(&resolved_path, content)
-            .with_context(|| format!("Failed to write file: {}", resolved_path))?;
-
-        if existed {
-            Ok(format!("File updated successfully: {}", file_path))
-        } else {
-            Ok(format!("File created successfully: {}", file_path))
+    /// Write content to a file, creating its directories if asked to
+    fn write_file(path: &Path, content: &str, create_dirs: bool) -> Result<()> {
+        if create_dirs {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+            }
         }
+        fs::write(path, content).with_context(|| format!("Failed to write file: {}", path.display()))
+    }
+}
+
+/// What a write replaced, for the tool result
+fn describe_replaced(previous: &[u8], backup: Option<&Path>) -> String {
+    let size = match std::str::from_utf8(previous) {
+        Ok(text) => format!("{}-line", text.lines().count()),
+        Err(_) => format!("{}-byte", previous.len()),
+    };
+    let mut summary = format!("replaced {} file, previous content hash {}", size, content_hash(previous));
+    if let Some(backup) = backup {
+        summary.push_str(&format!("; backup at {}", backup.display()));
     }
+    summary
 }
 
 impl Default for WriteTool {
@@ -105,7 +165,7 @@ impl Tool for WriteTool {
             name: "Write".to_string(),
             description: Some(
                 "Writes a file to the local filesystem. \
-                This will overwrite the existing file if there is one. \
+                Fails if the file exists, unless overwrite is true; Read the file before replacing it. \
                 ALWAYS prefer editing existing files. NEVER write new files unless explicitly required."
                     .to_string(),
             ),
@@ -119,6 +179,14 @@ impl Tool for WriteTool {
                     "content": {
                         "type": "string",
                         "description": "The content to write to the file"
+                    },
+                    "overwrite": {
+                        "type": "boolean",
+                        "description": "Replace the file if it already exists. Default false: writing to an existing file fails."
+                    },
+                    "create_dirs": {
+                        "type": "boolean",
+                        "description": "Create missing parent directories. Default false: writing into a missing directory fails."
                     }
                 })),
                 required: Some(vec!["file_path".to_string(), "content".to_string()]),
@@ -138,16 +206,80 @@ impl Tool for WriteTool {
             name: "Write".to_string(),
             action_description: format!("Write file: {}", file_path),
             details: None,
+            read_only: false,
         }
     }
 
-    async fn execute(&self, input: &Value, _internals: &mut AgentInternals) -> Result<ToolResult> {
+    fn describe_effect(&self, input: &Value) -> String {
+        let Ok(input) = serde_json::from_value::<WriteInput>(input.clone()) else {
+            return input.to_string();
+        };
+        let path = self.resolve_path(&input.file_path);
+        let action = if Path::new(&path).exists() { "Replace" } else { "Create" };
+        format!(
+            "{} {} with {} lines ({} bytes)",
+            action,
+            path,
+            input.content.lines().count(),
+            input.content.len()
+        )
+    }
+
+    async fn execute(&self, input: &Value, internals: &mut AgentInternals) -> Result<ToolResult> {
         let write_input: WriteInput = serde_json::from_value(input.clone())
             .map_err(|e| anyhow::anyhow!("Invalid write input: {}", e))?;
 
-        match self.write_file(&write_input.file_path, &write_input.content) {
-            Ok(output) => Ok(ToolResult::success(output)),
-            Err(e) => Ok(ToolResult::error(format!("{}", e))),
+        let mut resolved_path = self.resolve_path(&write_input.file_path);
+        if let Some(policy) = effective_policy(&self.policy, internals) {
+            match policy.check(&resolved_path) {
+                Ok(checked) => resolved_path = checked.to_string_lossy().into_owned(),
+                Err(e) => return Ok(ToolResult::error(e.to_string())),
+            }
+        }
+        let path = Path::new(&resolved_path);
+        if let Some(refusal) = Self::refusal(&write_input, path) {
+            return Ok(ToolResult::error(refusal));
+        }
+        let tracker = internals.context.get_resource::<FileTracker>();
+        let warning = match tracker.as_ref().map(|t| t.check_write(&resolved_path)) {
+            Some(WriteCheck::Denied(reason)) => return Ok(ToolResult::error(reason)),
+            Some(WriteCheck::Warning(warning)) => Some(warning),
+            _ => None,
+        };
+
+        let previous = if path.exists() {
+            match fs::read(path) {
+                Ok(previous) => Some(previous),
+                Err(e) => return Ok(ToolResult::error(format!("Failed to read {}: {}", resolved_path, e))),
+            }
+        } else {
+            None
+        };
+        let backup = match &previous {
+            Some(previous) if self.backups => save_backup(internals, path, previous).await,
+            _ => None,
+        };
+
+        tracing::info!("Writing file: {}", resolved_path);
+        if let Err(e) = Self::write_file(path, &write_input.content, write_input.create_dirs) {
+            return Ok(ToolResult::error(format!("{}", e)));
+        }
+        if let Some(tracker) = &tracker {
+            tracker.record_read(&resolved_path);
+        }
+        record_change(internals, path, previous.as_deref(), backup.clone());
+
+        let output = match &previous {
+            Some(previous) => format!(
+                "File updated successfully: {} ({})",
+                write_input.file_path,
+                describe_replaced(previous, backup.as_deref())
+            ),
+            None => format!("File created successfully: {}", write_input.file_path),
+        };
+        match warning {
+            Some(warning) => Ok(ToolResult::success(format!("{}\n\nWarning: {}", output, warning))),
+            None => Ok(ToolResult::success(output)),
         }
     }
 
@@ -156,5 +288,94 @@ impl Tool for WriteTool {
     }
 }
 
-// Tests temporarily disabled - require AgentInternals test helper
-// TODO: Create test infrastructure for tools that need AgentInternals
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ToolResultData;
+
+    fn internals(sessions: &Path) -> AgentInternals {
+        let mut internals = AgentInternals::for_test(sessions);
+        internals.context.current_tool_use_id = Some("toolu_1".to_string());
+        internals
+    }
+
+    fn text(result: &ToolResult) -> &str {
+        match &result.content[..] {
+            [ToolResultData::Text(text)] => text,
+            other => panic!("expected text, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_new_file_and_refused_overwrite() {
+        let (work, sessions) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let tool = WriteTool::with_base_dir(work.path().to_string_lossy());
+        let mut internals = internals(sessions.path());
+
+        let result = tool.execute(&json!({"file_path": "a.txt", "content": "one"}), &mut internals).await.unwrap();
+        assert_eq!(text(&result), "File created successfully: a.txt");
+
+        let result = tool.execute(&json!({"file_path": "a.txt", "content": "two"}), &mut internals).await.unwrap();
+        assert!(result.is_error);
+        assert!(text(&result).starts_with("File already exists: a.txt."), "{}", text(&result));
+        assert_eq!(fs::read_to_string(work.path().join("a.txt")).unwrap(), "one");
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_keeps_a_backup() {
+        let (work, sessions) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        fs::write(work.path().join("a.txt"), "one\ntwo\n").unwrap();
+        let tool = WriteTool::with_base_dir(work.path().to_string_lossy());
+        let mut internals = internals(sessions.path());
+
+        let input = json!({"file_path": "a.txt", "content": "three", "overwrite": true});
+        let result = tool.execute(&input, &mut internals).await.unwrap();
+        assert!(!result.is_error);
+        assert_eq!(fs::read_to_string(work.path().join("a.txt")).unwrap(), "three");
+
+        let backup = fs::canonicalize(sessions.path().join("s/backups/toolu_1/a.txt")).unwrap();
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "one\ntwo\n");
+        assert_eq!(
+            text(&result),
+            format!(
+                "File updated successfully: a.txt (replaced 2-line file, previous content hash {}; backup at {})",
+                content_hash(b"one\ntwo\n"),
+                backup.display()
+            )
+        );
+
+        // Without backups, only the summary
+        let tool = tool.with_backups(false);
+        let input = json!({"file_path": "a.txt", "content": "", "overwrite": true});
+        let result = tool.execute(&input, &mut internals).await.unwrap();
+        let summary = format!("(replaced 1-line file, previous content hash {})", content_hash(b"three"));
+        assert!(text(&result).ends_with(&summary), "{}", text(&result));
+    }
+
+    #[tokio::test]
+    async fn test_missing_directories_are_created_only_when_asked() {
+        let (work, sessions) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let tool = WriteTool::with_base_dir(work.path().to_string_lossy());
+        let mut internals = internals(sessions.path());
+
+        let input = json!({"file_path": "src/new/a.rs", "content": ""});
+        let result = tool.execute(&input, &mut internals).await.unwrap();
+        assert!(result.is_error);
+        assert!(text(&result).contains("Pass \"create_dirs\": true"), "{}", text(&result));
+        assert!(!work.path().join("src").exists());
+
+        let input = json!({"file_path": "src/new/a.rs", "content": "", "create_dirs": true});
+        let result = tool.execute(&input, &mut internals).await.unwrap();
+        assert!(!result.is_error, "{}", text(&result));
+        assert!(work.path().join("src/new/a.rs").exists());
+
+        // The sandbox applies before any directory is made
+        let outside = tempfile::tempdir().unwrap();
+        let tool = tool.with_policy(PathPolicy::new().allow_root(work.path()));
+        let path = outside.path().join("x/a.rs").to_string_lossy().to_string();
+        let input = json!({"file_path": path, "content": "", "create_dirs": true});
+        let result = tool.execute(&input, &mut internals).await.unwrap();
+        assert!(result.is_error);
+        assert!(!outside.path().join("x").exists());
+    }
+}
@@ -0,0 +1,107 @@
+//! Tool concurrency limits
+//!
+//! Some tools front a resource that can only take so many callers at once
+//! (a browser, an MCP server with a single backend). A tool declares its
+//! cap with `Tool::max_concurrency`, and `AgentRuntime::with_max_concurrent_tools`
+//! caps all tool calls together. Both are enforced by the executor for
+//! every agent of the runtime, through the `ToolConcurrency` shared resource.
+//!
+//! A call waits for a free slot after its `ToolStart` is sent; the agent
+//! sends a `Status` chunk ("Waiting for a Bash slot") when it has to wait.
+//! The wait counts against the tool's `timeout`.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Slots held by a running tool call, released on drop
+pub struct ToolPermit {
+    _tool: Option<OwnedSemaphorePermit>,
+    _global: Option<OwnedSemaphorePermit>,
+}
+
+/// Per-tool and global concurrency slots, shared by a runtime's agents
+#[derive(Default)]
+pub struct ToolConcurrency {
+    global: Option<Arc<Semaphore>>,
+    per_tool: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl ToolConcurrency {
+    /// Slots with an optional cap across all tools
+    pub fn new(max_concurrent_tools: Option<usize>) -> Self {
+        Self {
+            global: max_concurrent_tools.map(|max| Arc::new(Semaphore::new(max))),
+            per_tool: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take the slots for a call without waiting
+    ///
+    /// `max` is the tool's own cap; the first call of a tool fixes it.
+    pub fn try_acquire(&self, tool_name: &str, max: Option<usize>) -> Option<ToolPermit> {
+        let tool = match self.tool_slots(tool_name, max) {
+            Some(slots) => Some(slots.try_acquire_owned().ok()?),
+            None => None,
+        };
+        let global = match &self.global {
+            Some(slots) => Some(slots.clone().try_acquire_owned().ok()?),
+            None => None,
+        };
+        Some(ToolPermit { _tool: tool, _global: global })
+    }
+
+    /// Wait for the slots for a call
+    ///
+    /// The tool's slot is taken first, so a call waiting on its own tool
+    /// doesn't hold a global slot meanwhile.
+    pub async fn acquire(&self, tool_name: &str, max: Option<usize>) -> ToolPermit {
+        let tool = match self.tool_slots(tool_name, max) {
+            Some(slots) => Some(slots.acquire_owned().await.expect("tool semaphore is never closed")),
+            None => None,
+        };
+        let global = match &self.global {
+            Some(slots) => Some(slots.clone().acquire_owned().await.expect("tool semaphore is never closed")),
+            None => None,
+        };
+        ToolPermit { _tool: tool, _global: global }
+    }
+
+    /// Free slots for a tool (None if it has no cap or hasn't run yet)
+    pub fn available(&self, tool_name: &str) -> Option<usize> {
+        let per_tool = self.per_tool.lock().unwrap();
+        per_tool.get(tool_name).map(|slots| slots.available_permits())
+    }
+
+    fn tool_slots(&self, tool_name: &str, max: Option<usize>) -> Option<Arc<Semaphore>> {
+        let max = max?;
+        let mut per_tool = self.per_tool.lock().unwrap();
+        let slots = per_tool
+            .entry(tool_name.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(max.max(1))));
+        Some(slots.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_and_global_caps() {
+        let slots = ToolConcurrency::new(Some(2));
+        let first = slots.try_acquire("Browser", Some(1)).unwrap();
+        assert!(slots.try_acquire("Browser", Some(1)).is_none());
+        assert_eq!(slots.available("Browser"), Some(0));
+
+        // Uncapped tools only count against the global cap
+        let second = slots.try_acquire("Read", None).unwrap();
+        assert!(slots.try_acquire("Read", None).is_none());
+        assert_eq!(slots.available("Read"), None);
+
+        drop(first);
+        drop(second);
+        assert!(slots.try_acquire("Browser", Some(1)).is_some());
+    }
+}
@@ -0,0 +1,311 @@
+//! Tool input schemas derived from the input type
+//!
+//! A tool deserializes its input into a struct, and advertises a JSON
+//! schema for it in `definition()`. Rather than writing the schema out by
+//! hand, derive it:
+//!
+//! ```ignore
+//! use serde::Deserialize;
+//! use shadow_agent_sdk::tools::ToolInput;
+//!
+//! #[derive(Deserialize, ToolInput)]
+//! struct SearchInput {
+//!     /// What to search for
+//!     query: String,
+//!     /// Maximum number of results
+//!     limit: Option<u32>,
+//! }
+//!
+//! // In the Tool impl
+//! input_schema: SearchInput::schema(),
+//! // ...
+//! let input = SearchInput::from_value(input.clone())?;
+//! ```
+//!
+//! Field types must implement `ToolInput` themselves; it's implemented for
+//! strings, numbers, booleans, `Option`, `Vec`, string-keyed maps and
+//! `serde_json::Value`, and derived structs and enums can be nested.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::llm::ToolInputSchema;
+
+pub use shadow_agent_derive::ToolInput;
+
+/// A type a tool's input (or part of it) deserializes into
+pub trait ToolInput: DeserializeOwned {
+    /// The JSON schema for this type
+    fn json_schema() -> Value;
+
+    /// Whether a field of this type may be left out
+    fn optional() -> bool {
+        false
+    }
+
+    /// The schema to put in the tool's definition
+    fn schema() -> ToolInputSchema {
+        let schema = Self::json_schema();
+        ToolInputSchema {
+            schema_type: "object".to_string(),
+            properties: Some(schema.get("properties").cloned().unwrap_or_else(|| json!({}))),
+            required: schema.get("required").and_then(|r| r.as_array()).map(|required| {
+                required.iter().filter_map(|r| r.as_str().map(str::to_string)).collect()
+            }),
+        }
+    }
+
+    /// Deserialize a tool's input, naming the offending field on failure
+    fn from_value(value: Value) -> anyhow::Result<Self> {
+        serde_path_to_error::deserialize(value).map_err(|e| {
+            let path = e.path().to_string();
+            if path == "." {
+                anyhow::anyhow!("{}", e.inner())
+            } else {
+                anyhow::anyhow!("{}: {}", path, e.inner())
+            }
+        })
+    }
+}
+
+/// For the code `#[derive(ToolInput)]` generates
+#[doc(hidden)]
+pub mod __private {
+    pub use serde_json;
+}
+
+macro_rules! schema_impl {
+    ($schema:tt => $($ty:ty),*) => {
+        $(impl ToolInput for $ty {
+            fn json_schema() -> Value {
+                json!($schema)
+            }
+        })*
+    };
+}
+
+schema_impl!({"type": "string"} => String, char, PathBuf);
+schema_impl!({"type": "boolean"} => bool);
+schema_impl!({"type": "integer"} => i8, i16, i32, i64, isize);
+schema_impl!({"type": "integer", "minimum": 0} => u8, u16, u32, u64, usize);
+schema_impl!({"type": "number"} => f32, f64);
+schema_impl!({} => Value);
+
+impl<T: ToolInput> ToolInput for Option<T> {
+    fn json_schema() -> Value {
+        T::json_schema()
+    }
+
+    fn optional() -> bool {
+        true
+    }
+}
+
+impl<T: ToolInput> ToolInput for Box<T> {
+    fn json_schema() -> Value {
+        T::json_schema()
+    }
+
+    fn optional() -> bool {
+        T::optional()
+    }
+}
+
+impl<T: ToolInput> ToolInput for Vec<T> {
+    fn json_schema() -> Value {
+        json!({"type": "array", "items": T::json_schema()})
+    }
+}
+
+impl<T: ToolInput> ToolInput for HashMap<String, T> {
+    fn json_schema() -> Value {
+        json!({"type": "object", "additionalProperties": T::json_schema()})
+    }
+}
+
+impl<T: ToolInput> ToolInput for BTreeMap<String, T> {
+    fn json_schema() -> Value {
+        json!({"type": "object", "additionalProperties": T::json_schema()})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    /// How to sort
+    #[derive(Debug, Deserialize, ToolInput, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    enum Order {
+        Newest,
+        OldestFirst,
+        #[serde(rename = "by-name")]
+        ByName,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize, ToolInput)]
+    #[serde(rename_all = "camelCase")]
+    struct Range {
+        /// First line
+        start_line: u32,
+        /// Last line
+        ///
+        /// Inclusive.
+        end_line: Option<u32>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize, ToolInput)]
+    struct SearchInput {
+        /// The text to find
+        query: String,
+        /// Where to look
+        ranges: Vec<Range>,
+        order: Option<Order>,
+        #[serde(rename = "-i", default)]
+        ignore_case: bool,
+        #[serde(skip)]
+        cached: Vec<String>,
+        tags: HashMap<String, Value>,
+    }
+
+    #[test]
+    fn test_derived_schema() {
+        assert_eq!(
+            SearchInput::json_schema(),
+            json!({
+                "type": "object",
+                "properties": {
+                    "query": {"type": "string", "description": "The text to find"},
+                    "ranges": {
+                        "type": "array",
+                        "description": "Where to look",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "startLine": {"type": "integer", "minimum": 0, "description": "First line"},
+                                "endLine": {"type": "integer", "minimum": 0, "description": "Last line\n\nInclusive."}
+                            },
+                            "required": ["startLine"]
+                        }
+                    },
+                    "order": {"type": "string", "enum": ["newest", "oldest_first", "by-name"]},
+                    "-i": {"type": "boolean"},
+                    "tags": {"type": "object", "additionalProperties": {}}
+                },
+                "required": ["query", "ranges", "tags"]
+            })
+        );
+
+        let schema = SearchInput::schema();
+        assert_eq!(schema.schema_type, "object");
+        assert_eq!(schema.required, Some(vec!["query".to_string(), "ranges".to_string(), "tags".to_string()]));
+    }
+
+    /// A struct and an enum with `rename_all = $rule`, named `$module::{Fields, Variants}`
+    macro_rules! renamed {
+        ($module:ident, $rule:literal) => {
+            mod $module {
+                use super::*;
+
+                #[allow(dead_code)]
+                #[derive(Debug, Deserialize, ToolInput)]
+                #[serde(rename_all = $rule)]
+                pub struct Fields {
+                    pub max_count: u32,
+                    pub x: u32,
+                }
+
+                #[derive(Debug, Deserialize, ToolInput)]
+                #[serde(rename_all = $rule)]
+                pub enum Variants {
+                    MaxCount,
+                    X,
+                }
+            }
+        };
+    }
+
+    renamed!(lowercase, "lowercase");
+    renamed!(uppercase, "UPPERCASE");
+    renamed!(pascal_case, "PascalCase");
+    renamed!(camel_case, "camelCase");
+    renamed!(snake_case, "snake_case");
+    renamed!(screaming_snake_case, "SCREAMING_SNAKE_CASE");
+    renamed!(kebab_case, "kebab-case");
+    renamed!(screaming_kebab_case, "SCREAMING-KEBAB-CASE");
+
+    #[test]
+    fn test_rename_rules_match_serde() {
+        let cases: &[(Value, Value, [&str; 2], [&str; 2])] = &[
+            (lowercase::Fields::json_schema(), lowercase::Variants::json_schema(), ["max_count", "x"], ["maxcount", "x"]),
+            (uppercase::Fields::json_schema(), uppercase::Variants::json_schema(), ["MAX_COUNT", "X"], ["MAXCOUNT", "X"]),
+            (pascal_case::Fields::json_schema(), pascal_case::Variants::json_schema(), ["MaxCount", "X"], ["MaxCount", "X"]),
+            (camel_case::Fields::json_schema(), camel_case::Variants::json_schema(), ["maxCount", "x"], ["maxCount", "x"]),
+            (snake_case::Fields::json_schema(), snake_case::Variants::json_schema(), ["max_count", "x"], ["max_count", "x"]),
+            (
+                screaming_snake_case::Fields::json_schema(),
+                screaming_snake_case::Variants::json_schema(),
+                ["MAX_COUNT", "X"],
+                ["MAX_COUNT", "X"],
+            ),
+            (kebab_case::Fields::json_schema(), kebab_case::Variants::json_schema(), ["max-count", "x"], ["max-count", "x"]),
+            (
+                screaming_kebab_case::Fields::json_schema(),
+                screaming_kebab_case::Variants::json_schema(),
+                ["MAX-COUNT", "X"],
+                ["MAX-COUNT", "X"],
+            ),
+        ];
+        for (fields, variants, field_names, variant_names) in cases {
+            let properties: Vec<&str> = fields["properties"].as_object().unwrap().keys().map(String::as_str).collect();
+            let mut expected = field_names.to_vec();
+            expected.sort();
+            assert_eq!(properties, expected);
+            assert_eq!(variants["enum"], json!(variant_names));
+        }
+
+        // Serde reads what the schema advertises
+        macro_rules! accepts {
+            ($module:ident, $fields:expr, $variant:expr) => {
+                let object: serde_json::Map<String, Value> = $fields.iter().map(|name| (name.to_string(), json!(1))).collect();
+                serde_json::from_value::<$module::Fields>(Value::Object(object)).unwrap();
+                serde_json::from_value::<$module::Variants>(json!($variant)).unwrap();
+            };
+        }
+        accepts!(lowercase, ["max_count", "x"], "maxcount");
+        accepts!(uppercase, ["MAX_COUNT", "X"], "MAXCOUNT");
+        accepts!(pascal_case, ["MaxCount", "X"], "MaxCount");
+        accepts!(camel_case, ["maxCount", "x"], "maxCount");
+        accepts!(snake_case, ["max_count", "x"], "max_count");
+        accepts!(screaming_snake_case, ["MAX_COUNT", "X"], "MAX_COUNT");
+        accepts!(kebab_case, ["max-count", "x"], "max-count");
+        accepts!(screaming_kebab_case, ["MAX-COUNT", "X"], "MAX-COUNT");
+    }
+
+    #[test]
+    fn test_from_value_names_the_field() {
+        let input = SearchInput::from_value(json!({
+            "query": "fn main",
+            "ranges": [{"startLine": 1}],
+            "order": "oldest_first",
+            "tags": {}
+        }))
+        .unwrap();
+        assert_eq!(input.order, Some(Order::OldestFirst));
+        assert!(!input.ignore_case);
+
+        let error = SearchInput::from_value(json!({"query": "x", "ranges": [{"endLine": 2}], "tags": {}})).unwrap_err();
+        assert_eq!(error.to_string(), "ranges[0]: missing field `startLine`");
+        let error = SearchInput::from_value(json!({"ranges": [], "tags": {}})).unwrap_err();
+        assert_eq!(error.to_string(), "missing field `query`");
+        let error = SearchInput::from_value(json!({"query": "x", "ranges": [], "tags": {}, "order": "random"})).unwrap_err();
+        assert!(error.to_string().starts_with("order: unknown variant `random`"), "{}", error);
+    }
+}
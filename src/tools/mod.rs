@@ -4,23 +4,35 @@
 //! - `Tool` trait - Interface for implementing tools
 //! - `ToolResult` - Result type for tool execution
 //! - `ToolRegistry` - Registry for managing available tools
+//! - `ToolInput` - Input schemas derived from the type a tool deserializes
 //! - `ToolProvider` trait - Interface for dynamic tool sources (MCP, OpenAPI, etc.)
-//! - `common` - Built-in tools (Bash, Read, Write, Edit, Glob, Grep, Todo)
+//! - `concurrency` - Per-tool and global limits on concurrent calls
+//! - `InputValidation` - Checking call input against the tool's schema before it runs
+//! - `common` - Built-in tools (Bash, Read, Write, Edit, Glob, Grep, Todo, WebFetch)
 
+mod input;
 mod provider;
 mod registry;
 mod tool;
+mod validation;
 
 /// Common/built-in tools
 pub mod common;
+pub mod concurrency;
 
 // Core exports
+pub use input::ToolInput;
+#[doc(hidden)]
+pub use input::__private;
 pub use provider::ToolProvider;
-pub use registry::ToolRegistry;
+pub use concurrency::{ToolConcurrency, ToolPermit};
+pub use registry::{ConflictPolicy, ToolRegistry};
 pub use tool::{Tool, ToolInfo, ToolResult, ToolResultData};
+pub use validation::{validate_tool_input, InputValidation};
 
 // Re-export common tools for convenience
 pub use common::{
-    AskUserQuestionTool, BashTool, EditTool, GlobTool, GrepTool, PresentFileTool, ReadTool,
-    TodoWriteTool, WriteTool,
+    register_file_tools, AskUserQuestionTool, BashTool, EditMatching, EditTool, GlobTool, GrepBackend, GrepTool,
+    HttpRequestTool, LsTool, MemoryTool, NotebookEditTool, NotebookReadTool, PathPolicy, PresentFileTool, ReadTool, SendToAgentTool, TodoWriteTool,
+    UndoTool, WebFetchTool, WriteTool,
 };
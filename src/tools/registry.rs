@@ -3,6 +3,21 @@
 //! The registry holds all tools that are available to the agent.
 //! It supports both static tools (registered directly) and dynamic tools
 //! from providers (like MCP servers).
+//!
+//! # Name Conflicts
+//!
+//! Two MCP servers can expose the same namespaced name, or a provider tool
+//! can collide with a built-in. What happens then is the registry's
+//! `ConflictPolicy`; by default the second tool replaces the first, as a
+//! plain `register` call would expect.
+//! Every conflict is logged with the tool that was there and the one that
+//! collided with it.
+//!
+//! ```ignore
+//! let mut tools = ToolRegistry::new().with_conflict_policy(ConflictPolicy::Rename);
+//! tools.register(ReadTool::new()?);
+//! tools.add_provider(mcp_provider).await?; // a second "Read" becomes "Read_2"
+//! ```
 
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -15,13 +30,59 @@ use super::tool::{Tool, ToolInfo, ToolResult};
 use crate::llm::ToolDefinition;
 use crate::runtime::AgentInternals;
 
+/// What the registry does when a tool's name is already taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Refuse the new tool with an error
+    Error,
+    /// Keep the existing tool and ignore the new one
+    Skip,
+    /// Replace the existing tool with the new one
+    #[default]
+    Replace,
+    /// Register the new tool under the first free `name_2`, `name_3`, ...
+    ///
+    /// The model sees the new name in the tool's definition; the registry
+    /// remembers the tool's own name (see `ToolRegistry::original_name`).
+    /// Only custom tool definitions can be renamed; a collision between
+    /// Anthropic's built-in tool types is refused instead.
+    Rename,
+}
+
+/// Where a registered tool came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Origin {
+    /// `register`
+    Static,
+    /// `add_provider`, with the provider's name
+    Provider(String),
+}
+
+impl std::fmt::Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Origin::Static => write!(f, "registered directly"),
+            Origin::Provider(name) => write!(f, "from provider '{}'", name),
+        }
+    }
+}
+
 /// Registry that holds all available tools
 pub struct ToolRegistry {
-    /// Static tools registered directly
+    /// Tools by the name the model calls them with
     tools: HashMap<String, Arc<dyn Tool>>,
 
+    /// Where each tool came from
+    origins: HashMap<String, Origin>,
+
+    /// Tools registered under a new name, and their own names
+    renamed: HashMap<String, String>,
+
     /// Dynamic tool providers (MCP, etc.)
     providers: Vec<Arc<dyn ToolProvider>>,
+
+    /// What to do when a name is already taken
+    conflict_policy: ConflictPolicy,
 }
 
 impl ToolRegistry {
@@ -29,21 +90,48 @@ impl ToolRegistry {
     pub fn new() -> Self {
         Self {
             tools: HashMap::new(),
+            origins: HashMap::new(),
+            renamed: HashMap::new(),
             providers: Vec::new(),
+            conflict_policy: ConflictPolicy::default(),
         }
     }
 
+    /// Set what happens when a tool's name is already taken
+    pub fn with_conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.conflict_policy = policy;
+        self
+    }
+
+    /// The policy for name conflicts
+    pub fn conflict_policy(&self) -> ConflictPolicy {
+        self.conflict_policy
+    }
+
     /// Register a static tool in the registry
+    ///
+    /// A name conflict is handled by the conflict policy; by default the new
+    /// tool replaces the old one. If the policy refuses the new tool, the
+    /// error is only logged; use `try_register` to handle it instead.
     pub fn register<T: Tool + 'static>(&mut self, tool: T) {
-        let name = tool.name().to_string();
-        tracing::info!("Registering tool: {}", name);
-        self.tools.insert(name, Arc::new(tool));
+        if let Err(e) = self.try_register(tool) {
+            tracing::error!("[ToolRegistry] {}", e);
+        }
+    }
+
+    /// Register a static tool, failing on a name conflict under `ConflictPolicy::Error`
+    ///
+    /// Returns the name the tool was registered under, or None if it was skipped.
+    pub fn try_register<T: Tool + 'static>(&mut self, tool: T) -> Result<Option<String>> {
+        self.insert(Arc::new(tool), Origin::Static)
     }
 
     /// Add a tool provider (MCP, etc.)
     ///
     /// This will immediately fetch all tools from the provider and add them to the registry.
-    /// Returns an error if any tool name conflicts with existing tools.
+    /// Name conflicts are handled by the conflict policy; under
+    /// `ConflictPolicy::Error` none of the provider's tools are added if any
+    /// of them conflicts.
     pub async fn add_provider(&mut self, provider: Arc<dyn ToolProvider>) -> Result<()> {
         tracing::info!(
             "[ToolRegistry] Adding provider '{}' (dynamic: {})",
@@ -52,25 +140,10 @@ impl ToolRegistry {
         );
 
         let tools = provider.get_tools().await?;
-
+        let origin = Origin::Provider(provider.name().to_string());
+        self.check_conflicts(&tools, &origin)?;
         for tool in tools {
-            let name = tool.name().to_string();
-
-            // Check for conflicts
-            if self.tools.contains_key(&name) {
-                return Err(anyhow::anyhow!(
-                    "Tool name conflict: '{}' already exists (from provider '{}')",
-                    name,
-                    provider.name()
-                ));
-            }
-
-            tracing::info!(
-                "[ToolRegistry] Registering tool '{}' from provider '{}'",
-                name,
-                provider.name()
-            );
-            self.tools.insert(name, tool);
+            self.insert(tool, origin.clone())?;
         }
 
         self.providers.push(provider);
@@ -85,25 +158,26 @@ impl ToolRegistry {
     pub async fn refresh_providers(&mut self) -> Result<()> {
         tracing::info!("[ToolRegistry] Refreshing all dynamic providers");
 
-        // Remove all tools from providers
-        let provider_names: Vec<_> = self.providers.iter().map(|p| p.name()).collect();
-
-        self.tools.retain(|name, _| {
-            // Keep static tools, remove provider tools
-            !provider_names.iter().any(|p| name.starts_with(&format!("{}:", p)))
-        });
+        // Remove all tools from providers, keep static tools
+        let from_providers: Vec<String> = self
+            .origins
+            .iter()
+            .filter(|(_, origin)| matches!(origin, Origin::Provider(_)))
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in from_providers {
+            self.remove(&name);
+        }
 
         // Re-add tools from all providers
-        for provider in &self.providers {
+        for provider in self.providers.clone() {
             if provider.is_dynamic() {
                 provider.refresh().await?;
             }
 
-            let tools = provider.get_tools().await?;
-
-            for tool in tools {
-                let name = tool.name().to_string();
-                self.tools.insert(name, tool);
+            let origin = Origin::Provider(provider.name().to_string());
+            for tool in provider.get_tools().await? {
+                self.insert(tool, origin.clone())?;
             }
         }
 
@@ -112,14 +186,135 @@ impl ToolRegistry {
         Ok(())
     }
 
+    /// Add a tool under its name, or as the conflict policy says
+    fn insert(&mut self, tool: Arc<dyn Tool>, origin: Origin) -> Result<Option<String>> {
+        let name = tool.name().to_string();
+        let Some(existing) = self.origins.get(&name).cloned() else {
+            tracing::info!("[ToolRegistry] Registering tool '{}' ({})", name, origin);
+            self.tools.insert(name.clone(), tool);
+            self.origins.insert(name.clone(), origin);
+            return Ok(Some(name));
+        };
+
+        let renameable = matches!(tool.definition(), ToolDefinition::Custom(_));
+        match self.conflict_policy {
+            ConflictPolicy::Skip => {
+                tracing::warn!(
+                    "[ToolRegistry] Skipping tool '{}' ({}): the tool {} has that name",
+                    name,
+                    origin,
+                    existing
+                );
+                Ok(None)
+            }
+            ConflictPolicy::Replace => {
+                tracing::warn!(
+                    "[ToolRegistry] Tool '{}' ({}) replaces the tool {}",
+                    name,
+                    origin,
+                    existing
+                );
+                self.remove(&name);
+                self.tools.insert(name.clone(), tool);
+                self.origins.insert(name.clone(), origin);
+                Ok(Some(name))
+            }
+            ConflictPolicy::Rename if renameable => {
+                let new_name = (2..)
+                    .map(|i| format!("{}_{}", name, i))
+                    .find(|candidate| !self.tools.contains_key(candidate))
+                    .expect("some suffix is free");
+                tracing::warn!(
+                    "[ToolRegistry] Tool '{}' ({}) registered as '{}': the tool {} has its name",
+                    name,
+                    origin,
+                    new_name,
+                    existing
+                );
+                self.tools.insert(new_name.clone(), tool);
+                self.origins.insert(new_name.clone(), origin);
+                self.renamed.insert(new_name.clone(), name);
+                Ok(Some(new_name))
+            }
+            ConflictPolicy::Error | ConflictPolicy::Rename => Err(anyhow::anyhow!(
+                "Tool name conflict: '{}' ({}) is already registered ({})",
+                name,
+                origin,
+                existing
+            )),
+        }
+    }
+
+    /// Fail if adding `tools` would hit a conflict under `ConflictPolicy::Error`
+    fn check_conflicts(&self, tools: &[Arc<dyn Tool>], origin: &Origin) -> Result<()> {
+        if self.conflict_policy != ConflictPolicy::Error {
+            return Ok(());
+        }
+        let mut seen = std::collections::HashSet::new();
+        for tool in tools {
+            let name = tool.name();
+            if let Some(existing) = self.origins.get(name) {
+                anyhow::bail!("Tool name conflict: '{}' ({}) is already registered ({})", name, origin, existing);
+            }
+            if !seen.insert(name) {
+                anyhow::bail!("Tool name conflict: '{}' ({}) appears twice", name, origin);
+            }
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, name: &str) {
+        self.tools.remove(name);
+        self.origins.remove(name);
+        self.renamed.remove(name);
+    }
+
     /// Get a tool by name
     pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
         self.tools.get(name).cloned()
     }
 
+    /// The tool's own name, for a tool that `ConflictPolicy::Rename` renamed
+    pub fn original_name(&self, name: &str) -> Option<&str> {
+        self.renamed.get(name).map(|s| s.as_str())
+    }
+
+    /// Create a registry with only the named tools
+    ///
+    /// Tools from providers are copied as they are now; the new registry does
+    /// not refresh them. Returns an error naming the first unknown tool.
+    pub fn subset<S: AsRef<str>>(&self, names: &[S]) -> Result<Self> {
+        let mut subset = Self::new().with_conflict_policy(self.conflict_policy);
+        for name in names {
+            let name = name.as_ref();
+            let tool = self
+                .get(name)
+                .with_context(|| format!("Unknown tool '{}'", name))?;
+            subset.tools.insert(name.to_string(), tool);
+            if let Some(origin) = self.origins.get(name) {
+                subset.origins.insert(name.to_string(), origin.clone());
+            }
+            if let Some(original) = self.renamed.get(name) {
+                subset.renamed.insert(name.to_string(), original.clone());
+            }
+        }
+        Ok(subset)
+    }
+
     /// Get all tool definitions for the Anthropic API
+    ///
+    /// Renamed tools are listed under their new name.
     pub fn get_definitions(&self) -> Vec<ToolDefinition> {
-        self.tools.values().map(|t| t.definition()).collect()
+        self.tools
+            .iter()
+            .map(|(name, tool)| match tool.definition() {
+                ToolDefinition::Custom(mut custom) if self.renamed.contains_key(name) => {
+                    custom.name = name.clone();
+                    ToolDefinition::Custom(custom)
+                }
+                definition => definition,
+            })
+            .collect()
     }
 
     /// Get information about a tool invocation
@@ -186,6 +381,88 @@ impl Default for ToolRegistry {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use async_trait::async_trait;
+
+    use crate::llm::define_tool;
+
+    /// A tool that answers with a fixed text
+    struct NamedTool {
+        name: &'static str,
+        answer: &'static str,
+    }
+
+    #[async_trait]
+    impl Tool for NamedTool {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn description(&self) -> &str {
+            "Answers"
+        }
+
+        fn definition(&self) -> ToolDefinition {
+            define_tool(self.name, "Answers", serde_json::json!({}), vec![])
+        }
+
+        fn get_info(&self, _input: &Value) -> ToolInfo {
+            ToolInfo {
+                name: self.name.to_string(),
+                action_description: "Answer".to_string(),
+                details: None,
+                read_only: true,
+            }
+        }
+
+        async fn execute(&self, _input: &Value, _internals: &mut AgentInternals) -> Result<ToolResult> {
+            Ok(ToolResult::success(self.answer))
+        }
+    }
+
+    struct StaticProvider(&'static str, Vec<(&'static str, &'static str)>);
+
+    #[async_trait]
+    impl ToolProvider for StaticProvider {
+        async fn get_tools(&self) -> Result<Vec<Arc<dyn Tool>>> {
+            Ok(self
+                .1
+                .iter()
+                .map(|&(name, answer)| Arc::new(NamedTool { name, answer }) as Arc<dyn Tool>)
+                .collect())
+        }
+
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn is_dynamic(&self) -> bool {
+            true
+        }
+    }
+
+    /// Register a built-in "fs__read", then a provider with the same name
+    async fn colliding(policy: ConflictPolicy) -> (ToolRegistry, Result<()>) {
+        let mut registry = ToolRegistry::new().with_conflict_policy(policy);
+        registry.register(NamedTool { name: "fs__read", answer: "built-in" });
+        let provider = StaticProvider("MCP", vec![("fs__read", "mcp"), ("fs__list", "listed")]);
+        let added = registry.add_provider(Arc::new(provider)).await;
+        (registry, added)
+    }
+
+    fn names(registry: &ToolRegistry) -> Vec<&str> {
+        let mut names = registry.tool_names();
+        names.sort();
+        names
+    }
+
+    async fn answer(registry: &ToolRegistry, name: &str) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let result = registry.execute(name, &serde_json::json!({}), &mut AgentInternals::for_test(dir.path())).await.unwrap();
+        match &result.content[..] {
+            [super::super::ToolResultData::Text(text)] => text.clone(),
+            other => panic!("expected text, got {:?}", other),
+        }
+    }
 
     #[test]
     fn test_empty_registry() {
@@ -194,4 +471,62 @@ mod tests {
         assert_eq!(registry.len(), 0);
         assert!(registry.get("nonexistent").is_none());
     }
+
+    #[tokio::test]
+    async fn test_conflict_error_adds_nothing() {
+        let (registry, added) = colliding(ConflictPolicy::Error).await;
+        let e = added.unwrap_err().to_string();
+        assert!(e.contains("'fs__read' (from provider 'MCP') is already registered (registered directly)"), "{}", e);
+        assert_eq!(names(&registry), vec!["fs__read"]);
+        assert_eq!(answer(&registry, "fs__read").await, "built-in");
+
+        let mut registry = registry;
+        assert!(registry.try_register(NamedTool { name: "fs__read", answer: "again" }).is_err());
+        assert_eq!(answer(&registry, "fs__read").await, "built-in");
+    }
+
+    #[tokio::test]
+    async fn test_conflict_skip_keeps_first() {
+        let (registry, added) = colliding(ConflictPolicy::Skip).await;
+        added.unwrap();
+        assert_eq!(names(&registry), vec!["fs__list", "fs__read"]);
+        assert_eq!(answer(&registry, "fs__read").await, "built-in");
+    }
+
+    #[tokio::test]
+    async fn test_conflict_replace_keeps_last() {
+        assert_eq!(ConflictPolicy::default(), ConflictPolicy::Replace);
+        let (registry, added) = colliding(ConflictPolicy::Replace).await;
+        added.unwrap();
+        assert_eq!(names(&registry), vec!["fs__list", "fs__read"]);
+        assert_eq!(answer(&registry, "fs__read").await, "mcp");
+    }
+
+    #[tokio::test]
+    async fn test_conflict_rename_keeps_both() {
+        let (mut registry, added) = colliding(ConflictPolicy::Rename).await;
+        added.unwrap();
+        assert_eq!(names(&registry), vec!["fs__list", "fs__read", "fs__read_2"]);
+        assert_eq!(answer(&registry, "fs__read").await, "built-in");
+        assert_eq!(answer(&registry, "fs__read_2").await, "mcp");
+        assert_eq!(registry.original_name("fs__read_2"), Some("fs__read"));
+        assert_eq!(registry.original_name("fs__read"), None);
+
+        // The model is shown the new name
+        let mut defined: Vec<String> = registry
+            .get_definitions()
+            .into_iter()
+            .map(|d| match d {
+                ToolDefinition::Custom(custom) => custom.name,
+                other => panic!("unexpected {:?}", other),
+            })
+            .collect();
+        defined.sort();
+        assert_eq!(defined, vec!["fs__list", "fs__read", "fs__read_2"]);
+
+        // Refreshing re-adds the provider's tools, not more copies
+        registry.refresh_providers().await.unwrap();
+        assert_eq!(names(&registry), vec!["fs__list", "fs__read", "fs__read_2"]);
+        assert_eq!(answer(&registry, "fs__read_2").await, "mcp");
+    }
 }
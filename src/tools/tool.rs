@@ -6,12 +6,13 @@ use anyhow::Result;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::time::Duration;
 
 use crate::llm::ToolDefinition;
 use crate::runtime::AgentInternals;
 
 /// Content type for tool results
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ToolResultData {
     /// Text content
     Text(String),
@@ -25,41 +26,81 @@ pub enum ToolResultData {
     },
 }
 
+/// Text of the result recorded for an interrupted call
+const INTERRUPTED: &str = "Interrupted";
+
 /// Result of executing a tool
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Usually holds one item; a tool can also return text alongside images
+/// or documents:
+///
+/// ```ignore
+/// ToolResult::success("Captured 2 screenshots")
+///     .with_content(ToolResultData::Image { data: before, media_type: "image/png".into() })
+///     .with_content(ToolResultData::Image { data: after, media_type: "image/png".into() })
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ToolResult {
-    /// The content of the tool result
-    pub content: ToolResultData,
+    /// The content of the tool result, in order
+    #[serde(deserialize_with = "one_or_many")]
+    pub content: Vec<ToolResultData>,
     /// Whether the tool execution resulted in an error
     pub is_error: bool,
 }
 
+/// Accept results serialized before `content` became a list
+fn one_or_many<'de, D>(deserializer: D) -> std::result::Result<Vec<ToolResultData>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        Many(Vec<ToolResultData>),
+        One(ToolResultData),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::Many(items) => items,
+        OneOrMany::One(item) => vec![item],
+    })
+}
+
 impl ToolResult {
+    /// Create a result from content items
+    pub fn new(content: Vec<ToolResultData>, is_error: bool) -> Self {
+        Self { content, is_error }
+    }
+
     /// Create a successful tool result with text content
     pub fn success(output: impl Into<String>) -> Self {
-        Self {
-            content: ToolResultData::Text(output.into()),
-            is_error: false,
-        }
+        Self::new(vec![ToolResultData::Text(output.into())], false)
     }
 
     /// Create an error tool result
     pub fn error(message: impl Into<String>) -> Self {
-        Self {
-            content: ToolResultData::Text(message.into()),
-            is_error: true,
-        }
+        Self::new(vec![ToolResultData::Text(message.into())], true)
+    }
+
+    /// The result recorded for a call the user interrupted, or one
+    /// skipped because of an interrupt
+    pub fn interrupted() -> Self {
+        Self::error(INTERRUPTED)
+    }
+
+    /// Whether this is the result of an interrupted call
+    pub fn is_interrupted(&self) -> bool {
+        self.is_error && matches!(self.content.as_slice(), [ToolResultData::Text(text)] if text == INTERRUPTED)
     }
 
     /// Create a successful image result
     pub fn image(data: Vec<u8>, media_type: impl Into<String>) -> Self {
-        Self {
-            content: ToolResultData::Image {
+        Self::new(
+            vec![ToolResultData::Image {
                 data,
                 media_type: media_type.into(),
-            },
-            is_error: false,
-        }
+            }],
+            false,
+        )
     }
 
     /// Create a successful document result
@@ -68,14 +109,51 @@ impl ToolResult {
         media_type: impl Into<String>,
         description: impl Into<String>,
     ) -> Self {
-        Self {
-            content: ToolResultData::Document {
+        Self::new(
+            vec![ToolResultData::Document {
                 data,
                 media_type: media_type.into(),
                 description: description.into(),
-            },
-            is_error: false,
-        }
+            }],
+            false,
+        )
+    }
+
+    /// Add a content item after the existing ones
+    pub fn with_content(mut self, item: ToolResultData) -> Self {
+        self.content.push(item);
+        self
+    }
+
+    /// The text items, joined by blank lines
+    pub fn text(&self) -> String {
+        let texts: Vec<&str> = self
+            .content
+            .iter()
+            .filter_map(|item| match item {
+                ToolResultData::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        texts.join("\n\n")
+    }
+
+    /// Every item on its own line, media as type and size, for display
+    pub fn summary(&self) -> String {
+        let lines: Vec<String> = self
+            .content
+            .iter()
+            .map(|item| match item {
+                ToolResultData::Text(text) => text.clone(),
+                ToolResultData::Image { data, media_type } => {
+                    format!("Image ({}, {} bytes)", media_type, data.len())
+                }
+                ToolResultData::Document { description, data, media_type } => {
+                    format!("{} ({}, {} bytes)", description, media_type, data.len())
+                }
+            })
+            .collect();
+        lines.join("\n")
     }
 }
 
@@ -88,6 +166,8 @@ pub struct ToolInfo {
     pub action_description: String,
     /// Additional details about the action (e.g., command to run, file to edit)
     pub details: Option<String>,
+    /// Whether this invocation only reads (never changes files or state)
+    pub read_only: bool,
 }
 
 /// Trait for tools that the agent can use
@@ -113,14 +193,54 @@ pub trait Tool: Send + Sync {
     ///
     /// The input is a JSON value that matches the tool's input schema.
     /// The internals provide access to agent context, output channel, etc.
+    ///
+    /// When the user interrupts, `internals.context.cancellation` is
+    /// cancelled and the call is given a moment to return before it is
+    /// dropped. Tools that leave work behind when dropped (child
+    /// processes, remote requests) should watch the token and clean up.
     async fn execute(&self, input: &Value, internals: &mut AgentInternals) -> Result<ToolResult>;
 
+    /// Describe what a call would do, without doing it
+    ///
+    /// Sent to the model in place of the result when the agent runs in
+    /// dry-run mode (see `AgentConfig::with_dry_run`). Read-only tools run
+    /// normally in that mode and never need this. The default is the
+    /// pretty-printed input.
+    fn describe_effect(&self, input: &Value) -> String {
+        serde_json::to_string_pretty(input).unwrap_or_else(|_| input.to_string())
+    }
+
+    /// Whether calls still run in dry-run mode although the tool isn't
+    /// read-only
+    ///
+    /// For tools whose only effect is on the agent's own state or the
+    /// conversation (the todo list, a question to the user), which a
+    /// planning run needs. Default is false.
+    fn runs_in_dry_run(&self) -> bool {
+        false
+    }
+
     /// Check if this tool requires permission before execution
     ///
     /// Default is true - tools should generally require permission.
     fn requires_permission(&self) -> bool {
         true
     }
+
+    /// How many calls of this tool may run at once across the runtime
+    ///
+    /// None (the default) means no cap of its own; see `tools::concurrency`.
+    fn max_concurrency(&self) -> Option<usize> {
+        None
+    }
+
+    /// How long a call may take, including waiting for a concurrency slot
+    ///
+    /// A call that runs out of time fails with a timeout error. None (the
+    /// default) means no limit.
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
 }
 
 #[cfg(test)]
@@ -130,8 +250,8 @@ mod tests {
     #[test]
     fn test_tool_result_success() {
         let result = ToolResult::success("output");
-        match result.content {
-            ToolResultData::Text(text) => assert_eq!(text, "output"),
+        match &result.content[..] {
+            [ToolResultData::Text(text)] => assert_eq!(text, "output"),
             _ => panic!("Expected text content"),
         }
         assert!(!result.is_error);
@@ -140,8 +260,8 @@ mod tests {
     #[test]
     fn test_tool_result_error() {
         let result = ToolResult::error("error message");
-        match result.content {
-            ToolResultData::Text(text) => assert_eq!(text, "error message"),
+        match &result.content[..] {
+            [ToolResultData::Text(text)] => assert_eq!(text, "error message"),
             _ => panic!("Expected text content"),
         }
         assert!(result.is_error);
@@ -151,12 +271,12 @@ mod tests {
     fn test_tool_result_image() {
         let data = vec![1, 2, 3, 4];
         let result = ToolResult::image(data.clone(), "image/png");
-        match result.content {
-            ToolResultData::Image {
+        match &result.content[..] {
+            [ToolResultData::Image {
                 data: img_data,
                 media_type,
-            } => {
-                assert_eq!(img_data, data);
+            }] => {
+                assert_eq!(img_data, &data);
                 assert_eq!(media_type, "image/png");
             }
             _ => panic!("Expected image content"),
@@ -168,13 +288,13 @@ mod tests {
     fn test_tool_result_document() {
         let data = vec![1, 2, 3, 4];
         let result = ToolResult::document(data.clone(), "application/pdf", "Test PDF");
-        match result.content {
-            ToolResultData::Document {
+        match &result.content[..] {
+            [ToolResultData::Document {
                 data: doc_data,
                 media_type,
                 description,
-            } => {
-                assert_eq!(doc_data, data);
+            }] => {
+                assert_eq!(doc_data, &data);
                 assert_eq!(media_type, "application/pdf");
                 assert_eq!(description, "Test PDF");
             }
@@ -182,4 +302,20 @@ mod tests {
         }
         assert!(!result.is_error);
     }
+
+    #[test]
+    fn test_mixed_content_serialization() {
+        let result = ToolResult::success("Two screenshots")
+            .with_content(ToolResultData::Image { data: vec![1], media_type: "image/png".to_string() })
+            .with_content(ToolResultData::Text("Done".to_string()));
+        assert_eq!(result.text(), "Two screenshots\n\nDone");
+
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["content"].as_array().unwrap().len(), 3);
+        assert_eq!(serde_json::from_value::<ToolResult>(json).unwrap(), result);
+
+        // A single item, as results were serialized before
+        let old = serde_json::json!({"content": {"Text": "output"}, "is_error": false});
+        assert_eq!(serde_json::from_value::<ToolResult>(old).unwrap(), ToolResult::success("output"));
+    }
 }
@@ -0,0 +1,208 @@
+//! Tool input validation
+//!
+//! Before a call runs, `ToolExecutor` checks its input against the input
+//! schema of the tool's definition. A call with missing or mistyped fields
+//! gets an error result listing each problem and the expected schema, so the
+//! model can fix the call in one go instead of reading a deserialization
+//! error. Fields the schema doesn't mention are allowed.
+//!
+//! Validation runs on the input PreToolUse hooks leave, and each tool's
+//! compiled schema is kept in the `InputValidation` resource until the
+//! schema changes.
+//!
+//! `StandardAgent` validates every tool by default; see
+//! `AgentConfig::with_input_validation` and
+//! `AgentConfig::with_unvalidated_tools` for tools whose schemas are too
+//! loose (some MCP servers).
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use jsonschema::Validator;
+use serde_json::Value;
+
+use crate::llm::ToolDefinition;
+
+/// Maximum number of problems listed in a validation error
+const MAX_REPORTED_ERRORS: usize = 10;
+
+/// A tool's compiled schema: the schema it came from, and the validator
+/// (None if the schema isn't valid JSON Schema)
+type CompiledSchema = (Value, Option<Arc<Validator>>);
+
+/// Which tools have their input validated (an agent context resource)
+///
+/// Clones share the compiled schemas.
+#[derive(Debug, Clone, Default)]
+pub struct InputValidation {
+    skipped: HashSet<String>,
+    compiled: Arc<Mutex<HashMap<String, CompiledSchema>>>,
+}
+
+impl InputValidation {
+    /// Validate every tool
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Don't validate the input of this tool
+    pub fn skip_tool(mut self, name: impl Into<String>) -> Self {
+        self.skipped.insert(name.into());
+        self
+    }
+
+    /// Whether this tool's input is validated
+    pub fn applies_to(&self, name: &str) -> bool {
+        !self.skipped.contains(name)
+    }
+
+    /// Check a call's input like `validate_tool_input`, compiling each tool's schema once
+    pub fn validate(&self, definition: &ToolDefinition, input: &Value) -> Result<(), String> {
+        let Some((name, schema)) = input_schema(definition) else {
+            return Ok(());
+        };
+        let validator = {
+            let mut compiled = self.compiled.lock().unwrap();
+            match compiled.get(name) {
+                Some((cached, validator)) if *cached == schema => validator.clone(),
+                _ => {
+                    let validator = compile(name, &schema).map(Arc::new);
+                    compiled.insert(name.to_string(), (schema.clone(), validator.clone()));
+                    validator
+                }
+            }
+        };
+        match validator {
+            Some(validator) => check(name, &schema, &validator, input),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The name and input schema of a custom tool
+fn input_schema(definition: &ToolDefinition) -> Option<(&str, Value)> {
+    let ToolDefinition::Custom(tool) = definition else {
+        return None;
+    };
+    let schema = serde_json::to_value(&tool.input_schema).ok()?;
+    Some((tool.name.as_str(), schema))
+}
+
+/// Compile a schema, or None if it isn't valid JSON Schema
+fn compile(name: &str, schema: &Value) -> Option<Validator> {
+    match jsonschema::validator_for(schema) {
+        Ok(validator) => Some(validator),
+        Err(e) => {
+            tracing::debug!("[Executor] Not validating {} input, its schema is invalid: {}", name, e);
+            None
+        }
+    }
+}
+
+/// Check a call's input against a tool definition's input schema
+///
+/// Only custom tools have a schema to check. A schema that isn't valid
+/// JSON Schema is not enforced. The error is the tool result sent to the
+/// model.
+pub fn validate_tool_input(definition: &ToolDefinition, input: &Value) -> Result<(), String> {
+    let Some((name, schema)) = input_schema(definition) else {
+        return Ok(());
+    };
+    match compile(name, &schema) {
+        Some(validator) => check(name, &schema, &validator, input),
+        None => Ok(()),
+    }
+}
+
+/// Check input against a compiled schema
+fn check(name: &str, schema: &Value, validator: &Validator, input: &Value) -> Result<(), String> {
+    let problems: Vec<String> = validator
+        .iter_errors(input)
+        .take(MAX_REPORTED_ERRORS)
+        .map(|e| {
+            let path = e.instance_path().to_string();
+            if path.is_empty() {
+                format!("- {}", e)
+            } else {
+                format!("- {}: {}", path, e)
+            }
+        })
+        .collect();
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    let schema = serde_json::to_string_pretty(schema).unwrap_or_else(|_| schema.to_string());
+    Err(format!(
+        "Invalid input for {}:\n{}\n\nThe tool was not run. Call it again with input matching this schema:\n```json\n{}\n```",
+        name,
+        problems.join("\n"),
+        schema
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::ToolInputSchema;
+    use serde_json::json;
+
+    fn read_definition() -> ToolDefinition {
+        let mut schema = ToolInputSchema::new();
+        schema.properties = Some(json!({
+            "file_path": {"type": "string"},
+            "limit": {"type": "integer"}
+        }));
+        schema.required = Some(vec!["file_path".to_string()]);
+        ToolDefinition::Custom(crate::llm::types::CustomTool {
+            name: "Read".into(),
+            description: None,
+            input_schema: schema,
+            tool_type: None,
+            cache_control: None,
+        })
+    }
+
+    #[test]
+    fn test_missing_and_mistyped_fields_are_listed() {
+        let err = validate_tool_input(&read_definition(), &json!({"limit": "10"})).unwrap_err();
+        assert!(err.starts_with("Invalid input for Read:"), "{}", err);
+        assert!(err.contains("\"file_path\" is a required property"), "{}", err);
+        assert!(err.contains("- /limit: \"10\" is not of type \"integer\""), "{}", err);
+        assert!(err.contains("\"required\": [\n    \"file_path\"\n  ]"), "{}", err);
+    }
+
+    #[test]
+    fn test_valid_and_extra_fields_pass() {
+        let definition = read_definition();
+        assert!(validate_tool_input(&definition, &json!({"file_path": "a.rs", "limit": 5})).is_ok());
+        assert!(validate_tool_input(&definition, &json!({"file_path": "a.rs", "note": "extra"})).is_ok());
+    }
+
+    #[test]
+    fn test_compiled_schemas_are_reused_until_they_change() {
+        let validation = InputValidation::new();
+        let definition = read_definition();
+        assert!(validation.validate(&definition, &json!({"file_path": "a.rs"})).is_ok());
+        assert!(validation.clone().validate(&definition, &json!({"limit": 1})).is_err());
+        let first = validation.compiled.lock().unwrap()["Read"].1.clone().unwrap();
+        assert!(validation.validate(&definition, &json!({"file_path": "b.rs"})).is_ok());
+        assert!(Arc::ptr_eq(&first, validation.compiled.lock().unwrap()["Read"].1.as_ref().unwrap()));
+
+        // A new schema under the same name is compiled again
+        let ToolDefinition::Custom(mut tool) = definition else {
+            unreachable!()
+        };
+        tool.input_schema.required = Some(vec!["limit".to_string()]);
+        let definition = ToolDefinition::Custom(tool);
+        let err = validation.validate(&definition, &json!({"file_path": "a.rs"})).unwrap_err();
+        assert!(err.contains("\"limit\" is a required property"), "{}", err);
+    }
+
+    #[test]
+    fn test_skipped_tools() {
+        let validation = InputValidation::new().skip_tool("mcp__loose__search");
+        assert!(validation.applies_to("Read"));
+        assert!(!validation.applies_to("mcp__loose__search"));
+    }
+}